@@ -39,6 +39,7 @@ install -d $dest/share/docs/internal
 # install the binary:
 
 install target/$source/rustogrammer $dest/bin
+install target/$source/evt2par $dest/bin
 
 # install the client scripts
 
@@ -35,6 +35,7 @@ mkdir %dest%\docs\internal
 REM Install rustogramer.exe:
 
 COPY target\%target%\rustogrammer.exe %dest%
+COPY target\%target%\evt2par.exe %dest%
 
 REM Install the restclients:
 
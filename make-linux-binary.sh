@@ -22,6 +22,8 @@ tar cvzf rustogramer-linux.tar.gz \
       -C .. \
        rustogrammer/target/debug/rustogrammer \
        rustogrammer/target/release/rustogrammer \
+       rustogrammer/target/debug/evt2par \
+       rustogrammer/target/release/evt2par \
        rustogrammer/target/doc \
        rustogrammer/docs/book \
        rustogrammer/restclients \
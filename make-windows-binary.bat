@@ -21,6 +21,8 @@ tar cvzf rustogramer-windows.tar.gz ^
       -C .. ^
        rustogrammer/target/debug/rustogrammer.exe ^
        rustogrammer/target/release/rustogrammer.exe ^
+       rustogrammer/target/debug/evt2par.exe ^
+       rustogrammer/target/release/evt2par.exe ^
        rustogrammer/target/doc ^
        rustogrammer/docs/book ^
        rustogrammer/restclients ^
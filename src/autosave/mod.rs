@@ -0,0 +1,420 @@
+//!  Periodic saving of spectra.  Long experiment runs can lose hours
+//!  of histogrammed data if rustogramer, or the machine it runs on,
+//!  crashes.  An auto-save can be started that, at a fixed interval,
+//!  writes all or selected spectra to disk in the same formats as
+//!  swrite ("json" or "ascii") or as a ROOT macro ("root").
+//!
+//!  Each save goes to a new file whose name is made from a pattern by
+//!  replacing SEQUENCE_PLACEHOLDER with the save number (1, 2, ...).
+//!  Old files are rotated out: only the most recent *keep* files
+//!  written by the auto-save are retained, older ones are deleted.
+//!  Files are first written under a temporary name and then renamed so
+//!  that a crash during a save never leaves a truncated file in place
+//!  of a good one.
+//!
+//!  As with the simulator, only one auto-save runs at a time.  The
+//!  AutoSaver struct manages the auto-save thread and is shared with
+//!  the REST handlers in the Rocket state.
+//!
+use crate::messaging;
+use crate::messaging::spectrum_messages::SpectrumMessageClient;
+use crate::processing::endrun;
+use std::collections::VecDeque;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Text in a file pattern that is replaced by the save number.
+pub const SEQUENCE_PLACEHOLDER: &str = "{seq}";
+
+// Longest time the auto-save thread sleeps before checking for
+// a stop request:
+
+const MAX_SLEEP: Duration = Duration::from_millis(100);
+
+/// Describes what to save and how often:
+///
+/// * spectra - glob patterns of the spectra to save.
+/// * file_pattern - The file to write; SEQUENCE_PLACEHOLDER is replaced
+///   by the save number.
+/// * format - the file format, one of endrun::SUPPORTED_FORMATS.
+/// * interval - time between saves.
+/// * keep - number of files to retain, 0 means keep them all.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct AutoSavePolicy {
+    spectra: Vec<String>,
+    file_pattern: String,
+    format: String,
+    interval: Duration,
+    keep: usize,
+}
+
+impl AutoSavePolicy {
+    /// Create a policy.  The format is matched case insensitively.
+    /// Fails if there are no spectra, the file pattern does not contain
+    /// SEQUENCE_PLACEHOLDER, the format is not supported or the interval
+    /// (seconds) is not positive or too large to schedule.
+    pub fn new(
+        spectra: &[String],
+        file_pattern: &str,
+        format: &str,
+        interval: f64,
+        keep: usize,
+    ) -> Result<AutoSavePolicy, String> {
+        if spectra.is_empty() {
+            return Err(String::from("At least one spectrum must be saved"));
+        }
+        if !file_pattern.contains(SEQUENCE_PLACEHOLDER) {
+            return Err(format!(
+                "The file pattern '{}' must contain {}",
+                file_pattern, SEQUENCE_PLACEHOLDER
+            ));
+        }
+        let format = format.to_ascii_lowercase();
+        if !endrun::SUPPORTED_FORMATS.contains(&format.as_str()) {
+            return Err(format!("Unsupported format '{}'", format));
+        }
+        // The auto-save thread schedules saves at Instant::now() + interval
+        // so that must not overflow either:
+
+        let invalid = || format!("Invalid auto-save interval {}", interval);
+        if interval <= 0.0 {
+            return Err(invalid());
+        }
+        let interval = Duration::try_from_secs_f64(interval).map_err(|_| invalid())?;
+        if Instant::now().checked_add(interval).is_none() {
+            return Err(invalid());
+        }
+        Ok(AutoSavePolicy {
+            spectra: spectra.to_vec(),
+            file_pattern: String::from(file_pattern),
+            format,
+            interval,
+            keep,
+        })
+    }
+    pub fn spectra(&self) -> Vec<String> {
+        self.spectra.clone()
+    }
+    pub fn file_pattern(&self) -> String {
+        self.file_pattern.clone()
+    }
+    pub fn format(&self) -> String {
+        self.format.clone()
+    }
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+    pub fn keep(&self) -> usize {
+        self.keep
+    }
+    /// The name of the file written by a save.
+    pub fn file_name(&self, seq: u64) -> String {
+        self.file_pattern
+            .replace(SEQUENCE_PLACEHOLDER, &seq.to_string())
+    }
+}
+
+/// Status of the current or most recent auto-save:
+///
+/// * running - true if the auto-save thread is running.
+/// * policy - the policy of the current/most recent auto-save.
+/// * saves - number of successful saves.
+/// * last_save - when the most recent successful save was made.
+/// * files - the files that have been retained, oldest first.
+/// * error - If the most recent save failed, why.  Failures don't
+///   stop the auto-save; the next save is tried as usual.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AutoSaveStatus {
+    pub running: bool,
+    pub policy: Option<AutoSavePolicy>,
+    pub saves: u64,
+    pub last_save: Option<SystemTime>,
+    pub files: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Manages the auto-save thread.
+pub struct AutoSaver {
+    status: Arc<Mutex<AutoSaveStatus>>,
+    stop_flag: Arc<AtomicBool>,
+    thread: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+impl Default for AutoSaver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AutoSaver {
+    pub fn new() -> AutoSaver {
+        AutoSaver {
+            status: Arc::new(Mutex::new(AutoSaveStatus::default())),
+            stop_flag: Arc::new(AtomicBool::new(false)),
+            thread: Mutex::new(None),
+        }
+    }
+    /// Start auto-saving.  If an auto-save is already running it is
+    /// stopped first so that this is also how the policy is changed.
+    /// The first save is made one interval after the start.
+    ///
+    /// ### Parameters
+    /// * chan - channel to the histogram server.
+    /// * policy - what to save and how often.
+    ///
+    pub fn start(&self, chan: &mpsc::Sender<messaging::Request>, policy: AutoSavePolicy) {
+        self.stop();
+        let mut handle = self.thread.lock().unwrap();
+        *self.status.lock().unwrap() = AutoSaveStatus {
+            running: true,
+            policy: Some(policy.clone()),
+            ..AutoSaveStatus::default()
+        };
+        self.stop_flag.store(false, Ordering::SeqCst);
+
+        let api = SpectrumMessageClient::new(chan);
+        let status = self.status.clone();
+        let stop_flag = self.stop_flag.clone();
+        *handle = Some(thread::spawn(move || {
+            autosave_thread(api, policy, status, stop_flag)
+        }));
+    }
+    /// Stop the running auto-save, if any, and wait for its thread
+    /// to exit.  The files already written are left in place.
+    pub fn stop(&self) {
+        self.stop_flag.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.lock().unwrap().take() {
+            let _ = thread.join();
+        }
+    }
+    pub fn status(&self) -> AutoSaveStatus {
+        self.status.lock().unwrap().clone()
+    }
+}
+
+/// Make one save: the spectra are written to a temporary file
+/// which is then renamed to the file for this save.  Once that's
+/// done, files beyond the number to keep are deleted.
+///
+/// ### Parameters
+/// * policy - what to save.
+/// * seq - the save number.
+/// * api - client to the histogramer.
+/// * files - the files retained so far, oldest first.  On success,
+///   the new file is added and rotated files are removed.
+///
+/// ### Returns
+/// Result<(), String> - the error string describes what failed.
+pub fn save(
+    policy: &AutoSavePolicy,
+    seq: u64,
+    api: &SpectrumMessageClient,
+    files: &mut VecDeque<String>,
+) -> Result<(), String> {
+    let file = policy.file_name(seq);
+    let temporary = format!("{}.tmp", file);
+    let spectra = endrun::get_spectra(&policy.spectra, api)?;
+    if let Err(s) = endrun::write_file(&temporary, &policy.format, &spectra) {
+        let _ = fs::remove_file(&temporary);
+        return Err(s);
+    }
+    fs::rename(&temporary, &file)
+        .map_err(|e| format!("Unable to rename {} to {}: {}", temporary, file, e))?;
+
+    files.push_back(file);
+    if policy.keep > 0 {
+        while files.len() > policy.keep {
+            if let Some(old) = files.pop_front() {
+                let _ = fs::remove_file(old); // May have been removed by the user.
+            }
+        }
+    }
+    Ok(())
+}
+
+// The auto-save thread.  Saves are scheduled at multiples of the
+// interval from the start so that slow saves don't make the schedule
+// drift.
+
+fn autosave_thread(
+    api: SpectrumMessageClient,
+    policy: AutoSavePolicy,
+    status: Arc<Mutex<AutoSaveStatus>>,
+    stop_flag: Arc<AtomicBool>,
+) {
+    let mut due = Instant::now() + policy.interval;
+    let mut seq: u64 = 0;
+    let mut files = VecDeque::<String>::new();
+
+    while !stop_flag.load(Ordering::SeqCst) {
+        let now = Instant::now();
+        if now < due {
+            thread::sleep((due - now).min(MAX_SLEEP));
+            continue;
+        }
+        due += policy.interval;
+        seq += 1;
+        let result = save(&policy, seq, &api, &mut files);
+
+        let mut s = status.lock().unwrap();
+        match result {
+            Ok(()) => {
+                s.saves += 1;
+                s.last_save = Some(SystemTime::now());
+                s.files = files.iter().cloned().collect();
+                s.error = None;
+            }
+            Err(msg) => s.error = Some(msg),
+        }
+    }
+    status.lock().unwrap().running = false;
+}
+
+#[cfg(test)]
+mod autosave_tests {
+    use super::*;
+    use crate::messaging::parameter_messages::ParameterMessageClient;
+    use crate::parameters::{Event, EventParameter};
+    use crate::rest::spectrumio;
+    use crate::test::histogramer_common;
+    use rocket::serde::json;
+    use std::path::Path;
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| String::from(*s)).collect()
+    }
+    // Make spectra s1, s2 and other with a count in each:
+
+    fn make_spectra(chan: &mpsc::Sender<messaging::Request>) -> SpectrumMessageClient {
+        let papi = ParameterMessageClient::new(chan);
+        papi.create_parameter("a").expect("Making parameter");
+        let id = papi.list_parameters("a").expect("Listing")[0].get_id();
+        let api = SpectrumMessageClient::new(chan);
+        for name in ["s1", "s2", "other"] {
+            api.create_spectrum_1d(name, "a", 0.0, 10.0, 10)
+                .expect("Making spectrum");
+        }
+        let event: Event = vec![EventParameter::new(id, 5.0)];
+        api.process_events(&[event]).expect("Processing event");
+        api
+    }
+
+    #[test]
+    fn new_1() {
+        let policy = AutoSavePolicy::new(&names(&["*"]), "save-{seq}.json", "JSON", 1.5, 3)
+            .expect("Making policy");
+        assert_eq!(names(&["*"]), policy.spectra());
+        assert_eq!("save-{seq}.json", policy.file_pattern());
+        assert_eq!("json", policy.format());
+        assert_eq!(Duration::from_millis(1500), policy.interval());
+        assert_eq!(3, policy.keep());
+        assert_eq!("save-7.json", policy.file_name(7));
+    }
+    #[test]
+    fn new_2() {
+        // Invalid policies:
+
+        assert!(AutoSavePolicy::new(&[], "save-{seq}.json", "json", 1.0, 0).is_err());
+        assert!(AutoSavePolicy::new(&names(&["*"]), "save.json", "json", 1.0, 0).is_err());
+        assert!(AutoSavePolicy::new(&names(&["*"]), "save-{seq}", "npy", 1.0, 0).is_err());
+        // Intervals that are not positive or too large to schedule:
+
+        for interval in [0.0, -1.0, f64::NAN, f64::INFINITY, 1.0e300, 1.0e19] {
+            assert!(
+                AutoSavePolicy::new(&names(&["*"]), "save-{seq}", "json", interval, 0).is_err(),
+                "{}",
+                interval
+            );
+        }
+    }
+    #[test]
+    fn save_1() {
+        // Saves rotate out old files:
+
+        let (chan, jh) = histogramer_common::setup();
+        let api = make_spectra(&chan);
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let pattern = dir.path().join("save-{seq}.json");
+        let policy = AutoSavePolicy::new(
+            &names(&["s*", "s1"]),
+            pattern.to_str().unwrap(),
+            "json",
+            1.0,
+            2,
+        )
+        .expect("Making policy");
+
+        let mut files = VecDeque::new();
+        for seq in 1..=3 {
+            save(&policy, seq, &api, &mut files).expect("Saving");
+        }
+        assert_eq!(2, files.len());
+        assert_eq!(policy.file_name(2), files[0]);
+        assert_eq!(policy.file_name(3), files[1]);
+        assert!(!Path::new(&policy.file_name(1)).exists());
+        assert!(!Path::new(&format!("{}.tmp", policy.file_name(3))).exists());
+
+        let text = fs::read_to_string(&files[1]).expect("Reading file");
+        let spectra: Vec<spectrumio::SpectrumFileData> =
+            json::from_str(&text).expect("Parsing file");
+        assert_eq!(2, spectra.len());
+
+        histogramer_common::teardown(chan, jh);
+    }
+    #[test]
+    fn save_2() {
+        // Failed save leaves the retained files alone:
+
+        let (chan, jh) = histogramer_common::setup();
+        let api = make_spectra(&chan);
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let pattern = dir.path().join("no-such-dir").join("save-{seq}.txt");
+        let policy =
+            AutoSavePolicy::new(&names(&["*"]), pattern.to_str().unwrap(), "ascii", 1.0, 1)
+                .expect("Making policy");
+
+        let mut files = VecDeque::from(vec![String::from("kept")]);
+        assert!(save(&policy, 1, &api, &mut files).is_err());
+        assert_eq!(VecDeque::from(vec![String::from("kept")]), files);
+
+        histogramer_common::teardown(chan, jh);
+    }
+    #[test]
+    fn thread_1() {
+        // The thread saves periodically until stopped:
+
+        let (chan, jh) = histogramer_common::setup();
+        make_spectra(&chan);
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let pattern = dir.path().join("save-{seq}.json");
+        let policy =
+            AutoSavePolicy::new(&names(&["*"]), pattern.to_str().unwrap(), "json", 0.05, 2)
+                .expect("Making policy");
+
+        let saver = AutoSaver::new();
+        saver.start(&chan, policy.clone());
+        assert!(saver.status().running);
+        let start = Instant::now();
+        while saver.status().saves < 3 {
+            assert!(start.elapsed() < Duration::from_secs(10), "Saves too slow");
+            thread::sleep(Duration::from_millis(10));
+        }
+        saver.stop();
+
+        let status = saver.status();
+        assert!(!status.running);
+        assert_eq!(Some(policy), status.policy);
+        assert!(status.last_save.is_some());
+        assert!(status.error.is_none());
+        assert_eq!(2, status.files.len());
+        for file in status.files.iter() {
+            assert!(Path::new(file).exists(), "{}", file);
+        }
+
+        histogramer_common::teardown(chan, jh);
+    }
+}
@@ -0,0 +1,99 @@
+//!  evt2par - converts a raw NSCLDAQ event file into a parameter file
+//!  that rustogramer can analyze.  Physics events are unpacked
+//!  by one of the unpackers in rustogramer::unpackers chosen on the
+//!  command line.
+//!
+use clap::Parser;
+use rustogramer::ring_items::analysis_ring_items::ParameterFileWriter;
+use rustogramer::ring_items::RingVersion;
+use rustogramer::unpackers;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::process;
+
+const DEFAULT_PROGRESS_INTERVAL: usize = 100000;
+
+// Program parameters as parsed by Clap:
+
+#[derive(Parser, Debug)]
+#[command(author, version, about="Convert raw event files to rustogramer parameter files", long_about=None)]
+struct Args {
+    /// Raw event file to convert.
+    input: String,
+    /// Parameter file to write.
+    output: String,
+    /// Unpacker to use (words or longs).
+    #[arg(short, long, default_value = "words")]
+    unpacker: String,
+    /// Number of parameters the unpacker defines.
+    #[arg(short, long, default_value_t = 16)]
+    parameters: usize,
+    /// Ring item format version of the input and output (11 or 12).
+    #[arg(short, long, default_value = "12")]
+    format: String,
+    /// Report progress every this many events (0 for never).
+    #[arg(long, default_value_t=DEFAULT_PROGRESS_INTERVAL)]
+    progress: usize,
+    /// Maximum number of event errors to report individually.
+    #[arg(long, default_value_t = 10)]
+    max_errors: usize,
+}
+
+// Report a fatal error and exit:
+
+fn fail(msg: &str) -> ! {
+    eprintln!("evt2par: {}", msg);
+    process::exit(1);
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let version = args
+        .format
+        .parse::<RingVersion>()
+        .unwrap_or_else(|e| fail(&e));
+    let mut unpacker =
+        unpackers::create(&args.unpacker, args.parameters).unwrap_or_else(|e| fail(&e));
+
+    let input = File::open(&args.input)
+        .unwrap_or_else(|e| fail(&format!("Unable to open {}: {}", args.input, e)));
+    let mut input = BufReader::new(input);
+    let output = File::create(&args.output)
+        .unwrap_or_else(|e| fail(&format!("Unable to create {}: {}", args.output, e)));
+    let mut writer = ParameterFileWriter::new(BufWriter::new(output), version)
+        .unwrap_or_else(|e| fail(&format!("Unable to write {}: {}", args.output, e)));
+
+    let mut reported = 0;
+    let result = unpackers::convert(
+        &mut input,
+        &mut writer,
+        unpacker.as_mut(),
+        args.progress,
+        &mut |stats| {
+            println!(
+                "{} events read ({} bytes), {} written, {} errors",
+                stats.events, stats.bytes, stats.written, stats.errors
+            )
+        },
+        &mut |trigger, msg| {
+            if reported < args.max_errors {
+                eprintln!("Event {}: {}", trigger, msg);
+                reported += 1;
+                if reported == args.max_errors {
+                    eprintln!("Further event errors will not be reported individually");
+                }
+            }
+        },
+    );
+
+    match result {
+        Ok(stats) => {
+            println!(
+                "Converted {} to {}: {} ring items, {} events read, {} written, {} errors",
+                args.input, args.output, stats.items, stats.events, stats.written, stats.errors
+            );
+        }
+        Err(msg) => fail(&msg),
+    }
+}
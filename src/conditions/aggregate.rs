@@ -0,0 +1,220 @@
+//!  Aggregate conditions are set on a value computed from
+//!  a set of parameters rather than on a single parameter.
+//!  The aggregates supported are:
+//!
+//!  *  Sum  - the sum of the parameters present in the event.
+//!  *  Mean - the mean of the parameters present in the event.
+//!  *  Max  - the largest of the parameters present in the event.
+//!
+//!  The condition is true if at least one of the parameters is
+//!  present in the event and the aggregate lies in the range
+//!  [low, high].  For example, a Sum condition on the energies of all
+//!  detectors can be used to gate on the total energy deposited.
+//!
+//!  Aggregate conditions support caching.
+//!
+use super::*;
+use crate::parameters;
+
+/// The aggregate computed from the parameters:
+///
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum AggregateKind {
+    Sum,
+    Mean,
+    Max,
+}
+
+/// Aggregate
+///  This struct implements the condition.
+///
+#[derive(PartialEq, Debug)]
+pub struct Aggregate {
+    kind: AggregateKind,
+    parameters: Vec<u32>,
+    low: f64,
+    high: f64,
+    cache: Option<bool>,
+}
+impl Aggregate {
+    pub fn new(kind: AggregateKind, params: &[u32], low: f64, high: f64) -> Aggregate {
+        Aggregate {
+            kind,
+            parameters: params.to_owned(),
+            low,
+            high,
+            cache: None,
+        }
+    }
+    /// Compute the aggregate for an event.  None if none of the
+    /// parameters are present.
+    ///
+    pub fn aggregate(&self, event: &parameters::FlatEvent) -> Option<f64> {
+        let mut n = 0;
+        let mut sum = 0.0;
+        let mut max = f64::NEG_INFINITY;
+
+        for p in self.parameters.iter() {
+            if let Some(value) = event[*p] {
+                n += 1;
+                sum += value;
+                if value > max {
+                    max = value;
+                }
+            }
+        }
+        if n == 0 {
+            None
+        } else {
+            Some(match self.kind {
+                AggregateKind::Sum => sum,
+                AggregateKind::Mean => sum / (n as f64),
+                AggregateKind::Max => max,
+            })
+        }
+    }
+}
+impl Condition for Aggregate {
+    fn evaluate(&mut self, event: &parameters::FlatEvent) -> bool {
+        let result = if let Some(value) = self.aggregate(event) {
+            (value >= self.low) && (value <= self.high)
+        } else {
+            false
+        };
+        self.cache = Some(result);
+        result
+    }
+    fn condition_type(&self) -> String {
+        String::from(match self.kind {
+            AggregateKind::Sum => "Sum",
+            AggregateKind::Mean => "Mean",
+            AggregateKind::Max => "Max",
+        })
+    }
+    fn condition_points(&self) -> Vec<(f64, f64)> {
+        vec![(self.low, 0.0), (self.high, 0.0)]
+    }
+    fn dependent_conditions(&self) -> Vec<ContainerReference> {
+        vec![]
+    }
+    fn dependent_parameters(&self) -> Vec<u32> {
+        self.parameters.clone()
+    }
+    fn get_cached_value(&self) -> Option<bool> {
+        self.cache
+    }
+    fn invalidate_cache(&mut self) {
+        self.cache = None;
+    }
+}
+#[cfg(test)]
+mod aggregate_tests {
+    use super::*;
+    use crate::parameters::{EventParameter, FlatEvent};
+
+    #[test]
+    fn new_1() {
+        let a = Aggregate::new(AggregateKind::Sum, &[1, 2, 3], 100.0, 200.0);
+        assert_eq!(
+            Aggregate {
+                kind: AggregateKind::Sum,
+                parameters: vec![1, 2, 3],
+                low: 100.0,
+                high: 200.0,
+                cache: None
+            },
+            a
+        );
+    }
+    #[test]
+    fn type_1() {
+        assert_eq!(
+            "Sum",
+            Aggregate::new(AggregateKind::Sum, &[1], 0.0, 1.0).condition_type()
+        );
+        assert_eq!(
+            "Mean",
+            Aggregate::new(AggregateKind::Mean, &[1], 0.0, 1.0).condition_type()
+        );
+        assert_eq!(
+            "Max",
+            Aggregate::new(AggregateKind::Max, &[1], 0.0, 1.0).condition_type()
+        );
+    }
+    #[test]
+    fn describe_1() {
+        let a = Aggregate::new(AggregateKind::Mean, &[1, 2, 3], 100.0, 200.0);
+        assert_eq!(vec![(100.0, 0.0), (200.0, 0.0)], a.condition_points());
+        assert_eq!(vec![1, 2, 3], a.dependent_parameters());
+        assert!(a.dependent_conditions().is_empty());
+        assert!(!a.is_fold());
+    }
+    #[test]
+    fn empty_1() {
+        // No parameters present - false regardless of limits:
+
+        let mut a = Aggregate::new(AggregateKind::Sum, &[1, 2, 3], -100.0, 100.0);
+        let e = FlatEvent::new();
+        assert!(a.aggregate(&e).is_none());
+        assert!(!a.check(&e));
+        assert_eq!(Some(false), a.get_cached_value());
+        a.invalidate_cache();
+        assert!(a.get_cached_value().is_none());
+    }
+    #[test]
+    fn sum_1() {
+        let mut a = Aggregate::new(AggregateKind::Sum, &[1, 2, 3], 100.0, 200.0);
+        let mut e = FlatEvent::new();
+        // Parameter 4 is not in the condition and must not count:
+        e.load_event(&vec![
+            EventParameter::new(1, 50.0),
+            EventParameter::new(3, 75.0),
+            EventParameter::new(4, 1000.0),
+        ]);
+        assert_eq!(Some(125.0), a.aggregate(&e));
+        assert!(a.check(&e));
+
+        a.invalidate_cache();
+        e.load_event(&vec![
+            EventParameter::new(1, 50.0),
+            EventParameter::new(2, 100.0),
+            EventParameter::new(3, 75.0),
+        ]);
+        assert!(!a.check(&e));
+    }
+    #[test]
+    fn mean_1() {
+        let mut a = Aggregate::new(AggregateKind::Mean, &[1, 2, 3], 50.0, 60.0);
+        let mut e = FlatEvent::new();
+        e.load_event(&vec![
+            EventParameter::new(1, 50.0),
+            EventParameter::new(3, 60.0),
+        ]);
+        assert_eq!(Some(55.0), a.aggregate(&e));
+        assert!(a.check(&e));
+
+        a.invalidate_cache();
+        e.load_event(&vec![
+            EventParameter::new(1, 50.0),
+            EventParameter::new(2, 0.0),
+            EventParameter::new(3, 60.0),
+        ]);
+        assert!(!a.check(&e)); // mean is 36.66...
+    }
+    #[test]
+    fn max_1() {
+        let mut a = Aggregate::new(AggregateKind::Max, &[1, 2, 3], 100.0, 200.0);
+        let mut e = FlatEvent::new();
+        e.load_event(&vec![
+            EventParameter::new(1, -50.0),
+            EventParameter::new(2, 150.0),
+            EventParameter::new(3, 10.0),
+        ]);
+        assert_eq!(Some(150.0), a.aggregate(&e));
+        assert!(a.check(&e));
+
+        a.invalidate_cache();
+        e.load_event(&vec![EventParameter::new(2, 250.0)]);
+        assert!(!a.check(&e));
+    }
+}
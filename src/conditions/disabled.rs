@@ -0,0 +1,127 @@
+//!  A disabled condition is a condition that has been taken out of service
+//!  without being deleted.  It wraps the condition it disables and,
+//!  when checked, evaluates to a fixed value (normally True) no matter
+//!  what the event contains.  This allows users to see the effect of
+//!  a condition on the spectra it gates, directly or through compound
+//!  conditions, without rebuilding anything.
+//!
+//!  The description of a disabled condition (its type, points, dependent
+//!  conditions and parameters) is that of the condition it wraps so
+//!  that it lists as the same condition.  Folds are not gates, so a
+//!  disabled condition used as a fold continues to fold.
+//!
+//!  Since spectra and compound conditions hold references to the
+//!  condition container, disabling a condition replaces the contents
+//!  of its container with a Disabled condition and enabling it
+//!  puts the wrapped condition back.
+//!
+use super::*;
+use crate::parameters::*;
+use std::mem;
+
+/// Disabled
+///  This struct implements a disabled condition:
+///
+pub struct Disabled {
+    condition: Box<dyn Condition>,
+    value: bool,
+}
+impl Disabled {
+    /// Disable a condition.
+    ///
+    /// ### Parameters
+    /// *  condition - the condition being disabled.
+    /// *  value - the value the disabled condition evaluates to.
+    ///
+    pub fn new(condition: Box<dyn Condition>, value: bool) -> Disabled {
+        Disabled { condition, value }
+    }
+}
+impl Condition for Disabled {
+    fn evaluate(&mut self, _event: &FlatEvent) -> bool {
+        self.value
+    }
+    fn condition_type(&self) -> String {
+        self.condition.condition_type()
+    }
+    fn condition_points(&self) -> Vec<(f64, f64)> {
+        self.condition.condition_points()
+    }
+    fn condition_polygons(&self) -> Vec<Vec<(f64, f64)>> {
+        self.condition.condition_polygons()
+    }
+    fn dependent_conditions(&self) -> Vec<ContainerReference> {
+        self.condition.dependent_conditions()
+    }
+    fn dependent_parameters(&self) -> Vec<u32> {
+        self.condition.dependent_parameters()
+    }
+    fn invalidate_cache(&mut self) {
+        self.condition.invalidate_cache();
+    }
+    fn disabled_value(&self) -> Option<bool> {
+        Some(self.value)
+    }
+    fn take_enabled(&mut self) -> Option<Box<dyn Condition>> {
+        Some(mem::replace(&mut self.condition, Box::new(True {})))
+    }
+    fn is_fold(&self) -> bool {
+        self.condition.is_fold()
+    }
+    fn evaluate_1(&mut self, event: &FlatEvent) -> HashSet<u32> {
+        self.condition.evaluate_1(event)
+    }
+    fn evaluate_2(&mut self, event: &FlatEvent) -> HashSet<(u32, u32)> {
+        self.condition.evaluate_2(event)
+    }
+}
+#[cfg(test)]
+mod disabled_tests {
+    use super::*;
+    use crate::conditions::cut::Cut;
+
+    #[test]
+    fn new_1() {
+        let d = Disabled::new(Box::new(Cut::new(12, 100.0, 200.0)), true);
+
+        // Describes as the cut:
+
+        assert_eq!("Cut", d.condition_type());
+        assert_eq!(vec![(100.0, 0.0), (200.0, 0.0)], d.condition_points());
+        assert_eq!(vec![12], d.dependent_parameters());
+        assert_eq!(Some(true), d.disabled_value());
+    }
+    #[test]
+    fn check_1() {
+        // Disabled evaluates to its value regardless of the event:
+
+        let mut t = Disabled::new(Box::new(Cut::new(1, 100.0, 200.0)), true);
+        let mut f = Disabled::new(Box::new(Cut::new(1, 100.0, 200.0)), false);
+
+        let mut fe = FlatEvent::new();
+        let event = vec![EventParameter::new(1, 150.0)];
+        fe.load_event(&event);
+        assert!(t.check(&fe));
+        assert!(!f.check(&fe));
+
+        let event = vec![EventParameter::new(1, 50.0)];
+        fe.load_event(&event);
+        assert!(t.check(&fe));
+        assert!(!f.check(&fe));
+    }
+    #[test]
+    fn take_1() {
+        // Taking the enabled condition gives back the cut:
+
+        let mut d = Disabled::new(Box::new(Cut::new(1, 100.0, 200.0)), true);
+        let mut c = d.take_enabled().expect("Should have a condition");
+        assert_eq!("Cut", c.condition_type());
+        assert_eq!(None, c.disabled_value());
+        assert!(c.take_enabled().is_none());
+
+        let mut fe = FlatEvent::new();
+        let event = vec![EventParameter::new(1, 50.0)];
+        fe.load_event(&event);
+        assert!(!c.check(&fe));
+    }
+}
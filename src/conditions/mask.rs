@@ -0,0 +1,174 @@
+//!  Mask conditions gate on the bits of integer valued parameters,
+//!  e.g. trigger or hit pattern words.  These are SpecTcl's bitmask
+//!  gates.  The parameter value is truncated to an integer and
+//!  compared with a mask according to the kind of condition:
+//!
+//!  *  Equal (SpecTcl em) - the value is the mask.
+//!  *  And   (SpecTcl am) - all of the bits set in the mask are set in the value.
+//!  *  NotAnd (SpecTcl nm) - none of the bits set in the mask are set in the value.
+//!
+//!  The condition is false for events that don't have the parameter.
+//!  Negative values are treated as zero.
+//!
+//!  Mask conditions support caching.
+//!
+use super::*;
+use crate::parameters;
+
+/// How the value is compared with the mask:
+///
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum MaskKind {
+    Equal,
+    And,
+    NotAnd,
+}
+
+/// Mask
+///   This struct implements the condition.
+///
+#[derive(PartialEq, Debug)]
+pub struct Mask {
+    kind: MaskKind,
+    parameter_id: u32,
+    mask: u32,
+    cache: Option<bool>,
+}
+impl Mask {
+    pub fn new(kind: MaskKind, id: u32, mask: u32) -> Mask {
+        Mask {
+            kind,
+            parameter_id: id,
+            mask,
+            cache: None,
+        }
+    }
+    /// Compare an integer value with the mask.
+    ///
+    pub fn matches(&self, value: u64) -> bool {
+        let mask = self.mask as u64;
+        match self.kind {
+            MaskKind::Equal => value == mask,
+            MaskKind::And => (value & mask) == mask,
+            MaskKind::NotAnd => (value & mask) == 0,
+        }
+    }
+}
+impl Condition for Mask {
+    fn evaluate(&mut self, event: &parameters::FlatEvent) -> bool {
+        // as truncates and saturates so negative values become 0:
+
+        let result = if let Some(p) = event[self.parameter_id] {
+            self.matches(p as u64)
+        } else {
+            false
+        };
+        self.cache = Some(result);
+        result
+    }
+    fn condition_type(&self) -> String {
+        String::from(match self.kind {
+            MaskKind::Equal => "MaskEqual",
+            MaskKind::And => "MaskAnd",
+            MaskKind::NotAnd => "MaskNotAnd",
+        })
+    }
+    // The mask is the x coordinate of the only point; u32 values are
+    // exact in an f64:
+
+    fn condition_points(&self) -> Vec<(f64, f64)> {
+        vec![(self.mask as f64, 0.0)]
+    }
+    fn dependent_conditions(&self) -> Vec<ContainerReference> {
+        vec![]
+    }
+    fn dependent_parameters(&self) -> Vec<u32> {
+        vec![self.parameter_id]
+    }
+    fn get_cached_value(&self) -> Option<bool> {
+        self.cache
+    }
+    fn invalidate_cache(&mut self) {
+        self.cache = None;
+    }
+}
+#[cfg(test)]
+mod mask_tests {
+    use super::*;
+    use crate::parameters::{EventParameter, FlatEvent};
+
+    fn event(id: u32, value: f64) -> FlatEvent {
+        let mut e = FlatEvent::new();
+        e.load_event(&vec![EventParameter::new(id, value)]);
+        e
+    }
+
+    #[test]
+    fn new_1() {
+        let m = Mask::new(MaskKind::And, 1, 0x5);
+        assert_eq!(
+            Mask {
+                kind: MaskKind::And,
+                parameter_id: 1,
+                mask: 0x5,
+                cache: None
+            },
+            m
+        );
+    }
+    #[test]
+    fn describe_1() {
+        for (kind, name) in [
+            (MaskKind::Equal, "MaskEqual"),
+            (MaskKind::And, "MaskAnd"),
+            (MaskKind::NotAnd, "MaskNotAnd"),
+        ] {
+            let m = Mask::new(kind, 3, 0xffffffff);
+            assert_eq!(name, m.condition_type());
+            assert_eq!(vec![(4294967295.0, 0.0)], m.condition_points());
+            assert_eq!(vec![3], m.dependent_parameters());
+            assert!(m.dependent_conditions().is_empty());
+            assert!(!m.is_fold());
+        }
+    }
+    #[test]
+    fn matches_1() {
+        let equal = Mask::new(MaskKind::Equal, 1, 0b0101);
+        assert!(equal.matches(0b0101));
+        assert!(!equal.matches(0b0111));
+        assert!(!equal.matches(0b0100));
+
+        let and = Mask::new(MaskKind::And, 1, 0b0101);
+        assert!(and.matches(0b0101));
+        assert!(and.matches(0b1111));
+        assert!(!and.matches(0b0100));
+
+        let notand = Mask::new(MaskKind::NotAnd, 1, 0b0101);
+        assert!(notand.matches(0b1010));
+        assert!(!notand.matches(0b0100));
+        assert!(!notand.matches(0b0101));
+    }
+    #[test]
+    fn evaluate_1() {
+        // Values are truncated to integers:
+
+        let mut m = Mask::new(MaskKind::And, 1, 0b11);
+        assert!(m.check(&event(1, 3.7)));
+        assert_eq!(Some(true), m.get_cached_value());
+        m.invalidate_cache();
+        assert!(m.get_cached_value().is_none());
+        assert!(!m.check(&event(1, 2.9)));
+    }
+    #[test]
+    fn evaluate_2() {
+        // Missing parameters are false, negative values are zero:
+
+        let mut m = Mask::new(MaskKind::NotAnd, 1, 0b11);
+        assert!(!m.check(&event(2, 0.0)));
+        m.invalidate_cache();
+        assert!(m.check(&event(1, -5.0)));
+        m.invalidate_cache();
+        let mut m = Mask::new(MaskKind::Equal, 1, 0);
+        assert!(m.check(&event(1, -5.0)));
+    }
+}
@@ -63,6 +63,14 @@ pub mod compound;
 pub use compound::*;
 pub mod twod;
 pub use twod::*;
+pub mod aggregate;
+pub use aggregate::{Aggregate, AggregateKind};
+pub mod ratio;
+pub use ratio::{RatioCut, RatioKind};
+pub mod mask;
+pub use mask::{Mask, MaskKind};
+pub mod disabled;
+pub use disabled::Disabled;
 
 /// The Container trait defines the interface to a condition through
 /// a Condition container.   This interface includes:
@@ -90,6 +98,15 @@ pub trait Condition {
     fn dependent_parameters(&self) -> Vec<u32>;
 
     /// Optional methods:
+    ///
+    /// Conditions made up of several polygons (see twod::ContourSet)
+    /// return the points of each polygon.  condition_points returns
+    /// those points one polygon after the other.  Other conditions
+    /// return an empty vector.
+    ///
+    fn condition_polygons(&self) -> Vec<Vec<(f64, f64)>> {
+        Vec::new()
+    }
     /// Caching not implemented is the default.
     ///
     fn get_cached_value(&self) -> Option<bool> {
@@ -110,6 +127,25 @@ pub trait Condition {
         }
     }
 
+    /// Conditions can be disabled without being deleted
+    /// (see the disabled module).  A disabled condition evaluates
+    /// to a fixed value.
+    ///
+    /// ### Returns:
+    /// *  None - the condition is enabled.
+    /// *  Some(value) - the condition is disabled and evaluates to value.
+    ///
+    fn disabled_value(&self) -> Option<bool> {
+        None
+    }
+    /// If the condition is disabled, takes the condition it disabled
+    /// so that it can be put back in service.  None if the condition
+    /// is not disabled.
+    ///
+    fn take_enabled(&mut self) -> Option<Box<dyn Condition>> {
+        None
+    }
+
     /// Some conditions can be treated as folds on a Gamma spectrum.
     /// A fold takes an event and reduces it to the set of parameters
     /// or parameter pairs that can increment a gamma spectrum.
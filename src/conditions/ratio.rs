@@ -0,0 +1,202 @@
+//!  Two parameter cuts are slices set on a value computed from
+//!  an ordered pair of parameters:
+//!
+//!  *  Ratio - p1/p2.  A common particle identification cut.
+//!  *  Difference - p1 - p2.
+//!
+//!  The condition is true if both parameters are present in the event
+//!  and the computed value lies in the range [low, high].  A Ratio
+//!  condition is false for events where p2 is zero.
+//!
+//!  This allows these cuts without having to make a pseudo parameter
+//!  for the ratio or difference.
+//!
+//!  Two parameter cuts support caching.
+//!
+use super::*;
+use crate::parameters;
+
+/// The value computed from the parameters:
+///
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum RatioKind {
+    Ratio,
+    Difference,
+}
+
+/// RatioCut
+///   This struct implements the condition.
+///
+#[derive(PartialEq, Debug)]
+pub struct RatioCut {
+    kind: RatioKind,
+    p1: u32,
+    p2: u32,
+    low: f64,
+    high: f64,
+    cache: Option<bool>,
+}
+impl RatioCut {
+    pub fn new(kind: RatioKind, p1: u32, p2: u32, low: f64, high: f64) -> RatioCut {
+        RatioCut {
+            kind,
+            p1,
+            p2,
+            low,
+            high,
+            cache: None,
+        }
+    }
+    /// Compute the value for an event.  None if either parameter
+    /// is missing or, for a ratio, p2 is zero.
+    ///
+    pub fn value(&self, event: &parameters::FlatEvent) -> Option<f64> {
+        if let (Some(p1), Some(p2)) = (event[self.p1], event[self.p2]) {
+            match self.kind {
+                RatioKind::Ratio => {
+                    if p2 == 0.0 {
+                        None
+                    } else {
+                        Some(p1 / p2)
+                    }
+                }
+                RatioKind::Difference => Some(p1 - p2),
+            }
+        } else {
+            None
+        }
+    }
+}
+impl Condition for RatioCut {
+    fn evaluate(&mut self, event: &parameters::FlatEvent) -> bool {
+        let result = if let Some(value) = self.value(event) {
+            (value >= self.low) && (value <= self.high)
+        } else {
+            false
+        };
+        self.cache = Some(result);
+        result
+    }
+    fn condition_type(&self) -> String {
+        String::from(match self.kind {
+            RatioKind::Ratio => "Ratio",
+            RatioKind::Difference => "Difference",
+        })
+    }
+    fn condition_points(&self) -> Vec<(f64, f64)> {
+        vec![(self.low, 0.0), (self.high, 0.0)]
+    }
+    fn dependent_conditions(&self) -> Vec<ContainerReference> {
+        vec![]
+    }
+    fn dependent_parameters(&self) -> Vec<u32> {
+        vec![self.p1, self.p2]
+    }
+    fn get_cached_value(&self) -> Option<bool> {
+        self.cache
+    }
+    fn invalidate_cache(&mut self) {
+        self.cache = None;
+    }
+}
+#[cfg(test)]
+mod ratio_tests {
+    use super::*;
+    use crate::parameters::{EventParameter, FlatEvent};
+
+    #[test]
+    fn new_1() {
+        let r = RatioCut::new(RatioKind::Ratio, 1, 2, 0.5, 1.5);
+        assert_eq!(
+            RatioCut {
+                kind: RatioKind::Ratio,
+                p1: 1,
+                p2: 2,
+                low: 0.5,
+                high: 1.5,
+                cache: None
+            },
+            r
+        );
+    }
+    #[test]
+    fn describe_1() {
+        let r = RatioCut::new(RatioKind::Difference, 3, 1, -10.0, 10.0);
+        assert_eq!("Difference", r.condition_type());
+        assert_eq!(
+            "Ratio",
+            RatioCut::new(RatioKind::Ratio, 3, 1, -10.0, 10.0).condition_type()
+        );
+        assert_eq!(vec![(-10.0, 0.0), (10.0, 0.0)], r.condition_points());
+        assert_eq!(vec![3, 1], r.dependent_parameters()); // Order matters.
+        assert!(r.dependent_conditions().is_empty());
+        assert!(!r.is_fold());
+    }
+    #[test]
+    fn missing_1() {
+        // Both parameters are needed:
+
+        let mut r = RatioCut::new(RatioKind::Difference, 1, 2, -100.0, 100.0);
+        let mut e = FlatEvent::new();
+        e.load_event(&vec![EventParameter::new(1, 10.0)]);
+        assert!(r.value(&e).is_none());
+        assert!(!r.check(&e));
+        assert_eq!(Some(false), r.get_cached_value());
+        r.invalidate_cache();
+        assert!(r.get_cached_value().is_none());
+
+        e.load_event(&vec![EventParameter::new(2, 10.0)]);
+        assert!(!r.check(&e));
+    }
+    #[test]
+    fn ratio_1() {
+        let mut r = RatioCut::new(RatioKind::Ratio, 1, 2, 0.5, 1.5);
+        let mut e = FlatEvent::new();
+        e.load_event(&vec![
+            EventParameter::new(1, 50.0),
+            EventParameter::new(2, 100.0),
+        ]);
+        assert_eq!(Some(0.5), r.value(&e)); // Limits are inclusive.
+        assert!(r.check(&e));
+
+        // p2/p1 is in range but we compute p1/p2:
+
+        r.invalidate_cache();
+        e.load_event(&vec![
+            EventParameter::new(1, 100.0),
+            EventParameter::new(2, 40.0),
+        ]);
+        assert!(!r.check(&e));
+    }
+    #[test]
+    fn ratio_2() {
+        // Divide by zero is false rather than infinite or NaN:
+
+        let mut r = RatioCut::new(RatioKind::Ratio, 1, 2, f64::MIN, f64::MAX);
+        let mut e = FlatEvent::new();
+        e.load_event(&vec![
+            EventParameter::new(1, 0.0),
+            EventParameter::new(2, 0.0),
+        ]);
+        assert!(r.value(&e).is_none());
+        assert!(!r.check(&e));
+    }
+    #[test]
+    fn difference_1() {
+        let mut r = RatioCut::new(RatioKind::Difference, 1, 2, -10.0, 10.0);
+        let mut e = FlatEvent::new();
+        e.load_event(&vec![
+            EventParameter::new(1, 105.0),
+            EventParameter::new(2, 100.0),
+        ]);
+        assert_eq!(Some(5.0), r.value(&e));
+        assert!(r.check(&e));
+
+        r.invalidate_cache();
+        e.load_event(&vec![
+            EventParameter::new(1, 85.0),
+            EventParameter::new(2, 100.0),
+        ]);
+        assert!(!r.check(&e));
+    }
+}
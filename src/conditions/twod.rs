@@ -1,11 +1,17 @@
 //!  2-d conditions are conditions that are defined in the plane
-//!  defined by two dependent parameters.  There are currently two types
+//!  defined by two dependent parameters.  There are currently three types
 //!  Of 2-d conditions:
 //!
 //!  *  Bands - which can be thought of as a polyline.
 //!  *  Contours, which can be thought of as a closed figure created
 //!     by taking the  last point and joining it to the first poin tof a
 //!     band.
+//!  *  Contour sets, which are several contours on the same parameters.
+//!  *  Contours made from two bands (C2Band, SpecTcl's c2band).
+//!
+//!  Bands and contours also have gamma forms (MultiBand and MultiContour)
+//!  that apply the figure to every pair of a set of parameters and can be
+//!  used to fold spectra.
 //!
 //!  Each of these has its own requirements and definitions of
 //!  acceptance.  Since the computations required to compute if
@@ -42,6 +48,23 @@
 //! do object color fills in graphical objects.  It supports arbitrarily
 //! pathalogical figures in a well defined way.  
 //!
+//! ## Contour sets
+//!    Contour sets require a pair of dependent parameters and at least one
+//!    polygon, each of which must have at least three points.  The contour
+//!    set is true for any event that is inside any of the contours those
+//!    polygons define.  This is the same as an Or of contours but is simpler
+//!    to define for figures with islands (e.g. a banana gate whose
+//!    particle identification band is broken up).
+//!
+//! ## Contours from two bands
+//!    A C2Band depends on two bands on the same pair of parameters.  It is
+//!    the contour whose points are those of the first band followed by
+//!    those of the second band in reverse order.  The bands are
+//!    referenced like the dependent conditions of compound conditions so
+//!    editing either band changes the contour.  If either band is deleted,
+//!    is replaced by something that is not a band or the bands no longer
+//!    have the same parameters, the condition is false.
+//!
 //! The acceptancd criteria for bands and contours is identical to the
 //! criteria used by SpecTcl for these types of conditions.
 use super::*;
@@ -162,42 +185,40 @@ impl Band {
     pub fn get_points(&self) -> Points {
         self.points.clone()
     }
+    /// Determine if a point is inside (below) the band.
+    pub fn inside(&self, x: f64, y: f64) -> bool {
+        // Look for a line segment that makes the condition true:
+
+        for s in &self.segments {
+            if (x >= s.p1.x) && (x <= s.p2.x) {
+                // Two cases vertical line...we must be below
+                // the  largest y ( or one of the 's)
+                // If not vertical compute the point on the segment
+                // at that point we must be below it.
+
+                return match (s.m, s.b) {
+                    (Some(m), Some(b)) => y <= m * x + b,
+                    _ => y <= s.p1.y || y <= s.p2.y,
+                };
+            }
+        }
+        false
+    }
 }
 impl Condition for Band {
     fn evaluate(&mut self, event: &FlatEvent) -> bool {
         // Need both parameters:
 
-        if event[self.parameters.0].is_none() || event[self.parameters.1].is_none() {
-            self.cache = Some(false);
+        let result = if event[self.parameters.0].is_none() || event[self.parameters.1].is_none() {
             false
         } else {
-            let x = event[self.parameters.0].unwrap();
-            let y = event[self.parameters.1].unwrap();
-
-            // Look for a line segment that makes the condition true:
-
-            for s in &self.segments {
-                if (x >= s.p1.x) && (x <= s.p2.x) {
-                    // Two cases vertical line...we must be below
-                    // the  largest y ( or one of the 's)
-                    // If not vertical compute the point on the segment
-                    // at that point we must be below it.
-
-                    if s.m.is_none() {
-                        let result = y <= s.p1.y || y <= s.p2.y;
-                        self.cache = Some(result);
-                        return result;
-                    } else {
-                        let pty = s.m.unwrap() * x + s.b.unwrap();
-                        let result = y <= pty;
-                        self.cache = Some(result);
-                        return result;
-                    }
-                }
-            }
-            self.cache = Some(false);
-            false
-        }
+            self.inside(
+                event[self.parameters.0].unwrap(),
+                event[self.parameters.1].unwrap(),
+            )
+        };
+        self.cache = Some(result);
+        result
     }
     fn condition_type(&self) -> String {
         String::from("Band")
@@ -505,6 +526,287 @@ impl Condition for MultiContour {
         result
     }
 }
+///
+/// MultiBand is what SpecTcl called a gb.  Like MultiContour it implements
+/// both the condition and fold traits and can be used to fold spectra.
+///
+/// When using MultiBand as a gate, it is true as long as any pair of
+/// parameters is below the band.
+///
+/// When using MultiBand as a 1-d fold, any parameters that are in a pair
+/// of parameters that is not below the band are returned.
+///
+/// When using MultiBand as a 2-d fold, any pair of parameters not below the
+/// band is returned.
+///
+/// Implementation note... we are really just a Band that ignores its
+/// parameters and supplies an unbounded vector of parameter ids instead.
+///
+pub struct MultiBand {
+    band: Band,
+    parameters: Vec<u32>,
+    cache: Option<bool>,
+}
+
+impl MultiBand {
+    /// Create a new gamma band.
+    ///
+    /// ### Parameters:
+    ///  *  parameters the parameters that are actually used for the condition/fold.
+    ///  *  pts  - the points that define the band (at least two).
+    ///
+    pub fn new(parameters: &[u32], pts: Points) -> Option<MultiBand> {
+        Band::new(0, 0, pts).map(|b| MultiBand {
+            band: b, // Use dummy parameter ids
+            parameters: parameters.to_owned(),
+            cache: None,
+        })
+    }
+}
+impl Condition for MultiBand {
+    fn evaluate(&mut self, event: &FlatEvent) -> bool {
+        for (i, p1) in self.parameters.iter().enumerate() {
+            for p2 in self.parameters.iter().skip(i + 1) {
+                if let (Some(x), Some(y)) = (event[*p1], event[*p2]) {
+                    // Use both orientations:
+
+                    if self.band.inside(x, y) || self.band.inside(y, x) {
+                        self.cache = Some(true);
+                        return true;
+                    }
+                }
+            }
+        }
+        self.cache = Some(false);
+        false
+    }
+    fn condition_type(&self) -> String {
+        String::from("MultiBand")
+    }
+    fn condition_points(&self) -> Vec<(f64, f64)> {
+        self.band.condition_points()
+    }
+    fn dependent_conditions(&self) -> Vec<ContainerReference> {
+        vec![]
+    }
+    fn dependent_parameters(&self) -> Vec<u32> {
+        self.parameters.clone()
+    }
+    fn get_cached_value(&self) -> Option<bool> {
+        self.cache
+    }
+    fn invalidate_cache(&mut self) {
+        self.cache = None;
+    }
+
+    // fold
+
+    fn is_fold(&self) -> bool {
+        true
+    }
+    fn evaluate_1(&mut self, event: &parameters::FlatEvent) -> HashSet<u32> {
+        // The parameters of the pairs evaluate_2 gives:
+
+        self.evaluate_2(event)
+            .into_iter()
+            .flat_map(|(p1, p2)| [p1, p2])
+            .collect()
+    }
+    fn evaluate_2(&mut self, event: &parameters::FlatEvent) -> HashSet<(u32, u32)> {
+        let mut result = HashSet::<(u32, u32)>::new();
+        for (i, p1) in self.parameters.iter().enumerate() {
+            for p2 in self.parameters.iter().skip(i + 1) {
+                if let (Some(x), Some(y)) = (event[*p1], event[*p2]) {
+                    if !self.band.inside(x, y) {
+                        result.insert((*p1, *p2));
+                    }
+                }
+            }
+        }
+
+        result
+    }
+}
+///
+/// ContourSet is a set of contours on the same pair of parameters.  It is
+/// true if the event is inside any of the contours.  Each contour keeps its
+/// own circumscribing rectangle so an event is only checked in detail
+/// against the contours it might be in.
+///
+pub struct ContourSet {
+    p1: u32,
+    p2: u32,
+    contours: Vec<Contour>,
+    cache: Option<bool>,
+}
+impl ContourSet {
+    /// Create a contour set.
+    ///
+    /// ### Parameters:
+    ///  *  p1, p2 - the x and y parameters.
+    ///  *  polygons - the points of each contour.
+    ///
+    /// ### Returns:
+    ///  None if there are no polygons or a polygon has fewer than three points.
+    ///
+    pub fn new(p1: u32, p2: u32, polygons: Vec<Points>) -> Option<ContourSet> {
+        if polygons.is_empty() {
+            return None;
+        }
+        let mut contours = Vec::<Contour>::new();
+        for pts in polygons {
+            contours.push(Contour::new(p1, p2, pts)?);
+        }
+        Some(ContourSet {
+            p1,
+            p2,
+            contours,
+            cache: None,
+        })
+    }
+    ///
+    /// Given a point return true if it is inside any of the contours.
+    ///
+    pub fn inside(&self, x: f64, y: f64) -> bool {
+        self.contours.iter().any(|c| c.inside(x, y))
+    }
+}
+impl Condition for ContourSet {
+    fn evaluate(&mut self, event: &FlatEvent) -> bool {
+        let result = if event[self.p1].is_none() || event[self.p2].is_none() {
+            false
+        } else {
+            self.inside(event[self.p1].unwrap(), event[self.p2].unwrap())
+        };
+        self.cache = Some(result);
+        result
+    }
+    fn condition_type(&self) -> String {
+        String::from("ContourSet")
+    }
+    fn condition_points(&self) -> Vec<(f64, f64)> {
+        self.contours
+            .iter()
+            .flat_map(|c| c.condition_points())
+            .collect()
+    }
+    fn condition_polygons(&self) -> Vec<Vec<(f64, f64)>> {
+        self.contours.iter().map(|c| c.condition_points()).collect()
+    }
+    fn dependent_conditions(&self) -> Vec<ContainerReference> {
+        Vec::<ContainerReference>::new()
+    }
+    fn dependent_parameters(&self) -> Vec<u32> {
+        vec![self.p1, self.p2]
+    }
+    fn get_cached_value(&self) -> Option<bool> {
+        self.cache
+    }
+    fn invalidate_cache(&mut self) {
+        self.cache = None;
+    }
+}
+
+///
+/// C2Band is a contour made from two bands.  The contour is made from
+/// the bands when first evaluated and remade whenever the points or
+/// parameters of either band are found to have changed.
+///
+pub struct C2Band {
+    bands: [ContainerReference; 2],
+    contour: Option<Contour>,
+    cache: Option<bool>,
+}
+impl C2Band {
+    /// Create the condition.
+    ///
+    /// ### Parameters:
+    ///  *  band1, band2 - containers holding the bands.
+    ///
+    /// ### Returns:
+    ///  None unless both are bands on the same parameters.
+    ///
+    pub fn new(band1: &Container, band2: &Container) -> Option<C2Band> {
+        let result = C2Band {
+            bands: [Rc::downgrade(band1), Rc::downgrade(band2)],
+            contour: None,
+            cache: None,
+        };
+        result.definition()?;
+        Some(result)
+    }
+    // The parameters and contour points defined by the bands as they are
+    // now.  None if that does not make a contour.  try_borrow keeps a
+    // C2Band that has replaced one of its own bands from panicking.
+
+    fn definition(&self) -> Option<(u32, u32, Points)> {
+        let mut parameters = Vec::<Vec<u32>>::new();
+        let mut points = Vec::<Vec<(f64, f64)>>::new();
+        for band in self.bands.iter() {
+            let band = band.upgrade()?;
+            let band = band.try_borrow().ok()?;
+            if band.condition_type() != "Band" {
+                return None;
+            }
+            parameters.push(band.dependent_parameters());
+            points.push(band.condition_points());
+        }
+        if parameters[0] != parameters[1] || parameters[0].len() != 2 {
+            return None;
+        }
+        let pts = points[0]
+            .iter()
+            .chain(points[1].iter().rev())
+            .map(|(x, y)| Point::new(*x, *y))
+            .collect();
+        Some((parameters[0][0], parameters[0][1], pts))
+    }
+}
+impl Condition for C2Band {
+    fn evaluate(&mut self, event: &FlatEvent) -> bool {
+        let result = if let Some((p1, p2, pts)) = self.definition() {
+            let stale = match &self.contour {
+                Some(c) => c.p1 != p1 || c.p2 != p2 || c.pts != pts,
+                None => true,
+            };
+            if stale {
+                self.contour = Contour::new(p1, p2, pts);
+            }
+            match self.contour.as_mut() {
+                Some(c) => c.evaluate(event),
+                None => false,
+            }
+        } else {
+            false
+        };
+        self.cache = Some(result);
+        result
+    }
+    fn condition_type(&self) -> String {
+        String::from("C2Band")
+    }
+    fn condition_points(&self) -> Vec<(f64, f64)> {
+        match self.definition() {
+            Some((_, _, pts)) => pts.iter().map(|p| (p.x, p.y)).collect(),
+            None => vec![],
+        }
+    }
+    fn dependent_conditions(&self) -> Vec<ContainerReference> {
+        self.bands.to_vec()
+    }
+    fn dependent_parameters(&self) -> Vec<u32> {
+        match self.definition() {
+            Some((p1, p2, _)) => vec![p1, p2],
+            None => vec![],
+        }
+    }
+    fn get_cached_value(&self) -> Option<bool> {
+        self.cache
+    }
+    fn invalidate_cache(&mut self) {
+        self.cache = None;
+    }
+}
 
 #[cfg(test)]
 mod band_tests {
@@ -1401,3 +1703,275 @@ mod multicontour_tests {
         assert_eq!(HashSet::from_iter([(1, 2), (1, 3)].iter().cloned()), p);
     }
 }
+#[cfg(test)]
+mod multiband_tests {
+    use super::*;
+    use crate::parameters::{EventParameter, FlatEvent};
+
+    // A tent shaped band - the band tests already test being below it:
+
+    fn test_points() -> Points {
+        vec![
+            Point::new(0.0, 0.0),
+            Point::new(50.0, 100.0),
+            Point::new(100.0, 0.0),
+        ]
+    }
+    fn event(values: &[(u32, f64)]) -> FlatEvent {
+        let e: Vec<EventParameter> = values
+            .iter()
+            .map(|(id, v)| EventParameter::new(*id, *v))
+            .collect();
+        let mut fe = FlatEvent::new();
+        fe.load_event(&e);
+        fe
+    }
+
+    #[test]
+    fn new_1() {
+        let b = MultiBand::new(&[1, 2, 3], test_points()).expect("Making multiband");
+        assert_eq!(vec![1, 2, 3], b.parameters);
+        assert_eq!(None, b.cache);
+        assert_eq!("MultiBand", b.condition_type());
+        assert_eq!(
+            vec![(0.0, 0.0), (50.0, 100.0), (100.0, 0.0)],
+            b.condition_points()
+        );
+        assert_eq!(vec![1, 2, 3], b.dependent_parameters());
+        assert!(b.is_fold());
+    }
+    #[test]
+    fn new_2() {
+        // A band needs two points:
+
+        assert!(MultiBand::new(&[1, 2], vec![Point::new(0.0, 0.0)]).is_none());
+    }
+    #[test]
+    fn check_1() {
+        // One pair below the band makes the condition:
+
+        let mut b = MultiBand::new(&[1, 2, 3], test_points()).expect("Making multiband");
+        assert!(b.check(&event(&[(1, 50.0), (2, 200.0), (3, 20.0)])));
+        assert_eq!(Some(true), b.get_cached_value());
+    }
+    #[test]
+    fn check_2() {
+        // Pairs are tried in both orientations:
+
+        let mut b = MultiBand::new(&[1, 2], test_points()).expect("Making multiband");
+        assert!(b.check(&event(&[(1, 10.0), (2, 50.0)])));
+    }
+    #[test]
+    fn check_3() {
+        // No pair below the band:
+
+        let mut b = MultiBand::new(&[1, 2, 3], test_points()).expect("Making multiband");
+        assert!(!b.check(&event(&[(1, 50.0), (2, 200.0), (3, 300.0)])));
+        assert_eq!(Some(false), b.get_cached_value());
+        assert!(!b.check(&event(&[(1, 50.0)])));
+    }
+    #[test]
+    fn fold2_1() {
+        // Pairs that are not below the band come out:
+
+        let mut b = MultiBand::new(&[1, 2, 3], test_points()).expect("Making multiband");
+        let pairs = b.evaluate_2(&event(&[(1, 50.0), (2, 20.0), (3, 300.0)]));
+        assert_eq!(HashSet::from_iter([(1, 3), (2, 3)].iter().cloned()), pairs);
+    }
+    #[test]
+    fn fold1_1() {
+        let mut b = MultiBand::new(&[1, 2, 3], test_points()).expect("Making multiband");
+        let params = b.evaluate_1(&event(&[(1, 50.0), (2, 20.0), (3, 300.0)]));
+        assert_eq!(HashSet::from_iter([1, 2, 3].iter().cloned()), params);
+
+        let params = b.evaluate_1(&event(&[(1, 50.0), (2, 20.0), (3, 40.0)]));
+        assert!(params.is_empty());
+    }
+}
+#[cfg(test)]
+mod contourset_tests {
+    use super::*;
+
+    // Two squares: (0,0)-(10,10) and an island (20,20)-(30,30):
+
+    fn test_polygons() -> Vec<Points> {
+        vec![
+            vec![
+                Point::new(0.0, 0.0),
+                Point::new(10.0, 0.0),
+                Point::new(10.0, 10.0),
+                Point::new(0.0, 10.0),
+            ],
+            vec![
+                Point::new(20.0, 20.0),
+                Point::new(30.0, 20.0),
+                Point::new(30.0, 30.0),
+            ],
+        ]
+    }
+    fn event(x: Option<f64>, y: Option<f64>) -> FlatEvent {
+        let mut e = vec![];
+        if let Some(x) = x {
+            e.push(EventParameter::new(1, x));
+        }
+        if let Some(y) = y {
+            e.push(EventParameter::new(2, y));
+        }
+        let mut fe = FlatEvent::new();
+        fe.load_event(&e);
+        fe
+    }
+
+    #[test]
+    fn new_1() {
+        // Need at least one polygon and at least 3 points in each:
+
+        assert!(ContourSet::new(1, 2, vec![]).is_none());
+        let mut polygons = test_polygons();
+        polygons[1].pop();
+        assert!(ContourSet::new(1, 2, polygons).is_none());
+        assert!(ContourSet::new(1, 2, test_polygons()).is_some());
+    }
+    #[test]
+    fn check_1() {
+        // Inside either contour, outside both or missing a parameter:
+
+        let mut c = ContourSet::new(1, 2, test_polygons()).expect("Making contour set");
+        for (x, y, result) in [
+            (Some(5.0), Some(5.0), true),
+            (Some(28.0), Some(22.0), true),
+            (Some(15.0), Some(15.0), false),
+            (Some(22.0), Some(28.0), false),
+            (Some(5.0), None, false),
+            (None, Some(5.0), false),
+        ] {
+            c.invalidate_cache();
+            assert_eq!(result, c.check(&event(x, y)), "{:?}, {:?}", x, y);
+            assert_eq!(Some(result), c.get_cached_value());
+        }
+    }
+    #[test]
+    fn describe_1() {
+        let c = ContourSet::new(1, 2, test_polygons()).expect("Making contour set");
+        assert_eq!("ContourSet", c.condition_type());
+        assert_eq!(vec![1, 2], c.dependent_parameters());
+        assert!(c.dependent_conditions().is_empty());
+
+        let polygons = c.condition_polygons();
+        assert_eq!(2, polygons.len());
+        for (i, p) in test_polygons().iter().enumerate() {
+            let pts: Vec<(f64, f64)> = p.iter().map(|pt| (pt.x, pt.y)).collect();
+            assert_eq!(pts, polygons[i]);
+        }
+        assert_eq!(7, c.condition_points().len());
+        assert_eq!((20.0, 20.0), c.condition_points()[4]);
+    }
+}
+#[cfg(test)]
+mod c2band_tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    fn band(p1: u32, p2: u32, pts: &[(f64, f64)]) -> Container {
+        let pts = pts.iter().map(|(x, y)| Point::new(*x, *y)).collect();
+        Rc::new(RefCell::new(Box::new(
+            Band::new(p1, p2, pts).expect("Making band"),
+        )))
+    }
+    fn event(x: f64, y: f64) -> FlatEvent {
+        let mut e = FlatEvent::new();
+        e.load_event(&vec![EventParameter::new(1, x), EventParameter::new(2, y)]);
+        e
+    }
+
+    #[test]
+    fn new_1() {
+        // Bands must be on the same parameters:
+
+        let upper = band(1, 2, &[(0.0, 100.0), (100.0, 100.0)]);
+        let lower = band(1, 2, &[(0.0, 50.0), (100.0, 50.0)]);
+        let other = band(1, 3, &[(0.0, 50.0), (100.0, 50.0)]);
+        let t: Container = Rc::new(RefCell::new(Box::new(True {})));
+
+        assert!(C2Band::new(&upper, &lower).is_some());
+        assert!(C2Band::new(&upper, &other).is_none());
+        assert!(C2Band::new(&upper, &t).is_none());
+    }
+    #[test]
+    fn describe_1() {
+        let upper = band(1, 2, &[(0.0, 100.0), (100.0, 100.0)]);
+        let lower = band(1, 2, &[(0.0, 50.0), (50.0, 40.0), (100.0, 50.0)]);
+        let c = C2Band::new(&upper, &lower).expect("Making c2band");
+
+        assert_eq!("C2Band", c.condition_type());
+        assert_eq!(
+            vec![
+                (0.0, 100.0),
+                (100.0, 100.0),
+                (100.0, 50.0),
+                (50.0, 40.0),
+                (0.0, 50.0)
+            ],
+            c.condition_points()
+        );
+        assert_eq!(vec![1, 2], c.dependent_parameters());
+        let deps = c.dependent_conditions();
+        assert_eq!(2, deps.len());
+        assert!(Rc::ptr_eq(&upper, &deps[0].upgrade().unwrap()));
+        assert!(Rc::ptr_eq(&lower, &deps[1].upgrade().unwrap()));
+    }
+    #[test]
+    fn evaluate_1() {
+        // Between the bands is inside:
+
+        let upper = band(1, 2, &[(0.0, 100.0), (100.0, 100.0)]);
+        let lower = band(1, 2, &[(0.0, 50.0), (100.0, 50.0)]);
+        let mut c = C2Band::new(&upper, &lower).expect("Making c2band");
+
+        assert!(c.check(&event(50.0, 75.0)));
+        assert_eq!(Some(true), c.get_cached_value());
+        c.invalidate_cache();
+        assert!(!c.check(&event(50.0, 25.0)));
+        c.invalidate_cache();
+        assert!(!c.check(&event(50.0, 125.0)));
+        c.invalidate_cache();
+
+        let mut e = FlatEvent::new();
+        e.load_event(&vec![EventParameter::new(1, 50.0)]);
+        assert!(!c.check(&e));
+    }
+    #[test]
+    fn evaluate_2() {
+        // Editing a band changes the contour:
+
+        let upper = band(1, 2, &[(0.0, 100.0), (100.0, 100.0)]);
+        let lower = band(1, 2, &[(0.0, 50.0), (100.0, 50.0)]);
+        let mut c = C2Band::new(&upper, &lower).expect("Making c2band");
+        assert!(!c.check(&event(50.0, 25.0)));
+
+        let pts = vec![Point::new(0.0, 10.0), Point::new(100.0, 10.0)];
+        lower.replace(Box::new(Band::new(1, 2, pts).unwrap()));
+        c.invalidate_cache();
+        assert!(c.check(&event(50.0, 25.0)));
+        assert_eq!((0.0, 10.0), c.condition_points()[3]);
+    }
+    #[test]
+    fn evaluate_3() {
+        // False if a band is deleted or is no longer a band:
+
+        let upper = band(1, 2, &[(0.0, 100.0), (100.0, 100.0)]);
+        let lower = band(1, 2, &[(0.0, 50.0), (100.0, 50.0)]);
+        let mut c = C2Band::new(&upper, &lower).expect("Making c2band");
+        assert!(c.check(&event(50.0, 75.0)));
+
+        upper.replace(Box::new(True {}));
+        c.invalidate_cache();
+        assert!(!c.check(&event(50.0, 75.0)));
+        assert!(c.condition_points().is_empty());
+
+        let upper = band(1, 2, &[(0.0, 100.0), (100.0, 100.0)]);
+        let mut c = C2Band::new(&upper, &lower).expect("Making c2band");
+        drop(lower);
+        assert!(!c.check(&event(50.0, 75.0)));
+    }
+}
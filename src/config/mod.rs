@@ -0,0 +1,154 @@
+//!  Configuration files.  Facility deployments often need many
+//!  command line options.  Rather than putting them all in a start
+//!  script they can be put in a TOML file named by the --config option.
+//!
+//!  The keys of the file are the long names of the command line options
+//!  without the leading --, e.g.:
+//!
+//!  ```toml
+//!  shm-mbytes = 128
+//!  rest-service = "RUSTO_REST"
+//!  mirror-service = "RUSTO_MIRROR"
+//!  read-only = true
+//!  ```
+//!
+//!  Every option other than --config and --selftest can be given.
+//!  Options given on the command line override the file.  Unknown keys
+//!  are errors so that misspelled options are not silently ignored.
+//!
+//!  The file only covers command line options.  There are no logging
+//!  options, and the definitions loaded at startup come from the
+//!  session named by resume-session, so there are no keys for logging
+//!  or startup definition files.
+//!
+use rocket::figment::providers::{Format, Toml};
+use rocket::figment::Figment;
+use rocket::serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// The options that can be set in a configuration file.  Options that
+/// are not in the file are None.
+///
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(
+    crate = "rocket::serde",
+    rename_all = "kebab-case",
+    deny_unknown_fields
+)]
+pub struct ConfigFile {
+    pub shm_mbytes: Option<usize>,
+    pub rest_port: Option<u16>,
+    pub rest_service: Option<String>,
+    pub mirror_port: Option<u16>,
+    pub mirror_service: Option<String>,
+    pub max_fill_points: Option<usize>,
+    pub max_bulk_spectra: Option<usize>,
+    pub max_body_bytes: Option<u64>,
+    pub name_punctuation: Option<String>,
+    pub max_name_length: Option<usize>,
+    pub case_insensitive_names: Option<bool>,
+    pub enable_simulation: Option<bool>,
+    pub histogramer_timeout: Option<f64>,
+    pub response_cache_ttl: Option<f64>,
+    pub session: Option<String>,
+    pub resume_session: Option<String>,
+    pub session_contents: Option<bool>,
+    pub session_dir: Option<String>,
+    pub parameter_id_map: Option<String>,
+    pub read_only: Option<bool>,
+    pub transfer_dir: Option<String>,
+}
+
+/// Parse the text of a configuration file.
+///
+/// ### Returns
+/// Result<ConfigFile, String> - the error string describes what is wrong
+/// with the text.
+pub fn parse(text: &str) -> Result<ConfigFile, String> {
+    Figment::from(Toml::string(text))
+        .extract::<ConfigFile>()
+        .map_err(|e| e.to_string())
+}
+
+/// Read a configuration file.  Unlike Rocket's own configuration file,
+/// a missing file is an error as it was explicitly asked for.
+///
+/// ### Parameters
+/// * path - the file to read.
+///
+/// ### Returns
+/// Result<ConfigFile, String> - the error string says what went wrong.
+pub fn read(path: &Path) -> Result<ConfigFile, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+    parse(&text).map_err(|e| format!("Invalid configuration file {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn parse_1() {
+        // Empty file sets nothing:
+
+        assert_eq!(ConfigFile::default(), parse("").expect("Parsing"));
+    }
+    #[test]
+    fn parse_2() {
+        let config = parse(
+            r#"
+shm-mbytes = 128
+rest-service = "RUSTO_REST"
+histogramer-timeout = 2.5
+read-only = true
+"#,
+        )
+        .expect("Parsing");
+        assert_eq!(
+            ConfigFile {
+                shm_mbytes: Some(128),
+                rest_service: Some(String::from("RUSTO_REST")),
+                histogramer_timeout: Some(2.5),
+                read_only: Some(true),
+                ..Default::default()
+            },
+            config
+        );
+    }
+    #[test]
+    fn parse_3() {
+        // Unknown keys, bad values and bad syntax:
+
+        for text in [
+            "shm_mbytes = 128",
+            "selftest = true",
+            "rest-port = 100000",
+            "read-only = \"yes\"",
+            "shm-mbytes = ",
+        ] {
+            assert!(parse(text).is_err(), "{}", text);
+        }
+    }
+    #[test]
+    fn read_1() {
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let path = dir.path().join("rustogramer.toml");
+        assert!(read(&path).is_err());
+
+        let mut file = fs::File::create(&path).expect("Creating file");
+        file.write_all(b"mirror-port = 9001\nsession = \"run\"\n")
+            .expect("Writing file");
+        drop(file);
+        assert_eq!(
+            ConfigFile {
+                mirror_port: Some(9001),
+                session: Some(String::from("run")),
+                ..Default::default()
+            },
+            read(&path).expect("Reading")
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//!  This module retains crash reports for the threads that make up
+//!  rustogramer (the histogramer, binder, mirror and processing threads).
+//!  Without this, when one of those threads panics, the remote users
+//!  of the ReST interface just see requests that hang or fail
+//!  with no indication of why.
+//!
+//!  A panic hook is installed that, for each panic, records the name of
+//!  the thread that panicked, the panic message, where the panic happened
+//!  and a backtrace into a bounded crash report store.  The hook
+//!  then chains to the hook it replaced so that panics are still
+//!  logged to stderr.  The store is available to the ReST interface
+//!  (see src/rest/crashes.rs).
+//!
+//!  Threads should be started with the spawn function below so that
+//!  their crash reports identify them by name.
+//!
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::panic;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time;
+
+/// Maximum number of crash reports retained.  When exceeded, the
+/// oldest reports are discarded.
+pub const MAX_CRASH_REPORTS: usize = 100;
+
+/// A crash report:
+///
+/// * stamp - when the panic happened.
+/// * thread - name of the thread that panicked ("unnamed" if it has no name).
+/// * message - the panic message.
+/// * location - file:line:column of the panic if known.
+/// * backtrace - the backtrace at the time of the panic.
+///
+#[derive(Clone)]
+pub struct CrashReport {
+    pub stamp: time::SystemTime,
+    pub thread: String,
+    pub message: String,
+    pub location: String,
+    pub backtrace: String,
+}
+impl CrashReport {
+    pub fn new(thread: &str, message: &str, location: &str, backtrace: &str) -> CrashReport {
+        CrashReport {
+            stamp: time::SystemTime::now(),
+            thread: String::from(thread),
+            message: String::from(message),
+            location: String::from(location),
+            backtrace: String::from(backtrace),
+        }
+    }
+}
+
+/// The crash report store shared between the panic hook and
+/// the ReST interface.  Clones share the same store.
+///
+#[derive(Clone)]
+pub struct SharedCrashStore {
+    store: Arc<Mutex<Vec<CrashReport>>>,
+}
+
+impl SharedCrashStore {
+    pub fn new() -> SharedCrashStore {
+        SharedCrashStore {
+            store: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+    /// Record a crash report.  If the store is full, the oldest report
+    /// is discarded.  Since this is called from the panic hook it must not
+    /// panic, so a poisoned store is quietly ignored.
+    pub fn record(&self, report: CrashReport) {
+        if let Ok(mut store) = self.store.lock() {
+            store.push(report);
+            if store.len() > MAX_CRASH_REPORTS {
+                let excess = store.len() - MAX_CRASH_REPORTS;
+                store.drain(0..excess);
+            }
+        }
+    }
+    /// Return the crash reports, oldest first.
+    pub fn list(&self) -> Vec<CrashReport> {
+        self.store.lock().unwrap().clone()
+    }
+    /// Remove all crash reports returning the number removed.
+    pub fn clear(&self) -> usize {
+        let mut store = self.store.lock().unwrap();
+        let result = store.len();
+        store.clear();
+        result
+    }
+    /// Install a panic hook that records crash reports in this store.
+    /// The hook that was in place is called after the report is recorded.
+    pub fn install_panic_hook(&self) {
+        let store = self.clone();
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let current = thread::current();
+            let thread_name = current.name().unwrap_or("unnamed");
+            let location = if let Some(l) = info.location() {
+                format!("{}:{}:{}", l.file(), l.line(), l.column())
+            } else {
+                String::from("unknown")
+            };
+            store.record(CrashReport::new(
+                thread_name,
+                &panic_message(info.payload()),
+                &location,
+                &Backtrace::force_capture().to_string(),
+            ));
+            eprintln!(
+                "Thread {} panicked - a crash report was recorded",
+                thread_name
+            );
+            previous(info);
+        }));
+    }
+}
+impl Default for SharedCrashStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+/// Get the message from a panic payload.  Panics with a formatted message
+/// have a String payload, those with a literal message a &str.
+///
+/// ### Parameters:
+/// *  payload - the panic payload.
+///
+/// ### Returns:
+/// String - the message, or a note that the payload is not a message.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        String::from(*s)
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        String::from("Panic payload is not a message")
+    }
+}
+/// Spawn a named thread.  The name identifies the thread in
+/// crash reports.
+///
+/// ### Parameters:
+/// *  name - the thread name.
+/// *  f - the thread body.
+///
+/// ### Returns:
+/// The thread's join handle.
+pub fn spawn<F, T>(name: &str, f: F) -> thread::JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    thread::Builder::new()
+        .name(String::from(name))
+        .spawn(f)
+        .expect("Unable to start thread")
+}
+
+#[cfg(test)]
+mod crash_tests {
+    use super::*;
+
+    #[test]
+    fn record_1() {
+        let store = SharedCrashStore::new();
+        assert!(store.list().is_empty());
+
+        store.record(CrashReport::new("histogramer", "oops", "here", "trace"));
+        let reports = store.list();
+        assert_eq!(1, reports.len());
+        assert_eq!("histogramer", reports[0].thread);
+        assert_eq!("oops", reports[0].message);
+        assert_eq!("here", reports[0].location);
+        assert_eq!("trace", reports[0].backtrace);
+
+        // Clones share the store:
+
+        assert_eq!(1, store.clone().clear());
+        assert!(store.list().is_empty());
+    }
+    #[test]
+    fn record_2() {
+        // The store is bounded, oldest reports go first:
+
+        let store = SharedCrashStore::new();
+        for i in 0..MAX_CRASH_REPORTS + 2 {
+            store.record(CrashReport::new("t", &format!("{}", i), "", ""));
+        }
+        let reports = store.list();
+        assert_eq!(MAX_CRASH_REPORTS, reports.len());
+        assert_eq!("2", reports[0].message);
+    }
+    #[test]
+    fn message_1() {
+        let r = panic::catch_unwind(|| panic!("literal"));
+        assert_eq!("literal", panic_message(r.unwrap_err().as_ref()));
+
+        let value = 12;
+        let r = panic::catch_unwind(|| panic!("formatted {}", value));
+        assert_eq!("formatted 12", panic_message(r.unwrap_err().as_ref()));
+
+        let r = panic::catch_unwind(|| panic::panic_any(12));
+        assert_eq!(
+            "Panic payload is not a message",
+            panic_message(r.unwrap_err().as_ref())
+        );
+    }
+    #[test]
+    fn spawn_1() {
+        let name = spawn("test-thread", || {
+            String::from(thread::current().name().unwrap())
+        })
+        .join()
+        .unwrap();
+        assert_eq!("test-thread", name);
+    }
+}
@@ -0,0 +1,637 @@
+//!  This module provides the fitting engine and the store of named
+//!  fits behind the /spectcl/fit REST interface.  As in SpecTcl, a fit
+//!  is defined on a range of channels of a 1-d spectrum.  Each time the
+//!  fit is updated, the current contents of those channels are fitted
+//!  and the resulting parameters are kept with the fit.
+//!
+//!  The only fit type is ```gaussian```;  a Gaussian peak on a linear
+//!  background:
+//!
+//!  f(x) = baseline + slope*x + height*exp(-(x-centroid)^2/(2*sigma^2))
+//!
+//!  where x is the channel number.  Fits are done by minimizing chi-square
+//!  with the Levenberg-Marquardt method where the channel errors are
+//!  Poisson (sqrt of the counts but at least 1).
+//!
+//!  A fit can be turned into a slice condition on its spectrum's
+//!  parameter that accepts centroid ± k*sigma (see FitGate).  The fit
+//!  remembers the conditions made from it so that updating the fit can
+//!  also move their limits.
+//!
+//!  Fitting is slow compared with histogramming an event so it's done
+//!  outside of the histogramer, in the REST handler, on contents gotten
+//!  from it by message exchanges (see crate::messaging).  The store only
+//!  holds the definitions and results of the fits.
+//!
+use crate::messaging::spectrum_messages::{Channel, ChannelType};
+use glob::Pattern;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// The type name of Gaussian on linear background fits.
+pub const GAUSSIAN: &str = "gaussian";
+
+// Number of free parameters in a Gaussian fit:
+
+const NPARAMS: usize = 5;
+// Iteration limits for the minimization:
+
+const MAX_ITERATIONS: usize = 500;
+const CONVERGENCE: f64 = 1.0e-12;
+
+/// The parameters of a Gaussian on a linear background.  chisquare
+/// is the chi-square per degree of freedom of the fit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GaussianParameters {
+    pub baseline: f64,
+    pub slope: f64,
+    pub height: f64,
+    pub centroid: f64,
+    pub sigma: f64,
+    pub chisquare: f64,
+}
+
+impl GaussianParameters {
+    /// Evaluate the fit function at x.
+    pub fn value(&self, x: f64) -> f64 {
+        let d = x - self.centroid;
+        self.baseline
+            + self.slope * x
+            + self.height * (-(d * d) / (2.0 * self.sigma * self.sigma)).exp()
+    }
+    /// The parameters as name/value pairs.  The names are those
+    /// SpecTcl uses for its gaussian fits with slope added.
+    pub fn as_pairs(&self) -> Vec<(String, f64)> {
+        vec![
+            (String::from("baseline"), self.baseline),
+            (String::from("slope"), self.slope),
+            (String::from("height"), self.height),
+            (String::from("centroid"), self.centroid),
+            (String::from("sigma"), self.sigma),
+            (String::from("chisquare"), self.chisquare),
+        ]
+    }
+}
+
+///
+/// Extract the channels to fit from the contents of a 1-d spectrum.
+///
+/// ### Parameters:
+/// * contents - the spectrum contents from the histogram server.
+/// * low, high - the (inclusive) range of channels.  Channel 0 is the
+///   first channel of the axis (not the underflow channel).
+///
+/// ### Returns:
+///   (channel, counts) pairs for each channel in the range.  Channels
+///   not in contents (which only has non-zero channels) have no counts.
+///
+pub fn region(contents: &[Channel], low: u32, high: u32) -> Vec<(f64, f64)> {
+    let mut result: Vec<(f64, f64)> = (low..=high).map(|c| (c as f64, 0.0)).collect();
+    for c in contents.iter().filter(|c| c.chan_type == ChannelType::Bin) {
+        // Bin 0 is the underflow channel:
+
+        if c.bin >= 1 {
+            let channel = (c.bin - 1) as u32;
+            if channel >= low && channel <= high {
+                result[(channel - low) as usize].1 += c.value;
+            }
+        }
+    }
+    result
+}
+
+///
+/// Fit a Gaussian on a linear background to a set of points.
+///
+/// ### Parameters:
+/// *  points - (x, counts) pairs.
+///
+/// ### Returns:
+///  Result<GaussianParameters, String> - an error if there are too few
+///  points, no peak over the background or the fit can't be done.
+///
+pub fn fit_gaussian(points: &[(f64, f64)]) -> Result<GaussianParameters, String> {
+    if points.len() <= NPARAMS {
+        return Err(format!(
+            "A gaussian fit needs more than {} channels",
+            NPARAMS
+        ));
+    }
+    // Fit with x relative to the middle of the region so that the
+    // background parameters are not strongly correlated:
+
+    let xmid = (points[0].0 + points[points.len() - 1].0) / 2.0;
+    let points: Vec<(f64, f64)> = points.iter().map(|(x, y)| (x - xmid, *y)).collect();
+    let mut p = initial_guess(&points)?;
+    let mut chisq = chisquare(&points, &p);
+    let mut lambda = 1.0e-3;
+
+    for _ in 0..MAX_ITERATIONS {
+        let (alpha, beta) = normal_equations(&points, &p);
+        let mut accepted = false;
+        while lambda < 1.0e10 {
+            let mut a = alpha;
+            for (i, row) in a.iter_mut().enumerate() {
+                row[i] *= 1.0 + lambda;
+            }
+            let step = match solve(a, beta) {
+                Some(s) => s,
+                None => {
+                    lambda *= 10.0;
+                    continue;
+                }
+            };
+            let mut trial = p;
+            for (t, s) in trial.iter_mut().zip(step.iter()) {
+                *t += s;
+            }
+            let trial_chisq = chisquare(&points, &trial);
+            if trial_chisq.is_finite() && trial_chisq <= chisq {
+                let improvement = chisq - trial_chisq;
+                p = trial;
+                chisq = trial_chisq;
+                lambda /= 10.0;
+                accepted = improvement > CONVERGENCE * chisq.max(1.0);
+                break;
+            }
+            lambda *= 10.0;
+        }
+        if !accepted {
+            break;
+        }
+    }
+    if p.iter().any(|v| !v.is_finite()) || p[4] == 0.0 {
+        return Err(String::from("The gaussian fit did not converge"));
+    }
+    Ok(GaussianParameters {
+        baseline: p[0] - p[1] * xmid,
+        slope: p[1],
+        height: p[2],
+        centroid: p[3] + xmid,
+        sigma: p[4].abs(),
+        chisquare: chisq / (points.len() - NPARAMS) as f64,
+    })
+}
+// The starting point is a background through the ends of the region
+// and a peak at the highest point over that background whose sigma
+// is estimated from its area.
+
+fn initial_guess(points: &[(f64, f64)]) -> Result<[f64; NPARAMS], String> {
+    let (x0, y0) = points[0];
+    let (x1, y1) = points[points.len() - 1];
+    let slope = (y1 - y0) / (x1 - x0);
+    let baseline = y0 - slope * x0;
+
+    let mut height = 0.0;
+    let mut centroid = 0.0;
+    let mut area = 0.0;
+    for (x, y) in points {
+        let excess = y - (baseline + slope * x);
+        if excess > 0.0 {
+            area += excess;
+        }
+        if excess > height {
+            height = excess;
+            centroid = *x;
+        }
+    }
+    if height <= 0.0 {
+        return Err(String::from(
+            "There is no peak above the background in the fit region",
+        ));
+    }
+    let sigma = (area / (height * (2.0 * std::f64::consts::PI).sqrt())).max(0.5);
+    Ok([baseline, slope, height, centroid, sigma])
+}
+// Channel weights are 1/variance where the variance is the counts but
+// at least one:
+
+fn weight(y: f64) -> f64 {
+    1.0 / y.max(1.0)
+}
+fn model(x: f64, p: &[f64; NPARAMS]) -> f64 {
+    GaussianParameters {
+        baseline: p[0],
+        slope: p[1],
+        height: p[2],
+        centroid: p[3],
+        sigma: p[4],
+        chisquare: 0.0,
+    }
+    .value(x)
+}
+fn chisquare(points: &[(f64, f64)], p: &[f64; NPARAMS]) -> f64 {
+    points
+        .iter()
+        .map(|(x, y)| {
+            let r = y - model(*x, p);
+            r * r * weight(*y)
+        })
+        .sum()
+}
+// Partial derivatives of the model with respect to each parameter:
+
+fn gradient(x: f64, p: &[f64; NPARAMS]) -> [f64; NPARAMS] {
+    let d = x - p[3];
+    let s2 = p[4] * p[4];
+    let g = (-(d * d) / (2.0 * s2)).exp();
+    [1.0, x, g, p[2] * g * d / s2, p[2] * g * d * d / (s2 * p[4])]
+}
+// The (weighted) normal equations alpha*step = beta of the linearized
+// least squares problem:
+
+type Matrix = [[f64; NPARAMS]; NPARAMS];
+
+fn normal_equations(points: &[(f64, f64)], p: &[f64; NPARAMS]) -> (Matrix, [f64; NPARAMS]) {
+    let mut alpha = [[0.0; NPARAMS]; NPARAMS];
+    let mut beta = [0.0; NPARAMS];
+    for (x, y) in points {
+        let w = weight(*y);
+        let grad = gradient(*x, p);
+        let r = y - model(*x, p);
+        for i in 0..NPARAMS {
+            beta[i] += w * r * grad[i];
+            for j in 0..NPARAMS {
+                alpha[i][j] += w * grad[i] * grad[j];
+            }
+        }
+    }
+    (alpha, beta)
+}
+// Solve a*x = b by Gaussian elimination with partial pivoting.
+// None if a is singular.
+
+fn solve(mut a: Matrix, mut b: [f64; NPARAMS]) -> Option<[f64; NPARAMS]> {
+    for col in 0..NPARAMS {
+        let pivot = (col..NPARAMS).max_by(|i, j| a[*i][col].abs().total_cmp(&a[*j][col].abs()))?;
+        if a[pivot][col].abs() < f64::MIN_POSITIVE {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let pivot_row = a[col];
+        for row in col + 1..NPARAMS {
+            let factor = a[row][col] / pivot_row[col];
+            for (element, pivot_element) in a[row].iter_mut().zip(pivot_row.iter()).skip(col) {
+                *element -= factor * pivot_element;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = [0.0; NPARAMS];
+    for row in (0..NPARAMS).rev() {
+        let sum: f64 = (row + 1..NPARAMS).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
+
+///
+/// The limits of a slice that accepts centroid ± sigmas*sigma of a fit.
+/// Fits are done in channels so the limits are converted to parameter
+/// coordinates using the fitted spectrum's axis.
+///
+/// ### Parameters:
+/// * parameters - the fit parameters.
+/// * sigmas - half width of the slice in units of the fit's sigma.
+/// * low - low limit of the spectrum axis (low edge of channel 0).
+/// * width - width of a channel in parameter coordinates.
+///
+/// ### Returns:
+///   (low, high) limits of the slice.  The counts in channel c are fitted
+///   at c so the centroid maps to the center of its channel.
+///
+pub fn slice_limits(
+    parameters: &GaussianParameters,
+    sigmas: f64,
+    low: f64,
+    width: f64,
+) -> (f64, f64) {
+    let centroid = low + (parameters.centroid + 0.5) * width;
+    let half_width = sigmas * parameters.sigma.abs() * width;
+    (centroid - half_width, centroid + half_width)
+}
+
+/// A slice condition made from a fit.  The condition is on parameter
+/// and accepts centroid ± sigmas*sigma (see slice_limits).
+#[derive(Clone, Debug, PartialEq)]
+pub struct FitGate {
+    pub condition: String,
+    pub parameter: String,
+    pub sigmas: f64,
+}
+
+/// A named fit.  parameters are those of the most recent update and
+/// gates are the conditions made from the fit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fit {
+    pub name: String,
+    pub spectrum: String,
+    pub fit_type: String,
+    pub low: u32,
+    pub high: u32,
+    pub parameters: GaussianParameters,
+    pub gates: Vec<FitGate>,
+}
+
+/// The shared store of fits.
+#[derive(Clone, Default)]
+pub struct SharedFitStore {
+    store: Arc<Mutex<BTreeMap<String, Fit>>>,
+}
+
+impl SharedFitStore {
+    pub fn new() -> SharedFitStore {
+        SharedFitStore {
+            store: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+    /// Add a new fit.  Fails if a fit with the same name exists.
+    pub fn add(&self, fit: Fit) -> Result<(), String> {
+        let mut store = self.store.lock().unwrap();
+        if store.contains_key(&fit.name) {
+            return Err(format!("A fit named {} already exists", fit.name));
+        }
+        store.insert(fit.name.clone(), fit);
+        Ok(())
+    }
+    /// Replace the parameters of an existing fit.
+    pub fn set_parameters(&self, name: &str, parameters: GaussianParameters) -> Result<(), String> {
+        match self.store.lock().unwrap().get_mut(name) {
+            Some(f) => {
+                f.parameters = parameters;
+                Ok(())
+            }
+            None => Err(format!("There is no fit named {}", name)),
+        }
+    }
+    /// Link a condition to the fit it was made from.  A condition's
+    /// limits can only come from one fit so any other link it has is
+    /// removed.
+    pub fn link(&self, name: &str, gate: FitGate) -> Result<(), String> {
+        let mut store = self.store.lock().unwrap();
+        if !store.contains_key(name) {
+            return Err(format!("There is no fit named {}", name));
+        }
+        for fit in store.values_mut() {
+            fit.gates.retain(|g| g.condition != gate.condition);
+        }
+        store.get_mut(name).unwrap().gates.push(gate);
+        Ok(())
+    }
+    /// Remove any link between a condition and a fit.
+    pub fn unlink(&self, condition: &str) {
+        for fit in self.store.lock().unwrap().values_mut() {
+            fit.gates.retain(|g| g.condition != condition);
+        }
+    }
+    /// Get a fit by name.
+    pub fn get(&self, name: &str) -> Option<Fit> {
+        self.store.lock().unwrap().get(name).cloned()
+    }
+    /// Remove a fit.
+    pub fn remove(&self, name: &str) -> Result<(), String> {
+        match self.store.lock().unwrap().remove(name) {
+            Some(_) => Ok(()),
+            None => Err(format!("There is no fit named {}", name)),
+        }
+    }
+    /// The fits whose names match a glob pattern, sorted by name.
+    pub fn list(&self, pattern: &str) -> Result<Vec<Fit>, String> {
+        let pattern = Pattern::new(pattern).map_err(|e| e.msg.to_string())?;
+        Ok(self
+            .store
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|f| pattern.matches(&f.name))
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod fit_tests {
+    use super::*;
+
+    // Points from a set of parameters:
+
+    fn make_points(p: &GaussianParameters, low: u32, high: u32) -> Vec<(f64, f64)> {
+        (low..=high)
+            .map(|c| (c as f64, p.value(c as f64)))
+            .collect()
+    }
+    fn truth() -> GaussianParameters {
+        GaussianParameters {
+            baseline: 20.0,
+            slope: 0.1,
+            height: 500.0,
+            centroid: 150.0,
+            sigma: 8.0,
+            chisquare: 0.0,
+        }
+    }
+    fn assert_close(expected: f64, actual: f64, what: &str) {
+        assert!(
+            (expected - actual).abs() < 1.0e-4 * expected.abs().max(1.0),
+            "{}: expected {} got {}",
+            what,
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn value_1() {
+        let p = truth();
+        assert_eq!(20.0 + 15.0 + 500.0, p.value(150.0));
+        let names: Vec<String> = p.as_pairs().into_iter().map(|(n, _)| n).collect();
+        assert_eq!(
+            vec![
+                "baseline",
+                "slope",
+                "height",
+                "centroid",
+                "sigma",
+                "chisquare"
+            ],
+            names
+        );
+    }
+    #[test]
+    fn region_1() {
+        // Missing channels are zero and over/underflows are ignored:
+
+        let contents = vec![
+            Channel {
+                chan_type: ChannelType::Underflow,
+                x: -1.0,
+                y: 0.0,
+                bin: 0,
+                value: 100.0,
+            },
+            Channel {
+                chan_type: ChannelType::Bin,
+                x: 10.0,
+                y: 0.0,
+                bin: 11,
+                value: 5.0,
+            },
+            Channel {
+                chan_type: ChannelType::Bin,
+                x: 12.0,
+                y: 0.0,
+                bin: 13,
+                value: 7.0,
+            },
+            Channel {
+                chan_type: ChannelType::Bin,
+                x: 100.0,
+                y: 0.0,
+                bin: 101,
+                value: 7.0,
+            },
+        ];
+        assert_eq!(
+            vec![(10.0, 5.0), (11.0, 0.0), (12.0, 7.0)],
+            region(&contents, 10, 12)
+        );
+    }
+    #[test]
+    fn fit_1() {
+        // Exact data recovers the parameters:
+
+        let p = truth();
+        let result = fit_gaussian(&make_points(&p, 100, 200)).expect("Fitting");
+        assert_close(p.baseline, result.baseline, "baseline");
+        assert_close(p.slope, result.slope, "slope");
+        assert_close(p.height, result.height, "height");
+        assert_close(p.centroid, result.centroid, "centroid");
+        assert_close(p.sigma, result.sigma, "sigma");
+        assert!(result.chisquare < 1.0e-6);
+    }
+    #[test]
+    fn fit_2() {
+        // Peak off center of the region on a flat background with
+        // some deterministic 'noise':
+
+        let p = GaussianParameters {
+            baseline: 5.0,
+            slope: 0.0,
+            height: 1000.0,
+            centroid: 42.5,
+            sigma: 3.0,
+            chisquare: 0.0,
+        };
+        let points: Vec<(f64, f64)> = make_points(&p, 20, 80)
+            .into_iter()
+            .enumerate()
+            .map(|(i, (x, y))| (x, y + if i % 2 == 0 { 1.0 } else { -1.0 }))
+            .collect();
+        let result = fit_gaussian(&points).expect("Fitting");
+        assert!((result.centroid - 42.5).abs() < 0.05);
+        assert!((result.sigma - 3.0).abs() < 0.05);
+        assert!((result.height - 1000.0).abs() < 5.0);
+        assert!(result.chisquare > 0.0);
+    }
+    #[test]
+    fn fit_3() {
+        // Failures:
+
+        assert!(fit_gaussian(&[(0.0, 1.0), (1.0, 2.0), (2.0, 1.0)]).is_err()); // Too few.
+        let flat: Vec<(f64, f64)> = (0..20).map(|x| (x as f64, 10.0)).collect();
+        assert!(fit_gaussian(&flat).is_err()); // No peak.
+    }
+    #[test]
+    fn store_1() {
+        let store = SharedFitStore::new();
+        let fit = Fit {
+            name: String::from("peak"),
+            spectrum: String::from("spec"),
+            fit_type: String::from(GAUSSIAN),
+            low: 100,
+            high: 200,
+            parameters: truth(),
+            gates: vec![],
+        };
+        store.add(fit.clone()).expect("Adding");
+        assert!(store.add(fit.clone()).is_err());
+        store
+            .add(Fit {
+                name: String::from("other"),
+                ..fit.clone()
+            })
+            .expect("Adding other");
+
+        assert_eq!(vec![fit.clone()], store.list("p*").expect("Listing"));
+        let names: Vec<String> = store
+            .list("*")
+            .expect("Listing")
+            .into_iter()
+            .map(|f| f.name)
+            .collect();
+        assert_eq!(vec!["other", "peak"], names);
+
+        let mut p = truth();
+        p.centroid = 160.0;
+        store.set_parameters("peak", p).expect("Setting parameters");
+        assert_eq!(160.0, store.list("peak").unwrap()[0].parameters.centroid);
+        assert!(store.set_parameters("nope", p).is_err());
+
+        store.remove("peak").expect("Removing");
+        assert!(store.remove("peak").is_err());
+        assert_eq!(1, store.list("*").unwrap().len());
+    }
+    #[test]
+    fn store_2() {
+        // Links between conditions and fits:
+
+        let store = SharedFitStore::new();
+        let fit = Fit {
+            name: String::from("peak"),
+            spectrum: String::from("spec"),
+            fit_type: String::from(GAUSSIAN),
+            low: 100,
+            high: 200,
+            parameters: truth(),
+            gates: vec![],
+        };
+        store.add(fit.clone()).expect("Adding");
+        store
+            .add(Fit {
+                name: String::from("other"),
+                ..fit.clone()
+            })
+            .expect("Adding other");
+        let gate = FitGate {
+            condition: String::from("g"),
+            parameter: String::from("p"),
+            sigmas: 2.0,
+        };
+        store.link("peak", gate.clone()).expect("Linking");
+        assert!(store.link("nope", gate.clone()).is_err());
+        assert_eq!(vec![gate.clone()], store.get("peak").unwrap().gates);
+
+        // Linking to another fit moves the link:
+
+        store.link("other", gate.clone()).expect("Linking");
+        assert!(store.get("peak").unwrap().gates.is_empty());
+        assert_eq!(vec![gate], store.get("other").unwrap().gates);
+
+        store.unlink("g");
+        assert!(store.get("other").unwrap().gates.is_empty());
+        assert!(store.get("nope").is_none());
+    }
+    #[test]
+    fn limits_1() {
+        // Channel 150 of a 0.5 wide axis starting at 10 is centered at 85.25:
+
+        let (low, high) = slice_limits(&truth(), 2.0, 10.0, 0.5);
+        assert_close(85.25 - 8.0, low, "low");
+        assert_close(85.25 + 8.0, high, "high");
+
+        let mut p = truth();
+        p.sigma = -8.0;
+        assert_eq!((low, high), slice_limits(&p, 2.0, 10.0, 0.5));
+    }
+}
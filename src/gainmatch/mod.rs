@@ -0,0 +1,340 @@
+//!
+//!  This module contains the computations needed to gain match
+//!  parameters.  Gain matching determines the linear calibration
+//!  (gain and offset) that maps the peaks of a parameter's distribution
+//!  onto the peaks of a reference distribution.  Typically the
+//!  distributions come from a summary spectrum so that all of the
+//!  detectors of an array can be matched to a reference at once.
+//!
+//!  As with projections, this code runs outside of the histogram
+//!  server and works with spectrum definitions and contents gotten
+//!  from message exchanges with that server (see crate::messaging).
+//!
+
+use crate::messaging::spectrum_messages::{Channel, ChannelType, SpectrumProperties};
+
+/// Peaks closer than this number of bins to a higher peak are ignored.
+const PEAK_SEPARATION_BINS: usize = 3;
+/// Peak centroids are computed over this many bins either side of the
+/// peak.
+const CENTROID_HALF_WIDTH: usize = 2;
+
+/// The distribution of one parameter as a dense array of channels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution {
+    pub parameter: String,
+    pub low: f64,
+    pub width: f64,
+    pub channels: Vec<f64>,
+}
+
+/// The result of fitting a linear calibration.
+/// Calibrated values are gain*raw + offset.  The residual is the
+/// RMS difference between the calibrated target peaks and the
+/// reference peaks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinearFit {
+    pub gain: f64,
+    pub offset: f64,
+    pub residual: f64,
+}
+
+///
+/// Extract the parameter distributions from the contents of a spectrum.
+///
+/// ### Parameters:
+/// * def - definition of the spectrum.
+/// * contents - contents of the spectrum.
+///
+/// ### Returns:
+///   Result<Vec<Distribution>, String> - For 1D spectra there is a single
+/// distribution.  For summary spectra there's one for each parameter (x
+/// channel) in the order of the spectrum's parameters.  Other spectrum
+/// types are an error.
+///
+pub fn distributions(
+    def: &SpectrumProperties,
+    contents: &[Channel],
+) -> Result<Vec<Distribution>, String> {
+    match def.type_name.as_str() {
+        "1D" => {
+            let axis = def
+                .xaxis
+                .ok_or_else(|| format!("{} has no x axis", def.name))?;
+            let mut d = empty_distribution(&def.xparams[0], axis.low, axis.high, axis.bins);
+            for c in contents.iter().filter(|c| c.chan_type == ChannelType::Bin) {
+                d.add(c.x, c.value);
+            }
+            Ok(vec![d])
+        }
+        "Summary" => {
+            let axis = def
+                .yaxis
+                .ok_or_else(|| format!("{} has no y axis", def.name))?;
+            let mut result: Vec<Distribution> = def
+                .xparams
+                .iter()
+                .map(|p| empty_distribution(p, axis.low, axis.high, axis.bins))
+                .collect();
+            for c in contents.iter().filter(|c| c.chan_type == ChannelType::Bin) {
+                if c.x >= 0.0 {
+                    if let Some(d) = result.get_mut(c.x.round() as usize) {
+                        d.add(c.y, c.value);
+                    }
+                }
+            }
+            Ok(result)
+        }
+        _ => Err(format!(
+            "{} is a {} spectrum; only 1D and Summary spectra can be gain matched",
+            def.name, def.type_name
+        )),
+    }
+}
+// Axis bins include the under and overflow bins:
+
+fn empty_distribution(parameter: &str, low: f64, high: f64, bins: u32) -> Distribution {
+    let bins = (bins as usize).saturating_sub(2).max(1);
+    Distribution {
+        parameter: String::from(parameter),
+        low,
+        width: (high - low) / bins as f64,
+        channels: vec![0.0; bins],
+    }
+}
+impl Distribution {
+    // Add counts given the low edge of their bin:
+
+    fn add(&mut self, x: f64, value: f64) {
+        let bin = ((x - self.low) / self.width).round();
+        if bin >= 0.0 {
+            if let Some(c) = self.channels.get_mut(bin as usize) {
+                *c += value;
+            }
+        }
+    }
+    ///
+    /// Find the most prominent peaks of the distribution.
+    ///
+    /// Peaks are local maxima.  They are chosen in order of decreasing
+    /// height ignoring maxima that are too close to peaks that were
+    /// already chosen.  The position of each peak is the centroid of the
+    /// channels around the maximum.
+    ///
+    /// ### Parameters:
+    /// * count - number of peaks to find.
+    ///
+    /// ### Returns:
+    ///  Result<Vec<f64>, String> - the peak positions in increasing order.
+    /// It is an error if there are fewer than _count_ peaks.
+    ///
+    pub fn find_peaks(&self, count: usize) -> Result<Vec<f64>, String> {
+        let c = &self.channels;
+        let n = c.len();
+        let mut maxima: Vec<usize> = (0..n)
+            .filter(|&i| {
+                c[i] > 0.0 && (i == 0 || c[i] >= c[i - 1]) && (i + 1 == n || c[i] > c[i + 1])
+            })
+            .collect();
+        maxima.sort_by(|a, b| c[*b].total_cmp(&c[*a]));
+
+        let mut chosen: Vec<usize> = vec![];
+        for m in maxima {
+            if chosen.len() == count {
+                break;
+            }
+            if chosen.iter().all(|p| p.abs_diff(m) >= PEAK_SEPARATION_BINS) {
+                chosen.push(m);
+            }
+        }
+        if chosen.len() < count {
+            return Err(format!(
+                "Only found {} of {} peaks for {}",
+                chosen.len(),
+                count,
+                self.parameter
+            ));
+        }
+        let mut peaks: Vec<f64> = chosen.iter().map(|&p| self.centroid(p)).collect();
+        peaks.sort_by(|a, b| a.total_cmp(b));
+        Ok(peaks)
+    }
+    // Centroid of the channels around a peak using bin centers:
+
+    fn centroid(&self, peak: usize) -> f64 {
+        let first = peak.saturating_sub(CENTROID_HALF_WIDTH);
+        let last = (peak + CENTROID_HALF_WIDTH).min(self.channels.len() - 1);
+        let mut sum = 0.0;
+        let mut weighted = 0.0;
+        for (i, v) in self.channels.iter().enumerate().take(last + 1).skip(first) {
+            sum += v;
+            weighted += v * (self.low + (i as f64 + 0.5) * self.width);
+        }
+        weighted / sum
+    }
+}
+
+///
+/// Fit the linear calibration that maps target peaks onto reference peaks
+/// by least squares.  The i'th target peak corresponds to the i'th
+/// reference peak.  With a single pair of peaks, the offset is zero and
+/// only the gain is computed.
+///
+/// ### Parameters:
+/// * target - peak positions of the parameter to calibrate.
+/// * reference - the corresponding reference peak positions.
+///
+/// ### Returns:
+///   Result<LinearFit, String>
+///
+pub fn fit_linear(target: &[f64], reference: &[f64]) -> Result<LinearFit, String> {
+    if target.len() != reference.len() {
+        return Err(format!(
+            "There are {} target peaks but {} reference peaks",
+            target.len(),
+            reference.len()
+        ));
+    }
+    let n = target.len() as f64;
+    let (gain, offset) = match target.len() {
+        0 => return Err(String::from("At least one pair of peaks is needed")),
+        1 => {
+            if target[0] == 0.0 {
+                return Err(String::from("A single target peak cannot be at zero"));
+            }
+            (reference[0] / target[0], 0.0)
+        }
+        _ => {
+            let tmean = target.iter().sum::<f64>() / n;
+            let rmean = reference.iter().sum::<f64>() / n;
+            let mut sxx = 0.0;
+            let mut sxy = 0.0;
+            for (t, r) in target.iter().zip(reference.iter()) {
+                sxx += (t - tmean) * (t - tmean);
+                sxy += (t - tmean) * (r - rmean);
+            }
+            if sxx == 0.0 {
+                return Err(String::from("The target peaks must not all be the same"));
+            }
+            let gain = sxy / sxx;
+            (gain, rmean - gain * tmean)
+        }
+    };
+    let residual = (target
+        .iter()
+        .zip(reference.iter())
+        .map(|(t, r)| (gain * t + offset - r).powi(2))
+        .sum::<f64>()
+        / n)
+        .sqrt();
+    Ok(LinearFit {
+        gain,
+        offset,
+        residual,
+    })
+}
+
+#[cfg(test)]
+mod gainmatch_tests {
+    use super::*;
+    use crate::messaging::spectrum_messages::AxisSpecification;
+
+    fn chan(x: f64, y: f64, value: f64) -> Channel {
+        Channel {
+            chan_type: ChannelType::Bin,
+            x,
+            y,
+            bin: 0,
+            value,
+        }
+    }
+    fn props(type_name: &str, xparams: &[&str]) -> SpectrumProperties {
+        SpectrumProperties {
+            id: 1,
+            name: String::from("test"),
+            type_name: String::from(type_name),
+            xparams: xparams.iter().map(|s| String::from(*s)).collect(),
+            yparams: vec![],
+            xaxis: Some(AxisSpecification {
+                low: 0.0,
+                high: 100.0,
+                bins: 102,
+            }),
+            yaxis: Some(AxisSpecification {
+                low: 0.0,
+                high: 100.0,
+                bins: 102,
+            }),
+            gate: None,
+            fold: None,
+        }
+    }
+
+    #[test]
+    fn fit_1() {
+        let f = fit_linear(&[10.0, 20.0, 30.0], &[25.0, 45.0, 65.0]).unwrap();
+        assert!((f.gain - 2.0).abs() < 1.0e-9);
+        assert!((f.offset - 5.0).abs() < 1.0e-9);
+        assert!(f.residual < 1.0e-9);
+    }
+    #[test]
+    fn fit_2() {
+        let f = fit_linear(&[10.0], &[25.0]).unwrap();
+        assert_eq!(2.5, f.gain);
+        assert_eq!(0.0, f.offset);
+
+        assert!(fit_linear(&[], &[]).is_err());
+        assert!(fit_linear(&[1.0], &[1.0, 2.0]).is_err());
+        assert!(fit_linear(&[1.0, 1.0], &[1.0, 2.0]).is_err());
+        assert!(fit_linear(&[0.0], &[1.0]).is_err());
+    }
+    #[test]
+    fn peaks_1() {
+        // Two symmetric peaks and a smaller one:
+
+        let mut channels = vec![0.0; 100];
+        for (center, height) in [(20, 100.0), (60, 50.0), (80, 10.0)] {
+            channels[center - 1] = height / 2.0;
+            channels[center] = height;
+            channels[center + 1] = height / 2.0;
+        }
+        let d = Distribution {
+            parameter: String::from("p"),
+            low: 0.0,
+            width: 1.0,
+            channels,
+        };
+        assert_eq!(vec![20.5, 60.5], d.find_peaks(2).unwrap());
+        assert_eq!(vec![20.5, 60.5, 80.5], d.find_peaks(3).unwrap());
+        assert!(d.find_peaks(4).is_err());
+    }
+    #[test]
+    fn distributions_1() {
+        let d = distributions(
+            &props("1D", &["p"]),
+            &[chan(10.0, 0.0, 5.0), chan(99.0, 0.0, 1.0)],
+        )
+        .unwrap();
+        assert_eq!(1, d.len());
+        assert_eq!("p", d[0].parameter);
+        assert_eq!(100, d[0].channels.len());
+        assert_eq!(1.0, d[0].width);
+        assert_eq!(5.0, d[0].channels[10]);
+        assert_eq!(1.0, d[0].channels[99]);
+    }
+    #[test]
+    fn distributions_2() {
+        let d = distributions(
+            &props("Summary", &["p0", "p1"]),
+            &[chan(0.0, 10.0, 5.0), chan(1.0, 20.0, 3.0)],
+        )
+        .unwrap();
+        assert_eq!(2, d.len());
+        assert_eq!("p0", d[0].parameter);
+        assert_eq!(5.0, d[0].channels[10]);
+        assert_eq!("p1", d[1].parameter);
+        assert_eq!(3.0, d[1].channels[20]);
+
+        assert!(distributions(&props("2D", &["p0"]), &[]).is_err());
+    }
+}
@@ -0,0 +1,299 @@
+//!  This module provides an in-process integration test harness.  It
+//!  starts the parts of rustogramer clients talk to other than the
+//!  REST server: the histogramer, the spectrum binder with a temporary
+//!  shared memory region and a mirror server for that region.
+//!
+//!  It is exported by the library when the test-harness feature is
+//!  enabled so that downstream crates and client developers (e.g. work
+//!  on the CutiePie mirror protocol) can write integration tests
+//!  without duplicating the setup and teardown our own test modules do:
+//!
+//! ```ignore
+//!  let harness = Harness::new(8 * 1024 * 1024, 0)?;
+//!  let spectra = SpectrumMessageClient::new(&harness.histogramer());
+//!  ...  // make and bind spectra.
+//!  let mut peer = TcpStream::connect(("127.0.0.1", harness.mirror_port()))?;
+//!  let (kind, image) = mirror::request_update(&mut peer)?;
+//!  ...
+//!  harness.shutdown();
+//! ```
+//!
+//!  Dropping the harness shuts it down as well.  Shutdown stops the
+//!  mirror listener, binder and histogramer and removes the shared memory
+//!  backing file.
+//!
+use crate::crash;
+use crate::histogramer;
+use crate::messaging;
+use crate::sharedmem::{binder, mirror};
+use crate::trace;
+
+use std::fs;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How long we wait for the mirror server to start listening:
+
+const MIRROR_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The running servers.  Use the accessors to get what's needed to
+/// talk to them.
+pub struct Harness {
+    histogramer: mpsc::Sender<messaging::Request>,
+    histogramer_thread: Option<thread::JoinHandle<()>>,
+    binder: mpsc::Sender<binder::Request>,
+    binder_thread: Option<thread::JoinHandle<()>>,
+    traces: trace::SharedTraceStore,
+    mirror_directory: mirror::SharedMirrorDirectory,
+    mirror_exit: mpsc::Sender<bool>,
+    mirror_port: u16,
+    shm_name: String,
+    running: bool,
+}
+
+impl Harness {
+    /// Start the servers.
+    ///
+    /// ### Parameters
+    /// * shm_bytes - size of the spectrum storage in the shared memory.
+    /// * mirror_port - port the mirror server listens on.  If 0, a free
+    ///   port is chosen; see mirror_port().
+    ///
+    /// ### Returns
+    /// Result<Harness, String> - on failure (e.g. the shared memory
+    /// could not be made) everything that was started is stopped.
+    pub fn new(shm_bytes: usize, mirror_port: u16) -> Result<Harness, String> {
+        let traces = trace::SharedTraceStore::new();
+        let (histogramer_thread, histogramer) = histogramer::start_server(traces.clone());
+        let (binder, binder_thread) = binder::start_server(&histogramer, shm_bytes, &traces);
+
+        let mut harness = Harness {
+            histogramer,
+            histogramer_thread: Some(histogramer_thread),
+            binder,
+            binder_thread: Some(binder_thread),
+            traces,
+            mirror_directory: Arc::new(Mutex::new(mirror::Directory::new())),
+            mirror_exit: mpsc::channel().0,
+            mirror_port: 0,
+            shm_name: String::new(),
+            running: true,
+        };
+        if let Err(e) = harness.start_mirror(mirror_port) {
+            harness.stop();
+            return Err(e);
+        }
+        Ok(harness)
+    }
+    /// Channel on which to make histogramer requests e.g. via
+    /// messaging::spectrum_messages::SpectrumMessageClient.
+    pub fn histogramer(&self) -> mpsc::Sender<messaging::Request> {
+        self.histogramer.clone()
+    }
+    /// API to bind spectra into the shared memory.
+    pub fn binder(&self) -> binder::BindingApi {
+        binder::BindingApi::new(&self.binder)
+    }
+    /// The trace store the servers post traces to.
+    pub fn traces(&self) -> trace::SharedTraceStore {
+        self.traces.clone()
+    }
+    /// The directory of mirrors registered with the mirror server.
+    pub fn mirror_directory(&self) -> mirror::SharedMirrorDirectory {
+        self.mirror_directory.clone()
+    }
+    /// Port the mirror server listens on (on all interfaces).
+    pub fn mirror_port(&self) -> u16 {
+        self.mirror_port
+    }
+    /// Path to the shared memory backing file (without the file: prefix
+    /// the binder reports).
+    pub fn shm_name(&self) -> String {
+        self.shm_name.clone()
+    }
+    /// Stop the servers and remove the shared memory backing file.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    // Find out where the shared memory is and start the mirror server
+    // on it.  We don't return until the server accepts connections.
+
+    fn start_mirror(&mut self, port: u16) -> Result<(), String> {
+        let shm_name = self.binder().get_shname()?;
+        self.shm_name = match shm_name.find(':') {
+            Some(colon) => String::from(&shm_name[colon + 1..]),
+            None => shm_name,
+        };
+        self.mirror_port = if port == 0 { free_port()? } else { port };
+
+        let (exit_send, exit_rcv) = mpsc::channel();
+        self.mirror_exit = exit_send;
+        let port = self.mirror_port;
+        let shm_name = self.shm_name.clone();
+        let dir = self.mirror_directory.clone();
+        crash::spawn("mirror", move || {
+            let mut server = mirror::MirrorServer::new(port, &shm_name, exit_rcv, dir);
+            server.run();
+        });
+
+        let start = Instant::now();
+        loop {
+            match TcpStream::connect(("127.0.0.1", port)) {
+                Ok(s) => {
+                    let _ = s.shutdown(Shutdown::Both);
+                    return Ok(());
+                }
+                Err(e) => {
+                    if start.elapsed() > MIRROR_TIMEOUT {
+                        return Err(format!(
+                            "Mirror server did not start on port {}: {}",
+                            port, e
+                        ));
+                    }
+                    thread::sleep(Duration::from_millis(50));
+                }
+            }
+        }
+    }
+    // Stop everything that's running.  The mirror server only checks
+    // for exit requests after a connection so we make one.
+
+    fn stop(&mut self) {
+        if !self.running {
+            return;
+        }
+        self.running = false;
+        if self.mirror_port != 0 && self.mirror_exit.send(true).is_ok() {
+            if let Ok(s) = TcpStream::connect(("127.0.0.1", self.mirror_port)) {
+                let _ = s.shutdown(Shutdown::Both);
+            }
+        }
+        if let Ok(backing_file) = self.binder().exit() {
+            if !backing_file.is_empty() {
+                let _ = fs::remove_file(Path::new(&backing_file));
+            }
+        }
+        if let Some(jh) = self.binder_thread.take() {
+            let _ = jh.join();
+        }
+        histogramer::stop_server(&self.histogramer);
+        if let Some(jh) = self.histogramer_thread.take() {
+            let _ = jh.join();
+        }
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// Let the system choose a port that's free right now.
+
+fn free_port() -> Result<u16, String> {
+    let listener =
+        TcpListener::bind("0.0.0.0:0").map_err(|e| format!("Unable to find a free port: {}", e))?;
+    listener
+        .local_addr()
+        .map(|a| a.port())
+        .map_err(|e| format!("Unable to find a free port: {}", e))
+}
+
+#[cfg(test)]
+mod harness_tests {
+    use super::*;
+    use crate::messaging::parameter_messages::ParameterMessageClient;
+    use crate::messaging::spectrum_messages::SpectrumMessageClient;
+    use std::io::Write;
+
+    #[test]
+    fn start_1() {
+        // The harness starts and the shared memory exists until shutdown:
+
+        let harness = Harness::new(1024 * 1024, 0).expect("Starting harness");
+        assert_ne!(0, harness.mirror_port());
+        let shm = harness.shm_name();
+        assert!(Path::new(&shm).exists());
+
+        harness.shutdown();
+        assert!(!Path::new(&shm).exists());
+    }
+    #[test]
+    fn mirror_1() {
+        // Bound spectra are visible through the mirror:
+
+        let harness = Harness::new(1024 * 1024, 0).expect("Starting harness");
+        let chan = harness.histogramer();
+        ParameterMessageClient::new(&chan)
+            .create_parameter("p")
+            .expect("Making parameter");
+        SpectrumMessageClient::new(&chan)
+            .create_spectrum_1d("spec", "p", 0.0, 1024.0, 1024)
+            .expect("Making spectrum");
+        harness.binder().bind("spec").expect("Binding spectrum");
+        assert_eq!(1, harness.binder().list_bindings("*").unwrap().len());
+
+        let mut peer =
+            TcpStream::connect(("127.0.0.1", harness.mirror_port())).expect("Connecting to mirror");
+        let (kind, image) = mirror::request_update(&mut peer).expect("Requesting update");
+        assert_eq!(mirror::FULL_UPDATE, kind);
+        assert!(!image.is_empty());
+        peer.shutdown(Shutdown::Both).expect("Closing connection");
+    }
+    #[test]
+    fn traces_1() {
+        // Servers post their traces to the harness's trace store:
+
+        let harness = Harness::new(1024 * 1024, 0).expect("Starting harness");
+        let traces = harness.traces();
+        let token = traces.new_client(Duration::from_secs(60));
+        ParameterMessageClient::new(&harness.histogramer())
+            .create_parameter("p")
+            .expect("Making parameter");
+
+        let events = traces.get_traces(token).expect("Getting traces");
+        assert_eq!(1, events.len());
+        assert!(matches!(events[0].event(), trace::TraceEvent::NewParameter(name) if name == "p"));
+    }
+    #[test]
+    fn directory_1() {
+        // Mirror clients that describe themselves are in the directory:
+
+        let harness = Harness::new(1024 * 1024, 0).expect("Starting harness");
+        let directory = harness.mirror_directory();
+        assert_eq!(0, directory.lock().unwrap().iter().count());
+
+        let mut peer =
+            TcpStream::connect(("127.0.0.1", harness.mirror_port())).expect("Connecting to mirror");
+        let body = b"Xa3b";
+        let mut message = Vec::<u8>::new();
+        message.extend_from_slice(&(8 + body.len() as u32).to_ne_bytes());
+        message.extend_from_slice(&mirror::SHM_INFO.to_ne_bytes());
+        message.extend_from_slice(body);
+        peer.write_all(&message).expect("Sending SHM_INFO");
+
+        let start = Instant::now();
+        while directory.lock().unwrap().iter().count() == 0 {
+            assert!(start.elapsed() < MIRROR_TIMEOUT, "No directory entry");
+            thread::sleep(Duration::from_millis(10));
+        }
+        let entries: Vec<String> = directory.lock().unwrap().iter().map(|e| e.key()).collect();
+        assert_eq!(vec![String::from("Xa3b")], entries);
+        peer.shutdown(Shutdown::Both).expect("Closing connection");
+    }
+    #[test]
+    fn drop_1() {
+        // Dropping the harness also cleans up:
+
+        let shm = {
+            let harness = Harness::new(1024 * 1024, 0).expect("Starting harness");
+            harness.shm_name()
+        };
+        assert!(!Path::new(&shm).exists());
+    }
+}
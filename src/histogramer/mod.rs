@@ -10,6 +10,7 @@
 //! * A public function to stop the thread.
 //!
 
+use crate::crash;
 use crate::messaging::*;
 use crate::trace;
 use std::sync::mpsc;
@@ -64,6 +65,7 @@ impl RequestProcessor {
                 self.conditions.get_dict(),
                 tracedb,
             )),
+            MessageType::Ping => Reply::Pong,
             MessageType::Exit => Reply::Exiting,
         }
     }
@@ -104,16 +106,14 @@ impl Histogramer {
             // do it as per below or clone the reply which
             // might be computationally expensive (imagine it contains
             // the contents of a dense, large, 2d histogram e.g.).
+            // A failed send means the client gave up waiting for us
+            // (e.g. a REST request that timed out) and is ignored.
 
             if let Reply::Exiting = reply {
-                req.reply_channel
-                    .send(reply)
-                    .expect("Failed to send reply to request");
+                let _ = req.reply_channel.send(reply);
                 break;
             } else {
-                req.reply_channel
-                    .send(reply)
-                    .expect("Failed to send reply to request");
+                let _ = req.reply_channel.send(reply);
             }
         }
     }
@@ -133,7 +133,7 @@ pub fn start_server(
     let (req_send, req_recv) = mpsc::channel();
 
     let db = tracdb.clone();
-    let join_handle = thread::spawn(move || {
+    let join_handle = crash::spawn("histogramer", move || {
         let mut processor = Histogramer::new(req_recv, db);
         processor.run();
     });
@@ -214,6 +214,13 @@ mod request_tests {
         let msg = MessageType::Exit;
         assert!(matches!(req.process_message(msg, &tracedb), Reply::Exiting));
     }
+    #[test]
+    fn ping_1() {
+        let mut req = RequestProcessor::new();
+        let tracedb = trace::SharedTraceStore::new();
+        let msg = MessageType::Ping;
+        assert!(matches!(req.process_message(msg, &tracedb), Reply::Pong));
+    }
 }
 #[cfg(test)]
 mod hgrammer_tests {
@@ -223,6 +230,7 @@ mod hgrammer_tests {
     use std::matches;
     use std::sync::mpsc;
     use std::thread;
+    use std::time;
 
     fn setup() -> (thread::JoinHandle<()>, mpsc::Sender<Request>) {
         let (req, jh) = histogramer_common::setup();
@@ -243,6 +251,40 @@ mod hgrammer_tests {
         jh.join().unwrap();
     }
     #[test]
+    fn ping_1() {
+        // A responsive server answers pings:
+
+        let (jh, ch) = setup();
+        assert!(Request::ping(ch.clone(), time::Duration::from_secs(5)));
+        teardown(ch, jh);
+    }
+    #[test]
+    fn ping_2() {
+        // An exited server does not:
+
+        let (jh, ch) = setup();
+        stop_server(ch.clone());
+        jh.join().unwrap();
+        assert!(!Request::ping(ch, time::Duration::from_millis(100)));
+    }
+    #[test]
+    fn abandoned_1() {
+        // A client that stops waiting for its reply does not
+        // kill the server:
+
+        let (jh, ch) = setup();
+        let (reply_send, reply_recv) = mpsc::channel();
+        let req = Request {
+            reply_channel: reply_send,
+            message: MessageType::Ping,
+        };
+        req.send(ch.clone()).expect("Sending ping");
+        drop(reply_recv);
+
+        assert!(Request::ping(ch.clone(), time::Duration::from_secs(5)));
+        teardown(ch, jh);
+    }
+    #[test]
     fn params_1() {
         // test parameters:
 
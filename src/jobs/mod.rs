@@ -0,0 +1,330 @@
+//!  This module provides the job store.  Some REST operations, e.g.
+//!  writing many large spectra or projecting a big 2d spectrum, can
+//!  take longer than a client (or a proxy between it and us) is
+//!  willing to wait for a reply.  Those operations can instead be
+//!  started as jobs:  the request returns a job id at once and the
+//!  work is done in its own thread.  Clients then poll the job's
+//!  status and progress and may ask that it be cancelled via the
+//!  /spectcl/jobs REST interface.
+//!
+//!  Cancellation is cooperative:  the work is handed a JobContext
+//!  and is expected to check JobContext::cancelled as it goes.
+//!  Finished jobs are kept so that their results can be polled;
+//!  only the newest MAX_FINISHED_JOBS of them are retained.
+//!
+//!  A job's thread reports progress through its JobContext while REST
+//!  handlers poll and cancel it through the SharedJobStore.  Both refer
+//!  to the same mutex protected table of jobs.
+//!
+use crate::crash;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time;
+
+/// The number of finished jobs that are retained.
+pub const MAX_FINISHED_JOBS: usize = 100;
+
+/// Job status values.
+pub const RUNNING: &str = "running";
+pub const COMPLETED: &str = "completed";
+pub const FAILED: &str = "failed";
+pub const CANCELLED: &str = "cancelled";
+
+/// The description of a job.  _done_ and _total_ are the job's
+/// progress in units that depend on the kind of job (e.g. spectra
+/// written).  _detail_ is the result of a finished job or why it
+/// failed.  Times are seconds since the epoch.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct JobDescription {
+    pub id: u64,
+    pub kind: String,
+    pub description: String,
+    pub status: String,
+    pub done: u64,
+    pub total: u64,
+    pub detail: String,
+    pub started: u64,
+    pub finished: Option<u64>,
+}
+
+struct Job {
+    description: JobDescription,
+    cancel_requested: bool,
+}
+
+#[derive(Default)]
+struct JobTable {
+    next_id: u64,
+    jobs: BTreeMap<u64, Job>,
+}
+
+impl JobTable {
+    // Forget the oldest finished jobs so that at most MAX_FINISHED_JOBS
+    // remain:
+
+    fn prune(&mut self) {
+        let finished: Vec<u64> = self
+            .jobs
+            .iter()
+            .filter(|(_, j)| j.description.status != RUNNING)
+            .map(|(id, _)| *id)
+            .collect();
+        if finished.len() > MAX_FINISHED_JOBS {
+            for id in &finished[0..finished.len() - MAX_FINISHED_JOBS] {
+                self.jobs.remove(id);
+            }
+        }
+    }
+}
+
+fn now() -> u64 {
+    time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// What a job's work is given to report progress and check for
+/// cancellation.
+pub struct JobContext {
+    id: u64,
+    table: Arc<Mutex<JobTable>>,
+}
+
+impl JobContext {
+    /// Report that _done_ of _total_ units of work have been done.
+    pub fn progress(&self, done: u64, total: u64) {
+        if let Some(job) = self.table.lock().unwrap().jobs.get_mut(&self.id) {
+            job.description.done = done;
+            job.description.total = total;
+        }
+    }
+    /// True if the job has been asked to stop.
+    pub fn cancelled(&self) -> bool {
+        self.table
+            .lock()
+            .unwrap()
+            .jobs
+            .get(&self.id)
+            .map(|j| j.cancel_requested)
+            .unwrap_or(true)
+    }
+}
+
+/// The shared job store.
+#[derive(Clone, Default)]
+pub struct SharedJobStore {
+    table: Arc<Mutex<JobTable>>,
+}
+
+impl SharedJobStore {
+    pub fn new() -> SharedJobStore {
+        SharedJobStore {
+            table: Arc::new(Mutex::new(JobTable::default())),
+        }
+    }
+    /// Start a job, returning its id.  The work is run in a thread of
+    /// its own.  Its Ok value is the detail of the completed job and
+    /// its Err value the reason it failed.  A job that fails after it
+    /// was asked to stop is cancelled.
+    pub fn start<F>(&self, kind: &str, description: &str, work: F) -> u64
+    where
+        F: FnOnce(&JobContext) -> Result<String, String> + Send + 'static,
+    {
+        let id = {
+            let mut table = self.table.lock().unwrap();
+            table.next_id += 1;
+            let id = table.next_id;
+            table.jobs.insert(
+                id,
+                Job {
+                    description: JobDescription {
+                        id,
+                        kind: String::from(kind),
+                        description: String::from(description),
+                        status: String::from(RUNNING),
+                        done: 0,
+                        total: 0,
+                        detail: String::new(),
+                        started: now(),
+                        finished: None,
+                    },
+                    cancel_requested: false,
+                },
+            );
+            id
+        };
+        let context = JobContext {
+            id,
+            table: self.table.clone(),
+        };
+        crash::spawn(&format!("job-{}", id), move || {
+            let result = work(&context);
+            let mut table = context.table.lock().unwrap();
+            if let Some(job) = table.jobs.get_mut(&id) {
+                let (status, detail) = match result {
+                    Ok(s) => (COMPLETED, s),
+                    Err(s) => {
+                        if job.cancel_requested {
+                            (CANCELLED, s)
+                        } else {
+                            (FAILED, s)
+                        }
+                    }
+                };
+                job.description.status = String::from(status);
+                job.description.detail = detail;
+                job.description.finished = Some(now());
+            }
+            table.prune();
+        });
+        id
+    }
+    /// Ask a running job to stop.
+    pub fn cancel(&self, id: u64) -> Result<(), String> {
+        match self.table.lock().unwrap().jobs.get_mut(&id) {
+            Some(job) => {
+                if job.description.status == RUNNING {
+                    job.cancel_requested = true;
+                    Ok(())
+                } else {
+                    Err(format!("Job {} is already {}", id, job.description.status))
+                }
+            }
+            None => Err(format!("There is no job {}", id)),
+        }
+    }
+    /// Forget a finished job.
+    pub fn forget(&self, id: u64) -> Result<(), String> {
+        let mut table = self.table.lock().unwrap();
+        match table.jobs.get(&id) {
+            Some(job) => {
+                if job.description.status == RUNNING {
+                    Err(format!("Job {} is still running", id))
+                } else {
+                    table.jobs.remove(&id);
+                    Ok(())
+                }
+            }
+            None => Err(format!("There is no job {}", id)),
+        }
+    }
+    /// The description of a job.
+    pub fn status(&self, id: u64) -> Option<JobDescription> {
+        self.table
+            .lock()
+            .unwrap()
+            .jobs
+            .get(&id)
+            .map(|j| j.description.clone())
+    }
+    /// The descriptions of all jobs, oldest first.
+    pub fn list(&self) -> Vec<JobDescription> {
+        self.table
+            .lock()
+            .unwrap()
+            .jobs
+            .values()
+            .map(|j| j.description.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod job_store_tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    // Wait for a job to finish:
+
+    fn wait(store: &SharedJobStore, id: u64) -> JobDescription {
+        for _ in 0..500 {
+            let job = store.status(id).expect("Job status");
+            if job.status != RUNNING {
+                return job;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("Job {} did not finish", id);
+    }
+
+    #[test]
+    fn complete_1() {
+        let store = SharedJobStore::new();
+        let id = store.start("test", "a job", |ctx| {
+            ctx.progress(2, 2);
+            Ok(String::from("done"))
+        });
+        let job = wait(&store, id);
+        assert_eq!(1, job.id);
+        assert_eq!("test", job.kind);
+        assert_eq!("a job", job.description);
+        assert_eq!(COMPLETED, job.status);
+        assert_eq!((2, 2), (job.done, job.total));
+        assert_eq!("done", job.detail);
+        assert!(job.finished.is_some());
+    }
+    #[test]
+    fn fail_1() {
+        let store = SharedJobStore::new();
+        let id = store.start("test", "", |_| Err(String::from("broken")));
+        let job = wait(&store, id);
+        assert_eq!(FAILED, job.status);
+        assert_eq!("broken", job.detail);
+    }
+    #[test]
+    fn cancel_1() {
+        // The job runs until it's cancelled:
+
+        let store = SharedJobStore::new();
+        let (tx, rx) = mpsc::channel();
+        let id = store.start("test", "", move |ctx| {
+            tx.send(()).unwrap();
+            while !ctx.cancelled() {
+                thread::sleep(Duration::from_millis(5));
+            }
+            Err(String::from("stopped"))
+        });
+        rx.recv().unwrap();
+        assert!(store.forget(id).is_err());
+        store.cancel(id).expect("Cancelling");
+        let job = wait(&store, id);
+        assert_eq!(CANCELLED, job.status);
+        assert_eq!("stopped", job.detail);
+
+        assert!(store.cancel(id).is_err());
+        assert!(store.cancel(1234).is_err());
+    }
+    #[test]
+    fn forget_1() {
+        let store = SharedJobStore::new();
+        let id1 = store.start("test", "", |_| Ok(String::new()));
+        let id2 = store.start("test", "", |_| Ok(String::new()));
+        wait(&store, id1);
+        wait(&store, id2);
+        assert_eq!(2, store.list().len());
+        store.forget(id1).expect("Forgetting");
+        assert!(store.status(id1).is_none());
+        assert!(store.forget(id1).is_err());
+        let ids: Vec<u64> = store.list().iter().map(|j| j.id).collect();
+        assert_eq!(vec![id2], ids);
+    }
+    #[test]
+    fn prune_1() {
+        // Only the newest finished jobs are kept:
+
+        let store = SharedJobStore::new();
+        let mut last = 0;
+        for _ in 0..MAX_FINISHED_JOBS + 5 {
+            last = store.start("test", "", |_| Ok(String::new()));
+            wait(&store, last);
+        }
+        let jobs = store.list();
+        assert_eq!(MAX_FINISHED_JOBS, jobs.len());
+        assert_eq!(6, jobs[0].id);
+        assert_eq!(last, jobs[MAX_FINISHED_JOBS - 1].id);
+    }
+}
@@ -0,0 +1,35 @@
+//! The rustogramer library.  This exports the ring item support
+//! used by the histogramer so that external programs (e.g. unpackers
+//! written in Rust) can produce parameter files rustogramer can
+//! analyze.  See ring_items::analysis_ring_items::ParameterFileWriter.
+//!
+//! It also provides the unpackers used by the evt2par program which
+//! converts raw event files into parameter files.
+//!
+//! With the test-harness feature, it also exports harness::Harness,
+//! which runs the histogramer, spectrum binder and mirror server
+//! in-process for integration tests, along with the modules needed to
+//! talk to them.  These are excluded from the library's own unit tests,
+//! which are run as part of the rustogrammer binary's tests.
+
+pub mod ring_items;
+pub mod unpackers;
+
+#[cfg(all(feature = "test-harness", not(test)))]
+pub mod conditions;
+#[cfg(all(feature = "test-harness", not(test)))]
+pub mod crash;
+#[cfg(all(feature = "test-harness", not(test)))]
+pub mod harness;
+#[cfg(all(feature = "test-harness", not(test)))]
+pub mod histogramer;
+#[cfg(all(feature = "test-harness", not(test)))]
+pub mod messaging;
+#[cfg(all(feature = "test-harness", not(test)))]
+pub mod parameters;
+#[cfg(all(feature = "test-harness", not(test)))]
+pub mod sharedmem;
+#[cfg(all(feature = "test-harness", not(test)))]
+pub mod spectra;
+#[cfg(all(feature = "test-harness", not(test)))]
+pub mod trace;
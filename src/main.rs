@@ -2,29 +2,53 @@
 // for it to be visible.. you also have to indicate the a desire for the
 // module if it's not 'used' in main for it to be visible elsewhere hence:
 
+mod autosave;
 mod conditions;
+mod config;
+mod crash;
+mod fits;
+mod gainmatch;
+#[cfg(test)]
+mod harness;
 mod histogramer;
+mod jobs;
 mod messaging;
+mod npy;
 mod parameters;
 mod processing;
 mod projections;
 mod rest;
 mod ring_items;
+mod script;
+mod selftest;
+mod session;
 mod sharedmem;
+mod simulation;
 mod spectclio;
 mod spectra;
+mod tags;
+mod templates;
 mod trace;
+mod treevariables;
+// The server unpacks raw physics events with the unpackers module.
+// Parts of it (e.g. convert) are only for evt2par which gets it from
+// the library.
+#[allow(dead_code)]
+mod unpackers;
 
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
 use rest::{
-    apply, channel, data_processing, evbunpack, exit, filter, fit, fold, gates, getstats,
-    integrate, mirror_list, project, rest_parameter, ringversion, sbind, shm, spectrum, spectrumio,
-    traces, unbind, unimplemented, version,
+    apply, autosave as rest_autosave, busy, cache, channel, compress, crashes, data_processing,
+    evbunpack, exit, filter, fit, fold, fs, gates, getstats, help, idspace, integrate, job, limits,
+    mirror_list, names, project, pseudo, readonly, rest_parameter, ringversion, roottree, runinfo,
+    sbind, scaler, shm, simulate, spectrum, spectrumio, tag, template, traces, treevariable,
+    unbind, unimplemented, unpacker, version,
 };
+use rocket::data::{Limits, ToByteUnit};
 use sharedmem::{binder, mirror};
 use std::env;
 use std::sync::{mpsc, Arc, Mutex};
-use std::thread;
 
 // Pull in Rocket features:
 
@@ -53,13 +77,126 @@ struct Args {
     mirror_port: u16,
     #[arg(long)]
     mirror_service: Option<String>,
+    #[arg(long, default_value_t=limits::DEFAULT_MAX_FILL_POINTS)]
+    max_fill_points: usize,
+    #[arg(long, default_value_t=limits::DEFAULT_MAX_BULK_SPECTRA)]
+    max_bulk_spectra: usize,
+    #[arg(long, default_value_t=limits::DEFAULT_MAX_BODY_BYTES)]
+    max_body_bytes: u64,
+    #[arg(long, default_value_t=String::from(names::DEFAULT_NAME_PUNCTUATION))]
+    name_punctuation: String,
+    #[arg(long, default_value_t=names::DEFAULT_MAX_NAME_LENGTH)]
+    max_name_length: usize,
+    #[arg(long)]
+    case_insensitive_names: bool,
+    #[arg(long)]
+    enable_simulation: bool,
+    #[arg(long, default_value_t=busy::DEFAULT_HISTOGRAMER_TIMEOUT_SECS)]
+    histogramer_timeout: f64,
+    #[arg(long, default_value_t=cache::DEFAULT_CACHE_TTL_SECS)]
+    response_cache_ttl: f64,
+    #[arg(long)]
+    session: Option<String>,
+    #[arg(long)]
+    resume_session: Option<String>,
+    #[arg(long)]
+    session_contents: bool,
+    #[arg(long)]
+    session_dir: Option<String>,
+    #[arg(long)]
+    selftest: bool,
+    #[arg(long, default_value_t = String::from("auto"))]
+    parameter_id_map: String,
+    #[arg(long)]
+    read_only: bool,
+    #[arg(long)]
+    transfer_dir: Option<String>,
+    #[arg(long)]
+    config: Option<String>,
+}
+
+// Options not given on the command line are taken from the
+// configuration file, if it has them:
+
+fn apply_config(args: &mut Args, file: config::ConfigFile, matches: &ArgMatches) {
+    let from_command_line = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    macro_rules! values {
+        ($($field:ident),*) => {
+            $(
+                if let Some(v) = file.$field {
+                    if !from_command_line(stringify!($field)) {
+                        args.$field = v;
+                    }
+                }
+            )*
+        };
+    }
+    macro_rules! optional_values {
+        ($($field:ident),*) => {
+            $(
+                if file.$field.is_some() && !from_command_line(stringify!($field)) {
+                    args.$field = file.$field;
+                }
+            )*
+        };
+    }
+    values!(
+        shm_mbytes,
+        rest_port,
+        mirror_port,
+        max_fill_points,
+        max_bulk_spectra,
+        max_body_bytes,
+        name_punctuation,
+        max_name_length,
+        case_insensitive_names,
+        enable_simulation,
+        histogramer_timeout,
+        response_cache_ttl,
+        session_contents,
+        parameter_id_map,
+        read_only
+    );
+    optional_values!(
+        rest_service,
+        mirror_service,
+        session,
+        resume_session,
+        session_dir,
+        transfer_dir
+    );
 }
 
 // This is now the entry point as Rocket has the main
 //
 #[launch]
 fn rocket() -> _ {
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let Some(path) = args.config.clone() {
+        match config::read(std::path::Path::new(&path)) {
+            Ok(file) => apply_config(&mut args, file, &matches),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // A self test must neither restore nor save an analysis session
+    // and must be able to create the objects it exercises:
+
+    if args.selftest {
+        args.session = None;
+        args.resume_session = None;
+        args.read_only = false;
+    }
+
+    // Record crash reports for threads that panic so that
+    // ReST clients can find out why:
+
+    let crash_store = crash::SharedCrashStore::new();
+    crash_store.install_panic_hook();
 
     // Create the trace database and start its prune thread.
     // we will make a separate state for it.
@@ -67,38 +204,120 @@ fn rocket() -> _ {
     let trace_store = trace::SharedTraceStore::new();
     trace_store.start_prune_thread();
 
+    // The tree variable store is shared by the processing thread,
+    // which fills it from the data, and the REST handlers:
+
+    let treevariable_store = treevariables::SharedTreeVariableStore::new();
+
+    // Spectrum templates and tags are only used by the REST handlers
+    // but are saved and restored with sessions:
+
+    let template_store = templates::SharedTemplateStore::new();
+    let tag_store = tags::SharedTagStore::new();
+
+    // Fits are only used by the REST handlers:
+
+    let fit_store = fits::SharedFitStore::new();
+
     // start the histogram server in a thread:
     //
 
     let (_, histogramer_channel) = histogramer::start_server(trace_store.clone());
-    let processor = processing::ProcessingApi::new(&histogramer_channel);
+    let processor =
+        processing::ProcessingApi::new(&histogramer_channel, &trace_store, &treevariable_store);
+    match parameters::IdMapStrategy::parse(&args.parameter_id_map) {
+        Ok(strategy) => {
+            if let Err(e) = processor.set_id_map_strategy(strategy) {
+                println!("Unable to set the parameter id map strategy: {}", e);
+            }
+        }
+        Err(e) => println!("{} - using auto", e),
+    }
     let binder = binder::start_server(
         &histogramer_channel,
         args.shm_mbytes * 1024 * 1024,
         &trace_store,
     );
+    // If the shared memory could not be made, we run without
+    // bindings or mirroring rather than giving up:
 
-    let (rest_port, mirror_port, portman_client) = get_ports(&args);
+    let shm_available = match binder::BindingApi::new(&binder.0).check_available() {
+        Ok(()) => true,
+        Err(reason) => {
+            println!("{} - spectrum binding and mirroring are disabled", reason);
+            false
+        }
+    };
 
-    // Start the mirror server:
+    // Restore any session we're resuming.  Unless told otherwise,
+    // we save back to that session on exit:
+
+    let session_dir = match &args.session_dir {
+        Some(d) => std::path::PathBuf::from(d),
+        None => session::default_directory(),
+    };
+    let session_config = session::SessionConfig::new(
+        args.session.clone().or(args.resume_session.clone()),
+        &session_dir,
+        args.session_contents,
+    );
+    if let Some(name) = &args.resume_session {
+        let path = session_config.session_path(name);
+        match session::read(&path) {
+            Ok(s) => {
+                let problems = session::validate(&s);
+                if !problems.is_empty() {
+                    println!(
+                        "Session {} has unresolved references:\n{}",
+                        name,
+                        problems.join("\n")
+                    );
+                }
+                if let Err(e) = session::restore(&s, &histogramer_channel, &treevariable_store) {
+                    println!("Some of session {} could not be restored:\n{}", name, e);
+                }
+                session::restore_templates(&s.templates, &template_store);
+                session::restore_tags(&s.tags, &tag_store);
+                if shm_available {
+                    if let Err(e) = session::restore_display_transforms(
+                        &s.display_transforms,
+                        &binder::BindingApi::new(&binder.0),
+                    ) {
+                        println!(
+                            "Some display transforms of session {} could not be restored:\n{}",
+                            name, e
+                        );
+                    }
+                }
+            }
+            Err(e) => println!("Unable to resume session {}: {}", name, e),
+        }
+    }
+
+    let (rest_port, mirror_port, portman_client) = get_ports(&args);
 
-    let shm_name = binder::BindingApi::new(&binder.0)
-        .get_shname()
-        .expect("Unable to get shared memoryname");
-    // Remove the file: prefix:
-    let colon = shm_name
-        .find(':')
-        .expect("Finding end of file: in shm name");
-    let shm_name = String::from(&shm_name.as_str()[colon + 1..]);
-    println!("Mirroring {} port: {}", shm_name, mirror_port);
+    // Start the mirror server if there's a shared memory to mirror:
 
     let (mirror_send, mirror_rcv) = mpsc::channel();
     let mirror_directory = Arc::new(Mutex::new(mirror::Directory::new()));
-    let server_dir = mirror_directory.clone();
-    thread::spawn(move || {
-        let mut server = mirror::MirrorServer::new(mirror_port, &shm_name, mirror_rcv, server_dir);
-        server.run();
-    });
+    if shm_available {
+        let shm_name = binder::BindingApi::new(&binder.0)
+            .get_shname()
+            .expect("Unable to get shared memoryname");
+        // Remove the file: prefix:
+        let colon = shm_name
+            .find(':')
+            .expect("Finding end of file: in shm name");
+        let shm_name = String::from(&shm_name.as_str()[colon + 1..]);
+        println!("Mirroring {} port: {}", shm_name, mirror_port);
+
+        let server_dir = mirror_directory.clone();
+        crash::spawn("mirror", move || {
+            let mut server =
+                mirror::MirrorServer::new(mirror_port, &shm_name, mirror_rcv, server_dir);
+            server.run();
+        });
+    }
 
     let state = rest::MirrorState {
         mirror_exit: Arc::new(Mutex::new(mirror_send)),
@@ -109,10 +328,74 @@ fn rocket() -> _ {
 
     env::set_var("ROCKET_PORT", rest_port.to_string());
 
-    rocket::build()
+    // Request size limits.  Rocket's own body limits are set from
+    // max_body_bytes so oversized bodies are refused before we see them:
+
+    let rest_limits = limits::RestLimits::new(
+        args.max_fill_points,
+        args.max_bulk_spectra,
+        args.max_body_bytes,
+    );
+    let body_limit = rest_limits.max_body_bytes.bytes();
+    let body_limits = Limits::default()
+        .limit("form", body_limit)
+        .limit("data-form", body_limit)
+        .limit("file", body_limit)
+        .limit("string", body_limit)
+        .limit("bytes", body_limit)
+        .limit("json", body_limit)
+        .limit("msgpack", body_limit);
+
+    // REST requests give up on a histogramer that's too busy to
+    // answer within this time:
+
+    let histogramer_timeout = busy::HistogramerTimeout::from_secs(args.histogramer_timeout);
+
+    // Policy for the names of new spectra and conditions:
+
+    let name_policy = names::NamePolicy::new(
+        &args.name_punctuation,
+        args.max_name_length,
+        !args.case_insensitive_names,
+    );
+
+    // Remote clients upload and download files in the transfer
+    // directory, which defaults to the working directory:
+
+    let transfer_dir = match &args.transfer_dir {
+        Some(d) => std::path::PathBuf::from(d),
+        None => env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from(".")),
+    };
+
+    // A read-only server rejects requests that would modify it:
+
+    if args.read_only {
+        println!("Running read-only - requests that modify the server are rejected");
+    }
+
+    let rocket = rocket::custom(rocket::Config::figment().merge(("limits", body_limits)))
+        .manage(rest_limits)
+        .manage(histogramer_timeout)
+        .manage(cache::ResponseCache::from_secs(args.response_cache_ttl))
+        .manage(name_policy)
+        .manage(readonly::ReadOnly::new(args.read_only))
+        .manage(simulation::Simulator::new(
+            args.enable_simulation || args.selftest,
+        ))
+        .manage(autosave::AutoSaver::new())
+        .manage(roottree::RootExports::new())
+        .manage(fs::TransferDirectory::new(&transfer_dir))
+        .manage(evbunpack::EvbUnpackers::new())
+        .manage(jobs::SharedJobStore::new())
         .manage(mirror_directory.clone())
         .manage(state)
         .manage(trace_store.clone())
+        .manage(crash_store)
+        .manage(treevariable_store)
+        .manage(template_store)
+        .manage(tag_store)
+        .manage(fit_store)
+        .manage(session_config)
         .manage(Mutex::new(binder.0.clone()))
         .manage(Mutex::new(histogramer_channel.clone()))
         .manage(Mutex::new(processor))
@@ -140,7 +423,16 @@ fn rocket() -> _ {
         )
         .mount(
             "/spectcl/gate",
-            routes![gates::list_gates, gates::delete_gate, gates::edit_gate],
+            routes![
+                gates::list_gates,
+                gates::delete_gate,
+                gates::edit_gate,
+                gates::gate_applications,
+                gates::roi_gate,
+                gates::disable_gate,
+                gates::enable_gate,
+                gates::gate_statistics
+            ],
         )
         .mount(
             "/spectcl/spectrum",
@@ -149,7 +441,20 @@ fn rocket() -> _ {
                 spectrum::delete_spectrum,
                 spectrum::create_spectrum,
                 spectrum::get_contents,
+                spectrum::get_multi_contents,
                 spectrum::clear_spectra,
+                spectrum::clone_spectrum,
+                spectrum::create_2dsum_from_spectra,
+                spectrum::shadow_spectrum,
+                spectrum::temporary_spectrum,
+                spectrum::list_temporary_spectra,
+                spectrum::coordinates_to_bins,
+                spectrum::bins_to_coordinates,
+                spectrum::threshold_spectrum,
+                spectrum::create_pid_matrix,
+                spectrum::rebin_spectrum,
+                spectrum::combine_spectra,
+                spectrum::copy_spectrum,
             ],
         )
         .mount(
@@ -157,7 +462,8 @@ fn rocket() -> _ {
             routes![
                 data_processing::attach_source,
                 data_processing::list_source,
-                data_processing::detach_source
+                data_processing::detach_source,
+                data_processing::verify_source
             ],
         )
         .mount(
@@ -165,17 +471,27 @@ fn rocket() -> _ {
             routes![
                 data_processing::start_processing,
                 data_processing::stop_processing,
-                data_processing::set_event_batch
+                data_processing::set_event_batch,
+                data_processing::pause_processing,
+                data_processing::resume_processing,
+                data_processing::analysis_status,
+                data_processing::set_read_buffer,
+                data_processing::read_statistics,
+                data_processing::set_sampling,
+                data_processing::verify_timestamps,
+                data_processing::set_end_run_write,
+                data_processing::cancel_end_run_write,
+                data_processing::end_run_write_status
             ],
         )
         .mount(
             "/spectcl/apply",
-            routes![apply::apply_gate, apply::apply_list],
+            routes![apply::apply_gate, apply::apply_list, apply::apply_history],
         )
         .mount("/spectcl/ungate", routes![apply::ungate_spectrum])
         .mount(
             "/spectcl/channel",
-            routes![channel::set_chan, channel::get_chan],
+            routes![channel::set_chan, channel::get_chan, channel::set_roi],
         )
         .mount(
             "/spectcl/evbunpack",
@@ -183,6 +499,30 @@ fn rocket() -> _ {
                 evbunpack::create_evbunpack,
                 evbunpack::add_evbunpack,
                 evbunpack::list_evbunpack,
+                evbunpack::use_evbunpack,
+            ],
+        )
+        .mount(
+            "/spectcl/runinfo",
+            routes![runinfo::run_info, runinfo::set_autoclear],
+        )
+        .mount(
+            "/spectcl/scaler",
+            routes![
+                scaler::list_scalers,
+                scaler::scaler_totals,
+                scaler::clear_scalers,
+                scaler::set_scaler_spectra,
+                scaler::cancel_scaler_spectra,
+                scaler::scaler_spectra_status,
+            ],
+        )
+        .mount(
+            "/spectcl/unpacker",
+            routes![
+                unpacker::list_unpackers,
+                unpacker::use_unpacker,
+                unpacker::current_unpacker,
             ],
         )
         .mount(
@@ -194,21 +534,65 @@ fn rocket() -> _ {
                 filter::disable,
                 filter::regate,
                 filter::file,
+                filter::format,
                 filter::list
             ],
         )
         .mount(
             "/spectcl/fit",
-            routes![fit::create, fit::update, fit::delete, fit::list, fit::proc],
+            routes![
+                fit::create,
+                fit::update,
+                fit::delete,
+                fit::list,
+                fit::gate,
+                fit::proc
+            ],
         )
         .mount(
             "/spectcl/fold",
             routes![fold::apply, fold::list, fold::remove],
         )
+        .mount(
+            "/spectcl/fs",
+            routes![
+                fs::fs_roots,
+                fs::fs_listdir,
+                fs::fs_stat,
+                fs::fs_transfer_dir,
+                fs::fs_upload,
+                fs::fs_download
+            ],
+        )
+        .mount(
+            "/spectcl/calibration",
+            routes![
+                rest::calibration::set_calibration,
+                rest::calibration::list_calibrations,
+                rest::calibration::delete_calibration
+            ],
+        )
+        .mount(
+            "/spectcl/gainmatch",
+            routes![
+                rest::gainmatch::fit_gains,
+                rest::gainmatch::list_calibrations,
+                rest::gainmatch::clear_calibrations
+            ],
+        )
         .mount("/spectcl/integrate", routes![integrate::integrate])
+        .mount(
+            "/spectcl/jobs",
+            routes![job::list, job::status, job::cancel, job::forget],
+        )
         .mount(
             "/spectcl/shmem",
-            routes![shm::shmem_name, shm::shmem_size, shm::get_variables],
+            routes![
+                shm::shmem_name,
+                shm::shmem_size,
+                shm::get_variables,
+                shm::get_statistics
+            ],
         )
         .mount(
             "/spectcl/sbind",
@@ -217,7 +601,10 @@ fn rocket() -> _ {
                 sbind::sbind_list,
                 sbind::sbind_bindings,
                 sbind::set_update,
-                sbind::get_update
+                sbind::get_update,
+                sbind::set_transform,
+                sbind::clear_transform,
+                sbind::list_transforms
             ],
         )
         .mount(
@@ -248,31 +635,71 @@ fn rocket() -> _ {
         .mount(
             "/spectcl/pseudo",
             routes![
-                unimplemented::pseudo_create,
-                unimplemented::pseudo_list,
-                unimplemented::pseudo_delete
+                pseudo::pseudo_create,
+                pseudo::pseudo_list,
+                pseudo::pseudo_delete
             ],
         )
         .mount(
             "/spectcl/roottree",
             routes![
-                unimplemented::roottree_create,
-                unimplemented::roottree_delete,
-                unimplemented::roottree_list
+                roottree::roottree_create,
+                roottree::roottree_delete,
+                roottree::roottree_list
             ],
         )
-        .mount("/spectcl/script", routes![unimplemented::script_execute])
+        .mount("/spectcl/script", routes![rest::script::script_execute])
         .mount(
             "/spectcl/treevariable",
             routes![
-                unimplemented::treevariable_list,
-                unimplemented::treevariable_set,
-                unimplemented::treevariable_check,
-                unimplemented::treevariable_set_changed,
-                unimplemented::treevariable_fire_traces
+                treevariable::treevariable_list,
+                treevariable::treevariable_set,
+                treevariable::treevariable_check,
+                treevariable::treevariable_set_changed,
+                treevariable::treevariable_fire_traces
+            ],
+        )
+        .mount(
+            "/spectcl/tag",
+            routes![
+                tag::add_tags,
+                tag::remove_tags,
+                tag::list_tags,
+                tag::clear_tagged,
+                tag::bind_tagged,
+                tag::write_tagged
+            ],
+        )
+        .mount(
+            "/spectcl/template",
+            routes![
+                template::create_template,
+                template::list_templates,
+                template::delete_template,
+                template::make_from_template
             ],
         )
         .mount("/spectcl/version", routes![version::get_version])
+        .mount("/spectcl/limits", routes![limits::get_limits])
+        .mount("/spectcl/busy", routes![busy::busy_statistics])
+        .mount("/spectcl/cache", routes![cache::cache_status])
+        .mount("/spectcl/readonly", routes![readonly::read_only_status])
+        .mount(
+            "/spectcl/simulate",
+            routes![
+                simulate::start_simulation,
+                simulate::stop_simulation,
+                simulate::simulation_status
+            ],
+        )
+        .mount(
+            "/spectcl/autosave",
+            routes![
+                rest_autosave::start_autosave,
+                rest_autosave::stop_autosave,
+                rest_autosave::autosave_status
+            ],
+        )
         .mount("/spectcl/exit", routes![exit::shutdown])
         .mount(
             "/spectcl/ringformat",
@@ -285,10 +712,40 @@ fn rocket() -> _ {
             "/spectcl/trace",
             routes![
                 traces::establish_trace,
+                traces::trace_filter,
                 traces::trace_done,
-                traces::fetch_traces
+                traces::fetch_traces,
+                traces::trace_events,
+                traces::run_log
+            ],
+        )
+        .mount(
+            "/spectcl/crashes",
+            routes![crashes::list_crashes, crashes::clear_crashes],
+        )
+        .mount(
+            "/spectcl/idspace",
+            routes![
+                idspace::report_idspace,
+                idspace::compact_idspace,
+                idspace::set_idmap_strategy
             ],
         )
+        .mount("/spectcl/help", routes![help::help_all, help::help_route])
+        .register(
+            "/",
+            catchers![busy::histogramer_busy, readonly::server_read_only],
+        )
+        .attach(cache::ClearOnWrite)
+        .attach(compress::Compression::default());
+
+    // In selftest mode the server is exercised and we exit rather
+    // than launching it:
+
+    if args.selftest {
+        selftest::run_and_exit(rocket, mirror_port);
+    }
+    rocket
 }
 ///
 /// Gets the port to use for our REST service.
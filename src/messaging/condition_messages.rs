@@ -64,17 +64,65 @@ pub enum ConditionRequest {
         ids: Vec<u32>,
         points: Vec<(f64, f64)>,
     },
+    CreateMultiBand {
+        name: String,
+        ids: Vec<u32>,
+        points: Vec<(f64, f64)>,
+    },
+    CreateAggregate {
+        name: String,
+        kind: AggregateKind,
+        ids: Vec<u32>,
+        low: f64,
+        high: f64,
+    },
+    CreateRatio {
+        name: String,
+        kind: RatioKind,
+        p1: u32,
+        p2: u32,
+        low: f64,
+        high: f64,
+    },
+    CreateMask {
+        name: String,
+        kind: MaskKind,
+        param_id: u32,
+        mask: u32,
+    },
+    CreateC2Band {
+        name: String,
+        band1: String,
+        band2: String,
+    },
+    CreateContourSet {
+        name: String,
+        x_id: u32,
+        y_id: u32,
+        polygons: Vec<Vec<(f64, f64)>>,
+    },
     DeleteCondition(String),
     List(String),
+    Disable {
+        name: String,
+        value: bool,
+    },
+    Enable(String),
 }
-/// This structure provides condition properties:
+/// This structure provides condition properties.
+/// disabled is None if the condition is enabled and otherwise
+/// Some with the value the disabled condition evaluates to.
+/// polygons is empty unless the condition is made of several polygons
+/// (ContourSet), in which case points has the points of all of them.
 #[derive(Clone, Debug, PartialEq)]
 pub struct ConditionProperties {
     pub cond_name: String,
     pub type_name: String,
     pub points: Vec<(f64, f64)>,
+    pub polygons: Vec<Vec<(f64, f64)>>,
     pub gates: Vec<String>,
     pub parameters: Vec<u32>,
+    pub disabled: Option<bool>,
 }
 ///
 /// These are replies that can be sent from the condition manager
@@ -86,6 +134,7 @@ pub enum ConditionReply {
     Created,
     Replaced,
     Deleted,
+    Modified,
     Listing(Vec<ConditionProperties>),
 }
 // Having learned our lessons from parameter_messages.rs our
@@ -180,12 +229,93 @@ impl ConditionMessageClient {
             points: points.to_owned(),
         }
     }
+    fn make_multiband_creation(name: &str, ids: &[u32], points: &[(f64, f64)]) -> ConditionRequest {
+        ConditionRequest::CreateMultiBand {
+            name: String::from(name),
+            ids: ids.to_owned(),
+            points: points.to_owned(),
+        }
+    }
+    fn make_aggregate_creation(
+        name: &str,
+        kind: AggregateKind,
+        ids: &[u32],
+        low: f64,
+        high: f64,
+    ) -> ConditionRequest {
+        ConditionRequest::CreateAggregate {
+            name: String::from(name),
+            kind,
+            ids: ids.to_owned(),
+            low,
+            high,
+        }
+    }
+    fn make_ratio_creation(
+        name: &str,
+        kind: RatioKind,
+        p1: u32,
+        p2: u32,
+        low: f64,
+        high: f64,
+    ) -> ConditionRequest {
+        ConditionRequest::CreateRatio {
+            name: String::from(name),
+            kind,
+            p1,
+            p2,
+            low,
+            high,
+        }
+    }
+    fn make_mask_creation(
+        name: &str,
+        kind: MaskKind,
+        param_id: u32,
+        mask: u32,
+    ) -> ConditionRequest {
+        ConditionRequest::CreateMask {
+            name: String::from(name),
+            kind,
+            param_id,
+            mask,
+        }
+    }
+    fn make_c2band_creation(name: &str, band1: &str, band2: &str) -> ConditionRequest {
+        ConditionRequest::CreateC2Band {
+            name: String::from(name),
+            band1: String::from(band1),
+            band2: String::from(band2),
+        }
+    }
+    fn make_contourset_creation(
+        name: &str,
+        x_id: u32,
+        y_id: u32,
+        polygons: &[Vec<(f64, f64)>],
+    ) -> ConditionRequest {
+        ConditionRequest::CreateContourSet {
+            name: String::from(name),
+            x_id,
+            y_id,
+            polygons: polygons.to_owned(),
+        }
+    }
     fn make_delete(name: &str) -> ConditionRequest {
         ConditionRequest::DeleteCondition(String::from(name))
     }
     fn make_list(pattern: &str) -> ConditionRequest {
         ConditionRequest::List(String::from(pattern))
     }
+    fn make_disable(name: &str, value: bool) -> ConditionRequest {
+        ConditionRequest::Disable {
+            name: String::from(name),
+            value,
+        }
+    }
+    fn make_enable(name: &str) -> ConditionRequest {
+        ConditionRequest::Enable(String::from(name))
+    }
     fn make_request(reply_channel: mpsc::Sender<Reply>, req: ConditionRequest) -> Request {
         Request {
             reply_channel,
@@ -428,6 +558,136 @@ impl ConditionMessageClient {
         self.transaction(Self::make_multicontour_creation(name, ids, points))
     }
     ///
+    /// Create a multiband
+    ///   MultiBands are analagous to SpecTcl gamma-bands.  They get an
+    /// array of ids and at least two 2-d points:
+    ///
+    /// ### Parameters
+    ///  *   name - name of the new condition.
+    ///  *   ids - array of parameter ids.
+    ///  *   points - array of points.
+    ///
+    /// ### Returns:
+    ///   Condition reply which is hopefully either Created or Replaced
+    ///
+    pub fn create_multiband_condition(
+        &self,
+        name: &str,
+        ids: &[u32],
+        points: &[(f64, f64)],
+    ) -> ConditionReply {
+        self.transaction(Self::make_multiband_creation(name, ids, points))
+    }
+    ///
+    /// Create an aggregate condition.  Aggregate conditions are cuts
+    /// on the sum, mean or maximum of the parameters in a set that are
+    /// present in the event.
+    ///
+    /// ### Parameters
+    /// *  name - name of the new condition.
+    /// *  kind - The aggregate to compute.
+    /// *  ids  - Array of parameter ids the aggregate is computed over.
+    /// *  low, high - the condition limits on the aggregate.
+    ///
+    /// ### Returns
+    ///    ConditionReply - this should be either Created or Replaced or Error.
+    ///
+    pub fn create_aggregate_condition(
+        &self,
+        name: &str,
+        kind: AggregateKind,
+        ids: &[u32],
+        low: f64,
+        high: f64,
+    ) -> ConditionReply {
+        self.transaction(Self::make_aggregate_creation(name, kind, ids, low, high))
+    }
+    ///
+    /// Create a two parameter cut.  These are cuts on the ratio
+    /// or difference of a pair of parameters.
+    ///
+    /// ### Parameters
+    /// *  name - name of the new condition.
+    /// *  kind - What to compute from the parameters.
+    /// *  p1, p2 - Ids of the parameters.  The value computed is p1/p2 or p1-p2.
+    /// *  low, high - the condition limits on the computed value.
+    ///
+    /// ### Returns
+    ///    ConditionReply - this should be either Created or Replaced or Error.
+    ///
+    pub fn create_ratio_condition(
+        &self,
+        name: &str,
+        kind: RatioKind,
+        p1: u32,
+        p2: u32,
+        low: f64,
+        high: f64,
+    ) -> ConditionReply {
+        self.transaction(Self::make_ratio_creation(name, kind, p1, p2, low, high))
+    }
+    ///
+    /// Create a mask condition.  These compare the bits of an integer
+    /// valued parameter with a mask.
+    ///
+    /// ### Parameters
+    /// *  name - name of the new condition.
+    /// *  kind - How the parameter value is compared with the mask.
+    /// *  param_id - Id of the parameter.
+    /// *  mask - the bit mask.
+    ///
+    /// ### Returns
+    ///    ConditionReply - this should be either Created or Replaced or Error.
+    ///
+    pub fn create_mask_condition(
+        &self,
+        name: &str,
+        kind: MaskKind,
+        param_id: u32,
+        mask: u32,
+    ) -> ConditionReply {
+        self.transaction(Self::make_mask_creation(name, kind, param_id, mask))
+    }
+    ///
+    /// Create a contour from two bands on the same parameters.  The
+    /// condition refers to the bands so editing them changes the contour.
+    ///
+    /// ### Parameters
+    /// *  name - name of the new condition.
+    /// *  band1, band2 - names of the bands.  The contour is the points
+    ///    of band1 followed by those of band2 in reverse order.
+    ///
+    /// ### Returns
+    ///    ConditionReply - this should be either Created or Replaced or Error.
+    ///
+    pub fn create_c2band_condition(&self, name: &str, band1: &str, band2: &str) -> ConditionReply {
+        self.transaction(Self::make_c2band_creation(name, band1, band2))
+    }
+    ///
+    /// Create a contour set.  Contour sets are several contours in the
+    /// plane defined by two parameters.  The condition is true if the
+    /// event is inside any of the contours.  This avoids making an Or
+    /// of contours for figures with islands.
+    ///
+    /// ### Parameters
+    /// *  name - name of the new condition.
+    /// *  x_id, y_id - Ids of the X and Y parameters.
+    /// *  polygons - the points of each contour.  There must be at least
+    ///    one and each must have at least three points.
+    ///
+    /// ### Returns
+    ///    ConditionReply - this should be either Created or Replaced or Error.
+    ///
+    pub fn create_contourset_condition(
+        &self,
+        name: &str,
+        x_id: u32,
+        y_id: u32,
+        polygons: &[Vec<(f64, f64)>],
+    ) -> ConditionReply {
+        self.transaction(Self::make_contourset_creation(name, x_id, y_id, polygons))
+    }
+    ///
     /// Deletes a condition.  The condition is removed fromt he dictionary.
     /// All remaining references are 'weak' by definition and will fail to promote
     /// to a strong reference when use is attemped.
@@ -455,6 +715,33 @@ impl ConditionMessageClient {
     pub fn list_conditions(&self, pattern: &str) -> ConditionReply {
         self.transaction(Self::make_list(pattern))
     }
+    ///
+    /// Disable a condition without deleting it.  Until it is enabled,
+    /// the condition evaluates to a fixed value.  Spectra and compound
+    /// conditions that depend on it continue to do so.
+    ///
+    ///  *  name - name of the condition to disable.
+    ///  *  value - the value the disabled condition evaluates to.
+    ///
+    /// Returns ConditionReply.  On success this is Modified.
+    /// Other returns are errors.  A simple error condition is that the
+    /// name is not a condition that is defined.
+    ///
+    pub fn disable_condition(&self, name: &str, value: bool) -> ConditionReply {
+        self.transaction(Self::make_disable(name, value))
+    }
+    ///
+    /// Put a disabled condition back in service.  Enabling a condition
+    /// that is not disabled is not an error.
+    ///
+    ///  *  name - name of the condition to enable.
+    ///
+    /// Returns ConditionReply.  On success this is Modified.
+    /// Other returns are errors.
+    ///
+    pub fn enable_condition(&self, name: &str) -> ConditionReply {
+        self.transaction(Self::make_enable(name))
+    }
 }
 // Sever side stuff.
 
@@ -635,6 +922,100 @@ impl ConditionProcessor {
             ConditionReply::Error(String::from("Unable to create multicontour"))
         }
     }
+    fn add_multiband(
+        &mut self,
+        name: &str,
+        ids: &[u32],
+        points: &[(f64, f64)],
+        tracedb: &trace::SharedTraceStore,
+    ) -> ConditionReply {
+        let pts = points.iter().map(|pt| Point::new(pt.0, pt.1)).collect();
+        if let Some(b) = MultiBand::new(ids, pts) {
+            self.add_condition(name, b, tracedb)
+        } else {
+            ConditionReply::Error(String::from("Unable to create multiband"))
+        }
+    }
+    fn add_aggregate(
+        &mut self,
+        name: &str,
+        kind: AggregateKind,
+        ids: &[u32],
+        low: f64,
+        high: f64,
+        tracedb: &trace::SharedTraceStore,
+    ) -> ConditionReply {
+        if ids.is_empty() {
+            ConditionReply::Error(String::from(
+                "Aggregate conditions require at least one parameter",
+            ))
+        } else {
+            self.add_condition(name, Aggregate::new(kind, ids, low, high), tracedb)
+        }
+    }
+    fn add_ratio(
+        &mut self,
+        name: &str,
+        kind: RatioKind,
+        p1: u32,
+        p2: u32,
+        low: f64,
+        high: f64,
+        tracedb: &trace::SharedTraceStore,
+    ) -> ConditionReply {
+        self.add_condition(name, RatioCut::new(kind, p1, p2, low, high), tracedb)
+    }
+    fn add_mask(
+        &mut self,
+        name: &str,
+        kind: MaskKind,
+        param_id: u32,
+        mask: u32,
+        tracedb: &trace::SharedTraceStore,
+    ) -> ConditionReply {
+        self.add_condition(name, Mask::new(kind, param_id, mask), tracedb)
+    }
+    fn add_c2band(
+        &mut self,
+        name: &str,
+        band1: &str,
+        band2: &str,
+        tracedb: &trace::SharedTraceStore,
+    ) -> ConditionReply {
+        let b1 = match self.dict.get(band1) {
+            Some(b) => b,
+            None => return ConditionReply::Error(format!("Band {} not found", band1)),
+        };
+        let b2 = match self.dict.get(band2) {
+            Some(b) => b,
+            None => return ConditionReply::Error(format!("Band {} not found", band2)),
+        };
+        if let Some(c) = C2Band::new(b1, b2) {
+            self.add_condition(name, c, tracedb)
+        } else {
+            ConditionReply::Error(format!(
+                "{} and {} must both be bands on the same parameters",
+                band1, band2
+            ))
+        }
+    }
+    fn add_contourset(
+        &mut self,
+        name: &str,
+        x_id: u32,
+        y_id: u32,
+        polygons: Vec<Vec<(f64, f64)>>,
+        tracedb: &trace::SharedTraceStore,
+    ) -> ConditionReply {
+        let polygons = polygons.into_iter().map(Self::convert_points).collect();
+        if let Some(c) = ContourSet::new(x_id, y_id, polygons) {
+            self.add_condition(name, c, tracedb)
+        } else {
+            ConditionReply::Error(String::from(
+                "A contour set needs at least one polygon and each polygon needs at least three points",
+            ))
+        }
+    }
     fn remove_condition(
         &mut self,
         name: &str,
@@ -647,6 +1028,48 @@ impl ConditionProcessor {
             ConditionReply::Error(format!("No such condition {}", name))
         }
     }
+    // Take the condition out of a container, unwrapping it if it
+    // is disabled.  The container is left holding a True condition
+    // and should be refilled by the caller.
+
+    fn take_enabled(c: &Container) -> Box<dyn Condition> {
+        let enabled = c.borrow_mut().take_enabled();
+        match enabled {
+            Some(condition) => condition,
+            None => c.replace(Box::new(True {})),
+        }
+    }
+    fn disable_condition(
+        &mut self,
+        name: &str,
+        value: bool,
+        tracedb: &trace::SharedTraceStore,
+    ) -> ConditionReply {
+        if let Some(c) = self.dict.get(name) {
+            let condition = Self::take_enabled(c);
+            c.replace(Box::new(Disabled::new(condition, value)));
+            tracedb.add_event(trace::TraceEvent::ConditionModified(String::from(name)));
+            ConditionReply::Modified
+        } else {
+            ConditionReply::Error(format!("No such condition {}", name))
+        }
+    }
+    fn enable_condition(
+        &mut self,
+        name: &str,
+        tracedb: &trace::SharedTraceStore,
+    ) -> ConditionReply {
+        if let Some(c) = self.dict.get(name) {
+            if c.borrow().disabled_value().is_some() {
+                let condition = Self::take_enabled(c);
+                c.replace(condition);
+                tracedb.add_event(trace::TraceEvent::ConditionModified(String::from(name)));
+            }
+            ConditionReply::Modified
+        } else {
+            ConditionReply::Error(format!("No such condition {}", name))
+        }
+    }
     // make CondtionPropreties from a condition and its name.
 
     fn make_props(&self, name: &str, c: &Container) -> ConditionProperties {
@@ -665,8 +1088,10 @@ impl ConditionProcessor {
             cond_name: String::from(name),
             type_name: c.borrow().condition_type(),
             points: c.borrow().condition_points(),
+            polygons: c.borrow().condition_polygons(),
             gates: d_names,
             parameters: c.borrow().dependent_parameters(),
+            disabled: c.borrow().disabled_value(),
         }
     }
 
@@ -740,8 +1165,45 @@ impl ConditionProcessor {
             ConditionRequest::CreateMultiContour { name, ids, points } => {
                 self.add_multicontour(&name, &ids, &points, tracedb)
             }
+            ConditionRequest::CreateMultiBand { name, ids, points } => {
+                self.add_multiband(&name, &ids, &points, tracedb)
+            }
+            ConditionRequest::CreateAggregate {
+                name,
+                kind,
+                ids,
+                low,
+                high,
+            } => self.add_aggregate(&name, kind, &ids, low, high, tracedb),
+            ConditionRequest::CreateRatio {
+                name,
+                kind,
+                p1,
+                p2,
+                low,
+                high,
+            } => self.add_ratio(&name, kind, p1, p2, low, high, tracedb),
+            ConditionRequest::CreateMask {
+                name,
+                kind,
+                param_id,
+                mask,
+            } => self.add_mask(&name, kind, param_id, mask, tracedb),
+            ConditionRequest::CreateC2Band { name, band1, band2 } => {
+                self.add_c2band(&name, &band1, &band2, tracedb)
+            }
+            ConditionRequest::CreateContourSet {
+                name,
+                x_id,
+                y_id,
+                polygons,
+            } => self.add_contourset(&name, x_id, y_id, polygons, tracedb),
             ConditionRequest::DeleteCondition(name) => self.remove_condition(&name, tracedb),
             ConditionRequest::List(pattern) => self.list_conditions(&pattern),
+            ConditionRequest::Disable { name, value } => {
+                self.disable_condition(&name, value, tracedb)
+            }
+            ConditionRequest::Enable(name) => self.enable_condition(&name, tracedb),
         }
     }
     pub fn get_dict(&mut self) -> &mut ConditionDictionary {
@@ -761,13 +1223,13 @@ impl ConditionProcessor {
 ///   Result<conditions::twod::Contour, String>  - where:
 ///   *  Ok encapsulates the reconstituted contour
 ///   *  Err encapsulates an error string (normally if props are not a
-/// contour).
+/// contour).  Contours made from two bands (C2Band) are contours too.
 ///
 /// ### NOTE:
 ///   Dummy parameter numbers 0 and 1 are used for the parameter ids.
 ///
 pub fn reconstitute_contour(props: ConditionProperties) -> Result<twod::Contour, String> {
-    if props.type_name == "Contour" {
+    if props.type_name == "Contour" || props.type_name == "C2Band" {
         let mut pts = Vec::<twod::Point>::new();
         for (x, y) in props.points {
             pts.push(twod::Point::new(x, y));
@@ -942,6 +1404,22 @@ mod cond_msg_tests {
         }
     }
     #[test]
+    fn make_disable_1() {
+        let mr = ConditionMessageClient::make_disable("junk", false);
+        assert_eq!(
+            ConditionRequest::Disable {
+                name: String::from("junk"),
+                value: false
+            },
+            mr
+        );
+    }
+    #[test]
+    fn make_enable_1() {
+        let mr = ConditionMessageClient::make_enable("junk");
+        assert_eq!(ConditionRequest::Enable(String::from("junk")), mr);
+    }
+    #[test]
     fn make_multicut_1() {
         let mc = ConditionMessageClient::make_multicut_creation("name", &[1, 2, 3], 100.0, 200.0);
         assert_eq!(
@@ -955,25 +1433,42 @@ mod cond_msg_tests {
         )
     }
     #[test]
-    fn make_multicontour_1() {
-        let mc = ConditionMessageClient::make_multicontour_creation(
+    fn make_multicontour_1() {
+        let mc = ConditionMessageClient::make_multicontour_creation(
+            "name",
+            &[1, 2, 3],
+            &[(100.0, 100.0), (150.0, 100.0), (125.0, 150.0)],
+        );
+        assert_eq!(
+            ConditionRequest::CreateMultiContour {
+                name: String::from("name"),
+                ids: vec![1, 2, 3],
+                points: vec![(100.0, 100.0), (150.0, 100.0), (125.0, 150.0)]
+            },
+            mc
+        );
+    }
+    #[test]
+    fn make_multiband_1() {
+        let mb = ConditionMessageClient::make_multiband_creation(
             "name",
             &[1, 2, 3],
-            &[(100.0, 100.0), (150.0, 100.0), (125.0, 150.0)],
+            &[(100.0, 100.0), (150.0, 100.0)],
         );
         assert_eq!(
-            ConditionRequest::CreateMultiContour {
+            ConditionRequest::CreateMultiBand {
                 name: String::from("name"),
                 ids: vec![1, 2, 3],
-                points: vec![(100.0, 100.0), (150.0, 100.0), (125.0, 150.0)]
+                points: vec![(100.0, 100.0), (150.0, 100.0)]
             },
-            mc
+            mb
         );
     }
 }
 #[cfg(test)]
 mod cnd_processor_tests {
     use super::*;
+    use crate::parameters::FlatEvent;
     use crate::trace;
     use std::collections::HashMap;
     use std::matches;
@@ -1329,6 +1824,78 @@ mod cnd_processor_tests {
         assert!(matches!(reply, ConditionReply::Error(_)));
     }
     #[test]
+    fn disable_1() {
+        // Disabling a condition keeps its description and the
+        // container dependent conditions refer to:
+
+        let mut cp = make_list_conditions();
+        let tracedb = trace::SharedTraceStore::new();
+        let before = cp.dict.get("t-cut").unwrap().as_ptr();
+        let reply = cp.process_request(
+            ConditionMessageClient::make_disable("t-cut", true),
+            &tracedb,
+        );
+        assert_eq!(ConditionReply::Modified, reply);
+        assert_eq!(before, cp.dict.get("t-cut").unwrap().as_ptr());
+
+        let reply = cp.process_request(ConditionMessageClient::make_list("t-cut"), &tracedb);
+        if let ConditionReply::Listing(list) = reply {
+            assert_eq!(1, list.len());
+            assert_eq!(String::from("Cut"), list[0].type_name);
+            assert_eq!(vec![(100.0, 0.0), (200.0, 0.0)], list[0].points);
+            assert_eq!(vec![12], list[0].parameters);
+            assert_eq!(Some(true), list[0].disabled);
+        } else {
+            panic!("list request did not return a listing");
+        }
+        // The and depends on the disabled cut so it's now true
+        // for any event:
+
+        let fe = FlatEvent::new();
+        assert!(cp.dict.get("fake").unwrap().borrow_mut().check(&fe));
+    }
+    #[test]
+    fn disable_2() {
+        // Disabling a condition that does not exist is an error:
+
+        let mut cp = make_list_conditions();
+        let tracedb = trace::SharedTraceStore::new();
+        let reply = cp.process_request(
+            ConditionMessageClient::make_disable("no-such", true),
+            &tracedb,
+        );
+        assert!(matches!(reply, ConditionReply::Error(_)));
+    }
+    #[test]
+    fn enable_1() {
+        // Enabling a disabled condition puts it back in service:
+
+        let mut cp = make_list_conditions();
+        let tracedb = trace::SharedTraceStore::new();
+        cp.process_request(
+            ConditionMessageClient::make_disable("true", false),
+            &tracedb,
+        );
+        let fe = FlatEvent::new();
+        assert!(!cp.dict.get("true").unwrap().borrow_mut().check(&fe));
+
+        let reply = cp.process_request(ConditionMessageClient::make_enable("true"), &tracedb);
+        assert_eq!(ConditionReply::Modified, reply);
+        let t = cp.dict.get("true").unwrap();
+        assert!(t.borrow().disabled_value().is_none());
+        assert_eq!(String::from("True"), t.borrow().condition_type());
+        assert!(t.borrow_mut().check(&fe));
+
+        // Enabling an enabled condition is fine:
+
+        let reply = cp.process_request(ConditionMessageClient::make_enable("true"), &tracedb);
+        assert_eq!(ConditionReply::Modified, reply);
+        assert_eq!(
+            String::from("True"),
+            cp.dict.get("true").unwrap().borrow().condition_type()
+        );
+    }
+    #[test]
     fn create_multi1_1() {
         let tracedb = trace::SharedTraceStore::new();
         let mut cp = ConditionProcessor::new();
@@ -1346,6 +1913,139 @@ mod cnd_processor_tests {
         );
     }
     #[test]
+    fn create_aggregate_1() {
+        let tracedb = trace::SharedTraceStore::new();
+        let mut cp = ConditionProcessor::new();
+        let rep = cp.process_request(
+            ConditionMessageClient::make_aggregate_creation(
+                "test",
+                AggregateKind::Mean,
+                &[1, 2, 3],
+                100.0,
+                200.0,
+            ),
+            &tracedb,
+        );
+        assert_eq!(ConditionReply::Created, rep);
+
+        let item = cp.dict.get("test");
+        assert!(item.is_some());
+        assert_eq!(
+            String::from("Mean"),
+            item.unwrap().borrow().condition_type()
+        );
+    }
+    #[test]
+    fn create_ratio_1() {
+        let tracedb = trace::SharedTraceStore::new();
+        let mut cp = ConditionProcessor::new();
+        let rep = cp.process_request(
+            ConditionMessageClient::make_ratio_creation("test", RatioKind::Ratio, 1, 2, 0.5, 1.5),
+            &tracedb,
+        );
+        assert_eq!(ConditionReply::Created, rep);
+
+        let item = cp.dict.get("test");
+        assert!(item.is_some());
+        assert_eq!(
+            String::from("Ratio"),
+            item.unwrap().borrow().condition_type()
+        );
+    }
+    #[test]
+    fn create_mask_1() {
+        let tracedb = trace::SharedTraceStore::new();
+        let mut cp = ConditionProcessor::new();
+        let rep = cp.process_request(
+            ConditionMessageClient::make_mask_creation("test", MaskKind::NotAnd, 1, 0x80),
+            &tracedb,
+        );
+        assert_eq!(ConditionReply::Created, rep);
+
+        let item = cp.dict.get("test");
+        assert!(item.is_some());
+        assert_eq!(
+            String::from("MaskNotAnd"),
+            item.unwrap().borrow().condition_type()
+        );
+    }
+    #[test]
+    fn create_c2band_1() {
+        // Needs two bands on the same parameters:
+
+        let tracedb = trace::SharedTraceStore::new();
+        let mut cp = ConditionProcessor::new();
+        for (name, y) in [("upper", 100.0), ("lower", 50.0)] {
+            cp.process_request(
+                ConditionMessageClient::make_band_creation(name, 1, 2, &[(0.0, y), (100.0, y)]),
+                &tracedb,
+            );
+        }
+        cp.process_request(
+            ConditionMessageClient::make_band_creation("other", 1, 3, &[(0.0, 0.0), (100.0, 0.0)]),
+            &tracedb,
+        );
+        let rep = cp.process_request(
+            ConditionMessageClient::make_c2band_creation("test", "upper", "lower"),
+            &tracedb,
+        );
+        assert_eq!(ConditionReply::Created, rep);
+        assert_eq!(
+            String::from("C2Band"),
+            cp.dict.get("test").unwrap().borrow().condition_type()
+        );
+
+        for (b1, b2) in [("upper", "nosuch"), ("nosuch", "lower"), ("upper", "other")] {
+            let rep = cp.process_request(
+                ConditionMessageClient::make_c2band_creation("bad", b1, b2),
+                &tracedb,
+            );
+            assert!(matches!(rep, ConditionReply::Error(_)), "{} {}", b1, b2);
+        }
+        assert!(!cp.dict.contains_key("bad"));
+    }
+    #[test]
+    fn create_contourset_1() {
+        // Good contour set and one with a polygon that has too few points:
+
+        let tracedb = trace::SharedTraceStore::new();
+        let mut cp = ConditionProcessor::new();
+        let rep = cp.process_request(
+            ConditionMessageClient::make_contourset_creation(
+                "test",
+                1,
+                2,
+                &[
+                    vec![(0.0, 0.0), (10.0, 0.0), (5.0, 10.0)],
+                    vec![(20.0, 20.0), (30.0, 20.0), (25.0, 30.0)],
+                ],
+            ),
+            &tracedb,
+        );
+        assert_eq!(ConditionReply::Created, rep);
+        let item = cp.dict.get("test");
+        assert!(item.is_some());
+        assert_eq!(
+            String::from("ContourSet"),
+            item.unwrap().borrow().condition_type()
+        );
+
+        let rep = cp.process_request(
+            ConditionMessageClient::make_contourset_creation(
+                "bad",
+                1,
+                2,
+                &[
+                    vec![(0.0, 0.0), (10.0, 0.0), (5.0, 10.0)],
+                    vec![(20.0, 20.0), (30.0, 20.0)],
+                ],
+            ),
+            &tracedb,
+        );
+        assert!(matches!(rep, ConditionReply::Error(_)));
+        assert!(!cp.dict.contains_key("bad"));
+    }
+    #[test]
     fn create_multi2_1() {
         // Create a multi-contour -no error.
 
@@ -1384,6 +2084,33 @@ mod cnd_processor_tests {
         );
         assert!(matches!(rep, ConditionReply::Error(_)));
     }
+    #[test]
+    fn create_multiband_1() {
+        // Create a multi-band and fail with too few points:
+
+        let tracedb = trace::SharedTraceStore::new();
+        let mut cp = ConditionProcessor::new();
+        let rep = cp.process_request(
+            ConditionMessageClient::make_multiband_creation(
+                "test",
+                &[1, 2, 3],
+                &[(100.0, 100.0), (150.0, 100.0)],
+            ),
+            &tracedb,
+        );
+        assert_eq!(ConditionReply::Created, rep);
+        assert_eq!(
+            "MultiBand",
+            cp.dict.get("test").unwrap().borrow().condition_type()
+        );
+
+        let rep = cp.process_request(
+            ConditionMessageClient::make_multiband_creation("bad", &[1, 2], &[(100.0, 100.0)]),
+            &tracedb,
+        );
+        assert!(matches!(rep, ConditionReply::Error(_)));
+        assert!(!cp.dict.contains_key("bad"));
+    }
 }
 #[cfg(test)]
 mod cnd_api_tests {
@@ -1527,8 +2254,10 @@ mod cnd_api_tests {
                 cond_name: String::from("test"),
                 type_name: String::from("MultiCut"),
                 points: vec![(100.0, 0.0), (200.0, 0.0)],
+                polygons: vec![],
                 gates: vec![],
-                parameters: vec![1, 2, 3]
+                parameters: vec![1, 2, 3],
+                disabled: None
             },]),
             l
         );
@@ -1555,8 +2284,173 @@ mod cnd_api_tests {
                 cond_name: String::from("test"),
                 type_name: String::from("MultiContour"),
                 points: vec![(10.0, 0.0), (20.0, 0.0), (15.0, 20.0)],
+                polygons: vec![],
+                gates: vec![],
+                parameters: vec![1, 2, 3],
+                disabled: None
+            },]),
+            l
+        );
+
+        stop_server(jh, send);
+    }
+    #[test]
+    fn multi_band_1() {
+        // Make a multi band:
+
+        let (jh, send) = start_server();
+        let api = ConditionMessageClient::new(&send);
+
+        let reply =
+            api.create_multiband_condition("test", &[1, 2, 3], &[(10.0, 0.0), (20.0, 10.0)]);
+        assert_eq!(ConditionReply::Created, reply);
+
+        let l = api.list_conditions("test");
+        assert_eq!(
+            ConditionReply::Listing(vec![ConditionProperties {
+                cond_name: String::from("test"),
+                type_name: String::from("MultiBand"),
+                points: vec![(10.0, 0.0), (20.0, 10.0)],
+                polygons: vec![],
+                gates: vec![],
+                parameters: vec![1, 2, 3],
+                disabled: None
+            },]),
+            l
+        );
+
+        stop_server(jh, send);
+    }
+    #[test]
+    fn aggregate_1() {
+        let (jh, send) = start_server();
+        let api = ConditionMessageClient::new(&send);
+
+        let reply =
+            api.create_aggregate_condition("test", AggregateKind::Sum, &[1, 2, 3], 100.0, 200.0);
+        assert_eq!(ConditionReply::Created, reply);
+
+        let l = api.list_conditions("test");
+        assert_eq!(
+            ConditionReply::Listing(vec![ConditionProperties {
+                cond_name: String::from("test"),
+                type_name: String::from("Sum"),
+                points: vec![(100.0, 0.0), (200.0, 0.0)],
+                polygons: vec![],
+                gates: vec![],
+                parameters: vec![1, 2, 3],
+                disabled: None
+            },]),
+            l
+        );
+
+        // Must have parameters:
+
+        let reply = api.create_aggregate_condition("bad", AggregateKind::Max, &[], 100.0, 200.0);
+        assert!(matches!(reply, ConditionReply::Error(_)));
+
+        stop_server(jh, send);
+    }
+    #[test]
+    fn ratio_1() {
+        let (jh, send) = start_server();
+        let api = ConditionMessageClient::new(&send);
+
+        let reply = api.create_ratio_condition("test", RatioKind::Difference, 2, 1, -10.0, 10.0);
+        assert_eq!(ConditionReply::Created, reply);
+
+        let l = api.list_conditions("test");
+        assert_eq!(
+            ConditionReply::Listing(vec![ConditionProperties {
+                cond_name: String::from("test"),
+                type_name: String::from("Difference"),
+                points: vec![(-10.0, 0.0), (10.0, 0.0)],
+                polygons: vec![],
+                gates: vec![],
+                parameters: vec![2, 1],
+                disabled: None
+            },]),
+            l
+        );
+
+        stop_server(jh, send);
+    }
+    #[test]
+    fn mask_1() {
+        let (jh, send) = start_server();
+        let api = ConditionMessageClient::new(&send);
+
+        let reply = api.create_mask_condition("test", MaskKind::Equal, 2, 0x1234);
+        assert_eq!(ConditionReply::Created, reply);
+
+        let l = api.list_conditions("test");
+        assert_eq!(
+            ConditionReply::Listing(vec![ConditionProperties {
+                cond_name: String::from("test"),
+                type_name: String::from("MaskEqual"),
+                points: vec![(4660.0, 0.0)],
+                polygons: vec![],
+                gates: vec![],
+                parameters: vec![2],
+                disabled: None
+            },]),
+            l
+        );
+
+        stop_server(jh, send);
+    }
+    #[test]
+    fn c2band_1() {
+        // The contour follows edits of the bands:
+
+        let (jh, send) = start_server();
+        let api = ConditionMessageClient::new(&send);
+        api.create_band_condition("upper", 1, 2, &[(0.0, 100.0), (100.0, 100.0)]);
+        api.create_band_condition("lower", 1, 2, &[(0.0, 50.0), (100.0, 50.0)]);
+
+        let reply = api.create_c2band_condition("test", "upper", "lower");
+        assert_eq!(ConditionReply::Created, reply);
+
+        let expected = |low: f64| {
+            ConditionReply::Listing(vec![ConditionProperties {
+                cond_name: String::from("test"),
+                type_name: String::from("C2Band"),
+                points: vec![(0.0, 100.0), (100.0, 100.0), (100.0, low), (0.0, low)],
+                polygons: vec![],
+                gates: vec![String::from("upper"), String::from("lower")],
+                parameters: vec![1, 2],
+                disabled: None,
+            }])
+        };
+        assert_eq!(expected(50.0), api.list_conditions("test"));
+
+        api.create_band_condition("lower", 1, 2, &[(0.0, 10.0), (100.0, 10.0)]);
+        assert_eq!(expected(10.0), api.list_conditions("test"));
+
+        stop_server(jh, send);
+    }
+    #[test]
+    fn contourset_1() {
+        let (jh, send) = start_server();
+        let api = ConditionMessageClient::new(&send);
+
+        let polygons = vec![
+            vec![(0.0, 0.0), (10.0, 0.0), (5.0, 10.0)],
+            vec![(20.0, 20.0), (30.0, 20.0), (30.0, 30.0), (20.0, 30.0)],
+        ];
+        let reply = api.create_contourset_condition("test", 2, 1, &polygons);
+        assert_eq!(ConditionReply::Created, reply);
+
+        let l = api.list_conditions("test");
+        assert_eq!(
+            ConditionReply::Listing(vec![ConditionProperties {
+                cond_name: String::from("test"),
+                type_name: String::from("ContourSet"),
+                points: polygons.concat(),
+                polygons: polygons.clone(),
                 gates: vec![],
-                parameters: vec![1, 2, 3]
+                parameters: vec![2, 1],
+                disabled: None
             },]),
             l
         );
@@ -1864,8 +2758,10 @@ mod recons_contour_tests {
             cond_name: String::from("junk"),
             type_name: String::from("Not a contour"),
             points: vec![],
+            polygons: vec![],
             gates: vec![],
             parameters: vec![],
+            disabled: None,
         };
         assert!(reconstitute_contour(desc).is_err());
     }
@@ -1877,20 +2773,41 @@ mod recons_contour_tests {
             cond_name: String::from("junk"),
             type_name: String::from("Contour"),
             points: vec![(100.0, 100.0), (200.0, 100.0)],
+            polygons: vec![],
             gates: vec![],
             parameters: vec![],
+            disabled: None,
         };
         assert!(reconstitute_contour(desc).is_err());
     }
     #[test]
+    fn ok_2() {
+        // Contours from two bands are contours:
+
+        let desc = condition_messages::ConditionProperties {
+            cond_name: String::from("junk"),
+            type_name: String::from("C2Band"),
+            points: vec![(0.0, 100.0), (100.0, 100.0), (100.0, 50.0), (0.0, 50.0)],
+            polygons: vec![],
+            gates: vec![String::from("upper"), String::from("lower")],
+            parameters: vec![1, 2],
+            disabled: None,
+        };
+        let contour = reconstitute_contour(desc).expect("Reconstituting");
+        assert!(contour.inside(50.0, 75.0));
+        assert!(!contour.inside(50.0, 25.0));
+    }
+    #[test]
     fn ok_1() {
         let pts = vec![(100.0, 100.0), (200.0, 100.0), (150.0, 150.0)]; // needed for later assertion:
         let desc = condition_messages::ConditionProperties {
             cond_name: String::from("junk"),
             type_name: String::from("Contour"),
             points: pts.clone(),
+            polygons: vec![],
             gates: vec![],
             parameters: vec![],
+            disabled: None,
         };
         let result = reconstitute_contour(desc);
         assert!(result.is_ok());
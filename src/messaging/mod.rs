@@ -23,6 +23,7 @@
 //! histograming thread and for replies to come back.
 //!
 use std::sync::mpsc;
+use std::time;
 
 // Re exports
 
@@ -41,6 +42,7 @@ pub enum MessageType {
     Parameter(ParameterRequest),
     Condition(ConditionRequest),
     Spectrum(SpectrumRequest),
+    Ping,
     Exit,
 }
 
@@ -53,6 +55,7 @@ pub enum Reply {
     Parameter(ParameterReply),
     Condition(ConditionReply),
     Spectrum(SpectrumReply),
+    Pong,
     Exiting,
     Failed,
 }
@@ -133,4 +136,27 @@ impl Request {
             Reply::Failed
         }
     }
+    /// Check that the histogramer is responsive.  A Ping is
+    /// queued and we wait at most timeout for its Pong.  Since
+    /// requests are processed in order, this fails if the
+    /// histogramer is backlogged (e.g. with event data) as well as
+    /// if it has exited.
+    ///
+    /// ### Parameters
+    /// * req - channel on which to send the ping.
+    /// * timeout - How long to wait for the reply.
+    ///
+    /// ### Returns
+    /// bool - true if the Pong came back in time.
+    pub fn ping(req: mpsc::Sender<Request>, timeout: time::Duration) -> bool {
+        let (reply_send, reply_recv) = mpsc::channel();
+        let request = Request {
+            reply_channel: reply_send,
+            message: MessageType::Ping,
+        };
+        if request.send(req).is_err() {
+            return false;
+        }
+        matches!(reply_recv.recv_timeout(timeout), Ok(Reply::Pong))
+    }
 }
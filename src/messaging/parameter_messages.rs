@@ -8,9 +8,16 @@
 //!  *    List   - list the parameters and their properties
 //!  that match a glob pattern.
 //!  *    SetMetaData - Modifies the metadata for a parameter.
+//!  *    SetCalibration - Sets or removes the calibration of a parameter.
+//!  *    SetCalibratedParameter, ListCalibratedParameters,
+//!       DeleteCalibratedParameter - manipulate the calibrated parameters
+//!       that are computed from raw parameters.
+//!  *    CreatePseudo, ListPseudos, DeletePseudo - manipulate the
+//!       pseudo parameters that are computed from other parameters.
 //!
 //!  Note that it is a design property of parameters that, while they
-//!  can be modified they *cannot* be deleted.
+//!  can be modified they *cannot* be deleted.  Deleting a pseudo or
+//!  calibrated parameter only stops it from being computed.
 //!
 //!  
 use super::MessageType;
@@ -19,7 +26,7 @@ use super::Request;
 
 use glob::Pattern;
 
-use crate::parameters::{Parameter, ParameterDictionary};
+use crate::parameters::{calibration, pseudo, Parameter, ParameterDictionary};
 use crate::trace;
 use std::sync::mpsc;
 
@@ -37,6 +44,40 @@ pub enum ParameterRequest {
         units: Option<String>,
         description: Option<String>,
     },
+    SetCalibration {
+        name: String,
+        calibration: Option<(f64, f64)>,
+    },
+    CreatePseudo {
+        name: String,
+        parameters: Vec<String>,
+        computation: String,
+    },
+    ListPseudos(String),
+    DeletePseudo(String),
+    SetCalibratedParameter {
+        name: String,
+        raw: String,
+        calibration: calibration::Calibration,
+        units: Option<String>,
+    },
+    ListCalibratedParameters(String),
+    DeleteCalibratedParameter(String),
+}
+/// Description of a pseudo parameter in a listing:
+#[derive(Clone, Debug, PartialEq)]
+pub struct PseudoProperties {
+    pub name: String,
+    pub parameters: Vec<String>,
+    pub computation: String,
+}
+/// Description of a calibrated parameter in a listing:
+#[derive(Clone, Debug, PartialEq)]
+pub struct CalibratedProperties {
+    pub name: String,
+    pub raw: String,
+    pub calibration: calibration::Calibration,
+    pub units: Option<String>,
 }
 /// The following are possible reply mesages:
 #[derive(Clone, Debug, PartialEq)]
@@ -45,11 +86,16 @@ pub enum ParameterReply {
     Created,
     Listing(Vec<Parameter>),
     Modified,
+    PseudoListing(Vec<PseudoProperties>),
+    CalibratedListing(Vec<CalibratedProperties>),
+    Deleted,
 }
 /// Result types:
 
 pub type ParameterResult = Result<(), String>; // /Generic result.
 pub type ListResult = Result<Vec<Parameter>, String>; // Result from list request.
+pub type PseudoListResult = Result<Vec<PseudoProperties>, String>; // Result from pseudo list.
+pub type CalibratedListResult = Result<Vec<CalibratedProperties>, String>; // Calibrated list.
 
 /// This struct and its implementation are part of the solution to
 /// issue23 which drastically simplifies the clien's use of the
@@ -90,6 +136,52 @@ impl ParameterMessageClient {
         };
         MessageType::Parameter(req_data)
     }
+    fn make_calibration_request(name: &str, calibration: Option<(f64, f64)>) -> MessageType {
+        MessageType::Parameter(ParameterRequest::SetCalibration {
+            name: String::from(name),
+            calibration,
+        })
+    }
+    fn make_create_pseudo_request(
+        name: &str,
+        parameters: &[String],
+        computation: &str,
+    ) -> MessageType {
+        MessageType::Parameter(ParameterRequest::CreatePseudo {
+            name: String::from(name),
+            parameters: parameters.to_vec(),
+            computation: String::from(computation),
+        })
+    }
+    fn make_list_pseudos_request(pattern: &str) -> MessageType {
+        MessageType::Parameter(ParameterRequest::ListPseudos(String::from(pattern)))
+    }
+    fn make_delete_pseudo_request(name: &str) -> MessageType {
+        MessageType::Parameter(ParameterRequest::DeletePseudo(String::from(name)))
+    }
+    fn make_set_calibrated_request(
+        name: &str,
+        raw: &str,
+        calibration: calibration::Calibration,
+        units: Option<String>,
+    ) -> MessageType {
+        MessageType::Parameter(ParameterRequest::SetCalibratedParameter {
+            name: String::from(name),
+            raw: String::from(raw),
+            calibration,
+            units,
+        })
+    }
+    fn make_list_calibrated_request(pattern: &str) -> MessageType {
+        MessageType::Parameter(ParameterRequest::ListCalibratedParameters(String::from(
+            pattern,
+        )))
+    }
+    fn make_delete_calibrated_request(name: &str) -> MessageType {
+        MessageType::Parameter(ParameterRequest::DeleteCalibratedParameter(String::from(
+            name,
+        )))
+    }
     // Making all transactions go through this simplifies stuff:
 
     fn transaction(&self, r: MessageType) -> ParameterReply {
@@ -131,6 +223,13 @@ impl ParameterMessageClient {
             ParameterReply::Created => Ok(()),
             ParameterReply::Listing(_) => Err(String::from("BUG!! Create got a Listing reply")),
             ParameterReply::Modified => Err(String::from("BUG!! Create got a Modified reply")),
+            ParameterReply::PseudoListing(_) => {
+                Err(String::from("BUG!! Create got a PseudoListing reply"))
+            }
+            ParameterReply::CalibratedListing(_) => {
+                Err(String::from("BUG!! Create got a CalibratedListing reply"))
+            }
+            ParameterReply::Deleted => Err(String::from("BUG!! Create got a Deleted reply")),
         }
     }
     /// Request a list of the set of parameters that match a specified pattern.
@@ -191,6 +290,146 @@ impl ParameterMessageClient {
             )),
         }
     }
+    ///
+    /// Set or remove the calibration of a parameter.  Once set, the
+    /// histogramer applies the calibration to the values of the parameter
+    /// before they are histogrammed.
+    ///
+    ///  -   name is the name of the parameter.
+    ///  -   calibration - Some is a (gain, offset) pair; calibrated values are
+    /// gain*raw + offset.  None removes any existing calibration.
+    ///
+    /// The return is the generic ParameterResult
+    pub fn set_parameter_calibration(
+        &self,
+        name: &str,
+        calibration: Option<(f64, f64)>,
+    ) -> ParameterResult {
+        let reply = self.transaction(Self::make_calibration_request(name, calibration));
+        match reply {
+            ParameterReply::Modified => Ok(()),
+            ParameterReply::Error(s) => Err(s),
+            _ => Err(String::from(
+                "Bug: Invalid histogram Parameter response to Parameter::SetCalibration request",
+            )),
+        }
+    }
+    ///
+    /// Create a pseudo parameter.  The histogramer computes the pseudo
+    /// for each event that has all of the parameters it depends on.
+    ///
+    ///  -   name is the name of the pseudo.  The pseudo computes the value
+    ///      of the parameter with this name, which is created if necessary.
+    ///  -   parameters - names of the existing parameters the pseudo
+    ///      depends on.
+    ///  -   computation - arithmetic expression of those parameters
+    ///      (see parameters::pseudo).
+    ///
+    /// The return is the generic ParameterResult
+    pub fn create_pseudo(
+        &self,
+        name: &str,
+        parameters: &[String],
+        computation: &str,
+    ) -> ParameterResult {
+        let reply = self.transaction(Self::make_create_pseudo_request(
+            name,
+            parameters,
+            computation,
+        ));
+        match reply {
+            ParameterReply::Created => Ok(()),
+            ParameterReply::Error(s) => Err(s),
+            _ => Err(String::from(
+                "Bug: Invalid histogram Parameter response to Parameter::CreatePseudo request",
+            )),
+        }
+    }
+    ///
+    /// List the pseudo parameters whose names match a glob pattern.
+    /// The pseudos are listed in the order in which they are computed.
+    ///
+    pub fn list_pseudos(&self, pattern: &str) -> PseudoListResult {
+        match self.transaction(Self::make_list_pseudos_request(pattern)) {
+            ParameterReply::PseudoListing(l) => Ok(l),
+            ParameterReply::Error(s) => Err(s),
+            _ => Err(String::from(
+                "Bug: Invalid histogram Parameter response to Parameter::ListPseudos request",
+            )),
+        }
+    }
+    ///
+    /// Delete a pseudo parameter.  The parameter it computed still
+    /// exists but is no longer computed.
+    ///
+    pub fn delete_pseudo(&self, name: &str) -> ParameterResult {
+        match self.transaction(Self::make_delete_pseudo_request(name)) {
+            ParameterReply::Deleted => Ok(()),
+            ParameterReply::Error(s) => Err(s),
+            _ => Err(String::from(
+                "Bug: Invalid histogram Parameter response to Parameter::DeletePseudo request",
+            )),
+        }
+    }
+    ///
+    /// Define a calibrated parameter.  The histogramer computes the
+    /// calibrated parameter for each event that has the raw parameter.
+    ///
+    ///  -   name is the name of the calibrated parameter, which is created
+    ///      if necessary.  If it is already a calibrated parameter, its
+    ///      definition is replaced.
+    ///  -   raw - name of the existing parameter that is calibrated.
+    ///  -   calibration - the calibration coefficients
+    ///      (see parameters::calibration).
+    ///  -   units - optional units of the calibrated parameter.
+    ///
+    /// The return is the generic ParameterResult
+    pub fn set_calibrated_parameter(
+        &self,
+        name: &str,
+        raw: &str,
+        calibration: calibration::Calibration,
+        units: Option<String>,
+    ) -> ParameterResult {
+        match self.transaction(Self::make_set_calibrated_request(
+            name,
+            raw,
+            calibration,
+            units,
+        )) {
+            ParameterReply::Created => Ok(()),
+            ParameterReply::Error(s) => Err(s),
+            _ => Err(String::from(
+                "Bug: Invalid histogram Parameter response to Parameter::SetCalibratedParameter request",
+            )),
+        }
+    }
+    ///
+    /// List the calibrated parameters whose names match a glob pattern.
+    /// They are listed in the order in which they are computed.
+    ///
+    pub fn list_calibrated_parameters(&self, pattern: &str) -> CalibratedListResult {
+        match self.transaction(Self::make_list_calibrated_request(pattern)) {
+            ParameterReply::CalibratedListing(l) => Ok(l),
+            ParameterReply::Error(s) => Err(s),
+            _ => Err(String::from(
+                "Bug: Invalid histogram Parameter response to Parameter::ListCalibratedParameters request",
+            )),
+        }
+    }
+    ///
+    /// Delete a calibrated parameter.  The parameter still exists but
+    /// is no longer computed.
+    ///
+    pub fn delete_calibrated_parameter(&self, name: &str) -> ParameterResult {
+        match self.transaction(Self::make_delete_calibrated_request(name)) {
+            ParameterReply::Deleted => Ok(()),
+            ParameterReply::Error(s) => Err(s),
+            _ => Err(String::from(
+                "Bug: Invalid histogram Parameter response to Parameter::DeleteCalibratedParameter request",
+            )),
+        }
+    }
 }
 /// ParameterProcessor is a struct that encapsulates a ParmeterDictionary
 /// and implements code that can process ParameterRequest objects
@@ -256,6 +495,181 @@ impl ParameterProcessor {
             ParameterReply::Error(format!("Parameter {} does not exist", name))
         }
     }
+    fn set_calibration(
+        &mut self,
+        name: &str,
+        calibration: Option<(f64, f64)>,
+        tracedb: &trace::SharedTraceStore,
+    ) -> ParameterReply {
+        if let Some(p) = self.dict.lookup_mut(name) {
+            p.set_calibration(calibration);
+            tracedb.add_event(trace::TraceEvent::ParameterModified(String::from(name)));
+            ParameterReply::Modified
+        } else {
+            ParameterReply::Error(format!("Parameter {} does not exist", name))
+        }
+    }
+    // Create a pseudo parameter.  The dependencies must exist but the
+    // parameter the pseudo computes is created if needed.  We check
+    // everything before creating that parameter so a bad pseudo leaves
+    // nothing behind.
+
+    fn create_pseudo(
+        &mut self,
+        name: &str,
+        parameters: &[String],
+        computation: &str,
+        tracedb: &trace::SharedTraceStore,
+    ) -> ParameterReply {
+        if self.dict.pseudos().contains(name) {
+            return ParameterReply::Error(format!("Pseudo parameter {} already exists", name));
+        }
+        if self.dict.calibrated().contains(name) {
+            return ParameterReply::Error(format!("{} is a calibrated parameter", name));
+        }
+        if parameters.is_empty() {
+            return ParameterReply::Error(String::from(
+                "A pseudo parameter must depend on at least one parameter",
+            ));
+        }
+        if parameters.iter().any(|p| p == name) {
+            return ParameterReply::Error(format!(
+                "Pseudo parameter {} can't depend on itself",
+                name
+            ));
+        }
+        let mut dependencies = Vec::<(String, u32)>::new();
+        for p in parameters {
+            match self.dict.lookup(p) {
+                Some(param) => dependencies.push((p.clone(), param.get_id())),
+                None => return ParameterReply::Error(format!("Parameter {} does not exist", p)),
+            }
+        }
+        let expression = match pseudo::Expression::parse(computation, parameters) {
+            Ok(e) => e,
+            Err(s) => return ParameterReply::Error(format!("Invalid computation: {}", s)),
+        };
+        if self.dict.lookup(name).is_none() {
+            if let Err(s) = self.dict.add(name) {
+                return ParameterReply::Error(s);
+            }
+            tracedb.add_event(trace::TraceEvent::NewParameter(String::from(name)));
+        }
+        let id = self.dict.lookup(name).unwrap().get_id();
+        match self.dict.pseudos_mut().add(pseudo::PseudoParameter::new(
+            name,
+            id,
+            &dependencies,
+            computation,
+            expression,
+        )) {
+            Ok(()) => ParameterReply::Created,
+            Err(s) => ParameterReply::Error(s),
+        }
+    }
+    fn list_pseudos(&self, pattern: &str) -> ParameterReply {
+        let pat = match Pattern::new(pattern) {
+            Ok(p) => p,
+            Err(e) => return ParameterReply::Error(String::from(e.msg)),
+        };
+        ParameterReply::PseudoListing(
+            self.dict
+                .pseudos()
+                .iter()
+                .filter(|p| pat.matches(&p.name()))
+                .map(|p| PseudoProperties {
+                    name: p.name(),
+                    parameters: p.parameters(),
+                    computation: p.computation(),
+                })
+                .collect(),
+        )
+    }
+    fn delete_pseudo(&mut self, name: &str) -> ParameterReply {
+        match self.dict.pseudos_mut().delete(name) {
+            Ok(()) => ParameterReply::Deleted,
+            Err(s) => ParameterReply::Error(s),
+        }
+    }
+    // Define a calibrated parameter.  As with pseudos, the raw parameter
+    // must exist but the calibrated parameter is created if needed and
+    // everything is checked before it is.
+
+    fn set_calibrated(
+        &mut self,
+        name: &str,
+        raw: &str,
+        calibration: calibration::Calibration,
+        units: Option<String>,
+        tracedb: &trace::SharedTraceStore,
+    ) -> ParameterReply {
+        if name == raw {
+            return ParameterReply::Error(format!(
+                "Calibrated parameter {} can't be calibrated from itself",
+                name
+            ));
+        }
+        if self.dict.pseudos().contains(name) {
+            return ParameterReply::Error(format!("{} is a pseudo parameter", name));
+        }
+        let raw_id = match self.dict.lookup(raw) {
+            Some(p) => p.get_id(),
+            None => return ParameterReply::Error(format!("Parameter {} does not exist", raw)),
+        };
+        if !(calibration.offset.is_finite()
+            && calibration.slope.is_finite()
+            && calibration.quadratic.is_finite())
+        {
+            return ParameterReply::Error(String::from("Calibration coefficients must be finite"));
+        }
+        if self.dict.lookup(name).is_none() {
+            if let Err(s) = self.dict.add(name) {
+                return ParameterReply::Error(s);
+            }
+            tracedb.add_event(trace::TraceEvent::NewParameter(String::from(name)));
+        }
+        let parameter = self.dict.lookup_mut(name).unwrap();
+        if let Some(u) = units.as_ref() {
+            parameter.set_units(u);
+        }
+        let id = parameter.get_id();
+        self.dict
+            .calibrated_mut()
+            .set(calibration::CalibratedParameter::new(
+                name,
+                id,
+                raw,
+                raw_id,
+                calibration,
+                units,
+            ));
+        ParameterReply::Created
+    }
+    fn list_calibrated(&self, pattern: &str) -> ParameterReply {
+        let pat = match Pattern::new(pattern) {
+            Ok(p) => p,
+            Err(e) => return ParameterReply::Error(String::from(e.msg)),
+        };
+        ParameterReply::CalibratedListing(
+            self.dict
+                .calibrated()
+                .iter()
+                .filter(|p| pat.matches(&p.name()))
+                .map(|p| CalibratedProperties {
+                    name: p.name(),
+                    raw: p.raw(),
+                    calibration: p.calibration(),
+                    units: p.units(),
+                })
+                .collect(),
+        )
+    }
+    fn delete_calibrated(&mut self, name: &str) -> ParameterReply {
+        match self.dict.calibrated_mut().delete(name) {
+            Ok(()) => ParameterReply::Deleted,
+            Err(s) => ParameterReply::Error(s),
+        }
+    }
 
     /// Create a new processor.
     pub fn new() -> ParameterProcessor {
@@ -280,6 +694,24 @@ impl ParameterProcessor {
                 units,
                 description,
             } => self.modify(&name, bins, limits, units, description, tracedb),
+            ParameterRequest::SetCalibration { name, calibration } => {
+                self.set_calibration(&name, calibration, tracedb)
+            }
+            ParameterRequest::CreatePseudo {
+                name,
+                parameters,
+                computation,
+            } => self.create_pseudo(&name, &parameters, &computation, tracedb),
+            ParameterRequest::ListPseudos(pattern) => self.list_pseudos(&pattern),
+            ParameterRequest::DeletePseudo(name) => self.delete_pseudo(&name),
+            ParameterRequest::SetCalibratedParameter {
+                name,
+                raw,
+                calibration,
+                units,
+            } => self.set_calibrated(&name, &raw, calibration, units, tracedb),
+            ParameterRequest::ListCalibratedParameters(pattern) => self.list_calibrated(&pattern),
+            ParameterRequest::DeleteCalibratedParameter(name) => self.delete_calibrated(&name),
         }
     }
     pub fn get_dict(&mut self) -> &mut ParameterDictionary {
@@ -469,6 +901,37 @@ mod pprocessor_tests {
             panic!("make_mdify_request did not make a parameter request object");
         }
     }
+    fn calibration_req(name: &str, calibration: Option<(f64, f64)>) -> ParameterRequest {
+        if let MessageType::Parameter(req) =
+            ParameterMessageClient::make_calibration_request(name, calibration)
+        {
+            req
+        } else {
+            panic!("make_calibration_request did not make a parameter request object");
+        }
+    }
+    fn create_pseudo_req(name: &str, parameters: &[&str], computation: &str) -> ParameterRequest {
+        let parameters: Vec<String> = parameters.iter().map(|p| String::from(*p)).collect();
+        if let MessageType::Parameter(req) =
+            ParameterMessageClient::make_create_pseudo_request(name, &parameters, computation)
+        {
+            req
+        } else {
+            panic!("make_create_pseudo_request did not make a parameter request object");
+        }
+    }
+    fn set_calibrated_req(name: &str, raw: &str, c: (f64, f64, f64)) -> ParameterRequest {
+        if let MessageType::Parameter(req) = ParameterMessageClient::make_set_calibrated_request(
+            name,
+            raw,
+            calibration::Calibration::new(c.0, c.1, c.2),
+            Some(String::from("keV")),
+        ) {
+            req
+        } else {
+            panic!("make_set_calibrated_request did not make a parameter request object");
+        }
+    }
     // Make 10 parameters named param.0..9
     // and 10 more parameter named others.0..9
     //
@@ -619,8 +1082,10 @@ mod pprocessor_tests {
         // Glob pattern syntax errors ->Error return.
         let mut pp = create_some_params();
         let tracedb = trace::SharedTraceStore::new();
-        assert!(matches!(pp.process_request(list_req("p["), &tracedb), ParameterReply::Error(_) ));
-        
+        assert!(matches!(
+            pp.process_request(list_req("p["), &tracedb),
+            ParameterReply::Error(_)
+        ));
     }
     #[test]
     fn modify_1() {
@@ -768,6 +1233,199 @@ mod pprocessor_tests {
             ParameterReply::Error(_)
         ));
     }
+    #[test]
+    fn calibration_1() {
+        // Set and then remove a calibration:
+
+        let mut pp = create_some_params();
+        let tracedb = trace::SharedTraceStore::new();
+        let req = calibration_req("param.1", Some((2.0, -1.0)));
+        assert!(matches!(
+            pp.process_request(req, &tracedb),
+            ParameterReply::Modified
+        ));
+        assert_eq!(
+            Some((2.0, -1.0)),
+            pp.get_dict().lookup("param.1").unwrap().get_calibration()
+        );
+
+        let req = calibration_req("param.1", None);
+        assert!(matches!(
+            pp.process_request(req, &tracedb),
+            ParameterReply::Modified
+        ));
+        assert_eq!(
+            None,
+            pp.get_dict().lookup("param.1").unwrap().get_calibration()
+        );
+    }
+    #[test]
+    fn calibration_2() {
+        // Calibrating a nonexistent parameter is an error:
+
+        let mut pp = create_some_params();
+        let tracedb = trace::SharedTraceStore::new();
+        let req = calibration_req("no.such.parameter", Some((1.0, 0.0)));
+        assert!(matches!(
+            pp.process_request(req, &tracedb),
+            ParameterReply::Error(_)
+        ));
+    }
+    #[test]
+    fn pseudo_1() {
+        // Create a pseudo - its parameter is made, list and delete it:
+
+        let mut pp = create_some_params();
+        let tracedb = trace::SharedTraceStore::new();
+        let req = create_pseudo_req("sum", &["param.0", "param.1"], "param.0 + param.1");
+        assert_eq!(ParameterReply::Created, pp.process_request(req, &tracedb));
+        assert!(pp.get_dict().lookup("sum").is_some());
+
+        let reply = pp.process_request(ParameterRequest::ListPseudos(String::from("*")), &tracedb);
+        assert_eq!(
+            ParameterReply::PseudoListing(vec![PseudoProperties {
+                name: String::from("sum"),
+                parameters: vec![String::from("param.0"), String::from("param.1")],
+                computation: String::from("param.0 + param.1"),
+            }]),
+            reply
+        );
+        let reply = pp.process_request(ParameterRequest::ListPseudos(String::from("x*")), &tracedb);
+        assert_eq!(ParameterReply::PseudoListing(vec![]), reply);
+
+        let req = ParameterRequest::DeletePseudo(String::from("sum"));
+        assert_eq!(ParameterReply::Deleted, pp.process_request(req, &tracedb));
+        assert!(pp.get_dict().pseudos().is_empty());
+        assert!(pp.get_dict().lookup("sum").is_some()); // Parameters are forever.
+
+        let req = ParameterRequest::DeletePseudo(String::from("sum"));
+        assert!(matches!(
+            pp.process_request(req, &tracedb),
+            ParameterReply::Error(_)
+        ));
+    }
+    #[test]
+    fn pseudo_2() {
+        // Failed creations leave no parameter or pseudo behind:
+
+        let mut pp = create_some_params();
+        let tracedb = trace::SharedTraceStore::new();
+        for req in [
+            create_pseudo_req("p", &[], "1"),
+            create_pseudo_req("p", &["p"], "p"),
+            create_pseudo_req("p", &["no.such"], "no.such"),
+            create_pseudo_req("p", &["param.0"], "param.0 +"),
+            create_pseudo_req("p", &["param.0"], "param.1"),
+        ] {
+            assert!(matches!(
+                pp.process_request(req, &tracedb),
+                ParameterReply::Error(_)
+            ));
+        }
+        assert!(pp.get_dict().lookup("p").is_none());
+        assert!(pp.get_dict().pseudos().is_empty());
+
+        // Duplicates:
+
+        let req = create_pseudo_req("p", &["param.0"], "param.0*2");
+        assert_eq!(ParameterReply::Created, pp.process_request(req, &tracedb));
+        let req = create_pseudo_req("p", &["param.1"], "param.1*2");
+        assert!(matches!(
+            pp.process_request(req, &tracedb),
+            ParameterReply::Error(_)
+        ));
+
+        // A calibrated parameter can't also be a pseudo:
+
+        let req = set_calibrated_req("e", "param.0", (0.0, 1.0, 0.0));
+        assert_eq!(ParameterReply::Created, pp.process_request(req, &tracedb));
+        let req = create_pseudo_req("e", &["param.1"], "param.1*2");
+        assert!(matches!(
+            pp.process_request(req, &tracedb),
+            ParameterReply::Error(_)
+        ));
+    }
+    #[test]
+    fn calibrated_1() {
+        // Define a calibrated parameter - its parameter is made with
+        // the units, list, redefine and delete it:
+
+        let mut pp = create_some_params();
+        let tracedb = trace::SharedTraceStore::new();
+        let req = set_calibrated_req("energy", "param.0", (1.0, 2.0, 0.0));
+        assert_eq!(ParameterReply::Created, pp.process_request(req, &tracedb));
+        assert_eq!(
+            Some(String::from("keV")),
+            pp.get_dict().lookup("energy").unwrap().get_units()
+        );
+
+        let req = ParameterRequest::ListCalibratedParameters(String::from("*"));
+        assert_eq!(
+            ParameterReply::CalibratedListing(vec![CalibratedProperties {
+                name: String::from("energy"),
+                raw: String::from("param.0"),
+                calibration: calibration::Calibration::new(1.0, 2.0, 0.0),
+                units: Some(String::from("keV")),
+            }]),
+            pp.process_request(req, &tracedb)
+        );
+        let req = ParameterRequest::ListCalibratedParameters(String::from("x*"));
+        assert_eq!(
+            ParameterReply::CalibratedListing(vec![]),
+            pp.process_request(req, &tracedb)
+        );
+
+        let req = set_calibrated_req("energy", "param.1", (0.0, 1.0, 0.5));
+        assert_eq!(ParameterReply::Created, pp.process_request(req, &tracedb));
+        let listing = pp.process_request(
+            ParameterRequest::ListCalibratedParameters(String::from("*")),
+            &tracedb,
+        );
+        if let ParameterReply::CalibratedListing(l) = listing {
+            assert_eq!(1, l.len());
+            assert_eq!("param.1", l[0].raw);
+            assert_eq!(
+                calibration::Calibration::new(0.0, 1.0, 0.5),
+                l[0].calibration
+            );
+        } else {
+            panic!("Expected a calibrated listing");
+        }
+
+        let req = ParameterRequest::DeleteCalibratedParameter(String::from("energy"));
+        assert_eq!(ParameterReply::Deleted, pp.process_request(req, &tracedb));
+        assert!(pp.get_dict().calibrated().is_empty());
+        assert!(pp.get_dict().lookup("energy").is_some());
+
+        let req = ParameterRequest::DeleteCalibratedParameter(String::from("energy"));
+        assert!(matches!(
+            pp.process_request(req, &tracedb),
+            ParameterReply::Error(_)
+        ));
+    }
+    #[test]
+    fn calibrated_2() {
+        // Failed definitions leave nothing behind:
+
+        let mut pp = create_some_params();
+        let tracedb = trace::SharedTraceStore::new();
+        let req = create_pseudo_req("sum", &["param.0", "param.1"], "param.0 + param.1");
+        assert_eq!(ParameterReply::Created, pp.process_request(req, &tracedb));
+        for req in [
+            set_calibrated_req("e", "e", (0.0, 1.0, 0.0)),
+            set_calibrated_req("e", "no.such", (0.0, 1.0, 0.0)),
+            set_calibrated_req("e", "param.0", (f64::NAN, 1.0, 0.0)),
+            set_calibrated_req("e", "param.0", (0.0, f64::INFINITY, 0.0)),
+            set_calibrated_req("sum", "param.0", (0.0, 1.0, 0.0)),
+        ] {
+            assert!(matches!(
+                pp.process_request(req, &tracedb),
+                ParameterReply::Error(_)
+            ));
+        }
+        assert!(pp.get_dict().lookup("e").is_none());
+        assert!(pp.get_dict().calibrated().is_empty());
+    }
 }
 // Test tracing
 
@@ -21,7 +21,9 @@ use std::sync::mpsc;
 use glob::Pattern;
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct AxisSpecification {
@@ -57,6 +59,37 @@ pub struct SpectrumProperties {
     pub gate: Option<String>,
     pub fold: Option<String>,
 }
+/// Describes a spectrum gated on a condition.  current is true
+/// if the spectrum still refers to the condition in the condition
+/// dictionary.  See spectra::SpectrumGate::is_current.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GateApplication {
+    pub spectrum: String,
+    pub type_name: String,
+    pub current: bool,
+}
+/// Counts kept for each condition as events are histogrammed.
+/// checked is the number of events for which the condition had to be
+/// evaluated (e.g. to increment a spectrum gated on it) and accepted
+/// the number of those for which it was true.  Only conditions that
+/// cache their values are counted; True, False and disabled
+/// conditions are not.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GateStatistics {
+    pub checked: u64,
+    pub accepted: u64,
+}
+/// Describes a temporary spectrum, e.g. a projection made for a
+/// quick look.  Temporary spectra are not saved in sessions.  If
+/// idle_limit is set, the spectrum is deleted once it has been idle
+/// for that long.  A spectrum is idle when no one fetches its contents,
+/// channels or statistics; idle is how long that has been.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemporarySpectrum {
+    pub name: String,
+    pub idle_limit: Option<Duration>,
+    pub idle: Duration,
+}
 /// xunder, yunder, xover, yover from get stats.
 ///
 pub type SpectrumStatistics = (u32, u32, u32, u32);
@@ -93,6 +126,26 @@ pub enum SpectrumRequest {
         params: Vec<String>,
         yaxis: AxisSpecification,
     },
+    CreateGammaSummary {
+        name: String,
+        params: Vec<Vec<String>>,
+        yaxis: AxisSpecification,
+    },
+    CreateStripChart {
+        name: String,
+        time: String,
+        value: String,
+        axis: AxisSpecification,
+    },
+    CreateBitmask {
+        name: String,
+        parameter: String,
+        axis: AxisSpecification,
+    },
+    CreateSnapshot1D {
+        name: String,
+        axis: AxisSpecification,
+    },
     Create2D {
         name: String,
         xparam: String,
@@ -115,6 +168,7 @@ pub enum SpectrumRequest {
     },
     Ungate(String),
     Clear(String),
+    ClearWithGateStatistics(String),
     GetContents {
         name: String,
         xlow: f64,
@@ -122,7 +176,9 @@ pub enum SpectrumRequest {
         ylow: f64,
         yhigh: f64,
     },
+    GetMultiContents(Vec<String>),
     Events(Vec<parameters::Event>),
+    WeightedEvents(Vec<parameters::Event>, f64),
     GetStats(String),
     SetContents {
         name: String,
@@ -139,12 +195,47 @@ pub enum SpectrumRequest {
         ychan: Option<i32>,
         value: f64,
     },
+    SetRoi {
+        name: String,
+        xchans: (i32, i32),
+        ychans: Option<(i32, i32)>,
+        value: f64,
+    },
     Fold {
         spectrum_name: String,
         condition_name: String,
     },
     Unfold(String),
     Is1D(String),
+    GateApplications(String),
+    IdSpan,
+    CompactIndex,
+    GateStatistics(String),
+    SetTemporary {
+        name: String,
+        temporary: bool,
+        idle_limit: Option<Duration>,
+    },
+    ListTemporary(String),
+    Select {
+        gate: String,
+        events: Vec<parameters::Event>,
+    },
+    Rebin {
+        name: String,
+        newname: String,
+        xgroup: u32,
+        ygroup: u32,
+    },
+    Combine {
+        name: String,
+        terms: Vec<(String, f64)>,
+    },
+    Copy {
+        name: String,
+        newname: String,
+        contents: bool,
+    },
 }
 
 /// Defines the replies the spectrum par tof the histogram
@@ -163,9 +254,33 @@ pub enum SpectrumReply {
     Statistics(SpectrumStatistics),   // Spectrum statistics.
     ChannelValue(f64),                // GetChan
     ChannelSet,                       // SetChan
+    ChannelsSet(usize),               // SetRoi - number of channels set.
     Folded,
     Unfolded,
     Flag(bool),
+    MultiContents(Vec<(String, SpectrumContents)>),
+    GateApplications(Vec<GateApplication>),
+    IdSpan { span: usize, allocated: usize }, // Parameter index span.
+    GateStatistics(Vec<(String, GateStatistics)>),
+    TemporarySet,
+    TemporaryListing(Vec<TemporarySpectrum>),
+    Selected(Vec<parameters::Event>), // Events that satisfy a condition.
+}
+//  Coordinate/bin conversions.  The bins on an axis include the
+//  underflow bin (0) and the overflow bin (bins - 1).  The regular
+//  bins, 1 through bins - 2, evenly divide [low, high).  These
+//  functions are the one place this is encoded; projections, file
+//  I/O, conditions from regions of interest and the REST conversion
+//  requests all use them.
+
+/// The number of regular bins on an axis, that is the bins
+/// without the under/overflow bins.
+pub fn regular_bins(a: AxisSpecification) -> u32 {
+    a.bins.saturating_sub(2)
+}
+/// The width of a regular bin on an axis.
+pub fn bin_width(a: AxisSpecification) -> f64 {
+    (a.high - a.low) / regular_bins(a).max(1) as f64
 }
 /// Convert a coordinate to a bin:
 ///
@@ -175,17 +290,27 @@ pub enum SpectrumReply {
 ///
 /// ### Returns
 ///    u32 - note that if c is beyond the axis the appropriate over/underflow
-/// bin is returned
+///    bin is returned
 ///
 pub fn coord_to_bin(c: f64, a: AxisSpecification) -> u32 {
+    let regular = regular_bins(a);
     if c < a.low {
         0
     } else if c >= a.high {
-        a.bins
+        regular + 1
     } else {
-        // The 1.0 reflects that bin 0 is underflow.
-        let result = 1.0 + (c * ((a.bins - 2) as f64) / (a.high - a.low));
-        result as u32
+        // The 1 reflects that bin 0 is underflow.  Rounding can put
+        // a coordinate on a bin edge into the neighboring bin so
+        // the result is checked against the edges bin_to_coord gives:
+
+        let bin = (1 + ((c - a.low) / bin_width(a)) as u32).clamp(1, regular.max(1));
+        if bin < regular && c >= bin_to_coord(bin + 1, a) {
+            bin + 1
+        } else if bin > 1 && c < bin_to_coord(bin, a) {
+            bin - 1
+        } else {
+            bin
+        }
     }
 }
 /// Convert a bin to a coordinate:
@@ -195,11 +320,11 @@ pub fn coord_to_bin(c: f64, a: AxisSpecification) -> u32 {
 ///    a   - Axis specification.
 ///
 /// ### Returns
-///   f64 - note that there are some special cases:
+///   f64 - the low edge of the bin.  There are some special cases:
 ///  *  bin == 0 is an underflow and translates to low-1.0
 ///  *  bin >= bins-1 - is an overflow and translates to high+1.0
-/// All others map to the range [low, high)
 ///
+/// All others map to the range [low, high)
 ///
 pub fn bin_to_coord(bin: u32, a: AxisSpecification) -> f64 {
     // Special case handling:
@@ -207,16 +332,73 @@ pub fn bin_to_coord(bin: u32, a: AxisSpecification) -> f64 {
     if bin == 0 {
         return a.low - 1.0; // Underflow
     }
-    if bin >= (a.bins - 1) {
+    if bin > regular_bins(a) {
         return a.high + 1.0; // overflow.
     }
-    // The rest maps [0, bins-2] -> [low, high]
+    // The rest maps [1, bins-2] -> [low, high)
+
+    a.low + (bin - 1) as f64 * bin_width(a)
+}
+// A listed axis (with under/overflow bins) as it would be specified
+// to create a spectrum (without them).
+
+fn regular_axis(a: AxisSpecification) -> AxisSpecification {
+    AxisSpecification {
+        low: a.low,
+        high: a.high,
+        bins: regular_bins(a),
+    }
+}
+// The axis of a spectrum rebinned by summing groups of bins.  Axes
+// are specified, as when spectra are created, without the
+// under/overflow bins.
+
+fn rebin_axis(
+    axis: Option<AxisSpecification>,
+    group: u32,
+    which: &str,
+) -> Result<Option<AxisSpecification>, String> {
+    match axis {
+        Some(a) => {
+            let bins = regular_bins(a);
+            let leftover = bins % group;
+            if leftover != 0 {
+                Err(format!(
+                    "The {} axis has {} bins which is not a multiple of {}",
+                    which, bins, group
+                ))
+            } else {
+                Ok(Some(AxisSpecification {
+                    low: a.low,
+                    high: a.high,
+                    bins: bins / group,
+                }))
+            }
+        }
+        None if group == 1 => Ok(None),
+        None => Err(format!("There is no {} axis to rebin", which)),
+    }
+}
+// The bin of a rebinned axis that holds a bin of the original axis
+// which has _regular_ regular bins grouped by _group_.  Under/overflows
+// stay under/overflows.
 
-    let b = (bin - 1) as f64;
-    let bin_range = (a.bins - 2) as f64;
+fn rebinned_bin(bin: usize, regular: usize, group: usize) -> usize {
+    if bin == 0 {
+        0
+    } else if bin > regular {
+        regular / group + 1
+    } else {
+        (bin - 1) / group + 1
+    }
+}
+// What's kept for each temporary spectrum:
 
-    b * (a.high - a.low) / bin_range // Simple linear scaling.
+struct Temporary {
+    idle_limit: Option<Duration>,
+    last_used: Instant,
 }
+
 ///  
 ///
 /// SpectrumProcessor is the struct that processes
@@ -226,6 +408,8 @@ pub fn bin_to_coord(bin: u32, a: AxisSpecification) -> f64 {
 /// actual message.  This makes testing the impl easier.
 pub struct SpectrumProcessor {
     dict: spectra::SpectrumStorage,
+    gate_statistics: HashMap<String, GateStatistics>,
+    temporary: HashMap<String, Temporary>,
 }
 
 impl SpectrumProcessor {
@@ -391,6 +575,121 @@ impl SpectrumProcessor {
             SpectrumReply::Error(format!("Spectrum {} already exists", name))
         }
     }
+    // Make a gamma summary spectrum - each x channel has its own
+    // list of parameters:
+
+    fn make_gamma_summary(
+        &mut self,
+        name: &str,
+        params: &[Vec<String>],
+        yaxis: &AxisSpecification,
+        pdict: &parameters::ParameterDictionary,
+        tracedb: &trace::SharedTraceStore,
+    ) -> SpectrumReply {
+        if !self.dict.exists(name) {
+            match spectra::GammaSummary::new(
+                name,
+                params.to_owned(),
+                pdict,
+                Some(yaxis.low),
+                Some(yaxis.high),
+                Some(yaxis.bins),
+            ) {
+                Ok(spec) => {
+                    self.dict.add(Rc::new(RefCell::new(spec)));
+                    tracedb.add_event(trace::TraceEvent::SpectrumCreated(String::from(name)));
+                    SpectrumReply::Created
+                }
+                Err(msg) => SpectrumReply::Error(msg),
+            }
+        } else {
+            SpectrumReply::Error(format!("Spectrum {} already exists", name))
+        }
+    }
+    // Make a strip chart spectrum - the value parameter is summed
+    // in the channels of the time parameter:
+
+    fn make_stripchart(
+        &mut self,
+        name: &str,
+        time: &str,
+        value: &str,
+        axis: &AxisSpecification,
+        pdict: &parameters::ParameterDictionary,
+        tracedb: &trace::SharedTraceStore,
+    ) -> SpectrumReply {
+        if !self.dict.exists(name) {
+            match spectra::StripChart::new(
+                name,
+                time,
+                value,
+                pdict,
+                Some(axis.low),
+                Some(axis.high),
+                Some(axis.bins),
+            ) {
+                Ok(spec) => {
+                    self.dict.add(Rc::new(RefCell::new(spec)));
+                    tracedb.add_event(trace::TraceEvent::SpectrumCreated(String::from(name)));
+                    SpectrumReply::Created
+                }
+                Err(msg) => SpectrumReply::Error(msg),
+            }
+        } else {
+            SpectrumReply::Error(format!("Spectrum {} already exists", name))
+        }
+    }
+    // Make a bit mask spectrum:
+
+    fn make_bitmask(
+        &mut self,
+        name: &str,
+        parameter: &str,
+        axis: &AxisSpecification,
+        pdict: &parameters::ParameterDictionary,
+        tracedb: &trace::SharedTraceStore,
+    ) -> SpectrumReply {
+        if !self.dict.exists(name) {
+            match spectra::Bitmask::new(
+                name,
+                parameter,
+                pdict,
+                Some(axis.low),
+                Some(axis.high),
+                Some(axis.bins),
+            ) {
+                Ok(spec) => {
+                    self.dict.add(Rc::new(RefCell::new(spec)));
+                    tracedb.add_event(trace::TraceEvent::SpectrumCreated(String::from(name)));
+                    SpectrumReply::Created
+                }
+                Err(msg) => SpectrumReply::Error(msg),
+            }
+        } else {
+            SpectrumReply::Error(format!("Spectrum {} already exists", name))
+        }
+    }
+    // Make a parameterless 1d snapshot spectrum:
+
+    fn make_snapshot1d(
+        &mut self,
+        name: &str,
+        axis: &AxisSpecification,
+        tracedb: &trace::SharedTraceStore,
+    ) -> SpectrumReply {
+        if !self.dict.exists(name) {
+            match spectra::Snapshot1D::new(name, axis.low, axis.high, axis.bins) {
+                Ok(spec) => {
+                    self.dict.add(Rc::new(RefCell::new(spec)));
+                    tracedb.add_event(trace::TraceEvent::SpectrumCreated(String::from(name)));
+                    SpectrumReply::Created
+                }
+                Err(msg) => SpectrumReply::Error(msg),
+            }
+        } else {
+            SpectrumReply::Error(format!("Spectrum {} already exists", name))
+        }
+    }
     // Make 2-d spectrum.
 
     fn make_2d(
@@ -476,6 +775,7 @@ impl SpectrumProcessor {
 
     fn delete_spectrum(&mut self, name: &str, tracedb: &trace::SharedTraceStore) -> SpectrumReply {
         if self.dict.remove(name).is_some() {
+            self.temporary.remove(name);
             tracedb.add_event(trace::TraceEvent::SpectrumDeleted(String::from(name)));
             SpectrumReply::Deleted
         } else {
@@ -561,6 +861,70 @@ impl SpectrumProcessor {
         }
         SpectrumReply::Cleared
     }
+    // Clear spectra and, in the same transaction, the statistics of
+    // the conditions that gate them so that rates and efficiencies
+    // computed from the two stay consistent.
+
+    fn clear_with_gate_statistics(&mut self, pattern: &str) -> SpectrumReply {
+        let pat = match Pattern::new(pattern) {
+            Ok(p) => p,
+            Err(e) => return SpectrumReply::Error(format!("Bad glob pattern: {}", e.msg)),
+        };
+        for (name, s) in self.dict.iter() {
+            if pat.matches(name) {
+                let mut spectrum = s.0.borrow_mut();
+                spectrum.clear();
+                if let Some(gate) = spectrum.get_gate() {
+                    self.gate_statistics.remove(&gate);
+                }
+            }
+        }
+        SpectrumReply::Cleared
+    }
+    // Count the conditions that were evaluated for an event.
+    // Conditions that were not needed have no cached value.
+
+    fn tally_gates(&mut self, cdict: &conditions::ConditionDictionary) {
+        for (name, c) in cdict.iter() {
+            if let Some(value) = c.borrow().get_cached_value() {
+                if !self.gate_statistics.contains_key(name) {
+                    self.gate_statistics
+                        .insert(name.clone(), GateStatistics::default());
+                }
+                let stats = self.gate_statistics.get_mut(name).unwrap();
+                stats.checked += 1;
+                if value {
+                    stats.accepted += 1;
+                }
+            }
+        }
+    }
+    // Statistics for the conditions whose names match a pattern,
+    // sorted by name.  Conditions that were never evaluated have zero
+    // counts.
+
+    fn get_gate_statistics(
+        &self,
+        pattern: &str,
+        cdict: &conditions::ConditionDictionary,
+    ) -> SpectrumReply {
+        let pat = match Pattern::new(pattern) {
+            Ok(p) => p,
+            Err(e) => return SpectrumReply::Error(format!("Bad glob pattern: {}", e.msg)),
+        };
+        let mut result: Vec<(String, GateStatistics)> = cdict
+            .keys()
+            .filter(|name| pat.matches(name))
+            .map(|name| {
+                (
+                    name.clone(),
+                    self.gate_statistics.get(name).copied().unwrap_or_default(),
+                )
+            })
+            .collect();
+        result.sort_by(|a, b| a.0.cmp(&b.0));
+        SpectrumReply::GateStatistics(result)
+    }
     fn get_contents(
         &self,
         name: &str,
@@ -666,18 +1030,85 @@ impl SpectrumProcessor {
             SpectrumReply::Error(format!("Spectrum {} does not exist", name))
         }
     }
+    // Get the full contents of several spectra.  Since this is done
+    // in a single request, no events are processed between fetching
+    // the contents of one spectrum and the next.  If any spectrum
+    // does not exist, the whole request fails.
+
+    fn get_multi_contents(&self, names: &[String]) -> SpectrumReply {
+        let mut result = Vec::<(String, SpectrumContents)>::new();
+        for name in names {
+            match self.get_contents(name, f64::MIN, f64::MAX, f64::MIN, f64::MAX) {
+                SpectrumReply::Contents(c) => result.push((name.clone(), c)),
+                SpectrumReply::Error(s) => return SpectrumReply::Error(s),
+                _ => {
+                    return SpectrumReply::Error(String::from("Unexpected reply from get_contents"))
+                }
+            }
+        }
+        SpectrumReply::MultiContents(result)
+    }
+    // Parameter calibrations are applied to the events and then calibrated
+    // and pseudo parameters are computed before they are histogrammed.
+    // With no calibrations, calibrated parameters or pseudos the events
+    // are processed as is.  Each event increments its spectra by weight.
     fn process_events(
         &mut self,
         events: &[parameters::Event],
+        weight: f64,
+        pdict: &parameters::ParameterDictionary,
         cdict: &mut conditions::ConditionDictionary,
     ) -> SpectrumReply {
+        let calibrations = pdict.calibrations();
+        let calibrated = pdict.calibrated();
+        let pseudos = pdict.pseudos();
         for e in events.iter() {
-            
             conditions::invalidate_cache(cdict);
-            self.dict.process_event(e);
+            if calibrations.is_empty() && calibrated.is_empty() && pseudos.is_empty() {
+                self.dict.process_weighted_event(e, weight);
+            } else {
+                let mut event = parameters::calibrate_event(e, &calibrations);
+                calibrated.compute(&mut event);
+                pseudos.compute(&mut event);
+                self.dict.process_weighted_event(&event, weight);
+            }
+            self.tally_gates(cdict);
         }
         SpectrumReply::Processed
     }
+    // Select the events that satisfy a condition.  As when they are
+    // histogrammed, calibrations are applied to the events and calibrated
+    // and pseudo parameters computed, and the selected events are returned that
+    // way.  A condition that does not exist is false so it selects
+    // nothing.
+    fn select_events(
+        &self,
+        gate: &str,
+        events: &[parameters::Event],
+        pdict: &parameters::ParameterDictionary,
+        cdict: &mut conditions::ConditionDictionary,
+    ) -> SpectrumReply {
+        let condition = match cdict.get(gate) {
+            Some(c) => c.clone(),
+            None => return SpectrumReply::Selected(vec![]),
+        };
+        let calibrations = pdict.calibrations();
+        let calibrated = pdict.calibrated();
+        let pseudos = pdict.pseudos();
+        let mut flat = parameters::FlatEvent::new();
+        let mut result = Vec::new();
+        for e in events.iter() {
+            conditions::invalidate_cache(cdict);
+            let mut event = parameters::calibrate_event(e, &calibrations);
+            calibrated.compute(&mut event);
+            pseudos.compute(&mut event);
+            flat.load_event(&event);
+            if condition.borrow_mut().check(&flat) {
+                result.push(event);
+            }
+        }
+        SpectrumReply::Selected(result)
+    }
     // Get spectrumstatistics:
     fn get_statistics(&self, name: &str) -> SpectrumReply {
         if let Some(spec) = self.dict.get(name) {
@@ -858,6 +1289,72 @@ impl SpectrumProcessor {
             SpectrumReply::Error(format!("No such spectrum: {}", name))
         }
     }
+    // Set all channels in a rectangular region of interest to a value.
+    // The channel ranges are inclusive and numbered as for SetChan.
+    // The whole region is range checked before anything is set so
+    // that a bad region leaves the spectrum unmodified.
+
+    fn set_roi(
+        &mut self,
+        name: &str,
+        xchans: (i32, i32),
+        ychans: Option<(i32, i32)>,
+        value: f64,
+    ) -> SpectrumReply {
+        let spec = if let Some(spec) = self.dict.get(name) {
+            spec
+        } else {
+            return SpectrumReply::Error(format!("No such spectrum: {}", name));
+        };
+        if xchans.0 > xchans.1 {
+            return SpectrumReply::Error(String::from("X channel range is empty"));
+        }
+        if xchans.0 < -1 {
+            return SpectrumReply::Error(String::from("X index is out of range"));
+        }
+        if spec.0.borrow().is_1d() {
+            let h = spec.0.borrow().get_histogram_1d().unwrap();
+            let mut h = h.borrow_mut();
+            let first = (xchans.0 + 1) as usize;
+            let last = (xchans.1 + 1) as usize;
+            if h.value_at_index(last).is_none() {
+                return SpectrumReply::Error(String::from("X index is out of range"));
+            }
+            for index in first..=last {
+                if let Some(c) = h.value_at_index_mut(index) {
+                    c.fill_with(value);
+                }
+            }
+            SpectrumReply::ChannelsSet(last - first + 1)
+        } else {
+            let ychans = if let Some(y) = ychans {
+                y
+            } else {
+                return SpectrumReply::Error(String::from("2d spectra need a y bin range"));
+            };
+            if ychans.0 > ychans.1 {
+                return SpectrumReply::Error(String::from("Y channel range is empty"));
+            }
+            if ychans.0 < -1 {
+                return SpectrumReply::Error(String::from("Y index is out of range"));
+            }
+            let h = spec.0.borrow().get_histogram_2d().unwrap();
+            if let Err(s) = Self::channels2d_to_index(&h, xchans.1, ychans.1) {
+                return SpectrumReply::Error(s);
+            }
+            let mut count = 0;
+            for ybin in ychans.0..=ychans.1 {
+                for xbin in xchans.0..=xchans.1 {
+                    let index = Self::channels2d_to_index(&h, xbin, ybin).unwrap();
+                    if let Some(c) = h.borrow_mut().value_at_index_mut(index) {
+                        c.fill_with(value);
+                        count += 1;
+                    }
+                }
+            }
+            SpectrumReply::ChannelsSet(count)
+        }
+    }
     // Fold a spectrum given a condition  name and a condition name:
 
     fn fold_spectrum(
@@ -865,11 +1362,16 @@ impl SpectrumProcessor {
         spectrum: &str,
         condition: &str,
         cdict: &conditions::ConditionDictionary,
+        tracedb: &trace::SharedTraceStore,
     ) -> SpectrumReply {
         if let Some(s) = self.dict.get(spectrum) {
             if let Err(s) = s.0.borrow_mut().fold(condition, cdict) {
                 SpectrumReply::Error(format!("Failed to fold {}: {}", spectrum, s))
             } else {
+                tracedb.add_event(trace::TraceEvent::FoldApplied {
+                    spectrum: String::from(spectrum),
+                    condition: String::from(condition),
+                });
                 SpectrumReply::Folded
             }
         } else {
@@ -878,11 +1380,16 @@ impl SpectrumProcessor {
     }
     // Unfold a spectrum:
 
-    fn unfold_spectrum(&mut self, spectrum: &str) -> SpectrumReply {
+    fn unfold_spectrum(
+        &mut self,
+        spectrum: &str,
+        tracedb: &trace::SharedTraceStore,
+    ) -> SpectrumReply {
         if let Some(s) = self.dict.get(spectrum) {
             if let Err(s) = s.0.borrow_mut().unfold() {
                 SpectrumReply::Error(format!("Failed to unfold spectrum {}: {}", spectrum, s))
             } else {
+                tracedb.add_event(trace::TraceEvent::FoldRemoved(String::from(spectrum)));
                 SpectrumReply::Unfolded
             }
         } else {
@@ -899,26 +1406,444 @@ impl SpectrumProcessor {
         }
     }
 
-    // Public methods
-    /// Construction
+    // List the spectra gated on a condition and whether each
+    // still refers to the condition in the dictionary.  The list
+    // is sorted by spectrum name:
 
-    pub fn new() -> SpectrumProcessor {
-        SpectrumProcessor {
-            dict: spectra::SpectrumStorage::new(),
+    fn gate_applications(
+        &self,
+        condition: &str,
+        cdict: &conditions::ConditionDictionary,
+    ) -> SpectrumReply {
+        let mut result = Vec::<GateApplication>::new();
+        for (name, spec) in self.dict.iter() {
+            let s = spec.0.borrow();
+            if s.get_gate().as_deref() == Some(condition) {
+                result.push(GateApplication {
+                    spectrum: name.clone(),
+                    type_name: s.get_type(),
+                    current: s.gate_is_current(cdict).unwrap_or(false),
+                });
+            }
         }
+        result.sort_by(|a, b| a.spectrum.cmp(&b.spectrum));
+        SpectrumReply::GateApplications(result)
     }
-    /// Process requests returning replies:
+    // Describe the span of the parameter index used to find the
+    // spectra an event increments:
 
-    pub fn process_request(
+    fn id_span(&self) -> SpectrumReply {
+        SpectrumReply::IdSpan {
+            span: self.dict.id_span(),
+            allocated: self.dict.id_allocated(),
+        }
+    }
+    // Compact the parameter index, describing the result:
+
+    fn compact_index(&mut self) -> SpectrumReply {
+        self.dict.compact();
+        self.id_span()
+    }
+
+    // Mark a spectrum temporary or not.  Marking it (again) restarts
+    // its idle time:
+
+    fn set_temporary(
         &mut self,
-        req: SpectrumRequest,
+        name: &str,
+        temporary: bool,
+        idle_limit: Option<Duration>,
+    ) -> SpectrumReply {
+        if !self.dict.exists(name) {
+            return SpectrumReply::Error(format!("Spectrum {} does not exist", name));
+        }
+        if temporary {
+            self.temporary.insert(
+                String::from(name),
+                Temporary {
+                    idle_limit,
+                    last_used: Instant::now(),
+                },
+            );
+        } else {
+            self.temporary.remove(name);
+        }
+        SpectrumReply::TemporarySet
+    }
+    // List the temporary spectra whose names match a pattern, sorted
+    // by name:
+
+    fn list_temporary(&self, pattern: &str) -> SpectrumReply {
+        let pat = match Pattern::new(pattern) {
+            Ok(p) => p,
+            Err(e) => return SpectrumReply::Error(format!("Bad glob pattern: {}", e.msg)),
+        };
+        let mut result: Vec<TemporarySpectrum> = self
+            .temporary
+            .iter()
+            .filter(|(name, _)| pat.matches(name))
+            .map(|(name, t)| TemporarySpectrum {
+                name: name.clone(),
+                idle_limit: t.idle_limit,
+                idle: t.last_used.elapsed(),
+            })
+            .collect();
+        result.sort_by(|a, b| a.name.cmp(&b.name));
+        SpectrumReply::TemporaryListing(result)
+    }
+    // Make a spectrum with the type and parameters described by props
+    // but the axes xaxis, yaxis (without under/overflow bins):
+
+    fn make_like(
+        &mut self,
+        name: &str,
+        props: &SpectrumProperties,
+        xaxis: Option<AxisSpecification>,
+        yaxis: Option<AxisSpecification>,
         pdict: &parameters::ParameterDictionary,
-        cdict: &mut conditions::ConditionDictionary,
         tracedb: &trace::SharedTraceStore,
     ) -> SpectrumReply {
-        match req {
-            SpectrumRequest::Create1D {
-                name,
+        match (props.type_name.as_str(), xaxis, yaxis) {
+            ("1D", Some(x), None) => self.make_1d(name, &props.xparams[0], &x, pdict, tracedb),
+            ("Multi1d", Some(x), None) => {
+                self.make_multi1d(name, &props.xparams, &x, pdict, tracedb)
+            }
+            ("Multi2d", Some(x), Some(y)) => {
+                self.make_multi2d(name, &props.xparams, &x, &y, pdict, tracedb)
+            }
+            ("PGamma", Some(x), Some(y)) => {
+                self.make_pgamma(name, &props.xparams, &props.yparams, &x, &y, pdict, tracedb)
+            }
+            ("Summary", Some(_), Some(y)) => {
+                self.make_summary(name, &props.xparams, &y, pdict, tracedb)
+            }
+            ("GammaSummary", Some(_), Some(y)) => {
+                let params: Vec<Vec<String>> = props
+                    .xparams
+                    .iter()
+                    .map(|l| l.split_whitespace().map(String::from).collect())
+                    .collect();
+                self.make_gamma_summary(name, &params, &y, pdict, tracedb)
+            }
+            ("Bitmask", Some(x), None) => {
+                self.make_bitmask(name, &props.xparams[0], &x, pdict, tracedb)
+            }
+            ("Snapshot1D", Some(x), None) => self.make_snapshot1d(name, &x, tracedb),
+            ("StripChart", Some(x), None) => self.make_stripchart(
+                name,
+                &props.xparams[0],
+                &props.yparams[0],
+                &x,
+                pdict,
+                tracedb,
+            ),
+            ("2D", Some(x), Some(y)) => self.make_2d(
+                name,
+                &props.xparams[0],
+                &props.yparams[0],
+                &x,
+                &y,
+                pdict,
+                tracedb,
+            ),
+            ("2DSum", Some(x), Some(y)) => {
+                self.make_2dsum(name, &props.xparams, &props.yparams, &x, &y, pdict, tracedb)
+            }
+            (t, _, _) => {
+                SpectrumReply::Error(format!("Can't make {} spectra from {}", t, props.name))
+            }
+        }
+    }
+    // Give the new spectrum name the gate and fold described by props.
+    // If that fails, the new spectrum is deleted.
+
+    fn apply_gating(
+        &mut self,
+        name: &str,
+        props: &SpectrumProperties,
+        cdict: &conditions::ConditionDictionary,
+        tracedb: &trace::SharedTraceStore,
+    ) -> SpectrumReply {
+        let mut reply = SpectrumReply::Created;
+        if let Some(gate) = &props.gate {
+            if let SpectrumReply::Error(s) = self.gate_spectrum(name, gate, cdict) {
+                reply = SpectrumReply::Error(s);
+            }
+        }
+        if let (Some(fold), SpectrumReply::Created) = (&props.fold, &reply) {
+            if let SpectrumReply::Error(s) = self.fold_spectrum(name, fold, cdict, tracedb) {
+                reply = SpectrumReply::Error(s);
+            }
+        }
+        if let SpectrumReply::Error(_) = reply {
+            self.delete_spectrum(name, tracedb);
+        }
+        reply
+    }
+    // Make newname a copy of the definition of name with the same
+    // gate and fold.  If contents is true, the counts in name are
+    // copied as well.
+
+    fn copy_spectrum(
+        &mut self,
+        name: &str,
+        newname: &str,
+        contents: bool,
+        pdict: &parameters::ParameterDictionary,
+        cdict: &conditions::ConditionDictionary,
+        tracedb: &trace::SharedTraceStore,
+    ) -> SpectrumReply {
+        let props = match self.dict.get(name) {
+            Some(s) => Self::get_properties(&(s.0.clone(), s.1)),
+            None => return SpectrumReply::Error(format!("Spectrum {} does not exist", name)),
+        };
+        let reply = self.make_like(
+            newname,
+            &props,
+            props.xaxis.map(regular_axis),
+            props.yaxis.map(regular_axis),
+            pdict,
+            tracedb,
+        );
+        if let SpectrumReply::Error(_) = reply {
+            return reply;
+        }
+        let reply = self.apply_gating(newname, &props, cdict, tracedb);
+        if let (SpectrumReply::Created, true) = (&reply, contents) {
+            self.add_bins(name, newname, 1.0);
+        }
+        reply
+    }
+    // Make newname a copy of name in which groups of xgroup x bins
+    // and ygroup y bins are summed into one.  The copy has the type,
+    // parameters, gate and fold of the original so it continues to
+    // increment as the original would.  If the copy can't be fully
+    // made, it's deleted.
+
+    #[allow(clippy::too_many_arguments)]
+    fn rebin_spectrum(
+        &mut self,
+        name: &str,
+        newname: &str,
+        xgroup: u32,
+        ygroup: u32,
+        pdict: &parameters::ParameterDictionary,
+        cdict: &conditions::ConditionDictionary,
+        tracedb: &trace::SharedTraceStore,
+    ) -> SpectrumReply {
+        let props = match self.dict.get(name) {
+            Some(s) => Self::get_properties(&(s.0.clone(), s.1)),
+            None => return SpectrumReply::Error(format!("Spectrum {} does not exist", name)),
+        };
+        if xgroup == 0 || ygroup == 0 {
+            return SpectrumReply::Error(String::from("Bins must be grouped by at least 1"));
+        }
+        if xgroup == 1 && ygroup == 1 {
+            return SpectrumReply::Error(String::from("At least one axis must be rebinned"));
+        }
+        if (props.type_name == "Summary" || props.type_name == "GammaSummary") && xgroup != 1 {
+            return SpectrumReply::Error(String::from(
+                "The x axis of a summary spectrum can't be rebinned",
+            ));
+        }
+        let xaxis = match rebin_axis(props.xaxis, xgroup, "x") {
+            Ok(a) => a,
+            Err(s) => return SpectrumReply::Error(s),
+        };
+        let yaxis = match rebin_axis(props.yaxis, ygroup, "y") {
+            Ok(a) => a,
+            Err(s) => return SpectrumReply::Error(s),
+        };
+        let reply = self.make_like(newname, &props, xaxis, yaxis, pdict, tracedb);
+        if let SpectrumReply::Error(_) = reply {
+            return reply;
+        }
+        let reply = self.apply_gating(newname, &props, cdict, tracedb);
+        if let SpectrumReply::Error(_) = reply {
+            return reply;
+        }
+        self.sum_bins(name, newname, xgroup, ygroup);
+        reply
+    }
+    // Add the counts of the bins of the spectrum name into the bins
+    // they're grouped into in the rebinned spectrum newname:
+
+    fn sum_bins(&self, name: &str, newname: &str, xgroup: u32, ygroup: u32) {
+        let source = self.dict.get(name).unwrap().0.borrow();
+        let target = self.dict.get(newname).unwrap().0.borrow();
+        if let (Some(from), Some(to)) = (source.get_histogram_1d(), target.get_histogram_1d()) {
+            let regular = from.borrow().axes().as_tuple().0.num_bins() - 2;
+            let mut to = to.borrow_mut();
+            for c in from.borrow().iter() {
+                let v = c.value.get();
+                if v != 0.0 {
+                    let bin = rebinned_bin(c.index, regular, xgroup as usize);
+                    to.value_at_index_mut(bin).unwrap().fill_with(v);
+                }
+            }
+        } else if let (Some(from), Some(to)) =
+            (source.get_histogram_2d(), target.get_histogram_2d())
+        {
+            let (xbins, ybins) = {
+                let axes = from.borrow().axes().as_tuple().clone();
+                (axes.0.num_bins(), axes.1.num_bins())
+            };
+            let to_xbins = to.borrow().axes().as_tuple().0.num_bins();
+            let mut to = to.borrow_mut();
+            for c in from.borrow().iter() {
+                let v = c.value.get();
+                if v != 0.0 {
+                    let x = rebinned_bin(c.index % xbins, xbins - 2, xgroup as usize);
+                    let y = rebinned_bin(c.index / xbins, ybins - 2, ygroup as usize);
+                    to.value_at_index_mut(x + y * to_xbins)
+                        .unwrap()
+                        .fill_with(v);
+                }
+            }
+        }
+    }
+    // Make name a spectrum whose bins are the sum of the bins of the
+    // spectra in terms, each multiplied by its scale factor.  All
+    // spectra must have the same type and axes; name gets the type,
+    // parameters and axes of the first.  Under/overflows are combined
+    // as well.  The new spectrum is not gated; it's up to the caller
+    // to keep it from incrementing if that's wanted.
+
+    fn combine_spectra(
+        &mut self,
+        name: &str,
+        terms: &[(String, f64)],
+        pdict: &parameters::ParameterDictionary,
+        tracedb: &trace::SharedTraceStore,
+    ) -> SpectrumReply {
+        let mut properties = Vec::new();
+        for (spectrum, _) in terms {
+            match self.dict.get(spectrum) {
+                Some(s) => properties.push(Self::get_properties(&(s.0.clone(), s.1))),
+                None => {
+                    return SpectrumReply::Error(format!("Spectrum {} does not exist", spectrum))
+                }
+            }
+        }
+        let first = match properties.first() {
+            Some(p) => p,
+            None => return SpectrumReply::Error(String::from("There are no spectra to combine")),
+        };
+        for other in properties.iter().skip(1) {
+            if other.type_name != first.type_name
+                || other.xaxis != first.xaxis
+                || other.yaxis != first.yaxis
+            {
+                return SpectrumReply::Error(format!(
+                    "{} and {} do not have the same type and axes",
+                    first.name, other.name
+                ));
+            }
+        }
+        let reply = self.make_like(
+            name,
+            first,
+            first.xaxis.map(regular_axis),
+            first.yaxis.map(regular_axis),
+            pdict,
+            tracedb,
+        );
+        if let SpectrumReply::Error(_) = reply {
+            return reply;
+        }
+        for (spectrum, scale) in terms {
+            self.add_bins(spectrum, name, *scale);
+        }
+        SpectrumReply::Created
+    }
+    // Add the bins of the spectrum name, multiplied by scale, to the
+    // bins of the spectrum to which has the same axes:
+
+    fn add_bins(&self, name: &str, to: &str, scale: f64) {
+        let source = self.dict.get(name).unwrap().0.borrow();
+        let target = self.dict.get(to).unwrap().0.borrow();
+        if let (Some(from), Some(to)) = (source.get_histogram_1d(), target.get_histogram_1d()) {
+            let mut to = to.borrow_mut();
+            for c in from.borrow().iter() {
+                let v = c.value.get();
+                if v != 0.0 {
+                    to.value_at_index_mut(c.index).unwrap().fill_with(v * scale);
+                }
+            }
+        } else if let (Some(from), Some(to)) =
+            (source.get_histogram_2d(), target.get_histogram_2d())
+        {
+            let mut to = to.borrow_mut();
+            for c in from.borrow().iter() {
+                let v = c.value.get();
+                if v != 0.0 {
+                    to.value_at_index_mut(c.index).unwrap().fill_with(v * scale);
+                }
+            }
+        }
+    }
+    // Fetching the contents, channels or statistics of a spectrum
+    // uses it:
+
+    fn mark_used(&mut self, req: &SpectrumRequest) {
+        if self.temporary.is_empty() {
+            return;
+        }
+        let names: Vec<&String> = match req {
+            SpectrumRequest::GetContents { name, .. }
+            | SpectrumRequest::GetChan { name, .. }
+            | SpectrumRequest::GetStats(name) => vec![name],
+            SpectrumRequest::GetMultiContents(names) => names.iter().collect(),
+            _ => vec![],
+        };
+        for name in names {
+            if let Some(t) = self.temporary.get_mut(name) {
+                t.last_used = Instant::now();
+            }
+        }
+    }
+    // Delete the temporary spectra that have been idle longer than
+    // their limits:
+
+    fn delete_idle(&mut self, tracedb: &trace::SharedTraceStore) {
+        let idle: Vec<String> = self
+            .temporary
+            .iter()
+            .filter(|(_, t)| matches!(t.idle_limit, Some(limit) if t.last_used.elapsed() > limit))
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in idle {
+            self.delete_spectrum(&name, tracedb);
+        }
+    }
+
+    // Public methods
+    /// Construction
+
+    pub fn new() -> SpectrumProcessor {
+        SpectrumProcessor {
+            dict: spectra::SpectrumStorage::new(),
+            gate_statistics: HashMap::new(),
+            temporary: HashMap::new(),
+        }
+    }
+    /// Process requests returning replies:
+
+    pub fn process_request(
+        &mut self,
+        req: SpectrumRequest,
+        pdict: &parameters::ParameterDictionary,
+        cdict: &mut conditions::ConditionDictionary,
+        tracedb: &trace::SharedTraceStore,
+    ) -> SpectrumReply {
+        // Temporary spectra are only deleted for idleness as requests
+        // arrive, after any use by this request is noted:
+
+        self.mark_used(&req);
+        self.delete_idle(tracedb);
+        match req {
+            SpectrumRequest::Create1D {
+                name,
                 parameter,
                 axis,
             } => self.make_1d(&name, &parameter, &axis, pdict, tracedb),
@@ -943,6 +1868,25 @@ impl SpectrumProcessor {
                 params,
                 yaxis,
             } => self.make_summary(&name, &params, &yaxis, pdict, tracedb),
+            SpectrumRequest::CreateGammaSummary {
+                name,
+                params,
+                yaxis,
+            } => self.make_gamma_summary(&name, &params, &yaxis, pdict, tracedb),
+            SpectrumRequest::CreateStripChart {
+                name,
+                time,
+                value,
+                axis,
+            } => self.make_stripchart(&name, &time, &value, &axis, pdict, tracedb),
+            SpectrumRequest::CreateBitmask {
+                name,
+                parameter,
+                axis,
+            } => self.make_bitmask(&name, &parameter, &axis, pdict, tracedb),
+            SpectrumRequest::CreateSnapshot1D { name, axis } => {
+                self.make_snapshot1d(&name, &axis, tracedb)
+            }
             SpectrumRequest::Create2D {
                 name,
                 xparam,
@@ -962,6 +1906,9 @@ impl SpectrumProcessor {
             SpectrumRequest::Gate { spectrum, gate } => self.gate_spectrum(&spectrum, &gate, cdict),
             SpectrumRequest::Ungate(name) => self.ungate_spectrum(&name),
             SpectrumRequest::Clear(pattern) => self.clear_spectra(&pattern),
+            SpectrumRequest::ClearWithGateStatistics(pattern) => {
+                self.clear_with_gate_statistics(&pattern)
+            }
             SpectrumRequest::GetContents {
                 name,
                 xlow,
@@ -969,7 +1916,11 @@ impl SpectrumProcessor {
                 ylow,
                 yhigh,
             } => self.get_contents(&name, xlow, xhigh, ylow, yhigh),
-            SpectrumRequest::Events(events) => self.process_events(&events, cdict),
+            SpectrumRequest::GetMultiContents(names) => self.get_multi_contents(&names),
+            SpectrumRequest::Events(events) => self.process_events(&events, 1.0, pdict, cdict),
+            SpectrumRequest::WeightedEvents(events, weight) => {
+                self.process_events(&events, weight, pdict, cdict)
+            }
             SpectrumRequest::GetStats(name) => self.get_statistics(&name),
             SpectrumRequest::SetContents { name, contents } => self.set_contents(&name, &contents),
             SpectrumRequest::GetChan { name, xchan, ychan } => {
@@ -981,12 +1932,47 @@ impl SpectrumProcessor {
                 ychan,
                 value,
             } => self.set_channel_value(&name, xchan, ychan, value),
+            SpectrumRequest::SetRoi {
+                name,
+                xchans,
+                ychans,
+                value,
+            } => self.set_roi(&name, xchans, ychans, value),
             SpectrumRequest::Fold {
                 spectrum_name,
                 condition_name,
-            } => self.fold_spectrum(&spectrum_name, &condition_name, cdict),
-            SpectrumRequest::Unfold(spectrum) => self.unfold_spectrum(&spectrum),
+            } => self.fold_spectrum(&spectrum_name, &condition_name, cdict, tracedb),
+            SpectrumRequest::Unfold(spectrum) => self.unfold_spectrum(&spectrum, tracedb),
             SpectrumRequest::Is1D(spectrum) => self.is_1d(&spectrum),
+            SpectrumRequest::GateApplications(condition) => {
+                self.gate_applications(&condition, cdict)
+            }
+            SpectrumRequest::IdSpan => self.id_span(),
+            SpectrumRequest::CompactIndex => self.compact_index(),
+            SpectrumRequest::GateStatistics(pattern) => self.get_gate_statistics(&pattern, cdict),
+            SpectrumRequest::SetTemporary {
+                name,
+                temporary,
+                idle_limit,
+            } => self.set_temporary(&name, temporary, idle_limit),
+            SpectrumRequest::ListTemporary(pattern) => self.list_temporary(&pattern),
+            SpectrumRequest::Select { gate, events } => {
+                self.select_events(&gate, &events, pdict, cdict)
+            }
+            SpectrumRequest::Rebin {
+                name,
+                newname,
+                xgroup,
+                ygroup,
+            } => self.rebin_spectrum(&name, &newname, xgroup, ygroup, pdict, cdict, tracedb),
+            SpectrumRequest::Combine { name, terms } => {
+                self.combine_spectra(&name, &terms, pdict, tracedb)
+            }
+            SpectrumRequest::Copy {
+                name,
+                newname,
+                contents,
+            } => self.copy_spectrum(&name, &newname, contents, pdict, cdict, tracedb),
         }
     }
 }
@@ -1006,6 +1992,10 @@ pub type SpectrumServerListingResult = Result<Vec<SpectrumProperties>, String>;
 /// This type is a result the API will sue to return spectrum
 /// contents:
 pub type SpectrumServerContentsResult = Result<SpectrumContents, String>;
+///
+/// Result the API uses to return the contents of several spectra
+/// as name/contents pairs:
+pub type SpectrumServerMultiContentsResult = Result<Vec<(String, SpectrumContents)>, String>;
 
 /// Result for spectrum statistics request:
 
@@ -1015,10 +2005,32 @@ pub type SpectrumServerStatisticsResult = Result<SpectrumStatistics, String>;
 
 pub type SpectrumChannelResult = Result<f64, String>;
 
+/// Result from SetRoi - the number of channels set:
+
+pub type SpectrumChannelCountResult = Result<usize, String>;
+
 // Results for abool:
 
 pub type SpectrumFlagResult = Result<bool, String>;
 
+// Result for the spectra gated on a condition:
+
+pub type SpectrumGateApplicationsResult = Result<Vec<GateApplication>, String>;
+
+// Result for condition statistics; (condition name, statistics):
+
+pub type SpectrumGateStatisticsResult = Result<Vec<(String, GateStatistics)>, String>;
+
+/// On success, the temporary spectra that were listed.
+pub type SpectrumTemporaryListResult = Result<Vec<TemporarySpectrum>, String>;
+
+/// On success, the events selected by a condition.
+pub type SpectrumSelectResult = Result<Vec<parameters::Event>, String>;
+
+// Result for the parameter index span; (span, allocated):
+
+pub type SpectrumIdSpanResult = Result<(usize, usize), String>;
+
 ///
 /// This struct provides a container for the channel used to
 /// make server requests.  The implementation can then be simplified
@@ -1121,6 +2133,53 @@ impl SpectrumMessageClient {
             yaxis: AxisSpecification { low, high, bins },
         }
     }
+    fn creategammasummary_request(
+        name: &str,
+        params: &[Vec<String>],
+        low: f64,
+        high: f64,
+        bins: u32,
+    ) -> SpectrumRequest {
+        SpectrumRequest::CreateGammaSummary {
+            name: String::from(name),
+            params: params.to_owned(),
+            yaxis: AxisSpecification { low, high, bins },
+        }
+    }
+    fn createstripchart_request(
+        name: &str,
+        time: &str,
+        value: &str,
+        low: f64,
+        high: f64,
+        bins: u32,
+    ) -> SpectrumRequest {
+        SpectrumRequest::CreateStripChart {
+            name: String::from(name),
+            time: String::from(time),
+            value: String::from(value),
+            axis: AxisSpecification { low, high, bins },
+        }
+    }
+    fn createbitmask_request(
+        name: &str,
+        parameter: &str,
+        low: f64,
+        high: f64,
+        bins: u32,
+    ) -> SpectrumRequest {
+        SpectrumRequest::CreateBitmask {
+            name: String::from(name),
+            parameter: String::from(parameter),
+            axis: AxisSpecification { low, high, bins },
+        }
+    }
+    fn createsnapshot1d_request(name: &str, low: f64, high: f64, bins: u32) -> SpectrumRequest {
+        SpectrumRequest::CreateSnapshot1D {
+            name: String::from(name),
+            axis: AxisSpecification { low, high, bins },
+        }
+    }
     fn create2d_request(
         name: &str,
         xparam: &str,
@@ -1211,6 +2270,9 @@ impl SpectrumMessageClient {
     fn events_request(events: &[parameters::Event]) -> SpectrumRequest {
         SpectrumRequest::Events(events.to_owned())
     }
+    fn weighted_events_request(events: &[parameters::Event], weight: f64) -> SpectrumRequest {
+        SpectrumRequest::WeightedEvents(events.to_owned(), weight)
+    }
 
     fn transact(&self, req: SpectrumRequest) -> SpectrumReply {
         let (reply_send, reply_recv) = mpsc::channel::<Reply>();
@@ -1372,6 +2434,101 @@ impl SpectrumMessageClient {
             Ok(())
         }
     }
+    /// Create a gamma summary spectrum:
+    ///
+    /// *  name - name of the spectrum
+    /// *  params - the parameters of each x channel.
+    /// *  low, high, bins - axis specifications (y axis).
+    ///
+    /// Returns: SpectrumServerEmptyResult
+    pub fn create_spectrum_gamma_summary(
+        &self,
+        name: &str,
+        params: &[Vec<String>],
+        low: f64,
+        high: f64,
+        bins: u32,
+    ) -> SpectrumServerEmptyResult {
+        let reply = self.transact(Self::creategammasummary_request(
+            name, params, low, high, bins,
+        ));
+        if let SpectrumReply::Error(s) = reply {
+            Err(s)
+        } else {
+            Ok(())
+        }
+    }
+    /// Create a strip chart spectrum:
+    ///
+    /// *  name - name of the spectrum.
+    /// *  time - name of the time parameter.
+    /// *  value - name of the parameter whose values are summed.
+    /// *  low, high, bins - initial time axis specification.
+    ///
+    /// Returns: SpectrumServerEmptyResult
+    pub fn create_spectrum_stripchart(
+        &self,
+        name: &str,
+        time: &str,
+        value: &str,
+        low: f64,
+        high: f64,
+        bins: u32,
+    ) -> SpectrumServerEmptyResult {
+        let reply = self.transact(Self::createstripchart_request(
+            name, time, value, low, high, bins,
+        ));
+        if let SpectrumReply::Error(s) = reply {
+            Err(s)
+        } else {
+            Ok(())
+        }
+    }
+    /// Create a bit mask spectrum:
+    ///
+    /// *  name - name of the spectrum.
+    /// *  parameter - name of the parameter whose bits are histogrammed.
+    /// *  low, high, bins - axis specification (bit numbers).
+    ///
+    /// Returns: SpectrumServerEmptyResult
+    pub fn create_spectrum_bitmask(
+        &self,
+        name: &str,
+        parameter: &str,
+        low: f64,
+        high: f64,
+        bins: u32,
+    ) -> SpectrumServerEmptyResult {
+        let reply = self.transact(Self::createbitmask_request(
+            name, parameter, low, high, bins,
+        ));
+        if let SpectrumReply::Error(s) = reply {
+            Err(s)
+        } else {
+            Ok(())
+        }
+    }
+    /// Create a snapshot 1d spectrum.  It has no parameters so its
+    /// contents must be set with fill_spectrum.
+    ///
+    /// *  name - name of the spectrum.
+    /// *  low, high, bins - axis specification.
+    ///
+    /// Returns: SpectrumServerEmptyResult
+    pub fn create_spectrum_snapshot1d(
+        &self,
+        name: &str,
+        low: f64,
+        high: f64,
+        bins: u32,
+    ) -> SpectrumServerEmptyResult {
+        let reply = self.transact(Self::createsnapshot1d_request(name, low, high, bins));
+        if let SpectrumReply::Error(s) = reply {
+            Err(s)
+        } else {
+            Ok(())
+        }
+    }
     /// Create 2d spectrum.
     ///
     /// * name - name of the spectrum.
@@ -1513,12 +2670,30 @@ impl SpectrumMessageClient {
             Ok(())
         }
     }
+    /// Clear spectra and, in the same histogramer transaction, the
+    /// statistics of the conditions that gate them.
     ///
-    /// Get the contents of a spectrum.
+    /// *  pattern - glob pattern that describes the spectra to clear.
     ///
-    /// * name - name of the spectrum.
-    /// * xlow, xhigh, ylow, yhigh - a rectangular region of interest in
-    /// parameter coordinate space within which the data are returned.
+    /// Returns: SpectrumServerEmptyResult.
+    ///
+    pub fn clear_spectra_and_gate_statistics(&self, pattern: &str) -> SpectrumServerEmptyResult {
+        match self.transact(SpectrumRequest::ClearWithGateStatistics(String::from(
+            pattern,
+        ))) {
+            SpectrumReply::Cleared => Ok(()),
+            SpectrumReply::Error(s) => Err(s),
+            _ => Err(String::from(
+                "Unexpected reply type in clear_spectra_and_gate_statistics",
+            )),
+        }
+    }
+    ///
+    /// Get the contents of a spectrum.
+    ///
+    /// * name - name of the spectrum.
+    /// * xlow, xhigh, ylow, yhigh - a rectangular region of interest in
+    /// parameter coordinate space within which the data are returned.
     /// Note that only data with non-zero channel values are returned.
     ///
     /// Returns:  SpectrumServerContentsResult
@@ -1538,6 +2713,25 @@ impl SpectrumMessageClient {
         }
     }
     ///
+    /// Get the full contents of several spectra.  The contents are
+    /// all fetched in a single transaction with the histogram server
+    /// so they are consistent with each other; no events are
+    /// histogrammed between fetching one spectrum and the next.
+    ///
+    /// * names - names of the spectra.
+    ///
+    /// Returns: SpectrumServerMultiContentsResult - on success, the
+    /// name/contents pairs in the order of names.  If any spectrum does not
+    /// exist an error is returned.
+    ///
+    pub fn get_multi_contents(&self, names: &[String]) -> SpectrumServerMultiContentsResult {
+        match self.transact(SpectrumRequest::GetMultiContents(names.to_owned())) {
+            SpectrumReply::Error(s) => Err(s),
+            SpectrumReply::MultiContents(c) => Ok(c),
+            _ => Err(String::from("Unexpected reply type for get_multi_contents")),
+        }
+    }
+    ///
     /// Process events.
     ///
     /// *  events - vector of flat event.
@@ -1550,6 +2744,24 @@ impl SpectrumMessageClient {
             _ => Err(String::from("processEvents -unexpected reply type")),
         }
     }
+    ///
+    /// Process events where each event increments its spectra by a weight
+    /// rather than by one.
+    ///
+    /// *  events - vector of flat event.
+    /// *  weight - the increment each event makes to its spectra.
+    ///
+    pub fn process_weighted_events(
+        &self,
+        e: &[parameters::Event],
+        weight: f64,
+    ) -> SpectrumServerEmptyResult {
+        match self.transact(Self::weighted_events_request(e, weight)) {
+            SpectrumReply::Processed => Ok(()),
+            SpectrumReply::Error(s) => Err(s),
+            _ => Err(String::from("processWeightedEvents -unexpected reply type")),
+        }
+    }
     /// Return the over/underflow statistics for a spectrum.
     ///
     /// ### Parameters:
@@ -1610,7 +2822,7 @@ impl SpectrumMessageClient {
     /// *  ychan - optional y channel, required for 2d spectra.
     ///
     ///  ### Returns:
-    ///     SpectrumChannelResult - which, on Ok encapsulates the f64 value of
+    ///  SpectrumChannelResult - which, on Ok encapsulates the f64 value of
     ///  the requested channel.
     ///
     ///  ### Notes:
@@ -1673,6 +2885,42 @@ impl SpectrumMessageClient {
             _ => Err(String::from("Unexpected reply type in set_channel_value")),
         }
     }
+    /// Set all channels in a rectangular region of a spectrum to
+    /// the same value.  This is much faster than setting them one at
+    /// a time with set_channel_value.
+    ///
+    /// ### Parameters:
+    /// *  name - name of the spectrum.
+    /// *  xchans - first and last x channels of the region (inclusive).
+    /// *  ychans - first and last y channels of the region, required for
+    /// 2d spectra and ignored for 1d spectra.
+    /// *  value - New value for the channels.
+    ///
+    /// Returns: SpectrumChannelCountResult - the number of channels set.
+    ///
+    ///  ### Notes:
+    ///   * Channels are numbered as for set_channel_value.
+    ///   * If any of the region is out of range, nothing is set.
+    ///
+    pub fn set_roi(
+        &self,
+        name: &str,
+        xchans: (i32, i32),
+        ychans: Option<(i32, i32)>,
+        value: f64,
+    ) -> SpectrumChannelCountResult {
+        let request = SpectrumRequest::SetRoi {
+            name: String::from(name),
+            xchans,
+            ychans,
+            value,
+        };
+        match self.transact(request) {
+            SpectrumReply::ChannelsSet(n) => Ok(n),
+            SpectrumReply::Error(s) => Err(s),
+            _ => Err(String::from("Unexpected reply type in set_roi")),
+        }
+    }
     /// Determine if a spectrum is 1d:
     ///
     /// ### Parameters:
@@ -1727,6 +2975,220 @@ impl SpectrumMessageClient {
             _ => Err(String::from("Unexpected reply type in unfold_spectrum")),
         }
     }
+    /// List the spectra that are gated on a condition.
+    ///
+    /// ### Parameters
+    ///  *    condition - name of the condition.
+    ///
+    /// ### Returns:
+    ///  *  SpectrumGateApplicationsResult - on success, the spectra gated on
+    /// the condition, sorted by name, with their types and whether or not
+    /// they still refer to the condition in the condition dictionary.
+    ///
+    pub fn gate_applications(&self, condition: &str) -> SpectrumGateApplicationsResult {
+        let request = SpectrumRequest::GateApplications(String::from(condition));
+
+        match self.transact(request) {
+            SpectrumReply::GateApplications(l) => Ok(l),
+            SpectrumReply::Error(s) => Err(s),
+            _ => Err(String::from("Unexpected reply type in gate_applications")),
+        }
+    }
+    /// Describe the parameter index used to find the spectra
+    /// an event increments.
+    ///
+    /// ### Returns:
+    ///  *  SpectrumIdSpanResult - on success, the span of parameter ids
+    /// indexed and the number of index slots allocated.
+    ///
+    pub fn get_id_span(&self) -> SpectrumIdSpanResult {
+        match self.transact(SpectrumRequest::IdSpan) {
+            SpectrumReply::IdSpan { span, allocated } => Ok((span, allocated)),
+            SpectrumReply::Error(s) => Err(s),
+            _ => Err(String::from("Unexpected reply type in get_id_span")),
+        }
+    }
+    /// Release parameter index storage no longer needed because
+    /// spectra were deleted.
+    ///
+    /// ### Returns:
+    ///  *  SpectrumIdSpanResult - on success, the span of parameter ids
+    /// indexed and the number of index slots allocated after compaction.
+    ///
+    pub fn compact_index(&self) -> SpectrumIdSpanResult {
+        match self.transact(SpectrumRequest::CompactIndex) {
+            SpectrumReply::IdSpan { span, allocated } => Ok((span, allocated)),
+            SpectrumReply::Error(s) => Err(s),
+            _ => Err(String::from("Unexpected reply type in compact_index")),
+        }
+    }
+    /// Get the statistics of the conditions whose names match a pattern.
+    ///
+    /// ### Returns:
+    ///  *  SpectrumGateStatisticsResult - on success, condition names and
+    /// their statistics sorted by name.
+    ///
+    pub fn get_gate_statistics(&self, pattern: &str) -> SpectrumGateStatisticsResult {
+        match self.transact(SpectrumRequest::GateStatistics(String::from(pattern))) {
+            SpectrumReply::GateStatistics(l) => Ok(l),
+            SpectrumReply::Error(s) => Err(s),
+            _ => Err(String::from("Unexpected reply type in get_gate_statistics")),
+        }
+    }
+    /// Mark a spectrum as temporary or not.
+    ///
+    /// ### Parameters:
+    /// * name - name of the spectrum.
+    /// * temporary - true to make the spectrum temporary, false to make
+    ///   it permanent.
+    /// * idle_limit - if Some, a temporary spectrum is deleted once it
+    ///   has been idle this long.  Ignored if temporary is false.
+    ///
+    pub fn set_temporary(
+        &self,
+        name: &str,
+        temporary: bool,
+        idle_limit: Option<Duration>,
+    ) -> SpectrumServerEmptyResult {
+        match self.transact(SpectrumRequest::SetTemporary {
+            name: String::from(name),
+            temporary,
+            idle_limit,
+        }) {
+            SpectrumReply::TemporarySet => Ok(()),
+            SpectrumReply::Error(s) => Err(s),
+            _ => Err(String::from("Unexpected reply type in set_temporary")),
+        }
+    }
+    /// List the temporary spectra whose names match a pattern.
+    ///
+    /// ### Returns:
+    /// * SpectrumTemporaryListResult - on success, the temporary
+    ///   spectra sorted by name.
+    ///
+    pub fn list_temporary(&self, pattern: &str) -> SpectrumTemporaryListResult {
+        match self.transact(SpectrumRequest::ListTemporary(String::from(pattern))) {
+            SpectrumReply::TemporaryListing(l) => Ok(l),
+            SpectrumReply::Error(s) => Err(s),
+            _ => Err(String::from("Unexpected reply type in list_temporary")),
+        }
+    }
+    /// Select the events that satisfy a condition.
+    ///
+    /// ### Parameters:
+    /// * gate - name of the condition.  A condition that does not
+    ///   exist selects no events.
+    /// * events - the events to check.
+    ///
+    /// ### Returns:
+    /// * SpectrumSelectResult - on success, the selected events with
+    ///   calibrations applied and calibrated and pseudo parameters
+    ///   computed.
+    ///
+    pub fn select_events(&self, gate: &str, events: &[parameters::Event]) -> SpectrumSelectResult {
+        match self.transact(SpectrumRequest::Select {
+            gate: String::from(gate),
+            events: events.to_owned(),
+        }) {
+            SpectrumReply::Selected(e) => Ok(e),
+            SpectrumReply::Error(s) => Err(s),
+            _ => Err(String::from("Unexpected reply type in select_events")),
+        }
+    }
+    /// Make a spectrum with fewer bins from an existing spectrum by
+    /// summing groups of its bins.
+    ///
+    /// ### Parameters:
+    /// * name - name of the existing spectrum.
+    /// * newname - name of the spectrum to make.
+    /// * xgroup - number of x bins summed into each bin of the new
+    ///   spectrum.  The number of x bins must be a multiple of this.
+    /// * ygroup - the same for the y axis.  Must be 1 if the spectrum
+    ///   has no y axis.
+    ///
+    /// The new spectrum has the type, parameters, gate and fold of the
+    /// original so it continues to increment as the original would.
+    ///
+    /// ### Returns:
+    /// * SpectrumServerEmptyResult
+    ///
+    pub fn rebin_spectrum(
+        &self,
+        name: &str,
+        newname: &str,
+        xgroup: u32,
+        ygroup: u32,
+    ) -> SpectrumServerEmptyResult {
+        match self.transact(SpectrumRequest::Rebin {
+            name: String::from(name),
+            newname: String::from(newname),
+            xgroup,
+            ygroup,
+        }) {
+            SpectrumReply::Created => Ok(()),
+            SpectrumReply::Error(s) => Err(s),
+            _ => Err(String::from("Unexpected reply type in rebin_spectrum")),
+        }
+    }
+    /// Make a spectrum that combines existing spectra bin by bin.
+    ///
+    /// ### Parameters:
+    /// * name - name of the spectrum to make.
+    /// * terms - the spectra to combine and the factor each is
+    ///   multiplied by.  For example, [("signal", 1.0), ("background", -1.0)]
+    ///   subtracts background from signal.
+    ///
+    /// All spectra must have the same type and axes.  The new spectrum
+    /// has the parameters of the first one.  It is not gated so it will
+    /// increment unless the caller gates it.
+    ///
+    /// ### Returns:
+    /// * SpectrumServerEmptyResult
+    ///
+    pub fn combine_spectra(
+        &self,
+        name: &str,
+        terms: &[(String, f64)],
+    ) -> SpectrumServerEmptyResult {
+        match self.transact(SpectrumRequest::Combine {
+            name: String::from(name),
+            terms: terms.to_vec(),
+        }) {
+            SpectrumReply::Created => Ok(()),
+            SpectrumReply::Error(s) => Err(s),
+            _ => Err(String::from("Unexpected reply type in combine_spectra")),
+        }
+    }
+    /// Duplicate a spectrum.
+    ///
+    /// ### Parameters:
+    /// * name - name of the spectrum to copy.
+    /// * newname - name of the copy.
+    /// * contents - if true the counts in the spectrum are copied too.
+    ///   Otherwise the copy starts out empty.
+    ///
+    /// The copy has the type, parameters, axes, gate and fold of the
+    /// original.
+    ///
+    /// ### Returns:
+    /// * SpectrumServerEmptyResult
+    ///
+    pub fn copy_spectrum(
+        &self,
+        name: &str,
+        newname: &str,
+        contents: bool,
+    ) -> SpectrumServerEmptyResult {
+        match self.transact(SpectrumRequest::Copy {
+            name: String::from(name),
+            newname: String::from(newname),
+            contents,
+        }) {
+            SpectrumReply::Created => Ok(()),
+            SpectrumReply::Error(s) => Err(s),
+            _ => Err(String::from("Unexpected reply type in copy_spectrum")),
+        }
+    }
 }
 
 //--------------------------- Tests ------------------------------
@@ -2530,23 +3992,22 @@ mod spproc_tests {
         assert!(matches!(reply, SpectrumReply::Error(_)));
     }
     #[test]
-    fn cr2d_1() {
+    fn crgsummary_1() {
         let mut to = make_test_objs();
         make_some_params(&mut to);
+        let params = vec![
+            vec![String::from("param.1"), String::from("param.2")],
+            vec![String::from("param.4")],
+            vec![String::from("param.8"), String::from("param.9")],
+        ];
         let reply = to.processor.process_request(
-            SpectrumRequest::Create2D {
+            SpectrumRequest::CreateGammaSummary {
                 name: String::from("test"),
-                xparam: String::from("param.5"),
-                yparam: String::from("param.7"),
-                xaxis: AxisSpecification {
-                    low: -10.0,
-                    high: 10.0,
-                    bins: 100,
-                },
+                params,
                 yaxis: AxisSpecification {
                     low: 0.0,
-                    high: 1024.0,
-                    bins: 256,
+                    high: 1.0,
+                    bins: 100,
                 },
             },
             &to.parameters,
@@ -2554,71 +4015,38 @@ mod spproc_tests {
             &to.tracedb,
         );
         assert_eq!(SpectrumReply::Created, reply);
+
         let spec = to
             .processor
             .dict
             .get("test")
-            .expect("Missing spectrum")
+            .expect("Missing gamma summary spectrum")
             .0
             .borrow();
-
-        assert_eq!(String::from("test"), spec.get_name());
-        assert_eq!(String::from("2D"), spec.get_type());
-        let xp = spec.get_xparams();
-        assert_eq!(1, xp.len());
-        assert_eq!(String::from("param.5"), xp[0]);
-        let yp = spec.get_yparams();
-        assert_eq!(1, yp.len());
-        assert_eq!(String::from("param.7"), yp[0]);
-
-        let x = spec.get_xaxis().expect("Missing x axis");
-        assert_eq!(
-            AxisSpecification {
-                low: -10.0,
-                high: 10.0,
-                bins: 102
-            },
-            AxisSpecification {
-                low: x.0,
-                high: x.1,
-                bins: x.2
-            }
-        );
-        let y = spec.get_yaxis().expect("Missing y axis");
+        assert_eq!(String::from("GammaSummary"), spec.get_type());
         assert_eq!(
-            AxisSpecification {
-                low: 0.0,
-                high: 1024.0,
-                bins: 258
-            },
-            AxisSpecification {
-                low: y.0,
-                high: y.1,
-                bins: y.2
-            }
+            vec!["param.1 param.2", "param.4", "param.8 param.9"],
+            spec.get_xparams()
         );
+        assert_eq!(0, spec.get_yparams().len());
+        assert_eq!(Some((0.0, 3.0, 5)), spec.get_xaxis());
+        assert_eq!(Some((0.0, 1.0, 102)), spec.get_yaxis());
         assert!(spec.get_gate().is_none());
     }
     #[test]
-    fn cr2d_2() {
-        // invalid x parameter.
+    fn crgsummary_2() {
+        // Bad parameter and duplicate name:
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
         let reply = to.processor.process_request(
-            SpectrumRequest::Create2D {
+            SpectrumRequest::CreateGammaSummary {
                 name: String::from("test"),
-                xparam: String::from("param.15"),
-                yparam: String::from("param.7"),
-                xaxis: AxisSpecification {
-                    low: -10.0,
-                    high: 10.0,
-                    bins: 100,
-                },
+                params: vec![vec![String::from("param.1"), String::from("param.14")]],
                 yaxis: AxisSpecification {
                     low: 0.0,
-                    high: 1024.0,
-                    bins: 256,
+                    high: 1.0,
+                    bins: 100,
                 },
             },
             &to.parameters,
@@ -2626,27 +4054,17 @@ mod spproc_tests {
             &to.tracedb,
         );
         assert!(matches!(reply, SpectrumReply::Error(_)));
-    }
-    #[test]
-    fn cr2d_3() {
-        // invalid y parameter;
+        assert!(!to.processor.dict.exists("test"));
 
-        let mut to = make_test_objs();
-        make_some_params(&mut to);
+        make_1d(&mut to, "test", "param.1", 100);
         let reply = to.processor.process_request(
-            SpectrumRequest::Create2D {
+            SpectrumRequest::CreateGammaSummary {
                 name: String::from("test"),
-                xparam: String::from("param.5"),
-                yparam: String::from("param.17"),
-                xaxis: AxisSpecification {
-                    low: -10.0,
-                    high: 10.0,
-                    bins: 100,
-                },
+                params: vec![vec![String::from("param.1")]],
                 yaxis: AxisSpecification {
                     low: 0.0,
-                    high: 1024.0,
-                    bins: 256,
+                    high: 1.0,
+                    bins: 100,
                 },
             },
             &to.parameters,
@@ -2656,24 +4074,22 @@ mod spproc_tests {
         assert!(matches!(reply, SpectrumReply::Error(_)));
     }
     #[test]
-    fn cr2d_4() {
-        // duplicate spectrum:
+    fn crgsummary_3() {
+        // Copies are gamma summaries with the same parameter lists:
+
         let mut to = make_test_objs();
         make_some_params(&mut to);
         let reply = to.processor.process_request(
-            SpectrumRequest::Create2D {
+            SpectrumRequest::CreateGammaSummary {
                 name: String::from("test"),
-                xparam: String::from("param.5"),
-                yparam: String::from("param.7"),
-                xaxis: AxisSpecification {
-                    low: -10.0,
-                    high: 10.0,
-                    bins: 100,
-                },
+                params: vec![
+                    vec![String::from("param.1"), String::from("param.2")],
+                    vec![String::from("param.3")],
+                ],
                 yaxis: AxisSpecification {
                     low: 0.0,
-                    high: 1024.0,
-                    bins: 256,
+                    high: 1.0,
+                    bins: 100,
                 },
             },
             &to.parameters,
@@ -2682,60 +4098,196 @@ mod spproc_tests {
         );
         assert_eq!(SpectrumReply::Created, reply);
         let reply = to.processor.process_request(
-            SpectrumRequest::Create2D {
+            SpectrumRequest::Copy {
                 name: String::from("test"),
-                xparam: String::from("param.5"),
-                yparam: String::from("param.7"),
-                xaxis: AxisSpecification {
-                    low: -10.0,
-                    high: 10.0,
-                    bins: 100,
-                },
-                yaxis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 256,
-                },
+                newname: String::from("copy"),
+                contents: false,
             },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert!(matches!(reply, SpectrumReply::Error(_)));
+        assert_eq!(SpectrumReply::Created, reply);
+        let spec = to.processor.dict.get("copy").unwrap().0.borrow();
+        assert_eq!("GammaSummary", spec.get_type());
+        assert_eq!(vec!["param.1 param.2", "param.3"], spec.get_xparams());
+        assert_eq!(Some((0.0, 1.0, 102)), spec.get_yaxis());
     }
     #[test]
-    fn cr2dsum_1() {
+    fn crstripchart_1() {
         let mut to = make_test_objs();
         make_some_params(&mut to);
-        let xpars = vec![
-            String::from("param.0"),
-            String::from("param.2"),
-            String::from("param.4"),
-            String::from("param.6"),
-            String::from("param.7"),
-        ];
-        let ypars = vec![
-            String::from("param.1"),
-            String::from("param.3"),
-            String::from("param.5"),
-            String::from("param.7"),
-            String::from("param.9"),
-        ];
+        let reply = to.processor.process_request(
+            SpectrumRequest::CreateStripChart {
+                name: String::from("test"),
+                time: String::from("param.1"),
+                value: String::from("param.2"),
+                axis: AxisSpecification {
+                    low: 0.0,
+                    high: 100.0,
+                    bins: 100,
+                },
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Created, reply);
+
+        let spec = to
+            .processor
+            .dict
+            .get("test")
+            .expect("Missing strip chart spectrum")
+            .0
+            .borrow();
+        assert_eq!(String::from("StripChart"), spec.get_type());
+        assert_eq!(vec!["param.1"], spec.get_xparams());
+        assert_eq!(vec!["param.2"], spec.get_yparams());
+        assert_eq!(Some((0.0, 100.0, 102)), spec.get_xaxis());
+        assert!(spec.get_yaxis().is_none());
+        assert!(spec.get_gate().is_none());
+    }
+    #[test]
+    fn crstripchart_2() {
+        // Bad parameter and duplicate name:
 
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        let request = |value: &str| SpectrumRequest::CreateStripChart {
+            name: String::from("test"),
+            time: String::from("param.1"),
+            value: String::from(value),
+            axis: AxisSpecification {
+                low: 0.0,
+                high: 100.0,
+                bins: 100,
+            },
+        };
         let reply = to.processor.process_request(
-            SpectrumRequest::Create2DSum {
+            request("param.14"),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(matches!(reply, SpectrumReply::Error(_)));
+        assert!(!to.processor.dict.exists("test"));
+
+        make_1d(&mut to, "test", "param.1", 100);
+        let reply = to.processor.process_request(
+            request("param.2"),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(matches!(reply, SpectrumReply::Error(_)));
+    }
+    #[test]
+    fn crbitmask_1() {
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        let request = || SpectrumRequest::CreateBitmask {
+            name: String::from("test"),
+            parameter: String::from("param.1"),
+            axis: AxisSpecification {
+                low: 0.0,
+                high: 16.0,
+                bins: 16,
+            },
+        };
+        let reply = to.processor.process_request(
+            request(),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Created, reply);
+        {
+            let spec = to
+                .processor
+                .dict
+                .get("test")
+                .expect("Missing bitmask spectrum")
+                .0
+                .borrow();
+            assert_eq!(String::from("Bitmask"), spec.get_type());
+            assert_eq!(vec!["param.1"], spec.get_xparams());
+            assert!(spec.get_yparams().is_empty());
+            assert_eq!(Some((0.0, 16.0, 18)), spec.get_xaxis());
+            assert!(spec.get_yaxis().is_none());
+        }
+
+        // Duplicate:
+
+        let reply = to.processor.process_request(
+            request(),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(matches!(reply, SpectrumReply::Error(_)));
+    }
+    #[test]
+    fn crsnapshot1d_1() {
+        let mut to = make_test_objs();
+        let request = || SpectrumRequest::CreateSnapshot1D {
+            name: String::from("test"),
+            axis: AxisSpecification {
+                low: 0.0,
+                high: 16.0,
+                bins: 16,
+            },
+        };
+        let reply = to.processor.process_request(
+            request(),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Created, reply);
+        {
+            let spec = to
+                .processor
+                .dict
+                .get("test")
+                .expect("Missing snapshot spectrum")
+                .0
+                .borrow();
+            assert_eq!(String::from("Snapshot1D"), spec.get_type());
+            assert!(spec.get_xparams().is_empty());
+            assert!(spec.get_yparams().is_empty());
+            assert_eq!(Some((0.0, 16.0, 18)), spec.get_xaxis());
+            assert!(spec.get_yaxis().is_none());
+        }
+
+        // Duplicate:
+
+        let reply = to.processor.process_request(
+            request(),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(matches!(reply, SpectrumReply::Error(_)));
+    }
+    #[test]
+    fn cr2d_1() {
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        let reply = to.processor.process_request(
+            SpectrumRequest::Create2D {
                 name: String::from("test"),
-                xparams: xpars.clone(),
-                yparams: ypars.clone(),
+                xparam: String::from("param.5"),
+                yparam: String::from("param.7"),
                 xaxis: AxisSpecification {
-                    low: -1.0,
-                    high: 1.0,
-                    bins: 512,
+                    low: -10.0,
+                    high: 10.0,
+                    bins: 100,
                 },
                 yaxis: AxisSpecification {
                     low: 0.0,
-                    high: 4096.0,
-                    bins: 512,
+                    high: 1024.0,
+                    bins: 256,
                 },
             },
             &to.parameters,
@@ -2743,25 +4295,29 @@ mod spproc_tests {
             &to.tracedb,
         );
         assert_eq!(SpectrumReply::Created, reply);
-
         let spec = to
             .processor
             .dict
             .get("test")
-            .expect("Could not find spectrum")
+            .expect("Missing spectrum")
             .0
             .borrow();
+
         assert_eq!(String::from("test"), spec.get_name());
-        assert_eq!(String::from("2DSum"), spec.get_type());
-        assert_eq!(xpars, spec.get_xparams());
-        assert_eq!(ypars, spec.get_yparams());
-        assert!(spec.get_gate().is_none());
+        assert_eq!(String::from("2D"), spec.get_type());
+        let xp = spec.get_xparams();
+        assert_eq!(1, xp.len());
+        assert_eq!(String::from("param.5"), xp[0]);
+        let yp = spec.get_yparams();
+        assert_eq!(1, yp.len());
+        assert_eq!(String::from("param.7"), yp[0]);
+
         let x = spec.get_xaxis().expect("Missing x axis");
         assert_eq!(
             AxisSpecification {
-                low: -1.0,
-                high: 1.0,
-                bins: 514,
+                low: -10.0,
+                high: 10.0,
+                bins: 102
             },
             AxisSpecification {
                 low: x.0,
@@ -2773,8 +4329,8 @@ mod spproc_tests {
         assert_eq!(
             AxisSpecification {
                 low: 0.0,
-                high: 4096.0,
-                bins: 514,
+                high: 1024.0,
+                bins: 258
             },
             AxisSpecification {
                 low: y.0,
@@ -2782,42 +4338,28 @@ mod spproc_tests {
                 bins: y.2
             }
         );
+        assert!(spec.get_gate().is_none());
     }
     #[test]
-    fn cr2dsum_2() {
-        // bad x parameter:
+    fn cr2d_2() {
+        // invalid x parameter.
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
-        let xpars = vec![
-            String::from("param.0"),
-            String::from("param.2"),
-            String::from("param.4"),
-            String::from("param.16"),
-            String::from("param.7"),
-        ];
-        let ypars = vec![
-            String::from("param.1"),
-            String::from("param.3"),
-            String::from("param.5"),
-            String::from("param.7"),
-            String::from("param.9"),
-        ];
-
         let reply = to.processor.process_request(
-            SpectrumRequest::Create2DSum {
+            SpectrumRequest::Create2D {
                 name: String::from("test"),
-                xparams: xpars.clone(),
-                yparams: ypars.clone(),
+                xparam: String::from("param.15"),
+                yparam: String::from("param.7"),
                 xaxis: AxisSpecification {
-                    low: -1.0,
-                    high: 1.0,
-                    bins: 512,
+                    low: -10.0,
+                    high: 10.0,
+                    bins: 100,
                 },
                 yaxis: AxisSpecification {
                     low: 0.0,
-                    high: 4096.0,
-                    bins: 512,
+                    high: 1024.0,
+                    bins: 256,
                 },
             },
             &to.parameters,
@@ -2827,40 +4369,25 @@ mod spproc_tests {
         assert!(matches!(reply, SpectrumReply::Error(_)));
     }
     #[test]
-    fn cr2dsum_3() {
-        // bad y parameter:
+    fn cr2d_3() {
+        // invalid y parameter;
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
-        let xpars = vec![
-            String::from("param.0"),
-            String::from("param.2"),
-            String::from("param.4"),
-            String::from("param.6"),
-            String::from("param.7"),
-        ];
-        let ypars = vec![
-            String::from("param.11"),
-            String::from("param.3"),
-            String::from("param.5"),
-            String::from("param.7"),
-            String::from("param.9"),
-        ];
-
         let reply = to.processor.process_request(
-            SpectrumRequest::Create2DSum {
+            SpectrumRequest::Create2D {
                 name: String::from("test"),
-                xparams: xpars.clone(),
-                yparams: ypars.clone(),
+                xparam: String::from("param.5"),
+                yparam: String::from("param.17"),
                 xaxis: AxisSpecification {
-                    low: -1.0,
-                    high: 1.0,
-                    bins: 512,
+                    low: -10.0,
+                    high: 10.0,
+                    bins: 100,
                 },
                 yaxis: AxisSpecification {
                     low: 0.0,
-                    high: 4096.0,
-                    bins: 512,
+                    high: 1024.0,
+                    bins: 256,
                 },
             },
             &to.parameters,
@@ -2870,40 +4397,254 @@ mod spproc_tests {
         assert!(matches!(reply, SpectrumReply::Error(_)));
     }
     #[test]
-    fn cr2dsum_4() {
+    fn cr2d_4() {
         // duplicate spectrum:
-
         let mut to = make_test_objs();
         make_some_params(&mut to);
-        let xpars = vec![
-            String::from("param.0"),
-            String::from("param.2"),
-            String::from("param.4"),
-            String::from("param.6"),
-            String::from("param.7"),
-        ];
-        let ypars = vec![
-            String::from("param.1"),
-            String::from("param.3"),
-            String::from("param.5"),
-            String::from("param.7"),
-            String::from("param.9"),
-        ];
-
         let reply = to.processor.process_request(
-            SpectrumRequest::Create2DSum {
+            SpectrumRequest::Create2D {
                 name: String::from("test"),
-                xparams: xpars.clone(),
-                yparams: ypars.clone(),
+                xparam: String::from("param.5"),
+                yparam: String::from("param.7"),
                 xaxis: AxisSpecification {
-                    low: -1.0,
-                    high: 1.0,
-                    bins: 512,
+                    low: -10.0,
+                    high: 10.0,
+                    bins: 100,
                 },
                 yaxis: AxisSpecification {
                     low: 0.0,
-                    high: 4096.0,
-                    bins: 512,
+                    high: 1024.0,
+                    bins: 256,
+                },
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Created, reply);
+        let reply = to.processor.process_request(
+            SpectrumRequest::Create2D {
+                name: String::from("test"),
+                xparam: String::from("param.5"),
+                yparam: String::from("param.7"),
+                xaxis: AxisSpecification {
+                    low: -10.0,
+                    high: 10.0,
+                    bins: 100,
+                },
+                yaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 256,
+                },
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(matches!(reply, SpectrumReply::Error(_)));
+    }
+    #[test]
+    fn cr2dsum_1() {
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        let xpars = vec![
+            String::from("param.0"),
+            String::from("param.2"),
+            String::from("param.4"),
+            String::from("param.6"),
+            String::from("param.7"),
+        ];
+        let ypars = vec![
+            String::from("param.1"),
+            String::from("param.3"),
+            String::from("param.5"),
+            String::from("param.7"),
+            String::from("param.9"),
+        ];
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::Create2DSum {
+                name: String::from("test"),
+                xparams: xpars.clone(),
+                yparams: ypars.clone(),
+                xaxis: AxisSpecification {
+                    low: -1.0,
+                    high: 1.0,
+                    bins: 512,
+                },
+                yaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 4096.0,
+                    bins: 512,
+                },
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Created, reply);
+
+        let spec = to
+            .processor
+            .dict
+            .get("test")
+            .expect("Could not find spectrum")
+            .0
+            .borrow();
+        assert_eq!(String::from("test"), spec.get_name());
+        assert_eq!(String::from("2DSum"), spec.get_type());
+        assert_eq!(xpars, spec.get_xparams());
+        assert_eq!(ypars, spec.get_yparams());
+        assert!(spec.get_gate().is_none());
+        let x = spec.get_xaxis().expect("Missing x axis");
+        assert_eq!(
+            AxisSpecification {
+                low: -1.0,
+                high: 1.0,
+                bins: 514,
+            },
+            AxisSpecification {
+                low: x.0,
+                high: x.1,
+                bins: x.2
+            }
+        );
+        let y = spec.get_yaxis().expect("Missing y axis");
+        assert_eq!(
+            AxisSpecification {
+                low: 0.0,
+                high: 4096.0,
+                bins: 514,
+            },
+            AxisSpecification {
+                low: y.0,
+                high: y.1,
+                bins: y.2
+            }
+        );
+    }
+    #[test]
+    fn cr2dsum_2() {
+        // bad x parameter:
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        let xpars = vec![
+            String::from("param.0"),
+            String::from("param.2"),
+            String::from("param.4"),
+            String::from("param.16"),
+            String::from("param.7"),
+        ];
+        let ypars = vec![
+            String::from("param.1"),
+            String::from("param.3"),
+            String::from("param.5"),
+            String::from("param.7"),
+            String::from("param.9"),
+        ];
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::Create2DSum {
+                name: String::from("test"),
+                xparams: xpars.clone(),
+                yparams: ypars.clone(),
+                xaxis: AxisSpecification {
+                    low: -1.0,
+                    high: 1.0,
+                    bins: 512,
+                },
+                yaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 4096.0,
+                    bins: 512,
+                },
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(matches!(reply, SpectrumReply::Error(_)));
+    }
+    #[test]
+    fn cr2dsum_3() {
+        // bad y parameter:
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        let xpars = vec![
+            String::from("param.0"),
+            String::from("param.2"),
+            String::from("param.4"),
+            String::from("param.6"),
+            String::from("param.7"),
+        ];
+        let ypars = vec![
+            String::from("param.11"),
+            String::from("param.3"),
+            String::from("param.5"),
+            String::from("param.7"),
+            String::from("param.9"),
+        ];
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::Create2DSum {
+                name: String::from("test"),
+                xparams: xpars.clone(),
+                yparams: ypars.clone(),
+                xaxis: AxisSpecification {
+                    low: -1.0,
+                    high: 1.0,
+                    bins: 512,
+                },
+                yaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 4096.0,
+                    bins: 512,
+                },
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(matches!(reply, SpectrumReply::Error(_)));
+    }
+    #[test]
+    fn cr2dsum_4() {
+        // duplicate spectrum:
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        let xpars = vec![
+            String::from("param.0"),
+            String::from("param.2"),
+            String::from("param.4"),
+            String::from("param.6"),
+            String::from("param.7"),
+        ];
+        let ypars = vec![
+            String::from("param.1"),
+            String::from("param.3"),
+            String::from("param.5"),
+            String::from("param.7"),
+            String::from("param.9"),
+        ];
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::Create2DSum {
+                name: String::from("test"),
+                xparams: xpars.clone(),
+                yparams: ypars.clone(),
+                xaxis: AxisSpecification {
+                    low: -1.0,
+                    high: 1.0,
+                    bins: 512,
+                },
+                yaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 4096.0,
+                    bins: 512,
                 },
             },
             &to.parameters,
@@ -3369,94 +5110,154 @@ mod spproc_tests {
         );
         assert!(matches!(reply, SpectrumReply::Error(_)));
     }
-
     #[test]
-    fn ungate_1() {
-        // Good ungate:
+    fn gate_applications_1() {
+        // List the spectra gated on a condition:
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
         make_some_gates(&mut to.conditions);
 
-        let reply = to.processor.process_request(
-            SpectrumRequest::Create1D {
-                name: String::from("test"),
-                parameter: String::from("param.1"),
-                axis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 1024,
+        for (name, gate) in [("b", "cond.1"), ("a", "cond.1"), ("c", "cond.2")] {
+            let reply = to.processor.process_request(
+                SpectrumRequest::Create1D {
+                    name: String::from(name),
+                    parameter: String::from("param.1"),
+                    axis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 1024,
+                    },
                 },
-            },
-            &to.parameters,
-            &mut to.conditions,
-            &to.tracedb,
-        );
-        assert_eq!(SpectrumReply::Created, reply);
-
-        let reply = to.processor.process_request(
-            SpectrumRequest::Gate {
-                spectrum: String::from("test"),
-                gate: String::from("cond.5"),
-            },
-            &to.parameters,
-            &mut to.conditions,
-            &to.tracedb,
-        );
-        assert_eq!(SpectrumReply::Gated, reply);
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            );
+            assert_eq!(SpectrumReply::Created, reply);
+            let reply = to.processor.process_request(
+                SpectrumRequest::Gate {
+                    spectrum: String::from(name),
+                    gate: String::from(gate),
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            );
+            assert_eq!(SpectrumReply::Gated, reply);
+        }
 
         let reply = to.processor.process_request(
-            SpectrumRequest::Ungate(String::from("test")),
+            SpectrumRequest::GateApplications(String::from("cond.1")),
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert_eq!(SpectrumReply::Ungated, reply);
+        assert_eq!(
+            SpectrumReply::GateApplications(vec![
+                GateApplication {
+                    spectrum: String::from("a"),
+                    type_name: String::from("1D"),
+                    current: true
+                },
+                GateApplication {
+                    spectrum: String::from("b"),
+                    type_name: String::from("1D"),
+                    current: true
+                }
+            ]),
+            reply
+        );
+
+        // Nothing is gated on cond.3:
 
         let reply = to.processor.process_request(
-            SpectrumRequest::List(String::from("test")),
+            SpectrumRequest::GateApplications(String::from("cond.3")),
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert!(if let SpectrumReply::Listing(l) = reply {
-            assert_eq!(1, l.len());
-            assert!(l[0].gate.is_none());
-            true
-        } else {
-            false
-        });
+        assert_eq!(SpectrumReply::GateApplications(vec![]), reply);
     }
     #[test]
-    fn ungate_2() {
-        // no such spectrum
+    fn gate_applications_2() {
+        // A condition whose container was replaced leaves dangling gates:
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
         make_some_gates(&mut to.conditions);
 
         let reply = to.processor.process_request(
-            SpectrumRequest::Ungate(String::from("test")),
+            SpectrumRequest::Create1D {
+                name: String::from("test"),
+                parameter: String::from("param.1"),
+                axis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 1024,
+                },
+            },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert!(matches!(reply, SpectrumReply::Error(_)));
+        assert_eq!(SpectrumReply::Created, reply);
+        let reply = to.processor.process_request(
+            SpectrumRequest::Gate {
+                spectrum: String::from("test"),
+                gate: String::from("cond.1"),
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Gated, reply);
+
+        to.conditions.insert(
+            String::from("cond.1"),
+            Rc::new(RefCell::new(Box::new(conditions::False {}))),
+        );
+        let reply = to.processor.process_request(
+            SpectrumRequest::GateApplications(String::from("cond.1")),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(
+            SpectrumReply::GateApplications(vec![GateApplication {
+                spectrum: String::from("test"),
+                type_name: String::from("1D"),
+                current: false
+            }]),
+            reply
+        );
     }
     #[test]
-    fn events_1() {
-        // Increment some spectra via an event:
+    fn gate_statistics_1() {
+        // Conditions are counted as events are histogrammed and
+        // clearing with statistics resets only the gates of the
+        // cleared spectra:
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
-
-        for i in 0..10 {
-            let name = format!("spec.{}", i);
-            let par = format!("param.{}", i);
+        let p1 = to.parameters.lookup("param.1").unwrap().get_id();
+        let p2 = to.parameters.lookup("param.2").unwrap().get_id();
+        to.conditions.insert(
+            String::from("cut"),
+            Rc::new(RefCell::new(Box::new(conditions::cut::Cut::new(
+                p1, 100.0, 200.0,
+            )))),
+        );
+        to.conditions.insert(
+            String::from("unused"),
+            Rc::new(RefCell::new(Box::new(conditions::cut::Cut::new(
+                p2, 100.0, 200.0,
+            )))),
+        );
+        for (name, param) in [("a", "param.1"), ("b", "param.2")] {
             let reply = to.processor.process_request(
                 SpectrumRequest::Create1D {
-                    name,
-                    parameter: par,
+                    name: String::from(name),
+                    parameter: String::from(param),
                     axis: AxisSpecification {
                         low: 0.0,
                         high: 1024.0,
@@ -3469,34 +5270,24 @@ mod spproc_tests {
             );
             assert_eq!(SpectrumReply::Created, reply);
         }
-        // Make some events and fill some (not all) of the spectra:
+        let reply = to.processor.process_request(
+            SpectrumRequest::Gate {
+                spectrum: String::from("a"),
+                gate: String::from("cut"),
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Gated, reply);
 
-        let id1 = to.parameters.lookup("param.5").unwrap().get_id();
-        let id2 = to.parameters.lookup("param.7").unwrap().get_id();
+        // The cut is only checked for events with param.1:
 
         let events = vec![
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
+            vec![parameters::EventParameter::new(p1, 150.0)],
+            vec![parameters::EventParameter::new(p1, 50.0)],
+            vec![parameters::EventParameter::new(p2, 150.0)],
         ];
-
         let reply = to.processor.process_request(
             SpectrumRequest::Events(events),
             &to.parameters,
@@ -3504,61 +5295,146 @@ mod spproc_tests {
             &to.tracedb,
         );
         assert_eq!(SpectrumReply::Processed, reply);
-        let with_counts = vec![
-            (String::from("spec.5"), 512.0),
-            (String::from("spec.7"), 700.0),
-        ];
-        let no_counts = vec![
-            String::from("spec.0"),
-            String::from("spec.1"),
-            String::from("spec.2"),
-            String::from("spec.3"),
-            String::from("spec.4"),
-            String::from("spec.6"),
-            String::from("spec.8"),
-            String::from("spec.9"),
-        ];
-        // These should havve counts in the indicated channels:
 
-        for (name, chan) in with_counts {
-            let spec = to.processor.dict.get(&name).unwrap().0.borrow();
-            for ch in spec.get_histogram_1d().unwrap().borrow().iter() {
-                let d = ch.value.get();
-                if d != 0.0 {
-                    assert_eq!(5.0, d);
-                    if let BinInterval::Bin { start, end: _end } = ch.bin {
-                        assert_eq!(chan, start);
-                    } else {
-                        panic!("Under or overflow counts in histogram");
+        let reply = to.processor.process_request(
+            SpectrumRequest::GateStatistics(String::from("*")),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(
+            SpectrumReply::GateStatistics(vec![
+                (
+                    String::from("cut"),
+                    GateStatistics {
+                        checked: 2,
+                        accepted: 1
                     }
-                }
-            }
-        }
-        // these should have no counts.
-        for name in no_counts {
-            let spec = to.processor.dict.get(&name).unwrap().0.borrow();
-            for ch in spec.get_histogram_1d().unwrap().borrow().iter() {
-                assert_eq!(0.0, ch.value.get());
-            }
+                ),
+                (String::from("unused"), GateStatistics::default())
+            ]),
+            reply
+        );
+
+        // Clearing b leaves the cut's statistics alone:
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::ClearWithGateStatistics(String::from("b")),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Cleared, reply);
+        assert_eq!(
+            Some(2),
+            to.processor.gate_statistics.get("cut").map(|s| s.checked)
+        );
+
+        // Clearing a clears both the spectrum and the statistics:
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::ClearWithGateStatistics(String::from("a")),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Cleared, reply);
+        assert!(!to.processor.gate_statistics.contains_key("cut"));
+        let reply = to.processor.process_request(
+            SpectrumRequest::GetContents {
+                name: String::from("a"),
+                xlow: 0.0,
+                xhigh: 1024.0,
+                ylow: 0.0,
+                yhigh: 0.0,
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Contents(vec![]), reply);
+    }
+    #[test]
+    fn gate_statistics_2() {
+        // Bad patterns are errors:
+
+        let mut to = make_test_objs();
+        for req in [
+            SpectrumRequest::GateStatistics(String::from("[")),
+            SpectrumRequest::ClearWithGateStatistics(String::from("[")),
+        ] {
+            let reply =
+                to.processor
+                    .process_request(req, &to.parameters, &mut to.conditions, &to.tracedb);
+            assert!(matches!(reply, SpectrumReply::Error(_)));
         }
     }
     #[test]
-    fn contents_1() {
-        // Process some events as in events_1, get the
-        // contents of the spectra...
-        // should be one channel entry for each of the
-        // two histograms with data and non for those with none
+    fn select_1() {
+        // Events are selected after calibration and a condition that
+        // does not exist selects nothing:
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
+        to.parameters
+            .lookup_mut("param.1")
+            .unwrap()
+            .set_calibration(Some((2.0, 10.0)));
+        let p1 = to.parameters.lookup("param.1").unwrap().get_id();
+        let p2 = to.parameters.lookup("param.2").unwrap().get_id();
+        to.conditions.insert(
+            String::from("cut"),
+            Rc::new(RefCell::new(Box::new(conditions::cut::Cut::new(
+                p1, 100.0, 200.0,
+            )))),
+        );
+        let events = vec![
+            vec![
+                parameters::EventParameter::new(p1, 50.0),
+                parameters::EventParameter::new(p2, 1.0),
+            ],
+            vec![parameters::EventParameter::new(p1, 100.0)],
+            vec![parameters::EventParameter::new(p2, 150.0)],
+        ];
+        let reply = to.processor.process_request(
+            SpectrumRequest::Select {
+                gate: String::from("cut"),
+                events: events.clone(),
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(
+            SpectrumReply::Selected(vec![vec![
+                parameters::EventParameter::new(p1, 110.0),
+                parameters::EventParameter::new(p2, 1.0),
+            ]]),
+            reply
+        );
 
-        for i in 0..10 {
-            let name = format!("spec.{}", i);
-            let par = format!("param.{}", i);
+        let reply = to.processor.process_request(
+            SpectrumRequest::Select {
+                gate: String::from("nosuch"),
+                events,
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Selected(vec![]), reply);
+    }
+    #[test]
+    fn temporary_1() {
+        // Marking, listing and unmarking temporary spectra:
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        for name in ["a", "b"] {
             let reply = to.processor.process_request(
                 SpectrumRequest::Create1D {
-                    name,
-                    parameter: par,
+                    name: String::from(name),
+                    parameter: String::from("param.1"),
                     axis: AxisSpecification {
                         low: 0.0,
                         high: 1024.0,
@@ -3571,116 +5447,135 @@ mod spproc_tests {
             );
             assert_eq!(SpectrumReply::Created, reply);
         }
-        // Make some evnts and fill some (not all) of the spectra:
+        let reply = to.processor.process_request(
+            SpectrumRequest::SetTemporary {
+                name: String::from("a"),
+                temporary: true,
+                idle_limit: Some(Duration::from_secs(100)),
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::TemporarySet, reply);
+        let reply = to.processor.process_request(
+            SpectrumRequest::ListTemporary(String::from("*")),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        if let SpectrumReply::TemporaryListing(l) = reply {
+            assert_eq!(1, l.len());
+            assert_eq!("a", l[0].name);
+            assert_eq!(Some(Duration::from_secs(100)), l[0].idle_limit);
+        } else {
+            panic!("Expected a temporary listing, got {:?}", reply);
+        }
 
-        let id1 = to.parameters.lookup("param.5").unwrap().get_id();
-        let id2 = to.parameters.lookup("param.7").unwrap().get_id();
-
-        let events = vec![
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-        ];
+        // Unmark it:
 
         let reply = to.processor.process_request(
-            SpectrumRequest::Events(events),
+            SpectrumRequest::SetTemporary {
+                name: String::from("a"),
+                temporary: false,
+                idle_limit: None,
+            },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert_eq!(SpectrumReply::Processed, reply);
-        let with_counts = vec![
-            (String::from("spec.5"), 512.0),
-            (String::from("spec.7"), 700.0),
-        ];
-        let no_counts = vec![
-            String::from("spec.0"),
-            String::from("spec.1"),
-            String::from("spec.2"),
-            String::from("spec.3"),
-            String::from("spec.4"),
-            String::from("spec.6"),
-            String::from("spec.8"),
-            String::from("spec.9"),
-        ];
-        // we'll ask for the entire ROI:
-        for (name, chan) in with_counts {
-            let reply = to.processor.process_request(
-                SpectrumRequest::GetContents {
-                    name,
-                    xlow: 0.0,
-                    xhigh: 1024.0,
-                    ylow: 0.0,
-                    yhigh: 0.0,
+        assert_eq!(SpectrumReply::TemporarySet, reply);
+        assert!(to.processor.temporary.is_empty());
+
+        // Errors - no such spectrum, bad pattern:
+
+        for req in [
+            SpectrumRequest::SetTemporary {
+                name: String::from("c"),
+                temporary: true,
+                idle_limit: None,
+            },
+            SpectrumRequest::ListTemporary(String::from("[")),
+        ] {
+            let reply =
+                to.processor
+                    .process_request(req, &to.parameters, &mut to.conditions, &to.tracedb);
+            assert!(matches!(reply, SpectrumReply::Error(_)));
+        }
+    }
+    #[test]
+    fn temporary_2() {
+        // Idle temporary spectra are deleted, used ones are not:
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        for name in ["idle", "used", "permanent"] {
+            to.processor.process_request(
+                SpectrumRequest::Create1D {
+                    name: String::from(name),
+                    parameter: String::from("param.1"),
+                    axis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 1024,
+                    },
                 },
                 &to.parameters,
                 &mut to.conditions,
                 &to.tracedb,
             );
-            assert!(if let SpectrumReply::Contents(sc) = reply {
-                assert_eq!(1, sc.len());
-                assert_eq!(ChannelType::Bin, sc[0].chan_type);
-                assert_eq!(chan, sc[0].x);
-                assert_eq!(5.0, sc[0].value);
-                true
-            } else {
-                false
-            });
         }
-        // Nobody else should have counts:
-
-        for name in no_counts {
+        for name in ["idle", "used"] {
             let reply = to.processor.process_request(
-                SpectrumRequest::GetContents {
-                    name,
-                    xlow: 0.0,
-                    xhigh: 1024.0,
-                    ylow: 0.0,
-                    yhigh: 0.0,
+                SpectrumRequest::SetTemporary {
+                    name: String::from(name),
+                    temporary: true,
+                    idle_limit: Some(Duration::from_millis(200)),
                 },
                 &to.parameters,
                 &mut to.conditions,
                 &to.tracedb,
             );
-            assert!(if let SpectrumReply::Contents(sc) = reply {
-                assert_eq!(0, sc.len());
-                true
-            } else {
-                false
-            });
+            assert_eq!(SpectrumReply::TemporarySet, reply);
+        }
+        for _ in 0..3 {
+            std::thread::sleep(Duration::from_millis(100));
+            to.processor.process_request(
+                SpectrumRequest::GetStats(String::from("used")),
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            );
+        }
+        let reply = to.processor.process_request(
+            SpectrumRequest::List(String::from("*")),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        if let SpectrumReply::Listing(l) = reply {
+            let mut names: Vec<String> = l.into_iter().map(|s| s.name).collect();
+            names.sort();
+            assert_eq!(vec![String::from("permanent"), String::from("used")], names);
+        } else {
+            panic!("Expected a listing, got {:?}", reply);
         }
+        assert!(!to.processor.temporary.contains_key("idle"));
     }
     #[test]
-    fn contents_2() {
-        // Ask with ROI outside of where counts are:
+    fn id_span_1() {
+        // Deleting a spectrum leaves its index slots until compacted:
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
+        let low_id = to.parameters.lookup("param.1").unwrap().get_id() as usize;
+        let high_id = to.parameters.lookup("param.9").unwrap().get_id() as usize;
 
-        for i in 0..10 {
-            let name = format!("spec.{}", i);
-            let par = format!("param.{}", i);
+        for (name, param) in [("low", "param.1"), ("high", "param.9")] {
             let reply = to.processor.process_request(
                 SpectrumRequest::Create1D {
-                    name,
-                    parameter: par,
+                    name: String::from(name),
+                    parameter: String::from(param),
                     axis: AxisSpecification {
                         low: 0.0,
                         high: 1024.0,
@@ -3693,79 +5588,54 @@ mod spproc_tests {
             );
             assert_eq!(SpectrumReply::Created, reply);
         }
-        // Make some evnts and fill some (not all) of the spectra:
-
-        let id1 = to.parameters.lookup("param.5").unwrap().get_id();
-        let id2 = to.parameters.lookup("param.7").unwrap().get_id();
-
-        let events = vec![
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-        ];
-
         let reply = to.processor.process_request(
-            SpectrumRequest::Events(events),
+            SpectrumRequest::Delete(String::from("high")),
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert_eq!(SpectrumReply::Processed, reply);
+        assert_eq!(SpectrumReply::Deleted, reply);
 
         let reply = to.processor.process_request(
-            SpectrumRequest::GetContents {
-                name: String::from("spec.5"),
-                xlow: 0.0,
-                xhigh: 200.0,
-                ylow: 0.0,
-                yhigh: 0.0,
-            },
+            SpectrumRequest::IdSpan,
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert!(if let SpectrumReply::Contents(sc) = reply {
-            assert_eq!(0, sc.len());
-            true
+        if let SpectrumReply::IdSpan { span, .. } = reply {
+            assert_eq!(high_id + 1, span);
         } else {
-            false
-        });
+            panic!("Expected IdSpan got {:?}", reply);
+        }
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::CompactIndex,
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(
+            SpectrumReply::IdSpan {
+                span: low_id + 1,
+                allocated: low_id + 1
+            },
+            reply
+        );
     }
+
     #[test]
-    fn events_2() {
-        // Events for a 2-d histogram:
+    fn ungate_1() {
+        // Good ungate:
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
+        make_some_gates(&mut to.conditions);
 
         let reply = to.processor.process_request(
-            SpectrumRequest::Create2D {
+            SpectrumRequest::Create1D {
                 name: String::from("test"),
-                xparam: String::from("param.5"),
-                yparam: String::from("param.7"),
-                xaxis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 1024,
-                },
-                yaxis: AxisSpecification {
+                parameter: String::from("param.1"),
+                axis: AxisSpecification {
                     low: 0.0,
                     high: 1024.0,
                     bins: 1024,
@@ -3777,125 +5647,90 @@ mod spproc_tests {
         );
         assert_eq!(SpectrumReply::Created, reply);
 
-        // Make and process events that will fill (512.0, 700.0):
-
-        let id1 = to.parameters.lookup("param.5").unwrap().get_id();
-        let id2 = to.parameters.lookup("param.7").unwrap().get_id();
-
-        let events = vec![
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-        ];
-
         let reply = to.processor.process_request(
-            SpectrumRequest::Events(events),
+            SpectrumRequest::Gate {
+                spectrum: String::from("test"),
+                gate: String::from("cond.5"),
+            },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert_eq!(SpectrumReply::Processed, reply);
+        assert_eq!(SpectrumReply::Gated, reply);
 
-        // Contents over the whole spectrum should only have 5
-        // counts in channel 512.0, 700.0
+        let reply = to.processor.process_request(
+            SpectrumRequest::Ungate(String::from("test")),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Ungated, reply);
 
         let reply = to.processor.process_request(
-            SpectrumRequest::GetContents {
-                name: String::from("test"),
-                xlow: 0.0,
-                xhigh: 1024.0,
-                ylow: 0.0,
-                yhigh: 1024.0,
-            },
+            SpectrumRequest::List(String::from("test")),
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert!(if let SpectrumReply::Contents(l) = reply {
+        assert!(if let SpectrumReply::Listing(l) = reply {
             assert_eq!(1, l.len());
-            assert_eq!(ChannelType::Bin, l[0].chan_type);
-            assert_eq!(5.0, l[0].value);
-            assert_eq!(512.0, l[0].x);
-            assert_eq!(700.0, l[0].y);
+            assert!(l[0].gate.is_none());
             true
         } else {
             false
         });
     }
     #[test]
-    fn contents_3() {
-        // 2d ROI checking:
+    fn ungate_2() {
+        // no such spectrum
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
+        make_some_gates(&mut to.conditions);
 
         let reply = to.processor.process_request(
-            SpectrumRequest::Create2D {
-                name: String::from("test"),
-                xparam: String::from("param.5"),
-                yparam: String::from("param.7"),
-                xaxis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 1024,
-                },
-                yaxis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 1024,
-                },
-            },
+            SpectrumRequest::Ungate(String::from("test")),
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert_eq!(SpectrumReply::Created, reply);
-
-        // Make and process events that will fill (512.0, 700.0):
-
-        let id1 = to.parameters.lookup("param.5").unwrap().get_id();
-        let id2 = to.parameters.lookup("param.7").unwrap().get_id();
-
-        let events = vec![
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-            vec![
-                EventParameter::new(id1, 512.0),
-                EventParameter::new(id2, 700.0),
-            ],
-        ];
+        assert!(matches!(reply, SpectrumReply::Error(_)));
+    }
+    #[test]
+    fn events_calibrated_1() {
+        // Calibrated parameters are histogrammed with their calibration
+        // applied:
 
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        for i in 0..2 {
+            let reply = to.processor.process_request(
+                SpectrumRequest::Create1D {
+                    name: format!("spec.{}", i),
+                    parameter: format!("param.{}", i),
+                    axis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 1024,
+                    },
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            );
+            assert_eq!(SpectrumReply::Created, reply);
+        }
+        to.parameters
+            .lookup_mut("param.1")
+            .unwrap()
+            .set_calibration(Some((2.0, 10.0)));
+        let id0 = to.parameters.lookup("param.0").unwrap().get_id();
+        let id1 = to.parameters.lookup("param.1").unwrap().get_id();
+
+        let events = vec![vec![
+            EventParameter::new(id0, 100.0),
+            EventParameter::new(id1, 100.0),
+        ]];
         let reply = to.processor.process_request(
             SpectrumRequest::Events(events),
             &to.parameters,
@@ -3904,94 +5739,34 @@ mod spproc_tests {
         );
         assert_eq!(SpectrumReply::Processed, reply);
 
-        // Contents over the whole spectrum should only have 5
-        // counts in channel 512.0, 700.0
-
-        let reply = to.processor.process_request(
-            SpectrumRequest::GetContents {
-                name: String::from("test"),
-                xlow: 0.0,
-                xhigh: 1024.0,
-                ylow: 100.0,
-                yhigh: 300.0, // Too small for ROI.
-            },
-            &to.parameters,
-            &mut to.conditions,
-            &to.tracedb,
-        );
-        assert!(if let SpectrumReply::Contents(l) = reply {
-            assert_eq!(0, l.len());
-            true
-        } else {
-            false
-        });
-        let reply = to.processor.process_request(
-            SpectrumRequest::GetContents {
-                name: String::from("test"),
-                xlow: 0.0,
-                xhigh: 1024.0,
-                ylow: 720.0, // Too large for ROI
-                yhigh: 1024.0,
-            },
-            &to.parameters,
-            &mut to.conditions,
-            &to.tracedb,
-        );
-        assert!(if let SpectrumReply::Contents(l) = reply {
-            assert_eq!(0, l.len());
-            true
-        } else {
-            false
-        });
-
-        let reply = to.processor.process_request(
-            SpectrumRequest::GetContents {
-                name: String::from("test"),
-                xlow: 0.0,
-                xhigh: 200.0, // Small for ROI
-                ylow: 0.0,
-                yhigh: 1024.0,
-            },
-            &to.parameters,
-            &mut to.conditions,
-            &to.tracedb,
-        );
-        assert!(if let SpectrumReply::Contents(l) = reply {
-            assert_eq!(0, l.len());
-            true
-        } else {
-            false
-        });
-        let reply = to.processor.process_request(
-            SpectrumRequest::GetContents {
-                name: String::from("test"),
-                xlow: 600.0, // too big for ROI.
-                xhigh: 1024.0,
-                ylow: 0.0,
-                yhigh: 1024.0,
-            },
-            &to.parameters,
-            &mut to.conditions,
-            &to.tracedb,
-        );
-        assert!(if let SpectrumReply::Contents(l) = reply {
-            assert_eq!(0, l.len());
-            true
-        } else {
-            false
-        });
+        for (name, chan) in [("spec.0", 100.0), ("spec.1", 210.0)] {
+            let spec = to.processor.dict.get(name).unwrap().0.borrow();
+            let mut total = 0.0;
+            for ch in spec.get_histogram_1d().unwrap().borrow().iter() {
+                let d = ch.value.get();
+                if d != 0.0 {
+                    total += d;
+                    if let BinInterval::Bin { start, end: _end } = ch.bin {
+                        assert_eq!(chan, start);
+                    } else {
+                        panic!("Under or overflow counts in histogram");
+                    }
+                }
+            }
+            assert_eq!(1.0, total);
+        }
     }
     #[test]
-
-    fn is1d_1() {
-        // It is a oned
+    fn events_pseudo_1() {
+        // Pseudo parameters are computed from calibrated parameters
+        // and histogrammed:
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
         let reply = to.processor.process_request(
             SpectrumRequest::Create1D {
-                name: String::from("test"),
-                parameter: String::from("param.1"),
+                name: String::from("sum"),
+                parameter: String::from("param.2"),
                 axis: AxisSpecification {
                     low: 0.0,
                     high: 1024.0,
@@ -4003,88 +5778,73 @@ mod spproc_tests {
             &to.tracedb,
         );
         assert_eq!(SpectrumReply::Created, reply);
+        to.parameters
+            .lookup_mut("param.1")
+            .unwrap()
+            .set_calibration(Some((2.0, 10.0)));
+        let id0 = to.parameters.lookup("param.0").unwrap().get_id();
+        let id1 = to.parameters.lookup("param.1").unwrap().get_id();
+        let id2 = to.parameters.lookup("param.2").unwrap().get_id();
+        let dependencies = vec![
+            (String::from("param.0"), id0),
+            (String::from("param.1"), id1),
+        ];
+        let names = vec![String::from("param.0"), String::from("param.1")];
+        let computation = "param.0 + param.1";
+        let expression = pseudo::Expression::parse(computation, &names).unwrap();
+        to.parameters
+            .pseudos_mut()
+            .add(pseudo::PseudoParameter::new(
+                "param.2",
+                id2,
+                &dependencies,
+                computation,
+                expression,
+            ))
+            .unwrap();
 
-        match to.processor.process_request(
-            SpectrumRequest::Is1D(String::from("test")),
-            &to.parameters,
-            &mut to.conditions,
-            &to.tracedb,
-        ) {
-            SpectrumReply::Flag(b) => assert!(b),
-            _ => panic!("Should have gotten Flag(true)"),
-        }
-    }
-    #[test]
-    fn is1d_2() {
-        // it is not a oned
-
-        let mut to = make_test_objs();
-        make_some_params(&mut to);
+        let events = vec![
+            vec![
+                EventParameter::new(id0, 100.0),
+                EventParameter::new(id1, 100.0),
+            ],
+            vec![EventParameter::new(id0, 100.0)], // No pseudo.
+        ];
         let reply = to.processor.process_request(
-            SpectrumRequest::Create2D {
-                name: String::from("test"),
-                xparam: String::from("param.1"),
-                yparam: String::from("param.2"),
-                xaxis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 1024,
-                },
-                yaxis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 1024,
-                },
-            },
+            SpectrumRequest::Events(events),
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert_eq!(SpectrumReply::Created, reply);
+        assert_eq!(SpectrumReply::Processed, reply);
 
-        match to.processor.process_request(
-            SpectrumRequest::Is1D(String::from("test")),
-            &to.parameters,
-            &mut to.conditions,
-            &to.tracedb,
-        ) {
-            SpectrumReply::Flag(b) => assert!(!b),
-            _ => panic!("Should have gotten Flag(false)"),
+        let spec = to.processor.dict.get("sum").unwrap().0.borrow();
+        let mut total = 0.0;
+        for ch in spec.get_histogram_1d().unwrap().borrow().iter() {
+            let d = ch.value.get();
+            if d != 0.0 {
+                total += d;
+                if let BinInterval::Bin { start, end: _end } = ch.bin {
+                    assert_eq!(310.0, start);
+                } else {
+                    panic!("Under or overflow counts in histogram");
+                }
+            }
         }
+        assert_eq!(1.0, total);
     }
     #[test]
-    fn is1d_3() {
-        // does not exist.
-        let mut to = make_test_objs();
-        let reply = to.processor.process_request(
-            SpectrumRequest::Is1D(String::from("junk")),
-            &to.parameters,
-            &mut to.conditions,
-            &to.tracedb,
-        );
-        assert!(matches!(reply, SpectrumReply::Error(_)));
-    }
-    #[test]
-    fn specstats_1() {
-        // Get the statistics from a spectrum.
-        // Note the statistics functions themselves are tested in
-        // spectrum/mod.rs so we only check that the right
-        // things get returned
+    fn events_calibrated_2() {
+        // Calibrated parameters are computed from their raw parameters
+        // and histogrammed:
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
-
         let reply = to.processor.process_request(
-            SpectrumRequest::Create2D {
-                name: String::from("test"),
-                xparam: String::from("param.5"),
-                yparam: String::from("param.7"),
-                xaxis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 1024,
-                },
-                yaxis: AxisSpecification {
+            SpectrumRequest::Create1D {
+                name: String::from("energy"),
+                parameter: String::from("param.2"),
+                axis: AxisSpecification {
                     low: 0.0,
                     high: 1024.0,
                     bins: 1024,
@@ -4095,44 +5855,736 @@ mod spproc_tests {
             &to.tracedb,
         );
         assert_eq!(SpectrumReply::Created, reply);
+        let id0 = to.parameters.lookup("param.0").unwrap().get_id();
+        let id2 = to.parameters.lookup("param.2").unwrap().get_id();
+        to.parameters
+            .calibrated_mut()
+            .set(calibration::CalibratedParameter::new(
+                "param.2",
+                id2,
+                "param.0",
+                id0,
+                calibration::Calibration::new(10.0, 0.5, 0.001),
+                None,
+            ));
 
+        let events = vec![
+            vec![EventParameter::new(id0, 100.0)],
+            vec![EventParameter::new(id2 + 1, 100.0)], // No raw parameter.
+        ];
         let reply = to.processor.process_request(
-            SpectrumRequest::GetStats(String::from("test")),
+            SpectrumRequest::Events(events),
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert!(if let SpectrumReply::Statistics(s) = reply {
-            assert_eq!((0, 0, 0, 0), s);
-            true
-        } else {
-            false
-        });
-        // IF we use the wrong name:
+        assert_eq!(SpectrumReply::Processed, reply);
 
-        assert!(matches!(
-            to.processor.process_request(
-                SpectrumRequest::GetStats(String::from("none")),
-                &to.parameters,
-                &mut to.conditions,
-                &to.tracedb
-            ),
-            SpectrumReply::Error(_)
-        ));
+        let spec = to.processor.dict.get("energy").unwrap().0.borrow();
+        let mut total = 0.0;
+        for ch in spec.get_histogram_1d().unwrap().borrow().iter() {
+            let d = ch.value.get();
+            if d != 0.0 {
+                total += d;
+                if let BinInterval::Bin { start, end: _end } = ch.bin {
+                    assert_eq!(70.0, start);
+                } else {
+                    panic!("Under or overflow counts in histogram");
+                }
+            }
+        }
+        assert_eq!(1.0, total);
     }
     #[test]
-    fn load_1() {
-        // Load 1d spectrum contents:
+    fn events_1() {
+        // Increment some spectra via an event:
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
 
-        let reply = to.processor.process_request(
-            SpectrumRequest::Create1D {
-                name: String::from("test"),
-                parameter: String::from("param.1"),
-                axis: AxisSpecification {
-                    low: 0.0,
+        for i in 0..10 {
+            let name = format!("spec.{}", i);
+            let par = format!("param.{}", i);
+            let reply = to.processor.process_request(
+                SpectrumRequest::Create1D {
+                    name,
+                    parameter: par,
+                    axis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 1024,
+                    },
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            );
+            assert_eq!(SpectrumReply::Created, reply);
+        }
+        // Make some events and fill some (not all) of the spectra:
+
+        let id1 = to.parameters.lookup("param.5").unwrap().get_id();
+        let id2 = to.parameters.lookup("param.7").unwrap().get_id();
+
+        let events = vec![
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+        ];
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::Events(events),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Processed, reply);
+        let with_counts = vec![
+            (String::from("spec.5"), 512.0),
+            (String::from("spec.7"), 700.0),
+        ];
+        let no_counts = vec![
+            String::from("spec.0"),
+            String::from("spec.1"),
+            String::from("spec.2"),
+            String::from("spec.3"),
+            String::from("spec.4"),
+            String::from("spec.6"),
+            String::from("spec.8"),
+            String::from("spec.9"),
+        ];
+        // These should havve counts in the indicated channels:
+
+        for (name, chan) in with_counts {
+            let spec = to.processor.dict.get(&name).unwrap().0.borrow();
+            for ch in spec.get_histogram_1d().unwrap().borrow().iter() {
+                let d = ch.value.get();
+                if d != 0.0 {
+                    assert_eq!(5.0, d);
+                    if let BinInterval::Bin { start, end: _end } = ch.bin {
+                        assert_eq!(chan, start);
+                    } else {
+                        panic!("Under or overflow counts in histogram");
+                    }
+                }
+            }
+        }
+        // these should have no counts.
+        for name in no_counts {
+            let spec = to.processor.dict.get(&name).unwrap().0.borrow();
+            for ch in spec.get_histogram_1d().unwrap().borrow().iter() {
+                assert_eq!(0.0, ch.value.get());
+            }
+        }
+    }
+    #[test]
+    fn contents_1() {
+        // Process some events as in events_1, get the
+        // contents of the spectra...
+        // should be one channel entry for each of the
+        // two histograms with data and non for those with none
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+
+        for i in 0..10 {
+            let name = format!("spec.{}", i);
+            let par = format!("param.{}", i);
+            let reply = to.processor.process_request(
+                SpectrumRequest::Create1D {
+                    name,
+                    parameter: par,
+                    axis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 1024,
+                    },
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            );
+            assert_eq!(SpectrumReply::Created, reply);
+        }
+        // Make some evnts and fill some (not all) of the spectra:
+
+        let id1 = to.parameters.lookup("param.5").unwrap().get_id();
+        let id2 = to.parameters.lookup("param.7").unwrap().get_id();
+
+        let events = vec![
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+        ];
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::Events(events),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Processed, reply);
+        let with_counts = vec![
+            (String::from("spec.5"), 512.0),
+            (String::from("spec.7"), 700.0),
+        ];
+        let no_counts = vec![
+            String::from("spec.0"),
+            String::from("spec.1"),
+            String::from("spec.2"),
+            String::from("spec.3"),
+            String::from("spec.4"),
+            String::from("spec.6"),
+            String::from("spec.8"),
+            String::from("spec.9"),
+        ];
+        // we'll ask for the entire ROI:
+        for (name, chan) in with_counts {
+            let reply = to.processor.process_request(
+                SpectrumRequest::GetContents {
+                    name,
+                    xlow: 0.0,
+                    xhigh: 1024.0,
+                    ylow: 0.0,
+                    yhigh: 0.0,
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            );
+            assert!(if let SpectrumReply::Contents(sc) = reply {
+                assert_eq!(1, sc.len());
+                assert_eq!(ChannelType::Bin, sc[0].chan_type);
+                assert_eq!(chan, sc[0].x);
+                assert_eq!(5.0, sc[0].value);
+                true
+            } else {
+                false
+            });
+        }
+        // Nobody else should have counts:
+
+        for name in no_counts {
+            let reply = to.processor.process_request(
+                SpectrumRequest::GetContents {
+                    name,
+                    xlow: 0.0,
+                    xhigh: 1024.0,
+                    ylow: 0.0,
+                    yhigh: 0.0,
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            );
+            assert!(if let SpectrumReply::Contents(sc) = reply {
+                assert_eq!(0, sc.len());
+                true
+            } else {
+                false
+            });
+        }
+    }
+    #[test]
+    fn contents_2() {
+        // Ask with ROI outside of where counts are:
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+
+        for i in 0..10 {
+            let name = format!("spec.{}", i);
+            let par = format!("param.{}", i);
+            let reply = to.processor.process_request(
+                SpectrumRequest::Create1D {
+                    name,
+                    parameter: par,
+                    axis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 1024,
+                    },
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            );
+            assert_eq!(SpectrumReply::Created, reply);
+        }
+        // Make some evnts and fill some (not all) of the spectra:
+
+        let id1 = to.parameters.lookup("param.5").unwrap().get_id();
+        let id2 = to.parameters.lookup("param.7").unwrap().get_id();
+
+        let events = vec![
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+        ];
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::Events(events),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Processed, reply);
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::GetContents {
+                name: String::from("spec.5"),
+                xlow: 0.0,
+                xhigh: 200.0,
+                ylow: 0.0,
+                yhigh: 0.0,
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(if let SpectrumReply::Contents(sc) = reply {
+            assert_eq!(0, sc.len());
+            true
+        } else {
+            false
+        });
+    }
+    #[test]
+    fn events_2() {
+        // Events for a 2-d histogram:
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::Create2D {
+                name: String::from("test"),
+                xparam: String::from("param.5"),
+                yparam: String::from("param.7"),
+                xaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 1024,
+                },
+                yaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 1024,
+                },
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Created, reply);
+
+        // Make and process events that will fill (512.0, 700.0):
+
+        let id1 = to.parameters.lookup("param.5").unwrap().get_id();
+        let id2 = to.parameters.lookup("param.7").unwrap().get_id();
+
+        let events = vec![
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+        ];
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::Events(events),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Processed, reply);
+
+        // Contents over the whole spectrum should only have 5
+        // counts in channel 512.0, 700.0
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::GetContents {
+                name: String::from("test"),
+                xlow: 0.0,
+                xhigh: 1024.0,
+                ylow: 0.0,
+                yhigh: 1024.0,
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(if let SpectrumReply::Contents(l) = reply {
+            assert_eq!(1, l.len());
+            assert_eq!(ChannelType::Bin, l[0].chan_type);
+            assert_eq!(5.0, l[0].value);
+            assert_eq!(512.0, l[0].x);
+            assert_eq!(700.0, l[0].y);
+            true
+        } else {
+            false
+        });
+    }
+    #[test]
+    fn contents_3() {
+        // 2d ROI checking:
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::Create2D {
+                name: String::from("test"),
+                xparam: String::from("param.5"),
+                yparam: String::from("param.7"),
+                xaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 1024,
+                },
+                yaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 1024,
+                },
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Created, reply);
+
+        // Make and process events that will fill (512.0, 700.0):
+
+        let id1 = to.parameters.lookup("param.5").unwrap().get_id();
+        let id2 = to.parameters.lookup("param.7").unwrap().get_id();
+
+        let events = vec![
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+            vec![
+                EventParameter::new(id1, 512.0),
+                EventParameter::new(id2, 700.0),
+            ],
+        ];
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::Events(events),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Processed, reply);
+
+        // Contents over the whole spectrum should only have 5
+        // counts in channel 512.0, 700.0
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::GetContents {
+                name: String::from("test"),
+                xlow: 0.0,
+                xhigh: 1024.0,
+                ylow: 100.0,
+                yhigh: 300.0, // Too small for ROI.
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(if let SpectrumReply::Contents(l) = reply {
+            assert_eq!(0, l.len());
+            true
+        } else {
+            false
+        });
+        let reply = to.processor.process_request(
+            SpectrumRequest::GetContents {
+                name: String::from("test"),
+                xlow: 0.0,
+                xhigh: 1024.0,
+                ylow: 720.0, // Too large for ROI
+                yhigh: 1024.0,
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(if let SpectrumReply::Contents(l) = reply {
+            assert_eq!(0, l.len());
+            true
+        } else {
+            false
+        });
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::GetContents {
+                name: String::from("test"),
+                xlow: 0.0,
+                xhigh: 200.0, // Small for ROI
+                ylow: 0.0,
+                yhigh: 1024.0,
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(if let SpectrumReply::Contents(l) = reply {
+            assert_eq!(0, l.len());
+            true
+        } else {
+            false
+        });
+        let reply = to.processor.process_request(
+            SpectrumRequest::GetContents {
+                name: String::from("test"),
+                xlow: 600.0, // too big for ROI.
+                xhigh: 1024.0,
+                ylow: 0.0,
+                yhigh: 1024.0,
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(if let SpectrumReply::Contents(l) = reply {
+            assert_eq!(0, l.len());
+            true
+        } else {
+            false
+        });
+    }
+    #[test]
+
+    fn is1d_1() {
+        // It is a oned
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        let reply = to.processor.process_request(
+            SpectrumRequest::Create1D {
+                name: String::from("test"),
+                parameter: String::from("param.1"),
+                axis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 1024,
+                },
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Created, reply);
+
+        match to.processor.process_request(
+            SpectrumRequest::Is1D(String::from("test")),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        ) {
+            SpectrumReply::Flag(b) => assert!(b),
+            _ => panic!("Should have gotten Flag(true)"),
+        }
+    }
+    #[test]
+    fn is1d_2() {
+        // it is not a oned
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        let reply = to.processor.process_request(
+            SpectrumRequest::Create2D {
+                name: String::from("test"),
+                xparam: String::from("param.1"),
+                yparam: String::from("param.2"),
+                xaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 1024,
+                },
+                yaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 1024,
+                },
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Created, reply);
+
+        match to.processor.process_request(
+            SpectrumRequest::Is1D(String::from("test")),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        ) {
+            SpectrumReply::Flag(b) => assert!(!b),
+            _ => panic!("Should have gotten Flag(false)"),
+        }
+    }
+    #[test]
+    fn is1d_3() {
+        // does not exist.
+        let mut to = make_test_objs();
+        let reply = to.processor.process_request(
+            SpectrumRequest::Is1D(String::from("junk")),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(matches!(reply, SpectrumReply::Error(_)));
+    }
+    #[test]
+    fn specstats_1() {
+        // Get the statistics from a spectrum.
+        // Note the statistics functions themselves are tested in
+        // spectrum/mod.rs so we only check that the right
+        // things get returned
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::Create2D {
+                name: String::from("test"),
+                xparam: String::from("param.5"),
+                yparam: String::from("param.7"),
+                xaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 1024,
+                },
+                yaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 1024,
+                },
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Created, reply);
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::GetStats(String::from("test")),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(if let SpectrumReply::Statistics(s) = reply {
+            assert_eq!((0, 0, 0, 0), s);
+            true
+        } else {
+            false
+        });
+        // IF we use the wrong name:
+
+        assert!(matches!(
+            to.processor.process_request(
+                SpectrumRequest::GetStats(String::from("none")),
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb
+            ),
+            SpectrumReply::Error(_)
+        ));
+    }
+    #[test]
+    fn load_1() {
+        // Load 1d spectrum contents:
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::Create1D {
+                name: String::from("test"),
+                parameter: String::from("param.1"),
+                axis: AxisSpecification {
+                    low: 0.0,
                     high: 1024.0,
                     bins: 1024,
                 },
@@ -4739,17 +7191,154 @@ mod spproc_tests {
             SpectrumRequest::GetChan {
                 name: String::from("test"),
                 xchan: 512,
-                ychan: Some(-2),
+                ychan: Some(-2),
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(matches!(reply, SpectrumReply::Error(_)));
+    }
+    #[test]
+    fn getchan2_5() {
+        // y too big.
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        let reply = to.processor.process_request(
+            SpectrumRequest::Create2D {
+                name: String::from("test"),
+                xparam: String::from("param.1"),
+                yparam: String::from("param.2"),
+                xaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 512,
+                },
+                yaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 512,
+                },
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Created, reply);
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::GetChan {
+                name: String::from("test"),
+                xchan: 0,
+                ychan: Some(513),
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(matches!(reply, SpectrumReply::Error(_)));
+    }
+    #[test]
+    fn getchan2_6() {
+        // an x underflow
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        let reply = to.processor.process_request(
+            SpectrumRequest::Create2D {
+                name: String::from("test"),
+                xparam: String::from("param.1"),
+                yparam: String::from("param.2"),
+                xaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 512,
+                },
+                yaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 512,
+                },
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Created, reply);
+
+        // increment coordinate (-1.0, 512.0) that's an x underflow
+        // on the 256 line.
+
+        {
+            let spc = to.processor.dict.get("test").unwrap().0.borrow();
+            spc.get_histogram_2d()
+                .unwrap()
+                .borrow_mut()
+                .fill(&(-1.0, 512.0));
+        }
+        let reply = to.processor.process_request(
+            SpectrumRequest::GetChan {
+                name: String::from("test"),
+                xchan: -1,
+                ychan: Some(256),
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+
+        assert_eq!(SpectrumReply::ChannelValue(1.0), reply);
+    }
+    #[test]
+    fn getchan2_7() {
+        // an x overflow
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        let reply = to.processor.process_request(
+            SpectrumRequest::Create2D {
+                name: String::from("test"),
+                xparam: String::from("param.1"),
+                yparam: String::from("param.2"),
+                xaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 512,
+                },
+                yaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 512,
+                },
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Created, reply);
+
+        // Set increment x channel at coordinate 1025.0 and y 512.0  that should
+        // be an overflow an the y bin 256
+        {
+            let spc = to.processor.dict.get("test").unwrap().0.borrow();
+            spc.get_histogram_2d()
+                .unwrap()
+                .borrow_mut()
+                .fill(&(1025.0, 512.0));
+        }
+        let reply = to.processor.process_request(
+            SpectrumRequest::GetChan {
+                name: String::from("test"),
+                xchan: 512,
+                ychan: Some(256),
             },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert!(matches!(reply, SpectrumReply::Error(_)));
+        assert_eq!(SpectrumReply::ChannelValue(1.0), reply);
     }
     #[test]
-    fn getchan2_5() {
-        // y too big.
+    fn getchan2_8() {
+        // a y underflow
         let mut to = make_test_objs();
         make_some_params(&mut to);
         let reply = to.processor.process_request(
@@ -4774,21 +7363,32 @@ mod spproc_tests {
         );
         assert_eq!(SpectrumReply::Created, reply);
 
+        // Y underflow:  set coordinate 512.0, -2.0  that's
+        // bin (256, -1) in our coords for underflow
+
+        {
+            let spc = to.processor.dict.get("test").unwrap().0.borrow();
+            spc.get_histogram_2d()
+                .unwrap()
+                .borrow_mut()
+                .fill(&(512.0, -2.0));
+        }
+
         let reply = to.processor.process_request(
             SpectrumRequest::GetChan {
                 name: String::from("test"),
-                xchan: 0,
-                ychan: Some(513),
+                xchan: 256,
+                ychan: Some(-1),
             },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert!(matches!(reply, SpectrumReply::Error(_)));
+        assert_eq!(SpectrumReply::ChannelValue(1.0), reply);
     }
     #[test]
-    fn getchan2_6() {
-        // an x underflow
+    fn getchan2_9() {
+        // a y overflow.
         let mut to = make_test_objs();
         make_some_params(&mut to);
         let reply = to.processor.process_request(
@@ -4813,32 +7413,33 @@ mod spproc_tests {
         );
         assert_eq!(SpectrumReply::Created, reply);
 
-        // increment coordinate (-1.0, 512.0) that's an x underflow
-        // on the 256 line.
+        // Increment coordinate 512, 1025.0
+        // This will be 256, 512 in bin space.
 
         {
             let spc = to.processor.dict.get("test").unwrap().0.borrow();
             spc.get_histogram_2d()
                 .unwrap()
                 .borrow_mut()
-                .fill(&(-1.0, 512.0));
+                .fill(&(512.0, 1025.0));
         }
+
         let reply = to.processor.process_request(
             SpectrumRequest::GetChan {
                 name: String::from("test"),
-                xchan: -1,
-                ychan: Some(256),
+                xchan: 256,
+                ychan: Some(512),
             },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-
         assert_eq!(SpectrumReply::ChannelValue(1.0), reply);
     }
     #[test]
-    fn getchan2_7() {
-        // an x overflow
+    fn getchan2_10() {
+        // ybin cannot be None
+
         let mut to = make_test_objs();
         make_some_params(&mut to);
         let reply = to.processor.process_request(
@@ -4861,173 +7462,400 @@ mod spproc_tests {
             &mut to.conditions,
             &to.tracedb,
         );
-        assert_eq!(SpectrumReply::Created, reply);
+        assert_eq!(SpectrumReply::Created, reply);
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::GetChan {
+                name: String::from("test"),
+                xchan: 256,
+                ychan: None,
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(matches!(reply, SpectrumReply::Error(_)));
+    }
+    // Channel setting --
+    #[test]
+    fn setchan1_1() {
+        // Good set of 1d channnel:
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+
+        assert_eq!(
+            SpectrumReply::Created,
+            to.processor.process_request(
+                SpectrumRequest::Create1D {
+                    name: String::from("test"),
+                    parameter: String::from("param.1"),
+                    axis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 1024
+                    }
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            )
+        );
+        // Set channel 512 to 1234.0:
+
+        assert_eq!(
+            SpectrumReply::ChannelSet,
+            to.processor.process_request(
+                SpectrumRequest::SetChan {
+                    name: String::from("test"),
+                    xchan: 512,
+                    ychan: None,
+                    value: 12345.0
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            )
+        );
+
+        // Get the value --
+
+        assert_eq!(
+            SpectrumReply::ChannelValue(12345.0),
+            to.processor.process_request(
+                SpectrumRequest::GetChan {
+                    name: String::from("test"),
+                    xchan: 512,
+                    ychan: None
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            )
+        );
+    }
+    #[test]
+    fn setchan1_2() {
+        // 1d spectrum - underflow channel:
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+
+        assert_eq!(
+            SpectrumReply::Created,
+            to.processor.process_request(
+                SpectrumRequest::Create1D {
+                    name: String::from("test"),
+                    parameter: String::from("param.1"),
+                    axis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 1024
+                    }
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            )
+        );
+        // Set channel -1 to 1234.0:
+
+        assert_eq!(
+            SpectrumReply::ChannelSet,
+            to.processor.process_request(
+                SpectrumRequest::SetChan {
+                    name: String::from("test"),
+                    xchan: -1,
+                    ychan: None,
+                    value: 12345.0
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            )
+        );
+
+        // Get the value --
+
+        assert_eq!(
+            SpectrumReply::ChannelValue(12345.0),
+            to.processor.process_request(
+                SpectrumRequest::GetChan {
+                    name: String::from("test"),
+                    xchan: -1,
+                    ychan: None
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            )
+        );
+    }
+    #[test]
+    fn setchan1_3() {
+        // Overflow channel:
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+
+        assert_eq!(
+            SpectrumReply::Created,
+            to.processor.process_request(
+                SpectrumRequest::Create1D {
+                    name: String::from("test"),
+                    parameter: String::from("param.1"),
+                    axis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 1024
+                    }
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            )
+        );
+        // Set channel 1024 to 1234.0:
+
+        assert_eq!(
+            SpectrumReply::ChannelSet,
+            to.processor.process_request(
+                SpectrumRequest::SetChan {
+                    name: String::from("test"),
+                    xchan: 1024,
+                    ychan: None,
+                    value: 12345.0
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            )
+        );
+
+        // Get the value --
+
+        assert_eq!(
+            SpectrumReply::ChannelValue(12345.0),
+            to.processor.process_request(
+                SpectrumRequest::GetChan {
+                    name: String::from("test"),
+                    xchan: 1024,
+                    ychan: None
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            )
+        );
+    }
+    #[test]
+    fn setchan1_4() {
+        // channel number is too small
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+
+        assert_eq!(
+            SpectrumReply::Created,
+            to.processor.process_request(
+                SpectrumRequest::Create1D {
+                    name: String::from("test"),
+                    parameter: String::from("param.1"),
+                    axis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 1024
+                    }
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            )
+        );
+        // Set channel -2 (too small)
 
-        // Set increment x channel at coordinate 1025.0 and y 512.0  that should
-        // be an overflow an the y bin 256
-        {
-            let spc = to.processor.dict.get("test").unwrap().0.borrow();
-            spc.get_histogram_2d()
-                .unwrap()
-                .borrow_mut()
-                .fill(&(1025.0, 512.0));
-        }
         let reply = to.processor.process_request(
-            SpectrumRequest::GetChan {
+            SpectrumRequest::SetChan {
                 name: String::from("test"),
-                xchan: 512,
-                ychan: Some(256),
+                xchan: -2,
+                ychan: None,
+                value: 12345.0,
             },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert_eq!(SpectrumReply::ChannelValue(1.0), reply);
+        assert!(matches!(reply, SpectrumReply::Error(_)));
     }
     #[test]
-    fn getchan2_8() {
-        // a y underflow
+    fn setchan1_5() {
+        // Channel too big:
+
         let mut to = make_test_objs();
         make_some_params(&mut to);
-        let reply = to.processor.process_request(
-            SpectrumRequest::Create2D {
-                name: String::from("test"),
-                xparam: String::from("param.1"),
-                yparam: String::from("param.2"),
-                xaxis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 512,
-                },
-                yaxis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 512,
+
+        assert_eq!(
+            SpectrumReply::Created,
+            to.processor.process_request(
+                SpectrumRequest::Create1D {
+                    name: String::from("test"),
+                    parameter: String::from("param.1"),
+                    axis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 1024
+                    }
                 },
-            },
-            &to.parameters,
-            &mut to.conditions,
-            &to.tracedb,
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            )
         );
-        assert_eq!(SpectrumReply::Created, reply);
-
-        // Y underflow:  set coordinate 512.0, -2.0  that's
-        // bin (256, -1) in our coords for underflow
-
-        {
-            let spc = to.processor.dict.get("test").unwrap().0.borrow();
-            spc.get_histogram_2d()
-                .unwrap()
-                .borrow_mut()
-                .fill(&(512.0, -2.0));
-        }
+        // Set channel 1025 (too small)
 
         let reply = to.processor.process_request(
-            SpectrumRequest::GetChan {
+            SpectrumRequest::SetChan {
                 name: String::from("test"),
-                xchan: 256,
-                ychan: Some(-1),
+                xchan: 1025,
+                ychan: None,
+                value: 12345.0,
             },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert_eq!(SpectrumReply::ChannelValue(1.0), reply);
+        assert!(matches!(reply, SpectrumReply::Error(_)));
     }
+    // Setchan for 2-d spectra:
+
     #[test]
-    fn getchan2_9() {
-        // a y overflow.
+    fn setchan2_1() {
+        // Middle of a 2-d spectrum:
+
         let mut to = make_test_objs();
         make_some_params(&mut to);
-        let reply = to.processor.process_request(
-            SpectrumRequest::Create2D {
-                name: String::from("test"),
-                xparam: String::from("param.1"),
-                yparam: String::from("param.2"),
-                xaxis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 512,
-                },
-                yaxis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 512,
+
+        assert_eq!(
+            SpectrumReply::Created,
+            to.processor.process_request(
+                SpectrumRequest::Create2D {
+                    name: String::from("test"),
+                    xparam: String::from("param.1"),
+                    yparam: String::from("param.2"),
+                    xaxis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 256
+                    },
+                    yaxis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 256
+                    }
                 },
-            },
-            &to.parameters,
-            &mut to.conditions,
-            &to.tracedb,
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            )
         );
-        assert_eq!(SpectrumReply::Created, reply);
 
-        // Increment coordinate 512, 1025.0
-        // This will be 256, 512 in bin space.
+        // Set channel 128,128 to 12345:
 
-        {
-            let spc = to.processor.dict.get("test").unwrap().0.borrow();
-            spc.get_histogram_2d()
-                .unwrap()
-                .borrow_mut()
-                .fill(&(512.0, 1025.0));
-        }
+        assert_eq!(
+            SpectrumReply::ChannelSet,
+            to.processor.process_request(
+                SpectrumRequest::SetChan {
+                    name: String::from("test"),
+                    xchan: 128,
+                    ychan: Some(128),
+                    value: 12345.0
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            )
+        );
+        // Ensure it got set:
 
-        let reply = to.processor.process_request(
-            SpectrumRequest::GetChan {
-                name: String::from("test"),
-                xchan: 256,
-                ychan: Some(512),
-            },
-            &to.parameters,
-            &mut to.conditions,
-            &to.tracedb,
+        assert_eq!(
+            SpectrumReply::ChannelValue(12345.0),
+            to.processor.process_request(
+                SpectrumRequest::GetChan {
+                    name: String::from("test"),
+                    xchan: 128,
+                    ychan: Some(128)
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            )
         );
-        assert_eq!(SpectrumReply::ChannelValue(1.0), reply);
     }
     #[test]
-    fn getchan2_10() {
-        // ybin cannot be None
-
+    fn setchan2_2() {
+        // X underflow.
         let mut to = make_test_objs();
         make_some_params(&mut to);
-        let reply = to.processor.process_request(
-            SpectrumRequest::Create2D {
-                name: String::from("test"),
-                xparam: String::from("param.1"),
-                yparam: String::from("param.2"),
-                xaxis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 512,
+
+        assert_eq!(
+            SpectrumReply::Created,
+            to.processor.process_request(
+                SpectrumRequest::Create2D {
+                    name: String::from("test"),
+                    xparam: String::from("param.1"),
+                    yparam: String::from("param.2"),
+                    xaxis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 256
+                    },
+                    yaxis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 256
+                    }
                 },
-                yaxis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 512,
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            )
+        );
+
+        // Set channel -1,128 to 12345:
+
+        assert_eq!(
+            SpectrumReply::ChannelSet,
+            to.processor.process_request(
+                SpectrumRequest::SetChan {
+                    name: String::from("test"),
+                    xchan: -1,
+                    ychan: Some(128),
+                    value: 12345.0
                 },
-            },
-            &to.parameters,
-            &mut to.conditions,
-            &to.tracedb,
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            )
         );
-        assert_eq!(SpectrumReply::Created, reply);
+        // Ensure it got set:
 
-        let reply = to.processor.process_request(
-            SpectrumRequest::GetChan {
-                name: String::from("test"),
-                xchan: 256,
-                ychan: None,
-            },
-            &to.parameters,
-            &mut to.conditions,
-            &to.tracedb,
+        assert_eq!(
+            SpectrumReply::ChannelValue(12345.0),
+            to.processor.process_request(
+                SpectrumRequest::GetChan {
+                    name: String::from("test"),
+                    xchan: -1,
+                    ychan: Some(128)
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            )
         );
-        assert!(matches!(reply, SpectrumReply::Error(_)));
     }
-    // Channel setting --
     #[test]
-    fn setchan1_1() {
-        // Good set of 1d channnel:
+    fn setchan2_3() {
+        // x overflow
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
@@ -5035,13 +7863,19 @@ mod spproc_tests {
         assert_eq!(
             SpectrumReply::Created,
             to.processor.process_request(
-                SpectrumRequest::Create1D {
+                SpectrumRequest::Create2D {
                     name: String::from("test"),
-                    parameter: String::from("param.1"),
-                    axis: AxisSpecification {
+                    xparam: String::from("param.1"),
+                    yparam: String::from("param.2"),
+                    xaxis: AxisSpecification {
                         low: 0.0,
                         high: 1024.0,
-                        bins: 1024
+                        bins: 256
+                    },
+                    yaxis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 256
                     }
                 },
                 &to.parameters,
@@ -5049,15 +7883,16 @@ mod spproc_tests {
                 &to.tracedb,
             )
         );
-        // Set channel 512 to 1234.0:
+
+        // Set channel 256,128 to 12345:
 
         assert_eq!(
             SpectrumReply::ChannelSet,
             to.processor.process_request(
                 SpectrumRequest::SetChan {
                     name: String::from("test"),
-                    xchan: 512,
-                    ychan: None,
+                    xchan: 256,
+                    ychan: Some(128),
                     value: 12345.0
                 },
                 &to.parameters,
@@ -5065,16 +7900,15 @@ mod spproc_tests {
                 &to.tracedb,
             )
         );
-
-        // Get the value --
+        // Ensure it got set:
 
         assert_eq!(
             SpectrumReply::ChannelValue(12345.0),
             to.processor.process_request(
                 SpectrumRequest::GetChan {
                     name: String::from("test"),
-                    xchan: 512,
-                    ychan: None
+                    xchan: 256,
+                    ychan: Some(128)
                 },
                 &to.parameters,
                 &mut to.conditions,
@@ -5083,8 +7917,8 @@ mod spproc_tests {
         );
     }
     #[test]
-    fn setchan1_2() {
-        // 1d spectrum - underflow channel:
+    fn setchan2_4() {
+        // y underflow
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
@@ -5092,13 +7926,19 @@ mod spproc_tests {
         assert_eq!(
             SpectrumReply::Created,
             to.processor.process_request(
-                SpectrumRequest::Create1D {
+                SpectrumRequest::Create2D {
                     name: String::from("test"),
-                    parameter: String::from("param.1"),
-                    axis: AxisSpecification {
+                    xparam: String::from("param.1"),
+                    yparam: String::from("param.2"),
+                    xaxis: AxisSpecification {
                         low: 0.0,
                         high: 1024.0,
-                        bins: 1024
+                        bins: 256
+                    },
+                    yaxis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 256
                     }
                 },
                 &to.parameters,
@@ -5106,15 +7946,16 @@ mod spproc_tests {
                 &to.tracedb,
             )
         );
-        // Set channel -1 to 1234.0:
+
+        // Set channel 128,-1 to 12345:
 
         assert_eq!(
             SpectrumReply::ChannelSet,
             to.processor.process_request(
                 SpectrumRequest::SetChan {
                     name: String::from("test"),
-                    xchan: -1,
-                    ychan: None,
+                    xchan: 128,
+                    ychan: Some(-1),
                     value: 12345.0
                 },
                 &to.parameters,
@@ -5122,16 +7963,15 @@ mod spproc_tests {
                 &to.tracedb,
             )
         );
-
-        // Get the value --
+        // Ensure it got set:
 
         assert_eq!(
             SpectrumReply::ChannelValue(12345.0),
             to.processor.process_request(
                 SpectrumRequest::GetChan {
                     name: String::from("test"),
-                    xchan: -1,
-                    ychan: None
+                    xchan: 128,
+                    ychan: Some(-1)
                 },
                 &to.parameters,
                 &mut to.conditions,
@@ -5140,8 +7980,8 @@ mod spproc_tests {
         );
     }
     #[test]
-    fn setchan1_3() {
-        // Overflow channel:
+    fn setchan2_5() {
+        // y overflow
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
@@ -5149,13 +7989,19 @@ mod spproc_tests {
         assert_eq!(
             SpectrumReply::Created,
             to.processor.process_request(
-                SpectrumRequest::Create1D {
+                SpectrumRequest::Create2D {
                     name: String::from("test"),
-                    parameter: String::from("param.1"),
-                    axis: AxisSpecification {
+                    xparam: String::from("param.1"),
+                    yparam: String::from("param.2"),
+                    xaxis: AxisSpecification {
                         low: 0.0,
                         high: 1024.0,
-                        bins: 1024
+                        bins: 256
+                    },
+                    yaxis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 256
                     }
                 },
                 &to.parameters,
@@ -5163,15 +8009,16 @@ mod spproc_tests {
                 &to.tracedb,
             )
         );
-        // Set channel 1024 to 1234.0:
+
+        // Set channel 128,256 to 12345:
 
         assert_eq!(
             SpectrumReply::ChannelSet,
             to.processor.process_request(
                 SpectrumRequest::SetChan {
                     name: String::from("test"),
-                    xchan: 1024,
-                    ychan: None,
+                    xchan: 128,
+                    ychan: Some(256),
                     value: 12345.0
                 },
                 &to.parameters,
@@ -5179,16 +8026,15 @@ mod spproc_tests {
                 &to.tracedb,
             )
         );
-
-        // Get the value --
+        // Ensure it got set:
 
         assert_eq!(
             SpectrumReply::ChannelValue(12345.0),
             to.processor.process_request(
                 SpectrumRequest::GetChan {
                     name: String::from("test"),
-                    xchan: 1024,
-                    ychan: None
+                    xchan: 128,
+                    ychan: Some(256)
                 },
                 &to.parameters,
                 &mut to.conditions,
@@ -5197,8 +8043,8 @@ mod spproc_tests {
         );
     }
     #[test]
-    fn setchan1_4() {
-        // channel number is too small
+    fn setchan2_6() {
+        // xchannel too small
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
@@ -5206,13 +8052,19 @@ mod spproc_tests {
         assert_eq!(
             SpectrumReply::Created,
             to.processor.process_request(
-                SpectrumRequest::Create1D {
+                SpectrumRequest::Create2D {
                     name: String::from("test"),
-                    parameter: String::from("param.1"),
-                    axis: AxisSpecification {
+                    xparam: String::from("param.1"),
+                    yparam: String::from("param.2"),
+                    xaxis: AxisSpecification {
                         low: 0.0,
                         high: 1024.0,
-                        bins: 1024
+                        bins: 256
+                    },
+                    yaxis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 256
                     }
                 },
                 &to.parameters,
@@ -5220,13 +8072,12 @@ mod spproc_tests {
                 &to.tracedb,
             )
         );
-        // Set channel -2 (too small)
 
         let reply = to.processor.process_request(
             SpectrumRequest::SetChan {
                 name: String::from("test"),
                 xchan: -2,
-                ychan: None,
+                ychan: Some(128),
                 value: 12345.0,
             },
             &to.parameters,
@@ -5236,22 +8087,27 @@ mod spproc_tests {
         assert!(matches!(reply, SpectrumReply::Error(_)));
     }
     #[test]
-    fn setchan1_5() {
-        // Channel too big:
-
+    fn setchan2_7() {
+        // xchannel too big.
         let mut to = make_test_objs();
         make_some_params(&mut to);
 
         assert_eq!(
             SpectrumReply::Created,
             to.processor.process_request(
-                SpectrumRequest::Create1D {
+                SpectrumRequest::Create2D {
                     name: String::from("test"),
-                    parameter: String::from("param.1"),
-                    axis: AxisSpecification {
+                    xparam: String::from("param.1"),
+                    yparam: String::from("param.2"),
+                    xaxis: AxisSpecification {
                         low: 0.0,
                         high: 1024.0,
-                        bins: 1024
+                        bins: 256
+                    },
+                    yaxis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 256
                     }
                 },
                 &to.parameters,
@@ -5259,13 +8115,12 @@ mod spproc_tests {
                 &to.tracedb,
             )
         );
-        // Set channel 1025 (too small)
 
         let reply = to.processor.process_request(
             SpectrumRequest::SetChan {
                 name: String::from("test"),
-                xchan: 1025,
-                ychan: None,
+                xchan: 257,
+                ychan: Some(128),
                 value: 12345.0,
             },
             &to.parameters,
@@ -5274,11 +8129,51 @@ mod spproc_tests {
         );
         assert!(matches!(reply, SpectrumReply::Error(_)));
     }
-    // Setchan for 2-d spectra:
+    #[test]
+    fn setchan2_8() {
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+
+        assert_eq!(
+            SpectrumReply::Created,
+            to.processor.process_request(
+                SpectrumRequest::Create2D {
+                    name: String::from("test"),
+                    xparam: String::from("param.1"),
+                    yparam: String::from("param.2"),
+                    xaxis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 256
+                    },
+                    yaxis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 256
+                    }
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            )
+        );
 
+        let reply = to.processor.process_request(
+            SpectrumRequest::SetChan {
+                name: String::from("test"),
+                xchan: 129,
+                ychan: Some(-2),
+                value: 12345.0,
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(matches!(reply, SpectrumReply::Error(_)));
+    }
     #[test]
-    fn setchan2_1() {
-        // Middle of a 2-d spectrum:
+    fn setchan2_9() {
+        // ychannel too big.
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
@@ -5307,49 +8202,87 @@ mod spproc_tests {
             )
         );
 
-        // Set channel 128,128 to 12345:
+        let reply = to.processor.process_request(
+            SpectrumRequest::SetChan {
+                name: String::from("test"),
+                xchan: 129,
+                ychan: Some(257),
+                value: 12345.0,
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(matches!(reply, SpectrumReply::Error(_)));
+    }
+    #[test]
+    fn setchan2_10() {
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
 
         assert_eq!(
-            SpectrumReply::ChannelSet,
+            SpectrumReply::Created,
             to.processor.process_request(
-                SpectrumRequest::SetChan {
+                SpectrumRequest::Create2D {
                     name: String::from("test"),
-                    xchan: 128,
-                    ychan: Some(128),
-                    value: 12345.0
+                    xparam: String::from("param.1"),
+                    yparam: String::from("param.2"),
+                    xaxis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 256
+                    },
+                    yaxis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 256
+                    }
                 },
                 &to.parameters,
                 &mut to.conditions,
                 &to.tracedb,
             )
         );
-        // Ensure it got set:
 
+        let reply = to.processor.process_request(
+            SpectrumRequest::SetChan {
+                name: String::from("test"),
+                xchan: 129,
+                ychan: None,
+                value: 12345.0,
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(matches!(reply, SpectrumReply::Error(_)));
+    }
+    // Make the 1d and 2d spectra the setroi tests use:
+
+    fn make_roi_spectra(to: &mut TestObjects) {
+        make_some_params(to);
         assert_eq!(
-            SpectrumReply::ChannelValue(12345.0),
+            SpectrumReply::Created,
             to.processor.process_request(
-                SpectrumRequest::GetChan {
-                    name: String::from("test"),
-                    xchan: 128,
-                    ychan: Some(128)
+                SpectrumRequest::Create1D {
+                    name: String::from("oned"),
+                    parameter: String::from("param.1"),
+                    axis: AxisSpecification {
+                        low: 0.0,
+                        high: 1024.0,
+                        bins: 1024
+                    }
                 },
                 &to.parameters,
                 &mut to.conditions,
                 &to.tracedb,
             )
         );
-    }
-    #[test]
-    fn setchan2_2() {
-        // X underflow.
-        let mut to = make_test_objs();
-        make_some_params(&mut to);
-
         assert_eq!(
             SpectrumReply::Created,
             to.processor.process_request(
                 SpectrumRequest::Create2D {
-                    name: String::from("test"),
+                    name: String::from("twod"),
                     xparam: String::from("param.1"),
                     yparam: String::from("param.2"),
                     xaxis: AxisSpecification {
@@ -5368,309 +8301,457 @@ mod spproc_tests {
                 &to.tracedb,
             )
         );
+    }
+    fn get_chan(to: &mut TestObjects, name: &str, xchan: i32, ychan: Option<i32>) -> f64 {
+        match to.processor.process_request(
+            SpectrumRequest::GetChan {
+                name: String::from(name),
+                xchan,
+                ychan,
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        ) {
+            SpectrumReply::ChannelValue(v) => v,
+            r => panic!("Unexpected reply {:?}", r),
+        }
+    }
+    #[test]
+    fn setroi_1() {
+        // 1d region including the underflow channel:
 
-        // Set channel -1,128 to 12345:
+        let mut to = make_test_objs();
+        make_roi_spectra(&mut to);
 
         assert_eq!(
-            SpectrumReply::ChannelSet,
+            SpectrumReply::ChannelsSet(11),
             to.processor.process_request(
-                SpectrumRequest::SetChan {
-                    name: String::from("test"),
-                    xchan: -1,
-                    ychan: Some(128),
-                    value: 12345.0
+                SpectrumRequest::SetRoi {
+                    name: String::from("oned"),
+                    xchans: (-1, 9),
+                    ychans: None,
+                    value: 5.0
                 },
                 &to.parameters,
                 &mut to.conditions,
                 &to.tracedb,
             )
         );
-        // Ensure it got set:
+        assert_eq!(5.0, get_chan(&mut to, "oned", -1, None));
+        assert_eq!(5.0, get_chan(&mut to, "oned", 9, None));
+        assert_eq!(0.0, get_chan(&mut to, "oned", 10, None));
+    }
+    #[test]
+    fn setroi_2() {
+        // 2d region:
+
+        let mut to = make_test_objs();
+        make_roi_spectra(&mut to);
 
         assert_eq!(
-            SpectrumReply::ChannelValue(12345.0),
+            SpectrumReply::ChannelsSet(6),
             to.processor.process_request(
-                SpectrumRequest::GetChan {
-                    name: String::from("test"),
-                    xchan: -1,
-                    ychan: Some(128)
+                SpectrumRequest::SetRoi {
+                    name: String::from("twod"),
+                    xchans: (10, 12),
+                    ychans: Some((20, 21)),
+                    value: 7.0
                 },
                 &to.parameters,
                 &mut to.conditions,
                 &to.tracedb,
             )
         );
+        assert_eq!(7.0, get_chan(&mut to, "twod", 10, Some(20)));
+        assert_eq!(7.0, get_chan(&mut to, "twod", 12, Some(21)));
+        assert_eq!(0.0, get_chan(&mut to, "twod", 13, Some(21)));
+        assert_eq!(0.0, get_chan(&mut to, "twod", 12, Some(22)));
     }
     #[test]
-    fn setchan2_3() {
-        // x overflow
+    fn setroi_3() {
+        // Bad regions are errors and set nothing:
 
         let mut to = make_test_objs();
-        make_some_params(&mut to);
-
-        assert_eq!(
-            SpectrumReply::Created,
-            to.processor.process_request(
-                SpectrumRequest::Create2D {
-                    name: String::from("test"),
-                    xparam: String::from("param.1"),
-                    yparam: String::from("param.2"),
-                    xaxis: AxisSpecification {
-                        low: 0.0,
-                        high: 1024.0,
-                        bins: 256
-                    },
-                    yaxis: AxisSpecification {
-                        low: 0.0,
-                        high: 1024.0,
-                        bins: 256
-                    }
+        make_roi_spectra(&mut to);
+
+        for (name, xchans, ychans) in [
+            ("nosuch", (0, 1), None),
+            ("oned", (5, 4), None),
+            ("oned", (-2, 4), None),
+            ("oned", (0, 1025), None),
+            ("twod", (0, 1), None),
+            ("twod", (0, 1), Some((3, 2))),
+            ("twod", (0, 257), Some((0, 1))),
+            ("twod", (0, 1), Some((0, 257))),
+        ] {
+            let reply = to.processor.process_request(
+                SpectrumRequest::SetRoi {
+                    name: String::from(name),
+                    xchans,
+                    ychans,
+                    value: 1.0,
                 },
                 &to.parameters,
                 &mut to.conditions,
                 &to.tracedb,
-            )
+            );
+            assert!(matches!(reply, SpectrumReply::Error(_)), "{:?}", xchans);
+        }
+        assert_eq!(0.0, get_chan(&mut to, "oned", 0, None));
+        assert_eq!(0.0, get_chan(&mut to, "twod", 0, Some(0)));
+    }
+    #[test]
+    fn fold_1() {
+        let mut to = make_test_objs();
+
+        // Try to fold with no such spectrum:
+
+        to.conditions.insert(
+            String::from("true"),
+            Rc::new(RefCell::new(Box::new(True {}))),
+        );
+        let reply = to.processor.process_request(
+            SpectrumRequest::Fold {
+                spectrum_name: String::from("junk"),
+                condition_name: String::from("true"),
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
         );
+        assert!(matches!(reply, SpectrumReply::Error(_)));
+    }
+    #[test]
+    fn fold_2() {
+        // Have the spectrum (and it's even ok) but condition not defined is
+        // also an error:
 
-        // Set channel 256,128 to 12345:
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        let reply = to.processor.process_request(
+            SpectrumRequest::CreateMulti1D {
+                name: String::from("test"),
+                params: vec![
+                    String::from("param.0"),
+                    String::from("param.1"),
+                    String::from("param.2"),
+                ],
+                axis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 1024,
+                },
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Created, reply);
+        let reply = to.processor.process_request(
+            SpectrumRequest::Fold {
+                spectrum_name: String::from("test"),
+                condition_name: String::from("nosuch"),
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(matches!(reply, SpectrumReply::Error(_)));
+    }
+    #[test]
+    fn fold_3() {
+        // Wrong type of spectrum, right type of condition is also an error.
 
-        assert_eq!(
-            SpectrumReply::ChannelSet,
-            to.processor.process_request(
-                SpectrumRequest::SetChan {
-                    name: String::from("test"),
-                    xchan: 256,
-                    ychan: Some(128),
-                    value: 12345.0
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        let reply = to.processor.process_request(
+            // Ordinary 1d spectrum.
+            SpectrumRequest::Create1D {
+                name: String::from("test"),
+                parameter: String::from("param.2"),
+                axis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 1024,
                 },
-                &to.parameters,
-                &mut to.conditions,
-                &to.tracedb,
-            )
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
         );
-        // Ensure it got set:
+        assert_eq!(SpectrumReply::Created, reply);
 
-        assert_eq!(
-            SpectrumReply::ChannelValue(12345.0),
-            to.processor.process_request(
-                SpectrumRequest::GetChan {
-                    name: String::from("test"),
-                    xchan: 256,
-                    ychan: Some(128)
+        // MultiCut condition:
+
+        let cond = conditions::MultiCut::new(&[1, 2, 3, 4], 100.0, 200.0);
+        to.conditions
+            .insert(String::from("cut"), Rc::new(RefCell::new(Box::new(cond))));
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::Fold {
+                spectrum_name: String::from("test"),
+                condition_name: String::from("cut"),
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(matches!(reply, SpectrumReply::Error(_)));
+    }
+    #[test]
+    fn fold_4() {
+        // Right type of spectrum, wrong type of condition is an error.
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        let reply = to.processor.process_request(
+            SpectrumRequest::CreateMulti1D {
+                name: String::from("test"),
+                params: vec![
+                    String::from("param.0"),
+                    String::from("param.1"),
+                    String::from("param.2"),
+                ],
+                axis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 1024,
                 },
-                &to.parameters,
-                &mut to.conditions,
-                &to.tracedb,
-            )
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Created, reply);
+
+        let cond = conditions::True {};
+        to.conditions
+            .insert(String::from("true"), Rc::new(RefCell::new(Box::new(cond))));
+        let reply = to.processor.process_request(
+            SpectrumRequest::Fold {
+                spectrum_name: String::from("test"),
+                condition_name: String::from("true"),
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert!(matches!(reply, SpectrumReply::Error(_)));
+    }
+    #[test]
+    fn fold_5() {
+        // Right type of spectrum and condition succeeds (multi1/multislice)
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        let reply = to.processor.process_request(
+            SpectrumRequest::CreateMulti1D {
+                name: String::from("test"),
+                params: vec![
+                    String::from("param.0"),
+                    String::from("param.1"),
+                    String::from("param.2"),
+                ],
+                axis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 1024,
+                },
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Created, reply);
+        let cond = conditions::cut::MultiCut::new(&[1, 2, 3], 100.0, 200.0);
+        to.conditions
+            .insert(String::from("slice"), Rc::new(RefCell::new(Box::new(cond))));
+        let reply = to.processor.process_request(
+            SpectrumRequest::Fold {
+                spectrum_name: String::from("test"),
+                condition_name: String::from("slice"),
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Folded, reply);
+
+        // Get the name of the fold via listing:
+
+        let ls = to.processor.process_request(
+            SpectrumRequest::List(String::from("test")),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
         );
+        if let SpectrumReply::Listing(l) = ls {
+            assert_eq!(1, l.len());
+            let props = l[0].clone();
+            assert!(props.fold.is_some());
+            assert_eq!("slice", props.fold.unwrap());
+        } else {
+            panic!("Incorrect reply from list_spectra");
+        }
     }
     #[test]
-    fn setchan2_4() {
-        // y underflow
+    fn fold_6() {
+        // right type of spectrum and condition succeeds (multi2/multislice)
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
+        let reply = to.processor.process_request(
+            SpectrumRequest::CreateMulti2D {
+                name: String::from("test"),
+                params: vec![
+                    String::from("param.0"),
+                    String::from("param.1"),
+                    String::from("param.2"),
+                ],
 
-        assert_eq!(
-            SpectrumReply::Created,
-            to.processor.process_request(
-                SpectrumRequest::Create2D {
-                    name: String::from("test"),
-                    xparam: String::from("param.1"),
-                    yparam: String::from("param.2"),
-                    xaxis: AxisSpecification {
-                        low: 0.0,
-                        high: 1024.0,
-                        bins: 256
-                    },
-                    yaxis: AxisSpecification {
-                        low: 0.0,
-                        high: 1024.0,
-                        bins: 256
-                    }
+                xaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 256,
                 },
-                &to.parameters,
-                &mut to.conditions,
-                &to.tracedb,
-            )
-        );
-
-        // Set channel 128,-1 to 12345:
-
-        assert_eq!(
-            SpectrumReply::ChannelSet,
-            to.processor.process_request(
-                SpectrumRequest::SetChan {
-                    name: String::from("test"),
-                    xchan: 128,
-                    ychan: Some(-1),
-                    value: 12345.0
+                yaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 256,
                 },
-                &to.parameters,
-                &mut to.conditions,
-                &to.tracedb,
-            )
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
         );
-        // Ensure it got set:
-
-        assert_eq!(
-            SpectrumReply::ChannelValue(12345.0),
-            to.processor.process_request(
-                SpectrumRequest::GetChan {
-                    name: String::from("test"),
-                    xchan: 128,
-                    ychan: Some(-1)
-                },
-                &to.parameters,
-                &mut to.conditions,
-                &to.tracedb,
-            )
+        assert_eq!(SpectrumReply::Created, reply);
+        let cond = conditions::cut::MultiCut::new(&[1, 2, 3], 100.0, 200.0);
+        to.conditions
+            .insert(String::from("slice"), Rc::new(RefCell::new(Box::new(cond))));
+        let reply = to.processor.process_request(
+            SpectrumRequest::Fold {
+                spectrum_name: String::from("test"),
+                condition_name: String::from("slice"),
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
         );
+        assert_eq!(SpectrumReply::Folded, reply);
     }
     #[test]
-    fn setchan2_5() {
-        // y overflow
-
+    fn fold_7() {
+        // right type of spectrum and condition succeeds (multi1/multicontour)
         let mut to = make_test_objs();
         make_some_params(&mut to);
-
-        assert_eq!(
-            SpectrumReply::Created,
-            to.processor.process_request(
-                SpectrumRequest::Create2D {
-                    name: String::from("test"),
-                    xparam: String::from("param.1"),
-                    yparam: String::from("param.2"),
-                    xaxis: AxisSpecification {
-                        low: 0.0,
-                        high: 1024.0,
-                        bins: 256
-                    },
-                    yaxis: AxisSpecification {
-                        low: 0.0,
-                        high: 1024.0,
-                        bins: 256
-                    }
+        let reply = to.processor.process_request(
+            SpectrumRequest::CreateMulti1D {
+                name: String::from("test"),
+                params: vec![
+                    String::from("param.0"),
+                    String::from("param.1"),
+                    String::from("param.2"),
+                ],
+                axis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 1024,
                 },
-                &to.parameters,
-                &mut to.conditions,
-                &to.tracedb,
-            )
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
         );
-
-        // Set channel 128,256 to 12345:
-
-        assert_eq!(
-            SpectrumReply::ChannelSet,
-            to.processor.process_request(
-                SpectrumRequest::SetChan {
-                    name: String::from("test"),
-                    xchan: 128,
-                    ychan: Some(256),
-                    value: 12345.0
-                },
-                &to.parameters,
-                &mut to.conditions,
-                &to.tracedb,
-            )
+        assert_eq!(SpectrumReply::Created, reply);
+        let cond = conditions::MultiContour::new(
+            &[1, 2, 3],
+            vec![
+                conditions::twod::Point::new(0.0, 0.0),
+                conditions::twod::Point::new(100.0, 0.0),
+                conditions::twod::Point::new(50.0, 100.0),
+            ],
+        )
+        .expect("Making multicontour");
+        to.conditions.insert(
+            String::from("contour"),
+            Rc::new(RefCell::new(Box::new(cond))),
         );
-        // Ensure it got set:
-
-        assert_eq!(
-            SpectrumReply::ChannelValue(12345.0),
-            to.processor.process_request(
-                SpectrumRequest::GetChan {
-                    name: String::from("test"),
-                    xchan: 128,
-                    ychan: Some(256)
-                },
-                &to.parameters,
-                &mut to.conditions,
-                &to.tracedb,
-            )
+        let reply = to.processor.process_request(
+            SpectrumRequest::Fold {
+                spectrum_name: String::from("test"),
+                condition_name: String::from("contour"),
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
         );
+        assert_eq!(SpectrumReply::Folded, reply);
     }
     #[test]
-    fn setchan2_6() {
-        // xchannel too small
+    fn fold_8() {
+        // right type of spectrum and condition succeeds (multi2/multicontour)
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
+        let reply = to.processor.process_request(
+            SpectrumRequest::CreateMulti2D {
+                name: String::from("test"),
+                params: vec![
+                    String::from("param.0"),
+                    String::from("param.1"),
+                    String::from("param.2"),
+                ],
 
-        assert_eq!(
-            SpectrumReply::Created,
-            to.processor.process_request(
-                SpectrumRequest::Create2D {
-                    name: String::from("test"),
-                    xparam: String::from("param.1"),
-                    yparam: String::from("param.2"),
-                    xaxis: AxisSpecification {
-                        low: 0.0,
-                        high: 1024.0,
-                        bins: 256
-                    },
-                    yaxis: AxisSpecification {
-                        low: 0.0,
-                        high: 1024.0,
-                        bins: 256
-                    }
+                xaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 256,
                 },
-                &to.parameters,
-                &mut to.conditions,
-                &to.tracedb,
-            )
+                yaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 256,
+                },
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Created, reply);
+        let cond = conditions::MultiContour::new(
+            &[1, 2, 3],
+            vec![
+                conditions::twod::Point::new(0.0, 0.0),
+                conditions::twod::Point::new(100.0, 0.0),
+                conditions::twod::Point::new(50.0, 100.0),
+            ],
+        )
+        .expect("Making multicontour");
+        to.conditions.insert(
+            String::from("contour"),
+            Rc::new(RefCell::new(Box::new(cond))),
         );
-
         let reply = to.processor.process_request(
-            SpectrumRequest::SetChan {
-                name: String::from("test"),
-                xchan: -2,
-                ychan: Some(128),
-                value: 12345.0,
+            SpectrumRequest::Fold {
+                spectrum_name: String::from("test"),
+                condition_name: String::from("contour"),
             },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert!(matches!(reply, SpectrumReply::Error(_)));
+        assert_eq!(SpectrumReply::Folded, reply);
     }
-    #[test]
-    fn setchan2_7() {
-        // xchannel too big.
-        let mut to = make_test_objs();
-        make_some_params(&mut to);
+    // unfold tests
 
-        assert_eq!(
-            SpectrumReply::Created,
-            to.processor.process_request(
-                SpectrumRequest::Create2D {
-                    name: String::from("test"),
-                    xparam: String::from("param.1"),
-                    yparam: String::from("param.2"),
-                    xaxis: AxisSpecification {
-                        low: 0.0,
-                        high: 1024.0,
-                        bins: 256
-                    },
-                    yaxis: AxisSpecification {
-                        low: 0.0,
-                        high: 1024.0,
-                        bins: 256
-                    }
-                },
-                &to.parameters,
-                &mut to.conditions,
-                &to.tracedb,
-            )
-        );
+    #[test]
+    fn unfold_0() {
+        // Cannot unfold a nonexistent spectrum.
 
+        let mut to = make_test_objs();
         let reply = to.processor.process_request(
-            SpectrumRequest::SetChan {
-                name: String::from("test"),
-                xchan: 257,
-                ychan: Some(128),
-                value: 12345.0,
-            },
+            SpectrumRequest::Unfold(String::from("junk")),
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
@@ -5678,41 +8759,30 @@ mod spproc_tests {
         assert!(matches!(reply, SpectrumReply::Error(_)));
     }
     #[test]
-    fn setchan2_8() {
+    fn unfold_1() {
+        // Cannot unfold 'ordinary spectra'
+
         let mut to = make_test_objs();
         make_some_params(&mut to);
 
-        assert_eq!(
-            SpectrumReply::Created,
-            to.processor.process_request(
-                SpectrumRequest::Create2D {
-                    name: String::from("test"),
-                    xparam: String::from("param.1"),
-                    yparam: String::from("param.2"),
-                    xaxis: AxisSpecification {
-                        low: 0.0,
-                        high: 1024.0,
-                        bins: 256
-                    },
-                    yaxis: AxisSpecification {
-                        low: 0.0,
-                        high: 1024.0,
-                        bins: 256
-                    }
+        let reply = to.processor.process_request(
+            SpectrumRequest::Create1D {
+                name: String::from("test"),
+                parameter: String::from("param.0"),
+                axis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 1024,
                 },
-                &to.parameters,
-                &mut to.conditions,
-                &to.tracedb,
-            )
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
         );
+        assert_eq!(SpectrumReply::Created, reply);
 
         let reply = to.processor.process_request(
-            SpectrumRequest::SetChan {
-                name: String::from("test"),
-                xchan: 129,
-                ychan: Some(-2),
-                value: 12345.0,
-            },
+            SpectrumRequest::Unfold(String::from("test")),
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
@@ -5720,127 +8790,110 @@ mod spproc_tests {
         assert!(matches!(reply, SpectrumReply::Error(_)));
     }
     #[test]
-    fn setchan2_9() {
-        // ychannel too big.
+    fn unfold_2() {
+        // Unfolding ok spectra without folds is ok.
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
 
-        assert_eq!(
-            SpectrumReply::Created,
-            to.processor.process_request(
-                SpectrumRequest::Create2D {
-                    name: String::from("test"),
-                    xparam: String::from("param.1"),
-                    yparam: String::from("param.2"),
-                    xaxis: AxisSpecification {
-                        low: 0.0,
-                        high: 1024.0,
-                        bins: 256
-                    },
-                    yaxis: AxisSpecification {
-                        low: 0.0,
-                        high: 1024.0,
-                        bins: 256
-                    }
+        let reply = to.processor.process_request(
+            SpectrumRequest::CreateMulti1D {
+                name: String::from("test"),
+                params: vec![
+                    String::from("param.0"),
+                    String::from("param.1"),
+                    String::from("param.2"),
+                ],
+                axis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 1024,
                 },
-                &to.parameters,
-                &mut to.conditions,
-                &to.tracedb,
-            )
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
         );
 
+        assert_eq!(SpectrumReply::Created, reply);
+
         let reply = to.processor.process_request(
-            SpectrumRequest::SetChan {
-                name: String::from("test"),
-                xchan: 129,
-                ychan: Some(257),
-                value: 12345.0,
-            },
+            SpectrumRequest::Unfold(String::from("test")),
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert!(matches!(reply, SpectrumReply::Error(_)));
+        assert_eq!(SpectrumReply::Unfolded, reply);
     }
     #[test]
-    fn setchan2_10() {
+    fn unfold_3() {
+        // Unfolding ok spectra with folds is ok and removes the fold.
         let mut to = make_test_objs();
         make_some_params(&mut to);
 
-        assert_eq!(
-            SpectrumReply::Created,
-            to.processor.process_request(
-                SpectrumRequest::Create2D {
-                    name: String::from("test"),
-                    xparam: String::from("param.1"),
-                    yparam: String::from("param.2"),
-                    xaxis: AxisSpecification {
-                        low: 0.0,
-                        high: 1024.0,
-                        bins: 256
-                    },
-                    yaxis: AxisSpecification {
-                        low: 0.0,
-                        high: 1024.0,
-                        bins: 256
-                    }
-                },
-                &to.parameters,
-                &mut to.conditions,
-                &to.tracedb,
-            )
-        );
-
         let reply = to.processor.process_request(
-            SpectrumRequest::SetChan {
+            SpectrumRequest::CreateMulti1D {
                 name: String::from("test"),
-                xchan: 129,
-                ychan: None,
-                value: 12345.0,
+                params: vec![
+                    String::from("param.0"),
+                    String::from("param.1"),
+                    String::from("param.2"),
+                ],
+                axis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 1024,
+                },
             },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert!(matches!(reply, SpectrumReply::Error(_)));
-    }
-    #[test]
-    fn fold_1() {
-        let mut to = make_test_objs();
 
-        // Try to fold with no such spectrum:
-
-        to.conditions.insert(
-            String::from("true"),
-            Rc::new(RefCell::new(Box::new(True {}))),
-        );
+        assert_eq!(SpectrumReply::Created, reply);
+        let cond = conditions::cut::MultiCut::new(&[1, 2, 3], 100.0, 200.0);
+        to.conditions
+            .insert(String::from("slice"), Rc::new(RefCell::new(Box::new(cond))));
         let reply = to.processor.process_request(
             SpectrumRequest::Fold {
-                spectrum_name: String::from("junk"),
-                condition_name: String::from("true"),
+                spectrum_name: String::from("test"),
+                condition_name: String::from("slice"),
             },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert!(matches!(reply, SpectrumReply::Error(_)));
+        assert_eq!(SpectrumReply::Folded, reply);
+
+        let reply = to.processor.process_request(
+            SpectrumRequest::Unfold(String::from("test")),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Unfolded, reply);
+    }
+    // Tests for rebinning spectra:
+
+    fn rebin_request(name: &str, newname: &str, xgroup: u32, ygroup: u32) -> SpectrumRequest {
+        SpectrumRequest::Rebin {
+            name: String::from(name),
+            newname: String::from(newname),
+            xgroup,
+            ygroup,
+        }
     }
     #[test]
-    fn fold_2() {
-        // Have the spectrum (and it's even ok) but condition not defined is
-        // also an error:
+    fn rebin_1() {
+        // 1d spectrum - groups of channels are summed, flow bins stay
+        // flow bins.
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
         let reply = to.processor.process_request(
-            SpectrumRequest::CreateMulti1D {
+            SpectrumRequest::Create1D {
                 name: String::from("test"),
-                params: vec![
-                    String::from("param.0"),
-                    String::from("param.1"),
-                    String::from("param.2"),
-                ],
+                parameter: String::from("param.1"),
                 axis: AxisSpecification {
                     low: 0.0,
                     high: 1024.0,
@@ -5852,32 +8905,64 @@ mod spproc_tests {
             &to.tracedb,
         );
         assert_eq!(SpectrumReply::Created, reply);
+        let id = to.parameters.lookup("param.1").unwrap().get_id();
+        let events = vec![
+            vec![EventParameter::new(id, 0.0)],
+            vec![EventParameter::new(id, 1.0)],
+            vec![EventParameter::new(id, 3.0)],
+            vec![EventParameter::new(id, 4.0)],
+            vec![EventParameter::new(id, -1.0)],
+            vec![EventParameter::new(id, 2000.0)],
+        ];
         let reply = to.processor.process_request(
-            SpectrumRequest::Fold {
-                spectrum_name: String::from("test"),
-                condition_name: String::from("nosuch"),
-            },
+            SpectrumRequest::Events(events),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        assert_eq!(SpectrumReply::Processed, reply);
+
+        let reply = to.processor.process_request(
+            rebin_request("test", "rebinned", 4, 1),
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert!(matches!(reply, SpectrumReply::Error(_)));
+        assert_eq!(SpectrumReply::Created, reply);
+
+        let spec = to.processor.dict.get("rebinned").unwrap().0.borrow();
+        assert_eq!("1D", spec.get_type());
+        assert_eq!(vec![String::from("param.1")], spec.get_xparams());
+        assert_eq!(Some((0.0, 1024.0, 258)), spec.get_xaxis());
+        let h = spec.get_histogram_1d().unwrap();
+        let h = h.borrow();
+        assert_eq!(1.0, h.value_at_index(0).unwrap().get());
+        assert_eq!(3.0, h.value_at_index(1).unwrap().get());
+        assert_eq!(1.0, h.value_at_index(2).unwrap().get());
+        assert_eq!(1.0, h.value_at_index(257).unwrap().get());
+        let total: f64 = h.iter().map(|c| c.value.get()).sum();
+        assert_eq!(6.0, total);
     }
     #[test]
-    fn fold_3() {
-        // Wrong type of spectrum, right type of condition is also an error.
+    fn rebin_2() {
+        // 2d spectrum rebinned on both axes:
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
         let reply = to.processor.process_request(
-            // Ordinary 1d spectrum.
-            SpectrumRequest::Create1D {
+            SpectrumRequest::Create2D {
                 name: String::from("test"),
-                parameter: String::from("param.2"),
-                axis: AxisSpecification {
+                xparam: String::from("param.1"),
+                yparam: String::from("param.2"),
+                xaxis: AxisSpecification {
                     low: 0.0,
-                    high: 1024.0,
-                    bins: 1024,
+                    high: 16.0,
+                    bins: 16,
+                },
+                yaxis: AxisSpecification {
+                    low: 0.0,
+                    high: 8.0,
+                    bins: 8,
                 },
             },
             &to.parameters,
@@ -5885,78 +8970,66 @@ mod spproc_tests {
             &to.tracedb,
         );
         assert_eq!(SpectrumReply::Created, reply);
-
-        // MultiCut condition:
-
-        let cond = conditions::MultiCut::new(&[1, 2, 3, 4], 100.0, 200.0);
-        to.conditions
-            .insert(String::from("cut"), Rc::new(RefCell::new(Box::new(cond))));
-
+        let x = to.parameters.lookup("param.1").unwrap().get_id();
+        let y = to.parameters.lookup("param.2").unwrap().get_id();
+        let events = vec![
+            vec![EventParameter::new(x, 0.5), EventParameter::new(y, 0.5)],
+            vec![EventParameter::new(x, 3.5), EventParameter::new(y, 1.5)],
+            vec![EventParameter::new(x, 4.5), EventParameter::new(y, 7.5)],
+        ];
         let reply = to.processor.process_request(
-            SpectrumRequest::Fold {
-                spectrum_name: String::from("test"),
-                condition_name: String::from("cut"),
-            },
+            SpectrumRequest::Events(events),
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert!(matches!(reply, SpectrumReply::Error(_)));
-    }
-    #[test]
-    fn fold_4() {
-        // Right type of spectrum, wrong type of condition is an error.
+        assert_eq!(SpectrumReply::Processed, reply);
 
-        let mut to = make_test_objs();
-        make_some_params(&mut to);
         let reply = to.processor.process_request(
-            SpectrumRequest::CreateMulti1D {
-                name: String::from("test"),
-                params: vec![
-                    String::from("param.0"),
-                    String::from("param.1"),
-                    String::from("param.2"),
-                ],
-                axis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 1024,
-                },
-            },
+            rebin_request("test", "rebinned", 4, 2),
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
         assert_eq!(SpectrumReply::Created, reply);
 
-        let cond = conditions::True {};
-        to.conditions
-            .insert(String::from("true"), Rc::new(RefCell::new(Box::new(cond))));
         let reply = to.processor.process_request(
-            SpectrumRequest::Fold {
-                spectrum_name: String::from("test"),
-                condition_name: String::from("true"),
+            SpectrumRequest::GetContents {
+                name: String::from("rebinned"),
+                xlow: 0.0,
+                xhigh: 16.0,
+                ylow: 0.0,
+                yhigh: 8.0,
             },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert!(matches!(reply, SpectrumReply::Error(_)));
+        if let SpectrumReply::Contents(sc) = reply {
+            assert_eq!(2, sc.len());
+            assert_eq!((0.0, 0.0, 2.0), (sc[0].x, sc[0].y, sc[0].value));
+            assert_eq!((4.0, 6.0, 1.0), (sc[1].x, sc[1].y, sc[1].value));
+        } else {
+            panic!("Expected contents got {:?}", reply);
+        }
     }
     #[test]
-    fn fold_5() {
-        // Right type of spectrum and condition succeeds (multi1/multislice)
+    fn rebin_3() {
+        // The gate is preserved:
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
+        let id = to.parameters.lookup("param.1").unwrap().get_id();
+        to.conditions.insert(
+            String::from("cut"),
+            Rc::new(RefCell::new(Box::new(conditions::cut::Cut::new(
+                id, 100.0, 200.0,
+            )))),
+        );
         let reply = to.processor.process_request(
-            SpectrumRequest::CreateMulti1D {
+            SpectrumRequest::Create1D {
                 name: String::from("test"),
-                params: vec![
-                    String::from("param.0"),
-                    String::from("param.1"),
-                    String::from("param.2"),
-                ],
+                parameter: String::from("param.1"),
                 axis: AxisSpecification {
                     low: 0.0,
                     high: 1024.0,
@@ -5968,61 +9041,41 @@ mod spproc_tests {
             &to.tracedb,
         );
         assert_eq!(SpectrumReply::Created, reply);
-        let cond = conditions::cut::MultiCut::new(&[1, 2, 3], 100.0, 200.0);
-        to.conditions
-            .insert(String::from("slice"), Rc::new(RefCell::new(Box::new(cond))));
         let reply = to.processor.process_request(
-            SpectrumRequest::Fold {
-                spectrum_name: String::from("test"),
-                condition_name: String::from("slice"),
+            SpectrumRequest::Gate {
+                spectrum: String::from("test"),
+                gate: String::from("cut"),
             },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert_eq!(SpectrumReply::Folded, reply);
-
-        // Get the name of the fold via listing:
+        assert_eq!(SpectrumReply::Gated, reply);
 
-        let ls = to.processor.process_request(
-            SpectrumRequest::List(String::from("test")),
+        let reply = to.processor.process_request(
+            rebin_request("test", "rebinned", 2, 1),
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        if let SpectrumReply::Listing(l) = ls {
-            assert_eq!(1, l.len());
-            let props = l[0].clone();
-            assert!(props.fold.is_some());
-            assert_eq!("slice", props.fold.unwrap());
-        } else {
-            panic!("Incorrect reply from list_spectra");
-        }
+        assert_eq!(SpectrumReply::Created, reply);
+        let spec = to.processor.dict.get("rebinned").unwrap().0.borrow();
+        assert_eq!(Some(String::from("cut")), spec.get_gate());
     }
     #[test]
-    fn fold_6() {
-        // right type of spectrum and condition succeeds (multi2/multislice)
+    fn rebin_4() {
+        // Errors:
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
         let reply = to.processor.process_request(
-            SpectrumRequest::CreateMulti2D {
+            SpectrumRequest::Create1D {
                 name: String::from("test"),
-                params: vec![
-                    String::from("param.0"),
-                    String::from("param.1"),
-                    String::from("param.2"),
-                ],
-
-                xaxis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 256,
-                },
-                yaxis: AxisSpecification {
+                parameter: String::from("param.1"),
+                axis: AxisSpecification {
                     low: 0.0,
                     high: 1024.0,
-                    bins: 256,
+                    bins: 1000,
                 },
             },
             &to.parameters,
@@ -6030,92 +9083,115 @@ mod spproc_tests {
             &to.tracedb,
         );
         assert_eq!(SpectrumReply::Created, reply);
-        let cond = conditions::cut::MultiCut::new(&[1, 2, 3], 100.0, 200.0);
-        to.conditions
-            .insert(String::from("slice"), Rc::new(RefCell::new(Box::new(cond))));
+
+        for (name, newname, xgroup, ygroup) in [
+            ("nosuch", "rebinned", 2, 1), // No such spectrum.
+            ("test", "rebinned", 0, 1),   // Zero group.
+            ("test", "rebinned", 1, 1),   // Nothing rebinned.
+            ("test", "rebinned", 3, 1),   // Not a multiple.
+            ("test", "rebinned", 2, 2),   // No y axis.
+            ("test", "test", 2, 1),       // Duplicate name.
+        ] {
+            let reply = to.processor.process_request(
+                rebin_request(name, newname, xgroup, ygroup),
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            );
+            assert!(matches!(reply, SpectrumReply::Error(_)), "{:?}", reply);
+            assert!(!to.processor.dict.exists("rebinned"));
+        }
+    }
+    // Tests for combining spectra:
+
+    fn make_1d(to: &mut TestObjects, name: &str, param: &str, bins: u32) {
         let reply = to.processor.process_request(
-            SpectrumRequest::Fold {
-                spectrum_name: String::from("test"),
-                condition_name: String::from("slice"),
+            SpectrumRequest::Create1D {
+                name: String::from(name),
+                parameter: String::from(param),
+                axis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins,
+                },
             },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert_eq!(SpectrumReply::Folded, reply);
+        assert_eq!(SpectrumReply::Created, reply);
     }
     #[test]
-    fn fold_7() {
-        // right type of spectrum and condition succeeds (multi1/multicontour)
+    fn combine_1() {
+        // Subtract one spectrum from another:
+
         let mut to = make_test_objs();
         make_some_params(&mut to);
+        make_1d(&mut to, "signal", "param.1", 1024);
+        make_1d(&mut to, "background", "param.2", 1024);
+        let p1 = to.parameters.lookup("param.1").unwrap().get_id();
+        let p2 = to.parameters.lookup("param.2").unwrap().get_id();
+        let events = vec![
+            vec![EventParameter::new(p1, 10.0), EventParameter::new(p2, 10.0)],
+            vec![EventParameter::new(p1, 10.0), EventParameter::new(p2, 20.0)],
+            vec![EventParameter::new(p1, 10.0)],
+            vec![EventParameter::new(p1, 2000.0)],
+        ];
         let reply = to.processor.process_request(
-            SpectrumRequest::CreateMulti1D {
-                name: String::from("test"),
-                params: vec![
-                    String::from("param.0"),
-                    String::from("param.1"),
-                    String::from("param.2"),
-                ],
-                axis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 1024,
-                },
-            },
+            SpectrumRequest::Events(events),
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert_eq!(SpectrumReply::Created, reply);
-        let cond = conditions::MultiContour::new(
-            &[1, 2, 3],
-            vec![
-                conditions::twod::Point::new(0.0, 0.0),
-                conditions::twod::Point::new(100.0, 0.0),
-                conditions::twod::Point::new(50.0, 100.0),
-            ],
-        )
-        .expect("Making multicontour");
-        to.conditions.insert(
-            String::from("contour"),
-            Rc::new(RefCell::new(Box::new(cond))),
-        );
+        assert_eq!(SpectrumReply::Processed, reply);
+
         let reply = to.processor.process_request(
-            SpectrumRequest::Fold {
-                spectrum_name: String::from("test"),
-                condition_name: String::from("contour"),
+            SpectrumRequest::Combine {
+                name: String::from("difference"),
+                terms: vec![
+                    (String::from("signal"), 1.0),
+                    (String::from("background"), -1.0),
+                ],
             },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert_eq!(SpectrumReply::Folded, reply);
+        assert_eq!(SpectrumReply::Created, reply);
+
+        let spec = to.processor.dict.get("difference").unwrap().0.borrow();
+        assert_eq!("1D", spec.get_type());
+        assert_eq!(vec![String::from("param.1")], spec.get_xparams());
+        assert_eq!(Some((0.0, 1024.0, 1026)), spec.get_xaxis());
+        assert!(spec.get_gate().is_none());
+        let h = spec.get_histogram_1d().unwrap();
+        let h = h.borrow();
+        assert_eq!(2.0, h.value_at_index(11).unwrap().get());
+        assert_eq!(-1.0, h.value_at_index(21).unwrap().get());
+        assert_eq!(1.0, h.value_at_index(1025).unwrap().get());
+        let total: f64 = h.iter().map(|c| c.value.get()).sum();
+        assert_eq!(2.0, total);
     }
     #[test]
-    fn fold_8() {
-        // right type of spectrum and condition succeeds (multi2/multicontour)
+    fn combine_2() {
+        // Scale a single 2d spectrum:
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
         let reply = to.processor.process_request(
-            SpectrumRequest::CreateMulti2D {
+            SpectrumRequest::Create2D {
                 name: String::from("test"),
-                params: vec![
-                    String::from("param.0"),
-                    String::from("param.1"),
-                    String::from("param.2"),
-                ],
-
+                xparam: String::from("param.1"),
+                yparam: String::from("param.2"),
                 xaxis: AxisSpecification {
                     low: 0.0,
-                    high: 1024.0,
-                    bins: 256,
+                    high: 16.0,
+                    bins: 16,
                 },
                 yaxis: AxisSpecification {
                     low: 0.0,
-                    high: 1024.0,
-                    bins: 256,
+                    high: 8.0,
+                    bins: 8,
                 },
             },
             &to.parameters,
@@ -6123,159 +9199,213 @@ mod spproc_tests {
             &to.tracedb,
         );
         assert_eq!(SpectrumReply::Created, reply);
-        let cond = conditions::MultiContour::new(
-            &[1, 2, 3],
-            vec![
-                conditions::twod::Point::new(0.0, 0.0),
-                conditions::twod::Point::new(100.0, 0.0),
-                conditions::twod::Point::new(50.0, 100.0),
-            ],
-        )
-        .expect("Making multicontour");
-        to.conditions.insert(
-            String::from("contour"),
-            Rc::new(RefCell::new(Box::new(cond))),
+        let x = to.parameters.lookup("param.1").unwrap().get_id();
+        let y = to.parameters.lookup("param.2").unwrap().get_id();
+        let events = vec![
+            vec![EventParameter::new(x, 3.5), EventParameter::new(y, 1.5)],
+            vec![EventParameter::new(x, 3.5), EventParameter::new(y, 1.5)],
+        ];
+        let reply = to.processor.process_request(
+            SpectrumRequest::Events(events),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
         );
+        assert_eq!(SpectrumReply::Processed, reply);
+
         let reply = to.processor.process_request(
-            SpectrumRequest::Fold {
-                spectrum_name: String::from("test"),
-                condition_name: String::from("contour"),
+            SpectrumRequest::Combine {
+                name: String::from("scaled"),
+                terms: vec![(String::from("test"), 0.5)],
             },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert_eq!(SpectrumReply::Folded, reply);
-    }
-    // unfold tests
-
-    #[test]
-    fn unfold_0() {
-        // Cannot unfold a nonexistent spectrum.
+        assert_eq!(SpectrumReply::Created, reply);
 
-        let mut to = make_test_objs();
         let reply = to.processor.process_request(
-            SpectrumRequest::Unfold(String::from("junk")),
+            SpectrumRequest::GetContents {
+                name: String::from("scaled"),
+                xlow: 0.0,
+                xhigh: 16.0,
+                ylow: 0.0,
+                yhigh: 8.0,
+            },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert!(matches!(reply, SpectrumReply::Error(_)));
+        if let SpectrumReply::Contents(sc) = reply {
+            assert_eq!(1, sc.len());
+            assert_eq!((3.0, 1.0, 1.0), (sc[0].x, sc[0].y, sc[0].value));
+        } else {
+            panic!("Expected contents got {:?}", reply);
+        }
+    }
+    #[test]
+    fn combine_3() {
+        // Errors:
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        make_1d(&mut to, "a", "param.1", 1024);
+        make_1d(&mut to, "b", "param.2", 512);
+        make_1d(&mut to, "c", "param.3", 1024);
+
+        for (name, terms) in [
+            ("sum", vec![]),              // Nothing to combine.
+            ("sum", vec!["a", "nosuch"]), // No such spectrum.
+            ("sum", vec!["a", "b"]),      // Different axes.
+            ("c", vec!["a", "c"]),        // Duplicate name.
+        ] {
+            let reply = to.processor.process_request(
+                SpectrumRequest::Combine {
+                    name: String::from(name),
+                    terms: terms.iter().map(|s| (String::from(*s), 1.0)).collect(),
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            );
+            assert!(matches!(reply, SpectrumReply::Error(_)), "{:?}", reply);
+            assert!(!to.processor.dict.exists("sum"));
+        }
     }
+    // Tests for copying spectra:
+
     #[test]
-    fn unfold_1() {
-        // Cannot unfold 'ordinary spectra'
+    fn copy_1() {
+        // Copy definition, gate and contents:
 
         let mut to = make_test_objs();
         make_some_params(&mut to);
-
+        make_1d(&mut to, "test", "param.1", 1024);
+        let id = to.parameters.lookup("param.1").unwrap().get_id();
+        to.conditions.insert(
+            String::from("cut"),
+            Rc::new(RefCell::new(Box::new(conditions::cut::Cut::new(
+                id, 0.0, 100.0,
+            )))),
+        );
         let reply = to.processor.process_request(
-            SpectrumRequest::Create1D {
-                name: String::from("test"),
-                parameter: String::from("param.0"),
-                axis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 1024,
-                },
+            SpectrumRequest::Gate {
+                spectrum: String::from("test"),
+                gate: String::from("cut"),
             },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert_eq!(SpectrumReply::Created, reply);
-
+        assert_eq!(SpectrumReply::Gated, reply);
         let reply = to.processor.process_request(
-            SpectrumRequest::Unfold(String::from("test")),
+            SpectrumRequest::Events(vec![
+                vec![EventParameter::new(id, 10.0)],
+                vec![EventParameter::new(id, 10.0)],
+            ]),
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert!(matches!(reply, SpectrumReply::Error(_)));
-    }
-    #[test]
-    fn unfold_2() {
-        // Unfolding ok spectra without folds is ok.
-
-        let mut to = make_test_objs();
-        make_some_params(&mut to);
+        assert_eq!(SpectrumReply::Processed, reply);
 
         let reply = to.processor.process_request(
-            SpectrumRequest::CreateMulti1D {
+            SpectrumRequest::Copy {
                 name: String::from("test"),
-                params: vec![
-                    String::from("param.0"),
-                    String::from("param.1"),
-                    String::from("param.2"),
-                ],
-                axis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 1024,
-                },
+                newname: String::from("copy"),
+                contents: true,
             },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-
         assert_eq!(SpectrumReply::Created, reply);
+        {
+            let spec = to.processor.dict.get("copy").unwrap().0.borrow();
+            assert_eq!("1D", spec.get_type());
+            assert_eq!(vec![String::from("param.1")], spec.get_xparams());
+            assert_eq!(Some((0.0, 1024.0, 1026)), spec.get_xaxis());
+            assert_eq!(Some(String::from("cut")), spec.get_gate());
+            let h = spec.get_histogram_1d().unwrap();
+            assert_eq!(2.0, h.borrow().value_at_index(11).unwrap().get());
+        }
+
+        // The copy increments like the original:
 
         let reply = to.processor.process_request(
-            SpectrumRequest::Unfold(String::from("test")),
+            SpectrumRequest::Events(vec![
+                vec![EventParameter::new(id, 10.0)],
+                vec![EventParameter::new(id, 200.0)],
+            ]),
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert_eq!(SpectrumReply::Unfolded, reply);
+        assert_eq!(SpectrumReply::Processed, reply);
+        let spec = to.processor.dict.get("copy").unwrap().0.borrow();
+        let h = spec.get_histogram_1d().unwrap();
+        let total: f64 = h.borrow().iter().map(|c| c.value.get()).sum();
+        assert_eq!(3.0, total);
     }
     #[test]
-    fn unfold_3() {
-        // Unfolding ok spectra with folds is ok and removes the fold.
+    fn copy_2() {
+        // Copy only the definition:
+
         let mut to = make_test_objs();
         make_some_params(&mut to);
-
+        make_1d(&mut to, "test", "param.1", 512);
+        let id = to.parameters.lookup("param.1").unwrap().get_id();
         let reply = to.processor.process_request(
-            SpectrumRequest::CreateMulti1D {
-                name: String::from("test"),
-                params: vec![
-                    String::from("param.0"),
-                    String::from("param.1"),
-                    String::from("param.2"),
-                ],
-                axis: AxisSpecification {
-                    low: 0.0,
-                    high: 1024.0,
-                    bins: 1024,
-                },
-            },
+            SpectrumRequest::Events(vec![vec![EventParameter::new(id, 10.0)]]),
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
+        assert_eq!(SpectrumReply::Processed, reply);
 
-        assert_eq!(SpectrumReply::Created, reply);
-        let cond = conditions::cut::MultiCut::new(&[1, 2, 3], 100.0, 200.0);
-        to.conditions
-            .insert(String::from("slice"), Rc::new(RefCell::new(Box::new(cond))));
         let reply = to.processor.process_request(
-            SpectrumRequest::Fold {
-                spectrum_name: String::from("test"),
-                condition_name: String::from("slice"),
+            SpectrumRequest::Copy {
+                name: String::from("test"),
+                newname: String::from("copy"),
+                contents: false,
             },
             &to.parameters,
             &mut to.conditions,
             &to.tracedb,
         );
-        assert_eq!(SpectrumReply::Folded, reply);
+        assert_eq!(SpectrumReply::Created, reply);
+        let spec = to.processor.dict.get("copy").unwrap().0.borrow();
+        assert_eq!(Some((0.0, 1024.0, 514)), spec.get_xaxis());
+        assert!(spec.get_gate().is_none());
+        let h = spec.get_histogram_1d().unwrap();
+        let total: f64 = h.borrow().iter().map(|c| c.value.get()).sum();
+        assert_eq!(0.0, total);
+    }
+    #[test]
+    fn copy_3() {
+        // Errors:
 
-        let reply = to.processor.process_request(
-            SpectrumRequest::Unfold(String::from("test")),
-            &to.parameters,
-            &mut to.conditions,
-            &to.tracedb,
-        );
-        assert_eq!(SpectrumReply::Unfolded, reply);
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        make_1d(&mut to, "test", "param.1", 512);
+        make_1d(&mut to, "other", "param.2", 512);
+
+        for (name, newname) in [("nosuch", "copy"), ("test", "other")] {
+            let reply = to.processor.process_request(
+                SpectrumRequest::Copy {
+                    name: String::from(name),
+                    newname: String::from(newname),
+                    contents: true,
+                },
+                &to.parameters,
+                &mut to.conditions,
+                &to.tracedb,
+            );
+            assert!(matches!(reply, SpectrumReply::Error(_)), "{:?}", reply);
+        }
+        assert!(!to.processor.dict.exists("copy"));
+        let spec = to.processor.dict.get("other").unwrap().0.borrow();
+        assert_eq!(vec![String::from("param.2")], spec.get_xparams());
     }
 }
 #[cfg(test)]
@@ -6520,6 +9650,15 @@ mod reqstruct_tests {
         let req = SpectrumMessageClient::events_request(&events);
         assert_eq!(SpectrumRequest::Events(events), req);
     }
+    #[test]
+    fn process_2() {
+        let events = vec![vec![
+            EventParameter::new(1, 2.0),
+            EventParameter::new(7, 100.),
+        ]];
+        let req = SpectrumMessageClient::weighted_events_request(&events, 10.0);
+        assert_eq!(SpectrumRequest::WeightedEvents(events, 10.0), req);
+    }
 }
 #[cfg(test)]
 mod spectrum_api_tests {
@@ -7059,6 +10198,49 @@ mod spectrum_api_tests {
         stop_server(jh, send);
     }
     #[test]
+    fn get_multi_contents_1() {
+        let (jh, send) = start_server();
+        let api = SpectrumMessageClient::new(&send);
+
+        api.create_spectrum_1d("test1", "param.1", 0.0, 1024.0, 1024)
+            .expect("Failed to make spectrum");
+        api.create_spectrum_1d("test2", "param.2", 0.0, 1024.0, 1024)
+            .expect("Failed to make spectrum");
+
+        let events = vec![
+            vec![
+                parameters::EventParameter::new(2, 100.0),
+                parameters::EventParameter::new(3, 200.0),
+            ],
+            vec![parameters::EventParameter::new(2, 100.0)],
+        ];
+        api.process_events(&events)
+            .expect("Failed to process events");
+
+        let contents = api
+            .get_multi_contents(&[String::from("test2"), String::from("test1")])
+            .expect("Unable to get contents");
+        assert_eq!(2, contents.len());
+
+        assert_eq!("test2", contents[0].0);
+        assert_eq!(1, contents[0].1.len());
+        assert_eq!(200.0, contents[0].1[0].x);
+        assert_eq!(1.0, contents[0].1[0].value);
+
+        assert_eq!("test1", contents[1].0);
+        assert_eq!(1, contents[1].1.len());
+        assert_eq!(100.0, contents[1].1[0].x);
+        assert_eq!(2.0, contents[1].1[0].value);
+
+        // Any nonexistent spectrum fails the whole thing:
+
+        assert!(api
+            .get_multi_contents(&[String::from("test1"), String::from("junk")])
+            .is_err());
+
+        stop_server(jh, send);
+    }
+    #[test]
     fn event_1() {
         // only way to put non-zero contents in spectra
         // is via events.
@@ -7146,6 +10328,42 @@ mod spectrum_api_tests {
         stop_server(jh, send);
     }
     #[test]
+    fn clear_2() {
+        // Clearing with gate statistics resets the statistics of the
+        // spectrum's gate:
+
+        let (jh, send) = start_server();
+        let api = SpectrumMessageClient::new(&send);
+
+        api.create_spectrum_1d("test", "param.1", 0.0, 1024.0, 1024)
+            .expect("Failed to make spectrum");
+        api.gate_spectrum("test", "multicut")
+            .expect("Failed to gate spectrum");
+        let events = vec![vec![parameters::EventParameter::new(2, 100.0)]; 6];
+        api.process_events(&events)
+            .expect("Failed to process events");
+
+        let stats = api
+            .get_gate_statistics("multi*")
+            .expect("Getting gate statistics");
+        assert_eq!(1, stats.len());
+        assert_eq!("multicut", stats[0].0);
+        assert_eq!(6, stats[0].1.checked);
+
+        api.clear_spectra_and_gate_statistics("test")
+            .expect("Failed to request clear");
+        let stats = api
+            .get_gate_statistics("multicut")
+            .expect("Getting gate statistics");
+        assert_eq!(GateStatistics::default(), stats[0].1);
+        let contents = api
+            .get_contents("test", 0.0, 1024.0, 0.0, 0.0)
+            .expect("Unable to get spectrumcontents");
+        assert_eq!(0, contents.len());
+
+        stop_server(jh, send);
+    }
+    #[test]
     fn getstats_1() {
         let (jh, send) = start_server();
         let api = SpectrumMessageClient::new(&send);
@@ -7286,6 +10504,30 @@ mod spectrum_api_tests {
         stop_server(jh, send);
     }
     #[test]
+    fn set_roi_1() {
+        let (jh, send) = start_server();
+        let api = SpectrumMessageClient::new(&send);
+
+        api.create_spectrum_2d(
+            "test", "param.1", "param.2", 0.0, 1024.0, 256, 0.0, 1024.0, 256,
+        )
+        .expect("Making spectrum");
+
+        assert_eq!(
+            100,
+            api.set_roi("test", (0, 9), Some((100, 109)), 3.0)
+                .expect("Setting region")
+        );
+        assert_eq!(
+            3.0,
+            api.get_channel_value("test", 9, Some(109))
+                .expect("Getting value")
+        );
+        assert!(api.set_roi("test", (0, 9), None, 3.0).is_err());
+
+        stop_server(jh, send);
+    }
+    #[test]
     fn fold_1() {
         // Correctly folding a spectrum.
 
@@ -7377,6 +10619,32 @@ mod spectrum_api_tests {
 
         assert!(sapi.unfold_spectrum("test").is_err());
 
+        stop_server(jh, send);
+    }
+    #[test]
+    fn temporary_1() {
+        let (jh, send) = start_server();
+        let sapi = SpectrumMessageClient::new(&send);
+
+        assert!(sapi
+            .create_spectrum_1d("test", "param.0", 0.0, 1024.0, 1024)
+            .is_ok());
+        assert!(sapi
+            .set_temporary("test", true, Some(Duration::from_secs(60)))
+            .is_ok());
+        assert!(sapi.set_temporary("nosuch", true, None).is_err());
+
+        let l = sapi.list_temporary("*").expect("Listing");
+        assert_eq!(1, l.len());
+        assert_eq!("test", l[0].name);
+        assert_eq!(Some(Duration::from_secs(60)), l[0].idle_limit);
+        assert!(sapi.list_temporary("[").is_err());
+
+        // Deleting the spectrum forgets it's temporary:
+
+        assert!(sapi.delete_spectrum("test").is_ok());
+        assert!(sapi.list_temporary("*").expect("Listing").is_empty());
+
         stop_server(jh, send);
     }
 }
@@ -7497,4 +10765,146 @@ mod spectrum_trace_tests {
             }
         );
     }
+    #[test]
+    fn fold_1() {
+        // Folding and unfolding a spectrum fire traces:
+
+        let mut to = make_test_objs();
+        make_some_params(&mut to);
+        to.processor.process_request(
+            SpectrumRequest::CreateMulti1D {
+                name: String::from("test"),
+                params: vec![String::from("param.0"), String::from("param.1")],
+                axis: AxisSpecification {
+                    low: 0.0,
+                    high: 1024.0,
+                    bins: 1024,
+                },
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        let cond = cut::MultiCut::new(&[0, 1], 100.0, 200.0);
+        to.conditions
+            .insert(String::from("slice"), Rc::new(RefCell::new(Box::new(cond))));
+
+        let token = to.tracedb.new_client(Duration::from_secs(100));
+        to.processor.process_request(
+            SpectrumRequest::Fold {
+                spectrum_name: String::from("test"),
+                condition_name: String::from("slice"),
+            },
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        to.processor.process_request(
+            SpectrumRequest::Unfold(String::from("test")),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+        // A failed unfold does not fire:
+
+        to.processor.process_request(
+            SpectrumRequest::Unfold(String::from("nosuch")),
+            &to.parameters,
+            &mut to.conditions,
+            &to.tracedb,
+        );
+
+        let traces = to.tracedb.get_traces(token).expect("Fetching traces.");
+        assert_eq!(2, traces.len());
+        assert!(if let trace::TraceEvent::FoldApplied {
+            spectrum,
+            condition,
+        } = traces[0].event()
+        {
+            assert_eq!("test", spectrum);
+            assert_eq!("slice", condition);
+            true
+        } else {
+            false
+        });
+        assert!(
+            if let trace::TraceEvent::FoldRemoved(name) = traces[1].event() {
+                assert_eq!("test", name);
+                true
+            } else {
+                false
+            }
+        );
+    }
+}
+#[cfg(test)]
+mod conversion_tests {
+    use super::*;
+
+    fn axis() -> AxisSpecification {
+        // 10 regular bins of width 1.0 from 100.0:
+
+        AxisSpecification {
+            low: 100.0,
+            high: 110.0,
+            bins: 12,
+        }
+    }
+    #[test]
+    fn bins_1() {
+        assert_eq!(10, regular_bins(axis()));
+        assert_eq!(1.0, bin_width(axis()));
+        let empty = AxisSpecification {
+            low: 0.0,
+            high: 1.0,
+            bins: 1,
+        };
+        assert_eq!(0, regular_bins(empty));
+    }
+    #[test]
+    fn coord_to_bin_1() {
+        // Regular bins are offset by the underflow and the axis low:
+
+        assert_eq!(1, coord_to_bin(100.0, axis()));
+        assert_eq!(1, coord_to_bin(100.5, axis()));
+        assert_eq!(6, coord_to_bin(105.0, axis()));
+        assert_eq!(10, coord_to_bin(109.999, axis()));
+        let below_high = f64::from_bits(110.0_f64.to_bits() - 1);
+        assert_eq!(10, coord_to_bin(below_high, axis()));
+    }
+    #[test]
+    fn coord_to_bin_2() {
+        // Under and overflows:
+
+        assert_eq!(0, coord_to_bin(99.999, axis()));
+        assert_eq!(0, coord_to_bin(-5.0, axis()));
+        assert_eq!(11, coord_to_bin(110.0, axis()));
+        assert_eq!(11, coord_to_bin(1000.0, axis()));
+    }
+    #[test]
+    fn bin_to_coord_1() {
+        assert_eq!(100.0, bin_to_coord(1, axis()));
+        assert_eq!(105.0, bin_to_coord(6, axis()));
+        assert_eq!(109.0, bin_to_coord(10, axis()));
+        assert_eq!(99.0, bin_to_coord(0, axis()));
+        assert_eq!(111.0, bin_to_coord(11, axis()));
+        assert_eq!(111.0, bin_to_coord(100, axis()));
+    }
+    #[test]
+    fn round_trip_1() {
+        // Each bin's low edge is in that bin for awkward bin widths:
+
+        for (low, high, bins) in [(0.0, 1.0, 12), (-1.0, 2.0, 1026), (0.3, 0.7, 7)] {
+            let a = AxisSpecification { low, high, bins };
+            for bin in 0..bins {
+                assert_eq!(
+                    bin,
+                    coord_to_bin(bin_to_coord(bin, a), a),
+                    "{:?} bin {}",
+                    a,
+                    bin
+                );
+            }
+        }
+    }
 }
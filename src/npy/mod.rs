@@ -0,0 +1,237 @@
+//!  This module writes spectrum contents as NumPy .npy files so that
+//!  Python users can load them losslessly with numpy.load rather than
+//!  parsing ASCII.  See
+//!  <https://numpy.org/doc/stable/reference/generated/numpy.lib.format.html>
+//!  for the file format; we write version 1.0 files.
+//!
+//!  A .npy file holds a single array:
+//!
+//!  *  1-d spectra are written with shape (xbins,).
+//!  *  2-d spectra are written with shape (ybins, xbins) so that
+//!     array\[y, x\] is the channel at (x, y).
+//!
+//!  The element type is little endian unsigned 64 bit integers.  Bin
+//!  counts include the underflow and overflow bins.  Index 0 on each
+//!  axis is the underflow bin and the last index the overflow bin, just
+//!  as for the x_bin/y_bin values of the JSON spectrum format.
+//!
+//!  Since the array can't carry the axis definitions, they are written
+//!  to a JSON sidecar file (see NpyMetadata and write_metadata).
+//!
+use crate::rest::spectrumio::{SpectrumFileData, SpectrumProperties};
+use rocket::serde::json;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+const HEADER_ALIGNMENT: usize = 64;
+
+/// The contents of the JSON sidecar file.  The definition is the
+/// spectrum definition as written in JSON spectrum files; shape is the
+/// shape of the array in the .npy file.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NpyMetadata {
+    pub definition: SpectrumProperties,
+    pub shape: Vec<usize>,
+}
+
+// The number of x and y bins; y is None for 1-d spectra.
+// Summary spectra have one x bin per parameter (plus under/overflow).
+
+fn dimensions(d: &SpectrumProperties) -> (usize, Option<usize>) {
+    let xbins = if d.type_string == "s" || d.type_string == "gs" {
+        d.x_parameters.len() + 2
+    } else {
+        d.x_axis.map(|a| a.2 as usize).unwrap_or(1)
+    };
+    (xbins, d.y_axis.map(|a| a.2 as usize))
+}
+
+/// The shape of the array a spectrum is written as.
+pub fn shape(d: &SpectrumProperties) -> Vec<usize> {
+    match dimensions(d) {
+        (x, None) => vec![x],
+        (x, Some(y)) => vec![y, x],
+    }
+}
+
+// Produce the .npy header: magic, version, header length and the
+// header dict padded with spaces and terminated with a newline so that
+// the data are aligned.
+
+fn header(shape: &[usize]) -> Vec<u8> {
+    let shape = match shape.len() {
+        1 => format!("({},)", shape[0]),
+        _ => format!(
+            "({})",
+            shape
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+    };
+    let mut dict = format!(
+        "{{'descr': '<u8', 'fortran_order': False, 'shape': {}, }}",
+        shape
+    );
+    let prefix = MAGIC.len() + 2 + 2; // version and header length.
+    let total = prefix + dict.len() + 1;
+    let padding = (HEADER_ALIGNMENT - total % HEADER_ALIGNMENT) % HEADER_ALIGNMENT;
+    dict.push_str(&" ".repeat(padding));
+    dict.push('\n');
+
+    let mut result = Vec::with_capacity(prefix + dict.len());
+    result.extend_from_slice(MAGIC);
+    result.extend_from_slice(&[1, 0]);
+    result.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+    result.extend_from_slice(dict.as_bytes());
+    result
+}
+
+/// Write a spectrum as a .npy file to anything that supports the Write
+/// trait.  Channels that lie outside the array (which should not
+/// happen) are ignored.
+pub fn write_spectrum(fd: &mut dyn Write, spectrum: &SpectrumFileData) -> Result<(), String> {
+    let (xbins, ybins) = dimensions(&spectrum.definition);
+    let ybins = ybins.unwrap_or(1);
+    let mut data = vec![0_u64; xbins * ybins];
+    for c in spectrum.channels.iter() {
+        if c.x_bin < xbins && c.y_bin < ybins {
+            data[c.y_bin * xbins + c.x_bin] = c.value;
+        }
+    }
+
+    let mut bytes = header(&shape(&spectrum.definition));
+    bytes.reserve(data.len() * 8);
+    for v in data {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+    fd.write_all(&bytes)
+        .map_err(|e| format!("Failed to write {}: {}", spectrum.definition.name, e))
+}
+
+/// Write the JSON sidecar file contents for a spectrum.
+pub fn write_metadata(fd: &mut dyn Write, spectrum: &SpectrumFileData) -> Result<(), String> {
+    let metadata = NpyMetadata {
+        definition: spectrum.definition.clone(),
+        shape: shape(&spectrum.definition),
+    };
+    let json = json::to_pretty_string(&metadata).map_err(|e| e.to_string())?;
+    fd.write_all(json.as_bytes())
+        .map_err(|e| format!("Failed to write axis metadata: {}", e))
+}
+
+#[cfg(test)]
+mod npy_tests {
+    use super::*;
+    use crate::messaging::spectrum_messages::ChannelType;
+    use crate::rest::spectrumio::SpectrumChannel;
+
+    fn oned() -> SpectrumFileData {
+        SpectrumFileData {
+            definition: SpectrumProperties {
+                name: String::from("oned"),
+                type_string: String::from("1"),
+                x_parameters: vec![String::from("p")],
+                y_parameters: vec![],
+                x_axis: Some((0.0, 4.0, 6)),
+                y_axis: None,
+                gate: None,
+                fold: None,
+            },
+            channels: vec![
+                SpectrumChannel {
+                    chan_type: ChannelType::Bin,
+                    x_coord: 1.0,
+                    y_coord: 0.0,
+                    x_bin: 2,
+                    y_bin: 0,
+                    value: 10,
+                },
+                SpectrumChannel {
+                    chan_type: ChannelType::Overflow,
+                    x_coord: 4.0,
+                    y_coord: 0.0,
+                    x_bin: 5,
+                    y_bin: 0,
+                    value: 3,
+                },
+            ],
+        }
+    }
+    fn twod() -> SpectrumFileData {
+        let mut result = oned();
+        result.definition.type_string = String::from("2");
+        result.definition.y_parameters = vec![String::from("q")];
+        result.definition.y_axis = Some((0.0, 2.0, 4));
+        result.channels = vec![SpectrumChannel {
+            chan_type: ChannelType::Bin,
+            x_coord: 1.0,
+            y_coord: 1.0,
+            x_bin: 2,
+            y_bin: 3,
+            value: 7,
+        }];
+        result
+    }
+    // Split a file into its header dict and data values:
+
+    fn parse(bytes: &[u8]) -> (String, Vec<u64>) {
+        assert_eq!(MAGIC, &bytes[0..6]);
+        assert_eq!(&[1, 0], &bytes[6..8]);
+        let hlen = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!(0, (10 + hlen) % HEADER_ALIGNMENT);
+        let dict = String::from_utf8(bytes[10..10 + hlen].to_vec()).unwrap();
+        assert!(dict.ends_with('\n'));
+        let data = bytes[10 + hlen..]
+            .chunks(8)
+            .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        (dict, data)
+    }
+
+    #[test]
+    fn shape_1() {
+        assert_eq!(vec![6], shape(&oned().definition));
+        assert_eq!(vec![4, 6], shape(&twod().definition));
+
+        let mut summary = twod();
+        summary.definition.type_string = String::from("s");
+        summary.definition.x_parameters = vec![String::from("a"), String::from("b")];
+        summary.definition.x_axis = None;
+        assert_eq!(vec![4, 4], shape(&summary.definition));
+    }
+    #[test]
+    fn write_1() {
+        // 1-d spectrum:
+
+        let mut bytes = Vec::<u8>::new();
+        write_spectrum(&mut bytes, &oned()).expect("Writing");
+        let (dict, data) = parse(&bytes);
+        assert!(dict.starts_with("{'descr': '<u8', 'fortran_order': False, 'shape': (6,), }"));
+        assert_eq!(vec![0, 0, 10, 0, 0, 3], data);
+    }
+    #[test]
+    fn write_2() {
+        // 2-d spectra are row major in y:
+
+        let mut bytes = Vec::<u8>::new();
+        write_spectrum(&mut bytes, &twod()).expect("Writing");
+        let (dict, data) = parse(&bytes);
+        assert!(dict.contains("'shape': (4, 6)"));
+        assert_eq!(24, data.len());
+        assert_eq!(7, data[3 * 6 + 2]);
+        assert_eq!(7, data.iter().sum::<u64>());
+    }
+    #[test]
+    fn metadata_1() {
+        let mut bytes = Vec::<u8>::new();
+        write_metadata(&mut bytes, &twod()).expect("Writing");
+        let metadata: NpyMetadata =
+            json::from_str(std::str::from_utf8(&bytes).unwrap()).expect("Parsing");
+        assert_eq!(vec![4, 6], metadata.shape);
+        assert_eq!("oned", metadata.definition.name);
+        assert_eq!(Some((0.0, 2.0, 4)), metadata.definition.y_axis);
+    }
+}
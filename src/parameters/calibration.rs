@@ -0,0 +1,268 @@
+//!  Calibrated parameters are parameters computed, for each event, by
+//!  applying a calibration to a raw parameter:
+//!
+//! ```text
+//!  calibrated = offset + slope*raw + quadratic*raw*raw
+//! ```
+//!
+//!  The quadratic term is optional (zero) so most calibrations are
+//!  linear.  Unlike the (gain, offset) calibration of a parameter (see
+//!  Parameter::set_calibration), which replaces the raw values, the raw
+//!  parameter is left alone and the calibrated values are a new parameter
+//!  that can be histogrammed alongside it.
+//!
+//!  A calibrated parameter is only computed for events that have its raw
+//!  parameter and then only if the result is finite.  Calibrated
+//!  parameters are computed in the order in which they were defined,
+//!  after parameter calibrations are applied and before pseudo
+//!  parameters are computed.  A calibrated parameter can therefore be
+//!  calibrated from one defined before it and pseudos can use them.
+//!
+use super::{Event, EventParameter};
+use std::collections::HashMap;
+use std::slice::Iter;
+
+/// The coefficients of a calibration.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Calibration {
+    pub offset: f64,
+    pub slope: f64,
+    pub quadratic: f64,
+}
+
+impl Calibration {
+    pub fn new(offset: f64, slope: f64, quadratic: f64) -> Calibration {
+        Calibration {
+            offset,
+            slope,
+            quadratic,
+        }
+    }
+    /// Calibrate a raw value.
+    pub fn apply(&self, raw: f64) -> f64 {
+        self.offset + self.slope * raw + self.quadratic * raw * raw
+    }
+}
+
+/// A calibrated parameter definition.  The definition computes the
+/// value of the parameter with the same name from the raw parameter.
+#[derive(Clone, Debug)]
+pub struct CalibratedParameter {
+    name: String,
+    id: u32,
+    raw: String,
+    raw_id: u32,
+    calibration: Calibration,
+    units: Option<String>,
+}
+
+impl CalibratedParameter {
+    /// Create a calibrated parameter.
+    ///
+    /// ### Parameters
+    /// * name, id - name and id of the calibrated parameter.
+    /// * raw, raw_id - name and id of the parameter it is calibrated from.
+    /// * calibration - the calibration coefficients.
+    /// * units - optional units of the calibrated values.
+    pub fn new(
+        name: &str,
+        id: u32,
+        raw: &str,
+        raw_id: u32,
+        calibration: Calibration,
+        units: Option<String>,
+    ) -> CalibratedParameter {
+        CalibratedParameter {
+            name: String::from(name),
+            id,
+            raw: String::from(raw),
+            raw_id,
+            calibration,
+            units,
+        }
+    }
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+    pub fn raw(&self) -> String {
+        self.raw.clone()
+    }
+    pub fn calibration(&self) -> Calibration {
+        self.calibration
+    }
+    pub fn units(&self) -> Option<String> {
+        self.units.clone()
+    }
+}
+
+/// The calibrated parameters in the order in which they are computed.
+#[derive(Clone, Debug, Default)]
+pub struct CalibratedParameters {
+    parameters: Vec<CalibratedParameter>,
+}
+
+impl CalibratedParameters {
+    pub fn new() -> CalibratedParameters {
+        CalibratedParameters::default()
+    }
+    /// Define a calibrated parameter.  A new definition is added to the
+    /// end of the list.  Redefining a calibrated parameter replaces its
+    /// definition but keeps its place in the list.
+    pub fn set(&mut self, parameter: CalibratedParameter) {
+        match self
+            .parameters
+            .iter_mut()
+            .find(|p| p.name == parameter.name)
+        {
+            Some(existing) => *existing = parameter,
+            None => self.parameters.push(parameter),
+        }
+    }
+    /// Remove a calibrated parameter.  The parameter remains but is no
+    /// longer computed.
+    pub fn delete(&mut self, name: &str) -> Result<(), String> {
+        match self.parameters.iter().position(|p| p.name == name) {
+            Some(i) => {
+                self.parameters.remove(i);
+                Ok(())
+            }
+            None => Err(format!("Calibrated parameter {} does not exist", name)),
+        }
+    }
+    pub fn contains(&self, name: &str) -> bool {
+        self.parameters.iter().any(|p| p.name == name)
+    }
+    pub fn iter(&self) -> Iter<'_, CalibratedParameter> {
+        self.parameters.iter()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.parameters.is_empty()
+    }
+    /// Compute the calibrated parameters for an event, adding them to it.
+    /// A calibrated parameter replaces any value the event already has
+    /// for its parameter.
+    pub fn compute(&self, e: &mut Event) {
+        let mut values: HashMap<u32, f64> = e.iter().map(|p| (p.id, p.value)).collect();
+        for parameter in self.parameters.iter() {
+            let raw = match values.get(&parameter.raw_id) {
+                Some(v) => *v,
+                None => continue,
+            };
+            let value = parameter.calibration.apply(raw);
+            if !value.is_finite() {
+                continue;
+            }
+            if values.insert(parameter.id, value).is_some() {
+                for p in e.iter_mut().filter(|p| p.id == parameter.id) {
+                    p.value = value;
+                }
+            } else {
+                e.push(EventParameter::new(parameter.id, value));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod calibration_tests {
+    use super::*;
+
+    fn make(
+        name: &str,
+        id: u32,
+        raw: &str,
+        raw_id: u32,
+        c: (f64, f64, f64),
+    ) -> CalibratedParameter {
+        CalibratedParameter::new(name, id, raw, raw_id, Calibration::new(c.0, c.1, c.2), None)
+    }
+
+    #[test]
+    fn apply_1() {
+        assert_eq!(21.0, Calibration::new(1.0, 2.0, 0.0).apply(10.0));
+        assert_eq!(121.0, Calibration::new(1.0, 2.0, 1.0).apply(10.0));
+        assert_eq!(-99.0, Calibration::new(1.0, 0.0, -1.0).apply(10.0));
+    }
+    #[test]
+    fn list_1() {
+        let mut cals = CalibratedParameters::new();
+        assert!(cals.is_empty());
+        cals.set(make("energy", 2, "raw", 1, (1.0, 2.0, 0.0)));
+        cals.set(make("time", 4, "traw", 3, (0.0, 1.0, 0.0)));
+        assert!(cals.contains("energy"));
+        assert!(!cals.contains("raw"));
+
+        let p = cals.iter().next().unwrap();
+        assert_eq!("energy", p.name());
+        assert_eq!("raw", p.raw());
+        assert_eq!(Calibration::new(1.0, 2.0, 0.0), p.calibration());
+        assert!(p.units().is_none());
+
+        // Redefinition replaces in place:
+
+        cals.set(CalibratedParameter::new(
+            "energy",
+            2,
+            "raw",
+            1,
+            Calibration::new(0.0, 0.5, 0.1),
+            Some(String::from("keV")),
+        ));
+        let names: Vec<String> = cals.iter().map(|p| p.name()).collect();
+        assert_eq!(vec![String::from("energy"), String::from("time")], names);
+        let p = cals.iter().next().unwrap();
+        assert_eq!(Calibration::new(0.0, 0.5, 0.1), p.calibration());
+        assert_eq!(Some(String::from("keV")), p.units());
+
+        assert!(cals.delete("nosuch").is_err());
+        cals.delete("energy").expect("Deleting");
+        cals.delete("time").expect("Deleting");
+        assert!(cals.is_empty());
+    }
+    #[test]
+    fn compute_1() {
+        // Calibrated parameters are added and may use earlier ones:
+
+        let mut cals = CalibratedParameters::new();
+        cals.set(make("energy", 2, "raw", 1, (1.0, 2.0, 0.0)));
+        cals.set(make("energy2", 3, "energy", 2, (0.0, 0.0, 1.0)));
+
+        let mut e = vec![EventParameter::new(1, 10.0)];
+        cals.compute(&mut e);
+        assert_eq!(
+            vec![
+                EventParameter::new(1, 10.0),
+                EventParameter::new(2, 21.0),
+                EventParameter::new(3, 441.0)
+            ],
+            e
+        );
+    }
+    #[test]
+    fn compute_2() {
+        // No raw parameter, or a non-finite result, produces nothing:
+
+        let mut cals = CalibratedParameters::new();
+        cals.set(make("energy", 2, "raw", 1, (1.0, f64::MAX, 0.0)));
+
+        let mut e = vec![EventParameter::new(5, 10.0)];
+        cals.compute(&mut e);
+        assert_eq!(1, e.len());
+
+        let mut e = vec![EventParameter::new(1, 10.0)];
+        cals.compute(&mut e);
+        assert_eq!(1, e.len());
+    }
+    #[test]
+    fn compute_3() {
+        // A calibrated parameter replaces an existing value:
+
+        let mut cals = CalibratedParameters::new();
+        cals.set(make("energy", 2, "raw", 1, (0.0, 10.0, 0.0)));
+
+        let mut e = vec![EventParameter::new(2, 5.0), EventParameter::new(1, 1.0)];
+        cals.compute(&mut e);
+        assert_eq!(2, e.len());
+        assert_eq!(EventParameter::new(2, 10.0), e[0]);
+    }
+}
@@ -8,7 +8,13 @@
 //!  *   units - units of measure of the parameter.
 //!  *   description - new from SpecTcl a full text description of what the
 //!     parameter means.
+//!  *   calibration - new with Rustogramer, a linear gain and offset applied
+//!     to the parameter's values before they are histogrammed.
 //!  
+//! The dictionary also holds the calibrated parameter definitions
+//! (see calibration.rs) and the pseudo parameter definitions (see pseudo.rs)
+//! that compute parameters from other parameters for each event.
+//!
 //! In addition to praameters and dict that can be used to look them up (std::map),
 //! Each event comes in as a set of id/value pairs but
 //!  since the incoming data may have different paramter indices than our
@@ -24,6 +30,10 @@ use std::collections::HashMap;
 
 use std::fmt;
 use std::ops::Index;
+
+pub mod calibration;
+pub mod pseudo;
+
 ///
 /// A parameter is a named entity and optional metadata describing how
 /// best to histogram it and an optional verbose description.
@@ -37,6 +47,7 @@ pub struct Parameter {
     bins: Option<u32>,
     units: Option<String>,
     description: Option<String>,
+    calibration: Option<(f64, f64)>,
 }
 
 impl Parameter {
@@ -52,6 +63,7 @@ impl Parameter {
             bins: None,
             units: None,
             description: None,
+            calibration: None,
         }
     }
     /// Set histogram axis suggested limits:
@@ -77,6 +89,12 @@ impl Parameter {
         self.description = Some(String::from(d));
         self
     }
+    /// Set or, if None, remove the calibration.  The calibration
+    /// is a (gain, offset) pair; calibrated values are gain*raw + offset.
+    pub fn set_calibration(&mut self, c: Option<(f64, f64)>) -> &mut Self {
+        self.calibration = c;
+        self
+    }
 
     /// The name:
     pub fn get_name(&self) -> String {
@@ -106,6 +124,11 @@ impl Parameter {
     pub fn get_description(&self) -> Option<String> {
         self.description.as_ref().cloned()
     }
+    /// Get the calibration (gain, offset) if there is one.
+
+    pub fn get_calibration(&self) -> Option<(f64, f64)> {
+        self.calibration
+    }
 }
 
 impl fmt::Display for Parameter {
@@ -159,12 +182,16 @@ impl fmt::Display for Parameter {
 pub struct ParameterDictionary {
     next_id: u32,
     dictionary: HashMap<String, Parameter>,
+    calibrated: calibration::CalibratedParameters,
+    pseudos: pseudo::PseudoParameters,
 }
 impl ParameterDictionary {
     pub fn new() -> ParameterDictionary {
         ParameterDictionary {
             next_id: 1,
             dictionary: HashMap::<String, Parameter>::new(),
+            calibrated: calibration::CalibratedParameters::new(),
+            pseudos: pseudo::PseudoParameters::new(),
         }
     }
     ///
@@ -210,6 +237,29 @@ impl ParameterDictionary {
     pub fn len(&self) -> usize {
         self.dictionary.len()
     }
+    /// Get the calibrations of the parameters that have one
+    /// indexed by parameter id:
+
+    pub fn calibrations(&self) -> HashMap<u32, (f64, f64)> {
+        self.dictionary
+            .values()
+            .filter_map(|p| p.calibration.map(|c| (p.id, c)))
+            .collect()
+    }
+    /// The calibrated parameter definitions.
+    pub fn calibrated(&self) -> &calibration::CalibratedParameters {
+        &self.calibrated
+    }
+    pub fn calibrated_mut(&mut self) -> &mut calibration::CalibratedParameters {
+        &mut self.calibrated
+    }
+    /// The pseudo parameter definitions.
+    pub fn pseudos(&self) -> &pseudo::PseudoParameters {
+        &self.pseudos
+    }
+    pub fn pseudos_mut(&mut self) -> &mut pseudo::PseudoParameters {
+        &mut self.pseudos
+    }
 }
 ///
 /// Display trait:
@@ -248,33 +298,203 @@ impl fmt::Display for EventParameter {
 
 pub type Event = Vec<EventParameter>;
 
+/// Apply calibrations (see ParameterDictionary::calibrations) to an
+/// event.  Parameters without a calibration are unchanged.
+///
+pub fn calibrate_event(e: &Event, calibrations: &HashMap<u32, (f64, f64)>) -> Event {
+    e.iter()
+        .map(|p| match calibrations.get(&p.id) {
+            Some((gain, offset)) => EventParameter::new(p.id, gain * p.value + offset),
+            None => *p,
+        })
+        .collect()
+}
+
+/// Input ids spanning fewer than this many ids are always mapped
+/// densely by the Auto IdMapStrategy.
+pub const AUTO_DENSE_SPAN: usize = 65536;
+
+/// How a ParameterIdMap stores its mappings:
+///
+/// * Dense - an array indexed by input id.  This is fastest but its
+/// size is proportional to the largest input id.
+/// * Sparse - a hash map from input id to output id.  Its size is
+/// proportional to the number of mapped ids.
+/// * Auto - dense until the largest input id is more than
+/// AUTO_DENSE_SPAN and more than four times the number of mapped ids, then sparse.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IdMapStrategy {
+    Dense,
+    Sparse,
+    Auto,
+}
+impl IdMapStrategy {
+    /// Parse a strategy from its name (dense, sparse or auto).
+    pub fn parse(name: &str) -> Result<IdMapStrategy, String> {
+        match name {
+            "dense" => Ok(IdMapStrategy::Dense),
+            "sparse" => Ok(IdMapStrategy::Sparse),
+            "auto" => Ok(IdMapStrategy::Auto),
+            _ => Err(format!(
+                "Invalid id map strategy {} must be one of dense, sparse or auto",
+                name
+            )),
+        }
+    }
+}
+impl fmt::Display for IdMapStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IdMapStrategy::Dense => write!(f, "dense"),
+            IdMapStrategy::Sparse => write!(f, "sparse"),
+            IdMapStrategy::Auto => write!(f, "auto"),
+        }
+    }
+}
+
 /// ParameterIdMap provides a correspondence between
 /// parameter ids in an Event and parameter ids in some dictionary.
 /// It can be used to take some input event with a different Id space
 /// and map it into an output event with the same parameter space as
 /// the dictionary.
 /// The input dictionary is used to determine the map while
-/// which is an array of output ids indexed by input ids.
+/// which is an array of output ids indexed by input ids or,
+/// if the map is sparse, a hash of output ids indexed by input ids.
 ///
 #[derive(Debug)]
 pub struct ParameterIdMap {
     dict: HashMap<String, u32>,
     map: Vec<Option<u32>>,
+    sparse: Option<HashMap<u32, u32>>,
+    strategy: IdMapStrategy,
 }
 impl ParameterIdMap {
     fn get_mapping(&self, input_id: u32) -> Option<u32> {
+        if let Some(sparse) = &self.sparse {
+            return sparse.get(&input_id).copied();
+        }
         let input_id = input_id as usize;
-        if input_id <= self.map.len() {
+        if input_id < self.map.len() {
             self.map[input_id]
         } else {
             None
         }
     }
+    // Switch to sparse storage:
+
+    fn make_sparse(&mut self) {
+        let mut sparse = HashMap::new();
+        for (input_id, output_id) in self.map.iter().enumerate() {
+            if let Some(id) = output_id {
+                sparse.insert(input_id as u32, *id);
+            }
+        }
+        self.map = Vec::new();
+        self.sparse = Some(sparse);
+    }
+    // True if storing input_id densely is more than Auto allows:
+
+    fn too_sparse(&self, input_id: usize) -> bool {
+        input_id >= AUTO_DENSE_SPAN && input_id >= 4 * (self.len() + 1)
+    }
 
     pub fn new() -> ParameterIdMap {
+        Self::with_strategy(IdMapStrategy::Auto)
+    }
+    /// Create an empty map that uses the specified storage strategy.
+    pub fn with_strategy(strategy: IdMapStrategy) -> ParameterIdMap {
         ParameterIdMap {
             dict: HashMap::new(),
             map: Vec::<Option<u32>>::new(),
+            sparse: if strategy == IdMapStrategy::Sparse {
+                Some(HashMap::new())
+            } else {
+                None
+            },
+            strategy,
+        }
+    }
+    /// The storage strategy the map was created with.
+    pub fn strategy(&self) -> IdMapStrategy {
+        self.strategy
+    }
+    /// True if the map is currently stored sparsely.
+    pub fn is_sparse(&self) -> bool {
+        self.sparse.is_some()
+    }
+    /// Change the storage strategy.  Existing mappings are kept.
+    /// Changing to Auto keeps the current storage until a mapping
+    /// is added.
+    pub fn set_strategy(&mut self, strategy: IdMapStrategy) {
+        self.strategy = strategy;
+        match strategy {
+            IdMapStrategy::Sparse => {
+                if self.sparse.is_none() {
+                    self.make_sparse();
+                }
+            }
+            IdMapStrategy::Dense => {
+                if let Some(sparse) = self.sparse.take() {
+                    let span = sparse.keys().max().map(|m| *m as usize + 1).unwrap_or(0);
+                    self.map = vec![None; span];
+                    for (input_id, output_id) in sparse {
+                        self.map[input_id as usize] = Some(output_id);
+                    }
+                }
+            }
+            IdMapStrategy::Auto => {}
+        }
+    }
+    /// Number of input ids that are mapped.
+    pub fn len(&self) -> usize {
+        if let Some(sparse) = &self.sparse {
+            sparse.len()
+        } else {
+            self.map.iter().filter(|m| m.is_some()).count()
+        }
+    }
+    /// The span of the input id space; one more than the largest
+    /// input id that is mapped (0 if none are).
+    pub fn span(&self) -> usize {
+        if let Some(sparse) = &self.sparse {
+            sparse.keys().max().map(|m| *m as usize + 1).unwrap_or(0)
+        } else {
+            self.map
+                .iter()
+                .rposition(|m| m.is_some())
+                .map(|i| i + 1)
+                .unwrap_or(0)
+        }
+    }
+    /// The ids the input ids are mapped to, sorted.
+    pub fn mapped_ids(&self) -> Vec<u32> {
+        let mut result: Vec<u32> = if let Some(sparse) = &self.sparse {
+            sparse.values().copied().collect()
+        } else {
+            self.map.iter().flatten().copied().collect()
+        };
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
+    /// Number of map entries allocated.  For a dense map
+    /// this can be more than the span.
+    pub fn allocated(&self) -> usize {
+        if let Some(sparse) = &self.sparse {
+            sparse.capacity()
+        } else {
+            self.map.capacity()
+        }
+    }
+    /// Release storage that is not needed for the mapped ids.
+    pub fn compact(&mut self) {
+        let span = self.span();
+        if let Some(sparse) = self.sparse.as_mut() {
+            sparse.shrink_to_fit();
+        } else {
+            self.map.truncate(span);
+            self.map.shrink_to_fit();
         }
     }
     /// Rather than wrapping all of the dicts
@@ -302,9 +522,24 @@ impl ParameterIdMap {
     /// Note - we don't attempt to detectk many to one mappings, only
     ///   one -to many.
     pub fn map(&mut self, input_id: u32, name: &str) -> Result<u32, String> {
-        let input_id: usize = input_id as usize;
         if let Some(p) = self.dict.get(&String::from(name)) {
             let mapped_id = *p;
+            if self.sparse.is_none()
+                && self.strategy == IdMapStrategy::Auto
+                && self.too_sparse(input_id as usize)
+            {
+                self.make_sparse();
+            }
+            if let Some(sparse) = self.sparse.as_mut() {
+                if let Some(outid) = sparse.get(&input_id) {
+                    if *outid != mapped_id {
+                        return Err(String::from("Duplicate Map"));
+                    }
+                }
+                sparse.insert(input_id, mapped_id);
+                return Ok(mapped_id);
+            }
+            let input_id: usize = input_id as usize;
             if self.map.len() <= input_id {
                 self.map.resize(input_id + 1, None);
             }
@@ -392,6 +627,7 @@ impl EventParameterInfo {
 pub struct FlatEvent {
     generation: u64, // Supports O(1) invalidation.
     event: Vec<EventParameterInfo>,
+    weight: f64, // Increment applied to spectra for this event.
 }
 
 impl FlatEvent {
@@ -407,8 +643,21 @@ impl FlatEvent {
         FlatEvent {
             generation: 1, // So anything made by ensure_size is invalid
             event: Vec::<EventParameterInfo>::new(),
+            weight: 1.0,
         }
     }
+    /// Set the weight with which the event increments spectra.
+    /// Normally this is 1.0, however when only a sample of the
+    /// events are histogrammed, the weight can be set to the
+    /// sampling factor to compensate.
+    ///
+    pub fn set_weight(&mut self, weight: f64) {
+        self.weight = weight;
+    }
+    /// Return the weight with which the event increments spectra.
+    pub fn weight(&self) -> f64 {
+        self.weight
+    }
     /// Given a dope vectored event loads the flattened event
     /// from it.  Note this increments the generation number
     /// this means that you can't load several events into a single
@@ -462,7 +711,8 @@ mod parameters_test {
                 high: None,
                 bins: None,
                 units: None,
-                description: None
+                description: None,
+                calibration: None
             },
             p
         );
@@ -479,7 +729,8 @@ mod parameters_test {
                 high: Some(1.0),
                 bins: None,
                 units: None,
-                description: None
+                description: None,
+                calibration: None
             },
             p
         );
@@ -496,7 +747,8 @@ mod parameters_test {
                 high: None,
                 bins: Some(128),
                 units: None,
-                description: None
+                description: None,
+                calibration: None
             },
             p
         );
@@ -513,7 +765,8 @@ mod parameters_test {
                 high: None,
                 bins: None,
                 units: None,
-                description: Some(String::from("Test parameter"))
+                description: Some(String::from("Test parameter")),
+                calibration: None
             },
             p
         );
@@ -533,7 +786,8 @@ mod parameters_test {
                 high: Some(1.0),
                 bins: Some(128),
                 units: None,
-                description: Some(String::from("Test parameter"))
+                description: Some(String::from("Test parameter")),
+                calibration: None
             },
             p
         );
@@ -550,7 +804,8 @@ mod parameters_test {
                 high: None,
                 bins: Some(128),
                 units: Some(String::from("mm")),
-                description: None
+                description: None,
+                calibration: None
             },
             p
         )
@@ -728,6 +983,31 @@ mod pdict_tests {
             d.lookup("param2").unwrap().get_limits()
         )
     }
+    #[test]
+    fn calibrations_1() {
+        let mut d = ParameterDictionary::new();
+        d.add("parameter").unwrap();
+        d.add("param2").unwrap();
+        assert!(d.calibrations().is_empty());
+
+        d.lookup_mut("param2")
+            .unwrap()
+            .set_calibration(Some((2.0, 1.0)));
+        let id = d.lookup("param2").unwrap().get_id();
+        let cals = d.calibrations();
+        assert_eq!(1, cals.len());
+        assert_eq!(Some(&(2.0, 1.0)), cals.get(&id));
+
+        let e = vec![EventParameter::new(1, 10.0), EventParameter::new(id, 10.0)];
+        let c = calibrate_event(&e, &cals);
+        assert_eq!(
+            vec![EventParameter::new(1, 10.0), EventParameter::new(id, 21.0)],
+            c
+        );
+
+        d.lookup_mut("param2").unwrap().set_calibration(None);
+        assert!(d.calibrations().is_empty());
+    }
 }
 #[cfg(test)]
 mod pevent_test {
@@ -936,6 +1216,137 @@ mod paramap_test {
         assert_eq!(EventParameter::new(3, 5.5), oute[1]);
         assert_eq!(EventParameter::new(2, 5.231), oute[2]);
     }
+    #[test]
+    fn map_event_3() {
+        // Ids just past the end of a dense map are not mapped:
+
+        let mut map = ParameterIdMap::new();
+        stock_map(&mut map);
+        make_map(&mut map);
+        let span = map.span() as u32;
+
+        let oute = map.map_event(&vec![EventParameter::new(span, 1.0)]);
+        assert!(oute.is_empty());
+    }
+    #[test]
+    fn strategy_1() {
+        assert_eq!(IdMapStrategy::Dense, IdMapStrategy::parse("dense").unwrap());
+        assert_eq!(
+            IdMapStrategy::Sparse,
+            IdMapStrategy::parse("sparse").unwrap()
+        );
+        assert_eq!(IdMapStrategy::Auto, IdMapStrategy::parse("auto").unwrap());
+        assert!(IdMapStrategy::parse("compact").is_err());
+        for s in ["dense", "sparse", "auto"] {
+            assert_eq!(s, IdMapStrategy::parse(s).unwrap().to_string());
+        }
+    }
+    #[test]
+    fn sparse_1() {
+        // A sparse map maps like a dense one:
+
+        let mut map = ParameterIdMap::with_strategy(IdMapStrategy::Sparse);
+        stock_map(&mut map);
+        make_map(&mut map);
+        assert!(map.is_sparse());
+        assert_eq!(3, map.len());
+        assert_eq!(13, map.span());
+        assert!(map.map.is_empty());
+
+        let ine: Event = vec![
+            EventParameter::new(10, 1.234),
+            EventParameter::new(7, 3.1122), // should vanish
+            EventParameter::new(12, 5.5),
+            EventParameter::new(5, 5.231),
+        ];
+        let oute = map.map_event(&ine);
+        assert_eq!(3, oute.len());
+        assert_eq!(EventParameter::new(1, 1.234), oute[0]);
+        assert_eq!(EventParameter::new(3, 5.5), oute[1]);
+        assert_eq!(EventParameter::new(2, 5.231), oute[2]);
+
+        assert!(map.map(5, "Parameter2").is_ok());
+        assert!(map.map(5, "Parameter1").is_err());
+    }
+    #[test]
+    fn auto_1() {
+        // Auto maps go sparse when a huge id is mapped:
+
+        let mut map = ParameterIdMap::new();
+        stock_map(&mut map);
+        make_map(&mut map);
+        assert!(!map.is_sparse());
+        assert_eq!(IdMapStrategy::Auto, map.strategy());
+
+        map.get_dict_mut().insert(String::from("huge"), 4);
+        assert_eq!(4, map.map(100_000_000, "huge").unwrap());
+        assert!(map.is_sparse());
+        assert_eq!(4, map.len());
+        assert_eq!(100_000_001, map.span());
+        assert!(map.allocated() < 1000);
+        let oute = map.map_event(&vec![
+            EventParameter::new(100_000_000, 1.0),
+            EventParameter::new(10, 2.0),
+        ]);
+        assert_eq!(
+            vec![EventParameter::new(4, 1.0), EventParameter::new(1, 2.0)],
+            oute
+        );
+    }
+    #[test]
+    fn dense_1() {
+        // Dense maps stay dense:
+
+        let mut map = ParameterIdMap::with_strategy(IdMapStrategy::Dense);
+        map.get_dict_mut().insert(String::from("big"), 1);
+        map.map(AUTO_DENSE_SPAN as u32 + 1, "big").unwrap();
+        assert!(!map.is_sparse());
+        assert_eq!(AUTO_DENSE_SPAN + 2, map.span());
+    }
+    #[test]
+    fn set_strategy_1() {
+        // Converting between storage keeps the mappings:
+
+        let mut map = ParameterIdMap::new();
+        stock_map(&mut map);
+        make_map(&mut map);
+
+        map.set_strategy(IdMapStrategy::Sparse);
+        assert!(map.is_sparse());
+        assert_eq!(3, map.len());
+        assert_eq!(Some(3), map.get_mapping(12));
+
+        map.set_strategy(IdMapStrategy::Dense);
+        assert!(!map.is_sparse());
+        assert_eq!(13, map.map.len());
+        assert_eq!(Some(1), map.get_mapping(10));
+        assert_eq!(Some(2), map.get_mapping(5));
+        assert_eq!(Some(3), map.get_mapping(12));
+    }
+    #[test]
+    fn mapped_ids_1() {
+        let mut map = ParameterIdMap::new();
+        assert!(map.mapped_ids().is_empty());
+        stock_map(&mut map);
+        make_map(&mut map);
+        assert_eq!(vec![1, 2, 3], map.mapped_ids());
+
+        map.set_strategy(IdMapStrategy::Sparse);
+        assert_eq!(vec![1, 2, 3], map.mapped_ids());
+    }
+    #[test]
+    fn compact_1() {
+        let mut map = ParameterIdMap::new();
+        assert_eq!(0, map.len());
+        assert_eq!(0, map.span());
+        stock_map(&mut map);
+        make_map(&mut map);
+        map.map.resize(1000, None);
+        map.compact();
+        assert_eq!(13, map.map.len());
+        assert_eq!(13, map.span());
+        assert_eq!(Some(3), map.get_mapping(12));
+    }
 }
 #[cfg(test)]
 mod parflatevt_test {
@@ -983,7 +1394,8 @@ mod parflatevt_test {
         assert_eq!(
             FlatEvent {
                 generation: 1,
-                event: Vec::new()
+                event: Vec::new(),
+                weight: 1.0
             },
             ev
         );
@@ -0,0 +1,537 @@
+//!  Pseudo parameters are parameters that are computed, for each event,
+//!  from other parameters.  In SpecTcl, a pseudo parameter's computation
+//!  is a Tcl script.  Rustogramer has no interpreter, so here the
+//!  computation is a simple arithmetic expression of the parameters the
+//!  pseudo depends on e.g.:
+//!
+//! ```text
+//!  (event.raw.00 + event.raw.01) / 2
+//! ```
+//!
+//!  Expressions are made up of:
+//!
+//!  *  The names of the parameters the pseudo depends on.  As in Tcl,
+//!     a name can also be written as $name, or ${name} for names that
+//!     contain characters that would otherwise be operators.
+//!  *  Numeric constants e.g. 2, 1.5 or 1.0e-3.
+//!  *  The binary operators +, -, * and / with the usual precedence,
+//!     unary minus and parentheses.
+//!
+//!  Computations are limited to MAX_COMPUTATION_LENGTH characters and
+//!  MAX_NESTING levels of parentheses and unary operators.  Parsing,
+//!  evaluating and dropping an expression all recurse along it so this
+//!  keeps a malicious or runaway computation from overflowing the stack.
+//!
+//!  A pseudo parameter is only computed for events that have all of the
+//!  parameters it depends on and then only if the result is finite
+//!  (e.g. not for a division by zero).  Pseudos are computed in the order
+//!  in which they were defined, after parameter calibrations are applied
+//!  and calibrated parameters are computed, so a pseudo can depend on
+//!  calibrated parameters and on pseudos that were defined before it.
+//!
+use super::{Event, EventParameter};
+use std::collections::HashMap;
+use std::slice::Iter;
+
+/// Longest computation that will be parsed.
+pub const MAX_COMPUTATION_LENGTH: usize = 1024;
+/// Deepest nesting of parentheses and unary operators that will be parsed.
+pub const MAX_NESTING: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+/// A parsed computation.  Parameters are referred to by their index
+/// in the list of parameters the pseudo depends on.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
+    Constant(f64),
+    Parameter(usize),
+    Negate(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    /// Parse a computation.
+    ///
+    /// ### Parameters
+    /// * text - the computation.
+    /// * parameters - names of the parameters the computation may use.
+    ///
+    /// ### Returns
+    /// Result<Expression, String> - the error describes what's wrong
+    /// with the computation.
+    pub fn parse(text: &str, parameters: &[String]) -> Result<Expression, String> {
+        let chars: Vec<char> = text.chars().collect();
+        if chars.len() > MAX_COMPUTATION_LENGTH {
+            return Err(format!(
+                "The computation is longer than {} characters",
+                MAX_COMPUTATION_LENGTH
+            ));
+        }
+        let mut parser = Parser {
+            chars,
+            pos: 0,
+            depth: 0,
+            parameters,
+        };
+        let result = parser.expression()?;
+        parser.skip_space();
+        if let Some(c) = parser.peek() {
+            return Err(format!("Unexpected '{}' at position {}", c, parser.pos));
+        }
+        Ok(result)
+    }
+    /// Evaluate the expression given the values of the parameters.
+    pub fn evaluate(&self, values: &[f64]) -> f64 {
+        match self {
+            Expression::Constant(v) => *v,
+            Expression::Parameter(i) => values[*i],
+            Expression::Negate(e) => -e.evaluate(values),
+            Expression::Binary(op, l, r) => {
+                let (l, r) = (l.evaluate(values), r.evaluate(values));
+                match op {
+                    Operator::Add => l + r,
+                    Operator::Subtract => l - r,
+                    Operator::Multiply => l * r,
+                    Operator::Divide => l / r,
+                }
+            }
+        }
+    }
+}
+
+// Recursive descent parser for computations:
+//
+//   expression := term (('+' | '-') term)*
+//   term       := unary (('*' | '/') unary)*
+//   unary      := ('-' | '+') unary | primary
+//   primary    := number | name | '(' expression ')'
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    depth: usize, // Current nesting of parentheses and unary operators.
+    parameters: &'a [String],
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+    fn skip_space(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+    fn is_name_char(c: char) -> bool {
+        c.is_alphanumeric() || c == '_' || c == '.' || c == ':'
+    }
+    // Parse a nested construct, limiting the nesting depth:
+
+    fn nested<F>(&mut self, parse: F) -> Result<Expression, String>
+    where
+        F: FnOnce(&mut Self) -> Result<Expression, String>,
+    {
+        if self.depth >= MAX_NESTING {
+            return Err(format!(
+                "The computation is nested more than {} deep at position {}",
+                MAX_NESTING, self.pos
+            ));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn expression(&mut self) -> Result<Expression, String> {
+        let mut result = self.term()?;
+        loop {
+            self.skip_space();
+            let op = match self.peek() {
+                Some('+') => Operator::Add,
+                Some('-') => Operator::Subtract,
+                _ => return Ok(result),
+            };
+            self.pos += 1;
+            result = Expression::Binary(op, Box::new(result), Box::new(self.term()?));
+        }
+    }
+    fn term(&mut self) -> Result<Expression, String> {
+        let mut result = self.unary()?;
+        loop {
+            self.skip_space();
+            let op = match self.peek() {
+                Some('*') => Operator::Multiply,
+                Some('/') => Operator::Divide,
+                _ => return Ok(result),
+            };
+            self.pos += 1;
+            result = Expression::Binary(op, Box::new(result), Box::new(self.unary()?));
+        }
+    }
+    fn unary(&mut self) -> Result<Expression, String> {
+        self.skip_space();
+        match self.peek() {
+            Some('-') => {
+                self.pos += 1;
+                let operand = self.nested(Self::unary)?;
+                Ok(Expression::Negate(Box::new(operand)))
+            }
+            Some('+') => {
+                self.pos += 1;
+                self.nested(Self::unary)
+            }
+            _ => self.primary(),
+        }
+    }
+    fn primary(&mut self) -> Result<Expression, String> {
+        self.skip_space();
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let result = self.nested(Self::expression)?;
+                self.skip_space();
+                if self.peek() != Some(')') {
+                    return Err(format!("Expected ')' at position {}", self.pos));
+                }
+                self.pos += 1;
+                Ok(result)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some('$') => {
+                self.pos += 1;
+                if self.peek() == Some('{') {
+                    self.pos += 1;
+                    let start = self.pos;
+                    while self.peek().is_some_and(|c| c != '}') {
+                        self.pos += 1;
+                    }
+                    if self.peek().is_none() {
+                        return Err(String::from("Missing '}' after ${"));
+                    }
+                    let name: String = self.chars[start..self.pos].iter().collect();
+                    self.pos += 1;
+                    self.parameter(&name)
+                } else {
+                    self.name()
+                }
+            }
+            Some(c) if Self::is_name_char(c) => self.name(),
+            Some(c) => Err(format!("Unexpected '{}' at position {}", c, self.pos)),
+            None => Err(String::from("Unexpected end of computation")),
+        }
+    }
+    fn number(&mut self) -> Result<Expression, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+            self.pos += 1;
+        }
+        // Exponent only if digits follow the e[+-]:
+
+        if matches!(self.peek(), Some('e') | Some('E')) {
+            let mut end = self.pos + 1;
+            if matches!(self.chars.get(end), Some('+') | Some('-')) {
+                end += 1;
+            }
+            if self.chars.get(end).is_some_and(|c| c.is_ascii_digit()) {
+                self.pos = end;
+                while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    self.pos += 1;
+                }
+            }
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse::<f64>()
+            .map(Expression::Constant)
+            .map_err(|_| format!("Invalid number '{}' at position {}", text, start))
+    }
+    fn name(&mut self) -> Result<Expression, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(Self::is_name_char) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(format!("Expected a parameter name at position {}", start));
+        }
+        let name: String = self.chars[start..self.pos].iter().collect();
+        self.parameter(&name)
+    }
+    fn parameter(&self, name: &str) -> Result<Expression, String> {
+        self.parameters
+            .iter()
+            .position(|p| p == name)
+            .map(Expression::Parameter)
+            .ok_or_else(|| format!("'{}' is not a parameter of the pseudo", name))
+    }
+}
+
+/// A pseudo parameter definition.  The pseudo computes the value of
+/// the parameter with the same name.
+#[derive(Clone, Debug)]
+pub struct PseudoParameter {
+    name: String,
+    id: u32,
+    parameters: Vec<String>,
+    ids: Vec<u32>,
+    computation: String,
+    expression: Expression,
+}
+
+impl PseudoParameter {
+    /// Create a pseudo.
+    ///
+    /// ### Parameters
+    /// * name - name of the pseudo and the parameter it computes.
+    /// * id - id of that parameter.
+    /// * dependencies - names and ids of the parameters the pseudo
+    ///   depends on.
+    /// * computation - the computation as the user gave it.
+    /// * expression - the computation parsed with the names of
+    ///   the dependencies (see Expression::parse).
+    pub fn new(
+        name: &str,
+        id: u32,
+        dependencies: &[(String, u32)],
+        computation: &str,
+        expression: Expression,
+    ) -> PseudoParameter {
+        PseudoParameter {
+            name: String::from(name),
+            id,
+            parameters: dependencies.iter().map(|d| d.0.clone()).collect(),
+            ids: dependencies.iter().map(|d| d.1).collect(),
+            computation: String::from(computation),
+            expression,
+        }
+    }
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+    pub fn parameters(&self) -> Vec<String> {
+        self.parameters.clone()
+    }
+    pub fn computation(&self) -> String {
+        self.computation.clone()
+    }
+}
+
+/// The pseudo parameters in the order in which they are computed.
+#[derive(Clone, Debug, Default)]
+pub struct PseudoParameters {
+    pseudos: Vec<PseudoParameter>,
+}
+
+impl PseudoParameters {
+    pub fn new() -> PseudoParameters {
+        PseudoParameters::default()
+    }
+    /// Add a pseudo to the end of the list.  Pseudo names must be
+    /// unique.
+    pub fn add(&mut self, pseudo: PseudoParameter) -> Result<(), String> {
+        if self.contains(&pseudo.name) {
+            Err(format!("Pseudo parameter {} already exists", pseudo.name))
+        } else {
+            self.pseudos.push(pseudo);
+            Ok(())
+        }
+    }
+    /// Remove a pseudo.  The parameter it computed remains but is no
+    /// longer produced.
+    pub fn delete(&mut self, name: &str) -> Result<(), String> {
+        match self.pseudos.iter().position(|p| p.name == name) {
+            Some(i) => {
+                self.pseudos.remove(i);
+                Ok(())
+            }
+            None => Err(format!("Pseudo parameter {} does not exist", name)),
+        }
+    }
+    pub fn contains(&self, name: &str) -> bool {
+        self.pseudos.iter().any(|p| p.name == name)
+    }
+    pub fn iter(&self) -> Iter<'_, PseudoParameter> {
+        self.pseudos.iter()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.pseudos.is_empty()
+    }
+    /// Compute the pseudos for an event, adding them to it.  A pseudo
+    /// replaces any value the event already has for its parameter.
+    pub fn compute(&self, e: &mut Event) {
+        let mut values: HashMap<u32, f64> = e.iter().map(|p| (p.id, p.value)).collect();
+        let mut args = Vec::<f64>::new();
+        for pseudo in self.pseudos.iter() {
+            args.clear();
+            for id in pseudo.ids.iter() {
+                match values.get(id) {
+                    Some(v) => args.push(*v),
+                    None => break,
+                }
+            }
+            if args.len() != pseudo.ids.len() {
+                continue;
+            }
+            let value = pseudo.expression.evaluate(&args);
+            if !value.is_finite() {
+                continue;
+            }
+            if values.insert(pseudo.id, value).is_some() {
+                for p in e.iter_mut().filter(|p| p.id == pseudo.id) {
+                    p.value = value;
+                }
+            } else {
+                e.push(EventParameter::new(pseudo.id, value));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod pseudo_tests {
+    use super::*;
+
+    fn names(n: &[&str]) -> Vec<String> {
+        n.iter().map(|s| String::from(*s)).collect()
+    }
+    fn eval(text: &str, values: &[f64]) -> f64 {
+        let params = names(&["a", "b", "event.raw.00"]);
+        Expression::parse(text, &params)
+            .expect("Parsing")
+            .evaluate(values)
+    }
+    fn make(name: &str, id: u32, deps: &[(&str, u32)], computation: &str) -> PseudoParameter {
+        let deps: Vec<(String, u32)> = deps.iter().map(|d| (String::from(d.0), d.1)).collect();
+        let params: Vec<String> = deps.iter().map(|d| d.0.clone()).collect();
+        let expression = Expression::parse(computation, &params).expect("Parsing");
+        PseudoParameter::new(name, id, &deps, computation, expression)
+    }
+
+    #[test]
+    fn parse_1() {
+        // Operands and precedence:
+
+        assert_eq!(3.0, eval("a + b", &[1.0, 2.0, 0.0]));
+        assert_eq!(7.0, eval("a + b * 3", &[1.0, 2.0, 0.0]));
+        assert_eq!(9.0, eval("(a + b) * 3", &[1.0, 2.0, 0.0]));
+        assert_eq!(0.5, eval("a/b", &[1.0, 2.0, 0.0]));
+        assert_eq!(-1.0, eval("a - b", &[1.0, 2.0, 0.0]));
+        assert_eq!(-3.0, eval("-(a + b)", &[1.0, 2.0, 0.0]));
+        assert_eq!(2.0, eval("10 - 4 - 4", &[0.0, 0.0, 0.0]));
+        assert_eq!(1500.0, eval("1.5e3", &[0.0, 0.0, 0.0]));
+        assert_eq!(0.25, eval(".25", &[0.0, 0.0, 0.0]));
+    }
+    #[test]
+    fn parse_2() {
+        // Name forms:
+
+        assert_eq!(4.0, eval("event.raw.00 * 2", &[0.0, 0.0, 2.0]));
+        assert_eq!(3.0, eval("$a + ${b}", &[1.0, 2.0, 0.0]));
+        assert_eq!(2.0, eval("${event.raw.00}", &[0.0, 0.0, 2.0]));
+    }
+    #[test]
+    fn parse_3() {
+        // Errors:
+
+        let params = names(&["a", "b"]);
+        for bad in [
+            "", "a +", "(a + b", "a b", "c + a", "a + * b", "${a", "1.2.3", "a )",
+        ] {
+            assert!(
+                Expression::parse(bad, &params).is_err(),
+                "'{}' should not parse",
+                bad
+            );
+        }
+    }
+    #[test]
+    fn parse_4() {
+        // Nesting and length limits:
+
+        let params = names(&["a"]);
+        let nested = format!("{}a{}", "(".repeat(MAX_NESTING), ")".repeat(MAX_NESTING));
+        assert!(Expression::parse(&nested, &params).is_ok());
+        let nested = format!("-{}", nested);
+        assert!(Expression::parse(&nested, &params).is_err());
+        assert!(Expression::parse(&"-".repeat(MAX_NESTING + 1), &params).is_err());
+
+        let deep = format!("{}a{}", "(".repeat(20000), ")".repeat(20000));
+        assert!(Expression::parse(&deep, &params).is_err());
+        let long = vec!["a"; MAX_COMPUTATION_LENGTH / 2 + 1].join("+");
+        assert!(Expression::parse(&long, &params).is_err());
+        let long = vec!["a"; MAX_COMPUTATION_LENGTH / 2].join("+");
+        assert_eq!(512.0, eval(&long.replace('a', "b"), &[0.0, 1.0, 0.0]));
+    }
+    #[test]
+    fn list_1() {
+        let mut pseudos = PseudoParameters::new();
+        assert!(pseudos.is_empty());
+        pseudos
+            .add(make("sum", 3, &[("a", 1), ("b", 2)], "a+b"))
+            .expect("Adding");
+        assert!(pseudos.contains("sum"));
+        assert!(pseudos.add(make("sum", 3, &[("a", 1)], "a")).is_err());
+
+        let p = pseudos.iter().next().unwrap();
+        assert_eq!("sum", p.name());
+        assert_eq!(names(&["a", "b"]), p.parameters());
+        assert_eq!("a+b", p.computation());
+
+        assert!(pseudos.delete("nosuch").is_err());
+        pseudos.delete("sum").expect("Deleting");
+        assert!(pseudos.is_empty());
+    }
+    #[test]
+    fn compute_1() {
+        // Pseudos are added to the event and may use earlier pseudos:
+
+        let mut pseudos = PseudoParameters::new();
+        pseudos
+            .add(make("sum", 3, &[("a", 1), ("b", 2)], "a+b"))
+            .unwrap();
+        pseudos
+            .add(make("half", 4, &[("sum", 3)], "sum/2"))
+            .unwrap();
+
+        let mut e = vec![EventParameter::new(1, 1.0), EventParameter::new(2, 3.0)];
+        pseudos.compute(&mut e);
+        assert_eq!(4, e.len());
+        assert_eq!(EventParameter::new(3, 4.0), e[2]);
+        assert_eq!(EventParameter::new(4, 2.0), e[3]);
+    }
+    #[test]
+    fn compute_2() {
+        // Missing dependencies and non-finite results produce nothing:
+
+        let mut pseudos = PseudoParameters::new();
+        pseudos
+            .add(make("ratio", 3, &[("a", 1), ("b", 2)], "a/b"))
+            .unwrap();
+
+        let mut e = vec![EventParameter::new(1, 1.0)];
+        pseudos.compute(&mut e);
+        assert_eq!(1, e.len());
+
+        let mut e = vec![EventParameter::new(1, 1.0), EventParameter::new(2, 0.0)];
+        pseudos.compute(&mut e);
+        assert_eq!(2, e.len());
+    }
+    #[test]
+    fn compute_3() {
+        // A pseudo replaces an existing value:
+
+        let mut pseudos = PseudoParameters::new();
+        pseudos.add(make("scaled", 2, &[("a", 1)], "a*10")).unwrap();
+
+        let mut e = vec![EventParameter::new(2, 5.0), EventParameter::new(1, 1.0)];
+        pseudos.compute(&mut e);
+        assert_eq!(2, e.len());
+        assert_eq!(EventParameter::new(2, 10.0), e[0]);
+    }
+}
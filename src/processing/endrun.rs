@@ -0,0 +1,318 @@
+//!  Automatic writing of spectra at the end of each run.  For
+//!  unattended batch histogramming of several runs (e.g. a chained
+//!  replay), a policy can be set that, when the processing thread
+//!  encounters an END_RUN state change item, writes a set of spectra
+//!  to a file whose name includes the run number and then, optionally,
+//!  clears those spectra so that the next run starts from zero.
+//!
+//!  The policy is shared between the processing thread, which applies
+//!  it, and the processing API, which sets it and reports the outcome
+//!  of the most recent write (see SharedEndRunWrite).
+//!
+//!  Spectra are written in the same formats as swrite: "json" or
+//!  "ascii" (SpecTcl format), or as a ROOT macro ("root", see
+//!  spectclio::root).  If the write fails the spectra are not
+//!  cleared so that the data are not lost.
+//!
+use crate::messaging::spectrum_messages::SpectrumMessageClient;
+use crate::rest::spectrumio;
+use crate::spectclio;
+use rocket::serde::json;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// Text in a file pattern that is replaced by the run number.
+pub const RUN_PLACEHOLDER: &str = "{run}";
+/// Formats in which spectra can be written.
+pub const SUPPORTED_FORMATS: [&str; 3] = ["json", "ascii", "root"];
+
+/// What to do at the end of each run:
+///
+/// * spectra - glob patterns of the spectra to write.
+/// * file_pattern - The file to write; RUN_PLACEHOLDER is replaced
+///   by the run number.
+/// * format - the file format, one of SUPPORTED_FORMATS.
+/// * clear - If true the spectra are cleared once written.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct EndRunWrite {
+    spectra: Vec<String>,
+    file_pattern: String,
+    format: String,
+    clear: bool,
+}
+
+impl EndRunWrite {
+    /// Create a policy.  The format is matched case insensitively.
+    /// Fails if there are no spectra, the file pattern does not
+    /// contain RUN_PLACEHOLDER or the format is not supported.
+    pub fn new(
+        spectra: &[String],
+        file_pattern: &str,
+        format: &str,
+        clear: bool,
+    ) -> Result<EndRunWrite, String> {
+        if spectra.is_empty() {
+            return Err(String::from("At least one spectrum must be written"));
+        }
+        if !file_pattern.contains(RUN_PLACEHOLDER) {
+            return Err(format!(
+                "The file pattern '{}' must contain {}",
+                file_pattern, RUN_PLACEHOLDER
+            ));
+        }
+        let format = format.to_ascii_lowercase();
+        if !SUPPORTED_FORMATS.contains(&format.as_str()) {
+            return Err(format!("Unsupported format '{}'", format));
+        }
+        Ok(EndRunWrite {
+            spectra: spectra.to_vec(),
+            file_pattern: String::from(file_pattern),
+            format,
+            clear,
+        })
+    }
+    pub fn spectra(&self) -> Vec<String> {
+        self.spectra.clone()
+    }
+    pub fn file_pattern(&self) -> String {
+        self.file_pattern.clone()
+    }
+    pub fn format(&self) -> String {
+        self.format.clone()
+    }
+    pub fn clear(&self) -> bool {
+        self.clear
+    }
+    /// The name of the file written for a run.
+    pub fn file_name(&self, run: u32) -> String {
+        self.file_pattern.replace(RUN_PLACEHOLDER, &run.to_string())
+    }
+}
+
+/// The outcome of writing spectra at the end of a run:
+///
+/// * run - the run number from the END_RUN item.
+/// * file - the file written.
+/// * spectra - number of spectra written.
+/// * cleared - true if the spectra were cleared.
+/// * error - If the write failed, why.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct EndRunWriteResult {
+    pub run: u32,
+    pub file: String,
+    pub spectra: usize,
+    pub cleared: bool,
+    pub error: Option<String>,
+}
+
+/// The policy, if one is set, and the outcome of the most recent
+/// end of run write.
+#[derive(Clone, Debug, Default)]
+pub struct EndRunWriteState {
+    pub policy: Option<EndRunWrite>,
+    pub last: Option<EndRunWriteResult>,
+}
+/// The end of run write state is shared between the processing thread
+/// and its API.
+pub type SharedEndRunWrite = Arc<Mutex<EndRunWriteState>>;
+
+/// Get the file data of the spectra that match a set of glob patterns.
+/// Spectra matching more than one pattern are only returned once.
+/// As with swrite, spectra deleted while we run are dropped.
+pub fn get_spectra(
+    patterns: &[String],
+    api: &SpectrumMessageClient,
+) -> Result<Vec<spectrumio::SpectrumFileData>, String> {
+    let mut names = HashSet::<String>::new();
+    let mut result = Vec::new();
+    for pattern in patterns.iter() {
+        for info in api.list_spectra(pattern)? {
+            if names.insert(info.name.clone()) {
+                let description = spectrumio::describe_spectrum(&info);
+                if let Ok(data) = spectrumio::get_spectrum_data(&description, api) {
+                    result.push(data);
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Write spectrum file data to a file in one of SUPPORTED_FORMATS.
+pub fn write_file(
+    file: &str,
+    format: &str,
+    spectra: &[spectrumio::SpectrumFileData],
+) -> Result<(), String> {
+    let mut fd =
+        File::create(file).map_err(|e| format!("Unable to create file {}: {}", file, e))?;
+    match format {
+        "json" => {
+            let text = json::to_string(&spectra).map_err(|e| e.to_string())?;
+            fd.write_all(text.as_bytes())
+                .map_err(|e| format!("Failed to write spectra to file: {}", e))
+        }
+        "ascii" => spectclio::write_spectrum(&mut fd, spectra),
+        "root" => spectclio::root::write_root_macro(&mut fd, spectra),
+        _ => Err(format!("Unsupported format '{}'", format)),
+    }
+}
+
+/// Apply a policy at the end of a run: write the spectra to the
+/// run's file and, if that worked and the policy says to, clear them.
+///
+/// ### Parameters
+/// * policy - the policy to apply.
+/// * run - the run number from the END_RUN item.
+/// * api - client to the histogramer.
+///
+/// ### Returns
+/// EndRunWriteResult describing what was done.
+pub fn write(policy: &EndRunWrite, run: u32, api: &SpectrumMessageClient) -> EndRunWriteResult {
+    let mut result = EndRunWriteResult {
+        run,
+        file: policy.file_name(run),
+        spectra: 0,
+        cleared: false,
+        error: None,
+    };
+    let spectra = match get_spectra(&policy.spectra, api) {
+        Ok(s) => s,
+        Err(s) => {
+            result.error = Some(s);
+            return result;
+        }
+    };
+    if let Err(s) = write_file(&result.file, &policy.format, &spectra) {
+        result.error = Some(s);
+        return result;
+    }
+    result.spectra = spectra.len();
+    if policy.clear {
+        for pattern in policy.spectra.iter() {
+            if let Err(s) = api.clear_spectra(pattern) {
+                result.error = Some(format!("Failed to clear spectra: {}", s));
+                return result;
+            }
+        }
+        result.cleared = true;
+    }
+    result
+}
+
+#[cfg(test)]
+mod endrun_tests {
+    use super::*;
+    use crate::messaging::parameter_messages::ParameterMessageClient;
+    use crate::parameters::{Event, EventParameter};
+    use crate::test::histogramer_common;
+    use std::fs;
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| String::from(*s)).collect()
+    }
+
+    #[test]
+    fn new_1() {
+        let policy = EndRunWrite::new(&names(&["a*"]), "run-{run}.json", "JSON", true)
+            .expect("Making policy");
+        assert_eq!(names(&["a*"]), policy.spectra());
+        assert_eq!("run-{run}.json", policy.file_pattern());
+        assert_eq!("json", policy.format());
+        assert!(policy.clear());
+        assert_eq!("run-12.json", policy.file_name(12));
+    }
+    #[test]
+    fn new_2() {
+        // Invalid policies:
+
+        assert!(EndRunWrite::new(&[], "run-{run}.json", "json", false).is_err());
+        assert!(EndRunWrite::new(&names(&["*"]), "run.json", "json", false).is_err());
+        assert!(EndRunWrite::new(&names(&["*"]), "run-{run}.npy", "npy", false).is_err());
+    }
+    #[test]
+    fn write_1() {
+        // Write then clear matching spectra:
+
+        let (chan, jh) = histogramer_common::setup();
+        let papi = ParameterMessageClient::new(&chan);
+        papi.create_parameter("a").expect("Making parameter");
+        let id = papi.list_parameters("a").expect("Listing")[0].get_id();
+        let api = SpectrumMessageClient::new(&chan);
+        for name in ["s1", "s2", "other"] {
+            api.create_spectrum_1d(name, "a", 0.0, 10.0, 10)
+                .expect("Making spectrum");
+        }
+        let event: Event = vec![EventParameter::new(id, 5.0)];
+        api.process_events(&[event]).expect("Processing event");
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let pattern = dir.path().join("run-{run}.json");
+        let policy = EndRunWrite::new(
+            &names(&["s*", "s1"]),
+            pattern.to_str().unwrap(),
+            "json",
+            true,
+        )
+        .expect("Making policy");
+
+        let result = write(&policy, 3, &api);
+        assert_eq!(None, result.error);
+        assert_eq!(3, result.run);
+        assert_eq!(2, result.spectra);
+        assert!(result.cleared);
+
+        let text = fs::read_to_string(dir.path().join("run-3.json")).expect("Reading file");
+        let spectra: Vec<spectrumio::SpectrumFileData> =
+            json::from_str(&text).expect("Parsing file");
+        assert_eq!(2, spectra.len());
+        for s in spectra.iter() {
+            assert_eq!(1, s.channels.len());
+        }
+
+        // s1, s2 cleared, other is not:
+
+        for (name, sum) in [("s1", 0.0), ("s2", 0.0), ("other", 1.0)] {
+            let contents = api
+                .get_contents(name, -1.0, 11.0, 0.0, 0.0)
+                .expect("Getting contents");
+            let total: f64 = contents.iter().map(|c| c.value).sum();
+            assert_eq!(sum, total, "{}", name);
+        }
+
+        histogramer_common::teardown(chan, jh);
+    }
+    #[test]
+    fn write_2() {
+        // If the file can't be written the spectra aren't cleared:
+
+        let (chan, jh) = histogramer_common::setup();
+        let papi = ParameterMessageClient::new(&chan);
+        papi.create_parameter("a").expect("Making parameter");
+        let id = papi.list_parameters("a").expect("Listing")[0].get_id();
+        let api = SpectrumMessageClient::new(&chan);
+        api.create_spectrum_1d("s", "a", 0.0, 10.0, 10)
+            .expect("Making spectrum");
+        let event: Event = vec![EventParameter::new(id, 5.0)];
+        api.process_events(&[event]).expect("Processing event");
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let pattern = dir.path().join("no-such-dir").join("run-{run}.txt");
+        let policy = EndRunWrite::new(&names(&["s"]), pattern.to_str().unwrap(), "ascii", true)
+            .expect("Making policy");
+
+        let result = write(&policy, 1, &api);
+        assert!(result.error.is_some());
+        assert!(!result.cleared);
+        let contents = api
+            .get_contents("s", -1.0, 11.0, 0.0, 0.0)
+            .expect("Getting contents");
+        assert_eq!(1, contents.len());
+
+        histogramer_common::teardown(chan, jh);
+    }
+}
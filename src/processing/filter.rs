@@ -0,0 +1,449 @@
+//!  Filters write the events that satisfy a gate to a file so that a
+//!  subset of the data can be replayed more quickly or analyzed by
+//!  other programs.  Each filter writes a chosen set of parameters.
+//!
+//!  Filter files are FRIB analysis ring item files that Rustogramer can
+//!  analyze: a format item, then a parameter definitions item that
+//!  describes the filter's parameters (with their histogramer ids) and
+//!  then a PARAMETER_DATA item for each event the filter wrote.
+//!
+//!  The filters are shared between the processing thread, which
+//!  writes events to them as it sends each batch of events to the
+//!  histogramer, and the processing API, which manages them (see
+//!  SharedFilters).  Gates live in the histogramer so the processing
+//!  thread asks it for the events of a batch that satisfy each enabled
+//!  filter's gate.  A gate that does not exist selects no events.
+//!
+//!  A filter is created disabled and without a file.  Once it has a
+//!  file it can be enabled.  The file is opened (and truncated) when
+//!  the filter is first enabled.  Disabling a filter flushes its file
+//!  but leaves it open so that enabling it again continues that file.
+//!  Setting a new file closes the old one.  If writing to a file fails,
+//!  the filter is disabled and the error is retained.
+//!
+use crate::parameters;
+use crate::ring_items::analysis_ring_items::{
+    ParameterDefinitions, ParameterFileWriter, ParameterItem,
+};
+use crate::ring_items::RingVersion;
+use glob::Pattern;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::BufWriter;
+use std::sync::{Arc, Mutex};
+
+/// The formats filters can write; ring items of version 11 or 12.
+pub const SUPPORTED_FORMATS: [&str; 2] = ["ring11", "ring12"];
+/// The format of new filters.  This matches the default ring version
+/// of the processing thread.
+pub const DEFAULT_FORMAT: &str = "ring11";
+
+/// Describes a filter:
+///
+/// * name - the filter name.
+/// * gate - name of the gate that selects the events written.
+/// * parameters - names of the parameters written.
+/// * file - the file written, None if not yet set.
+/// * enabled - true if events are being written.
+/// * format - the file format, one of SUPPORTED_FORMATS.
+/// * events - number of events written to the file.
+/// * error - if writing failed, why.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilterDescription {
+    pub name: String,
+    pub gate: String,
+    pub parameters: Vec<String>,
+    pub file: Option<String>,
+    pub enabled: bool,
+    pub format: String,
+    pub events: u64,
+    pub error: Option<String>,
+}
+
+struct Filter {
+    gate: String,
+    parameters: Vec<(String, u32)>,
+    file: Option<String>,
+    format: String,
+    enabled: bool,
+    writer: Option<ParameterFileWriter<BufWriter<File>>>,
+    events: u64,
+    error: Option<String>,
+}
+
+impl Filter {
+    // Open the file and write the parameter definitions:
+
+    fn open(&mut self) -> Result<(), String> {
+        let name = match &self.file {
+            Some(f) => f.clone(),
+            None => return Err(String::from("The filter has no file")),
+        };
+        let version = if self.format == "ring12" {
+            RingVersion::V12
+        } else {
+            RingVersion::V11
+        };
+        let file = File::create(&name).map_err(|e| format!("Unable to open {}: {}", name, e))?;
+        let mut writer = ParameterFileWriter::new(BufWriter::new(file), version)
+            .map_err(|e| format!("Unable to write {}: {}", name, e))?;
+        let mut defs = ParameterDefinitions::new();
+        for (name, id) in self.parameters.iter() {
+            defs.add(*id, name);
+        }
+        writer
+            .write_definitions(&defs)
+            .map_err(|e| format!("Unable to write {}: {}", name, e))?;
+        self.writer = Some(writer);
+        self.events = 0;
+        self.error = None;
+        Ok(())
+    }
+    // Flush and close the file, if it is open:
+
+    fn close(&mut self) {
+        if let Some(mut writer) = self.writer.take() {
+            if let Err(e) = writer.flush() {
+                self.error = Some(format!("Flushing the file failed: {}", e));
+            }
+        }
+    }
+    // Write the filter's parameters from events.  A failure disables
+    // the filter:
+
+    fn write(&mut self, events: &[parameters::Event]) {
+        if let Some(writer) = self.writer.as_mut() {
+            for event in events {
+                let mut item = ParameterItem::new(self.events);
+                for p in event {
+                    if self.parameters.iter().any(|(_, id)| *id == p.id) {
+                        item.add(p.id, p.value);
+                    }
+                }
+                if let Err(e) = writer.write_event(&item) {
+                    self.error = Some(format!("Writing an event failed: {}", e));
+                    self.enabled = false;
+                    return;
+                }
+                self.events += 1;
+            }
+        }
+    }
+    fn describe(&self, name: &str) -> FilterDescription {
+        FilterDescription {
+            name: String::from(name),
+            gate: self.gate.clone(),
+            parameters: self.parameters.iter().map(|(n, _)| n.clone()).collect(),
+            file: self.file.clone(),
+            enabled: self.enabled,
+            format: self.format.clone(),
+            events: self.events,
+            error: self.error.clone(),
+        }
+    }
+}
+
+/// The set of filters, keyed by name.
+#[derive(Default)]
+pub struct FilterSet {
+    filters: BTreeMap<String, Filter>,
+}
+
+impl FilterSet {
+    // Look up a filter that must exist:
+
+    fn get_mut(&mut self, name: &str) -> Result<&mut Filter, String> {
+        self.filters
+            .get_mut(name)
+            .ok_or_else(|| format!("The filter {} does not exist", name))
+    }
+    /// Create a disabled filter without a file.
+    ///
+    /// ### Parameters
+    /// * name - name of the filter, must be unique.
+    /// * gate - name of the gate that selects the events to write.
+    /// * parameters - names and histogramer ids of the parameters to
+    ///   write.  There must be at least one.
+    ///
+    pub fn add(
+        &mut self,
+        name: &str,
+        gate: &str,
+        parameters: &[(String, u32)],
+    ) -> Result<(), String> {
+        if self.filters.contains_key(name) {
+            return Err(format!("The filter {} already exists", name));
+        }
+        if parameters.is_empty() {
+            return Err(String::from("A filter must write at least one parameter"));
+        }
+        self.filters.insert(
+            String::from(name),
+            Filter {
+                gate: String::from(gate),
+                parameters: parameters.to_vec(),
+                file: None,
+                format: String::from(DEFAULT_FORMAT),
+                enabled: false,
+                writer: None,
+                events: 0,
+                error: None,
+            },
+        );
+        Ok(())
+    }
+    /// Delete a filter, closing its file.
+    pub fn delete(&mut self, name: &str) -> Result<(), String> {
+        let mut filter = self
+            .filters
+            .remove(name)
+            .ok_or_else(|| format!("The filter {} does not exist", name))?;
+        filter.close();
+        Ok(())
+    }
+    /// Enable a filter.  Its file must have been set.  If the file is
+    /// not open it is opened and the parameter definitions written.
+    pub fn enable(&mut self, name: &str) -> Result<(), String> {
+        let filter = self.get_mut(name)?;
+        if filter.writer.is_none() {
+            filter.open()?;
+        }
+        filter.enabled = true;
+        Ok(())
+    }
+    /// Disable a filter, flushing its file.
+    pub fn disable(&mut self, name: &str) -> Result<(), String> {
+        let filter = self.get_mut(name)?;
+        filter.enabled = false;
+        if let Some(writer) = filter.writer.as_mut() {
+            writer
+                .flush()
+                .map_err(|e| format!("Flushing the file failed: {}", e))?;
+        }
+        Ok(())
+    }
+    /// Change the gate of a filter.
+    pub fn regate(&mut self, name: &str, gate: &str) -> Result<(), String> {
+        self.get_mut(name)?.gate = String::from(gate);
+        Ok(())
+    }
+    /// Set the file of a filter.  Any open file is closed.  If the
+    /// filter is enabled, the new file is opened; if that fails the
+    /// filter is disabled.
+    pub fn set_file(&mut self, name: &str, file: &str) -> Result<(), String> {
+        let filter = self.get_mut(name)?;
+        filter.close();
+        filter.file = Some(String::from(file));
+        if filter.enabled {
+            if let Err(e) = filter.open() {
+                filter.enabled = false;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+    /// Set the format of a filter.  This can't be done while the
+    /// filter's file is open, that is once the filter has been enabled
+    /// until a new file is set.
+    pub fn set_format(&mut self, name: &str, format: &str) -> Result<(), String> {
+        let filter = self.get_mut(name)?;
+        let format = format.to_ascii_lowercase();
+        if !SUPPORTED_FORMATS.contains(&format.as_str()) {
+            return Err(format!("Unsupported format '{}'", format));
+        }
+        if filter.writer.is_some() {
+            return Err(String::from(
+                "The format can't be changed while the filter's file is open",
+            ));
+        }
+        filter.format = format;
+        Ok(())
+    }
+    /// Describe the filters whose names match a glob pattern, sorted by
+    /// name.
+    pub fn list(&self, pattern: &str) -> Result<Vec<FilterDescription>, String> {
+        let pattern = Pattern::new(pattern).map_err(|e| String::from(e.msg))?;
+        Ok(self
+            .filters
+            .iter()
+            .filter(|(name, _)| pattern.matches(name))
+            .map(|(name, f)| f.describe(name))
+            .collect())
+    }
+    /// The names and gates of the enabled filters.
+    pub fn enabled_gates(&self) -> Vec<(String, String)> {
+        self.filters
+            .iter()
+            .filter(|(_, f)| f.enabled)
+            .map(|(name, f)| (name.clone(), f.gate.clone()))
+            .collect()
+    }
+    /// Write events to the enabled filters.  selected has the events
+    /// selected by each filter's gate, keyed by filter name.
+    pub fn write(&mut self, selected: &HashMap<String, Vec<parameters::Event>>) {
+        for (name, filter) in self.filters.iter_mut() {
+            if filter.enabled {
+                if let Some(events) = selected.get(name) {
+                    filter.write(events);
+                }
+            }
+        }
+    }
+}
+impl Drop for FilterSet {
+    fn drop(&mut self) {
+        for filter in self.filters.values_mut() {
+            filter.close();
+        }
+    }
+}
+
+/// The filters are shared between the processing thread and its API.
+pub type SharedFilters = Arc<Mutex<FilterSet>>;
+
+#[cfg(test)]
+mod filter_tests {
+    use super::*;
+    use crate::ring_items::{self, FromRaw, RingItem};
+    use std::fs;
+
+    fn params() -> Vec<(String, u32)> {
+        vec![(String::from("p1"), 1), (String::from("p2"), 2)]
+    }
+    // Read the ring items in a file:
+
+    fn read_items(name: &str) -> Vec<RingItem> {
+        let mut file = fs::File::open(name).expect("Opening filter file");
+        let mut result = vec![];
+        while let Ok(item) = RingItem::read_item(&mut file) {
+            result.push(item);
+        }
+        result
+    }
+
+    #[test]
+    fn add_1() {
+        let mut filters = FilterSet::default();
+        filters.add("f", "g", &params()).expect("Adding");
+        assert!(filters.add("f", "g", &params()).is_err());
+        assert!(filters.add("empty", "g", &[]).is_err());
+
+        let list = filters.list("*").expect("Listing");
+        assert_eq!(
+            vec![FilterDescription {
+                name: String::from("f"),
+                gate: String::from("g"),
+                parameters: vec![String::from("p1"), String::from("p2")],
+                file: None,
+                enabled: false,
+                format: String::from(DEFAULT_FORMAT),
+                events: 0,
+                error: None
+            }],
+            list
+        );
+        assert!(filters.list("x*").unwrap().is_empty());
+        assert!(filters.list("[").is_err());
+    }
+    #[test]
+    fn enable_1() {
+        // A filter needs a file to be enabled:
+
+        let mut filters = FilterSet::default();
+        filters.add("f", "g", &params()).expect("Adding");
+        assert!(filters.enable("f").is_err());
+        assert!(filters.enable("nosuch").is_err());
+        assert!(filters.enabled_gates().is_empty());
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let name = dir.path().join("f.evt");
+        filters
+            .set_file("f", name.to_str().unwrap())
+            .expect("Setting file");
+        filters.enable("f").expect("Enabling");
+        assert_eq!(
+            vec![(String::from("f"), String::from("g"))],
+            filters.enabled_gates()
+        );
+        filters.disable("f").expect("Disabling");
+        assert!(filters.enabled_gates().is_empty());
+    }
+    #[test]
+    fn write_1() {
+        // The file has the definitions then the filter's parameters
+        // of each event.  Disabling and enabling continues the file:
+
+        let mut filters = FilterSet::default();
+        filters
+            .add("f", "g", &[(String::from("p1"), 1)])
+            .expect("Adding");
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let name = dir.path().join("f.evt");
+        let name = name.to_str().unwrap();
+        filters.set_file("f", name).expect("Setting file");
+        filters.enable("f").expect("Enabling");
+
+        let mut selected = HashMap::new();
+        selected.insert(
+            String::from("f"),
+            vec![vec![
+                parameters::EventParameter::new(1, 10.0),
+                parameters::EventParameter::new(2, 20.0),
+            ]],
+        );
+        filters.write(&selected);
+        filters.disable("f").expect("Disabling");
+        filters.write(&selected); // Not written.
+        filters.enable("f").expect("Enabling");
+        filters.write(&selected);
+        assert_eq!(2, filters.list("f").unwrap()[0].events);
+        filters.delete("f").expect("Deleting");
+        assert!(filters.delete("f").is_err());
+
+        let items = read_items(name);
+        assert_eq!(4, items.len());
+        assert_eq!(ring_items::FORMAT_ITEM, items[0].type_id());
+        let defs: ParameterDefinitions =
+            FromRaw::to_specific(&items[1], RingVersion::V11).expect("Definitions");
+        let defs: Vec<(u32, String)> = defs.iter().map(|d| (d.id(), d.name())).collect();
+        assert_eq!(vec![(1, String::from("p1"))], defs);
+        for (i, item) in items[2..].iter().enumerate() {
+            let event: ParameterItem = FromRaw::to_specific(item, RingVersion::V11).expect("Event");
+            assert_eq!(i as u64, event.trigger());
+            let values: Vec<(u32, f64)> = event.iter().map(|p| (p.id(), p.value())).collect();
+            assert_eq!(vec![(1, 10.0)], values);
+        }
+    }
+    #[test]
+    fn format_1() {
+        let mut filters = FilterSet::default();
+        filters.add("f", "g", &params()).expect("Adding");
+        assert!(filters.set_format("f", "xdr").is_err());
+        filters.set_format("f", "RING12").expect("Setting format");
+        assert_eq!("ring12", filters.list("f").unwrap()[0].format);
+
+        // Not while the file is open, but once a new file is set:
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let name = dir.path().join("f.evt");
+        filters
+            .set_file("f", name.to_str().unwrap())
+            .expect("Setting file");
+        filters.enable("f").expect("Enabling");
+        assert!(filters.set_format("f", "ring11").is_err());
+        filters.disable("f").expect("Disabling");
+        filters
+            .set_file("f", name.to_str().unwrap())
+            .expect("Setting file");
+        filters.set_format("f", "ring11").expect("Setting format");
+    }
+    #[test]
+    fn regate_1() {
+        let mut filters = FilterSet::default();
+        filters.add("f", "g", &params()).expect("Adding");
+        filters.regate("f", "other").expect("Regating");
+        assert_eq!("other", filters.list("f").unwrap()[0].gate);
+        assert!(filters.regate("nosuch", "g").is_err());
+    }
+}
@@ -12,11 +12,51 @@
 //! - Start the thread.
 //! - Stop the thread.
 //! - Set the thread event aggregation size.
-//! - Attach the thread to a data source.
+//! - Attach the thread to a data source: a file, a live NSCLDAQ
+//! ring buffer or a SpecTcl XDR filter file.
+//! - Attach the thread to a list of files that are analyzed one after
+//! the other (a chained replay).
 //! - Start processing from the data source.
 //! - Stop processing data from the data source.
 //! - List the currently attached file.
+//! - Pause processing from the data source.
+//! - Resume processing from the data source.
+//! - Report the position within the data source.
+//! - Set the size of the data source read buffer.
+//! - Report read statistics (bytes and items read, time spent reading
+//! and time spent processing what was read).
+//! - Report summaries of the most recent replays.
+//! - Verify that body header timestamps increase for each source id.
+//! - Set a policy for writing spectra at the end of each run.
+//! - Manage filters that write the events satisfying a gate to a file.
+//! - Accumulate scalers and, optionally, histogram them against time.
+//! - Track the run being analyzed from state change items and,
+//!   optionally, clear spectra at the beginning of each run.
+//! - Set the unpacker for raw (e.g. event built) physics events.  Programs
+//!   register unpackers for their own event formats with the API.
 //!
+//!  The data source is read through a buffered reader.  Ring items
+//!  are read with several small reads which, without buffering, each
+//!  become a system call.  This matters when replaying files of small
+//!  parameter items from network file systems.  Ring items that fit
+//!  in the reader's buffer are parsed in place (see RingItemRef) rather
+//!  than being copied out of it first.
+//!
+//!  Pause differs from Stop in that Pause retains the events that
+//!  have been read but not yet sent to the histogramer.  Resume picks
+//!  up from exactly the ring item following the last one read
+//!  without flushing, re-reading or skipping anything.
+//!
+//!  Ring buffer data sources are named ring:tcp://host/ringname (see
+//!  the ring module).  They have the same Start/Stop semantics as files
+//!  but never end; if the connection to the ring is lost it's
+//!  re-established.  Events from rings are sent to the histogramer
+//!  whenever no data arrive for RING_POLL_INTERVAL so that spectra
+//!  stay current when the event rate is low.
+//!
+//!  SpecTcl XDR filter file data sources are named xdr:path (see the
+//!  xdr module).  Their records are converted to parameter file ring
+//!  items as they are read.
 //!
 //!  When processing starts, if there are parameter description
 //!  records, the processing thread creats a map between the
@@ -30,31 +70,104 @@
 //!  those events are then blocked up and sent to the
 //!  histogramer from processing.
 //!
+//!  In a chained replay, the next file is opened and its initial
+//!  ring items, including its parameter definitions, are read and
+//!  mapped in a separate thread while the current file is analyzed
+//!  (see the prefetch module).  At the end of each file, analysis
+//!  continues with the next file in the list.
+//!
+//!  When run state change items are encountered, the pending events
+//!  are flushed to the histogramer and a trace is declared so that
+//!  clients can annotate their displays with run boundaries.  At the
+//!  end of a run, if an end of run write policy is set, spectra are
+//!  written to a file for that run and optionally cleared (see the
+//!  endrun module).
+//!
+//!  Before each batch of events is sent to the histogramer, the events
+//!  that satisfy the gate of each enabled filter are written to that
+//!  filter's file (see the filter module).
+//!
+//!  A replay runs from when analysis starts until it is stopped, the
+//!  end of the data is reached, a read error occurs or the data source
+//!  is replaced or detached.  When a replay ends, a summary of it is
+//!  recorded (see ReplaySummary).  The summaries of the last
+//!  MAX_REPLAY_SUMMARIES replays are retained.
+//!
+//!  Event built data must have non-decreasing timestamps from each
+//!  event source.  A misconfigured event builder can break this, silently
+//!  corrupting time difference spectra.  When timestamp verification is
+//!  enabled, the body header timestamps of ring items are checked for each
+//!  source id and items whose timestamp is less than that of the previous
+//!  item from the same source are counted (see SourceOrdering).
+//!
+use crate::crash;
 use crate::messaging;
 use crate::messaging::parameter_messages;
 use crate::messaging::spectrum_messages;
 use crate::parameters;
 use crate::ring_items;
 use crate::ring_items::*;
+use crate::trace;
+use crate::treevariables;
+use crate::unpackers;
+use crate::unpackers::evb;
+use crate::unpackers::Unpacker;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fs;
 use std::fs::File;
-use std::sync::mpsc;
-use std::thread;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub mod endrun;
+pub mod filter;
+pub mod prefetch;
+pub mod ring;
+pub mod runinfo;
+pub mod scalers;
+pub mod xdr;
 
 const DEFAULT_EVENT_CHUNKSIZE: usize = 100;
+pub const DEFAULT_READ_BUFFER_SIZE: usize = 1024 * 1024;
+/// Longest the processing thread waits for data from a ring before
+/// checking for requests.
+pub const RING_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Number of replay summaries retained.
+pub const MAX_REPLAY_SUMMARIES: usize = 10;
+/// Body header timestamp of items that have no timestamp.  These are not
+/// checked when verifying timestamp ordering.
+pub const NULL_TIMESTAMP: u64 = u64::MAX;
 
 pub enum RequestType {
-    Attach(String),   // Attach this file.
-    Detach,           // Stop analyzing and close source
-    Start,            // Start analyzing source
-    Stop,             // Stop analyzing, keep file open.
-    ChunkSize(usize), // Set # events per request to Histogramer
-    GetChunkSize,     // Return chunksize.
-    Exit,             // Exit thread (mostly for testing).
-    List,             // Descsribe what's attached.
-    Version(RingVersion), // Version of the Ring.
-    GetVersion,      // Return current ring version
-    State,           //"Active" if processing, "Inactive" otherwise.
+    Attach(String),                           // Attach this file.
+    AttachList(Vec<String>),                  // Attach files for a chained replay.
+    Detach,                                   // Stop analyzing and close source
+    Start,                                    // Start analyzing source
+    Stop,                                     // Stop analyzing, keep file open.
+    ChunkSize(usize),                         // Set # events per request to Histogramer
+    GetChunkSize,                             // Return chunksize.
+    Exit,                                     // Exit thread (mostly for testing).
+    List,                                     // Descsribe what's attached.
+    Version(RingVersion),                     // Version of the Ring.
+    GetVersion,                               // Return current ring version
+    State,               //"Active" if processing, "Paused" if paused, "Inactive" otherwise.
+    Pause,               // Stop analyzing but retain the in-flight event batch.
+    Resume,              // Resume paused analysis.
+    Position,            // Return the offset and number of items read.
+    BufferSize(usize),   // Set the read buffer size in bytes.
+    GetBufferSize,       // Return the read buffer size.
+    Statistics,          // Return the read statistics.
+    Sampling(u64, bool), // Histogram every Nth event, optionally weighted by N.
+    GetSampling,         // Return the sampling factor and compensation flag.
+    IdMapStrategy(parameters::IdMapStrategy), // Set how parameter id maps are stored.
+    GetIdMap,            // Describe the parameter id map.
+    CompactIdMap,        // Release unused parameter id map storage.
+    MappedParameters,    // Ids of the histogramer parameters the data source produces.
+    VerifyTimestamps(bool), // Enable/disable timestamp order verification.
+    TimestampOrdering,   // Return the timestamp ordering statistics.
+    EvbUnpacker(Option<evb::EvbDescription>), // Unpack event built physics events.
+    Unpacker(Option<(String, usize)>), // Unpack physics events with a registered unpacker.
+    GetUnpacker,         // Name of the unpacker physics events are unpacked with.
 }
 pub struct Request {
     reply_chan: mpsc::Sender<Reply>,
@@ -63,6 +176,132 @@ pub struct Request {
 
 pub type Reply = Result<String, String>;
 
+/// Read statistics for the attached data source.  These are reset
+/// when a data source is attached.
+///
+/// * bytes - number of bytes of ring items read.
+/// * items - number of ring items read.
+/// * read_seconds - time spent reading ring items.
+/// * process_seconds - time spent processing the ring items read
+/// (including sending events to the histogramer).
+/// * sample_factor - only one in this many events is histogrammed.
+/// * ring_dropped - ring items dropped because analysis could not keep
+///   up with a live ring (see ring::RingSource).  0 for other sources.
+/// * ring_reconnects - number of times a lost connection to a live ring
+///   was remade.  0 for other sources.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReadStatistics {
+    pub bytes: u64,
+    pub items: u64,
+    pub read_seconds: f64,
+    pub process_seconds: f64,
+    pub sample_factor: u64,
+    pub ring_dropped: u64,
+    pub ring_reconnects: u64,
+}
+impl ReadStatistics {
+    /// Read throughput in bytes/second.  0 if nothing has been read.
+    pub fn read_rate(&self) -> f64 {
+        if self.read_seconds > 0.0 {
+            self.bytes as f64 / self.read_seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Timestamp ordering statistics for one source id.  These are reset
+/// when a data source is attached, when analysis moves to the next file
+/// of a chained replay and when verification is enabled.
+///
+/// * source_id - the source id from the body headers.
+/// * items - number of items from the source with timestamps.
+/// * out_of_order - number of those items whose timestamp was less than
+/// that of the previous item from the source.
+/// * last_timestamp - timestamp of the most recent item from the source.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourceOrdering {
+    pub source_id: u32,
+    pub items: u64,
+    pub out_of_order: u64,
+    pub last_timestamp: u64,
+}
+
+/// Description of the map from the parameter ids in the event file
+/// to those of the histogramer:
+///
+/// * strategy - how the map is stored (see parameters::IdMapStrategy).
+/// * sparse - true if the map is currently stored sparsely.
+/// * span - one more than the largest file parameter id mapped.
+/// * mapped - number of file parameter ids that are mapped.
+/// * allocated - number of map entries allocated.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IdMapDescription {
+    pub strategy: parameters::IdMapStrategy,
+    pub sparse: bool,
+    pub span: usize,
+    pub mapped: usize,
+    pub allocated: usize,
+}
+
+/// Summary of a replay:
+///
+/// * source - the data source that was being analyzed when the replay
+/// started.  For a chained replay, this is the first file.
+/// * events - number of events processed.
+/// * items - number of ring items read.
+/// * skipped - number of ring items read that were not processed
+/// (item types that are ignored and items that could not be decoded).
+/// * unsampled - number of events that were not histogrammed because
+/// they were not part of the sample.
+/// * seconds - time spent analyzing, not counting time paused.
+/// * end_reason - why the replay ended: "stopped", "end of data",
+/// "read error: reason", "attached", "detached" or "exit".
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplaySummary {
+    pub source: String,
+    pub events: u64,
+    pub items: u64,
+    pub skipped: u64,
+    pub unsampled: u64,
+    pub seconds: f64,
+    pub end_reason: String,
+}
+impl ReplaySummary {
+    fn new(source: &str) -> ReplaySummary {
+        ReplaySummary {
+            source: String::from(source),
+            events: 0,
+            items: 0,
+            skipped: 0,
+            unsampled: 0,
+            seconds: 0.0,
+            end_reason: String::new(),
+        }
+    }
+    /// Throughput in events/second.  0 if no time was spent analyzing.
+    pub fn event_rate(&self) -> f64 {
+        if self.seconds > 0.0 {
+            self.events as f64 / self.seconds
+        } else {
+            0.0
+        }
+    }
+}
+/// Replay summaries are shared between the processing thread, which
+/// makes them, and its API, which reports them.  Oldest first.
+pub type SharedReplaySummaries = Arc<Mutex<VecDeque<ReplaySummary>>>;
+/// The unpackers that can be selected by name.  Shared between the
+/// processing thread, which creates them, and its API, which adds to
+/// them.
+pub type SharedUnpackerRegistry = Arc<Mutex<unpackers::Registry>>;
+/// Prefix of the name of an event built data unpacker in use.
+pub const EVB_UNPACKER_PREFIX: &str = "evb:";
+
 // for now stubs:
 
 /// We'll need an API object so that we can hold
@@ -73,6 +312,12 @@ pub type Reply = Result<String, String>;
 #[derive(Clone)]
 pub struct ProcessingApi {
     req_chan: mpsc::Sender<Request>,
+    summaries: SharedReplaySummaries,
+    end_run_write: endrun::SharedEndRunWrite,
+    filters: filter::SharedFilters,
+    unpackers: SharedUnpackerRegistry,
+    scalers: scalers::SharedScalers,
+    run_info: runinfo::SharedRunInfo,
 }
 
 impl ProcessingApi {
@@ -101,11 +346,50 @@ impl ProcessingApi {
     /// event file to be processed at the same time,  however
     /// rustogrammer only actually creates one of these.
 
-    pub fn new(chan: &mpsc::Sender<messaging::Request>) -> ProcessingApi {
+    pub fn new(
+        chan: &mpsc::Sender<messaging::Request>,
+        tracedb: &trace::SharedTraceStore,
+        treevariables: &treevariables::SharedTreeVariableStore,
+    ) -> ProcessingApi {
         let (send, recv) = mpsc::channel();
         let api_chan = chan.clone();
-        thread::spawn(move || processing_thread(recv, api_chan));
-        ProcessingApi { req_chan: send }
+        let tracedb = tracedb.clone();
+        let treevariables = treevariables.clone();
+        let summaries: SharedReplaySummaries = Arc::new(Mutex::new(VecDeque::new()));
+        let thread_summaries = summaries.clone();
+        let end_run_write = endrun::SharedEndRunWrite::default();
+        let thread_end_run_write = end_run_write.clone();
+        let filters = filter::SharedFilters::default();
+        let thread_filters = filters.clone();
+        let unpackers: SharedUnpackerRegistry = Arc::new(Mutex::new(unpackers::Registry::new()));
+        let thread_unpackers = unpackers.clone();
+        let scalers: scalers::SharedScalers = Arc::new(Mutex::new(scalers::ScalerStore::new()));
+        let thread_scalers = scalers.clone();
+        let run_info = runinfo::SharedRunInfo::default();
+        let thread_run_info = run_info.clone();
+        crash::spawn("processing", move || {
+            processing_thread(
+                recv,
+                api_chan,
+                tracedb,
+                treevariables,
+                thread_summaries,
+                thread_end_run_write,
+                thread_filters,
+                thread_unpackers,
+                thread_scalers,
+                thread_run_info,
+            )
+        });
+        ProcessingApi {
+            req_chan: send,
+            summaries,
+            end_run_write,
+            filters,
+            unpackers,
+            scalers,
+            run_info,
+        }
     }
 
     pub fn stop_thread(&self) -> Result<String, String> {
@@ -114,6 +398,13 @@ impl ProcessingApi {
     pub fn attach(&self, source: &str) -> Result<String, String> {
         self.transaction(RequestType::Attach(String::from(source)))
     }
+    /// Attach a list of files that are analyzed one after the other.
+    /// The first file is attached immediately; an error opening it is
+    /// an error.  Files later in the list that can't be opened are
+    /// reported and skipped when analysis gets to them.
+    pub fn attach_list(&self, files: &[String]) -> Result<String, String> {
+        self.transaction(RequestType::AttachList(files.to_vec()))
+    }
     pub fn detach(&self) -> Result<String, String> {
         self.transaction(RequestType::Detach)
     }
@@ -150,6 +441,269 @@ impl ProcessingApi {
     pub fn processing_state(&self) -> Result<String, String> {
         self.transaction(RequestType::State)
     }
+    pub fn pause_analysis(&self) -> Result<String, String> {
+        self.transaction(RequestType::Pause)
+    }
+    pub fn resume_analysis(&self) -> Result<String, String> {
+        self.transaction(RequestType::Resume)
+    }
+    /// Returns the byte offset in the data source of the next ring item
+    /// that will be read and the number of ring items read so far.
+    pub fn get_position(&self) -> Result<(u64, u64), String> {
+        let raw = self.transaction(RequestType::Position)?;
+        let fields: Vec<&str> = raw.split_whitespace().collect();
+        if fields.len() != 2 {
+            return Err(format!("Invalid position reply: '{}'", raw));
+        }
+        let offset = fields[0]
+            .parse::<u64>()
+            .map_err(|e| format!("Bad offset in position reply: {}", e))?;
+        let items = fields[1]
+            .parse::<u64>()
+            .map_err(|e| format!("Bad item count in position reply: {}", e))?;
+        Ok((offset, items))
+    }
+    /// Set the size of the buffer used to read the data source.
+    /// This takes effect immediately, even if a file is attached.
+    pub fn set_buffer_size(&self, bytes: usize) -> Result<String, String> {
+        self.transaction(RequestType::BufferSize(bytes))
+    }
+    pub fn get_buffer_size(&self) -> Result<usize, String> {
+        let raw = self.transaction(RequestType::GetBufferSize)?;
+        raw.parse::<usize>()
+            .map_err(|e| format!("Bad buffer size reply: {}", e))
+    }
+    /// Returns the read statistics for the attached data source.
+    pub fn get_read_statistics(&self) -> Result<ReadStatistics, String> {
+        let raw = self.transaction(RequestType::Statistics)?;
+        let fields: Vec<&str> = raw.split_whitespace().collect();
+        if fields.len() != 7 {
+            return Err(format!("Invalid statistics reply: '{}'", raw));
+        }
+        let bytes = fields[0]
+            .parse::<u64>()
+            .map_err(|e| format!("Bad byte count in statistics reply: {}", e))?;
+        let items = fields[1]
+            .parse::<u64>()
+            .map_err(|e| format!("Bad item count in statistics reply: {}", e))?;
+        let read_seconds = fields[2]
+            .parse::<f64>()
+            .map_err(|e| format!("Bad read time in statistics reply: {}", e))?;
+        let process_seconds = fields[3]
+            .parse::<f64>()
+            .map_err(|e| format!("Bad processing time in statistics reply: {}", e))?;
+        let sample_factor = fields[4]
+            .parse::<u64>()
+            .map_err(|e| format!("Bad sample factor in statistics reply: {}", e))?;
+        let ring_dropped = fields[5]
+            .parse::<u64>()
+            .map_err(|e| format!("Bad dropped item count in statistics reply: {}", e))?;
+        let ring_reconnects = fields[6]
+            .parse::<u64>()
+            .map_err(|e| format!("Bad reconnect count in statistics reply: {}", e))?;
+        Ok(ReadStatistics {
+            bytes,
+            items,
+            read_seconds,
+            process_seconds,
+            sample_factor,
+            ring_dropped,
+            ring_reconnects,
+        })
+    }
+    /// Histogram only every _factor_th event.  A factor of 1 histograms
+    /// all events.  If _compensate_ is true, sampled events increment
+    /// spectra by _factor_ so that the spectra approximate those of a
+    /// full replay.  Events already read are histogrammed before
+    /// the new sampling takes effect.
+    pub fn set_sampling(&self, factor: u64, compensate: bool) -> Result<String, String> {
+        self.transaction(RequestType::Sampling(factor, compensate))
+    }
+    /// Returns the sampling factor and whether sampled events are weighted.
+    pub fn get_sampling(&self) -> Result<(u64, bool), String> {
+        let raw = self.transaction(RequestType::GetSampling)?;
+        let fields: Vec<&str> = raw.split_whitespace().collect();
+        if fields.len() != 2 {
+            return Err(format!("Invalid sampling reply: '{}'", raw));
+        }
+        let factor = fields[0]
+            .parse::<u64>()
+            .map_err(|e| format!("Bad sample factor in sampling reply: {}", e))?;
+        let compensate = fields[1]
+            .parse::<bool>()
+            .map_err(|e| format!("Bad compensation flag in sampling reply: {}", e))?;
+        Ok((factor, compensate))
+    }
+    /// Set how the parameter id map is stored.  This applies to the
+    /// current map as well as maps made for subsequent files.
+    pub fn set_id_map_strategy(
+        &self,
+        strategy: parameters::IdMapStrategy,
+    ) -> Result<String, String> {
+        self.transaction(RequestType::IdMapStrategy(strategy))
+    }
+    /// Describe the current parameter id map.
+    pub fn get_id_map(&self) -> Result<IdMapDescription, String> {
+        let raw = self.transaction(RequestType::GetIdMap)?;
+        let fields: Vec<&str> = raw.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!("Invalid id map reply: '{}'", raw));
+        }
+        let strategy = parameters::IdMapStrategy::parse(fields[0])?;
+        let sparse = fields[1]
+            .parse::<bool>()
+            .map_err(|e| format!("Bad sparse flag in id map reply: {}", e))?;
+        let span = fields[2]
+            .parse::<usize>()
+            .map_err(|e| format!("Bad span in id map reply: {}", e))?;
+        let mapped = fields[3]
+            .parse::<usize>()
+            .map_err(|e| format!("Bad mapped count in id map reply: {}", e))?;
+        let allocated = fields[4]
+            .parse::<usize>()
+            .map_err(|e| format!("Bad allocation in id map reply: {}", e))?;
+        Ok(IdMapDescription {
+            strategy,
+            sparse,
+            span,
+            mapped,
+            allocated,
+        })
+    }
+    /// Release parameter id map storage that is not needed for the
+    /// ids that are mapped.
+    pub fn compact_id_map(&self) -> Result<String, String> {
+        self.transaction(RequestType::CompactIdMap)
+    }
+    /// Returns the ids of the histogramer parameters the attached data
+    /// source produces, i.e. those its parameter definitions were mapped to.
+    /// This is empty until the data source's parameter definitions
+    /// have been processed.
+    pub fn get_mapped_parameters(&self) -> Result<Vec<u32>, String> {
+        let raw = self.transaction(RequestType::MappedParameters)?;
+        raw.split_whitespace()
+            .map(|id| {
+                id.parse::<u32>()
+                    .map_err(|e| format!("Bad parameter id in mapped parameters reply: {}", e))
+            })
+            .collect()
+    }
+    /// Enable or disable verification of the ordering of body header
+    /// timestamps.  Enabling verification resets the ordering statistics.
+    pub fn set_timestamp_verification(&self, enable: bool) -> Result<String, String> {
+        self.transaction(RequestType::VerifyTimestamps(enable))
+    }
+    /// Returns whether timestamp ordering is being verified and the
+    /// ordering statistics of each source id, in source id order.
+    pub fn get_timestamp_ordering(&self) -> Result<(bool, Vec<SourceOrdering>), String> {
+        let raw = self.transaction(RequestType::TimestampOrdering)?;
+        let fields: Vec<&str> = raw.split_whitespace().collect();
+        if fields.is_empty() || !(fields.len() - 1).is_multiple_of(4) {
+            return Err(format!("Invalid timestamp ordering reply: '{}'", raw));
+        }
+        let enabled = fields[0]
+            .parse::<bool>()
+            .map_err(|e| format!("Bad enable flag in timestamp ordering reply: {}", e))?;
+        let mut sources = vec![];
+        for source in fields[1..].chunks(4) {
+            let value = |i: usize| {
+                source[i]
+                    .parse::<u64>()
+                    .map_err(|e| format!("Bad value in timestamp ordering reply: {}", e))
+            };
+            sources.push(SourceOrdering {
+                source_id: value(0)? as u32,
+                items: value(1)?,
+                out_of_order: value(2)?,
+                last_timestamp: value(3)?,
+            });
+        }
+        Ok((enabled, sources))
+    }
+    /// Set the unpacker for event built physics events.  None stops
+    /// physics events from being unpacked.  The unpacker's parameter
+    /// definitions replace the parameter map of the data source.
+    pub fn set_evb_unpacker(
+        &self,
+        description: Option<evb::EvbDescription>,
+    ) -> Result<String, String> {
+        self.transaction(RequestType::EvbUnpacker(description))
+    }
+    /// Register an unpacker so that it can be selected by name.  Only
+    /// tests do this; Rustogramer's unpackers are those of
+    /// unpackers::Registry::new.
+    #[cfg(test)]
+    pub fn register_unpacker(
+        &self,
+        name: &str,
+        factory: unpackers::UnpackerFactory,
+    ) -> Result<(), String> {
+        self.unpackers.lock().unwrap().register(name, factory)
+    }
+    /// The registered unpackers.
+    pub fn unpacker_registry(&self) -> unpackers::Registry {
+        self.unpackers.lock().unwrap().clone()
+    }
+    /// Unpack physics events with the registered unpacker _name_ which
+    /// produces at most _count_ parameters.  None stops physics events
+    /// from being unpacked.  The unpacker's parameter definitions
+    /// replace the parameter map of the data source.
+    pub fn set_unpacker(&self, unpacker: Option<(&str, usize)>) -> Result<String, String> {
+        self.transaction(RequestType::Unpacker(
+            unpacker.map(|(name, count)| (String::from(name), count)),
+        ))
+    }
+    /// The name of the unpacker physics events are unpacked with, if
+    /// any.  Event built data unpackers have EVB_UNPACKER_PREFIX
+    /// prepended to their names.
+    pub fn get_unpacker(&self) -> Result<Option<String>, String> {
+        let name = self.transaction(RequestType::GetUnpacker)?;
+        Ok(if name.is_empty() { None } else { Some(name) })
+    }
+    /// Returns the summaries of the most recent replays, oldest first.
+    /// A replay's summary is recorded before the reply to the request that
+    /// ended it (e.g. stop_analysis) is sent.
+    pub fn get_replay_summaries(&self) -> Vec<ReplaySummary> {
+        self.summaries.lock().unwrap().iter().cloned().collect()
+    }
+    /// Returns the summary of the most recent replay, if there was one.
+    pub fn last_replay_summary(&self) -> Option<ReplaySummary> {
+        self.summaries.lock().unwrap().back().cloned()
+    }
+    /// Set the policy for writing spectra when an END_RUN item is
+    /// processed.  None removes the policy.  This takes effect at the
+    /// next end of run.
+    pub fn set_end_run_write(&self, policy: Option<endrun::EndRunWrite>) {
+        self.end_run_write.lock().unwrap().policy = policy;
+    }
+    /// Returns the end of run write policy and the outcome of the most
+    /// recent end of run write.
+    pub fn get_end_run_write(&self) -> endrun::EndRunWriteState {
+        self.end_run_write.lock().unwrap().clone()
+    }
+    /// The filters.  Changes take effect with the next batch of
+    /// events.
+    pub fn filters(&self) -> filter::SharedFilters {
+        self.filters.clone()
+    }
+    /// The accumulated scalers.  Changes to how scaler spectra are
+    /// made take effect with the next scaler item.
+    pub fn scalers(&self) -> scalers::SharedScalers {
+        self.scalers.clone()
+    }
+    /// What's known about the run being analyzed.
+    pub fn get_run_info(&self) -> runinfo::RunInfo {
+        self.run_info.lock().unwrap().info.clone()
+    }
+    /// Set the glob patterns of the spectra that are cleared when a
+    /// BEGIN_RUN item is processed.  An empty set of patterns turns
+    /// this off.
+    pub fn set_clear_at_begin(&self, patterns: &[String]) {
+        self.run_info.lock().unwrap().clear_at_begin = patterns.to_vec();
+    }
+    pub fn get_clear_at_begin(&self) -> Vec<String> {
+        self.run_info.lock().unwrap().clear_at_begin.clone()
+    }
 }
 /// The processing thread requires state that's held across
 /// several functions.  That implies a struct and implementation.
@@ -160,55 +714,353 @@ impl ProcessingApi {
 /// * parameter_api is used to communicate with the histogram server's
 /// parameter api.
 /// * attach_name - contains the name of the data source. None indicates we're not attached.
-/// * attached_file - contains the file descriptor of the file we're attached
-/// None indicates we are not attached.
+/// * attached_file - contains the buffered reader on the file we're attached
+/// None indicates we are not attached (or are attached to a ring).
+/// * attached_ring - the ring we're attached to.  None if not attached to
+/// a ring.
+/// * attached_xdr - the SpecTcl XDR filter file we're attached to, if any.
+/// * read_buffer_size - size of the attached_file read buffer.
 /// * parameter_mapping is a mapping between the parameter ids in the
 /// histogram server's parameter dictionary and the ones in the event file.
 /// this will be regenerated on each attach since it's possible that
 /// these mappings change from file to file.
+/// * id_map_strategy - how parameter_mapping is stored.
 /// * chunk_size is the number of events that are batched together
 /// in calls to spectrum_api.process_events.
+/// * sample_factor - Only one of every sample_factor events is histogrammed.
+/// * sample_compensate - If true, sampled events are histogrammed with
+/// a weight of sample_factor.
+/// * sample_phase - Number of events seen since the last sampled event
+/// (modulo sample_factor).
 /// * processing means that we are analyzing data from a file.
+/// * paused means that analysis was paused.  The event_chunk is
+/// retained as is and the file is left positioned at the next item.
+/// * file_offset - byte offset of the next ring item in the attached file.
+/// * items_read - Number of ring items read from the attached file.
+/// * read_time - Time spent reading ring items from the attached file.
+/// * process_time - Time spent processing the ring items read.
 /// * keep_running - when an exit request is received, this is
 /// set to false indicating that when convenienct the thread should
 /// cleanly exit.
+/// * tracedb - the trace store to which run state changes are declared.
+/// * treevariables - the tree variable store updated from variable value items.
+/// * api_chan - channel to the histogramer, used by prefetch threads.
+/// * playlist - Files of a chained replay that follow the attached file.
+/// * prefetch - The prefetch of the first file in the playlist, if running.
+/// * pending_items - Prefetched ring items of the attached file that have
+/// not yet been processed.  These are processed before reading the file.
+/// * replay - Summary of the replay in progress, if there is one.
+/// * replay_resumed - When analysis last started or resumed.  None while
+/// paused.
+/// * summaries - Summaries of completed replays.
+/// * end_run_write - The end of run write policy and its last outcome.
+/// * filters - The filters events are written to.
+/// * scalers - The accumulated scalers.
+/// * run_info - The run being analyzed.
+/// * unpackers - The unpackers that can be selected by name.
+/// * unpacker - unpacks physics events, if set.
+/// * unpacker_name - name of the unpacker.
+/// * verify_timestamps - true if timestamp ordering is verified.
+/// * timestamp_ordering - Timestamp ordering statistics by source id.
 ///
 struct ProcessingThread {
     request_chan: mpsc::Receiver<Request>,
+    api_chan: mpsc::Sender<messaging::Request>,
 
     spectrum_api: spectrum_messages::SpectrumMessageClient,
     parameter_api: parameter_messages::ParameterMessageClient,
 
     attach_name: Option<String>,
-    attached_file: Option<fs::File>,
+    attached_file: Option<BufReader<fs::File>>,
+    attached_ring: Option<ring::RingSource>,
+    attached_xdr: Option<xdr::XdrSource>,
+    read_buffer_size: usize,
     parameter_mapping: parameters::ParameterIdMap,
+    id_map_strategy: parameters::IdMapStrategy,
     chunk_size: usize,
+    sample_factor: u64,
+    sample_compensate: bool,
+    sample_phase: u64,
     processing: bool,
+    paused: bool,
     keep_running: bool,
+    file_offset: u64,
+    items_read: u64,
+    read_time: Duration,
+    process_time: Duration,
 
     event_chunk: Vec<parameters::Event>,
     ring_version: RingVersion,
+    tracedb: trace::SharedTraceStore,
+    treevariables: treevariables::SharedTreeVariableStore,
+
+    playlist: VecDeque<String>,
+    prefetch: Option<prefetch::Prefetch>,
+    pending_items: VecDeque<RingItem>,
+
+    replay: Option<ReplaySummary>,
+    replay_resumed: Option<Instant>,
+    summaries: SharedReplaySummaries,
+    end_run_write: endrun::SharedEndRunWrite,
+    filters: filter::SharedFilters,
+    scalers: scalers::SharedScalers,
+    run_info: runinfo::SharedRunInfo,
+    unpackers: SharedUnpackerRegistry,
+    unpacker: Option<Box<dyn Unpacker>>,
+    unpacker_name: Option<String>,
+
+    verify_timestamps: bool,
+    timestamp_ordering: BTreeMap<u32, SourceOrdering>,
+}
+
+/// Make a map between the parameter ids in a parameter definitions
+/// item and the ids of the histogramer's parameters:
+/// - ask the parameter api to list the parameters.
+/// - use those to stock the parameter dictionary of the map.
+/// - Use the parameter definitions in the parameter definition item:
+///   *  If the parameter does not exist in the dictionary,
+/// define it to the histogramer.
+///   *  Make a map from its id in the record to the id in the histogramer.
+///
+/// Since this can run in a prefetch thread at the same time as the
+/// processing thread, another thread creating the parameter first is
+/// not an error.
+///
+pub fn make_parameter_map(
+    api: &parameter_messages::ParameterMessageClient,
+    defs: &analysis_ring_items::ParameterDefinitions,
+) -> Result<parameters::ParameterIdMap, String> {
+    let mut mapping = parameters::ParameterIdMap::new();
+    let known_parameters = api
+        .list_parameters("*")
+        .map_err(|s| format!("Could not get parameter defs from histogram thread: {}", s))?;
+
+    // Stock the map with the parameters the histogramer has defined:
+
+    for p in known_parameters {
+        mapping.get_dict_mut().insert(p.get_name(), p.get_id());
+    }
+    // Iterate over the definitions in the parameter definition
+    // item.  If making a map for a parameter fails, then
+    // we need to add the parameter to the histogramer,
+    // fetch its id and make an new map.
+    // Duplicate mapping is an error.
+
+    for def in defs.iter() {
+        let name = def.name();
+        let id = def.id();
+        if let Err(reason) = mapping.map(id, &name) {
+            if reason == *"Duplicate Map" {
+                return Err(format!("Duplicate map for parameter {}", name));
+            }
+            let create_status = api.create_parameter(&name);
+
+            // Get the id of the new parameter:
+
+            let param = api.list_parameters(&name).map_err(|s| {
+                format!(
+                    "Just created parameter {} but failed to get its id: {}",
+                    name, s
+                )
+            })?;
+            if param.is_empty() {
+                return Err(match create_status {
+                    Err(s) => format!("Failed to create new parameter {} : {}", name, s),
+                    Ok(_) => format!(
+                        "Just made parameter {} but got an empty list fetching it def",
+                        name
+                    ),
+                });
+            }
+            mapping
+                .get_dict_mut()
+                .insert(name.clone(), param[0].get_id());
+            mapping.map(id, &name).map_err(|reason| {
+                format!(
+                    "After creating parameter {}, failed to make map entry {}",
+                    name, reason
+                )
+            })?;
+        }
+    }
+    Ok(mapping)
 }
+
 impl ProcessingThread {
     // Handle the Attach request:
-    // Attempt to open the file.  If it exists,
-    // store the attached fil and attached name as some.
+    // Attempt to open the file or connect to the ring.  If that works,
+    // store the attached file or ring and attached name as some.
     // additionaly, set processing -> false in order to
     // halt processing of the old file...if it was in progress.
     // On error, return that as the error string:
     //
     fn attach(&mut self, fname: &str) -> Reply {
-        match File::open(fname) {
-            Ok(fp) => {
+        let source = if let Some(url) = fname.strip_prefix(ring::RING_PREFIX) {
+            ring::RingSource::connect(url).map(|r| (None, Some(r), None))
+        } else if let Some(path) = fname.strip_prefix(xdr::XDR_PREFIX) {
+            xdr::XdrSource::open(path).map(|x| (None, None, Some(x)))
+        } else {
+            File::open(fname)
+                .map(|fp| {
+                    (
+                        Some(BufReader::with_capacity(self.read_buffer_size, fp)),
+                        None,
+                        None,
+                    )
+                })
+                .map_err(|e| e.to_string())
+        };
+        match source {
+            Ok((file, ring, xdr)) => {
+                self.end_replay("attached");
+                self.abandon_pause();
+                self.forget_playlist();
                 self.attach_name = Some(String::from(fname));
-                self.attached_file = Some(fp);
+                self.attached_file = file;
+                self.attached_ring = ring;
+                self.attached_xdr = xdr;
+                // The file's parameter definitions make the new map:
+
+                let mapped = self.forget_parameter_map();
                 self.processing = false;
-                Ok(String::from(""))
-               
+                self.file_offset = 0;
+                self.items_read = 0;
+                self.read_time = Duration::ZERO;
+                self.process_time = Duration::ZERO;
+                self.timestamp_ordering.clear();
+                self.tracedb
+                    .add_event(trace::TraceEvent::SourceAttached(String::from(fname)));
+                mapped.map(|_| String::from(""))
             }
-            Err(e) => Err(e.to_string()),
+            Err(e) => Err(e),
+        }
+    }
+    // Attach the first of a list of files and queue the rest for
+    // a chained replay.  Prefetch of the second file starts
+    // right away.
+    //
+    fn attach_list(&mut self, files: Vec<String>) -> Reply {
+        let mut files = VecDeque::from(files);
+        if let Some(first) = files.pop_front() {
+            self.attach(&first)?;
+            self.playlist = files;
+            self.start_prefetch();
+            Ok(String::from(""))
+        } else {
+            Err(String::from("The file list is empty"))
         }
     }
+    // Forget any chained replay files.  A running prefetch is
+    // abandoned; its thread finishes on its own.
+    //
+    fn forget_playlist(&mut self) {
+        self.playlist.clear();
+        self.prefetch = None;
+        self.pending_items.clear();
+    }
+    // Forget the parameter map of the data source.  Until the next
+    // source defines its parameters, no parameters are produced.  If
+    // physics events are being unpacked, the unpacker's parameters are
+    // mapped again; the error says why that failed.
+    //
+    fn forget_parameter_map(&mut self) -> Result<(), String> {
+        self.parameter_mapping = parameters::ParameterIdMap::with_strategy(self.id_map_strategy);
+        if let Some(unpacker) = self.unpacker.as_ref() {
+            let definitions = unpacker.definitions();
+            self.rebuild_parameter_map(&definitions)
+                .map_err(|s| format!("Could not map the parameters of the unpacker: {}", s))?;
+        }
+        Ok(())
+    }
+    // Start prefetching the next file in the playlist:
+
+    fn start_prefetch(&mut self) {
+        self.prefetch = self.playlist.front().map(|name| {
+            prefetch::Prefetch::start(
+                name,
+                self.read_buffer_size,
+                self.ring_version,
+                &self.api_chan,
+            )
+        });
+    }
+    // Move on to the next file in the playlist.  Files that can't be
+    // opened are reported and skipped.  Returns false if there are no
+    // more files.
+    //
+    fn next_file(&mut self) -> bool {
+        while let Some(name) = self.playlist.pop_front() {
+            let file = match self.prefetch.take() {
+                Some(p) if p.name() == name => p.wait(),
+                _ => prefetch::prefetch_file(
+                    &name,
+                    self.read_buffer_size,
+                    self.ring_version,
+                    &self.api_chan,
+                ),
+            };
+            match file {
+                Ok(mut file) => {
+                    // The reader is positioned just after the prefetched
+                    // items:
+
+                    let prefetched: u64 = file.items.iter().map(|i| i.size() as u64).sum();
+
+                    // If the prefetch mapped the parameters, their
+                    // definitions don't need to be processed again:
+
+                    if let Some(mut mapping) = file.mapping.take() {
+                        mapping.set_strategy(self.id_map_strategy);
+                        self.parameter_mapping = mapping;
+                        file.items
+                            .retain(|i| i.type_id() != ring_items::PARAMETER_DEFINITIONS);
+                    }
+                    // The pending items are counted as they are processed
+                    // so that the offset ends up at the reader's position.
+                    // The definitions that were dropped count as read:
+
+                    let pending: u64 = file.items.iter().map(|i| i.size() as u64).sum();
+                    self.tracedb
+                        .add_event(trace::TraceEvent::SourceAttached(file.name.clone()));
+                    self.attach_name = Some(file.name);
+                    self.attached_file = Some(file.reader);
+                    self.pending_items = file.items;
+                    self.file_offset = prefetched - pending;
+                    self.items_read = 0;
+                    self.timestamp_ordering.clear();
+                    self.start_prefetch();
+                    return true;
+                }
+                Err(e) => println!("Skipping {} in the file list: {}", name, e),
+            }
+        }
+        false
+    }
+    // Set the read buffer size.  If a file is attached, its reader
+    // is replaced by one with the new buffer size.  Since the old
+    // reader may have buffered data past the next ring item, the
+    // file is repositioned to where the old reader had got to.  That's
+    // not file_offset if there are prefetched items still pending;
+    // those have already been read.
+    //
+    fn set_buffer_size(&mut self, size: usize) -> Reply {
+        if size == 0 {
+            return Err(String::from(
+                "The read buffer size must be greater than zero",
+            ));
+        }
+        self.read_buffer_size = size;
+        if let Some(mut reader) = self.attached_file.take() {
+            let position = reader.stream_position();
+            let mut fp = reader.into_inner();
+            let status = position.and_then(|p| fp.seek(SeekFrom::Start(p)));
+            self.attached_file = Some(BufReader::with_capacity(size, fp));
+            if let Err(e) = status {
+                return Err(format!("Unable to reposition the data source: {}", e));
+            }
+        }
+        Ok(String::from(""))
+    }
     // Implement the List request - this is always
     // successful
     // If attach_name is Some, return its contents
@@ -216,7 +1068,11 @@ impl ProcessingThread {
 
     fn list(&mut self) -> Reply {
         if let Some(s) = &self.attach_name {
-            Ok(String::from("file:") + s)
+            if s.starts_with(ring::RING_PREFIX) || s.starts_with(xdr::XDR_PREFIX) {
+                Ok(s.clone())
+            } else {
+                Ok(String::from("file:") + s)
+            }
         } else {
             Ok(String::from("Not Attached"))
         }
@@ -229,11 +1085,20 @@ impl ProcessingThread {
     // else return an error (not attached).
     //
     fn detach(&mut self) -> Reply {
-        if self.attach_name.is_some() {
-            self.attach_name = None;
+        if let Some(name) = self.attach_name.take() {
+            self.end_replay("detached");
+            self.abandon_pause();
+            self.forget_playlist();
+            let mapped = self.forget_parameter_map();
             self.attached_file = None;
+            self.attached_ring = None;
+            self.attached_xdr = None;
             self.processing = false;
-            Ok(String::from(""))
+            self.file_offset = 0;
+            self.items_read = 0;
+            self.tracedb
+                .add_event(trace::TraceEvent::SourceDetached(name));
+            mapped.map(|_| String::from(""))
         } else {
             Err(String::from("Not attached to a data source"))
         }
@@ -244,6 +1109,8 @@ impl ProcessingThread {
     fn start_processing(&mut self) -> Reply {
         if self.attach_name.is_none() {
             Err(String::from("No file is attached"))
+        } else if self.paused {
+            Err(String::from("Analysis is paused - resume it instead"))
         } else if self.processing {
             Err(format!(
                 "Already processing {}",
@@ -251,6 +1118,8 @@ impl ProcessingThread {
             ))
         } else {
             self.processing = true;
+            self.replay = Some(ReplaySummary::new(self.attach_name.as_ref().unwrap()));
+            self.replay_resumed = Some(Instant::now());
             Ok(String::from("Processing begins"))
         }
     }
@@ -260,84 +1129,95 @@ impl ProcessingThread {
     fn stop_processing(&mut self) -> Reply {
         if self.processing {
             self.processing = false;
+            self.end_replay("stopped");
+            Ok(String::from(""))
+        } else if self.paused {
+            self.abandon_pause();
+            self.end_replay("stopped");
             Ok(String::from(""))
         } else {
             Err(String::from("Not processing data"))
         }
     }
-
-    //  given a new set of parameter definitions, rebuild the parameter
-    // map
-    // - ask the parameter api to list the parameter.
-    // - use those to stock the parameter dictionary of them ap.
-    // - Use the parameter definitions in the parameter definition item:
-    //   *  If the parameter does not exist in the dictionary,
-    // define it to the histograme  Make the 1:1 map in our dictionary.
-    //   * If the parameter does exist in the dictionary, make a map
-    // from its id in the record to the id in the histogramer.
+    // Pause processing - only legal if processing.  We set
+    // processing false and paused true.  When processing() returns,
+    // it sees paused and does not flush the partial event batch.
     //
-    fn rebuild_parameter_map(&mut self, defs: &analysis_ring_items::ParameterDefinitions) {
-        self.parameter_mapping = parameters::ParameterIdMap::new();
-        let known_parameters = self
-            .parameter_api
-            .list_parameters("*")
-            .expect("Could not get parameter defs from histogram thread");
-
-        // Stock the map with the parameters the histogramer has defined:
-
-        for p in known_parameters {
-            self.parameter_mapping
-                .get_dict_mut()
-                .insert(p.get_name(), p.get_id());
-        }
-        // Iterate over the definitions in the parameter definition
-        // item.  If making a map for a parameter fails, then
-        // we need to add the parameter to the histogramer,
-        // fetch its id and make an new map.
-        // Duplicate mapping is cause for a panic.
-
-        for def in defs.iter() {
-            let name = def.name();
-            let id = def.id();
-            if let Err(reason) = self.parameter_mapping.map(id, &name) {
-                if reason == *"Duplicate Map" {
-                    panic!("ProcessingThread failed to make a map due to duplication");
-                }
-                if let Err(s) = self.parameter_api.create_parameter(&name) {
-                    panic!("Failed to create new parameter {} : {}", name, s);
-                }
-                // Get the id of the new parameter:
-
-                let param = self.parameter_api.list_parameters(&name);
-                if let Err(s) = param {
-                    panic!(
-                        "Just created parameter {} but failed to get its id: {}",
-                        name, s
-                    );
-                }
-                let param = param.unwrap();
-                if param.is_empty() {
-                    panic!(
-                        "Just made parameter {} but got an empty list fetching it def",
-                        name
-                    );
-                }
-                let param = &param[0];
-                self.parameter_mapping
-                    .get_dict_mut()
-                    .insert(name.clone(), param.get_id());
+    fn pause_processing(&mut self) -> Reply {
+        if self.processing {
+            self.processing = false;
+            self.paused = true;
+            self.suspend_replay();
+            Ok(String::from(""))
+        } else {
+            Err(String::from("Not processing data"))
+        }
+    }
+    // Resume processing - only legal if paused.  The retained event
+    // batch continues to fill from the next ring item in the file.
+    //
+    fn resume_processing(&mut self) -> Reply {
+        if self.paused {
+            self.paused = false;
+            self.processing = true;
+            self.replay_resumed = Some(Instant::now());
+            Ok(String::from("Processing resumes"))
+        } else {
+            Err(String::from("Analysis is not paused"))
+        }
+    }
+    // If paused, the retained events are flushed and the pause
+    // is forgotten.  Used when a pause is turned into a stop or
+    // the data source goes away.
+    //
+    fn abandon_pause(&mut self) {
+        if self.paused {
+            self.paused = false;
+            self.flush_events();
+        }
+    }
+    // Account for the time spent analyzing since the replay last
+    // started or resumed:
 
-                // If it's still an error then it's panic time:
+    fn suspend_replay(&mut self) {
+        if let (Some(replay), Some(resumed)) = (self.replay.as_mut(), self.replay_resumed.take()) {
+            replay.seconds += resumed.elapsed().as_secs_f64();
+        }
+    }
+    // If a replay is in progress, it ends for the reason given and
+    // its summary is recorded, discarding the oldest if needed:
 
-                if let Err(reason) = self.parameter_mapping.map(id, &name) {
-                    panic!(
-                        "After creating parameter {}, failed to make map entry {}",
-                        name, reason
-                    );
-                }
+    fn end_replay(&mut self, reason: &str) {
+        self.suspend_replay();
+        if let Some(mut replay) = self.replay.take() {
+            replay.end_reason = String::from(reason);
+            let mut summaries = self.summaries.lock().unwrap();
+            summaries.push_back(replay);
+            while summaries.len() > MAX_REPLAY_SUMMARIES {
+                summaries.pop_front();
             }
         }
     }
+    // Count a ring item that was read but not processed:
+
+    fn skip_item(&mut self) {
+        if let Some(replay) = self.replay.as_mut() {
+            replay.skipped += 1;
+        }
+    }
+
+    //  given a new set of parameter definitions, rebuild the parameter
+    // map.  See make_parameter_map.  On failure the map is unchanged.
+    //
+    fn rebuild_parameter_map(
+        &mut self,
+        defs: &analysis_ring_items::ParameterDefinitions,
+    ) -> Result<(), String> {
+        let mut mapping = make_parameter_map(&self.parameter_api, defs)?;
+        mapping.set_strategy(self.id_map_strategy);
+        self.parameter_mapping = mapping;
+        Ok(())
+    }
     // Build an event from a ParameterItem ring item:
 
     fn build_event(raw: &analysis_ring_items::ParameterItem) -> parameters::Event {
@@ -348,78 +1228,504 @@ impl ProcessingThread {
         result
     }
 
+    // Write the events of the batch that satisfy the gates of the
+    // enabled filters to them.  The gates are evaluated by the
+    // histogramer before the filters are locked so that the API is not
+    // locked out while that's done.
+
+    fn write_filters(&mut self) {
+        let gates = self.filters.lock().unwrap().enabled_gates();
+        if gates.is_empty() {
+            return;
+        }
+        let mut selected = HashMap::new();
+        for (name, gate) in gates {
+            match self.spectrum_api.select_events(&gate, &self.event_chunk) {
+                Ok(events) => {
+                    selected.insert(name, events);
+                }
+                Err(s) => panic!("Unable to get the histogram thread to select events {}", s),
+            }
+        }
+        self.filters.lock().unwrap().write(&selected);
+    }
     //
     // Flush the event batch to the histogramer:
     //
     fn flush_events(&mut self) {
         if !self.event_chunk.is_empty() {
-            if let Err(s) = self.spectrum_api.process_events(&self.event_chunk) {
+            self.write_filters();
+            let status = if self.sample_compensate && self.sample_factor > 1 {
+                self.spectrum_api
+                    .process_weighted_events(&self.event_chunk, self.sample_factor as f64)
+            } else {
+                self.spectrum_api.process_events(&self.event_chunk)
+            };
+            if let Err(s) = status {
                 panic!("Unable to get the histogram thread to process events {}", s);
             }
             self.event_chunk.clear();
         }
     }
+    // Set the sampling.  The event batch was built with the old
+    // sampling so it's flushed first, which also ensures its events
+    // get the right weight.
+
+    fn set_sampling(&mut self, factor: u64, compensate: bool) -> Reply {
+        if factor == 0 {
+            return Err(String::from("The sampling factor must be at least 1"));
+        }
+        self.flush_events();
+        self.sample_factor = factor;
+        self.sample_compensate = compensate;
+        self.sample_phase = 0;
+        Ok(String::from(""))
+    }
     // Process a ring item with event data.
     // We create an event from our ring item.
     // We ask the parameter map to create an event from it with the
     // parameter ids that are native to the histogramer.
     // For now we just send the event to the histogramer.
     // in a future implementation we'll send batches of events.
+    // When sampling, only the first of every sample_factor events is
+    // histogrammed.
     //
     fn process_event(&mut self, event: &analysis_ring_items::ParameterItem) {
+        let sampled = self.sample_phase == 0;
+        self.sample_phase = (self.sample_phase + 1) % self.sample_factor;
+        if !sampled {
+            if let Some(replay) = self.replay.as_mut() {
+                replay.unsampled += 1;
+            }
+            return;
+        }
         let event = Self::build_event(event);
         let event = self.parameter_mapping.map_event(&event);
 
         self.event_chunk.push(event);
+        if let Some(replay) = self.replay.as_mut() {
+            replay.events += 1;
+        }
         if self.event_chunk.len() >= self.chunk_size {
             self.flush_events();
         }
     }
 
+    // Unpack a raw physics event into parameters and process
+    // them.  Events that don't unpack are skipped.
+
+    fn unpack_physics_event(&mut self, item: &RingItemRef) {
+        let event: Option<event_item::PhysicsEvent> = item.to_specific(self.ring_version);
+        let mut parameters = analysis_ring_items::ParameterItem::new(self.items_read);
+        let unpacked = match (event, self.unpacker.as_mut()) {
+            (Some(mut event), Some(unpacker)) => unpacker.unpack(&mut event, &mut parameters),
+            _ => Err(String::from("Not an unpackable physics event")),
+        };
+        match unpacked {
+            Ok(()) => self.process_event(&parameters),
+            Err(_) => self.skip_item(),
+        }
+    }
+    // Set the unpacker of physics events.  The map from its parameters
+    // to the histogramer's is made right away so that errors can be
+    // reported.
+
+    fn set_unpacker(&mut self, unpacker: Option<(Box<dyn Unpacker>, String)>) -> Reply {
+        match unpacker {
+            Some((unpacker, name)) => {
+                self.rebuild_parameter_map(&unpacker.definitions())?;
+                self.unpacker = Some(unpacker);
+                self.unpacker_name = Some(name);
+            }
+            None => {
+                self.unpacker = None;
+                self.unpacker_name = None;
+            }
+        }
+        Ok(String::from(""))
+    }
+    fn set_evb_unpacker(&mut self, description: Option<evb::EvbDescription>) -> Reply {
+        let unpacker = match description {
+            Some(d) => {
+                let registry = self.unpackers.lock().unwrap().clone();
+                let unpacker: Box<dyn Unpacker> = Box::new(evb::EvbUnpacker::new(&d, &registry)?);
+                Some((unpacker, format!("{}{}", EVB_UNPACKER_PREFIX, d.name)))
+            }
+            None => None,
+        };
+        self.set_unpacker(unpacker)
+    }
+    fn set_registered_unpacker(&mut self, unpacker: Option<(String, usize)>) -> Reply {
+        let unpacker = match unpacker {
+            Some((name, count)) => {
+                let unpacker = self.unpackers.lock().unwrap().create(&name, count)?;
+                Some((unpacker, name))
+            }
+            None => None,
+        };
+        self.set_unpacker(unpacker)
+    }
+    // Accumulate a scaler item.  If scaler spectra are being made,
+    // each channel's increment is histogrammed at the item's time.
+    // Failures to make scaler spectra are reported but don't stop
+    // the analysis.
+
+    fn process_scalers(&mut self, item: &scaler_item::ScalerItem) {
+        let (increments, spectra) = {
+            let mut scalers = self.scalers.lock().unwrap();
+            (scalers.add(item), scalers.spectra())
+        };
+        if let Some(spectra) = spectra {
+            for (channel, increment) in increments.increments.iter().enumerate() {
+                if *increment == 0 {
+                    continue;
+                }
+                let name = scalers::scaler_name(increments.source, channel);
+                let status = self.scaler_parameter(&name, &spectra).and_then(|id| {
+                    let event = vec![parameters::EventParameter::new(id, increments.time)];
+                    self.spectrum_api
+                        .process_weighted_events(&[event], *increment as f64)
+                });
+                if let Err(s) = status {
+                    println!("Unable to histogram scaler {}: {}", name, s);
+                }
+            }
+        }
+    }
+    // The histogramer id of a scaler parameter.  The parameter and its
+    // scaler-vs-time spectrum are made if they don't exist.
+
+    fn scaler_parameter(
+        &self,
+        name: &str,
+        spectra: &scalers::ScalerSpectra,
+    ) -> Result<u32, String> {
+        let find = || -> Result<Option<u32>, String> {
+            Ok(self
+                .parameter_api
+                .list_parameters(name)?
+                .iter()
+                .find(|p| p.get_name() == name)
+                .map(|p| p.get_id()))
+        };
+        let id = match find()? {
+            Some(id) => id,
+            None => {
+                self.parameter_api.create_parameter(name)?;
+                find()?.ok_or_else(|| format!("Parameter {} was not created", name))?
+            }
+        };
+        if self.spectrum_api.list_spectra(name)?.is_empty() {
+            self.spectrum_api.create_spectrum_1d(
+                name,
+                name,
+                0.0,
+                spectra.duration,
+                spectra.bins,
+            )?;
+        }
+        Ok(id)
+    }
+    // Process a state change item.  Any pending events are flushed so that
+    // spectra reflect all data prior to the state change before the
+    // trace is declared.  At the end of a run, the end of run write
+    // policy, if any, is applied.  At the beginning of a run the
+    // spectra that match the clear at begin patterns are cleared:
+
+    fn process_state_change(&mut self, item: &state_change::StateChange) {
+        self.flush_events();
+        let clear_at_begin = {
+            let mut run_info = self.run_info.lock().unwrap();
+            run_info.info.update(item);
+            run_info.clear_at_begin.clone()
+        };
+        match item.change_type() {
+            state_change::StateChangeType::Begin => {
+                self.scalers.lock().unwrap().clear();
+                for pattern in clear_at_begin.iter() {
+                    if let Err(s) = self.spectrum_api.clear_spectra(pattern) {
+                        println!("Unable to clear spectra at the beginning of a run: {}", s);
+                    }
+                }
+            }
+            state_change::StateChangeType::End => self.write_end_run(item.run_number()),
+            _ => {}
+        }
+        let change = match item.change_type() {
+            state_change::StateChangeType::Begin => "begin",
+            state_change::StateChangeType::End => "end",
+            state_change::StateChangeType::Pause => "pause",
+            state_change::StateChangeType::Resume => "resume",
+        };
+        self.tracedb.add_event(trace::TraceEvent::RunStateChange {
+            change: String::from(change),
+            run: item.run_number(),
+            title: item.title(),
+        });
+    }
+
+    // Apply the end of run write policy.  The policy is copied so that
+    // the API is not locked out while the spectra are written:
+
+    fn write_end_run(&mut self, run: u32) {
+        let policy = self.end_run_write.lock().unwrap().policy.clone();
+        if let Some(policy) = policy {
+            let result = endrun::write(&policy, run, &self.spectrum_api);
+            self.end_run_write.lock().unwrap().last = Some(result);
+        }
+    }
+
+    // Process a variable values item.  The tree variable store is
+    // updated and a trace declared for each variable whose value or
+    // units changed:
+
+    fn process_variables(&mut self, item: &analysis_ring_items::VariableValues) {
+        for variable in item.iter() {
+            let name = variable.name();
+            if self
+                .treevariables
+                .update(&name, variable.value(), &variable.units())
+            {
+                self.tracedb
+                    .add_event(trace::TraceEvent::TreeVariableChanged(name));
+            }
+        }
+    }
+
     // Process a ring item from the file we only process
     // *  Parameter definition records - which cause us to
     // rebuild the parameterm ap.
     // *  Parameter value records which get processed into an event,
     // mapped to an event in the server's parameter space and
     // sent to the histogram thread
+    // *  State change items which are declared as traces.
+    // *  Variable value items which update the tree variable store.
+    // *  Physics events, if there's an event built data unpacker.
+    fn process_item(&mut self, item: &RingItemRef) {
+        if self.verify_timestamps {
+            self.check_timestamp(item);
+        }
+        match item.type_id() {
+            ring_items::PARAMETER_DEFINITIONS => {
+                let definitions: Option<analysis_ring_items::ParameterDefinitions> =
+                    item.to_specific(self.ring_version);
+                if definitions.is_none() {
+                    panic!("Converting a parameter definitions ring item failed!");
+                }
+                let definitions = definitions.unwrap();
+                if let Err(s) = self.rebuild_parameter_map(&definitions) {
+                    panic!("ProcessingThread failed to make a parameter map: {}", s);
+                }
+            }
+            ring_items::PARAMETER_DATA => {
+                let data: Option<analysis_ring_items::ParameterItem> =
+                    item.to_specific(self.ring_version);
+                if data.is_none() {
+                    panic!("Converting parameter encoded data from raw ring item failed!");
+                }
+                let event = data.unwrap();
+                self.process_event(&event);
+            }
+            ring_items::PHYSICS_EVENT if self.unpacker.is_some() => self.unpack_physics_event(item),
+            ring_items::BEGIN_RUN
+            | ring_items::END_RUN
+            | ring_items::PAUSE_RUN
+            | ring_items::RESUME_RUN => {
+                let state_change: Option<state_change::StateChange> =
+                    item.to_specific(self.ring_version);
+                if let Some(state_change) = state_change {
+                    self.process_state_change(&state_change);
+                } else {
+                    println!("Failed to convert a state change item - ignored");
+                    self.skip_item();
+                }
+            }
+            ring_items::PERIODIC_SCALERS => {
+                let scalers: Option<scaler_item::ScalerItem> = item.to_specific(self.ring_version);
+                if let Some(scalers) = scalers {
+                    self.process_scalers(&scalers);
+                } else {
+                    println!("Failed to convert a scaler item - ignored");
+                    self.skip_item();
+                }
+            }
+            ring_items::VARIABLE_VALUES => {
+                let variables: Option<analysis_ring_items::VariableValues> =
+                    item.to_specific(self.ring_version);
+                if let Some(variables) = variables {
+                    self.process_variables(&variables);
+                } else {
+                    println!("Failed to convert a variable values item - ignored");
+                    self.skip_item();
+                }
+            }
+            _ => self.skip_item(), // Ignore all other ring item types.
+        };
+    }
+    // Check the body header timestamp of an item against that of the
+    // previous item from the same source.  Items without body headers
+    // or timestamps are not checked.
+    //
+    fn check_timestamp(&mut self, item: &RingItemRef) {
+        if let Some(header) = item.get_bodyheader() {
+            if header.timestamp == NULL_TIMESTAMP {
+                return;
+            }
+            let ordering =
+                self.timestamp_ordering
+                    .entry(header.source_id)
+                    .or_insert(SourceOrdering {
+                        source_id: header.source_id,
+                        items: 0,
+                        out_of_order: 0,
+                        last_timestamp: 0,
+                    });
+            if ordering.items > 0 && header.timestamp < ordering.last_timestamp {
+                ordering.out_of_order += 1;
+            }
+            ordering.items += 1;
+            ordering.last_timestamp = header.timestamp;
+        }
+    }
+    // Read and process the next ring item from the attached file.
+    // Where possible the item is parsed in place in the reader's
+    // buffer and then consumed.  If the buffer does not hold the
+    // whole item (it straddles the end of the buffer or is larger
+    // than the buffer), it is read into a RingItem instead.
+    //
+    // Prefetched items are processed before anything is read.
+    //
+    // Items from an attached ring come from its reader thread.  A ring
+    // never ends.
+    //
+    // At the end of a file in a chained replay, we move on to the next
+    // file.
+    //
+    // Returns true if the end of the data source was hit.
     fn read_an_event(&mut self) -> bool {
-        if let Some(fp) = self.attached_file.as_mut() {
-            let try_item = RingItem::read_item(fp);
+        if let Some(item) = self.pending_items.pop_front() {
+            self.count_item(item.size());
+            let process_start = Instant::now();
+            self.process_item(&item.view());
+            self.process_time += process_start.elapsed();
+            return false;
+        }
+        // Live data: if none arrive, send what we have to the
+        // histogramer.
+
+        if let Some(ring) = self.attached_ring.as_ref() {
+            match ring.next_item(RING_POLL_INTERVAL) {
+                Some(item) => {
+                    self.count_item(item.size());
+                    let process_start = Instant::now();
+                    self.process_item(&item.view());
+                    self.process_time += process_start.elapsed();
+                }
+                None => self.flush_events(),
+            }
+            return false;
+        }
+        // SpecTcl XDR filter file records are converted to ring items:
+
+        if let Some(source) = self.attached_xdr.as_mut() {
+            let read_start = Instant::now();
+            let item = source.next_item();
+            let position = source.position();
+            self.read_time += read_start.elapsed();
+            let reason = match item {
+                Ok(Some(item)) => {
+                    self.count_item(item.size());
+                    self.file_offset = position;
+                    let process_start = Instant::now();
+                    self.process_item(&item.view());
+                    self.process_time += process_start.elapsed();
+                    return false;
+                }
+                Ok(None) => String::from("end of data"),
+                Err(reason) => {
+                    println!("Failed to read an XDR filter file record: {}", reason);
+                    format!("read error: {}", reason)
+                }
+            };
+            self.flush_events();
+            self.processing = false;
+            self.end_replay(&reason);
+            return true;
+        }
+        // The reader is taken out of self so that the item can borrow
+        // its buffer while we process it.
+
+        if let Some(mut fp) = self.attached_file.take() {
+            let read_start = Instant::now();
+            // Only an item that is not entirely in the buffer is read
+            // the slow way; a bad header is a read error either way:
+
+            let in_place = match fp.fill_buf() {
+                Ok(bytes) => match RingItemRef::from_bytes(bytes) {
+                    Err(RingItemError::FileTooSmall) | Err(RingItemError::HeaderReadFailed) => None,
+                    result => Some(result),
+                },
+                Err(_) => None,
+            };
+            let status = match in_place {
+                Some(Ok(item)) => {
+                    self.read_time += read_start.elapsed();
+                    let size = item.size();
+                    self.count_item(size);
+                    let process_start = Instant::now();
+                    self.process_item(&item);
+                    self.process_time += process_start.elapsed();
+                    fp.consume(size as usize);
+                    Ok(())
+                }
+                Some(Err(reason)) => {
+                    self.read_time += read_start.elapsed();
+                    Err(reason)
+                }
+                None => {
+                    let try_item = RingItem::read_item(&mut fp);
+                    self.read_time += read_start.elapsed();
+                    try_item.map(|item| {
+                        self.count_item(item.size());
+                        let process_start = Instant::now();
+                        self.process_item(&item.view());
+                        self.process_time += process_start.elapsed();
+                    })
+                }
+            };
+            self.attached_file = Some(fp);
 
             // Any error will be treated as an end
 
-            if let Err(reason) = try_item {
+            if let Err(reason) = status {
+                if self.next_file() {
+                    return false;
+                }
                 println!("Failed to read a ring item: {}", reason);
                 self.flush_events();
                 self.processing = false;
                 self.flush_events();
+                // Reading past the last item fails to read a header:
+
+                if let RingItemError::HeaderReadFailed = reason {
+                    self.end_replay("end of data");
+                } else {
+                    self.end_replay(&format!("read error: {}", reason));
+                }
                 return true;
             }
-            let item = try_item.unwrap();
-            match item.type_id() {
-                ring_items::PARAMETER_DEFINITIONS => {
-                    let definitions: Option<analysis_ring_items::ParameterDefinitions> =
-                        item.to_specific(self.ring_version);
-                    if definitions.is_none() {
-                        panic!("Converting a parameter definitions ring item failed!");
-                    }
-                    let definitions = definitions.unwrap();
-                    self.rebuild_parameter_map(&definitions);
-                }
-                ring_items::PARAMETER_DATA => {
-                    let data: Option<analysis_ring_items::ParameterItem> =
-                        item.to_specific(self.ring_version);
-                    if data.is_none() {
-                        panic!("Converting parameter encoded data from raw ring item failed!");
-                    }
-                    let event = data.unwrap();
-                    self.process_event(&event);
-                }
-                _ => {} // Ignore all other ring item types.
-            };
         }
         false
     }
+    // Account for a ring item of size bytes having been read:
+
+    fn count_item(&mut self, size: u32) {
+        self.file_offset += size as u64;
+        self.items_read += 1;
+        if let Some(replay) = self.replay.as_mut() {
+            replay.items += 1;
+        }
+    }
 
     // This is the method that's used when processing a data file:
     // It gets entered from run when self.processing is true after
@@ -440,9 +1746,12 @@ impl ProcessingThread {
                 Err(why) => {
                     // if disconnected we exit:
                     if let mpsc::TryRecvError::Disconnected = why {
+                        self.end_replay("exit");
                         self.processing = false;
                         self.keep_running = false;
                         self.attached_file = None; // Closes any file.
+                        self.attached_ring = None;
+                        self.attached_xdr = None;
                         self.attach_name = None;
                     } // Otherwise just means there's no request.
                 }
@@ -454,13 +1763,18 @@ impl ProcessingThread {
                 eof = self.read_an_event();
             }
         }
-        self.flush_events();
+        // A pause retains the in-flight batch for resume:
+
+        if !self.paused {
+            self.flush_events();
+        }
     }
     // Process any request received from other threads:
 
     fn process_request(&mut self, request: Request) {
         let reply = match request.request {
             RequestType::Attach(fname) => self.attach(&fname),
+            RequestType::AttachList(files) => self.attach_list(files),
             RequestType::Detach => self.detach(),
             RequestType::Start => self.start_processing(),
             RequestType::Stop => self.stop_processing(),
@@ -470,6 +1784,7 @@ impl ProcessingThread {
             }
             RequestType::GetChunkSize => Ok(self.chunk_size.to_string()),
             RequestType::Exit => {
+                self.end_replay("exit");
                 self.keep_running = false;
                 Ok(String::from(""))
             }
@@ -480,14 +1795,88 @@ impl ProcessingThread {
             }
             RequestType::GetVersion => Ok(format!("{}", self.ring_version)),
             RequestType::State => {
-                let result = 
-                if self.processing {
+                let result = if self.processing {
                     String::from("Active")
+                } else if self.paused {
+                    String::from("Paused")
                 } else {
                     String::from("Inactive")
                 };
                 Ok(result)
             }
+            RequestType::Pause => self.pause_processing(),
+            RequestType::Resume => self.resume_processing(),
+            RequestType::Position => Ok(format!("{} {}", self.file_offset, self.items_read)),
+            RequestType::BufferSize(n) => self.set_buffer_size(n),
+            RequestType::GetBufferSize => Ok(self.read_buffer_size.to_string()),
+            RequestType::Statistics => {
+                let (dropped, reconnects) = self
+                    .attached_ring
+                    .as_ref()
+                    .map(|r| (r.dropped(), r.reconnects()))
+                    .unwrap_or((0, 0));
+                Ok(format!(
+                    "{} {} {} {} {} {} {}",
+                    self.file_offset,
+                    self.items_read,
+                    self.read_time.as_secs_f64(),
+                    self.process_time.as_secs_f64(),
+                    self.sample_factor,
+                    dropped,
+                    reconnects
+                ))
+            }
+            RequestType::Sampling(factor, compensate) => self.set_sampling(factor, compensate),
+            RequestType::GetSampling => {
+                Ok(format!("{} {}", self.sample_factor, self.sample_compensate))
+            }
+            RequestType::IdMapStrategy(strategy) => {
+                self.id_map_strategy = strategy;
+                self.parameter_mapping.set_strategy(strategy);
+                Ok(String::from(""))
+            }
+            RequestType::GetIdMap => Ok(format!(
+                "{} {} {} {} {}",
+                self.parameter_mapping.strategy(),
+                self.parameter_mapping.is_sparse(),
+                self.parameter_mapping.span(),
+                self.parameter_mapping.len(),
+                self.parameter_mapping.allocated()
+            )),
+            RequestType::CompactIdMap => {
+                self.parameter_mapping.compact();
+                Ok(String::from(""))
+            }
+            RequestType::MappedParameters => Ok(self
+                .parameter_mapping
+                .mapped_ids()
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<String>>()
+                .join(" ")),
+            RequestType::VerifyTimestamps(enable) => {
+                if enable {
+                    self.timestamp_ordering.clear();
+                }
+                self.verify_timestamps = enable;
+                Ok(String::from(""))
+            }
+            RequestType::TimestampOrdering => {
+                let mut result = self.verify_timestamps.to_string();
+                for ordering in self.timestamp_ordering.values() {
+                    result += &format!(
+                        " {} {} {} {}",
+                        ordering.source_id,
+                        ordering.items,
+                        ordering.out_of_order,
+                        ordering.last_timestamp
+                    );
+                }
+                Ok(result)
+            }
+            RequestType::EvbUnpacker(description) => self.set_evb_unpacker(description),
+            RequestType::Unpacker(unpacker) => self.set_registered_unpacker(unpacker),
+            RequestType::GetUnpacker => Ok(self.unpacker_name.clone().unwrap_or_default()),
         };
         request
             .reply_chan
@@ -500,23 +1889,69 @@ impl ProcessingThread {
     /// * api_chan is the channel on which we send request to the histogram
     /// server it is used to create the API objects for the spectrum
     /// and event interfaces that we need.
+    /// * tracedb is the trace store to which we declare traces.
+    /// * treevariables is the store of tree variable values.
+    /// * summaries is where summaries of completed replays are put.
+    /// * end_run_write is the end of run write policy.
+    /// * filters are the filters events are written to.
+    /// * unpackers are the unpackers that can be selected by name.
+    /// * scalers are where scalers are accumulated.
+    /// * run_info is where the run being analyzed is recorded.
     ///
     pub fn new(
         req_chan: mpsc::Receiver<Request>,
         api_chan: mpsc::Sender<messaging::Request>,
+        tracedb: trace::SharedTraceStore,
+        treevariables: treevariables::SharedTreeVariableStore,
+        summaries: SharedReplaySummaries,
+        end_run_write: endrun::SharedEndRunWrite,
+        filters: filter::SharedFilters,
+        unpackers: SharedUnpackerRegistry,
+        scalers: scalers::SharedScalers,
+        run_info: runinfo::SharedRunInfo,
     ) -> ProcessingThread {
         ProcessingThread {
             request_chan: req_chan,
+            api_chan: api_chan.clone(),
             spectrum_api: spectrum_messages::SpectrumMessageClient::new(&api_chan),
             parameter_api: parameter_messages::ParameterMessageClient::new(&api_chan),
             attach_name: None,
             attached_file: None,
+            attached_ring: None,
+            attached_xdr: None,
+            read_buffer_size: DEFAULT_READ_BUFFER_SIZE,
             parameter_mapping: parameters::ParameterIdMap::new(),
+            id_map_strategy: parameters::IdMapStrategy::Auto,
             chunk_size: DEFAULT_EVENT_CHUNKSIZE,
+            sample_factor: 1,
+            sample_compensate: false,
+            sample_phase: 0,
             processing: false,
+            paused: false,
             keep_running: true,
+            file_offset: 0,
+            items_read: 0,
+            read_time: Duration::ZERO,
+            process_time: Duration::ZERO,
             event_chunk: Vec::new(),
             ring_version: RingVersion::V11,
+            tracedb,
+            treevariables,
+            playlist: VecDeque::new(),
+            prefetch: None,
+            pending_items: VecDeque::new(),
+            replay: None,
+            replay_resumed: None,
+            summaries,
+            end_run_write,
+            filters,
+            scalers,
+            run_info,
+            unpackers,
+            unpacker: None,
+            unpacker_name: None,
+            verify_timestamps: false,
+            timestamp_ordering: BTreeMap::new(),
         }
     }
     /// run the thread.
@@ -556,7 +1991,29 @@ impl ProcessingThread {
 ///  *  P -> NP between event batches an attach or detach request
 /// is received.
 ///
-fn processing_thread(req: mpsc::Receiver<Request>, api_chan: mpsc::Sender<messaging::Request>) {
-    let mut thread = ProcessingThread::new(req, api_chan);
+fn processing_thread(
+    req: mpsc::Receiver<Request>,
+    api_chan: mpsc::Sender<messaging::Request>,
+    tracedb: trace::SharedTraceStore,
+    treevariables: treevariables::SharedTreeVariableStore,
+    summaries: SharedReplaySummaries,
+    end_run_write: endrun::SharedEndRunWrite,
+    filters: filter::SharedFilters,
+    unpackers: SharedUnpackerRegistry,
+    scalers: scalers::SharedScalers,
+    run_info: runinfo::SharedRunInfo,
+) {
+    let mut thread = ProcessingThread::new(
+        req,
+        api_chan,
+        tracedb,
+        treevariables,
+        summaries,
+        end_run_write,
+        filters,
+        unpackers,
+        scalers,
+        run_info,
+    );
     thread.run();
 }
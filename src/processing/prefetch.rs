@@ -0,0 +1,231 @@
+//!  Prefetching for chained replays.  When a list of files is
+//!  attached, the processing thread analyzes them one after the
+//!  other.  So that the histogramer isn't left waiting while the next
+//!  file is opened and its parameter definitions are read and mapped,
+//!  that's done in a separate thread while the current file is still
+//!  being analyzed.
+//!
+//!  The prefetch thread opens the file and reads ring items until the
+//!  first parameter data item (inclusive), PREFETCH_ITEM_LIMIT items or
+//!  the end of the file.  If it reads parameter definitions, it also
+//!  builds the parameter map for them, creating any new parameters in
+//!  the histogramer.  The processing thread then starts the file by
+//!  processing the buffered items and continues reading from where the
+//!  prefetch left off.
+//!
+use super::make_parameter_map;
+use crate::messaging;
+use crate::messaging::parameter_messages::ParameterMessageClient;
+use crate::parameters;
+use crate::ring_items;
+use crate::ring_items::*;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::mpsc;
+use std::thread;
+
+/// Maximum number of ring items that are prefetched from a file.
+pub const PREFETCH_ITEM_LIMIT: usize = 1000;
+
+/// A prefetched file:
+///
+/// * name - the file name.
+/// * reader - reader positioned just after the prefetched items.
+/// * items - the ring items that were prefetched.
+/// * mapping - If parameter definitions were prefetched, the parameter
+/// map they produce.  None if there were no definitions or the map
+/// could not be made.  In that case the definitions have to be processed
+/// normally.
+///
+pub struct PrefetchedFile {
+    pub name: String,
+    pub reader: BufReader<File>,
+    pub items: VecDeque<RingItem>,
+    pub mapping: Option<parameters::ParameterIdMap>,
+}
+
+/// Prefetch a file in the calling thread.
+///
+/// ### Parameters
+/// * name - name of the file.
+/// * buffer_size - size of the reader's buffer.
+/// * version - ring item format version.
+/// * api_chan - channel to the histogramer, used to make the parameter map.
+///
+/// ### Returns
+/// * Ok(PrefetchedFile) on success.
+/// * Err(message) if the file could not be opened.
+pub fn prefetch_file(
+    name: &str,
+    buffer_size: usize,
+    version: RingVersion,
+    api_chan: &mpsc::Sender<messaging::Request>,
+) -> Result<PrefetchedFile, String> {
+    let fp = File::open(name).map_err(|e| e.to_string())?;
+    let mut reader = BufReader::with_capacity(buffer_size, fp);
+    let mut items = VecDeque::new();
+    let mut mapping = None;
+
+    while items.len() < PREFETCH_ITEM_LIMIT {
+        let item = match RingItem::read_item(&mut reader) {
+            Ok(item) => item,
+            Err(_) => break, // The processing thread will see this too.
+        };
+        let type_id = item.type_id();
+        if type_id == ring_items::PARAMETER_DEFINITIONS {
+            let definitions: Option<analysis_ring_items::ParameterDefinitions> =
+                item.to_specific(version);
+            mapping = definitions
+                .and_then(|d| make_parameter_map(&ParameterMessageClient::new(api_chan), &d).ok());
+        }
+        items.push_back(item);
+        if type_id == ring_items::PARAMETER_DATA {
+            break;
+        }
+    }
+    Ok(PrefetchedFile {
+        name: String::from(name),
+        reader,
+        items,
+        mapping,
+    })
+}
+
+/// A prefetch running in its own thread.
+///
+pub struct Prefetch {
+    name: String,
+    handle: thread::JoinHandle<Result<PrefetchedFile, String>>,
+}
+
+impl Prefetch {
+    /// Start prefetching a file.  See prefetch_file for the parameters.
+    pub fn start(
+        name: &str,
+        buffer_size: usize,
+        version: RingVersion,
+        api_chan: &mpsc::Sender<messaging::Request>,
+    ) -> Prefetch {
+        let file = String::from(name);
+        let chan = api_chan.clone();
+        Prefetch {
+            name: String::from(name),
+            handle: thread::spawn(move || prefetch_file(&file, buffer_size, version, &chan)),
+        }
+    }
+    /// Name of the file being prefetched.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Wait for the prefetch to finish and return its result.
+    pub fn wait(self) -> Result<PrefetchedFile, String> {
+        self.handle
+            .join()
+            .unwrap_or_else(|_| Err(String::from("The prefetch thread panicked")))
+    }
+}
+
+#[cfg(test)]
+mod prefetch_tests {
+    use super::*;
+    use crate::ring_items::analysis_ring_items::{
+        ParameterDefinitions, ParameterFileWriter, ParameterItem,
+    };
+    use crate::test::histogramer_common;
+    use std::path::Path;
+
+    // Write a file with definitions for a and b (ids 1, 2) then
+    // the number of events requested.
+
+    fn write_file(path: &Path, events: usize) {
+        let mut writer =
+            ParameterFileWriter::new(File::create(path).expect("Creating file"), RingVersion::V11)
+                .expect("Making writer");
+        let mut defs = ParameterDefinitions::new();
+        defs.add(1, "a").add(2, "b");
+        writer
+            .write_definitions(&defs)
+            .expect("Writing definitions");
+        for i in 0..events {
+            let mut event = ParameterItem::new(i as u64);
+            event.add(1, i as f64).add(2, 2.0 * i as f64);
+            writer.write_event(&event).expect("Writing event");
+        }
+        writer.flush().expect("Flushing");
+    }
+
+    #[test]
+    fn prefetch_1() {
+        // Prefetch stops after the first event and maps the parameters:
+
+        let (chan, jh) = histogramer_common::setup();
+        let api = ParameterMessageClient::new(&chan);
+        api.create_parameter("b").expect("Making b");
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let path = dir.path().join("run.par");
+        write_file(&path, 10);
+
+        let file = Prefetch::start(path.to_str().unwrap(), 4096, RingVersion::V11, &chan);
+        assert_eq!(path.to_str().unwrap(), file.name());
+        let file = file.wait().expect("Prefetching");
+        assert_eq!(path.to_str().unwrap(), file.name);
+        assert!(file.items.len() >= 2);
+        assert_eq!(
+            ring_items::PARAMETER_DATA,
+            file.items.back().unwrap().type_id()
+        );
+
+        // a was created, b was already there:
+
+        let mapping = file.mapping.expect("Should have a mapping");
+        let a = api.list_parameters("a").expect("Listing a");
+        assert_eq!(1, a.len());
+        let event = vec![
+            parameters::EventParameter::new(1, 1.0),
+            parameters::EventParameter::new(2, 2.0),
+        ];
+        let mapped = mapping.map_event(&event);
+        assert_eq!(
+            vec![
+                parameters::EventParameter::new(a[0].get_id(), 1.0),
+                parameters::EventParameter::new(1, 2.0)
+            ],
+            mapped
+        );
+
+        histogramer_common::teardown(chan, jh);
+    }
+    #[test]
+    fn prefetch_2() {
+        // A file with no events is read to the end:
+
+        let (chan, jh) = histogramer_common::setup();
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let path = dir.path().join("empty.par");
+        write_file(&path, 0);
+
+        let file = prefetch_file(path.to_str().unwrap(), 4096, RingVersion::V11, &chan)
+            .expect("Prefetching");
+        assert!(file
+            .items
+            .iter()
+            .all(|i| i.type_id() != ring_items::PARAMETER_DATA));
+        assert!(file.mapping.is_some());
+
+        histogramer_common::teardown(chan, jh);
+    }
+    #[test]
+    fn prefetch_3() {
+        // Missing files are an error:
+
+        let (chan, jh) = histogramer_common::setup();
+        assert!(
+            Prefetch::start("/no/such/file.par", 4096, RingVersion::V11, &chan)
+                .wait()
+                .is_err()
+        );
+        histogramer_common::teardown(chan, jh);
+    }
+}
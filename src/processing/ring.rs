@@ -0,0 +1,425 @@
+//!  Live data from NSCLDAQ ring buffers.  A ring data source is
+//!  named by a URL of the form tcp://host\[:port\]/ringname.  Data are
+//!  obtained from the RingMaster on that host the same way NSCLDAQ's own
+//!  remote consumers get them:
+//!
+//!  *  The RingMaster's port is looked up in the host's port manager
+//!     (service RingMaster) unless the URL gives it explicitly.
+//!  *  We connect to the RingMaster and send REMOTE ringname.
+//!  *  The RingMaster replies with a line that starts with OK and then
+//!     feeds the ring items from the ring (via ringtostdout) over the
+//!     connection.  Any other reply is a failure.
+//!
+//!  A reader thread reads ring items from the connection and queues
+//!  them for the processing thread.  Waiting for live data would
+//!  otherwise keep the processing thread from handling requests (e.g.
+//!  Stop).  If the connection is lost, the reader thread reconnects,
+//!  waiting RECONNECT_DELAY after the first failure and doubling that
+//!  up to MAX_RECONNECT_DELAY after each one that follows.
+//!
+//!  Live data can't be held back.  If the queue is full because analysis
+//!  is stopped or can't keep up, the items that don't fit are dropped
+//!  (and counted) rather than backing up the ring.
+//!
+use crate::crash;
+use crate::ring_items::RingItem;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Prefix of data source names that are ring buffer URLs.
+pub const RING_PREFIX: &str = "ring:";
+/// Port on which NSCLDAQ port managers listen.
+pub const PORT_MANAGER_PORT: u16 = 30000;
+/// Service name the RingMaster advertises in the port manager.
+pub const RINGMASTER_SERVICE: &str = "RingMaster";
+/// Number of ring items queued for the processing thread.
+pub const ITEM_QUEUE_DEPTH: usize = 4096;
+/// Initial delay before reconnecting a lost connection.
+pub const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+/// Longest delay between reconnection attempts.
+pub const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// The parts of a ring buffer URL:
+///
+/// * host - host the ring lives in.
+/// * port - RingMaster port if given in the URL.  If None it's looked
+/// up in the host's port manager.
+/// * ring - name of the ring.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct RingUrl {
+    pub host: String,
+    pub port: Option<u16>,
+    pub ring: String,
+}
+
+/// Parse a ring buffer URL (tcp://host\[:port\]/ringname).
+pub fn parse_url(url: &str) -> Result<RingUrl, String> {
+    let rest = url
+        .strip_prefix("tcp://")
+        .ok_or_else(|| format!("Ring URL '{}' must start with tcp://", url))?;
+    let (authority, ring) = rest
+        .split_once('/')
+        .ok_or_else(|| format!("Ring URL '{}' has no ring name", url))?;
+    if ring.is_empty() || ring.contains('/') {
+        return Err(format!("Ring URL '{}' has an invalid ring name", url));
+    }
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            Some(
+                port.parse::<u16>()
+                    .map_err(|_| format!("Ring URL '{}' has an invalid port", url))?,
+            ),
+        ),
+        None => (authority, None),
+    };
+    if host.is_empty() {
+        return Err(format!("Ring URL '{}' has no host", url));
+    }
+    Ok(RingUrl {
+        host: String::from(host),
+        port,
+        ring: String::from(ring),
+    })
+}
+
+// Ask the port manager on host (listening on pm_port) for the port of
+// the RingMaster.  The port manager replies to LIST with OK n followed
+// by n lines of port service user.
+
+fn lookup_ringmaster(host: &str, pm_port: u16) -> Result<u16, String> {
+    let mut socket = TcpStream::connect((host, pm_port))
+        .map_err(|e| format!("Unable to contact the port manager on {}: {}", host, e))?;
+    socket
+        .write_all(b"LIST\n")
+        .map_err(|e| format!("Unable to query the port manager on {}: {}", host, e))?;
+    let mut reader = BufReader::new(socket);
+    let mut line = String::new();
+    let read_err = |e: std::io::Error| format!("Port manager on {} failed: {}", host, e);
+    reader.read_line(&mut line).map_err(read_err)?;
+    let count = match line.split_whitespace().collect::<Vec<&str>>()[..] {
+        ["OK", n] => n
+            .parse::<usize>()
+            .map_err(|_| format!("Unexpected port manager reply: {}", line.trim()))?,
+        _ => return Err(format!("Unexpected port manager reply: {}", line.trim())),
+    };
+    for _ in 0..count {
+        line.clear();
+        reader.read_line(&mut line).map_err(read_err)?;
+        let fields = line.split_whitespace().collect::<Vec<&str>>();
+        if fields.len() >= 2 && fields[1] == RINGMASTER_SERVICE {
+            return fields[0]
+                .parse::<u16>()
+                .map_err(|_| format!("Unexpected port manager reply: {}", line.trim()));
+        }
+    }
+    Err(format!("No RingMaster is running on {}", host))
+}
+
+// Connect to the RingMaster and ask it for the ring's data.
+// Returns a reader on the data and the socket (for shutdown).
+
+fn connect(url: &RingUrl) -> Result<(BufReader<TcpStream>, TcpStream), String> {
+    let port = match url.port {
+        Some(p) => p,
+        None => lookup_ringmaster(&url.host, PORT_MANAGER_PORT)?,
+    };
+    let mut socket = TcpStream::connect((url.host.as_str(), port))
+        .map_err(|e| format!("Unable to connect to the RingMaster on {}: {}", url.host, e))?;
+    socket
+        .write_all(format!("REMOTE {}\n", url.ring).as_bytes())
+        .map_err(|e| format!("Unable to request ring {}: {}", url.ring, e))?;
+    let shutdown_socket = socket
+        .try_clone()
+        .map_err(|e| format!("Unable to request ring {}: {}", url.ring, e))?;
+    let mut reader = BufReader::new(socket);
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .map_err(|e| format!("Unable to request ring {}: {}", url.ring, e))?;
+    if line.starts_with("OK") {
+        Ok((reader, shutdown_socket))
+    } else {
+        Err(format!(
+            "RingMaster refused ring {}: {}",
+            url.ring,
+            line.trim()
+        ))
+    }
+}
+
+// State shared between a RingSource and its reader thread:
+
+struct Shared {
+    stop: AtomicBool,
+    socket: Mutex<Option<TcpStream>>,
+    dropped: AtomicU64,
+    reconnects: AtomicU64,
+}
+
+/// A connection to a ring buffer.  Dropping it stops the reader thread.
+///
+pub struct RingSource {
+    items: mpsc::Receiver<RingItem>,
+    shared: Arc<Shared>,
+}
+
+impl RingSource {
+    /// Connect to a ring.  The initial connection is made in the
+    /// calling thread so that failures can be reported.
+    ///
+    /// ### Parameters
+    /// * url - the ring URL (without the ring: prefix).
+    ///
+    /// ### Returns
+    /// Result<RingSource, String>
+    pub fn connect(url: &str) -> Result<RingSource, String> {
+        let url = parse_url(url)?;
+        let (reader, socket) = connect(&url)?;
+        let shared = Arc::new(Shared {
+            stop: AtomicBool::new(false),
+            socket: Mutex::new(Some(socket)),
+            dropped: AtomicU64::new(0),
+            reconnects: AtomicU64::new(0),
+        });
+        let (sender, items) = mpsc::sync_channel(ITEM_QUEUE_DEPTH);
+        let thread_shared = shared.clone();
+        crash::spawn("ring reader", move || {
+            read_ring(url, reader, sender, thread_shared)
+        });
+        Ok(RingSource { items, shared })
+    }
+    /// Get the next ring item, waiting at most timeout for one.
+    /// None if there was none.
+    pub fn next_item(&self, timeout: Duration) -> Option<RingItem> {
+        self.items.recv_timeout(timeout).ok()
+    }
+    /// Number of ring items dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+    /// Number of times the connection was re-established.
+    pub fn reconnects(&self) -> u64 {
+        self.shared.reconnects.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for RingSource {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::SeqCst);
+        if let Some(socket) = self.shared.socket.lock().unwrap().take() {
+            let _ = socket.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+// The reader thread.  Reads ring items and queues them until the
+// RingSource is dropped, reconnecting when the connection is lost.
+
+fn read_ring(
+    url: RingUrl,
+    mut reader: BufReader<TcpStream>,
+    sender: mpsc::SyncSender<RingItem>,
+    shared: Arc<Shared>,
+) {
+    loop {
+        match RingItem::read_item(&mut reader) {
+            Ok(item) => match sender.try_send(item) {
+                Ok(()) => {}
+                Err(mpsc::TrySendError::Full(_)) => {
+                    shared.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(mpsc::TrySendError::Disconnected(_)) => return,
+            },
+            Err(reason) => {
+                if shared.stop.load(Ordering::SeqCst) {
+                    return;
+                }
+                println!(
+                    "Lost connection to ring {} on {}: {} - reconnecting",
+                    url.ring, url.host, reason
+                );
+                match reconnect(&url, &shared) {
+                    Some(r) => reader = r,
+                    None => return,
+                }
+            }
+        }
+    }
+}
+
+// Reconnect with backoff.  None if the source was dropped first.
+
+fn reconnect(url: &RingUrl, shared: &Shared) -> Option<BufReader<TcpStream>> {
+    let mut delay = RECONNECT_DELAY;
+    loop {
+        // Sleep in short steps so a drop is noticed promptly:
+
+        let mut slept = Duration::ZERO;
+        while slept < delay {
+            if shared.stop.load(Ordering::SeqCst) {
+                return None;
+            }
+            let step = Duration::from_millis(50).min(delay - slept);
+            thread::sleep(step);
+            slept += step;
+        }
+        match connect(url) {
+            Ok((reader, socket)) => {
+                let mut current = shared.socket.lock().unwrap();
+                if shared.stop.load(Ordering::SeqCst) {
+                    let _ = socket.shutdown(Shutdown::Both);
+                    return None;
+                }
+                *current = Some(socket);
+                shared.reconnects.fetch_add(1, Ordering::Relaxed);
+                return Some(reader);
+            }
+            Err(e) => {
+                println!("Reconnecting to ring {} failed: {}", url.ring, e);
+                delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod ring_tests {
+    use super::*;
+    use crate::ring_items::analysis_ring_items::{
+        ParameterDefinitions, ParameterFileWriter, ParameterItem,
+    };
+    use crate::ring_items::{RingVersion, PARAMETER_DATA};
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    // A fake RingMaster: For each connection it accepts, it checks the
+    // REMOTE request, replies with reply and, if that's OK, sends a
+    // format item, the definition of parameter 1 and an item for each
+    // trigger and closes the connection.
+
+    fn ringmaster(reply: &str, connections: Vec<Vec<u64>>) -> (u16, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Binding listener");
+        let port = listener.local_addr().unwrap().port();
+        let reply = String::from(reply);
+        let jh = thread::spawn(move || {
+            for triggers in connections {
+                let (mut socket, _) = listener.accept().expect("Accepting");
+                let mut request = BufReader::new(socket.try_clone().unwrap());
+                let mut line = String::new();
+                request.read_line(&mut line).expect("Reading request");
+                assert_eq!("REMOTE fox\n", line);
+                socket.write_all(reply.as_bytes()).expect("Replying");
+                if reply.starts_with("OK") {
+                    let mut writer =
+                        ParameterFileWriter::new(socket, RingVersion::V11).expect("Making writer");
+                    let mut defs = ParameterDefinitions::new();
+                    defs.add(1, "a");
+                    writer
+                        .write_definitions(&defs)
+                        .expect("Writing definitions");
+                    for t in triggers {
+                        let mut event = ParameterItem::new(t);
+                        event.add(1, t as f64);
+                        writer.write_event(&event).expect("Writing item");
+                    }
+                    writer.flush().expect("Flushing");
+                }
+            }
+        });
+        (port, jh)
+    }
+    fn get_items(source: &RingSource, n: usize) -> Vec<RingItem> {
+        let start = Instant::now();
+        let mut result = vec![];
+        while result.len() < n && start.elapsed() < Duration::from_secs(10) {
+            if let Some(item) = source.next_item(Duration::from_millis(100)) {
+                result.push(item);
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn parse_1() {
+        assert_eq!(
+            RingUrl {
+                host: String::from("spdaq"),
+                port: None,
+                ring: String::from("fox")
+            },
+            parse_url("tcp://spdaq/fox").unwrap()
+        );
+        assert_eq!(Some(1234), parse_url("tcp://spdaq:1234/fox").unwrap().port);
+
+        for bad in [
+            "spdaq/fox",
+            "tcp://spdaq",
+            "tcp://spdaq/",
+            "tcp:///fox",
+            "tcp://spdaq:port/fox",
+            "tcp://spdaq/a/b",
+        ] {
+            assert!(parse_url(bad).is_err(), "{}", bad);
+        }
+    }
+    #[test]
+    fn lookup_1() {
+        // Find the RingMaster in a fake port manager:
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Binding listener");
+        let pm_port = listener.local_addr().unwrap().port();
+        let jh = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().expect("Accepting");
+            let mut request = [0_u8; 5];
+            socket.read_exact(&mut request).expect("Reading request");
+            assert_eq!(b"LIST\n", &request);
+            socket
+                .write_all(b"OK 2\n30001 RestServer fox\n30002 RingMaster root\n")
+                .expect("Replying");
+        });
+        assert_eq!(30002, lookup_ringmaster("127.0.0.1", pm_port).unwrap());
+        jh.join().unwrap();
+    }
+    #[test]
+    fn connect_1() {
+        // Items come through:
+
+        let (port, jh) = ringmaster("OK BINARY FOLLOWS\n", vec![vec![1, 2, 3]]);
+        let source =
+            RingSource::connect(&format!("tcp://127.0.0.1:{}/fox", port)).expect("Connecting");
+        let items = get_items(&source, 5);
+        assert_eq!(5, items.len());
+        assert!(items[2..].iter().all(|i| i.type_id() == PARAMETER_DATA));
+        assert_eq!(0, source.dropped());
+        jh.join().unwrap();
+    }
+    #[test]
+    fn connect_2() {
+        // The RingMaster refuses:
+
+        let (port, jh) = ringmaster("FAIL no such ring\n", vec![vec![]]);
+        let result = RingSource::connect(&format!("tcp://127.0.0.1:{}/fox", port));
+        assert!(result.is_err());
+        assert!(result.err().unwrap().contains("no such ring"));
+        jh.join().unwrap();
+    }
+    #[test]
+    fn reconnect_1() {
+        // Lost connections are re-established:
+
+        let (port, jh) = ringmaster("OK BINARY FOLLOWS\n", vec![vec![1, 2], vec![3, 4, 5]]);
+        let source =
+            RingSource::connect(&format!("tcp://127.0.0.1:{}/fox", port)).expect("Connecting");
+        let items = get_items(&source, 9);
+        assert_eq!(9, items.len());
+        assert_eq!(1, source.reconnects());
+        jh.join().unwrap();
+    }
+}
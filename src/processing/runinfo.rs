@@ -0,0 +1,152 @@
+//!  Tracking of the run being analyzed.  As the processing thread
+//!  encounters state change items (BEGIN_RUN, END_RUN, PAUSE_RUN and
+//!  RESUME_RUN) it records the run number, title and when the run
+//!  started and stopped so that GUIs can show which run is being
+//!  analyzed (see /spectcl/runinfo).  The state changes are also
+//!  declared as run traces.
+//!
+//!  Optionally, all or selected spectra are cleared when a BEGIN_RUN
+//!  item is processed so that each run is histogrammed from zero, as
+//!  is typical for online analysis.
+//!
+//!  As with the end of run write policy, the run information is shared
+//!  between the processing thread, which updates it, and the API,
+//!  which reports it (see SharedRunInfo).
+//!
+use crate::ring_items::state_change::{StateChange, StateChangeType};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// The state of the run as of the most recent state change item.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RunState {
+    /// No state change item has been seen.
+    Unknown,
+    Active,
+    Paused,
+    Ended,
+}
+
+impl RunState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunState::Unknown => "Unknown",
+            RunState::Active => "Active",
+            RunState::Paused => "Paused",
+            RunState::Ended => "Ended",
+        }
+    }
+}
+
+/// What's known about the run being analyzed:
+///
+/// * state - the state of the run.
+/// * run - the run number.
+/// * title - the run title.
+/// * start - when the run began, from the BEGIN_RUN item.
+/// * stop - when the run ended, from the END_RUN item.  None while
+///   the run is active or paused.
+/// * elapsed - seconds into the run of the most recent state change.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunInfo {
+    pub state: RunState,
+    pub run: Option<u32>,
+    pub title: String,
+    pub start: Option<SystemTime>,
+    pub stop: Option<SystemTime>,
+    pub elapsed: f32,
+}
+
+impl Default for RunInfo {
+    fn default() -> RunInfo {
+        RunInfo {
+            state: RunState::Unknown,
+            run: None,
+            title: String::new(),
+            start: None,
+            stop: None,
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl RunInfo {
+    /// Update from a state change item.  A BEGIN_RUN item starts
+    /// a new run, forgetting the previous one.
+    pub fn update(&mut self, item: &StateChange) {
+        match item.change_type() {
+            StateChangeType::Begin => {
+                self.state = RunState::Active;
+                self.start = Some(item.absolute_time());
+                self.stop = None;
+            }
+            StateChangeType::End => {
+                self.state = RunState::Ended;
+                self.stop = Some(item.absolute_time());
+            }
+            StateChangeType::Pause => self.state = RunState::Paused,
+            StateChangeType::Resume => self.state = RunState::Active,
+        }
+        self.run = Some(item.run_number());
+        self.title = item.title();
+        self.elapsed = item.time_offset();
+    }
+}
+
+/// The run information and glob patterns of the spectra cleared at
+/// the beginning of each run.  If there are none, no spectra are
+/// cleared.
+#[derive(Clone, Debug, Default)]
+pub struct RunInfoState {
+    pub info: RunInfo,
+    pub clear_at_begin: Vec<String>,
+}
+
+/// The run information is shared between the processing thread and
+/// its API.
+pub type SharedRunInfo = Arc<Mutex<RunInfoState>>;
+
+#[cfg(test)]
+mod runinfo_tests {
+    use super::*;
+
+    fn item(change: StateChangeType, offset: u32) -> StateChange {
+        StateChange::new_without_body_header(change, 12, offset, 1, "A title", None)
+    }
+
+    #[test]
+    fn update_1() {
+        let mut info = RunInfo::default();
+        assert_eq!(RunState::Unknown, info.state);
+        assert!(info.run.is_none());
+
+        let begin = item(StateChangeType::Begin, 0);
+        info.update(&begin);
+        assert_eq!(RunState::Active, info.state);
+        assert_eq!(Some(12), info.run);
+        assert_eq!("A title", info.title);
+        assert_eq!(Some(begin.absolute_time()), info.start);
+        assert!(info.stop.is_none());
+
+        info.update(&item(StateChangeType::Pause, 10));
+        assert_eq!(RunState::Paused, info.state);
+        assert_eq!(10.0, info.elapsed);
+        info.update(&item(StateChangeType::Resume, 20));
+        assert_eq!(RunState::Active, info.state);
+
+        let end = item(StateChangeType::End, 100);
+        info.update(&end);
+        assert_eq!(RunState::Ended, info.state);
+        assert_eq!(100.0, info.elapsed);
+        assert_eq!(Some(end.absolute_time()), info.stop);
+
+        // The next run forgets the stop time:
+
+        let begin = item(StateChangeType::Begin, 0);
+        info.update(&begin);
+        assert!(info.stop.is_none());
+        assert_eq!(Some(begin.absolute_time()), info.start);
+        assert_eq!(0.0, info.elapsed);
+    }
+}
@@ -0,0 +1,311 @@
+//!  Accumulation of scalers.  Data sources periodically emit
+//!  PERIODIC_SCALERS ring items that hold the counts of their scaler
+//!  channels over an interval of the run.  The processing thread
+//!  accumulates them for each source id into per channel totals and
+//!  rates which are reported via the /spectcl/scaler REST interface.
+//!  Totals are cleared at the start of each run.
+//!
+//!  Scaler items without a body header are accumulated for source
+//!  id 0.  Non-incremental scalers count from the start of the run;
+//!  their increments are the differences between successive items.
+//!
+//!  Optionally, the processing thread can also histogram each
+//!  channel's counts against the time into the run.  It then makes,
+//!  if needed, a parameter and a 1d spectrum named
+//!  scaler.sid.nnn for channel nnn of source sid.  The increments
+//!  of each scaler item are put in the spectrum at the middle of the
+//!  item's interval.
+//!
+//!  As with the filters, the scalers are shared between the
+//!  processing thread, which accumulates them, and the API, which
+//!  reports them (see SharedScalers).
+//!
+use crate::ring_items::scaler_item::ScalerItem;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Source id of scaler items without a body header.
+pub const NO_SOURCE_ID: u32 = 0;
+
+/// The name of the parameter and spectrum of a scaler channel.
+pub fn scaler_name(source: u32, channel: usize) -> String {
+    format!("scaler.{}.{:03}", source, channel)
+}
+
+/// The accumulated counts of a scaler channel.  _increment_ and
+/// _rate_ (counts per second) are those of the most recent item.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ScalerChannel {
+    pub channel: usize,
+    pub total: u64,
+    pub increment: u64,
+    pub rate: f64,
+}
+
+/// The scalers of a source id:
+///
+/// * source - the source id.
+/// * items - number of scaler items accumulated.
+/// * elapsed - seconds into the run at the end of the last item.
+/// * interval - seconds covered by the last item.
+/// * channels - the channels.
+///
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ScalerSource {
+    pub source: u32,
+    pub items: u64,
+    pub elapsed: f64,
+    pub interval: f64,
+    pub channels: Vec<ScalerChannel>,
+}
+
+/// How scaler-vs-time spectra are made:  their time axes cover
+/// _duration_ seconds with _bins_ bins.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ScalerSpectra {
+    pub duration: f64,
+    pub bins: u32,
+}
+
+impl ScalerSpectra {
+    pub fn new(duration: f64, bins: u32) -> Result<ScalerSpectra, String> {
+        if !(duration.is_finite() && duration > 0.0) {
+            return Err(format!("The duration must be positive, not {}", duration));
+        }
+        if bins == 0 {
+            return Err(String::from("There must be at least one bin"));
+        }
+        Ok(ScalerSpectra { duration, bins })
+    }
+}
+
+/// The increments of a scaler item.  _time_ is the middle of the
+/// item's interval in seconds into the run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScalerIncrements {
+    pub source: u32,
+    pub time: f64,
+    pub increments: Vec<u64>,
+}
+
+// Accumulated source and, for non-incremental scalers, the values of
+// its last item:
+
+struct SourceState {
+    scalers: ScalerSource,
+    last_values: Vec<u32>,
+}
+
+/// The accumulated scalers of all source ids.
+#[derive(Default)]
+pub struct ScalerStore {
+    sources: BTreeMap<u32, SourceState>,
+    spectra: Option<ScalerSpectra>,
+}
+
+impl ScalerStore {
+    pub fn new() -> ScalerStore {
+        ScalerStore::default()
+    }
+    /// Accumulate a scaler item returning its increments.
+    pub fn add(&mut self, item: &ScalerItem) -> ScalerIncrements {
+        let source = item
+            .get_body_header()
+            .map(|h| h.source_id)
+            .unwrap_or(NO_SOURCE_ID);
+        let state = self.sources.entry(source).or_insert_with(|| SourceState {
+            scalers: ScalerSource {
+                source,
+                ..Default::default()
+            },
+            last_values: Vec::new(),
+        });
+        let start = item.get_start_secs() as f64;
+        let end = item.get_end_secs() as f64;
+        let interval = end - start;
+        let values = item.get_scaler_values();
+        let increments: Vec<u64> = values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                if item.is_incremental() {
+                    *v as u64
+                } else {
+                    v.wrapping_sub(*state.last_values.get(i).unwrap_or(&0)) as u64
+                }
+            })
+            .collect();
+        if !item.is_incremental() {
+            state.last_values = values.clone();
+        }
+
+        let scalers = &mut state.scalers;
+        scalers.items += 1;
+        scalers.elapsed = end;
+        scalers.interval = interval;
+        if scalers.channels.len() < increments.len() {
+            let first = scalers.channels.len();
+            scalers
+                .channels
+                .extend((first..increments.len()).map(|channel| ScalerChannel {
+                    channel,
+                    ..Default::default()
+                }));
+        }
+        for (channel, increment) in scalers.channels.iter_mut().zip(increments.iter()) {
+            channel.total += increment;
+            channel.increment = *increment;
+            channel.rate = if interval > 0.0 {
+                *increment as f64 / interval
+            } else {
+                0.0
+            };
+        }
+        ScalerIncrements {
+            source,
+            time: (start + end) / 2.0,
+            increments,
+        }
+    }
+    /// Forget the accumulated scalers.
+    pub fn clear(&mut self) {
+        self.sources.clear();
+    }
+    /// The accumulated scalers by source id.
+    pub fn sources(&self) -> Vec<ScalerSource> {
+        self.sources.values().map(|s| s.scalers.clone()).collect()
+    }
+    /// Set how scaler-vs-time spectra are made.  None stops making
+    /// them.
+    pub fn set_spectra(&mut self, spectra: Option<ScalerSpectra>) {
+        self.spectra = spectra;
+    }
+    pub fn spectra(&self) -> Option<ScalerSpectra> {
+        self.spectra.clone()
+    }
+}
+
+/// The scalers are shared by the processing thread and its API.
+pub type SharedScalers = Arc<Mutex<ScalerStore>>;
+
+#[cfg(test)]
+mod scaler_tests {
+    use super::*;
+    use crate::ring_items::BodyHeader;
+    use std::time;
+
+    fn item(
+        source: Option<u32>,
+        start: u32,
+        end: u32,
+        incremental: bool,
+        values: &[u32],
+    ) -> ScalerItem {
+        let header = source.map(|source_id| BodyHeader {
+            timestamp: 0,
+            source_id,
+            barrier_type: 0,
+        });
+        ScalerItem::new(
+            header,
+            start,
+            end,
+            time::SystemTime::now(),
+            1,
+            incremental,
+            None,
+            &mut values.to_vec(),
+        )
+    }
+
+    #[test]
+    fn add_1() {
+        // Incremental scalers are summed:
+
+        let mut store = ScalerStore::new();
+        let inc = store.add(&item(Some(3), 0, 10, true, &[100, 20]));
+        assert_eq!(
+            ScalerIncrements {
+                source: 3,
+                time: 5.0,
+                increments: vec![100, 20]
+            },
+            inc
+        );
+        store.add(&item(Some(3), 10, 20, true, &[50, 0]));
+
+        let sources = store.sources();
+        assert_eq!(1, sources.len());
+        let s = &sources[0];
+        assert_eq!(
+            (3, 2, 20.0, 10.0),
+            (s.source, s.items, s.elapsed, s.interval)
+        );
+        assert_eq!(
+            vec![
+                ScalerChannel {
+                    channel: 0,
+                    total: 150,
+                    increment: 50,
+                    rate: 5.0
+                },
+                ScalerChannel {
+                    channel: 1,
+                    total: 20,
+                    increment: 0,
+                    rate: 0.0
+                }
+            ],
+            s.channels
+        );
+    }
+    #[test]
+    fn add_2() {
+        // Non-incremental scalers are differenced, including when
+        // they wrap:
+
+        let mut store = ScalerStore::new();
+        store.add(&item(None, 0, 2, false, &[10]));
+        let inc = store.add(&item(None, 2, 4, false, &[30]));
+        assert_eq!(vec![20], inc.increments);
+        let inc = store.add(&item(None, 4, 6, false, &[4]));
+        assert_eq!(vec![u32::MAX as u64 - 25], inc.increments);
+
+        let s = &store.sources()[0];
+        assert_eq!(NO_SOURCE_ID, s.source);
+        assert_eq!(u32::MAX as u64 + 5, s.channels[0].total);
+    }
+    #[test]
+    fn add_3() {
+        // Sources are separate and channels can be added:
+
+        let mut store = ScalerStore::new();
+        store.add(&item(Some(2), 0, 1, true, &[1]));
+        store.add(&item(Some(1), 0, 1, true, &[1, 2]));
+        store.add(&item(Some(2), 1, 2, true, &[1, 5]));
+        let sources = store.sources();
+        assert_eq!(
+            vec![1, 2],
+            sources.iter().map(|s| s.source).collect::<Vec<u32>>()
+        );
+        let totals: Vec<u64> = sources[1].channels.iter().map(|c| c.total).collect();
+        assert_eq!(vec![2, 5], totals);
+
+        store.clear();
+        assert!(store.sources().is_empty());
+    }
+    #[test]
+    fn spectra_1() {
+        assert!(ScalerSpectra::new(0.0, 10).is_err());
+        assert!(ScalerSpectra::new(100.0, 0).is_err());
+        let spectra = ScalerSpectra::new(100.0, 10).expect("Making spectra");
+
+        let mut store = ScalerStore::new();
+        assert!(store.spectra().is_none());
+        store.set_spectra(Some(spectra.clone()));
+        assert_eq!(Some(spectra), store.spectra());
+        store.set_spectra(None);
+        assert!(store.spectra().is_none());
+    }
+}
@@ -0,0 +1,317 @@
+//!  SpecTcl XDR filter files as a data source.  SpecTcl filters write
+//!  the events that satisfy a gate in XDR (RFC 4506) encoding.  Reduced
+//!  data sets made by SpecTcl can then be re-analyzed by attaching them
+//!  here.  Filter files written by Rustogramer are parameter files and
+//!  are attached like any other file.
+//!
+//!  A SpecTcl XDR filter file is a sequence of XDR_BLOCK_SIZE byte
+//!  blocks.  Records don't span blocks; the unused end of a block is
+//!  padding.  Each record starts with an XDR string giving its type:
+//!
+//!  *  header - the number of parameters in the file followed by
+//!     their names as XDR strings.
+//!  *  event - a bit mask of the parameters the event has, one bit per
+//!     parameter in 32 bit XDR integers (bit i of word w is parameter
+//!     32w + i), followed by the values of those parameters as XDR
+//!     floats in parameter order.
+//!
+//!  Anything else, including padding, ends the records in a block.
+//!
+//!  Records are converted to the ring items of parameter files:  the
+//!  header becomes a parameter definitions item in which the parameter
+//!  ids are their position in the header (from 1) and each event a
+//!  parameter data item whose trigger number is its position in the file.
+//!  The processing thread then analyzes them as it would a parameter file.
+//!
+use crate::ring_items::analysis_ring_items::{ParameterDefinitions, ParameterItem};
+use crate::ring_items::{RingItem, ToRaw};
+use std::fs::File;
+use std::io::{BufReader, Read};
+
+/// Prefix of data source names that are SpecTcl XDR filter files.
+pub const XDR_PREFIX: &str = "xdr:";
+/// Size of the blocks of an XDR filter file.
+pub const XDR_BLOCK_SIZE: usize = 8192;
+
+/// An attached XDR filter file.
+pub struct XdrSource {
+    reader: BufReader<File>,
+    block: Vec<u8>,
+    block_offset: u64,
+    cursor: usize,
+    parameters: usize,
+    events: u64,
+}
+
+impl XdrSource {
+    /// Open an XDR filter file.
+    pub fn open(path: &str) -> Result<XdrSource, String> {
+        let fp = File::open(path).map_err(|e| e.to_string())?;
+        Ok(XdrSource {
+            reader: BufReader::with_capacity(XDR_BLOCK_SIZE, fp),
+            block: Vec::new(),
+            block_offset: 0,
+            cursor: 0,
+            parameters: 0,
+            events: 0,
+        })
+    }
+    /// Byte offset in the file of the next record.
+    pub fn position(&self) -> u64 {
+        self.block_offset + self.cursor as u64
+    }
+    /// The ring item made from the next record.  None at the end of
+    /// the file.
+    pub fn next_item(&mut self) -> Result<Option<RingItem>, String> {
+        loop {
+            if self.cursor + 4 > self.block.len() {
+                if !self.next_block()? {
+                    return Ok(None);
+                }
+                continue;
+            }
+            match self.string().as_deref() {
+                Some("header") => return self.header().map(Some),
+                Some("event") => return self.event().map(Some),
+                _ => self.cursor = self.block.len(),
+            }
+        }
+    }
+    // Read the next block.  The last block of a file may be short.
+    // Returns false at the end of the file.
+
+    fn next_block(&mut self) -> Result<bool, String> {
+        self.block_offset += self.block.len() as u64;
+        self.block.clear();
+        self.cursor = 0;
+        (&mut self.reader)
+            .take(XDR_BLOCK_SIZE as u64)
+            .read_to_end(&mut self.block)
+            .map_err(|e| format!("Unable to read XDR filter file: {}", e))?;
+        Ok(!self.block.is_empty())
+    }
+    fn bytes(&mut self, n: usize) -> Option<&[u8]> {
+        if self.cursor + n > self.block.len() {
+            None
+        } else {
+            let bytes = &self.block[self.cursor..self.cursor + n];
+            self.cursor += n;
+            Some(bytes)
+        }
+    }
+    fn int(&mut self) -> Option<u32> {
+        self.bytes(4)
+            .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+    }
+    fn float(&mut self) -> Option<f32> {
+        self.bytes(4)
+            .map(|b| f32::from_be_bytes(b.try_into().unwrap()))
+    }
+    // Strings are padded to a multiple of 4 bytes:
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.int()? as usize;
+        let padded = len.checked_add(3)? & !3;
+        self.bytes(padded)
+            .map(|b| String::from_utf8_lossy(&b[0..len]).into_owned())
+    }
+    fn header(&mut self) -> Result<RingItem, String> {
+        let truncated = || String::from("Truncated XDR filter file header");
+        let count = self.int().ok_or_else(truncated)? as usize;
+        let mut defs = ParameterDefinitions::new();
+        for i in 0..count {
+            let name = self.string().ok_or_else(truncated)?;
+            defs.add(i as u32 + 1, &name);
+        }
+        self.parameters = count;
+        Ok(defs.to_raw())
+    }
+    fn event(&mut self) -> Result<RingItem, String> {
+        if self.parameters == 0 {
+            return Err(String::from("XDR filter file event precedes its header"));
+        }
+        let truncated = || String::from("Truncated XDR filter file event");
+        let mut mask = Vec::new();
+        for _ in 0..self.parameters.div_ceil(32) {
+            mask.push(self.int().ok_or_else(truncated)?);
+        }
+        let mut event = ParameterItem::new(self.events);
+        for i in 0..self.parameters {
+            if mask[i / 32] & (1 << (i % 32)) != 0 {
+                let value = self.float().ok_or_else(truncated)?;
+                event.add(i as u32 + 1, value as f64);
+            }
+        }
+        self.events += 1;
+        Ok(event.to_raw())
+    }
+}
+
+/// Encode an XDR filter file with the parameters in names.  Each event
+/// is a list of (parameter index, value) pairs in index order.  Used
+/// to make test data.
+#[cfg(test)]
+pub fn encode(names: &[&str], events: &[Vec<(usize, f32)>]) -> Vec<u8> {
+    fn string(s: &str, out: &mut Vec<u8>) {
+        out.extend((s.len() as u32).to_be_bytes());
+        out.extend(s.as_bytes());
+        out.resize(out.len() + (4 - s.len() % 4) % 4, 0);
+    }
+    // Records that don't fit in what's left of a block start the next:
+
+    fn put(record: Vec<u8>, out: &mut Vec<u8>) {
+        let used = out.len() % XDR_BLOCK_SIZE;
+        if used + record.len() > XDR_BLOCK_SIZE {
+            out.resize(out.len() + XDR_BLOCK_SIZE - used, 0);
+        }
+        out.extend(record);
+    }
+    let mut out = Vec::new();
+    let mut header = Vec::new();
+    string("header", &mut header);
+    header.extend((names.len() as u32).to_be_bytes());
+    for name in names {
+        string(name, &mut header);
+    }
+    put(header, &mut out);
+
+    for e in events {
+        let mut record = Vec::new();
+        string("event", &mut record);
+        let mut mask = vec![0u32; names.len().div_ceil(32)];
+        for (i, _) in e {
+            mask[i / 32] |= 1 << (i % 32);
+        }
+        for word in mask {
+            record.extend(word.to_be_bytes());
+        }
+        for (_, value) in e {
+            record.extend(value.to_be_bytes());
+        }
+        put(record, &mut out);
+    }
+    let used = out.len() % XDR_BLOCK_SIZE;
+    if used != 0 {
+        out.resize(out.len() + XDR_BLOCK_SIZE - used, 0);
+    }
+    out
+}
+
+#[cfg(test)]
+mod xdr_tests {
+    use super::*;
+    use crate::ring_items::{FromRaw, RingVersion};
+    use std::io::Write;
+
+    fn write_file(dir: &tempfile::TempDir, bytes: &[u8]) -> String {
+        let path = dir.path().join("filter.xdr");
+        File::create(&path)
+            .expect("Creating file")
+            .write_all(bytes)
+            .expect("Writing file");
+        String::from(path.to_str().unwrap())
+    }
+    fn definitions(item: &RingItem) -> Vec<(u32, String)> {
+        let defs: ParameterDefinitions = item
+            .view()
+            .to_specific(RingVersion::V11)
+            .expect("Parameter definitions");
+        defs.iter().map(|d| (d.id(), d.name())).collect()
+    }
+    fn values(item: &RingItem) -> (u64, Vec<(u32, f64)>) {
+        let event: ParameterItem = item
+            .view()
+            .to_specific(RingVersion::V11)
+            .expect("Parameter data");
+        (
+            event.trigger(),
+            event.iter().map(|p| (p.id(), p.value())).collect(),
+        )
+    }
+
+    #[test]
+    fn header_1() {
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let path = write_file(&dir, &encode(&["a", "bb", "event.raw.00"], &[]));
+        let mut source = XdrSource::open(&path).expect("Opening");
+
+        let item = source.next_item().expect("Reading").expect("An item");
+        assert_eq!(
+            vec![
+                (1, String::from("a")),
+                (2, String::from("bb")),
+                (3, String::from("event.raw.00"))
+            ],
+            definitions(&item)
+        );
+        assert!(source.next_item().expect("Reading").is_none());
+        assert_eq!(XDR_BLOCK_SIZE as u64, source.position());
+    }
+    #[test]
+    fn event_1() {
+        // Events with more than 32 parameters and sparse events:
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let names: Vec<String> = (0..40).map(|i| format!("p{}", i)).collect();
+        let names: Vec<&str> = names.iter().map(|s| s.as_str()).collect();
+        let path = write_file(
+            &dir,
+            &encode(
+                &names,
+                &[vec![(0, 1.5), (33, 2.0)], vec![], vec![(39, -1.0)]],
+            ),
+        );
+        let mut source = XdrSource::open(&path).expect("Opening");
+
+        assert_eq!(40, definitions(&source.next_item().unwrap().unwrap()).len());
+        assert_eq!(
+            (0, vec![(1, 1.5), (34, 2.0)]),
+            values(&source.next_item().unwrap().unwrap())
+        );
+        assert_eq!((1, vec![]), values(&source.next_item().unwrap().unwrap()));
+        assert_eq!(
+            (2, vec![(40, -1.0)]),
+            values(&source.next_item().unwrap().unwrap())
+        );
+        assert!(source.next_item().unwrap().is_none());
+    }
+    #[test]
+    fn block_1() {
+        // Events continue in the next block:
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let events: Vec<Vec<(usize, f32)>> =
+            (0..2000).map(|i| vec![(0, i as f32), (1, 1.0)]).collect();
+        let bytes = encode(&["x", "y"], &events);
+        assert!(bytes.len() > XDR_BLOCK_SIZE);
+        let path = write_file(&dir, &bytes);
+        let mut source = XdrSource::open(&path).expect("Opening");
+
+        source.next_item().unwrap().unwrap();
+        for i in 0..2000 {
+            let (trigger, v) = values(&source.next_item().unwrap().unwrap());
+            assert_eq!(i, trigger);
+            assert_eq!(vec![(1, i as f64), (2, 1.0)], v);
+        }
+        assert!(source.next_item().unwrap().is_none());
+        assert_eq!(bytes.len() as u64, source.position());
+    }
+    #[test]
+    fn error_1() {
+        // An event without a header and a truncated event:
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let bytes = encode(&["x"], &[vec![(0, 1.0)]]);
+        let header_size = 4 + 8 + 4 + 4 + 4;
+        let path = write_file(&dir, &bytes[header_size..]);
+        let mut source = XdrSource::open(&path).expect("Opening");
+        assert!(source.next_item().is_err());
+
+        let path = write_file(&dir, &bytes[0..header_size + 12]);
+        let mut source = XdrSource::open(&path).expect("Opening");
+        source.next_item().unwrap().unwrap();
+        assert!(source.next_item().is_err());
+
+        assert!(XdrSource::open("/no/such/file.xdr").is_err());
+    }
+}
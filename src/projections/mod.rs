@@ -8,7 +8,7 @@
 //!
 //!  
 
-use crate::messaging::{condition_messages, spectrum_messages};
+use crate::messaging::{condition_messages, parameter_messages, spectrum_messages};
 
 ///
 /// Legal projection directions:
@@ -20,6 +20,27 @@ pub enum ProjectionDirection {
     Y,
 }
 
+///  Given a spectrum definition and the requested projection direction,
+///  returns the axis the projection data are summed onto.  This is
+///  the requested direction for all spectra but Summary spectra.
+///  For those, an X projection sums over all of the strips (onto the
+///  Y, parameter value, axis) while a Y projection totals each strip
+///  (onto the X, strip index, axis).
+///
+fn data_direction(
+    def: &spectrum_messages::SpectrumProperties,
+    direction: ProjectionDirection,
+) -> ProjectionDirection {
+    if def.type_name == "Summary" {
+        match direction {
+            ProjectionDirection::X => ProjectionDirection::Y,
+            ProjectionDirection::Y => ProjectionDirection::X,
+        }
+    } else {
+        direction
+    }
+}
+
 ///  Given a spectrum_messages::SpectrumProperties reference,
 ///  returns a pre-zeroed vector that can hold a projection
 ///  along the specified axis.
@@ -39,7 +60,7 @@ fn make_sum_vector(
     direction: ProjectionDirection,
 ) -> Result<Vec<f64>, String> {
     if def.xaxis.is_some() && def.yaxis.is_some() {
-        let n_element = if direction == ProjectionDirection::X {
+        let n_element = if data_direction(def, direction) == ProjectionDirection::X {
             def.xaxis.unwrap().bins
         } else {
             def.yaxis.unwrap().bins
@@ -85,6 +106,7 @@ where
         Ok(mut v) => {
             // Get the axis specification:
 
+            let direction = data_direction(desc, direction);
             let axis = if let ProjectionDirection::X = direction {
                 desc.xaxis.unwrap()
             } else {
@@ -137,7 +159,7 @@ pub fn make_projection_spectrum(
 ) -> Result<(), String> {
     // in general the axis is the axis of the projection direction:
 
-    let mut resulting_axis = match direction {
+    let mut resulting_axis = match data_direction(desc, direction) {
         ProjectionDirection::X => {
             
             if let Some(a) = desc.xaxis {
@@ -216,6 +238,25 @@ pub fn make_projection_spectrum(
             resulting_axis.high,
             resulting_axis.bins,
         ),
+        "Summary" => match direction {
+            // Summing over all strips is faithfully incremented by a
+            // Multi1d on all of the parameters.  Strip totals have no
+            // parameter that can increment them so they go in a
+            // parameterless snapshot spectrum.
+            ProjectionDirection::X => api.create_spectrum_multi1d(
+                new_name,
+                &desc.xparams,
+                resulting_axis.low,
+                resulting_axis.high,
+                resulting_axis.bins,
+            ),
+            ProjectionDirection::Y => api.create_spectrum_snapshot1d(
+                new_name,
+                resulting_axis.low,
+                resulting_axis.high,
+                resulting_axis.bins,
+            ),
+        },
         _ => Err(format!("{} spectra cannot be projected", desc.type_name)),
     };
     // Still need to fill the spectrum....
@@ -294,6 +335,49 @@ fn create_projection_gate(
     None
 }
 
+/// PGamma spectra increment for every pair of an x and a y parameter.
+/// A contour drawn on one, however, is on a single pair of parameter ids,
+/// so gating a projection on it would not increment faithfully.  Instead
+/// a MultiContour (SpecTcl gamma contour) with the same points on all of
+/// the spectrum's parameters is created to gate the projection.
+///
+/// ### Parameters:
+///   *  papi - parameter API instance reference used to look up parameter ids.
+///   *  gapi - condition API instance reference.
+///   *  dest_name - destination spectrum name - used to name the condition
+///      ("_dest_name_projection_contour_").
+///   *  source_desc - References the description of the source (PGamma) spectrum.
+///   *  points - the points of the area of interest contour.
+///
+/// ### Returns:
+///   Result<String, String> - on success the name of the condition.
+///
+fn create_pgamma_contour(
+    papi: &parameter_messages::ParameterMessageClient,
+    gapi: &condition_messages::ConditionMessageClient,
+    dest_name: &str,
+    source_desc: &spectrum_messages::SpectrumProperties,
+    points: &[(f64, f64)],
+) -> Result<String, String> {
+    let mut ids = Vec::<u32>::new();
+    for name in source_desc.xparams.iter().chain(source_desc.yparams.iter()) {
+        let id = papi
+            .list_parameters(name)?
+            .iter()
+            .find(|p| p.get_name() == *name)
+            .map(|p| p.get_id())
+            .ok_or_else(|| format!("Parameter {} does not exist", name))?;
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+    let cond_name = format!("_{}_projection_contour_", dest_name);
+    match gapi.create_multicontour_condition(&cond_name, &ids, points) {
+        condition_messages::ConditionReply::Error(s) => Err(s),
+        _ => Ok(cond_name),
+    }
+}
+
 /// Actually do the projection.
 /// This is called by the ReST handler to:
 /// *  Figure out the contents of the projected spectrum.
@@ -304,6 +388,7 @@ fn create_projection_gate(
 /// ### Parameters:
 /// *  sapi - spectrum messaging api reference.
 /// *  gapi - Condition/gate messaging api reference.
+/// *  papi - Parameter messaging api reference.
 /// *  source - Name of the spectrum to be projected.
 /// *  direction - desired direction of projection.
 /// *  dest - Name of the resulting spectrum if successful.
@@ -334,6 +419,11 @@ fn create_projection_gate(
 /// gates the resulting spectrum.
 /// If there is a region of interest it is anded with any non-snapshot gate.
 ///
+/// For PGamma spectra, the region of interest is replaced by a MultiContour
+/// with the same points on all of the spectrum's parameters
+/// (see create_pgamma_contour).  Projections of Summary spectra are
+/// always snapshots if they are Y projections or have a region of interest.
+///
 /// The intent of these gating rules is that either the specturm never increments
 /// because it's a snapshot (false gate) or it increments in a manner that makes it
 /// a faithful projection
@@ -343,6 +433,7 @@ fn create_projection_gate(
 pub fn project(
     sapi: &spectrum_messages::SpectrumMessageClient,
     gapi: &condition_messages::ConditionMessageClient,
+    papi: &parameter_messages::ParameterMessageClient,
     source: &str,
     direction: ProjectionDirection,
     dest: &str,
@@ -380,6 +471,7 @@ pub fn project(
         return Err(format!("Failed to get spectrum contents: {}", s));
     }
     let contents = contents.unwrap();
+    let mut roi_points = vec![];
     let data = if let Some(roi) = aoi.clone() {
         let cprops = match gapi.list_conditions(&roi) {
             condition_messages::ConditionReply::Error(s) => {
@@ -394,6 +486,7 @@ pub fn project(
         if cprops.len() != 1 {
             return Err(format!("{} does not uniquely identify a condition", roi));
         }
+        roi_points = cprops[0].points.clone();
         let contour = condition_messages::reconstitute_contour(cprops[0].clone());
         if let Err(s) = contour {
             return Err(format!("Could not recontitute {} as a contoure {}", roi, s));
@@ -416,9 +509,23 @@ pub fn project(
         return Err(format!("Failed to create projection spectrum: {}", s));
     }
 
-    // Figure out the correct gate:
+    // Figure out the correct gate.  The Y projection of a summary spectrum
+    // can't be faithfully incremented so it's always a snapshot.  Neither
+    // can an X projection within a contour;  the contour is on strip
+    // index and value, not on any pair of the summary's parameters:
 
-    if let Some(g) = create_projection_gate(gapi, dest, &source_desc, aoi.clone(), snapshot) {
+    let snapshot = snapshot
+        || (source_desc.type_name == "Summary"
+            && (direction == ProjectionDirection::Y || aoi.is_some()));
+    let aoi = if aoi.is_some() && !snapshot && source_desc.type_name == "PGamma" {
+        match create_pgamma_contour(papi, gapi, dest, &source_desc, &roi_points) {
+            Ok(name) => Some(name),
+            Err(s) => return Err(format!("Failed to create projection contour: {}", s)),
+        }
+    } else {
+        aoi
+    };
+    if let Some(g) = create_projection_gate(gapi, dest, &source_desc, aoi, snapshot) {
         sapi.gate_spectrum(dest, &g)
     } else {
         Ok(())
@@ -810,6 +917,64 @@ mod project_spectrum_tests {
             assert_eq!(0.0, *x, "Nonzero value in Y chanel {}", i)
         }
     }
+    #[test]
+    fn ok_6() {
+        // Summary spectra project the other way around:  X sums over the
+        // strips onto the y axis and Y totals each strip:
+
+        let props = spectrum_messages::SpectrumProperties {
+            id: 0,
+            name: String::from("test"),
+            type_name: String::from("Summary"),
+            xparams: vec![String::from("p1"), String::from("p2")],
+            yparams: vec![],
+            xaxis: Some(spectrum_messages::AxisSpecification {
+                low: 0.0,
+                high: 2.0,
+                bins: 4,
+            }),
+            yaxis: Some(spectrum_messages::AxisSpecification {
+                low: 0.0,
+                high: 1024.0,
+                bins: 1026,
+            }),
+            gate: None,
+            fold: None,
+        };
+        let contents = vec![
+            spectrum_messages::Channel {
+                chan_type: spectrum_messages::ChannelType::Bin,
+                x: 0.0,
+                y: 100.0,
+                bin: 0,
+                value: 10.0,
+            },
+            spectrum_messages::Channel {
+                chan_type: spectrum_messages::ChannelType::Bin,
+                x: 1.0,
+                y: 100.0,
+                bin: 0,
+                value: 20.0,
+            },
+            spectrum_messages::Channel {
+                chan_type: spectrum_messages::ChannelType::Bin,
+                x: 1.0,
+                y: 200.0,
+                bin: 0,
+                value: 5.0,
+            },
+        ];
+        let xproj = project_spectrum(&props, &contents, ProjectionDirection::X, |_, _| true)
+            .expect("X Projection");
+        assert_eq!(1026, xproj.len());
+        assert_eq!(30.0, xproj[101]);
+        assert_eq!(5.0, xproj[201]);
+        assert_eq!(35.0, xproj.iter().sum::<f64>());
+
+        let yproj = project_spectrum(&props, &contents, ProjectionDirection::Y, |_, _| true)
+            .expect("Y Projection");
+        assert_eq!(vec![0.0, 10.0, 25.0, 0.0], yproj);
+    }
 }
 // Tests for make_projection_spectrum  Note these will need a
 // server to work properly.
@@ -1737,6 +1902,133 @@ mod make_spectrum_tests {
             );
         }
 
+        teardown(ch, jh);
+    }
+    // Summary spectra - X projections are Multi1d spectra of the
+    // sum over strips, Y projections Snapshot1D spectra of the strip totals.
+
+    fn make_summary_properties(
+        ch: &mpsc::Sender<messaging::Request>,
+    ) -> spectrum_messages::SpectrumProperties {
+        let papi = parameter_messages::ParameterMessageClient::new(ch);
+        for p in ["s1", "s2", "s3"] {
+            papi.create_parameter(p).expect("Making a parameter");
+        }
+
+        spectrum_messages::SpectrumProperties {
+            id: 0,
+            name: String::from("input"),
+            type_name: String::from("Summary"),
+            xparams: vec![String::from("s1"), String::from("s2"), String::from("s3")],
+            yparams: vec![],
+            xaxis: Some(spectrum_messages::AxisSpecification {
+                low: 0.0,
+                high: 3.0,
+                bins: 5,
+            }),
+            yaxis: Some(spectrum_messages::AxisSpecification {
+                low: 0.0,
+                high: 512.0,
+                bins: 514,
+            }),
+            gate: None,
+            fold: None,
+        }
+    }
+    #[test]
+    fn summary_1() {
+        // X projection has correct properties.
+
+        let (ch, jh) = setup();
+        let desc = make_summary_properties(&ch);
+        let api = spectrum_messages::SpectrumMessageClient::new(&ch);
+        make_projection_spectrum(&api, "test1", &desc, ProjectionDirection::X, vec![])
+            .expect("Making X projection");
+
+        let props = api
+            .list_spectra("test1")
+            .expect("failed to get spectrum list");
+        assert_eq!(1, props.len());
+        assert_eq!(
+            spectrum_messages::SpectrumProperties {
+                id: 0,
+                name: String::from("test1"),
+                type_name: String::from("Multi1d"),
+                xparams: vec![String::from("s1"), String::from("s2"), String::from("s3"),],
+                yparams: vec![],
+                xaxis: Some(spectrum_messages::AxisSpecification {
+                    low: 0.0,
+                    high: 512.0,
+                    bins: 514,
+                }),
+                yaxis: None,
+                gate: None,
+                fold: None
+            },
+            props[0]
+        );
+
+        teardown(ch, jh);
+    }
+    #[test]
+    fn summary_2() {
+        // Y projection has correct properties.
+
+        let (ch, jh) = setup();
+        let desc = make_summary_properties(&ch);
+        let api = spectrum_messages::SpectrumMessageClient::new(&ch);
+        make_projection_spectrum(&api, "test1", &desc, ProjectionDirection::Y, vec![])
+            .expect("Making Y projection");
+
+        let props = api
+            .list_spectra("test1")
+            .expect("failed to get spectrum list");
+        assert_eq!(1, props.len());
+        assert_eq!(
+            spectrum_messages::SpectrumProperties {
+                id: 0,
+                name: String::from("test1"),
+                type_name: String::from("Snapshot1D"),
+                xparams: vec![],
+                yparams: vec![],
+                xaxis: Some(spectrum_messages::AxisSpecification {
+                    low: 0.0,
+                    high: 3.0,
+                    bins: 5,
+                }),
+                yaxis: None,
+                gate: None,
+                fold: None
+            },
+            props[0]
+        );
+
+        teardown(ch, jh);
+    }
+    #[test]
+    fn summary_3() {
+        // Both projections have the correct contents.
+
+        let (ch, jh) = setup();
+        let desc = make_summary_properties(&ch);
+        let api = spectrum_messages::SpectrumMessageClient::new(&ch);
+        for (name, direction, bins) in [
+            ("test1", ProjectionDirection::X, desc.yaxis.unwrap().bins),
+            ("test2", ProjectionDirection::Y, desc.xaxis.unwrap().bins),
+        ] {
+            let data: Vec<f64> = (0..bins).map(|i| (i + 10) as f64).collect();
+            make_projection_spectrum(&api, name, &desc, direction, data.clone())
+                .expect("Making projection");
+
+            let projection = api
+                .get_contents(name, -2048.0, 2048.0, -2048.0, 2048.0)
+                .expect("Getting spectrum contents");
+            assert_eq!(data.len(), projection.len());
+            for (i, x) in data.iter().enumerate() {
+                assert_eq!(*x, projection[i].value, "{}: {:?}", i, projection[i]);
+            }
+        }
+
         teardown(ch, jh);
     }
 }
@@ -1991,6 +2283,7 @@ mod project_tests {
         let (ch, jh) = setup();
         let sapi = spectrum_messages::SpectrumMessageClient::new(&ch);
         let gapi = condition_messages::ConditionMessageClient::new(&ch);
+        let papi = parameter_messages::ParameterMessageClient::new(&ch);
 
         // Put some data into "test" to project.  We're projecting on x/ no contour:
         // put a horizontal line of data in the test spectrum:
@@ -2013,6 +2306,7 @@ mod project_tests {
         project(
             &sapi,
             &gapi,
+            &papi,
             "test",
             ProjectionDirection::X,
             "proj",
@@ -2074,6 +2368,7 @@ mod project_tests {
         let (ch, jh) = setup();
         let sapi = spectrum_messages::SpectrumMessageClient::new(&ch);
         let gapi = condition_messages::ConditionMessageClient::new(&ch);
+        let papi = parameter_messages::ParameterMessageClient::new(&ch);
 
         // Put some data into "test" to project.  We're projecting on x/ no contour:
         // put a horizontal line of data in the test spectrum:
@@ -2099,6 +2394,7 @@ mod project_tests {
         project(
             &sapi,
             &gapi,
+            &papi,
             "test",
             ProjectionDirection::Y,
             "proj",
@@ -2155,6 +2451,7 @@ mod project_tests {
 
         let sapi = spectrum_messages::SpectrumMessageClient::new(&ch);
         let gapi = condition_messages::ConditionMessageClient::new(&ch);
+        let papi = parameter_messages::ParameterMessageClient::new(&ch);
 
         // Put some data into "test" to project.  We're projecting on x/ no contour:
         // put a horizontal line of data in the test spectrum:
@@ -2175,6 +2472,7 @@ mod project_tests {
         project(
             &sapi,
             &gapi,
+            &papi,
             "test",
             ProjectionDirection::X,
             "proj",
@@ -2233,6 +2531,7 @@ mod project_tests {
 
         let sapi = spectrum_messages::SpectrumMessageClient::new(&ch);
         let gapi = condition_messages::ConditionMessageClient::new(&ch);
+        let papi = parameter_messages::ParameterMessageClient::new(&ch);
 
         // Put some data into "test" to project.  We're projecting on x/ no contour:
         // put a horizontal line of data in the test spectrum:
@@ -2259,6 +2558,7 @@ mod project_tests {
         project(
             &sapi,
             &gapi,
+            &papi,
             "test",
             ProjectionDirection::Y,
             "proj",
@@ -2318,6 +2618,7 @@ mod project_tests {
 
         let sapi = spectrum_messages::SpectrumMessageClient::new(&ch);
         let gapi = condition_messages::ConditionMessageClient::new(&ch);
+        let papi = parameter_messages::ParameterMessageClient::new(&ch);
 
         // gate the test spectrum:
 
@@ -2346,6 +2647,7 @@ mod project_tests {
         project(
             &sapi,
             &gapi,
+            &papi,
             "test",
             ProjectionDirection::X,
             "proj",
@@ -2405,8 +2707,10 @@ mod project_tests {
                         cond_name: String::from("_proj_projection_gate_"),
                         type_name: String::from("And"),
                         points: vec![],
+                        polygons: vec![],
                         gates: vec![String::from("true"), String::from("contour")],
-                        parameters: vec![]
+                        parameters: vec![],
+                        disabled: None
                     },
                     gate
                 );
@@ -2426,6 +2730,7 @@ mod project_tests {
 
         let sapi = spectrum_messages::SpectrumMessageClient::new(&ch);
         let gapi = condition_messages::ConditionMessageClient::new(&ch);
+        let papi = parameter_messages::ParameterMessageClient::new(&ch);
 
         sapi.gate_spectrum("test", "true").expect("Gating spectrum");
 
@@ -2454,6 +2759,7 @@ mod project_tests {
         project(
             &sapi,
             &gapi,
+            &papi,
             "test",
             ProjectionDirection::Y,
             "proj",
@@ -2506,8 +2812,10 @@ mod project_tests {
                         cond_name: String::from("_proj_projection_gate_"),
                         type_name: String::from("And"),
                         points: vec![],
+                        polygons: vec![],
                         gates: vec![String::from("true"), String::from("contour")],
-                        parameters: vec![]
+                        parameters: vec![],
+                        disabled: None
                     },
                     condition
                 );
@@ -2516,4 +2824,185 @@ mod project_tests {
         }
         teardown(ch, jh);
     }
+    #[test]
+    fn project_7() {
+        // Projections of a summary spectrum.  The Y projection (strip totals)
+        // is always a snapshot.
+
+        let (ch, jh) = setup();
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&ch);
+        let gapi = condition_messages::ConditionMessageClient::new(&ch);
+        let papi = parameter_messages::ParameterMessageClient::new(&ch);
+
+        let params: Vec<String> = (0..3).map(|i| format!("param.{}", i)).collect();
+        sapi.create_spectrum_summary("summary", &params, 0.0, 1024.0, 512)
+            .expect("Creating summary spectrum");
+
+        // Strip i has i+1 counts at y = 100:
+
+        let contents: Vec<spectrum_messages::Channel> = (0..3)
+            .map(|i| spectrum_messages::Channel {
+                chan_type: spectrum_messages::ChannelType::Bin,
+                x: i as f64,
+                y: 100.0,
+                bin: 0,
+                value: (i + 1) as f64,
+            })
+            .collect();
+        sapi.fill_spectrum("summary", contents)
+            .expect("Filling summary spectrum");
+
+        project(
+            &sapi,
+            &gapi,
+            &papi,
+            "summary",
+            ProjectionDirection::X,
+            "xproj",
+            false,
+            None,
+        )
+        .expect("X projection");
+        let desc = sapi.list_spectra("xproj").expect("Listing xproj");
+        assert_eq!("Multi1d", desc[0].type_name);
+        assert_eq!(params, desc[0].xparams);
+        assert!(desc[0].gate.is_none());
+        let data = sapi
+            .get_contents("xproj", -2048.0, 2048.0, -2048.0, 2048.0)
+            .expect("Getting xproj contents");
+        assert_eq!(1, data.len());
+        assert_eq!(100.0, data[0].x);
+        assert_eq!(6.0, data[0].value);
+
+        project(
+            &sapi,
+            &gapi,
+            &papi,
+            "summary",
+            ProjectionDirection::Y,
+            "yproj",
+            false,
+            None,
+        )
+        .expect("Y projection");
+        let desc = sapi.list_spectra("yproj").expect("Listing yproj");
+        assert_eq!("Snapshot1D", desc[0].type_name);
+        assert!(desc[0].xparams.is_empty());
+        assert_eq!(Some(String::from("_snapshot_condition_")), desc[0].gate);
+        let data = sapi
+            .get_contents("yproj", -2048.0, 2048.0, -2048.0, 2048.0)
+            .expect("Getting yproj contents");
+        assert_eq!(3, data.len());
+        for (i, d) in data.iter().enumerate() {
+            assert_eq!(i as f64, d.x);
+            assert_eq!((i + 1) as f64, d.value);
+        }
+
+        // An X projection within a contour is a snapshot too.  The
+        // contour takes strips 0 and 1 and its parameters are unrelated
+        // to the summary's:
+
+        let points = vec![(-0.5, 50.0), (1.7, 50.0), (1.7, 150.0), (-0.5, 150.0)];
+        if let condition_messages::ConditionReply::Error(s) =
+            gapi.create_contour_condition("strips", 5, 6, &points)
+        {
+            panic!("Failed to create contour : {}", s);
+        }
+        project(
+            &sapi,
+            &gapi,
+            &papi,
+            "summary",
+            ProjectionDirection::X,
+            "xaoi",
+            false,
+            Some(String::from("strips")),
+        )
+        .expect("X projection in contour");
+        let desc = sapi.list_spectra("xaoi").expect("Listing xaoi");
+        assert_eq!("Multi1d", desc[0].type_name);
+        assert_eq!(Some(String::from("_snapshot_condition_")), desc[0].gate);
+        let data = sapi
+            .get_contents("xaoi", -2048.0, 2048.0, -2048.0, 2048.0)
+            .expect("Getting xaoi contents");
+        assert_eq!(1, data.len());
+        assert_eq!(100.0, data[0].x);
+        assert_eq!(3.0, data[0].value);
+
+        teardown(ch, jh);
+    }
+    #[test]
+    fn project_8() {
+        // Projecting a PGamma spectrum within a contour gates the
+        // projection on a MultiContour on all of the spectrum's parameters
+        // rather than on the contour itself, in either direction:
+
+        let (ch, jh) = setup();
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&ch);
+        let gapi = condition_messages::ConditionMessageClient::new(&ch);
+        let papi = parameter_messages::ParameterMessageClient::new(&ch);
+
+        let xparams = vec![String::from("param.2"), String::from("param.3")];
+        let yparams = vec![String::from("param.4")];
+        sapi.create_spectrum_pgamma("pg", &xparams, &yparams, 0.0, 1024.0, 512, 0.0, 1024.0, 512)
+            .expect("Creating pgamma spectrum");
+
+        for (direction, dest) in [
+            (ProjectionDirection::X, "xproj"),
+            (ProjectionDirection::Y, "yproj"),
+        ] {
+            project(
+                &sapi,
+                &gapi,
+                &papi,
+                "pg",
+                direction,
+                dest,
+                false,
+                Some(String::from("contour")),
+            )
+            .expect("Projecting");
+
+            let gate_name = format!("_{}_projection_contour_", dest);
+            let desc = sapi.list_spectra(dest).expect("Getting spectrum list");
+            assert_eq!(1, desc.len());
+            assert_eq!("Multi1d", desc[0].type_name);
+            assert_eq!(Some(gate_name.clone()), desc[0].gate);
+
+            match gapi.list_conditions(&gate_name) {
+                condition_messages::ConditionReply::Listing(v) => {
+                    assert_eq!(1, v.len());
+                    assert_eq!("MultiContour", v[0].type_name);
+                    assert_eq!(vec![3, 4, 5], v[0].parameters);
+                    assert_eq!(
+                        vec![
+                            (100.0, 100.0),
+                            (200.0, 100.0),
+                            (200.0, 600.0),
+                            (100.0, 600.0)
+                        ],
+                        v[0].points
+                    );
+                }
+                _ => panic!("Could not list {}", gate_name),
+            }
+        }
+        // Snapshots are still gated on the snapshot condition:
+
+        project(
+            &sapi,
+            &gapi,
+            &papi,
+            "pg",
+            ProjectionDirection::X,
+            "snap",
+            true,
+            Some(String::from("contour")),
+        )
+        .expect("Projecting snapshot");
+        let desc = sapi.list_spectra("snap").expect("Getting spectrum list");
+        assert_eq!(Some(String::from("_snapshot_condition_")), desc[0].gate);
+
+        teardown(ch, jh);
+    }
 }
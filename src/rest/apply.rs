@@ -6,13 +6,30 @@
 //! be incremented for events that make that condition true.
 //!  *  list - lists the gates applied to a set of spectra that match
 //! the pattern supplied in the request.
+//!  *  history - (Rustogramer only) lists the history of gate applications
+//! and removals of the spectra that match the pattern supplied.
 //!
+//!  Applications and removals made via apply and /spectcl/ungate are
+//! recorded in the trace store's application history along with the
+//! address of the client that asked for them.
+//!
+//!  list responses are briefly cached for polling clients (see cache.rs).
 
 use rocket::serde::{json::Json, Deserialize, Serialize};
 use rocket::State;
 
 use super::*;
 use crate::messaging::spectrum_messages::SpectrumMessageClient;
+use crate::trace;
+use std::net::IpAddr;
+use std::time;
+
+/// Describe who asked for a gate change.
+pub fn requestor(client: Option<IpAddr>) -> String {
+    client
+        .map(|a| a.to_string())
+        .unwrap_or_else(|| String::from("unknown"))
+}
 
 //---------------------------------------------------------------
 // Stuff needed to implement apply:
@@ -39,21 +56,30 @@ pub struct GateApplicationResponse {
 /// and the detail is an array of the spectrum for which we could not
 /// apply the condition.
 ///
+/// Successful applications are recorded in the application history.
+///
 #[get("/apply?<gate>&<spectrum>")]
 pub fn apply_gate(
     gate: String,
     spectrum: Vec<String>,
     state: &State<SharedHistogramChannel>,
+    tracedb: &State<trace::SharedTraceStore>,
+    client: Option<IpAddr>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
 ) -> Json<GateApplicationResponse> {
     let mut response = GateApplicationResponse {
         status: String::from("OK"),
         detail: Vec::new(),
     };
+    let who = requestor(client);
     let api = SpectrumMessageClient::new(&state.inner().lock().unwrap());
     for name in spectrum {
         if let Err(s) = api.gate_spectrum(&name, &gate) {
             response.status = format!("Failed to apply {} to some spectra", gate);
             response.detail.push((name, s));
+        } else {
+            tracedb.inner().log_application(&name, Some(&gate), &who);
         }
     }
     Json(response)
@@ -61,13 +87,13 @@ pub fn apply_gate(
 //---------------------------------------------------------------------
 // Stuff needed to provde the application list.
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(crate = "rocket::serde")]
 pub struct Application {
     spectrum: String,
     gate: Option<String>,
 }
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(crate = "rocket::serde")]
 pub struct ApplicationListing {
     status: String,
@@ -78,32 +104,117 @@ pub struct ApplicationListing {
 pub fn apply_list(
     pattern: OptionalString,
     state: &State<SharedHistogramChannel>,
+    _ready: busy::HistogramerReady,
+    cache: cache::Cache,
 ) -> Json<ApplicationListing> {
     let mut pat = String::from("*"); // Default pattern
     if let Some(s) = pattern {
         pat = s; // User supplied pattern.
     }
 
-    let api = SpectrumMessageClient::new(&state.inner().lock().unwrap());
-    let listing = api.list_spectra(&pat);
-    if listing.is_err() {
-        return Json(ApplicationListing {
-            status: format!("Failed to get spectrum listing: {}", listing.unwrap_err()),
+    Json(cache.get_or_compute(&format!("apply/list?{}", pat), || {
+        let api = SpectrumMessageClient::new(&state.inner().lock().unwrap());
+        let listing = match api.list_spectra(&pat) {
+            Ok(l) => l,
+            Err(e) => {
+                return ApplicationListing {
+                    status: format!("Failed to get spectrum listing: {}", e),
+                    detail: Vec::new(),
+                };
+            }
+        };
+        let mut result = ApplicationListing {
+            status: String::from("OK"),
             detail: Vec::new(),
-        });
-    }
-    let listing = listing.unwrap();
-    let mut result = ApplicationListing {
-        status: String::from("OK"),
-        detail: Vec::new(),
-    };
-    for spectrum in listing {
-        result.detail.push(Application {
-            spectrum: spectrum.name,
-            gate: spectrum.gate,
-        });
-    }
-    Json(result)
+        };
+        for spectrum in listing {
+            result.detail.push(Application {
+                spectrum: spectrum.name,
+                gate: spectrum.gate,
+            });
+        }
+        result
+    }))
+}
+//---------------------------------------------------------------------
+// Stuff needed to provide the application history.
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ApplicationEvent {
+    time: u64,
+    operation: String,
+    gate: Option<String>,
+    who: String,
+    run: Option<u32>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ApplicationHistory {
+    spectrum: String,
+    history: Vec<ApplicationEvent>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ApplicationHistoryListing {
+    status: String,
+    detail: Vec<ApplicationHistory>,
+}
+///
+/// List the gate application history of spectra.  This is
+/// Rustogramer specific.  The optional pattern query parameter
+/// is a glob pattern that selects the spectra whose histories
+/// are listed (defaults to "*").
+///
+/// The detail is an array with an element for each spectrum that has
+/// had a gate applied or removed, sorted by spectrum name, containing the
+/// spectrum name and its history, oldest first.  Each history entry has:
+///
+/// * time - unix time (seconds) at which the change was made.
+/// * operation - "apply" or "remove".
+/// * gate - the gate applied (null for "remove").
+/// * who - the address of the client that asked for the change.
+/// * run - the run being analyzed at the time (null if none).
+///
+#[get("/history?<pattern>")]
+pub fn apply_history(
+    pattern: OptionalString,
+    tracedb: &State<trace::SharedTraceStore>,
+) -> Json<ApplicationHistoryListing> {
+    let pat = pattern.unwrap_or_else(|| String::from("*"));
+    Json(match tracedb.inner().get_application_history(&pat) {
+        Ok(histories) => ApplicationHistoryListing {
+            status: String::from("OK"),
+            detail: histories
+                .into_iter()
+                .map(|(spectrum, entries)| ApplicationHistory {
+                    spectrum,
+                    history: entries
+                        .into_iter()
+                        .map(|e| ApplicationEvent {
+                            time: e
+                                .stamp
+                                .duration_since(time::UNIX_EPOCH)
+                                .map(|d| d.as_secs())
+                                .unwrap_or(0),
+                            operation: String::from(if e.gate.is_some() {
+                                "apply"
+                            } else {
+                                "remove"
+                            }),
+                            gate: e.gate,
+                            who: e.who,
+                            run: e.run,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        },
+        Err(s) => ApplicationHistoryListing {
+            status: format!("Bad pattern {}: {}", pat, s),
+            detail: Vec::new(),
+        },
+    })
 }
 //-----------------------------------------------------------------
 // what we need for /spectcl/ungate.
@@ -122,20 +233,29 @@ pub fn apply_list(
 /// ungated and the second the reason given by the spectrum
 /// messaging API.
 ///
+/// Successful removals are recorded in the application history.
+///
 #[get("/?<name>")]
 pub fn ungate_spectrum(
     name: Vec<String>,
     state: &State<SharedHistogramChannel>,
+    tracedb: &State<trace::SharedTraceStore>,
+    client: Option<IpAddr>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
 ) -> Json<GateApplicationResponse> {
     let api = SpectrumMessageClient::new(&state.inner().lock().unwrap());
     let mut result = GateApplicationResponse {
         status: String::from("OK"),
         detail: Vec::new(),
     };
+    let who = requestor(client);
     for spectrum in name {
         if let Err(s) = api.ungate_spectrum(&spectrum) {
             result.status = String::from("Unable to ungate at least one spectrum");
             result.detail.push((spectrum, s));
+        } else {
+            tracedb.inner().log_application(&spectrum, None, &who);
         }
     }
     Json(result)
@@ -159,7 +279,10 @@ mod apply_tests {
     use std::sync::mpsc;
 
     fn setup() -> Rocket<Build> {
-        rest_common::setup().mount("/", routes![apply_gate, apply_list, ungate_spectrum])
+        rest_common::setup().mount(
+            "/",
+            routes![apply_gate, apply_list, apply_history, ungate_spectrum],
+        )
     }
     fn teardown(
         c: mpsc::Sender<messaging::Request>,
@@ -550,6 +673,75 @@ mod apply_tests {
         assert_eq!(1, listing.len());
         assert!(listing[0].gate.is_none());
 
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn history_1() {
+        // Applications and removals show up in the history along with
+        // who made them; failures do not:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+
+        let param_api = parameter_messages::ParameterMessageClient::new(&chan);
+        let cnd_api = condition_messages::ConditionMessageClient::new(&chan);
+        let spec_api = spectrum_messages::SpectrumMessageClient::new(&chan);
+        param_api
+            .create_parameter("test")
+            .expect("Making parameter");
+        assert!(matches!(
+            cnd_api.create_true_condition("True"),
+            condition_messages::ConditionReply::Created
+        ));
+        spec_api
+            .create_spectrum_1d("test_spec", "test", 0.0, 1024.0, 1024)
+            .expect("making spectrum");
+
+        let c = Client::tracked(rocket).unwrap();
+        let remote: std::net::SocketAddr = "10.0.0.1:5000".parse().unwrap();
+        let reply = c
+            .get("/apply?gate=True&spectrum=test_spec&spectrum=nosuch")
+            .remote(remote)
+            .dispatch()
+            .into_json::<GateApplicationResponse>()
+            .expect("Bad JSON");
+        assert_eq!(1, reply.detail.len()); // nosuch failed.
+        let reply = c
+            .get("/?name=test_spec")
+            .dispatch()
+            .into_json::<GateApplicationResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status);
+
+        let reply = c
+            .get("/history")
+            .dispatch()
+            .into_json::<ApplicationHistoryListing>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail.len());
+        assert_eq!("test_spec", reply.detail[0].spectrum);
+
+        let history = &reply.detail[0].history;
+        assert_eq!(2, history.len());
+        assert_eq!("apply", history[0].operation);
+        assert_eq!(Some(String::from("True")), history[0].gate);
+        assert_eq!("10.0.0.1", history[0].who);
+        assert!(history[0].run.is_none());
+        assert_eq!("remove", history[1].operation);
+        assert!(history[1].gate.is_none());
+        assert_eq!("unknown", history[1].who);
+
+        // Patterns select the spectra:
+
+        let reply = c
+            .get("/history?pattern=other*")
+            .dispatch()
+            .into_json::<ApplicationHistoryListing>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status);
+        assert!(reply.detail.is_empty());
+
         teardown(chan, &papi, &bapi);
     }
 }
@@ -0,0 +1,249 @@
+//! This module provides the /spectcl/autosave domain.  This is a
+//! Rustogramer only domain that starts and stops periodic saving of
+//! spectra to disk (see src/autosave/mod.rs) to protect long runs
+//! against crashes.
+//!
+//! We depend on the main to have set the Rocket state to include an
+//! autosave::AutoSaver.
+//!
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+use std::time;
+
+use super::*;
+use crate::autosave;
+
+/// Start auto-saving.  If an auto-save is running, it is replaced.
+///
+/// #### Query Parameters:
+/// * spectrum - Glob patterns of the spectra to save, can be supplied
+///   more than once.  Defaults to "*", all spectra.
+/// * file - File name pattern.  {seq} is replaced by the save number.
+/// * format - "json" (default), "ascii" or "root".
+/// * interval - Seconds between saves.
+/// * keep - Number of files to retain.  Defaults to 0 which keeps all
+///   files.
+///
+#[get("/start?<spectrum>&<file>&<format>&<interval>&<keep>")]
+pub fn start_autosave(
+    spectrum: OptionalStringVec,
+    file: String,
+    format: OptionalString,
+    interval: f64,
+    keep: Option<usize>,
+    state: &State<SharedHistogramChannel>,
+    saver: &State<autosave::AutoSaver>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    let spectra = match spectrum {
+        Some(v) if !v.is_empty() => v,
+        _ => vec![String::from("*")],
+    };
+    let format = format.unwrap_or_else(|| String::from("json"));
+    Json(
+        match autosave::AutoSavePolicy::new(&spectra, &file, &format, interval, keep.unwrap_or(0)) {
+            Ok(policy) => {
+                saver.inner().start(&state.inner().lock().unwrap(), policy);
+                GenericResponse::ok("")
+            }
+            Err(msg) => GenericResponse::err("Unable to start auto-save", &msg),
+        },
+    )
+}
+/// Stop auto-saving.  It is not an error to stop when no auto-save
+/// is running.
+#[get("/stop")]
+pub fn stop_autosave(
+    saver: &State<autosave::AutoSaver>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    saver.inner().stop();
+    Json(GenericResponse::ok(""))
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AutoSavePolicy {
+    spectra: Vec<String>,
+    file: String,
+    format: String,
+    interval: f64,
+    keep: usize,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AutoSaveStatus {
+    running: bool,
+    policy: Option<AutoSavePolicy>,
+    saves: u64,
+    last_save: Option<u64>,
+    files: Vec<String>,
+    error: Option<String>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AutoSaveStatusResponse {
+    status: String,
+    detail: AutoSaveStatus,
+}
+/// Report the status of the current or most recent auto-save.
+/// last_save is the time of the last successful save in seconds
+/// since the epoch.
+#[get("/status")]
+pub fn autosave_status(saver: &State<autosave::AutoSaver>) -> Json<AutoSaveStatusResponse> {
+    let status = saver.inner().status();
+    Json(AutoSaveStatusResponse {
+        status: String::from("OK"),
+        detail: AutoSaveStatus {
+            running: status.running,
+            policy: status.policy.map(|p| AutoSavePolicy {
+                spectra: p.spectra(),
+                file: p.file_pattern(),
+                format: p.format(),
+                interval: p.interval().as_secs_f64(),
+                keep: p.keep(),
+            }),
+            saves: status.saves,
+            last_save: status.last_save.map(|t| {
+                t.duration_since(time::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0)
+            }),
+            files: status.files,
+            error: status.error,
+        },
+    })
+}
+
+#[cfg(test)]
+mod autosave_tests {
+    use super::*;
+    use crate::messaging;
+    use crate::messaging::spectrum_messages;
+    use crate::test::rest_common;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+    use std::path::Path;
+    use std::thread;
+
+    fn setup() -> Rocket<Build> {
+        rest_common::setup().mount("/", routes![start_autosave, stop_autosave, autosave_status])
+    }
+    fn getstate(
+        r: &Rocket<Build>,
+    ) -> (
+        mpsc::Sender<messaging::Request>,
+        processing::ProcessingApi,
+        binder::BindingApi,
+    ) {
+        rest_common::get_state(r)
+    }
+    fn teardown(
+        c: mpsc::Sender<messaging::Request>,
+        p: &processing::ProcessingApi,
+        b: &binder::BindingApi,
+    ) {
+        rest_common::teardown(c, p, b);
+    }
+    fn get_status(client: &Client) -> AutoSaveStatusResponse {
+        client
+            .get("/status")
+            .dispatch()
+            .into_json::<AutoSaveStatusResponse>()
+            .expect("Parsing JSON")
+    }
+
+    #[test]
+    fn start_1() {
+        // Errors in the request:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Making client");
+        for uri in [
+            "/start?file=save.json&interval=10",
+            "/start?file=save-%7Bseq%7D.npy&format=npy&interval=10",
+            "/start?file=save-%7Bseq%7D.json&interval=0",
+        ] {
+            let reply = client
+                .get(uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!("Unable to start auto-save", reply.status, "{}", uri);
+        }
+        let status = get_status(&client);
+        assert!(!status.detail.running);
+        assert!(status.detail.policy.is_none());
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn start_2() {
+        // Saves are made until stopped:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+        ParameterMessageClient::new(&chan)
+            .create_parameter("p1")
+            .expect("Making p1");
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&chan);
+        sapi.create_spectrum_1d("s1", "p1", 0.0, 10.0, 10)
+            .expect("Making s1");
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let pattern = dir.path().join("save-{seq}.json");
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let uri = format!(
+            "/start?file={}&interval=0.05&keep=2",
+            pattern
+                .to_str()
+                .unwrap()
+                .replace('{', "%7B")
+                .replace('}', "%7D")
+        );
+        let reply = client
+            .get(uri)
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        let start = time::Instant::now();
+        while get_status(&client).detail.saves < 3 {
+            assert!(
+                start.elapsed() < time::Duration::from_secs(10),
+                "Saves too slow"
+            );
+            thread::sleep(time::Duration::from_millis(10));
+        }
+        let reply = client
+            .get("/stop")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        let status = get_status(&client);
+        assert_eq!("OK", status.status);
+        assert!(!status.detail.running);
+        let policy = status.detail.policy.expect("No policy");
+        assert_eq!(vec![String::from("*")], policy.spectra);
+        assert_eq!(pattern.to_str().unwrap(), policy.file);
+        assert_eq!("json", policy.format);
+        assert_eq!(0.05, policy.interval);
+        assert_eq!(2, policy.keep);
+        assert!(status.detail.last_save.is_some());
+        assert!(status.detail.error.is_none());
+        assert_eq!(2, status.detail.files.len());
+        for file in status.detail.files.iter() {
+            assert!(Path::new(file).exists(), "{}", file);
+        }
+
+        teardown(chan, &papi, &bapi);
+    }
+}
@@ -0,0 +1,287 @@
+//!  Protects the REST server from a saturated histogramer.
+//!  Requests to the histogram thread are queued behind any event
+//!  data it's been sent.  When the histogramer can't keep up, a handler
+//!  that talks to it would block its Rocket worker thread until the
+//!  backlog clears and, if enough of those pile up, the REST server
+//!  stops responding to anything.
+//!
+//!  Handlers that talk to the histogramer therefore take a
+//!  HistogramerReady request guard.  The guard pings the histogramer and,
+//!  if the reply does not come back within the configured timeout,
+//!  fails the request with a 503 (Service Unavailable).  The
+//!  histogramer_busy catcher turns that into the usual JSON response
+//!  with a Retry-After header.
+//!
+//!  The timeout is set from the command line and stored in the Rocket
+//!  state as a HistogramerTimeout struct which also counts the requests
+//!  that timed out.  The single URI supported here, /spectcl/busy,
+//!  reports the timeout and that count.
+//!
+use rocket::http::{Header, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time;
+
+use super::*;
+
+pub const DEFAULT_HISTOGRAMER_TIMEOUT_SECS: f64 = 10.0;
+
+/// The timeout and the count of requests that exceeded it.
+///
+pub struct HistogramerTimeout {
+    timeout: Option<time::Duration>,
+    timed_out: AtomicU64,
+}
+
+impl HistogramerTimeout {
+    /// Create the state.
+    ///
+    /// ### Parameters
+    /// * timeout - How long to wait for the histogramer. None disables
+    /// the check (requests wait as long as it takes).
+    pub fn new(timeout: Option<time::Duration>) -> HistogramerTimeout {
+        HistogramerTimeout {
+            timeout,
+            timed_out: AtomicU64::new(0),
+        }
+    }
+    /// Create the state from the command line value.
+    ///
+    /// ### Parameters
+    /// * seconds - timeout in seconds, zero or less disables the check.
+    pub fn from_secs(seconds: f64) -> HistogramerTimeout {
+        Self::new(if seconds > 0.0 {
+            Some(time::Duration::from_secs_f64(seconds))
+        } else {
+            None
+        })
+    }
+    pub fn timeout(&self) -> Option<time::Duration> {
+        self.timeout
+    }
+    /// Number of requests that have timed out.
+    pub fn timed_out(&self) -> u64 {
+        self.timed_out.load(Ordering::Relaxed)
+    }
+    /// Count a timed out request.
+    pub fn add_timeout(&self) {
+        self.timed_out.fetch_add(1, Ordering::Relaxed);
+    }
+    /// Value of the Retry-After header we send with busy responses.
+    /// This is the timeout rounded up to the next second.
+    pub fn retry_after(&self) -> u64 {
+        match self.timeout {
+            Some(t) => (t.as_secs_f64().ceil() as u64).max(1),
+            None => 1,
+        }
+    }
+}
+
+/// Request guard for handlers that talk to the histogramer.
+/// Succeeds if the histogramer answered a ping within the timeout.
+///
+pub struct HistogramerReady;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for HistogramerReady {
+    type Error = String;
+
+    async fn from_request(req: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        let timeouts = match req.rocket().state::<HistogramerTimeout>() {
+            Some(t) => t,
+            None => return Outcome::Success(HistogramerReady),
+        };
+        let timeout = match timeouts.timeout() {
+            Some(t) => t,
+            None => return Outcome::Success(HistogramerReady),
+        };
+        let chan = match req.rocket().state::<SharedHistogramChannel>() {
+            Some(c) => c.lock().unwrap().clone(),
+            None => return Outcome::Success(HistogramerReady),
+        };
+        // The ping blocks so keep it off the async worker threads:
+
+        let answered = rocket::tokio::task::spawn_blocking(move || Request::ping(chan, timeout))
+            .await
+            .unwrap_or(false);
+        if answered {
+            Outcome::Success(HistogramerReady)
+        } else {
+            timeouts.add_timeout();
+            Outcome::Failure((
+                Status::ServiceUnavailable,
+                String::from("Histogramer did not respond"),
+            ))
+        }
+    }
+}
+
+/// The response sent when the histogramer is busy.
+///
+#[derive(Responder)]
+#[response(status = 503)]
+pub struct BusyResponse {
+    body: Json<GenericResponse>,
+    retry_after: Header<'static>,
+}
+
+/// Catcher for 503 - the HistogramerReady guard failed.
+/// The status is "Histogramer busy" and the detail, the URI of
+/// the request that failed.
+#[catch(503)]
+pub fn histogramer_busy(req: &rocket::Request) -> BusyResponse {
+    let retry = req
+        .rocket()
+        .state::<HistogramerTimeout>()
+        .map(|t| t.retry_after())
+        .unwrap_or(1);
+    BusyResponse {
+        body: Json(GenericResponse::err(
+            "Histogramer busy",
+            &req.uri().to_string(),
+        )),
+        retry_after: Header::new("Retry-After", retry.to_string()),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct BusyStatistics {
+    timeout: f64,
+    timed_out: u64,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct BusyStatisticsResponse {
+    status: String,
+    detail: BusyStatistics,
+}
+
+/// Report the histogramer timeout (seconds, 0 if disabled) and the
+/// number of requests that have timed out.
+#[get("/")]
+pub fn busy_statistics(timeouts: &State<HistogramerTimeout>) -> Json<BusyStatisticsResponse> {
+    Json(BusyStatisticsResponse {
+        status: String::from("OK"),
+        detail: BusyStatistics {
+            timeout: timeouts
+                .inner()
+                .timeout()
+                .map(|t| t.as_secs_f64())
+                .unwrap_or(0.0),
+            timed_out: timeouts.inner().timed_out(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod busy_tests {
+    use super::*;
+    use crate::messaging;
+    use crate::messaging::parameter_messages::ParameterMessageClient;
+    use crate::test::rest_common;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+
+    // A handler that needs the histogramer:
+
+    #[get("/guarded")]
+    fn guarded(_ready: HistogramerReady) -> Json<GenericResponse> {
+        Json(GenericResponse::ok(""))
+    }
+
+    // The receiver is returned so the channel stays open but
+    // nobody ever reads it -- a histogramer that never responds.
+
+    fn setup_stuck(
+        timeout: Option<time::Duration>,
+    ) -> (Rocket<Build>, mpsc::Receiver<messaging::Request>) {
+        let (send, recv) = mpsc::channel();
+        (
+            rocket::build()
+                .manage(Mutex::new(send))
+                .manage(HistogramerTimeout::new(timeout))
+                .mount("/", routes![guarded])
+                .mount("/spectcl/busy", routes![busy_statistics])
+                .register("/", catchers![histogramer_busy]),
+            recv,
+        )
+    }
+
+    #[test]
+    fn stuck_1() {
+        // A histogramer that does not respond gives a 503 with
+        // a retry-after and is counted:
+
+        let (rocket, _recv) = setup_stuck(Some(time::Duration::from_millis(50)));
+        let client = Client::untracked(rocket).expect("Making client");
+
+        let response = client.get("/guarded").dispatch();
+        assert_eq!(Status::ServiceUnavailable, response.status());
+        assert_eq!(Some("1"), response.headers().get_one("Retry-After"));
+        let reply = response
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("Histogramer busy", reply.status);
+        assert_eq!("/guarded", reply.detail);
+
+        client.get("/guarded").dispatch();
+        let reply = client
+            .get("/spectcl/busy")
+            .dispatch()
+            .into_json::<BusyStatisticsResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(0.05, reply.detail.timeout);
+        assert_eq!(2, reply.detail.timed_out);
+    }
+    #[test]
+    fn stuck_2() {
+        // With the timeout disabled, the guard does not check:
+
+        let (rocket, _recv) = setup_stuck(None);
+        let client = Client::untracked(rocket).expect("Making client");
+
+        let response = client.get("/guarded").dispatch();
+        assert_eq!(Status::Ok, response.status());
+
+        let reply = client
+            .get("/spectcl/busy")
+            .dispatch()
+            .into_json::<BusyStatisticsResponse>()
+            .expect("Parsing JSON");
+        assert_eq!(0.0, reply.detail.timeout);
+        assert_eq!(0, reply.detail.timed_out);
+    }
+    #[test]
+    fn ready_1() {
+        // A running histogramer passes the guard and its handler
+        // works normally:
+
+        let rocket = rest_common::setup()
+            .manage(HistogramerTimeout::new(Some(time::Duration::from_secs(5))))
+            .mount("/", routes![guarded])
+            .register("/", catchers![histogramer_busy]);
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        let client = Client::untracked(rocket).expect("Making client");
+
+        let response = client.get("/guarded").dispatch();
+        assert_eq!(Status::Ok, response.status());
+        let api = ParameterMessageClient::new(&chan);
+        assert!(api.list_parameters("*").is_ok());
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn from_secs_1() {
+        assert!(HistogramerTimeout::from_secs(0.0).timeout().is_none());
+        let t = HistogramerTimeout::from_secs(2.5);
+        assert_eq!(Some(time::Duration::from_millis(2500)), t.timeout());
+        assert_eq!(3, t.retry_after());
+    }
+}
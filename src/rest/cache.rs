@@ -0,0 +1,371 @@
+//!  Sheds load from polling clients.  During a replay several GUIs
+//!  often poll the same expensive read requests (e.g. /spectcl/specstats
+//!  for all spectra or /spectcl/apply/list).  Each poll is a round trip
+//!  to the histogramer which is already busy with the data, so each
+//!  additional GUI multiplies that load.
+//!
+//!  Handlers for those requests take a Cache request guard and compute
+//!  their response through it.  A response computed less than the time
+//!  to live (TTL, normally well under a second) ago is reused rather
+//!  than asking the histogramer again.  Requests that modify the
+//!  server (see readonly::Writable and readonly::WriteAccess) empty the
+//!  cache so that a client sees its own changes.  The ClearOnWrite
+//!  fairing does this after the handler has responded; emptying the
+//!  cache before the handler runs would let a concurrent poll cache the
+//!  state from before the change.
+//!
+//!  The cache is stored in the Rocket state as a ResponseCache which
+//!  also counts hits and misses.  The single URI supported here,
+//!  /spectcl/cache, reports the TTL and those counts.  If no
+//!  ResponseCache is managed, responses are always computed.
+//!
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::request::{FromRequest, Outcome};
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+use rocket::{Request, Response};
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time;
+
+pub const DEFAULT_CACHE_TTL_SECS: f64 = 0.5;
+
+// A cached response and when it was computed:
+
+struct CacheEntry {
+    computed: time::Instant,
+    response: Box<dyn Any + Send>,
+}
+
+/// The cached responses, their time to live and the hit/miss counts.
+///
+pub struct ResponseCache {
+    ttl: Option<time::Duration>,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResponseCache {
+    /// Create the cache.
+    ///
+    /// ### Parameters
+    /// * ttl - How long a response is reused.  None disables caching.
+    pub fn new(ttl: Option<time::Duration>) -> ResponseCache {
+        ResponseCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+    /// Create the cache from the command line value.
+    ///
+    /// ### Parameters
+    /// * seconds - time to live in seconds, zero or less disables caching.
+    pub fn from_secs(seconds: f64) -> ResponseCache {
+        Self::new(if seconds > 0.0 {
+            Some(time::Duration::from_secs_f64(seconds))
+        } else {
+            None
+        })
+    }
+    pub fn ttl(&self) -> Option<time::Duration> {
+        self.ttl
+    }
+    /// Number of responses that were reused.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+    /// Number of responses that had to be computed.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+    /// Number of responses in the cache, including expired ones.
+    pub fn entries(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+    /// Forget all cached responses.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+    /// Get a response.  If the response for the key was computed within
+    /// the TTL it is reused, otherwise compute makes it and it is saved.
+    ///
+    /// The cache is locked while the response is computed.  Clients
+    /// polling together then wait for the first of them to get the
+    /// response rather than all asking the histogramer for it.
+    ///
+    /// ### Parameters
+    /// * key - identifies the request, e.g. its URI.
+    /// * compute - computes the response.
+    ///
+    pub fn get_or_compute<T, F>(&self, key: &str, compute: F) -> T
+    where
+        T: Clone + Send + 'static,
+        F: FnOnce() -> T,
+    {
+        let ttl = match self.ttl {
+            Some(t) => t,
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                return compute();
+            }
+        };
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.get(key) {
+            if entry.computed.elapsed() < ttl {
+                if let Some(response) = entry.response.downcast_ref::<T>() {
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                    return response.clone();
+                }
+            }
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let response = compute();
+        entries.insert(
+            String::from(key),
+            CacheEntry {
+                computed: time::Instant::now(),
+                response: Box::new(response.clone()),
+            },
+        );
+        response
+    }
+}
+
+/// Request guard for handlers whose responses can be cached.
+/// This never fails.
+///
+pub struct Cache<'r>(Option<&'r ResponseCache>);
+
+impl<'r> Cache<'r> {
+    /// See ResponseCache::get_or_compute.  Without a ResponseCache
+    /// the response is always computed.
+    pub fn get_or_compute<T, F>(&self, key: &str, compute: F) -> T
+    where
+        T: Clone + Send + 'static,
+        F: FnOnce() -> T,
+    {
+        match self.0 {
+            Some(cache) => cache.get_or_compute(key, compute),
+            None => compute(),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Cache<'r> {
+    type Error = String;
+
+    async fn from_request(req: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Cache(req.rocket().state::<ResponseCache>()))
+    }
+}
+
+/// Request local state that is set if the request modifies the
+/// server; set by the readonly::Writable guard and by
+/// readonly::WriteAccess checks that allow the modification.
+#[derive(Default)]
+pub struct Modifies(AtomicBool);
+
+impl Modifies {
+    pub fn set(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+    pub fn is_set(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The fairing that empties the cache once a request that modifies
+/// the server has been handled.
+pub struct ClearOnWrite;
+
+#[rocket::async_trait]
+impl Fairing for ClearOnWrite {
+    fn info(&self) -> Info {
+        Info {
+            name: "Response cache invalidation",
+            kind: Kind::Response,
+        }
+    }
+    async fn on_response<'r>(&self, req: &'r Request<'_>, _res: &mut Response<'r>) {
+        if req.local_cache(Modifies::default).is_set() {
+            if let Some(cache) = req.rocket().state::<ResponseCache>() {
+                cache.clear();
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CacheStatus {
+    ttl: Option<f64>,
+    hits: u64,
+    misses: u64,
+    entries: usize,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CacheStatusResponse {
+    status: String,
+    detail: CacheStatus,
+}
+
+/// Report the cache time to live in seconds (null if caching is
+/// disabled), the number of responses that were reused (hits) and
+/// computed (misses) and the number of cached responses.
+#[get("/")]
+pub fn cache_status(cache: &State<ResponseCache>) -> Json<CacheStatusResponse> {
+    let cache = cache.inner();
+    Json(CacheStatusResponse {
+        status: String::from("OK"),
+        detail: CacheStatus {
+            ttl: cache.ttl().map(|t| t.as_secs_f64()),
+            hits: cache.hits(),
+            misses: cache.misses(),
+            entries: cache.entries(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod cache_tests {
+    use super::*;
+    use crate::rest::{apply, gainmatch, getstats, rest_parameter, GenericResponse};
+    use crate::test::rest_common;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+    use std::thread;
+
+    fn setup(ttl: f64) -> Rocket<Build> {
+        rest_common::setup()
+            .manage(ResponseCache::from_secs(ttl))
+            .mount("/spectcl/specstats", routes![getstats::get_statistics])
+            .mount("/spectcl/apply", routes![apply::apply_list])
+            .mount(
+                "/spectcl/parameter",
+                routes![rest_parameter::create_parameter],
+            )
+            .mount("/spectcl/gainmatch", routes![gainmatch::fit_gains])
+            .mount("/spectcl/cache", routes![cache_status])
+            .attach(ClearOnWrite)
+    }
+    fn status(client: &Client) -> CacheStatus {
+        client
+            .get("/spectcl/cache")
+            .dispatch()
+            .into_json::<CacheStatusResponse>()
+            .expect("Parsing JSON")
+            .detail
+    }
+
+    #[test]
+    fn get_1() {
+        // Responses are reused within the TTL and recomputed after:
+
+        let cache = ResponseCache::from_secs(0.1);
+        assert_eq!(1, cache.get_or_compute("a", || 1));
+        assert_eq!(1, cache.get_or_compute("a", || 2));
+        assert_eq!(3, cache.get_or_compute("b", || 3));
+        assert_eq!(1, cache.hits());
+        assert_eq!(2, cache.misses());
+        assert_eq!(2, cache.entries());
+
+        thread::sleep(time::Duration::from_millis(150));
+        assert_eq!(4, cache.get_or_compute("a", || 4));
+        assert_eq!(3, cache.misses());
+
+        cache.clear();
+        assert_eq!(0, cache.entries());
+        assert_eq!(5, cache.get_or_compute("a", || 5));
+    }
+    #[test]
+    fn get_2() {
+        // Caching can be disabled:
+
+        let cache = ResponseCache::from_secs(0.0);
+        assert!(cache.ttl().is_none());
+        assert_eq!(1, cache.get_or_compute("a", || 1));
+        assert_eq!(2, cache.get_or_compute("a", || 2));
+        assert_eq!(0, cache.hits());
+        assert_eq!(2, cache.misses());
+        assert_eq!(0, cache.entries());
+    }
+    #[test]
+    fn rest_1() {
+        // Polling specstats and apply/list hits the cache:
+
+        let rocket = setup(60.0);
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        let client = Client::untracked(rocket).expect("Making client");
+
+        for uri in [
+            "/spectcl/specstats",
+            "/spectcl/specstats",
+            "/spectcl/apply/list",
+            "/spectcl/apply/list",
+            "/spectcl/specstats",
+        ] {
+            client.get(uri).dispatch();
+        }
+        let s = status(&client);
+        assert_eq!(Some(60.0), s.ttl);
+        assert_eq!(3, s.hits);
+        assert_eq!(2, s.misses);
+        assert_eq!(2, s.entries);
+
+        // Different patterns are different responses:
+
+        client.get("/spectcl/specstats?pattern=a*").dispatch();
+        assert_eq!(3, status(&client).misses);
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn rest_2() {
+        // Modifications empty the cache so clients see their changes:
+
+        let rocket = setup(60.0);
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        let client = Client::untracked(rocket).expect("Making client");
+
+        client.get("/spectcl/apply/list").dispatch();
+        assert_eq!(1, status(&client).entries);
+
+        let reply = client
+            .get("/spectcl/parameter/create?name=p1")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(0, status(&client).entries);
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn rest_3() {
+        // Handlers that check for write access themselves empty the
+        // cache only when they're allowed to modify the state:
+
+        let rocket = setup(60.0);
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        let client = Client::untracked(rocket).expect("Making client");
+
+        client.get("/spectcl/apply/list").dispatch();
+        client.get("/spectcl/gainmatch/fit").dispatch();
+        assert_eq!(1, status(&client).entries);
+
+        client.get("/spectcl/gainmatch/fit?apply=true").dispatch();
+        assert_eq!(0, status(&client).entries);
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+}
@@ -0,0 +1,315 @@
+//! This module provides the /spectcl/calibration REST interface to
+//! calibrated parameters.  This is specific to Rustogramer.  A
+//! calibrated parameter is computed, for each event, from a raw
+//! parameter as:
+//!
+//!    offset + slope*raw + quadratic*raw*raw
+//!
+//! Where the quadratic term is optional (see src/parameters/calibration.rs).
+//! The raw parameter is unchanged; compare this with the gain and offset
+//! calibrations of /spectcl/gainmatch which replace the raw values.
+//!
+//! *   ../set - Define or redefine a calibrated parameter.
+//! *   ../list - List the calibrated parameters.
+//! *   ../delete - Stop computing a calibrated parameter.
+//!
+//! The calibration of a calibrated parameter is also reported by
+//! /spectcl/parameter/list.
+//!
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+
+use super::*;
+
+use crate::messaging::parameter_messages::ParameterMessageClient;
+use crate::parameters::calibration::Calibration;
+
+/// Define a calibrated parameter.
+///
+/// #### Query Parameters:
+/// * name - name of the calibrated parameter.  It is created if it does
+///   not exist.  If it already is a calibrated parameter, its
+///   calibration is replaced.
+/// * raw - name of the existing parameter that is calibrated.
+/// * slope - the linear term of the calibration.
+/// * offset - the constant term of the calibration.
+/// * quadratic - optional quadratic term of the calibration; defaults to 0.
+/// * units - optional units of the calibrated parameter.
+///
+/// The response is a GenericResponse.
+#[get("/set?<name>&<raw>&<slope>&<offset>&<quadratic>&<units>")]
+#[allow(clippy::too_many_arguments)]
+pub fn set_calibration(
+    name: String,
+    raw: String,
+    slope: f64,
+    offset: f64,
+    quadratic: Option<f64>,
+    units: OptionalString,
+    state: &State<SharedHistogramChannel>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    let api = ParameterMessageClient::new(&state.inner().lock().unwrap());
+    let calibration = Calibration::new(offset, slope, quadratic.unwrap_or(0.0));
+    match api.set_calibrated_parameter(&name, &raw, calibration, units) {
+        Ok(()) => Json(GenericResponse::ok("")),
+        Err(s) => Json(GenericResponse::err("Failed to set calibration", &s)),
+    }
+}
+// Description of a calibrated parameter:
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CalibrationDescription {
+    name: String,
+    raw: String,
+    slope: f64,
+    offset: f64,
+    quadratic: f64,
+    units: Option<String>,
+}
+// Response to /calibration/list:
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CalibrationListResponse {
+    status: String,
+    detail: Vec<CalibrationDescription>,
+}
+/// List the calibrated parameters whose names match the optional
+/// pattern (defaults to "*").  They are listed in the order in which
+/// they are computed.
+#[get("/list?<pattern>")]
+pub fn list_calibrations(
+    pattern: OptionalString,
+    state: &State<SharedHistogramChannel>,
+    _ready: busy::HistogramerReady,
+) -> Json<CalibrationListResponse> {
+    let pattern = pattern.unwrap_or_else(|| String::from("*"));
+    let api = ParameterMessageClient::new(&state.inner().lock().unwrap());
+    match api.list_calibrated_parameters(&pattern) {
+        Ok(l) => Json(CalibrationListResponse {
+            status: String::from("OK"),
+            detail: l
+                .into_iter()
+                .map(|c| CalibrationDescription {
+                    name: c.name,
+                    raw: c.raw,
+                    slope: c.calibration.slope,
+                    offset: c.calibration.offset,
+                    quadratic: c.calibration.quadratic,
+                    units: c.units,
+                })
+                .collect(),
+        }),
+        Err(s) => Json(CalibrationListResponse {
+            status: format!("Failed to list calibrations: {}", s),
+            detail: vec![],
+        }),
+    }
+}
+/// Delete a calibrated parameter.  The parameter still exists but is
+/// no longer computed.
+#[get("/delete?<name>")]
+pub fn delete_calibration(
+    name: String,
+    state: &State<SharedHistogramChannel>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    let api = ParameterMessageClient::new(&state.inner().lock().unwrap());
+    match api.delete_calibrated_parameter(&name) {
+        Ok(()) => Json(GenericResponse::ok("")),
+        Err(s) => Json(GenericResponse::err("Failed to delete calibration", &s)),
+    }
+}
+
+#[cfg(test)]
+mod calibration_tests {
+    use super::*;
+    use crate::messaging;
+    use crate::test::rest_common;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+
+    use std::sync::mpsc;
+
+    fn setup() -> Rocket<Build> {
+        rest_common::setup().mount(
+            "/",
+            routes![set_calibration, list_calibrations, delete_calibration],
+        )
+    }
+    fn getstate(
+        r: &Rocket<Build>,
+    ) -> (
+        mpsc::Sender<messaging::Request>,
+        processing::ProcessingApi,
+        binder::BindingApi,
+    ) {
+        rest_common::get_state(r)
+    }
+    fn teardown(
+        c: mpsc::Sender<messaging::Request>,
+        p: &processing::ProcessingApi,
+        b: &binder::BindingApi,
+    ) {
+        rest_common::teardown(c, p, b);
+    }
+    fn make_params(chan: &mpsc::Sender<messaging::Request>) {
+        let api = ParameterMessageClient::new(chan);
+        for name in ["a", "b"] {
+            api.create_parameter(name).expect("Making parameter");
+        }
+    }
+    #[test]
+    fn set_1() {
+        // Success - the calibrated parameter exists with its units:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+        make_params(&chan);
+
+        let client = Client::untracked(rocket).expect("creating client");
+        let reply = client
+            .get("/set?name=energy&raw=a&slope=0.5&offset=10&quadratic=0.001&units=keV")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        let api = ParameterMessageClient::new(&chan);
+        let cals = api.list_calibrated_parameters("*").expect("Listing");
+        assert_eq!(1, cals.len());
+        assert_eq!("energy", cals[0].name);
+        assert_eq!("a", cals[0].raw);
+        assert_eq!(Calibration::new(10.0, 0.5, 0.001), cals[0].calibration);
+        assert_eq!(Some(String::from("keV")), cals[0].units);
+        let params = api.list_parameters("energy").expect("Listing");
+        assert_eq!(1, params.len());
+        assert_eq!(Some(String::from("keV")), params[0].get_units());
+
+        // Quadratic defaults to zero and a redefinition replaces:
+
+        let reply = client
+            .get("/set?name=energy&raw=b&slope=2&offset=-1")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        let cals = api.list_calibrated_parameters("*").expect("Listing");
+        assert_eq!(1, cals.len());
+        assert_eq!("b", cals[0].raw);
+        assert_eq!(Calibration::new(-1.0, 2.0, 0.0), cals[0].calibration);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn set_2() {
+        // Failures:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+        make_params(&chan);
+
+        let client = Client::untracked(rocket).expect("creating client");
+        for uri in [
+            "/set?name=energy&raw=c&slope=1&offset=0",
+            "/set?name=a&raw=a&slope=1&offset=0",
+            "/set?name=energy&raw=a&slope=inf&offset=0",
+        ] {
+            let reply = client
+                .get(uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!("Failed to set calibration", reply.status, "{}", uri);
+        }
+        let api = ParameterMessageClient::new(&chan);
+        assert!(api
+            .list_calibrated_parameters("*")
+            .expect("Listing")
+            .is_empty());
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn list_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+        make_params(&chan);
+        let api = ParameterMessageClient::new(&chan);
+        api.set_calibrated_parameter("ecal", "a", Calibration::new(1.0, 2.0, 0.0), None)
+            .expect("Calibrating");
+        api.set_calibrated_parameter(
+            "bcal",
+            "b",
+            Calibration::new(0.0, 1.0, 0.5),
+            Some(String::from("mm")),
+        )
+        .expect("Calibrating");
+
+        let client = Client::untracked(rocket).expect("creating client");
+        let reply = client
+            .get("/list")
+            .dispatch()
+            .into_json::<CalibrationListResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(2, reply.detail.len());
+        assert_eq!("ecal", reply.detail[0].name); // Definition order.
+        assert_eq!("bcal", reply.detail[1].name);
+
+        let reply = client
+            .get("/list?pattern=b*")
+            .dispatch()
+            .into_json::<CalibrationListResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail.len());
+        let c = &reply.detail[0];
+        assert_eq!("bcal", c.name);
+        assert_eq!("b", c.raw);
+        assert_eq!(1.0, c.slope);
+        assert_eq!(0.0, c.offset);
+        assert_eq!(0.5, c.quadratic);
+        assert_eq!(Some(String::from("mm")), c.units);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn delete_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+        make_params(&chan);
+        let api = ParameterMessageClient::new(&chan);
+        api.set_calibrated_parameter("ecal", "a", Calibration::new(1.0, 2.0, 0.0), None)
+            .expect("Calibrating");
+
+        let client = Client::untracked(rocket).expect("creating client");
+        let reply = client
+            .get("/delete?name=ecal")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert!(api
+            .list_calibrated_parameters("*")
+            .expect("Listing")
+            .is_empty());
+        assert_eq!(1, api.list_parameters("ecal").expect("Listing").len());
+
+        let reply = client
+            .get("/delete?name=ecal")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("Failed to delete calibration", reply.status);
+
+        teardown(chan, &papi, &bapi);
+    }
+}
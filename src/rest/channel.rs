@@ -7,8 +7,8 @@
 //!
 //!  set - sets a channel value.
 //!  get - gets a channel value.
-//!
-//!  Both of these just return a GenericResponse::err.
+//!  setroi - sets all channels in a rectangular region to a value
+//!  (Rustogramer only).
 //!
 
 use rocket::serde::json::Json;
@@ -34,6 +34,8 @@ pub fn set_chan(
     ychannel: Option<i32>,
     value: f64,
     api_chan: &State<SharedHistogramChannel>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
 ) -> Json<GenericResponse> {
     let api = spectrum_messages::SpectrumMessageClient::new(&api_chan.lock().unwrap());
 
@@ -43,6 +45,66 @@ pub fn set_chan(
     };
     Json(reply)
 }
+// Response to setroi; detail is the number of channels set:
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ChannelCountResponse {
+    status: String,
+    detail: usize,
+}
+
+/// Set all channels in a rectangular region of interest to the same
+/// value in one request.  This is much faster than setting the channels
+/// one at a time with /set when restoring or synthesizing spectra.
+///
+/// * spectrum (mandatory) - name of the spectrum.
+/// * xlow, xhigh (mandatory) - first and last x channels of the region.
+/// * ylow, yhigh (optional) - first and last y channels of the region.
+/// Required for 2d spectra.  Both or neither must be given.
+/// * value (mandatory) - value to set the channels to.
+///
+/// Channels are numbered as for /set and the ranges are inclusive.  If
+/// any of the region is out of range nothing is set.  On success the
+/// detail is the number of channels that were set.
+///
+#[get("/setroi?<spectrum>&<xlow>&<xhigh>&<ylow>&<yhigh>&<value>")]
+#[allow(clippy::too_many_arguments)]
+pub fn set_roi(
+    spectrum: &str,
+    xlow: i32,
+    xhigh: i32,
+    ylow: Option<i32>,
+    yhigh: Option<i32>,
+    value: f64,
+    api_chan: &State<SharedHistogramChannel>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<ChannelCountResponse> {
+    let ychans = match (ylow, yhigh) {
+        (Some(low), Some(high)) => Some((low, high)),
+        (None, None) => None,
+        _ => {
+            return Json(ChannelCountResponse {
+                status: String::from("Unable to set channels: both ylow and yhigh are needed"),
+                detail: 0,
+            })
+        }
+    };
+    let api = spectrum_messages::SpectrumMessageClient::new(&api_chan.lock().unwrap());
+
+    let reply = match api.set_roi(spectrum, (xlow, xhigh), ychans, value) {
+        Ok(n) => ChannelCountResponse {
+            status: String::from("OK"),
+            detail: n,
+        },
+        Err(s) => ChannelCountResponse {
+            status: format!("Unable to set channels: {}", s),
+            detail: 0,
+        },
+    };
+    Json(reply)
+}
 // Stuff needed for getchan:
 
 #[derive(Serialize, Deserialize)]
@@ -72,6 +134,7 @@ pub fn get_chan(
     xchannel: i32,
     ychannel: Option<i32>,
     api_chan: &State<SharedHistogramChannel>,
+    _ready: busy::HistogramerReady,
 ) -> Json<ChannelValueResponse> {
     let api = spectrum_messages::SpectrumMessageClient::new(&api_chan.lock().unwrap());
 
@@ -104,7 +167,7 @@ mod channels_tests {
     use rocket::Rocket;
 
     fn setup() -> Rocket<Build> {
-        rest_common::setup().mount("/", routes![set_chan, get_chan])
+        rest_common::setup().mount("/", routes![set_chan, get_chan, set_roi])
     }
     fn get_state(
         r: &Rocket<Build>,
@@ -249,6 +312,59 @@ mod channels_tests {
                 .expect("getting value")
         );
 
+        teardown(hg, &p, &b);
+    }
+    #[test]
+    fn setroi_1() {
+        // Set a region of a 2d spectrum:
+
+        let r = setup();
+        let (hg, p, b) = get_state(&r);
+
+        let param_api = parameter_messages::ParameterMessageClient::new(&hg);
+        param_api.create_parameter("p0").expect("Making p0");
+        param_api.create_parameter("p1").expect("Making p1");
+        let spec_api = spectrum_messages::SpectrumMessageClient::new(&hg);
+        spec_api
+            .create_spectrum_2d("test", "p0", "p1", 0.0, 512.0, 512, 0.0, 512.0, 512)
+            .expect("Making spectrum");
+
+        let client = Client::untracked(r).expect("Making client");
+        let reply = client
+            .get("/setroi?spectrum=test&xlow=10&xhigh=19&ylow=100&yhigh=104&value=3")
+            .dispatch()
+            .into_json::<ChannelCountResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(50, reply.detail);
+        assert_eq!(
+            3.0,
+            spec_api
+                .get_channel_value("test", 19, Some(104))
+                .expect("getting value")
+        );
+        assert_eq!(
+            0.0,
+            spec_api
+                .get_channel_value("test", 20, Some(104))
+                .expect("getting value")
+        );
+
+        // Only one y limit and out of range regions fail:
+
+        for uri in [
+            "/setroi?spectrum=test&xlow=10&xhigh=19&ylow=100&value=3",
+            "/setroi?spectrum=test&xlow=10&xhigh=600&ylow=100&yhigh=104&value=3",
+        ] {
+            let reply = client
+                .get(uri)
+                .dispatch()
+                .into_json::<ChannelCountResponse>()
+                .expect("Parsing JSON");
+            assert!(reply.status.starts_with("Unable to set channels"));
+            assert_eq!(0, reply.detail);
+        }
+
         teardown(hg, &p, &b);
     }
 }
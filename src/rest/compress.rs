@@ -0,0 +1,399 @@
+//!  Compresses REST responses.  Spectrum contents, spectrum lists
+//!  and files written by swrite and downloaded via /spectcl/fs can be
+//!  megabytes of very repetitive JSON/text that compresses well.
+//!
+//!  The Compression fairing encodes response bodies with gzip or
+//!  deflate (the zlib format, as HTTP specifies) when the request's
+//!  Accept-Encoding header allows it.  gzip is preferred if the client
+//!  accepts both equally.  Clients that don't send Accept-Encoding get
+//!  the uncompressed response as before.
+//!
+//!  Bodies are compressed as they are sent so that streamed responses
+//!  (e.g. spectrum contents) are still streamed; compressed bodies are
+//!  therefore sent with chunked transfer encoding.  Responses are not
+//!  compressed if:
+//!
+//!  *  The body is known to be smaller than the fairing's minimum
+//!     size; there's nothing to gain.
+//!  *  The body is a server sent event stream.  Compression would hold
+//!     back events until enough of them had arrived.
+//!  *  The response already has a Content-Encoding.
+//!
+//!  Since the encoding depends on Accept-Encoding, all responses say
+//!  so with a Vary header.
+//!
+use flate2::write::{GzEncoder, ZlibEncoder};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Header};
+use rocket::response::Body;
+use rocket::tokio::io::{AsyncRead, ReadBuf};
+use rocket::{Request, Response};
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// Responses smaller than this are not compressed by default.
+pub const DEFAULT_MIN_COMPRESS_BYTES: usize = 1024;
+
+// Size of the chunks of the uncompressed body we compress at a time:
+
+const CHUNK_BYTES: usize = 8192;
+
+/// The content codings we support.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+}
+
+// The quality value of a coding in an Accept-Encoding header, or
+// None if the header does not mention it or '*'.  Malformed
+// qualities are taken as 1.
+
+fn quality(accept: &str, coding: &str) -> Option<f64> {
+    let mut wildcard = None;
+    for item in accept.split(',') {
+        let mut parts = item.split(';');
+        let name = parts.next().unwrap_or("").trim();
+        let q = parts
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .map(|q| q.trim().parse::<f64>().unwrap_or(1.0))
+            .next()
+            .unwrap_or(1.0);
+        if name.eq_ignore_ascii_case(coding) {
+            return Some(q);
+        } else if name == "*" {
+            wildcard = Some(q);
+        }
+    }
+    wildcard
+}
+
+/// Choose the encoding for a response given the request's
+/// Accept-Encoding header.  None means send the body as is.
+pub fn choose_encoding(accept: &str) -> Option<Encoding> {
+    let gzip = quality(accept, "gzip").unwrap_or(0.0);
+    let deflate = quality(accept, "deflate").unwrap_or(0.0);
+    if gzip > 0.0 && gzip >= deflate {
+        Some(Encoding::Gzip)
+    } else if deflate > 0.0 {
+        Some(Encoding::Deflate)
+    } else {
+        None
+    }
+}
+
+// Compresses into a buffer that is emptied as it's sent:
+
+enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Deflate(ZlibEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+    fn new(encoding: Encoding) -> Encoder {
+        let level = flate2::Compression::default();
+        match encoding {
+            Encoding::Gzip => Encoder::Gzip(GzEncoder::new(Vec::new(), level)),
+            Encoding::Deflate => Encoder::Deflate(ZlibEncoder::new(Vec::new(), level)),
+        }
+    }
+    // Compress data returning the compressed bytes that are ready:
+
+    fn write(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Encoder::Gzip(e) => {
+                e.write_all(data)?;
+                Ok(std::mem::take(e.get_mut()))
+            }
+            Encoder::Deflate(e) => {
+                e.write_all(data)?;
+                Ok(std::mem::take(e.get_mut()))
+            }
+        }
+    }
+    // Return the rest of the compressed bytes:
+
+    fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            Encoder::Gzip(e) => e.finish(),
+            Encoder::Deflate(e) => e.finish(),
+        }
+    }
+}
+
+// A response body that is compressed as it's read.  The encoder is
+// None once the body has been compressed.
+
+struct CompressedBody<'r> {
+    body: Body<'r>,
+    encoder: Option<Encoder>,
+    output: Vec<u8>,
+    sent: usize,
+}
+
+impl AsyncRead for CompressedBody<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        loop {
+            if this.sent < this.output.len() {
+                let n = buf.remaining().min(this.output.len() - this.sent);
+                buf.put_slice(&this.output[this.sent..this.sent + n]);
+                this.sent += n;
+                return Poll::Ready(Ok(()));
+            }
+            if this.encoder.is_none() {
+                return Poll::Ready(Ok(())); // End of body.
+            }
+            let mut chunk = [0u8; CHUNK_BYTES];
+            let mut chunk_buf = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.body).poll_read(cx, &mut chunk_buf))?;
+            let data = chunk_buf.filled();
+            this.output = match this.encoder.take() {
+                Some(encoder) if data.is_empty() => encoder.finish()?,
+                Some(mut encoder) => {
+                    let output = encoder.write(data)?;
+                    this.encoder = Some(encoder);
+                    output
+                }
+                None => Vec::new(),
+            };
+            this.sent = 0;
+        }
+    }
+}
+
+/// The fairing that compresses responses of at least min_bytes.
+pub struct Compression {
+    min_bytes: usize,
+}
+
+impl Compression {
+    pub fn new(min_bytes: usize) -> Compression {
+        Compression { min_bytes }
+    }
+}
+
+impl Default for Compression {
+    fn default() -> Compression {
+        Compression::new(DEFAULT_MIN_COMPRESS_BYTES)
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for Compression {
+    fn info(&self) -> Info {
+        Info {
+            name: "Response compression",
+            kind: Kind::Response,
+        }
+    }
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        res.adjoin_header(Header::new("Vary", "Accept-Encoding"));
+        if res.headers().contains("Content-Encoding")
+            || res.content_type() == Some(ContentType::EventStream)
+        {
+            return;
+        }
+        if matches!(res.body().preset_size(), Some(size) if size < self.min_bytes) {
+            return;
+        }
+        let encoding = match req
+            .headers()
+            .get("Accept-Encoding")
+            .filter_map(choose_encoding)
+            .next()
+        {
+            Some(e) => e,
+            None => return,
+        };
+        let body = res.body_mut().take();
+        res.set_header(Header::new("Content-Encoding", encoding.as_str()));
+        res.set_streamed_body(CompressedBody {
+            body,
+            encoder: Some(Encoder::new(encoding)),
+            output: Vec::new(),
+            sent: 0,
+        });
+    }
+}
+
+#[cfg(test)]
+mod compress_tests {
+    use super::*;
+    use crate::messaging::{parameter_messages, spectrum_messages};
+    use crate::rest::spectrum;
+    use crate::test::rest_common;
+    use flate2::read::{GzDecoder, ZlibDecoder};
+    use rocket::local::blocking::Client;
+    use rocket::response::stream::{Event, EventStream};
+    use std::io::Read;
+
+    #[get("/big")]
+    fn big() -> String {
+        "spectrum contents ".repeat(200)
+    }
+    #[get("/events")]
+    fn events() -> EventStream![] {
+        EventStream! {
+            for i in 0..200 {
+                yield Event::data(format!("event {}", i));
+            }
+        }
+    }
+    #[get("/small")]
+    fn small() -> &'static str {
+        "OK"
+    }
+
+    fn client() -> Client {
+        let rocket = rocket::build()
+            .mount("/", routes![big, events, small])
+            .attach(Compression::default());
+        Client::untracked(rocket).expect("Making client")
+    }
+
+    #[test]
+    fn choose_1() {
+        assert_eq!(Some(Encoding::Gzip), choose_encoding("gzip, deflate, br"));
+        assert_eq!(Some(Encoding::Deflate), choose_encoding("deflate"));
+        assert_eq!(
+            Some(Encoding::Deflate),
+            choose_encoding("gzip;q=0.5, deflate")
+        );
+        assert_eq!(Some(Encoding::Gzip), choose_encoding("*"));
+        assert_eq!(Some(Encoding::Deflate), choose_encoding("gzip;q=0, *"));
+        assert_eq!(None, choose_encoding("identity"));
+        assert_eq!(None, choose_encoding("br"));
+        assert_eq!(None, choose_encoding("gzip;q=0"));
+        assert_eq!(None, choose_encoding("*;q=0"));
+        assert_eq!(None, choose_encoding(""));
+    }
+    #[test]
+    fn gzip_1() {
+        let client = client();
+        let reply = client
+            .get("/big")
+            .header(Header::new("Accept-Encoding", "gzip, deflate"))
+            .dispatch();
+        assert_eq!(Some("gzip"), reply.headers().get_one("Content-Encoding"));
+        assert_eq!(Some("Accept-Encoding"), reply.headers().get_one("Vary"));
+        let body = reply.into_bytes().expect("Getting body");
+        assert!(body.len() < 18 * 200);
+
+        let mut text = String::new();
+        GzDecoder::new(&body[..])
+            .read_to_string(&mut text)
+            .expect("Decompressing");
+        assert_eq!(big(), text);
+    }
+    #[test]
+    fn deflate_1() {
+        let client = client();
+        let reply = client
+            .get("/big")
+            .header(Header::new("Accept-Encoding", "deflate"))
+            .dispatch();
+        assert_eq!(Some("deflate"), reply.headers().get_one("Content-Encoding"));
+        let body = reply.into_bytes().expect("Getting body");
+        let mut text = String::new();
+        ZlibDecoder::new(&body[..])
+            .read_to_string(&mut text)
+            .expect("Decompressing");
+        assert_eq!(big(), text);
+    }
+    #[test]
+    fn identity_1() {
+        // Not requested or too small - not compressed:
+
+        let client = client();
+        let reply = client.get("/big").dispatch();
+        assert!(reply.headers().get_one("Content-Encoding").is_none());
+        assert_eq!(Some(ContentType::Plain), reply.content_type());
+        assert_eq!(Some(big()), reply.into_string());
+
+        let reply = client
+            .get("/small")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch();
+        assert!(reply.headers().get_one("Content-Encoding").is_none());
+        assert_eq!(Some(String::from("OK")), reply.into_string());
+    }
+    #[test]
+    fn contents_1() {
+        // Spectrum contents decompress to the uncompressed reply:
+
+        let rocket = rest_common::setup()
+            .mount("/", routes![spectrum::get_contents])
+            .attach(Compression::new(0));
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        parameter_messages::ParameterMessageClient::new(&chan)
+            .create_parameter("p1")
+            .expect("Making parameter");
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&chan);
+        sapi.create_spectrum_1d("s1", "p1", 0.0, 100.0, 100)
+            .expect("Making spectrum");
+        let mut contents = spectrum_messages::SpectrumContents::new();
+        for i in 0..100 {
+            contents.push(spectrum_messages::Channel {
+                chan_type: spectrum_messages::ChannelType::Bin,
+                x: i as f64 + 0.5,
+                y: 0.0,
+                bin: 0,
+                value: 1.0,
+            });
+        }
+        sapi.fill_spectrum("s1", contents)
+            .expect("Filling spectrum");
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let plain = client
+            .get("/contents?name=s1")
+            .dispatch()
+            .into_bytes()
+            .expect("Getting body");
+        let reply = client
+            .get("/contents?name=s1")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch();
+        assert_eq!(Some("gzip"), reply.headers().get_one("Content-Encoding"));
+        assert_eq!(Some(ContentType::JSON), reply.content_type());
+        let body = reply.into_bytes().expect("Getting body");
+        assert!(body.len() < plain.len());
+        let mut text = Vec::new();
+        GzDecoder::new(&body[..])
+            .read_to_end(&mut text)
+            .expect("Decompressing");
+        assert_eq!(plain, text);
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn events_1() {
+        // Server sent events are not held back by compression:
+
+        let client = client();
+        let reply = client
+            .get("/events")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch();
+        assert!(reply.headers().get_one("Content-Encoding").is_none());
+        assert!(reply
+            .into_string()
+            .expect("Getting body")
+            .contains("data:event 199"));
+    }
+}
@@ -0,0 +1,184 @@
+//! Implements the /spectcl/crashes domain.  This provides access to the
+//! crash reports recorded when one of the rustogramer threads panics
+//! (see src/crash/mod.rs).  Since these are most interesting when
+//! the histogramer is no longer responding, none of these requests
+//! require the histogramer.
+//!
+//! *  /spectcl/crashes - lists the crash reports.
+//! *  /spectcl/crashes/clear - removes the crash reports.
+//!
+use super::*;
+use crate::crash;
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+use std::time;
+
+/// A crash report as it appears in the response:
+///
+/// * time - unix time (seconds) at which the thread panicked.
+/// * thread - name of the thread that panicked.
+/// * message - the panic message.
+/// * location - source file, line and column of the panic.
+/// * backtrace - the backtrace at the time of the panic.
+///
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CrashReport {
+    time: u64,
+    thread: String,
+    message: String,
+    location: String,
+    backtrace: String,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CrashReportsResponse {
+    status: String,
+    detail: Vec<CrashReport>,
+}
+
+/// List the crash reports, oldest first.  Listing the reports
+/// does not remove them.
+///
+#[get("/")]
+pub fn list_crashes(state: &State<crash::SharedCrashStore>) -> Json<CrashReportsResponse> {
+    let mut result = CrashReportsResponse {
+        status: String::from("OK"),
+        detail: Vec::new(),
+    };
+    for report in state.inner().list() {
+        let time = report
+            .stamp
+            .duration_since(time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        result.detail.push(CrashReport {
+            time,
+            thread: report.thread,
+            message: report.message,
+            location: report.location,
+            backtrace: report.backtrace,
+        });
+    }
+    Json(result)
+}
+/// Remove the crash reports.  The detail is the number of
+/// reports that were removed.
+///
+#[get("/clear")]
+pub fn clear_crashes(
+    state: &State<crash::SharedCrashStore>,
+    _writable: readonly::Writable,
+) -> Json<UnsignedResponse> {
+    Json(UnsignedResponse::new("OK", state.inner().clear() as u64))
+}
+
+#[cfg(test)]
+mod crashes_tests {
+    use super::*;
+    use crate::messaging;
+    use crate::processing;
+    use crate::sharedmem::binder;
+    use crate::test::rest_common;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+
+    use std::sync::mpsc;
+
+    fn setup() -> Rocket<Build> {
+        rest_common::setup().mount("/", routes![list_crashes, clear_crashes])
+    }
+    fn getstate(
+        r: &Rocket<Build>,
+    ) -> (
+        mpsc::Sender<messaging::Request>,
+        processing::ProcessingApi,
+        binder::BindingApi,
+        crash::SharedCrashStore,
+    ) {
+        let (chan, papi, bapi) = rest_common::get_state(r);
+        let store = r
+            .state::<crash::SharedCrashStore>()
+            .expect("Getting crash store")
+            .clone();
+        (chan, papi, bapi, store)
+    }
+    fn teardown(
+        c: mpsc::Sender<messaging::Request>,
+        p: &processing::ProcessingApi,
+        b: &binder::BindingApi,
+    ) {
+        rest_common::teardown(c, p, b);
+    }
+
+    #[test]
+    fn list_1() {
+        // Initially there are no crashes:
+
+        let rocket = setup();
+        let (chan, papi, bapi, _) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let reply = client
+            .get("/")
+            .dispatch()
+            .into_json::<CrashReportsResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert!(reply.detail.is_empty());
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn list_2() {
+        // Recorded crashes are listed:
+
+        let rocket = setup();
+        let (chan, papi, bapi, store) = getstate(&rocket);
+        store.record(crash::CrashReport::new(
+            "processing",
+            "Bad data",
+            "src/processing/mod.rs:1:1",
+            "backtrace",
+        ));
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let reply = client
+            .get("/")
+            .dispatch()
+            .into_json::<CrashReportsResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail.len());
+        let report = &reply.detail[0];
+        assert!(report.time > 0);
+        assert_eq!("processing", report.thread);
+        assert_eq!("Bad data", report.message);
+        assert_eq!("src/processing/mod.rs:1:1", report.location);
+        assert_eq!("backtrace", report.backtrace);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn clear_1() {
+        let rocket = setup();
+        let (chan, papi, bapi, store) = getstate(&rocket);
+        store.record(crash::CrashReport::new("binder", "oops", "", ""));
+        store.record(crash::CrashReport::new("mirror", "oops", "", ""));
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let reply = client
+            .get("/clear")
+            .dispatch()
+            .into_json::<UnsignedResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(2, reply.detail);
+        assert!(store.list().is_empty());
+
+        teardown(chan, &papi, &bapi);
+    }
+}
@@ -6,29 +6,137 @@
 //!  
 //! Two mount points are provided:
 //!  
-//!  *  /attach which provides the attach, detach and list methods.
-//!  *  /analyze which provides the start, stop and eventchunk
-//! methods.
+//!  *  /attach which provides the attach, detach, list and verify methods.
+//!  *  /analyze which provides the start, stop, pause, resume, status,
+//! eventchunk, read buffer size, read statistics, timestamp
+//! verification and end of run write methods.
+//!
+//!  The stop and status methods also describe replays (see
+//! processing::ReplaySummary).
+//!
+//!  The endrun methods manage the policy for writing spectra to a file
+//! for each run when its END_RUN item is processed (see
+//! processing::endrun).
 
 // Imports:
 
-use rocket::serde::json::Json;
+use rocket::serde::{json::Json, Deserialize, Serialize};
 use rocket::State;
 
 use super::*;
+use crate::messaging::condition_messages::{ConditionMessageClient, ConditionReply};
+use crate::messaging::parameter_messages::ParameterMessageClient;
+use crate::messaging::spectrum_messages::SpectrumMessageClient;
+use crate::processing;
+use std::collections::{HashMap, HashSet};
 
 //---------------------------------------------------------------
 // The /attach mount point:
 
+/// The data source types that can be attached:
+
+const SUPPORTED_SOURCE_TYPES: [&str; 4] = ["file", "list", "ring", "filter"];
+
+/// Response to an attach request.  This is a generic response
+/// with an additional _supported_ field that is only present when the
+/// request failed because the data source type is not supported.  It
+/// lists the types that are, so that clients written for SpecTcl can
+/// tell a capability error from a failure to open the source.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AttachResponse {
+    status: String,
+    detail: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    supported: Vec<String>,
+}
+impl AttachResponse {
+    fn new(status: &str, detail: &str) -> AttachResponse {
+        AttachResponse {
+            status: String::from(status),
+            detail: String::from(detail),
+            supported: vec![],
+        }
+    }
+    fn unsupported(source_type: &str, reason: &str) -> AttachResponse {
+        AttachResponse {
+            status: format!("Data source type '{}' is not supported", source_type),
+            detail: String::from(reason),
+            supported: SUPPORTED_SOURCE_TYPES
+                .iter()
+                .map(|s| String::from(*s))
+                .collect(),
+        }
+    }
+}
+
+// Split a Tcl list of file names.  Elements are separated by
+// whitespace; names that contain whitespace can be enclosed in {}.
+
+fn parse_file_list(list: &str) -> Result<Vec<String>, String> {
+    let mut files = Vec::<String>::new();
+    let mut chars = list.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.next() {
+            None => break,
+            Some('{') => {
+                let mut name = String::new();
+                let mut depth = 1;
+                loop {
+                    let c = match chars.next() {
+                        None => return Err(format!("Unmatched {{ in '{}'", list)),
+                        Some(c) => c,
+                    };
+                    if c == '{' {
+                        depth += 1;
+                    } else if c == '}' {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    name.push(c);
+                }
+                files.push(name);
+            }
+            Some(c) => {
+                let mut name = String::from(c);
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    name.push(c);
+                }
+                files.push(name);
+            }
+        }
+    }
+    if files.is_empty() {
+        Err(String::from("The file list is empty"))
+    } else {
+        Ok(files)
+    }
+}
+
 /// Attach a data source.
-/// Note that this version of rustogrammer only support type=file
 /// Query parameters:
 ///
-/// *  type - the type of attach (file is the only one supported).
-/// *  source - in this case the name of the data file to attach.
+/// *  type - the type of attach:
+///     - file - source is the name of a parameter file to attach.
+///     - list - source is a Tcl list of parameter files that are
+/// analyzed one after the other (a chained replay).
+///     - ring - source is the URL of a live NSCLDAQ ring buffer e.g.
+/// tcp://spdaq/fox (see processing::ring).
+///     - filter - source is the name of a SpecTcl XDR filter file (see
+/// processing::xdr).  Filter files written by rustogramer are parameter
+/// files and are attached as type file.
+/// *  source - the data source as described above.
 /// *  size (ignored) - for compatiblity with SpecTcl's API.
 ///
-/// The response is a generic resposne with the detail empty on
+/// SpecTcl also supports type=pipe.  Rustogramer can't analyze the
+/// output of a program so that type, like any other unsupported type,
+/// results in an error whose _supported_ field lists the types that can
+/// be used.
+///
+/// The response is an AttachResponse with the detail empty on
 /// success and containing more detailed error message on failure
 /// than that in status.
 #[allow(unused_variables)]
@@ -38,21 +146,39 @@ pub fn attach_source(
     source: String,
     size: OptionalString,
     state: &State<SharedProcessingApi>,
-) -> Json<GenericResponse> {
-    let reply = if r#type == "file" {
-        let api = state.inner().lock().unwrap();
-        if let Err(s) = api.attach(&source) {
-            GenericResponse::err("Attach failed", &s)
-        } else {
-            GenericResponse::ok("")
+    _writable: readonly::Writable,
+) -> Json<AttachResponse> {
+    let status = match r#type.as_str() {
+        "file" => state.inner().lock().unwrap().attach(&source),
+        "list" => parse_file_list(&source)
+            .and_then(|files| state.inner().lock().unwrap().attach_list(&files)),
+        "ring" => state.inner().lock().unwrap().attach(&format!(
+            "{}{}",
+            processing::ring::RING_PREFIX,
+            source
+        )),
+        "filter" => state.inner().lock().unwrap().attach(&format!(
+            "{}{}",
+            processing::xdr::XDR_PREFIX,
+            source
+        )),
+        "pipe" => {
+            return Json(AttachResponse::unsupported(
+                &r#type,
+                "Rustogramer can only analyze parameter files, not the output of programs",
+            ))
+        }
+        _ => {
+            return Json(AttachResponse::unsupported(
+                &r#type,
+                "This is Rustogramer not SpecTcl",
+            ))
         }
-    } else {
-        GenericResponse::err(
-            &format!("Data source type '{}' is not supported", r#type),
-            "This is Rustogramer not SpecTcl",
-        )
     };
-    Json(reply)
+    Json(match status {
+        Ok(_) => AttachResponse::new("OK", ""),
+        Err(s) => AttachResponse::new("Attach failed", &s),
+    })
 }
 
 /// list the current data source.
@@ -71,7 +197,10 @@ pub fn list_source(state: &State<SharedProcessingApi>) -> Json<GenericResponse>
 ///  This is specific to Rustogramer.
 ///
 #[get("/detach")]
-pub fn detach_source(state: &State<SharedProcessingApi>) -> Json<GenericResponse> {
+pub fn detach_source(
+    state: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
     let api = state.inner().lock().unwrap();
     Json(match api.detach() {
         Ok(s) => GenericResponse::ok(&s),
@@ -79,6 +208,151 @@ pub fn detach_source(state: &State<SharedProcessingApi>) -> Json<GenericResponse
     })
 }
 
+/// A spectrum or condition that uses parameters the data source
+/// does not produce:
+///
+/// * name - name of the spectrum or condition.
+/// * parameters - the parameters it uses that are not produced.
+///
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct UnproducedReference {
+    name: String,
+    parameters: Vec<String>,
+}
+/// Result of verifying the data source:
+///
+/// * produced - number of parameters the data source produces.
+/// * spectra - spectra that use parameters that are not produced.
+/// * conditions - conditions that use parameters that are not produced.
+///
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SourceVerification {
+    produced: usize,
+    spectra: Vec<UnproducedReference>,
+    conditions: Vec<UnproducedReference>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct VerifyResponse {
+    status: String,
+    detail: SourceVerification,
+}
+
+/// Verify that the spectra and conditions are consistent with the
+/// attached data source.  Spectra and conditions that use parameters the
+/// data source never produces are never incremented/true.  This typically
+/// happens, silently, after attaching a file from a different experiment.
+///
+/// The parameters the data source produces are those its parameter
+/// definitions are mapped to.  They are known once the definitions have
+/// been processed, i.e. once analysis has started.  Until then
+/// verification fails.
+///
+/// No query parameters are accepted.
+///
+#[get("/verify")]
+pub fn verify_source(
+    state: &State<SharedProcessingApi>,
+    hg: &State<SharedHistogramChannel>,
+    _ready: busy::HistogramerReady,
+) -> Json<VerifyResponse> {
+    let mut result = VerifyResponse {
+        status: String::from("OK"),
+        detail: SourceVerification {
+            produced: 0,
+            spectra: Vec::new(),
+            conditions: Vec::new(),
+        },
+    };
+    let produced = match state.inner().lock().unwrap().get_mapped_parameters() {
+        Ok(ids) => ids.into_iter().collect::<HashSet<u32>>(),
+        Err(s) => {
+            result.status = format!(
+                "Failed to get the parameters the data source produces: {}",
+                s
+            );
+            return Json(result);
+        }
+    };
+    if produced.is_empty() {
+        result.status = String::from(
+            "The data source has not defined its parameters - analysis must be started first",
+        );
+        return Json(result);
+    }
+    result.detail.produced = produced.len();
+
+    let chan = hg.inner().lock().unwrap();
+    let parameters = match ParameterMessageClient::new(&chan).list_parameters("*") {
+        Ok(l) => l,
+        Err(s) => {
+            result.status = format!("Failed to list parameters: {}", s);
+            return Json(result);
+        }
+    };
+    let ids: HashMap<String, u32> = parameters
+        .iter()
+        .map(|p| (p.get_name(), p.get_id()))
+        .collect();
+    let names: HashMap<u32, String> = parameters
+        .iter()
+        .map(|p| (p.get_id(), p.get_name()))
+        .collect();
+
+    match SpectrumMessageClient::new(&chan).list_spectra("*") {
+        Ok(spectra) => {
+            for spectrum in spectra {
+                let mut unproduced: Vec<String> = spectrum
+                    .xparams
+                    .iter()
+                    .chain(spectrum.yparams.iter())
+                    .filter(|p| !ids.get(*p).is_some_and(|id| produced.contains(id)))
+                    .cloned()
+                    .collect();
+                unproduced.dedup();
+                if !unproduced.is_empty() {
+                    result.detail.spectra.push(UnproducedReference {
+                        name: spectrum.name,
+                        parameters: unproduced,
+                    });
+                }
+            }
+        }
+        Err(s) => result.status = format!("Failed to list spectra: {}", s),
+    }
+    match ConditionMessageClient::new(&chan).list_conditions("*") {
+        ConditionReply::Listing(conditions) => {
+            for condition in conditions {
+                let mut unproduced: Vec<String> = condition
+                    .parameters
+                    .iter()
+                    .filter(|id| !produced.contains(id))
+                    .map(|id| {
+                        names
+                            .get(id)
+                            .cloned()
+                            .unwrap_or_else(|| format!("id {}", id))
+                    })
+                    .collect();
+                unproduced.dedup();
+                if !unproduced.is_empty() {
+                    result.detail.conditions.push(UnproducedReference {
+                        name: condition.cond_name,
+                        parameters: unproduced,
+                    });
+                }
+            }
+        }
+        ConditionReply::Error(s) => result.status = format!("Failed to list conditions: {}", s),
+        _ => result.status = String::from("Unexpected reply listing conditions"),
+    }
+    result.detail.spectra.sort_by(|a, b| a.name.cmp(&b.name));
+    result.detail.conditions.sort_by(|a, b| a.name.cmp(&b.name));
+    Json(result)
+}
+
 //--------------------------------------------------------------
 // The /analyze mount point.
 //
@@ -86,44 +360,477 @@ pub fn detach_source(state: &State<SharedProcessingApi>) -> Json<GenericResponse
 /// start - starts analyzing data on the currently attached
 /// data source.  No query parameters are required/accepted.
 #[get("/start")]
-pub fn start_processing(state: &State<SharedProcessingApi>) -> Json<GenericResponse> {
+pub fn start_processing(
+    state: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
     let api = state.inner().lock().unwrap();
     Json(match api.start_analysis() {
         Ok(_) => GenericResponse::ok(""),
         Err(s) => GenericResponse::err("Failed to start analysis", &s),
     })
 }
+// Summary of a replay:
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct ReplaySummary {
+    source: String,
+    events: u64,
+    items: u64,
+    skipped: u64,
+    unsampled: u64,
+    seconds: f64,
+    event_rate: f64,
+    end_reason: String,
+}
+impl From<processing::ReplaySummary> for ReplaySummary {
+    fn from(summary: processing::ReplaySummary) -> ReplaySummary {
+        ReplaySummary {
+            event_rate: summary.event_rate(),
+            source: summary.source,
+            events: summary.events,
+            items: summary.items,
+            skipped: summary.skipped,
+            unsampled: summary.unsampled,
+            seconds: summary.seconds,
+            end_reason: summary.end_reason,
+        }
+    }
+}
+// The detail of a stop reply is the summary on success and the
+// reason for the failure otherwise:
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", untagged)]
+pub enum StopDetail {
+    Summary(ReplaySummary),
+    Message(String),
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct StopResponse {
+    status: String,
+    detail: StopDetail,
+}
 ///
 /// stop stops analyzing data on the current data source.
 /// No query parameters are required.
 ///
+/// On success, the detail is the summary of the replay that was
+/// stopped.  On failure it is the reason stop failed.
+///
 #[get("/stop")]
-pub fn stop_processing(state: &State<SharedProcessingApi>) -> Json<GenericResponse> {
+pub fn stop_processing(
+    state: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<StopResponse> {
     let api = state.inner().lock().unwrap();
     Json(match api.stop_analysis() {
+        Ok(_) => StopResponse {
+            status: String::from("OK"),
+            detail: match api.last_replay_summary() {
+                Some(summary) => StopDetail::Summary(summary.into()),
+                None => StopDetail::Message(String::new()),
+            },
+        },
+        Err(s) => StopResponse {
+            status: String::from("Failed to stop analysis"),
+            detail: StopDetail::Message(s),
+        },
+    })
+}
+///
+/// pause suspends analysis of the current data source.  Unlike stop,
+/// the events that have been read but not yet histogramed are retained
+/// and the data source remains positioned at the next ring item so that
+/// resume continues exactly where analysis left off.
+///
+#[get("/pause")]
+pub fn pause_processing(
+    state: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    let api = state.inner().lock().unwrap();
+    Json(match api.pause_analysis() {
+        Ok(_) => GenericResponse::ok(""),
+        Err(s) => GenericResponse::err("Failed to pause analysis", &s),
+    })
+}
+///
+/// resume continues analysis that was paused.
+///
+#[get("/resume")]
+pub fn resume_processing(
+    state: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    let api = state.inner().lock().unwrap();
+    Json(match api.resume_analysis() {
         Ok(_) => GenericResponse::ok(""),
-        Err(s) => GenericResponse::err("Failed to stop analysis", &s),
+        Err(s) => GenericResponse::err("Failed to resume analysis", &s),
     })
 }
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AnalysisStatus {
+    state: String,
+    source: String,
+    offset: u64,
+    items: u64,
+    replays: Vec<ReplaySummary>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AnalysisStatusResponse {
+    status: String,
+    detail: AnalysisStatus,
+}
+///
+/// status returns the analysis state ("Active", "Paused" or "Inactive"),
+/// the data source, the byte offset of the next ring item to be read,
+/// the number of ring items read from the data source and the summaries
+/// of the most recent replays (oldest first).
+///
+#[get("/status")]
+pub fn analysis_status(state: &State<SharedProcessingApi>) -> Json<AnalysisStatusResponse> {
+    let api = state.inner().lock().unwrap();
+    let mut result = AnalysisStatusResponse {
+        status: String::from("OK"),
+        detail: AnalysisStatus {
+            state: String::from(""),
+            source: String::from(""),
+            offset: 0,
+            items: 0,
+            replays: api
+                .get_replay_summaries()
+                .into_iter()
+                .map(ReplaySummary::from)
+                .collect(),
+        },
+    };
+    match api.processing_state() {
+        Ok(s) => result.detail.state = s,
+        Err(s) => result.status = format!("Failed to get analysis state: {}", s),
+    }
+    match api.list() {
+        Ok(s) => result.detail.source = s,
+        Err(s) => result.status = format!("Failed to get data source: {}", s),
+    }
+    match api.get_position() {
+        Ok((offset, items)) => {
+            result.detail.offset = offset;
+            result.detail.items = items;
+        }
+        Err(s) => result.status = format!("Failed to get data source position: {}", s),
+    }
+    Json(result)
+}
 /// Set the analysis block size.  This is the number of events that
 /// will be sent to the histograming thread for each analysis request.
 ///
 /// The query parameter _events_ must be the number of events.
 ///
 #[get("/size?<events>")]
-pub fn set_event_batch(events: usize, state: &State<SharedProcessingApi>) -> Json<GenericResponse> {
+pub fn set_event_batch(
+    events: usize,
+    state: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
     let mut api = state.inner().lock().unwrap();
     Json(match api.set_batching(events) {
         Ok(_) => GenericResponse::ok(""),
         Err(s) => GenericResponse::err("Failed to set event processing batch size", &s),
     })
 }
+/// Set the size of the buffer used to read the data source.
+///
+/// The query parameter _bytes_ is the new buffer size in bytes.
+/// This takes effect immediately.
+///
+#[get("/buffer?<bytes>")]
+pub fn set_read_buffer(
+    bytes: usize,
+    state: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    let api = state.inner().lock().unwrap();
+    Json(match api.set_buffer_size(bytes) {
+        Ok(_) => GenericResponse::ok(""),
+        Err(s) => GenericResponse::err("Failed to set read buffer size", &s),
+    })
+}
+
+/// Set the event sampling.  Only one of every _factor_ events is
+/// histogrammed, which gives a quick preview of a large data source.
+/// A factor of 1 histograms every event.
+///
+/// If the optional _compensate_ flag is true, the sampled events
+/// increment spectra by _factor_ so that channel values approximate
+/// those of a full replay.
+///
+#[get("/sample?<factor>&<compensate>")]
+pub fn set_sampling(
+    factor: u64,
+    compensate: OptionalFlag,
+    state: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    let api = state.inner().lock().unwrap();
+    Json(
+        match api.set_sampling(factor, compensate.unwrap_or(false)) {
+            Ok(_) => GenericResponse::ok(""),
+            Err(s) => GenericResponse::err("Failed to set event sampling", &s),
+        },
+    )
+}
+
+/// Enable or disable verification that the body header timestamps of
+/// each source id don't decrease.  Items whose timestamps are less than
+/// that of the previous item from the same source are counted and reported
+/// by /readstats.  Enabling verification resets those counts.
+///
+/// ### Query parameters
+/// * enable - optional flag, defaults to true.
+///
+#[get("/timestamps?<enable>")]
+pub fn verify_timestamps(
+    enable: OptionalFlag,
+    state: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    let api = state.inner().lock().unwrap();
+    Json(
+        match api.set_timestamp_verification(enable.unwrap_or(true)) {
+            Ok(_) => GenericResponse::ok(""),
+            Err(s) => GenericResponse::err("Failed to set timestamp verification", &s),
+        },
+    )
+}
+
+/// Timestamp ordering of one source id (see processing::SourceOrdering).
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SourceOrdering {
+    source_id: u32,
+    items: u64,
+    out_of_order: u64,
+    last_timestamp: u64,
+}
+impl From<processing::SourceOrdering> for SourceOrdering {
+    fn from(ordering: processing::SourceOrdering) -> SourceOrdering {
+        SourceOrdering {
+            source_id: ordering.source_id,
+            items: ordering.items,
+            out_of_order: ordering.out_of_order,
+            last_timestamp: ordering.last_timestamp,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReadStatistics {
+    buffer_size: usize,
+    bytes: u64,
+    items: u64,
+    read_seconds: f64,
+    process_seconds: f64,
+    read_rate: f64,
+    sample_factor: u64,
+    sample_compensated: bool,
+    ring_dropped: u64,
+    ring_reconnects: u64,
+    verify_timestamps: bool,
+    timestamp_ordering: Vec<SourceOrdering>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReadStatisticsResponse {
+    status: String,
+    detail: ReadStatistics,
+}
+///
+/// readstats returns the read buffer size and the statistics for
+/// reading the attached data source: bytes and ring items read,
+/// the time spent reading and processing them, the read throughput
+/// in bytes/second and the event sampling.  For live rings, the items
+/// dropped and the number of reconnections are included.  If timestamp
+/// verification is enabled, the timestamp ordering of each source id
+/// is included.
+///
+#[get("/readstats")]
+pub fn read_statistics(state: &State<SharedProcessingApi>) -> Json<ReadStatisticsResponse> {
+    let api = state.inner().lock().unwrap();
+    let mut result = ReadStatisticsResponse {
+        status: String::from("OK"),
+        detail: ReadStatistics {
+            buffer_size: 0,
+            bytes: 0,
+            items: 0,
+            read_seconds: 0.0,
+            process_seconds: 0.0,
+            read_rate: 0.0,
+            sample_factor: 1,
+            sample_compensated: false,
+            ring_dropped: 0,
+            ring_reconnects: 0,
+            verify_timestamps: false,
+            timestamp_ordering: vec![],
+        },
+    };
+    match api.get_buffer_size() {
+        Ok(n) => result.detail.buffer_size = n,
+        Err(s) => result.status = format!("Failed to get read buffer size: {}", s),
+    }
+    match api.get_read_statistics() {
+        Ok(stats) => {
+            result.detail.bytes = stats.bytes;
+            result.detail.items = stats.items;
+            result.detail.read_seconds = stats.read_seconds;
+            result.detail.process_seconds = stats.process_seconds;
+            result.detail.read_rate = stats.read_rate();
+            result.detail.sample_factor = stats.sample_factor;
+            result.detail.ring_dropped = stats.ring_dropped;
+            result.detail.ring_reconnects = stats.ring_reconnects;
+        }
+        Err(s) => result.status = format!("Failed to get read statistics: {}", s),
+    }
+    match api.get_sampling() {
+        Ok((_, compensate)) => result.detail.sample_compensated = compensate,
+        Err(s) => result.status = format!("Failed to get event sampling: {}", s),
+    }
+    match api.get_timestamp_ordering() {
+        Ok((enabled, sources)) => {
+            result.detail.verify_timestamps = enabled;
+            result.detail.timestamp_ordering = sources.into_iter().map(|o| o.into()).collect();
+        }
+        Err(s) => result.status = format!("Failed to get timestamp ordering: {}", s),
+    }
+    Json(result)
+}
+/// Set the policy for writing spectra at the end of each run.
+/// When an END_RUN item is processed, the spectra are written to a
+/// file for that run and, optionally, cleared so that the next run
+/// of a batch starts with empty spectra.
+///
+/// ### Query parameters
+/// * spectrum - glob patterns of the spectra to write, at least one
+/// is required and it can appear multiple times.
+/// * file - the file to write.  {run} is replaced by the run number
+/// (e.g. run-{run}.json).
+/// * format - "json" (default), "ascii" or "root".
+/// * clear - optional flag, if true the spectra are cleared after they
+/// are written.  Defaults to false.
+///
+#[get("/endrun?<spectrum>&<file>&<format>&<clear>")]
+pub fn set_end_run_write(
+    spectrum: Vec<String>,
+    file: String,
+    format: OptionalString,
+    clear: OptionalFlag,
+    state: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    let format = format.unwrap_or_else(|| String::from("json"));
+    Json(
+        match processing::endrun::EndRunWrite::new(
+            &spectrum,
+            &file,
+            &format,
+            clear.unwrap_or(false),
+        ) {
+            Ok(policy) => {
+                state
+                    .inner()
+                    .lock()
+                    .unwrap()
+                    .set_end_run_write(Some(policy));
+                GenericResponse::ok("")
+            }
+            Err(s) => GenericResponse::err("Failed to set end of run write", &s),
+        },
+    )
+}
+/// Remove the end of run write policy.  It is not an error if there
+/// is none.
+#[get("/endrun/cancel")]
+pub fn cancel_end_run_write(
+    state: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    state.inner().lock().unwrap().set_end_run_write(None);
+    Json(GenericResponse::ok(""))
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct EndRunPolicy {
+    spectra: Vec<String>,
+    file: String,
+    format: String,
+    clear: bool,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct EndRunResult {
+    run: u32,
+    file: String,
+    spectra: usize,
+    cleared: bool,
+    error: Option<String>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct EndRunStatus {
+    policy: Option<EndRunPolicy>,
+    last: Option<EndRunResult>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct EndRunStatusResponse {
+    status: String,
+    detail: EndRunStatus,
+}
+/// Report the end of run write policy (null if there is none) and
+/// the outcome of the most recent end of run write (null if there
+/// has not been one).
+#[get("/endrun/status")]
+pub fn end_run_write_status(state: &State<SharedProcessingApi>) -> Json<EndRunStatusResponse> {
+    let end_run = state.inner().lock().unwrap().get_end_run_write();
+    Json(EndRunStatusResponse {
+        status: String::from("OK"),
+        detail: EndRunStatus {
+            policy: end_run.policy.map(|p| EndRunPolicy {
+                spectra: p.spectra(),
+                file: p.file_pattern(),
+                format: p.format(),
+                clear: p.clear(),
+            }),
+            last: end_run.last.map(|r| EndRunResult {
+                run: r.run,
+                file: r.file,
+                spectra: r.spectra,
+                cleared: r.cleared,
+                error: r.error,
+            }),
+        },
+    })
+}
 #[cfg(test)]
 mod processing_tests {
     use super::*;
     use crate::messaging;
     use crate::processing;
+    use crate::ring_items;
+    use crate::ring_items::analysis_ring_items::{
+        ParameterDefinitions, ParameterFileWriter, ParameterItem,
+    };
+    use crate::ring_items::state_change::{StateChange, StateChangeType};
+    use crate::ring_items::{RingItem, RingVersion, ToRaw};
     use crate::sharedmem::binder;
+    use crate::trace;
 
     use rocket;
     use rocket::local::blocking::Client;
@@ -131,7 +838,10 @@ mod processing_tests {
     use rocket::Rocket;
 
     use crate::test::rest_common;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{Shutdown, TcpListener};
     use std::sync::mpsc;
+    use std::thread;
     // Setup needs to set a state for Rocket that includes valid
     // histogramer request channel and thread.
     // binder channel (no need for thread).
@@ -145,9 +855,20 @@ mod processing_tests {
                 attach_source,
                 list_source,
                 detach_source,
+                verify_source,
                 start_processing,
                 stop_processing,
-                set_event_batch
+                set_event_batch,
+                pause_processing,
+                resume_processing,
+                analysis_status,
+                set_read_buffer,
+                read_statistics,
+                set_sampling,
+                verify_timestamps,
+                set_end_run_write,
+                cancel_end_run_write,
+                end_run_write_status
             ],
         )
     }
@@ -179,13 +900,14 @@ mod processing_tests {
         let reply = req.dispatch();
 
         let json = reply
-            .into_json::<GenericResponse>()
+            .into_json::<AttachResponse>()
             .expect("Bad Json returned");
 
         assert_eq!(
             "Data source type 'pipe' is not supported",
             json.status.as_str()
         );
+        assert_eq!(vec!["file", "list", "ring", "filter"], json.supported);
 
         teardown(chan, &papi, &bapi);
     }
@@ -230,6 +952,233 @@ mod processing_tests {
         teardown(chan, &papi, &bapi);
     }
     #[test]
+    fn attach_4() {
+        // Attach a list of files:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+
+        let client = Client::tracked(rocket).expect("Creating client");
+        let req = client.get("/attach?type=list&source=run-0000-00.par%20%7Brun-0000-00.par%7D");
+        let json = req
+            .dispatch()
+            .into_json::<AttachResponse>()
+            .expect("Bad JSON");
+
+        assert_eq!("OK", json.status.as_str());
+        assert!(json.supported.is_empty());
+
+        let reply = papi.list().expect("Getting attchment");
+        assert_eq!("file:run-0000-00.par", reply);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn attach_5() {
+        // Empty list, unmatched brace and a missing first file fail:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+
+        let client = Client::tracked(rocket).expect("Creating client");
+        for uri in [
+            "/attach?type=list&source=%20",
+            "/attach?type=list&source=%7Brun-0000-00.par",
+            "/attach?type=list&source=no-such-file.par%20run-0000-00.par",
+        ] {
+            let json = client
+                .get(uri)
+                .dispatch()
+                .into_json::<AttachResponse>()
+                .expect("Bad JSON");
+            assert_eq!("Attach failed", json.status.as_str());
+            assert!(json.supported.is_empty());
+        }
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn attach_6() {
+        // Attach a ring.  A fake RingMaster accepts the request.  It
+        // closes the first connection so the ring is reconnected:
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Binding listener");
+        let port = listener.local_addr().unwrap().port();
+        let ringmaster = thread::spawn(move || {
+            let mut line = String::new();
+            let mut sockets = vec![];
+            for _ in 0..2 {
+                let (mut socket, _) = listener.accept().expect("Accepting");
+                line.clear();
+                BufReader::new(socket.try_clone().unwrap())
+                    .read_line(&mut line)
+                    .expect("Reading request");
+                socket.write_all(b"OK BINARY FOLLOWS\n").expect("Replying");
+                if sockets.is_empty() {
+                    socket.shutdown(Shutdown::Both).expect("Closing");
+                }
+                sockets.push(socket);
+            }
+            (line, sockets)
+        });
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+
+        let client = Client::tracked(rocket).expect("Creating client");
+        let uri = format!("/attach?type=ring&source=tcp://127.0.0.1:{}/fox", port);
+        let json = client
+            .get(uri)
+            .dispatch()
+            .into_json::<AttachResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", json.status.as_str());
+        let (line, _sockets) = ringmaster.join().unwrap();
+        assert_eq!("REMOTE fox\n", line);
+
+        let reply = papi.list().expect("Getting attachment");
+        assert_eq!(format!("ring:tcp://127.0.0.1:{}/fox", port), reply);
+
+        // The reconnection is in the read statistics:
+
+        let start = std::time::Instant::now();
+        loop {
+            let stats = client
+                .get("/readstats")
+                .dispatch()
+                .into_json::<ReadStatisticsResponse>()
+                .expect("Bad JSON");
+            assert_eq!("OK", stats.status);
+            assert_eq!(0, stats.detail.ring_dropped);
+            if stats.detail.ring_reconnects == 1 {
+                break;
+            }
+            assert!(start.elapsed().as_secs() < 5, "Ring was not reconnected");
+            thread::sleep(std::time::Duration::from_millis(50));
+        }
+
+        // A bad URL fails:
+
+        let json = client
+            .get("/attach?type=ring&source=spdaq/fox")
+            .dispatch()
+            .into_json::<AttachResponse>()
+            .expect("Bad JSON");
+        assert_eq!("Attach failed", json.status.as_str());
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn attach_7() {
+        // Attach a SpecTcl XDR filter file and analyze it:
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let path = dir.path().join("run.xdr");
+        let events: Vec<Vec<(usize, f32)>> = (0..10).map(|i| vec![(1, i as f32)]).collect();
+        std::fs::write(&path, processing::xdr::encode(&["a", "b"], &events))
+            .expect("Writing file");
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        let spec_api = messaging::spectrum_messages::SpectrumMessageClient::new(&chan);
+        let param_api = messaging::parameter_messages::ParameterMessageClient::new(&chan);
+        param_api.create_parameter("b").expect("Creating parameter");
+        spec_api
+            .create_spectrum_1d("test", "b", 0.0, 10.0, 10)
+            .expect("Creating spectrum");
+
+        let client = Client::tracked(rocket).expect("Creating client");
+        let name = path.to_str().unwrap();
+        let json = client
+            .get(format!("/attach?type=filter&source={}", name))
+            .dispatch()
+            .into_json::<AttachResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", json.status.as_str());
+        assert_eq!(format!("xdr:{}", name), papi.list().expect("Listing"));
+
+        papi.start_analysis().expect("Starting analysis");
+        let start = std::time::Instant::now();
+        while papi.processing_state().expect("Getting state") != "Inactive" {
+            assert!(start.elapsed() < std::time::Duration::from_secs(10));
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let contents = spec_api
+            .get_contents("test", 0.0, 10.0, 0.0, 0.0)
+            .expect("Getting contents");
+        assert_eq!(10.0, contents.iter().map(|c| c.value).sum::<f64>());
+
+        // Missing files fail:
+
+        let json = client
+            .get("/attach?type=filter&source=no-such-file.xdr")
+            .dispatch()
+            .into_json::<AttachResponse>()
+            .expect("Bad JSON");
+        assert_eq!("Attach failed", json.status.as_str());
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn attach_8() {
+        // In a chained replay, the definitions the prefetch mapped are
+        // not processed again but their bytes are counted so the
+        // position ends up at the end of the file:
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let empty = dir.path().join("empty.par");
+        std::fs::File::create(&empty).expect("Creating empty file");
+        let path = dir.path().join("run.par");
+        let mut writer = ParameterFileWriter::new(
+            std::fs::File::create(&path).expect("Creating file"),
+            RingVersion::V11,
+        )
+        .expect("Making writer");
+        let mut defs = ParameterDefinitions::new();
+        defs.add(1, "a");
+        writer
+            .write_definitions(&defs)
+            .expect("Writing definitions");
+        for i in 0..100 {
+            let mut event = ParameterItem::new(i);
+            event.add(1, i as f64);
+            writer.write_event(&event).expect("Writing event");
+        }
+        writer.flush().expect("Flushing");
+        drop(writer);
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        papi.attach_list(&[
+            String::from(empty.to_str().unwrap()),
+            String::from(path.to_str().unwrap()),
+        ])
+        .expect("Attaching list");
+        papi.start_analysis().expect("Starting analysis");
+        let start = std::time::Instant::now();
+        while papi.processing_state().expect("Getting state") != "Inactive" {
+            assert!(start.elapsed() < std::time::Duration::from_secs(10));
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        // The format item and the events are the items read:
+
+        let size = std::fs::metadata(&path).expect("Getting size").len();
+        assert_eq!((size, 101), papi.get_position().expect("Getting position"));
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn file_list_1() {
+        assert_eq!(
+            vec!["a", "b c", "d{e}"],
+            parse_file_list(" a {b c}\td{e} ").unwrap()
+        );
+        assert_eq!(vec!["x {y}"], parse_file_list("{x {y}}").unwrap());
+        assert!(parse_file_list("").is_err());
+        assert!(parse_file_list("{a b").is_err());
+    }
+    #[test]
     fn list_1() {
         // not attached:
 
@@ -308,42 +1257,168 @@ mod processing_tests {
         teardown(chan, &papi, &bapi);
     }
     #[test]
-    fn start_1() {
-        // nothing attached.
+    fn detach_3() {
+        // Attach and detach are traced:
+
         let rocket = setup();
         let (chan, papi, bapi) = get_state(&rocket);
+        let tracedb = rocket
+            .state::<trace::SharedTraceStore>()
+            .expect("Valid state")
+            .clone();
+        let token = tracedb.new_client(std::time::Duration::from_secs(10));
 
         let client = Client::tracked(rocket).expect("Creating client");
-        let req = client.get("/start");
-        let reply = req
+        let reply = client
+            .get("/attach?type=file&source=run-0000-00.par")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status.as_str());
+        let reply = client
+            .get("/detach")
             .dispatch()
             .into_json::<GenericResponse>()
             .expect("Bad JSON");
+        assert_eq!("OK", reply.status.as_str());
 
-        assert_eq!("Failed to start analysis", reply.status.as_str());
-        assert_eq!("No file is attached", reply.detail.as_str());
+        let traces = tracedb.get_traces(token).expect("Getting traces");
+        assert_eq!(2, traces.len());
+        assert!(matches!(
+            traces[0].event(),
+            trace::TraceEvent::SourceAttached(name) if name == "run-0000-00.par"
+        ));
+        assert!(matches!(
+            traces[1].event(),
+            trace::TraceEvent::SourceDetached(name) if name == "run-0000-00.par"
+        ));
 
         teardown(chan, &papi, &bapi);
     }
-    // I truly don't understand this but this test claims that
-    // the histogramer thread probably exited when the processing
-    // thread is trying to figure out which parameters need to be
-    // crated and creates them.
-    // start_2 works and that basically does the same thing programmatically
-    // in the paip.start_analysis call.
-    // before.  I'm leaving the code here in case some brilliant person
-    // can figure out how to make it pass but commenting out the test
-    // which makes this dead code...btw this manifestly works in
-    // the running program e.g. _sigh_
-    // #[test]
-    #[allow(dead_code)]
-    fn start_2() {
-        // attached - ok.
+    #[test]
+    fn verify_1() {
+        // Not attached so no parameters are produced:
 
         let rocket = setup();
         let (chan, papi, bapi) = get_state(&rocket);
 
-        papi.attach("run-0000-00.par").expect("attaching file"); // attach the easy way
+        let client = Client::tracked(rocket).expect("Creating client");
+        let reply = client
+            .get("/verify")
+            .dispatch()
+            .into_json::<VerifyResponse>()
+            .expect("Bad JSON");
+        assert_eq!(
+            "The data source has not defined its parameters - analysis must be started first",
+            reply.status
+        );
+        assert_eq!(0, reply.detail.produced);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn verify_2() {
+        // The file only produces "a" so the spectrum and condition
+        // on "b" are reported but the spectrum on "a" is not:
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let path = dir.path().join("run.par");
+        let mut writer = ParameterFileWriter::new(
+            std::fs::File::create(&path).expect("Creating file"),
+            RingVersion::V11,
+        )
+        .expect("Making writer");
+        let mut defs = ParameterDefinitions::new();
+        defs.add(1, "a");
+        writer
+            .write_definitions(&defs)
+            .expect("Writing definitions");
+        let mut event = ParameterItem::new(0);
+        event.add(1, 1.0);
+        writer.write_event(&event).expect("Writing event");
+        writer.flush().expect("Flushing");
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        let param_api = messaging::parameter_messages::ParameterMessageClient::new(&chan);
+        param_api.create_parameter("a").expect("Creating parameter");
+        param_api.create_parameter("b").expect("Creating parameter");
+        let bid = param_api.list_parameters("b").expect("Listing parameters")[0].get_id();
+        let spec_api = messaging::spectrum_messages::SpectrumMessageClient::new(&chan);
+        spec_api
+            .create_spectrum_1d("good", "a", 0.0, 100.0, 100)
+            .expect("Creating spectrum");
+        spec_api
+            .create_spectrum_1d("bad", "b", 0.0, 100.0, 100)
+            .expect("Creating spectrum");
+        ConditionMessageClient::new(&chan).create_cut_condition("cut", bid, 0.0, 10.0);
+
+        let name = path.to_str().unwrap();
+        papi.attach(name).expect("attaching file");
+        papi.start_analysis().expect("Starting analysis");
+        let start = std::time::Instant::now();
+        while papi.processing_state().expect("Getting state") != "Inactive" {
+            assert!(start.elapsed() < std::time::Duration::from_secs(10));
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let client = Client::tracked(rocket).expect("Creating client");
+        let reply = client
+            .get("/verify")
+            .dispatch()
+            .into_json::<VerifyResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail.produced);
+        assert_eq!(1, reply.detail.spectra.len());
+        assert_eq!("bad", reply.detail.spectra[0].name);
+        assert_eq!(vec![String::from("b")], reply.detail.spectra[0].parameters);
+        assert_eq!(1, reply.detail.conditions.len());
+        assert_eq!("cut", reply.detail.conditions[0].name);
+        assert_eq!(
+            vec![String::from("b")],
+            reply.detail.conditions[0].parameters
+        );
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn start_1() {
+        // nothing attached.
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+
+        let client = Client::tracked(rocket).expect("Creating client");
+        let req = client.get("/start");
+        let reply = req
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+
+        assert_eq!("Failed to start analysis", reply.status.as_str());
+        assert_eq!("No file is attached", reply.detail.as_str());
+
+        teardown(chan, &papi, &bapi);
+    }
+    // I truly don't understand this but this test claims that
+    // the histogramer thread probably exited when the processing
+    // thread is trying to figure out which parameters need to be
+    // crated and creates them.
+    // start_2 works and that basically does the same thing programmatically
+    // in the paip.start_analysis call.
+    // before.  I'm leaving the code here in case some brilliant person
+    // can figure out how to make it pass but commenting out the test
+    // which makes this dead code...btw this manifestly works in
+    // the running program e.g. _sigh_
+    // #[test]
+    #[allow(dead_code)]
+    fn start_2() {
+        // attached - ok.
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+
+        papi.attach("run-0000-00.par").expect("attaching file"); // attach the easy way
 
         let client = Client::tracked(rocket).expect("Creating client");
         let req = client.get("/start");
@@ -401,11 +1476,14 @@ mod processing_tests {
         let req = client.get("/stop");
         let reply = req
             .dispatch()
-            .into_json::<GenericResponse>()
+            .into_json::<StopResponse>()
             .expect("Bad JSON");
 
         assert_eq!("Failed to stop analysis", reply.status.as_str());
-        assert_eq!("Not processing data", reply.detail.as_str());
+        assert!(matches!(
+            reply.detail,
+            StopDetail::Message(s) if s == "Not processing data"
+        ));
 
         teardown(chan, &papi, &bapi);
     }
@@ -423,11 +1501,18 @@ mod processing_tests {
         papi.start_analysis().expect("Starting via api");
         let reply = req
             .dispatch()
-            .into_json::<GenericResponse>()
+            .into_json::<StopResponse>()
             .expect("Bad JSON");
 
+        // The detail summarizes the replay that was stopped:
+
         assert_eq!("OK", reply.status.as_str());
-        assert_eq!(String::from("").as_str(), reply.detail.as_str());
+        if let StopDetail::Summary(summary) = reply.detail {
+            assert_eq!("run-0000-00.par", summary.source);
+            assert_eq!("stopped", summary.end_reason);
+        } else {
+            panic!("Stop reply did not have a summary");
+        }
 
         teardown(chan, &papi, &bapi);
     }
@@ -454,6 +1539,590 @@ mod processing_tests {
         // check the value:
         assert_eq!(12345, papi.get_batching());
 
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn pause_1() {
+        // Not processing so pause fails:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+
+        papi.attach("run-0000-00.par").expect("attaching file");
+
+        let client = Client::tracked(rocket).expect("Creating client");
+        let req = client.get("/pause");
+        let reply = req
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+
+        assert_eq!("Failed to pause analysis", reply.status.as_str());
+        assert_eq!("Not processing data", reply.detail.as_str());
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn resume_1() {
+        // Not paused so resume fails:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+
+        papi.attach("run-0000-00.par").expect("attaching file");
+
+        let client = Client::tracked(rocket).expect("Creating client");
+        let req = client.get("/resume");
+        let reply = req
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+
+        assert_eq!("Failed to resume analysis", reply.status.as_str());
+        assert_eq!("Analysis is not paused", reply.detail.as_str());
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn status_1() {
+        // Attached but not started:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+
+        papi.attach("run-0000-00.par").expect("attaching file");
+
+        let client = Client::tracked(rocket).expect("Creating client");
+        let req = client.get("/status");
+        let reply = req
+            .dispatch()
+            .into_json::<AnalysisStatusResponse>()
+            .expect("Bad JSON");
+
+        assert_eq!("OK", reply.status.as_str());
+        assert_eq!("Inactive", reply.detail.state.as_str());
+        assert_eq!("file:run-0000-00.par", reply.detail.source.as_str());
+        assert_eq!(0, reply.detail.offset);
+        assert_eq!(0, reply.detail.items);
+        assert!(reply.detail.replays.is_empty());
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn status_2() {
+        // A replay that hits the end of the data is summarized:
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let path = dir.path().join("run.par");
+        let mut writer = ParameterFileWriter::new(
+            std::fs::File::create(&path).expect("Creating file"),
+            RingVersion::V11,
+        )
+        .expect("Making writer");
+        let mut defs = ParameterDefinitions::new();
+        defs.add(1, "a");
+        writer
+            .write_definitions(&defs)
+            .expect("Writing definitions");
+        for i in 0..100 {
+            let mut event = ParameterItem::new(i);
+            event.add(1, i as f64);
+            writer.write_event(&event).expect("Writing event");
+        }
+        writer.flush().expect("Flushing");
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        let name = path.to_str().unwrap();
+        papi.attach(name).expect("attaching file");
+        papi.start_analysis().expect("Starting analysis");
+        let start = std::time::Instant::now();
+        while papi.processing_state().expect("Getting state") != "Inactive" {
+            assert!(start.elapsed() < std::time::Duration::from_secs(10));
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let client = Client::tracked(rocket).expect("Creating client");
+        let reply = client
+            .get("/status")
+            .dispatch()
+            .into_json::<AnalysisStatusResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status.as_str());
+        assert_eq!(1, reply.detail.replays.len());
+
+        // The format item is skipped:
+
+        let summary = &reply.detail.replays[0];
+        assert_eq!(name, summary.source);
+        assert_eq!(100, summary.events);
+        assert_eq!(102, summary.items);
+        assert_eq!(1, summary.skipped);
+        assert_eq!("end of data", summary.end_reason);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn sample_1() {
+        // Histogram every 10th event with compensation:
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let path = dir.path().join("run.par");
+        let mut writer = ParameterFileWriter::new(
+            std::fs::File::create(&path).expect("Creating file"),
+            RingVersion::V11,
+        )
+        .expect("Making writer");
+        let mut defs = ParameterDefinitions::new();
+        defs.add(1, "a");
+        writer
+            .write_definitions(&defs)
+            .expect("Writing definitions");
+        for i in 0..100 {
+            let mut event = ParameterItem::new(i);
+            event.add(1, i as f64);
+            writer.write_event(&event).expect("Writing event");
+        }
+        writer.flush().expect("Flushing");
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        let param_api = messaging::parameter_messages::ParameterMessageClient::new(&chan);
+        param_api.create_parameter("a").expect("Creating parameter");
+        let spec_api = messaging::spectrum_messages::SpectrumMessageClient::new(&chan);
+        spec_api
+            .create_spectrum_1d("test", "a", 0.0, 100.0, 100)
+            .expect("Creating spectrum");
+
+        let client = Client::tracked(rocket).expect("Creating client");
+        let reply = client
+            .get("/sample?factor=10&compensate=true")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status.as_str());
+        assert_eq!((10, true), papi.get_sampling().expect("Getting sampling"));
+
+        let name = path.to_str().unwrap();
+        papi.attach(name).expect("attaching file");
+        papi.start_analysis().expect("Starting analysis");
+        let start = std::time::Instant::now();
+        while papi.processing_state().expect("Getting state") != "Inactive" {
+            assert!(start.elapsed() < std::time::Duration::from_secs(10));
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let reply = client
+            .get("/status")
+            .dispatch()
+            .into_json::<AnalysisStatusResponse>()
+            .expect("Bad JSON");
+        let summary = &reply.detail.replays[0];
+        assert_eq!(10, summary.events);
+        assert_eq!(90, summary.unsampled);
+
+        let reply = client
+            .get("/readstats")
+            .dispatch()
+            .into_json::<ReadStatisticsResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status.as_str());
+        assert_eq!(10, reply.detail.sample_factor);
+        assert!(reply.detail.sample_compensated);
+
+        // 10 events each weighted by 10:
+
+        let contents = spec_api
+            .get_contents("test", -1.0, 101.0, 0.0, 0.0)
+            .expect("Getting contents");
+        let sum: f64 = contents.iter().map(|c| c.value).sum();
+        assert_eq!(100.0, sum);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn sample_2() {
+        // A sampling factor of zero is an error:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+
+        let client = Client::tracked(rocket).expect("Creating client");
+        let reply = client
+            .get("/sample?factor=0")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("Failed to set event sampling", reply.status.as_str());
+        assert_eq!((1, false), papi.get_sampling().expect("Getting sampling"));
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn buffer_1() {
+        // Set the buffer size - readstats reports it:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+
+        papi.attach("run-0000-00.par").expect("attaching file");
+
+        let client = Client::tracked(rocket).expect("Creating client");
+        let req = client.get("/buffer?bytes=4096");
+        let reply = req
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status.as_str());
+        assert_eq!(4096, papi.get_buffer_size().expect("Getting buffer size"));
+
+        let req = client.get("/readstats");
+        let reply = req
+            .dispatch()
+            .into_json::<ReadStatisticsResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status.as_str());
+        assert_eq!(4096, reply.detail.buffer_size);
+        assert_eq!(0, reply.detail.bytes);
+        assert_eq!(0, reply.detail.items);
+        assert_eq!(0.0, reply.detail.read_rate);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn buffer_2() {
+        // A zero sized buffer is an error:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+
+        let client = Client::tracked(rocket).expect("Creating client");
+        let req = client.get("/buffer?bytes=0");
+        let reply = req
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("Failed to set read buffer size", reply.status.as_str());
+        assert_eq!(
+            processing::DEFAULT_READ_BUFFER_SIZE,
+            papi.get_buffer_size().expect("Getting buffer size")
+        );
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn timestamps_1() {
+        // Out of order timestamps are counted for each source:
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let path = dir.path().join("run.par");
+        let mut writer = ParameterFileWriter::new(
+            std::fs::File::create(&path).expect("Creating file"),
+            RingVersion::V11,
+        )
+        .expect("Making writer");
+        for (stamp, source) in [(10, 1), (20, 1), (5, 2), (15, 1), (30, 1), (6, 2), (1, 2)] {
+            writer
+                .write_item(&RingItem::new_with_body_header(
+                    ring_items::PHYSICS_EVENT,
+                    stamp,
+                    source,
+                    0,
+                ))
+                .expect("Writing item");
+        }
+        // Items without timestamps are not checked:
+
+        writer
+            .write_item(&RingItem::new_with_body_header(
+                ring_items::PHYSICS_EVENT,
+                processing::NULL_TIMESTAMP,
+                1,
+                0,
+            ))
+            .expect("Writing item");
+        writer.flush().expect("Flushing");
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        let client = Client::tracked(rocket).expect("Creating client");
+        let reply = client
+            .get("/timestamps")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status.as_str());
+
+        papi.attach(path.to_str().unwrap()).expect("attaching file");
+        papi.start_analysis().expect("Starting analysis");
+        let start = std::time::Instant::now();
+        while papi.processing_state().expect("Getting state") != "Inactive" {
+            assert!(start.elapsed() < std::time::Duration::from_secs(10));
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let reply = client
+            .get("/readstats")
+            .dispatch()
+            .into_json::<ReadStatisticsResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status.as_str());
+        assert!(reply.detail.verify_timestamps);
+        let ordering = &reply.detail.timestamp_ordering;
+        assert_eq!(2, ordering.len());
+        assert_eq!(1, ordering[0].source_id);
+        assert_eq!(4, ordering[0].items);
+        assert_eq!(1, ordering[0].out_of_order);
+        assert_eq!(30, ordering[0].last_timestamp);
+        assert_eq!(2, ordering[1].source_id);
+        assert_eq!(3, ordering[1].items);
+        assert_eq!(1, ordering[1].out_of_order);
+        assert_eq!(1, ordering[1].last_timestamp);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn timestamps_2() {
+        // Verification is off by default and can be turned off:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        let client = Client::tracked(rocket).expect("Creating client");
+
+        let reply = client
+            .get("/readstats")
+            .dispatch()
+            .into_json::<ReadStatisticsResponse>()
+            .expect("Bad JSON");
+        assert!(!reply.detail.verify_timestamps);
+
+        client.get("/timestamps?enable=true").dispatch();
+        assert!(papi.get_timestamp_ordering().expect("Getting ordering").0);
+        let reply = client
+            .get("/timestamps?enable=false")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status.as_str());
+        let (enabled, sources) = papi.get_timestamp_ordering().expect("Getting ordering");
+        assert!(!enabled);
+        assert!(sources.is_empty());
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn timestamps_3() {
+        // An item whose body header does not fit in it ends the replay
+        // with a read error rather than being checked:
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let path = dir.path().join("run.par");
+        let mut writer = ParameterFileWriter::new(
+            std::fs::File::create(&path).expect("Creating file"),
+            RingVersion::V11,
+        )
+        .expect("Making writer");
+        writer
+            .write_item(&RingItem::new_with_body_header(
+                ring_items::PHYSICS_EVENT,
+                10,
+                1,
+                0,
+            ))
+            .expect("Writing item");
+        writer.flush().expect("Flushing");
+        drop(writer);
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .expect("Opening file");
+        for long in [16, ring_items::PHYSICS_EVENT, 20, 0] {
+            file.write_all(&u32::to_ne_bytes(long))
+                .expect("Writing bad item");
+        }
+        drop(file);
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        papi.set_timestamp_verification(true)
+            .expect("Enabling verification");
+        papi.attach(path.to_str().unwrap()).expect("attaching file");
+        papi.start_analysis().expect("Starting analysis");
+        let start = std::time::Instant::now();
+        while papi.processing_state().expect("Getting state") != "Inactive" {
+            assert!(start.elapsed() < std::time::Duration::from_secs(10));
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let summary = papi.last_replay_summary().expect("Getting summary");
+        assert_eq!("read error: Invalid header", summary.end_reason);
+        let (_, sources) = papi.get_timestamp_ordering().expect("Getting ordering");
+        assert_eq!(1, sources.len());
+        assert_eq!(1, sources[0].items);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn endrun_1() {
+        // Setting, reporting and cancelling the policy:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        let client = Client::tracked(rocket).expect("Creating client");
+
+        let reply = client
+            .get("/endrun/status")
+            .dispatch()
+            .into_json::<EndRunStatusResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status.as_str());
+        assert!(reply.detail.policy.is_none());
+        assert!(reply.detail.last.is_none());
+
+        let reply = client
+            .get("/endrun?spectrum=a*&spectrum=b&file=run-%7Brun%7D.spec&format=ASCII&clear=true")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status.as_str());
+
+        let reply = client
+            .get("/endrun/status")
+            .dispatch()
+            .into_json::<EndRunStatusResponse>()
+            .expect("Bad JSON");
+        let policy = reply.detail.policy.expect("Should have a policy");
+        assert_eq!(vec!["a*", "b"], policy.spectra);
+        assert_eq!("run-{run}.spec", policy.file);
+        assert_eq!("ascii", policy.format);
+        assert!(policy.clear);
+
+        let reply = client
+            .get("/endrun/cancel")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status.as_str());
+        assert!(papi.get_end_run_write().policy.is_none());
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn endrun_2() {
+        // Invalid policies are rejected:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        let client = Client::tracked(rocket).expect("Creating client");
+
+        for uri in [
+            "/endrun?file=run-%7Brun%7D.json",
+            "/endrun?spectrum=a&file=run.json",
+            "/endrun?spectrum=a&file=run-%7Brun%7D.npy&format=npy",
+        ] {
+            let reply = client
+                .get(uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Bad JSON");
+            assert_eq!(
+                "Failed to set end of run write",
+                reply.status.as_str(),
+                "{}",
+                uri
+            );
+        }
+        assert!(papi.get_end_run_write().policy.is_none());
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn endrun_3() {
+        // Replay two runs, each is written to its own file and cleared:
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let path = dir.path().join("runs.par");
+        let mut writer = ParameterFileWriter::new(
+            std::fs::File::create(&path).expect("Creating file"),
+            RingVersion::V11,
+        )
+        .expect("Making writer");
+        let mut defs = ParameterDefinitions::new();
+        defs.add(1, "a");
+        writer
+            .write_definitions(&defs)
+            .expect("Writing definitions");
+        for (run, events) in [(5, 10), (6, 20)] {
+            for change in [StateChangeType::Begin, StateChangeType::End] {
+                if change == StateChangeType::End {
+                    for i in 0..events {
+                        let mut event = ParameterItem::new(i);
+                        event.add(1, 1.0);
+                        writer.write_event(&event).expect("Writing event");
+                    }
+                }
+                let item = StateChange::new(change, None, run, 0, 1, "A run", None);
+                writer.write_item(&item.to_raw()).expect("Writing item");
+            }
+        }
+        writer.flush().expect("Flushing");
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        let param_api = messaging::parameter_messages::ParameterMessageClient::new(&chan);
+        param_api.create_parameter("a").expect("Creating parameter");
+        let spec_api = messaging::spectrum_messages::SpectrumMessageClient::new(&chan);
+        spec_api
+            .create_spectrum_1d("test", "a", 0.0, 10.0, 10)
+            .expect("Creating spectrum");
+
+        let client = Client::tracked(rocket).expect("Creating client");
+        let pattern = dir.path().join("run-{run}.json");
+        let uri = format!(
+            "/endrun?spectrum=test&file={}&clear=true",
+            pattern.to_str().unwrap().replace("{run}", "%7Brun%7D")
+        );
+        let reply = client
+            .get(uri)
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status.as_str());
+
+        papi.attach(path.to_str().unwrap()).expect("attaching file");
+        papi.start_analysis().expect("Starting analysis");
+        let start = std::time::Instant::now();
+        while papi.processing_state().expect("Getting state") != "Inactive" {
+            assert!(start.elapsed() < std::time::Duration::from_secs(10));
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        for (run, events) in [(5, 10), (6, 20)] {
+            let file = dir.path().join(format!("run-{}.json", run));
+            let text = std::fs::read_to_string(&file).expect("Reading run file");
+            let spectra: Vec<spectrumio::SpectrumFileData> =
+                rocket::serde::json::from_str(&text).expect("Parsing run file");
+            assert_eq!(1, spectra.len());
+            let sum: u64 = spectra[0].channels.iter().map(|c| c.value).sum();
+            assert_eq!(events, sum, "run {}", run);
+        }
+        let contents = spec_api
+            .get_contents("test", -1.0, 11.0, 0.0, 0.0)
+            .expect("Getting contents");
+        assert!(contents.is_empty());
+
+        let reply = client
+            .get("/endrun/status")
+            .dispatch()
+            .into_json::<EndRunStatusResponse>()
+            .expect("Bad JSON");
+        let last = reply.detail.last.expect("Should have a last write");
+        assert_eq!(6, last.run);
+        assert_eq!(1, last.spectra);
+        assert!(last.cleared);
+        assert!(last.error.is_none());
+
         teardown(chan, &papi, &bapi);
     }
 }
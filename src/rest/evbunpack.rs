@@ -1,79 +1,233 @@
 //!  Implements handlers for the /spectcl/evbunpack domain.
-//!  In SpecTcl, this sets up event decoders for event built data
-//!  In our case, however, this is unimplemented/not necessary
-//!  because we deal only with data that has already been broken down
-//!  into decoded parameter data.  That is the parameters extracted
-//!  from event built data.
+//!  In SpecTcl, this sets up event decoders for event built data.
+//!  Rustogramer can do the same for raw physics events from the
+//!  NSCLDAQ event builder; see src/unpackers/evb.rs for how fragments
+//!  are split by source id and how the parameters are named.
 //!
 //!  subdomains are:
 //!
-//! *   create - create a new event built data unpacker.,
-//! *   add - Add a processor for a source id to an event built
+//! *   create - create a new event built data unpacker.
+//! *   add - Add an unpacker for a source id to an event built
 //! unpacker.
 //! *   list - List the event built data unpackers that have been
 //! created.
+//! *   use - (Rustogramer only) Choose the event built data unpacker
+//!     the processing thread applies to physics events.  This replaces
+//!     any unpacker chosen with /spectcl/unpacker/use.
 //!
-//!  All of this is, in SpecTcl coupled to the dynamically controlled
-//! event processing pipeline which is not needed in Rustogramer
-//! because data are already  unpacked into a mechanically
-//! usable form.
+//!  SpecTcl couples these to its dynamically controlled event
+//!  processing pipeline.  Rustogramer has no pipeline; instead, one
+//!  unpacker (see src/rest/unpacker.rs) can be in use at a time.  Physics events read
+//!  from the data source are then unpacked by it.  Data sources that
+//!  are parameter files are analyzed as always.
+//!
+//!  We depend on the main to have set the Rocket state to include an
+//!  EvbUnpackers.
+//!
+use glob::Pattern;
 use rocket::serde::json::Json;
+use rocket::State;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
 
 use super::*; // For GenericResponse.
+use crate::unpackers::evb;
+
+/// Default number of parameters an unpacker added for a source
+/// produces.
+pub const DEFAULT_SOURCE_PARAMETERS: usize = 32;
+
+/// The event built data unpackers keyed by name.  Which, if any, is
+/// in use is known by the processing thread.
+#[derive(Default)]
+pub struct EvbUnpackers {
+    unpackers: Mutex<BTreeMap<String, evb::EvbDescription>>,
+}
+
+impl EvbUnpackers {
+    pub fn new() -> EvbUnpackers {
+        EvbUnpackers::default()
+    }
+}
 
-/// create.   If this were implemented, it would require the
-/// following parameters
+/// create an event built data unpacker.
+///
+/// #### Query Parameters
 ///
-/// * name (mandatory) - name of the new unpacker.
+/// * name (mandatory) - name of the new unpacker, must be unique.
 /// * frequency (mandatory) - event builder clock frequency in MHz,
-/// this would be used to generate diagnostic parameters.
-/// * basename - parameter base name for the diagnostic parameters.
+///   used to convert fragment timestamps to seconds.
+/// * basename (mandatory) - parameter base name of the unpacker's
+///   parameters.
 ///
-/// A GenericResponse would likely be generated if implemented.
-#[get("/create")]
-pub fn create_evbunpack() -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "/spectcl/evbunpack/create is not implemented",
-        "This is not SpecTcl",
-    ))
+/// #### Returns
+/// GenericResponse
+#[get("/create?<name>&<frequency>&<basename>")]
+pub fn create_evbunpack(
+    name: String,
+    frequency: f64,
+    basename: String,
+    unpackers: &State<EvbUnpackers>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    let mut table = unpackers.unpackers.lock().unwrap();
+    if table.contains_key(&name) {
+        return Json(GenericResponse::err(
+            "evbunpack create command failed",
+            &format!("The event built data unpacker {} already exists", name),
+        ));
+    }
+    match evb::EvbDescription::new(&name, frequency, &basename) {
+        Ok(description) => {
+            table.insert(name, description);
+            Json(GenericResponse::ok(""))
+        }
+        Err(s) => Json(GenericResponse::err("evbunpack create command failed", &s)),
+    }
 }
-/// add.   If this were implemented; it would require the following
-/// query parameters:
+/// add an unpacker for a source id.
+///
+/// #### Query Parameters
 ///
-/// *   name - name of the event processor being manipulated.
-/// *   source - source id we're adding a processing pipeline for.
-/// *   pipe - Name of the event processing pipeline that will handle
-/// data from that source.
+/// *   evpname - name of the event built data unpacker.
+/// *   source - source id whose fragments are unpacked.
+/// *   pipe - name of the registered unpacker for the fragments
+///   (see /spectcl/unpacker/list).  A source that already has an
+///   unpacker gets this one instead.
+/// *   parameters - (Rustogramer only) maximum number of parameters
+///   the unpacker produces.  Defaults to DEFAULT_SOURCE_PARAMETERS.
 ///
-/// A GenericResponse would likely still be returned.
+/// If the event built data unpacker is in use, the processing thread
+/// starts using the new unpacker at once.
 ///
-#[get("/add")]
-pub fn add_evbunpack() -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "/spectcl/evbunpack/add is not implemented",
-        "This is not SpecTcl",
-    ))
+/// #### Returns
+/// GenericResponse
+#[get("/add?<evpname>&<source>&<pipe>&<parameters>")]
+#[allow(clippy::too_many_arguments)]
+pub fn add_evbunpack(
+    evpname: String,
+    source: u32,
+    pipe: String,
+    parameters: Option<usize>,
+    unpackers: &State<EvbUnpackers>,
+    papi: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    let mut table = unpackers.unpackers.lock().unwrap();
+    let mut description = match table.get(&evpname) {
+        Some(d) => d.clone(),
+        None => {
+            return Json(GenericResponse::err(
+                "evbunpack addprocessor command failed",
+                &format!("There is no event built data unpacker named {}", evpname),
+            ))
+        }
+    };
+    let api = papi.inner().lock().unwrap();
+    if let Err(s) = description.set_source(
+        &api.unpacker_registry(),
+        source,
+        &pipe,
+        parameters.unwrap_or(DEFAULT_SOURCE_PARAMETERS),
+    ) {
+        return Json(GenericResponse::err(
+            "evbunpack addprocessor command failed",
+            &s,
+        ));
+    }
+    let in_use = format!("{}{}", processing::EVB_UNPACKER_PREFIX, evpname);
+    if api.get_unpacker() == Ok(Some(in_use)) {
+        if let Err(s) = api.set_evb_unpacker(Some(description.clone())) {
+            return Json(GenericResponse::err(
+                "evbunpack addprocessor command failed",
+                &s,
+            ));
+        }
+    }
+    table.insert(evpname, description);
+    Json(GenericResponse::ok(""))
 }
 
-/// list.  If this were implemented; it would take an optional
-/// query parameter _pattern_ which would restrict the names of
-/// the patterns matched to add to the listing.
+/// list the event built data unpackers.
 ///
-/// If implemented this would return Json that is
-/// has a detail that constis of an array of evb event processor
-/// names that have been created, or otherwise known to the system.
+/// #### Query Parameters
 ///
-#[get("/list")]
-pub fn list_evbunpack() -> Json<StringArrayResponse> {
-    Json(StringArrayResponse::new(
-        "/spectcl/evbunpack/list is not implemented - this is not SpecTcl",
-    ))
+/// * pattern - optional glob pattern the names listed must match.
+///   Defaults to "*".
+///
+/// #### Returns
+/// StringArrayResponse whose detail are the names, sorted.
+///
+#[get("/list?<pattern>")]
+pub fn list_evbunpack(
+    pattern: OptionalString,
+    unpackers: &State<EvbUnpackers>,
+) -> Json<StringArrayResponse> {
+    let pattern = pattern.unwrap_or_else(|| String::from("*"));
+    let pattern = match Pattern::new(&pattern) {
+        Ok(p) => p,
+        Err(e) => {
+            return Json(StringArrayResponse::new(&format!(
+                "Bad glob pattern {}: {}",
+                pattern, e.msg
+            )))
+        }
+    };
+    let mut result = StringArrayResponse::new("OK");
+    result.detail = unpackers
+        .unpackers
+        .lock()
+        .unwrap()
+        .keys()
+        .filter(|n| pattern.matches(n))
+        .cloned()
+        .collect();
+    Json(result)
+}
+/// use an event built data unpacker (Rustogramer only).
+///
+/// #### Query Parameters
+///
+/// * name - name of the event built data unpacker the processing
+///   thread unpacks physics events with.  If omitted, physics events
+///   are no longer unpacked.
+///
+/// #### Returns
+/// GenericResponse
+#[get("/use?<name>")]
+pub fn use_evbunpack(
+    name: OptionalString,
+    unpackers: &State<EvbUnpackers>,
+    papi: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    let description = match &name {
+        Some(n) => match unpackers.unpackers.lock().unwrap().get(n) {
+            Some(d) => Some(d.clone()),
+            None => {
+                return Json(GenericResponse::err(
+                    "evbunpack use command failed",
+                    &format!("There is no event built data unpacker named {}", n),
+                ))
+            }
+        },
+        None => None,
+    };
+    match papi.inner().lock().unwrap().set_evb_unpacker(description) {
+        Ok(_) => Json(GenericResponse::ok("")),
+        Err(s) => Json(GenericResponse::err("evbunpack use command failed", &s)),
+    }
 }
 #[cfg(test)]
 mod evb_unpack_tests {
     use super::*;
     use crate::messaging;
     use crate::processing;
+    use crate::ring_items::analysis_ring_items::ParameterFileWriter;
+    use crate::ring_items::event_item::PhysicsEvent;
+    use crate::ring_items::{RingVersion, ToRaw};
     use crate::sharedmem::binder;
 
     use rocket;
@@ -84,12 +238,15 @@ mod evb_unpack_tests {
     use crate::test::rest_common;
     use std::sync::mpsc;
 
-    // note these are all unimplemented URLS so...
-
     fn setup() -> Rocket<Build> {
-        rest_common::setup().mount(
+        rest_common::setup().manage(EvbUnpackers::new()).mount(
             "/",
-            routes![create_evbunpack, add_evbunpack, list_evbunpack],
+            routes![
+                create_evbunpack,
+                add_evbunpack,
+                list_evbunpack,
+                use_evbunpack
+            ],
         )
     }
     fn teardown(
@@ -108,6 +265,20 @@ mod evb_unpack_tests {
     ) {
         rest_common::get_state(r)
     }
+    fn get(client: &Client, uri: &str) -> GenericResponse {
+        client
+            .get(uri)
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON")
+    }
+    fn list(client: &Client, uri: &str) -> StringArrayResponse {
+        client
+            .get(uri)
+            .dispatch()
+            .into_json::<StringArrayResponse>()
+            .expect("Bad JSON")
+    }
 
     #[test]
     fn create_1() {
@@ -115,17 +286,33 @@ mod evb_unpack_tests {
         let (r, papi, bapi) = get_state(&rocket);
 
         let client = Client::tracked(rocket).expect("Failed to make client");
-        let req = client.get("/create");
-        let reply = req
-            .dispatch()
-            .into_json::<GenericResponse>()
-            .expect("Bad JSON");
+        let reply = get(&client, "/create?name=test&frequency=100&basename=evb");
+        assert_eq!("OK", reply.status.as_str());
+
+        let reply = list(&client, "/list");
+        assert_eq!("OK", reply.status.as_str());
+        assert_eq!(vec![String::from("test")], reply.detail);
+
+        teardown(r, &papi, &bapi);
+    }
+    #[test]
+    fn create_2() {
+        // Duplicate names and bad frequencies fail:
+
+        let rocket = setup();
+        let (r, papi, bapi) = get_state(&rocket);
 
+        let client = Client::tracked(rocket).expect("Failed to make client");
+        get(&client, "/create?name=test&frequency=100&basename=evb");
+        let reply = get(&client, "/create?name=test&frequency=100&basename=evb");
+        assert_eq!("evbunpack create command failed", reply.status.as_str());
         assert_eq!(
-            "/spectcl/evbunpack/create is not implemented",
-            reply.status.as_str()
+            "The event built data unpacker test already exists",
+            reply.detail.as_str()
         );
-        assert_eq!("This is not SpecTcl", reply.detail.as_str());
+        let reply = get(&client, "/create?name=other&frequency=0&basename=evb");
+        assert_eq!("evbunpack create command failed", reply.status.as_str());
+        assert_eq!(1, list(&client, "/list").detail.len());
 
         teardown(r, &papi, &bapi);
     }
@@ -135,17 +322,31 @@ mod evb_unpack_tests {
         let (r, papi, bapi) = get_state(&rocket);
 
         let client = Client::tracked(rocket).expect("Failed to make client");
-        let req = client.get("/add");
-        let reply = req
-            .dispatch()
-            .into_json::<GenericResponse>()
-            .expect("Bad JSON");
+        get(&client, "/create?name=test&frequency=100&basename=evb");
+        let reply = get(&client, "/add?evpname=test&source=2&pipe=words");
+        assert_eq!("OK", reply.status.as_str());
+        let reply = get(
+            &client,
+            "/add?evpname=test&source=5&pipe=longs&parameters=4",
+        );
+        assert_eq!("OK", reply.status.as_str());
+
+        // No such unpacker or pipe:
 
+        let reply = get(&client, "/add?evpname=none&source=2&pipe=words");
         assert_eq!(
-            "/spectcl/evbunpack/add is not implemented",
+            "evbunpack addprocessor command failed",
+            reply.status.as_str()
+        );
+        assert_eq!(
+            "There is no event built data unpacker named none",
+            reply.detail.as_str()
+        );
+        let reply = get(&client, "/add?evpname=test&source=2&pipe=bytes");
+        assert_eq!(
+            "evbunpack addprocessor command failed",
             reply.status.as_str()
         );
-        assert_eq!("This is not SpecTcl", reply.detail.as_str());
 
         teardown(r, &papi, &bapi);
     }
@@ -155,17 +356,152 @@ mod evb_unpack_tests {
         let (r, papi, bapi) = get_state(&rocket);
 
         let client = Client::tracked(rocket).expect("Failed to make client");
-        let req = client.get("/list");
-        let reply = req
-            .dispatch()
-            .into_json::<StringArrayResponse>()
-            .expect("Bad JSON");
+        let reply = list(&client, "/list");
+        assert_eq!("OK", reply.status.as_str());
+        assert_eq!(0, reply.detail.len());
+
+        for name in ["s800", "lenda", "greta"] {
+            get(
+                &client,
+                &format!("/create?name={}&frequency=100&basename={}", name, name),
+            );
+        }
+        let reply = list(&client, "/list");
+        assert_eq!(vec!["greta", "lenda", "s800"], reply.detail);
+        let reply = list(&client, "/list?pattern=*e*");
+        assert_eq!(vec!["greta", "lenda"], reply.detail);
+        let reply = list(&client, "/list?pattern=[");
+        assert!(reply.status.starts_with("Bad glob pattern"));
+
+        teardown(r, &papi, &bapi);
+    }
+    #[test]
+    fn use_1() {
+        // Using an unpacker defines its parameters:
+
+        let rocket = setup();
+        let (r, papi, bapi) = get_state(&rocket);
 
+        let client = Client::tracked(rocket).expect("Failed to make client");
+        let reply = get(&client, "/use?name=test");
+        assert_eq!("evbunpack use command failed", reply.status.as_str());
+
+        get(&client, "/create?name=test&frequency=100&basename=evb");
+        get(
+            &client,
+            "/add?evpname=test&source=2&pipe=words&parameters=2",
+        );
+        let reply = get(&client, "/use?name=test");
+        assert_eq!("OK", reply.status.as_str());
+
+        let param_api = messaging::parameter_messages::ParameterMessageClient::new(&r);
+        let mut names: Vec<String> = param_api
+            .list_parameters("evb.*")
+            .expect("Listing parameters")
+            .iter()
+            .map(|p| p.get_name())
+            .collect();
+        names.sort();
         assert_eq!(
-            "/spectcl/evbunpack/list is not implemented - this is not SpecTcl",
-            reply.status.as_str()
+            vec![
+                "evb.2.timestamp",
+                "evb.2.words.000",
+                "evb.2.words.001",
+                "evb.fragments"
+            ],
+            names
         );
-        assert_eq!(0, reply.detail.len());
+
+        // Adding to an unpacker in use updates the processing thread:
+
+        get(
+            &client,
+            "/add?evpname=test&source=3&pipe=longs&parameters=1",
+        );
+        assert_eq!(
+            1,
+            param_api
+                .list_parameters("evb.3.longs.*")
+                .expect("Listing parameters")
+                .len()
+        );
+
+        let reply = get(&client, "/use");
+        assert_eq!("OK", reply.status.as_str());
+
+        teardown(r, &papi, &bapi);
+    }
+    #[test]
+    fn analyze_1() {
+        // Event built physics events are unpacked into spectra:
+
+        fn fragment(words: &[u16]) -> Vec<u8> {
+            let mut event = PhysicsEvent::new(None);
+            for w in words {
+                event.add(*w);
+            }
+            let mut bytes = vec![];
+            event.to_raw().write_item(&mut bytes).unwrap();
+            bytes
+        }
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let path = dir.path().join("run.evt");
+        let mut writer = ParameterFileWriter::new(
+            std::fs::File::create(&path).expect("Creating file"),
+            RingVersion::V11,
+        )
+        .expect("Making writer");
+        for i in 0..10_u16 {
+            let mut event = PhysicsEvent::new(None);
+            for b in evb::build_body(&[
+                (100, 2, fragment(&[i, 2 * i])),
+                (100, 7, fragment(&[1])), // no unpacker.
+            ]) {
+                event.add(b);
+            }
+            writer.write_item(&event.to_raw()).expect("Writing event");
+        }
+        writer.flush().expect("Flushing");
+
+        let rocket = setup();
+        let (r, papi, bapi) = get_state(&rocket);
+        let client = Client::tracked(rocket).expect("Failed to make client");
+        get(&client, "/create?name=test&frequency=100&basename=evb");
+        get(
+            &client,
+            "/add?evpname=test&source=2&pipe=words&parameters=2",
+        );
+        assert_eq!("OK", get(&client, "/use?name=test").status.as_str());
+
+        let spec_api = messaging::spectrum_messages::SpectrumMessageClient::new(&r);
+        spec_api
+            .create_spectrum_1d("w1", "evb.2.words.001", 0.0, 20.0, 20)
+            .expect("Creating spectrum");
+        spec_api
+            .create_spectrum_1d("frags", "evb.fragments", 0.0, 4.0, 4)
+            .expect("Creating spectrum");
+
+        papi.attach(path.to_str().unwrap()).expect("attaching file");
+        papi.start_analysis().expect("Starting analysis");
+        let start = std::time::Instant::now();
+        while papi.processing_state().expect("Getting state") != "Inactive" {
+            assert!(start.elapsed() < std::time::Duration::from_secs(10));
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let contents = spec_api
+            .get_contents("w1", -1.0, 21.0, 0.0, 0.0)
+            .expect("Getting contents");
+        let channels: Vec<(f64, f64)> = contents.iter().map(|c| (c.x, c.value)).collect();
+        let expected: Vec<(f64, f64)> = (0..10).map(|i| (2.0 * i as f64, 1.0)).collect();
+        assert_eq!(expected, channels);
+
+        let contents = spec_api
+            .get_contents("frags", -1.0, 5.0, 0.0, 0.0)
+            .expect("Getting contents");
+        let channels: Vec<(f64, f64)> = contents.iter().map(|c| (c.x, c.value)).collect();
+        assert_eq!(vec![(2.0, 10.0)], channels);
 
         teardown(r, &papi, &bapi);
     }
@@ -1,6 +1,7 @@
 //! Supports the /spectcl/exit URL.
 //! this provides the ability to, in order:
 //!
+//! * Save the analysis session if one was named on the command line.
 //! * Shutdown the rustogramer threads via their APIs.
 //! * Reply to the caller that, yes we're shutting down.
 //! * Notify rocket that when the request is complete it too should
@@ -12,8 +13,13 @@ use rocket::Shutdown;
 
 use super::*; // For generic response.
 use crate::histogramer;
+use crate::session;
 use crate::sharedmem::binder::BindingApi;
+use crate::simulation;
+use crate::tags;
+use crate::templates;
 use crate::trace;
+use crate::treevariables;
 use std::fs;
 use std::net::TcpStream;
 use std::path::Path;
@@ -30,12 +36,22 @@ pub fn shutdown(
     b_chan: &State<SharedBinderChannel>,
     p_api: &State<SharedProcessingApi>,
     tracedb: &State<trace::SharedTraceStore>,
+    simulator: &State<simulation::Simulator>,
+    session: &State<session::SessionConfig>,
+    treevariables: &State<treevariables::SharedTreeVariableStore>,
+    templates: &State<templates::SharedTemplateStore>,
+    tags: &State<tags::SharedTagStore>,
+    _writable: readonly::Writable,
 ) -> Json<GenericResponse> {
     // Stop the trace prune thread (or rather schedule it to stop - within
     // one second it will stop).
 
     tracedb.inner().stop_prune();
 
+    // Stop any simulation as it feeds the histogrammer:
+
+    simulator.inner().stop();
+
     // Shutdown the processor:
 
     let prc_api = p_api.inner().lock().unwrap();
@@ -45,6 +61,20 @@ pub fn shutdown(
             s
         );
     }
+    // Now that nothing is feeding the histogramer, save the session.
+    // Failure is reported but must not prevent the exit.
+
+    match session.inner().save(
+        &hg_chan.inner().lock().unwrap(),
+        &b_chan.inner().lock().unwrap(),
+        treevariables.inner(),
+        templates.inner(),
+        tags.inner(),
+    ) {
+        Ok(Some(path)) => println!("Saved session to {}", path.display()),
+        Ok(None) => {}
+        Err(s) => println!("Failed to save session: {}", s),
+    }
     // Kill off the mirror server:...again ignore errors.
 
     let _ = state.inner().mirror_exit.lock().unwrap().send(true); // ignore errors;
@@ -56,10 +86,13 @@ pub fn shutdown(
 
     match bind_api.exit() {
         Ok(s) => {
-            // Let the thread exit first...
+            // Let the thread exit first... There's no backing store if the
+            // shared memory could not be created.
             thread::sleep(time::Duration::from_millis(500));
-            if let Err(e) = fs::remove_file(Path::new(&s)) {
-                println!("Failed to remove shared memory backing store {}: {}", s, e);
+            if !s.is_empty() {
+                if let Err(e) = fs::remove_file(Path::new(&s)) {
+                    println!("Failed to remove shared memory backing store {}: {}", s, e);
+                }
             }
         }
         Err(s) => {
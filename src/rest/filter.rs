@@ -1,120 +1,210 @@
-//! This module implements, the /spectcl/filter domain of URIS,
-//! or rather documents to the client that these are no implemented for Rustogramer
-//! These URIS are not implemented because Rustogramer does not,
-//! and probably never will, implement filters.  It operates on what is,
-//! essentially, already filtered data.
+//! This module implements the /spectcl/filter domain of URIS.
 //!
 //!  Filters in SpecTcl perform two functions:
-//!  
+//!
 //! *   Provide data in an already decoded format for speedy playback.
 //! *   Provide some subset of the  full data set (in SpecTcl this subset is
 //! defined by events that satisfy a condition and parameter list).
 //!
 //!  The first of these function is provided already by the fact that
 //! Rustogramer operates on data that is the output of the analysis
-//! pipeline (that is already decoded data).  
-//!
-//! The second of these functions would be provided by using
-//! The analysis pipeline to filter out events you don't want rustogramer
-//! to see.
+//! pipeline (that is already decoded data).  Rustogramer filters
+//! provide the second; they write the events that satisfy a gate
+//! to a parameter file that can be analyzed again (see
+//! processing::filter).
 //!
 //!  The following URIS are caught within the /spectcl/filter domain:
 //!
-//! *  new - would create a new filter.
-//! *  delete - Would delete an existing filter.
-//! *  enable - would enable an existing filter to output data.
-//! *  disable - would disable an existing filter from outputting data.
-//! *  regate - would replace the condition on an existing filter that determines
+//! *  new - creates a new filter.
+//! *  delete - deletes an existing filter.
+//! *  enable - enables an existing filter to output data.
+//! *  disable - disables an existing filter from outputting data.
+//! *  regate - replaces the condition on an existing filter that determines
 //! which subset it writes.
 //! *  file - Defines the file an existing filter writes data to.
 //! *  list - lists the set of filters that match an optional Glob pattern.
 //! *  format - selects the output format for an existing filter.
 //!
+//! The filters are held by the processing thread, so we depend on the
+//! main to have set the Rocket state to include the processing API.
+//!
 use super::*;
+use crate::messaging::condition_messages::{ConditionMessageClient, ConditionReply};
+use crate::processing::filter;
+use glob::Pattern;
 use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+
+// Check that a gate exists:
+
+fn check_gate(hg: &State<SharedHistogramChannel>, gate: &str) -> Result<(), String> {
+    let api = ConditionMessageClient::new(&hg.inner().lock().unwrap());
+    match api.list_conditions(&Pattern::escape(gate)) {
+        ConditionReply::Listing(l) if l.is_empty() => {
+            Err(format!("The gate {} does not exist", gate))
+        }
+        ConditionReply::Listing(_) => Ok(()),
+        ConditionReply::Error(s) => Err(s),
+        _ => Err(String::from("Unexpected reply listing gates")),
+    }
+}
+// Look up the histogramer ids of parameters by name:
+
+fn parameter_ids(
+    hg: &State<SharedHistogramChannel>,
+    names: &[String],
+) -> Result<Vec<(String, u32)>, String> {
+    let api = ParameterMessageClient::new(&hg.inner().lock().unwrap());
+    let mut result = Vec::new();
+    for name in names {
+        match api.list_parameters(&Pattern::escape(name))?.first() {
+            Some(p) => result.push((name.clone(), p.get_id())),
+            None => return Err(format!("The parameter {} does not exist", name)),
+        }
+    }
+    Ok(result)
+}
+// Apply an operation to the filters:
+
+fn with_filters<F>(papi: &State<SharedProcessingApi>, op: F) -> Result<(), String>
+where
+    F: FnOnce(&mut filter::FilterSet) -> Result<(), String>,
+{
+    let filters = papi.inner().lock().unwrap().filters();
+    let mut filters = filters.lock().unwrap();
+    op(&mut filters)
+}
+fn response(result: Result<(), String>, failure: &str) -> Json<GenericResponse> {
+    Json(match result {
+        Ok(()) => GenericResponse::ok(""),
+        Err(msg) => GenericResponse::err(failure, &msg),
+    })
+}
 
-/// new - would create a new filter.  Parameters, if implemented
-/// are:
+/// new - creates a new filter.  The filter is disabled and has no
+/// file.  Query parameters:
 ///
 /// *   name the name of the new filter.
 /// *   gate - the condition that will select the event the filter outputs.
 /// *   parameter - can repeat as many times as needed -the set of parameters
 /// that will be output.
 ///
-/// In the implementation a GenericResponse would also likely be
-/// returned.
-///
-#[get("/new")]
-pub fn new() -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "/spectcl/filter/new is not implemented",
-        "This is not SpecTcl",
-    ))
+#[get("/new?<name>&<gate>&<parameter>")]
+pub fn new(
+    name: String,
+    gate: String,
+    parameter: OptionalStringVec,
+    hg: &State<SharedHistogramChannel>,
+    papi: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    let result = check_gate(hg, &gate)
+        .and_then(|_| parameter_ids(hg, &parameter.unwrap_or_default()))
+        .and_then(|parameters| with_filters(papi, |f| f.add(&name, &gate, &parameters)));
+    response(result, "Unable to create filter")
 }
-/// delete - deletes an existing filter.  The only parameter
-/// required is the name of the filter.  Note that a Generic
-/// response would be used if this is implemented.
+/// delete - deletes an existing filter, closing its file.  The only
+/// query parameter is the name of the filter.
 ///
-#[get("/delete")]
-pub fn delete() -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "/spectcl/filter/delete is not implemented",
-        "This is not SpecTcl",
-    ))
+#[get("/delete?<name>")]
+pub fn delete(
+    name: String,
+    papi: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    response(
+        with_filters(papi, |f| f.delete(&name)),
+        "Unable to delete filter",
+    )
 }
-/// enable - would enable an existing filter.  The only
-/// query parameter is the name of the filter to enable.
-/// If implemented a GenericResponse would be used.
+/// enable - enables an existing filter.  The only query parameter is
+/// the name of the filter to enable.  The filter must have a file.
 ///
-#[get("/enable")]
-pub fn enable() -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "/spectcl/filter/enable is not implemented",
-        "This is not SpecTcl",
-    ))
+#[get("/enable?<name>")]
+pub fn enable(
+    name: String,
+    papi: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    response(
+        with_filters(papi, |f| f.enable(&name)),
+        "Unable to enable filter",
+    )
 }
-/// disable - would disable an existing filter.  Only the
-/// name of the filter is required as a query parameter.
+/// disable - disables an existing filter, flushing its file.  Only
+/// the name of the filter is required as a query parameter.
 ///
-#[get("/disable")]
-pub fn disable() -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "/spectcl/filter/disable is not implemented",
-        "This is not SpecTcl",
-    ))
+#[get("/disable?<name>")]
+pub fn disable(
+    name: String,
+    papi: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    response(
+        with_filters(papi, |f| f.disable(&name)),
+        "Unable to disable filter",
+    )
 }
-/// regate - would specify a new condition be used to select the
-/// set of events written by the filter.
+/// regate - specifies a new condition to select the set of events
+/// written by the filter.
 /// Query parameters;
 ///
 /// *   name - Name of the filter to modify.
 /// *   gate - condition to use to select output events
 ///
-/// A GenericResponse is fine for this if it were implemented.
-///
-#[get("/regate")]
-pub fn regate() -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "/spectcl/filter/regate is not implemented",
-        "This is not SpecTcl",
-    ))
+#[get("/regate?<name>&<gate>")]
+pub fn regate(
+    name: String,
+    gate: String,
+    hg: &State<SharedHistogramChannel>,
+    papi: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    let result = check_gate(hg, &gate).and_then(|_| with_filters(papi, |f| f.regate(&name, &gate)));
+    response(result, "Unable to regate filter")
 }
 /// file - set the otput file for the filter.
-/// The query parameters would be:
+/// The query parameters are:
 ///
 /// *  name -filter name.
 /// * file - name of the new output file for the filter.
 ///
-#[get("/file")]
-pub fn file() -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "/spectcl/filter/file is not implemented",
-        "This is not SpecTcl",
-    ))
+#[get("/file?<name>&<file>")]
+pub fn file(
+    name: String,
+    file: String,
+    papi: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    response(
+        with_filters(papi, |f| f.set_file(&name, &file)),
+        "Unable to set filter file",
+    )
+}
+/// format - set the output format for the filter.
+/// The query parameters are:
+///
+/// * name - filter name.
+/// * format - "ring11" or "ring12".
+///
+#[get("/format?<name>&<format>")]
+pub fn format(
+    name: String,
+    format: String,
+    papi: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    response(
+        with_filters(papi, |f| f.set_format(&name, &format)),
+        "Unable to set filter format",
+    )
 }
 
 //----------------------------------------------------------------
 
+// enabled is "enabled" or "disabled" as in SpecTcl.  events and
+// error are Rustogramer only.
+
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct FilterDetail {
@@ -122,8 +212,10 @@ pub struct FilterDetail {
     gate: String,
     file: String,
     parameters: Vec<String>,
-    enabled: bool,
+    enabled: String,
     format: String,
+    events: u64,
+    error: Option<String>,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
@@ -133,15 +225,37 @@ pub struct FilterListResponse {
 }
 
 /// list - lists the filters that match an optional
-/// _pattern_ query parameter.  The FilterListResponse
-/// struct defined above describes what the successful listing
-/// response would be if this were ever implemented.
+/// _pattern_ query parameter (defaults to "*").
 ///
-#[get("/list")]
-pub fn list() -> Json<FilterListResponse> {
-    Json(FilterListResponse {
-        status: String::from("/spectcl/filter/list is not implemented - this is not SpecTcl"),
-        detail: vec![],
+#[get("/list?<pattern>")]
+pub fn list(
+    pattern: OptionalString,
+    papi: &State<SharedProcessingApi>,
+) -> Json<FilterListResponse> {
+    let pattern = pattern.unwrap_or_else(|| String::from("*"));
+    let filters = papi.inner().lock().unwrap().filters();
+    let listing = filters.lock().unwrap().list(&pattern);
+    Json(match listing {
+        Ok(l) => FilterListResponse {
+            status: String::from("OK"),
+            detail: l
+                .into_iter()
+                .map(|f| FilterDetail {
+                    name: f.name,
+                    gate: f.gate,
+                    file: f.file.unwrap_or_default(),
+                    parameters: f.parameters,
+                    enabled: String::from(if f.enabled { "enabled" } else { "disabled" }),
+                    format: f.format,
+                    events: f.events,
+                    error: f.error,
+                })
+                .collect(),
+        },
+        Err(msg) => FilterListResponse {
+            status: format!("Unable to list filters: {}", msg),
+            detail: vec![],
+        },
     })
 }
 
@@ -150,6 +264,10 @@ mod filter_tests {
     use super::*;
     use crate::messaging;
     use crate::processing;
+    use crate::ring_items::analysis_ring_items::{
+        ParameterDefinitions, ParameterFileWriter, ParameterItem,
+    };
+    use crate::ring_items::{FromRaw, RingItem, RingVersion};
     use crate::sharedmem::binder;
     use crate::test::rest_common;
 
@@ -158,11 +276,17 @@ mod filter_tests {
     use rocket::Build;
     use rocket::Rocket;
 
+    use std::fs;
+    use std::path::Path;
     use std::sync::mpsc;
-    // note these are all unimplemented URLS so...
+    use std::thread;
+    use std::time;
 
     fn setup() -> Rocket<Build> {
-        rest_common::setup().mount("/", routes![new, delete, enable, disable, regate, file])
+        rest_common::setup().mount(
+            "/",
+            routes![new, delete, enable, disable, regate, file, format, list],
+        )
     }
     fn teardown(
         c: mpsc::Sender<messaging::Request>,
@@ -180,124 +304,205 @@ mod filter_tests {
     ) {
         rest_common::get_state(r)
     }
-
-    #[test]
-    fn new_1() {
-        let rocket = setup();
-        let (r, papi, bapi) = get_state(&rocket);
-
-        let client = Client::tracked(rocket).expect("Failed to make client");
-        let req = client.get("/new");
-        let reply = req
+    fn get(client: &Client, uri: &str) -> GenericResponse {
+        client
+            .get(uri)
             .dispatch()
             .into_json::<GenericResponse>()
-            .expect("Bad JSON");
-
-        assert_eq!(
-            "/spectcl/filter/new is not implemented",
-            reply.status.as_str()
-        );
-        assert_eq!("This is not SpecTcl", reply.detail.as_str());
-
-        teardown(r, &papi, &bapi);
+            .expect("Bad JSON")
     }
-    #[test]
-    fn delete_1() {
-        let rocket = setup();
-        let (r, papi, bapi) = get_state(&rocket);
-
-        let client = Client::tracked(rocket).expect("Failed to make client");
-        let req = client.get("/delete");
-        let reply = req
+    fn list(client: &Client, pattern: &str) -> FilterListResponse {
+        client
+            .get(format!("/list?pattern={}", pattern))
             .dispatch()
-            .into_json::<GenericResponse>()
-            .expect("Bad JSON");
+            .into_json::<FilterListResponse>()
+            .expect("Bad JSON")
+    }
+    // Make parameters a and b and a cut on a in [0, 10):
 
-        assert_eq!(
-            "/spectcl/filter/delete is not implemented",
-            reply.status.as_str()
-        );
-        assert_eq!("This is not SpecTcl", reply.detail.as_str());
+    fn make_objects(chan: &mpsc::Sender<messaging::Request>) {
+        let api = ParameterMessageClient::new(chan);
+        api.create_parameter("a").expect("Making a");
+        api.create_parameter("b").expect("Making b");
+        let aid = api.list_parameters("a").expect("Listing a")[0].get_id();
+        ConditionMessageClient::new(chan).create_cut_condition("cut", aid, 0.0, 10.0);
+    }
+    // Write a parameter file with events whose a values are given;
+    // b is always 100:
 
-        teardown(r, &papi, &bapi);
+    fn write_events(path: &Path, values: &[f64]) {
+        let mut writer = ParameterFileWriter::new(
+            fs::File::create(path).expect("Creating file"),
+            RingVersion::V11,
+        )
+        .expect("Making writer");
+        let mut defs = ParameterDefinitions::new();
+        defs.add(1, "a");
+        defs.add(2, "b");
+        writer
+            .write_definitions(&defs)
+            .expect("Writing definitions");
+        for (i, a) in values.iter().enumerate() {
+            let mut event = ParameterItem::new(i as u64);
+            event.add(1, *a);
+            event.add(2, 100.0);
+            writer.write_event(&event).expect("Writing event");
+        }
+        writer.flush().expect("Flushing");
     }
+    fn analyze(papi: &processing::ProcessingApi, path: &Path) {
+        papi.attach(path.to_str().unwrap()).expect("Attaching");
+        papi.start_analysis().expect("Starting analysis");
+        let start = time::Instant::now();
+        while papi.processing_state().expect("Getting state") != "Inactive" {
+            assert!(start.elapsed() < time::Duration::from_secs(10));
+            thread::sleep(time::Duration::from_millis(10));
+        }
+    }
+
     #[test]
-    fn enable_1() {
+    fn new_1() {
+        // Errors creating filters:
+
         let rocket = setup();
         let (r, papi, bapi) = get_state(&rocket);
+        make_objects(&r);
 
         let client = Client::tracked(rocket).expect("Failed to make client");
-        let req = client.get("/enable");
-        let reply = req
-            .dispatch()
-            .into_json::<GenericResponse>()
-            .expect("Bad JSON");
-
+        for uri in [
+            "/new?name=f&gate=nosuch&parameter=a",
+            "/new?name=f&gate=cut&parameter=nosuch",
+            "/new?name=f&gate=cut",
+        ] {
+            assert_eq!(
+                "Unable to create filter",
+                get(&client, uri).status,
+                "{}",
+                uri
+            );
+        }
         assert_eq!(
-            "/spectcl/filter/enable is not implemented",
-            reply.status.as_str()
+            "OK",
+            get(&client, "/new?name=f&gate=cut&parameter=a&parameter=b").status
         );
-        assert_eq!("This is not SpecTcl", reply.detail.as_str());
+        assert_eq!(
+            "Unable to create filter",
+            get(&client, "/new?name=f&gate=cut&parameter=a").status
+        );
+
+        let reply = list(&client, "*");
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail.len());
+        let f = &reply.detail[0];
+        assert_eq!("f", f.name);
+        assert_eq!("cut", f.gate);
+        assert_eq!("", f.file);
+        assert_eq!(vec![String::from("a"), String::from("b")], f.parameters);
+        assert_eq!("disabled", f.enabled);
+        assert_eq!("ring11", f.format);
 
         teardown(r, &papi, &bapi);
     }
     #[test]
-    fn disable_1() {
+    fn ops_1() {
+        // Operations on filters that don't exist fail as do enabling
+        // without a file and regating to a gate that doesn't exist:
+
         let rocket = setup();
         let (r, papi, bapi) = get_state(&rocket);
+        make_objects(&r);
 
         let client = Client::tracked(rocket).expect("Failed to make client");
-        let req = client.get("/disable");
-        let reply = req
-            .dispatch()
-            .into_json::<GenericResponse>()
-            .expect("Bad JSON");
-
+        for (uri, status) in [
+            ("/delete?name=f", "Unable to delete filter"),
+            ("/enable?name=f", "Unable to enable filter"),
+            ("/disable?name=f", "Unable to disable filter"),
+            ("/regate?name=f&gate=cut", "Unable to regate filter"),
+            ("/file?name=f&file=f.evt", "Unable to set filter file"),
+            (
+                "/format?name=f&format=ring12",
+                "Unable to set filter format",
+            ),
+        ] {
+            assert_eq!(status, get(&client, uri).status, "{}", uri);
+        }
+        get(&client, "/new?name=f&gate=cut&parameter=a");
+        assert_eq!(
+            "Unable to enable filter",
+            get(&client, "/enable?name=f").status
+        );
         assert_eq!(
-            "/spectcl/filter/disable is not implemented",
-            reply.status.as_str()
+            "Unable to regate filter",
+            get(&client, "/regate?name=f&gate=nosuch").status
         );
-        assert_eq!("This is not SpecTcl", reply.detail.as_str());
+        assert_eq!(
+            "Unable to set filter format",
+            get(&client, "/format?name=f&format=xdr").status
+        );
+        assert_eq!("OK", get(&client, "/format?name=f&format=ring12").status);
+        assert_eq!("ring12", list(&client, "f").detail[0].format);
+        assert!(list(&client, "%5B")
+            .status
+            .starts_with("Unable to list filters"));
+
+        assert_eq!("OK", get(&client, "/delete?name=f").status);
+        assert!(list(&client, "*").detail.is_empty());
 
         teardown(r, &papi, &bapi);
     }
     #[test]
-    fn regate_1() {
+    fn write_1() {
+        // An enabled filter writes the events that satisfy its gate:
+
         let rocket = setup();
         let (r, papi, bapi) = get_state(&rocket);
+        make_objects(&r);
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let input = dir.path().join("run.par");
+        write_events(&input, &[5.0, 50.0, 7.0]);
+        let output = dir.path().join("filtered.par");
 
         let client = Client::tracked(rocket).expect("Failed to make client");
-        let req = client.get("/regate");
-        let reply = req
-            .dispatch()
-            .into_json::<GenericResponse>()
-            .expect("Bad JSON");
-
+        get(&client, "/new?name=f&gate=cut&parameter=b");
         assert_eq!(
-            "/spectcl/filter/regate is not implemented",
-            reply.status.as_str()
+            "OK",
+            get(
+                &client,
+                &format!("/file?name=f&file={}", output.to_str().unwrap())
+            )
+            .status
         );
-        assert_eq!("This is not SpecTcl", reply.detail.as_str());
+        assert_eq!("OK", get(&client, "/enable?name=f").status);
+        assert_eq!("enabled", list(&client, "f").detail[0].enabled);
+        analyze(&papi, &input);
+        assert_eq!("OK", get(&client, "/disable?name=f").status);
+        let f = &list(&client, "f").detail[0];
+        assert_eq!("disabled", f.enabled);
+        assert_eq!(2, f.events);
+        assert!(f.error.is_none());
 
-        teardown(r, &papi, &bapi);
-    }
-    #[test]
-    fn file_1() {
-        let rocket = setup();
-        let (r, papi, bapi) = get_state(&rocket);
+        // Disabled, the filter writes nothing:
 
-        let client = Client::tracked(rocket).expect("Failed to make client");
-        let req = client.get("/file");
-        let reply = req
-            .dispatch()
-            .into_json::<GenericResponse>()
-            .expect("Bad JSON");
+        analyze(&papi, &input);
+        assert_eq!(2, list(&client, "f").detail[0].events);
 
+        let mut fd = fs::File::open(&output).expect("Opening output");
+        let mut items = vec![];
+        while let Ok(item) = RingItem::read_item(&mut fd) {
+            items.push(item);
+        }
+        assert_eq!(4, items.len());
+        let defs: ParameterDefinitions =
+            FromRaw::to_specific(&items[1], RingVersion::V11).expect("Definitions");
         assert_eq!(
-            "/spectcl/filter/file is not implemented",
-            reply.status.as_str()
+            vec![String::from("b")],
+            defs.iter().map(|d| d.name()).collect::<Vec<String>>()
         );
-        assert_eq!("This is not SpecTcl", reply.detail.as_str());
+        for item in items[2..].iter() {
+            let event: ParameterItem = FromRaw::to_specific(item, RingVersion::V11).expect("Event");
+            let values: Vec<f64> = event.iter().map(|p| p.value()).collect();
+            assert_eq!(vec![100.0], values);
+        }
 
         teardown(r, &papi, &bapi);
     }
@@ -1,79 +1,243 @@
 //!  Implements the /spectcl/fit domain of URIs.
-//!  This entire domain is implemented to return to the
-//!  client that this functionality is not supported by Rustogramer.
-//!  With the development of the, e.g. CutiePie, visualizer,
-//!  it seems that doing fitting in the visualizer is the appropriate
-//!  place for this functionality.
+//!  Fits are Gaussians on a linear background fitted to a range of
+//!  channels of a 1-d spectrum.  The fitting itself and the store of
+//!  named fits are in the fits module (src/fits/mod.rs).
 //!
 //!  The /spectcl/fit domain has the following URIs that will
 //!  have handlers:
 //!
-//!  *  create - creates a new fit object.
+//!  *  create - creates a new fit object and performs the fit.
 //!  *  update - Update fit parameters based on current data.
 //!  *  delete - Delete a fit object.
 //!  *  list   - list the fit objects that exist.
+//!  *  gate   - make a slice condition from a fit (Rustogramer only).
 //!  *  proc   - Returns the name of the fit proc associated with the fit.
-//! (In SpecTcl this allowed evaulation of the fit).
-//!  
+//!     (In SpecTcl this allowed evaulation of the fit).  This is not
+//!     supported.
+//!
+//!  We depend on the main to have set the Rocket state to include a
+//!  fits::SharedFitStore.
+//!
 use super::*;
+use crate::fits;
+use crate::messaging::condition_messages::{ConditionMessageClient, ConditionReply};
+use crate::messaging::spectrum_messages::{bin_width, SpectrumMessageClient};
 use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+
+// Fit the current contents of a range of channels of a spectrum:
+
+fn perform_fit(
+    api: &SpectrumMessageClient,
+    spectrum: &str,
+    low: u32,
+    high: u32,
+) -> Result<fits::GaussianParameters, String> {
+    let description = api
+        .list_spectra(spectrum)?
+        .into_iter()
+        .find(|d| d.name == spectrum)
+        .ok_or_else(|| format!("{} does not exist", spectrum))?;
+    if !api.is_1d(spectrum)? {
+        return Err(format!("{} is not a 1-d spectrum", spectrum));
+    }
+    let axis = description
+        .xaxis
+        .ok_or_else(|| format!("{} has no x axis", spectrum))?;
+    if high >= axis.bins.saturating_sub(2) {
+        return Err(format!(
+            "Channel {} is outside of {} which has {} channels",
+            high,
+            spectrum,
+            axis.bins.saturating_sub(2)
+        ));
+    }
+    let contents = api.get_contents(spectrum, axis.low, axis.high, 0.0, 0.0)?;
+    fits::fit_gaussian(&fits::region(&contents, low, high))
+}
+// The id of the parameter a fit's gates are on and their limits.  The
+// spectrum must have a single parameter.
+
+fn gate_limits(
+    api: &SpectrumMessageClient,
+    fit: &fits::Fit,
+    sigmas: f64,
+    state: &State<SharedHistogramChannel>,
+) -> Result<(String, u32, f64, f64), String> {
+    let description = api
+        .list_spectra(&fit.spectrum)?
+        .into_iter()
+        .find(|d| d.name == fit.spectrum)
+        .ok_or_else(|| format!("{} does not exist", fit.spectrum))?;
+    let axis = match (description.xparams.len(), description.yparams.len()) {
+        (1, 0) => description.xaxis,
+        _ => None,
+    }
+    .ok_or_else(|| format!("{} does not have a single parameter", fit.spectrum))?;
+    let parameter = description.xparams[0].clone();
+    let pid = find_parameter_by_name(&parameter, state)
+        .ok_or_else(|| format!("Parameter {} does not exist", parameter))?;
+    let (low, high) = fits::slice_limits(&fit.parameters, sigmas, axis.low, bin_width(axis));
+    Ok((parameter, pid, low, high))
+}
+// Move the limits of the gates made from a fit.  Gates that are no
+// longer slices on the parameter are unlinked rather than replaced.
+
+fn update_gates(
+    fit: &fits::Fit,
+    store: &fits::SharedFitStore,
+    state: &State<SharedHistogramChannel>,
+) -> Result<(), String> {
+    let sapi = SpectrumMessageClient::new(&state.inner().lock().unwrap());
+    let capi = ConditionMessageClient::new(&state.inner().lock().unwrap());
+    for gate in fit.gates.iter() {
+        let (_, pid, low, high) = gate_limits(&sapi, fit, gate.sigmas, state)?;
+        let linked = match capi.list_conditions(&gate.condition) {
+            ConditionReply::Listing(l) => l.into_iter().any(|c| {
+                c.cond_name == gate.condition && c.type_name == "Cut" && c.parameters == vec![pid]
+            }),
+            _ => false,
+        };
+        if !linked {
+            store.unlink(&gate.condition);
+            continue;
+        }
+        if let ConditionReply::Error(s) = capi.create_cut_condition(&gate.condition, pid, low, high)
+        {
+            return Err(format!("Unable to update gate {}: {}", gate.condition, s));
+        }
+    }
+    Ok(())
+}
 
-/// create - create a new fit object. (unimplemented).
-/// If this is implemented the following query parameters
-/// would required:
+/// create - create a new fit object and fit the current spectrum
+/// contents.  The following query parameters are required:
 ///
-///  name - name of the fit object.
-///  spectrum - Name of the spectrum on which the fit is evaulated.
-///  low  - Low channel limit of the fitted region.
-///  high - high channel limit of the fitted region.
-///  type - Type of the fit (e.g. 'gaussian')
+/// * name - name of the fit object.
+/// * spectrum - Name of the 1-d spectrum on which the fit is evaulated.
+/// * low  - Low channel limit of the fitted region.
+/// * high - high channel limit of the fitted region.
+/// * type - Type of the fit;  only 'gaussian' is supported.
 ///
-/// Idf implemented a GenericResponse is perfectly appropriate.
+/// A GenericResponse is returned.
 ///
-#[get("/create")]
-pub fn create() -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "/spectcl/fit/create is not supported",
-        "This is not Spectcl",
-    ))
+#[get("/create?<name>&<spectrum>&<low>&<high>&<type>")]
+#[allow(clippy::too_many_arguments)]
+pub fn create(
+    name: String,
+    spectrum: String,
+    low: u32,
+    high: u32,
+    r#type: String,
+    store: &State<fits::SharedFitStore>,
+    state: &State<SharedHistogramChannel>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    if r#type != fits::GAUSSIAN {
+        return Json(GenericResponse::err(
+            "Unsupported fit type",
+            &format!("Only {} fits are supported", fits::GAUSSIAN),
+        ));
+    }
+    if low >= high {
+        return Json(GenericResponse::err(
+            "Invalid fit region",
+            "low must be less than high",
+        ));
+    }
+    let api = SpectrumMessageClient::new(&state.inner().lock().unwrap());
+    let parameters = match perform_fit(&api, &spectrum, low, high) {
+        Ok(p) => p,
+        Err(s) => return Json(GenericResponse::err("Failed to create fit", &s)),
+    };
+    let fit = fits::Fit {
+        name,
+        spectrum,
+        fit_type: r#type,
+        low,
+        high,
+        parameters,
+        gates: vec![],
+    };
+    Json(match store.add(fit) {
+        Ok(()) => GenericResponse::ok(""),
+        Err(s) => GenericResponse::err("Failed to create fit", &s),
+    })
 }
 
-/// update (not implemented) Give a set of fits that match a pattern,
+/// update Give a set of fits that match a pattern,
 /// the fit paramaeters are re-computed using the current spectrum
 /// data.  The concept is that as the data are processed,fit parameters
 /// will shift both because
 ///
 /// * Additional statisitcs may shift slightly the fit parameters.
 /// * After clearing the spectra and attaching a different data file,
-/// the data could change significantly (consider an experimental
-/// data set that includes an energy scan or multiple beam species
-/// for example).
+///   the data could change significantly (consider an experimental
+///   data set that includes an energy scan or multiple beam species
+///   for example).
 ///
-/// The query parameter that would be accepted if implemented would be
-/// _pattern_ which is a glob pattern.  Fits with matching names
-/// only will be recomputed.
+/// The query parameter is the optional _pattern_ which is a glob pattern
+/// (defaults to "*").  Fits with matching names only will be recomputed.
+/// Fits that can't be updated keep their parameters and are described in
+/// the error detail.
 ///
-#[get("/update")]
-pub fn update() -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "/spectcl/fit/update is not supported",
-        "This is not Spectcl",
-    ))
+/// If the optional _gates_ query parameter is true (defaults to false),
+/// the limits of the gates made from the updated fits are moved to the
+/// new peaks.
+///
+#[get("/update?<pattern>&<gates>")]
+pub fn update(
+    pattern: OptionalString,
+    gates: Option<bool>,
+    store: &State<fits::SharedFitStore>,
+    state: &State<SharedHistogramChannel>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    let pattern = pattern.unwrap_or_else(|| String::from("*"));
+    let fits = match store.list(&pattern) {
+        Ok(f) => f,
+        Err(s) => return Json(GenericResponse::err("Bad fit name pattern", &s)),
+    };
+    let api = SpectrumMessageClient::new(&state.inner().lock().unwrap());
+    let mut failures = vec![];
+    for fit in fits {
+        let mut result = perform_fit(&api, &fit.spectrum, fit.low, fit.high)
+            .and_then(|p| store.set_parameters(&fit.name, p));
+        if result.is_ok() && gates.unwrap_or(false) {
+            if let Some(fit) = store.get(&fit.name) {
+                result = update_gates(&fit, store, state);
+            }
+        }
+        if let Err(s) = result {
+            failures.push(format!("{}: {}", fit.name, s));
+        }
+    }
+    Json(if failures.is_empty() {
+        GenericResponse::ok("")
+    } else {
+        GenericResponse::err("Some fits could not be updated", &failures.join("; "))
+    })
 }
 
-/// delete (unimplemented)
+/// delete
 /// Deletes an existing fit object.  The only query parameter is
 /// _name_ which specifies the the name of the fit to delete.
+/// Gates made from the fit remain but are no longer linked to it.
 ///
-/// A GenericResponse is perfectly ok for any future implementation
-/// of this URI.
+/// A GenericResponse is returned.
 ///
-#[get("/delete")]
-pub fn delete() -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "/spectcl/fit/delete is not supported",
-        "This is not Spectcl",
-    ))
+#[get("/delete?<name>")]
+pub fn delete(
+    name: String,
+    store: &State<fits::SharedFitStore>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    Json(match store.remove(&name) {
+        Ok(()) => GenericResponse::ok(""),
+        Err(s) => GenericResponse::err("Failed to delete fit", &s),
+    })
 }
 //
 #[derive(Serialize, Deserialize)]
@@ -84,13 +248,21 @@ pub struct FitParameter {
 }
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
+pub struct FitGateDescription {
+    condition: String,
+    parameter: String,
+    sigmas: f64,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
 pub struct FitDescription {
     name: String,
     spectrum: String,
     r#type: String,
-    low: f64,
-    high: f64,
+    low: u32,
+    high: u32,
     parameters: Vec<FitParameter>,
+    gates: Vec<FitGateDescription>,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
@@ -100,7 +272,7 @@ pub struct FitListReply {
 }
 
 ///
-/// list (unimplemented).
+/// list
 ///
 /// Lists the set of fits that match the optional _pattern_ query
 /// parameter (defaults to "*").  The returned reply will be of the
@@ -109,14 +281,120 @@ pub struct FitListReply {
 /// is just a set of name/value pairs... which I don't quite know how
 /// to produce (Maybe a tuple would be better?).
 ///
-/// Not important at this time since we're going to
-/// return an unimplemented reply.'
+/// The parameters are those of the most recent create or update;
+/// baseline, slope, height, centroid, sigma and chisquare.  The gates
+/// are the conditions made from the fit with /gate.
 ///
-#[get("/list")]
-pub fn list() -> Json<FitListReply> {
-    Json(FitListReply {
-        status: String::from("/spectcl/fit/list is not implemented - this is not SpecTcl"),
-        detail: vec![],
+#[get("/list?<pattern>")]
+pub fn list(pattern: OptionalString, store: &State<fits::SharedFitStore>) -> Json<FitListReply> {
+    let pattern = pattern.unwrap_or_else(|| String::from("*"));
+    Json(match store.list(&pattern) {
+        Ok(fits) => FitListReply {
+            status: String::from("OK"),
+            detail: fits
+                .into_iter()
+                .map(|f| FitDescription {
+                    name: f.name,
+                    spectrum: f.spectrum,
+                    r#type: f.fit_type,
+                    low: f.low,
+                    high: f.high,
+                    parameters: f
+                        .parameters
+                        .as_pairs()
+                        .into_iter()
+                        .map(|(name, value)| FitParameter { name, value })
+                        .collect(),
+                    gates: f
+                        .gates
+                        .into_iter()
+                        .map(|g| FitGateDescription {
+                            condition: g.condition,
+                            parameter: g.parameter,
+                            sigmas: g.sigmas,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        },
+        Err(s) => FitListReply {
+            status: format!("Bad fit name pattern: {}", s),
+            detail: vec![],
+        },
+    })
+}
+///
+/// gate - make a slice condition from a fit.  This is Rustogramer only.
+/// The condition is on the parameter of the fit's spectrum and accepts
+/// centroid ± sigmas*sigma of the fit's most recent parameters.  The
+/// condition stays linked to the fit so that /update can move its limits.
+/// Query parameters:
+///
+/// * name - name of the fit.
+/// * gate - name of the condition to create or replace.
+/// * sigmas - optional half width of the slice in units of the fit's
+///   sigma (defaults to 1.0).
+///
+/// A GenericResponse is returned.  On success the detail is _Created_ or
+/// _Replaced_ as for /spectcl/gate/edit.
+///
+#[get("/gate?<name>&<gate>&<sigmas>")]
+#[allow(clippy::too_many_arguments)]
+pub fn gate(
+    name: String,
+    gate: String,
+    sigmas: Option<f64>,
+    store: &State<fits::SharedFitStore>,
+    state: &State<SharedHistogramChannel>,
+    policy: &State<names::NamePolicy>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    let failure = format!("Could not create condition {} from fit", gate);
+    let capi = ConditionMessageClient::new(&state.inner().lock().unwrap());
+    if let Err(s) = policy.check_condition_name(&gate, &capi) {
+        return Json(GenericResponse::err("Invalid condition name", &s));
+    }
+    let sigmas = sigmas.unwrap_or(1.0);
+    if !(sigmas.is_finite() && sigmas > 0.0) {
+        return Json(GenericResponse::err(
+            &failure,
+            "sigmas must be a positive number",
+        ));
+    }
+    let fit = match store.get(&name) {
+        Some(f) => f,
+        None => {
+            return Json(GenericResponse::err(
+                &failure,
+                &format!("There is no fit named {}", name),
+            ))
+        }
+    };
+    let sapi = SpectrumMessageClient::new(&state.inner().lock().unwrap());
+    let (parameter, pid, low, high) = match gate_limits(&sapi, &fit, sigmas, state) {
+        Ok(l) => l,
+        Err(s) => return Json(GenericResponse::err(&failure, &s)),
+    };
+    let detail = match capi.create_cut_condition(&gate, pid, low, high) {
+        ConditionReply::Created => "Created",
+        ConditionReply::Replaced => "Replaced",
+        ConditionReply::Error(s) => return Json(GenericResponse::err(&failure, &s)),
+        _ => {
+            return Json(GenericResponse::err(
+                &failure,
+                "Unexpected respones type from server",
+            ))
+        }
+    };
+    let link = fits::FitGate {
+        condition: gate,
+        parameter,
+        sigmas,
+    };
+    Json(match store.link(&name, link) {
+        Ok(()) => GenericResponse::ok(detail),
+        Err(s) => GenericResponse::err(&failure, &s),
     })
 }
 ///
@@ -141,6 +419,7 @@ pub fn proc() -> Json<GenericResponse> {
 mod fit_tests {
     use super::*;
     use crate::messaging;
+    use crate::messaging::{condition_messages, parameter_messages, spectrum_messages};
     use crate::processing;
     use crate::test::rest_common;
 
@@ -150,10 +429,13 @@ mod fit_tests {
     use rocket::Rocket;
 
     use std::sync::mpsc;
-    // note these are all unimplemented URLS so...
 
     fn setup() -> Rocket<Build> {
-        rest_common::setup().mount("/", routes![create, update, delete, list, proc])
+        let r = rest_common::setup()
+            .manage(fits::SharedFitStore::new())
+            .mount("/", routes![create, update, delete, list, gate, proc]);
+        make_spectra(&r);
+        r
     }
     fn teardown(
         c: mpsc::Sender<messaging::Request>,
@@ -171,43 +453,229 @@ mod fit_tests {
     ) {
         rest_common::get_state(r)
     }
+    fn peak(centroid: f64) -> fits::GaussianParameters {
+        fits::GaussianParameters {
+            baseline: 20.0,
+            slope: 0.1,
+            height: 500.0,
+            centroid,
+            sigma: 8.0,
+            chisquare: 0.0,
+        }
+    }
+    // Fill 'oned' with a peak - channels are one unit wide:
+
+    fn fill_peak(chan: &mpsc::Sender<messaging::Request>, centroid: f64) {
+        let api = spectrum_messages::SpectrumMessageClient::new(chan);
+        let p = peak(centroid);
+        let contents = (0..1024)
+            .map(|c| spectrum_messages::Channel {
+                chan_type: spectrum_messages::ChannelType::Bin,
+                x: c as f64,
+                y: 0.0,
+                bin: c + 1,
+                value: p.value(c as f64),
+            })
+            .collect();
+        api.fill_spectrum("oned", contents).expect("Filling oned");
+    }
+    // A 1-d spectrum with a peak at 150 and a 2-d spectrum:
+
+    fn make_spectra(r: &Rocket<Build>) {
+        let (chan, _, _) = get_state(r);
+        let papi = parameter_messages::ParameterMessageClient::new(&chan);
+        papi.create_parameter("p1").expect("Making p1");
+        papi.create_parameter("p2").expect("Making p2");
+
+        let api = spectrum_messages::SpectrumMessageClient::new(&chan);
+        api.create_spectrum_1d("oned", "p1", 0.0, 1024.0, 1024)
+            .expect("Making oned");
+        api.create_spectrum_2d("twod", "p1", "p2", 0.0, 1024.0, 256, 0.0, 1024.0, 256)
+            .expect("Making twod");
+        fill_peak(&chan, 150.0);
+    }
+    fn get_parameter(fit: &FitDescription, name: &str) -> f64 {
+        fit.parameters
+            .iter()
+            .find(|p| p.name == name)
+            .unwrap_or_else(|| panic!("No {} parameter", name))
+            .value
+    }
+    fn list_fits(client: &Client, pattern: &str) -> FitListReply {
+        client
+            .get(format!("/list?pattern={}", pattern))
+            .dispatch()
+            .into_json::<FitListReply>()
+            .expect("Bad JSON")
+    }
     #[test]
     fn create_1() {
+        // Fit the peak:
+
         let rocket = setup();
         let (r, papi, bapi) = get_state(&rocket);
 
         let client = Client::tracked(rocket).expect("Failed to make client");
-        let req = client.get("/create");
-        let response = req
+        let response = client
+            .get("/create?name=peak&spectrum=oned&low=100&high=200&type=gaussian")
             .dispatch()
             .into_json::<GenericResponse>()
             .expect("Bad JSON");
+        assert_eq!("OK", response.status);
 
-        assert_eq!(
-            "/spectcl/fit/create is not supported",
-            response.status.as_str()
-        );
-        assert_eq!("This is not Spectcl", response.detail.as_str());
+        let listing = list_fits(&client, "*");
+        assert_eq!("OK", listing.status);
+        assert_eq!(1, listing.detail.len());
+        let fit = &listing.detail[0];
+        assert_eq!("peak", fit.name);
+        assert_eq!("oned", fit.spectrum);
+        assert_eq!("gaussian", fit.r#type);
+        assert_eq!(100, fit.low);
+        assert_eq!(200, fit.high);
+        assert_eq!(6, fit.parameters.len());
+        assert!((get_parameter(fit, "centroid") - 150.0).abs() < 1.0e-3);
+        assert!((get_parameter(fit, "sigma") - 8.0).abs() < 1.0e-3);
+        assert!((get_parameter(fit, "height") - 500.0).abs() < 1.0e-2);
+        assert!(get_parameter(fit, "chisquare") < 1.0e-6);
+
+        teardown(r, &papi, &bapi);
+    }
+    #[test]
+    fn create_2() {
+        // Failures:
+
+        let rocket = setup();
+        let (r, papi, bapi) = get_state(&rocket);
+
+        let client = Client::tracked(rocket).expect("Failed to make client");
+        for (uri, status) in [
+            (
+                "/create?name=peak&spectrum=oned&low=100&high=200&type=linear",
+                "Unsupported fit type",
+            ),
+            (
+                "/create?name=peak&spectrum=oned&low=200&high=100&type=gaussian",
+                "Invalid fit region",
+            ),
+            (
+                "/create?name=peak&spectrum=nosuch&low=100&high=200&type=gaussian",
+                "Failed to create fit",
+            ),
+            (
+                "/create?name=peak&spectrum=twod&low=100&high=200&type=gaussian",
+                "Failed to create fit",
+            ),
+            (
+                "/create?name=peak&spectrum=oned&low=100&high=1024&type=gaussian",
+                "Failed to create fit",
+            ),
+            (
+                "/create?name=peak&spectrum=oned&low=100&high=4294967295&type=gaussian",
+                "Failed to create fit",
+            ),
+        ] {
+            let response = client
+                .get(uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Bad JSON");
+            assert_eq!(status, response.status, "{}", uri);
+        }
+        assert_eq!(0, list_fits(&client, "*").detail.len());
+
+        // Duplicate:
+
+        let uri = "/create?name=peak&spectrum=oned&low=100&high=200&type=gaussian";
+        let response = client
+            .get(uri)
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", response.status);
+        let response = client
+            .get(uri)
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("Failed to create fit", response.status);
 
         teardown(r, &papi, &bapi);
     }
     #[test]
     fn update_1() {
+        // Move the peak and update - only matching fits are updated:
+
         let rocket = setup();
         let (r, papi, bapi) = get_state(&rocket);
 
         let client = Client::tracked(rocket).expect("Failed to make client");
-        let req = client.get("/update");
-        let response = req
+        for name in ["peak", "other"] {
+            let response = client
+                .get(format!(
+                    "/create?name={}&spectrum=oned&low=100&high=200&type=gaussian",
+                    name
+                ))
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Bad JSON");
+            assert_eq!("OK", response.status);
+        }
+        fill_peak(&r, 160.0);
+
+        let response = client
+            .get("/update?pattern=p*")
             .dispatch()
             .into_json::<GenericResponse>()
             .expect("Bad JSON");
+        assert_eq!("OK", response.status);
 
-        assert_eq!(
-            "/spectcl/fit/update is not supported",
-            response.status.as_str()
-        );
-        assert_eq!("This is not Spectcl", response.detail.as_str());
+        let listing = list_fits(&client, "*");
+        assert_eq!(2, listing.detail.len());
+        assert_eq!("other", listing.detail[0].name);
+        assert!((get_parameter(&listing.detail[0], "centroid") - 150.0).abs() < 1.0e-3);
+        assert_eq!("peak", listing.detail[1].name);
+        assert!((get_parameter(&listing.detail[1], "centroid") - 160.0).abs() < 1.0e-3);
+
+        // Default pattern updates everything:
+
+        let response = client
+            .get("/update")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", response.status);
+        let listing = list_fits(&client, "other");
+        assert!((get_parameter(&listing.detail[0], "centroid") - 160.0).abs() < 1.0e-3);
+
+        teardown(r, &papi, &bapi);
+    }
+    #[test]
+    fn update_2() {
+        // Fits that can't be updated are reported and keep their parameters:
+
+        let rocket = setup();
+        let (r, papi, bapi) = get_state(&rocket);
+
+        let client = Client::tracked(rocket).expect("Failed to make client");
+        let response = client
+            .get("/create?name=peak&spectrum=oned&low=100&high=200&type=gaussian")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", response.status);
+
+        let api = spectrum_messages::SpectrumMessageClient::new(&r);
+        api.clear_spectra("oned").expect("Clearing oned");
+        let response = client
+            .get("/update")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("Some fits could not be updated", response.status);
+        assert!(response.detail.starts_with("peak: "));
+
+        let listing = list_fits(&client, "*");
+        assert!((get_parameter(&listing.detail[0], "centroid") - 150.0).abs() < 1.0e-3);
 
         teardown(r, &papi, &bapi);
     }
@@ -217,37 +685,242 @@ mod fit_tests {
         let (r, papi, bapi) = get_state(&rocket);
 
         let client = Client::tracked(rocket).expect("Failed to make client");
-        let req = client.get("/delete");
-        let response = req
+        let response = client
+            .get("/create?name=peak&spectrum=oned&low=100&high=200&type=gaussian")
             .dispatch()
             .into_json::<GenericResponse>()
             .expect("Bad JSON");
+        assert_eq!("OK", response.status);
 
-        assert_eq!(
-            "/spectcl/fit/delete is not supported",
-            response.status.as_str()
-        );
-        assert_eq!("This is not Spectcl", response.detail.as_str());
+        let response = client
+            .get("/delete?name=peak")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", response.status);
+        assert_eq!(0, list_fits(&client, "*").detail.len());
+
+        let response = client
+            .get("/delete?name=peak")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("Failed to delete fit", response.status);
 
         teardown(r, &papi, &bapi);
     }
     #[test]
     fn list_1() {
+        // Empty and filtered lists:
+
         let rocket = setup();
         let (r, papi, bapi) = get_state(&rocket);
 
         let client = Client::tracked(rocket).expect("Failed to make client");
-        let req = client.get("/list");
-        let response = req
+        let response = client
+            .get("/list")
             .dispatch()
             .into_json::<FitListReply>()
             .expect("Bad JSON");
+        assert_eq!("OK", response.status);
+        assert_eq!(0, response.detail.len());
+
+        let response = client
+            .get("/create?name=peak&spectrum=oned&low=100&high=200&type=gaussian")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", response.status);
+
+        assert_eq!(1, list_fits(&client, "p*").detail.len());
+        assert_eq!(0, list_fits(&client, "q*").detail.len());
 
+        teardown(r, &papi, &bapi);
+    }
+    // The type and limits of a condition:
+
+    fn condition(chan: &mpsc::Sender<messaging::Request>, name: &str) -> (String, f64, f64) {
+        let api = condition_messages::ConditionMessageClient::new(chan);
+        match api.list_conditions(name) {
+            condition_messages::ConditionReply::Listing(l) => {
+                assert_eq!(1, l.len());
+                let points = l[0].points.clone();
+                let (low, high) = if points.len() == 2 {
+                    (points[0].0, points[1].0)
+                } else {
+                    (0.0, 0.0)
+                };
+                (l[0].type_name.clone(), low, high)
+            }
+            _ => panic!("Failed to list {}", name),
+        }
+    }
+    fn assert_limits(expected: (f64, f64), actual: (String, f64, f64)) {
+        assert_eq!("Cut", actual.0);
+        assert!((expected.0 - actual.1).abs() < 1.0e-3, "{:?}", actual);
+        assert!((expected.1 - actual.2).abs() < 1.0e-3, "{:?}", actual);
+    }
+    #[test]
+    fn gate_1() {
+        // Gates are centroid +/- sigmas * sigma in parameter coordinates
+        // and are listed with the fit:
+
+        let rocket = setup();
+        let (r, papi, bapi) = get_state(&rocket);
+
+        let client = Client::tracked(rocket).expect("Failed to make client");
+        let response = client
+            .get("/create?name=peak&spectrum=oned&low=100&high=200&type=gaussian")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", response.status);
+
+        let response = client
+            .get("/gate?name=peak&gate=narrow")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", response.status);
+        assert_eq!("Created", response.detail);
+        assert_limits((142.5, 158.5), condition(&r, "narrow"));
+
+        let response = client
+            .get("/gate?name=peak&gate=wide&sigmas=2.5")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", response.status);
+        assert_limits((130.5, 170.5), condition(&r, "wide"));
+
+        let response = client
+            .get("/gate?name=peak&gate=wide&sigmas=2")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("Replaced", response.detail);
+        assert_limits((134.5, 166.5), condition(&r, "wide"));
+
+        let listing = list_fits(&client, "peak");
+        let gates: Vec<(String, String, f64)> = listing.detail[0]
+            .gates
+            .iter()
+            .map(|g| (g.condition.clone(), g.parameter.clone(), g.sigmas))
+            .collect();
         assert_eq!(
-            "/spectcl/fit/list is not implemented - this is not SpecTcl",
-            response.status.as_str()
+            vec![
+                (String::from("narrow"), String::from("p1"), 1.0),
+                (String::from("wide"), String::from("p1"), 2.0)
+            ],
+            gates
         );
-        assert_eq!(0, response.detail.len());
+
+        teardown(r, &papi, &bapi);
+    }
+    #[test]
+    fn gate_2() {
+        // Failures:
+
+        let rocket = setup();
+        let (r, papi, bapi) = get_state(&rocket);
+
+        let client = Client::tracked(rocket).expect("Failed to make client");
+        let response = client
+            .get("/create?name=peak&spectrum=oned&low=100&high=200&type=gaussian")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", response.status);
+
+        let failure = "Could not create condition g from fit";
+        for uri in [
+            "/gate?name=nosuch&gate=g",
+            "/gate?name=peak&gate=g&sigmas=0",
+            "/gate?name=peak&gate=g&sigmas=-1",
+        ] {
+            let response = client
+                .get(uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Bad JSON");
+            assert_eq!(failure, response.status, "{}", uri);
+        }
+        let response = client
+            .get("/gate?name=peak&gate=a%20b")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("Invalid condition name", response.status);
+
+        // The spectrum is gone:
+
+        let api = spectrum_messages::SpectrumMessageClient::new(&r);
+        api.delete_spectrum("oned").expect("Deleting oned");
+        let response = client
+            .get("/gate?name=peak&gate=g")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!(failure, response.status);
+        assert!(list_fits(&client, "peak").detail[0].gates.is_empty());
+
+        teardown(r, &papi, &bapi);
+    }
+    #[test]
+    fn gate_3() {
+        // Updates only move the gates when asked to and gates that
+        // have been redefined are unlinked:
+
+        let rocket = setup();
+        let (r, papi, bapi) = get_state(&rocket);
+
+        let client = Client::tracked(rocket).expect("Failed to make client");
+        let response = client
+            .get("/create?name=peak&spectrum=oned&low=100&high=200&type=gaussian")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", response.status);
+        for gate in ["g1", "g2"] {
+            let response = client
+                .get(format!("/gate?name=peak&gate={}", gate))
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Bad JSON");
+            assert_eq!("OK", response.status);
+        }
+        fill_peak(&r, 160.0);
+
+        let response = client
+            .get("/update")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", response.status);
+        assert_limits((142.5, 158.5), condition(&r, "g1"));
+
+        condition_messages::ConditionMessageClient::new(&r).create_true_condition("g2");
+        let response = client
+            .get("/update?gates=true")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", response.status);
+        assert_limits((152.5, 168.5), condition(&r, "g1"));
+        assert_eq!("True", condition(&r, "g2").0);
+        let listing = list_fits(&client, "peak");
+        assert_eq!(1, listing.detail[0].gates.len());
+        assert_eq!("g1", listing.detail[0].gates[0].condition);
+
+        // Deleting the fit leaves the gate:
+
+        let response = client
+            .get("/delete?name=peak")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", response.status);
+        assert_limits((152.5, 168.5), condition(&r, "g1"));
 
         teardown(r, &papi, &bapi);
     }
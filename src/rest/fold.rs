@@ -38,6 +38,8 @@ pub fn apply(
     gate: String,
     spectrum: String,
     state: &State<SharedHistogramChannel>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
 ) -> Json<GenericResponse> {
     let client = spectrum_messages::SpectrumMessageClient::new(&state.inner().lock().unwrap());
     let reply = if let Err(s) = client.fold_spectrum(&spectrum, &gate) {
@@ -69,6 +71,7 @@ pub struct FoldListResponse {
 pub fn list(
     pattern: OptionalString,
     msg_chan: &State<SharedHistogramChannel>,
+    _ready: busy::HistogramerReady,
 ) -> Json<FoldListResponse> {
     let hapi = spectrum_messages::SpectrumMessageClient::new(&msg_chan.inner().lock().unwrap());
 
@@ -110,7 +113,12 @@ pub fn list(
 /// GenericResponse is appropriate.
 ///
 #[get("/remove?<spectrum>")]
-pub fn remove(spectrum: String, msg_chan: &State<SharedHistogramChannel>) -> Json<GenericResponse> {
+pub fn remove(
+    spectrum: String,
+    msg_chan: &State<SharedHistogramChannel>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
     let sapi = spectrum_messages::SpectrumMessageClient::new(&msg_chan.inner().lock().unwrap());
 
     let reply = if let Err(s) = sapi.unfold_spectrum(&spectrum) {
@@ -0,0 +1,668 @@
+//! This module provides the /spectcl/fs domain.  This is Rustogramer
+//! only.  Remote GUIs often run on a system whose filesystem differs
+//! from the histogramer's (e.g. a laptop displaying a histogramer
+//! running on a cluster node).  Files named in requests such as
+//! attach, swrite and sread are opened by the histogramer so
+//! File->Open/Save dialogs must browse the histogramer's filesystem,
+//! not the client's.  This domain lets them do so.
+//!
+//! Subdomains are:
+//!
+//! *  roots - directories from which browsing can start.
+//! *  listdir - list the contents of a directory.
+//! *  stat - describe a single file or directory.
+//! *  transferdir - the directory files are transferred to and from.
+//! *  upload - upload a file to the transfer directory.
+//! *  download - download a file from the transfer directory.
+//!
+//! Without shared storage, a remote GUI can't open files it has on its
+//! own system or read the files the histogramer writes.  Files can,
+//! therefore, be uploaded to and downloaded from a directory designated
+//! by the --transfer-dir option (see TransferDirectory).  Uploads are
+//! the only requests here that modify the filesystem.
+//!
+use glob::Pattern;
+use rocket::data::{Data, Limits};
+use rocket::fs::NamedFile;
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time;
+
+use super::*;
+
+/// Description of a file or directory:
+///
+/// * name - the last component of the path.
+/// * path - the absolute path.
+/// * kind - "file", "directory" or "other" (e.g. a device or a
+///   dangling symbolic link).
+/// * link - true if the path is a symbolic link.  The other
+///   attributes describe what it links to.
+/// * size - size in bytes.
+/// * modified - unix time (seconds) of the last modification.
+/// * readable - true if the histogramer can read it.  For
+///   directories, this means it can list them.  For other kinds of
+///   file (e.g. FIFOs) it only reflects the permissions as opening
+///   them could block.
+/// * writable - true if the histogramer can write it.  For
+///   directories this reflects the permissions rather than a write
+///   attempt so it may not be exact.
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct FileInfo {
+    pub name: String,
+    pub path: String,
+    pub kind: String,
+    pub link: bool,
+    pub size: u64,
+    pub modified: Option<u64>,
+    pub readable: bool,
+    pub writable: bool,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct FileInfoResponse {
+    pub status: String,
+    pub detail: Option<FileInfo>,
+}
+
+/// The contents of a directory:
+///
+/// * path - the absolute path of the directory.
+/// * parent - the absolute path of its parent, None for a root.
+/// * entries - the directories and files in the directory.
+///
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct DirectoryListing {
+    pub path: String,
+    pub parent: Option<String>,
+    pub entries: Vec<FileInfo>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct DirectoryListingResponse {
+    pub status: String,
+    pub detail: Option<DirectoryListing>,
+}
+
+/// The directory files are uploaded to and downloaded from.  Files
+/// are named relative to it and can't be outside of it.
+pub struct TransferDirectory {
+    path: PathBuf,
+}
+
+impl TransferDirectory {
+    pub fn new(path: &Path) -> TransferDirectory {
+        TransferDirectory {
+            path: path.to_path_buf(),
+        }
+    }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// The path of a file in the directory.  Fails if the name is
+    /// empty, absolute or has .. components so that files outside of
+    /// the directory can't be reached.  The part of the path that
+    /// exists is canonicalized and must still be in the directory so
+    /// that symbolic links can't lead out of it either.
+    pub fn resolve(&self, name: &str) -> Result<PathBuf, String> {
+        let relative = Path::new(name);
+        if name.is_empty() {
+            return Err(String::from("A file name is required"));
+        }
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(format!(
+                "'{}' must be relative to the transfer directory and not contain ..",
+                name
+            ));
+        }
+        let root = self
+            .path
+            .canonicalize()
+            .unwrap_or_else(|_| self.path.clone());
+        let mut path = root.clone();
+        for part in relative.components() {
+            if let Component::Normal(part) = part {
+                path.push(part);
+                if fs::symlink_metadata(&path).is_ok() {
+                    path = path
+                        .canonicalize()
+                        .map_err(|e| format!("Unable to resolve {}: {}", path.display(), e))?;
+                    if !path.starts_with(&root) {
+                        return Err(format!("'{}' is outside of the transfer directory", name));
+                    }
+                }
+            }
+        }
+        Ok(path)
+    }
+}
+
+// Make a path absolute relative to the histogramer's working
+// directory.  Symbolic links and .. are resolved if possible:
+
+fn absolute_path(path: &str) -> Result<PathBuf, String> {
+    let path = Path::new(path);
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map_err(|e| format!("Unable to get the working directory: {}", e))?
+            .join(path)
+    };
+    Ok(path.canonicalize().unwrap_or(path))
+}
+
+/// Describe a file or directory.  Symbolic links are followed.
+pub fn file_info(path: &Path) -> Result<FileInfo, String> {
+    let link_metadata = fs::symlink_metadata(path)
+        .map_err(|e| format!("Unable to stat {}: {}", path.display(), e))?;
+    let link = link_metadata.file_type().is_symlink();
+    let metadata = fs::metadata(path).unwrap_or_else(|_| link_metadata.clone());
+    let kind = if metadata.is_dir() {
+        "directory"
+    } else if metadata.is_file() {
+        "file"
+    } else {
+        "other"
+    };
+    // Opening FIFOs and devices can block (or have side effects) so only
+    // regular files and directories are actually tried:
+
+    let readable = if metadata.is_dir() {
+        fs::read_dir(path).is_ok()
+    } else if metadata.is_file() {
+        fs::File::open(path).is_ok()
+    } else {
+        permits_read(&metadata)
+    };
+    let writable = if metadata.is_file() {
+        fs::OpenOptions::new().append(true).open(path).is_ok()
+    } else {
+        !metadata.permissions().readonly()
+    };
+    Ok(FileInfo {
+        name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.to_string_lossy().to_string()),
+        path: path.to_string_lossy().to_string(),
+        kind: String::from(kind),
+        link,
+        size: metadata.len(),
+        modified: metadata.modified().ok().map(|t| {
+            t.duration_since(time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        }),
+        readable,
+        writable,
+    })
+}
+
+// Whether a file's permission bits allow anyone to read it.
+
+#[cfg(unix)]
+fn permits_read(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o444 != 0
+}
+#[cfg(not(unix))]
+fn permits_read(_metadata: &fs::Metadata) -> bool {
+    true
+}
+
+/// Directories from which browsing can start: the filesystem root,
+/// the histogramer's home directory and its working directory.
+///
+/// #### Returns
+/// StringArrayResponse whose detail are the absolute paths.
+#[get("/roots")]
+pub fn fs_roots() -> Json<StringArrayResponse> {
+    let mut result = StringArrayResponse::new("OK");
+    let candidates = [
+        Some(PathBuf::from("/")),
+        dirs::home_dir(),
+        std::env::current_dir().ok(),
+    ];
+    for dir in candidates.into_iter().flatten() {
+        let dir = dir.to_string_lossy().to_string();
+        if !result.detail.contains(&dir) {
+            result.detail.push(dir);
+        }
+    }
+    Json(result)
+}
+/// List the contents of a directory.
+///
+/// #### Query Parameters
+/// * path - the directory.  Relative paths are relative to the
+///   histogramer's working directory, which is the default.
+/// * pattern - glob pattern the names of the files listed must
+///   match.  This can appear more than once, files matching any
+///   pattern are listed.  Directories are always listed so that they
+///   can be browsed.  If omitted, all files are listed.
+/// * hidden - if true, entries whose names begin with . are listed.
+///   Defaults to false.
+///
+/// #### Returns
+/// DirectoryListingResponse - directories are listed first, then
+/// files, each sorted by name.  Entries that can't be described (e.g.
+/// deleted while listing) are skipped.
+#[get("/listdir?<path>&<pattern>&<hidden>")]
+pub fn fs_listdir(
+    path: OptionalString,
+    pattern: Vec<String>,
+    hidden: OptionalFlag,
+) -> Json<DirectoryListingResponse> {
+    let error = |s: String| {
+        Json(DirectoryListingResponse {
+            status: format!("Unable to list directory: {}", s),
+            detail: None,
+        })
+    };
+    let mut patterns = Vec::new();
+    for p in pattern.iter() {
+        match Pattern::new(p) {
+            Ok(p) => patterns.push(p),
+            Err(e) => return error(format!("Bad pattern '{}': {}", p, e)),
+        }
+    }
+    let hidden = hidden.unwrap_or(false);
+    let dir = match absolute_path(&path.unwrap_or_else(|| String::from("."))) {
+        Ok(d) => d,
+        Err(s) => return error(s),
+    };
+    let contents = match fs::read_dir(&dir) {
+        Ok(c) => c,
+        Err(e) => return error(format!("{}: {}", dir.display(), e)),
+    };
+
+    let mut entries: Vec<FileInfo> = contents
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| hidden || !entry.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|entry| file_info(&entry.path()).ok())
+        .filter(|info| {
+            info.kind == "directory"
+                || patterns.is_empty()
+                || patterns.iter().any(|p| p.matches(&info.name))
+        })
+        .collect();
+    entries.sort_by(|a, b| (a.kind != "directory", &a.name).cmp(&(b.kind != "directory", &b.name)));
+
+    Json(DirectoryListingResponse {
+        status: String::from("OK"),
+        detail: Some(DirectoryListing {
+            path: dir.to_string_lossy().to_string(),
+            parent: dir.parent().map(|p| p.to_string_lossy().to_string()),
+            entries,
+        }),
+    })
+}
+/// Describe a file or directory.
+///
+/// #### Query Parameters
+/// * path - the file or directory.  Relative paths are relative to the
+///   histogramer's working directory.
+///
+/// #### Returns
+/// FileInfoResponse - the detail is null if the file does not exist.
+#[get("/stat?<path>")]
+pub fn fs_stat(path: String) -> Json<FileInfoResponse> {
+    match absolute_path(&path).and_then(|p| file_info(&p)) {
+        Ok(info) => Json(FileInfoResponse {
+            status: String::from("OK"),
+            detail: Some(info),
+        }),
+        Err(s) => Json(FileInfoResponse {
+            status: s,
+            detail: None,
+        }),
+    }
+}
+
+/// The directory files are transferred to and from.
+///
+/// #### Returns
+/// GenericResponse whose detail is the directory.
+#[get("/transferdir")]
+pub fn fs_transfer_dir(state: &State<TransferDirectory>) -> Json<GenericResponse> {
+    Json(GenericResponse::ok(&state.inner().path().to_string_lossy()))
+}
+/// Upload a file to the transfer directory.  The body of the request
+/// is the file contents.  Its size is limited by --max-body-bytes.
+///
+/// #### Query Parameters
+/// * name - the file name relative to the transfer directory.  Any
+///   subdirectories it names are created.
+/// * replace - if true, an existing file is replaced.  Defaults to
+///   false which makes uploading an existing file an error.
+///
+/// #### Returns
+/// GenericResponse whose detail is the path of the file written so
+/// that it can be used in e.g. an sread request.
+#[post("/upload?<name>&<replace>", data = "<data>")]
+pub async fn fs_upload(
+    name: String,
+    replace: OptionalFlag,
+    data: Data<'_>,
+    limits: &Limits,
+    state: &State<TransferDirectory>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    let error = |s: String| Json(GenericResponse::err("Unable to upload file", &s));
+    let path = match state.inner().resolve(&name) {
+        Ok(p) => p,
+        Err(s) => return error(s),
+    };
+    if path.exists() && !replace.unwrap_or(false) {
+        return error(format!("{} already exists", path.display()));
+    }
+    if let Some(dir) = path.parent() {
+        if let Err(e) = fs::create_dir_all(dir) {
+            return error(format!("Unable to create {}: {}", dir.display(), e));
+        }
+    }
+    let limit = limits.get("file").unwrap_or(Limits::FILE);
+    match data.open(limit).into_file(&path).await {
+        Ok(file) if file.is_complete() => Json(GenericResponse::ok(&path.to_string_lossy())),
+        Ok(_) => {
+            let _ = fs::remove_file(&path);
+            error(format!("The file is larger than the {} byte limit", limit))
+        }
+        Err(e) => error(format!("Unable to write {}: {}", path.display(), e)),
+    }
+}
+/// Download a file from the transfer directory.
+///
+/// #### Query Parameters
+/// * name - the file name relative to the transfer directory.
+///
+/// #### Returns
+/// The file contents, or a GenericResponse if they can't be
+/// returned.
+#[get("/download?<name>")]
+pub async fn fs_download(
+    name: String,
+    state: &State<TransferDirectory>,
+) -> Result<NamedFile, Json<GenericResponse>> {
+    let error = |s: String| Json(GenericResponse::err("Unable to download file", &s));
+    let path = state.inner().resolve(&name).map_err(error)?;
+    NamedFile::open(&path)
+        .await
+        .map_err(|e| error(format!("Unable to open {}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod fs_tests {
+    use super::*;
+    use rocket::data::ToByteUnit;
+    use rocket::local::blocking::Client;
+    use std::io::Write;
+
+    fn client() -> Client {
+        Client::tracked(rocket::build().mount("/", routes![fs_roots, fs_listdir, fs_stat]))
+            .expect("Making client")
+    }
+    // A client for file transfers to dir whose uploads are limited
+    // to 10 bytes:
+
+    fn transfer_client(dir: &Path) -> Client {
+        let limits = Limits::default().limit("file", 10.bytes());
+        Client::tracked(
+            rocket::custom(rocket::Config::figment().merge(("limits", limits)))
+                .manage(TransferDirectory::new(dir))
+                .mount("/", routes![fs_transfer_dir, fs_upload, fs_download]),
+        )
+        .expect("Making client")
+    }
+    fn upload(client: &Client, query: &str, body: &str) -> GenericResponse {
+        client
+            .post(format!("/upload?{}", query))
+            .body(body)
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON")
+    }
+    // A directory with a subdirectory, a hidden file and two files:
+
+    fn make_tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        fs::create_dir(dir.path().join("sub")).expect("Making subdirectory");
+        fs::File::create(dir.path().join(".hidden")).expect("Making file");
+        fs::File::create(dir.path().join("b.json")).expect("Making file");
+        fs::File::create(dir.path().join("a.spec"))
+            .expect("Making file")
+            .write_all(b"12345")
+            .expect("Writing file");
+        dir
+    }
+    fn listdir(client: &Client, query: &str) -> DirectoryListingResponse {
+        client
+            .get(format!("/listdir?{}", query))
+            .dispatch()
+            .into_json::<DirectoryListingResponse>()
+            .expect("Bad JSON")
+    }
+    fn names(listing: &DirectoryListing) -> Vec<String> {
+        listing.entries.iter().map(|e| e.name.clone()).collect()
+    }
+
+    #[test]
+    fn roots_1() {
+        let reply = client()
+            .get("/roots")
+            .dispatch()
+            .into_json::<StringArrayResponse>()
+            .expect("Bad JSON");
+        assert_eq!("/", reply.detail[0]);
+        let cwd = std::env::current_dir().expect("Getting cwd");
+        assert!(reply.detail.contains(&cwd.to_string_lossy().to_string()));
+    }
+    #[test]
+    fn listdir_1() {
+        let dir = make_tree();
+        let client = client();
+        let path = dir.path().canonicalize().expect("Canonicalizing");
+
+        let reply = listdir(&client, &format!("path={}", path.display()));
+        assert_eq!("OK", reply.status);
+        let listing = reply.detail.expect("Listing");
+        assert_eq!(path.to_string_lossy(), listing.path);
+        assert_eq!(
+            Some(path.parent().unwrap().to_string_lossy().to_string()),
+            listing.parent
+        );
+        assert_eq!(vec!["sub", "a.spec", "b.json"], names(&listing));
+        assert_eq!("directory", listing.entries[0].kind);
+        let file = &listing.entries[1];
+        assert_eq!(("file", 5), (file.kind.as_str(), file.size));
+        assert!(file.readable && file.writable);
+        assert!(file.modified.is_some());
+
+        // Hidden files and patterns:
+
+        let reply = listdir(&client, &format!("path={}&hidden=true", path.display()));
+        assert_eq!(
+            vec!["sub", ".hidden", "a.spec", "b.json"],
+            names(&reply.detail.unwrap())
+        );
+        let reply = listdir(
+            &client,
+            &format!("path={}&pattern=*.json&pattern=*.txt", path.display()),
+        );
+        assert_eq!(vec!["sub", "b.json"], names(&reply.detail.unwrap()));
+    }
+    #[test]
+    fn listdir_2() {
+        let dir = make_tree();
+        let client = client();
+
+        let reply = listdir(&client, &format!("path={}/junk", dir.path().display()));
+        assert!(reply.status.starts_with("Unable to list directory"));
+        assert!(reply.detail.is_none());
+
+        let reply = listdir(
+            &client,
+            &format!("path={}&pattern=%5B", dir.path().display()),
+        );
+        assert!(reply.status.starts_with("Unable to list directory"));
+    }
+    #[test]
+    fn stat_1() {
+        let dir = make_tree();
+        let client = client();
+        let path = dir.path().canonicalize().expect("Canonicalizing");
+
+        let reply = client
+            .get(format!("/stat?path={}/a.spec", path.display()))
+            .dispatch()
+            .into_json::<FileInfoResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status);
+        let info = reply.detail.expect("Stat");
+        assert_eq!("a.spec", info.name);
+        assert_eq!(path.join("a.spec").to_string_lossy(), info.path);
+        assert_eq!(5, info.size);
+        assert!(!info.link);
+
+        let reply = client
+            .get(format!("/stat?path={}/junk", path.display()))
+            .dispatch()
+            .into_json::<FileInfoResponse>()
+            .expect("Bad JSON");
+        assert!(reply.status.starts_with("Unable to stat"));
+        assert!(reply.detail.is_none());
+    }
+    #[cfg(unix)]
+    #[test]
+    fn stat_2() {
+        // Stating a FIFO must not block trying to open it:
+
+        let dir = make_tree();
+        let client = client();
+        let path = dir.path().canonicalize().expect("Canonicalizing");
+        let fifo = path.join("fifo");
+        let status = std::process::Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .expect("Running mkfifo");
+        assert!(status.success());
+
+        let reply = client
+            .get(format!("/stat?path={}", fifo.display()))
+            .dispatch()
+            .into_json::<FileInfoResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status);
+        let info = reply.detail.expect("Stat");
+        assert_eq!("other", info.kind);
+        assert!(info.readable);
+    }
+    #[test]
+    fn resolve_1() {
+        let dir = TransferDirectory::new(Path::new("/transfers"));
+        assert_eq!(
+            PathBuf::from("/transfers/spectra/run.json"),
+            dir.resolve("spectra/run.json").expect("Resolving")
+        );
+        assert!(dir.resolve("").is_err());
+        assert!(dir.resolve("/etc/passwd").is_err());
+        assert!(dir.resolve("../run.json").is_err());
+        assert!(dir.resolve("spectra/../../run.json").is_err());
+    }
+    #[test]
+    fn resolve_2() {
+        // Symbolic links can't lead out of the directory:
+
+        use std::os::unix::fs::symlink;
+
+        let outside = tempfile::tempdir().expect("Making temp dir");
+        let tree = tempfile::tempdir().expect("Making temp dir");
+        let root = tree.path().canonicalize().expect("Canonicalizing");
+        fs::create_dir(root.join("sub")).expect("Making subdirectory");
+        symlink(outside.path(), root.join("out")).expect("Linking out");
+        symlink(root.join("sub"), root.join("in")).expect("Linking in");
+        symlink(outside.path().join("junk"), root.join("dangling")).expect("Linking junk");
+
+        let dir = TransferDirectory::new(tree.path());
+        assert_eq!(
+            root.join("sub").join("run.json"),
+            dir.resolve("in/run.json").expect("Resolving")
+        );
+        assert!(dir.resolve("out").is_err());
+        assert!(dir.resolve("out/run.json").is_err());
+        assert!(dir.resolve("dangling").is_err());
+    }
+    #[test]
+    fn upload_1() {
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let client = transfer_client(dir.path());
+
+        let reply = client
+            .get("/transferdir")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!(dir.path().to_string_lossy(), reply.detail);
+
+        let reply = upload(&client, "name=sub/a.json", "12345");
+        assert_eq!("OK", reply.status);
+        let path = dir.path().join("sub").join("a.json");
+        assert_eq!(path.to_string_lossy(), reply.detail);
+        assert_eq!("12345", fs::read_to_string(&path).expect("Reading"));
+
+        // Existing files are only replaced if asked:
+
+        let reply = upload(&client, "name=sub/a.json", "678");
+        assert_eq!("Unable to upload file", reply.status);
+        assert_eq!("12345", fs::read_to_string(&path).expect("Reading"));
+        let reply = upload(&client, "name=sub/a.json&replace=true", "678");
+        assert_eq!("OK", reply.status);
+        assert_eq!("678", fs::read_to_string(&path).expect("Reading"));
+    }
+    #[test]
+    fn upload_2() {
+        // Files outside the directory and files that are too big:
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let client = transfer_client(dir.path());
+
+        let reply = upload(&client, "name=../a.json", "1");
+        assert_eq!("Unable to upload file", reply.status);
+
+        let reply = upload(&client, "name=big.json", "12345678901");
+        assert_eq!("Unable to upload file", reply.status);
+        assert!(!dir.path().join("big.json").exists());
+    }
+    #[test]
+    fn download_1() {
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        fs::write(dir.path().join("run.json"), "contents").expect("Writing file");
+        let client = transfer_client(dir.path());
+
+        let response = client.get("/download?name=run.json").dispatch();
+        assert_eq!(rocket::http::Status::Ok, response.status());
+        assert_eq!(
+            Some(rocket::http::ContentType::JSON),
+            response.content_type()
+        );
+        assert_eq!("contents", response.into_string().expect("Reading body"));
+
+        for name in ["junk.json", "../run.json"] {
+            let reply = client
+                .get(format!("/download?name={}", name))
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Bad JSON");
+            assert_eq!("Unable to download file", reply.status);
+        }
+    }
+}
@@ -0,0 +1,554 @@
+//!  Implements the /spectcl/gainmatch domain.  This is specific to
+//!  Rustogramer.  Gain matching fits a linear calibration (gain and
+//!  offset) that maps the peaks of a parameter's distribution onto
+//!  those of a reference distribution.  The fit can be registered as
+//!  the parameter's calibration, which the histogramer then applies to
+//!  the parameter's values before they are histogrammed.
+//!
+//!  The URIs are:
+//!
+//! *  /spectcl/gainmatch/fit - fit (and optionally apply) calibrations.
+//! *  /spectcl/gainmatch/list - list parameter calibrations.
+//! *  /spectcl/gainmatch/clear - remove parameter calibrations.
+//!
+//!  Using a summary spectrum as the target gain matches all of the
+//!  parameters in that spectrum in one request.
+//!
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+
+use super::*;
+use crate::gainmatch;
+use crate::messaging::parameter_messages::ParameterMessageClient;
+use crate::messaging::spectrum_messages::{SpectrumMessageClient, SpectrumProperties};
+
+/// The default number of peaks found in spectra.
+const DEFAULT_PEAK_COUNT: usize = 2;
+
+/// A parameter calibration; calibrated values are gain*raw + offset.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct Calibration {
+    parameter: String,
+    gain: f64,
+    offset: f64,
+}
+/// The result of gain matching one parameter.  If status is not _OK_,
+/// it describes why the gain match failed and the remaining fields
+/// other than parameter are meaningless.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct GainMatch {
+    parameter: String,
+    status: String,
+    gain: f64,
+    offset: f64,
+    residual: f64,
+    reference_peaks: Vec<f64>,
+    target_peaks: Vec<f64>,
+    calibration: Option<Calibration>,
+}
+impl GainMatch {
+    fn failed(parameter: &str, reason: &str) -> GainMatch {
+        GainMatch {
+            parameter: String::from(parameter),
+            status: String::from(reason),
+            gain: 0.0,
+            offset: 0.0,
+            residual: 0.0,
+            reference_peaks: vec![],
+            target_peaks: vec![],
+            calibration: None,
+        }
+    }
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct GainMatchResponse {
+    status: String,
+    detail: Vec<GainMatch>,
+}
+
+// Get the definition and parameter distributions of a spectrum:
+
+fn spectrum_distributions(
+    name: &str,
+    api: &SpectrumMessageClient,
+) -> Result<(SpectrumProperties, Vec<gainmatch::Distribution>), String> {
+    let def = api
+        .list_spectra(name)?
+        .into_iter()
+        .find(|s| s.name == name)
+        .ok_or_else(|| format!("Spectrum {} does not exist", name))?;
+    let contents = api.get_contents(name, f64::MIN, f64::MAX, f64::MIN, f64::MAX)?;
+    let distributions = gainmatch::distributions(&def, &contents)?;
+    Ok((def, distributions))
+}
+// Get the peaks of the reference spectrum, which must be 1D:
+
+fn reference_peaks(
+    name: &str,
+    count: usize,
+    api: &SpectrumMessageClient,
+) -> Result<Vec<f64>, String> {
+    let (def, distributions) = spectrum_distributions(name, api)?;
+    if def.type_name != "1D" {
+        return Err(format!(
+            "The reference spectrum {} must be a 1D spectrum",
+            name
+        ));
+    }
+    distributions[0].find_peaks(count)
+}
+// Get the current calibration of a parameter:
+
+fn current_calibration(
+    name: &str,
+    api: &ParameterMessageClient,
+) -> Result<Option<(f64, f64)>, String> {
+    api.list_parameters(name)?
+        .into_iter()
+        .find(|p| p.get_name() == name)
+        .map(|p| p.get_calibration())
+        .ok_or_else(|| format!("Parameter {} does not exist", name))
+}
+// Fit one parameter and, if requested, register the calibration.
+// Since the target peaks are positions of calibrated values if the
+// parameter already has a calibration, the new calibration is the
+// fit composed with the existing calibration.
+
+fn match_parameter(
+    parameter: &str,
+    target_peaks: Result<Vec<f64>, String>,
+    reference_peaks: &[f64],
+    apply: bool,
+    api: &ParameterMessageClient,
+) -> GainMatch {
+    let target_peaks = match target_peaks {
+        Ok(p) => p,
+        Err(s) => return GainMatch::failed(parameter, &s),
+    };
+    let fit = match gainmatch::fit_linear(&target_peaks, reference_peaks) {
+        Ok(f) => f,
+        Err(s) => return GainMatch::failed(parameter, &s),
+    };
+    let calibration = if apply {
+        let (gain, offset) = match current_calibration(parameter, api) {
+            Ok(Some((g, o))) => (fit.gain * g, fit.gain * o + fit.offset),
+            Ok(None) => (fit.gain, fit.offset),
+            Err(s) => return GainMatch::failed(parameter, &s),
+        };
+        if let Err(s) = api.set_parameter_calibration(parameter, Some((gain, offset))) {
+            return GainMatch::failed(parameter, &s);
+        }
+        Some(Calibration {
+            parameter: String::from(parameter),
+            gain,
+            offset,
+        })
+    } else {
+        None
+    };
+    GainMatch {
+        parameter: String::from(parameter),
+        status: String::from("OK"),
+        gain: fit.gain,
+        offset: fit.offset,
+        residual: fit.residual,
+        reference_peaks: reference_peaks.to_vec(),
+        target_peaks,
+        calibration,
+    }
+}
+
+///
+/// Fit linear calibrations that gain match parameters to a reference.
+///
+/// ### Parameters
+/// * reference - Name of a 1D reference spectrum whose peaks are found.
+/// * refpeak - Reference peak positions, can appear more than once.
+/// Exactly one of reference and refpeak must be supplied.
+/// * target - Name of the spectrum to gain match.  For 1D spectra, the
+/// spectrum's parameter is matched.  For summary spectra, all of the
+/// spectrum's parameters are matched.
+/// * targetpeak - Target peak positions, can appear more than once.
+/// Exactly one of target and targetpeak must be supplied.
+/// * parameter - The parameter to match.  Required with targetpeak.  With
+/// target, restricts the match to that parameter.
+/// * peaks - Number of peaks to find in spectra.  Defaults to 2 but is the
+/// number of refpeak values if those are given.
+/// * apply - If true the fits are registered as the parameter calibrations.
+/// * state - Histogramer state.
+/// * access - A read-only server refuses to apply the fits.
+///
+/// ### Returns
+/// * Json encoded GainMatchResponse.  The status is _OK_ only if all
+/// parameters were matched; the detail describes the match of each
+/// parameter.
+///
+#[allow(clippy::too_many_arguments)]
+#[get("/fit?<reference>&<target>&<parameter>&<peaks>&<refpeak>&<targetpeak>&<apply>")]
+pub fn fit_gains(
+    reference: OptionalString,
+    target: OptionalString,
+    parameter: OptionalString,
+    peaks: Option<usize>,
+    refpeak: OptionalF64Vec,
+    targetpeak: OptionalF64Vec,
+    apply: OptionalFlag,
+    state: &State<SharedHistogramChannel>,
+    access: readonly::WriteAccess,
+    _ready: busy::HistogramerReady,
+) -> Json<GainMatchResponse> {
+    let failed = |s: &str| {
+        Json(GainMatchResponse {
+            status: format!("Gain matching failed: {}", s),
+            detail: vec![],
+        })
+    };
+    let sapi = SpectrumMessageClient::new(&state.inner().lock().unwrap());
+    let papi = ParameterMessageClient::new(&state.inner().lock().unwrap());
+    let apply = apply.unwrap_or(false);
+
+    // Fitting is fine in a read-only server but applying the
+    // calibrations is not:
+
+    if apply {
+        if let Err(s) = access.check_writable() {
+            return failed(&s);
+        }
+    }
+
+    let reference_peaks = match (reference, refpeak) {
+        (Some(name), None) => {
+            match reference_peaks(&name, peaks.unwrap_or(DEFAULT_PEAK_COUNT), &sapi) {
+                Ok(p) => p,
+                Err(s) => return failed(&s),
+            }
+        }
+        (None, Some(mut p)) => {
+            p.sort_by(|a, b| a.total_cmp(b));
+            p
+        }
+        _ => return failed("Exactly one of reference and refpeak must be supplied"),
+    };
+
+    let targets = match (target, targetpeak, parameter) {
+        (None, Some(mut p), Some(parameter)) => {
+            p.sort_by(|a, b| a.total_cmp(b));
+            vec![(parameter, Ok(p))]
+        }
+        (None, Some(_), None) => return failed("parameter is required with targetpeak"),
+        (Some(name), None, parameter) => {
+            let distributions = match spectrum_distributions(&name, &sapi) {
+                Ok((_, d)) => d,
+                Err(s) => return failed(&s),
+            };
+            let targets: Vec<(String, Result<Vec<f64>, String>)> = distributions
+                .into_iter()
+                .filter(|d| match &parameter {
+                    Some(p) => *p == d.parameter,
+                    None => true,
+                })
+                .map(|d| {
+                    let peaks = d.find_peaks(reference_peaks.len());
+                    (d.parameter, peaks)
+                })
+                .collect();
+            if targets.is_empty() {
+                return failed(&format!(
+                    "{} is not a parameter of {}",
+                    parameter.unwrap_or_default(),
+                    name
+                ));
+            }
+            targets
+        }
+        _ => return failed("Exactly one of target and targetpeak must be supplied"),
+    };
+
+    let detail: Vec<GainMatch> = targets
+        .into_iter()
+        .map(|(p, peaks)| match_parameter(&p, peaks, &reference_peaks, apply, &papi))
+        .collect();
+    let status = if detail.iter().all(|m| m.status == "OK") {
+        String::from("OK")
+    } else {
+        String::from("Some parameters could not be gain matched")
+    };
+    Json(GainMatchResponse { status, detail })
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CalibrationListResponse {
+    status: String,
+    detail: Vec<Calibration>,
+}
+
+///
+/// List the calibrations of parameters.
+///
+/// ### Parameters
+/// * pattern - Optional glob pattern the names of listed parameters must
+/// match.  Defaults to _*_.  Only parameters with calibrations are listed.
+/// * state - Histogramer state.
+///
+/// ### Returns
+/// * Json encoded CalibrationListResponse.  The detail is sorted by
+/// parameter name.
+///
+#[get("/list?<pattern>")]
+pub fn list_calibrations(
+    pattern: OptionalString,
+    state: &State<SharedHistogramChannel>,
+    _ready: busy::HistogramerReady,
+) -> Json<CalibrationListResponse> {
+    let api = ParameterMessageClient::new(&state.inner().lock().unwrap());
+    Json(
+        match api.list_parameters(&pattern.unwrap_or(String::from("*"))) {
+            Ok(l) => {
+                let mut detail: Vec<Calibration> = l
+                    .into_iter()
+                    .filter_map(|p| {
+                        p.get_calibration().map(|(gain, offset)| Calibration {
+                            parameter: p.get_name(),
+                            gain,
+                            offset,
+                        })
+                    })
+                    .collect();
+                detail.sort_by(|a, b| a.parameter.cmp(&b.parameter));
+                CalibrationListResponse {
+                    status: String::from("OK"),
+                    detail,
+                }
+            }
+            Err(s) => CalibrationListResponse {
+                status: format!("Failed to list calibrations: {}", s),
+                detail: vec![],
+            },
+        },
+    )
+}
+
+///
+/// Remove the calibrations of parameters.
+///
+/// ### Parameters
+/// * pattern - Glob pattern; the calibrations of all matching parameters
+/// are removed.
+/// * state - Histogramer state.
+///
+/// ### Returns
+/// * Json encoded GenericResponse.
+///
+#[get("/clear?<pattern>")]
+pub fn clear_calibrations(
+    pattern: String,
+    state: &State<SharedHistogramChannel>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    let api = ParameterMessageClient::new(&state.inner().lock().unwrap());
+    let failure = "Failed to clear calibrations";
+    let parameters = match api.list_parameters(&pattern) {
+        Ok(l) => l,
+        Err(s) => return Json(GenericResponse::err(failure, &s)),
+    };
+    for p in parameters.iter().filter(|p| p.get_calibration().is_some()) {
+        if let Err(s) = api.set_parameter_calibration(&p.get_name(), None) {
+            return Json(GenericResponse::err(failure, &s));
+        }
+    }
+    Json(GenericResponse::ok(""))
+}
+
+#[cfg(test)]
+mod gainmatch_tests {
+    use super::*;
+    use crate::messaging;
+    use crate::parameters::EventParameter;
+    use crate::processing;
+    use crate::sharedmem::binder;
+    use crate::test::rest_common;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+
+    use std::sync::mpsc;
+
+    fn setup() -> Rocket<Build> {
+        rest_common::setup().mount(
+            "/",
+            routes![fit_gains, list_calibrations, clear_calibrations],
+        )
+    }
+    fn getstate(
+        r: &Rocket<Build>,
+    ) -> (
+        mpsc::Sender<messaging::Request>,
+        processing::ProcessingApi,
+        binder::BindingApi,
+    ) {
+        rest_common::get_state(r)
+    }
+    // Make the parameters p0, p1 and the spectra:
+    // * ref - 1D on p0 with peaks at 20.5 and 60.5
+    // * sum - Summary on p1 with peaks at 10.5 and 30.5
+
+    fn make_test_objects(chan: &mpsc::Sender<messaging::Request>) {
+        let papi = ParameterMessageClient::new(chan);
+        papi.create_parameter("p0").expect("Making p0");
+        papi.create_parameter("p1").expect("Making p1");
+        let p0 = papi.list_parameters("p0").expect("Listing p0")[0].get_id();
+        let p1 = papi.list_parameters("p1").expect("Listing p1")[0].get_id();
+
+        let sapi = SpectrumMessageClient::new(chan);
+        sapi.create_spectrum_1d("ref", "p0", 0.0, 100.0, 100)
+            .expect("Making ref");
+        sapi.create_spectrum_summary("sum", &[String::from("p1")], 0.0, 100.0, 100)
+            .expect("Making sum");
+
+        let mut events = vec![];
+        for (v0, v1) in [(20.5, 10.5), (60.5, 30.5)] {
+            for _ in 0..10 {
+                events.push(vec![
+                    EventParameter::new(p0, v0),
+                    EventParameter::new(p1, v1),
+                ]);
+            }
+        }
+        sapi.process_events(&events).expect("Processing events");
+    }
+
+    #[test]
+    fn fit_1() {
+        // Summary target, no apply:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+        make_test_objects(&chan);
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let reply = client
+            .get("/fit?reference=ref&target=sum")
+            .dispatch()
+            .into_json::<GainMatchResponse>()
+            .expect("Decoding JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail.len());
+        let m = &reply.detail[0];
+        assert_eq!("p1", m.parameter);
+        assert_eq!("OK", m.status);
+        assert!((m.gain - 2.0).abs() < 1.0e-6);
+        assert!((m.offset + 0.5).abs() < 1.0e-6);
+        assert_eq!(vec![20.5, 60.5], m.reference_peaks);
+        assert_eq!(vec![10.5, 30.5], m.target_peaks);
+        assert!(m.calibration.is_none());
+
+        let reply = client
+            .get("/list")
+            .dispatch()
+            .into_json::<CalibrationListResponse>()
+            .expect("Decoding JSON");
+        assert_eq!("OK", reply.status);
+        assert!(reply.detail.is_empty());
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn fit_2() {
+        // Peak lists, applied then cleared:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+        make_test_objects(&chan);
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let reply = client
+            .get("/fit?refpeak=60&refpeak=20&targetpeak=10&targetpeak=30&parameter=p1&apply=true")
+            .dispatch()
+            .into_json::<GainMatchResponse>()
+            .expect("Decoding JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail.len());
+        assert_eq!(
+            Some(Calibration {
+                parameter: String::from("p1"),
+                gain: 2.0,
+                offset: 0.0
+            }),
+            reply.detail[0].calibration
+        );
+
+        let reply = client
+            .get("/list")
+            .dispatch()
+            .into_json::<CalibrationListResponse>()
+            .expect("Decoding JSON");
+        assert_eq!(
+            vec![Calibration {
+                parameter: String::from("p1"),
+                gain: 2.0,
+                offset: 0.0
+            }],
+            reply.detail
+        );
+
+        let reply = client
+            .get("/clear?pattern=*")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Decoding JSON");
+        assert_eq!("OK", reply.status);
+        let reply = client
+            .get("/list")
+            .dispatch()
+            .into_json::<CalibrationListResponse>()
+            .expect("Decoding JSON");
+        assert!(reply.detail.is_empty());
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn fit_3() {
+        // Errors:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+        make_test_objects(&chan);
+
+        let client = Client::untracked(rocket).expect("Making client");
+        for uri in [
+            "/fit?target=sum",
+            "/fit?reference=ref&refpeak=1&target=sum",
+            "/fit?reference=sum&target=sum",
+            "/fit?reference=ref&targetpeak=10&targetpeak=30",
+            "/fit?reference=ref&target=sum&parameter=p0",
+            "/fit?reference=nosuch&target=sum",
+        ] {
+            let reply = client
+                .get(uri)
+                .dispatch()
+                .into_json::<GainMatchResponse>()
+                .expect("Decoding JSON");
+            assert!(reply.status.starts_with("Gain matching failed"), "{}", uri);
+            assert!(reply.detail.is_empty());
+        }
+        // Failing to find the target peaks fails the parameter:
+
+        let reply = client
+            .get("/fit?refpeak=10&refpeak=30&refpeak=50&target=sum")
+            .dispatch()
+            .into_json::<GainMatchResponse>()
+            .expect("Decoding JSON");
+        assert_eq!("Some parameters could not be gain matched", reply.status);
+        assert_eq!(1, reply.detail.len());
+        assert_eq!("Only found 2 of 3 peaks for p1", reply.detail[0].status);
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+}
@@ -20,7 +20,11 @@ use rocket::State;
 
 use super::*;
 
+use crate::conditions::{AggregateKind, MaskKind, RatioKind};
 use crate::messaging::condition_messages::{ConditionMessageClient, ConditionReply};
+use crate::messaging::spectrum_messages::{
+    bin_to_coord, bin_width, regular_bins, AxisSpecification, SpectrumMessageClient,
+};
 
 // Private mappings between SpecTcl <-> Rustogramer condition types:
 // Note making a static hashmap is possible but requires unsafe to access.
@@ -39,6 +43,17 @@ fn rg_condition_to_spctl(rg_type: &str) -> String {
         "Cut" => String::from("s"),
         "MultiCut" => String::from("gs"),
         "MultiContour" => String::from("gc"),
+        "MultiBand" => String::from("gb"),
+        "Sum" => String::from("sum"),
+        "Mean" => String::from("mean"),
+        "Max" => String::from("max"),
+        "Ratio" => String::from("ratio"),
+        "Difference" => String::from("difference"),
+        "ContourSet" => String::from("contours"),
+        "MaskEqual" => String::from("em"),
+        "MaskAnd" => String::from("am"),
+        "MaskNotAnd" => String::from("nm"),
+        "C2Band" => String::from("c2band"),
         _ => String::from("-unsupported-"),
     }
 }
@@ -63,7 +78,17 @@ pub struct GateProperties {
     points: Vec<GatePoint>,
     low: f64,
     high: f64,
-    // value : u32            // Note Rustogrammer has no support for mask conditions.
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+    #[serde(default)]
+    disabled_value: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    polygons: Vec<Vec<GatePoint>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<u32>,
+}
+fn default_enabled() -> bool {
+    true
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -103,6 +128,12 @@ fn marshall_points(p: &mut GateProperties, raw_pts: &Vec<(f64, f64)>) {
         p.low = p.points[0].x;
         p.high = p.points[1].x;
     }
+    // Mask conditions have one point whose x coordinate is the mask:
+
+    if ["em", "am", "nm"].contains(&p.type_name.as_str()) && p.points.len() == 1 {
+        p.value = Some(p.points[0].x as u32);
+        p.points.clear();
+    }
 }
 
 /// list conditions that match an optional _pattern_ string.
@@ -115,7 +146,7 @@ fn marshall_points(p: &mut GateProperties, raw_pts: &Vec<(f64, f64)>) {
 /// *   type - Condition type in SpecTcl notation e.g. a Rustogramer *BAND*
 /// has type *b*
 /// *   gates - Possibly empty array of dependent condition names.  This will only
-/// be nonempty if the type string is one of _+_, _-_, or _*_
+/// be nonempty if the type string is one of _+_, _-_, _*_ or _c2band_ (the bands).
 /// *   parameters - Possibly empty array of parameters that must be
 /// present in order for the condition to be evaluated (does not include
 /// parameters in dependent conditions).  This will only be nonempty for
@@ -125,6 +156,13 @@ fn marshall_points(p: &mut GateProperties, raw_pts: &Vec<(f64, f64)>) {
 /// *   high - the high limit of a _s_ condition - this is just the x coordinate
 /// of the second point in points.
 /// *   points for 2-d conditions an array of {x,y} objects.
+/// *   enabled - false if the condition has been disabled.
+/// *   disabled_value - For disabled conditions, the value they evaluate to.
+/// null for enabled conditions.
+/// *   polygons - Only present for _contours_ conditions.  An array with
+/// the array of {x,y} points of each contour.  points then contains the
+/// points of all of the contours.
+/// *   value - Only present for _em_, _am_ and _nm_ conditions.  The mask.
 ///
 /// The simplistic manner in which each GateProperties struct is filled in
 /// provides for the presence of data in fields where the SpecTcl REST
@@ -135,6 +173,7 @@ fn marshall_points(p: &mut GateProperties, raw_pts: &Vec<(f64, f64)>) {
 pub fn list_gates(
     pattern: Option<String>,
     state: &State<SharedHistogramChannel>,
+    _ready: busy::HistogramerReady,
 ) -> Json<ListReply> {
     // figure out the pattern:
 
@@ -160,6 +199,18 @@ pub fn list_gates(
                     points: Vec::<GatePoint>::new(),
                     low: 0.0,
                     high: 0.0,
+                    enabled: condition.disabled.is_none(),
+                    disabled_value: condition.disabled,
+                    polygons: condition
+                        .polygons
+                        .iter()
+                        .map(|pts| {
+                            pts.iter()
+                                .map(|(x, y)| GatePoint { x: *x, y: *y })
+                                .collect()
+                        })
+                        .collect(),
+                    value: None,
                 };
                 // Marshall the parameters:
 
@@ -192,7 +243,12 @@ pub fn list_gates(
 /// with the detail the actual messagse from the internal Histogram server.
 ///
 #[get("/delete?<name>")]
-pub fn delete_gate(name: String, state: &State<SharedHistogramChannel>) -> Json<GenericResponse> {
+pub fn delete_gate(
+    name: String,
+    state: &State<SharedHistogramChannel>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
     let api = ConditionMessageClient::new(&state.inner().lock().unwrap());
     let response = match api.delete_condition(&name) {
         ConditionReply::Deleted => GenericResponse::ok(""),
@@ -206,6 +262,75 @@ pub fn delete_gate(name: String, state: &State<SharedHistogramChannel>) -> Json<
     };
     Json(response)
 }
+//--------------------------------------------------------------------
+// Enable/disable conditions.
+
+/// Disable a condition without deleting it.
+///
+/// Query parameters:
+///
+/// * name - name of the condition to disable.
+/// * value - optional value the disabled condition evaluates to.  This
+/// defaults to true so that spectra gated on the condition are
+/// incremented as if they were ungated.
+///
+/// Spectra and compound conditions that depend on the condition continue
+/// to do so.  The condition is listed with its original definition.
+///
+/// * Successful response has status = "OK" and detail an empty string.
+/// * Failure response has status something like "Failed to disable condition {}"
+/// with the detail the actual message from the internal Histogram server.
+///
+#[get("/disable?<name>&<value>")]
+pub fn disable_gate(
+    name: String,
+    value: OptionalFlag,
+    state: &State<SharedHistogramChannel>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    let api = ConditionMessageClient::new(&state.inner().lock().unwrap());
+    let response = match api.disable_condition(&name, value.unwrap_or(true)) {
+        ConditionReply::Modified => GenericResponse::ok(""),
+        ConditionReply::Error(s) => {
+            GenericResponse::err(&format!("Failed to disable condition {}", name), &s)
+        }
+        _ => GenericResponse::err(
+            &format!("Failed to disable condition {}", name),
+            "Invalid response from server",
+        ),
+    };
+    Json(response)
+}
+/// Enable a condition that was disabled.
+///
+/// Requires the name of the condition as a query parameter.  Enabling
+/// a condition that is not disabled is not an error.
+///
+/// * Successful response has status = "OK" and detail an empty string.
+/// * Failure response has status something like "Failed to enable condition {}"
+/// with the detail the actual message from the internal Histogram server.
+///
+#[get("/enable?<name>")]
+pub fn enable_gate(
+    name: String,
+    state: &State<SharedHistogramChannel>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    let api = ConditionMessageClient::new(&state.inner().lock().unwrap());
+    let response = match api.enable_condition(&name) {
+        ConditionReply::Modified => GenericResponse::ok(""),
+        ConditionReply::Error(s) => {
+            GenericResponse::err(&format!("Failed to enable condition {}", name), &s)
+        }
+        _ => GenericResponse::err(
+            &format!("Failed to enable condition {}", name),
+            "Invalid response from server",
+        ),
+    };
+    Json(response)
+}
 //--------------------------------------------------------------
 // Edit/create conditions:
 
@@ -339,6 +464,132 @@ fn validate_multi1_parameters(
     }
     Ok((ids, low.unwrap(), high.unwrap()))
 }
+// Validate the parameters for an aggregate condition (sum, mean, max).
+// These are like multi slices but we want error messages that
+// make sense for the condition type:
+
+fn validate_aggregate_parameters(
+    parameter: OptionalStringVec,
+    low: Option<f64>,
+    high: Option<f64>,
+    state: &State<SharedHistogramChannel>,
+) -> Result<(Vec<u32>, f64, f64), String> {
+    if low.is_none() || high.is_none() {
+        return Err(String::from(
+            "Both low and high must be present to make an aggregate condition",
+        ));
+    }
+    let parameter = parameter.unwrap_or_default();
+    if parameter.is_empty() {
+        return Err(String::from(
+            "Aggregate conditions require at least one parameter",
+        ));
+    }
+    let mut ids = Vec::<u32>::new();
+    for name in parameter.iter() {
+        if let Some(id) = find_parameter_by_name(name, state) {
+            ids.push(id);
+        } else {
+            return Err(format!("Parameter: {} does not exist", name));
+        }
+    }
+    Ok((ids, low.unwrap(), high.unwrap()))
+}
+// Validate the parameters for a ratio or difference condition.
+// There must be exactly two parameters, the numerator (or minuend)
+// first, and a low and high:
+
+fn validate_ratio_parameters(
+    parameter: OptionalStringVec,
+    low: Option<f64>,
+    high: Option<f64>,
+    state: &State<SharedHistogramChannel>,
+) -> Result<(u32, u32, f64, f64), String> {
+    if low.is_none() || high.is_none() {
+        return Err(String::from(
+            "Both low and high must be present to make a ratio or difference condition",
+        ));
+    }
+    let parameter = parameter.unwrap_or_default();
+    if parameter.len() != 2 {
+        return Err(String::from(
+            "Ratio and difference conditions require exactly two parameters",
+        ));
+    }
+    let mut ids = Vec::<u32>::new();
+    for name in parameter.iter() {
+        if let Some(id) = find_parameter_by_name(name, state) {
+            ids.push(id);
+        } else {
+            return Err(format!("Parameter: {} does not exist", name));
+        }
+    }
+    Ok((ids[0], ids[1], low.unwrap(), high.unwrap()))
+}
+// Validate the parameters for a mask condition.  There must be
+// exactly one parameter and a mask value:
+
+fn validate_mask_parameters(
+    parameter: OptionalStringVec,
+    value: Option<u32>,
+    state: &State<SharedHistogramChannel>,
+) -> Result<(u32, u32), String> {
+    let parameter = parameter.unwrap_or_default();
+    if parameter.len() != 1 {
+        return Err(String::from(
+            "Mask conditions require exactly one parameter",
+        ));
+    }
+    let mask = match value {
+        Some(v) => v,
+        None => {
+            return Err(String::from(
+                "The value query parameter is required for mask conditions",
+            ))
+        }
+    };
+    match find_parameter_by_name(&parameter[0], state) {
+        Some(id) => Ok((id, mask)),
+        None => Err(format!("Parameter: {} does not exist", parameter[0])),
+    }
+}
+// Validate the parameters for a contour set.  These are like those of
+// a contour but the points are split into polygons using npoints, the
+// number of points in each polygon:
+
+type ContourSetParameters = (u32, u32, Vec<Vec<(f64, f64)>>);
+
+fn validate_contourset_parameters(
+    xpname: OptionalString,
+    ypname: OptionalString,
+    xcoord: OptionalF64Vec,
+    ycoord: OptionalF64Vec,
+    npoints: Option<Vec<usize>>,
+    state: &State<SharedHistogramChannel>,
+) -> Result<ContourSetParameters, String> {
+    let npoints = npoints.unwrap_or_default();
+    if npoints.is_empty() {
+        return Err(String::from(
+            "npoints is a mandatory query parameter for contour sets",
+        ));
+    }
+    let (xid, yid, points) = validate_2d_parameters(xpname, ypname, xcoord, ycoord, state)?;
+    let total: usize = npoints.iter().sum();
+    if total != points.len() {
+        return Err(format!(
+            "The polygons have {} points in all but there are {} coordinates",
+            total,
+            points.len()
+        ));
+    }
+    let mut polygons = Vec::new();
+    let mut first = 0;
+    for n in npoints {
+        polygons.push(points[first..first + n].to_vec());
+        first += n;
+    }
+    Ok((xid, yid, polygons))
+}
 // Validate the parameters for a multi parameter contour:
 
 type ParameterIdAndCoords = (Vec<u32>, Vec<(f64, f64)>);
@@ -410,6 +661,9 @@ fn validate_multi2_parameters(
 /// *  T, F conditions require nothing else.
 /// *  + - * conditions require condition - a list of conditions the condition depends on.
 ///These conditions must already be defined.
+/// *  c2band requires gate - exactly twice, the names of two bands on the same
+/// parameters.  The contour is the points of the first band followed by those of
+/// the second in reverse order and follows edits of the bands.
 /// *  c, b require:
 ///     -   xparameter, yparameter - the parameters the condition is set on.
 ///     -   xcoord, ycoord - the x/y coordinates of the points that make up the condition.
@@ -417,6 +671,32 @@ fn validate_multi2_parameters(
 ///     - parameter for the parameter the condition is set on.
 ///     - low - low limit of the slice.
 ///     - high - high limit of the slice.
+/// * gs requires parameter as many times as needed, low and high.
+/// * gc, gb require:
+///     - parameter - as many times as needed for the parameters the condition
+/// is evaluated on in pairs.
+///     - xcoord, ycoord - the x/y coordinates of the points of the contour
+/// or band.
+/// * sum, mean, max (Rustogramer only) require:
+///     - parameter - as many times as needed for the parameters the aggregate is
+/// computed over.
+///     - low, high - limits the aggregate must be in for the condition to be true.
+/// * ratio, difference (Rustogramer only) require:
+///     - parameter - exactly twice.  The condition is on first/second or
+/// first - second.
+///     - low, high - limits the computed value must be in for the condition
+/// to be true.
+/// * contours (Rustogramer only) is a set of contours.  It is true if the event
+/// is inside any of them and requires:
+///     -   xparameter, yparameter - the parameters the condition is set on.
+///     -   xcoord, ycoord - the x/y coordinates of the points of all of the
+/// contours, one contour after the other.
+///     -   npoints - once for each contour, the number of points in that contour.
+/// * em, am, nm are bit mask conditions on an integer parameter and require:
+///     -   parameter - the parameter the condition is set on.
+///     -   value - the mask.  em is true if the parameter is the mask, am
+/// if all of the bits in the mask are set in the parameter and nm if none
+/// of them are.
 /// Other condition types are not supported.
 ///
 /// The response is a GenericResponse.  On success,
@@ -431,7 +711,7 @@ fn validate_multi2_parameters(
 /// * detail provides more information about the error e.g
 ///   _only one name allowed_ or _parameter {} does not exist_
 ///
-#[get("/edit?<name>&<type>&<gate>&<xparameter>&<yparameter>&<parameter>&<xcoord>&<ycoord>&<low>&<high>")]
+#[get("/edit?<name>&<type>&<gate>&<xparameter>&<yparameter>&<parameter>&<xcoord>&<ycoord>&<npoints>&<low>&<high>&<value>")]
 pub fn edit_gate(
     name: String,
     r#type: String,
@@ -441,11 +721,19 @@ pub fn edit_gate(
     parameter: OptionalStringVec,
     xcoord: OptionalF64Vec,
     ycoord: OptionalF64Vec,
+    npoints: Option<Vec<usize>>,
     low: Option<f64>,
     high: Option<f64>,
+    value: Option<u32>,
     state: &State<SharedHistogramChannel>,
+    policy: &State<names::NamePolicy>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
 ) -> Json<GenericResponse> {
     let api = ConditionMessageClient::new(&state.inner().lock().unwrap());
+    if let Err(s) = policy.check_condition_name(&name, &api) {
+        return Json(GenericResponse::err("Invalid condition name", &s));
+    }
 
     let raw_result = match r#type.as_str() {
         "T" => api.create_true_condition(&name),
@@ -501,6 +789,18 @@ pub fn edit_gate(
                 ))
             }
         }
+        "c2band" => {
+            // There must be exactly two bands:
+
+            match gate {
+                Some(gate) if gate.len() == 2 => {
+                    api.create_c2band_condition(&name, &gate[0], &gate[1])
+                }
+                _ => ConditionReply::Error(String::from(
+                    "c2band conditions require exactly two bands as gate query parameters",
+                )),
+            }
+        }
         "s" => {
             // There must be one parameter, low and high.
 
@@ -525,6 +825,53 @@ pub fn edit_gate(
             Err(s) => ConditionReply::Error(s),
             Ok((ids, points)) => api.create_multicontour_condition(&name, &ids, &points),
         },
+        "gb" => match validate_multi2_parameters(parameter, xcoord, ycoord, state) {
+            Err(s) => ConditionReply::Error(s),
+            Ok((ids, points)) => api.create_multiband_condition(&name, &ids, &points),
+        },
+        "sum" | "mean" | "max" => {
+            let kind = match r#type.as_str() {
+                "sum" => AggregateKind::Sum,
+                "mean" => AggregateKind::Mean,
+                _ => AggregateKind::Max,
+            };
+            match validate_aggregate_parameters(parameter, low, high, state) {
+                Err(s) => ConditionReply::Error(s),
+                Ok((ids, low, high)) => {
+                    api.create_aggregate_condition(&name, kind, &ids, low, high)
+                }
+            }
+        }
+        "ratio" | "difference" => {
+            let kind = if r#type == "ratio" {
+                RatioKind::Ratio
+            } else {
+                RatioKind::Difference
+            };
+            match validate_ratio_parameters(parameter, low, high, state) {
+                Err(s) => ConditionReply::Error(s),
+                Ok((p1, p2, low, high)) => {
+                    api.create_ratio_condition(&name, kind, p1, p2, low, high)
+                }
+            }
+        }
+        "contours" => match validate_contourset_parameters(
+            xparameter, yparameter, xcoord, ycoord, npoints, state,
+        ) {
+            Err(s) => ConditionReply::Error(s),
+            Ok((xid, yid, polygons)) => api.create_contourset_condition(&name, xid, yid, &polygons),
+        },
+        "em" | "am" | "nm" => {
+            let kind = match r#type.as_str() {
+                "em" => MaskKind::Equal,
+                "am" => MaskKind::And,
+                _ => MaskKind::NotAnd,
+            };
+            match validate_mask_parameters(parameter, value, state) {
+                Err(s) => ConditionReply::Error(s),
+                Ok((pid, mask)) => api.create_mask_condition(&name, kind, pid, mask),
+            }
+        }
         _ => ConditionReply::Error(format!("Unsupported condition type: {}", r#type)),
     };
 
@@ -541,12 +888,328 @@ pub fn edit_gate(
     };
     Json(reply)
 }
+//--------------------------------------------------------------------
+// Conditions from regions of interest on bound spectra.
+
+// Convert a bin number on an axis of a bound spectrum to the world
+// coordinate of the bin's low edge.  The axis bins include the
+// under/overflow bins but those are only in shared memory if the
+// binding includes them; in that case shared memory bin 0 is the
+// underflow bin.  ROIs can't include the under/overflow bins.
+
+fn bin_low_edge(
+    axis: &AxisSpecification,
+    include_overflows: bool,
+    bin: u32,
+) -> Result<f64, String> {
+    let bins = regular_bins(*axis);
+    let first = if include_overflows { 1 } else { 0 };
+    if bin < first || bin >= bins + first {
+        return Err(format!(
+            "Bin {} is not one of the bins {} - {} on the axis",
+            bin,
+            first,
+            bins + first - 1
+        ));
+    }
+    Ok(bin_to_coord(bin - first + 1, *axis))
+}
+
+///
+/// Create a condition from a region of interest on a bound spectrum.
+/// The region of interest is given in shared memory bin coordinates
+/// (what a displayer that maps the shared memory sees) and is converted
+/// to world coordinates here.  The conversion accounts for whether the
+/// binding includes the under/overflow bins.
+///
+/// ### Parameters
+/// * name - name of the condition to create or replace.
+/// * spectrum - name of the bound spectrum the ROI was drawn on.
+/// * slot - the binding slot of the spectrum the ROI was drawn on.  Exactly
+/// one of spectrum and slot must be given.
+/// * xbin - x bin numbers.  For 1D spectra there must be exactly two which
+/// are the first and last bins in the slice.  For 2D spectra these are the
+/// x bins of the contour vertices.
+/// * ybin - y bin numbers of the contour vertices of a 2D spectrum.
+///
+/// ### Returns
+/// * Json encoded GenericResponse.  On success the detail is _Created_
+/// or _Replaced_ as for /edit.
+///
+/// 1D spectra produce a slice (s) condition on the spectrum's parameter
+/// from the low edge of the first bin to the high edge of the last bin.
+/// 2D spectra produce a contour (c) condition on the spectrum's parameters
+/// with vertices at the centers of the bins.  Other spectrum types are
+/// not supported.
+///
+#[allow(clippy::too_many_arguments)]
+#[get("/roi?<name>&<spectrum>&<slot>&<xbin>&<ybin>")]
+pub fn roi_gate(
+    name: String,
+    spectrum: OptionalString,
+    slot: Option<usize>,
+    xbin: Option<Vec<u32>>,
+    ybin: Option<Vec<u32>>,
+    state: &State<SharedHistogramChannel>,
+    b_state: &State<SharedBinderChannel>,
+    policy: &State<names::NamePolicy>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    let failure = format!("Could not create condition {} from ROI", name);
+    let api = ConditionMessageClient::new(&state.inner().lock().unwrap());
+    if let Err(s) = policy.check_condition_name(&name, &api) {
+        return Json(GenericResponse::err("Invalid condition name", &s));
+    }
+    // Find the binding:
+
+    let bapi = binder::BindingApi::new(&b_state.inner().lock().unwrap());
+    let bindings = match bapi.list_binding_details("*") {
+        Ok(l) => l,
+        Err(s) => return Json(GenericResponse::err(&failure, &s)),
+    };
+    let binding = match (spectrum, slot) {
+        (Some(spectrum), None) => bindings
+            .into_iter()
+            .find(|b| b.name == spectrum)
+            .ok_or_else(|| format!("Spectrum {} is not bound", spectrum)),
+        (None, Some(slot)) => bindings
+            .into_iter()
+            .find(|b| b.binding == slot)
+            .ok_or_else(|| format!("No spectrum is bound to slot {}", slot)),
+        _ => Err(String::from(
+            "Exactly one of spectrum and slot must be supplied",
+        )),
+    };
+    let binding = match binding {
+        Ok(b) => b,
+        Err(s) => return Json(GenericResponse::err(&failure, &s)),
+    };
+    // Get the spectrum definition:
+
+    let sapi = SpectrumMessageClient::new(&state.inner().lock().unwrap());
+    let def = match sapi.list_spectra(&binding.name) {
+        Ok(l) => l.into_iter().find(|s| s.name == binding.name),
+        Err(s) => return Json(GenericResponse::err(&failure, &s)),
+    };
+    let def = match def {
+        Some(d) => d,
+        None => {
+            return Json(GenericResponse::err(
+                &failure,
+                &format!("Spectrum {} does not exist", binding.name),
+            ))
+        }
+    };
+    let overflows = binding.include_overflows;
+    let xbin = xbin.unwrap_or_default();
+    let ybin = ybin.unwrap_or_default();
+
+    let raw_result = match (def.type_name.as_str(), def.xaxis, def.yaxis) {
+        ("1D", Some(xaxis), _) => {
+            if xbin.len() != 2 || !ybin.is_empty() {
+                ConditionReply::Error(String::from(
+                    "ROIs on 1D spectra require exactly two xbin values and no ybin values",
+                ))
+            } else {
+                let first = xbin[0].min(xbin[1]);
+                let last = xbin[0].max(xbin[1]);
+                match (
+                    bin_low_edge(&xaxis, overflows, first),
+                    bin_low_edge(&xaxis, overflows, last),
+                    find_parameter_by_name(&def.xparams[0], state),
+                ) {
+                    (Ok(low), Ok(high), Some(pid)) => {
+                        api.create_cut_condition(&name, pid, low, high + bin_width(xaxis))
+                    }
+                    (Err(s), _, _) | (_, Err(s), _) => ConditionReply::Error(s),
+                    (_, _, None) => ConditionReply::Error(format!(
+                        "Parameter {} does not exist",
+                        def.xparams[0]
+                    )),
+                }
+            }
+        }
+        ("2D", Some(xaxis), Some(yaxis)) => {
+            if xbin.len() != ybin.len() || xbin.len() < 3 {
+                ConditionReply::Error(String::from(
+                    "ROIs on 2D spectra require the same number (at least 3) of xbin and ybin values",
+                ))
+            } else {
+                let points: Result<Vec<(f64, f64)>, String> = xbin
+                    .iter()
+                    .zip(ybin.iter())
+                    .map(|(&x, &y)| {
+                        Ok((
+                            bin_low_edge(&xaxis, overflows, x)? + bin_width(xaxis) / 2.0,
+                            bin_low_edge(&yaxis, overflows, y)? + bin_width(yaxis) / 2.0,
+                        ))
+                    })
+                    .collect();
+                match (
+                    points,
+                    find_parameter_by_name(&def.xparams[0], state),
+                    find_parameter_by_name(&def.yparams[0], state),
+                ) {
+                    (Ok(points), Some(xid), Some(yid)) => {
+                        api.create_contour_condition(&name, xid, yid, &points)
+                    }
+                    (Err(s), _, _) => ConditionReply::Error(s),
+                    _ => ConditionReply::Error(format!("Parameters of {} do not exist", def.name)),
+                }
+            }
+        }
+        _ => ConditionReply::Error(format!(
+            "{} is a {} spectrum; ROI conditions can only be made on 1D and 2D spectra",
+            def.name, def.type_name
+        )),
+    };
+    let reply = match raw_result {
+        ConditionReply::Created => GenericResponse::ok("Created"),
+        ConditionReply::Replaced => GenericResponse::ok("Replaced"),
+        ConditionReply::Error(s) => GenericResponse::err(&failure, &s),
+        _ => GenericResponse::err(&failure, "Unexpected respones type from server"),
+    };
+    Json(reply)
+}
+//--------------------------------------------------------------------
+// Spectra gated on a condition.
+
+/// Describes a spectrum to which a condition is applied.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct GateApplication {
+    spectrum: String,
+    #[serde(rename = "type")]
+    spectrum_type: String,
+    current: bool,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct GateApplicationsReply {
+    status: String,
+    detail: Vec<GateApplication>,
+}
+
+/// List the spectra gated on a condition.  This is intended to verify
+/// that editing a condition left the spectra gated on it still gated on
+/// the edited condition.  A spectrum that is no longer gated by the
+/// condition in the dictionary (e.g. because the condition was deleted
+/// and re-created rather than edited) silently behaves as if ungated.
+///
+/// Requires the name of the condition as a query parameter.
+///
+/// The detail is an array of objects, sorted by spectrum name, containing:
+///
+/// * spectrum - name of a spectrum gated on the condition.
+/// * type - SpecTcl spectrum type of that spectrum.
+/// * current - true if the spectrum is still gated by the condition.
+///
+/// The status is _OK_ if all of the spectra are still gated by the
+/// condition.  Otherwise it reports that some spectra are not and the
+/// detail can be used to determine which ones.
+///
+#[get("/applications?<name>")]
+pub fn gate_applications(
+    name: String,
+    state: &State<SharedHistogramChannel>,
+    _ready: busy::HistogramerReady,
+) -> Json<GateApplicationsReply> {
+    let api = SpectrumMessageClient::new(&state.inner().lock().unwrap());
+    let reply = match api.gate_applications(&name) {
+        Ok(l) => {
+            let status = if l.iter().all(|a| a.current) {
+                String::from("OK")
+            } else {
+                format!("Some spectra are no longer gated on {}", name)
+            };
+            GateApplicationsReply {
+                status,
+                detail: l
+                    .into_iter()
+                    .map(|a| GateApplication {
+                        spectrum: a.spectrum,
+                        spectrum_type: spectrum::rg_sptype_to_spectcl(&a.type_name),
+                        current: a.current,
+                    })
+                    .collect(),
+            }
+        }
+        Err(s) => GateApplicationsReply {
+            status: format!("Failed to list spectra gated on {}: {}", name, s),
+            detail: vec![],
+        },
+    };
+    Json(reply)
+}
+//--------------------------------------------------------------------
+// Condition statistics.
+
+/// The statistics for one condition.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct GateStatistics {
+    name: String,
+    checked: u64,
+    accepted: u64,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct GateStatisticsReply {
+    status: String,
+    detail: Vec<GateStatistics>,
+}
+
+/// Report how many events each condition was evaluated for and how
+/// many of those it accepted.  Only conditions that cache their
+/// values are counted; True, False and disabled conditions always
+/// report zero.  The counts of the gates of spectra can be reset when
+/// the spectra are cleared (see spectrum::clear_spectra).
+///
+/// The optional pattern query parameter selects the conditions
+/// (defaults to _*_).  The detail is an array of objects, sorted by
+/// name, containing:
+///
+/// * name - name of the condition.
+/// * checked - number of events for which the condition was evaluated.
+/// * accepted - number of those events for which it was true.
+///
+#[get("/statistics?<pattern>")]
+pub fn gate_statistics(
+    pattern: OptionalString,
+    state: &State<SharedHistogramChannel>,
+    _ready: busy::HistogramerReady,
+) -> Json<GateStatisticsReply> {
+    let pat = pattern.unwrap_or_else(|| String::from("*"));
+    let api = SpectrumMessageClient::new(&state.inner().lock().unwrap());
+    Json(match api.get_gate_statistics(&pat) {
+        Ok(l) => GateStatisticsReply {
+            status: String::from("OK"),
+            detail: l
+                .into_iter()
+                .map(|(name, s)| GateStatistics {
+                    name,
+                    checked: s.checked,
+                    accepted: s.accepted,
+                })
+                .collect(),
+        },
+        Err(s) => GateStatisticsReply {
+            status: format!(
+                "Failed to get statistics for conditions matching '{}': {}",
+                pat, s
+            ),
+            detail: vec![],
+        },
+    })
+}
 
 #[cfg(test)]
 mod gate_tests {
     use super::*;
     use crate::messaging;
-    use crate::messaging::{condition_messages, parameter_messages};
+    use crate::messaging::{condition_messages, parameter_messages, spectrum_messages};
+    use crate::parameters;
     use crate::processing;
     use crate::test::rest_common;
 
@@ -559,7 +1222,19 @@ mod gate_tests {
     // note these are all unimplemented URLS so...
 
     fn setup() -> Rocket<Build> {
-        rest_common::setup().mount("/", routes![list_gates, delete_gate, edit_gate])
+        rest_common::setup().mount(
+            "/",
+            routes![
+                list_gates,
+                delete_gate,
+                edit_gate,
+                gate_applications,
+                roi_gate,
+                disable_gate,
+                enable_gate,
+                gate_statistics
+            ],
+        )
     }
     fn teardown(
         c: mpsc::Sender<messaging::Request>,
@@ -1690,8 +2365,10 @@ mod gate_tests {
                     cond_name: String::from("test"),
                     type_name: String::from("MultiCut"),
                     points: vec![(100.0, 0.0), (200.0, 0.0)],
+                    polygons: vec![],
                     gates: vec![],
-                    parameters: vec![1, 2, 3]
+                    parameters: vec![1, 2, 3],
+                    disabled: None
                 },
             ]),
             l
@@ -1773,6 +2450,911 @@ mod gate_tests {
 
         assert_eq!("Could not create/edit condition test", reply.status);
 
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn edit_29() {
+        // Good creation of each of the aggregate conditions:
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        for (rest_type, rg_type) in [("sum", "Sum"), ("mean", "Mean"), ("max", "Max")] {
+            let uri = format!(
+                "/edit?name={}&type={}&parameter=p1&parameter=p2&parameter=p3&low=100&high=200",
+                rest_type, rest_type
+            );
+            let req = client.get(uri.as_str());
+            let reply = req
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing Json");
+            assert_eq!("OK", reply.status);
+            assert_eq!("Created", reply.detail);
+
+            let api = condition_messages::ConditionMessageClient::new(&c);
+            let l = api.list_conditions(rest_type);
+            assert_eq!(
+                condition_messages::ConditionReply::Listing(vec![
+                    condition_messages::ConditionProperties {
+                        cond_name: String::from(rest_type),
+                        type_name: String::from(rg_type),
+                        points: vec![(100.0, 0.0), (200.0, 0.0)],
+                        polygons: vec![],
+                        gates: vec![],
+                        parameters: vec![1, 2, 3],
+                        disabled: None
+                    },
+                ]),
+                l
+            );
+        }
+
+        // List gives the REST type back:
+
+        let reply = client
+            .get("/list?pattern=mean")
+            .dispatch()
+            .into_json::<ListReply>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail.len());
+        assert_eq!("mean", reply.detail[0].type_name);
+        assert_eq!(100.0, reply.detail[0].low);
+        assert_eq!(200.0, reply.detail[0].high);
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn edit_30() {
+        // Aggregate condition errors - no parameters, bad parameter, no high.
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        for uri in [
+            "/edit?name=test&type=sum&low=100&high=200",
+            "/edit?name=test&type=sum&parameter=p1&parameter=p333&low=100&high=200",
+            "/edit?name=test&type=max&parameter=p1&low=100",
+        ] {
+            let reply = client
+                .get(uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!("Could not create/edit condition test", reply.status);
+        }
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn edit_31() {
+        // Good creation of ratio and difference conditions.
+        // The parameter order is preserved:
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        for (rest_type, rg_type) in [("ratio", "Ratio"), ("difference", "Difference")] {
+            let uri = format!(
+                "/edit?name={}&type={}&parameter=p3&parameter=p1&low=0.5&high=1.5",
+                rest_type, rest_type
+            );
+            let reply = client
+                .get(uri.as_str())
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing Json");
+            assert_eq!("OK", reply.status);
+            assert_eq!("Created", reply.detail);
+
+            let api = condition_messages::ConditionMessageClient::new(&c);
+            let l = api.list_conditions(rest_type);
+            assert_eq!(
+                condition_messages::ConditionReply::Listing(vec![
+                    condition_messages::ConditionProperties {
+                        cond_name: String::from(rest_type),
+                        type_name: String::from(rg_type),
+                        points: vec![(0.5, 0.0), (1.5, 0.0)],
+                        polygons: vec![],
+                        gates: vec![],
+                        parameters: vec![3, 1],
+                        disabled: None
+                    },
+                ]),
+                l
+            );
+        }
+
+        let reply = client
+            .get("/list?pattern=ratio")
+            .dispatch()
+            .into_json::<ListReply>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail.len());
+        assert_eq!("ratio", reply.detail[0].type_name);
+        assert_eq!(0.5, reply.detail[0].low);
+        assert_eq!(1.5, reply.detail[0].high);
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn edit_32() {
+        // Ratio condition errors - one parameter, three parameters,
+        // bad parameter, no low.
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        for uri in [
+            "/edit?name=test&type=ratio&parameter=p1&low=0&high=2",
+            "/edit?name=test&type=ratio&parameter=p1&parameter=p2&parameter=p3&low=0&high=2",
+            "/edit?name=test&type=difference&parameter=p1&parameter=p333&low=0&high=2",
+            "/edit?name=test&type=difference&parameter=p1&parameter=p2&high=2",
+        ] {
+            let reply = client
+                .get(uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!("Could not create/edit condition test", reply.status);
+        }
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn edit_33() {
+        // Names that violate the default name policy are rejected:
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        for uri in ["/edit?name=has%20space&type=T", "/edit?name=&type=T"] {
+            let reply = client
+                .get(uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!("Invalid condition name", reply.status);
+        }
+        let api = condition_messages::ConditionMessageClient::new(&c);
+        assert!(
+            if let ConditionReply::Listing(l) = api.list_conditions("*") {
+                l.is_empty()
+            } else {
+                false
+            }
+        );
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn edit_34() {
+        // Good creation of a contour set with two polygons; the list
+        // gives the points of each polygon:
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        let reply = client
+            .get("/edit?name=islands&type=contours&xparameter=p1&yparameter=p2&xcoord=0&xcoord=10&xcoord=5&xcoord=20&xcoord=30&xcoord=30&xcoord=20&ycoord=0&ycoord=0&ycoord=10&ycoord=20&ycoord=20&ycoord=30&ycoord=30&npoints=3&npoints=4")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!("Created", reply.detail);
+
+        let reply = client
+            .get("/list?pattern=islands")
+            .dispatch()
+            .into_json::<ListReply>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail.len());
+        let gate = &reply.detail[0];
+        assert_eq!("contours", gate.type_name);
+        assert_eq!(vec!["p1", "p2"], gate.parameters);
+        assert_eq!(7, gate.points.len());
+        assert_eq!(2, gate.polygons.len());
+        assert_eq!(
+            vec![
+                GatePoint { x: 0.0, y: 0.0 },
+                GatePoint { x: 10.0, y: 0.0 },
+                GatePoint { x: 5.0, y: 10.0 }
+            ],
+            gate.polygons[0]
+        );
+        assert_eq!(4, gate.polygons[1].len());
+        assert_eq!(GatePoint { x: 30.0, y: 30.0 }, gate.polygons[1][2]);
+
+        // Editing replaces the polygons:
+
+        let reply = client
+            .get("/edit?name=islands&type=contours&xparameter=p1&yparameter=p2&xcoord=0&xcoord=10&xcoord=5&ycoord=0&ycoord=0&ycoord=10&npoints=3")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!("Replaced", reply.detail);
+        let reply = client
+            .get("/list?pattern=islands")
+            .dispatch()
+            .into_json::<ListReply>()
+            .expect("Parsing JSON");
+        assert_eq!(1, reply.detail[0].polygons.len());
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn edit_35() {
+        // Contour set errors - no npoints, npoints doesn't match the
+        // coordinates, a polygon with too few points, bad parameter.
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        for uri in [
+            "/edit?name=test&type=contours&xparameter=p1&yparameter=p2&xcoord=0&xcoord=10&xcoord=5&ycoord=0&ycoord=0&ycoord=10",
+            "/edit?name=test&type=contours&xparameter=p1&yparameter=p2&xcoord=0&xcoord=10&xcoord=5&ycoord=0&ycoord=0&ycoord=10&npoints=4",
+            "/edit?name=test&type=contours&xparameter=p1&yparameter=p2&xcoord=0&xcoord=10&xcoord=5&xcoord=6&xcoord=7&ycoord=0&ycoord=0&ycoord=10&ycoord=6&ycoord=7&npoints=3&npoints=2",
+            "/edit?name=test&type=contours&xparameter=p1&yparameter=p333&xcoord=0&xcoord=10&xcoord=5&ycoord=0&ycoord=0&ycoord=10&npoints=3",
+        ] {
+            let reply = client
+                .get(uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!("Could not create/edit condition test", reply.status, "{}", uri);
+        }
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn edit_36() {
+        // Gamma band creation - listed as gb:
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        let reply = client
+            .get("/edit?name=test&type=gb&parameter=p1&parameter=p2&parameter=p3&xcoord=100&ycoord=50&xcoord=200&ycoord=60")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing Json");
+        assert_eq!("OK", reply.status);
+        assert_eq!("Created", reply.detail);
+
+        let api = condition_messages::ConditionMessageClient::new(&c);
+        assert_eq!(
+            condition_messages::ConditionReply::Listing(vec![
+                condition_messages::ConditionProperties {
+                    cond_name: String::from("test"),
+                    type_name: String::from("MultiBand"),
+                    points: vec![(100.0, 50.0), (200.0, 60.0)],
+                    polygons: vec![],
+                    gates: vec![],
+                    parameters: vec![1, 2, 3],
+                    disabled: None
+                },
+            ]),
+            api.list_conditions("test")
+        );
+
+        let reply = client
+            .get("/list")
+            .dispatch()
+            .into_json::<ListReply>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!("gb", reply.detail[0].type_name);
+        assert_eq!(
+            vec![String::from("p1"), String::from("p2"), String::from("p3")],
+            reply.detail[0].parameters
+        );
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn edit_37() {
+        // Gamma band failures:
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        for uri in [
+            "/edit?name=test&type=gb&parameter=p1&parameter=p2&xcoord=100&ycoord=50",
+            "/edit?name=test&type=gb&xcoord=100&ycoord=50&xcoord=200&ycoord=60",
+            "/edit?name=test&type=gb&parameter=p1&parameter=p333&xcoord=100&ycoord=50&xcoord=200&ycoord=60",
+        ] {
+            let reply = client
+                .get(uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!("Could not create/edit condition test", reply.status, "{}", uri);
+        }
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn edit_38() {
+        // Good creation of mask conditions; the list gives the mask
+        // as the value:
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        for (rest_type, rg_type) in [("em", "MaskEqual"), ("am", "MaskAnd"), ("nm", "MaskNotAnd")] {
+            let uri = format!(
+                "/edit?name={}&type={}&parameter=p2&value=129",
+                rest_type, rest_type
+            );
+            let reply = client
+                .get(uri.as_str())
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing Json");
+            assert_eq!("OK", reply.status);
+            assert_eq!("Created", reply.detail);
+
+            let api = condition_messages::ConditionMessageClient::new(&c);
+            let l = api.list_conditions(rest_type);
+            assert_eq!(
+                condition_messages::ConditionReply::Listing(vec![
+                    condition_messages::ConditionProperties {
+                        cond_name: String::from(rest_type),
+                        type_name: String::from(rg_type),
+                        points: vec![(129.0, 0.0)],
+                        polygons: vec![],
+                        gates: vec![],
+                        parameters: vec![2],
+                        disabled: None
+                    },
+                ]),
+                l
+            );
+
+            let reply = client
+                .get(format!("/list?pattern={}", rest_type))
+                .dispatch()
+                .into_json::<ListReply>()
+                .expect("Parsing JSON");
+            assert_eq!("OK", reply.status);
+            assert_eq!(1, reply.detail.len());
+            assert_eq!(rest_type, reply.detail[0].type_name);
+            assert_eq!(vec![String::from("p2")], reply.detail[0].parameters);
+            assert_eq!(Some(129), reply.detail[0].value);
+            assert!(reply.detail[0].points.is_empty());
+        }
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn edit_39() {
+        // Mask condition errors - no parameter, two parameters,
+        // bad parameter, no value.
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        for uri in [
+            "/edit?name=test&type=em&value=1",
+            "/edit?name=test&type=am&parameter=p1&parameter=p2&value=1",
+            "/edit?name=test&type=nm&parameter=p333&value=1",
+            "/edit?name=test&type=em&parameter=p1",
+        ] {
+            let reply = client
+                .get(uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!(
+                "Could not create/edit condition test", reply.status,
+                "{}",
+                uri
+            );
+        }
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn edit_40() {
+        // Contours from two bands; editing a band edits the contour:
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        for uri in [
+            "/edit?name=upper&type=b&xparameter=p1&yparameter=p2&xcoord=0&ycoord=100&xcoord=100&ycoord=100",
+            "/edit?name=lower&type=b&xparameter=p1&yparameter=p2&xcoord=0&ycoord=50&xcoord=100&ycoord=50",
+            "/edit?name=test&type=c2band&gate=upper&gate=lower",
+            "/edit?name=lower&type=b&xparameter=p1&yparameter=p2&xcoord=0&ycoord=10&xcoord=100&ycoord=10",
+        ] {
+            let reply = client
+                .get(uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!("OK", reply.status, "{}", uri);
+        }
+
+        let reply = client
+            .get("/list?pattern=test")
+            .dispatch()
+            .into_json::<ListReply>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail.len());
+        let gate = &reply.detail[0];
+        assert_eq!("c2band", gate.type_name);
+        assert_eq!(vec!["upper", "lower"], gate.gates);
+        assert_eq!(vec!["p1", "p2"], gate.parameters);
+        assert_eq!(
+            vec![
+                GatePoint { x: 0.0, y: 100.0 },
+                GatePoint { x: 100.0, y: 100.0 },
+                GatePoint { x: 100.0, y: 10.0 },
+                GatePoint { x: 0.0, y: 10.0 }
+            ],
+            gate.points
+        );
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn edit_41() {
+        // c2band failures - one band, a band that does not exist and
+        // bands on different parameters:
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+        let api = condition_messages::ConditionMessageClient::new(&c);
+        api.create_band_condition("b12", 1, 2, &[(0.0, 100.0), (100.0, 100.0)]);
+        api.create_band_condition("b13", 1, 3, &[(0.0, 50.0), (100.0, 50.0)]);
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        for uri in [
+            "/edit?name=test&type=c2band&gate=b12",
+            "/edit?name=test&type=c2band&gate=b12&gate=nosuch",
+            "/edit?name=test&type=c2band&gate=b12&gate=b13",
+        ] {
+            let reply = client
+                .get(uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!(
+                "Could not create/edit condition test", reply.status,
+                "{}",
+                uri
+            );
+        }
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn roi_1() {
+        // Slices from 1D spectra with and without under/overflows bound:
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&c);
+        sapi.create_spectrum_1d("s1", "p1", 0.0, 1024.0, 512)
+            .expect("Making s1");
+        sapi.create_spectrum_1d("s2", "p2", 0.0, 1024.0, 512)
+            .expect("Making s2");
+        bapi.bind("s1").expect("Binding s1");
+        bapi.bind_overflows("s2", false).expect("Binding s2");
+        let slot = bapi.list_bindings("s2").expect("Listing s2 binding")[0].0;
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        let reply = client
+            .get("/roi?name=cut1&spectrum=s1&xbin=51&xbin=11")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!("Created", reply.detail);
+        let uri = format!("/roi?name=cut2&slot={}&xbin=10&xbin=50", slot);
+        let reply = client
+            .get(uri.as_str())
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        // Both are [20, 102):
+
+        let mut reply = client
+            .get("/list")
+            .dispatch()
+            .into_json::<ListReply>()
+            .expect("Parsing JSON");
+        assert_eq!(2, reply.detail.len());
+        reply.detail.sort_by(|a, b| a.name.cmp(&b.name));
+        for (gate, parameter) in reply.detail.iter().zip(["p1", "p2"]) {
+            assert_eq!("s", gate.type_name);
+            assert_eq!(vec![String::from(parameter)], gate.parameters);
+            assert_eq!(20.0, gate.low);
+            assert_eq!(102.0, gate.high);
+        }
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn roi_2() {
+        // Contour from a 2D spectrum:
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&c);
+        sapi.create_spectrum_2d("s", "p1", "p2", 0.0, 100.0, 100, 0.0, 200.0, 100)
+            .expect("Making s");
+        bapi.bind_overflows("s", false).expect("Binding s");
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        let reply = client
+            .get("/roi?name=c&spectrum=s&xbin=10&ybin=10&xbin=20&ybin=10&xbin=20&ybin=20")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        let reply = client
+            .get("/list")
+            .dispatch()
+            .into_json::<ListReply>()
+            .expect("Parsing JSON");
+        assert_eq!(1, reply.detail.len());
+        let gate = &reply.detail[0];
+        assert_eq!("c", gate.type_name);
+        assert_eq!(
+            vec![String::from("p1"), String::from("p2")],
+            gate.parameters
+        );
+        assert_eq!(
+            vec![
+                GatePoint { x: 10.5, y: 21.0 },
+                GatePoint { x: 20.5, y: 21.0 },
+                GatePoint { x: 20.5, y: 41.0 }
+            ],
+            gate.points
+        );
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn roi_3() {
+        // Errors:
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&c);
+        sapi.create_spectrum_1d("s1", "p1", 0.0, 1024.0, 512)
+            .expect("Making s1");
+        sapi.create_spectrum_1d("unbound", "p1", 0.0, 1024.0, 512)
+            .expect("Making unbound");
+        sapi.create_spectrum_summary("sum", &[String::from("p1")], 0.0, 1024.0, 512)
+            .expect("Making sum");
+        bapi.bind("s1").expect("Binding s1");
+        bapi.bind("sum").expect("Binding sum");
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        for uri in [
+            "/roi?name=c&spectrum=unbound&xbin=1&xbin=2",
+            "/roi?name=c&xbin=1&xbin=2",
+            "/roi?name=c&spectrum=s1&xbin=1",
+            "/roi?name=c&spectrum=s1&xbin=0&xbin=2",
+            "/roi?name=c&spectrum=s1&xbin=1&xbin=513",
+            "/roi?name=c&spectrum=sum&xbin=1&xbin=2",
+        ] {
+            let reply = client
+                .get(uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!(
+                "Could not create condition c from ROI", reply.status,
+                "{}",
+                uri
+            );
+        }
+        let api = condition_messages::ConditionMessageClient::new(&c);
+        assert!(
+            if let ConditionReply::Listing(l) = api.list_conditions("*") {
+                l.is_empty()
+            } else {
+                false
+            }
+        );
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn applications_1() {
+        // Nothing gated:
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let api = condition_messages::ConditionMessageClient::new(&c);
+        api.create_true_condition("TRUE");
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        let reply = client
+            .get("/applications?name=TRUE")
+            .dispatch()
+            .into_json::<GateApplicationsReply>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(0, reply.detail.len());
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn applications_2() {
+        // Editing a condition keeps the spectra gated on it:
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let capi = condition_messages::ConditionMessageClient::new(&c);
+        capi.create_cut_condition("cut", 1, 100.0, 200.0);
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&c);
+        sapi.create_spectrum_1d("s2", "p2", 0.0, 1024.0, 1024)
+            .expect("Making s2");
+        sapi.create_spectrum_1d("s1", "p1", 0.0, 1024.0, 1024)
+            .expect("Making s1");
+        sapi.create_spectrum_1d("s3", "p3", 0.0, 1024.0, 1024)
+            .expect("Making s3");
+        sapi.gate_spectrum("s1", "cut").expect("Gating s1");
+        sapi.gate_spectrum("s2", "cut").expect("Gating s2");
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        let reply = client
+            .get("/edit?name=cut&type=s&parameter=p1&low=150&high=250")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("Replaced", reply.detail);
+
+        let reply = client
+            .get("/applications?name=cut")
+            .dispatch()
+            .into_json::<GateApplicationsReply>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(2, reply.detail.len());
+        assert_eq!("s1", reply.detail[0].spectrum);
+        assert_eq!("1", reply.detail[0].spectrum_type);
+        assert!(reply.detail[0].current);
+        assert_eq!("s2", reply.detail[1].spectrum);
+        assert_eq!("1", reply.detail[1].spectrum_type);
+        assert!(reply.detail[1].current);
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn applications_3() {
+        // Deleting and re-creating the condition leaves the spectrum
+        // gated on a condition that no longer exists:
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let capi = condition_messages::ConditionMessageClient::new(&c);
+        capi.create_true_condition("TRUE");
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&c);
+        sapi.create_spectrum_1d("s1", "p1", 0.0, 1024.0, 1024)
+            .expect("Making s1");
+        sapi.gate_spectrum("s1", "TRUE").expect("Gating s1");
+
+        capi.delete_condition("TRUE");
+        capi.create_true_condition("TRUE");
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        let reply = client
+            .get("/applications?name=TRUE")
+            .dispatch()
+            .into_json::<GateApplicationsReply>()
+            .expect("Parsing JSON");
+        assert_eq!("Some spectra are no longer gated on TRUE", reply.status);
+        assert_eq!(1, reply.detail.len());
+        assert_eq!("s1", reply.detail[0].spectrum);
+        assert!(!reply.detail[0].current);
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn statistics_1() {
+        // Conditions gating spectra are counted:
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let capi = condition_messages::ConditionMessageClient::new(&c);
+        let parapi = parameter_messages::ParameterMessageClient::new(&c);
+        let p1 = parapi.list_parameters("p1").expect("Listing p1")[0].get_id();
+        capi.create_cut_condition("cut", p1, 100.0, 200.0);
+        capi.create_true_condition("TRUE");
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&c);
+        sapi.create_spectrum_1d("s1", "p1", 0.0, 1024.0, 1024)
+            .expect("Making s1");
+        sapi.gate_spectrum("s1", "cut").expect("Gating s1");
+        let events = vec![
+            vec![parameters::EventParameter::new(p1, 150.0)],
+            vec![parameters::EventParameter::new(p1, 250.0)],
+            vec![parameters::EventParameter::new(p1, 175.0)],
+        ];
+        sapi.process_events(&events).expect("Processing events");
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        let reply = client
+            .get("/statistics")
+            .dispatch()
+            .into_json::<GateStatisticsReply>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(2, reply.detail.len());
+        assert_eq!("TRUE", reply.detail[0].name);
+        assert_eq!(0, reply.detail[0].checked);
+        assert_eq!("cut", reply.detail[1].name);
+        assert_eq!(3, reply.detail[1].checked);
+        assert_eq!(2, reply.detail[1].accepted);
+
+        let reply = client
+            .get("/statistics?pattern=%5B")
+            .dispatch()
+            .into_json::<GateStatisticsReply>()
+            .expect("Parsing JSON");
+        assert!(reply.status.starts_with("Failed to get statistics"));
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn disable_1() {
+        // Disable a cut - it's listed as disabled and the spectrum
+        // gated on it gets events outside the cut:
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let capi = condition_messages::ConditionMessageClient::new(&c);
+        let parapi = parameter_messages::ParameterMessageClient::new(&c);
+        let p1 = parapi.list_parameters("p1").expect("Listing p1")[0].get_id();
+        capi.create_cut_condition("cut", p1, 100.0, 200.0);
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&c);
+        sapi.create_spectrum_1d("s1", "p1", 0.0, 1024.0, 1024)
+            .expect("Making s1");
+        sapi.gate_spectrum("s1", "cut").expect("Gating s1");
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        let reply = client
+            .get("/disable?name=cut")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        let reply = client
+            .get("/list")
+            .dispatch()
+            .into_json::<ListReply>()
+            .expect("Parsing JSON");
+        assert_eq!(1, reply.detail.len());
+        assert_eq!("s", reply.detail[0].type_name);
+        assert_eq!(100.0, reply.detail[0].low);
+        assert_eq!(200.0, reply.detail[0].high);
+        assert!(!reply.detail[0].enabled);
+        assert_eq!(Some(true), reply.detail[0].disabled_value);
+
+        let events = vec![vec![crate::parameters::EventParameter::new(p1, 50.0)]];
+        sapi.process_events(&events).expect("Processing events");
+        let contents = sapi
+            .get_contents("s1", 0.0, 1024.0, 0.0, 0.0)
+            .expect("Getting contents");
+        assert_eq!(1, contents.len());
+        assert_eq!(50.0, contents[0].x);
+
+        // Enable it and the event is outside the cut again:
+
+        let reply = client
+            .get("/enable?name=cut")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        let reply = client
+            .get("/list")
+            .dispatch()
+            .into_json::<ListReply>()
+            .expect("Parsing JSON");
+        assert!(reply.detail[0].enabled);
+        assert!(reply.detail[0].disabled_value.is_none());
+
+        sapi.process_events(&events).expect("Processing events");
+        let contents = sapi
+            .get_contents("s1", 0.0, 1024.0, 0.0, 0.0)
+            .expect("Getting contents");
+        assert_eq!(1.0, contents[0].value);
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn disable_2() {
+        // Disable with a false value and disable/enable of nonexistent conditions:
+
+        let rocket = setup();
+        let (c, papi, bapi) = get_state(&rocket);
+        make_test_objects(&c);
+
+        let capi = condition_messages::ConditionMessageClient::new(&c);
+        capi.create_true_condition("TRUE");
+
+        let client = Client::untracked(rocket).expect("Creating rocket client");
+        let reply = client
+            .get("/disable?name=TRUE&value=false")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        if let condition_messages::ConditionReply::Listing(l) = capi.list_conditions("TRUE") {
+            assert_eq!(Some(false), l[0].disabled);
+        } else {
+            panic!("Listing conditions failed");
+        }
+
+        let reply = client
+            .get("/disable?name=junk")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("Failed to disable condition junk", reply.status);
+
+        let reply = client
+            .get("/enable?name=junk")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("Failed to enable condition junk", reply.status);
+
         teardown(c, &papi, &bapi);
     }
 }
@@ -8,6 +8,9 @@ use rocket::serde::{json::Json, Deserialize, Serialize};
 use rocket::State;
 
 ///  Spectrum statistics are in the following struct
+///  Since folds act when spectra are incremented, the under and overflow
+///  counts of folded spectra only include the parameters that
+///  survive the fold.  The fold field names that fold, if any.
 ///
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(crate = "rocket::serde")]
@@ -15,6 +18,8 @@ pub struct SpectrumStatistics {
     name: String,
     underflows: [u32; 2],
     overflows: [u32; 2],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fold: Option<String>,
 }
 /// This is turned into Json for the response:
 
@@ -35,6 +40,7 @@ pub struct SpectrumStatisticsReply {
 /// matches _pattern_
 /// *  state : The REST server state object which includes the
 /// request channel needed to build an API Object.
+/// *  cache : Responses computed within the cache TTL are reused (see cache.rs).
 /// ### Returns:
 /// * JSON encoded SpectrumStatisticsReply.  On success, status is _OK_ on failure
 /// it is an error nessage describing the problem.
@@ -47,6 +53,8 @@ pub struct SpectrumStatisticsReply {
 pub fn get_statistics(
     pattern: OptionalString,
     state: &State<SharedHistogramChannel>,
+    _ready: busy::HistogramerReady,
+    cache: cache::Cache,
 ) -> Json<SpectrumStatisticsReply> {
     let pat = if let Some(p) = pattern {
         p
@@ -54,38 +62,43 @@ pub fn get_statistics(
         String::from("*")
     };
 
-    let api = spectrum_messages::SpectrumMessageClient::new(&state.inner().lock().unwrap());
-    let spectra = api.list_spectra(&pat);
-    if let Err(s) = spectra {
-        return Json(SpectrumStatisticsReply {
-            status: format!("Failed to get spectrum list for {} : {}", pat, s),
+    // Polling clients share responses rather than each asking the
+    // histogramer:
+
+    Json(cache.get_or_compute(&format!("specstats?{}", pat), || {
+        let api = spectrum_messages::SpectrumMessageClient::new(&state.inner().lock().unwrap());
+        let spectra = api.list_spectra(&pat);
+        if let Err(s) = spectra {
+            return SpectrumStatisticsReply {
+                status: format!("Failed to get spectrum list for {} : {}", pat, s),
+                detail: vec![],
+            };
+        }
+        let spectra = spectra.unwrap();
+        let mut response = SpectrumStatisticsReply {
+            status: String::from("OK"),
             detail: vec![],
-        });
-    }
-    let spectra = spectra.unwrap();
-    let mut response = SpectrumStatisticsReply {
-        status: String::from("OK"),
-        detail: vec![],
-    };
-    for s in spectra {
-        let stats = api.get_statistics(&s.name);
-        if let Ok(st) = stats {
-            response.detail.push(SpectrumStatistics {
-                name: s.name.clone(),
-                underflows: [st.0, st.1],
-                overflows: [st.2, st.3],
-            });
+        };
+        for s in spectra {
+            let stats = api.get_statistics(&s.name);
+            if let Ok(st) = stats {
+                response.detail.push(SpectrumStatistics {
+                    name: s.name.clone(),
+                    underflows: [st.0, st.1],
+                    overflows: [st.2, st.3],
+                    fold: s.fold.clone(),
+                });
+            }
         }
-    }
-
-    Json(response)
+        response
+    }))
 }
 
 #[cfg(test)]
 mod getstats_tests {
     use super::*;
     use crate::messaging;
-    use crate::messaging::{parameter_messages, spectrum_messages};
+    use crate::messaging::{condition_messages, parameter_messages, spectrum_messages};
     use crate::parameters::{Event, EventParameter};
     use crate::processing;
     use crate::test::rest_common;
@@ -319,6 +332,48 @@ mod getstats_tests {
         assert_eq!(vec![1, 0], stats.underflows);
         assert_eq!(vec![2, 0], stats.overflows);
 
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn getstats_6() {
+        // Folded parameters don't overflow a folded gamma spectrum:
+
+        let rocket = setup();
+        let (c, papi, bapi) = getstate(&rocket);
+        let api = spectrum_messages::SpectrumMessageClient::new(&c);
+        let capi = condition_messages::ConditionMessageClient::new(&c);
+        match capi.create_multicut_condition("fold", &[1, 2], 1500.0, 3000.0) {
+            condition_messages::ConditionReply::Created => {}
+            _ => panic!("Failed to make the fold"),
+        }
+        api.create_spectrum_multi1d(
+            "gamma",
+            &[String::from("p1"), String::from("p2")],
+            0.0,
+            1024.0,
+            1024,
+        )
+        .expect("Making gamma spectrum");
+        api.fold_spectrum("gamma", "fold").expect("Folding gamma");
+
+        let events = make_events();
+        assert!(api.process_events(&events).is_ok());
+
+        let client = Client::tracked(rocket).expect("Creating client");
+        let request = client.get("/?pattern=gamma");
+        let reply = request
+            .dispatch()
+            .into_json::<SpectrumStatisticsReply>()
+            .expect("Parsing json");
+
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail.len());
+        let stats = &reply.detail[0];
+        assert_eq!("gamma", stats.name);
+        assert_eq!(vec![3, 0], stats.underflows);
+        assert_eq!(vec![0, 0], stats.overflows); // All in the fold.
+        assert_eq!(Some(String::from("fold")), stats.fold);
+
         teardown(c, &papi, &bapi);
     }
 }
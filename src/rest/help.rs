@@ -0,0 +1,695 @@
+//! Implements the /spectcl/help domain.  This makes the REST
+//! interface discoverable by script writers.  For each route it
+//! describes the method, the query parameters the route accepts, what
+//! it does and an example request with valid values.
+//!
+//! The routes and their query parameters come from the routes Rocket
+//! actually has mounted so they can't drift from the handlers.  The
+//! descriptions and examples come from the ROUTE_EXAMPLES table below.
+//! Routes that are not in the table get an example with placeholders for
+//! their query parameters.
+//!
+//! *  /spectcl/help - describes all routes.
+//! *  /spectcl/help/route - describes the routes whose path relative to
+//!    /spectcl is route (e.g. /spectcl/help/spectrum/create), or, if there
+//!    are none, the routes under that path (e.g. /spectcl/help/spectrum).
+//!
+use rocket::request::{FromRequest, Outcome};
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// The prefix of the routes that are described.  Routes are
+/// looked up and described relative to this prefix.
+///
+pub const ROUTE_PREFIX: &str = "/spectcl/";
+
+/// The route descriptions and examples.  Each element is:
+///
+/// * The route path relative to ROUTE_PREFIX.
+/// * What a request to the route does.
+/// * An example request relative to ROUTE_PREFIX.  Query values are
+///   not URL encoded; that's done when the example is described.
+///
+/// The examples assume parameters event.raw.00 and event.raw.01, a 1d
+/// spectrum test on event.raw.00, a 2d spectrum test2 on both and a
+/// slice condition cut.  Folds need a gamma condition, gamma.
+///
+pub const ROUTE_EXAMPLES: &[(&str, &str, &str)] = &[
+    ("analyze/buffer", "Set the data source read buffer size", "analyze/buffer?bytes=1048576"),
+    (
+        "analyze/endrun",
+        "Write spectra to a file for each run at its end",
+        "analyze/endrun?spectrum=*&file=run-{run}.json&format=json&clear=true",
+    ),
+    ("analyze/endrun/cancel", "Stop writing spectra at the end of each run", "analyze/endrun/cancel"),
+    ("analyze/endrun/status", "End of run write policy and last write", "analyze/endrun/status"),
+    ("analyze/pause", "Pause analysis keeping the events in flight", "analyze/pause"),
+    ("analyze/readstats", "Read buffer size and read statistics", "analyze/readstats"),
+    ("analyze/resume", "Resume paused analysis", "analyze/resume"),
+    ("analyze/sample", "Histogram only one of every factor events", "analyze/sample?factor=10&compensate=true"),
+    ("analyze/size", "Set the number of events sent to the histogramer at a time", "analyze/size?events=1000"),
+    ("analyze/start", "Start analyzing the attached data source", "analyze/start"),
+    ("analyze/status", "Analysis state and replay summary", "analyze/status"),
+    ("analyze/stop", "Stop analyzing the attached data source", "analyze/stop"),
+    ("analyze/timestamps", "Verify body header timestamps don't decrease for each source", "analyze/timestamps?enable=true"),
+    ("apply/apply", "Gate spectra on a condition", "apply/apply?gate=cut&spectrum=test&spectrum=test2"),
+    ("apply/history", "History of gate applications", "apply/history?pattern=*"),
+    ("apply/list", "List the gates applied to spectra", "apply/list?pattern=*"),
+    ("attach/attach", "Attach a data source", "attach/attach?type=file&source=run-0001-00.par"),
+    ("attach/detach", "Detach the data source", "attach/detach"),
+    ("attach/list", "Describe the attached data source", "attach/list"),
+    (
+        "attach/verify",
+        "Report spectra and conditions that use parameters the data source does not produce",
+        "attach/verify",
+    ),
+    (
+        "autosave/start",
+        "Periodically save spectra to files",
+        "autosave/start?spectrum=*&file=autosave-{seq}.json&interval=600&keep=3",
+    ),
+    ("autosave/status", "Auto-save status", "autosave/status"),
+    ("autosave/stop", "Stop periodically saving spectra", "autosave/stop"),
+    ("busy", "Histogramer busy timeout and count of requests that timed out", "busy"),
+    ("cache", "Response cache time to live and hit/miss counts", "cache"),
+    (
+        "calibration/delete",
+        "Stop computing a calibrated parameter",
+        "calibration/delete?name=energy",
+    ),
+    ("calibration/list", "List calibrated parameters", "calibration/list?pattern=*"),
+    (
+        "calibration/set",
+        "Compute a parameter by calibrating a raw parameter",
+        "calibration/set?name=energy&raw=event.raw.00&slope=0.5&offset=10&quadratic=0&units=keV",
+    ),
+    ("channel/get", "Get the value of a spectrum channel", "channel/get?spectrum=test&xchannel=100"),
+    ("channel/set", "Set the value of a spectrum channel", "channel/set?spectrum=test&xchannel=100&value=10"),
+    (
+        "channel/setroi",
+        "Set all channels in a rectangular region of a spectrum",
+        "channel/setroi?spectrum=test&xlow=10&xhigh=20&ylow=10&yhigh=20&value=10",
+    ),
+    ("crashes", "List the thread crash reports", "crashes"),
+    ("crashes/clear", "Remove the thread crash reports", "crashes/clear"),
+    (
+        "evbunpack/add",
+        "Unpack a source's fragments in event built data",
+        "evbunpack/add?evpname=evb&source=2&pipe=words",
+    ),
+    (
+        "evbunpack/create",
+        "Create an event built data unpacker",
+        "evbunpack/create?name=evb&frequency=100&basename=evb",
+    ),
+    ("evbunpack/list", "List event built data unpackers", "evbunpack/list"),
+    (
+        "evbunpack/use",
+        "Unpack physics events with an event built data unpacker",
+        "evbunpack/use?name=evb",
+    ),
+    ("exit", "Save the session and exit", "exit"),
+    ("filter/delete", "Delete a filter", "filter/delete?name=alphas"),
+    ("filter/disable", "Stop a filter writing events", "filter/disable?name=alphas"),
+    ("filter/enable", "Start a filter writing events", "filter/enable?name=alphas"),
+    (
+        "filter/file",
+        "Set the file a filter writes",
+        "filter/file?name=alphas&file=/scratch/alphas.par",
+    ),
+    (
+        "filter/format",
+        "Set the ring item version of a filter's file",
+        "filter/format?name=alphas&format=ring12",
+    ),
+    ("filter/list", "List filters", "filter/list?pattern=*"),
+    (
+        "filter/new",
+        "Make a filter that writes events satisfying a gate",
+        "filter/new?name=alphas&gate=alpha&parameter=alpha.energy&parameter=alpha.theta",
+    ),
+    ("filter/regate", "Change the gate of a filter", "filter/regate?name=alphas&gate=alpha2"),
+    (
+        "fit/create",
+        "Fit a gaussian on a linear background to a range of channels",
+        "fit/create?name=peak&spectrum=test&low=100&high=200&type=gaussian",
+    ),
+    ("fit/delete", "Delete a fit", "fit/delete?name=peak"),
+    (
+        "fit/gate",
+        "Make a slice gate of centroid +/- sigmas*sigma from a fit",
+        "fit/gate?name=peak&gate=peak.gate&sigmas=2",
+    ),
+    ("fit/list", "List fits and their parameters", "fit/list?pattern=*"),
+    ("fit/proc", "Not supported by rustogramer", "fit/proc"),
+    (
+        "fit/update",
+        "Refit fits to the current spectrum contents",
+        "fit/update?pattern=*&gates=false",
+    ),
+    ("fold/apply", "Fold spectra on a condition", "fold/apply?gate=gamma&spectrum=test"),
+    ("fold/list", "List folded spectra", "fold/list?pattern=*"),
+    ("fold/remove", "Unfold a spectrum", "fold/remove?spectrum=test"),
+    ("fs/download", "Download a file from the transfer directory", "fs/download?name=run-12.json"),
+    ("fs/listdir", "List a directory of the histogramer's filesystem", "fs/listdir?path=/home&pattern=*.json"),
+    ("fs/roots", "Directories from which to browse the histogramer's filesystem", "fs/roots"),
+    ("fs/stat", "Describe a file on the histogramer's filesystem", "fs/stat?path=/home/run-12.json"),
+    ("fs/transferdir", "The directory files are uploaded to and downloaded from", "fs/transferdir"),
+    ("fs/upload", "Upload a file to the transfer directory (POST the contents)", "fs/upload?name=spectra.json&replace=true"),
+    ("gainmatch/clear", "Remove parameter calibrations", "gainmatch/clear?pattern=*"),
+    ("gainmatch/fit", "Gain match spectra to a reference spectrum", "gainmatch/fit?reference=test&target=test_01&peaks=2&apply=false"),
+    ("gainmatch/list", "List parameter calibrations", "gainmatch/list?pattern=*"),
+    ("gate/applications", "List the spectra gated on a condition", "gate/applications?name=cut"),
+    ("gate/delete", "Delete a condition", "gate/delete?name=cut"),
+    ("gate/disable", "Make a condition evaluate to a fixed value", "gate/disable?name=cut&value=true"),
+    ("gate/edit", "Create or replace a condition", "gate/edit?name=cut&type=s&parameter=event.raw.00&low=100&high=200"),
+    ("gate/enable", "Re-enable a disabled condition", "gate/enable?name=cut"),
+    ("gate/list", "List conditions", "gate/list?pattern=*"),
+    ("gate/roi", "Make a condition from a region of interest in shared memory", "gate/roi?name=roi&spectrum=test&slot=0"),
+    ("gate/statistics", "Count the events conditions were evaluated for and accepted", "gate/statistics?pattern=*"),
+    ("help", "Describe all routes", "help"),
+    ("help/<route..>", "Describe the routes at or under a path", "help/spectrum/create"),
+    ("idspace", "Report on the parameter id spaces", "idspace"),
+    ("idspace/compact", "Release unused parameter id space storage", "idspace/compact"),
+    ("idspace/strategy", "Set how the parameter id map is stored", "idspace/strategy?strategy=sparse"),
+    ("integrate", "Integrate a spectrum in an area of interest", "integrate?spectrum=test&low=100&high=200"),
+    ("jobs/cancel", "Ask a running job to stop", "jobs/cancel?id=1"),
+    ("jobs/forget", "Forget a finished job", "jobs/forget?id=1"),
+    ("jobs/list", "List long running jobs", "jobs/list"),
+    ("jobs/status", "Status and progress of a job", "jobs/status?id=1"),
+    ("limits", "Request size limits", "limits"),
+    ("mirror", "List the shared memory mirrors", "mirror"),
+    ("parameter/check", "Set a parameter's changed flag", "parameter/check?name=event.raw.00"),
+    ("parameter/create", "Create a parameter", "parameter/create?name=event.raw.00&low=0&high=4096&bins=4096&units=channels"),
+    ("parameter/edit", "Change a parameter's metadata", "parameter/edit?name=event.raw.00&low=0&high=1024&bins=1024&units=channels"),
+    ("parameter/list", "List parameters", "parameter/list?filter=event.*"),
+    ("parameter/listnew", "List parameters whose metadata changed", "parameter/listnew"),
+    ("parameter/promote", "Add metadata to a raw parameter", "parameter/promote?name=event.raw.00&low=0&high=4096&bins=4096"),
+    ("parameter/uncheck", "Clear a parameter's changed flag", "parameter/uncheck?name=event.raw.00"),
+    ("parameter/version", "Version of the tree parameter implementation", "parameter/version"),
+    ("pman/add", "Not supported by rustogramer", "pman/add?pipeline=raw&processor=unpacker"),
+    ("pman/clear", "Not supported by rustogramer", "pman/clear?pipeline=raw"),
+    ("pman/clone", "Not supported by rustogramer", "pman/clone?source=raw&new=copy"),
+    ("pman/create", "Not supported by rustogramer", "pman/create?name=raw"),
+    ("pman/current", "Not supported by rustogramer", "pman/current"),
+    ("pman/list", "Not supported by rustogramer", "pman/list?pattern=*"),
+    ("pman/lsall", "Not supported by rustogramer", "pman/lsall?pattern=*"),
+    ("pman/lsevp", "Not supported by rustogramer", "pman/lsevp?pattern=*"),
+    ("pman/rm", "Not supported by rustogramer", "pman/rm?pipeline=raw&processor=unpacker"),
+    ("pman/use", "Not supported by rustogramer", "pman/use?name=raw"),
+    ("project", "Project a 2d spectrum onto an axis", "project?snapshot=false&source=test2&newname=test2_x&direction=x"),
+    (
+        "pseudo/create",
+        "Create a parameter computed from other parameters",
+        "pseudo/create?pseudo=sum&parameter=event.raw.00&parameter=event.raw.01&computation=event.raw.00%2Bevent.raw.01",
+    ),
+    ("pseudo/delete", "Delete a pseudo parameter", "pseudo/delete?name=sum"),
+    ("pseudo/list", "List pseudo parameters", "pseudo/list?pattern=*"),
+    ("readonly", "Whether the server rejects requests that modify it", "readonly"),
+    ("rawparameter/delete", "Not supported by rustogramer", "rawparameter/delete"),
+    ("rawparameter/list", "List raw parameters", "rawparameter/list?pattern=*"),
+    ("rawparameter/new", "Create a raw parameter", "rawparameter/new?name=event.raw.02&low=0&high=4096&bins=4096"),
+    ("ringformat", "Set the ring item format", "ringformat?major=11"),
+    ("ringformat/get", "Get the ring item format", "ringformat/get"),
+    (
+        "roottree/create",
+        "Periodically export spectra as a ROOT macro",
+        "roottree/create?tree=all&spectrum=*&file=spectra-{seq}.C&interval=60&keep=1",
+    ),
+    ("roottree/delete", "Stop and delete a spectrum export", "roottree/delete?tree=all"),
+    ("roottree/list", "List spectrum exports", "roottree/list?pattern=*"),
+    ("runinfo", "Describe the run being analyzed", "runinfo"),
+    ("runinfo/autoclear", "Set which spectra are cleared at each run's beginning", "runinfo/autoclear?spectrum=*"),
+    ("sbind/all", "Bind all spectra into shared memory", "sbind/all"),
+    ("sbind/get_update", "Get the shared memory update period", "sbind/get_update"),
+    ("sbind/list", "List the spectra bound into shared memory", "sbind/list?pattern=*"),
+    ("sbind/sbind", "Bind spectra into shared memory", "sbind/sbind?spectrum=test&spectrum=test2"),
+    ("sbind/set_update", "Set the shared memory update period", "sbind/set_update?seconds=2"),
+    ("sbind/transform", "Set the display transform of a bound spectrum", "sbind/transform?spectrum=test&xscale=0.5&xunit=keV"),
+    ("sbind/transforms", "List display transforms", "sbind/transforms?pattern=*"),
+    ("sbind/untransform", "Remove the display transform of a bound spectrum", "sbind/untransform?spectrum=test"),
+    ("scaler/clear", "Forget the accumulated scalers", "scaler/clear"),
+    ("scaler/list", "Summarize the scalers of each source id", "scaler/list"),
+    ("scaler/spectra", "Make scaler-vs-time spectra", "scaler/spectra?duration=3600&bins=360"),
+    ("scaler/spectra/cancel", "Stop making scaler-vs-time spectra", "scaler/spectra/cancel"),
+    ("scaler/spectra/status", "How scaler-vs-time spectra are made", "scaler/spectra/status"),
+    ("scaler/totals", "Scaler channel totals and rates", "scaler/totals?source=1"),
+    ("script", "Run a batch of definition commands", "script?command=gate t T {}"),
+    ("shmem/key", "Shared memory name", "shmem/key"),
+    ("shmem/size", "Shared memory size", "shmem/size"),
+    ("shmem/stats", "Shared memory usage statistics", "shmem/stats"),
+    ("shmem/variables", "SpecTcl shared memory variables", "shmem/variables"),
+    ("simulate/start", "Start generating simulated events", "simulate/start?parameter=event.raw.00&distribution=gaussian:512:20&rate=1000&events=100000"),
+    ("simulate/status", "Simulation status", "simulate/status"),
+    ("simulate/stop", "Stop generating simulated events", "simulate/stop"),
+    ("specstats", "Spectrum over/underflow statistics", "specstats?pattern=*"),
+    ("spectrum/clone", "Copy a spectrum onto other parameters", "spectrum/clone?name=test&newname=test_01&offset=1"),
+    ("spectrum/combine", "Snapshot sum of scaled spectra", "spectrum/combine?name=clean&spectrum=raw&spectrum=background&scale=1&scale=-1"),
+    ("spectrum/contents", "Get spectrum contents", "spectrum/contents?name=test&xlow=100&xhigh=200"),
+    ("spectrum/copy", "Duplicate a spectrum", "spectrum/copy?name=test&newname=test_copy&contents=true"),
+    ("spectrum/create", "Create a spectrum", "spectrum/create?name=test&type=1&parameters=event.raw.00&axes={0 1024 1024}"),
+    ("spectrum/delete", "Delete a spectrum", "spectrum/delete?name=test"),
+    ("spectrum/list", "List spectra", "spectrum/list?filter=*"),
+    ("spectrum/multicontents", "Get the contents of several spectra", "spectrum/multicontents?name=test&name=test2"),
+    ("spectrum/pidmatrix", "Make the 2d spectrum of each dE, E parameter pair", "spectrum/pidmatrix?de=det0.dE&e=det0.E&e=det1.E&axes={0 4096 512} {0 1024 256}&bind=true"),
+    ("spectrum/rebin", "Sum groups of bins into a new spectrum", "spectrum/rebin?name=test&newname=test_by4&xgroup=4"),
+    ("spectrum/shadow", "Keep a shadow copy of a spectrum", "spectrum/shadow?name=test&enable=true"),
+    ("spectrum/sum2d", "Create a 2d sum spectrum from 2d spectra", "spectrum/sum2d?name=sum&spectrum=test2"),
+    ("spectrum/temporary", "Make a spectrum temporary or permanent", "spectrum/temporary?name=test&enable=true&idle=600"),
+    ("spectrum/temporary/list", "List temporary spectra", "spectrum/temporary/list?pattern=*"),
+    ("spectrum/tobins", "Convert coordinates to bins on a spectrum's axes", "spectrum/tobins?name=test&x=100.5"),
+    ("spectrum/tocoords", "Convert bins to coordinates on a spectrum's axes", "spectrum/tocoords?name=test&xbin=101"),
+    ("spectrum/threshold", "Zero suppressed snapshot copy of a spectrum", "spectrum/threshold?name=test&newname=test_above&threshold=10"),
+    ("spectrum/zero", "Clear spectra", "spectrum/zero?pattern=*&gatestats=false"),
+    ("sread", "Read spectra from file", "sread?filename=spectra.json&format=json&snapshot=true"),
+    ("swrite", "Write spectra to file", "swrite?file=spectra.json&format=json&spectrum=test"),
+    ("tag/add", "Tag a spectrum", "tag/add?spectrum=test&tag=pid&tag=det=hagrid"),
+    ("tag/bind", "Bind the spectra with tags into shared memory", "tag/bind?tag=monitoring"),
+    ("tag/clear", "Clear the spectra with tags", "tag/clear?tag=pid&gatestats=false"),
+    ("tag/list", "List spectrum tags", "tag/list?pattern=*&tag=pid"),
+    ("tag/remove", "Remove tags from a spectrum", "tag/remove?spectrum=test&tag=pid"),
+    ("tag/write", "Write the spectra with tags to file", "tag/write?tag=pid&file=pid.json&format=json"),
+    ("template/create", "Define a spectrum template", "template/create?name=energy&type=1&axes={0 4096 4096}&gate=cut"),
+    ("template/delete", "Delete a spectrum template", "template/delete?name=energy"),
+    ("template/list", "List spectrum templates", "template/list?pattern=*"),
+    ("template/make", "Make a spectrum from a template", "template/make?template=energy&name=test_e&parameters=event.raw.00"),
+    ("trace/done", "End tracing", "trace/done?token=1"),
+    ("trace/establish", "Start tracing", "trace/establish?retention=60"),
+    ("trace/events", "Stream traces as server-sent events", "trace/events?category=spectrum"),
+    ("trace/fetch", "Fetch the traces since the last fetch", "trace/fetch?token=1"),
+    (
+        "trace/filter",
+        "Select the trace categories and object names traced",
+        "trace/filter?token=1&category=binding&pattern=*",
+    ),
+    ("trace/runlog", "The log of run state changes", "trace/runlog"),
+    ("treevariable/check", "Get a tree variable's changed flag", "treevariable/check?name=gain"),
+    ("treevariable/firetraces", "Not supported by rustogramer", "treevariable/firetraces?pattern=*"),
+    ("treevariable/list", "List tree variables", "treevariable/list"),
+    ("treevariable/set", "Set a tree variable", "treevariable/set?name=gain&value=1.5&units=keV"),
+    ("treevariable/setchanged", "Set a tree variable's changed flag", "treevariable/setchanged?name=gain"),
+    ("unbind/all", "Unbind all spectra from shared memory", "unbind/all"),
+    ("unbind/byid", "Not supported by rustogramer", "unbind/byid"),
+    ("unbind/byname", "Unbind spectra from shared memory", "unbind/byname?name=test"),
+    ("ungate", "Remove the gate from spectra", "ungate?name=test"),
+    ("unpacker/current", "Name of the unpacker physics events are unpacked with", "unpacker/current"),
+    ("unpacker/list", "List the registered physics event unpackers", "unpacker/list"),
+    (
+        "unpacker/use",
+        "Unpack physics events with a registered unpacker",
+        "unpacker/use?name=words&parameters=16",
+    ),
+    ("version", "Program version", "version"),
+];
+
+// Percent encode a query value.
+
+fn encode(value: &str) -> String {
+    let mut result = String::new();
+    for b in value.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.~*:".contains(&b) {
+            result.push(b as char);
+        } else {
+            result.push_str(&format!("%{:02X}", b));
+        }
+    }
+    result
+}
+// Encode the query values of an example.
+
+fn encode_example(example: &str) -> String {
+    match example.split_once('?') {
+        Some((path, query)) => {
+            let query: Vec<String> = query
+                .split('&')
+                .map(|p| match p.split_once('=') {
+                    Some((name, value)) => format!("{}={}", name, encode(value)),
+                    None => String::from(p),
+                })
+                .collect();
+            format!("{}?{}", path, query.join("&"))
+        }
+        None => String::from(example),
+    }
+}
+// Names of the query parameters in a route's query template
+// e.g. "<name>&<type>" gives name, type.
+
+fn query_parameters(template: Option<&str>) -> Vec<String> {
+    template
+        .unwrap_or("")
+        .split('&')
+        .map(|p| p.trim_matches(|c| c == '<' || c == '>' || c == '.'))
+        .filter(|p| !p.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// A mounted route as described by the help:
+///
+/// * method - HTTP method of the route.
+/// * route - path of the route.
+/// * parameters - the query parameters the route accepts.
+/// * description - what the route does.
+/// * example - an example request.
+///
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct RouteHelp {
+    method: String,
+    route: String,
+    parameters: Vec<String>,
+    description: String,
+    example: String,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct HelpResponse {
+    status: String,
+    detail: Vec<RouteHelp>,
+}
+
+/// Request guard that describes the routes Rocket has mounted
+/// under ROUTE_PREFIX sorted by route.
+///
+pub struct MountedRoutes(Vec<RouteHelp>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for MountedRoutes {
+    type Error = String;
+
+    async fn from_request(req: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        let mut result = Vec::new();
+        for route in req.rocket().routes() {
+            let path = route.uri.path().to_string();
+            let path = path.trim_end_matches('/');
+            let relative = match path.strip_prefix(ROUTE_PREFIX) {
+                Some(r) => r,
+                None => continue,
+            };
+            let query = route.uri.query().map(|q| q.to_string());
+            let parameters = query_parameters(query.as_deref());
+            let (description, example) = match ROUTE_EXAMPLES.iter().find(|e| e.0 == relative) {
+                Some(e) => (String::from(e.1), encode_example(e.2)),
+                None => {
+                    let query: Vec<String> = parameters
+                        .iter()
+                        .map(|p| format!("{}=<{}>", p, p))
+                        .collect();
+                    let example = if query.is_empty() {
+                        String::from(relative)
+                    } else {
+                        format!("{}?{}", relative, query.join("&"))
+                    };
+                    (String::new(), example)
+                }
+            };
+            result.push(RouteHelp {
+                method: route.method.to_string(),
+                route: String::from(path),
+                parameters,
+                description,
+                example: format!("{}{}", ROUTE_PREFIX, example),
+            });
+        }
+        result.sort_by(|a, b| a.route.cmp(&b.route));
+        Outcome::Success(MountedRoutes(result))
+    }
+}
+
+/// Describe all routes.
+///
+#[get("/")]
+pub fn help_all(routes: MountedRoutes) -> Json<HelpResponse> {
+    Json(HelpResponse {
+        status: String::from("OK"),
+        detail: routes.0,
+    })
+}
+/// Describe the routes whose path relative to /spectcl is _route_.  If
+/// there are none, the routes whose paths start with _route_ are described.
+///
+#[get("/<route..>")]
+pub fn help_route(route: PathBuf, routes: MountedRoutes) -> Json<HelpResponse> {
+    let wanted = format!("{}{}", ROUTE_PREFIX, route.to_string_lossy());
+    let under = format!("{}/", wanted);
+    let mut detail: Vec<RouteHelp> = routes
+        .0
+        .iter()
+        .filter(|r| r.route == wanted)
+        .cloned()
+        .collect();
+    if detail.is_empty() {
+        detail = routes
+            .0
+            .into_iter()
+            .filter(|r| r.route.starts_with(&under))
+            .collect();
+    }
+    let status = if detail.is_empty() {
+        format!("No such route {}", wanted)
+    } else {
+        String::from("OK")
+    };
+    Json(HelpResponse { status, detail })
+}
+
+#[cfg(test)]
+mod help_tests {
+    use super::*;
+    use crate::messaging;
+    use crate::processing;
+    use crate::rest::*;
+    use crate::sharedmem::binder;
+    use crate::test::rest_common;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+
+    use std::sync::mpsc;
+
+    // A test route that has no example:
+
+    #[get("/undocumented?<name>&<value>")]
+    fn undocumented(name: String, value: u32) -> String {
+        format!("{} {}", name, value)
+    }
+
+    fn setup() -> Rocket<Build> {
+        rest_common::setup()
+            .mount("/spectcl/help", routes![help_all, help_route])
+            .mount(
+                "/spectcl/spectrum",
+                routes![
+                    spectrum::list_spectrum,
+                    spectrum::delete_spectrum,
+                    spectrum::create_spectrum,
+                    spectrum::get_contents,
+                    spectrum::clone_spectrum,
+                    spectrum::threshold_spectrum
+                ],
+            )
+            .mount(
+                "/spectcl/gate",
+                routes![gates::list_gates, gates::edit_gate, gates::delete_gate],
+            )
+            .mount("/spectcl/integrate", routes![integrate::integrate])
+            .mount("/spectcl/crashes", routes![crashes::list_crashes])
+            .mount("/spectcl/test", routes![undocumented])
+    }
+    fn getstate(
+        r: &Rocket<Build>,
+    ) -> (
+        mpsc::Sender<messaging::Request>,
+        processing::ProcessingApi,
+        binder::BindingApi,
+    ) {
+        rest_common::get_state(r)
+    }
+    fn teardown(
+        c: mpsc::Sender<messaging::Request>,
+        p: &processing::ProcessingApi,
+        b: &binder::BindingApi,
+    ) {
+        rest_common::teardown(c, p, b);
+    }
+
+    #[test]
+    fn encode_1() {
+        assert_eq!("event.raw.00", encode("event.raw.00"));
+        assert_eq!("%7B0%201024%201024%7D", encode("{0 1024 1024}"));
+        assert_eq!(
+            "spectrum/create?name=test&axes=%7B0%201%202%7D",
+            encode_example("spectrum/create?name=test&axes={0 1 2}")
+        );
+        assert_eq!("crashes", encode_example("crashes"));
+    }
+    #[test]
+    fn parameters_1() {
+        assert!(query_parameters(None).is_empty());
+        assert_eq!(
+            vec![String::from("name"), String::from("type")],
+            query_parameters(Some("<name>&<type>"))
+        );
+    }
+    #[test]
+    fn examples_1() {
+        // Examples are for their routes and only use parameters
+        // the test routes accept:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let reply = client
+            .get("/spectcl/help")
+            .dispatch()
+            .into_json::<HelpResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert!(!reply.detail.is_empty());
+        for r in reply.detail.iter().filter(|r| !r.route.contains('<')) {
+            assert!(r.example.starts_with(&r.route), "{}", r.example);
+            if let Some((_, query)) = r.example.split_once('?') {
+                for p in query.split('&') {
+                    let name = p.split_once('=').unwrap().0;
+                    assert!(
+                        r.parameters.contains(&String::from(name)),
+                        "{} {}",
+                        r.route,
+                        name
+                    );
+                }
+            }
+        }
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn route_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let reply = client
+            .get("/spectcl/help/spectrum/create")
+            .dispatch()
+            .into_json::<HelpResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail.len());
+        let help = &reply.detail[0];
+        assert_eq!("GET", help.method);
+        assert_eq!("/spectcl/spectrum/create", help.route);
+        assert_eq!(
+            vec![
+                "name",
+                "type",
+                "parameters",
+                "axes",
+                "shadow",
+                "temporary",
+                "idle"
+            ],
+            help.parameters
+        );
+        assert_eq!("Create a spectrum", help.description);
+        assert_eq!(
+            "/spectcl/spectrum/create?name=test&type=1&parameters=event.raw.00&axes=%7B0%201024%201024%7D",
+            help.example
+        );
+
+        // The example is a valid request:
+
+        ParameterMessageClient::new(&chan)
+            .create_parameter("event.raw.00")
+            .expect("Making parameter");
+        let reply = client
+            .get(help.example.clone())
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn route_2() {
+        // Routes under a path and routes without parameters:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let reply = client
+            .get("/spectcl/help/gate")
+            .dispatch()
+            .into_json::<HelpResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        let routes: Vec<&str> = reply.detail.iter().map(|r| r.route.as_str()).collect();
+        assert_eq!(
+            vec![
+                "/spectcl/gate/delete",
+                "/spectcl/gate/edit",
+                "/spectcl/gate/list"
+            ],
+            routes
+        );
+
+        let reply = client
+            .get("/spectcl/help/crashes")
+            .dispatch()
+            .into_json::<HelpResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail.len());
+        assert!(reply.detail[0].parameters.is_empty());
+        assert_eq!("/spectcl/crashes", reply.detail[0].example);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn route_3() {
+        // Routes without examples get placeholders:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let reply = client
+            .get("/spectcl/help/test/undocumented")
+            .dispatch()
+            .into_json::<HelpResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail.len());
+        assert_eq!("", reply.detail[0].description);
+        assert_eq!(
+            "/spectcl/test/undocumented?name=<name>&value=<value>",
+            reply.detail[0].example
+        );
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn route_4() {
+        // No such route:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let reply = client
+            .get("/spectcl/help/nosuch/route")
+            .dispatch()
+            .into_json::<HelpResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("No such route /spectcl/nosuch/route", reply.status);
+        assert!(reply.detail.is_empty());
+
+        teardown(chan, &papi, &bapi);
+    }
+}
@@ -0,0 +1,316 @@
+//! Implements the /spectcl/idspace domain.  This reports on, and
+//! maintains, the parameter id spaces whose storage grows with
+//! the largest parameter id rather than the number of parameters:
+//!
+//! *  The map from the parameter ids in the event file to the
+//! ids of the histogramer's parameters (see parameters::ParameterIdMap).
+//! Event files with sparse, very large parameter ids can make
+//! a dense map huge.
+//! *  The index the histogramer uses to find the spectra an event
+//! increments.  This is indexed by parameter id and retains slots for
+//! spectra that have been deleted until it is compacted.
+//!
+//! The requests are:
+//!
+//! *  /spectcl/idspace - reports on the id spaces.
+//! *  /spectcl/idspace/compact - releases storage that is no longer needed.
+//! *  /spectcl/idspace/strategy?strategy=name - sets how the parameter id
+//! map is stored (dense, sparse or auto).
+//!
+use super::*;
+use crate::messaging::parameter_messages::ParameterMessageClient;
+use crate::messaging::spectrum_messages::SpectrumMessageClient;
+use crate::parameters;
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+
+/// Description of the event file parameter id map:
+///
+/// * strategy - how the map is stored: dense, sparse or auto.
+/// * sparse - true if the map is currently stored sparsely.
+/// * span - one more than the largest event file parameter id mapped.
+/// * mapped - number of event file parameter ids mapped.
+/// * allocated - number of map entries allocated.
+///
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct IdMap {
+    strategy: String,
+    sparse: bool,
+    span: usize,
+    mapped: usize,
+    allocated: usize,
+}
+/// Description of the id spaces:
+///
+/// * parameters - number of parameters defined.
+/// * largest_id - largest parameter id defined (0 if there are none).
+/// * spectrum_index_span - span of the parameter ids in the spectrum index.
+/// * spectrum_index_allocated - number of spectrum index slots allocated.
+/// * map - the event file parameter id map.
+///
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct IdSpace {
+    parameters: usize,
+    largest_id: u32,
+    spectrum_index_span: usize,
+    spectrum_index_allocated: usize,
+    map: IdMap,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct IdSpaceResponse {
+    status: String,
+    detail: IdSpace,
+}
+
+// Describe the id spaces.  Any failure replaces the status
+// of the response.
+
+fn describe_idspace(
+    hg: &State<SharedHistogramChannel>,
+    papi: &State<SharedProcessingApi>,
+) -> IdSpaceResponse {
+    let mut result = IdSpaceResponse {
+        status: String::from("OK"),
+        detail: IdSpace {
+            parameters: 0,
+            largest_id: 0,
+            spectrum_index_span: 0,
+            spectrum_index_allocated: 0,
+            map: IdMap {
+                strategy: String::from(""),
+                sparse: false,
+                span: 0,
+                mapped: 0,
+                allocated: 0,
+            },
+        },
+    };
+    let chan = hg.inner().lock().unwrap();
+    match ParameterMessageClient::new(&chan).list_parameters("*") {
+        Ok(params) => {
+            result.detail.parameters = params.len();
+            result.detail.largest_id = params.iter().map(|p| p.get_id()).max().unwrap_or(0);
+        }
+        Err(s) => result.status = format!("Failed to list parameters: {}", s),
+    }
+    match SpectrumMessageClient::new(&chan).get_id_span() {
+        Ok((span, allocated)) => {
+            result.detail.spectrum_index_span = span;
+            result.detail.spectrum_index_allocated = allocated;
+        }
+        Err(s) => result.status = format!("Failed to get spectrum index span: {}", s),
+    }
+    match papi.inner().lock().unwrap().get_id_map() {
+        Ok(map) => {
+            result.detail.map = IdMap {
+                strategy: map.strategy.to_string(),
+                sparse: map.sparse,
+                span: map.span,
+                mapped: map.mapped,
+                allocated: map.allocated,
+            };
+        }
+        Err(s) => result.status = format!("Failed to get parameter id map: {}", s),
+    }
+    result
+}
+
+/// Report on the parameter id spaces.
+///
+#[get("/")]
+pub fn report_idspace(
+    hg: &State<SharedHistogramChannel>,
+    papi: &State<SharedProcessingApi>,
+    _ready: busy::HistogramerReady,
+) -> Json<IdSpaceResponse> {
+    Json(describe_idspace(hg, papi))
+}
+/// Release id space storage that is no longer needed, then report
+/// on the id spaces.
+///
+#[get("/compact")]
+pub fn compact_idspace(
+    hg: &State<SharedHistogramChannel>,
+    papi: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<IdSpaceResponse> {
+    let spectrum_result = SpectrumMessageClient::new(&hg.inner().lock().unwrap()).compact_index();
+    let map_result = papi.inner().lock().unwrap().compact_id_map();
+
+    let mut result = describe_idspace(hg, papi);
+    if let Err(s) = spectrum_result {
+        result.status = format!("Failed to compact the spectrum index: {}", s);
+    }
+    if let Err(s) = map_result {
+        result.status = format!("Failed to compact the parameter id map: {}", s);
+    }
+    Json(result)
+}
+/// Set how the parameter id map is stored.  The strategy is one of
+/// dense, sparse or auto.  It applies to the current map and the maps made
+/// for files attached later.
+///
+#[get("/strategy?<strategy>")]
+pub fn set_idmap_strategy(
+    strategy: String,
+    papi: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    let response = match parameters::IdMapStrategy::parse(&strategy) {
+        Ok(s) => match papi.inner().lock().unwrap().set_id_map_strategy(s) {
+            Ok(_) => GenericResponse::ok(""),
+            Err(e) => GenericResponse::err("Failed to set the parameter id map strategy", &e),
+        },
+        Err(e) => GenericResponse::err("Failed to set the parameter id map strategy", &e),
+    };
+    Json(response)
+}
+
+#[cfg(test)]
+mod idspace_tests {
+    use super::*;
+    use crate::messaging;
+    use crate::processing;
+    use crate::sharedmem::binder;
+    use crate::test::rest_common;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+
+    use std::sync::mpsc;
+
+    fn setup() -> Rocket<Build> {
+        rest_common::setup().mount(
+            "/",
+            routes![report_idspace, compact_idspace, set_idmap_strategy],
+        )
+    }
+    fn getstate(
+        r: &Rocket<Build>,
+    ) -> (
+        mpsc::Sender<messaging::Request>,
+        processing::ProcessingApi,
+        binder::BindingApi,
+    ) {
+        rest_common::get_state(r)
+    }
+    fn teardown(
+        c: mpsc::Sender<messaging::Request>,
+        p: &processing::ProcessingApi,
+        b: &binder::BindingApi,
+    ) {
+        rest_common::teardown(c, p, b);
+    }
+    // Make parameters p.0..p.n-1 and a 1d spectrum on each.
+
+    fn make_spectra(c: &mpsc::Sender<messaging::Request>, n: usize) {
+        let papi = ParameterMessageClient::new(c);
+        let sapi = SpectrumMessageClient::new(c);
+        for i in 0..n {
+            let pname = format!("p.{}", i);
+            papi.create_parameter(&pname).expect("Making parameter");
+            sapi.create_spectrum_1d(&format!("s.{}", i), &pname, 0.0, 1024.0, 1024)
+                .expect("Making spectrum");
+        }
+    }
+
+    #[test]
+    fn report_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+        make_spectra(&chan, 4);
+        let pid = ParameterMessageClient::new(&chan)
+            .list_parameters("p.3")
+            .expect("Listing parameters")[0]
+            .get_id();
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let reply = client
+            .get("/")
+            .dispatch()
+            .into_json::<IdSpaceResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(4, reply.detail.parameters);
+        assert_eq!(pid, reply.detail.largest_id);
+        assert_eq!(pid as usize + 1, reply.detail.spectrum_index_span);
+        assert_eq!("auto", reply.detail.map.strategy);
+        assert!(!reply.detail.map.sparse);
+        assert_eq!(0, reply.detail.map.mapped);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn compact_1() {
+        // Deleting the spectrum of the largest parameter id
+        // leaves its index slot until compacted:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+        make_spectra(&chan, 4);
+        let pid = ParameterMessageClient::new(&chan)
+            .list_parameters("p.2")
+            .expect("Listing parameters")[0]
+            .get_id();
+        SpectrumMessageClient::new(&chan)
+            .delete_spectrum("s.3")
+            .expect("Deleting spectrum");
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let reply = client
+            .get("/compact")
+            .dispatch()
+            .into_json::<IdSpaceResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(pid as usize + 1, reply.detail.spectrum_index_span);
+        assert_eq!(pid as usize + 1, reply.detail.spectrum_index_allocated);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn strategy_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let reply = client
+            .get("/strategy?strategy=sparse")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        let map = papi.get_id_map().expect("Getting id map");
+        assert_eq!(parameters::IdMapStrategy::Sparse, map.strategy);
+        assert!(map.sparse);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn strategy_2() {
+        // Invalid strategy:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let reply = client
+            .get("/strategy?strategy=compact")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("Failed to set the parameter id map strategy", reply.status);
+        assert_eq!(
+            parameters::IdMapStrategy::Auto,
+            papi.get_id_map().expect("Getting id map").strategy
+        );
+
+        teardown(chan, &papi, &bapi);
+    }
+}
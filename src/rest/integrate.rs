@@ -55,7 +55,7 @@ fn generate_aoi1d(
                 condition_messages::ConditionReply::Listing(l) => {
                     if l.len() != 1 {
                         Err(format!("{} either is a non-existent condition or is pattern that has more than one match", gate_name))
-                    } else if l[0].type_name == "Cut" {
+                    } else if l[0].type_name == "Cut" || l[0].type_name == "MultiCut" {
                         let condition = l[0].clone();
                         Ok(integration::AreaOfInterest::Oned {
                             low: condition.points[0].0,
@@ -63,7 +63,7 @@ fn generate_aoi1d(
                         })
                     } else {
                         Err(format!(
-                            "{} is not a Cut and must be for 1-d integrations",
+                            "{} is not a Cut or MultiCut and must be for 1-d integrations",
                             gate_name
                         ))
                     }
@@ -107,8 +107,9 @@ fn generate_aoi2d(
                 "For a 2d spectrum only the gate _OR_ the AOI coordinates are allowed, not both",
             ));
         }
-        // Get gate information - must be a contour and we
-        // then reconstruct it to make it a 2d area of interest:
+        // Get gate information - must be contour-like (Contour, C2Band,
+        // MultiContour or ContourSet) and we then reconstruct it to make
+        // it a 2d area of interest:
         match api.list_conditions(&gate_name) {
             condition_messages::ConditionReply::Listing(l) => {
                 if l.len() != 1 {
@@ -118,8 +119,8 @@ fn generate_aoi2d(
                     ));
                 }
 
-                match condition_messages::reconstitute_contour(l[0].clone()) {
-                    Ok(c) => Ok(integration::AreaOfInterest::Twod(c)),
+                match integration::contour_aoi(l[0].clone()) {
+                    Ok(aoi) => Ok(aoi),
                     Err(s) => Err(format!(
                         "Failed to construct a contour from {} : {}",
                         gate_name, s
@@ -151,8 +152,10 @@ fn generate_aoi2d(
                 cond_name: String::from("junk"),
                 type_name: String::from("Contour"),
                 points: pts,
+                polygons: vec![],
                 gates: vec![],
                 parameters: vec![0, 1],
+                disabled: None,
             };
             match condition_messages::reconstitute_contour(props) {
                 Ok(c) => Ok(integration::AreaOfInterest::Twod(c)),
@@ -172,10 +175,94 @@ fn generate_aoi2d(
     }
 }
 
-// Given spectrum characteristics and the inputs that might
-// describe the AOI, return an integration::AreaOfInterest
+// A gamma contour (MultiContour) has the same shape as a contour so
+// it can be reconstituted as one:
 
-fn generate_aoi(
+fn as_contour(
+    props: condition_messages::ConditionProperties,
+) -> condition_messages::ConditionProperties {
+    if props.type_name == "MultiContour" {
+        condition_messages::ConditionProperties {
+            type_name: String::from("Contour"),
+            ..props
+        }
+    } else {
+        props
+    }
+}
+// Given the fold (if any) of a spectrum, describe the channels the fold
+// removes from the spectrum.  The rules follow how folded spectra
+// are incremented:
+//
+// *  A MultiCut fold on a 1-d spectrum removes channels in the slice.
+// *  A MultiCut fold on a 2-d spectrum removes channels where either
+//    coordinate is in the slice as a pair is only incremented when
+//    both parameters are outside the slice.
+// *  A MultiContour fold on a 2-d spectrum removes channels inside the contour.
+// *  A MultiContour fold on a 1-d spectrum keeps a parameter if it pairs outside the
+//    contour with any other parameter.  That can't be determined
+//    from a channel so nothing is removed.
+
+fn generate_fold_exclusion(
+    api: &condition_messages::ConditionMessageClient,
+    oned: bool,
+    fold: &Option<String>,
+) -> Result<integration::FoldExclusion, String> {
+    let fold_name = if let Some(f) = fold {
+        f
+    } else {
+        return Ok(integration::FoldExclusion::None);
+    };
+    match api.list_conditions(fold_name) {
+        condition_messages::ConditionReply::Listing(l) => {
+            if l.len() != 1 {
+                return Err(format!("The fold {} no longer exists", fold_name));
+            }
+            let props = l[0].clone();
+            if props.type_name == "MultiCut" {
+                let (low, high) = (props.points[0].0, props.points[1].0);
+                if oned {
+                    Ok(integration::FoldExclusion::Oned { low, high })
+                } else {
+                    Ok(integration::FoldExclusion::Twod { low, high })
+                }
+            } else if props.type_name == "MultiContour" {
+                if oned {
+                    Ok(integration::FoldExclusion::None)
+                } else {
+                    match condition_messages::reconstitute_contour(as_contour(props)) {
+                        Ok(c) => Ok(integration::FoldExclusion::Contour(c)),
+                        Err(s) => Err(format!(
+                            "Failed to construct a contour from fold {}: {}",
+                            fold_name, s
+                        )),
+                    }
+                }
+            } else {
+                Err(format!(
+                    "{} is a {} which can't be a fold",
+                    fold_name, props.type_name
+                ))
+            }
+        }
+        condition_messages::ConditionReply::Error(s) => Err(format!(
+            "Unable to get the description of fold {}: {}",
+            fold_name, s
+        )),
+        _ => Err(format!(
+            "Unexpected response getting the description of fold {}",
+            fold_name
+        )),
+    }
+}
+
+/// Given spectrum characteristics and the inputs that might
+/// describe the AOI, return an integration::AreaOfInterest.
+/// 1-d spectra can have a gate (Cut or MultiCut) or low/high limits.
+/// 2-d spectra can have a gate (Contour, C2Band, MultiContour or ContourSet) or
+/// the x/y coordinates of a contour.  If nothing describes the AOI,
+/// the AOI is the entire spectrum.
+pub fn generate_aoi(
     api: &condition_messages::ConditionMessageClient,
     oned: bool,
     gate: OptionalString,
@@ -214,6 +301,11 @@ fn generate_aoi(
 ///
 /// The reply is an IntegrationResponse.
 ///
+/// Gamma conditions (MultiCut and MultiContour) can also supply the region
+/// of integration.  If the spectrum is folded, the channels its fold removes
+/// from the spectrum are not included in the integration, see
+/// generate_fold_exclusion for the rules.
+///
 #[get("/?<spectrum>&<gate>&<low>&<high>&<xcoord>&<ycoord>")]
 pub fn integrate(
     spectrum: String,
@@ -223,6 +315,7 @@ pub fn integrate(
     xcoord: OptionalF64Vec,
     ycoord: OptionalF64Vec,
     state: &State<SharedHistogramChannel>,
+    _ready: busy::HistogramerReady,
 ) -> Json<IntegrationResponse> {
     let name = spectrum.clone();
     // A few errors to check for:
@@ -297,17 +390,32 @@ pub fn integrate(
     }
     let aoi = aoi.unwrap().clone();
 
+    // Folded spectra don't include the channels folded out:
+
+    let fold = generate_fold_exclusion(&capi, is_1d, &description.fold);
+    if let Err(s) = fold {
+        return Json(IntegrationResponse {
+            status: format!("Could not determine the spectrum fold: {}", s),
+            detail: IntegrationDetail {
+                centroid: vec![0.0],
+                fwhm: vec![0.0],
+                counts: 0,
+            },
+        });
+    }
+    let fold = fold.unwrap();
+
     // Now do the integration and marshall the response - how that's done depends
     // on the spectrum dimensionality.
 
-    let result = integration::integrate(&contents, aoi);
+    let result = integration::integrate(&contents, aoi, fold);
 
     let response = if is_1d {
         IntegrationResponse {
             status: String::from("OK"),
             detail: IntegrationDetail {
-                centroid: vec![result.centroid.0, 0.0],   // Since CutiePie expects
-                fwhm: vec![result.fwhm.0, 0.0],           // both to always be there.
+                centroid: vec![result.centroid.0, 0.0], // Since CutiePie expects
+                fwhm: vec![result.fwhm.0, 0.0],         // both to always be there.
                 counts: result.sum as u64,
             },
         }
@@ -482,6 +590,25 @@ mod integrate_tests {
             condition_messages::ConditionReply::Created => {}
             _ => panic!("Bad reply from true creation."),
         }
+        // A contour set whose second square contains the spike:
+
+        match api.create_contourset_condition(
+            "good-set",
+            4,
+            5,
+            &[
+                vec![(0.0, 0.0), (50.0, 0.0), (50.0, 50.0), (0.0, 50.0)],
+                vec![
+                    (100.0, 100.0),
+                    (200.0, 100.0),
+                    (200.0, 200.0),
+                    (100.0, 200.0),
+                ],
+            ],
+        ) {
+            condition_messages::ConditionReply::Created => {}
+            _ => panic!("Bad reply from contour set creation."),
+        }
     }
     #[test]
     fn error_1() {
@@ -866,6 +993,32 @@ mod integrate_tests {
         teardown(chan, p, b);
     }
     #[test]
+    fn twod_6() {
+        // Integration within a contour set - the spike is in one of its contours:
+
+        let r = setup();
+        let (chan, p, b) = getstate(&r);
+
+        let c = Client::untracked(r).expect("unable to create client");
+        let req = c.get("/?spectrum=twod&gate=good-set");
+        let reply = req
+            .dispatch()
+            .into_json::<IntegrationResponse>()
+            .expect("Parsing JSON");
+
+        assert_eq!("OK", reply.status);
+        assert_eq!(
+            IntegrationDetail {
+                centroid: vec![150.0, 150.0],
+                fwhm: vec![0.0, 0.0],
+                counts: 4321
+            },
+            reply.detail
+        );
+
+        teardown(chan, p, b);
+    }
+    #[test]
     fn summary_1() {
         // Summary spectra integrate in 2-d:
 
@@ -890,4 +1043,137 @@ mod integrate_tests {
         );
         teardown(chan, p, b);
     }
+    // Make gamma spectra folded on a gamma slice.  The spectra
+    // have a spike inside the fold and one outside.
+
+    fn make_folded(r: &Rocket<Build>) {
+        let (req, _, _) = getstate(r);
+        let capi = condition_messages::ConditionMessageClient::new(&req);
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&req);
+
+        match capi.create_multicut_condition("gamma-slice", &[1, 2, 3], 100.0, 200.0) {
+            condition_messages::ConditionReply::Created => {}
+            _ => panic!("Bad reply from multicut creation"),
+        }
+        let params = vec![
+            String::from("param.0"),
+            String::from("param.1"),
+            String::from("param.2"),
+        ];
+        sapi.create_spectrum_multi1d("gamma1", &params, 0.0, 1024.0, 1024)
+            .expect("Making gamma 1d");
+        sapi.create_spectrum_multi2d("gamma2", &params, 0.0, 1024.0, 512, 0.0, 1024.0, 512)
+            .expect("Making gamma 2d");
+        sapi.fold_spectrum("gamma1", "gamma-slice")
+            .expect("Folding gamma1");
+        sapi.fold_spectrum("gamma2", "gamma-slice")
+            .expect("Folding gamma2");
+
+        sapi.fill_spectrum(
+            "gamma1",
+            vec![
+                spectrum_messages::Channel {
+                    chan_type: spectrum_messages::ChannelType::Bin,
+                    x: 150.0,
+                    y: 0.0,
+                    bin: 0,
+                    value: 1000.0,
+                },
+                spectrum_messages::Channel {
+                    chan_type: spectrum_messages::ChannelType::Bin,
+                    x: 300.0,
+                    y: 0.0,
+                    bin: 0,
+                    value: 100.0,
+                },
+            ],
+        )
+        .expect("Setting gamma1 contents");
+        sapi.fill_spectrum(
+            "gamma2",
+            vec![
+                spectrum_messages::Channel {
+                    chan_type: spectrum_messages::ChannelType::Bin,
+                    x: 300.0,
+                    y: 150.0,
+                    bin: 0,
+                    value: 1000.0,
+                },
+                spectrum_messages::Channel {
+                    chan_type: spectrum_messages::ChannelType::Bin,
+                    x: 300.0,
+                    y: 300.0,
+                    bin: 0,
+                    value: 100.0,
+                },
+            ],
+        )
+        .expect("Setting gamma2 contents");
+    }
+    #[test]
+    fn folded_1() {
+        // Folded channels are not part of a 1d integration:
+
+        let r = setup();
+        make_folded(&r);
+        let (chan, p, b) = getstate(&r);
+
+        let client = Client::untracked(r).expect("Making client");
+        let req = client.get("/?spectrum=gamma1");
+        let response = req
+            .dispatch()
+            .into_json::<IntegrationResponse>()
+            .expect("Parsing JSON");
+
+        assert_eq!("OK", response.status);
+        assert_eq!(
+            IntegrationDetail {
+                centroid: vec![300.0, 0.0],
+                fwhm: vec![0.0, 0.0],
+                counts: 100
+            },
+            response.detail
+        );
+
+        // Integrating in the fold itself gives nothing:
+
+        let req = client.get("/?spectrum=gamma1&gate=gamma-slice");
+        let response = req
+            .dispatch()
+            .into_json::<IntegrationResponse>()
+            .expect("Parsing JSON");
+
+        assert_eq!("OK", response.status);
+        assert_eq!(0, response.detail.counts);
+
+        teardown(chan, p, b);
+    }
+    #[test]
+    fn folded_2() {
+        // Channels with either coordinate in the slice are not part of
+        // a 2d integration:
+
+        let r = setup();
+        make_folded(&r);
+        let (chan, p, b) = getstate(&r);
+
+        let client = Client::untracked(r).expect("Making client");
+        let req = client.get("/?spectrum=gamma2");
+        let response = req
+            .dispatch()
+            .into_json::<IntegrationResponse>()
+            .expect("Parsing JSON");
+
+        assert_eq!("OK", response.status);
+        assert_eq!(
+            IntegrationDetail {
+                centroid: vec![300.0, 300.0],
+                fwhm: vec![0.0, 0.0],
+                counts: 100
+            },
+            response.detail
+        );
+
+        teardown(chan, p, b);
+    }
 }
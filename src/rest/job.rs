@@ -0,0 +1,259 @@
+//! This module provides the /spectcl/jobs REST interface.  Long running
+//! operations (see src/jobs/mod.rs) that are started as jobs return a
+//! job id at once.  This interface monitors and controls those jobs:
+//!
+//! *  /spectcl/jobs/list - lists the jobs, running and finished.
+//! *  /spectcl/jobs/status - the status and progress of one job.
+//! *  /spectcl/jobs/cancel - asks a running job to stop.
+//! *  /spectcl/jobs/forget - forgets a finished job.
+//!
+//! Operations that can be run as jobs take a _job_ flag and include
+//! /spectcl/swrite and /spectcl/project.
+//!
+//! We depend on the main to have set the Rocket state to include a
+//! jobs::SharedJobStore.
+//!
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+
+use super::*;
+use crate::jobs;
+
+/// The response to list and status requests.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct JobListResponse {
+    status: String,
+    detail: Vec<jobs::JobDescription>,
+}
+
+/// Convert the response of an operation run as a job into the job's
+/// result.
+pub fn job_result(response: GenericResponse) -> Result<String, String> {
+    if response.status == "OK" {
+        Ok(response.detail)
+    } else if response.detail.is_empty() {
+        Err(response.status)
+    } else {
+        Err(format!("{}: {}", response.status, response.detail))
+    }
+}
+
+/// List the jobs, oldest first.
+#[get("/list")]
+pub fn list(store: &State<jobs::SharedJobStore>) -> Json<JobListResponse> {
+    Json(JobListResponse {
+        status: String::from("OK"),
+        detail: store.inner().list(),
+    })
+}
+
+/// The status of a job.  The detail has the description of the job as
+/// its only element.
+///
+/// ### Query parameters
+/// * id - the job's id.
+///
+#[get("/status?<id>")]
+pub fn status(id: u64, store: &State<jobs::SharedJobStore>) -> Json<JobListResponse> {
+    Json(match store.inner().status(id) {
+        Some(job) => JobListResponse {
+            status: String::from("OK"),
+            detail: vec![job],
+        },
+        None => JobListResponse {
+            status: format!("There is no job {}", id),
+            detail: vec![],
+        },
+    })
+}
+
+/// Ask a running job to stop.  The job is cancelled once it notices
+/// the request, which the status of the job shows.
+///
+/// ### Query parameters
+/// * id - the job's id.
+///
+#[get("/cancel?<id>")]
+pub fn cancel(
+    id: u64,
+    store: &State<jobs::SharedJobStore>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    Json(match store.inner().cancel(id) {
+        Ok(()) => GenericResponse::ok(""),
+        Err(s) => GenericResponse::err("Unable to cancel job", &s),
+    })
+}
+
+/// Forget a finished job.
+///
+/// ### Query parameters
+/// * id - the job's id.
+///
+#[get("/forget?<id>")]
+pub fn forget(
+    id: u64,
+    store: &State<jobs::SharedJobStore>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    Json(match store.inner().forget(id) {
+        Ok(()) => GenericResponse::ok(""),
+        Err(s) => GenericResponse::err("Unable to forget job", &s),
+    })
+}
+
+#[cfg(test)]
+mod job_tests {
+    use super::*;
+    use crate::messaging;
+    use crate::processing;
+    use crate::sharedmem::binder;
+    use crate::test::rest_common;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    fn setup() -> Rocket<Build> {
+        rest_common::setup().mount("/", routes![list, status, cancel, forget])
+    }
+    fn getstate(
+        r: &Rocket<Build>,
+    ) -> (
+        mpsc::Sender<messaging::Request>,
+        processing::ProcessingApi,
+        binder::BindingApi,
+        jobs::SharedJobStore,
+    ) {
+        let (chan, papi, bapi) = rest_common::get_state(r);
+        let store = r
+            .state::<jobs::SharedJobStore>()
+            .expect("Getting job store")
+            .clone();
+        (chan, papi, bapi, store)
+    }
+    fn teardown(
+        c: mpsc::Sender<messaging::Request>,
+        p: &processing::ProcessingApi,
+        b: &binder::BindingApi,
+    ) {
+        rest_common::teardown(c, p, b);
+    }
+
+    #[test]
+    fn list_1() {
+        let rocket = setup();
+        let (chan, papi, bapi, store) = getstate(&rocket);
+        let client = Client::untracked(rocket).expect("Making client");
+
+        let reply = client
+            .get("/list")
+            .dispatch()
+            .into_json::<JobListResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert!(reply.detail.is_empty());
+
+        let id = store.start("test", "a job", |_| Ok(String::from("done")));
+        while store.status(id).unwrap().status == jobs::RUNNING {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let reply = client
+            .get("/list")
+            .dispatch()
+            .into_json::<JobListResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail.len());
+        assert_eq!(id, reply.detail[0].id);
+        assert_eq!(jobs::COMPLETED, reply.detail[0].status);
+        assert_eq!("done", reply.detail[0].detail);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn cancel_1() {
+        // Cancel a job, then forget it:
+
+        let rocket = setup();
+        let (chan, papi, bapi, store) = getstate(&rocket);
+        let client = Client::untracked(rocket).expect("Making client");
+
+        let id = store.start("test", "", |ctx| {
+            while !ctx.cancelled() {
+                ctx.progress(1, 2);
+                thread::sleep(Duration::from_millis(5));
+            }
+            Err(String::from("stopped"))
+        });
+        let reply = client
+            .get(format!("/forget?id={}", id))
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("Unable to forget job", reply.status);
+
+        let reply = client
+            .get(format!("/cancel?id={}", id))
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        let mut reply;
+        loop {
+            reply = client
+                .get(format!("/status?id={}", id))
+                .dispatch()
+                .into_json::<JobListResponse>()
+                .expect("Parsing JSON");
+            assert_eq!("OK", reply.status);
+            if reply.detail[0].status != jobs::RUNNING {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(jobs::CANCELLED, reply.detail[0].status);
+        assert_eq!("stopped", reply.detail[0].detail);
+
+        let reply = client
+            .get(format!("/forget?id={}", id))
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        let reply = client
+            .get(format!("/status?id={}", id))
+            .dispatch()
+            .into_json::<JobListResponse>()
+            .expect("Parsing JSON");
+        assert_eq!(format!("There is no job {}", id), reply.status);
+
+        let reply = client
+            .get(format!("/cancel?id={}", id))
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("Unable to cancel job", reply.status);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn result_1() {
+        assert_eq!(Ok(String::from("x")), job_result(GenericResponse::ok("x")));
+        assert_eq!(
+            Err(String::from("bad: why")),
+            job_result(GenericResponse::err("bad", "why"))
+        );
+        assert_eq!(
+            Err(String::from("bad")),
+            job_result(GenericResponse::err("bad", ""))
+        );
+    }
+}
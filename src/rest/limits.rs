@@ -0,0 +1,171 @@
+//!  Provides the /spectcl/limits domain and the limits it reports.
+//!  Rustogramer protects the histogram thread from requests that are
+//!  unreasonably large (usually the result of buggy clients) by
+//!  enforcing a set of limits:
+//!
+//! *  The maximum number of channels that can be filled into a single
+//! spectrum in one request (e.g. by sread).
+//! *  The maximum number of spectra a single bulk operation
+//! (swrite, sread, sbind) can operate on.
+//! *  The maximum size of a request body (and of files read by sread).
+//!
+//!  The limits are set from the command line and stored in the
+//!  Rocket state as a RestLimits struct.  The single URI supported
+//!  here, /spectcl/limits, allows clients to discover them.
+//!
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+
+pub const DEFAULT_MAX_FILL_POINTS: usize = 16 * 1024 * 1024;
+pub const DEFAULT_MAX_BULK_SPECTRA: usize = 10000;
+pub const DEFAULT_MAX_BODY_BYTES: u64 = 256 * 1024 * 1024;
+
+/// The limits themselves.  These are also serialized directly
+/// as the detail of the /spectcl/limits response.
+///
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct RestLimits {
+    pub max_fill_points: usize,
+    pub max_bulk_spectra: usize,
+    pub max_body_bytes: u64,
+}
+
+impl Default for RestLimits {
+    fn default() -> RestLimits {
+        RestLimits {
+            max_fill_points: DEFAULT_MAX_FILL_POINTS,
+            max_bulk_spectra: DEFAULT_MAX_BULK_SPECTRA,
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+        }
+    }
+}
+
+impl RestLimits {
+    pub fn new(max_fill_points: usize, max_bulk_spectra: usize, max_body_bytes: u64) -> RestLimits {
+        RestLimits {
+            max_fill_points,
+            max_bulk_spectra,
+            max_body_bytes,
+        }
+    }
+    /// Check the number of channels to be filled into a spectrum.
+    ///
+    /// ### Parameters
+    /// * name - name of the spectrum being filled (for the message).
+    /// * points - Number of channels in the request.
+    ///
+    /// ### Returns
+    /// Result<(), String> - the error string describes the violation.
+    pub fn check_fill_points(&self, name: &str, points: usize) -> Result<(), String> {
+        if points > self.max_fill_points {
+            Err(format!(
+                "Fill of {} has {} channels which exceeds the limit of {}",
+                name, points, self.max_fill_points
+            ))
+        } else {
+            Ok(())
+        }
+    }
+    /// Check the number of spectra a bulk operation will touch.
+    pub fn check_bulk_spectra(&self, count: usize) -> Result<(), String> {
+        if count > self.max_bulk_spectra {
+            Err(format!(
+                "Request specifies {} spectra which exceeds the limit of {}",
+                count, self.max_bulk_spectra
+            ))
+        } else {
+            Ok(())
+        }
+    }
+    /// Check the size of a body or file that will be deserialized.
+    pub fn check_body_bytes(&self, bytes: u64) -> Result<(), String> {
+        if bytes > self.max_body_bytes {
+            Err(format!(
+                "{} bytes exceeds the size limit of {} bytes",
+                bytes, self.max_body_bytes
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The response to /spectcl/limits:
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct LimitsResponse {
+    status: String,
+    detail: RestLimits,
+}
+
+/// Handler for /spectcl/limits - returns the limits currently in force.
+///
+/// ### Parameters
+/// * limits - The limits stored in the server state.
+///
+/// ### Returns
+/// * Json encoded LimitsResponse.  This cannot fail.
+#[get("/")]
+pub fn get_limits(limits: &State<RestLimits>) -> Json<LimitsResponse> {
+    Json(LimitsResponse {
+        status: String::from("OK"),
+        detail: *limits.inner(),
+    })
+}
+
+#[cfg(test)]
+mod limits_tests {
+    use super::*;
+    use crate::messaging;
+    use crate::processing;
+    use crate::sharedmem::binder;
+    use crate::test::rest_common;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+
+    use std::sync::mpsc;
+
+    fn setup() -> Rocket<Build> {
+        rest_common::setup().mount("/", routes![get_limits])
+    }
+    fn getstate(
+        r: &Rocket<Build>,
+    ) -> (
+        mpsc::Sender<messaging::Request>,
+        processing::ProcessingApi,
+        binder::BindingApi,
+    ) {
+        rest_common::get_state(r)
+    }
+    #[test]
+    fn check_1() {
+        let l = RestLimits::new(10, 2, 100);
+        assert!(l.check_fill_points("junk", 10).is_ok());
+        assert!(l.check_fill_points("junk", 11).is_err());
+        assert!(l.check_bulk_spectra(2).is_ok());
+        assert!(l.check_bulk_spectra(3).is_err());
+        assert!(l.check_body_bytes(100).is_ok());
+        assert!(l.check_body_bytes(101).is_err());
+    }
+    #[test]
+    fn get_1() {
+        let rocket = setup();
+        let (c, papi, bapi) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let req = client.get("/");
+        let reply = req
+            .dispatch()
+            .into_json::<LimitsResponse>()
+            .expect("Decoding JSON");
+
+        assert_eq!("OK", reply.status);
+        assert_eq!(RestLimits::default(), reply.detail);
+
+        rest_common::teardown(c, &papi, &bapi);
+    }
+}
@@ -8,11 +8,18 @@ use rocket::serde::{json::Json, Deserialize, Serialize};
 use rocket::State;
 
 // Description of a mirror client:
+// host and shmkey are what SpecTcl provides.  hostname is the
+// resolved name of host, memory the shared memory key/name the
+// client is mirroring and age how long, in seconds, the client
+// has been connected.
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(crate = "rocket::serde")]
 pub struct MirrorInfo {
     host: String,
     shmkey: String,
+    hostname: String,
+    memory: String,
+    age: u64,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
@@ -31,6 +38,9 @@ pub fn mirror_list(state: &State<mirror::SharedMirrorDirectory>) -> Json<MirrorR
         result.detail.push(MirrorInfo {
             host: entry.host(),
             shmkey: entry.key(),
+            hostname: entry.hostname(),
+            memory: entry.memory(),
+            age: entry.age().as_secs(),
         });
     }
     Json(result)
@@ -51,6 +61,7 @@ mod mirror_list_tests {
     use rocket::Rocket;
 
     use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
 
     fn setup() -> Rocket<Build> {
         let state: mirror::SharedMirrorDirectory = Arc::new(Mutex::new(mirror::Directory::new()));
@@ -138,4 +149,37 @@ mod mirror_list_tests {
             assert_eq!(mems[i], items[i].shmkey, "Failed on item; {}", i);
         }
     }
+    #[test]
+    fn list_4() {
+        // Client details are listed:
+
+        let rocket = setup();
+        let dir = get_directory(&rocket);
+        dir.lock()
+            .unwrap()
+            .add_entry(mirror::DirectoryEntry::new_client(
+                "127.0.0.1",
+                "localhost",
+                "Xa3b",
+                "file:/some/path",
+                Instant::now() - Duration::from_secs(10),
+            ))
+            .expect("Adding item");
+
+        let client = Client::untracked(rocket).expect("Making server");
+        let reply = client
+            .get("/")
+            .dispatch()
+            .into_json::<MirrorResponse>()
+            .expect("Parsing JSON");
+
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail.len());
+        let info = &reply.detail[0];
+        assert_eq!("127.0.0.1", info.host);
+        assert_eq!("Xa3b", info.shmkey);
+        assert_eq!("localhost", info.hostname);
+        assert_eq!("file:/some/path", info.memory);
+        assert!(info.age >= 10);
+    }
 }
@@ -8,10 +8,9 @@
 //!  For example, the SpecTcl REST interface allows
 //!  clients to execute arbitrary Tcl code in the
 //!  SpecTcl Tcl interpreter but Rustogramer has no
-//!  Tcl interpreter so therefore any attempt to use
-//!  that interface by a rustogramer client results in
-//!  an error return indicating there is no Tcl
-//!  interpreter.
+//!  Tcl interpreter so therefore that interface only
+//!  accepts scripts in a restricted command language
+//!  (see script.rs).
 //!  
 //!   The REST interface consists of a bunch of
 //!   Quasi-independent domains of URLS.  Each of those
@@ -32,27 +31,52 @@
 // Re exports:
 
 pub mod apply;
+pub mod autosave;
+pub mod busy;
+pub mod cache;
+pub mod calibration;
 pub mod channel;
+pub mod compress;
+pub mod crashes;
 pub mod data_processing;
 pub mod evbunpack;
 pub mod exit;
 pub mod filter;
 pub mod fit;
 pub mod fold;
+pub mod fs;
+pub mod gainmatch;
 pub mod gates;
 pub mod getstats;
+pub mod help;
+pub mod idspace;
 pub mod integrate;
+pub mod job;
+pub mod limits;
 pub mod mirror_list;
+pub mod names;
 pub mod parameter;
 pub mod project;
+pub mod pseudo;
+pub mod readonly;
 pub mod ringversion;
+pub mod roottree;
+pub mod runinfo;
 pub mod sbind;
+pub mod scaler;
+pub mod script;
 pub mod shm;
+pub mod simulate;
 pub mod spectrum;
 pub mod spectrumio;
+pub mod streaming;
+pub mod tag;
+pub mod template;
 pub mod traces;
+pub mod treevariable;
 pub mod unbind;
 pub mod unimplemented;
+pub mod unpacker;
 pub mod version;
 
 pub use parameter as rest_parameter;
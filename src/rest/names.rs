@@ -0,0 +1,195 @@
+//!  Provides the policy used to validate the names of new spectra
+//!  and conditions.  Names containing e.g. spaces or non-ASCII
+//!  characters break the spectrum titles in shared memory as well
+//!  as some client parsers (SpecTcl clients treat the names as
+//!  Tcl list elements).  The policy consists of:
+//!
+//! *  The set of characters allowed in a name: ASCII letters and digits
+//! as well as a configurable set of punctuation characters.
+//! *  The maximum length of a name.
+//! *  Whether names that differ only in case are considered distinct.
+//!
+//!  The policy is set from the command line and stored in the Rocket
+//!  state as a NamePolicy struct.  Handlers that create spectra or
+//!  conditions check the new names against it.
+//!
+use crate::messaging::condition_messages::{ConditionMessageClient, ConditionReply};
+use crate::messaging::spectrum_messages::SpectrumMessageClient;
+use crate::sharedmem;
+use rocket::serde::{Deserialize, Serialize};
+
+/// Punctuation allowed by default; all of the printable ASCII
+/// punctuation characters.
+pub const DEFAULT_NAME_PUNCTUATION: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+/// Names must fit in a shared memory spectrum title with its null terminator.
+pub const DEFAULT_MAX_NAME_LENGTH: usize = sharedmem::TITLE_LENGTH - 1;
+
+/// The name validation policy.
+///
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct NamePolicy {
+    pub punctuation: String,
+    pub max_length: usize,
+    pub case_sensitive: bool,
+}
+
+impl Default for NamePolicy {
+    fn default() -> NamePolicy {
+        NamePolicy {
+            punctuation: String::from(DEFAULT_NAME_PUNCTUATION),
+            max_length: DEFAULT_MAX_NAME_LENGTH,
+            case_sensitive: true,
+        }
+    }
+}
+
+impl NamePolicy {
+    pub fn new(punctuation: &str, max_length: usize, case_sensitive: bool) -> NamePolicy {
+        NamePolicy {
+            punctuation: String::from(punctuation),
+            max_length,
+            case_sensitive,
+        }
+    }
+    /// Check the characters and length of a name.
+    ///
+    /// ### Parameters
+    /// * kind - what is being named e.g. "Spectrum" (for the message).
+    /// * name - the proposed name.
+    ///
+    /// ### Returns
+    /// Result<(), String> - the error string describes the violation.
+    pub fn check_name(&self, kind: &str, name: &str) -> Result<(), String> {
+        if name.is_empty() {
+            return Err(format!("{} names cannot be empty", kind));
+        }
+        if let Some(c) = name
+            .chars()
+            .find(|&c| !(c.is_ascii_alphanumeric() || self.punctuation.contains(c)))
+        {
+            return Err(format!(
+                "{} name '{}' contains {:?} but names can only contain letters, digits and '{}'",
+                kind, name, c, self.punctuation
+            ));
+        }
+        let length = name.chars().count();
+        if length > self.max_length {
+            return Err(format!(
+                "{} name '{}' has {} characters which exceeds the limit of {}",
+                kind, name, length, self.max_length
+            ));
+        }
+        Ok(())
+    }
+    /// Check that a name does not clash with existing names under the
+    /// case sensitivity policy.  An existing name identical to _name_ is
+    /// not a clash; whether that's allowed (e.g. replacing a condition)
+    /// is up to the caller.
+    ///
+    /// ### Parameters
+    /// * kind - what is being named e.g. "Spectrum" (for the message).
+    /// * name - the proposed name.
+    /// * existing - the names already in use.
+    ///
+    /// ### Returns
+    /// Result<(), String> - the error string describes the clash.
+    pub fn check_unique<'a, I>(&self, kind: &str, name: &str, existing: I) -> Result<(), String>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        if self.case_sensitive {
+            return Ok(());
+        }
+        let lower = name.to_lowercase();
+        match existing
+            .into_iter()
+            .find(|&e| e != name && e.to_lowercase() == lower)
+        {
+            Some(e) => Err(format!(
+                "{} name '{}' differs only in case from the existing name '{}'",
+                kind, name, e
+            )),
+            None => Ok(()),
+        }
+    }
+    /// Check a proposed new spectrum name against the policy.
+    pub fn check_spectrum_name(
+        &self,
+        name: &str,
+        api: &SpectrumMessageClient,
+    ) -> Result<(), String> {
+        self.check_name("Spectrum", name)?;
+        if self.case_sensitive {
+            return Ok(());
+        }
+        let spectra = api.list_spectra("*")?;
+        self.check_unique("Spectrum", name, spectra.iter().map(|s| s.name.as_str()))
+    }
+    /// Check a proposed new condition name against the policy.
+    pub fn check_condition_name(
+        &self,
+        name: &str,
+        api: &ConditionMessageClient,
+    ) -> Result<(), String> {
+        self.check_name("Condition", name)?;
+        if self.case_sensitive {
+            return Ok(());
+        }
+        match api.list_conditions("*") {
+            ConditionReply::Listing(l) => {
+                self.check_unique("Condition", name, l.iter().map(|c| c.cond_name.as_str()))
+            }
+            ConditionReply::Error(s) => Err(s),
+            _ => Err(String::from("Unexpected reply listing conditions")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod names_tests {
+    use super::*;
+
+    #[test]
+    fn chars_1() {
+        let p = NamePolicy::default();
+        assert!(p.check_name("Spectrum", "event.raw.00").is_ok());
+        assert!(p.check_name("Spectrum", "a_b-c:d{1}").is_ok());
+        assert!(p.check_name("Spectrum", "").is_err());
+        assert!(p.check_name("Spectrum", "has space").is_err());
+        assert!(p.check_name("Spectrum", "tab\there").is_err());
+        assert!(p.check_name("Spectrum", "Ångström").is_err());
+    }
+    #[test]
+    fn chars_2() {
+        let p = NamePolicy::new("._", DEFAULT_MAX_NAME_LENGTH, true);
+        assert!(p.check_name("Spectrum", "event.raw_00").is_ok());
+        let e = p.check_name("Condition", "a-b").unwrap_err();
+        assert_eq!(
+            "Condition name 'a-b' contains '-' but names can only contain letters, digits and '._'",
+            e
+        );
+    }
+    #[test]
+    fn length_1() {
+        let p = NamePolicy::new("", 4, true);
+        assert!(p.check_name("Spectrum", "abcd").is_ok());
+        assert!(p.check_name("Spectrum", "abcde").is_err());
+    }
+    #[test]
+    fn unique_1() {
+        let p = NamePolicy::default();
+        assert!(p.check_unique("Spectrum", "Raw", ["raw", "other"]).is_ok());
+
+        let p = NamePolicy::new(DEFAULT_NAME_PUNCTUATION, DEFAULT_MAX_NAME_LENGTH, false);
+        assert!(p.check_unique("Spectrum", "raw", ["raw", "other"]).is_ok());
+        assert!(p.check_unique("Spectrum", "new", ["raw", "other"]).is_ok());
+        let e = p
+            .check_unique("Spectrum", "Raw", ["raw", "other"])
+            .unwrap_err();
+        assert_eq!(
+            "Spectrum name 'Raw' differs only in case from the existing name 'raw'",
+            e
+        );
+    }
+}
@@ -4,7 +4,8 @@
 //! histogram server.
 //! Specifically:
 //!
-//! *   ../list - list all or some of the parameters.
+//! *   ../list - list all or some of the parameters.  New with rustogramer,
+//!     the calibration of calibrated parameters is included.
 //! *   ../edit - modify the metadata properties of a parameter.
 //! *   ../promote - provide metadata properties of a parmaeter that may have none.
 //! for rustogramer this is the same as edit.
@@ -23,6 +24,7 @@ use rocket::State;
 use super::*;
 
 use crate::messaging::parameter_messages::ParameterMessageClient;
+use std::collections::HashMap;
 
 //------------------------- List operation ---------------------
 // These define structs that will be serialized.
@@ -31,6 +33,14 @@ use crate::messaging::parameter_messages::ParameterMessageClient;
 //
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
+pub struct ParameterCalibration {
+    raw: String,
+    slope: f64,
+    offset: f64,
+    quadratic: f64,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
 pub struct ParameterDefinition {
     name: String,
     id: u32,
@@ -38,7 +48,8 @@ pub struct ParameterDefinition {
     low: Option<f64>,
     hi: Option<f64>,
     units: Option<String>,
-    description: Option<String>, // New in rustogramer.
+    description: Option<String>,               // New in rustogramer.
+    calibration: Option<ParameterCalibration>, // New in rustogramer.
 }
 
 #[derive(Serialize, Deserialize)]
@@ -57,12 +68,14 @@ pub struct Parameters {
 /// If the filter pattern is supplied it must be a valid glob
 /// pattern used to select the names of the parameters
 /// actually listed.  detail on success is an array of
-/// ParameterDefinition values JSON encoded.
+/// ParameterDefinition values JSON encoded.  Calibrated parameters
+/// (see rest::calibration) include their calibration.
 ///
 #[get("/list?<filter>")]
 pub fn list_parameters(
     filter: Option<String>,
     state: &State<SharedHistogramChannel>,
+    _ready: busy::HistogramerReady,
 ) -> Json<Parameters> {
     let mut result = Parameters {
         status: String::from("OK"),
@@ -75,6 +88,27 @@ pub fn list_parameters(
     } else {
         String::from("*")
     };
+    let mut calibrations: HashMap<String, ParameterCalibration> =
+        match api.list_calibrated_parameters(&pattern) {
+            Ok(l) => l
+                .into_iter()
+                .map(|c| {
+                    (
+                        c.name,
+                        ParameterCalibration {
+                            raw: c.raw,
+                            slope: c.calibration.slope,
+                            offset: c.calibration.offset,
+                            quadratic: c.calibration.quadratic,
+                        },
+                    )
+                })
+                .collect(),
+            Err(s) => {
+                result.status = s;
+                return Json(result);
+            }
+        };
     let list = api.list_parameters(&pattern);
     match list {
         Ok(listing) => {
@@ -87,6 +121,7 @@ pub fn list_parameters(
                     hi: p.get_limits().1,
                     units: p.get_units(),
                     description: p.get_description(),
+                    calibration: calibrations.remove(&p.get_name()),
                 })
             }
         }
@@ -173,6 +208,8 @@ pub fn create_parameter(
     units: Option<String>,
     description: Option<String>,
     state: &State<SharedHistogramChannel>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
 ) -> Json<GenericResponse> {
     let mut response = GenericResponse::ok("");
 
@@ -233,6 +270,8 @@ pub fn edit_parameter(
     units: Option<String>,
     description: Option<String>,
     state: &State<SharedHistogramChannel>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
 ) -> Json<GenericResponse> {
     let mut response = GenericResponse::ok("");
 
@@ -266,8 +305,20 @@ pub fn promote_parameter(
     units: Option<String>,
     description: Option<String>,
     state: &State<SharedHistogramChannel>,
+    writable: readonly::Writable,
+    ready: busy::HistogramerReady,
 ) -> Json<GenericResponse> {
-    edit_parameter(name, bins, low, high, units, description, state)
+    edit_parameter(
+        name,
+        bins,
+        low,
+        high,
+        units,
+        description,
+        state,
+        writable,
+        ready,
+    )
 }
 //--------------------------------------------------------------------
 // CHeck status
@@ -318,7 +369,12 @@ fn check_uncheck_common_code(name: &str, state: &State<SharedHistogramChannel>)
 /// Failure:  Status is a top level error message and
 /// Empty.
 #[get("/check?<name>")]
-pub fn check_parameter(name: String, state: &State<SharedHistogramChannel>) -> Json<CheckResponse> {
+pub fn check_parameter(
+    name: String,
+    state: &State<SharedHistogramChannel>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<CheckResponse> {
     let response = check_uncheck_common_code(&name, state);
     Json(response)
 }
@@ -338,6 +394,8 @@ pub fn check_parameter(name: String, state: &State<SharedHistogramChannel>) -> J
 pub fn uncheck_parameter(
     name: String,
     state: &State<SharedHistogramChannel>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
 ) -> Json<CheckResponse> {
     let mut response = check_uncheck_common_code(&name, state);
     response.detail = None; // Fix up resposne.
@@ -363,8 +421,20 @@ pub fn new_rawparameter(
     units: Option<String>,
     description: Option<String>,
     state: &State<SharedHistogramChannel>,
+    writable: readonly::Writable,
+    ready: busy::HistogramerReady,
 ) -> Json<GenericResponse> {
-    create_parameter(name, low, high, bins, units, description, state)
+    create_parameter(
+        name,
+        low,
+        high,
+        bins,
+        units,
+        description,
+        state,
+        writable,
+        ready,
+    )
 }
 
 ///
@@ -384,6 +454,7 @@ pub fn list_rawparameter(
     pattern: Option<String>,
     id: Option<u32>,
     state: &State<SharedHistogramChannel>,
+    ready: busy::HistogramerReady,
 ) -> Json<Parameters> {
     if pattern.is_some() && id.is_some() {
         Json(Parameters {
@@ -396,11 +467,11 @@ pub fn list_rawparameter(
             detail: Vec::<ParameterDefinition>::new(),
         })
     } else if pattern.is_some() {
-        list_parameters(pattern, state)
+        list_parameters(pattern, state, ready)
     } else {
         let name = find_parameter_by_id(id.unwrap(), state);
         if name.is_some() {
-            list_parameters(name, state)
+            list_parameters(name, state, ready)
         } else {
             Json(Parameters {
                 status: format!("No parameter with id {} exists", id.unwrap()),
@@ -438,6 +509,7 @@ mod parameter_tests {
     use super::*;
     use crate::messaging;
     use crate::messaging::parameter_messages;
+    use crate::parameters::calibration::Calibration;
     use crate::processing;
     use crate::test::rest_common;
 
@@ -624,6 +696,53 @@ mod parameter_tests {
         teardown(c, &papi, &bapi);
     }
     #[test]
+    fn listp_5() {
+        // Calibrated parameters include their calibration:
+
+        let rocket = setup();
+        let (c, papi, bapi) = getstate(&rocket);
+
+        let param_api = parameter_messages::ParameterMessageClient::new(&c);
+        param_api.create_parameter("raw").expect("raw");
+        param_api
+            .set_calibrated_parameter(
+                "energy",
+                "raw",
+                Calibration::new(10.0, 0.5, 0.001),
+                Some(String::from("keV")),
+            )
+            .expect("Calibrating");
+
+        let client = Client::tracked(rocket).expect("Making client");
+        let reply = client
+            .get("/par/list")
+            .dispatch()
+            .into_json::<Parameters>()
+            .expect("Parsing JSON");
+
+        assert_eq!("OK", reply.status);
+        assert_eq!(2, reply.detail.len());
+        let energy = reply
+            .detail
+            .iter()
+            .find(|p| p.name == "energy")
+            .expect("energy listed");
+        assert_eq!(Some(String::from("keV")), energy.units);
+        let cal = energy.calibration.as_ref().expect("energy calibration");
+        assert_eq!("raw", cal.raw);
+        assert_eq!(0.5, cal.slope);
+        assert_eq!(10.0, cal.offset);
+        assert_eq!(0.001, cal.quadratic);
+        let raw = reply
+            .detail
+            .iter()
+            .find(|p| p.name == "raw")
+            .expect("raw listed");
+        assert!(raw.calibration.is_none());
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
     fn version_1() {
         let rocket = setup();
         let (c, papi, bapi) = getstate(&rocket);
@@ -9,6 +9,7 @@
 //!  *  Multiply incremented 2-d spectra (g2 in SpecTcl parlance).
 //!  *  Particle Gamma spectra (gd in SpecTcl parlance).
 //!  *  Twod sum spectra (m2 in SpecTcl parlance).
+//!  *  Summary spectra (s in SpecTcl parlance).
 //!
 //!  The resulting spectrum is populated by summing rows or columns
 //!  In the direction other than the projection direction.  For example,
@@ -27,6 +28,12 @@
 //! Spectrum has the original X parameters but repeated 3 times (to maintain the appropriate projection).
 //! * twodsum - a 1d multi spectrum.  The parmaeters are the parameters of the approprite axis.  For
 //! example an X projection will have all of the X parameters of the original spectrum.
+//! * summary - Projected the other way around.  An X projection sums over all of the
+//!   strips into a 1d multi spectrum on all of the parameters with the original y axis.
+//!   A Y projection is a parameterless Snapshot1D spectrum of the strip totals on the x
+//!   (strip index) axis.  As no parameter can faithfully increment strip totals, Y projections
+//!   are always snapshots.
+//!   So are X projections within a contour, which is on strip index and value.
 //!
 //!  Projections can also be within any contour that can be displayed on the underlying spectrum.
 //!
@@ -37,19 +44,27 @@
 //! * Gated Source spectra result in non-snapshots gated on the same condition that gates the original spectrum
 //! * Spectra projected within a contour will retain that contour as a gate.  If the source spectrum
 //! is gated, the gate used is the And of the original spectrum's gate and the contour.
+//! * PGamma spectra projected within a contour are instead gated on a MultiContour
+//!   (gamma contour) with the contour's points on all of the spectrum's parameters so that
+//!   the projection continues to increment faithfully.
+//!
+//!  Projections are often made for a quick look.  The optional _temporary_ flag makes
+//! the projection a temporary spectrum and _idle_ gives the seconds it can go unused
+//! before it is deleted (see spectrum::temporary_spectrum).
 //!
 
 use super::*;
 use rocket::serde::json::Json;
 use rocket::State;
 
-use crate::messaging::{condition_messages, spectrum_messages};
+use crate::jobs;
+use crate::messaging::{condition_messages, parameter_messages, spectrum_messages};
 use crate::projections;
 use crate::sharedmem::binder;
 //------------------------------------------------------------------
 // project:
 #[allow(unused_variables)]
-#[get("/?<snapshot>&<source>&<newname>&<direction>&<contour>&<bind>")]
+#[get("/?<snapshot>&<source>&<newname>&<direction>&<contour>&<bind>&<temporary>&<idle>&<job>")]
 pub fn project(
     snapshot: String,
     source: String,
@@ -57,8 +72,15 @@ pub fn project(
     direction: String,
     contour: OptionalString,
     bind: OptionalFlag,
+    temporary: OptionalFlag,
+    idle: Option<f64>,
+    job: OptionalFlag,
     hgchannel: &State<SharedHistogramChannel>,
     bchannel: &State<SharedBinderChannel>,
+    policy: &State<names::NamePolicy>,
+    jobs: &State<jobs::SharedJobStore>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
 ) -> Json<GenericResponse> {
     // Make the spectrum and condition APIs:
 
@@ -66,6 +88,9 @@ pub fn project(
     let capi =
         condition_messages::ConditionMessageClient::new(&(hgchannel.inner().lock().unwrap()));
 
+    if let Err(s) = policy.check_spectrum_name(&newname, &sapi) {
+        return Json(GenericResponse::err("Invalid spectrum name", &s));
+    }
     // Figure out direction:
 
     let projection_direction = match direction.as_str() {
@@ -91,39 +116,94 @@ pub fn project(
         }
     };
 
+    let idle_limit = match spectrum::idle_limit(idle) {
+        Ok(l) => l,
+        Err(s) => return Json(GenericResponse::err("Invalid idle time", &s)),
+    };
+
+    let request = ProjectionRequest {
+        source,
+        newname,
+        direction: projection_direction,
+        snapshot,
+        contour,
+        bind: bind.unwrap_or(false), // SpecTcl does not support this flag and does not bind
+        temporary: temporary.unwrap_or(false),
+        idle_limit,
+    };
+    let hch = hgchannel.inner().lock().unwrap().clone();
+    let bch = bchannel.inner().lock().unwrap().clone();
+    if job.unwrap_or(false) {
+        let id = jobs.inner().start(
+            "project",
+            &format!("Project {} into {}", request.source, request.newname),
+            move |ctx| {
+                ctx.progress(0, 1);
+                let result = job::job_result(make_projection(&request, &hch, &bch));
+                ctx.progress(1, 1);
+                result
+            },
+        );
+        return Json(GenericResponse::ok(&id.to_string()));
+    }
+
+    Json(make_projection(&request, &hch, &bch))
+}
+// A validated projection request:
+
+struct ProjectionRequest {
+    source: String,
+    newname: String,
+    direction: projections::ProjectionDirection,
+    snapshot: bool,
+    contour: OptionalString,
+    bind: bool,
+    temporary: bool,
+    idle_limit: Option<std::time::Duration>,
+}
+// Make the projection spectrum, make it temporary and bind it as
+// requested:
+
+fn make_projection(
+    request: &ProjectionRequest,
+    hch: &mpsc::Sender<Request>,
+    bch: &mpsc::Sender<binder::Request>,
+) -> GenericResponse {
+    let sapi = spectrum_messages::SpectrumMessageClient::new(hch);
+    let capi = condition_messages::ConditionMessageClient::new(hch);
+    let papi = parameter_messages::ParameterMessageClient::new(hch);
+
     // Can we make the spectrum?
 
     let mut reply = if let Err(s) = projections::project(
         &sapi,
         &capi,
-        &source,
-        projection_direction,
-        &newname,
-        snapshot,
-        contour,
+        &papi,
+        &request.source,
+        request.direction,
+        &request.newname,
+        request.snapshot,
+        request.contour.clone(),
     ) {
         GenericResponse::err("Failed to create projection spectrum", &s)
+    } else if request.temporary {
+        match sapi.set_temporary(&request.newname, true, request.idle_limit) {
+            Ok(()) => GenericResponse::ok(""),
+            Err(s) => GenericResponse::err("Could not make projected spectrum temporary", &s),
+        }
     } else {
         GenericResponse::ok("")
     };
     // On success, bind if requested:
 
-    if "OK" == reply.status.as_str() {
-        let do_bind = if let Some(b) = bind {
-            b
-        } else {
-            false // SpecTcl does not support this flag and does not bind
+    if "OK" == reply.status.as_str() && request.bind {
+        let bapi = binder::BindingApi::new(bch);
+        reply = match bapi.bind(&request.newname) {
+            Ok(()) => GenericResponse::ok(""),
+            Err(s) => GenericResponse::err("Could not bind projected spectrum", &s),
         };
-        if do_bind {
-            let bapi = binder::BindingApi::new(&bchannel.inner().lock().unwrap());
-            reply = match bapi.bind(&newname) {
-                Ok(()) => GenericResponse::ok(""),
-                Err(s) => GenericResponse::err("Could not bind projected spectrum", &s),
-            };
-        }
     }
-
-    Json(reply)
+    reply
 }
 // Tests of the REST interface.
 #[cfg(test)]
@@ -1001,6 +1081,97 @@ mod project_rest_tests {
             .expect("Getting bindings list");
         assert_eq!(0, bindings.len());
 
+        teardown(hch, &papi, &bapi);
+    }
+    #[test]
+    fn temporary_1() {
+        // The projection can be made temporary:
+
+        let r = setup();
+        let (hch, papi, bapi) = get_state(&r);
+
+        let c = Client::untracked(r).expect("Creating test client");
+        let reply = c
+            .get("/?snapshot=no&source=2&newname=projection&direction=X&temporary=true&idle=60")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&hch);
+        let temporary = sapi.list_temporary("*").expect("Listing temporaries");
+        assert_eq!(1, temporary.len());
+        assert_eq!("projection", temporary[0].name);
+        assert_eq!(
+            Some(std::time::Duration::from_secs(60)),
+            temporary[0].idle_limit
+        );
+
+        // Bad idle times are rejected before anything is made:
+
+        let reply = c
+            .get("/?snapshot=no&source=2&newname=projection2&direction=X&temporary=true&idle=-1")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("Invalid idle time", reply.status);
+        assert!(sapi
+            .list_spectra("projection2")
+            .expect("Listing")
+            .is_empty());
+
+        teardown(hch, &papi, &bapi);
+    }
+    #[test]
+    fn job_1() {
+        // The projection can be made by a job:
+
+        let r = setup();
+        let (hch, papi, bapi) = get_state(&r);
+        let jobs = r
+            .state::<jobs::SharedJobStore>()
+            .expect("Getting job store")
+            .clone();
+
+        let c = Client::untracked(r).expect("Creating test client");
+        let reply = c
+            .get("/?snapshot=no&source=2&newname=projection&direction=X&job=true")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        let id = reply.detail.parse::<u64>().expect("Job id");
+
+        let mut job = jobs.status(id).expect("Job status");
+        while job.status == jobs::RUNNING {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            job = jobs.status(id).expect("Job status");
+        }
+        assert_eq!(jobs::COMPLETED, job.status);
+        assert_eq!((1, 1), (job.done, job.total));
+
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&hch);
+        assert_eq!(1, sapi.list_spectra("projection").expect("Listing").len());
+
+        // Failures are reported in the job:
+
+        let reply = c
+            .get("/?snapshot=no&source=1&newname=projection2&direction=X&job=true")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        let id = reply.detail.parse::<u64>().expect("Job id");
+        let mut job = jobs.status(id).expect("Job status");
+        while job.status == jobs::RUNNING {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            job = jobs.status(id).expect("Job status");
+        }
+        assert_eq!(jobs::FAILED, job.status);
+        assert!(job
+            .detail
+            .starts_with("Failed to create projection spectrum"));
+
         teardown(hch, &papi, &bapi);
     }
 }
@@ -0,0 +1,276 @@
+//! This module provides the /spectcl/pseudo REST interface to
+//! pseudo parameters; parameters computed, for each event, from other
+//! parameters.  In SpecTcl the computation is a Tcl script.  In
+//! Rustogramer it is an arithmetic expression of the parameters the
+//! pseudo depends on (see src/parameters/pseudo.rs), e.g.:
+//!
+//!  /spectcl/pseudo/create?pseudo=sum&parameter=a&parameter=b&computation=a+b
+//!
+//! (with the + URL encoded as %2B).
+//!
+//! *   ../create - Create a new pseudo parameter.
+//! *   ../list - List the pseudo parameters.
+//! *   ../delete - Delete a pseudo parameter.
+//!
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+
+use super::*;
+
+use crate::messaging::parameter_messages::ParameterMessageClient;
+
+/// Create a pseudo parameter.
+///
+/// #### Query Parameters:
+/// * pseudo - name of the pseudo.  The pseudo computes the parameter
+/// with this name which is created if it does not exist.
+/// * parameter - names of the parameters the computation depends on.
+/// At least one is required.
+/// * computation - the computation; an arithmetic expression of the
+/// parameters.
+///
+/// The response is a GenericResponse.
+#[get("/create?<pseudo>&<parameter>&<computation>")]
+pub fn pseudo_create(
+    pseudo: String,
+    parameter: Vec<String>,
+    computation: OptionalString,
+    state: &State<SharedHistogramChannel>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    let computation = match computation {
+        Some(c) => c,
+        None => {
+            return Json(GenericResponse::err(
+                "'pseudo' command failed",
+                "A computation is required",
+            ))
+        }
+    };
+    let api = ParameterMessageClient::new(&state.inner().lock().unwrap());
+    match api.create_pseudo(&pseudo, &parameter, &computation) {
+        Ok(()) => Json(GenericResponse::ok("")),
+        Err(s) => Json(GenericResponse::err("'pseudo' command failed", &s)),
+    }
+}
+// Description of a pseudo parameter:
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PseudoDescription {
+    name: String,
+    parameters: Vec<String>,
+    computation: String,
+}
+// Response to /pseudo/list:
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PseudoListResponse {
+    status: String,
+    detail: Vec<PseudoDescription>,
+}
+/// List the pseudo parameters whose names match the optional pattern
+/// (defaults to "*").  Pseudos are listed in the order in which they
+/// are computed.
+#[get("/list?<pattern>")]
+pub fn pseudo_list(
+    pattern: OptionalString,
+    state: &State<SharedHistogramChannel>,
+    _ready: busy::HistogramerReady,
+) -> Json<PseudoListResponse> {
+    let pattern = pattern.unwrap_or_else(|| String::from("*"));
+    let api = ParameterMessageClient::new(&state.inner().lock().unwrap());
+    match api.list_pseudos(&pattern) {
+        Ok(pseudos) => Json(PseudoListResponse {
+            status: String::from("OK"),
+            detail: pseudos
+                .into_iter()
+                .map(|p| PseudoDescription {
+                    name: p.name,
+                    parameters: p.parameters,
+                    computation: p.computation,
+                })
+                .collect(),
+        }),
+        Err(s) => Json(PseudoListResponse {
+            status: format!("'pseudo -list' command failed: {}", s),
+            detail: vec![],
+        }),
+    }
+}
+/// Delete a pseudo parameter.  The parameter it computed still
+/// exists but is no longer computed.
+#[get("/delete?<name>")]
+pub fn pseudo_delete(
+    name: String,
+    state: &State<SharedHistogramChannel>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    let api = ParameterMessageClient::new(&state.inner().lock().unwrap());
+    match api.delete_pseudo(&name) {
+        Ok(()) => Json(GenericResponse::ok("")),
+        Err(s) => Json(GenericResponse::err("'pseudo -delete' command failed", &s)),
+    }
+}
+
+#[cfg(test)]
+mod pseudo_tests {
+    use super::*;
+    use crate::messaging;
+    use crate::test::rest_common;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+
+    use std::sync::mpsc;
+
+    fn setup() -> Rocket<Build> {
+        rest_common::setup().mount("/", routes![pseudo_create, pseudo_list, pseudo_delete])
+    }
+    fn getstate(
+        r: &Rocket<Build>,
+    ) -> (
+        mpsc::Sender<messaging::Request>,
+        processing::ProcessingApi,
+        binder::BindingApi,
+    ) {
+        rest_common::get_state(r)
+    }
+    fn teardown(
+        c: mpsc::Sender<messaging::Request>,
+        p: &processing::ProcessingApi,
+        b: &binder::BindingApi,
+    ) {
+        rest_common::teardown(c, p, b);
+    }
+    fn make_params(chan: &mpsc::Sender<messaging::Request>) {
+        let api = ParameterMessageClient::new(chan);
+        for name in ["a", "b"] {
+            api.create_parameter(name).expect("Making parameter");
+        }
+    }
+    #[test]
+    fn create_1() {
+        // Success - the pseudo and its parameter exist:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+        make_params(&chan);
+
+        let client = Client::untracked(rocket).expect("creating client");
+        let req = client.get("/create?pseudo=p&parameter=a&parameter=b&computation=%24a%2B%24b");
+        let reply = req
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        let api = ParameterMessageClient::new(&chan);
+        let pseudos = api.list_pseudos("*").expect("Listing pseudos");
+        assert_eq!(1, pseudos.len());
+        assert_eq!("p", pseudos[0].name);
+        assert_eq!(
+            vec![String::from("a"), String::from("b")],
+            pseudos[0].parameters
+        );
+        assert_eq!("$a+$b", pseudos[0].computation);
+        assert_eq!(1, api.list_parameters("p").expect("Listing").len());
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn create_2() {
+        // Failures:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+        make_params(&chan);
+
+        let client = Client::untracked(rocket).expect("creating client");
+        for uri in [
+            "/create?pseudo=p&parameter=a",
+            "/create?pseudo=p&parameter=c&computation=c",
+            "/create?pseudo=p&parameter=a&computation=a%2B",
+            "/create?pseudo=p&computation=1",
+        ] {
+            let reply = client
+                .get(uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!("'pseudo' command failed", reply.status, "{}", uri);
+        }
+        let api = ParameterMessageClient::new(&chan);
+        assert!(api.list_pseudos("*").expect("Listing").is_empty());
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn list_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+        make_params(&chan);
+        let api = ParameterMessageClient::new(&chan);
+        let deps = vec![String::from("a")];
+        api.create_pseudo("twice", &deps, "a*2")
+            .expect("Making pseudo");
+        api.create_pseudo("half", &deps, "a/2")
+            .expect("Making pseudo");
+
+        let client = Client::untracked(rocket).expect("creating client");
+        let reply = client
+            .get("/list")
+            .dispatch()
+            .into_json::<PseudoListResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(2, reply.detail.len());
+        assert_eq!("twice", reply.detail[0].name); // Definition order.
+        assert_eq!("half", reply.detail[1].name);
+
+        let reply = client
+            .get("/list?pattern=h*")
+            .dispatch()
+            .into_json::<PseudoListResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail.len());
+        assert_eq!("half", reply.detail[0].name);
+        assert_eq!(deps, reply.detail[0].parameters);
+        assert_eq!("a/2", reply.detail[0].computation);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn delete_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+        make_params(&chan);
+        let api = ParameterMessageClient::new(&chan);
+        api.create_pseudo("twice", &[String::from("a")], "a*2")
+            .expect("Making pseudo");
+
+        let client = Client::untracked(rocket).expect("creating client");
+        let reply = client
+            .get("/delete?name=twice")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert!(api.list_pseudos("*").expect("Listing").is_empty());
+
+        let reply = client
+            .get("/delete?name=twice")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("'pseudo -delete' command failed", reply.status);
+
+        teardown(chan, &papi, &bapi);
+    }
+}
@@ -0,0 +1,281 @@
+//!  Supports running the server read-only.  During an experiment a
+//!  "golden" configuration is often exposed to many viewers.  With
+//!  the --read-only command line flag, requests that would modify
+//!  the server's state (define/delete/edit objects, clear spectra,
+//!  attach data sources, control analysis and so on) are rejected while
+//!  requests that list things, get spectrum contents and statistics
+//!  and bind spectra for display/mirroring still work.
+//!
+//!  Handlers that modify state take a Writable request guard.  In
+//!  read-only mode the guard fails the request with a 403 (Forbidden)
+//!  and the server_read_only catcher turns that into the usual JSON
+//!  response.  Handlers where only some requests modify state take a
+//!  WriteAccess guard instead and check those requests themselves.
+//!  When the Writable guard succeeds, or a WriteAccess check allows
+//!  a modification, the request is also marked so that the response
+//!  cache (see cache.rs) is emptied once the handler has made the
+//!  modification and clients see it.
+//!
+//!  The mode is stored in the Rocket state as a ReadOnly struct which
+//!  also counts the requests that were rejected.  The single URI
+//!  supported here, /spectcl/readonly, reports the mode and that count.
+//!
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::*;
+
+/// The mode and the count of requests that were rejected.
+///
+pub struct ReadOnly {
+    read_only: bool,
+    rejected: AtomicU64,
+}
+
+impl ReadOnly {
+    pub fn new(read_only: bool) -> ReadOnly {
+        ReadOnly {
+            read_only,
+            rejected: AtomicU64::new(0),
+        }
+    }
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+    /// Number of requests rejected because the server is read-only.
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+    /// Count a rejected request.
+    pub fn add_rejected(&self) {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+    }
+    /// For handlers where only some requests modify the state:
+    /// Check that a modification is allowed, counting it if not.
+    ///
+    /// ### Returns
+    /// Result<(), String> - the error string describes why not.
+    pub fn check_writable(&self) -> Result<(), String> {
+        if self.read_only {
+            self.add_rejected();
+            Err(String::from("The server is read-only"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Request guard for handlers that modify the server state.
+/// Succeeds unless the server is read-only.
+///
+pub struct Writable;
+
+// Cached responses may no longer be correct once the state is modified.
+// The guard runs before the handler makes the modification, so it only
+// marks the request and cache::ClearOnWrite empties the cache after.
+
+fn mark_modifying(req: &rocket::Request<'_>) {
+    req.local_cache(cache::Modifies::default).set();
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Writable {
+    type Error = String;
+
+    async fn from_request(req: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        match req.rocket().state::<ReadOnly>() {
+            Some(mode) => match mode.check_writable() {
+                Ok(()) => {
+                    mark_modifying(req);
+                    Outcome::Success(Writable)
+                }
+                Err(s) => Outcome::Failure((Status::Forbidden, s)),
+            },
+            None => {
+                mark_modifying(req);
+                Outcome::Success(Writable)
+            }
+        }
+    }
+}
+
+/// Request guard for handlers where only some requests modify the
+/// server state.  This never fails; the handler checks the requests
+/// that modify the state.  The request's cache::Modifies flag is kept
+/// so that a check that allows the modification can mark the request.
+///
+pub struct WriteAccess<'r> {
+    mode: Option<&'r ReadOnly>,
+    modifies: &'r cache::Modifies,
+}
+
+impl<'r> WriteAccess<'r> {
+    pub fn check_writable(&self) -> Result<(), String> {
+        if let Some(mode) = self.mode {
+            mode.check_writable()?;
+        }
+        self.modifies.set();
+        Ok(())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for WriteAccess<'r> {
+    type Error = String;
+
+    async fn from_request(req: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(WriteAccess {
+            mode: req.rocket().state::<ReadOnly>(),
+            modifies: req.local_cache(cache::Modifies::default),
+        })
+    }
+}
+
+/// Catcher for 403 - the Writable guard failed.
+/// The status is "Server is read-only" and the detail, the URI of
+/// the request that was rejected.
+#[catch(403)]
+pub fn server_read_only(req: &rocket::Request) -> Json<GenericResponse> {
+    Json(GenericResponse::err(
+        "Server is read-only",
+        &req.uri().to_string(),
+    ))
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReadOnlyStatus {
+    read_only: bool,
+    rejected: u64,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReadOnlyResponse {
+    status: String,
+    detail: ReadOnlyStatus,
+}
+
+/// Report whether the server is read-only and the number of
+/// requests that were rejected because it is.
+#[get("/")]
+pub fn read_only_status(mode: &State<ReadOnly>) -> Json<ReadOnlyResponse> {
+    Json(ReadOnlyResponse {
+        status: String::from("OK"),
+        detail: ReadOnlyStatus {
+            read_only: mode.inner().is_read_only(),
+            rejected: mode.inner().rejected(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod readonly_tests {
+    use super::*;
+    use crate::messaging::parameter_messages::ParameterMessageClient;
+    use crate::test::rest_common;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+
+    fn setup(read_only: bool) -> Rocket<Build> {
+        rest_common::setup()
+            .manage(ReadOnly::new(read_only))
+            .mount(
+                "/spectcl/parameter",
+                routes![
+                    rest_parameter::create_parameter,
+                    rest_parameter::list_parameters
+                ],
+            )
+            .mount("/spectcl/readonly", routes![read_only_status])
+            .register("/", catchers![server_read_only])
+    }
+    fn create_uri() -> &'static str {
+        "/spectcl/parameter/create?name=event.raw.00"
+    }
+
+    #[test]
+    fn status_1() {
+        // Writable server reports so:
+
+        let rocket = setup(false);
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        let client = Client::untracked(rocket).expect("Making client");
+
+        let reply = client
+            .get("/spectcl/readonly")
+            .dispatch()
+            .into_json::<ReadOnlyResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert!(!reply.detail.read_only);
+        assert_eq!(0, reply.detail.rejected);
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn writable_1() {
+        // Writable server allows modifications:
+
+        let rocket = setup(false);
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        let client = Client::untracked(rocket).expect("Making client");
+
+        let response = client.get(create_uri()).dispatch();
+        assert_eq!(Status::Ok, response.status());
+        let reply = response
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        let api = ParameterMessageClient::new(&chan);
+        assert_eq!(1, api.list_parameters("*").expect("listing").len());
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn readonly_1() {
+        // Read-only server rejects modifications but allows lists:
+
+        let rocket = setup(true);
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        let client = Client::untracked(rocket).expect("Making client");
+
+        let response = client.get(create_uri()).dispatch();
+        assert_eq!(Status::Forbidden, response.status());
+        let reply = response
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("Server is read-only", reply.status);
+        assert!(reply.detail.starts_with("/spectcl/parameter/create"));
+
+        let api = ParameterMessageClient::new(&chan);
+        assert!(api.list_parameters("*").expect("listing").is_empty());
+
+        let response = client.get("/spectcl/parameter/list").dispatch();
+        assert_eq!(Status::Ok, response.status());
+
+        let reply = client
+            .get("/spectcl/readonly")
+            .dispatch()
+            .into_json::<ReadOnlyResponse>()
+            .expect("Parsing JSON");
+        assert!(reply.detail.read_only);
+        assert_eq!(1, reply.detail.rejected);
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn check_1() {
+        let mode = ReadOnly::new(false);
+        assert!(mode.check_writable().is_ok());
+        let mode = ReadOnly::new(true);
+        assert!(mode.check_writable().is_err());
+        assert_eq!(1, mode.rejected());
+    }
+}
@@ -25,7 +25,11 @@ use crate::ring_items::RingVersion;
 /// detail is the reason for the failure.
 ///
 #[get("/?<major>")]
-pub fn ringversion_set(major: String, state: &State<SharedProcessingApi>) -> Json<GenericResponse> {
+pub fn ringversion_set(
+    major: String,
+    state: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
     let api = state.inner().lock().unwrap();
 
     let result = major.parse::<RingVersion>();
@@ -0,0 +1,365 @@
+//! This module provides the /spectcl/roottree domain.  In SpecTcl a
+//! root tree writes the parameters of each event to a ROOT file.
+//! Rustogramer gets its parameters already decoded by an external
+//! pipeline which is where event trees should be made.  What analysts
+//! want from Rustogramer, instead, is its spectra in ROOT.
+//!
+//! A Rustogramer root tree is therefore a named, scheduled export of
+//! spectra.  Each tree runs an auto-save (see src/autosave/mod.rs) that
+//! periodically writes its spectra as a ROOT macro (see
+//! spectclio::root).  Any number of trees can be running.
+//!
+//! We depend on the main to have set the Rocket state to include a
+//! RootExports.
+//!
+use glob::Pattern;
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+use std::collections::BTreeMap;
+use std::sync::{mpsc, Mutex};
+
+use super::*;
+use crate::autosave;
+use crate::messaging;
+
+/// The format in which root trees write spectra.
+pub const ROOT_FORMAT: &str = "root";
+
+/// The root trees.  Each is an auto-saver that writes in ROOT_FORMAT
+/// keyed by the tree name.
+///
+#[derive(Default)]
+pub struct RootExports {
+    exports: Mutex<BTreeMap<String, autosave::AutoSaver>>,
+}
+
+impl RootExports {
+    pub fn new() -> RootExports {
+        RootExports::default()
+    }
+    /// Create a tree and start its exports.
+    ///
+    /// ### Parameters
+    /// * name - name of the tree, must be unique.
+    /// * chan - channel to the histogram server.
+    /// * policy - what to export and how often.
+    ///
+    /// ### Returns
+    /// Result<(), String> - the error string says why not.
+    pub fn create(
+        &self,
+        name: &str,
+        chan: &mpsc::Sender<messaging::Request>,
+        policy: autosave::AutoSavePolicy,
+    ) -> Result<(), String> {
+        let mut exports = self.exports.lock().unwrap();
+        if exports.contains_key(name) {
+            return Err(format!("The tree {} already exists", name));
+        }
+        let saver = autosave::AutoSaver::new();
+        saver.start(chan, policy);
+        exports.insert(String::from(name), saver);
+        Ok(())
+    }
+    /// Stop a tree's exports and delete it.  The files it wrote are
+    /// left in place.
+    pub fn delete(&self, name: &str) -> Result<(), String> {
+        match self.exports.lock().unwrap().remove(name) {
+            Some(saver) => {
+                saver.stop();
+                Ok(())
+            }
+            None => Err(format!("The tree {} does not exist", name)),
+        }
+    }
+    /// The names and statuses of the trees whose names match a glob
+    /// pattern, sorted by name.
+    pub fn list(&self, pattern: &str) -> Result<Vec<(String, autosave::AutoSaveStatus)>, String> {
+        let pattern = Pattern::new(pattern).map_err(|e| String::from(e.msg))?;
+        Ok(self
+            .exports
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(name, _)| pattern.matches(name))
+            .map(|(name, saver)| (name.clone(), saver.status()))
+            .collect())
+    }
+}
+
+/// Create a root tree.
+///
+/// #### Query Parameters:
+/// * tree - Name of the tree, must be unique.
+/// * spectrum - Glob patterns of the spectra to export, can be supplied
+///   more than once.  Defaults to "*", all spectra.
+/// * file - File name pattern.  {seq} is replaced by the export number.
+/// * interval - Seconds between exports.
+/// * keep - Number of files to retain.  Defaults to 0 which keeps all
+///   files.
+/// * gate - SpecTcl gates the events written to its trees.  Spectra
+///   have their own gates so this is not supported.
+///
+#[get("/create?<tree>&<spectrum>&<file>&<interval>&<keep>&<gate>")]
+#[allow(clippy::too_many_arguments)]
+pub fn roottree_create(
+    tree: String,
+    spectrum: OptionalStringVec,
+    file: String,
+    interval: f64,
+    keep: Option<usize>,
+    gate: OptionalString,
+    state: &State<SharedHistogramChannel>,
+    exports: &State<RootExports>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    if gate.is_some() {
+        return Json(GenericResponse::err(
+            "Unable to create root tree",
+            "Root trees export spectra and can't be gated",
+        ));
+    }
+    let spectra = match spectrum {
+        Some(v) if !v.is_empty() => v,
+        _ => vec![String::from("*")],
+    };
+    let result =
+        autosave::AutoSavePolicy::new(&spectra, &file, ROOT_FORMAT, interval, keep.unwrap_or(0))
+            .and_then(|policy| {
+                exports
+                    .inner()
+                    .create(&tree, &state.inner().lock().unwrap(), policy)
+            });
+    Json(match result {
+        Ok(()) => GenericResponse::ok(""),
+        Err(msg) => GenericResponse::err("Unable to create root tree", &msg),
+    })
+}
+
+/// Delete a root tree, stopping its exports.
+
+#[get("/delete?<tree>")]
+pub fn roottree_delete(
+    tree: String,
+    exports: &State<RootExports>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    Json(match exports.inner().delete(&tree) {
+        Ok(()) => GenericResponse::ok(""),
+        Err(msg) => GenericResponse::err("Unable to delete root tree", &msg),
+    })
+}
+
+// Description of a root tree.  tree, params and gate are what
+// SpecTcl reports; gate is always None.  The rest describe the
+// exports: last_save is the time of the last successful export in
+// seconds since the epoch.
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RootTreeDescription {
+    tree: String,
+    params: Vec<String>,
+    gate: Option<String>,
+    file: String,
+    interval: f64,
+    keep: usize,
+    saves: u64,
+    last_save: Option<u64>,
+    files: Vec<String>,
+    error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RootTreeListResponse {
+    status: String,
+    detail: Vec<RootTreeDescription>,
+}
+
+fn describe_tree(name: String, status: autosave::AutoSaveStatus) -> Option<RootTreeDescription> {
+    let policy = status.policy?;
+    Some(RootTreeDescription {
+        tree: name,
+        params: policy.spectra(),
+        gate: None,
+        file: policy.file_pattern(),
+        interval: policy.interval().as_secs_f64(),
+        keep: policy.keep(),
+        saves: status.saves,
+        last_save: status.last_save.map(|t| {
+            t.duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        }),
+        files: status.files,
+        error: status.error,
+    })
+}
+
+/// List the root trees whose names match the optional glob pattern
+/// (defaults to "*").
+#[get("/list?<pattern>")]
+pub fn roottree_list(
+    pattern: OptionalString,
+    exports: &State<RootExports>,
+) -> Json<RootTreeListResponse> {
+    let pattern = pattern.unwrap_or_else(|| String::from("*"));
+    Json(match exports.inner().list(&pattern) {
+        Ok(trees) => RootTreeListResponse {
+            status: String::from("OK"),
+            detail: trees
+                .into_iter()
+                .filter_map(|(name, status)| describe_tree(name, status))
+                .collect(),
+        },
+        Err(msg) => RootTreeListResponse {
+            status: format!("Unable to list root trees: {}", msg),
+            detail: vec![],
+        },
+    })
+}
+
+#[cfg(test)]
+mod roottree_tests {
+    use super::*;
+    use crate::messaging::spectrum_messages;
+    use crate::test::rest_common;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+    use std::fs;
+    use std::path::Path;
+    use std::thread;
+    use std::time;
+
+    fn setup() -> Rocket<Build> {
+        rest_common::setup().manage(RootExports::new()).mount(
+            "/",
+            routes![roottree_create, roottree_delete, roottree_list],
+        )
+    }
+    fn list(client: &Client, pattern: &str) -> RootTreeListResponse {
+        client
+            .get(format!("/list?pattern={}", pattern))
+            .dispatch()
+            .into_json::<RootTreeListResponse>()
+            .expect("Parsing JSON")
+    }
+    fn get(client: &Client, uri: &str) -> GenericResponse {
+        client
+            .get(uri)
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON")
+    }
+    fn file_pattern(dir: &Path, name: &str) -> String {
+        dir.join(name)
+            .to_str()
+            .unwrap()
+            .replace('{', "%7B")
+            .replace('}', "%7D")
+    }
+
+    #[test]
+    fn create_1() {
+        // Errors in the request:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+
+        let client = Client::untracked(rocket).expect("making rocket client");
+        for uri in [
+            "/create?tree=t&file=export.C&interval=10",
+            "/create?tree=t&file=export-%7Bseq%7D.C&interval=0",
+            "/create?tree=t&file=export-%7Bseq%7D.C&interval=10&gate=g",
+        ] {
+            let reply = get(&client, uri);
+            assert_eq!("Unable to create root tree", reply.status, "{}", uri);
+        }
+        assert!(list(&client, "*").detail.is_empty());
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn create_2() {
+        // Trees export until deleted and have unique names:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        ParameterMessageClient::new(&chan)
+            .create_parameter("p1")
+            .expect("Making p1");
+        spectrum_messages::SpectrumMessageClient::new(&chan)
+            .create_spectrum_1d("s1", "p1", 0.0, 10.0, 10)
+            .expect("Making s1");
+        let dir = tempfile::tempdir().expect("Making temp dir");
+
+        let client = Client::untracked(rocket).expect("making rocket client");
+        let uri = format!(
+            "/create?tree=t1&spectrum=s*&file={}&interval=0.05&keep=1",
+            file_pattern(dir.path(), "t1-{seq}.C")
+        );
+        assert_eq!("OK", get(&client, &uri).status);
+        assert_eq!("Unable to create root tree", get(&client, &uri).status);
+        let uri = format!(
+            "/create?tree=other&file={}&interval=100",
+            file_pattern(dir.path(), "other-{seq}.C")
+        );
+        assert_eq!("OK", get(&client, &uri).status);
+
+        let start = time::Instant::now();
+        while list(&client, "t1").detail[0].saves < 2 {
+            assert!(
+                start.elapsed() < time::Duration::from_secs(10),
+                "Exports too slow"
+            );
+            thread::sleep(time::Duration::from_millis(10));
+        }
+        let reply = list(&client, "*");
+        assert_eq!("OK", reply.status);
+        assert_eq!(2, reply.detail.len());
+        assert_eq!("other", reply.detail[0].tree);
+        assert_eq!(vec![String::from("*")], reply.detail[0].params);
+        assert_eq!(100.0, reply.detail[0].interval);
+
+        let t1 = &reply.detail[1];
+        assert_eq!("t1", t1.tree);
+        assert_eq!(vec![String::from("s*")], t1.params);
+        assert!(t1.gate.is_none());
+        assert_eq!(1, t1.keep);
+        assert!(t1.error.is_none());
+        assert_eq!(1, t1.files.len());
+        let text = fs::read_to_string(&t1.files[0]).expect("Reading export");
+        assert!(text.contains("new TH1D(\"s1\", \"s1\", 10, 0, 10);"));
+
+        assert_eq!("OK", get(&client, "/delete?tree=t1").status);
+        assert_eq!(
+            "Unable to delete root tree",
+            get(&client, "/delete?tree=t1").status
+        );
+        let reply = list(&client, "*");
+        assert_eq!(1, reply.detail.len());
+        assert_eq!("other", reply.detail[0].tree);
+        assert!(Path::new(&t1.files[0]).exists());
+
+        get(&client, "/delete?tree=other");
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn list_1() {
+        // Bad pattern:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+
+        let client = Client::untracked(rocket).expect("making rocket client");
+        let reply = list(&client, "%5B");
+        assert!(reply.status.starts_with("Unable to list root trees"));
+        assert!(reply.detail.is_empty());
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+}
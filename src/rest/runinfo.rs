@@ -0,0 +1,329 @@
+//! Implements the /spectcl/runinfo domain.  This is Rustogramer only.
+//! The processing thread records the run number, title and start/stop
+//! times from the state change items it processes (see
+//! processing::runinfo) so that GUIs can show which run is being
+//! analyzed.  The state changes themselves are also available as run
+//! traces and in the run log (see /spectcl/trace).
+//!
+//! *  /spectcl/runinfo - describes the run being analyzed.
+//! *  /spectcl/runinfo/autoclear - sets which spectra, if any, are
+//!    cleared at the beginning of each run.
+//!
+use super::*;
+use glob::Pattern;
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+use std::time;
+
+/// The run being analyzed:
+///
+/// * state - "Unknown" (no state change seen), "Active", "Paused" or
+///   "Ended".
+/// * run - the run number, null if unknown.
+/// * title - the run title.
+/// * start - unix time (seconds) at which the run began, if known.
+/// * stop - unix time (seconds) at which the run ended, if it has.
+/// * elapsed - seconds into the run of the most recent state change.
+/// * autoclear - true if spectra are cleared at the beginning of
+///   each run.
+/// * autoclear_spectra - glob patterns of the spectra cleared at the
+///   beginning of each run.
+///
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RunInfo {
+    pub state: String,
+    pub run: Option<u32>,
+    pub title: String,
+    pub start: Option<u64>,
+    pub stop: Option<u64>,
+    pub elapsed: f32,
+    pub autoclear: bool,
+    pub autoclear_spectra: Vec<String>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RunInfoResponse {
+    pub status: String,
+    pub detail: RunInfo,
+}
+
+fn unix_time(t: Option<time::SystemTime>) -> Option<u64> {
+    t.map(|t| {
+        t.duration_since(time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    })
+}
+
+/// Describe the run being analyzed.
+///
+#[get("/")]
+pub fn run_info(state: &State<SharedProcessingApi>) -> Json<RunInfoResponse> {
+    let api = state.inner().lock().unwrap();
+    let info = api.get_run_info();
+    let autoclear_spectra = api.get_clear_at_begin();
+    Json(RunInfoResponse {
+        status: String::from("OK"),
+        detail: RunInfo {
+            state: String::from(info.state.as_str()),
+            run: info.run,
+            title: info.title,
+            start: unix_time(info.start),
+            stop: unix_time(info.stop),
+            elapsed: info.elapsed,
+            autoclear: !autoclear_spectra.is_empty(),
+            autoclear_spectra,
+        },
+    })
+}
+/// Set which spectra are cleared when a BEGIN_RUN item is processed.
+///
+/// ### Query parameters
+/// * enable - optional flag, defaults to true.  If false, no spectra
+///   are cleared.
+/// * spectrum - optional glob pattern of the spectra to clear.  This
+///   can appear more than once.  Defaults to all spectra (*).
+///
+#[get("/autoclear?<enable>&<spectrum>")]
+pub fn set_autoclear(
+    enable: OptionalFlag,
+    spectrum: Vec<String>,
+    state: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    let patterns = if !enable.unwrap_or(true) {
+        Vec::new()
+    } else if spectrum.is_empty() {
+        vec![String::from("*")]
+    } else {
+        spectrum
+    };
+    for pattern in patterns.iter() {
+        if let Err(e) = Pattern::new(pattern) {
+            return Json(GenericResponse::err(
+                "Unable to set autoclear",
+                &format!("Bad spectrum pattern '{}': {}", pattern, e),
+            ));
+        }
+    }
+    state.inner().lock().unwrap().set_clear_at_begin(&patterns);
+    Json(GenericResponse::ok(""))
+}
+
+#[cfg(test)]
+mod runinfo_tests {
+    use super::*;
+    use crate::messaging;
+    use crate::processing;
+    use crate::ring_items::analysis_ring_items::{
+        ParameterDefinitions, ParameterFileWriter, ParameterItem,
+    };
+    use crate::ring_items::state_change::{StateChange, StateChangeType};
+    use crate::ring_items::{RingVersion, ToRaw};
+    use crate::sharedmem::binder;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+
+    use crate::test::rest_common;
+    use std::sync::mpsc;
+
+    fn setup() -> Rocket<Build> {
+        rest_common::setup().mount("/", routes![run_info, set_autoclear])
+    }
+    fn teardown(
+        c: mpsc::Sender<messaging::Request>,
+        p: &processing::ProcessingApi,
+        b: &binder::BindingApi,
+    ) {
+        rest_common::teardown(c, p, b);
+    }
+    fn get_state(
+        r: &Rocket<Build>,
+    ) -> (
+        mpsc::Sender<messaging::Request>,
+        processing::ProcessingApi,
+        binder::BindingApi,
+    ) {
+        rest_common::get_state(r)
+    }
+    fn get_info(client: &Client) -> RunInfo {
+        let reply = client
+            .get("/")
+            .dispatch()
+            .into_json::<RunInfoResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status);
+        reply.detail
+    }
+    // Write a file with a run of one event of parameter p1 and
+    // analyze it.  If end is false the run is not ended.
+
+    fn analyze(path: &std::path::Path, papi: &processing::ProcessingApi, end: bool) {
+        let mut writer = ParameterFileWriter::new(
+            std::fs::File::create(path).expect("Creating file"),
+            RingVersion::V11,
+        )
+        .expect("Making writer");
+        let mut defs = ParameterDefinitions::new();
+        defs.add(1, "p1");
+        writer.write_item(&defs.to_raw()).expect("Writing item");
+        let begin = StateChange::new(StateChangeType::Begin, None, 12, 0, 1, "A run", None);
+        writer.write_item(&begin.to_raw()).expect("Writing item");
+        let mut event = ParameterItem::new(0);
+        event.add(1, 5.0);
+        writer.write_item(&event.to_raw()).expect("Writing item");
+        if end {
+            let end = StateChange::new(StateChangeType::End, None, 12, 30, 1, "A run", None);
+            writer.write_item(&end.to_raw()).expect("Writing item");
+        }
+        writer.flush().expect("Flushing");
+
+        papi.attach(path.to_str().unwrap()).expect("attaching file");
+        papi.start_analysis().expect("Starting analysis");
+        let start = std::time::Instant::now();
+        while papi.processing_state().expect("Getting state") != "Inactive" {
+            assert!(start.elapsed() < std::time::Duration::from_secs(10));
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn info_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        let client = Client::tracked(rocket).expect("Making client");
+
+        let info = get_info(&client);
+        assert_eq!("Unknown", info.state);
+        assert!(info.run.is_none());
+        assert!(info.start.is_none());
+        assert!(!info.autoclear);
+        assert!(info.autoclear_spectra.is_empty());
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        analyze(&dir.path().join("run.evt"), &papi, false);
+        let info = get_info(&client);
+        assert_eq!("Active", info.state);
+        assert_eq!(Some(12), info.run);
+        assert_eq!("A run", info.title);
+        assert!(info.start.is_some());
+        assert!(info.stop.is_none());
+
+        analyze(&dir.path().join("run2.evt"), &papi, true);
+        let info = get_info(&client);
+        assert_eq!("Ended", info.state);
+        assert!(info.stop.is_some());
+        assert_eq!(30.0, info.elapsed);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn autoclear_1() {
+        // With autoclear, each run starts from empty spectra:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        let client = Client::tracked(rocket).expect("Making client");
+
+        let reply = client
+            .get("/autoclear")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status);
+        let info = get_info(&client);
+        assert!(info.autoclear);
+        assert_eq!(vec!["*"], info.autoclear_spectra);
+
+        let param_api = messaging::parameter_messages::ParameterMessageClient::new(&chan);
+        param_api.create_parameter("p1").expect("Making parameter");
+        let spec_api = messaging::spectrum_messages::SpectrumMessageClient::new(&chan);
+        spec_api
+            .create_spectrum_1d("s1", "p1", 0.0, 10.0, 10)
+            .expect("Creating spectrum");
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let total = |api: &messaging::spectrum_messages::SpectrumMessageClient| -> f64 {
+            api.get_contents("s1", 0.0, 10.0, 0.0, 0.0)
+                .expect("Getting contents")
+                .iter()
+                .map(|c| c.value)
+                .sum()
+        };
+        analyze(&dir.path().join("run1.evt"), &papi, true);
+        assert_eq!(1.0, total(&spec_api));
+        analyze(&dir.path().join("run2.evt"), &papi, true);
+        assert_eq!(1.0, total(&spec_api));
+
+        // Without it, they accumulate:
+
+        let reply = client
+            .get("/autoclear?enable=false")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status);
+        assert!(!get_info(&client).autoclear);
+        analyze(&dir.path().join("run3.evt"), &papi, true);
+        assert_eq!(2.0, total(&spec_api));
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn autoclear_2() {
+        // Only the selected spectra are cleared:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        let client = Client::tracked(rocket).expect("Making client");
+
+        let reply = client
+            .get("/autoclear?spectrum=a*&spectrum=b1")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(vec!["a*", "b1"], get_info(&client).autoclear_spectra);
+
+        let param_api = messaging::parameter_messages::ParameterMessageClient::new(&chan);
+        param_api.create_parameter("p1").expect("Making parameter");
+        let spec_api = messaging::spectrum_messages::SpectrumMessageClient::new(&chan);
+        for name in ["a1", "b1", "c1"] {
+            spec_api
+                .create_spectrum_1d(name, "p1", 0.0, 10.0, 10)
+                .expect("Creating spectrum");
+        }
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        analyze(&dir.path().join("run1.evt"), &papi, true);
+        analyze(&dir.path().join("run2.evt"), &papi, true);
+        let totals: Vec<f64> = ["a1", "b1", "c1"]
+            .iter()
+            .map(|name| {
+                spec_api
+                    .get_contents(name, 0.0, 10.0, 0.0, 0.0)
+                    .expect("Getting contents")
+                    .iter()
+                    .map(|c| c.value)
+                    .sum()
+            })
+            .collect();
+        assert_eq!(vec![1.0, 1.0, 2.0], totals);
+
+        // Bad patterns are rejected without changing anything:
+
+        let reply = client
+            .get("/autoclear?spectrum=%5B")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("Unable to set autoclear", reply.status);
+        assert_eq!(vec!["a*", "b1"], papi.get_clear_at_begin());
+
+        teardown(chan, &papi, &bapi);
+    }
+}
@@ -17,11 +17,25 @@
 //! by name.
 //! *  /spectcl/sbind/list - list the bindings.  See, however
 //! the documentation for sbind_list below.
+//! *  /spectcl/sbind/transform - Set the display transform of a spectrum.
+//! *  /spectcl/sbind/untransform - Remove the display transform of a spectrum.
+//! *  /spectcl/sbind/transforms - List display transforms.
+//!
+//!  The bind URIs accept an optional _overflows_ flag which
+//!  determines if the under/overflow channels of the spectra are
+//!  included in the shared memory copies (the default) or not.
+//!  They also accept an optional _chantype_ (long, word or byte) which
+//!  sets the width of the shared memory channels of 2d spectra.  Narrow
+//!  channels save shared memory; counts too big for them saturate.
+//!
+//!  Display transforms are Rustogramer only.  They change the axis
+//!  limits and labels shared memory viewers see (e.g. keV rather than
+//!  channels) without changing the binning of the spectrum.
 
 // Imports.
 use super::*;
 use crate::messaging::spectrum_messages;
-use crate::sharedmem::binder;
+use crate::sharedmem::{binder, ChannelWidth};
 use rocket::serde::{json::Json, Deserialize, Serialize};
 use rocket::State;
 
@@ -86,15 +100,25 @@ fn list_unbound_spectra(
 fn bind_spectrum_list(
     spectra_to_bind: &Vec<String>,
     binding_api: &binder::BindingApi,
+    include_overflows: bool,
+    width: ChannelWidth,
 ) -> GenericResponse {
     for name in spectra_to_bind {
-        if let Err(s) = binding_api.bind(name) {
+        if let Err(s) = binding_api.bind_with(name, include_overflows, width) {
             return GenericResponse::err(&format!("Unable to bind spectrum {}", name), &s);
         }
     }
 
     GenericResponse::ok("")
 }
+/// Get the channel width from the optional chantype query parameter.
+/// The default is long.  On failure, the error is the response to send.
+pub fn channel_width(chantype: &OptionalString) -> Result<ChannelWidth, GenericResponse> {
+    match chantype {
+        Some(t) => ChannelWidth::parse(t).map_err(|e| GenericResponse::err("Invalid chantype", &e)),
+        None => Ok(ChannelWidth::Long),
+    }
+}
 
 /// Bind all unbound spectra to the shared memory.  To avoid
 /// errors and minimize transaction with the binding thread,
@@ -106,6 +130,10 @@ fn bind_spectrum_list(
 /// for that binding attempt.
 ///
 /// ### Parameters
+/// *  overflows - optional flag, if false, the under/overflow channels
+/// are not included in the shared memory copies.  Defaults to true.
+/// *  chantype - optional width of the shared memory channels of 2d
+/// spectra; long (default), word or byte.
 /// *  state - Histogrammer REST interface state.
 ///
 /// ### Returns
@@ -122,11 +150,18 @@ fn bind_spectrum_list(
 /// the slot count is exceeded or the spectrum memory pool did not
 /// have a chunk big enough for the spectrum).
 ///
-#[get("/all")]
+#[get("/all?<overflows>&<chantype>")]
 pub fn sbind_all(
+    overflows: Option<bool>,
+    chantype: OptionalString,
     hg_state: &State<SharedHistogramChannel>,
     b_state: &State<SharedBinderChannel>,
+    _ready: busy::HistogramerReady,
 ) -> Json<GenericResponse> {
+    let width = match channel_width(&chantype) {
+        Ok(w) => w,
+        Err(response) => return Json(response),
+    };
     let spectrum_api =
         spectrum_messages::SpectrumMessageClient::new(&hg_state.inner().lock().unwrap());
     let binding_api = binder::BindingApi::new(&b_state.inner().lock().unwrap());
@@ -145,7 +180,12 @@ pub fn sbind_all(
     };
     // Now get the unique bindings:
     let spectra_to_bind = list_unbound_spectra(&spectrum_list, &binding_list);
-    let response = bind_spectrum_list(&spectra_to_bind, &binding_api);
+    let response = bind_spectrum_list(
+        &spectra_to_bind,
+        &binding_api,
+        overflows.unwrap_or(true),
+        width,
+    );
     Json(response)
 }
 //----------------------------------------------------------------
@@ -173,36 +213,76 @@ fn remove_duplicates(in_names: Vec<String>) -> Vec<String> {
 /// *  spectrum - Can be supplied as many times as needed to specify
 /// the spectra to be bound.  Note that in SpecTcl, attempts to
 /// bind an existing binding are just ignored.
+/// *  overflows - optional flag, if false, the under/overflow channels
+/// are not included in the shared memory copies.  Defaults to true.
+/// *  chantype - optional width of the shared memory channels of 2d
+/// spectra; long (default), word or byte.
 /// * state - the state of the REST server, which allows us to get the
 /// API we need.
+/// * limits - server limits, the number of spectra is checked against the
+/// bulk spectrum limit.
 ///
 /// ### Returns
 /// *  GenericResponse encoded as Json. On success, the detail is empty.
 /// There are several failures to consider:
+///     - Too many spectra: status is _Request exceeds server limits_
 ///     - Unable to get the list of bindings: status is _Failed to get spectrum bindings_
 /// and the detail is the reason given by the bindings API>
 ///     - Unable to bind a spectrum: status is _Unable to bind {spectrum name} and
 /// the detail is the reason given by the binding api.
 ///
-#[get("/sbind?<spectrum>")]
+#[get("/sbind?<spectrum>&<overflows>&<chantype>")]
 pub fn sbind_list(
     spectrum: Vec<String>,
+    overflows: Option<bool>,
+    chantype: OptionalString,
     state: &State<SharedBinderChannel>,
+    limits: &State<limits::RestLimits>,
 ) -> Json<GenericResponse> {
+    if let Err(e) = limits.check_bulk_spectra(spectrum.len()) {
+        return Json(GenericResponse::err("Request exceeds server limits", &e));
+    }
+    let width = match channel_width(&chantype) {
+        Ok(w) => w,
+        Err(response) => return Json(response),
+    };
     // We need the bindings api.
 
     let api = binder::BindingApi::new(&state.inner().lock().unwrap());
+    let spectrum = remove_duplicates(spectrum);
+    Json(bind_unbound_spectra(
+        &spectrum,
+        &api,
+        overflows.unwrap_or(true),
+        width,
+    ))
+}
+/// Bind the spectra in a list that are not already bound.  This is
+/// what /spectcl/sbind/sbind does once it has checked its request.
+///
+/// ### Parameters
+/// * spectra - names of the spectra to bind, without duplicates.
+/// * api - the binding API.
+/// * include_overflows - include the under/overflow channels.
+/// * width - width of the shared memory channels of 2d spectra.
+///
+/// ### Returns
+/// GenericResponse as for /spectcl/sbind/sbind.
+pub fn bind_unbound_spectra(
+    spectra: &Vec<String>,
+    api: &binder::BindingApi,
+    include_overflows: bool,
+    width: ChannelWidth,
+) -> GenericResponse {
     let binding_list = match api.list_bindings("*") {
         Ok(l) => l,
         Err(s) => {
-            return Json(GenericResponse::err("Unable to get bindings", &s));
+            return GenericResponse::err("Unable to get bindings", &s);
         }
     };
-    let spectrum = remove_duplicates(spectrum);
     let binding_hash = make_binding_hash(&binding_list);
-    let to_bind = remove_bound_spectra(&spectrum, &binding_hash);
-    let response = bind_spectrum_list(&to_bind, &api);
-    Json(response)
+    let to_bind = remove_bound_spectra(spectra, &binding_hash);
+    bind_spectrum_list(&to_bind, api, include_overflows, width)
 }
 //------------------------------------------------------------------
 // /spectcl/sbind/list[?pattern=glob-pattern]
@@ -216,6 +296,9 @@ pub struct Binding {
     spectrumid: usize,
     name: String,
     binding: usize,
+    overflows: bool,
+    chantype: String,
+    saturated: usize,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -249,7 +332,11 @@ fn get_spectrum_id(api: &spectrum_messages::SpectrumMessageClient, name: &str) -
 /// that allows us to create a bindings API>
 ///
 /// ### Returns
-///  * A Json encoded instance of a BindingsResponse.
+///  * A Json encoded instance of a BindingsResponse.  The _overflows_
+/// field of each binding is true if the under/overflow channels are
+/// included in the shared memory copy of the spectrum.  _chantype_ is
+/// the width of the shared memory channels and _saturated_ the number of
+/// channels that were too big for them in the last update.
 ///
 /// #### Note
 /// Rustogramer does not assign ids to spectra.  THerefore
@@ -260,6 +347,7 @@ pub fn sbind_bindings(
     pattern: OptionalString,
     state: &State<SharedBinderChannel>,
     spec_api: &State<SharedHistogramChannel>,
+    _ready: busy::HistogramerReady,
 ) -> Json<BindingsResponse> {
     let api = binder::BindingApi::new(&state.inner().lock().unwrap());
     let sapi = spectrum_messages::SpectrumMessageClient::new(&spec_api.inner().lock().unwrap());
@@ -272,14 +360,17 @@ pub fn sbind_bindings(
         status: String::from(""),
         detail: vec![],
     };
-    match api.list_bindings(&p) {
+    match api.list_binding_details(&p) {
         Ok(l) => {
             response.status = String::from("OK");
             for b in l {
                 response.detail.push(Binding {
-                    spectrumid: get_spectrum_id(&sapi, &b.1),
-                    name: b.1,
-                    binding: b.0,
+                    spectrumid: get_spectrum_id(&sapi, &b.name),
+                    name: b.name,
+                    binding: b.binding,
+                    overflows: b.include_overflows,
+                    chantype: String::from(b.width.name()),
+                    saturated: b.saturated,
                 });
             }
         }
@@ -295,7 +386,11 @@ pub fn sbind_bindings(
 /// a copy that must be periodically updated.  This ReST method
 /// sets that update period in seconds
 #[get("/set_update?<seconds>")]
-pub fn set_update(seconds: u64, state: &State<SharedBinderChannel>) -> Json<GenericResponse> {
+pub fn set_update(
+    seconds: u64,
+    state: &State<SharedBinderChannel>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
     let bapi = binder::BindingApi::new(&state.inner().lock().unwrap());
     let response = if let Err(s) = bapi.set_update_period(seconds) {
         GenericResponse::err("Could not set update rate", &s)
@@ -316,6 +411,146 @@ pub fn get_update(state: &State<SharedBinderChannel>) -> Json<UnsignedResponse>
     Json(response)
 }
 
+//------------------------------------------------------------------
+// Display transforms.
+
+// Make an axis transform from the query parameters for that axis.
+// None if none of them were given:
+
+fn make_axis_transform(
+    scale: Option<f64>,
+    offset: Option<f64>,
+    unit: OptionalString,
+) -> Option<binder::AxisTransform> {
+    if scale.is_none() && offset.is_none() && unit.is_none() {
+        None
+    } else {
+        Some(binder::AxisTransform::new(
+            scale.unwrap_or(1.0),
+            offset.unwrap_or(0.0),
+            &unit.unwrap_or_default(),
+        ))
+    }
+}
+
+/// Set the display transform of a spectrum.  When bound, viewers see
+/// an axis coordinate x as scale*x + offset and the axis is labeled with
+/// the unit.
+///
+/// ### Parameters
+/// *  spectrum - name of the spectrum.
+/// *  xscale, xoffset, xunit - x axis transform.  If any of these are given,
+/// the x axis is transformed; missing values default to 1.0, 0.0 and an
+/// empty unit.
+/// *  yscale, yoffset, yunit - same as above but for the y axis.
+/// *  state - the REST interface State which includes the binder channel.
+///
+/// ### Returns
+/// *  Json encoded GenericResponse.  On failure the status is
+/// _Could not set display transform_ and the detail is the reason.
+///
+#[get("/transform?<spectrum>&<xscale>&<xoffset>&<xunit>&<yscale>&<yoffset>&<yunit>")]
+pub fn set_transform(
+    spectrum: String,
+    xscale: Option<f64>,
+    xoffset: Option<f64>,
+    xunit: OptionalString,
+    yscale: Option<f64>,
+    yoffset: Option<f64>,
+    yunit: OptionalString,
+    state: &State<SharedBinderChannel>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    let transform = binder::DisplayTransform {
+        xaxis: make_axis_transform(xscale, xoffset, xunit),
+        yaxis: make_axis_transform(yscale, yoffset, yunit),
+    };
+    if transform.is_identity() {
+        return Json(GenericResponse::err(
+            "Could not set display transform",
+            "No axis transform was given",
+        ));
+    }
+    let api = binder::BindingApi::new(&state.inner().lock().unwrap());
+    Json(match api.set_display_transform(&spectrum, &transform) {
+        Ok(()) => GenericResponse::ok(""),
+        Err(s) => GenericResponse::err("Could not set display transform", &s),
+    })
+}
+/// Remove the display transform of a spectrum.  It is not an error
+/// if the spectrum has none.
+///
+#[get("/untransform?<spectrum>")]
+pub fn clear_transform(
+    spectrum: String,
+    state: &State<SharedBinderChannel>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    let api = binder::BindingApi::new(&state.inner().lock().unwrap());
+    Json(match api.clear_display_transform(&spectrum) {
+        Ok(()) => GenericResponse::ok(""),
+        Err(s) => GenericResponse::err("Could not remove display transform", &s),
+    })
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct AxisTransform {
+    scale: f64,
+    offset: f64,
+    unit: String,
+}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct Transform {
+    spectrum: String,
+    xaxis: Option<AxisTransform>,
+    yaxis: Option<AxisTransform>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct TransformsResponse {
+    status: String,
+    detail: Vec<Transform>,
+}
+
+fn make_rest_axis_transform(t: Option<binder::AxisTransform>) -> Option<AxisTransform> {
+    t.map(|t| AxisTransform {
+        scale: t.scale,
+        offset: t.offset,
+        unit: t.unit,
+    })
+}
+
+/// List the display transforms of spectra whose names match the
+/// optional glob _pattern_ (defaults to _*_).
+///
+#[get("/transforms?<pattern>")]
+pub fn list_transforms(
+    pattern: OptionalString,
+    state: &State<SharedBinderChannel>,
+) -> Json<TransformsResponse> {
+    let api = binder::BindingApi::new(&state.inner().lock().unwrap());
+    let pattern = pattern.unwrap_or_else(|| String::from("*"));
+    Json(match api.list_display_transforms(&pattern) {
+        Ok(l) => TransformsResponse {
+            status: String::from("OK"),
+            detail: l
+                .into_iter()
+                .map(|(spectrum, t)| Transform {
+                    spectrum,
+                    xaxis: make_rest_axis_transform(t.xaxis),
+                    yaxis: make_rest_axis_transform(t.yaxis),
+                })
+                .collect(),
+        },
+        Err(s) => TransformsResponse {
+            status: format!("Could not list display transforms: {}", s),
+            detail: vec![],
+        },
+    })
+}
+
 #[cfg(test)]
 mod sbind_tests {
     use super::*;
@@ -345,7 +580,10 @@ mod sbind_tests {
                 sbind_list,
                 sbind_bindings,
                 set_update,
-                get_update
+                get_update,
+                set_transform,
+                clear_transform,
+                list_transforms
             ],
         );
 
@@ -480,6 +718,54 @@ mod sbind_tests {
         teardown(c, &papi, &bapi);
     }
     #[test]
+    fn sbindall_2() {
+        // Bind all with byte channels - only the 2d is narrowed:
+
+        let rocket = setup();
+        let (c, papi, bapi) = getstate(&rocket);
+
+        let client = Client::tracked(rocket).expect("Making client");
+        let reply = client
+            .get("/all?chantype=byte")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        let reply = client
+            .get("/list")
+            .dispatch()
+            .into_json::<BindingsResponse>()
+            .expect("Parsing JSON");
+        let mut bindings = reply.detail.clone();
+        bindings.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(2, bindings.len());
+        assert_eq!("long", bindings[0].chantype);
+        assert_eq!("byte", bindings[1].chantype);
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn sbindall_3() {
+        // Bad chantype:
+
+        let rocket = setup();
+        let (c, papi, bapi) = getstate(&rocket);
+
+        let client = Client::tracked(rocket).expect("Making client");
+        for uri in ["/all?chantype=short", "/sbind?spectrum=twod&chantype=short"] {
+            let reply = client
+                .get(uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!("Invalid chantype", reply.status);
+        }
+        assert!(bapi.list_bindings("*").unwrap().is_empty());
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
     fn sbindlist_1() {
         // Bind a single spectrum that's not bound.
         // Should end up with a single bound spectrum.
@@ -652,6 +938,9 @@ mod sbind_tests {
 
         assert_eq!("oned", reply.detail[0].name);
         assert_eq!(0, reply.detail[0].spectrumid);
+        assert!(reply.detail[0].overflows);
+        assert_eq!("long", reply.detail[0].chantype);
+        assert_eq!(0, reply.detail[0].saturated);
 
         teardown(c, &papi, &bapi);
     }
@@ -707,6 +996,113 @@ mod sbind_tests {
         assert_eq!("twod", reply.detail[0].name);
         assert_eq!(1, reply.detail[0].spectrumid);
 
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn list_5() {
+        // The overflows flag reflects how the spectrum was bound:
+
+        let rocket = setup();
+        let (c, papi, bapi) = getstate(&rocket);
+
+        let client = Client::tracked(rocket).expect("Making client");
+        let req = client.get("/sbind?spectrum=oned&overflows=false");
+        let reply = req
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        bapi.bind("twod").expect("binding twod with api");
+
+        let req = client.get("/list");
+        let reply = req
+            .dispatch()
+            .into_json::<BindingsResponse>()
+            .expect("Parsing JSON");
+
+        assert_eq!("OK", reply.status);
+        assert_eq!(2, reply.detail.len());
+
+        let mut bind_list = reply.detail.clone();
+        bind_list.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!("oned", bind_list[0].name);
+        assert!(!bind_list[0].overflows);
+        assert_eq!("twod", bind_list[1].name);
+        assert!(bind_list[1].overflows);
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn transform_1() {
+        // Set, list and remove a transform:
+
+        let rocket = setup();
+        let (c, papi, bapi) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let reply = client
+            .get("/transform?spectrum=twod&xscale=0.5&xunit=keV&yoffset=10")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        let reply = client
+            .get("/transforms")
+            .dispatch()
+            .into_json::<TransformsResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(
+            vec![Transform {
+                spectrum: String::from("twod"),
+                xaxis: Some(AxisTransform {
+                    scale: 0.5,
+                    offset: 0.0,
+                    unit: String::from("keV")
+                }),
+                yaxis: Some(AxisTransform {
+                    scale: 1.0,
+                    offset: 10.0,
+                    unit: String::new()
+                })
+            }],
+            reply.detail
+        );
+
+        let reply = client
+            .get("/untransform?spectrum=twod")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert!(bapi.list_display_transforms("*").unwrap().is_empty());
+
+        teardown(c, &papi, &bapi);
+    }
+    #[test]
+    fn transform_2() {
+        // Errors: no transform, no such spectrum, bad scale, y transform on 1d.
+
+        let rocket = setup();
+        let (c, papi, bapi) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Making client");
+        for uri in [
+            "/transform?spectrum=oned",
+            "/transform?spectrum=nosuch&xscale=2",
+            "/transform?spectrum=oned&xscale=0",
+            "/transform?spectrum=oned&yunit=keV",
+        ] {
+            let reply = client
+                .get(uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!("Could not set display transform", reply.status, "{}", uri);
+        }
+        assert!(bapi.list_display_transforms("*").unwrap().is_empty());
+
         teardown(c, &papi, &bapi);
     }
 }
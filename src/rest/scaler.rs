@@ -0,0 +1,425 @@
+//! This module provides the /spectcl/scaler domain.  The processing
+//! thread accumulates the PERIODIC_SCALERS items of the data source
+//! for each source id (see processing::scalers).  This domain reports
+//! the accumulated totals and rates and controls the histogramming of
+//! scalers against time.  It is Rustogramer only; SpecTcl displays
+//! scalers with a separate Tcl program.
+//!
+//! Subdomains are:
+//!
+//! *  list - summarize the scalers of each source id.
+//! *  totals - the channel totals and rates of each source id.
+//! *  clear - forget the accumulated scalers.
+//! *  spectra - make scaler-vs-time spectra.
+//! *  spectra/cancel - stop making scaler-vs-time spectra.
+//! *  spectra/status - how scaler-vs-time spectra are made.
+//!
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+
+use super::*;
+use crate::processing::scalers;
+
+/// Summary of the scalers of a source id.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ScalerSummary {
+    pub source: u32,
+    pub channels: usize,
+    pub items: u64,
+    pub elapsed: f64,
+    pub interval: f64,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ScalerListResponse {
+    pub status: String,
+    pub detail: Vec<ScalerSummary>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ScalerTotalsResponse {
+    pub status: String,
+    pub detail: Vec<scalers::ScalerSource>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ScalerSpectraResponse {
+    pub status: String,
+    pub detail: Option<scalers::ScalerSpectra>,
+}
+
+/// Summarize the scalers of each source id.
+///
+/// #### Returns
+/// ScalerListResponse - sorted by source id.
+#[get("/list")]
+pub fn list_scalers(state: &State<SharedProcessingApi>) -> Json<ScalerListResponse> {
+    let scalers = state.inner().lock().unwrap().scalers();
+    let detail = scalers
+        .lock()
+        .unwrap()
+        .sources()
+        .iter()
+        .map(|s| ScalerSummary {
+            source: s.source,
+            channels: s.channels.len(),
+            items: s.items,
+            elapsed: s.elapsed,
+            interval: s.interval,
+        })
+        .collect();
+    Json(ScalerListResponse {
+        status: String::from("OK"),
+        detail,
+    })
+}
+/// The channel totals and rates.
+///
+/// #### Query Parameters
+/// * source - If supplied, only the scalers of this source id are
+///   returned.
+///
+/// #### Returns
+/// ScalerTotalsResponse - sorted by source id.
+#[get("/totals?<source>")]
+pub fn scaler_totals(
+    source: Option<u32>,
+    state: &State<SharedProcessingApi>,
+) -> Json<ScalerTotalsResponse> {
+    let scalers = state.inner().lock().unwrap().scalers();
+    let detail = scalers
+        .lock()
+        .unwrap()
+        .sources()
+        .into_iter()
+        .filter(|s| source.is_none_or(|id| id == s.source))
+        .collect();
+    Json(ScalerTotalsResponse {
+        status: String::from("OK"),
+        detail,
+    })
+}
+/// Forget the accumulated scalers.  This is also done at the start
+/// of each run.
+#[get("/clear")]
+pub fn clear_scalers(
+    state: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    state
+        .inner()
+        .lock()
+        .unwrap()
+        .scalers()
+        .lock()
+        .unwrap()
+        .clear();
+    Json(GenericResponse::ok(""))
+}
+/// Make scaler-vs-time spectra.
+///
+/// #### Query Parameters
+/// * duration - Seconds into the run covered by the spectra.
+/// * bins - Number of bins on the time axis.  Defaults to one per
+///   second.
+///
+/// Spectra that already exist are not changed.
+///
+/// #### Returns
+/// GenericResponse
+#[get("/spectra?<duration>&<bins>")]
+pub fn set_scaler_spectra(
+    duration: f64,
+    bins: Option<u32>,
+    state: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    let bins = bins.unwrap_or(duration.ceil() as u32);
+    match scalers::ScalerSpectra::new(duration, bins) {
+        Ok(spectra) => {
+            let scalers = state.inner().lock().unwrap().scalers();
+            scalers.lock().unwrap().set_spectra(Some(spectra));
+            Json(GenericResponse::ok(""))
+        }
+        Err(s) => Json(GenericResponse::err("Unable to make scaler spectra", &s)),
+    }
+}
+/// Stop making scaler-vs-time spectra.  The spectra are not deleted.
+#[get("/spectra/cancel")]
+pub fn cancel_scaler_spectra(
+    state: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    let scalers = state.inner().lock().unwrap().scalers();
+    scalers.lock().unwrap().set_spectra(None);
+    Json(GenericResponse::ok(""))
+}
+/// How scaler-vs-time spectra are made.
+///
+/// #### Returns
+/// ScalerSpectraResponse - the detail is null if they are not made.
+#[get("/spectra/status")]
+pub fn scaler_spectra_status(state: &State<SharedProcessingApi>) -> Json<ScalerSpectraResponse> {
+    let scalers = state.inner().lock().unwrap().scalers();
+    let detail = scalers.lock().unwrap().spectra();
+    Json(ScalerSpectraResponse {
+        status: String::from("OK"),
+        detail,
+    })
+}
+
+#[cfg(test)]
+mod scaler_tests {
+    use super::*;
+    use crate::messaging;
+    use crate::processing;
+    use crate::ring_items::analysis_ring_items::ParameterFileWriter;
+    use crate::ring_items::scaler_item::ScalerItem;
+    use crate::ring_items::state_change::{StateChange, StateChangeType};
+    use crate::ring_items::{BodyHeader, RingVersion, ToRaw};
+    use crate::sharedmem::binder;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+
+    use crate::test::rest_common;
+    use std::sync::mpsc;
+    use std::time;
+
+    fn setup() -> Rocket<Build> {
+        rest_common::setup().mount(
+            "/",
+            routes![
+                list_scalers,
+                scaler_totals,
+                clear_scalers,
+                set_scaler_spectra,
+                cancel_scaler_spectra,
+                scaler_spectra_status
+            ],
+        )
+    }
+    fn teardown(
+        c: mpsc::Sender<messaging::Request>,
+        p: &processing::ProcessingApi,
+        b: &binder::BindingApi,
+    ) {
+        rest_common::teardown(c, p, b);
+    }
+    fn get_state(
+        r: &Rocket<Build>,
+    ) -> (
+        mpsc::Sender<messaging::Request>,
+        processing::ProcessingApi,
+        binder::BindingApi,
+    ) {
+        rest_common::get_state(r)
+    }
+    fn scaler(source: u32, start: u32, end: u32, values: &[u32]) -> ScalerItem {
+        ScalerItem::new(
+            Some(BodyHeader {
+                timestamp: 0,
+                source_id: source,
+                barrier_type: 0,
+            }),
+            start,
+            end,
+            time::SystemTime::now(),
+            1,
+            true,
+            None,
+            &mut values.to_vec(),
+        )
+    }
+    // Analyze a run of scaler items from sources 1 and 2.  Source 1
+    // has two channels and source 2 one:
+
+    fn analyze(dir: &tempfile::TempDir, papi: &processing::ProcessingApi) {
+        let path = dir.path().join("run.evt");
+        let mut writer = ParameterFileWriter::new(
+            std::fs::File::create(&path).expect("Creating file"),
+            RingVersion::V11,
+        )
+        .expect("Making writer");
+        let begin = StateChange::new(StateChangeType::Begin, None, 12, 0, 1, "A run", None);
+        writer.write_item(&begin.to_raw()).expect("Writing item");
+        for i in 0..5 {
+            writer
+                .write_item(&scaler(1, i * 10, (i + 1) * 10, &[100, 10 * i]).to_raw())
+                .expect("Writing item");
+            writer
+                .write_item(&scaler(2, i * 10, (i + 1) * 10, &[5]).to_raw())
+                .expect("Writing item");
+        }
+        writer.flush().expect("Flushing");
+
+        papi.attach(path.to_str().unwrap()).expect("attaching file");
+        papi.start_analysis().expect("Starting analysis");
+        let start = std::time::Instant::now();
+        while papi.processing_state().expect("Getting state") != "Inactive" {
+            assert!(start.elapsed() < std::time::Duration::from_secs(10));
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn list_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        let client = Client::tracked(rocket).expect("Making client");
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        analyze(&dir, &papi);
+
+        let reply = client
+            .get("/list")
+            .dispatch()
+            .into_json::<ScalerListResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(2, reply.detail.len());
+        let s = &reply.detail[0];
+        assert_eq!((1, 2, 5), (s.source, s.channels, s.items));
+        assert_eq!((50.0, 10.0), (s.elapsed, s.interval));
+        assert_eq!((2, 1), (reply.detail[1].source, reply.detail[1].channels));
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn totals_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        let client = Client::tracked(rocket).expect("Making client");
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        analyze(&dir, &papi);
+
+        let reply = client
+            .get("/totals?source=1")
+            .dispatch()
+            .into_json::<ScalerTotalsResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail.len());
+        let channels = &reply.detail[0].channels;
+        assert_eq!(
+            vec![(0, 500, 100, 10.0), (1, 100, 40, 4.0)],
+            channels
+                .iter()
+                .map(|c| (c.channel, c.total, c.increment, c.rate))
+                .collect::<Vec<(usize, u64, u64, f64)>>()
+        );
+
+        let reply = client
+            .get("/totals")
+            .dispatch()
+            .into_json::<ScalerTotalsResponse>()
+            .expect("Bad JSON");
+        assert_eq!(2, reply.detail.len());
+        assert_eq!(25, reply.detail[1].channels[0].total);
+
+        // Clearing forgets them:
+
+        let reply = client
+            .get("/clear")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status);
+        let reply = client
+            .get("/totals")
+            .dispatch()
+            .into_json::<ScalerTotalsResponse>()
+            .expect("Bad JSON");
+        assert!(reply.detail.is_empty());
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn spectra_1() {
+        // Scaler-vs-time spectra are made and filled:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        let client = Client::tracked(rocket).expect("Making client");
+
+        let reply = client
+            .get("/spectra?duration=100&bins=10")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status);
+        let reply = client
+            .get("/spectra/status")
+            .dispatch()
+            .into_json::<ScalerSpectraResponse>()
+            .expect("Bad JSON");
+        let spectra = reply.detail.expect("Spectra are made");
+        assert_eq!((100.0, 10), (spectra.duration, spectra.bins));
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        analyze(&dir, &papi);
+
+        let spec_api = messaging::spectrum_messages::SpectrumMessageClient::new(&chan);
+        let mut names: Vec<String> = spec_api
+            .list_spectra("scaler.*")
+            .expect("Listing spectra")
+            .iter()
+            .map(|s| s.name.clone())
+            .collect();
+        names.sort();
+        assert_eq!(vec!["scaler.1.000", "scaler.1.001", "scaler.2.000"], names);
+
+        // Channel 1 of source 1 had no counts in the first interval:
+
+        let contents = spec_api
+            .get_contents("scaler.1.001", 0.0, 100.0, 0.0, 0.0)
+            .expect("Getting contents");
+        let channels: Vec<(f64, f64)> = contents.iter().map(|c| (c.x, c.value)).collect();
+        assert_eq!(
+            vec![(10.0, 10.0), (20.0, 20.0), (30.0, 30.0), (40.0, 40.0)],
+            channels
+        );
+
+        let reply = client
+            .get("/spectra/cancel")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status);
+        let reply = client
+            .get("/spectra/status")
+            .dispatch()
+            .into_json::<ScalerSpectraResponse>()
+            .expect("Bad JSON");
+        assert!(reply.detail.is_none());
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn spectra_2() {
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        let client = Client::tracked(rocket).expect("Making client");
+
+        let reply = client
+            .get("/spectra?duration=0")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("Unable to make scaler spectra", reply.status);
+        let reply = client
+            .get("/spectra?duration=60")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(
+            Some(60),
+            papi.scalers().lock().unwrap().spectra().map(|s| s.bins)
+        );
+
+        teardown(chan, &papi, &bapi);
+    }
+}
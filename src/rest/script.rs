@@ -0,0 +1,555 @@
+//! This module provides the /spectcl/script REST interface.  SpecTcl
+//! executes the command in its Tcl interpreter.  Rustogramer has no Tcl
+//! interpreter.  Instead the command is a script in the restricted
+//! command language described in src/script/mod.rs whose commands
+//! perform the definition work automation scripts typically do.  This
+//! allows a client to make many definitions in one request.
+//!
+//! The commands are (the syntax follows the SpecTcl commands of the
+//! same name):
+//!
+//! *   treeparameter -create name low high bins units - create a parameter.
+//! *   spectrum ?-new? name type {parameters} {axes} - create a spectrum.
+//! *   spectrum -delete name ?name...? - delete spectra.
+//! *   gate name type {description} - create or replace a condition.
+//! *   gate -delete name ?name...? - delete conditions.
+//! *   apply gate spectrum ?spectrum...? - gate spectra.
+//! *   ungate spectrum ?spectrum...? - ungate spectra.
+//! *   clear -all | clear name ?name...? - clear spectra.
+//! *   pseudo name {parameters} {computation} - create a pseudo parameter.
+//!
+//! Each command is carried out by the same code as the REST request
+//! that does the same thing.  The commands are executed in order until
+//! one fails;  commands before a failing command are not undone.
+//!
+use rocket::serde::json::Json;
+use rocket::State;
+
+use super::*;
+use crate::messaging::spectrum_messages::SpectrumMessageClient;
+use crate::script;
+use crate::trace;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+// Convert the GenericResponse of a request into a command result:
+
+fn check(reply: GenericResponse) -> Result<String, String> {
+    if reply.status == "OK" {
+        Ok(reply.detail)
+    } else {
+        Err(format!("{}: {}", reply.status, reply.detail))
+    }
+}
+
+fn number<T: FromStr>(word: &str, what: &str) -> Result<T, String> {
+    word.parse::<T>()
+        .map_err(|_| format!("{} must be a number but was '{}'", what, word))
+}
+
+// Split a list and require a specific number of elements:
+
+fn fixed_list(text: &str, n: usize, what: &str) -> Result<Vec<String>, String> {
+    let list = script::split_list(text)?;
+    if list.len() == n {
+        Ok(list)
+    } else {
+        Err(format!("{} must have {} elements: '{}'", what, n, text))
+    }
+}
+
+// A list of {x y} points into the x and y coordinates:
+
+fn points(text: &str) -> Result<(Vec<f64>, Vec<f64>), String> {
+    let mut xcoords = Vec::<f64>::new();
+    let mut ycoords = Vec::<f64>::new();
+    for point in script::split_list(text)? {
+        let xy = fixed_list(&point, 2, "A point")?;
+        xcoords.push(number(&xy[0], "A coordinate")?);
+        ycoords.push(number(&xy[1], "A coordinate")?);
+    }
+    Ok((xcoords, ycoords))
+}
+
+// The query parameters of /spectcl/gate/edit that describe a condition:
+
+#[derive(Default)]
+struct GateDefinition {
+    gate: OptionalStringVec,
+    xparameter: OptionalString,
+    yparameter: OptionalString,
+    parameter: OptionalStringVec,
+    xcoord: OptionalF64Vec,
+    ycoord: OptionalF64Vec,
+    low: Option<f64>,
+    high: Option<f64>,
+    value: Option<u32>,
+}
+
+// Translate a SpecTcl gate description into the query parameters of
+// /spectcl/gate/edit:
+
+fn gate_definition(gate_type: &str, description: &str) -> Result<GateDefinition, String> {
+    let mut def = GateDefinition::default();
+    match gate_type {
+        "T" | "F" => {
+            if !script::split_list(description)?.is_empty() {
+                return Err(String::from("True and False gates have no description"));
+            }
+        }
+        "-" | "*" | "+" => def.gate = Some(script::split_list(description)?),
+        "s" => {
+            let d = fixed_list(description, 2, "A slice description")?;
+            let limits = fixed_list(&d[1], 2, "Slice limits")?;
+            def.parameter = Some(vec![d[0].clone()]);
+            def.low = Some(number(&limits[0], "low")?);
+            def.high = Some(number(&limits[1], "high")?);
+        }
+        "c" | "b" => {
+            let d = fixed_list(description, 2, "A contour or band description")?;
+            let params = fixed_list(&d[0], 2, "The parameter list")?;
+            let (x, y) = points(&d[1])?;
+            def.xparameter = Some(params[0].clone());
+            def.yparameter = Some(params[1].clone());
+            def.xcoord = Some(x);
+            def.ycoord = Some(y);
+        }
+        "gs" => {
+            let d = fixed_list(description, 2, "A gamma slice description")?;
+            let limits = fixed_list(&d[0], 2, "Slice limits")?;
+            def.low = Some(number(&limits[0], "low")?);
+            def.high = Some(number(&limits[1], "high")?);
+            def.parameter = Some(script::split_list(&d[1])?);
+        }
+        "gc" | "gb" => {
+            let d = fixed_list(description, 2, "A gamma contour or band description")?;
+            let (x, y) = points(&d[0])?;
+            def.xcoord = Some(x);
+            def.ycoord = Some(y);
+            def.parameter = Some(script::split_list(&d[1])?);
+        }
+        "em" | "am" | "nm" => {
+            let d = fixed_list(description, 2, "A mask description")?;
+            def.parameter = Some(vec![d[0].clone()]);
+            def.value = Some(number(&d[1], "The mask")?);
+        }
+        _ => {
+            return Err(format!(
+                "Gate type '{}' is not supported in scripts",
+                gate_type
+            ))
+        }
+    }
+    Ok(def)
+}
+
+// Executes the commands of a script against the Rocket state:
+
+struct Interpreter<'a> {
+    hg: &'a State<SharedHistogramChannel>,
+    binder: &'a State<SharedBinderChannel>,
+    tracedb: &'a State<trace::SharedTraceStore>,
+    policy: &'a State<names::NamePolicy>,
+    who: String,
+}
+
+impl Interpreter<'_> {
+    fn execute(&self, words: &[String]) -> Result<String, String> {
+        let args = &words[1..];
+        match words[0].as_str() {
+            "treeparameter" => self.treeparameter(args),
+            "spectrum" => self.spectrum(args),
+            "gate" => self.gate(args),
+            "apply" => self.apply(args),
+            "ungate" => self.ungate(args),
+            "clear" => self.clear(args),
+            "pseudo" => self.pseudo(args),
+            command => Err(format!("invalid command name \"{}\"", command)),
+        }
+    }
+    fn treeparameter(&self, args: &[String]) -> Result<String, String> {
+        if args.len() != 6 || args[0] != "-create" {
+            return Err(String::from(
+                "Usage: treeparameter -create name low high bins units",
+            ));
+        }
+        check(
+            rest_parameter::create_parameter(
+                args[1].clone(),
+                Some(number(&args[2], "low")?),
+                Some(number(&args[3], "high")?),
+                Some(number(&args[4], "bins")?),
+                Some(args[5].clone()),
+                None,
+                self.hg,
+                readonly::Writable,
+                busy::HistogramerReady,
+            )
+            .into_inner(),
+        )?;
+        Ok(args[1].clone())
+    }
+    fn spectrum(&self, args: &[String]) -> Result<String, String> {
+        if args.first().map(|s| s.as_str()) == Some("-delete") {
+            for name in &args[1..] {
+                check(
+                    spectrum::delete_spectrum(
+                        name.clone(),
+                        self.hg,
+                        readonly::Writable,
+                        busy::HistogramerReady,
+                    )
+                    .into_inner(),
+                )?;
+            }
+            return Ok(String::new());
+        }
+        let args = if args.first().map(|s| s.as_str()) == Some("-new") {
+            &args[1..]
+        } else {
+            args
+        };
+        if args.len() != 4 {
+            return Err(String::from(
+                "Usage: spectrum ?-new? name type {parameters} {axes}",
+            ));
+        }
+        let api = SpectrumMessageClient::new(&self.hg.inner().lock().unwrap());
+        self.policy
+            .check_spectrum_name(&args[0], &api)
+            .map_err(|s| format!("Invalid spectrum name: {}", s))?;
+        check(spectrum::make_spectrum_of_type(
+            &args[0], &args[1], &args[2], &args[3], self.hg,
+        ))?;
+        Ok(args[0].clone())
+    }
+    fn gate(&self, args: &[String]) -> Result<String, String> {
+        if args.first().map(|s| s.as_str()) == Some("-delete") {
+            for name in &args[1..] {
+                check(
+                    gates::delete_gate(
+                        name.clone(),
+                        self.hg,
+                        readonly::Writable,
+                        busy::HistogramerReady,
+                    )
+                    .into_inner(),
+                )?;
+            }
+            return Ok(String::new());
+        }
+        if args.len() != 3 {
+            return Err(String::from("Usage: gate name type {description}"));
+        }
+        let def = gate_definition(&args[1], &args[2])?;
+        check(
+            gates::edit_gate(
+                args[0].clone(),
+                args[1].clone(),
+                def.gate,
+                def.xparameter,
+                def.yparameter,
+                def.parameter,
+                def.xcoord,
+                def.ycoord,
+                None,
+                def.low,
+                def.high,
+                def.value,
+                self.hg,
+                self.policy,
+                readonly::Writable,
+                busy::HistogramerReady,
+            )
+            .into_inner(),
+        )?;
+        Ok(args[0].clone())
+    }
+    fn apply(&self, args: &[String]) -> Result<String, String> {
+        if args.len() < 2 {
+            return Err(String::from("Usage: apply gate spectrum ?spectrum...?"));
+        }
+        let api = SpectrumMessageClient::new(&self.hg.inner().lock().unwrap());
+        let gate = &args[0];
+        for name in &args[1..] {
+            api.gate_spectrum(name, gate)
+                .map_err(|s| format!("Failed to apply {} to {}: {}", gate, name, s))?;
+            self.tracedb
+                .inner()
+                .log_application(name, Some(gate), &self.who);
+        }
+        Ok(String::new())
+    }
+    fn ungate(&self, args: &[String]) -> Result<String, String> {
+        if args.is_empty() {
+            return Err(String::from("Usage: ungate spectrum ?spectrum...?"));
+        }
+        let api = SpectrumMessageClient::new(&self.hg.inner().lock().unwrap());
+        for name in args {
+            api.ungate_spectrum(name)
+                .map_err(|s| format!("Failed to ungate {}: {}", name, s))?;
+            self.tracedb.inner().log_application(name, None, &self.who);
+        }
+        Ok(String::new())
+    }
+    fn clear(&self, args: &[String]) -> Result<String, String> {
+        let patterns = if args.len() == 1 && args[0] == "-all" {
+            vec![String::from("*")]
+        } else if !args.is_empty() && !args.iter().any(|a| a.starts_with('-')) {
+            args.to_vec()
+        } else {
+            return Err(String::from("Usage: clear -all | clear name ?name...?"));
+        };
+        for pattern in patterns {
+            check(
+                spectrum::clear_spectra(
+                    Some(pattern),
+                    None,
+                    self.hg,
+                    self.binder,
+                    readonly::Writable,
+                    busy::HistogramerReady,
+                )
+                .into_inner(),
+            )?;
+        }
+        Ok(String::new())
+    }
+    fn pseudo(&self, args: &[String]) -> Result<String, String> {
+        if args.len() != 3 {
+            return Err(String::from(
+                "Usage: pseudo name {parameters} {computation}",
+            ));
+        }
+        let parameters = script::split_list(&args[1])?;
+        let api = ParameterMessageClient::new(&self.hg.inner().lock().unwrap());
+        api.create_pseudo(&args[0], &parameters, &args[2])
+            .map_err(|s| format!("Failed to create pseudo {}: {}", args[0], s))?;
+        Ok(args[0].clone())
+    }
+}
+
+/// Execute a script.
+///
+/// ### Query parameters
+/// * command - the script.  See the module documentation for the
+///   commands and src/script/mod.rs for the syntax.
+///
+/// The response is a GenericResponse.  As with SpecTcl, on success the
+/// status is _OK_ and the detail the result of the last command:  the
+/// name of the object it defined or an empty string.  On failure, the
+/// status is _ERROR_ and the detail the line the failing command is on
+/// and the error message.  Nothing is executed if the script cannot be
+/// parsed.  Gate applications and removals are recorded in the
+/// application history (see apply.rs).
+#[get("/?<command>")]
+#[allow(clippy::too_many_arguments)]
+pub fn script_execute(
+    command: String,
+    hg: &State<SharedHistogramChannel>,
+    binder: &State<SharedBinderChannel>,
+    tracedb: &State<trace::SharedTraceStore>,
+    policy: &State<names::NamePolicy>,
+    client: Option<IpAddr>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    let commands = match script::parse(&command) {
+        Ok(c) => c,
+        Err(s) => return Json(GenericResponse::err("ERROR", &s)),
+    };
+    let interpreter = Interpreter {
+        hg,
+        binder,
+        tracedb,
+        policy,
+        who: apply::requestor(client),
+    };
+    let mut result = String::new();
+    for command in commands {
+        match interpreter.execute(&command.words) {
+            Ok(r) => result = r,
+            Err(s) => {
+                return Json(GenericResponse::err(
+                    "ERROR",
+                    &format!("line {}: {}", command.line, s),
+                ))
+            }
+        }
+    }
+    Json(GenericResponse::ok(&result))
+}
+
+#[cfg(test)]
+mod script_tests {
+    use super::*;
+    use crate::messaging;
+    use crate::messaging::condition_messages::ConditionMessageClient;
+    use crate::test::rest_common;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+
+    use std::sync::mpsc;
+
+    fn setup() -> Rocket<Build> {
+        rest_common::setup().mount("/", routes![script_execute])
+    }
+    fn getstate(
+        r: &Rocket<Build>,
+    ) -> (
+        mpsc::Sender<messaging::Request>,
+        processing::ProcessingApi,
+        binder::BindingApi,
+    ) {
+        rest_common::get_state(r)
+    }
+    fn teardown(
+        c: mpsc::Sender<messaging::Request>,
+        p: &processing::ProcessingApi,
+        b: &binder::BindingApi,
+    ) {
+        rest_common::teardown(c, p, b);
+    }
+    fn run(client: &Client, script: &str) -> GenericResponse {
+        client
+            .get(format!(
+                "/?command={}",
+                rocket::http::RawStr::new(script).percent_encode()
+            ))
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON")
+    }
+
+    #[test]
+    fn execute_1() {
+        // Batched definitions:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+        let tracedb = rocket
+            .state::<trace::SharedTraceStore>()
+            .expect("Trace store")
+            .clone();
+
+        let client = Client::untracked(rocket).expect("Making rocket client");
+        let reply = run(
+            &client,
+            "# Define the raw parameters and their spectra:
+            treeparameter -create x 0 1023 1024 chans
+            treeparameter -create y 0 1023 1024 chans
+            spectrum x 1 x {{0 1023 1024}}
+            spectrum -new xy 2 {x y} {{0 1023 1024} {0 1023 1024}}
+            gate peak s {x {100 200}}; gate both * {peak peak}
+            gate blob c {{x y} {{0 0} {10 0} {10 10}}}
+            gate pk2 gs {{10 20} {x y}}
+            pseudo sum {x y} {x + y}
+            apply peak x xy
+            ungate xy",
+        );
+        assert_eq!("OK", reply.status, "{}", reply.detail);
+        assert_eq!("", reply.detail);
+
+        let papi_client = ParameterMessageClient::new(&chan);
+        let params = papi_client
+            .list_parameters("*")
+            .expect("Listing parameters");
+        assert_eq!(3, params.len());
+        let sapi = SpectrumMessageClient::new(&chan);
+        let spectra = sapi.list_spectra("*").expect("Listing spectra");
+        assert_eq!(2, spectra.len());
+        let x = spectra.iter().find(|s| s.name == "x").unwrap();
+        assert_eq!(Some(String::from("peak")), x.gate);
+        let xy = spectra.iter().find(|s| s.name == "xy").unwrap();
+        assert!(xy.gate.is_none());
+        let capi = ConditionMessageClient::new(&chan);
+        let conditions = capi.list_conditions("*");
+        if let messaging::condition_messages::ConditionReply::Listing(l) = conditions {
+            assert_eq!(4, l.len());
+        } else {
+            panic!("Listing conditions failed");
+        }
+        let history = tracedb.get_application_history("*").expect("History");
+        assert_eq!(3, history.iter().map(|(_, h)| h.len()).sum::<usize>());
+
+        // The result is that of the last command:
+
+        let reply = run(&client, "gate t T {}");
+        assert_eq!("OK", reply.status);
+        assert_eq!("t", reply.detail);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn execute_2() {
+        // Deletion and clearing:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Making rocket client");
+        let reply = run(
+            &client,
+            "treeparameter -create x 0 1023 1024 chans
+            spectrum a 1 x {{0 1023 1024}}; spectrum b 1 x {{0 1023 1024}}
+            gate t T {}; gate f F {}
+            clear -all; clear a b
+            spectrum -delete a b; gate -delete t",
+        );
+        assert_eq!("OK", reply.status, "{}", reply.detail);
+
+        let sapi = SpectrumMessageClient::new(&chan);
+        assert!(sapi.list_spectra("*").expect("Listing spectra").is_empty());
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn execute_3() {
+        // Errors stop execution and report the line:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Making rocket client");
+        for (script, line) in [
+            ("\nputs hello", "line 2:"),
+            ("set a $b", "line 1:"),
+            ("treeparameter -create x 0 1023", "line 1:"),
+            ("treeparameter -create x low 1023 1024 chans", "line 1:"),
+            ("spectrum x 1 nosuch {{0 1023 1024}}", "line 1:"),
+            ("gate g s {nosuch {1 2}}", "line 1:"),
+            ("gate g c {{a b} {{0 0} {1}}}", "line 1:"),
+            ("gate g xyzzy {}", "line 1:"),
+            ("apply nosuch nosuch", "line 1:"),
+            ("clear", "line 1:"),
+            (
+                "gate t T {}\ngate t2 F {}; ungate nosuch\ngate t3 T {}",
+                "line 2:",
+            ),
+        ] {
+            let reply = run(&client, script);
+            assert_eq!("ERROR", reply.status, "{}", script);
+            assert!(
+                reply.detail.starts_with(line),
+                "{}: {}",
+                script,
+                reply.detail
+            );
+        }
+        // Commands before the failure were done, those after were not:
+
+        let capi = ConditionMessageClient::new(&chan);
+        if let messaging::condition_messages::ConditionReply::Listing(l) =
+            capi.list_conditions("t*")
+        {
+            assert_eq!(2, l.len());
+        } else {
+            panic!("Listing conditions failed");
+        }
+
+        teardown(chan, &papi, &bapi);
+    }
+}
@@ -3,6 +3,9 @@
 //! These include domains:
 //!
 //! * /spectcl/shmem - Gets the shared memory information.
+//! This includes allocation statistics and a short utilization
+//! history (/spectcl/shmem/stats) so that exhaustion can be
+//! anticipated before binds fail.
 use super::*;
 use crate::sharedmem::binder::BindingApi;
 use crate::sharedmem::XamineSharedMemory;
@@ -171,10 +174,115 @@ pub fn get_variables(
 
     Json(result)
 }
+//----------------------------------------------------------
+// stats
+
+/// One sample of the utilization history.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct UsageSample {
+    time: u64,
+    used_bytes: usize,
+    free_bytes: usize,
+    largest_free_bytes: usize,
+    bound_slots: usize,
+}
+/// The shared memory statistics.  Byte counts are for the spectrum
+/// storage part of the region; total_size includes the header.
+/// utilization is the percentage of the spectrum storage in use.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ShmStatistics {
+    total_size: usize,
+    spectrum_bytes: usize,
+    free_bytes: usize,
+    largest_free_bytes: usize,
+    used_bytes: usize,
+    largest_used_bytes: usize,
+    free_extents: usize,
+    allocated_extents: usize,
+    bound_slots: usize,
+    total_slots: usize,
+    utilization: f64,
+    history: Vec<UsageSample>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ShmStatisticsResponse {
+    status: String,
+    detail: Option<ShmStatistics>,
+}
+
+/// Return the shared memory allocation statistics, slot usage and
+/// utilization history.
+///
+/// ### Parameters
+/// * state - the binder state used to construct a BindingApi.
+///
+/// ### Returns
+/// * Json encoded ShmStatisticsResponse.  On failure, the status
+/// describes the problem and the detail is null.
+///
+#[get("/stats")]
+pub fn get_statistics(state: &State<SharedBinderChannel>) -> Json<ShmStatisticsResponse> {
+    let api = BindingApi::new(&state.inner().lock().unwrap());
+    let stats = match api.get_usage() {
+        Ok(s) => s,
+        Err(reason) => {
+            return Json(ShmStatisticsResponse {
+                status: format!("Could not get shared memory statistics: {}", reason),
+                detail: None,
+            })
+        }
+    };
+    let history = match api.get_usage_history() {
+        Ok(h) => h,
+        Err(reason) => {
+            return Json(ShmStatisticsResponse {
+                status: format!("Could not get shared memory usage history: {}", reason),
+                detail: None,
+            })
+        }
+    };
+    let spectrum_bytes = stats.free_bytes + stats.used_bytes;
+    let utilization = if spectrum_bytes > 0 {
+        100.0 * stats.used_bytes as f64 / spectrum_bytes as f64
+    } else {
+        0.0
+    };
+    Json(ShmStatisticsResponse {
+        status: String::from("OK"),
+        detail: Some(ShmStatistics {
+            total_size: stats.total_size,
+            spectrum_bytes,
+            free_bytes: stats.free_bytes,
+            largest_free_bytes: stats.largest_free_bytes,
+            used_bytes: stats.used_bytes,
+            largest_used_bytes: stats.largest_used_bytes,
+            free_extents: stats.free_extents,
+            allocated_extents: stats.allocated_extents,
+            bound_slots: stats.bound_indices,
+            total_slots: stats.total_indices,
+            utilization,
+            history: history
+                .into_iter()
+                .map(|s| UsageSample {
+                    time: s.time,
+                    used_bytes: s.used_bytes,
+                    free_bytes: s.free_bytes,
+                    largest_free_bytes: s.largest_free_bytes,
+                    bound_slots: s.bound_indices,
+                })
+                .collect(),
+        }),
+    })
+}
 #[cfg(test)]
 mod shm_tests {
     use super::*;
     use crate::messaging;
+    use crate::messaging::parameter_messages::ParameterMessageClient;
+    use crate::messaging::spectrum_messages::SpectrumMessageClient;
     use crate::processing;
     use crate::sharedmem::{binder, XamineSharedMemory};
     use crate::test::rest_common;
@@ -187,7 +295,10 @@ mod shm_tests {
     use std::sync::mpsc;
 
     fn setup() -> Rocket<Build> {
-        rest_common::setup().mount("/", routes![shmem_name, shmem_size, get_variables])
+        rest_common::setup().mount(
+            "/",
+            routes![shmem_name, shmem_size, get_variables, get_statistics],
+        )
     }
     fn getstate(
         r: &Rocket<Build>,
@@ -292,6 +403,75 @@ mod shm_tests {
         assert_eq!(get_instdir(), vars.instdir);
         assert_eq!("None", vars.display_type);
 
+        teardown(chan, &papi, &binder_api);
+    }
+    #[test]
+    fn stats_1() {
+        // Nothing bound:
+
+        let rocket = setup();
+        let (chan, papi, binder_api) = getstate(&rocket);
+
+        let client = Client::tracked(rocket).expect("Making client");
+        let reply = client
+            .get("/stats")
+            .dispatch()
+            .into_json::<ShmStatisticsResponse>()
+            .expect("Parsing json");
+
+        assert_eq!("OK", reply.status);
+        let stats = reply.detail.expect("No statistics");
+        let usage = binder_api.get_usage().expect("getting usage via API");
+        assert_eq!(usage.total_size, stats.total_size);
+        assert_eq!(usage.free_bytes, stats.spectrum_bytes);
+        assert_eq!(usage.free_bytes, stats.free_bytes);
+        assert_eq!(0, stats.used_bytes);
+        assert_eq!(1, stats.free_extents);
+        assert_eq!(0, stats.allocated_extents);
+        assert_eq!(0, stats.bound_slots);
+        assert_eq!(usage.total_indices, stats.total_slots);
+        assert_eq!(0.0, stats.utilization);
+        assert_eq!(1, stats.history.len());
+        assert_eq!(0, stats.history[0].used_bytes);
+
+        teardown(chan, &papi, &binder_api);
+    }
+    #[test]
+    fn stats_2() {
+        // Bind a spectrum and it shows up in the stats and history:
+
+        let rocket = setup();
+        let (chan, papi, binder_api) = getstate(&rocket);
+
+        let param_api = ParameterMessageClient::new(&chan);
+        let spec_api = SpectrumMessageClient::new(&chan);
+        param_api.create_parameter("p").expect("Making parameter");
+        spec_api
+            .create_spectrum_1d("s", "p", 0.0, 1024.0, 1024)
+            .expect("Making spectrum");
+        binder_api.bind("s").expect("Binding spectrum");
+
+        let client = Client::tracked(rocket).expect("Making client");
+        let reply = client
+            .get("/stats")
+            .dispatch()
+            .into_json::<ShmStatisticsResponse>()
+            .expect("Parsing json");
+
+        assert_eq!("OK", reply.status);
+        let stats = reply.detail.expect("No statistics");
+        let used = 1026 * mem::size_of::<u32>();
+        assert_eq!(used, stats.used_bytes);
+        assert_eq!(1, stats.allocated_extents);
+        assert_eq!(1, stats.bound_slots);
+        assert_eq!(
+            100.0 * used as f64 / stats.spectrum_bytes as f64,
+            stats.utilization
+        );
+        assert_eq!(2, stats.history.len());
+        assert_eq!(used, stats.history[1].used_bytes);
+        assert_eq!(1, stats.history[1].bound_slots);
+
         teardown(chan, &papi, &binder_api);
     }
 }
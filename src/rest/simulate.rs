@@ -0,0 +1,327 @@
+//! This module provides the /spectcl/simulate domain.  This is a
+//! Rustogramer only, test support domain that starts and stops
+//! synthetic event generation (see src/simulation/mod.rs).  It is only
+//! functional if rustogramer was started with --enable-simulation.
+//!
+//! We depend on the main to have set the Rocket state to include a
+//! simulation::Simulator.
+//!
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+
+use super::*;
+use crate::messaging::parameter_messages::ParameterMessageClient;
+use crate::simulation;
+
+/// Distribution used when none is supplied:
+pub const DEFAULT_DISTRIBUTION: &str = "uniform:0:1024";
+
+// Turn the parameter names and distributions into the
+// id/distribution pairs the simulation needs.  There can be
+// no distributions (the default is used for all parameters),
+// one (used for all parameters) or one per parameter.
+
+fn make_parameters(
+    names: &[String],
+    distributions: &[String],
+    state: &State<SharedHistogramChannel>,
+) -> Result<Vec<(u32, simulation::Distribution)>, String> {
+    let distributions: Vec<String> = match distributions.len() {
+        0 => vec![String::from(DEFAULT_DISTRIBUTION); names.len()],
+        1 => vec![distributions[0].clone(); names.len()],
+        n if n == names.len() => distributions.to_vec(),
+        n => {
+            return Err(format!(
+                "{} distributions given for {} parameters",
+                n,
+                names.len()
+            ))
+        }
+    };
+    let api = ParameterMessageClient::new(&state.inner().lock().unwrap());
+    let mut result = Vec::new();
+    for (name, distribution) in names.iter().zip(distributions.iter()) {
+        let id = match api.list_parameters(name) {
+            Ok(l) if l.len() == 1 => l[0].get_id(),
+            _ => return Err(format!("No such parameter {}", name)),
+        };
+        result.push((id, simulation::Distribution::parse(distribution)?));
+    }
+    Ok(result)
+}
+
+/// Start a simulation.
+///
+/// #### Query Parameters:
+/// * parameter - Names of the parameters to simulate (at least one).
+/// * distribution - Distributions of the parameters.  See
+/// simulation::Distribution::parse for the format.  There can be none
+/// (DEFAULT_DISTRIBUTION is used), one, used for all parameters or one
+/// per parameter.
+/// * rate - Events per second.  0 means as fast as possible.
+/// * events - Optional number of events after which the simulation stops.
+/// If not supplied the simulation runs until /stop.
+/// * seed - Optional random number seed, by default one is taken from
+/// the time of day.
+///
+#[get("/start?<parameter>&<distribution>&<rate>&<events>&<seed>")]
+pub fn start_simulation(
+    parameter: OptionalStringVec,
+    distribution: OptionalStringVec,
+    rate: f64,
+    events: Option<u64>,
+    seed: Option<u64>,
+    state: &State<SharedHistogramChannel>,
+    simulator: &State<simulation::Simulator>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    if !simulator.inner().is_enabled() {
+        return Json(GenericResponse::err(
+            "Simulation is not enabled",
+            "Run rustogramer with --enable-simulation",
+        ));
+    }
+    let names = parameter.unwrap_or_default();
+    if names.is_empty() {
+        return Json(GenericResponse::err(
+            "Unable to start simulation",
+            "At least one parameter is required",
+        ));
+    }
+    let parameters = match make_parameters(&names, &distribution.unwrap_or_default(), state) {
+        Ok(p) => p,
+        Err(msg) => return Json(GenericResponse::err("Unable to start simulation", &msg)),
+    };
+    let spec = simulation::SimulationSpec {
+        parameters,
+        rate,
+        events,
+        seed: seed.unwrap_or_else(simulation::time_seed),
+    };
+    Json(
+        match simulator
+            .inner()
+            .start(&state.inner().lock().unwrap(), spec)
+        {
+            Ok(()) => GenericResponse::ok(""),
+            Err(msg) => GenericResponse::err("Unable to start simulation", &msg),
+        },
+    )
+}
+/// Stop the simulation.  It is not an error to stop when no
+/// simulation is running.
+#[get("/stop")]
+pub fn stop_simulation(
+    simulator: &State<simulation::Simulator>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    simulator.inner().stop();
+    Json(GenericResponse::ok(""))
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SimulationStatus {
+    enabled: bool,
+    running: bool,
+    events: u64,
+    elapsed: f64,
+    rate: f64,
+    error: Option<String>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SimulationStatusResponse {
+    status: String,
+    detail: SimulationStatus,
+}
+/// Return the status of the current or most recent simulation.
+/// rate is the achieved rate in events per second.
+#[get("/status")]
+pub fn simulation_status(
+    simulator: &State<simulation::Simulator>,
+) -> Json<SimulationStatusResponse> {
+    let status = simulator.inner().status();
+    let elapsed = status.elapsed.as_secs_f64();
+    Json(SimulationStatusResponse {
+        status: String::from("OK"),
+        detail: SimulationStatus {
+            enabled: simulator.inner().is_enabled(),
+            running: status.running,
+            events: status.events,
+            elapsed,
+            rate: if elapsed > 0.0 {
+                status.events as f64 / elapsed
+            } else {
+                0.0
+            },
+            error: status.error,
+        },
+    })
+}
+
+#[cfg(test)]
+mod simulate_tests {
+    use super::*;
+    use crate::messaging;
+    use crate::messaging::spectrum_messages;
+    use crate::test::rest_common;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+    use std::thread;
+    use std::time;
+
+    fn setup() -> Rocket<Build> {
+        rest_common::setup().mount(
+            "/",
+            routes![start_simulation, stop_simulation, simulation_status],
+        )
+    }
+    fn getstate(
+        r: &Rocket<Build>,
+    ) -> (
+        mpsc::Sender<messaging::Request>,
+        processing::ProcessingApi,
+        binder::BindingApi,
+    ) {
+        rest_common::get_state(r)
+    }
+    fn teardown(
+        c: mpsc::Sender<messaging::Request>,
+        p: &processing::ProcessingApi,
+        b: &binder::BindingApi,
+    ) {
+        rest_common::teardown(c, p, b);
+    }
+    fn make_test_parameters(c: &mpsc::Sender<messaging::Request>) {
+        let api = ParameterMessageClient::new(c);
+        api.create_parameter("p1").expect("Making p1");
+        api.create_parameter("p2").expect("Making p2");
+    }
+    // Wait for the simulation to finish:
+
+    fn wait(client: &Client) -> SimulationStatusResponse {
+        loop {
+            let reply = client
+                .get("/status")
+                .dispatch()
+                .into_json::<SimulationStatusResponse>()
+                .expect("Parsing JSON");
+            if !reply.detail.running {
+                return reply;
+            }
+            thread::sleep(time::Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn start_1() {
+        // Errors in the request:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+        make_test_parameters(&chan);
+
+        let client = Client::untracked(rocket).expect("Making client");
+        for uri in [
+            "/start?rate=0",
+            "/start?parameter=nosuch&rate=0",
+            "/start?parameter=p1&distribution=poisson:3&rate=0",
+            "/start?parameter=p1&parameter=p2&distribution=constant:1&distribution=constant:2&distribution=constant:3&rate=0",
+            "/start?parameter=p1&rate=-10",
+        ] {
+            let reply = client
+                .get(uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!("Unable to start simulation", reply.status, "{}", uri);
+        }
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn start_2() {
+        // Events go through the histogram server into spectra:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+        make_test_parameters(&chan);
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&chan);
+        sapi.create_spectrum_1d("s1", "p1", 0.0, 1024.0, 1024)
+            .expect("Making s1");
+        sapi.create_spectrum_1d("s2", "p2", 0.0, 1024.0, 1024)
+            .expect("Making s2");
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let reply = client
+            .get("/start?parameter=p1&parameter=p2&distribution=constant:10&distribution=uniform:100:200&rate=0&events=1500&seed=7")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        let status = wait(&client);
+        assert_eq!(1500, status.detail.events);
+        assert!(status.detail.error.is_none());
+
+        let contents = sapi
+            .get_contents("s1", 0.0, 1024.0, 0.0, 0.0)
+            .expect("Getting s1");
+        assert_eq!(1, contents.len());
+        assert_eq!(10.0, contents[0].x);
+        assert_eq!(1500.0, contents[0].value);
+
+        let contents = sapi
+            .get_contents("s2", 0.0, 1024.0, 0.0, 0.0)
+            .expect("Getting s2");
+        assert!(contents.iter().all(|c| c.x >= 100.0 && c.x < 200.0));
+        assert_eq!(1500.0, contents.iter().map(|c| c.value).sum::<f64>());
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn stop_1() {
+        // A rate limited, unbounded simulation runs until stopped:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = getstate(&rocket);
+        make_test_parameters(&chan);
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let reply = client
+            .get("/start?parameter=p1&rate=1000")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        // Can't start a second one:
+
+        let reply = client
+            .get("/start?parameter=p1&rate=1000")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("Unable to start simulation", reply.status);
+
+        thread::sleep(time::Duration::from_millis(200));
+        let reply = client
+            .get("/stop")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        let status = wait(&client);
+        assert!(status.detail.events > 0);
+        // Generous to allow for slow test machines:
+        assert!(status.detail.events < 2000, "{}", status.detail.events);
+
+        teardown(chan, &papi, &bapi);
+    }
+}
@@ -1,7 +1,11 @@
 //!  This module contains the client code/REST server code for spectrum I/O.
 //!  We support two spectrum file formats:  SpecTcl old style format to support
 //!  interchanging spectrum data with SpecTcl and Java Script Object Notation
-//!  (JSON) encoded spectra.
+//!  (JSON) encoded spectra.  Spectra can also be exchanged with ROOT as
+//!  ROOT macros (see spectclio::root).  A single spectrum can be
+//!  exchanged with spreadsheets as comma separated values (see
+//!  spectclio::csv) or written (but not read) as a NumPy .npy file (see
+//!  the npy module).
 //!
 //!  Writing and (reading?) JSON encoded data is handled smoothly by
 //!  serde - we can put the spectrum metadata and channel data into a nice
@@ -10,9 +14,12 @@
 //!  the (json::from_str e.g.).
 //!
 use super::*;
+use crate::jobs;
 use crate::messaging::condition_messages;
 use crate::messaging::parameter_messages;
 use crate::messaging::spectrum_messages;
+use crate::npy;
+use crate::processing;
 use crate::sharedmem::binder;
 use crate::spectclio;
 use rocket::serde::{json, json::Json};
@@ -44,11 +51,12 @@ pub struct SpectrumChannel {
 
 /// This is, again, a bit different than the
 /// spectrum_message::SpectrumProperties, however mostly in that
-/// it's declared to be (de)serializable... but we also don't
-/// need the gate when serializing/deserializing a spectrum.
+/// it's declared to be (de)serializable.
 ///
 /// in this case field names are chosen a bit more carefully as they
-/// will appear verbatim in the JSON.
+/// will appear verbatim in the JSON.  The gate and fold are optional
+/// in the JSON so that files written before they were saved can
+/// still be read.  They are not part of the SpecTcl ASCII format.
 ///
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct SpectrumProperties {
@@ -58,6 +66,10 @@ pub struct SpectrumProperties {
     pub y_parameters: Vec<String>,
     pub x_axis: Option<(f64, f64, u32)>,
     pub y_axis: Option<(f64, f64, u32)>,
+    #[serde(default)]
+    pub gate: Option<String>,
+    #[serde(default)]
+    pub fold: Option<String>,
 }
 
 /// Spectra are their properties and a vector of their channels:
@@ -78,6 +90,22 @@ fn axis_to_tuple(i: Option<spectrum_messages::AxisSpecification>) -> Option<(f64
     i.map(|s| (s.low, s.high, s.bins))
 }
 
+/// Convert the histogramer's description of a spectrum into a
+/// SpectrumProperties.  Note that the axis bins include the
+/// under/overflow channels.
+pub fn describe_spectrum(info: &spectrum_messages::SpectrumProperties) -> SpectrumProperties {
+    SpectrumProperties {
+        name: info.name.clone(),
+        type_string: spectrum::rg_sptype_to_spectcl(&info.type_name),
+        x_parameters: info.xparams.clone(),
+        y_parameters: info.yparams.clone(),
+        x_axis: axis_to_tuple(info.xaxis),
+        y_axis: axis_to_tuple(info.yaxis),
+        gate: info.gate.clone(),
+        fold: info.fold.clone(),
+    }
+}
+
 // private function to get spectrum properties:
 
 fn get_spectrum_descriptions(
@@ -94,24 +122,20 @@ fn get_spectrum_descriptions(
         if info.is_empty() {
             return Err((name.clone(), String::from("Spectrum does not exist")));
         }
-        let info = &info[0];
-        ok_result.push(SpectrumProperties {
-            name: info.name.clone(),
-            type_string: spectrum::rg_sptype_to_spectcl(&info.type_name),
-            x_parameters: info.xparams.clone(),
-            y_parameters: info.yparams.clone(),
-            x_axis: axis_to_tuple(info.xaxis),
-            y_axis: axis_to_tuple(info.yaxis),
-        });
+        ok_result.push(describe_spectrum(&info[0]));
     }
 
     Ok(ok_result)
 }
 // Simple axis -> bin transformation:
-// The + 1 allows for the fact that bin 0 is underflows.
 
 fn transform(l: f64, h: f64, b: u32, c: f64) -> usize {
-    (((c - l) / (h - l)) * (b - 2) as f64) as usize + 1
+    let axis = spectrum_messages::AxisSpecification {
+        low: l,
+        high: h,
+        bins: b,
+    };
+    spectrum_messages::coord_to_bin(c, axis) as usize
 }
 
 // Given coordinates  in a normal bin - convert themto (xbin, ybin):
@@ -214,11 +238,11 @@ fn convert_channels(
 ) -> Vec<SpectrumChannel> {
     let mut result = Vec::<SpectrumChannel>::new();
 
-    // Summary type has axis defs that are 'special'.
+    // Summary and gamma summary types have axis defs that are 'special'.
     // specifically, the x axis specification has to be generated from
     // the x parameter list size.
 
-    let desc = if d.type_string == "s" {
+    let desc = if d.type_string == "s" || d.type_string == "gs" {
         let mut summary_d = d.clone();
         summary_d.x_axis = Some((
             0.0,
@@ -237,33 +261,92 @@ fn convert_channels(
     result
 }
 
+/// Get the contents of a spectrum and combine them with its
+/// description into the SpectrumFileData that is written to file.
+///
+/// ### Parameters
+/// * d - The spectrum description (see describe_spectrum).
+/// * api - client to the histogram thread.
+///
+/// ### Returns
+/// Result<SpectrumFileData, String> - Err if the contents could not be
+/// gotten (e.g. the spectrum was deleted out from under us).
+pub fn get_spectrum_data(
+    d: &SpectrumProperties,
+    api: &spectrum_messages::SpectrumMessageClient,
+) -> Result<SpectrumFileData, String> {
+    let (xlow, xhigh) = if let Some(x) = d.x_axis {
+        (x.0, x.1)
+    } else {
+        (0.0, d.x_parameters.len() as f64) // summary spectrum correction.
+    };
+    let (ylow, yhigh) = if let Some(y) = d.y_axis {
+        (y.0, y.1)
+    } else {
+        (-1.0, 1.0)
+    };
+    let contents = api.get_contents(&d.name, xlow, xhigh, ylow, yhigh)?;
+    Ok(SpectrumFileData {
+        definition: d.clone(),
+        channels: convert_channels(&contents, d),
+    })
+}
 /// This is the handler for the Spectrum write method.
 ///
 /// ### Parameters
 /// *  file - path to the file to create. Must not exist.
-/// *  format - Format - legal values are "ascii", and "json"  these
-/// are matched case insensitively (e.g. "ASCII" and "Json" are legal and do
-/// what you think they might do).
+/// *  format - Format - legal values are "ascii", "json", "root", "csv" and
+/// "npy" these are matched case insensitively (e.g. "ASCII" and "Json" are
+/// legal and do what you think they might do).  "root" writes a ROOT macro
+/// that makes a histogram for each spectrum (see spectclio::root).  "csv"
+/// writes exactly one spectrum as comma separated values (see
+/// spectclio::csv).  "npy" writes exactly one spectrum and its axis
+/// definitions to a JSON sidecar file named file.json.
 /// * spectrum - Can appear multiple times and are the names of the
 /// spectra that should be written to file.
+/// * job - Optional flag.  If true, the spectra are written by a job
+/// (see the jobs module) and the reply is sent as soon as the spectra
+/// have been looked up.
 /// * state - The REST state object that contains what we need to form an
 /// API object to talk to the histogram thread.
+/// * limits - Server limits; the number of spectra is checked against
+/// the bulk spectrum limit.
+/// * jobs - The job store.
 ///
 /// ### Returns:
 /// * JSON encoded GenericResponse object.  
 ///     -  On success only **status** is non-empty and contains _OK_
+///     unless a job was started, in which case the **detail** is the
+///     job's id.
 ///     -  On failure, the **status** contains the top level error reason
 ///  (e.g  Spectrum or spectra not found)
 /// and **detail** contains a more specific message e.g. in the case above, the
 /// set of spectra that could not be looked up in the histogram server.
 ///
-#[get("/?<file>&<format>&<spectrum>")]
+#[get("/?<file>&<format>&<spectrum>&<job>")]
 pub fn swrite_handler(
     file: String,
     format: String,
     spectrum: Vec<String>,
+    job: OptionalFlag,
     state: &State<SharedHistogramChannel>,
+    limits: &State<limits::RestLimits>,
+    jobs: &State<jobs::SharedJobStore>,
+    _ready: busy::HistogramerReady,
 ) -> Json<GenericResponse> {
+    if let Err(e) = limits.check_bulk_spectra(spectrum.len()) {
+        return Json(GenericResponse::err("Request exceeds server limits", &e));
+    }
+    // make the format lower case for string blind compare:
+
+    let mut fmt = format.clone();
+    fmt.make_ascii_lowercase();
+    if (fmt == "npy" || fmt == "csv") && spectrum.len() != 1 {
+        return Json(GenericResponse::err(
+            &format!("The {} format writes exactly one spectrum", fmt),
+            &format!("{} spectra were requested", spectrum.len()),
+        ));
+    }
     let api = spectrum_messages::SpectrumMessageClient::new(&(state.inner().lock().unwrap()));
 
     // Get the spectrum properties for the spectra:
@@ -276,49 +359,77 @@ pub fn swrite_handler(
         ));
     }
     let descriptions = descriptions.unwrap();
+
+    if job.unwrap_or(false) {
+        let channel = state.inner().lock().unwrap().clone();
+        let id = jobs.inner().start(
+            "swrite",
+            &format!("Write {} spectra to {}", descriptions.len(), file),
+            move |ctx| {
+                let api = spectrum_messages::SpectrumMessageClient::new(&channel);
+                job::job_result(write_spectra(
+                    &file,
+                    &format,
+                    &descriptions,
+                    &api,
+                    |done, total| {
+                        ctx.progress(done as u64, total as u64);
+                        !ctx.cancelled()
+                    },
+                ))
+            },
+        );
+        return Json(GenericResponse::ok(&id.to_string()));
+    }
+    Json(write_spectra(
+        &file,
+        &format,
+        &descriptions,
+        &api,
+        |_, _| true,
+    ))
+}
+// Write the described spectra to file.  progress is called with the
+// number of spectra whose contents have been gotten and the total;
+// if it returns false we give up without writing the file.
+
+fn write_spectra<F>(
+    file: &str,
+    format: &str,
+    descriptions: &[SpectrumProperties],
+    api: &spectrum_messages::SpectrumMessageClient,
+    mut progress: F,
+) -> GenericResponse
+where
+    F: FnMut(usize, usize) -> bool,
+{
     // For each description, get the contents and build a vector of Spectrum
     // file data from them.  Note it's possible to fail to get contents
     // if another process has killed off a spectrum whlie we're running.
     // In that case, we just drop that spectrum from the output file:
 
     let mut spectra = Vec::<SpectrumFileData>::new();
-    for d in descriptions.iter() {
-        let (xlow, xhigh) = if let Some(x) = d.x_axis {
-            (x.0, x.1)
-        } else {
-            (0.0, d.x_parameters.len() as f64) // summary spectrum correction.
-        };
-        let (ylow, yhigh) = if let Some(y) = d.y_axis {
-            (y.0, y.1)
-        } else {
-            (-1.0, 1.0)
-        };
-        let contents = api.get_contents(&d.name, xlow, xhigh, ylow, yhigh);
-        if let Ok(c) = contents {
-            spectra.push(SpectrumFileData {
-                definition: d.clone(),
-                channels: convert_channels(&c, d),
-            });
+    for (i, d) in descriptions.iter().enumerate() {
+        if let Ok(data) = get_spectrum_data(d, api) {
+            spectra.push(data);
+        }
+        if !progress(i + 1, descriptions.len()) {
+            return GenericResponse::err(
+                "Spectrum write cancelled",
+                &format!("after {} of {} spectra", i + 1, descriptions.len()),
+            );
         }
     }
 
     // Try to create the file
 
-    let fd = File::create(&file);
+    let fd = File::create(file);
     if let Err(e) = fd {
-        return Json(GenericResponse::err(
-            &format!("Unable to create file: {}", file),
-            &e.to_string(),
-        ));
+        return GenericResponse::err(&format!("Unable to create file: {}", file), &e.to_string());
     }
     let mut fd = fd.unwrap();
 
-    // make the format lower case for string blind compare:
-
-    let mut fmt = format.clone();
-    fmt.make_ascii_lowercase();
-
-    let response = match fmt.as_str() {
+    match format.to_ascii_lowercase().as_str() {
         "json" => {
             if let Err(e) = fd.write_all(
                 json::to_string(&spectra)
@@ -338,10 +449,51 @@ pub fn swrite_handler(
                 GenericResponse::ok("")
             }
         }
-        _ => GenericResponse::err("Invalid format type specification:", &format),
-    };
-
-    Json(response)
+        "root" => {
+            if let Err(s) = spectclio::root::write_root_macro(&mut fd, &spectra) {
+                GenericResponse::err("Unable to write ROOT spectra", &s)
+            } else {
+                GenericResponse::ok("")
+            }
+        }
+        "csv" => match spectra.first() {
+            Some(s) => match spectclio::csv::write_spectrum(&mut fd, s) {
+                Ok(()) => GenericResponse::ok(""),
+                Err(s) => GenericResponse::err("Unable to write CSV spectrum", &s),
+            },
+            None => GenericResponse::err(
+                "Failed to get spectrum contents",
+                &format!("{} was deleted while being written", descriptions[0].name),
+            ),
+        },
+        "npy" => match spectra.first() {
+            Some(s) => write_npy(&mut fd, file, s),
+            None => GenericResponse::err(
+                "Failed to get spectrum contents",
+                &format!("{} was deleted while being written", descriptions[0].name),
+            ),
+        },
+        _ => GenericResponse::err("Invalid format type specification:", format),
+    }
+}
+// Write a spectrum as .npy and its axis definitions to the
+// sidecar file (the .npy file name with .json appended):
+
+fn write_npy(fd: &mut File, file: &str, spectrum: &SpectrumFileData) -> GenericResponse {
+    if let Err(s) = npy::write_spectrum(fd, spectrum) {
+        return GenericResponse::err("Unable to write npy spectrum", &s);
+    }
+    let sidecar = format!("{}.json", file);
+    match File::create(&sidecar) {
+        Ok(mut sfd) => match npy::write_metadata(&mut sfd, spectrum) {
+            Ok(()) => GenericResponse::ok(""),
+            Err(s) => GenericResponse::err("Unable to write npy axis metadata", &s),
+        },
+        Err(e) => GenericResponse::err(
+            &format!("Unable to create file: {}", sidecar),
+            &e.to_string(),
+        ),
+    }
 }
 //--------------------------------------------------------------------
 // Stuff needed for sread.
@@ -381,17 +533,18 @@ fn make_parameter_set(
     Ok(result)
 }
 // Given a vector of parameter names, makes new parameters for all that are not
-// in the existing hash -- updating the hash.
+// in the existing hash -- updating the hash.  Gamma summary x parameters are
+// space separated lists of names.
 
 fn make_missing_params(
     params: &[String],
     existing: &mut HashSet<String>,
     api: &parameter_messages::ParameterMessageClient,
 ) -> Result<(), String> {
-    for p in params.iter() {
+    for p in params.iter().flat_map(|p| p.split_whitespace()) {
         if !existing.contains(p) {
             api.create_parameter(p)?;
-            existing.insert(p.clone());
+            existing.insert(String::from(p));
         }
     }
     Ok(())
@@ -443,15 +596,44 @@ fn make_unique_name(
     }
     Ok(candidate_name)
 }
-// Make a spectrum -- when we know that
-//  - all parameters have been defined.
-// - We won't be replacing an existing spectrum:
-//
-fn make_spectrum(
+/// Check that a spectrum definition has the parameters and axes its
+/// type needs for make_spectrum to make it.  Spectrum file readers use
+/// this to reject definitions they could not rebuild.
+pub fn check_definition(def: &SpectrumProperties) -> Result<(), String> {
+    let has_x = !def.x_parameters.is_empty();
+    let has_y = !def.y_parameters.is_empty();
+    let x_axis = def.x_axis.is_some();
+    let y_axis = def.y_axis.is_some();
+    let complete = match def.type_string.as_str() {
+        "1" | "g1" | "b" => has_x && x_axis,
+        "g2" => has_x && x_axis && y_axis,
+        "s" | "gs" => has_x && (x_axis || y_axis),
+        "S" => has_x && has_y && x_axis,
+        "snap1" => x_axis,
+        "2" | "gd" | "m2" => has_x && has_y && x_axis && y_axis,
+        _ => return Err(format!("Unsupported spectrum type {}", def.type_string)),
+    };
+    if complete {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} does not have the parameters and axes a type {} spectrum needs",
+            def.name, def.type_string
+        ))
+    }
+}
+/// Make a spectrum -- when we know that
+///  - all parameters have been defined.
+/// - We won't be replacing an existing spectrum:
+///
+/// The axis bins in def must not include the under/overflow channels.
+/// The definition is checked with check_definition first.
+pub fn make_spectrum(
     name: &str,
     def: &SpectrumProperties,
     api: &spectrum_messages::SpectrumMessageClient,
 ) -> Result<String, String> {
+    check_definition(def)?;
     match def.type_string.as_str() {
         "1" => {
             let axis = def.x_axis.unwrap();
@@ -500,6 +682,38 @@ fn make_spectrum(
             };
             api.create_spectrum_summary(name, &def.x_parameters, axis.0, axis.1, axis.2)?;
         }
+        "gs" => {
+            let axis = if let Some(y) = def.y_axis {
+                y
+            } else {
+                def.x_axis.unwrap()
+            };
+            let params: Vec<Vec<String>> = def
+                .x_parameters
+                .iter()
+                .map(|l| l.split_whitespace().map(String::from).collect())
+                .collect();
+            api.create_spectrum_gamma_summary(name, &params, axis.0, axis.1, axis.2)?;
+        }
+        "b" => {
+            let axis = def.x_axis.unwrap();
+            api.create_spectrum_bitmask(name, &def.x_parameters[0], axis.0, axis.1, axis.2)?;
+        }
+        "snap1" => {
+            let axis = def.x_axis.unwrap();
+            api.create_spectrum_snapshot1d(name, axis.0, axis.1, axis.2)?;
+        }
+        "S" => {
+            let axis = def.x_axis.unwrap();
+            api.create_spectrum_stripchart(
+                name,
+                &def.x_parameters[0],
+                &def.y_parameters[0],
+                axis.0,
+                axis.1,
+                axis.2,
+            )?;
+        }
         "2" => {
             let xaxis = def.x_axis.unwrap();
             let yaxis = def.y_axis.unwrap();
@@ -557,9 +771,8 @@ fn enter_spectrum(
     };
     make_spectrum(&actual_name, def, api)
 }
-// Given a spectrum we know now exists, fill it:
-
-fn fill_spectrum(
+/// Given a spectrum we know now exists, fill it:
+pub fn fill_spectrum(
     name: &str,
     c: &[SpectrumChannel],
     api: &spectrum_messages::SpectrumMessageClient,
@@ -583,6 +796,41 @@ fn fill_spectrum(
     }
 }
 
+// True if the named condition exists:
+
+fn condition_exists(name: &str, api: &condition_messages::ConditionMessageClient) -> bool {
+    if let condition_messages::ConditionReply::Listing(l) = api.list_conditions(name) {
+        !l.is_empty()
+    } else {
+        false
+    }
+}
+// Re-apply the gate and fold saved with a spectrum to the spectrum
+// that was made from it.  Only conditions that exist are applied.
+// The snapshot condition takes precedence over the saved gate.
+
+fn restore_gates(
+    name: &str,
+    def: &SpectrumProperties,
+    as_snapshot: bool,
+    spectrum_api: &spectrum_messages::SpectrumMessageClient,
+    condition_api: &condition_messages::ConditionMessageClient,
+) -> Result<(), String> {
+    if !as_snapshot {
+        if let Some(gate) = &def.gate {
+            if condition_exists(gate, condition_api) {
+                spectrum_api.gate_spectrum(name, gate)?;
+            }
+        }
+    }
+    if let Some(fold) = &def.fold {
+        if condition_exists(fold, condition_api) {
+            spectrum_api.fold_spectrum(name, fold)?;
+        }
+    }
+    Ok(())
+}
+
 // Given deserialized spectra - enter them in the histogram thread:
 
 fn enter_spectra(
@@ -590,6 +838,7 @@ fn enter_spectra(
     as_snapshot: bool,
     replace: bool,
     to_shm: bool,
+    with_gates: bool,
     hg_chan: &State<SharedHistogramChannel>,
     state: &State<SharedBinderChannel>,
 ) -> Result<(), String> {
@@ -599,12 +848,12 @@ fn enter_spectra(
         spectrum_messages::SpectrumMessageClient::new(&hg_chan.inner().lock().unwrap());
     let parameter_api =
         parameter_messages::ParameterMessageClient::new(&hg_chan.inner().lock().unwrap());
+    let condition_api =
+        condition_messages::ConditionMessageClient::new(&hg_chan.inner().lock().unwrap());
     let mut parameters = make_parameter_set(&parameter_api)?;
     // snapshots require a _snapshot_condition_ gate.  This is a False
     // condition.  No harm to make it again so just unconditionally make it:
     if as_snapshot {
-        let condition_api =
-            condition_messages::ConditionMessageClient::new(&hg_chan.inner().lock().unwrap());
         condition_api.create_false_condition("_snapshot_condition_");
     }
     for s in spectra {
@@ -619,6 +868,15 @@ fn enter_spectra(
         if as_snapshot {
             spectrum_api.gate_spectrum(&actual_name, "_snapshot_condition_")?
         }
+        if with_gates {
+            restore_gates(
+                &actual_name,
+                &s.definition,
+                as_snapshot,
+                &spectrum_api,
+                &condition_api,
+            )?;
+        }
 
         // Now fill the spectrum from the data we got from the file
         // Note that doing it in this order ensures that snapshots don't have
@@ -636,10 +894,35 @@ fn enter_spectra(
     }
     Ok(())
 }
-// JSON Spectra bin count includes the overflows so 2 must be
-// deducted from each one
+// Check the spectra read from file against the server limits before
+// any of them are entered in the histogram thread:
 
-fn fix_json_bins(input: Vec<SpectrumFileData>) -> Vec<SpectrumFileData> {
+fn check_read_limits(
+    spectra: &[SpectrumFileData],
+    limits: &limits::RestLimits,
+) -> Result<(), String> {
+    limits.check_bulk_spectra(spectra.len())?;
+    for s in spectra {
+        limits.check_fill_points(&s.definition.name, s.channels.len())?;
+    }
+    Ok(())
+}
+/// Detail of the sread reply when analysis was paused to enter the
+/// spectra.
+pub const SREAD_PAUSED: &str = "Analysis was paused while the spectra were entered";
+
+// Spectra are entered with several requests to the histogramer;
+// creation, gating and filling.  If analysis is active, events could be
+// histogrammed between those requests, so analysis is paused while the
+// spectra are entered.  Returns true if analysis was paused.  Analysis
+// that ends or is paused by someone else first is left alone.
+
+fn pause_active_analysis(api: &processing::ProcessingApi) -> bool {
+    matches!(api.processing_state(), Ok(s) if s == "Active") && api.pause_analysis().is_ok()
+}
+/// JSON Spectra bin count includes the overflows so 2 must be
+/// deducted from each one
+pub fn fix_json_bins(input: Vec<SpectrumFileData>) -> Vec<SpectrumFileData> {
     let mut result = Vec::<SpectrumFileData>::new();
 
     for item in input.iter().map(|x| {
@@ -675,8 +958,8 @@ fn fix_json_bins(input: Vec<SpectrumFileData>) -> Vec<SpectrumFileData> {
 ///
 /// ### Parameters:
 /// *  filename - (mandatory) path to the file to read.
-/// *  format - (mandatory) spectrum format.  json and ascii are supported in
-/// a case blind way.
+/// *  format - (mandatory) spectrum format.  json, ascii, root and csv
+/// are supported in a case blind way.
 /// *  snapshot - (optional) if true (default is yes), a _False_ condition is
 /// set on the spectrum that's read in.  If necessary a _False_ condition named
 /// _snapshot_condition_ is created.  If snapshot is false, then the spectrum
@@ -687,10 +970,22 @@ fn fix_json_bins(input: Vec<SpectrumFileData>) -> Vec<SpectrumFileData> {
 /// _similar_ spectrum name is constructedm created and used.
 /// *  bind - (optional) if true (defalt is yes),  the final spectrum is
 // bound to the Xamine shared memory.
+/// *  gates - (optional) if true (default is no), the gate and fold saved
+/// with each spectrum are re-applied if conditions with those names exist.
+/// If the spectrum is read as a snapshot, only the fold is re-applied.
+/// Only JSON files save gates and folds.
 /// * state (mandatory) the state of the server (contains what's needed to
 /// access various APIs).
+/// * limits - server limits.  The file size, number of spectra and
+/// channels per spectrum are checked against these before any spectrum
+/// is entered.
+/// * papi - the processing API.  If analysis is active it is paused
+/// while the spectra are entered so that no events are histogrammed
+/// into a spectrum between its creation and filling.
 ///
 /// ### Returns:
+///  GenericResponse - on success, the detail is SREAD_PAUSED if analysis
+/// was paused and resumed, otherwise it is empty.
 ///
 /// ### Notes:
 ///   *   It is possible that this will require the creation of new parameters.
@@ -700,15 +995,20 @@ fn fix_json_bins(input: Vec<SpectrumFileData>) -> Vec<SpectrumFileData> {
 ///   * The file is processed serially, that is if there is a failure (e.g.
 /// the file format has an error), any spectra correctly read in are fully
 /// processed.
-#[get("/?<filename>&<format>&<snapshot>&<replace>&<bind>")]
+#[get("/?<filename>&<format>&<snapshot>&<replace>&<bind>&<gates>")]
 pub fn sread_handler(
     filename: String,
     format: String,
     snapshot: OptionalFlag,
     replace: OptionalFlag,
     bind: OptionalFlag,
+    gates: OptionalFlag,
     hg_chan: &State<SharedHistogramChannel>,
     state: &State<SharedBinderChannel>,
+    limits: &State<limits::RestLimits>,
+    papi: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
 ) -> Json<GenericResponse> {
     // Figure out the flag states:
 
@@ -717,6 +1017,8 @@ pub fn sread_handler(
     let repl = if let Some(r) = replace { r } else { false };
 
     let toshm = if let Some(b) = bind { b } else { true };
+
+    let with_gates = gates.unwrap_or_default();
     //See if we can open the file:  If not that's an error:
 
     let fd = File::open(&filename);
@@ -728,6 +1030,21 @@ pub fn sread_handler(
     }
     let mut fd = fd.unwrap();
 
+    // Refuse to deserialize files that are unreasonably large:
+
+    let size = match fd.metadata() {
+        Ok(m) => m.len(),
+        Err(e) => {
+            return Json(GenericResponse::err(
+                &format!("Unable to get size of input file: {}", filename),
+                &e.to_string(),
+            ));
+        }
+    };
+    if let Err(e) = limits.check_body_bytes(size) {
+        return Json(GenericResponse::err("Input file exceeds server limits", &e));
+    }
+
     // how we read the spectra depends on the format:
 
     let mut fmt = format.clone();
@@ -736,6 +1053,8 @@ pub fn sread_handler(
     let spectra = match fmt.as_str() {
         "json" => read_json(&mut fd),
         "ascii" => spectclio::read_spectra(&mut fd),
+        "root" => spectclio::root::read_root_macro(&mut fd),
+        "csv" => spectclio::csv::read_spectra(&mut fd),
         _ => {
             return Json(GenericResponse::err("Unsupported format", &format));
         }
@@ -746,11 +1065,22 @@ pub fn sread_handler(
         return Json(GenericResponse::err("Unable to deserialize from file", msg));
     }
     let spectra = spectra.as_ref().unwrap();
+    if let Err(e) = check_read_limits(spectra, limits) {
+        return Json(GenericResponse::err("Input file exceeds server limits", &e));
+    }
 
-    let response = if let Err(e) = enter_spectra(spectra, snap, repl, toshm, hg_chan, state) {
-        GenericResponse::err("Unable to enter spectra in histogram thread: ", &e)
+    let papi = papi.inner().lock().unwrap().clone();
+    let paused = pause_active_analysis(&papi);
+    let result = enter_spectra(spectra, snap, repl, toshm, with_gates, hg_chan, state);
+    let resumed = if paused {
+        papi.resume_analysis().map(|_| ())
     } else {
-        GenericResponse::ok("")
+        Ok(())
+    };
+    let response = match (result, resumed) {
+        (Err(e), _) => GenericResponse::err("Unable to enter spectra in histogram thread: ", &e),
+        (Ok(()), Err(e)) => GenericResponse::err("Unable to resume analysis", &e),
+        (Ok(()), Ok(())) => GenericResponse::ok(if paused { SREAD_PAUSED } else { "" }),
     };
     Json(response)
 }
@@ -768,7 +1098,10 @@ mod read_tests {
     use rocket::Build;
     use rocket::Rocket;
 
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
     use std::sync::mpsc;
+    use std::thread;
 
     fn setup() -> Rocket<Build> {
         rest_common::setup().mount("/", routes![sread_handler])
@@ -795,6 +1128,24 @@ mod read_tests {
     // know that we only need to flip switches and look for
     // differences.
 
+    #[test]
+    fn limits_1() {
+        // Spectra read from test.json checked against limits:
+
+        let mut fd = File::open("test.json").expect("Opening test.json");
+        let spectra = read_json(&mut fd).expect("Reading test.json");
+        assert!(check_read_limits(&spectra, &limits::RestLimits::default()).is_ok());
+
+        // Too many spectra:
+
+        let l = limits::RestLimits::new(1000000, spectra.len() - 1, 1000000);
+        assert!(check_read_limits(&spectra, &l).is_err());
+
+        // Too many channels in a spectrum.
+
+        let l = limits::RestLimits::new(0, 1000, 1000000);
+        assert!(check_read_limits(&spectra, &l).is_err());
+    }
     #[test]
     fn json_1() {
         // All thedefaults on test.json make 1 and 2
@@ -1247,6 +1598,58 @@ mod read_tests {
 
         teardown(chan, &papi, &bind_api);
     }
+    #[test]
+    fn active_1() {
+        // Analysis of a ring that never sends data stays active.  It is
+        // paused while the spectra are entered and then resumed:
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Binding listener");
+        let port = listener.local_addr().unwrap().port();
+        let (done_send, done_recv) = mpsc::channel::<()>();
+        let ringmaster = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().expect("Accepting");
+            let mut line = String::new();
+            BufReader::new(socket.try_clone().unwrap())
+                .read_line(&mut line)
+                .expect("Reading request");
+            socket.write_all(b"OK BINARY FOLLOWS\n").expect("Replying");
+            let _ = done_recv.recv();
+        });
+
+        let rocket = setup();
+        let (chan, papi, bind_api) = getstate(&rocket);
+        papi.attach(&format!("ring:tcp://127.0.0.1:{}/fox", port))
+            .expect("Attaching ring");
+        papi.start_analysis().expect("Starting analysis");
+
+        let client = Client::untracked(rocket).expect("Making client");
+        let reply = client
+            .get("/?filename=test.json&format=json&bind=false")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(SREAD_PAUSED, reply.detail);
+        assert_eq!("Active", papi.processing_state().expect("Getting state"));
+
+        // Paused analysis is left alone:
+
+        papi.pause_analysis().expect("Pausing");
+        let reply = client
+            .get("/?filename=test.json&format=json&bind=false")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!("", reply.detail);
+        assert_eq!("Paused", papi.processing_state().expect("Getting state"));
+
+        papi.stop_analysis().expect("Stopping");
+        papi.detach().expect("Detaching");
+        done_send.send(()).expect("Ending ringmaster");
+        ringmaster.join().unwrap();
+        teardown(chan, &papi, &bind_api);
+    }
 }
 // Testing swrite is a bit harder.
 // I think what I'll do is populate spectra,
@@ -1270,7 +1673,8 @@ mod swrite_tests {
 
     use std::sync::mpsc;
 
-    use names;
+    // The names crate, not the rest::names module:
+    use ::names as namegen;
 
     fn setup() -> Rocket<Build> {
         let rocket = rest_common::setup()
@@ -1482,7 +1886,7 @@ mod swrite_tests {
     fn json1d_1() {
         // Write the empty 1d spectrum as json. see if it reads back:
 
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -1540,7 +1944,7 @@ mod swrite_tests {
     fn json1d_2() {
         // Put counts in the two spectra, they should match:
 
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
 
@@ -1582,10 +1986,124 @@ mod swrite_tests {
         teardown(chan, &papi, &bind_api);
     }
     #[test]
+    fn json1d_3() {
+        // The gate is saved and re-applied if requested:
+
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
+            .next()
+            .expect("making filename");
+        let rocket = setup();
+        let (chan, papi, bind_api) = getstate(&rocket);
+
+        let capi = condition_messages::ConditionMessageClient::new(&chan);
+        capi.create_true_condition("accept");
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&chan);
+        sapi.gate_spectrum("oned", "accept").expect("Gating oned");
+
+        let client = Client::untracked(rocket).expect("Making rocket client");
+        let write_uri = format!("/swrite?file={}&format=json&spectrum=oned", filename);
+        let write_req = client.get(&write_uri);
+        let write_response = write_req
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing write JSON");
+        assert_eq!("OK", write_response.status);
+
+        // Without gates=true the gate is not applied:
+
+        let read_uri = format!(
+            "/sread?filename={}&format=json&bind=false&snapshot=false",
+            filename
+        );
+        let read_req = client.get(&read_uri);
+        let read_response = read_req
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("parsing read JSON");
+        assert_eq!("OK", read_response.status);
+        let copy = sapi.list_spectra("oned_0").expect("Listing oned_0");
+        assert_eq!(1, copy.len());
+        assert!(copy[0].gate.is_none());
+
+        // With gates=true it is:
+
+        let read_uri = format!(
+            "/sread?filename={}&format=json&bind=false&snapshot=false&gates=true",
+            filename
+        );
+        let read_req = client.get(&read_uri);
+        let read_response = read_req
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("parsing read JSON");
+        assert_eq!("OK", read_response.status);
+        let copy = sapi.list_spectra("oned_1").expect("Listing oned_1");
+        assert_eq!(1, copy.len());
+        assert_eq!(Some(String::from("accept")), copy[0].gate);
+
+        std::fs::remove_file(&filename).expect("removing test file");
+        teardown(chan, &papi, &bind_api);
+    }
+    #[test]
+    fn json1d_4() {
+        // Saved gates that don't exist are not applied and snapshots
+        // keep the snapshot condition:
+
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
+            .next()
+            .expect("making filename");
+        let rocket = setup();
+        let (chan, papi, bind_api) = getstate(&rocket);
+
+        let capi = condition_messages::ConditionMessageClient::new(&chan);
+        capi.create_true_condition("accept");
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&chan);
+        sapi.gate_spectrum("oned", "accept").expect("Gating oned");
+
+        let client = Client::untracked(rocket).expect("Making rocket client");
+        let write_uri = format!("/swrite?file={}&format=json&spectrum=oned", filename);
+        let write_req = client.get(&write_uri);
+        let write_response = write_req
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing write JSON");
+        assert_eq!("OK", write_response.status);
+
+        let read_uri = format!(
+            "/sread?filename={}&format=json&bind=false&gates=true",
+            filename
+        );
+        let read_req = client.get(&read_uri);
+        let read_response = read_req
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("parsing read JSON");
+        assert_eq!("OK", read_response.status);
+        let copy = sapi.list_spectra("oned_0").expect("Listing oned_0");
+        assert_eq!(Some(String::from("_snapshot_condition_")), copy[0].gate);
+
+        capi.delete_condition("accept");
+        let read_uri = format!(
+            "/sread?filename={}&format=json&bind=false&snapshot=false&gates=true",
+            filename
+        );
+        let read_req = client.get(&read_uri);
+        let read_response = read_req
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("parsing read JSON");
+        assert_eq!("OK", read_response.status);
+        let copy = sapi.list_spectra("oned_1").expect("Listing oned_1");
+        assert!(copy[0].gate.is_none());
+
+        std::fs::remove_file(&filename).expect("removing test file");
+        teardown(chan, &papi, &bind_api);
+    }
+    #[test]
     fn ascii1d_1() {
         // Write the empty 1d spectrum as ascii. see if it reads back:
 
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -1643,7 +2161,7 @@ mod swrite_tests {
     fn ascii1d_2() {
         // Put counts in the two spectra, they should match:
 
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
 
@@ -1687,7 +2205,7 @@ mod swrite_tests {
     fn jsong1_1() {
         // empty g1 spectrum (the metadata are correct):
 
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -1738,7 +2256,7 @@ mod swrite_tests {
     #[test]
     fn jsong1_2() {
         // FIll the spectra this time:
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -1782,7 +2300,7 @@ mod swrite_tests {
     fn asciig1_1() {
         // empty g1 spectrum (the metadata are correct):
 
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -1833,7 +2351,7 @@ mod swrite_tests {
     #[test]
     fn asciig1_2() {
         // FIll the spectra this time:
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -1877,7 +2395,7 @@ mod swrite_tests {
     fn jsong2_1() {
         // empty g2 spectrum (the metadata are correct):
 
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -1928,7 +2446,7 @@ mod swrite_tests {
     #[test]
     fn jsong2_2() {
         // FIll the spectra this time:
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -1972,7 +2490,7 @@ mod swrite_tests {
     fn asciig2_1() {
         // empty g1 spectrum (the metadata are correct):
 
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -2023,7 +2541,7 @@ mod swrite_tests {
     #[test]
     fn asciig2_2() {
         // FIll the spectra this time:
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -2068,7 +2586,7 @@ mod swrite_tests {
     fn jsonpg_1() {
         // empty g2 spectrum (the metadata are correct):
 
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -2122,7 +2640,7 @@ mod swrite_tests {
     #[test]
     fn jsonpg_2() {
         // FIll the spectra this time:
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -2169,7 +2687,7 @@ mod swrite_tests {
     fn asciipg_1() {
         // empty g1 spectrum (the metadata are correct):
 
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -2223,7 +2741,7 @@ mod swrite_tests {
     #[test]
     fn asciipg_2() {
         // FIll the spectra this time:
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -2272,7 +2790,7 @@ mod swrite_tests {
     fn jsonsum_1() {
         // empty g2 spectrum (the metadata are correct):
 
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -2323,7 +2841,7 @@ mod swrite_tests {
     #[test]
     fn jsonsum_2() {
         // FIll the spectra this time:
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -2367,7 +2885,7 @@ mod swrite_tests {
     fn asciisum_1() {
         // empty g1 spectrum (the metadata are correct):
 
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -2418,7 +2936,7 @@ mod swrite_tests {
     #[test]
     fn asciisum_2() {
         // FIll the spectra this time:
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -2460,12 +2978,269 @@ mod swrite_tests {
         teardown(chan, &papi, &bind_api);
     }
     #[test]
+    fn gsum_1() {
+        // Gamma summary spectra round trip through both json and ascii
+        // files:
+
+        let rocket = setup();
+        let (chan, papi, bind_api) = getstate(&rocket);
+
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&chan);
+        let lists = vec![
+            vec![String::from("p.0"), String::from("p.1")],
+            vec![String::from("p.2")],
+            vec![
+                String::from("p.3"),
+                String::from("p.4"),
+                String::from("p.5"),
+            ],
+        ];
+        sapi.create_spectrum_gamma_summary("gsummary", &lists, 0.0, 1024.0, 1024)
+            .expect("gamma summary spectrum");
+        fill_test_spectra(&sapi);
+        let original = sapi
+            .list_spectra("gsummary")
+            .expect("getting gsummary description");
+        let o = &original[0];
+        let original_contents = sapi
+            .get_contents("gsummary", 0.0, 3.0, 0.0, 1024.0)
+            .expect("getting 'gsummary' contents");
+        assert!(!original_contents.is_empty());
+
+        let client = Client::untracked(rocket).expect("Making rocket client");
+        for (i, format) in ["json", "ascii"].iter().enumerate() {
+            let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
+                .next()
+                .expect("making filename");
+            let write_uri = format!(
+                "/swrite?spectrum=gsummary&format={}&file={}",
+                format, filename
+            );
+            let write_response = client
+                .get(&write_uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!("OK", write_response.status);
+
+            let read_uri = format!("/sread?format={}&bind=false&filename={}", format, filename);
+            let read_response = client
+                .get(&read_uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing read JSON");
+            assert_eq!("OK", read_response.status);
+
+            let name = format!("gsummary_{}", i);
+            let copy = sapi.list_spectra(&name).expect("Getting copy description");
+            assert_eq!(1, copy.len());
+            let c = &copy[0];
+            assert_eq!(o.type_name, c.type_name);
+            assert_eq!(o.xparams, c.xparams);
+            assert_eq!(o.yparams, c.yparams);
+            assert_eq!(o.xaxis, c.xaxis);
+            assert_eq!(o.yaxis, c.yaxis);
+
+            let copy_contents = sapi
+                .get_contents(&name, 0.0, 3.0, 0.0, 1024.0)
+                .expect("getting copy contents");
+            assert_eq!(original_contents, copy_contents);
+
+            std::fs::remove_file(&filename).expect("removing test file");
+        }
+        teardown(chan, &papi, &bind_api);
+    }
+    #[test]
+    fn stripchart_1() {
+        // Strip chart spectra round trip through both json and ascii
+        // files - including a scrolled time axis:
+
+        let rocket = setup();
+        let (chan, papi, bind_api) = getstate(&rocket);
+
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&chan);
+        sapi.create_spectrum_stripchart("strip", "p.0", "p.1", 0.0, 100.0, 100)
+            .expect("strip chart spectrum");
+        fill_test_spectra(&sapi);
+        let original = sapi
+            .list_spectra("strip")
+            .expect("getting strip description");
+        let o = &original[0];
+        assert_eq!(10.0, o.xaxis.unwrap().low); // Scrolled by the fill.
+        let original_contents = sapi
+            .get_contents("strip", 10.0, 110.0, 0.0, 0.0)
+            .expect("getting 'strip' contents");
+        assert!(!original_contents.is_empty());
+
+        let client = Client::untracked(rocket).expect("Making rocket client");
+        for (i, format) in ["json", "ascii"].iter().enumerate() {
+            let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
+                .next()
+                .expect("making filename");
+            let write_uri = format!("/swrite?spectrum=strip&format={}&file={}", format, filename);
+            let write_response = client
+                .get(&write_uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!("OK", write_response.status);
+
+            let read_uri = format!("/sread?format={}&bind=false&filename={}", format, filename);
+            let read_response = client
+                .get(&read_uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing read JSON");
+            assert_eq!("OK", read_response.status);
+
+            let name = format!("strip_{}", i);
+            let copy = sapi.list_spectra(&name).expect("Getting copy description");
+            assert_eq!(1, copy.len());
+            let c = &copy[0];
+            assert_eq!(o.type_name, c.type_name);
+            assert_eq!(o.xparams, c.xparams);
+            assert_eq!(o.yparams, c.yparams);
+            assert_eq!(o.xaxis, c.xaxis);
+
+            let copy_contents = sapi
+                .get_contents(&name, 10.0, 110.0, 0.0, 0.0)
+                .expect("getting copy contents");
+            assert_eq!(original_contents, copy_contents);
+
+            std::fs::remove_file(&filename).expect("removing test file");
+        }
+        teardown(chan, &papi, &bind_api);
+    }
+    #[test]
+    fn bitmask_1() {
+        // Bit mask spectra round trip through both json and ascii files:
+
+        let rocket = setup();
+        let (chan, papi, bind_api) = getstate(&rocket);
+
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&chan);
+        sapi.create_spectrum_bitmask("bits", "p.0", 0.0, 16.0, 16)
+            .expect("bitmask spectrum");
+        fill_test_spectra(&sapi);
+        let original = sapi.list_spectra("bits").expect("getting bits description");
+        let o = &original[0];
+        let original_contents = sapi
+            .get_contents("bits", 0.0, 16.0, 0.0, 0.0)
+            .expect("getting 'bits' contents");
+        assert!(!original_contents.is_empty());
+
+        let client = Client::untracked(rocket).expect("Making rocket client");
+        for (i, format) in ["json", "ascii"].iter().enumerate() {
+            let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
+                .next()
+                .expect("making filename");
+            let write_uri = format!("/swrite?spectrum=bits&format={}&file={}", format, filename);
+            let write_response = client
+                .get(&write_uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!("OK", write_response.status);
+
+            let read_uri = format!("/sread?format={}&bind=false&filename={}", format, filename);
+            let read_response = client
+                .get(&read_uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing read JSON");
+            assert_eq!("OK", read_response.status);
+
+            let name = format!("bits_{}", i);
+            let copy = sapi.list_spectra(&name).expect("Getting copy description");
+            assert_eq!(1, copy.len());
+            let c = &copy[0];
+            assert_eq!(o.type_name, c.type_name);
+            assert_eq!(o.xparams, c.xparams);
+            assert_eq!(o.xaxis, c.xaxis);
+
+            let copy_contents = sapi
+                .get_contents(&name, 0.0, 16.0, 0.0, 0.0)
+                .expect("getting copy contents");
+            assert_eq!(original_contents, copy_contents);
+
+            std::fs::remove_file(&filename).expect("removing test file");
+        }
+        teardown(chan, &papi, &bind_api);
+    }
+    #[test]
+    fn snapshot1d_1() {
+        // Parameterless snapshot spectra round trip through both json and
+        // ascii files:
+
+        let rocket = setup();
+        let (chan, papi, bind_api) = getstate(&rocket);
+
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&chan);
+        sapi.create_spectrum_snapshot1d("snap", 0.0, 16.0, 16)
+            .expect("snapshot spectrum");
+        let contents: Vec<spectrum_messages::Channel> = (0..4)
+            .map(|i| spectrum_messages::Channel {
+                chan_type: spectrum_messages::ChannelType::Bin,
+                x: (i * 2) as f64,
+                y: 0.0,
+                bin: 0,
+                value: (i + 1) as f64,
+            })
+            .collect();
+        sapi.fill_spectrum("snap", contents)
+            .expect("Filling snapshot");
+        let original = sapi.list_spectra("snap").expect("getting snap description");
+        let o = &original[0];
+        let original_contents = sapi
+            .get_contents("snap", 0.0, 16.0, 0.0, 0.0)
+            .expect("getting 'snap' contents");
+        assert_eq!(4, original_contents.len());
+
+        let client = Client::untracked(rocket).expect("Making rocket client");
+        for (i, format) in ["json", "ascii"].iter().enumerate() {
+            let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
+                .next()
+                .expect("making filename");
+            let write_uri = format!("/swrite?spectrum=snap&format={}&file={}", format, filename);
+            let write_response = client
+                .get(&write_uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!("OK", write_response.status);
+
+            let read_uri = format!("/sread?format={}&bind=false&filename={}", format, filename);
+            let read_response = client
+                .get(&read_uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing read JSON");
+            assert_eq!("OK", read_response.status);
+
+            let name = format!("snap_{}", i);
+            let copy = sapi.list_spectra(&name).expect("Getting copy description");
+            assert_eq!(1, copy.len());
+            let c = &copy[0];
+            assert_eq!(o.type_name, c.type_name);
+            assert!(c.xparams.is_empty());
+            assert_eq!(o.xaxis, c.xaxis);
+
+            let copy_contents = sapi
+                .get_contents(&name, 0.0, 16.0, 0.0, 0.0)
+                .expect("getting copy contents");
+            assert_eq!(original_contents, copy_contents);
+
+            std::fs::remove_file(&filename).expect("removing test file");
+        }
+        teardown(chan, &papi, &bind_api);
+    }
+    #[test]
     fn json2d_1() {
         // Empty 2d spectrum:
 
         // empty g2 spectrum (the metadata are correct):
 
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -2516,7 +3291,7 @@ mod swrite_tests {
     #[test]
     fn json2d_2() {
         // FIll the spectra this time:
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -2563,7 +3338,7 @@ mod swrite_tests {
 
         // empty g2 spectrum (the metadata are correct):
 
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -2614,7 +3389,7 @@ mod swrite_tests {
     #[test]
     fn ascii2d_2() {
         // FIll the spectra this time:
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -2661,7 +3436,7 @@ mod swrite_tests {
 
         // empty g2 spectrum (the metadata are correct):
 
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -2712,7 +3487,7 @@ mod swrite_tests {
     #[test]
     fn json2dsum_2() {
         // FIll the spectra this time:
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -2759,7 +3534,7 @@ mod swrite_tests {
 
         // empty g2 spectrum (the metadata are correct):
 
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -2810,7 +3585,7 @@ mod swrite_tests {
     #[test]
     fn ascii2dsum_2() {
         // FIll the spectra this time:
-        let filename = names::Generator::with_naming(names::Name::Numbered)
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
             .next()
             .expect("making filename");
         let rocket = setup();
@@ -2847,6 +3622,288 @@ mod swrite_tests {
             .expect("getting '2d-sum_0 contents");
         assert_eq!(original_contents, copy_contents);
 
+        std::fs::remove_file(&filename).expect("removing test file");
+        teardown(chan, &papi, &bind_api);
+    }
+    #[test]
+    fn root_1() {
+        // Filled 1d and 2d spectra read back from a ROOT macro match:
+
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
+            .next()
+            .expect("making filename");
+        let rocket = setup();
+        let (chan, papi, bind_api) = getstate(&rocket);
+
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&chan);
+        fill_test_spectra(&sapi);
+
+        let client = Client::untracked(rocket).expect("Making rocket client");
+        let write_uri = format!(
+            "/swrite?spectrum=oned&spectrum=twod&format=root&file={}",
+            filename
+        );
+        let write_response = client
+            .get(&write_uri)
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", write_response.status);
+
+        let read_uri = format!("/sread?format=root&bind=false&filename={}", filename);
+        let read_response = client
+            .get(&read_uri)
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing read JSON");
+        assert_eq!("OK", read_response.status);
+
+        for name in ["oned", "twod"] {
+            let o = &sapi.list_spectra(name).expect("Listing original")[0];
+            let copy_name = format!("{}_0", name);
+            let c = &sapi.list_spectra(&copy_name).expect("Listing copy")[0];
+            assert_eq!(o.type_name, c.type_name);
+            assert_eq!(o.xparams, c.xparams);
+            assert_eq!(o.yparams, c.yparams);
+            assert_eq!(o.xaxis, c.xaxis);
+            assert_eq!(o.yaxis, c.yaxis);
+
+            let original_contents = sapi
+                .get_contents(name, 0.0, 1024.0, 0.0, 1024.0)
+                .expect("getting original contents");
+            let copy_contents = sapi
+                .get_contents(&copy_name, 0.0, 1024.0, 0.0, 1024.0)
+                .expect("getting copy contents");
+            assert!(!original_contents.is_empty());
+            assert_eq!(original_contents, copy_contents);
+        }
+
+        std::fs::remove_file(&filename).expect("removing test file");
+        teardown(chan, &papi, &bind_api);
+    }
+    #[test]
+    fn root_2() {
+        // A ROOT saved histogram without axis titles has no parameters
+        // and is rejected:
+
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
+            .next()
+            .expect("making filename");
+        std::fs::write(
+            &filename,
+            "{\n   TH1F *h = new TH1F(\"h\",\"h\",4,-2,2);\n   h->SetBinContent(2,3.0);\n}\n",
+        )
+        .expect("Writing macro");
+        let rocket = setup();
+        let (chan, papi, bind_api) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Making rocket client");
+        let read_uri = format!("/sread?format=root&bind=false&filename={}", filename);
+        let read_response = client
+            .get(&read_uri)
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing read JSON");
+        assert_eq!("Unable to deserialize from file", read_response.status);
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&chan);
+        assert!(sapi.list_spectra("h*").expect("Listing").is_empty());
+
+        std::fs::remove_file(&filename).expect("removing test file");
+        teardown(chan, &papi, &bind_api);
+    }
+    #[test]
+    fn csv_1() {
+        // Filled 1d and 2d spectra read back from CSV files match:
+
+        let rocket = setup();
+        let (chan, papi, bind_api) = getstate(&rocket);
+
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&chan);
+        fill_test_spectra(&sapi);
+
+        let client = Client::untracked(rocket).expect("Making rocket client");
+        for name in ["oned", "twod"] {
+            let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
+                .next()
+                .expect("making filename");
+            let write_uri = format!("/swrite?spectrum={}&format=csv&file={}", name, filename);
+            let write_response = client
+                .get(&write_uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing JSON");
+            assert_eq!("OK", write_response.status);
+
+            let read_uri = format!("/sread?format=csv&bind=false&filename={}", filename);
+            let read_response = client
+                .get(&read_uri)
+                .dispatch()
+                .into_json::<GenericResponse>()
+                .expect("Parsing read JSON");
+            assert_eq!("OK", read_response.status);
+
+            let o = &sapi.list_spectra(name).expect("Listing original")[0];
+            let copy_name = format!("{}_0", name);
+            let c = &sapi.list_spectra(&copy_name).expect("Listing copy")[0];
+            assert_eq!(o.type_name, c.type_name);
+            assert_eq!(o.xparams, c.xparams);
+            assert_eq!(o.yparams, c.yparams);
+            assert_eq!(o.xaxis, c.xaxis);
+            assert_eq!(o.yaxis, c.yaxis);
+
+            let original_contents = sapi
+                .get_contents(name, 0.0, 1024.0, 0.0, 1024.0)
+                .expect("getting original contents");
+            let copy_contents = sapi
+                .get_contents(&copy_name, 0.0, 1024.0, 0.0, 1024.0)
+                .expect("getting copy contents");
+            assert!(!original_contents.is_empty());
+            assert_eq!(original_contents, copy_contents);
+
+            std::fs::remove_file(&filename).expect("removing test file");
+        }
+        teardown(chan, &papi, &bind_api);
+    }
+    #[test]
+    fn csv_2() {
+        // csv writes exactly one spectrum:
+
+        let rocket = setup();
+        let (chan, papi, bind_api) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Making rocket client");
+        let write_response = client
+            .get("/swrite?file=csv_2.csv&format=csv&spectrum=oned&spectrum=twod")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing write JSON");
+        assert_eq!(
+            "The csv format writes exactly one spectrum",
+            write_response.status
+        );
+        assert!(!std::path::Path::new("csv_2.csv").exists());
+
+        teardown(chan, &papi, &bind_api);
+    }
+    #[test]
+    fn npy2d_1() {
+        // Write a filled 2d spectrum as npy.  The array holds the counts
+        // and the sidecar the axes:
+
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
+            .next()
+            .expect("making filename");
+
+        let rocket = setup();
+        let (chan, papi, bind_api) = getstate(&rocket);
+
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&chan);
+        fill_test_spectra(&sapi);
+
+        let client = Client::untracked(rocket).expect("Making rocket client");
+        let write_uri = format!("/swrite?file={}&format=npy&spectrum=twod", filename);
+        let write_response = client
+            .get(&write_uri)
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing write JSON");
+        assert_eq!("OK", write_response.status);
+
+        let bytes = std::fs::read(&filename).expect("Reading npy file");
+        let hlen = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        let header = String::from_utf8(bytes[10..10 + hlen].to_vec()).unwrap();
+        assert!(header.contains("'shape': (258, 258)"));
+        assert_eq!(258 * 258 * 8, bytes.len() - 10 - hlen);
+        let total: u64 = bytes[10 + hlen..]
+            .chunks(8)
+            .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+            .sum();
+        let contents = sapi
+            .get_contents("twod", 0.0, 256.0, 0.0, 256.0)
+            .expect("Getting contents");
+        assert_eq!(contents.iter().map(|c| c.value as u64).sum::<u64>(), total);
+
+        let sidecar = format!("{}.json", filename);
+        let metadata = json::from_str::<npy::NpyMetadata>(
+            &std::fs::read_to_string(&sidecar).expect("Reading sidecar"),
+        )
+        .expect("Parsing sidecar");
+        assert_eq!(vec![258, 258], metadata.shape);
+        assert_eq!("twod", metadata.definition.name);
+        assert_eq!(Some((0.0, 256.0, 258)), metadata.definition.x_axis);
+
+        std::fs::remove_file(&filename).expect("removing test file");
+        std::fs::remove_file(&sidecar).expect("removing sidecar file");
+        teardown(chan, &papi, &bind_api);
+    }
+    #[test]
+    fn npy_2() {
+        // npy writes exactly one spectrum:
+
+        let rocket = setup();
+        let (chan, papi, bind_api) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Making rocket client");
+        let write_response = client
+            .get("/swrite?file=npy_2.npy&format=npy&spectrum=oned&spectrum=twod")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing write JSON");
+        assert_eq!(
+            "The npy format writes exactly one spectrum",
+            write_response.status
+        );
+        assert!(!std::path::Path::new("npy_2.npy").exists());
+
+        teardown(chan, &papi, &bind_api);
+    }
+    #[test]
+    fn job_1() {
+        // Write spectra as a job and wait for it to complete:
+
+        let filename = namegen::Generator::with_naming(namegen::Name::Numbered)
+            .next()
+            .expect("making filename");
+        let rocket = setup();
+        let (chan, papi, bind_api) = getstate(&rocket);
+        let jobs = rocket
+            .state::<jobs::SharedJobStore>()
+            .expect("Getting job store")
+            .clone();
+
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&chan);
+        fill_test_spectra(&sapi);
+
+        let client = Client::untracked(rocket).expect("Making rocket client");
+        let write_uri = format!(
+            "/swrite?file={}&format=json&spectrum=oned&spectrum=twod&job=true",
+            filename
+        );
+        let write_response = client
+            .get(&write_uri)
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing write JSON");
+        assert_eq!("OK", write_response.status);
+        let id = write_response.detail.parse::<u64>().expect("Job id");
+
+        let mut job = jobs.status(id).expect("Job status");
+        while job.status == jobs::RUNNING {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            job = jobs.status(id).expect("Job status");
+        }
+        assert_eq!(jobs::COMPLETED, job.status);
+        assert_eq!("swrite", job.kind);
+        assert_eq!((2, 2), (job.done, job.total));
+
+        let spectra = json::from_str::<Vec<SpectrumFileData>>(
+            &std::fs::read_to_string(&filename).expect("Reading file"),
+        )
+        .expect("Parsing file");
+        assert_eq!(2, spectra.len());
+        assert_eq!("oned", spectra[0].definition.name);
+        assert_eq!("twod", spectra[1].definition.name);
+
         std::fs::remove_file(&filename).expect("removing test file");
         teardown(chan, &papi, &bind_api);
     }
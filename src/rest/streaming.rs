@@ -0,0 +1,166 @@
+//! Support for streaming large JSON replies.
+//!
+//! Some replies (e.g. the spectrum list when thousands of spectra
+//! are defined or the contents of a large 2-d spectrum) are big
+//! enough that building the entire reply struct and then
+//! serializing it into one string costs a noticeable amount of
+//! server memory and delays the first byte the client sees.
+//!
+//! A JsonStream is a Rocket responder that produces its body
+//! incrementally as a chunked response.  The body consists of a
+//! fixed prefix, a JSON array whose elements are serialized one at a
+//! time as the client reads them and a fixed suffix.  The result is
+//! a single JSON document identical to what Json would have produced
+//! from the equivalent struct so clients need not change.
+//!
+use rocket::futures::stream;
+use rocket::http::ContentType;
+use rocket::response::{self, stream::TextStream, Responder};
+use rocket::serde::{json, Serialize};
+use rocket::Request;
+
+type JsonChunks = Box<dyn Iterator<Item = String> + Send>;
+
+/// The responder.  Construct it with _array_ or _object_.
+pub struct JsonStream {
+    chunks: JsonChunks,
+}
+
+impl JsonStream {
+    ///
+    /// Create a stream that generates _prefix_, the elements of _items_
+    /// as a JSON array and then _suffix_.
+    ///
+    /// ### Parameters:
+    /// *  prefix - JSON text that precedes the array, e.g. ```{"detail":```
+    /// *  items  - Iterator over the array elements.  Elements are only
+    /// serialized when the body is read.
+    /// *  suffix - JSON text that follows the array, e.g. ```}```
+    ///
+    /// ### Returns
+    ///   JsonStream
+    pub fn array<T, I>(prefix: String, items: I, suffix: &'static str) -> JsonStream
+    where
+        T: Serialize,
+        I: Iterator<Item = T> + Send + 'static,
+    {
+        let elements = items.enumerate().map(|(i, item)| {
+            let element = json::to_string(&item).unwrap_or_else(|_| String::from("null"));
+            if i == 0 {
+                element
+            } else {
+                format!(",{}", element)
+            }
+        });
+        let chunks = std::iter::once(format!("{}[", prefix))
+            .chain(elements)
+            .chain(std::iter::once(format!("]{}", suffix)));
+
+        JsonStream {
+            chunks: Box::new(chunks),
+        }
+    }
+    ///
+    /// Create a stream for the common reply shape of
+    /// ```{"status": status, "detail": [items...]}```.
+    ///
+    /// ### Parameters:
+    /// *  status - the value of the status field.
+    /// *  items  - Iterator over the elements of the detail array.
+    ///
+    /// ### Returns
+    ///    JsonStream
+    pub fn object<T, I>(status: &str, items: I) -> JsonStream
+    where
+        T: Serialize,
+        I: Iterator<Item = T> + Send + 'static,
+    {
+        JsonStream::array(
+            format!("{{\"status\":{},\"detail\":", quote(status)),
+            items,
+            "}",
+        )
+    }
+}
+
+/// Quote a string as a JSON string value.
+pub fn quote(s: &str) -> String {
+    json::to_string(s).unwrap_or_else(|_| String::from("\"\""))
+}
+
+impl<'r> Responder<'r, 'r> for JsonStream {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'r> {
+        let mut response = TextStream(stream::iter(self.chunks)).respond_to(request)?;
+        response.set_header(ContentType::JSON);
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod streaming_tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+    use rocket::serde::json::Value;
+
+    #[get("/empty")]
+    fn empty() -> JsonStream {
+        JsonStream::object("OK", Vec::<u32>::new().into_iter())
+    }
+    #[get("/numbers")]
+    fn numbers() -> JsonStream {
+        JsonStream::object("OK", 0..5)
+    }
+    #[get("/nested")]
+    fn nested() -> JsonStream {
+        JsonStream::array(
+            format!(
+                "{{\"status\":{},\"detail\":{{\"count\":2,\"items\":",
+                quote("a \"quoted\" status")
+            ),
+            vec![String::from("one"), String::from("two")].into_iter(),
+            "}}",
+        )
+    }
+
+    fn client() -> Client {
+        Client::tracked(rocket::build().mount("/", routes![empty, numbers, nested]))
+            .expect("Making client")
+    }
+
+    #[test]
+    fn empty_1() {
+        let client = client();
+        let reply = client.get("/empty").dispatch();
+        assert_eq!(Some(ContentType::JSON), reply.content_type());
+        let text = reply.into_string().expect("Reading body");
+        assert_eq!("{\"status\":\"OK\",\"detail\":[]}", text);
+    }
+    #[test]
+    fn numbers_1() {
+        let client = client();
+        let reply = client
+            .get("/numbers")
+            .dispatch()
+            .into_json::<Value>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply["status"]);
+        let detail = reply["detail"].as_array().expect("detail not an array");
+        assert_eq!(5, detail.len());
+        for (i, n) in detail.iter().enumerate() {
+            assert_eq!(i as u64, n.as_u64().expect("Not a number"));
+        }
+    }
+    #[test]
+    fn nested_1() {
+        let client = client();
+        let reply = client
+            .get("/nested")
+            .dispatch()
+            .into_json::<Value>()
+            .expect("Parsing JSON");
+        assert_eq!("a \"quoted\" status", reply["status"]);
+        assert_eq!(2, reply["detail"]["count"]);
+        assert_eq!("one", reply["detail"]["items"][0]);
+        assert_eq!("two", reply["detail"]["items"][1]);
+    }
+}
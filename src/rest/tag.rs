@@ -0,0 +1,510 @@
+//! This module provides the /spectcl/tag REST interface.  Spectrum
+//! tags (see src/tags/mod.rs) are arbitrary strings attached to
+//! spectra that group them for bulk operations:
+//!
+//! *  /spectcl/tag/add - add tags to a spectrum.
+//! *  /spectcl/tag/remove - remove tags from a spectrum.
+//! *  /spectcl/tag/list - list spectra and their tags.
+//! *  /spectcl/tag/clear - clear the spectra with tags.
+//! *  /spectcl/tag/bind - bind the spectra with tags into shared memory.
+//! *  /spectcl/tag/write - write the spectra with tags to file.
+//!
+//! Where a request takes more than one tag, the spectra it operates on
+//! are those that have all of the tags.  Before spectra are looked up
+//! by tag, the tags of spectra that no longer exist are forgotten.
+//!
+//! We depend on the main to have set the Rocket state to include a
+//! tags::SharedTagStore.
+//!
+use glob::Pattern;
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+use std::collections::HashSet;
+
+use super::*;
+use crate::messaging::spectrum_messages::SpectrumMessageClient;
+use crate::processing::endrun;
+use crate::tags;
+
+// Forget the tags of spectra that were deleted and return the spectra
+// that have all of the tags:
+
+fn existing_tagged(
+    tags: &[String],
+    store: &tags::SharedTagStore,
+    api: &SpectrumMessageClient,
+) -> Result<Vec<String>, String> {
+    let existing: HashSet<String> = api.list_spectra("*")?.into_iter().map(|s| s.name).collect();
+    store.retain(|name| existing.contains(name));
+    Ok(store.tagged(tags))
+}
+
+// Requests that operate on spectra by tag need at least one tag and
+// at least one spectrum with those tags:
+
+fn tagged_spectra(
+    tags: &[String],
+    store: &tags::SharedTagStore,
+    api: &SpectrumMessageClient,
+) -> Result<Vec<String>, GenericResponse> {
+    if tags.is_empty() {
+        return Err(GenericResponse::err(
+            "No tags",
+            "At least one tag must be given",
+        ));
+    }
+    match existing_tagged(tags, store, api) {
+        Ok(names) if names.is_empty() => Err(GenericResponse::err(
+            "No spectra have the tags",
+            &tags.join(", "),
+        )),
+        Ok(names) => Ok(names),
+        Err(s) => Err(GenericResponse::err("Unable to list spectra", &s)),
+    }
+}
+
+/// Add tags to a spectrum.
+///
+/// ### Query parameters
+/// * spectrum - name of the spectrum, it must exist.
+/// * tag - a tag to add.  Can be supplied more than once.
+///
+#[get("/add?<spectrum>&<tag>")]
+pub fn add_tags(
+    spectrum: String,
+    tag: Vec<String>,
+    store: &State<tags::SharedTagStore>,
+    hg: &State<SharedHistogramChannel>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    if tag.is_empty() {
+        return Json(GenericResponse::err(
+            "No tags",
+            "At least one tag must be given",
+        ));
+    }
+    let api = SpectrumMessageClient::new(&hg.inner().lock().unwrap());
+    match api.list_spectra(&Pattern::escape(&spectrum)) {
+        Ok(l) if l.is_empty() => {
+            return Json(GenericResponse::err(
+                "No such spectrum",
+                &format!("There is no spectrum named {}", spectrum),
+            ))
+        }
+        Ok(_) => {}
+        Err(s) => return Json(GenericResponse::err("Unable to list spectra", &s)),
+    }
+    Json(match store.inner().add(&spectrum, &tag) {
+        Ok(()) => GenericResponse::ok(""),
+        Err(s) => GenericResponse::err("Invalid tag", &s),
+    })
+}
+
+/// Remove tags from a spectrum.  If no tag is given, all of the
+/// spectrum's tags are removed.  It is not an error to remove a tag
+/// the spectrum does not have.
+///
+/// ### Query parameters
+/// * spectrum - name of the spectrum.
+/// * tag - a tag to remove.  Can be supplied more than once.
+///
+#[get("/remove?<spectrum>&<tag>")]
+pub fn remove_tags(
+    spectrum: String,
+    tag: Vec<String>,
+    store: &State<tags::SharedTagStore>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    store.inner().remove(&spectrum, &tag);
+    Json(GenericResponse::ok(""))
+}
+
+// What we get per spectrum in a listing:
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SpectrumTags {
+    spectrum: String,
+    tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct TagListResponse {
+    status: String,
+    detail: Vec<SpectrumTags>,
+}
+
+/// List the tagged spectra whose names match the optional pattern
+/// (defaults to "*") in alphabetical order by name.  If tags are
+/// given, only the spectra with all of them are listed.
+#[get("/list?<pattern>&<tag>")]
+pub fn list_tags(
+    pattern: OptionalString,
+    tag: Vec<String>,
+    store: &State<tags::SharedTagStore>,
+    hg: &State<SharedHistogramChannel>,
+) -> Json<TagListResponse> {
+    let pattern = pattern.unwrap_or_else(|| String::from("*"));
+    let api = SpectrumMessageClient::new(&hg.inner().lock().unwrap());
+    let listing = Pattern::new(&pattern)
+        .map_err(|e| String::from(e.msg))
+        .and_then(|p| Ok((p, existing_tagged(&tag, store.inner(), &api)?)));
+    Json(match listing {
+        Ok((pattern, tagged)) => TagListResponse {
+            status: String::from("OK"),
+            detail: tagged
+                .into_iter()
+                .filter(|name| pattern.matches(name))
+                .map(|name| SpectrumTags {
+                    tags: store.inner().tags(&name),
+                    spectrum: name,
+                })
+                .collect(),
+        },
+        Err(s) => TagListResponse {
+            status: format!("Unable to list tags: {}", s),
+            detail: vec![],
+        },
+    })
+}
+
+/// Clear the spectra that have the tags.
+///
+/// ### Query parameters
+/// * tag - a tag the spectra must have.  At least one is required.
+/// * gatestats - optional flag, if true, the statistics of the gates
+///   of the spectra are cleared as well.
+///
+#[get("/clear?<tag>&<gatestats>")]
+pub fn clear_tagged(
+    tag: Vec<String>,
+    gatestats: OptionalFlag,
+    store: &State<tags::SharedTagStore>,
+    hg: &State<SharedHistogramChannel>,
+    bind: &State<SharedBinderChannel>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    let api = SpectrumMessageClient::new(&hg.inner().lock().unwrap());
+    let names = match tagged_spectra(&tag, store.inner(), &api) {
+        Ok(n) => n,
+        Err(response) => return Json(response),
+    };
+    let bind_api = binder::BindingApi::new(&bind.inner().lock().unwrap());
+    for name in names.iter() {
+        let pattern = Pattern::escape(name);
+        let result = if gatestats.unwrap_or(false) {
+            api.clear_spectra_and_gate_statistics(&pattern)
+        } else {
+            api.clear_spectra(&pattern)
+        };
+        // Clear the shared memory copy of bound spectra too:
+
+        if let Err(s) = result.and_then(|_| bind_api.clear_spectra(&pattern)) {
+            return Json(GenericResponse::err(
+                &format!("Failed to clear spectrum {}", name),
+                &s,
+            ));
+        }
+    }
+    Json(GenericResponse::ok(""))
+}
+
+/// Bind the spectra that have the tags into shared memory.  Spectra
+/// that are already bound are left alone.
+///
+/// ### Query parameters
+/// * tag - a tag the spectra must have.  At least one is required.
+/// * overflows, chantype - as for /spectcl/sbind/sbind.
+///
+#[get("/bind?<tag>&<overflows>&<chantype>")]
+pub fn bind_tagged(
+    tag: Vec<String>,
+    overflows: Option<bool>,
+    chantype: OptionalString,
+    store: &State<tags::SharedTagStore>,
+    hg: &State<SharedHistogramChannel>,
+    bind: &State<SharedBinderChannel>,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    let width = match sbind::channel_width(&chantype) {
+        Ok(w) => w,
+        Err(response) => return Json(response),
+    };
+    let api = SpectrumMessageClient::new(&hg.inner().lock().unwrap());
+    let names = match tagged_spectra(&tag, store.inner(), &api) {
+        Ok(n) => n,
+        Err(response) => return Json(response),
+    };
+    let bind_api = binder::BindingApi::new(&bind.inner().lock().unwrap());
+    Json(sbind::bind_unbound_spectra(
+        &names,
+        &bind_api,
+        overflows.unwrap_or(true),
+        width,
+    ))
+}
+
+/// Write the spectra that have the tags to file.
+///
+/// ### Query parameters
+/// * tag - a tag the spectra must have.  At least one is required.
+/// * file - the file to write.  Existing files are overwritten.
+/// * format - "json" (default), "ascii" or "root".
+///
+#[get("/write?<tag>&<file>&<format>")]
+pub fn write_tagged(
+    tag: Vec<String>,
+    file: String,
+    format: OptionalString,
+    store: &State<tags::SharedTagStore>,
+    hg: &State<SharedHistogramChannel>,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    let format = format
+        .unwrap_or_else(|| String::from("json"))
+        .to_ascii_lowercase();
+    if !endrun::SUPPORTED_FORMATS.contains(&format.as_str()) {
+        return Json(GenericResponse::err("Invalid format", &format));
+    }
+    let api = SpectrumMessageClient::new(&hg.inner().lock().unwrap());
+    let names = match tagged_spectra(&tag, store.inner(), &api) {
+        Ok(n) => n,
+        Err(response) => return Json(response),
+    };
+    let patterns: Vec<String> = names.iter().map(|n| Pattern::escape(n)).collect();
+    Json(
+        match endrun::get_spectra(&patterns, &api)
+            .and_then(|spectra| endrun::write_file(&file, &format, &spectra))
+        {
+            Ok(()) => GenericResponse::ok(""),
+            Err(s) => GenericResponse::err("Unable to write spectra", &s),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tag_tests {
+    use super::*;
+    use crate::messaging::parameter_messages::ParameterMessageClient;
+    use crate::parameters::{Event, EventParameter};
+    use crate::rest::spectrumio;
+    use crate::test::rest_common;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::serde::json;
+    use rocket::Build;
+    use rocket::Rocket;
+    use std::fs;
+
+    fn setup() -> Rocket<Build> {
+        rest_common::setup()
+            .manage(tags::SharedTagStore::new())
+            .mount(
+                "/",
+                routes![
+                    add_tags,
+                    remove_tags,
+                    list_tags,
+                    clear_tagged,
+                    bind_tagged,
+                    write_tagged
+                ],
+            )
+    }
+    fn store(r: &Rocket<Build>) -> tags::SharedTagStore {
+        r.state::<tags::SharedTagStore>()
+            .expect("Tag store")
+            .clone()
+    }
+    // Make spectra s1, s2 and s3 with a count in each:
+
+    fn make_spectra(chan: &mpsc::Sender<Request>) -> SpectrumMessageClient {
+        let papi = ParameterMessageClient::new(chan);
+        papi.create_parameter("p").expect("Making parameter");
+        let id = papi.list_parameters("p").expect("Listing")[0].get_id();
+        let api = SpectrumMessageClient::new(chan);
+        for name in ["s1", "s2", "s3"] {
+            api.create_spectrum_1d(name, "p", 0.0, 10.0, 10)
+                .expect("Making spectrum");
+        }
+        let event: Event = vec![EventParameter::new(id, 5.0)];
+        api.process_events(&[event]).expect("Processing event");
+        api
+    }
+    fn get(client: &Client, uri: &str) -> GenericResponse {
+        client
+            .get(uri)
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON")
+    }
+    fn list(client: &Client, uri: &str) -> TagListResponse {
+        client
+            .get(uri)
+            .dispatch()
+            .into_json::<TagListResponse>()
+            .expect("Parsing JSON")
+    }
+    fn counts(api: &SpectrumMessageClient, name: &str) -> usize {
+        api.get_contents(name, 0.0, 10.0, 0.0, 0.0)
+            .expect("Getting contents")
+            .len()
+    }
+
+    #[test]
+    fn add_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        let tags = store(&rocket);
+        make_spectra(&chan);
+        let client = Client::untracked(rocket).expect("Making client");
+
+        assert_eq!(
+            "OK",
+            get(&client, "/add?spectrum=s1&tag=pid&tag=det%3Dhagrid").status
+        );
+        assert_eq!(
+            vec![String::from("det=hagrid"), String::from("pid")],
+            tags.tags("s1")
+        );
+        // Errors:
+
+        assert_eq!("No tags", get(&client, "/add?spectrum=s1").status);
+        assert_eq!(
+            "No such spectrum",
+            get(&client, "/add?spectrum=nosuch&tag=pid").status
+        );
+        assert_eq!("Invalid tag", get(&client, "/add?spectrum=s2&tag=").status);
+        assert!(tags.tags("s2").is_empty());
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn remove_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        let tags = store(&rocket);
+        make_spectra(&chan);
+        let client = Client::untracked(rocket).expect("Making client");
+
+        get(&client, "/add?spectrum=s1&tag=a&tag=b&tag=c");
+        assert_eq!("OK", get(&client, "/remove?spectrum=s1&tag=b").status);
+        assert_eq!(vec![String::from("a"), String::from("c")], tags.tags("s1"));
+        assert_eq!("OK", get(&client, "/remove?spectrum=s1").status);
+        assert!(tags.tags("s1").is_empty());
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn list_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        let tags = store(&rocket);
+        let api = make_spectra(&chan);
+        let client = Client::untracked(rocket).expect("Making client");
+
+        get(&client, "/add?spectrum=s2&tag=pid&tag=monitoring");
+        get(&client, "/add?spectrum=s1&tag=pid");
+        get(&client, "/add?spectrum=s3&tag=monitoring");
+
+        let reply = list(&client, "/list");
+        assert_eq!("OK", reply.status);
+        let names: Vec<String> = reply.detail.iter().map(|t| t.spectrum.clone()).collect();
+        assert_eq!(vec!["s1", "s2", "s3"], names);
+        assert_eq!(vec!["monitoring", "pid"], reply.detail[1].tags);
+
+        let reply = list(&client, "/list?tag=pid&tag=monitoring");
+        assert_eq!(1, reply.detail.len());
+        assert_eq!("s2", reply.detail[0].spectrum);
+        let reply = list(&client, "/list?pattern=s3&tag=pid");
+        assert!(reply.detail.is_empty());
+
+        // Deleted spectra are forgotten:
+
+        api.delete_spectrum("s1").expect("Deleting s1");
+        let reply = list(&client, "/list?tag=pid");
+        assert_eq!(1, reply.detail.len());
+        assert!(tags.tags("s1").is_empty());
+
+        let reply = list(&client, "/list?pattern=%5B");
+        assert!(reply.status.starts_with("Unable to list tags"));
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn clear_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        let api = make_spectra(&chan);
+        let client = Client::untracked(rocket).expect("Making client");
+
+        get(&client, "/add?spectrum=s1&tag=pid");
+        get(&client, "/add?spectrum=s2&tag=pid&tag=monitoring");
+        assert_eq!("No tags", get(&client, "/clear").status);
+        assert_eq!(
+            "No spectra have the tags",
+            get(&client, "/clear?tag=nosuch").status
+        );
+
+        assert_eq!("OK", get(&client, "/clear?tag=pid").status);
+        assert_eq!(0, counts(&api, "s1"));
+        assert_eq!(0, counts(&api, "s2"));
+        assert_eq!(1, counts(&api, "s3"));
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn bind_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        make_spectra(&chan);
+        let client = Client::untracked(rocket).expect("Making client");
+
+        get(&client, "/add?spectrum=s1&tag=monitoring");
+        get(&client, "/add?spectrum=s3&tag=monitoring");
+        assert_eq!(
+            "Invalid chantype",
+            get(&client, "/bind?tag=monitoring&chantype=nybble").status
+        );
+        assert_eq!("OK", get(&client, "/bind?tag=monitoring").status);
+        assert_eq!("OK", get(&client, "/bind?tag=monitoring").status);
+
+        let mut bound: Vec<String> = bapi
+            .list_bindings("*")
+            .expect("Listing bindings")
+            .into_iter()
+            .map(|b| b.1)
+            .collect();
+        bound.sort();
+        assert_eq!(vec!["s1", "s3"], bound);
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn write_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        make_spectra(&chan);
+        let client = Client::untracked(rocket).expect("Making client");
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let file = dir.path().join("pid.json");
+
+        get(&client, "/add?spectrum=s1&tag=pid");
+        get(&client, "/add?spectrum=s2&tag=pid");
+        let uri = format!("/write?tag=pid&file={}", file.to_str().unwrap());
+        assert_eq!("OK", get(&client, &uri).status);
+        let text = fs::read_to_string(&file).expect("Reading file");
+        let spectra: Vec<spectrumio::SpectrumFileData> =
+            json::from_str(&text).expect("Parsing file");
+        let names: Vec<String> = spectra.into_iter().map(|s| s.definition.name).collect();
+        assert_eq!(vec!["s1", "s2"], names);
+
+        let uri = format!("/write?tag=pid&file={}&format=npy", file.to_str().unwrap());
+        assert_eq!("Invalid format", get(&client, &uri).status);
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+}
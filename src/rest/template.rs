@@ -0,0 +1,416 @@
+//! This module provides the /spectcl/template REST interface.  Spectrum
+//! templates (see src/templates/mod.rs) are named spectrum types and
+//! axis definitions with an optional gate.  Spectra are made from a
+//! template by supplying just their name and parameters.
+//!
+//! *  /spectcl/template/create - define (or redefine) a template.
+//! *  /spectcl/template/list - list templates.
+//! *  /spectcl/template/delete - delete a template.
+//! *  /spectcl/template/make - make a spectrum from a template.
+//!
+//! We depend on the main to have set the Rocket state to include a
+//! templates::SharedTemplateStore.
+//!
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+
+use super::*;
+use crate::messaging::spectrum_messages::SpectrumMessageClient;
+use crate::templates;
+
+/// Define a spectrum template.  If a template with the same name exists,
+/// it is replaced.
+///
+/// ### Query parameters
+/// * name - name of the template.
+/// * type - SpecTcl spectrum type as in /spectcl/spectrum/create.
+/// * axes - axis definitions as in /spectcl/spectrum/create.
+/// * gate - optional condition spectra made from the template are
+/// gated on.  The condition need not exist until spectra are made.
+///
+#[get("/create?<name>&<type>&<axes>&<gate>")]
+pub fn create_template(
+    name: String,
+    r#type: String,
+    axes: String,
+    gate: OptionalString,
+    state: &State<templates::SharedTemplateStore>,
+    policy: &State<names::NamePolicy>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    if let Err(s) = policy.check_name("Template", &name) {
+        return Json(GenericResponse::err("Invalid template name", &s));
+    }
+    if let Err(s) = spectrum::check_type_axes(&r#type, &axes) {
+        return Json(GenericResponse::err("Invalid template definition", &s));
+    }
+    state.inner().define(templates::SpectrumTemplate {
+        name,
+        spectrum_type: r#type,
+        axes,
+        gate,
+    });
+    Json(GenericResponse::ok(""))
+}
+
+// What we get per template in a listing:
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Template {
+    name: String,
+    #[serde(rename = "type")]
+    spectrum_type: String,
+    axes: String,
+    gate: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct TemplateListResponse {
+    status: String,
+    detail: Vec<Template>,
+}
+
+/// List the templates whose names match the optional pattern
+/// (defaults to "*") in alphabetical order by name.
+#[get("/list?<pattern>")]
+pub fn list_templates(
+    pattern: OptionalString,
+    state: &State<templates::SharedTemplateStore>,
+) -> Json<TemplateListResponse> {
+    let pattern = pattern.unwrap_or_else(|| String::from("*"));
+    match state.inner().list(&pattern) {
+        Ok(l) => Json(TemplateListResponse {
+            status: String::from("OK"),
+            detail: l
+                .into_iter()
+                .map(|t| Template {
+                    name: t.name,
+                    spectrum_type: t.spectrum_type,
+                    axes: t.axes,
+                    gate: t.gate,
+                })
+                .collect(),
+        }),
+        Err(s) => Json(TemplateListResponse {
+            status: format!("Unable to list templates: {}", s),
+            detail: vec![],
+        }),
+    }
+}
+
+/// Delete a template.  Spectra that were made from it are not affected.
+#[get("/delete?<name>")]
+pub fn delete_template(
+    name: String,
+    state: &State<templates::SharedTemplateStore>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    Json(match state.inner().delete(&name) {
+        Ok(()) => GenericResponse::ok(""),
+        Err(s) => GenericResponse::err("Unable to delete template", &s),
+    })
+}
+
+/// Make a spectrum from a template.  If the template has a gate, the
+/// spectrum is gated on it.  If that fails, the spectrum is deleted so
+/// that the request either fully succeeds or does nothing.
+///
+/// ### Query parameters
+/// * template - name of the template.
+/// * name - name of the new spectrum.
+/// * parameters - the spectrum parameters as in /spectcl/spectrum/create.
+///
+#[get("/make?<template>&<name>&<parameters>")]
+pub fn make_from_template(
+    template: String,
+    name: String,
+    parameters: String,
+    state: &State<templates::SharedTemplateStore>,
+    hg: &State<SharedHistogramChannel>,
+    policy: &State<names::NamePolicy>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    let t = match state.inner().get(&template) {
+        Some(t) => t,
+        None => {
+            return Json(GenericResponse::err(
+                "No such template",
+                &format!("There is no spectrum template named {}", template),
+            ))
+        }
+    };
+    let api = SpectrumMessageClient::new(&hg.inner().lock().unwrap());
+    if let Err(s) = policy.check_spectrum_name(&name, &api) {
+        return Json(GenericResponse::err("Invalid spectrum name", &s));
+    }
+    let reply = spectrum::make_spectrum_of_type(&name, &t.spectrum_type, &parameters, &t.axes, hg);
+    if reply.status != "OK" {
+        return Json(reply);
+    }
+    if let Some(gate) = t.gate {
+        if let Err(s) = api.gate_spectrum(&name, &gate) {
+            let _ = api.delete_spectrum(&name);
+            return Json(GenericResponse::err(
+                "Failed to gate spectrum",
+                &format!("{} could not be gated on {}: {}", name, gate, s),
+            ));
+        }
+    }
+    Json(GenericResponse::ok(""))
+}
+
+#[cfg(test)]
+mod template_tests {
+    use super::*;
+    use crate::messaging::condition_messages::ConditionMessageClient;
+    use crate::messaging::parameter_messages::ParameterMessageClient;
+    use crate::test::rest_common;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+
+    fn setup() -> Rocket<Build> {
+        rest_common::setup()
+            .manage(templates::SharedTemplateStore::new())
+            .mount(
+                "/",
+                routes![
+                    create_template,
+                    list_templates,
+                    delete_template,
+                    make_from_template
+                ],
+            )
+    }
+    fn store(r: &Rocket<Build>) -> templates::SharedTemplateStore {
+        r.state::<templates::SharedTemplateStore>()
+            .expect("Template store")
+            .clone()
+    }
+
+    #[test]
+    fn create_1() {
+        // Valid templates are stored:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        let templates = store(&rocket);
+        let client = Client::untracked(rocket).expect("Making client");
+
+        let reply = client
+            .get("/create?name=energy&type=1&axes=%7B0%201024%201024%7D&gate=cut")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        let t = templates.get("energy").expect("Getting template");
+        assert_eq!("1", t.spectrum_type);
+        assert_eq!("{0 1024 1024}", t.axes);
+        assert_eq!(Some(String::from("cut")), t.gate);
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn create_2() {
+        // Bad types and axes are rejected:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        let templates = store(&rocket);
+        let client = Client::untracked(rocket).expect("Making client");
+
+        let reply = client
+            .get("/create?name=bad&type=x&axes=%7B0%201024%201024%7D")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("Invalid template definition", reply.status);
+
+        // 2d needs two axes:
+
+        let reply = client
+            .get("/create?name=bad&type=2&axes=%7B0%201024%201024%7D")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("Invalid template definition", reply.status);
+
+        let reply = client
+            .get("/create?name=bad&type=1&axes=%7B0%200%201024%7D")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("Invalid template definition", reply.status);
+
+        assert!(templates.list("*").unwrap().is_empty());
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn list_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        let client = Client::untracked(rocket).expect("Making client");
+
+        client
+            .get("/create?name=pid&type=2&axes=%7B0%20100%20100%7D%20%7B0%20200%20200%7D")
+            .dispatch();
+        client
+            .get("/create?name=energy&type=1&axes=%7B0%201024%201024%7D")
+            .dispatch();
+
+        let reply = client
+            .get("/list")
+            .dispatch()
+            .into_json::<TemplateListResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(2, reply.detail.len());
+        assert_eq!("energy", reply.detail[0].name);
+        assert_eq!("1", reply.detail[0].spectrum_type);
+        assert_eq!("pid", reply.detail[1].name);
+        assert_eq!("{0 100 100} {0 200 200}", reply.detail[1].axes);
+        assert!(reply.detail[1].gate.is_none());
+
+        let reply = client
+            .get("/list?pattern=p*")
+            .dispatch()
+            .into_json::<TemplateListResponse>()
+            .expect("Parsing JSON");
+        assert_eq!(1, reply.detail.len());
+        assert_eq!("pid", reply.detail[0].name);
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn delete_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        let templates = store(&rocket);
+        let client = Client::untracked(rocket).expect("Making client");
+
+        client
+            .get("/create?name=energy&type=1&axes=%7B0%201024%201024%7D")
+            .dispatch();
+        let reply = client
+            .get("/delete?name=energy")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert!(templates.get("energy").is_none());
+
+        let reply = client
+            .get("/delete?name=energy")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("Unable to delete template", reply.status);
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn make_1() {
+        // Make an ungated spectrum from a template:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        let client = Client::untracked(rocket).expect("Making client");
+        ParameterMessageClient::new(&chan)
+            .create_parameter("det.0")
+            .expect("Making parameter");
+
+        client
+            .get("/create?name=energy&type=1&axes=%7B0%201024%20512%7D")
+            .dispatch();
+        let reply = client
+            .get("/make?template=energy&name=det.0.e&parameters=det.0")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        let l = SpectrumMessageClient::new(&chan)
+            .list_spectra("det.0.e")
+            .expect("Listing");
+        assert_eq!(1, l.len());
+        assert_eq!("1D", l[0].type_name);
+        assert_eq!(vec![String::from("det.0")], l[0].xparams);
+        let x = l[0].xaxis.expect("X axis");
+        assert_eq!(0.0, x.low);
+        assert_eq!(1024.0, x.high);
+        assert_eq!(514, x.bins); // Includes over/underflow.
+        assert!(l[0].gate.is_none());
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn make_2() {
+        // Spectra are gated on the template's gate:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        let client = Client::untracked(rocket).expect("Making client");
+        ParameterMessageClient::new(&chan)
+            .create_parameter("det.0")
+            .expect("Making parameter");
+        ConditionMessageClient::new(&chan).create_true_condition("ok");
+
+        client
+            .get("/create?name=energy&type=1&axes=%7B0%201024%20512%7D&gate=ok")
+            .dispatch();
+        let reply = client
+            .get("/make?template=energy&name=det.0.e&parameters=det.0")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        let l = SpectrumMessageClient::new(&chan)
+            .list_spectra("det.0.e")
+            .expect("Listing");
+        assert_eq!(Some(String::from("ok")), l[0].gate);
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn make_3() {
+        // Failures to gate leave no spectrum and unknown templates fail:
+
+        let rocket = setup();
+        let (chan, papi, bapi) = rest_common::get_state(&rocket);
+        let client = Client::untracked(rocket).expect("Making client");
+        ParameterMessageClient::new(&chan)
+            .create_parameter("det.0")
+            .expect("Making parameter");
+
+        client
+            .get("/create?name=energy&type=1&axes=%7B0%201024%20512%7D&gate=missing")
+            .dispatch();
+        let reply = client
+            .get("/make?template=energy&name=det.0.e&parameters=det.0")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("Failed to gate spectrum", reply.status);
+        assert!(SpectrumMessageClient::new(&chan)
+            .list_spectra("*")
+            .expect("Listing")
+            .is_empty());
+
+        let reply = client
+            .get("/make?template=time&name=det.0.t&parameters=det.0")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("No such template", reply.status);
+
+        rest_common::teardown(chan, &papi, &bapi);
+    }
+}
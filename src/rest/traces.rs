@@ -9,6 +9,10 @@
 /// * Condition modification.
 /// * Addition of spectra to the bound set.
 /// * Removal of spectra from the bound set.
+/// * Run state changes (begin, end, pause, resume) seen in the data.
+/// * Tree variable modifications.
+/// * Application and removal of folds.
+/// * Attaching and detaching data sources.
 ///
 /// See the src/trace/mod.rs module for the guts of the rustogramer trace
 /// internals.
@@ -17,20 +21,32 @@
 ///
 /// * Clients registering interest in traces - along with a maximum retention
 ///lifetime for its traces in the trace database.
+/// * Clients restricting the traces they receive to some trace categories
+/// and/or to objects whose names match glob patterns.
 /// * Clients unregistering interest in traces.
 /// * Clients fetching the set of unexpired traces that were created since the
 /// last time it fetched them.
+/// * Clients subscribing to a stream of server-sent events on which
+///   traces are pushed as they happen rather than polling for them.
 ///
 ///  We are depending on the main to have set the Rocket State to include
 /// an trace::SharedTraceStore so that we can access the trace database created
 /// by all of the trace producers.
+use rocket::response::stream::{Event, EventStream};
 use rocket::serde::{json::Json, Deserialize, Serialize};
-use rocket::State;
+use rocket::{Shutdown, State};
 
 use super::*;
 use crate::trace;
 use std::time;
 
+/// How often an event stream checks for new traces.
+pub const EVENT_STREAM_POLL_MS: u64 = 100;
+/// Retention of the traces of an event stream's client.  Since the
+/// stream fetches them every EVENT_STREAM_POLL_MS this only matters
+/// if the stream stalls.
+pub const EVENT_STREAM_RETENTION_SECS: u64 = 60;
+
 //---------------------------------------------------------------------------
 // what's needed for the trace/establish interface:
 
@@ -42,17 +58,72 @@ use std::time;
 /// #### Query Parameters:
 /// *  retention - the number of seconds that traces for this client
 /// will be retained before aging out.
+/// *  category - optional, may be repeated.  The trace categories the
+/// client wants (the field names of the /trace/fetch detail).  If not
+/// provided, all categories are traced.
+/// *  pattern - optional, may be repeated.  Glob patterns that the name of
+/// the traced object must match.  If not provided all names match.
+///
+/// If the filter is invalid, no token is allocated and the status
+/// describes the problem.
 ///
-#[get("/establish?<retention>")]
+#[get("/establish?<retention>&<category>&<pattern>")]
 pub fn establish_trace(
     retention: u64,
+    category: OptionalStringVec,
+    pattern: OptionalStringVec,
     state: &State<trace::SharedTraceStore>,
 ) -> Json<UnsignedResponse> {
+    let filter = match trace::TraceFilter::new(
+        &category.unwrap_or_default(),
+        &pattern.unwrap_or_default(),
+    ) {
+        Ok(f) => f,
+        Err(s) => {
+            return Json(UnsignedResponse::new(
+                &format!("Invalid trace filter: {}", s),
+                0,
+            ))
+        }
+    };
     let lifetime = time::Duration::from_secs(retention);
     let token = state.inner().new_client(lifetime);
+    state
+        .inner()
+        .set_filter(token, filter)
+        .expect("Filtering a token just allocated");
 
     Json(UnsignedResponse::new("OK", token))
 }
+/// Replace the filter that selects the traces queued for a client.
+/// Traces already queued are not affected.
+///
+/// #### Query Parameters:
+/// *  token - the client's token.
+/// *  category - optional, may be repeated.  The trace categories the
+/// client wants.  If not provided, all categories are traced.
+/// *  pattern - optional, may be repeated.  Glob patterns that the name of
+/// the traced object must match.  If not provided all names match.
+///
+#[get("/filter?<token>&<category>&<pattern>")]
+pub fn trace_filter(
+    token: u64,
+    category: OptionalStringVec,
+    pattern: OptionalStringVec,
+    state: &State<trace::SharedTraceStore>,
+) -> Json<GenericResponse> {
+    let response = match trace::TraceFilter::new(
+        &category.unwrap_or_default(),
+        &pattern.unwrap_or_default(),
+    ) {
+        Ok(filter) => match state.inner().set_filter(token, filter) {
+            Ok(()) => GenericResponse::ok(""),
+            Err(s) => GenericResponse::err("Unable to set trace filter", &s),
+        },
+        Err(s) => GenericResponse::err("Invalid trace filter", &s),
+    };
+    Json(response)
+}
 ///  When done tracing, or before exiting, a client should do a
 /// call to trace/done - this releases all storage associted
 /// with the trace.  If this is not done, a very small memory leak will
@@ -82,6 +153,10 @@ pub struct TraceDetail {
     spectrum: Vec<String>,
     gate: Vec<String>,
     binding: Vec<String>,
+    run: Vec<String>,
+    treevariable: Vec<String>,
+    fold: Vec<String>,
+    source: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -90,6 +165,56 @@ pub struct TraceGetResponse {
     status: String,
     detail: TraceDetail,
 }
+
+impl TraceDetail {
+    // Add a trace to the vector of its category:
+
+    fn push(&mut self, event: &trace::TraceEvent) {
+        let text = trace_text(event);
+        match event.category() {
+            "parameter" => self.parameter.push(text),
+            "spectrum" => self.spectrum.push(text),
+            "gate" => self.gate.push(text),
+            "binding" => self.binding.push(text),
+            "run" => self.run.push(text),
+            "treevariable" => self.treevariable.push(text),
+            "fold" => self.fold.push(text),
+            "source" => self.source.push(text),
+            _ => {}
+        }
+    }
+}
+
+// The Tcl list that describes a trace (see fetch_traces below):
+
+fn trace_text(event: &trace::TraceEvent) -> String {
+    match event {
+        trace::TraceEvent::NewParameter(name) => format!("add {}", name),
+        trace::TraceEvent::ParameterModified(name) => format!("changed {}", name),
+        trace::TraceEvent::SpectrumCreated(name) => format!("add {}", name),
+        trace::TraceEvent::SpectrumDeleted(name) => format!("delete {}", name),
+        trace::TraceEvent::ConditionCreated(name) => format!("add {}", name),
+        trace::TraceEvent::ConditionModified(name) => format!("changed {}", name),
+        trace::TraceEvent::ConditionDeleted(name) => format!("delete {}", name),
+        trace::TraceEvent::SpectrumBound { name, binding_id } => {
+            format!("add {} {}", name, binding_id)
+        }
+        trace::TraceEvent::SpectrumUnbound { name, binding_id } => {
+            format!("remove {} {}", name, binding_id)
+        }
+        trace::TraceEvent::RunStateChange { change, run, title } => {
+            format!("{} {} {{{}}}", change, run, title)
+        }
+        trace::TraceEvent::TreeVariableChanged(name) => format!("changed {}", name),
+        trace::TraceEvent::FoldApplied {
+            spectrum,
+            condition,
+        } => format!("add {} {}", spectrum, condition),
+        trace::TraceEvent::FoldRemoved(name) => format!("remove {}", name),
+        trace::TraceEvent::SourceAttached(name) => format!("attach {}", name),
+        trace::TraceEvent::SourceDetached(name) => format!("detach {}", name),
+    }
+}
 ///
 /// A client can ask for its traces using /trace/fetch and providing its token
 ///
@@ -113,6 +238,21 @@ pub struct TraceGetResponse {
 ///      *  The trace reason which is one of "add" (bind), "remove" (unbind)
 ///      *  Then name of the affected spectrum.
 ///      *  The binding id of the affected spectrum.
+///  * run - Each list contains:
+///      *  The state change: "begin", "end", "pause" or "resume".
+///      *  The run number.
+///      *  The run title.
+///  * treevariable - Each list contains:
+///      *  The trace reason which is always "changed".
+///      *  The name of the tree variable whose value or units changed.
+///  * fold - Each list contains:
+///      *  The trace reason which is one of "add" (fold) or "remove" (unfold).
+///      *  The name of the affected spectrum.
+///      *  For "add" only, the name of the folding condition.
+///  * source - Each list contains:
+///      *  The trace reason which is one of "attach" or "detach".
+///      *  The name of the data source file.  Each file of a chained replay
+/// produces an "attach" trace when it starts being analyzed.
 ///  
 #[get("/fetch?<token>")]
 pub fn fetch_traces(token: u64, state: &State<trace::SharedTraceStore>) -> Json<TraceGetResponse> {
@@ -123,43 +263,16 @@ pub fn fetch_traces(token: u64, state: &State<trace::SharedTraceStore>) -> Json<
             spectrum: Vec::new(),
             gate: Vec::new(),
             binding: Vec::new(),
+            run: Vec::new(),
+            treevariable: Vec::new(),
+            fold: Vec::new(),
+            source: Vec::new(),
         },
     };
     match state.inner().get_traces(token) {
         Ok(traces) => {
-            // Process the traces:
             for trace in traces {
-                match trace.event() {
-                    trace::TraceEvent::NewParameter(name) => {
-                        result.detail.parameter.push(format!("add {}", name))
-                    }
-                    trace::TraceEvent::ParameterModified(name) => {
-                        result.detail.parameter.push(format!("changed {}", name))
-                    }
-                    trace::TraceEvent::SpectrumCreated(name) => {
-                        result.detail.spectrum.push(format!("add {}", name))
-                    }
-                    trace::TraceEvent::SpectrumDeleted(name) => {
-                        result.detail.spectrum.push(format!("delete {}", name))
-                    }
-                    trace::TraceEvent::ConditionCreated(name) => {
-                        result.detail.gate.push(format!("add {}", name))
-                    }
-                    trace::TraceEvent::ConditionModified(name) => {
-                        result.detail.gate.push(format!("changed {}", name))
-                    }
-                    trace::TraceEvent::ConditionDeleted(name) => {
-                        result.detail.gate.push(format!("delete {}", name))
-                    }
-                    trace::TraceEvent::SpectrumBound { name, binding_id } => result
-                        .detail
-                        .binding
-                        .push(format!("add {} {}", name, binding_id)),
-                    trace::TraceEvent::SpectrumUnbound { name, binding_id } => result
-                        .detail
-                        .binding
-                        .push(format!("remove {} {}", name, binding_id)),
-                }
+                result.detail.push(&trace.event());
             }
         }
         Err(msg) => result.status = format!("Unable to fetch traces for token {}: {}", token, msg),
@@ -168,6 +281,127 @@ pub fn fetch_traces(token: u64, state: &State<trace::SharedTraceStore>) -> Json<
     Json(result)
 }
 
+//-----------------------------------------------------------------------------
+// Stuff for trace/events
+
+// The trace client of an event stream.  It lives as long as the
+// stream so when the client disconnects, the stream, and with it this,
+// is dropped releasing the token:
+
+struct EventStreamClient {
+    store: trace::SharedTraceStore,
+    token: u64,
+}
+impl Drop for EventStreamClient {
+    fn drop(&mut self) {
+        let _ = self.store.delete_client(self.token);
+    }
+}
+
+///
+/// Subscribe to a stream of server-sent events (content type
+/// text/event-stream) on which traces are pushed as they happen.  This
+/// avoids the latency of polling /trace/fetch.  The name of each event
+/// is the trace category and its data is the Tcl list /trace/fetch
+/// would have returned for the trace.  The stream ends when the client
+/// disconnects or the server shuts down.
+///
+/// #### Query Parameters:
+/// *  category - optional, may be repeated.  The trace categories the
+///    client wants.  If not provided, all categories are traced.
+/// *  pattern - optional, may be repeated.  Glob patterns that the name
+///    of the traced object must match.  If not provided all names match.
+///
+/// If the filter is invalid a GenericResponse describing the problem is
+/// returned instead of the stream.
+///
+#[get("/events?<category>&<pattern>")]
+pub fn trace_events(
+    category: OptionalStringVec,
+    pattern: OptionalStringVec,
+    state: &State<trace::SharedTraceStore>,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![], Json<GenericResponse>> {
+    let filter =
+        trace::TraceFilter::new(&category.unwrap_or_default(), &pattern.unwrap_or_default())
+            .map_err(|s| Json(GenericResponse::err("Invalid trace filter", &s)))?;
+    let store = state.inner().clone();
+    let token = store.new_client(time::Duration::from_secs(EVENT_STREAM_RETENTION_SECS));
+    store
+        .set_filter(token, filter)
+        .expect("Filtering a token just allocated");
+    let client = EventStreamClient { store, token };
+
+    Ok(EventStream! {
+        let mut interval =
+            rocket::tokio::time::interval(time::Duration::from_millis(EVENT_STREAM_POLL_MS));
+        loop {
+            rocket::tokio::select! {
+                _ = interval.tick() => {},
+                _ = &mut shutdown => break,
+            };
+            let traces = match client.store.get_traces(client.token) {
+                Ok(traces) => traces,
+                Err(_) => break,
+            };
+            for trace in traces {
+                let event = trace.event();
+                yield Event::data(trace_text(&event)).event(event.category());
+            }
+        }
+    })
+}
+
+//-----------------------------------------------------------------------------
+// Stuff for trace/runlog
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RunLogEntry {
+    time: u64,
+    change: String,
+    run: u32,
+    title: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RunLogResponse {
+    status: String,
+    detail: Vec<RunLogEntry>,
+}
+///
+/// Return the run log.  This is the list of run state changes seen
+/// in the data, oldest first.  Unlike traces, this requires no token and
+/// fetching it does not clear it.  Each entry has:
+///
+/// * time - unix time (seconds) at which the state change was processed.
+/// * change - "begin", "end", "pause" or "resume".
+/// * run - the run number.
+/// * title - the run title.
+///
+#[get("/runlog")]
+pub fn run_log(state: &State<trace::SharedTraceStore>) -> Json<RunLogResponse> {
+    let mut result = RunLogResponse {
+        status: String::from("OK"),
+        detail: Vec::new(),
+    };
+    for entry in state.inner().get_run_log() {
+        let time = entry
+            .stamp
+            .duration_since(time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        result.detail.push(RunLogEntry {
+            time,
+            change: entry.change,
+            run: entry.run,
+            title: entry.title,
+        });
+    }
+    Json(result)
+}
+
 // Tests for the ReST interface.
 
 #[cfg(test)]
@@ -185,7 +419,17 @@ mod trace_rest_tests {
     use std::sync::mpsc;
 
     fn setup() -> Rocket<Build> {
-        rest_common::setup().mount("/", routes![establish_trace, trace_done, fetch_traces])
+        rest_common::setup().mount(
+            "/",
+            routes![
+                establish_trace,
+                trace_filter,
+                trace_done,
+                fetch_traces,
+                trace_events,
+                run_log
+            ],
+        )
     }
     fn getstate(
         r: &Rocket<Build>,
@@ -502,4 +746,338 @@ mod trace_rest_tests {
 
         free_token(&client, token);
     }
+    #[test]
+    fn get_run_1() {
+        // Run state change traces:
+
+        let rocket = setup();
+        let (msg_chan, papi, binder_api, tracedb) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("making client");
+        let token = get_token(&client, 10);
+
+        tracedb.add_event(trace::TraceEvent::RunStateChange {
+            change: String::from("begin"),
+            run: 2,
+            title: String::from("Some title"),
+        });
+        tracedb.add_event(trace::TraceEvent::RunStateChange {
+            change: String::from("end"),
+            run: 2,
+            title: String::from("Some title"),
+        });
+
+        let uri = format!("/fetch?token={}", token);
+        let req = client.get(&uri);
+        let response = req
+            .dispatch()
+            .into_json::<TraceGetResponse>()
+            .expect("Parsing JSon");
+
+        assert_eq!("OK", response.status);
+        assert_eq!(0, response.detail.parameter.len());
+        assert_eq!(2, response.detail.run.len());
+        assert_eq!("begin 2 {Some title}", response.detail.run[0]);
+        assert_eq!("end 2 {Some title}", response.detail.run[1]);
+
+        free_token(&client, token);
+        teardown(msg_chan, &papi, &binder_api);
+    }
+    #[test]
+    fn get_treevar_1() {
+        // Tree variable change traces:
+
+        let rocket = setup();
+        let (msg_chan, papi, binder_api, tracedb) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("making client");
+        let token = get_token(&client, 10);
+
+        tracedb.add_event(trace::TraceEvent::TreeVariableChanged(String::from(
+            "slope",
+        )));
+
+        let uri = format!("/fetch?token={}", token);
+        let req = client.get(&uri);
+        let response = req
+            .dispatch()
+            .into_json::<TraceGetResponse>()
+            .expect("Parsing JSon");
+
+        assert_eq!("OK", response.status);
+        assert_eq!(0, response.detail.run.len());
+        assert_eq!(1, response.detail.treevariable.len());
+        assert_eq!("changed slope", response.detail.treevariable[0]);
+
+        free_token(&client, token);
+        teardown(msg_chan, &papi, &binder_api);
+    }
+    #[test]
+    fn get_fold_1() {
+        // Fold and unfold traces:
+
+        let rocket = setup();
+        let (msg_chan, papi, binder_api, tracedb) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("making client");
+        let token = get_token(&client, 10);
+
+        tracedb.add_event(trace::TraceEvent::FoldApplied {
+            spectrum: String::from("gamma"),
+            condition: String::from("fold"),
+        });
+        tracedb.add_event(trace::TraceEvent::FoldRemoved(String::from("gamma")));
+
+        let uri = format!("/fetch?token={}", token);
+        let response = client
+            .get(&uri)
+            .dispatch()
+            .into_json::<TraceGetResponse>()
+            .expect("Parsing JSon");
+
+        assert_eq!("OK", response.status);
+        assert_eq!(0, response.detail.spectrum.len());
+        assert_eq!(2, response.detail.fold.len());
+        assert_eq!("add gamma fold", response.detail.fold[0]);
+        assert_eq!("remove gamma", response.detail.fold[1]);
+
+        free_token(&client, token);
+        teardown(msg_chan, &papi, &binder_api);
+    }
+    #[test]
+    fn get_source_1() {
+        // Data source attach/detach traces:
+
+        let rocket = setup();
+        let (msg_chan, papi, binder_api, tracedb) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("making client");
+        let token = get_token(&client, 10);
+
+        tracedb.add_event(trace::TraceEvent::SourceAttached(String::from(
+            "run-0000-00.par",
+        )));
+        tracedb.add_event(trace::TraceEvent::SourceDetached(String::from(
+            "run-0000-00.par",
+        )));
+
+        let uri = format!("/fetch?token={}", token);
+        let response = client
+            .get(&uri)
+            .dispatch()
+            .into_json::<TraceGetResponse>()
+            .expect("Parsing JSon");
+
+        assert_eq!("OK", response.status);
+        assert_eq!(0, response.detail.fold.len());
+        assert_eq!(2, response.detail.source.len());
+        assert_eq!("attach run-0000-00.par", response.detail.source[0]);
+        assert_eq!("detach run-0000-00.par", response.detail.source[1]);
+
+        free_token(&client, token);
+        teardown(msg_chan, &papi, &binder_api);
+    }
+    #[test]
+    fn filter_1() {
+        // Establish with a filter only queues the wanted traces:
+
+        let rocket = setup();
+        let (msg_chan, papi, binder_api, tracedb) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("making client");
+        let reply = client
+            .get("/establish?retention=10&category=binding&pattern=s.*")
+            .dispatch()
+            .into_json::<UnsignedResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        let token = reply.detail;
+
+        tracedb.add_event(trace::TraceEvent::SpectrumCreated(String::from("s.1")));
+        tracedb.add_event(trace::TraceEvent::SpectrumBound {
+            name: String::from("s.1"),
+            binding_id: 1,
+        });
+        tracedb.add_event(trace::TraceEvent::SpectrumBound {
+            name: String::from("t.1"),
+            binding_id: 2,
+        });
+
+        let uri = format!("/fetch?token={}", token);
+        let response = client
+            .get(&uri)
+            .dispatch()
+            .into_json::<TraceGetResponse>()
+            .expect("Parsing JSon");
+        assert_eq!("OK", response.status);
+        assert_eq!(0, response.detail.spectrum.len());
+        assert_eq!(vec![String::from("add s.1 1")], response.detail.binding);
+
+        free_token(&client, token);
+        teardown(msg_chan, &papi, &binder_api);
+    }
+    #[test]
+    fn filter_2() {
+        // Bad filters on establish are errors:
+
+        let rocket = setup();
+        let (msg_chan, papi, binder_api, _tracedb) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("making client");
+        let reply = client
+            .get("/establish?retention=10&category=junk")
+            .dispatch()
+            .into_json::<UnsignedResponse>()
+            .expect("Parsing JSON");
+        assert!(reply.status.starts_with("Invalid trace filter"));
+
+        teardown(msg_chan, &papi, &binder_api);
+    }
+    #[test]
+    fn filter_3() {
+        // Change the filter of an existing client:
+
+        let rocket = setup();
+        let (msg_chan, papi, binder_api, tracedb) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("making client");
+        let token = get_token(&client, 10);
+        let uri = format!("/filter?token={}&category=parameter&category=gate", token);
+        let reply = client
+            .get(&uri)
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        tracedb.add_event(trace::TraceEvent::NewParameter(String::from("p")));
+        tracedb.add_event(trace::TraceEvent::ConditionCreated(String::from("g")));
+        tracedb.add_event(trace::TraceEvent::SpectrumCreated(String::from("s")));
+
+        let uri = format!("/fetch?token={}", token);
+        let response = client
+            .get(&uri)
+            .dispatch()
+            .into_json::<TraceGetResponse>()
+            .expect("Parsing JSon");
+        assert_eq!(1, response.detail.parameter.len());
+        assert_eq!(1, response.detail.gate.len());
+        assert_eq!(0, response.detail.spectrum.len());
+
+        // Bad token and bad pattern:
+
+        let reply = client
+            .get("/filter?token=12345")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("Unable to set trace filter", reply.status);
+        let uri = format!("/filter?token={}&pattern=%5B", token);
+        let reply = client
+            .get(&uri)
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("Invalid trace filter", reply.status);
+
+        free_token(&client, token);
+        teardown(msg_chan, &papi, &binder_api);
+    }
+    #[test]
+    fn runlog_1() {
+        // The run log does not need a token:
+
+        let rocket = setup();
+        let (msg_chan, papi, binder_api, tracedb) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("making client");
+        tracedb.add_event(trace::TraceEvent::RunStateChange {
+            change: String::from("begin"),
+            run: 5,
+            title: String::from("Title"),
+        });
+        let response = client
+            .get("/runlog")
+            .dispatch()
+            .into_json::<RunLogResponse>()
+            .expect("Parsing JSON");
+
+        assert_eq!("OK", response.status);
+        assert_eq!(1, response.detail.len());
+        assert_eq!("begin", response.detail[0].change);
+        assert_eq!(5, response.detail[0].run);
+        assert_eq!("Title", response.detail[0].title);
+
+        teardown(msg_chan, &papi, &binder_api);
+    }
+    // Read from an event stream until n events have been received:
+
+    fn read_events(response: &mut rocket::local::blocking::LocalResponse, n: usize) -> String {
+        use std::io::Read;
+        let mut text = String::new();
+        let mut buffer = [0_u8; 1024];
+        while text.matches("\n\n").count() < n {
+            let count = response.read(&mut buffer).expect("Reading events");
+            assert!(count > 0, "Event stream ended");
+            text.push_str(std::str::from_utf8(&buffer[0..count]).expect("Bad UTF-8"));
+        }
+        text
+    }
+    #[test]
+    fn events_1() {
+        // Traces are pushed to the event stream and the client
+        // is released when the stream is dropped:
+
+        let rocket = setup();
+        let (msg_chan, papi, binder_api, tracedb) = getstate(&rocket);
+
+        let client = Client::tracked(rocket).expect("making client");
+        {
+            let mut response = client.get("/events?category=spectrum").dispatch();
+            assert_eq!(
+                Some(rocket::http::ContentType::EventStream),
+                response.content_type()
+            );
+            tracedb.add_event(trace::TraceEvent::NewParameter(String::from("p1")));
+            tracedb.add_event(trace::TraceEvent::SpectrumCreated(String::from("s1")));
+            tracedb.add_event(trace::TraceEvent::SpectrumDeleted(String::from("s1")));
+
+            // Lines starting with : are heartbeats:
+
+            let text = read_events(&mut response, 2);
+            let lines: Vec<&str> = text
+                .lines()
+                .filter(|l| !l.is_empty() && !l.starts_with(':'))
+                .collect();
+            assert_eq!(
+                vec![
+                    "event:spectrum",
+                    "data:add s1",
+                    "event:spectrum",
+                    "data:delete s1"
+                ],
+                lines
+            );
+        }
+        assert!(tracedb.get_traces(0).is_err());
+
+        teardown(msg_chan, &papi, &binder_api);
+    }
+    #[test]
+    fn events_2() {
+        // Bad filters are reported rather than streaming:
+
+        let rocket = setup();
+        let (msg_chan, papi, binder_api, _tracedb) = getstate(&rocket);
+
+        let client = Client::tracked(rocket).expect("making client");
+        let reply = client
+            .get("/events?category=junk")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("Invalid trace filter", reply.status);
+
+        teardown(msg_chan, &papi, &binder_api);
+    }
 }
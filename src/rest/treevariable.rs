@@ -0,0 +1,391 @@
+//! This module provides the /spectcl/treevariable REST interface.
+//! Rustogramer has no compiled analysis pipeline so its tree variables
+//! are those recorded in the data by the FRIB analysis pipeline.  As
+//! VARIABLE_VALUES ring items are processed, the variables they describe
+//! are entered into the tree variable store (see src/treevariables/mod.rs).
+//! These handlers provide SpecTcl compatible access to that store.
+//!
+//! We depend on the main to have set the Rocket state to include a
+//! treevariables::SharedTreeVariableStore and a trace::SharedTraceStore.
+//!
+use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
+
+use super::*;
+use crate::trace;
+use crate::treevariables;
+
+// What we get per tree variable in a listing:
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct TreeVariable {
+    name: String,
+    value: f64,
+    units: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct TreeVariableListResponse {
+    status: String,
+    detail: Vec<TreeVariable>,
+}
+/// List tree variables.  As with SpecTcl, there is no
+/// pattern; all variables are listed in alphabetical order by name.
+#[get("/list")]
+pub fn treevariable_list(
+    state: &State<treevariables::SharedTreeVariableStore>,
+) -> Json<TreeVariableListResponse> {
+    match state.inner().list("*") {
+        Ok(variables) => Json(TreeVariableListResponse {
+            status: String::from("OK"),
+            detail: variables
+                .iter()
+                .map(|v| TreeVariable {
+                    name: v.name(),
+                    value: v.value(),
+                    units: v.units(),
+                })
+                .collect(),
+        }),
+        Err(msg) => Json(TreeVariableListResponse {
+            status: format!("'treevariable -list' failed: {}", msg),
+            detail: vec![],
+        }),
+    }
+}
+
+/// Set a new value for a tree variable.  This sets the variable's
+/// changed flag and declares a trace.
+///
+/// #### Query Parameters:
+/// * name - name of an existing tree variable.
+/// * value - new value.
+/// * units - new units.  If omitted the units are unchanged.
+#[get("/set?<name>&<value>&<units>")]
+pub fn treevariable_set(
+    name: String,
+    value: f64,
+    units: OptionalString,
+    state: &State<treevariables::SharedTreeVariableStore>,
+    tracedb: &State<trace::SharedTraceStore>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    let store = state.inner();
+    let units = units.unwrap_or_else(|| store.get(&name).map(|v| v.units()).unwrap_or_default());
+    match store.set(&name, value, &units) {
+        Ok(()) => {
+            tracedb.add_event(trace::TraceEvent::TreeVariableChanged(name));
+            Json(GenericResponse::ok(""))
+        }
+        Err(msg) => Json(GenericResponse::err("'treevariable -set' failed", &msg)),
+    }
+}
+/// Get changed flag.  The detail is 1 if the flag is set and 0 if not.
+#[get("/check?<name>")]
+pub fn treevariable_check(
+    name: String,
+    state: &State<treevariables::SharedTreeVariableStore>,
+) -> Json<UnsignedResponse> {
+    match state.inner().is_changed(&name) {
+        Ok(changed) => Json(UnsignedResponse::new("OK", if changed { 1 } else { 0 })),
+        Err(msg) => Json(UnsignedResponse::new(
+            &format!("'treevariable -check' failed: {}", msg),
+            0,
+        )),
+    }
+}
+/// Set changed flag
+#[get("/setchanged?<name>")]
+pub fn treevariable_set_changed(
+    name: String,
+    state: &State<treevariables::SharedTreeVariableStore>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
+    match state.inner().set_changed(&name) {
+        Ok(()) => Json(GenericResponse::ok("")),
+        Err(msg) => Json(GenericResponse::err(
+            "'treevariable -setchanged' command failed",
+            &msg,
+        )),
+    }
+}
+/// Fire changed traces for the variables that match the optional
+/// pattern (defaults to "*").
+#[get("/firetraces?<pattern>")]
+pub fn treevariable_fire_traces(
+    pattern: OptionalString,
+    state: &State<treevariables::SharedTreeVariableStore>,
+    tracedb: &State<trace::SharedTraceStore>,
+) -> Json<GenericResponse> {
+    let pattern = pattern.unwrap_or_else(|| String::from("*"));
+    match state.inner().list(&pattern) {
+        Ok(variables) => {
+            for v in variables {
+                tracedb.add_event(trace::TraceEvent::TreeVariableChanged(v.name()));
+            }
+            Json(GenericResponse::ok(""))
+        }
+        Err(msg) => Json(GenericResponse::err(
+            "'treevariable -firetraces' failed: ",
+            &msg,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod treevar_tests {
+    use super::*;
+    use crate::messaging;
+    use crate::test::rest_common;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+    use std::time;
+
+    fn setup() -> Rocket<Build> {
+        rest_common::setup().mount(
+            "/",
+            routes![
+                treevariable_list,
+                treevariable_set,
+                treevariable_check,
+                treevariable_set_changed,
+                treevariable_fire_traces
+            ],
+        )
+    }
+    fn getstate(
+        r: &Rocket<Build>,
+    ) -> (
+        mpsc::Sender<messaging::Request>,
+        processing::ProcessingApi,
+        binder::BindingApi,
+        treevariables::SharedTreeVariableStore,
+        trace::SharedTraceStore,
+    ) {
+        let (chan, papi, bapi) = rest_common::get_state(r);
+        let store = r
+            .state::<treevariables::SharedTreeVariableStore>()
+            .expect("Valid state")
+            .clone();
+        let tracedb = r
+            .state::<trace::SharedTraceStore>()
+            .expect("Valid state")
+            .clone();
+        (chan, papi, bapi, store, tracedb)
+    }
+    fn teardown(
+        c: mpsc::Sender<messaging::Request>,
+        p: &processing::ProcessingApi,
+        b: &binder::BindingApi,
+    ) {
+        rest_common::teardown(c, p, b);
+    }
+    #[test]
+    fn list_1() {
+        // Empty store:
+
+        let rocket = setup();
+        let (chan, papi, bapi, _store, _tracedb) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Creating client");
+        let req = client.get("/list");
+        let reply = req
+            .dispatch()
+            .into_json::<TreeVariableListResponse>()
+            .expect("Parsing JSON");
+
+        assert_eq!("OK", reply.status);
+        assert_eq!(0, reply.detail.len());
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn list_2() {
+        // Variables from the data are listed sorted by name:
+
+        let rocket = setup();
+        let (chan, papi, bapi, store, _tracedb) = getstate(&rocket);
+        store.update("slope", 2.5, "keV/channel");
+        store.update("offset", 1.0, "keV");
+
+        let client = Client::untracked(rocket).expect("Creating client");
+        let req = client.get("/list");
+        let reply = req
+            .dispatch()
+            .into_json::<TreeVariableListResponse>()
+            .expect("Parsing JSON");
+
+        assert_eq!("OK", reply.status);
+        assert_eq!(2, reply.detail.len());
+        assert_eq!("offset", reply.detail[0].name);
+        assert_eq!(1.0, reply.detail[0].value);
+        assert_eq!("keV", reply.detail[0].units);
+        assert_eq!("slope", reply.detail[1].name);
+        assert_eq!(2.5, reply.detail[1].value);
+        assert_eq!("keV/channel", reply.detail[1].units);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn set_1() {
+        // Nonexistent variable:
+
+        let rocket = setup();
+        let (chan, papi, bapi, _store, _tracedb) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Creating client");
+        let req = client.get("/set?name=dummy&value=2.54&units=cm%2Fin");
+        let reply = req
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+
+        assert_eq!("'treevariable -set' failed", reply.status);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn set_2() {
+        // Set value and units, the changed flag is set and a trace fired:
+
+        let rocket = setup();
+        let (chan, papi, bapi, store, tracedb) = getstate(&rocket);
+        store.update("dummy", 1.0, "mm/in");
+        let token = tracedb.new_client(time::Duration::from_secs(10));
+
+        let client = Client::untracked(rocket).expect("Creating client");
+        let req = client.get("/set?name=dummy&value=2.54&units=cm%2Fin");
+        let reply = req
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+
+        assert_eq!("OK", reply.status);
+        let var = store.get("dummy").expect("Getting dummy");
+        assert_eq!(2.54, var.value());
+        assert_eq!("cm/in", var.units());
+        assert!(var.changed());
+
+        let traces = tracedb.get_traces(token).expect("Getting traces");
+        assert_eq!(1, traces.len());
+        assert!(matches!(
+            traces[0].event(),
+            trace::TraceEvent::TreeVariableChanged(name) if name == "dummy"
+        ));
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn set_3() {
+        // Omitting units retains them:
+
+        let rocket = setup();
+        let (chan, papi, bapi, store, _tracedb) = getstate(&rocket);
+        store.update("dummy", 1.0, "mm/in");
+
+        let client = Client::untracked(rocket).expect("Creating client");
+        let req = client.get("/set?name=dummy&value=25.4");
+        let reply = req
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+
+        assert_eq!("OK", reply.status);
+        let var = store.get("dummy").expect("Getting dummy");
+        assert_eq!(25.4, var.value());
+        assert_eq!("mm/in", var.units());
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn check_1() {
+        let rocket = setup();
+        let (chan, papi, bapi, store, _tracedb) = getstate(&rocket);
+        store.update("dummy", 1.0, "");
+
+        let client = Client::untracked(rocket).expect("Creating client");
+        let reply = client
+            .get("/check?name=dummy")
+            .dispatch()
+            .into_json::<UnsignedResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(0, reply.detail);
+
+        let reply = client
+            .get("/setchanged?name=dummy")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+
+        let reply = client
+            .get("/check?name=dummy")
+            .dispatch()
+            .into_json::<UnsignedResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(1, reply.detail);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn check_2() {
+        // Nonexistent variables fail:
+
+        let rocket = setup();
+        let (chan, papi, bapi, _store, _tracedb) = getstate(&rocket);
+
+        let client = Client::untracked(rocket).expect("Creating client");
+        let reply = client
+            .get("/check?name=dummy")
+            .dispatch()
+            .into_json::<UnsignedResponse>()
+            .expect("Parsing JSON");
+        assert_ne!("OK", reply.status);
+
+        let reply = client
+            .get("/setchanged?name=dummy")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("'treevariable -setchanged' command failed", reply.status);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn firetraces_1() {
+        let rocket = setup();
+        let (chan, papi, bapi, store, tracedb) = getstate(&rocket);
+        store.update("a.1", 1.0, "");
+        store.update("a.2", 2.0, "");
+        store.update("b", 3.0, "");
+        let token = tracedb.new_client(time::Duration::from_secs(10));
+
+        let client = Client::untracked(rocket).expect("Creating client");
+        let reply = client
+            .get("/firetraces?pattern=a*")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(2, tracedb.get_traces(token).expect("traces").len());
+
+        // Default pattern is everything:
+
+        let reply = client
+            .get("/firetraces")
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Parsing JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(3, tracedb.get_traces(token).expect("traces").len());
+
+        teardown(chan, &papi, &bapi);
+    }
+}
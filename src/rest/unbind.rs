@@ -35,7 +35,11 @@ use crate::sharedmem::binder;
 /// reason given for the failure.
 ///
 #[get("/byname?<name>")]
-pub fn unbind_byname(name: String, state: &State<SharedBinderChannel>) -> Json<GenericResponse> {
+pub fn unbind_byname(
+    name: String,
+    state: &State<SharedBinderChannel>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
     let api = binder::BindingApi::new(&state.inner().lock().unwrap());
 
     let response = if let Err(s) = api.unbind(&name) {
@@ -77,7 +81,10 @@ pub fn unbind_byid() -> Json<GenericResponse> {
 /// specific error message returned by the server.
 ///
 #[get("/all")]
-pub fn unbind_all(state: &State<SharedBinderChannel>) -> Json<GenericResponse> {
+pub fn unbind_all(
+    state: &State<SharedBinderChannel>,
+    _writable: readonly::Writable,
+) -> Json<GenericResponse> {
     let api = binder::BindingApi::new(&state.inner().lock().unwrap());
 
     let response = if let Err(s) = api.unbind_all() {
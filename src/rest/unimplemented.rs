@@ -7,16 +7,6 @@
 //! *   pipeline management - There is no analysis pipeline in Rustogramer,
 //! the analysis pipeline as concieved of for SpecTcl is external and
 //! provide Rustogramer with pre-decoded data.
-//! *   psuedo - Any computed parameters are done in the external analysis
-//! pipeline and, therefore are not supported in Rustogramer.  Note
-//! that the psuedo feature of SpecTcl itself is seldom used, more normally
-//! people add this to compiled code.
-//! *   roottree - Root tree creation is something that should be done
-//! by other parts of the analysis pipeline.   Not Rustogramer.
-//! *   script - There is on command language to script.
-//! I believe it might be needed for the tree GUI.
-//! *   treevariable - See pseudo for the rationale.  Treevariables are
-//! supported by the analysis pipeline.
 
 use super::*;
 use rocket::serde::{json::Json, Deserialize, Serialize};
@@ -129,186 +119,6 @@ pub fn pman_clone(source: String, new: String) -> Json<GenericResponse> {
     ))
 }
 
-//-----------------------------------------------------------------
-// Pseudo parameters.
-
-/// Create a scripted pseudo parameter.
-#[allow(unused_variables)]
-#[get("/create?<pseudo>&<parameter>&<computation>")]
-pub fn pseudo_create(
-    pseudo: String,
-    parameter: Vec<String>,
-    computation: OptionalString,
-) -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "Pseudo parameters are not implemented",
-        "This is not SpecTcl",
-    ))
-}
-// Description of a pseudo parameter:
-
-#[derive(Serialize, Deserialize)]
-#[serde(crate = "rocket::serde")]
-pub struct PseudoDescription {
-    name: String,
-    parameters: Vec<String>,
-    computation: String,
-}
-// Response to /pseudo/list:
-
-#[derive(Serialize, Deserialize)]
-#[serde(crate = "rocket::serde")]
-pub struct PseudoListResponse {
-    status: String,
-    detail: Vec<PseudoDescription>,
-}
-/// List pseudos.
-#[allow(unused_variables)]
-#[get("/list?<pattern>")]
-pub fn pseudo_list(pattern: OptionalString) -> Json<PseudoListResponse> {
-    Json(PseudoListResponse {
-        status: String::from("Psuedo parameters are not implemented - this is not SpecTcl"),
-        detail: vec![],
-    })
-}
-/// Delete pseudos
-#[allow(unused_variables)]
-#[get("/delete?<name>")]
-pub fn pseudo_delete(name: String) -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "Pseudo parameters are not implemented",
-        "This is not SpecTcl",
-    ))
-}
-//-----------------------------------------------------------
-// Root tree:
-
-/// Create a root output tree.
-#[allow(unused_variables)]
-#[get("/create?<tree>&<parameter>&<gate>")]
-pub fn roottree_create(
-    tree: String,
-    parameter: Vec<String>,
-    gate: OptionalString,
-) -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "Root Tree output is not supported",
-        "This is not SpecTcl",
-    ))
-}
-
-/// Delete a root output tree.
-
-#[get("/delete?<tree>")]
-#[allow(unused_variables)]
-pub fn roottree_delete(tree: String) -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "Root Tree output is not supported",
-        "This is not SpecTcl",
-    ))
-}
-
-// Description of a root tree:
-
-#[derive(Serialize, Deserialize)]
-#[serde(crate = "rocket::serde")]
-pub struct RootTreeDescription {
-    tree: String,
-    params: Vec<String>,
-    gate: Option<String>,
-}
-
-#[derive(Serialize, Deserialize)]
-#[serde(crate = "rocket::serde")]
-pub struct RootTreeListResponse {
-    status: String,
-    detail: Vec<RootTreeDescription>,
-}
-
-/// List the root trees:
-#[allow(unused_variables)]
-#[get("/list?<pattern>")]
-pub fn roottree_list(pattern: OptionalString) -> Json<RootTreeListResponse> {
-    Json(RootTreeListResponse {
-        status: String::from("Root tree output is not implemented - this is not SpecTcl"),
-        detail: vec![],
-    })
-}
-//----------------------------------------------------------------
-// Script.
-#[allow(unused_variables)]
-#[get("/?<command>")]
-pub fn script_execute(command: String) -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "Script execution is not supported",
-        "This is not SpecTcl",
-    ))
-}
-//---------------------------------------------------------------------
-// tree variables.
-
-// What we get per tree variable in a listing:
-
-#[derive(Serialize, Deserialize)]
-#[serde(crate = "rocket::serde")]
-pub struct TreeVariable {
-    name: String,
-    value: f64,
-    units: String,
-}
-
-#[derive(Serialize, Deserialize)]
-#[serde(crate = "rocket::serde")]
-pub struct TreeVariableListResponse {
-    status: String,
-    detail: Vec<TreeVariable>,
-}
-/// List tree variables.
-#[get("/list")]
-pub fn treevariable_list() -> Json<TreeVariableListResponse> {
-    Json(TreeVariableListResponse {
-        status: String::from("Tree variables are not implemented.  This is not SpecTcl"),
-        detail: vec![],
-    })
-}
-
-/// Set a new value for a tree variabls
-#[allow(unused_variables)]
-#[get("/set?<name>&<value>&<units>")]
-pub fn treevariable_set(name: String, value: f64, units: OptionalString) -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "Tree variables are not implemented",
-        "This is not SpecTcl",
-    ))
-}
-/// Get changed flag.
-#[allow(unused_variables)]
-#[get("/check?<name>")]
-pub fn treevariable_check(name: String) -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "Tree variables are not implemented",
-        "This is not SpecTcl",
-    ))
-}
-/// Set changed flag
-#[allow(unused_variables)]
-#[get("/setchanged?<name>")]
-pub fn treevariable_set_changed(name: String) -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "Tree variables are not implemented",
-        "This is not SpecTcl",
-    ))
-}
-/// Fire changed traces:
-#[allow(unused_variables)]
-#[get("/firetraces?<pattern>")]
-pub fn treevariable_fire_traces(pattern: OptionalString) -> Json<GenericResponse> {
-    Json(GenericResponse::err(
-        "Tree variables are not implemented",
-        "This is not SpecTcl",
-    ))
-}
-
 #[cfg(test)]
 mod pipeline_tests {
     use super::*;
@@ -535,356 +345,3 @@ mod pipeline_tests {
         teardown(chan, &papi, &bind_api);
     }
 }
-#[cfg(test)]
-mod pseudo_test {
-    use super::*;
-    use crate::messaging;
-    use crate::test::rest_common;
-
-    use rocket;
-    use rocket::local::blocking::Client;
-    use rocket::Build;
-    use rocket::Rocket;
-
-    use std::sync::mpsc;
-
-    fn setup() -> Rocket<Build> {
-        rest_common::setup().mount("/", routes![pseudo_create, pseudo_list, pseudo_delete])
-    }
-    fn getstate(
-        r: &Rocket<Build>,
-    ) -> (
-        mpsc::Sender<messaging::Request>,
-        processing::ProcessingApi,
-        binder::BindingApi,
-    ) {
-        rest_common::get_state(r)
-    }
-    fn teardown(
-        c: mpsc::Sender<messaging::Request>,
-        p: &processing::ProcessingApi,
-        b: &binder::BindingApi,
-    ) {
-        rest_common::teardown(c, p, b);
-    }
-    #[test]
-    fn create_1() {
-        let rocket = setup();
-        let (chan, papi, bapi) = getstate(&rocket);
-
-        let client = Client::untracked(rocket).expect("creating client");
-        let req = client.get("/create?pseudo=p&parameter=a&parameter=b&computation={$a+$b}");
-        let reply = req
-            .dispatch()
-            .into_json::<GenericResponse>()
-            .expect("Parsing JSON");
-
-        assert_eq!("Pseudo parameters are not implemented", reply.status);
-        assert_eq!("This is not SpecTcl", reply.detail);
-
-        teardown(chan, &papi, &bapi);
-    }
-    #[test]
-    fn list_1() {
-        let rocket = setup();
-        let (chan, papi, bapi) = getstate(&rocket);
-
-        let client = Client::untracked(rocket).expect("creating client");
-        let req = client.get("/list?pattern=*");
-        let reply = req
-            .dispatch()
-            .into_json::<PseudoListResponse>()
-            .expect("Parsing JSON");
-
-        assert_eq!(
-            "Psuedo parameters are not implemented - this is not SpecTcl",
-            reply.status
-        );
-        assert_eq!(0, reply.detail.len());
-
-        teardown(chan, &papi, &bapi);
-    }
-    #[test]
-    fn delete_1() {
-        let rocket = setup();
-        let (chan, papi, bapi) = getstate(&rocket);
-
-        let client = Client::untracked(rocket).expect("creating client");
-        let req = client.get("/delete?name=dummy");
-        let reply = req
-            .dispatch()
-            .into_json::<GenericResponse>()
-            .expect("Parsing JSON");
-
-        assert_eq!("Pseudo parameters are not implemented", reply.status);
-        assert_eq!("This is not SpecTcl", reply.detail);
-
-        teardown(chan, &papi, &bapi);
-    }
-}
-#[cfg(test)]
-mod roottree_tests {
-    use super::*;
-    use crate::messaging;
-    use crate::test::rest_common;
-
-    use rocket;
-    use rocket::local::blocking::Client;
-    use rocket::Build;
-    use rocket::Rocket;
-
-    use std::sync::mpsc;
-
-    fn setup() -> Rocket<Build> {
-        rest_common::setup().mount(
-            "/",
-            routes![roottree_create, roottree_delete, roottree_list],
-        )
-    }
-    fn getstate(
-        r: &Rocket<Build>,
-    ) -> (
-        mpsc::Sender<messaging::Request>,
-        processing::ProcessingApi,
-        binder::BindingApi,
-    ) {
-        rest_common::get_state(r)
-    }
-    fn teardown(
-        c: mpsc::Sender<messaging::Request>,
-        p: &processing::ProcessingApi,
-        b: &binder::BindingApi,
-    ) {
-        rest_common::teardown(c, p, b);
-    }
-    #[test]
-    fn create_1() {
-        let rocket = setup();
-        let (chan, papi, bapi) = getstate(&rocket);
-
-        let client = Client::untracked(rocket).expect("making rocket client");
-        let req = client.get("/create?tree=treename&parameter=p1&parmeter=p2");
-        let reply = req
-            .dispatch()
-            .into_json::<GenericResponse>()
-            .expect("Parsing JSON");
-
-        assert_eq!("Root Tree output is not supported", reply.status);
-        assert_eq!("This is not SpecTcl", reply.detail);
-
-        teardown(chan, &papi, &bapi);
-    }
-    #[test]
-    fn delete_1() {
-        let rocket = setup();
-        let (chan, papi, bapi) = getstate(&rocket);
-
-        let client = Client::untracked(rocket).expect("making rocket client");
-        let req = client.get("/delete?tree=treename");
-        let reply = req
-            .dispatch()
-            .into_json::<GenericResponse>()
-            .expect("Parsing JSON");
-
-        assert_eq!("Root Tree output is not supported", reply.status);
-        assert_eq!("This is not SpecTcl", reply.detail);
-
-        teardown(chan, &papi, &bapi);
-    }
-    #[test]
-    fn list_1() {
-        let rocket = setup();
-        let (chan, papi, bapi) = getstate(&rocket);
-
-        let client = Client::untracked(rocket).expect("making rocket client");
-        let req = client.get("/list?pattern=*");
-        let reply = req
-            .dispatch()
-            .into_json::<RootTreeListResponse>()
-            .expect("Parsing JSON");
-
-        assert_eq!(
-            "Root tree output is not implemented - this is not SpecTcl",
-            reply.status
-        );
-        assert_eq!(0, reply.detail.len());
-
-        teardown(chan, &papi, &bapi);
-    }
-}
-#[cfg(test)]
-mod script_tests {
-    use super::*;
-    use crate::messaging;
-    use crate::test::rest_common;
-
-    use rocket;
-    use rocket::local::blocking::Client;
-    use rocket::Build;
-    use rocket::Rocket;
-
-    fn setup() -> Rocket<Build> {
-        rest_common::setup().mount("/", routes![script_execute])
-    }
-    fn getstate(
-        r: &Rocket<Build>,
-    ) -> (
-        mpsc::Sender<messaging::Request>,
-        processing::ProcessingApi,
-        binder::BindingApi,
-    ) {
-        rest_common::get_state(r)
-    }
-    fn teardown(
-        c: mpsc::Sender<messaging::Request>,
-        p: &processing::ProcessingApi,
-        b: &binder::BindingApi,
-    ) {
-        rest_common::teardown(c, p, b);
-    }
-    #[test]
-    fn execute_1() {
-        let rocket = setup();
-        let (chan, papi, bapi) = getstate(&rocket);
-
-        let client = Client::untracked(rocket).expect("Making rocket client");
-        let req = client.get("/?command=something");
-        let reply = req
-            .dispatch()
-            .into_json::<GenericResponse>()
-            .expect("Parsing JSON");
-
-        assert_eq!("Script execution is not supported", reply.status);
-        assert_eq!("This is not SpecTcl", reply.detail);
-
-        teardown(chan, &papi, &bapi);
-    }
-}
-#[cfg(test)]
-mod treevar_tests {
-    use super::*;
-    use crate::messaging;
-    use crate::test::rest_common;
-
-    use rocket;
-    use rocket::local::blocking::Client;
-    use rocket::Build;
-    use rocket::Rocket;
-
-    fn setup() -> Rocket<Build> {
-        rest_common::setup().mount(
-            "/",
-            routes![
-                treevariable_list,
-                treevariable_set,
-                treevariable_check,
-                treevariable_set_changed,
-                treevariable_fire_traces
-            ],
-        )
-    }
-    fn getstate(
-        r: &Rocket<Build>,
-    ) -> (
-        mpsc::Sender<messaging::Request>,
-        processing::ProcessingApi,
-        binder::BindingApi,
-    ) {
-        rest_common::get_state(r)
-    }
-    fn teardown(
-        c: mpsc::Sender<messaging::Request>,
-        p: &processing::ProcessingApi,
-        b: &binder::BindingApi,
-    ) {
-        rest_common::teardown(c, p, b);
-    }
-    #[test]
-    fn list_1() {
-        let rocket = setup();
-        let (chan, papi, bapi) = getstate(&rocket);
-
-        let client = Client::untracked(rocket).expect("Creating client");
-        let req = client.get("/list");
-        let reply = req
-            .dispatch()
-            .into_json::<TreeVariableListResponse>()
-            .expect("Parsing JSON");
-
-        assert_eq!(
-            "Tree variables are not implemented.  This is not SpecTcl",
-            reply.status
-        );
-        assert_eq!(0, reply.detail.len());
-
-        teardown(chan, &papi, &bapi);
-    }
-    #[test]
-    fn set_1() {
-        let rocket = setup();
-        let (chan, papi, bapi) = getstate(&rocket);
-
-        let client = Client::untracked(rocket).expect("Creating client");
-        let req = client.get("/set?name=dummy&value=2.54&units=cm%2Fin");
-        let reply = req
-            .dispatch()
-            .into_json::<GenericResponse>()
-            .expect("Parsing JSON");
-
-        assert_eq!("Tree variables are not implemented", reply.status);
-        assert_eq!("This is not SpecTcl", reply.detail);
-
-        teardown(chan, &papi, &bapi);
-    }
-    #[test]
-    fn check_1() {
-        let rocket = setup();
-        let (chan, papi, bapi) = getstate(&rocket);
-
-        let client = Client::untracked(rocket).expect("Creating client");
-        let req = client.get("/check?name=dummy");
-        let reply = req
-            .dispatch()
-            .into_json::<GenericResponse>()
-            .expect("Parsing JSON");
-
-        assert_eq!("Tree variables are not implemented", reply.status);
-        assert_eq!("This is not SpecTcl", reply.detail);
-
-        teardown(chan, &papi, &bapi);
-    }
-    #[test]
-    fn setchanged_1() {
-        let rocket = setup();
-        let (chan, papi, bapi) = getstate(&rocket);
-
-        let client = Client::untracked(rocket).expect("Creating client");
-        let req = client.get("/setchanged?name=dummy");
-        let reply = req
-            .dispatch()
-            .into_json::<GenericResponse>()
-            .expect("Parsing JSON");
-
-        assert_eq!("Tree variables are not implemented", reply.status);
-        assert_eq!("This is not SpecTcl", reply.detail);
-
-        teardown(chan, &papi, &bapi);
-    }
-    #[test]
-    fn firetraces_1() {
-        let rocket = setup();
-        let (chan, papi, bapi) = getstate(&rocket);
-
-        let client = Client::untracked(rocket).expect("Creating client");
-        let req = client.get("/firetraces?pattern=*");
-        let reply = req
-            .dispatch()
-            .into_json::<GenericResponse>()
-            .expect("Parsing JSON");
-
-        assert_eq!("Tree variables are not implemented", reply.status);
-        assert_eq!("This is not SpecTcl", reply.detail);
-
-        teardown(chan, &papi, &bapi);
-    }
-}
@@ -0,0 +1,265 @@
+//! This module provides the /spectcl/unpacker domain.  This is a
+//! Rustogramer only domain.  Normally Rustogramer analyzes parameter
+//! files made by an external unpacking pipeline.  It can, instead,
+//! analyze raw event files (or rings) by unpacking their physics
+//! events with one of the unpackers registered with the processing
+//! thread (see unpackers::Registry).  The generic _words_ and _longs_
+//! unpackers are always registered.  Unpackers for other digitizers
+//! are added by implementing unpackers::Unpacker and adding a factory
+//! to unpackers::Registry::new.
+//!
+//! Subdomains are:
+//!
+//! *  list - list the registered unpackers.
+//! *  use - choose the unpacker physics events are unpacked with.
+//! *  current - the name of the unpacker in use.
+//!
+use rocket::serde::json::Json;
+use rocket::State;
+
+use super::*;
+
+/// Default number of parameters an unpacker produces.
+pub const DEFAULT_PARAMETERS: usize = 32;
+
+/// List the registered unpackers.
+///
+/// #### Returns
+/// StringArrayResponse whose detail are the names, sorted.
+#[get("/list")]
+pub fn list_unpackers(papi: &State<SharedProcessingApi>) -> Json<StringArrayResponse> {
+    let mut result = StringArrayResponse::new("OK");
+    result.detail = papi.inner().lock().unwrap().unpacker_registry().names();
+    Json(result)
+}
+/// Choose the unpacker physics events are unpacked with.
+///
+/// #### Query Parameters
+/// * name - name of a registered unpacker.  If omitted, physics
+///   events are no longer unpacked.
+/// * parameters - maximum number of parameters the unpacker
+///   produces.  Defaults to DEFAULT_PARAMETERS.
+///
+/// #### Returns
+/// GenericResponse
+#[get("/use?<name>&<parameters>")]
+pub fn use_unpacker(
+    name: OptionalString,
+    parameters: Option<usize>,
+    papi: &State<SharedProcessingApi>,
+    _writable: readonly::Writable,
+    _ready: busy::HistogramerReady,
+) -> Json<GenericResponse> {
+    let unpacker = name
+        .as_deref()
+        .map(|n| (n, parameters.unwrap_or(DEFAULT_PARAMETERS)));
+    match papi.inner().lock().unwrap().set_unpacker(unpacker) {
+        Ok(_) => Json(GenericResponse::ok("")),
+        Err(s) => Json(GenericResponse::err("Unable to use unpacker", &s)),
+    }
+}
+/// The name of the unpacker in use.
+///
+/// #### Returns
+/// GenericResponse whose detail is the name or empty if physics
+/// events are not being unpacked.  The names of event built data
+/// unpackers (see /spectcl/evbunpack) are prefixed with
+/// processing::EVB_UNPACKER_PREFIX.
+#[get("/current")]
+pub fn current_unpacker(papi: &State<SharedProcessingApi>) -> Json<GenericResponse> {
+    match papi.inner().lock().unwrap().get_unpacker() {
+        Ok(name) => Json(GenericResponse::ok(&name.unwrap_or_default())),
+        Err(s) => Json(GenericResponse::err("Unable to get the unpacker", &s)),
+    }
+}
+
+#[cfg(test)]
+mod unpacker_tests {
+    use super::*;
+    use crate::messaging;
+    use crate::processing;
+    use crate::ring_items::analysis_ring_items::{
+        ParameterDefinitions, ParameterFileWriter, ParameterItem,
+    };
+    use crate::ring_items::event_item::PhysicsEvent;
+    use crate::ring_items::{RingVersion, ToRaw};
+    use crate::sharedmem::binder;
+    use crate::unpackers::Unpacker;
+
+    use rocket;
+    use rocket::local::blocking::Client;
+    use rocket::Build;
+    use rocket::Rocket;
+
+    use crate::test::rest_common;
+    use std::sync::mpsc;
+
+    fn setup() -> Rocket<Build> {
+        rest_common::setup().mount("/", routes![list_unpackers, use_unpacker, current_unpacker])
+    }
+    fn teardown(
+        c: mpsc::Sender<messaging::Request>,
+        p: &processing::ProcessingApi,
+        b: &binder::BindingApi,
+    ) {
+        rest_common::teardown(c, p, b);
+    }
+    fn get_state(
+        r: &Rocket<Build>,
+    ) -> (
+        mpsc::Sender<messaging::Request>,
+        processing::ProcessingApi,
+        binder::BindingApi,
+    ) {
+        rest_common::get_state(r)
+    }
+    fn get(client: &Client, uri: &str) -> GenericResponse {
+        client
+            .get(uri)
+            .dispatch()
+            .into_json::<GenericResponse>()
+            .expect("Bad JSON")
+    }
+
+    // An unpacker for a digitizer whose events are a channel number
+    // followed by its value.  The parameters are adc.nnn
+
+    struct AdcUnpacker {
+        channels: usize,
+    }
+    impl Unpacker for AdcUnpacker {
+        fn definitions(&self) -> ParameterDefinitions {
+            let mut result = ParameterDefinitions::new();
+            for i in 0..self.channels {
+                result.add(i as u32 + 1, &format!("adc.{:03}", i));
+            }
+            result
+        }
+        fn unpack(
+            &mut self,
+            event: &mut PhysicsEvent,
+            parameters: &mut ParameterItem,
+        ) -> Result<(), String> {
+            event.rewind();
+            while let Some(channel) = event.get::<u16>() {
+                let value = event
+                    .get::<u16>()
+                    .ok_or_else(|| String::from("Channel without a value"))?;
+                if channel as usize >= self.channels {
+                    return Err(format!("Bad channel {}", channel));
+                }
+                parameters.add(channel as u32 + 1, value as f64);
+            }
+            Ok(())
+        }
+    }
+    fn adc(channels: usize) -> Box<dyn Unpacker> {
+        Box::new(AdcUnpacker { channels })
+    }
+
+    #[test]
+    fn list_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        papi.register_unpacker("adc", adc).expect("Registering");
+
+        let client = Client::tracked(rocket).expect("Making client");
+        let reply = client
+            .get("/list")
+            .dispatch()
+            .into_json::<StringArrayResponse>()
+            .expect("Bad JSON");
+        assert_eq!("OK", reply.status);
+        assert_eq!(vec!["adc", "longs", "words"], reply.detail);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn use_1() {
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+
+        let client = Client::tracked(rocket).expect("Making client");
+        let reply = get(&client, "/current");
+        assert_eq!("OK", reply.status);
+        assert_eq!("", reply.detail);
+
+        let reply = get(&client, "/use?name=longs&parameters=2");
+        assert_eq!("OK", reply.status);
+        assert_eq!("longs", get(&client, "/current").detail);
+        let param_api = messaging::parameter_messages::ParameterMessageClient::new(&chan);
+        assert_eq!(
+            2,
+            param_api
+                .list_parameters("longs.*")
+                .expect("Listing parameters")
+                .len()
+        );
+
+        // Unknown unpackers and no parameters are errors that leave
+        // the unpacker alone:
+
+        let reply = get(&client, "/use?name=junk");
+        assert_eq!("Unable to use unpacker", reply.status);
+        let reply = get(&client, "/use?name=words&parameters=0");
+        assert_eq!("Unable to use unpacker", reply.status);
+        assert_eq!("longs", get(&client, "/current").detail);
+
+        assert_eq!("OK", get(&client, "/use").status);
+        assert_eq!("", get(&client, "/current").detail);
+
+        teardown(chan, &papi, &bapi);
+    }
+    #[test]
+    fn analyze_1() {
+        // A raw event file is analyzed with a registered unpacker:
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let path = dir.path().join("run.evt");
+        let mut writer = ParameterFileWriter::new(
+            std::fs::File::create(&path).expect("Creating file"),
+            RingVersion::V11,
+        )
+        .expect("Making writer");
+        for i in 0..10_u16 {
+            let mut event = PhysicsEvent::new(None);
+            event.add(1_u16).add(i);
+            if i % 2 == 0 {
+                event.add(5_u16); // Bad channel, the event is skipped.
+                event.add(0_u16);
+            }
+            writer.write_item(&event.to_raw()).expect("Writing event");
+        }
+        writer.flush().expect("Flushing");
+
+        let rocket = setup();
+        let (chan, papi, bapi) = get_state(&rocket);
+        papi.register_unpacker("adc", adc).expect("Registering");
+        let client = Client::tracked(rocket).expect("Making client");
+        assert_eq!("OK", get(&client, "/use?name=adc&parameters=4").status);
+
+        let spec_api = messaging::spectrum_messages::SpectrumMessageClient::new(&chan);
+        spec_api
+            .create_spectrum_1d("adc1", "adc.001", 0.0, 10.0, 10)
+            .expect("Creating spectrum");
+
+        papi.attach(path.to_str().unwrap()).expect("attaching file");
+        papi.start_analysis().expect("Starting analysis");
+        let start = std::time::Instant::now();
+        while papi.processing_state().expect("Getting state") != "Inactive" {
+            assert!(start.elapsed() < std::time::Duration::from_secs(10));
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let contents = spec_api
+            .get_contents("adc1", -1.0, 11.0, 0.0, 0.0)
+            .expect("Getting contents");
+        let channels: Vec<(f64, f64)> = contents.iter().map(|c| (c.x, c.value)).collect();
+        assert_eq!(
+            vec![(1.0, 1.0), (3.0, 1.0), (5.0, 1.0), (7.0, 1.0), (9.0, 1.0)],
+            channels
+        );
+
+        teardown(chan, &papi, &bapi);
+    }
+}
@@ -7,8 +7,15 @@
 //!  We also add the package name to the restult so that
 //!  clients can differentiate us from SpecTcl.
 //!
+//!  If Rustogramer is running without some of its capabilities
+//!  (e.g. the shared memory could not be created), these are listed
+//!  as well.
+//!
 
+use super::*;
+use crate::sharedmem::binder::BindingApi;
 use rocket::serde::{json::Json, Deserialize, Serialize};
+use rocket::State;
 use std::env;
 
 ///  This is the detail returned to the client:
@@ -20,6 +27,8 @@ pub struct VersionDetail {
     minor: u32,
     editlevel: u32,
     program_name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    unavailable: Vec<String>,
 }
 
 /// The full result that's turned into JSON for the client:
@@ -40,6 +49,10 @@ pub struct VersionResponse {
 /// * CARGO_PKG_VERSION_PATCH -- The edit level of the program.
 /// * CARGO_PKG_NAME -- The name of the program.
 ///
+/// ### Parameters
+/// * bstate - the binding thread channel, used to find out if the
+/// shared memory is available.
+///
 /// ### Returns:
 /// *  Json serialized VersionResponse.  The only possible
 /// failures are an inability to get and, in the case of version elements,
@@ -47,19 +60,28 @@ pub struct VersionResponse {
 /// Status wil be _Unable to get the program version_ and the
 /// major, minoir, editlevel fields of the detail will be
 /// indeterminate values with the program_name defaulting to _Rustogramer_
+/// If capabilities are unavailable, the detail's unavailable field
+/// has a description of each of them and why it's unavailable.
 ///
 #[get("/")]
-pub fn get_version() -> Json<VersionResponse> {
+pub fn get_version(bstate: &State<SharedBinderChannel>) -> Json<VersionResponse> {
     // initialize for failure:
-    let  result = VersionResponse {
+    let mut result = VersionResponse {
         status: String::from("OK"),
         detail: VersionDetail {
             major: env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap(),
             minor: env!("CARGO_PKG_VERSION_MINOR").parse().unwrap(),
             editlevel: env!("CARGO_PKG_VERSION_PATCH").parse().unwrap(),
             program_name: String::from("Rustogramer"),
+            unavailable: Vec::new(),
         },
     };
+    if let Err(reason) = BindingApi::new(&bstate.inner().lock().unwrap()).check_available() {
+        result
+            .detail
+            .unavailable
+            .push(format!("Spectrum binding and mirroring: {}", reason));
+    }
 
     Json(result)
 }
@@ -67,7 +89,6 @@ pub fn get_version() -> Json<VersionResponse> {
 mod version_tests {
     use super::*;
     use crate::messaging;
-    use crate::rest::*;
     use crate::test::rest_common;
 
     use rocket;
@@ -127,6 +148,7 @@ mod version_tests {
         assert_eq!(major, reply.detail.major);
         assert_eq!(minor, reply.detail.minor);
         assert_eq!(patch, reply.detail.editlevel);
+        assert!(reply.detail.unavailable.is_empty());
 
         teardown(chan, &papi, &bapi);
     }
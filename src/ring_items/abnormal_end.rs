@@ -3,6 +3,7 @@ use std::fmt;
 ///
 /// Abnormal ends are empty actually.
 ///
+#[derive(Default)]
 pub struct AbnormalEnd {}
 
 impl AbnormalEnd {
@@ -23,11 +24,8 @@ impl ring_items::ToRaw for AbnormalEnd {
         ring_items::RingItem::new(ring_items::ABNORMAL_END)
     }
 }
-impl ring_items::FromRaw<AbnormalEnd> for ring_items::RingItem {
-    fn to_specific(
-        self: &ring_items::RingItem,
-        _v: ring_items::RingVersion,
-    ) -> Option<AbnormalEnd> {
+impl<'a> ring_items::FromRaw<AbnormalEnd> for ring_items::RingItemRef<'a> {
+    fn to_specific(&self, _v: ring_items::RingVersion) -> Option<AbnormalEnd> {
         if self.type_id() == ring_items::ABNORMAL_END {
             Some(AbnormalEnd::new())
         } else {
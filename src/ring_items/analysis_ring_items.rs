@@ -1,10 +1,16 @@
 use crate::ring_items;
+use crate::ring_items::format_item;
+use crate::ring_items::ToRaw;
 use std::fmt;
+use std::io;
+use std::io::Write;
 use std::mem;
 use std::slice::Iter;
 
 ///  This module contains definitions and implementations for the internal
-///  structure of
+///  structure of the ring items produced by the FRIB analysis pipeline.
+///  It also provides ParameterFileWriter which external producers can
+///  use to write parameter files rustogramer can analyze.
 
 //------------------------------------------------------------
 // Parameter defiition items:
@@ -12,6 +18,7 @@ use std::slice::Iter;
 
 /// This struct defines the corresopndence between a parameter id and
 /// a parameter name.
+#[derive(Clone)]
 pub struct ParameterDefinition {
     id: u32,
     name: String,
@@ -19,6 +26,7 @@ pub struct ParameterDefinition {
 /// The PARAMETER_DEFINITIONS ring item type is really just
 /// a vector of ParameterDefinitions
 ///
+#[derive(Default, Clone)]
 pub struct ParameterDefinitions {
     defs: Vec<ParameterDefinition>,
 }
@@ -59,6 +67,10 @@ impl ParameterDefinitions {
         self.defs.push(def);
         self
     }
+    /// Add a definition given the parameter id and name.
+    pub fn add(&mut self, id: u32, name: &str) -> &mut Self {
+        self.add_definition(ParameterDefinition::new(id, name))
+    }
 
     // Private methods.
 
@@ -97,14 +109,11 @@ impl ring_items::ToRaw for ParameterDefinitions {
     }
 }
 /// FromRaw trait with ParameterDefitions target:
-impl ring_items::FromRaw<ParameterDefinitions> for ring_items::RingItem {
-    fn to_specific(
-        self: &ring_items::RingItem,
-        _v: ring_items::RingVersion,
-    ) -> Option<ParameterDefinitions> {
+impl<'a> ring_items::FromRaw<ParameterDefinitions> for ring_items::RingItemRef<'a> {
+    fn to_specific(&self, _v: ring_items::RingVersion) -> Option<ParameterDefinitions> {
         if self.type_id() == ring_items::PARAMETER_DEFINITIONS {
             let mut result = ParameterDefinitions::new();
-            let payload = self.payload().as_slice();
+            let payload = self.payload();
             let num = u32::from_ne_bytes(payload[0..4].try_into().unwrap());
 
             let mut offset = 4;
@@ -167,6 +176,7 @@ impl fmt::Display for VariableValue {
 ///
 /// The variable item is really just a sequence of variable values:
 ///
+#[derive(Default)]
 pub struct VariableValues {
     defs: Vec<VariableValue>,
 }
@@ -188,6 +198,10 @@ impl VariableValues {
         self.defs.push(def);
         self
     }
+    /// Add a variable value given its value, name and units.
+    pub fn add(&mut self, value: f64, name: &str, units: &str) -> &mut Self {
+        self.add_def(VariableValue::new(value, name, units))
+    }
 }
 impl fmt::Display for VariableValues {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -210,8 +224,11 @@ impl ring_items::ToRaw for VariableValues {
         for def in self.defs.iter() {
             result.add(def.value);
             // build a units string padded with nulls out to MAX_UNITS_LENGTH:
+            // Units that are too long are truncated to leave room for
+            // at least one null.
 
             let mut units_bytes = String::into_bytes(def.units.clone());
+            units_bytes.truncate(MAX_UNITS_LENGTH - 1);
             while units_bytes.len() < MAX_UNITS_LENGTH {
                 units_bytes.push(0);
             }
@@ -228,14 +245,11 @@ impl ring_items::ToRaw for VariableValues {
 /// The FromRaw trait supports conversion from Raw->VariableValues
 /// If that's possible:
 
-impl ring_items::FromRaw<VariableValues> for ring_items::RingItem {
-    fn to_specific(
-        self: &ring_items::RingItem,
-        _v: ring_items::RingVersion,
-    ) -> Option<VariableValues> {
+impl<'a> ring_items::FromRaw<VariableValues> for ring_items::RingItemRef<'a> {
+    fn to_specific(&self, _v: ring_items::RingVersion) -> Option<VariableValues> {
         if self.type_id() == ring_items::VARIABLE_VALUES {
             let mut result = VariableValues::new();
-            let payload = self.payload().as_slice();
+            let payload = self.payload();
             let nvars = u32::from_ne_bytes(payload[0..4].try_into().unwrap());
             let mut offset = 4;
             for _ in 0..nvars {
@@ -335,13 +349,10 @@ impl ring_items::ToRaw for ParameterItem {
 /// FromFaw provides an attempt to convert a raw item
 /// to a ParameterItem.
 
-impl ring_items::FromRaw<ParameterItem> for ring_items::RingItem {
-    fn to_specific(
-        self: &ring_items::RingItem,
-        _v: ring_items::RingVersion,
-    ) -> Option<ParameterItem> {
+impl<'a> ring_items::FromRaw<ParameterItem> for ring_items::RingItemRef<'a> {
+    fn to_specific(&self, _v: ring_items::RingVersion) -> Option<ParameterItem> {
         if self.type_id() == ring_items::PARAMETER_DATA {
-            let payload = self.payload().as_slice();
+            let payload = self.payload();
             let trigger: u64 = u64::from_ne_bytes(payload[0..8].try_into().unwrap());
             let mut result = ParameterItem::new(trigger);
             let num = u32::from_ne_bytes(payload[8..12].try_into().unwrap());
@@ -369,6 +380,115 @@ impl ring_items::FromRaw<ParameterItem> for ring_items::RingItem {
     }
 }
 
+//---------------------------------------------------------------
+// Writing parameter files:
+//---------------------------------------------------------------
+
+/// Writes parameter files that rustogramer can analyze.  This is intended
+/// for use by external unpackers that produce parameter data.
+/// The file opens with a format item for the ring version requested,
+/// followed by the parameter definitions, optionally the variable values
+/// and then the parameter data for each event.
+///
+/// Note that rustogramer does not read the ring version from the format
+/// item.  The version must still be selected with the ringformat REST
+/// request when the file is analyzed.
+///
+pub struct ParameterFileWriter<W: Write> {
+    file: W,
+    version: ring_items::RingVersion,
+    definitions_written: bool,
+    items_written: usize,
+}
+
+impl<W: Write> ParameterFileWriter<W> {
+    /// Create a writer and write the format item.
+    ///
+    /// ### Parameters
+    /// * file - where the items are written.
+    /// * version - ring item version to declare in the format item.
+    ///
+    /// ### Returns
+    /// * io::Result<ParameterFileWriter> - errors are those writing the
+    /// format item.
+    pub fn new(file: W, version: ring_items::RingVersion) -> io::Result<ParameterFileWriter<W>> {
+        let mut result = ParameterFileWriter {
+            file,
+            version,
+            definitions_written: false,
+            items_written: 0,
+        };
+        let major = match version {
+            ring_items::RingVersion::V11 => 11,
+            ring_items::RingVersion::V12 => 12,
+        };
+        result.write_raw(&format_item::FormatItem::new(major, 0).to_raw())?;
+        Ok(result)
+    }
+    /// Write a parameter definitions item.  This must be done before
+    /// any parameter data items are written.
+    ///
+    /// ### Returns
+    /// * io::Result<usize> - number of bytes written on success.
+    pub fn write_definitions(&mut self, defs: &ParameterDefinitions) -> io::Result<usize> {
+        let result = self.write_raw(&defs.to_raw())?;
+        self.definitions_written = true;
+        Ok(result)
+    }
+    /// Write a variable values item.
+    ///
+    /// ### Returns
+    /// * io::Result<usize> - number of bytes written on success.
+    pub fn write_variables(&mut self, vars: &VariableValues) -> io::Result<usize> {
+        self.write_raw(&vars.to_raw())
+    }
+    /// Write the parameters of an event.
+    ///
+    /// ### Returns
+    /// * io::Result<usize> - number of bytes written on success.  It is
+    /// an error (InvalidInput) to write parameter data before the
+    /// parameter definitions.
+    pub fn write_event(&mut self, event: &ParameterItem) -> io::Result<usize> {
+        if !self.definitions_written {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Parameter definitions must be written before parameter data",
+            ));
+        }
+        self.write_raw(&event.to_raw())
+    }
+    /// Write some other ring item (e.g. a state change item) as is.
+    ///
+    /// ### Returns
+    /// * io::Result<usize> - number of bytes written on success.
+    pub fn write_item(&mut self, item: &ring_items::RingItem) -> io::Result<usize> {
+        self.write_raw(item)
+    }
+    /// Flush any data buffered in the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+    pub fn version(&self) -> ring_items::RingVersion {
+        self.version
+    }
+    /// Number of ring items written (including the format item).
+    pub fn items_written(&self) -> usize {
+        self.items_written
+    }
+    /// Consume the writer returning the underlying file.
+    pub fn into_inner(self) -> W {
+        self.file
+    }
+
+    // Write a raw item and count it:
+
+    fn write_raw(&mut self, item: &ring_items::RingItem) -> io::Result<usize> {
+        let result = item.write_item(&mut self.file)?;
+        self.items_written += 1;
+        Ok(result)
+    }
+}
+
 #[cfg(test)]
 mod test_paramdef {
     use super::*;
@@ -873,4 +993,138 @@ mod param_tests {
 
         assert!(copy.is_none());
     }
+    #[test]
+    fn from_raw_4() {
+        // Conversion in place from a byte buffer:
+
+        let mut orig = ParameterItem::new(12345);
+        orig.add(1, 1.2345).add(65, 5.555);
+        let mut bytes: Vec<u8> = vec![];
+        orig.to_raw().write_item(&mut bytes).unwrap();
+
+        let raw = RingItemRef::from_bytes(&bytes).unwrap();
+        let copy: Option<ParameterItem> = raw.to_specific(RingVersion::V11);
+
+        assert!(copy.is_some());
+        let copy = copy.unwrap();
+
+        assert_eq!(orig.trigger(), copy.trigger());
+        assert_eq!(orig.parameters.len(), copy.parameters.len());
+        for i in 0..orig.parameters.len() {
+            assert_eq!(orig.parameters[i].id(), copy.parameters[i].id());
+            assert_eq!(orig.parameters[i].value(), copy.parameters[i].value());
+        }
+    }
+}
+#[cfg(test)]
+mod writer_tests {
+    use super::*;
+    use crate::ring_items::*;
+
+    // Parse the ring items in a buffer:
+
+    fn parse_items(bytes: &[u8]) -> Vec<RingItemRef<'_>> {
+        let mut result = vec![];
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let item = RingItemRef::from_bytes(&bytes[offset..]).unwrap();
+            offset += item.size() as usize;
+            result.push(item);
+        }
+        result
+    }
+
+    #[test]
+    fn new_1() {
+        // Format item is written for the version:
+
+        let writer = ParameterFileWriter::new(vec![], RingVersion::V12).unwrap();
+        assert!(writer.version() == RingVersion::V12);
+        assert_eq!(1, writer.items_written());
+        let bytes = writer.into_inner();
+        let items = parse_items(&bytes);
+        assert_eq!(1, items.len());
+        let fmt: Option<format_item::FormatItem> = items[0].to_specific(RingVersion::V12);
+        let fmt = fmt.unwrap();
+        assert_eq!(12, fmt.major());
+        assert_eq!(0, fmt.minor());
+
+        let writer = ParameterFileWriter::new(vec![], RingVersion::V11).unwrap();
+        let bytes = writer.into_inner();
+        let fmt: Option<format_item::FormatItem> =
+            parse_items(&bytes)[0].to_specific(RingVersion::V11);
+        assert_eq!(11, fmt.unwrap().major());
+    }
+    #[test]
+    fn event_1() {
+        // Events can't be written before definitions:
+
+        let mut writer = ParameterFileWriter::new(vec![], RingVersion::V12).unwrap();
+        let mut event = ParameterItem::new(1);
+        event.add(1, 1.0);
+        let result = writer.write_event(&event);
+        assert!(result.is_err());
+        assert_eq!(io::ErrorKind::InvalidInput, result.unwrap_err().kind());
+        assert_eq!(1, writer.items_written());
+    }
+    #[test]
+    fn file_1() {
+        // Write a complete file and read it back.
+
+        let mut writer = ParameterFileWriter::new(vec![], RingVersion::V12).unwrap();
+        let mut defs = ParameterDefinitions::new();
+        defs.add(1, "x").add(2, "y");
+        let mut vars = VariableValues::new();
+        vars.add(1.5, "slope", "keV/channel");
+        writer.write_definitions(&defs).unwrap();
+        writer.write_variables(&vars).unwrap();
+        for i in 0..10 {
+            let mut event = ParameterItem::new(i);
+            event.add(1, i as f64).add(2, (i * 2) as f64);
+            writer.write_event(&event).unwrap();
+        }
+        writer.flush().unwrap();
+        assert_eq!(13, writer.items_written());
+
+        let bytes = writer.into_inner();
+        let items = parse_items(&bytes);
+        assert_eq!(13, items.len());
+        assert_eq!(FORMAT_ITEM, items[0].type_id());
+
+        let d: Option<ParameterDefinitions> = items[1].to_specific(RingVersion::V12);
+        let d = d.unwrap();
+        assert_eq!(2, d.iter().count());
+        assert_eq!(String::from("x"), d.defs[0].name());
+        assert_eq!(2, d.defs[1].id());
+
+        let v: Option<VariableValues> = items[2].to_specific(RingVersion::V12);
+        let v = v.unwrap();
+        assert_eq!(1.5, v.defs[0].value());
+        assert_eq!(String::from("keV/channel"), v.defs[0].units());
+
+        for (i, item) in items[3..].iter().enumerate() {
+            assert!(!item.has_body_header());
+            let e: Option<ParameterItem> = item.to_specific(RingVersion::V12);
+            let e = e.unwrap();
+            assert_eq!(i as u64, e.trigger());
+            assert_eq!(2, e.parameters.len());
+            assert_eq!((i * 2) as f64, e.parameters[1].value());
+        }
+    }
+    #[test]
+    fn units_1() {
+        // Overly long units are truncated so the name is still readable.
+
+        let long_units = "x".repeat(2 * MAX_UNITS_LENGTH);
+        let mut vars = VariableValues::new();
+        vars.add(1.0, "var", &long_units);
+        let raw = vars.to_raw();
+        let v: Option<VariableValues> = raw.to_specific(RingVersion::V12);
+        let v = v.unwrap();
+        assert_eq!(
+            &long_units[0..MAX_UNITS_LENGTH - 1],
+            v.defs[0].units().as_str()
+        );
+        assert_eq!(String::from("var"), v.defs[0].name());
+    }
 }
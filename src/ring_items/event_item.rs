@@ -84,6 +84,11 @@ impl PhysicsEvent {
     pub fn body_size(&self) -> usize {
         self.event_data.len()
     }
+    ///  The event body:
+    ///
+    pub fn body(&self) -> &[u8] {
+        &self.event_data
+    }
 }
 
 impl Iterator for PhysicsEvent {
@@ -164,7 +169,7 @@ impl ring_items::ToRaw for PhysicsEvent {
 /// From raw, a generic trait for RingItem allows an attempt to
 /// convert a ring item to a specific type:
 
-impl ring_items::FromRaw<PhysicsEvent> for ring_items::RingItem {
+impl<'a> ring_items::FromRaw<PhysicsEvent> for ring_items::RingItemRef<'a> {
     fn to_specific(&self, _v: ring_items::RingVersion) -> Option<PhysicsEvent> {
         if self.type_id() == ring_items::PHYSICS_EVENT {
             let mut payload_offset = 0;
@@ -174,7 +179,7 @@ impl ring_items::FromRaw<PhysicsEvent> for ring_items::RingItem {
             }
             result
                 .event_data
-                .extend_from_slice(&self.payload().as_slice()[payload_offset..]);
+                .extend_from_slice(&self.payload()[payload_offset..]);
             Some(result)
         } else {
             None
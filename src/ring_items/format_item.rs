@@ -40,13 +40,13 @@ impl ring_items::ToRaw for FormatItem {
 /// FromRaw provides a checked method for converting a raw item
 /// into a FormatItem:
 
-impl ring_items::FromRaw<FormatItem> for ring_items::RingItem {
+impl<'a> ring_items::FromRaw<FormatItem> for ring_items::RingItemRef<'a> {
     fn to_specific(&self, _v: ring_items::RingVersion) -> Option<FormatItem> {
         if self.type_id() != ring_items::FORMAT_ITEM {
             return None;
         }
         let mut result = FormatItem::new(0, 0);
-        let payload = self.payload().as_slice();
+        let payload = self.payload();
 
         // The first u16 is the major, the second u16 is the
         // minor:
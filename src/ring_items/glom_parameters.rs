@@ -103,11 +103,11 @@ impl ring_items::ToRaw for GlomParameters {
 /// FromRaw implementations of the generic allow attempts to convert
 /// from a generic RingItem to a specific type (e.g. GlomParameters)
 
-impl ring_items::FromRaw<GlomParameters> for ring_items::RingItem {
+impl<'a> ring_items::FromRaw<GlomParameters> for ring_items::RingItemRef<'a> {
     fn to_specific(&self, _v: ring_items::RingVersion) -> Option<GlomParameters> {
         if self.type_id() == ring_items::GLOM_INFO {
             let mut result = GlomParameters::new(0, true, TimestampPolicy::First);
-            let payload = self.payload().as_slice();
+            let payload = self.payload();
 
             result.coincidence_ticks = u64::from_ne_bytes(payload[0..8].try_into().unwrap());
             result.is_building = u16::from_ne_bytes(payload[8..10].try_into().unwrap()) != 0;
@@ -8,6 +8,13 @@
 //!   from specific ring item types to RingItem (ToRaw trait), and
 //!   attempted conversions from
 //!   RingItem to specific ring item types (FromRaw trait).
+//!
+//!   RingItemRef is a view of a ring item that borrows its payload
+//!   from a byte buffer (e.g. the buffer of a BufReader) rather than
+//!   owning a copy.  The FromRaw conversions are implemented on
+//!   RingItemRef so that specific ring items can be parsed in place
+//!   without first copying the raw item.  RingItem gets those
+//!   conversions through its view() method.
 //!  
 //!    In addition all ring item types are printable since they
 //!    implement the Display trait.
@@ -42,6 +49,17 @@ pub struct RingItem {
     body_header_size: u32,
     payload: Vec<u8>,
 }
+/// A ring item whose payload is borrowed from a buffer.
+/// This is produced either by parsing a byte buffer (from_bytes)
+/// or as a view of a RingItem (RingItem::view).
+///
+#[derive(Clone, Copy)]
+pub struct RingItemRef<'a> {
+    size: u32,
+    type_id: u32,
+    body_header_size: u32,
+    payload: &'a [u8],
+}
 #[derive(Clone, Copy)]
 pub struct BodyHeader {
     pub timestamp: u64,
@@ -73,20 +91,43 @@ impl fmt::Display for RingItemError {
 }
 pub type RingItemResult = Result<RingItem, RingItemError>;
 
-impl RingItem {
-    // Private methods:
+// The size of the ring item header (size, type and body header size):
 
-    // Read a u32:
+const RING_ITEM_HEADER_SIZE: usize = 3 * mem::size_of::<u32>();
 
-    fn read_long<T: Read>(f: &mut T) -> Result<u32, u8> {
-        let mut buf: [u8; 4] = [0; 4];
+// Get the u32 at an offset in a byte slice:
 
-        if f.read_exact(&mut buf).is_ok() {
-            Ok(u32::from_ne_bytes(buf))
-        } else {
-            Err(0)
+fn get_long(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+// A body header size includes its own u32 and get_bodyheader
+// decodes a full body header from the payload so it must fit:
+
+fn check_bodyheader_size(bh_size: u32, payload: &[u8]) -> Result<(), RingItemError> {
+    let bh_size = bh_size as usize;
+    if bh_size > mem::size_of::<u32>() {
+        let bh_payload = bh_size - mem::size_of::<u32>();
+        if bh_payload < body_header_size() || bh_payload > payload.len() {
+            return Err(RingItemError::InvalidHeader);
         }
     }
+    Ok(())
+}
+
+// Decode a body header from the front of a payload:
+
+fn decode_bodyheader(payload: &[u8]) -> BodyHeader {
+    BodyHeader {
+        timestamp: u64::from_ne_bytes(payload[0..8].try_into().unwrap()),
+        source_id: get_long(payload, 8),
+        barrier_type: get_long(payload, 12),
+    }
+}
+
+impl RingItem {
+    // Private methods:
+
     /// Write a u32:
 
     fn write_long<T: Write>(f: &mut T, l: u32) -> std::io::Result<usize> {
@@ -131,16 +172,16 @@ impl RingItem {
     /// Fetch the body header from the payload... if there is one.
     ///
     pub fn get_bodyheader(&self) -> Option<BodyHeader> {
-        if self.has_body_header() {
-            Some(BodyHeader {
-                timestamp: u64::from_ne_bytes(self.payload.as_slice()[0..8].try_into().unwrap()),
-                source_id: u32::from_ne_bytes(self.payload.as_slice()[8..12].try_into().unwrap()),
-                barrier_type: u32::from_ne_bytes(
-                    self.payload.as_slice()[12..16].try_into().unwrap(),
-                ),
-            })
-        } else {
-            None
+        self.view().get_bodyheader()
+    }
+    /// Borrow the ring item as a RingItemRef.  This is how
+    /// RingItems are converted to specific ring item types.
+    pub fn view(&self) -> RingItemRef<'_> {
+        RingItemRef {
+            size: self.size,
+            type_id: self.type_id,
+            body_header_size: self.body_header_size,
+            payload: self.payload.as_slice(),
         }
     }
     pub fn payload(&self) -> &Vec<u8> {
@@ -156,27 +197,21 @@ impl RingItem {
     ///   This is best used to put primitive types into the
     ///   payload
     pub fn add<T>(&mut self, item: T) -> &mut RingItem {
-        let pt = &item as *const T;
-        let mut p = pt.cast::<u8>();
+        let p = (&item as *const T).cast::<u8>();
 
-        // Now I have a byte pointer I can push the bytes of data
-        // into the vector payload:
+        // View the item as a slice of bytes and append it in one go:
 
-        for _i in 0..mem::size_of::<T>() {
-            unsafe {
-                self.payload.push(*p);
-                p = p.offset(1);
-            }
-        }
+        let bytes = unsafe { std::slice::from_raw_parts(p, mem::size_of::<T>()) };
+        self.payload.extend_from_slice(bytes);
         self.size += mem::size_of::<T>() as u32;
         self
     }
     pub fn add_byte_vec(&mut self, v: &Vec<u8>) {
-        for b in v {
-            self.add(*b);
-        }
+        self.payload.extend_from_slice(v);
+        self.size += v.len() as u32;
     }
-    /// Read a ring item from file.
+    /// Read a ring item from file.  A body header that does not fit in
+    /// the item is a RingItemError::InvalidHeader.
 
     pub fn read_item<T: Read>(file: &mut T) -> RingItemResult {
         // Create a new ring item - type is unimportant since
@@ -184,31 +219,19 @@ impl RingItem {
 
         let mut item = RingItem::new(0);
 
-        // The header fields must be read individually b/c
-        // rust could have rearranged them  read only reads
-        // to u8 arrays so we need to read and then copy into
-        // the fields:
+        // The header is read in one go and then the fields are
+        // pulled out of it as rust could have rearranged them:
 
-        if let Ok(n) = RingItem::read_long(file) {
-            item.size = n;
-        } else {
+        let mut header: [u8; RING_ITEM_HEADER_SIZE] = [0; RING_ITEM_HEADER_SIZE];
+        if file.read_exact(&mut header).is_err() {
             return Err(RingItemError::HeaderReadFailed);
         }
-        if item.size < 3 * mem::size_of::<u32>() as u32 {
+        item.size = get_long(&header, 0);
+        if item.size < RING_ITEM_HEADER_SIZE as u32 {
             return Err(RingItemError::InvalidHeader);
         }
-
-        if let Ok(n) = RingItem::read_long(file) {
-            item.type_id = n;
-        } else {
-            return Err(RingItemError::HeaderReadFailed);
-        }
-
-        if let Ok(n) = RingItem::read_long(file) {
-            item.body_header_size = n;
-        } else {
-            return Err(RingItemError::HeaderReadFailed);
-        }
+        item.type_id = get_long(&header, 4);
+        item.body_header_size = get_long(&header, 8);
 
         // Figure out how many bytes are in the body
         // and read those into the veftor:
@@ -220,6 +243,7 @@ impl RingItem {
                 return Err(RingItemError::FileTooSmall);
             }
         }
+        check_bodyheader_size(item.body_header_size, &item.payload)?;
 
         Ok(item)
     }
@@ -240,6 +264,74 @@ impl RingItem {
     }
 }
 
+impl<'a> RingItemRef<'a> {
+    /// Parse a ring item in place at the front of a byte buffer.
+    /// The payload of the result borrows from the buffer.
+    ///
+    /// ### Parameters
+    /// * bytes - the buffer.  It may contain more than one ring item.
+    ///
+    /// ### Returns
+    /// * RingItemRef on success.  Its size() is the number of bytes of
+    ///   the buffer it occupies.
+    /// * RingItemError::HeaderReadFailed if the buffer is too small to
+    ///   hold a header, RingItemError::InvalidHeader if the header is
+    ///   bad (including a body header that does not fit in the item) and
+    ///   RingItemError::FileTooSmall if the buffer does not hold the
+    ///   entire ring item.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<RingItemRef<'a>, RingItemError> {
+        if bytes.len() < RING_ITEM_HEADER_SIZE {
+            return Err(RingItemError::HeaderReadFailed);
+        }
+        let size = get_long(bytes, 0);
+        if size < RING_ITEM_HEADER_SIZE as u32 {
+            return Err(RingItemError::InvalidHeader);
+        }
+        if bytes.len() < size as usize {
+            return Err(RingItemError::FileTooSmall);
+        }
+        let payload = &bytes[RING_ITEM_HEADER_SIZE..size as usize];
+        let bh_size = get_long(bytes, 8);
+        check_bodyheader_size(bh_size, payload)?;
+        Ok(RingItemRef {
+            size,
+            type_id: get_long(bytes, 4),
+            body_header_size: bh_size,
+            payload,
+        })
+    }
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+    pub fn type_id(&self) -> u32 {
+        self.type_id
+    }
+    pub fn has_body_header(&self) -> bool {
+        self.body_header_size > mem::size_of::<u32>() as u32
+    }
+    /// Fetch the body header from the payload... if there is one.
+    ///
+    pub fn get_bodyheader(&self) -> Option<BodyHeader> {
+        if self.has_body_header() {
+            Some(decode_bodyheader(self.payload))
+        } else {
+            None
+        }
+    }
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+    /// Copy the view into an owned RingItem.
+    pub fn to_item(self) -> RingItem {
+        RingItem {
+            size: self.size,
+            type_id: self.type_id,
+            body_header_size: self.body_header_size,
+            payload: self.payload.to_vec(),
+        }
+    }
+}
+
 /// provide for textual formatting of a raw ring item:
 
 impl fmt::Display for RingItem {
@@ -276,12 +368,22 @@ pub trait ToRaw {
     fn to_raw(&self) -> RingItem;
 }
 /// This can be implemented for each destination type
-/// e.g. `ConvertRaw<StateChange>` for RingItem to convert a raw
+/// e.g. `FromRaw<StateChange>` for RingItemRef to convert a raw
 /// item to a ring item if possible (correct type_id).
 ///
 pub trait FromRaw<T> {
     fn to_specific(&self, vers: RingVersion) -> Option<T>;
 }
+/// RingItem supports every conversion RingItemRef does by viewing
+/// its payload in place.
+impl<T> FromRaw<T> for RingItem
+where
+    for<'a> RingItemRef<'a>: FromRaw<T>,
+{
+    fn to_specific(&self, vers: RingVersion) -> Option<T> {
+        self.view().to_specific(vers)
+    }
+}
 
 /// convert a u32 into a SystemTime:
 
@@ -386,7 +488,7 @@ pub const PARAMETER_DATA: u32 = 32770;
 //
 #[cfg(test)]
 mod tests {
-    use crate::ring_items::RingItem;
+    use crate::ring_items::{RingItem, RingItemError, RingItemRef};
     use std::io::{Seek, Write};
     use std::mem;
     use std::ptr;
@@ -643,6 +745,23 @@ mod tests {
         assert_eq!(out_item.payload, item.payload);
     }
     #[test]
+    fn read_5() {
+        // A body header that does not fit in the item is an invalid header:
+
+        let mut file = tempfile().unwrap();
+        file.write_all(&u32::to_ne_bytes(4 * mem::size_of::<u32>() as u32))
+            .unwrap();
+        file.write_all(&u32::to_ne_bytes(1)).unwrap();
+        file.write_all(&u32::to_ne_bytes(20)).unwrap();
+        file.write_all(&u32::to_ne_bytes(0x12345678)).unwrap();
+        file.rewind().unwrap();
+
+        assert!(matches!(
+            RingItem::read_item(&mut file),
+            Err(RingItemError::InvalidHeader)
+        ));
+    }
+    #[test]
     fn write_1() {
         // Write minimal item should read bnack the same.
 
@@ -716,6 +835,87 @@ mod tests {
         assert_eq!(out_item.body_header_size, in_item.body_header_size);
         assert_eq!(out_item.payload, in_item.payload);
     }
+    // RingItemRef tests:
+
+    #[test]
+    fn ref_1() {
+        // Parse an item written to a buffer; trailing bytes are not
+        // part of the item.
+
+        let mut out_item = RingItem::new_with_body_header(1, 0x1245123412, 2, 0);
+        let payload: Vec<u8> = vec![5, 4, 3, 2, 1, 0];
+        out_item.add_byte_vec(&payload);
+
+        let mut bytes: Vec<u8> = vec![];
+        out_item.write_item(&mut bytes).unwrap();
+        bytes.extend_from_slice(&[0xff, 0xff]);
+
+        let item = RingItemRef::from_bytes(&bytes).unwrap();
+        assert_eq!(out_item.size, item.size());
+        assert_eq!(out_item.type_id, item.type_id());
+        assert!(item.has_body_header());
+        let bh = item.get_bodyheader().unwrap();
+        assert_eq!(0x1245123412, bh.timestamp);
+        assert_eq!(2, bh.source_id);
+        assert_eq!(0, bh.barrier_type);
+        assert_eq!(out_item.payload.as_slice(), item.payload());
+        assert!(ptr::eq(&bytes[12], &item.payload()[0]));
+
+        let owned = item.to_item();
+        assert_eq!(out_item.size, owned.size);
+        assert_eq!(out_item.body_header_size, owned.body_header_size);
+        assert_eq!(out_item.payload, owned.payload);
+    }
+    #[test]
+    fn ref_2() {
+        // Truncated buffers and bad headers:
+
+        let mut out_item = RingItem::new(1);
+        out_item.add(0x12345678_u32);
+        let mut bytes: Vec<u8> = vec![];
+        out_item.write_item(&mut bytes).unwrap();
+
+        assert!(matches!(
+            RingItemRef::from_bytes(&bytes[0..8]),
+            Err(RingItemError::HeaderReadFailed)
+        ));
+        assert!(matches!(
+            RingItemRef::from_bytes(&bytes[0..bytes.len() - 1]),
+            Err(RingItemError::FileTooSmall)
+        ));
+        // Body headers that don't fit in the payload or are too small
+        // to decode:
+
+        for bh_size in [20_u32, 12] {
+            let mut bad_bh = bytes.clone();
+            bad_bh[8..12].copy_from_slice(&bh_size.to_ne_bytes());
+            assert!(matches!(
+                RingItemRef::from_bytes(&bad_bh),
+                Err(RingItemError::InvalidHeader)
+            ));
+        }
+        bytes[0] = 4;
+        bytes[1] = 0;
+        bytes[2] = 0;
+        bytes[3] = 0;
+        assert!(matches!(
+            RingItemRef::from_bytes(&bytes),
+            Err(RingItemError::InvalidHeader)
+        ));
+    }
+    #[test]
+    fn ref_3() {
+        // view borrows the item's payload:
+
+        let mut item = RingItem::new(5);
+        item.add(1234_u32);
+        let view = item.view();
+        assert_eq!(item.size, view.size());
+        assert_eq!(item.type_id, view.type_id());
+        assert!(!view.has_body_header());
+        assert!(view.get_bodyheader().is_none());
+        assert!(ptr::eq(item.payload.as_slice(), view.payload()));
+    }
     // Unbound functions:
     // Round time time conversion:
 
@@ -52,6 +52,9 @@ impl ScalerItem {
     pub fn len(&self) -> usize {
         self.scalers.len()
     }
+    pub fn is_empty(&self) -> bool {
+        self.scalers.is_empty()
+    }
     pub fn iter(&self) -> Iter<'_, u32> {
         self.scalers.iter()
     }
@@ -158,7 +161,7 @@ impl ring_items::ToRaw for ScalerItem {
 /// FromRaw is a generic implemented on RingItem types to
 /// attempt conversion to a specific type (e.g. ScalerItem in this
 /// case)
-impl ring_items::FromRaw<ScalerItem> for ring_items::RingItem {
+impl<'a> ring_items::FromRaw<ScalerItem> for ring_items::RingItemRef<'a> {
     fn to_specific(&self, fmt: ring_items::RingVersion) -> Option<ScalerItem> {
         if self.type_id() == ring_items::PERIODIC_SCALERS {
             // Pull parameters from the raw item:
@@ -169,7 +172,7 @@ impl ring_items::FromRaw<ScalerItem> for ring_items::RingItem {
             } else {
                 0
             };
-            let p = self.payload().as_slice();
+            let p = self.payload();
             let start = u32::from_ne_bytes(p[offset..offset + 4].try_into().unwrap());
             let end = u32::from_ne_bytes(p[offset + 4..offset + 8].try_into().unwrap());
             let raw_stamp = u32::from_ne_bytes(p[offset + 8..offset + 12].try_into().unwrap());
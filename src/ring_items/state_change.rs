@@ -217,7 +217,7 @@ impl ring_items::ToRaw for StateChange {
         item
     }
 }
-impl ring_items::FromRaw<StateChange> for ring_items::RingItem {
+impl<'a> ring_items::FromRaw<StateChange> for ring_items::RingItemRef<'a> {
     fn to_specific(&self, version: ring_items::RingVersion) -> Option<StateChange> {
         let body_header = self.get_bodyheader(); // Option of body header.
         if let Some(type_enum) = StateChange::type_from_type_id(self.type_id()) {
@@ -231,7 +231,7 @@ impl ring_items::FromRaw<StateChange> for ring_items::RingItem {
             };
             // Now we can fetch stuff out of the body:
 
-            let payload = self.payload().as_slice();
+            let payload = self.payload();
             result.run_number =
                 u32::from_ne_bytes(payload[body_pos..body_pos + 4].try_into().unwrap());
             result.time_offset =
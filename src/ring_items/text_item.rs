@@ -187,7 +187,7 @@ impl ring_items::ToRaw for TextItem {
     }
 }
 
-impl ring_items::FromRaw<TextItem> for ring_items::RingItem {
+impl<'a> ring_items::FromRaw<TextItem> for ring_items::RingItemRef<'a> {
     fn to_specific(&self, vers: ring_items::RingVersion) -> Option<TextItem> {
         // figure out the correct value for the
         // type:
@@ -212,7 +212,7 @@ impl ring_items::FromRaw<TextItem> for ring_items::RingItem {
             } else {
                 0
             };
-            let p = self.payload().as_slice();
+            let p = self.payload();
             result.time_offset = u32::from_ne_bytes(p[offset..offset + 4].try_into().unwrap());
             result.absolute_time = ring_items::raw_to_systime(u32::from_ne_bytes(
                 p[offset + 4..offset + 8].try_into().unwrap(),
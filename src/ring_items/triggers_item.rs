@@ -103,7 +103,7 @@ impl ring_items::ToRaw for PhysicsEventCountItem {
     }
 }
 
-impl ring_items::FromRaw<PhysicsEventCountItem> for ring_items::RingItem {
+impl<'a> ring_items::FromRaw<PhysicsEventCountItem> for ring_items::RingItemRef<'a> {
     fn to_specific(&self, version: ring_items::RingVersion) -> Option<PhysicsEventCountItem> {
         if self.type_id() == ring_items::PHYSICS_EVENT_COUNT {
             let mut result = PhysicsEventCountItem::new(None, 0, 1, None, 0);
@@ -113,7 +113,7 @@ impl ring_items::FromRaw<PhysicsEventCountItem> for ring_items::RingItem {
             } else {
                 0
             };
-            let payload = self.payload().as_slice();
+            let payload = self.payload();
             result.time_offset =
                 u32::from_ne_bytes(payload[offset..offset + 4].try_into().unwrap());
             result.time_divisor =
@@ -0,0 +1,350 @@
+//!  This module provides the parser for the restricted command language
+//!  accepted by the /spectcl/script REST interface.  Rustogramer has no
+//!  Tcl interpreter but automation scripts written for SpecTcl mostly
+//!  consist of definition commands like:
+//!
+//! ```text
+//!  treeparameter -create raw.0 0 4095 4096 channels
+//!  spectrum raw.0 1 raw.0 {{0 4095 4096}}
+//!  gate peak s {raw.0 {100 200}}; apply peak raw.0
+//! ```
+//!
+//!  The language is therefore the subset of Tcl syntax needed to write
+//!  those commands:
+//!
+//!  *  Commands are separated by newlines or semicolons.
+//!  *  A command whose first word starts with _#_ is a comment and
+//!     extends to the end of the line.
+//!  *  Words are separated by whitespace.  A word enclosed in braces
+//!     is taken verbatim (braces nest) and may span lines.  A word
+//!     enclosed in double quotes may contain whitespace and semicolons.
+//!  *  A backslash outside of braces makes the next character literal.
+//!  *  There is no variable or command substitution;  unquoted _$_ and
+//!     _[_ are errors rather than being silently taken literally.
+//!
+//!  Executing the commands is up to the caller (see rest/script.rs).
+//!
+
+/// A command parsed from a script.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Command {
+    /// Line number (from 1) the command starts on.
+    pub line: usize,
+    /// The words of the command with braces and quotes removed.
+    pub words: Vec<String>,
+}
+
+// Walks the characters of a script or list keeping track of the line:
+
+struct Scanner {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    script: bool, // Command separators and substitution checks apply.
+}
+
+impl Scanner {
+    fn new(text: &str, script: bool) -> Scanner {
+        Scanner {
+            chars: text.chars().collect(),
+            pos: 0,
+            line: 1,
+            script,
+        }
+    }
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if let Some(ch) = c {
+            self.pos += 1;
+            if ch == '\n' {
+                self.line += 1;
+            }
+        }
+        c
+    }
+    fn is_separator(&self, c: char) -> bool {
+        self.script && (c == '\n' || c == ';')
+    }
+    // Skip whitespace within a command (or anywhere in a list).
+
+    fn skip_blanks(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() || self.is_separator(c) {
+                break;
+            }
+            self.next();
+        }
+    }
+    // A quoted or braced word must be followed by the end of the word:
+
+    fn check_word_end(&self, what: &str) -> Result<(), String> {
+        match self.peek() {
+            Some(c) if !c.is_whitespace() && !self.is_separator(c) => {
+                Err(format!("extra characters after close-{}", what))
+            }
+            _ => Ok(()),
+        }
+    }
+    fn check_substitution(&self, c: char) -> Result<(), String> {
+        if self.script && (c == '$' || c == '[') {
+            Err(format!(
+                "'{}' substitution is not supported - quote it with braces or a backslash",
+                c
+            ))
+        } else {
+            Ok(())
+        }
+    }
+    // The opening brace has been consumed:
+
+    fn braced(&mut self) -> Result<String, String> {
+        let mut word = String::new();
+        let mut depth = 1;
+        while let Some(c) = self.next() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.check_word_end("brace")?;
+                        return Ok(word);
+                    }
+                }
+                '\\' => {
+                    // Escaped braces don't count towards nesting:
+
+                    word.push(c);
+                    if let Some(escaped) = self.next() {
+                        word.push(escaped);
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            word.push(c);
+        }
+        Err(String::from("missing close-brace"))
+    }
+    // The opening quote has been consumed:
+
+    fn quoted(&mut self) -> Result<String, String> {
+        let mut word = String::new();
+        while let Some(c) = self.next() {
+            match c {
+                '"' => {
+                    self.check_word_end("quote")?;
+                    return Ok(word);
+                }
+                '\\' => {
+                    if let Some(escaped) = self.next() {
+                        word.push(escaped);
+                    }
+                }
+                _ => {
+                    self.check_substitution(c)?;
+                    word.push(c);
+                }
+            }
+        }
+        Err(String::from("missing \""))
+    }
+    fn bare(&mut self) -> Result<String, String> {
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || self.is_separator(c) {
+                break;
+            }
+            self.next();
+            if c == '\\' {
+                if let Some(escaped) = self.next() {
+                    word.push(escaped);
+                }
+            } else {
+                self.check_substitution(c)?;
+                word.push(c);
+            }
+        }
+        Ok(word)
+    }
+    // Get the next word;  the caller has skipped blanks and there is one.
+
+    fn word(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some('{') => {
+                self.next();
+                self.braced()
+            }
+            Some('"') => {
+                self.next();
+                self.quoted()
+            }
+            _ => self.bare(),
+        }
+    }
+}
+
+/// Parse a script into its commands.  Empty commands and comments are
+/// dropped.  On failure, the error describes the problem and the line
+/// it was found on.
+pub fn parse(script: &str) -> Result<Vec<Command>, String> {
+    let mut scanner = Scanner::new(script, true);
+    let mut commands = Vec::<Command>::new();
+    loop {
+        // Skip to the start of the next command:
+
+        while let Some(c) = scanner.peek() {
+            if !c.is_whitespace() && c != ';' {
+                break;
+            }
+            scanner.next();
+        }
+        if scanner.peek().is_none() {
+            break;
+        }
+        let line = scanner.line;
+        if scanner.peek() == Some('#') {
+            while let Some(c) = scanner.next() {
+                if c == '\n' {
+                    break;
+                }
+            }
+            continue;
+        }
+        let mut words = Vec::<String>::new();
+        loop {
+            scanner.skip_blanks();
+            match scanner.peek() {
+                None => break,
+                Some(c) if scanner.is_separator(c) => break,
+                _ => {}
+            }
+            words.push(
+                scanner
+                    .word()
+                    .map_err(|s| format!("line {}: {}", scanner.line, s))?,
+            );
+        }
+        commands.push(Command { line, words });
+    }
+    Ok(commands)
+}
+
+/// Split a list (e.g. the contents of a braced word) into its elements
+/// using the same rules as words are split.  Newlines and semicolons are
+/// just whitespace in a list.
+pub fn split_list(text: &str) -> Result<Vec<String>, String> {
+    let mut scanner = Scanner::new(text, false);
+    let mut elements = Vec::<String>::new();
+    loop {
+        scanner.skip_blanks();
+        if scanner.peek().is_none() {
+            break;
+        }
+        elements.push(scanner.word()?);
+    }
+    Ok(elements)
+}
+
+#[cfg(test)]
+mod script_tests {
+    use super::*;
+
+    fn words(w: &[&str]) -> Vec<String> {
+        w.iter().map(|s| String::from(*s)).collect()
+    }
+
+    #[test]
+    fn parse_1() {
+        // Commands are separated by newlines and semicolons and
+        // empty commands are dropped:
+
+        let commands = parse("a b c\n\n  d e ; f\n;;").expect("Parsing");
+        assert_eq!(
+            vec![
+                Command {
+                    line: 1,
+                    words: words(&["a", "b", "c"])
+                },
+                Command {
+                    line: 3,
+                    words: words(&["d", "e"])
+                },
+                Command {
+                    line: 3,
+                    words: words(&["f"])
+                },
+            ],
+            commands
+        );
+        assert!(parse("").expect("Parsing").is_empty());
+        assert!(parse(" \n ; \t").expect("Parsing").is_empty());
+    }
+    #[test]
+    fn parse_2() {
+        // Comments:
+
+        let commands = parse("# a comment; still a comment\n  # another\na #b").expect("Parsing");
+        assert_eq!(1, commands.len());
+        assert_eq!(3, commands[0].line);
+        assert_eq!(words(&["a", "#b"]), commands[0].words);
+    }
+    #[test]
+    fn parse_3() {
+        // Braced words are verbatim, nest and can span lines:
+
+        let commands =
+            parse("spectrum s 2 {x y} {{0 1 2}\n {0 1 2}}; gate g T {}\nnext").expect("Parsing");
+        assert_eq!(3, commands.len());
+        assert_eq!(
+            words(&["spectrum", "s", "2", "x y", "{0 1 2}\n {0 1 2}"]),
+            commands[0].words
+        );
+        assert_eq!(words(&["gate", "g", "T", ""]), commands[1].words);
+        assert_eq!(3, commands[2].line);
+
+        let commands = parse("a {$x [y]; \\}}").expect("Parsing");
+        assert_eq!(words(&["a", "$x [y]; \\}"]), commands[0].words);
+    }
+    #[test]
+    fn parse_4() {
+        // Quotes and backslashes:
+
+        let commands = parse("a \"b c; d\" e\\ f \"g\\\"h\"").expect("Parsing");
+        assert_eq!(words(&["a", "b c; d", "e f", "g\"h"]), commands[0].words);
+        let commands = parse("a \\$x").expect("Parsing");
+        assert_eq!(words(&["a", "$x"]), commands[0].words);
+    }
+    #[test]
+    fn parse_5() {
+        // Failures report the line:
+
+        for (script, line) in [
+            ("a\nb {c", 2),
+            ("a \"b", 1),
+            ("a {b}c", 1),
+            ("a \"b\"c", 1),
+            ("\na $x", 2),
+            ("a \"[b]\"", 1),
+        ] {
+            let e = parse(script).expect_err(script);
+            assert!(
+                e.starts_with(&format!("line {}:", line)),
+                "{}: {}",
+                script,
+                e
+            );
+        }
+    }
+    #[test]
+    fn list_1() {
+        assert_eq!(
+            words(&["a", "b c", "{d e} f", "g;h"]),
+            split_list(" a {b c}\n{{d e} f}\tg;h ").expect("Splitting")
+        );
+        assert!(split_list("").expect("Splitting").is_empty());
+        assert!(split_list("{a").is_err());
+    }
+}
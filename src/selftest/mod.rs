@@ -0,0 +1,352 @@
+//! Implements the --selftest command line option.  The self test
+//! provides a quick way to validate an installation using the same
+//! code paths rustogramer uses in production:
+//!
+//! *  Parameters and spectra are created with REST requests.
+//! *  Synthetic events are generated by the simulator and histogrammed.
+//! *  The spectrum contents are checked with REST requests.
+//! *  The spectra are bound into shared memory and the shared memory
+//! contents are checked.
+//! *  An update is requested from the mirror server and checked against
+//! the contents of shared memory.
+//!
+//! REST requests are dispatched through Rocket's local client to the fully
+//! configured server so no REST port is needed.  The mirror server
+//! is contacted over TCP/IP just as a remote mirror client would.
+//!
+//! Each step reports its result and rustogramer then exits with status 0
+//! if all steps passed and 1 if not.
+//!
+use crate::sharedmem::{mirror, read_spectrum};
+use rocket::local::blocking::Client;
+use rocket::{Build, Rocket};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::fs;
+use std::net::TcpStream;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Number of synthetic events histogrammed.
+const EVENTS: u64 = 1000;
+/// Value of the x parameter in every event.
+const X_VALUE: f64 = 100.0;
+/// Value of the y parameter in every event.
+const Y_VALUE: f64 = 200.0;
+
+const SIMULATION_TIMEOUT: Duration = Duration::from_secs(60);
+const MIRROR_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The spectra that are made and what we expect of them.
+// channel is the shared memory channel that has all of the counts;
+// shared memory spectra include the under/overflow channels.
+
+struct TestSpectrum {
+    name: &'static str,
+    create: &'static str,
+    y: Option<f64>,
+    channel: usize,
+}
+
+const SPECTRA: [TestSpectrum; 2] = [
+    TestSpectrum {
+        name: "selftest.1d",
+        create: "type=1&parameters=selftest.x&axes=%7B0%201024%201024%7D",
+        y: None,
+        channel: 101,
+    },
+    TestSpectrum {
+        name: "selftest.2d",
+        create: "type=2&parameters=selftest.x%20selftest.y&axes=%7B0%201024%20256%7D%20%7B0%201024%20256%7D",
+        y: Some(Y_VALUE),
+        channel: 26 + 51 * 258,
+    },
+];
+
+// The parts of the REST replies we look at:
+
+#[derive(Deserialize)]
+struct GenericReply {
+    status: String,
+    detail: String,
+}
+
+#[derive(Deserialize)]
+struct ChannelValue {
+    x: f64,
+    y: f64,
+    v: f64,
+}
+#[derive(Deserialize)]
+struct Contents {
+    channels: Vec<ChannelValue>,
+}
+#[derive(Deserialize)]
+struct ContentsReply {
+    status: String,
+    detail: Contents,
+}
+
+#[derive(Deserialize)]
+struct SimulationStatus {
+    running: bool,
+    events: u64,
+    error: Option<String>,
+}
+#[derive(Deserialize)]
+struct SimulationReply {
+    detail: SimulationStatus,
+}
+
+#[derive(Deserialize)]
+struct Binding {
+    name: String,
+    binding: usize,
+}
+#[derive(Deserialize)]
+struct BindingsReply {
+    status: String,
+    detail: Vec<Binding>,
+}
+
+// Make a request and decode its reply:
+
+fn get<T: DeserializeOwned>(client: &Client, uri: &str) -> Result<T, String> {
+    client
+        .get(uri)
+        .dispatch()
+        .into_json::<T>()
+        .ok_or_else(|| format!("{} did not return the expected reply", uri))
+}
+// Make a request whose reply is a generic response and require
+// it to succeed:
+
+fn ok(client: &Client, uri: &str) -> Result<(), String> {
+    let reply: GenericReply = get(client, uri)?;
+    if reply.status == "OK" {
+        Ok(())
+    } else {
+        Err(format!("{} failed: {} {}", uri, reply.status, reply.detail))
+    }
+}
+
+// Report the result of a step:
+
+fn step<T>(description: &str, result: Result<T, String>) -> Result<T, String> {
+    match &result {
+        Ok(_) => println!("selftest: {} - passed", description),
+        Err(e) => println!("selftest: {} - FAILED: {}", description, e),
+    }
+    result
+}
+
+fn make_objects(client: &Client) -> Result<(), String> {
+    ok(client, "/spectcl/parameter/create?name=selftest.x")?;
+    ok(client, "/spectcl/parameter/create?name=selftest.y")?;
+    for spectrum in SPECTRA.iter() {
+        ok(
+            client,
+            &format!(
+                "/spectcl/spectrum/create?name={}&{}",
+                spectrum.name, spectrum.create
+            ),
+        )?;
+    }
+    Ok(())
+}
+// Histogram synthetic events and wait for that to finish:
+
+fn simulate(client: &Client) -> Result<(), String> {
+    ok(
+        client,
+        &format!(
+            "/spectcl/simulate/start?parameter=selftest.x&parameter=selftest.y&distribution=constant:{}&distribution=constant:{}&rate=0&events={}&seed=1",
+            X_VALUE, Y_VALUE, EVENTS
+        ),
+    )?;
+    let start = Instant::now();
+    loop {
+        let status = get::<SimulationReply>(client, "/spectcl/simulate/status")?.detail;
+        if !status.running {
+            if let Some(e) = status.error {
+                return Err(e);
+            }
+            if status.events != EVENTS {
+                return Err(format!(
+                    "{} events were generated, expected {}",
+                    status.events, EVENTS
+                ));
+            }
+            return Ok(());
+        }
+        if start.elapsed() > SIMULATION_TIMEOUT {
+            return Err(String::from("Timed out waiting for the simulation"));
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+// All of the counts must be in the channel for the event values:
+
+fn check_contents(client: &Client, spectrum: &TestSpectrum) -> Result<(), String> {
+    let reply: ContentsReply = get(
+        client,
+        &format!("/spectcl/spectrum/contents?name={}", spectrum.name),
+    )?;
+    if reply.status != "OK" {
+        return Err(reply.status);
+    }
+    match reply.detail.channels.as_slice() {
+        [c] if c.x == X_VALUE && spectrum.y.iter().all(|&y| c.y == y) && c.v == EVENTS as f64 => {
+            Ok(())
+        }
+        channels => Err(format!(
+            "Expected {} counts in one channel but {} channels have counts",
+            EVENTS,
+            channels.len()
+        )),
+    }
+}
+// Bind the spectra and return their names and slots:
+
+fn bind(client: &Client) -> Result<Vec<(String, usize)>, String> {
+    let names: Vec<String> = SPECTRA
+        .iter()
+        .map(|s| format!("spectrum={}", s.name))
+        .collect();
+    ok(client, &format!("/spectcl/sbind/sbind?{}", names.join("&")))?;
+    let reply: BindingsReply = get(client, "/spectcl/sbind/list?pattern=selftest.*")?;
+    if reply.status != "OK" {
+        return Err(reply.status);
+    }
+    if reply.detail.len() != SPECTRA.len() {
+        return Err(format!(
+            "{} spectra are bound, expected {}",
+            reply.detail.len(),
+            SPECTRA.len()
+        ));
+    }
+    Ok(reply
+        .detail
+        .into_iter()
+        .map(|b| (b.name, b.binding))
+        .collect())
+}
+// Read the shared memory region's backing store:
+
+fn shared_memory_image(client: &Client) -> Result<Vec<u8>, String> {
+    let reply: GenericReply = get(client, "/spectcl/shmem/key")?;
+    if reply.status != "OK" {
+        return Err(format!("{} {}", reply.status, reply.detail));
+    }
+    let path = reply.detail.trim_start_matches("file:");
+    fs::read(path).map_err(|e| format!("Unable to read {}: {}", path, e))
+}
+
+fn check_image(image: &[u8], bindings: &[(String, usize)]) -> Result<(), String> {
+    for (name, slot) in bindings {
+        let spectrum = SPECTRA
+            .iter()
+            .find(|s| s.name == name.as_str())
+            .ok_or_else(|| format!("Unexpected binding of {}", name))?;
+        let (title, channels) = read_spectrum(image, *slot)?;
+        if title != *name {
+            return Err(format!("Slot {} should be {} but is {}", slot, name, title));
+        }
+        let total: u64 = channels.iter().map(|&c| c as u64).sum();
+        if channels.get(spectrum.channel) != Some(&(EVENTS as u32)) || total != EVENTS {
+            return Err(format!(
+                "Shared memory copy of {} does not have {} counts in channel {}",
+                name, EVENTS, spectrum.channel
+            ));
+        }
+    }
+    Ok(())
+}
+// The mirror's image of the spectra must match shared memory:
+
+fn check_mirror(port: u16, image: &[u8], bindings: &[(String, usize)]) -> Result<(), String> {
+    let start = Instant::now();
+    let mut peer = loop {
+        match TcpStream::connect(("127.0.0.1", port)) {
+            Ok(s) => break s,
+            Err(e) => {
+                if start.elapsed() > MIRROR_TIMEOUT {
+                    return Err(format!("Unable to connect to port {}: {}", port, e));
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    };
+    let (reply_type, body) = mirror::request_update(&mut peer)?;
+    if reply_type != mirror::FULL_UPDATE {
+        return Err(String::from("The first update was not a full update"));
+    }
+    for (name, slot) in bindings {
+        if read_spectrum(&body, *slot)? != read_spectrum(image, *slot)? {
+            return Err(format!(
+                "The mirror copy of {} differs from shared memory",
+                name
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn run_steps(client: &Client, mirror_port: u16) -> Result<(), String> {
+    step("Creating parameters and spectra", make_objects(client))?;
+    step("Histogramming synthetic events", simulate(client))?;
+    for spectrum in SPECTRA.iter() {
+        step(
+            &format!("Checking the contents of {}", spectrum.name),
+            check_contents(client, spectrum),
+        )?;
+    }
+    let bindings = step("Binding spectra into shared memory", bind(client))?;
+    let image = step("Reading shared memory", shared_memory_image(client))?;
+    step(
+        "Checking shared memory contents",
+        check_image(&image, &bindings),
+    )?;
+    step(
+        "Checking the mirror server",
+        check_mirror(mirror_port, &image, &bindings),
+    )
+}
+
+///
+/// Run the self test and exit.
+///
+/// ### Parameters
+/// * rocket - the fully configured REST server.  It is not launched.
+/// * mirror_port - port on which the mirror server listens.
+///
+/// ### Returns
+/// Does not return; the process exits with 0 if the test passed
+/// and 1 if it failed.
+///
+pub fn run_and_exit(rocket: Rocket<Build>, mirror_port: u16) -> ! {
+    // The blocking client runs its own async runtime so it can't
+    // be used from the thread that is running Rocket's:
+
+    let result = thread::spawn(move || {
+        let client = Client::untracked(rocket)
+            .map_err(|e| format!("Unable to start the REST server: {}", e))?;
+        let result = run_steps(&client, mirror_port);
+
+        // Shutting down also removes the shared memory backing store:
+
+        client.get("/spectcl/exit").dispatch();
+        result
+    })
+    .join();
+    match result {
+        Ok(Ok(())) => {
+            println!("selftest passed");
+            process::exit(0)
+        }
+        Ok(Err(e)) => println!("selftest FAILED: {}", e),
+        Err(_) => println!("selftest FAILED: the test thread panicked"),
+    }
+    process::exit(1)
+}
@@ -0,0 +1,1193 @@
+//!  Named analysis sessions.  A session is a snapshot of the
+//!  definition state of the histogramer:
+//!
+//! *  Parameters and their metadata.
+//! *  Tree variables.
+//! *  Conditions.
+//! *  Spectra, the gates applied to them and their folds and,
+//!    optionally, their contents.  Temporary spectra (e.g. projections
+//!    made for a quick look) are not part of a session.
+//! *  Spectrum display transforms held by the binding thread.
+//! *  Spectrum templates.
+//!
+//!  If rustogramer is run with a session name, the session is written
+//!  to file when rustogramer exits via /spectcl/exit.  Rustogramer can
+//!  then be started with --resume-session to restore that state.  This
+//!  limits what's lost if an interactive session is accidentally exited.
+//!
+//!  Session files are JSON encoded Session structs that live in the
+//!  session directory with names of the form _name_.json.  Spectra
+//!  are stored in the same form as JSON swrite files.
+//!
+//!  Definitions are written in dependency order: parameters precede the
+//!  conditions and spectra that use them, conditions precede the
+//!  compound conditions that depend on them and all conditions precede
+//!  the spectra they are applied to.  validate checks a session for
+//!  references that can't be resolved when it is restored.
+//!
+use crate::conditions::{AggregateKind, MaskKind, RatioKind};
+use crate::messaging::condition_messages::{ConditionMessageClient, ConditionReply};
+use crate::messaging::parameter_messages::ParameterMessageClient;
+use crate::messaging::spectrum_messages::SpectrumMessageClient;
+use crate::messaging::Request;
+use crate::rest::spectrumio;
+use crate::sharedmem::binder;
+use crate::tags;
+use crate::templates;
+use crate::treevariables;
+use rocket::serde::json;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+/// Directory, relative to the home directory, in which sessions are
+/// kept by default.
+pub const DEFAULT_SESSION_SUBDIR: &str = ".rustogramer/sessions";
+
+/// A parameter and its metadata:
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SessionParameter {
+    pub name: String,
+    pub low: Option<f64>,
+    pub high: Option<f64>,
+    pub bins: Option<u32>,
+    pub units: Option<String>,
+    pub description: Option<String>,
+}
+/// A tree variable:
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SessionTreeVariable {
+    pub name: String,
+    pub value: f64,
+    pub units: String,
+    pub changed: bool,
+}
+/// A condition.  This is the condition_messages::ConditionProperties
+/// but with parameter names rather than ids as parameter ids are not
+/// stable across runs of the program.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SessionCondition {
+    pub name: String,
+    pub type_name: String,
+    pub points: Vec<(f64, f64)>,
+    #[serde(default)]
+    pub polygons: Vec<Vec<(f64, f64)>>,
+    pub gates: Vec<String>,
+    pub parameters: Vec<String>,
+}
+/// The display transform of a spectrum:
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SessionDisplayTransform {
+    pub spectrum: String,
+    pub transform: binder::DisplayTransform,
+}
+/// The contents of a session file.  If the session was saved
+/// without contents, the spectrum channel vectors are empty.
+/// Display transforms, templates and tags are optional so older
+/// session files can be read.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Session {
+    pub parameters: Vec<SessionParameter>,
+    pub treevariables: Vec<SessionTreeVariable>,
+    pub conditions: Vec<SessionCondition>,
+    pub spectra: Vec<spectrumio::SpectrumFileData>,
+    #[serde(default)]
+    pub display_transforms: Vec<SessionDisplayTransform>,
+    #[serde(default)]
+    pub templates: Vec<templates::SpectrumTemplate>,
+    #[serde(default)]
+    pub tags: Vec<tags::SpectrumTags>,
+}
+
+/// The session configuration, this is stored in the Rocket state so
+/// that /spectcl/exit knows what, if anything, to save.
+#[derive(Clone, Debug, Default)]
+pub struct SessionConfig {
+    name: Option<String>,
+    directory: PathBuf,
+    contents: bool,
+}
+
+impl SessionConfig {
+    /// ### Parameters
+    /// * name - Name of the session to save on exit, None if no session
+    /// should be saved.
+    /// * directory - Directory in which session files live.
+    /// * contents - If true, spectrum contents are saved as well as
+    /// definitions.
+    pub fn new(name: Option<String>, directory: &Path, contents: bool) -> SessionConfig {
+        SessionConfig {
+            name,
+            directory: PathBuf::from(directory),
+            contents,
+        }
+    }
+    /// Path to the file for a session in our directory.
+    pub fn session_path(&self, name: &str) -> PathBuf {
+        self.directory.join(format!("{}.json", name))
+    }
+    /// Save the configured session, if there is one.
+    ///
+    /// ### Returns
+    /// * Ok(None) - there's no session to save.
+    /// * Ok(Some(path)) - the session was saved to path.
+    /// * Err(msg) - the session could not be saved.
+    pub fn save(
+        &self,
+        chan: &mpsc::Sender<Request>,
+        binder_chan: &mpsc::Sender<binder::Request>,
+        treevariables: &treevariables::SharedTreeVariableStore,
+        templates: &templates::SharedTemplateStore,
+        tags: &tags::SharedTagStore,
+    ) -> Result<Option<PathBuf>, String> {
+        if let Some(name) = &self.name {
+            let mut session = capture(chan, treevariables, self.contents)?;
+            session.templates = templates.list("*")?;
+            session.tags = tags.list("*")?;
+            // Without shared memory there are no display transforms to save:
+
+            let bapi = binder::BindingApi::new(binder_chan);
+            if bapi.check_available().is_ok() {
+                session.display_transforms = capture_display_transforms(&bapi)?;
+            }
+            fs::create_dir_all(&self.directory).map_err(|e| {
+                format!(
+                    "Unable to create session directory {}: {}",
+                    self.directory.display(),
+                    e
+                )
+            })?;
+            let path = self.session_path(name);
+            write(&path, &session)?;
+            Ok(Some(path))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// The default session directory.
+pub fn default_directory() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(DEFAULT_SESSION_SUBDIR)
+}
+
+// Capture the parameters sorted by id so that, restored into an empty
+// histogramer, they get the same ids.  Also returns the id -> name map
+// needed to describe conditions.
+
+fn capture_parameters(
+    api: &ParameterMessageClient,
+) -> Result<(Vec<SessionParameter>, HashMap<u32, String>), String> {
+    let mut params = api.list_parameters("*")?;
+    params.sort_by_key(|p| p.get_id());
+
+    let mut names = HashMap::new();
+    let mut result = Vec::new();
+    for p in params {
+        names.insert(p.get_id(), p.get_name());
+        let (low, high) = p.get_limits();
+        result.push(SessionParameter {
+            name: p.get_name(),
+            low,
+            high,
+            bins: p.get_bins(),
+            units: p.get_units(),
+            description: p.get_description(),
+        });
+    }
+    Ok((result, names))
+}
+
+fn capture_conditions(
+    api: &ConditionMessageClient,
+    names: &HashMap<u32, String>,
+) -> Result<Vec<SessionCondition>, String> {
+    let conditions = match api.list_conditions("*") {
+        ConditionReply::Listing(l) => l,
+        ConditionReply::Error(s) => return Err(s),
+        _ => return Err(String::from("Unexpected reply to condition list request")),
+    };
+    let mut result = Vec::new();
+    for c in conditions {
+        let mut parameters = Vec::new();
+        for id in c.parameters {
+            match names.get(&id) {
+                Some(name) => parameters.push(name.clone()),
+                None => {
+                    return Err(format!(
+                        "Condition {} depends on unknown parameter id {}",
+                        c.cond_name, id
+                    ))
+                }
+            }
+        }
+        result.push(SessionCondition {
+            name: c.cond_name,
+            type_name: c.type_name,
+            points: c.points,
+            polygons: c.polygons,
+            gates: c.gates,
+            parameters,
+        });
+    }
+    Ok(order_conditions(result))
+}
+// Order conditions so that each follows the conditions it depends on.
+// Otherwise, conditions are in name order.  Conditions that can't be
+// ordered (they depend on conditions that don't exist or are in a
+// dependency cycle) are put at the end in name order.
+
+fn order_conditions(mut conditions: Vec<SessionCondition>) -> Vec<SessionCondition> {
+    conditions.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut placed = HashSet::<String>::new();
+    let mut result = Vec::with_capacity(conditions.len());
+    loop {
+        let (ready, waiting): (Vec<SessionCondition>, Vec<SessionCondition>) = conditions
+            .into_iter()
+            .partition(|c| c.gates.iter().all(|g| placed.contains(g)));
+        conditions = waiting;
+        if ready.is_empty() {
+            break;
+        }
+        for c in ready {
+            placed.insert(c.name.clone());
+            result.push(c);
+        }
+    }
+    result.extend(conditions);
+    result
+}
+
+/// Capture the current state of the histogramer and tree variables as
+/// a session.
+///
+/// ### Parameters
+/// * chan - Channel to the histogram thread.
+/// * treevariables - The tree variable store.
+/// * contents - if true spectrum contents are captured too.
+///
+pub fn capture(
+    chan: &mpsc::Sender<Request>,
+    treevariables: &treevariables::SharedTreeVariableStore,
+    contents: bool,
+) -> Result<Session, String> {
+    let (parameters, names) = capture_parameters(&ParameterMessageClient::new(chan))?;
+    let conditions = capture_conditions(&ConditionMessageClient::new(chan), &names)?;
+
+    let mut treevars = Vec::new();
+    for v in treevariables.list("*")? {
+        treevars.push(SessionTreeVariable {
+            name: v.name(),
+            value: v.value(),
+            units: v.units(),
+            changed: v.changed(),
+        });
+    }
+
+    let api = SpectrumMessageClient::new(chan);
+    let temporary: HashSet<String> = api
+        .list_temporary("*")?
+        .into_iter()
+        .map(|t| t.name)
+        .collect();
+    let mut listing = api.list_spectra("*")?;
+    listing.retain(|s| !temporary.contains(&s.name));
+    listing.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut spectra = Vec::new();
+    for s in listing {
+        let definition = spectrumio::describe_spectrum(&s);
+        if contents {
+            spectra.push(spectrumio::get_spectrum_data(&definition, &api)?);
+        } else {
+            spectra.push(spectrumio::SpectrumFileData {
+                definition,
+                channels: vec![],
+            });
+        }
+    }
+
+    Ok(Session {
+        parameters,
+        treevariables: treevars,
+        conditions,
+        spectra,
+        display_transforms: vec![],
+        templates: vec![],
+        tags: vec![],
+    })
+}
+
+/// Capture the spectrum display transforms from the binding thread.
+/// These are kept separately from the rest of the session because
+/// they are not part of the histogramer.
+pub fn capture_display_transforms(
+    api: &binder::BindingApi,
+) -> Result<Vec<SessionDisplayTransform>, String> {
+    Ok(api
+        .list_display_transforms("*")?
+        .into_iter()
+        .map(|(spectrum, transform)| SessionDisplayTransform {
+            spectrum,
+            transform,
+        })
+        .collect())
+}
+
+/// Check a session for references that can't be resolved when it
+/// is restored:
+///
+/// * Parameters of conditions and spectra that are not in the session.
+/// * Conditions that compound conditions depend on, or that gate or fold
+/// spectra, that are not in the session.
+/// * Compound conditions in dependency cycles.
+/// * Display transforms of spectra that are not in the session.
+///
+/// ### Returns
+/// A description of each unresolved reference.  This is empty if
+/// the session can be restored cleanly.
+pub fn validate(session: &Session) -> Vec<String> {
+    let parameters: HashSet<&str> = session.parameters.iter().map(|p| p.name.as_str()).collect();
+    let conditions: HashSet<&str> = session.conditions.iter().map(|c| c.name.as_str()).collect();
+    let mut result = Vec::new();
+
+    for c in session.conditions.iter() {
+        for p in c.parameters.iter() {
+            if !parameters.contains(p.as_str()) {
+                result.push(format!(
+                    "Condition {} needs parameter {} which is not in the session",
+                    c.name, p
+                ));
+            }
+        }
+        for g in c.gates.iter() {
+            if !conditions.contains(g.as_str()) {
+                result.push(format!(
+                    "Condition {} depends on condition {} which is not in the session",
+                    c.name, g
+                ));
+            }
+        }
+    }
+    // Ignoring the missing conditions reported above, conditions that
+    // still can't be ordered are in, or depend on, a dependency cycle:
+
+    let resolved: Vec<SessionCondition> = session
+        .conditions
+        .iter()
+        .map(|c| {
+            let mut c = c.clone();
+            c.gates.retain(|g| conditions.contains(g.as_str()));
+            c
+        })
+        .collect();
+    let ordered = order_conditions(resolved);
+    let mut placed = HashSet::<&str>::new();
+    for c in ordered.iter() {
+        if c.gates.iter().all(|g| placed.contains(g.as_str())) {
+            placed.insert(c.name.as_str());
+        } else {
+            result.push(format!(
+                "Condition {} is in or depends on a dependency cycle",
+                c.name
+            ));
+        }
+    }
+
+    let mut spectra = HashSet::<&str>::new();
+    for s in session.spectra.iter() {
+        let d = &s.definition;
+        spectra.insert(d.name.as_str());
+        for p in d.x_parameters.iter().chain(d.y_parameters.iter()) {
+            if !parameters.contains(p.as_str()) {
+                result.push(format!(
+                    "Spectrum {} needs parameter {} which is not in the session",
+                    d.name, p
+                ));
+            }
+        }
+        for (what, condition) in [("gated", &d.gate), ("folded", &d.fold)] {
+            if let Some(condition) = condition {
+                if !conditions.contains(condition.as_str()) {
+                    result.push(format!(
+                        "Spectrum {} is {} on condition {} which is not in the session",
+                        d.name, what, condition
+                    ));
+                }
+            }
+        }
+    }
+    for t in session.display_transforms.iter() {
+        if !spectra.contains(t.spectrum.as_str()) {
+            result.push(format!(
+                "There is a display transform for spectrum {} which is not in the session",
+                t.spectrum
+            ));
+        }
+    }
+    result
+}
+
+/// Write a session to file.
+pub fn write(path: &Path, session: &Session) -> Result<(), String> {
+    let json = json::to_string(session).map_err(|e| e.to_string())?;
+    fs::write(path, json).map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+}
+
+/// Read a session from file.
+pub fn read(path: &Path) -> Result<Session, String> {
+    let json = fs::read_to_string(path)
+        .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+    json::from_str::<Session>(&json)
+        .map_err(|e| format!("Unable to decode {}: {}", path.display(), e))
+}
+
+fn restore_parameters(
+    parameters: &[SessionParameter],
+    api: &ParameterMessageClient,
+) -> Result<(), String> {
+    let existing: HashSet<String> = api
+        .list_parameters("*")?
+        .iter()
+        .map(|p| p.get_name())
+        .collect();
+    for p in parameters {
+        if !existing.contains(&p.name) {
+            api.create_parameter(&p.name)?;
+        }
+        let limits = match (p.low, p.high) {
+            (Some(l), Some(h)) => Some((l, h)),
+            _ => None,
+        };
+        api.modify_parameter_metadata(
+            &p.name,
+            p.bins,
+            limits,
+            p.units.clone(),
+            p.description.clone(),
+        )?;
+    }
+    Ok(())
+}
+
+// Make one condition; all parameters and dependent conditions exist.
+
+fn make_condition(
+    c: &SessionCondition,
+    ids: &[u32],
+    api: &ConditionMessageClient,
+) -> Result<(), String> {
+    let limits = || -> Result<(f64, f64), String> {
+        if c.points.len() == 2 {
+            Ok((c.points[0].0, c.points[1].0))
+        } else {
+            Err(format!("{} needs a low and a high limit", c.name))
+        }
+    };
+    let reply = match c.type_name.as_str() {
+        "True" => api.create_true_condition(&c.name),
+        "False" => api.create_false_condition(&c.name),
+        "Not" if c.gates.len() == 1 => api.create_not_condition(&c.name, &c.gates[0]),
+        "C2Band" if c.gates.len() == 2 => {
+            api.create_c2band_condition(&c.name, &c.gates[0], &c.gates[1])
+        }
+        "And" => api.create_and_condition(&c.name, &c.gates),
+        "Or" => api.create_or_condition(&c.name, &c.gates),
+        "Cut" if ids.len() == 1 => {
+            let (low, high) = limits()?;
+            api.create_cut_condition(&c.name, ids[0], low, high)
+        }
+        "Band" if ids.len() == 2 => api.create_band_condition(&c.name, ids[0], ids[1], &c.points),
+        "Contour" if ids.len() == 2 => {
+            api.create_contour_condition(&c.name, ids[0], ids[1], &c.points)
+        }
+        "MultiCut" => {
+            let (low, high) = limits()?;
+            api.create_multicut_condition(&c.name, ids, low, high)
+        }
+        "MultiContour" => api.create_multicontour_condition(&c.name, ids, &c.points),
+        "MultiBand" => api.create_multiband_condition(&c.name, ids, &c.points),
+        "ContourSet" if ids.len() == 2 => {
+            api.create_contourset_condition(&c.name, ids[0], ids[1], &c.polygons)
+        }
+        "Sum" | "Mean" | "Max" => {
+            let kind = match c.type_name.as_str() {
+                "Sum" => AggregateKind::Sum,
+                "Mean" => AggregateKind::Mean,
+                _ => AggregateKind::Max,
+            };
+            let (low, high) = limits()?;
+            api.create_aggregate_condition(&c.name, kind, ids, low, high)
+        }
+        "Ratio" | "Difference" if ids.len() == 2 => {
+            let kind = if c.type_name == "Ratio" {
+                RatioKind::Ratio
+            } else {
+                RatioKind::Difference
+            };
+            let (low, high) = limits()?;
+            api.create_ratio_condition(&c.name, kind, ids[0], ids[1], low, high)
+        }
+        "MaskEqual" | "MaskAnd" | "MaskNotAnd" if ids.len() == 1 && c.points.len() == 1 => {
+            let kind = match c.type_name.as_str() {
+                "MaskEqual" => MaskKind::Equal,
+                "MaskAnd" => MaskKind::And,
+                _ => MaskKind::NotAnd,
+            };
+            api.create_mask_condition(&c.name, kind, ids[0], c.points[0].0 as u32)
+        }
+        _ => {
+            return Err(format!(
+                "Invalid definition of {} condition {}",
+                c.type_name, c.name
+            ))
+        }
+    };
+    match reply {
+        ConditionReply::Created | ConditionReply::Replaced => Ok(()),
+        ConditionReply::Error(s) => Err(format!("Unable to make condition {}: {}", c.name, s)),
+        _ => Err(format!("Unexpected reply making condition {}", c.name)),
+    }
+}
+
+// Compound conditions depend on other conditions which must be made
+// first.  We make passes over the conditions, making those whose
+// dependencies have been made until there's nothing more we can make.
+// Anything left has a dependency that's not in the session.
+
+fn restore_conditions(
+    conditions: &[SessionCondition],
+    papi: &ParameterMessageClient,
+    api: &ConditionMessageClient,
+    errors: &mut Vec<String>,
+) -> Result<(), String> {
+    let ids: HashMap<String, u32> = papi
+        .list_parameters("*")?
+        .iter()
+        .map(|p| (p.get_name(), p.get_id()))
+        .collect();
+
+    let mut made = HashSet::<String>::new();
+    let mut remaining: Vec<&SessionCondition> = conditions.iter().collect();
+    loop {
+        let (ready, waiting): (Vec<&SessionCondition>, Vec<&SessionCondition>) = remaining
+            .into_iter()
+            .partition(|c| c.gates.iter().all(|g| made.contains(g)));
+        if ready.is_empty() {
+            remaining = waiting;
+            break;
+        }
+        for c in ready {
+            let mut pids = Vec::new();
+            for p in c.parameters.iter() {
+                match ids.get(p) {
+                    Some(id) => pids.push(*id),
+                    None => errors.push(format!("Condition {} needs parameter {}", c.name, p)),
+                }
+            }
+            if pids.len() == c.parameters.len() {
+                if let Err(s) = make_condition(c, &pids, api) {
+                    errors.push(s);
+                }
+            }
+            // Mark made even on failure so dependents fail making
+            // rather than being reported as unresolvable:
+
+            made.insert(c.name.clone());
+        }
+        remaining = waiting;
+    }
+    for c in remaining {
+        errors.push(format!(
+            "Condition {} depends on conditions not in the session",
+            c.name
+        ));
+    }
+    Ok(())
+}
+
+fn restore_spectra(
+    spectra: &[spectrumio::SpectrumFileData],
+    api: &SpectrumMessageClient,
+    errors: &mut Vec<String>,
+) {
+    for s in spectrumio::fix_json_bins(spectra.to_vec()) {
+        let name = &s.definition.name;
+        if let Err(e) = spectrumio::make_spectrum(name, &s.definition, api) {
+            errors.push(format!("Unable to make spectrum {}: {}", name, e));
+            continue;
+        }
+        if let Some(gate) = &s.definition.gate {
+            if let Err(e) = api.gate_spectrum(name, gate) {
+                errors.push(format!("Unable to gate {} on {}: {}", name, gate, e));
+            }
+        }
+        if let Some(fold) = &s.definition.fold {
+            if let Err(e) = api.fold_spectrum(name, fold) {
+                errors.push(format!("Unable to fold {} on {}: {}", name, fold, e));
+            }
+        }
+        if !s.channels.is_empty() {
+            if let Err(e) = spectrumio::fill_spectrum(name, &s.channels, api) {
+                errors.push(format!("Unable to fill {}: {}", name, e));
+            }
+        }
+    }
+}
+
+/// Restore a session.  This is intended to be done at startup into an
+/// empty histogramer.  As much of the session as possible is restored;
+/// problems with individual objects (e.g. a spectrum with the same name
+/// as one that already exists) don't stop the rest of the session from
+/// being restored.
+///
+/// ### Parameters
+/// * session - the session to restore.
+/// * chan - Channel to the histogram thread.
+/// * treevariables - The tree variable store.
+///
+/// ### Returns
+/// Result<(), String> - The error string describes everything that
+/// could not be restored, one item per line.
+pub fn restore(
+    session: &Session,
+    chan: &mpsc::Sender<Request>,
+    treevariables: &treevariables::SharedTreeVariableStore,
+) -> Result<(), String> {
+    let papi = ParameterMessageClient::new(chan);
+    restore_parameters(&session.parameters, &papi)?;
+
+    for v in session.treevariables.iter() {
+        treevariables.update(&v.name, v.value, &v.units);
+        if v.changed {
+            treevariables.set_changed(&v.name)?;
+        }
+    }
+
+    let mut errors = Vec::new();
+    restore_conditions(
+        &session.conditions,
+        &papi,
+        &ConditionMessageClient::new(chan),
+        &mut errors,
+    )?;
+    restore_spectra(
+        &session.spectra,
+        &SpectrumMessageClient::new(chan),
+        &mut errors,
+    );
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+/// Restore the display transforms of a session.  This must be done
+/// after the spectra are restored.  Transforms that can't be restored
+/// are reported but don't stop the others from being restored.
+pub fn restore_display_transforms(
+    transforms: &[SessionDisplayTransform],
+    api: &binder::BindingApi,
+) -> Result<(), String> {
+    let errors: Vec<String> = transforms
+        .iter()
+        .filter_map(|t| api.set_display_transform(&t.spectrum, &t.transform).err())
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+/// Restore the spectrum templates of a session.  These replace
+/// existing templates with the same names.
+pub fn restore_templates(
+    session_templates: &[templates::SpectrumTemplate],
+    store: &templates::SharedTemplateStore,
+) {
+    for t in session_templates {
+        store.define(t.clone());
+    }
+}
+/// Restore the spectrum tags of a session.  These are added to any
+/// tags the spectra already have.
+pub fn restore_tags(session_tags: &[tags::SpectrumTags], store: &tags::SharedTagStore) {
+    for t in session_tags {
+        let _ = store.add(&t.spectrum, &t.tags); // Tags were valid when saved.
+    }
+}
+
+#[cfg(test)]
+mod session_tests {
+    use super::*;
+    use crate::messaging::spectrum_messages;
+    use crate::test::{binder_common, histogramer_common};
+    use std::thread;
+
+    fn setup() -> (mpsc::Sender<Request>, thread::JoinHandle<()>) {
+        histogramer_common::setup()
+    }
+    fn teardown(chan: mpsc::Sender<Request>, jh: thread::JoinHandle<()>) {
+        histogramer_common::teardown(chan, jh);
+    }
+
+    // Make a bit of everything:
+
+    fn populate(chan: &mpsc::Sender<Request>, treevars: &treevariables::SharedTreeVariableStore) {
+        let papi = ParameterMessageClient::new(chan);
+        for name in ["p1", "p2", "p3"] {
+            papi.create_parameter(name).expect("Making parameter");
+        }
+        papi.modify_parameter_metadata(
+            "p2",
+            Some(512),
+            Some((-1.0, 1.0)),
+            Some(String::from("cm")),
+            Some(String::from("A position")),
+        )
+        .expect("Setting metadata");
+
+        treevars.update("slope", 1.5, "MeV/ch");
+        treevars.update("offset", 0.0, "MeV");
+        treevars.set_changed("slope").expect("Setting changed");
+
+        let capi = ConditionMessageClient::new(chan);
+        capi.create_cut_condition("cut", 1, 10.0, 20.0);
+        capi.create_contour_condition("contour", 2, 3, &[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+        // Created before its dependencies alphabetically:
+        capi.create_and_condition("and", &[String::from("cut"), String::from("contour")]);
+        capi.create_not_condition("not", "and");
+        capi.create_aggregate_condition("sum", AggregateKind::Sum, &[1, 2], 0.0, 100.0);
+        capi.create_contourset_condition(
+            "islands",
+            1,
+            2,
+            &[
+                vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)],
+                vec![(20.0, 20.0), (30.0, 20.0), (30.0, 30.0)],
+            ],
+        );
+        capi.create_multiband_condition("gamma", &[1, 2], &[(0.0, 10.0), (100.0, 10.0)]);
+        capi.create_mask_condition("trigger", MaskKind::And, 1, 0x81);
+        capi.create_band_condition("low", 1, 2, &[(0.0, 10.0), (100.0, 10.0)]);
+        capi.create_band_condition("high", 1, 2, &[(0.0, 90.0), (100.0, 90.0)]);
+        capi.create_c2band_condition("between", "high", "low");
+
+        let sapi = SpectrumMessageClient::new(chan);
+        sapi.create_spectrum_1d("oned", "p1", 0.0, 1024.0, 1024)
+            .expect("Making oned");
+        sapi.create_spectrum_2d("twod", "p2", "p3", 0.0, 1024.0, 256, 0.0, 1024.0, 256)
+            .expect("making twod");
+        sapi.gate_spectrum("oned", "not").expect("Gating oned");
+
+        let contents = vec![spectrum_messages::Channel {
+            chan_type: spectrum_messages::ChannelType::Bin,
+            x: 100.0,
+            y: 0.0,
+            bin: 0,
+            value: 42.0,
+        }];
+        sapi.fill_spectrum("oned", contents).expect("Filling oned");
+    }
+
+    #[test]
+    fn capture_1() {
+        let (chan, jh) = setup();
+        let treevars = treevariables::SharedTreeVariableStore::new();
+        populate(&chan, &treevars);
+
+        let session = capture(&chan, &treevars, false).expect("Capturing");
+
+        let names: Vec<String> = session.parameters.iter().map(|p| p.name.clone()).collect();
+        assert_eq!(vec!["p1", "p2", "p3"], names);
+        assert_eq!(
+            SessionParameter {
+                name: String::from("p2"),
+                low: Some(-1.0),
+                high: Some(1.0),
+                bins: Some(512),
+                units: Some(String::from("cm")),
+                description: Some(String::from("A position")),
+            },
+            session.parameters[1]
+        );
+
+        assert_eq!(2, session.treevariables.len());
+        assert_eq!("offset", session.treevariables[0].name);
+        assert!(!session.treevariables[0].changed);
+        assert!(session.treevariables[1].changed);
+
+        // Conditions precede the conditions that depend on them:
+
+        let names: Vec<String> = session.conditions.iter().map(|c| c.name.clone()).collect();
+        assert_eq!(
+            vec![
+                "contour", "cut", "gamma", "high", "islands", "low", "sum", "trigger", "and",
+                "between", "not"
+            ],
+            names
+        );
+        assert_eq!(vec!["p2", "p3"], session.conditions[0].parameters);
+        assert!(validate(&session).is_empty());
+
+        assert_eq!(2, session.spectra.len());
+        assert!(session.spectra.iter().all(|s| s.channels.is_empty()));
+
+        teardown(chan, jh);
+    }
+    #[test]
+    fn capture_2() {
+        // With contents:
+
+        let (chan, jh) = setup();
+        let treevars = treevariables::SharedTreeVariableStore::new();
+        populate(&chan, &treevars);
+
+        let session = capture(&chan, &treevars, true).expect("Capturing");
+        let oned = session
+            .spectra
+            .iter()
+            .find(|s| s.definition.name == "oned")
+            .expect("Finding oned");
+        assert_eq!(1, oned.channels.len());
+        assert_eq!(42, oned.channels[0].value);
+
+        teardown(chan, jh);
+    }
+    #[test]
+    fn capture_3() {
+        // Temporary spectra are not captured:
+
+        let (chan, jh) = setup();
+        let treevars = treevariables::SharedTreeVariableStore::new();
+        populate(&chan, &treevars);
+        let sapi = SpectrumMessageClient::new(&chan);
+        sapi.create_spectrum_1d("quicklook", "p1", 0.0, 1024.0, 1024)
+            .expect("Making quicklook");
+        sapi.set_temporary("quicklook", true, None)
+            .expect("Making quicklook temporary");
+
+        let session = capture(&chan, &treevars, false).expect("Capturing");
+        let names: Vec<String> = session
+            .spectra
+            .iter()
+            .map(|s| s.definition.name.clone())
+            .collect();
+        assert_eq!(vec!["oned", "twod"], names);
+
+        teardown(chan, jh);
+    }
+    #[test]
+    fn restore_1() {
+        // Round trip through a file into a fresh histogramer:
+
+        let (chan, jh) = setup();
+        let treevars = treevariables::SharedTreeVariableStore::new();
+        populate(&chan, &treevars);
+        let original = capture(&chan, &treevars, true).expect("Capturing");
+        teardown(chan, jh);
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let path = dir.path().join("test.json");
+        write(&path, &original).expect("Writing session");
+        let session = read(&path).expect("Reading session");
+
+        let (chan, jh) = setup();
+        let treevars = treevariables::SharedTreeVariableStore::new();
+        restore(&session, &chan, &treevars).expect("Restoring");
+
+        let restored = capture(&chan, &treevars, true).expect("Capturing restored");
+        assert_eq!(original.parameters, restored.parameters);
+        assert_eq!(original.treevariables, restored.treevariables);
+        assert_eq!(original.conditions, restored.conditions);
+        assert_eq!(original.spectra.len(), restored.spectra.len());
+        for (o, r) in original.spectra.iter().zip(restored.spectra.iter()) {
+            assert_eq!(o.definition.name, r.definition.name);
+            assert_eq!(o.definition.x_axis, r.definition.x_axis);
+            assert_eq!(o.definition.y_axis, r.definition.y_axis);
+            assert_eq!(o.definition.gate, r.definition.gate);
+            assert_eq!(o.channels.len(), r.channels.len());
+        }
+
+        teardown(chan, jh);
+    }
+    #[test]
+    fn restore_2() {
+        // Conditions with missing dependencies are reported but
+        // don't stop the rest of the restore:
+
+        let session = Session {
+            parameters: vec![SessionParameter {
+                name: String::from("p1"),
+                low: None,
+                high: None,
+                bins: None,
+                units: None,
+                description: None,
+            }],
+            treevariables: vec![],
+            conditions: vec![
+                SessionCondition {
+                    name: String::from("not"),
+                    type_name: String::from("Not"),
+                    points: vec![],
+                    polygons: vec![],
+                    gates: vec![String::from("-deleted-")],
+                    parameters: vec![],
+                },
+                SessionCondition {
+                    name: String::from("cut"),
+                    type_name: String::from("Cut"),
+                    points: vec![(1.0, 0.0), (2.0, 0.0)],
+                    polygons: vec![],
+                    gates: vec![],
+                    parameters: vec![String::from("p1")],
+                },
+            ],
+            spectra: vec![],
+            display_transforms: vec![],
+            templates: vec![],
+            tags: vec![],
+        };
+        let (chan, jh) = setup();
+        let treevars = treevariables::SharedTreeVariableStore::new();
+        let result = restore(&session, &chan, &treevars);
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .contains("Condition not depends on conditions not in the session"));
+
+        let restored = capture(&chan, &treevars, false).expect("Capturing");
+        assert_eq!(1, restored.conditions.len());
+        assert_eq!("cut", restored.conditions[0].name);
+
+        teardown(chan, jh);
+    }
+    #[test]
+    fn config_1() {
+        // No session name means nothing is saved:
+
+        let (chan, jh) = setup();
+        let (bchan, bjh, _) = binder_common::setup(&chan);
+        let treevars = treevariables::SharedTreeVariableStore::new();
+        let store = templates::SharedTemplateStore::new();
+        let tag_store = tags::SharedTagStore::new();
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let config = SessionConfig::new(None, dir.path(), false);
+        assert_eq!(
+            None,
+            config
+                .save(&chan, &bchan, &treevars, &store, &tag_store)
+                .expect("Saving")
+        );
+
+        // A name saves in the directory, which is created:
+
+        let sessions = dir.path().join("sessions");
+        let config = SessionConfig::new(Some(String::from("mine")), &sessions, false);
+        let path = config
+            .save(&chan, &bchan, &treevars, &store, &tag_store)
+            .expect("Saving")
+            .expect("Should have saved");
+        assert_eq!(sessions.join("mine.json"), path);
+        assert!(read(&path).is_ok());
+
+        binder_common::teardown(bchan, bjh);
+        teardown(chan, jh);
+    }
+    #[test]
+    fn transforms_1() {
+        // Display transforms are saved and restored:
+
+        let (chan, jh) = setup();
+        let (bchan, bjh, _) = binder_common::setup(&chan);
+        let treevars = treevariables::SharedTreeVariableStore::new();
+        let store = templates::SharedTemplateStore::new();
+        let tag_store = tags::SharedTagStore::new();
+        populate(&chan, &treevars);
+        let bapi = binder::BindingApi::new(&bchan);
+        let kev = binder::DisplayTransform {
+            xaxis: Some(binder::AxisTransform::new(0.25, 1.0, "keV")),
+            yaxis: None,
+        };
+        bapi.set_display_transform("oned", &kev)
+            .expect("Setting transform");
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let config = SessionConfig::new(Some(String::from("t")), dir.path(), false);
+        let path = config
+            .save(&chan, &bchan, &treevars, &store, &tag_store)
+            .expect("Saving")
+            .expect("Should have saved");
+        let session = read(&path).expect("Reading");
+        assert_eq!(
+            vec![SessionDisplayTransform {
+                spectrum: String::from("oned"),
+                transform: kev.clone()
+            }],
+            session.display_transforms
+        );
+
+        bapi.clear_display_transform("oned").expect("Clearing");
+        restore_display_transforms(&session.display_transforms, &bapi).expect("Restoring");
+        assert_eq!(
+            vec![(String::from("oned"), kev.clone())],
+            bapi.list_display_transforms("*").expect("Listing")
+        );
+
+        // Transforms for missing spectra are reported:
+
+        let missing = vec![SessionDisplayTransform {
+            spectrum: String::from("nosuch"),
+            transform: kev,
+        }];
+        assert!(restore_display_transforms(&missing, &bapi).is_err());
+
+        binder_common::teardown(bchan, bjh);
+        teardown(chan, jh);
+    }
+    fn condition(name: &str, gates: &[&str], parameters: &[&str]) -> SessionCondition {
+        SessionCondition {
+            name: String::from(name),
+            type_name: String::from(if gates.is_empty() { "Cut" } else { "And" }),
+            points: vec![],
+            polygons: vec![],
+            gates: gates.iter().map(|g| String::from(*g)).collect(),
+            parameters: parameters.iter().map(|p| String::from(*p)).collect(),
+        }
+    }
+    #[test]
+    fn templates_1() {
+        // Templates are saved and restored:
+
+        let (chan, jh) = setup();
+        let (bchan, bjh, _) = binder_common::setup(&chan);
+        let treevars = treevariables::SharedTreeVariableStore::new();
+        let store = templates::SharedTemplateStore::new();
+        let tag_store = tags::SharedTagStore::new();
+        let energy = templates::SpectrumTemplate {
+            name: String::from("energy"),
+            spectrum_type: String::from("1"),
+            axes: String::from("{0 1024 1024}"),
+            gate: Some(String::from("cut")),
+        };
+        store.define(energy.clone());
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let config = SessionConfig::new(Some(String::from("t")), dir.path(), false);
+        let path = config
+            .save(&chan, &bchan, &treevars, &store, &tag_store)
+            .expect("Saving")
+            .expect("Should have saved");
+        let session = read(&path).expect("Reading");
+        assert_eq!(vec![energy.clone()], session.templates);
+
+        let restored = templates::SharedTemplateStore::new();
+        restore_templates(&session.templates, &restored);
+        assert_eq!(vec![energy], restored.list("*").expect("Listing"));
+
+        binder_common::teardown(bchan, bjh);
+        teardown(chan, jh);
+    }
+    #[test]
+    fn tags_1() {
+        // Spectrum tags are saved and restored:
+
+        let (chan, jh) = setup();
+        let (bchan, bjh, _) = binder_common::setup(&chan);
+        let treevars = treevariables::SharedTreeVariableStore::new();
+        let store = templates::SharedTemplateStore::new();
+        let tag_store = tags::SharedTagStore::new();
+        let pid = vec![String::from("det=hagrid"), String::from("pid")];
+        tag_store.add("oned", &pid).expect("Tagging");
+
+        let dir = tempfile::tempdir().expect("Making temp dir");
+        let config = SessionConfig::new(Some(String::from("t")), dir.path(), false);
+        let path = config
+            .save(&chan, &bchan, &treevars, &store, &tag_store)
+            .expect("Saving")
+            .expect("Should have saved");
+        let session = read(&path).expect("Reading");
+        assert_eq!(
+            vec![tags::SpectrumTags {
+                spectrum: String::from("oned"),
+                tags: pid.clone()
+            }],
+            session.tags
+        );
+
+        let restored = tags::SharedTagStore::new();
+        restored
+            .add("oned", &[String::from("monitoring")])
+            .expect("Tagging");
+        restore_tags(&session.tags, &restored);
+        assert_eq!(
+            vec!["det=hagrid", "monitoring", "pid"],
+            restored.tags("oned")
+        );
+
+        binder_common::teardown(bchan, bjh);
+        teardown(chan, jh);
+    }
+    #[test]
+    fn order_1() {
+        // Dependencies first, otherwise by name; unorderable conditions last:
+
+        let ordered = order_conditions(vec![
+            condition("a", &["b", "c"], &[]),
+            condition("b", &["d"], &[]),
+            condition("c", &[], &["p1"]),
+            condition("d", &[], &["p1"]),
+            condition("e", &["nosuch"], &[]),
+            condition("f", &["g"], &[]),
+            condition("g", &["f"], &[]),
+        ]);
+        let names: Vec<String> = ordered.iter().map(|c| c.name.clone()).collect();
+        assert_eq!(vec!["c", "d", "b", "a", "e", "f", "g"], names);
+    }
+    #[test]
+    fn validate_1() {
+        // Unresolved references are all reported:
+
+        let (chan, jh) = setup();
+        let treevars = treevariables::SharedTreeVariableStore::new();
+        populate(&chan, &treevars);
+        let mut session = capture(&chan, &treevars, false).expect("Capturing");
+        teardown(chan, jh);
+
+        session.parameters.retain(|p| p.name != "p3");
+        session
+            .conditions
+            .retain(|c| c.name != "cut" && c.name != "not");
+        session.conditions.push(condition("f", &["g"], &[]));
+        session.conditions.push(condition("g", &["f"], &[]));
+        session.display_transforms.push(SessionDisplayTransform {
+            spectrum: String::from("nosuch"),
+            transform: binder::DisplayTransform {
+                xaxis: None,
+                yaxis: None,
+            },
+        });
+
+        let problems = validate(&session);
+        assert_eq!(
+            vec![
+                "Condition contour needs parameter p3 which is not in the session",
+                "Condition and depends on condition cut which is not in the session",
+                "Condition f is in or depends on a dependency cycle",
+                "Condition g is in or depends on a dependency cycle",
+                "Spectrum oned is gated on condition not which is not in the session",
+                "Spectrum twod needs parameter p3 which is not in the session",
+                "There is a display transform for spectrum nosuch which is not in the session",
+            ],
+            problems
+        );
+    }
+}
@@ -9,15 +9,35 @@
 //!
 //!  See BindingThread for more information about how bound spectra
 //!  work.
+//!
+//!  The binding thread also holds display transforms for spectra.
+//!  A display transform changes the axis limits and labels viewers
+//!  see (e.g. to show keV rather than channels) without changing the
+//!  binning of the spectrum.
+//!
+//!  Finally, the binding thread keeps a short history of the memory
+//!  utilization.  A sample is taken when the thread starts and each
+//!  time the set of bindings changes so that operators can see
+//!  whether the shared memory is approaching exhaustion.
+//!
+//!  If the shared memory region can't be created (e.g. size limits or
+//!  disk quota on the backing store), the binding thread still runs but
+//!  fails all requests that need the shared memory, giving the reason.
+//!  This allows the rest of Rustogramer to run without the shared memory.
 
+use crate::crash;
 use crate::messaging;
 use crate::messaging::spectrum_messages;
 use crate::trace;
 
+use super::ChannelWidth;
 use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 use std::sync::mpsc;
 use std::thread;
 use std::time;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Memory statistics have this format:
 ///
@@ -30,6 +50,21 @@ pub struct MemoryStatistics {
     pub bound_indices: usize,
     pub total_indices: usize,
     pub total_size: usize,
+    pub free_extents: usize,
+    pub allocated_extents: usize,
+}
+/// The number of usage samples kept in the utilization history.
+pub const USAGE_HISTORY_LENGTH: usize = 64;
+
+/// A sample of the memory utilization.  time is in seconds since
+/// the epoch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UsageSample {
+    pub time: u64,
+    pub used_bytes: usize,
+    pub free_bytes: usize,
+    pub largest_free_bytes: usize,
+    pub bound_indices: usize,
 }
 // This enum represents the set of operations that can be
 // requested of this thread:
@@ -41,13 +76,18 @@ pub struct MemoryStatistics {
 enum RequestType {
     Unbind(String),
     UnbindAll,
-    Bind(String),
+    Bind(String, bool, ChannelWidth),
+    SetTransform(String, DisplayTransform),
+    ListTransforms(String),
     List(String),
+    ListDetails(String),
     Clear(String),
     SetUpdate(u64),
     GetUpdate,
     Statistics,
+    UsageHistory,
     ShmName,
+    Available,
     Exit,
 }
 pub struct Request {
@@ -67,8 +107,71 @@ pub type GenericResult = Result<(), String>;
 ///
 pub type ListResult = Result<Vec<(usize, String)>, String>;
 
+/// Detailed information about a binding.  In addition to the
+/// binding slot and spectrum name, this records whether or not the
+/// under/overflow channels were included in the shared memory copy,
+/// the width of the shared memory channels and how many channels
+/// saturated (were too big for that width) in the last update.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct BindingDetails {
+    pub binding: usize,
+    pub name: String,
+    pub include_overflows: bool,
+    pub width: ChannelWidth,
+    pub saturated: usize,
+}
+/// How an axis of a bound spectrum is displayed.  An axis coordinate
+/// x is shown as scale * x + offset and the axis is labeled with
+/// unit.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AxisTransform {
+    pub scale: f64,
+    pub offset: f64,
+    pub unit: String,
+}
+impl AxisTransform {
+    pub fn new(scale: f64, offset: f64, unit: &str) -> AxisTransform {
+        AxisTransform {
+            scale,
+            offset,
+            unit: String::from(unit),
+        }
+    }
+    /// Transform an axis coordinate.
+    pub fn transform(&self, value: f64) -> f64 {
+        self.scale * value + self.offset
+    }
+}
+/// The display transform of a spectrum.  An axis with no transform
+/// is displayed as is.
+///
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct DisplayTransform {
+    pub xaxis: Option<AxisTransform>,
+    pub yaxis: Option<AxisTransform>,
+}
+impl DisplayTransform {
+    /// True if neither axis is transformed.
+    pub fn is_identity(&self) -> bool {
+        self.xaxis.is_none() && self.yaxis.is_none()
+    }
+}
+/// Listing display transforms returns a TransformsResult.  The Ok
+/// vector has spectrum names and their transforms.
+///
+pub type TransformsResult = Result<Vec<(String, DisplayTransform)>, String>;
+
+/// Listing binding details returns a DetailsResult:
+
+pub type DetailsResult = Result<Vec<BindingDetails>, String>;
+
 /// What we get back from statisitcs requests:
 pub type StatisticsResult = Result<MemoryStatistics, String>;
+/// The utilization history, oldest sample first:
+
+pub type HistoryResult = Result<Vec<UsageSample>, String>;
 /// When replies just need a string:'
 
 pub type StringResult = Result<String, String>;
@@ -79,7 +182,10 @@ pub type UnsignedResult = Result<u64, String>;
 enum Reply {
     Generic(GenericResult),
     List(ListResult),
+    Details(DetailsResult),
+    Transforms(TransformsResult),
     Statistics(StatisticsResult),
+    History(HistoryResult),
     String(StringResult),
     Unsigned(UnsignedResult),
 }
@@ -111,6 +217,10 @@ pub const DEFAULT_TIMEOUT: u64 = 2;
 ///  * spectrum_api -  The Spectrum messaging API.
 ///  * request_chan - The channel on which requests will be sent.
 ///  * shm - the Xamine compatible shared memory segment.
+///  * transforms - Display transforms indexed by spectrum name.  These
+/// persist whether or not the spectrum is bound.
+///  * usage_history - The most recent USAGE_HISTORY_LENGTH utilization
+/// samples.
 ///
 struct BindingThread {
     request_chan: mpsc::Receiver<Request>,
@@ -118,6 +228,8 @@ struct BindingThread {
     timeout: u64,
     shm: super::SharedMemory,
     trace_db: trace::SharedTraceStore,
+    transforms: HashMap<String, DisplayTransform>,
+    usage_history: VecDeque<UsageSample>,
 }
 
 impl BindingThread {
@@ -139,30 +251,48 @@ impl BindingThread {
                 name: String::from(name),
                 binding_id: slot,
             });
+            self.record_usage();
             Ok(())
         } else {
             Err(String::from("Spectrum is not bound"))
         }
     }
-    // Bind a spectrum to shared memory and fill it in:
+    // Bind a spectrum to shared memory and fill it in.
+    // include_overflows determines if the under/overflow channels
+    // are part of the shared memory copy and width the width of
+    // the channels of 2d spectra.
 
-    fn bind(&mut self, name: &str) -> Result<(), String> {
+    fn bind(
+        &mut self,
+        name: &str,
+        include_overflows: bool,
+        width: ChannelWidth,
+    ) -> Result<(), String> {
         if let Some(n) = self.find_binding(name) {
             return Err(format!("{} is already bound", n));
         }
         if let Ok(info) = self.spectrum_info(name) {
+            let mut xaxis = Self::get_xaxis(&info).expect("No x axis!!!");
+            if !include_overflows && Self::is_summary(&info) {
+                // The summary x axis also has under/overflow channels:
+                xaxis.2 += 2;
+            }
             match self.shm.bind_spectrum(
                 name,
-                Self::get_xaxis(&info).expect("No x axis!!!"),
+                xaxis,
                 Self::get_yaxis(&info),
+                include_overflows,
+                width,
             ) {
                 Ok((slot, _)) => {
+                    self.set_axis_display(slot, &info);
                     self.shm.clear_contents(slot);
                     self.update_spectrum((slot, String::from(name)));
                     self.trace_db.add_event(trace::TraceEvent::SpectrumBound {
                         name: String::from(name),
                         binding_id: slot,
                     });
+                    self.record_usage();
                     Ok(())
                 }
                 Err(s) => Err(s),
@@ -191,16 +321,22 @@ impl BindingThread {
             spectrum_messages::SpectrumServerListingResult::Err(s) => Err(s),
         }
     }
+    // Summary and gamma summary spectra have one x channel per x
+    // parameter (list):
+
+    fn is_summary(info: &spectrum_messages::SpectrumProperties) -> bool {
+        info.type_name == "Summary" || info.type_name == "GammaSummary"
+    }
     fn get_yaxis(info: &spectrum_messages::SpectrumProperties) -> Option<(f64, f64, u32)> {
         // This is just extracted fom th Y axis if it's there:
 
         info.yaxis.map(|y| (y.low, y.high, y.bins))
     }
     fn get_xaxis(info: &spectrum_messages::SpectrumProperties) -> Option<(f64, f64, u32)> {
-        // Normally this will just be the X axis but for summary
+        // Normally this will just be the X axis but for (gamma) summary
         // spectra we constuct this from the number of parameters.
 
-        if info.type_name != *"Summary" {
+        if !Self::is_summary(info) {
             info.xaxis.map(|x| (x.low, x.high, x.bins))
         } else {
             let len = info.xparams.len();
@@ -224,13 +360,75 @@ impl BindingThread {
         }
         // Summary spectra have a y axis specification and the
         // X axis is determined by the number of x parameters.
-        if info.type_name == *"Summary" {
+        if Self::is_summary(info) {
             result.0 = 0.0;
             result.1 = info.xparams.len() as f64;
         }
         result
     }
 
+    // Set the axis limits and labels of a bound spectrum in shared
+    // memory from its definition and display transform:
+
+    fn set_axis_display(&mut self, slot: usize, info: &spectrum_messages::SpectrumProperties) {
+        let transform = self.transforms.get(&info.name).cloned().unwrap_or_default();
+        let display = |axis: (f64, f64, u32), t: &Option<AxisTransform>| match t {
+            Some(t) => (t.transform(axis.0), t.transform(axis.1), t.unit.clone()),
+            None => (axis.0, axis.1, String::new()),
+        };
+        let x = display(
+            Self::get_xaxis(info).expect("No x axis!!!"),
+            &transform.xaxis,
+        );
+        let y = Self::get_yaxis(info).map(|y| display(y, &transform.yaxis));
+        self.shm.set_axis_display(
+            slot,
+            (x.0, x.1, &x.2),
+            y.as_ref().map(|y| (y.0, y.1, y.2.as_str())),
+        );
+    }
+    // Set or, if the transform is the identity, remove a spectrum's
+    // display transform.  If the spectrum is bound, its shared memory
+    // description is updated right away.
+
+    fn set_transform(&mut self, name: &str, transform: DisplayTransform) -> GenericResult {
+        if transform.is_identity() {
+            self.transforms.remove(name);
+        } else {
+            let info = self.spectrum_info(name)?;
+            for axis in [&transform.xaxis, &transform.yaxis].into_iter().flatten() {
+                if axis.scale <= 0.0 {
+                    return Err(String::from("Axis scale factors must be positive"));
+                }
+            }
+            if transform.yaxis.is_some() && Self::get_yaxis(&info).is_none() {
+                return Err(format!("Spectrum {} has no y axis", name));
+            }
+            self.transforms.insert(String::from(name), transform);
+        }
+        if let Some(slot) = self.find_binding(name) {
+            if let Ok(info) = self.spectrum_info(name) {
+                self.set_axis_display(slot, &info);
+            }
+        }
+        Ok(())
+    }
+    // List the display transforms of spectra whose names match
+    // a pattern.  The list is sorted by spectrum name.
+
+    fn list_transforms(&self, pattern: &str) -> TransformsResult {
+        let p = Pattern::new(pattern)
+            .map_err(|reason| format!("Bad glob pattern {} :{}", pattern, reason.msg))?;
+        let mut listing: Vec<(String, DisplayTransform)> = self
+            .transforms
+            .iter()
+            .filter(|(name, _)| p.matches(name))
+            .map(|(name, t)| (name.clone(), t.clone()))
+            .collect();
+        listing.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(listing)
+    }
+
     // Update a single spectrum's contents
     fn update_spectrum(&mut self, binding: (usize, String)) {
         let slot = binding.0;
@@ -247,6 +445,13 @@ impl BindingThread {
                 axis_spec.2,
                 axis_spec.3,
             ) {
+                // Strip chart axes scroll so the old contents and
+                // axis limits may be stale:
+
+                if info.type_name == "StripChart" {
+                    self.shm.clear_contents(slot);
+                    self.set_axis_display(slot, &info);
+                }
                 self.shm.set_contents(slot, &contents);
             } else {
                 self.shm.unbind(slot);
@@ -272,6 +477,21 @@ impl BindingThread {
             Ok(listing)
         }
     }
+    /// Same as get_bindings but the listing includes the
+    /// binding details.
+    fn get_binding_details(&mut self, pattern: &str) -> DetailsResult {
+        let listing = self.get_bindings(pattern)?;
+        Ok(listing
+            .into_iter()
+            .map(|(binding, name)| BindingDetails {
+                binding,
+                name,
+                include_overflows: self.shm.includes_overflows(binding),
+                width: self.shm.channel_width(binding),
+                saturated: self.shm.saturated_channels(binding),
+            })
+            .collect())
+    }
     /// Clear the contents of bound spectra with names that match the
     /// pattern
     fn clear_spectra(&mut self, pattern: &str) {
@@ -285,6 +505,7 @@ impl BindingThread {
     /// memory and slot usage.
     fn get_statistics(&mut self) -> MemoryStatistics {
         let memory_stats = self.shm.statistics();
+        let extents = self.shm.extent_counts();
 
         MemoryStatistics {
             free_bytes: memory_stats.0,
@@ -294,7 +515,28 @@ impl BindingThread {
             bound_indices: memory_stats.4,
             total_indices: memory_stats.5,
             total_size: memory_stats.6,
+            free_extents: extents.0,
+            allocated_extents: extents.1,
+        }
+    }
+    /// Add a sample of the current utilization to the usage history,
+    /// discarding the oldest sample if the history is full.
+    fn record_usage(&mut self) {
+        let stats = self.get_statistics();
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        if self.usage_history.len() == USAGE_HISTORY_LENGTH {
+            self.usage_history.pop_front();
         }
+        self.usage_history.push_back(UsageSample {
+            time,
+            used_bytes: stats.used_bytes,
+            free_bytes: stats.free_bytes,
+            largest_free_bytes: stats.largest_free_bytes,
+            bound_indices: stats.bound_indices,
+        });
     }
     /// Update the contents of all spectra bound to shared memory:
 
@@ -332,13 +574,14 @@ impl BindingThread {
                         binding_id: b.0,
                     });
                 }
+                self.record_usage();
                 req.reply_chan
                     .send(Reply::Generic(GenericResult::Ok(())))
                     .expect("Failed to send reply to client from binding thread");
                 true
             }
-            RequestType::Bind(name) => {
-                if let Err(s) = self.bind(&name) {
+            RequestType::Bind(name, include_overflows, width) => {
+                if let Err(s) = self.bind(&name, include_overflows, width) {
                     req.reply_chan
                         .send(Reply::Generic(GenericResult::Err(format!(
                             "Could not bind spectrum {}; {}",
@@ -352,12 +595,33 @@ impl BindingThread {
                 }
                 true
             }
+            RequestType::SetTransform(name, transform) => {
+                let result = self
+                    .set_transform(&name, transform)
+                    .map_err(|s| format!("Could not set display transform for {}: {}", name, s));
+                req.reply_chan
+                    .send(Reply::Generic(result))
+                    .expect("Failed to send reply to client from binding thread");
+                true
+            }
+            RequestType::ListTransforms(pattern) => {
+                req.reply_chan
+                    .send(Reply::Transforms(self.list_transforms(&pattern)))
+                    .expect("Failed to send display transforms to client");
+                true
+            }
             RequestType::List(pattern) => {
                 req.reply_chan
                     .send(Reply::List(self.get_bindings(&pattern)))
                     .expect("Failed to send bindings list to client");
                 true
             }
+            RequestType::ListDetails(pattern) => {
+                req.reply_chan
+                    .send(Reply::Details(self.get_binding_details(&pattern)))
+                    .expect("Failed to send binding details to client");
+                true
+            }
             RequestType::Clear(pattern) => {
                 self.clear_spectra(&pattern);
                 req.reply_chan
@@ -384,12 +648,25 @@ impl BindingThread {
                     .expect("Failed to send reply to client from binding thread");
                 true
             }
+            RequestType::UsageHistory => {
+                let history = self.usage_history.iter().copied().collect();
+                req.reply_chan
+                    .send(Reply::History(Ok(history)))
+                    .expect("Failed to send reply to client from binding thread");
+                true
+            }
             RequestType::ShmName => {
                 req.reply_chan
                     .send(Reply::String(Ok(self.shm.get_shm_name())))
                     .expect("Failed to send reply to client from bindng thread");
                 true
             }
+            RequestType::Available => {
+                req.reply_chan
+                    .send(Reply::Generic(Ok(())))
+                    .expect("Failed to send reply to client from binding thread");
+                true
+            }
             RequestType::Exit => {
                 req.reply_chan
                     .send(Reply::String(Ok(self.shm.get_backing_store())))
@@ -402,21 +679,25 @@ impl BindingThread {
     /// this is done within the binding thread which then
     /// invokes the run  method on the newly created object.
     /// This is similar to how the Histogrammer thread is
-    /// created/started.
+    /// created/started.  The shared memory region is made by
+    /// the caller so that it can handle failure.
     pub fn new(
         req: mpsc::Receiver<Request>,
         api_chan: &mpsc::Sender<messaging::Request>,
-        spec_size: usize,
+        shm: super::SharedMemory,
         tracer: &trace::SharedTraceStore,
     ) -> BindingThread {
-        BindingThread {
+        let mut result = BindingThread {
             request_chan: req,
             spectrum_api: spectrum_messages::SpectrumMessageClient::new(api_chan),
             timeout: DEFAULT_TIMEOUT,
-            shm: super::SharedMemory::new(spec_size)
-                .expect("Failed to create shared memory region!!"),
+            shm,
             trace_db: tracer.clone(),
-        }
+            transforms: HashMap::new(),
+            usage_history: VecDeque::with_capacity(USAGE_HISTORY_LENGTH),
+        };
+        result.record_usage();
+        result
     }
     /// Runs the thread.  See the struct comments for a reasonably
     /// complete description of how the thread works.
@@ -458,12 +739,50 @@ pub fn start_server(
     let (sender, receiver) = mpsc::channel();
     let hreq = hreq_chan.clone();
     let thread_trace_db = trace_db.clone();
-    let join_handle = thread::spawn(move || {
-        let mut t = BindingThread::new(receiver, &hreq, spectrum_bytes, &thread_trace_db);
-        t.run();
+    let join_handle = crash::spawn("binder", move || {
+        match super::SharedMemory::new(spectrum_bytes) {
+            Ok(shm) => {
+                let mut t = BindingThread::new(receiver, &hreq, shm, &thread_trace_db);
+                t.run();
+            }
+            Err(reason) => serve_unavailable(receiver, &reason),
+        }
     });
     (sender, join_handle)
 }
+// Serve requests when the shared memory could not be created.
+// Requests that need the shared memory fail with the reason.  Exit
+// succeeds but, since there's no backing store, returns an empty
+// backing store name.
+
+fn serve_unavailable(requests: mpsc::Receiver<Request>, reason: &str) {
+    let reason = format!("Shared memory is unavailable: {}", reason);
+    while let Ok(req) = requests.recv() {
+        let (reply, more) = match req.request {
+            RequestType::Unbind(_)
+            | RequestType::UnbindAll
+            | RequestType::Bind(_, _, _)
+            | RequestType::SetTransform(_, _)
+            | RequestType::Clear(_)
+            | RequestType::SetUpdate(_)
+            | RequestType::Available => (Reply::Generic(Err(reason.clone())), true),
+            RequestType::ListTransforms(_) => (Reply::Transforms(Err(reason.clone())), true),
+            RequestType::List(_) => (Reply::List(Err(reason.clone())), true),
+            RequestType::ListDetails(_) => (Reply::Details(Err(reason.clone())), true),
+            RequestType::GetUpdate => (Reply::Unsigned(Err(reason.clone())), true),
+            RequestType::Statistics => (Reply::Statistics(Err(reason.clone())), true),
+            RequestType::UsageHistory => (Reply::History(Err(reason.clone())), true),
+            RequestType::ShmName => (Reply::String(Err(reason.clone())), true),
+            RequestType::Exit => (Reply::String(Ok(String::new())), false),
+        };
+        req.reply_chan
+            .send(reply)
+            .expect("Failed to send reply to client from binding thread");
+        if !more {
+            break;
+        }
+    }
+}
 /// This struct and its implementation provide an API to
 /// make requests of a running BindingThread.  Note that theoretically,
 /// more than one binding thread could be run, each managing a separate
@@ -580,17 +899,109 @@ impl BindingApi {
     /// until then ext refresh pass by BindingThread and should be
     /// called with "*") whenver histogrammer spectra are aslo cleared.
     ///
+    /// - The under/overflow channels are included in the shared memory
+    /// copy.  See bind_overflows to control this.
+    ///
     /// ### Parameters
     /// *  name - name of the spectrum to bind.
     ///
     /// ### Returns
     /// * GenericResult instance.
     pub fn bind(&self, name: &str) -> GenericResult {
-        match self.transaction(RequestType::Bind(String::from(name))) {
+        self.bind_overflows(name, true)
+    }
+    /// Bind a spectrum to the shared memory, choosing whether or not
+    /// the under/overflow channels are part of the shared memory copy.
+    /// When they are excluded, the shared memory spectrum has exactly the
+    /// number of bins the user asked for on each axis.
+    ///
+    /// ### Parameters
+    /// *  name - name of the spectrum to bind.
+    /// *  include_overflows - true to include the under/overflow channels.
+    ///
+    /// ### Returns
+    /// * GenericResult instance.
+    pub fn bind_overflows(&self, name: &str, include_overflows: bool) -> GenericResult {
+        self.bind_with(name, include_overflows, ChannelWidth::Long)
+    }
+    /// Bind a spectrum to the shared memory choosing both whether or not
+    /// the under/overflow channels are included and the width of the
+    /// shared memory channels.  Word and byte channels halve or quarter
+    /// the shared memory needed by 2d spectra whose counts fit in them;
+    /// channels that don't fit saturate.  1d spectra always have long
+    /// channels.
+    ///
+    /// ### Parameters
+    /// *  name - name of the spectrum to bind.
+    /// *  include_overflows - true to include the under/overflow channels.
+    /// *  width - width of the shared memory channels of 2d spectra.
+    ///
+    /// ### Returns
+    /// * GenericResult instance.
+    pub fn bind_with(
+        &self,
+        name: &str,
+        include_overflows: bool,
+        width: ChannelWidth,
+    ) -> GenericResult {
+        match self.transaction(RequestType::Bind(
+            String::from(name),
+            include_overflows,
+            width,
+        )) {
+            Reply::Generic(result) => result,
+            _ => Err(String::from("Unexpected return type from binding thread")),
+        }
+    }
+    /// Set the display transform of a spectrum.  The transform
+    /// determines the axis limits and labels viewers see when the
+    /// spectrum is bound to shared memory; the spectrum binning is not
+    /// changed.  Transforms are kept whether or not the spectrum is
+    /// bound and are applied whenever it is.
+    ///
+    /// ### Parameters
+    /// *  name - name of the spectrum.
+    /// *  transform - the transform. If neither axis is transformed,
+    /// any existing transform is removed.
+    ///
+    /// ### Returns
+    /// * GenericResult instance.  Setting a transform fails if the
+    /// spectrum does not exist, a scale factor is not positive or
+    /// a y axis transform is given for a spectrum with no y axis.
+    pub fn set_display_transform(&self, name: &str, transform: &DisplayTransform) -> GenericResult {
+        match self.transaction(RequestType::SetTransform(
+            String::from(name),
+            transform.clone(),
+        )) {
             Reply::Generic(result) => result,
             _ => Err(String::from("Unexpected return type from binding thread")),
         }
     }
+    /// Remove the display transform of a spectrum.  It is not an error
+    /// to remove a transform that does not exist.
+    ///
+    /// ### Parameters
+    /// *  name - name of the spectrum.
+    ///
+    /// ### Returns
+    /// * GenericResult instance.
+    pub fn clear_display_transform(&self, name: &str) -> GenericResult {
+        self.set_display_transform(name, &DisplayTransform::default())
+    }
+    /// List the display transforms.
+    ///
+    /// ### Parameters
+    /// *  pattern  - Glob pattern the spectrum names must match.
+    ///
+    /// ### Returns
+    /// *  TransformsResult instance, sorted by spectrum name.
+    ///
+    pub fn list_display_transforms(&self, pattern: &str) -> TransformsResult {
+        match self.transaction(RequestType::ListTransforms(String::from(pattern))) {
+            Reply::Transforms(r) => r,
+            _ => Err(String::from("Unexpected return type from binding thread")),
+        }
+    }
     /// List the bindings that are currently in force in the
     /// shared memory.  This makes no modifications to the share
     // memory contents.
@@ -609,6 +1020,21 @@ impl BindingApi {
             _ => Err(String::from("Unexpected return type from binding thread")),
         }
     }
+    /// List the bindings along with their details (e.g. if the
+    /// under/overflow channels are included in the shared memory copy).
+    ///
+    /// ### Parameters
+    /// *  pattern  - Glob pattern the spectrum names must match.
+    ///
+    /// ### Returns
+    /// *  DetailsResult instance.
+    ///
+    pub fn list_binding_details(&self, pattern: &str) -> DetailsResult {
+        match self.transaction(RequestType::ListDetails(String::from(pattern))) {
+            Reply::Details(r) => r,
+            _ => Err(String::from("Unexpected return type from binding thread")),
+        }
+    }
     /// Clear the contents of a collection of spectra in the shared memory.
     /// note that almost immediatetly the server will run a pass over
     /// the set of bound spectra, updating their contents.  
@@ -681,6 +1107,19 @@ impl BindingApi {
             _ => Err(String::from("Unexpected reply type from BindingServer")),
         }
     }
+    /// Obtains the history of the shared memory utilization.  A sample
+    /// is taken when the binding thread starts and each time the
+    /// bindings change.  At most USAGE_HISTORY_LENGTH samples are kept.
+    ///
+    /// ### Returns:
+    ///    An instance of HistoryResult; the oldest sample is first.
+    ///
+    pub fn get_usage_history(&self) -> HistoryResult {
+        match self.transaction(RequestType::UsageHistory) {
+            Reply::History(history) => history,
+            _ => Err(String::from("Unexpected reply type from BindingServer")),
+        }
+    }
     /// Asks the binding thread to tell us the name of the shared
     /// memory region. The name includes  a prefix separated from
     /// a name that makes sense given the prefix by a colon.
@@ -713,6 +1152,19 @@ impl BindingApi {
             _ => Err(String::from("Unexpected reply type from BindingServer")),
         }
     }
+    /// Determine if the shared memory is available.  It is not if
+    /// it could not be created when the binding thread started.
+    ///
+    /// ### Returns:
+    /// *  GenericResult instance - the error describes why the shared memory
+    /// is unavailable.
+    ///
+    pub fn check_available(&self) -> GenericResult {
+        match self.transaction(RequestType::Available) {
+            Reply::Generic(result) => result,
+            _ => Err(String::from("Unexpected reply type from BindingServer")),
+        }
+    }
     /// Asks the binding thread to exit.  On successful return all
     /// further requests of this and other API objects that talk to the
     /// same BindingServer will fail attempting to do the send part
@@ -751,7 +1203,9 @@ mod sbind_server_tests {
         let (hreq, jh) = histogramer_common::setup();
 
         let (_, rcv) = mpsc::channel();
-        let binder = BindingThread::new(rcv, &hreq, 1024 * 1024, &trace::SharedTraceStore::new());
+        let shm = sharedmem::SharedMemory::new(1024 * 1024)
+            .expect("Failed to create shared memory region!!");
+        let binder = BindingThread::new(rcv, &hreq, shm, &trace::SharedTraceStore::new());
 
         (jh, hreq.clone(), binder)
     }
@@ -765,7 +1219,7 @@ mod sbind_server_tests {
 
         let (jh, hreq, mut binder) = setup();
 
-        assert!(binder.bind("test").is_err());
+        assert!(binder.bind("test", true, ChannelWidth::Long).is_err());
 
         teardown(hreq, jh);
     }
@@ -784,7 +1238,7 @@ mod sbind_server_tests {
         sapi.create_spectrum_1d("george", "george", 0.0, 1024.0, 512)
             .expect("Failed to make a spectrum");
 
-        assert!(binder.bind("george").is_ok());
+        assert!(binder.bind("george", true, ChannelWidth::Long).is_ok());
 
         let list = binder.get_bindings("*").expect("Getting bindings list");
         assert_eq!(1, list.len());
@@ -792,6 +1246,283 @@ mod sbind_server_tests {
 
         teardown(hreq, jh);
     }
+    #[test]
+    fn bind_3() {
+        // Bind 1d spectra with and without the under/overflow channels
+        // and check the shared memory copies:
+
+        let (jh, hreq, mut binder) = setup();
+
+        let papi = parameter_messages::ParameterMessageClient::new(&hreq);
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&hreq);
+
+        papi.create_parameter("george")
+            .expect("Failed to make parameter");
+        for name in ["with", "without"] {
+            sapi.create_spectrum_1d(name, "george", 0.0, 1024.0, 512)
+                .expect("Failed to make a spectrum");
+            sapi.set_channel_value(name, -1, None, 3.0)
+                .expect("Setting underflow");
+            sapi.set_channel_value(name, 0, None, 5.0)
+                .expect("Setting channel 0");
+        }
+        binder
+            .bind("with", true, ChannelWidth::Long)
+            .expect("binding with");
+        binder
+            .bind("without", false, ChannelWidth::Long)
+            .expect("binding without");
+
+        let details = binder.get_binding_details("*").expect("Getting details");
+        assert_eq!(2, details.len());
+        for d in details {
+            let header = binder.shm.get_header();
+            let xchans = header.dsp_xy[d.binding].xchans;
+            let p = binder.shm.slot_as_pointer(d.binding);
+            if d.name == "with" {
+                assert!(d.include_overflows);
+                assert_eq!(514, xchans);
+                assert_eq!(3, unsafe { *p });
+                assert_eq!(5, unsafe { *p.add(1) });
+            } else {
+                assert_eq!("without", d.name);
+                assert!(!d.include_overflows);
+                assert_eq!(512, xchans);
+                assert_eq!(5, unsafe { *p });
+            }
+        }
+
+        teardown(hreq, jh);
+    }
+    #[test]
+    fn stripchart_1() {
+        // Bound strip charts follow the scrolling of their time axis:
+
+        let (jh, hreq, mut binder) = setup();
+
+        let papi = parameter_messages::ParameterMessageClient::new(&hreq);
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&hreq);
+        papi.create_parameter("time")
+            .expect("Failed to make parameter");
+        papi.create_parameter("value")
+            .expect("Failed to make parameter");
+        sapi.create_spectrum_stripchart("strip", "time", "value", 0.0, 10.0, 10)
+            .expect("Failed to make a spectrum");
+        binder
+            .bind("strip", false, ChannelWidth::Long)
+            .expect("binding strip");
+        let slot = binder.find_binding("strip").expect("Finding strip");
+        let event = |time: f64, value: f64| {
+            vec![vec![
+                crate::parameters::EventParameter::new(1, time),
+                crate::parameters::EventParameter::new(2, value),
+            ]]
+        };
+
+        sapi.process_events(&event(2.5, 3.0)).expect("Processing event");
+        binder.update_contents();
+        let p = binder.shm.slot_as_pointer(slot);
+        assert_eq!(3, unsafe { *p.add(2) });
+        assert_eq!(0.0, binder.shm.get_header().dsp_map[slot].xmin);
+
+        // Scroll by 3 channels - channel 2 is now channel -1:
+
+        sapi.process_events(&event(12.5, 1.0)).expect("Processing event");
+        binder.update_contents();
+        let header = binder.shm.get_header();
+        assert_eq!(3.0, header.dsp_map[slot].xmin);
+        assert_eq!(13.0, header.dsp_map[slot].xmax);
+        assert_eq!(0, unsafe { *p.add(2) });
+        assert_eq!(1, unsafe { *p.add(9) });
+
+        teardown(hreq, jh);
+    }
+    #[test]
+    fn bind_4() {
+        // 2d spectrum without under/overflows:
+
+        let (jh, hreq, mut binder) = setup();
+
+        let papi = parameter_messages::ParameterMessageClient::new(&hreq);
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&hreq);
+
+        papi.create_parameter("x")
+            .expect("Failed to make parameter");
+        papi.create_parameter("y")
+            .expect("Failed to make parameter");
+        sapi.create_spectrum_2d("twod", "x", "y", 0.0, 16.0, 16, 0.0, 8.0, 8)
+            .expect("Failed to make a spectrum");
+        sapi.set_channel_value("twod", 2, Some(3), 7.0)
+            .expect("Setting channel");
+        sapi.set_channel_value("twod", -1, Some(3), 9.0)
+            .expect("Setting x underflow");
+
+        binder
+            .bind("twod", false, ChannelWidth::Long)
+            .expect("binding twod");
+        let slot = binder.find_binding("twod").expect("Finding binding");
+        assert!(!binder.shm.includes_overflows(slot));
+        let header = binder.shm.get_header();
+        assert_eq!(16, header.dsp_xy[slot].xchans);
+        assert_eq!(8, header.dsp_xy[slot].ychans);
+
+        let p = binder.shm.slot_as_pointer(slot);
+        assert_eq!(7, unsafe { *p.add(2 + 3 * 16) });
+        let total: u32 = (0..16 * 8).map(|i| unsafe { *p.add(i) }).sum();
+        assert_eq!(7, total); // Underflow is not in the copy.
+
+        teardown(hreq, jh);
+    }
+    #[test]
+    fn bind_5() {
+        // 2d spectrum with word channels saturates:
+
+        let (jh, hreq, mut binder) = setup();
+
+        let papi = parameter_messages::ParameterMessageClient::new(&hreq);
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&hreq);
+
+        papi.create_parameter("x")
+            .expect("Failed to make parameter");
+        papi.create_parameter("y")
+            .expect("Failed to make parameter");
+        sapi.create_spectrum_2d("twod", "x", "y", 0.0, 16.0, 16, 0.0, 8.0, 8)
+            .expect("Failed to make a spectrum");
+        sapi.set_channel_value("twod", 2, Some(3), 7.0)
+            .expect("Setting channel");
+        sapi.set_channel_value("twod", 4, Some(3), 100000.0)
+            .expect("Setting big channel");
+
+        binder
+            .bind("twod", false, ChannelWidth::Word)
+            .expect("binding twod");
+        let details = binder.get_binding_details("*").expect("Getting details");
+        assert_eq!(1, details.len());
+        assert_eq!(ChannelWidth::Word, details[0].width);
+        assert_eq!(1, details[0].saturated);
+
+        let slot = details[0].binding;
+        let p = binder.shm.slot_as_pointer(slot) as *const u16;
+        assert_eq!(7, unsafe { *p.add(2 + 3 * 16) });
+        assert_eq!(u16::MAX, unsafe { *p.add(4 + 3 * 16) });
+        assert_eq!(
+            16 * 8 * mem::size_of::<u16>(),
+            binder.get_statistics().used_bytes
+        );
+
+        teardown(hreq, jh);
+    }
+    #[test]
+    fn transform_1() {
+        // A display transform changes the axis description in
+        // shared memory, whether set before or after binding:
+
+        let (jh, hreq, mut binder) = setup();
+
+        let papi = parameter_messages::ParameterMessageClient::new(&hreq);
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&hreq);
+
+        papi.create_parameter("x")
+            .expect("Failed to make parameter");
+        papi.create_parameter("y")
+            .expect("Failed to make parameter");
+        sapi.create_spectrum_1d("oned", "x", 0.0, 1024.0, 1024)
+            .expect("Failed to make a spectrum");
+        sapi.create_spectrum_2d("twod", "x", "y", 0.0, 16.0, 16, 0.0, 8.0, 8)
+            .expect("Failed to make a spectrum");
+
+        binder
+            .set_transform(
+                "oned",
+                DisplayTransform {
+                    xaxis: Some(AxisTransform::new(0.5, 10.0, "keV")),
+                    yaxis: None,
+                },
+            )
+            .expect("Setting oned transform");
+        binder
+            .bind("oned", true, ChannelWidth::Long)
+            .expect("binding oned");
+        binder
+            .bind("twod", true, ChannelWidth::Long)
+            .expect("binding twod");
+        binder
+            .set_transform(
+                "twod",
+                DisplayTransform {
+                    xaxis: None,
+                    yaxis: Some(AxisTransform::new(2.0, 0.0, "ns")),
+                },
+            )
+            .expect("Setting twod transform");
+
+        let slot = binder.find_binding("oned").expect("Finding oned");
+        let header = binder.shm.get_header();
+        assert_eq!(10.0, header.dsp_map[slot].xmin);
+        assert_eq!(522.0, header.dsp_map[slot].xmax);
+        assert_eq!(b"keV\0", &header.dsp_map[slot].xlabel[0..4]);
+        assert_eq!(1026, header.dsp_xy[slot].xchans); // binning unchanged.
+
+        let slot = binder.find_binding("twod").expect("Finding twod");
+        let header = binder.shm.get_header();
+        assert_eq!(0.0, header.dsp_map[slot].xmin);
+        assert_eq!(16.0, header.dsp_map[slot].xmax);
+        assert_eq!(0, header.dsp_map[slot].xlabel[0]);
+        assert_eq!(0.0, header.dsp_map[slot].ymin);
+        assert_eq!(16.0, header.dsp_map[slot].ymax);
+        assert_eq!(b"ns\0", &header.dsp_map[slot].ylabel[0..3]);
+
+        // Removing the transform restores the axis:
+
+        binder
+            .set_transform("twod", DisplayTransform::default())
+            .expect("Clearing twod transform");
+        let header = binder.shm.get_header();
+        assert_eq!(8.0, header.dsp_map[slot].ymax);
+        assert_eq!(0, header.dsp_map[slot].ylabel[0]);
+
+        teardown(hreq, jh);
+    }
+    #[test]
+    fn transform_2() {
+        // Errors: no such spectrum, bad scale, no y axis:
+
+        let (jh, hreq, mut binder) = setup();
+
+        let papi = parameter_messages::ParameterMessageClient::new(&hreq);
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&hreq);
+        papi.create_parameter("x")
+            .expect("Failed to make parameter");
+        sapi.create_spectrum_1d("oned", "x", 0.0, 1024.0, 1024)
+            .expect("Failed to make a spectrum");
+
+        let kev = DisplayTransform {
+            xaxis: Some(AxisTransform::new(0.5, 0.0, "keV")),
+            yaxis: None,
+        };
+        assert!(binder.set_transform("nosuch", kev.clone()).is_err());
+        assert!(binder
+            .set_transform(
+                "oned",
+                DisplayTransform {
+                    xaxis: Some(AxisTransform::new(0.0, 0.0, "keV")),
+                    yaxis: None,
+                }
+            )
+            .is_err());
+        assert!(binder
+            .set_transform(
+                "oned",
+                DisplayTransform {
+                    xaxis: None,
+                    yaxis: Some(AxisTransform::new(1.0, 0.0, "keV")),
+                }
+            )
+            .is_err());
+        assert!(binder.list_transforms("*").unwrap().is_empty());
+
+        teardown(hreq, jh);
+    }
 
     #[test]
     fn list_1() {
@@ -826,7 +1557,9 @@ mod sbind_server_tests {
         sapi.create_spectrum_1d("george", "george", 0.0, 1024.0, 512)
             .expect("maing spectrum");
 
-        binder.bind("george").expect("binding george");
+        binder
+            .bind("george", true, ChannelWidth::Long)
+            .expect("binding george");
         binder.unbind("george").expect("unbinding george");
 
         let list = binder.get_bindings("*").expect("listing");
@@ -851,6 +1584,8 @@ mod sbind_server_tests {
             1024 * 1024 + mem::size_of::<sharedmem::XamineSharedMemory>(),
             stats.total_size
         );
+        assert_eq!(1, stats.free_extents);
+        assert_eq!(0, stats.allocated_extents);
 
         teardown(hreq, jh);
     }
@@ -867,7 +1602,9 @@ mod sbind_server_tests {
         sapi.create_spectrum_1d("george", "george", 0.0, 1024.0, 512)
             .expect("maing spectrum");
 
-        binder.bind("george").expect("binding george");
+        binder
+            .bind("george", true, ChannelWidth::Long)
+            .expect("binding george");
 
         // Uses a slot and 1024*sizeof u32:
         // Spectrum is 514 channes not 512 due to the automatic over/underlow
@@ -886,6 +1623,47 @@ mod sbind_server_tests {
             1024 * 1024 + mem::size_of::<sharedmem::XamineSharedMemory>(),
             stats.total_size
         );
+        assert_eq!(1, stats.free_extents);
+        assert_eq!(1, stats.allocated_extents);
+
+        teardown(hreq, jh);
+    }
+    #[test]
+    fn history_1() {
+        // There's an initial sample and one for each binding change:
+
+        let (jh, hreq, mut binder) = setup();
+        assert_eq!(1, binder.usage_history.len());
+        assert_eq!(0, binder.usage_history[0].used_bytes);
+        assert_eq!(1024 * 1024, binder.usage_history[0].free_bytes);
+
+        let papi = parameter_messages::ParameterMessageClient::new(&hreq);
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&hreq);
+        papi.create_parameter("george").expect("making parameter");
+        sapi.create_spectrum_1d("george", "george", 0.0, 1024.0, 512)
+            .expect("making spectrum");
+
+        binder
+            .bind("george", true, ChannelWidth::Long)
+            .expect("binding george");
+        assert_eq!(2, binder.usage_history.len());
+        assert_eq!(
+            514 * mem::size_of::<u32>(),
+            binder.usage_history[1].used_bytes
+        );
+        assert_eq!(1, binder.usage_history[1].bound_indices);
+
+        binder.unbind("george").expect("unbinding george");
+        assert_eq!(3, binder.usage_history.len());
+        assert_eq!(0, binder.usage_history[2].used_bytes);
+        assert_eq!(0, binder.usage_history[2].bound_indices);
+
+        // The history is limited in length:
+
+        for _ in 0..USAGE_HISTORY_LENGTH {
+            binder.record_usage();
+        }
+        assert_eq!(USAGE_HISTORY_LENGTH, binder.usage_history.len());
 
         teardown(hreq, jh);
     }
@@ -1087,6 +1865,29 @@ mod sbind_client_tests {
         teardown(hreq, hjh, bapi, bjh);
     }
     #[test]
+    fn usage_history_1() {
+        let (hjh, hreq, bjh, bapi) = setup();
+
+        let papi = parameter_messages::ParameterMessageClient::new(&hreq);
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&hreq);
+
+        papi.create_parameter("junk").expect("Creating a parameter");
+        sapi.create_spectrum_1d("george", "junk", 0.0, 1024.0, 1024)
+            .expect("Making a spectrum");
+        bapi.bind("george")
+            .expect("Unable to bind existing spectrum");
+
+        let history = bapi.get_usage_history().expect("Getting history");
+        assert_eq!(2, history.len());
+        assert_eq!(0, history[0].used_bytes);
+        assert_eq!(0, history[0].bound_indices);
+        assert_eq!(1026 * mem::size_of::<u32>(), history[1].used_bytes);
+        assert_eq!(1, history[1].bound_indices);
+        assert!(history[0].time <= history[1].time);
+
+        teardown(hreq, hjh, bapi, bjh);
+    }
+    #[test]
     fn get_update_1() {
         // Initially the default update rate:
 
@@ -1111,6 +1912,79 @@ mod sbind_client_tests {
 
         teardown(hreq, hjh, bapi, bjh);
     }
+    #[test]
+    fn transform_1() {
+        // Set, list and clear display transforms:
+
+        let (hjh, hreq, bjh, bapi) = setup();
+
+        let papi = parameter_messages::ParameterMessageClient::new(&hreq);
+        let sapi = spectrum_messages::SpectrumMessageClient::new(&hreq);
+        papi.create_parameter("x").expect("Creating a parameter");
+        sapi.create_spectrum_1d("s1", "x", 0.0, 1024.0, 1024)
+            .expect("Creating s1");
+        sapi.create_spectrum_1d("s2", "x", 0.0, 1024.0, 1024)
+            .expect("Creating s2");
+        assert!(bapi.list_display_transforms("*").unwrap().is_empty());
+
+        let kev = DisplayTransform {
+            xaxis: Some(AxisTransform::new(0.5, 0.0, "keV")),
+            yaxis: None,
+        };
+        bapi.set_display_transform("s2", &kev).expect("Setting s2");
+        bapi.set_display_transform("s1", &kev).expect("Setting s1");
+        assert!(bapi.set_display_transform("s3", &kev).is_err());
+
+        let l = bapi.list_display_transforms("*").expect("Listing");
+        assert_eq!(
+            vec![
+                (String::from("s1"), kev.clone()),
+                (String::from("s2"), kev.clone())
+            ],
+            l
+        );
+        assert_eq!(1, bapi.list_display_transforms("s2").unwrap().len());
+
+        bapi.clear_display_transform("s1").expect("Clearing s1");
+        bapi.clear_display_transform("s1")
+            .expect("Clearing s1 again");
+        let l = bapi.list_display_transforms("*").expect("Listing");
+        assert_eq!(vec![(String::from("s2"), kev)], l);
+
+        teardown(hreq, hjh, bapi, bjh);
+    }
+    #[test]
+    fn available_1() {
+        let (hjh, hreq, bjh, bapi) = setup();
+
+        assert!(bapi.check_available().is_ok());
+
+        teardown(hreq, hjh, bapi, bjh);
+    }
+    #[test]
+    fn unavailable_1() {
+        // Without shared memory, requests that need it fail with
+        // the reason but exit works:
+
+        let (send, rcv) = mpsc::channel();
+        let jh = thread::spawn(move || serve_unavailable(rcv, "no space"));
+        let bapi = BindingApi::new(&send);
+
+        assert_eq!(
+            Err(String::from("Shared memory is unavailable: no space")),
+            bapi.check_available()
+        );
+        assert_eq!(
+            Err(String::from("Shared memory is unavailable: no space")),
+            bapi.bind("george")
+        );
+        assert!(bapi.list_bindings("*").is_err());
+        assert!(bapi.get_shname().is_err());
+        assert!(bapi.get_usage().is_err());
+
+        assert_eq!(Ok(String::new()), bapi.exit());
+        jh.join().expect("Joining binding thread");
+    }
 }
 
 // Test trace firing:
@@ -12,6 +12,7 @@
 //!
 //!
 use super::*;
+use crate::crash;
 use memmap;
 use std::collections::hash_map::Values;
 use std::collections::HashMap;
@@ -23,8 +24,7 @@ use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
 use std::ptr;
 use std::sync::mpsc::{Receiver, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use md5;
 
@@ -149,17 +149,58 @@ impl MessageHeader {
 /// chewing up bandwidth with additional mirrors.
 /// Here are entries in the mirror directory:
 ///
+/// * host - the client host's IP address.
+/// * key - the shared memory key of the client's mirror.
+/// * hostname - the client host's DNS name if it could be resolved,
+/// otherwise the IP address.
+/// * memory - the shared memory key/name (as returned by /spectcl/shmem/key)
+/// of the region the client is mirroring.
+/// * connected - when the client connected.
+///
 #[derive(Clone)] // Needed for tests will warn about dead code.
 pub struct DirectoryEntry {
     host: String,
     key: String,
+    hostname: String,
+    memory: String,
+    connected: Instant,
 }
 
 impl DirectoryEntry {
+    /// Create a minimal entry (the host is also the hostname).  Only
+    /// tests need this; clients are made with new_client.
+    #[cfg(test)]
     pub fn new(host: &str, key: &str) -> DirectoryEntry {
         DirectoryEntry {
             host: String::from(host),
             key: String::from(key),
+            hostname: String::from(host),
+            memory: String::new(),
+            connected: Instant::now(),
+        }
+    }
+    /// Create an entry for a connected mirror client.
+    ///
+    /// ### Parameters
+    /// * host - IP address of the client.
+    /// * hostname - Resolved name of the client host.
+    /// * key - Shared memory key of the client's mirror.
+    /// * memory - Key/name of the shared memory region being mirrored.
+    /// * connected - When the client connected.
+    ///
+    pub fn new_client(
+        host: &str,
+        hostname: &str,
+        key: &str,
+        memory: &str,
+        connected: Instant,
+    ) -> DirectoryEntry {
+        DirectoryEntry {
+            host: String::from(host),
+            key: String::from(key),
+            hostname: String::from(hostname),
+            memory: String::from(memory),
+            connected,
         }
     }
     pub fn host(&self) -> String {
@@ -168,6 +209,16 @@ impl DirectoryEntry {
     pub fn key(&self) -> String {
         self.key.clone()
     }
+    pub fn hostname(&self) -> String {
+        self.hostname.clone()
+    }
+    pub fn memory(&self) -> String {
+        self.memory.clone()
+    }
+    /// How long the client has been connected.
+    pub fn age(&self) -> Duration {
+        self.connected.elapsed()
+    }
 }
 
 ///  In defining the mirror directory, we assume that
@@ -198,16 +249,22 @@ impl Directory {
     /// *  If it's a duplicate, Errs indicating that.
     /// *  If it's not a duplicate, constructs a DirectoryEntry
     /// and inserts it into the items.
+    #[cfg(test)]
     pub fn add(&mut self, host: &str, key: &str) -> Result<(), String> {
-        let index = Self::compute_index(host, key);
+        self.add_entry(DirectoryEntry::new(host, key))
+    }
+    /// Adds a fully described directory entry.  As with add,
+    /// duplicate host/key pairs are an error.
+    pub fn add_entry(&mut self, entry: DirectoryEntry) -> Result<(), String> {
+        let index = Self::compute_index(&entry.host, &entry.key);
 
         if let std::collections::hash_map::Entry::Vacant(e) = self.items.entry(index) {
-            e.insert(DirectoryEntry::new(host, key));
+            e.insert(entry);
             Ok(())
         } else {
             Err(format!(
                 "The host/key pair {} {} are already registered",
-                host, key
+                entry.host, entry.key
             ))
         }
     }
@@ -241,7 +298,7 @@ pub type SharedMirrorDirectory = Arc<Mutex<Directory>>;
 /// in an option which is initially None.
 /// Thus the logic for doing upates is like this:
 ///
-/// ```
+/// ```text
 ///    compute the digest of the header
 ///    If the digest  is None
 ///      set the digest to Some(digest of the header).
@@ -265,6 +322,8 @@ struct MirrorServerInstance {
     mirror_directory: SharedMirrorDirectory,
     shm_info: Option<String>,
     digest: Option<md5::Digest>,
+    shm_name: String,
+    connected: Instant,
 }
 
 impl MirrorServerInstance {
@@ -302,41 +361,22 @@ impl MirrorServerInstance {
         };
         md5::compute(header)
     }
-    // Find the defined spectrum definition with the largest offset.
-    // note that it's possible there are no defined spectra in which case,
-    // None is returned:
-    fn find_largest_index(header: &XamineSharedMemory) -> Option<usize> {
-        let mut biggest_offset = 0;
-        let mut result = None;
-        // Note the >= on the offset compare deals with the fact that
-        // the 'lowest' spectrum can have an offset of 0...and might be the
-        // only one.
-        for i in 0..XAMINE_MAXSPEC {
-            if (header.dsp_types[i] != SpectrumTypes::Undefined)
-                && (header.dsp_offsets[i] >= biggest_offset)
-            {
-                result = Some(i);
-                biggest_offset = header.dsp_offsets[i];
-            }
-        }
-
-        result
-    }
     // Return the number of bytes used in the spectrum region.
-    // This is done by finding the spectrum with the largest offset,
-    // computing its size and converting that to bytes (offsets and sizes
-    // for rustogramer bound spectra are in u32's).
+    // This is done by finding the end of the bound spectrum that ends
+    // last.  Offsets and sizes are in units of the spectrum's channel
+    // size (rustogramer binds long, word and byte spectra).
     //  Note it's possible that there are no defined spectra in which case
     // we need to return 0.
     fn size_spectrum_region(&self) -> usize {
         let header = self.memory();
-        if let Some(largest_idx) = Self::find_largest_index(header) {
-            let offset = header.dsp_offsets[largest_idx];
-            let size = header.dsp_xy[largest_idx].xchans * header.dsp_xy[largest_idx].ychans;
-            ((offset + size) as usize) * mem::size_of::<u32>()
-        } else {
-            0
-        }
+        (0..XAMINE_MAXSPEC)
+            .filter(|&i| header.dsp_types[i] != SpectrumTypes::Undefined)
+            .map(|i| {
+                let size = (header.dsp_xy[i].xchans * header.dsp_xy[i].ychans) as usize;
+                (header.dsp_offsets[i] as usize + size) * header.dsp_types[i].channel_size()
+            })
+            .max()
+            .unwrap_or(0)
     }
     // Handle an SHM_INFO request.
     // No reply is needed:
@@ -360,12 +400,17 @@ impl MirrorServerInstance {
 
                     match std::str::from_utf8(&byte_buf) {
                         Ok(body) => {
-                            if let Err(s) = self
-                                .mirror_directory
-                                .lock()
-                                .unwrap()
-                                .add(&format!("{}", self.peer.ip()), body)
-                            {
+                            let ip = self.peer.ip();
+                            let hostname =
+                                dns_lookup::lookup_addr(&ip).unwrap_or_else(|_| ip.to_string());
+                            let entry = DirectoryEntry::new_client(
+                                &ip.to_string(),
+                                &hostname,
+                                body,
+                                &format!("file:{}", self.shm_name),
+                                self.connected,
+                            );
+                            if let Err(s) = self.mirror_directory.lock().unwrap().add_entry(entry) {
                                 Err(format!("Failed to make directory entry {}", s))
                             } else {
                                 self.shm_info = Some(String::from(body));
@@ -493,6 +538,8 @@ impl MirrorServerInstance {
                         mirror_directory: dir.clone(),
                         shm_info: None,
                         digest: None,
+                        shm_name: String::from(shm_name),
+                        connected: Instant::now(),
                     }
                 } else {
                     sock.shutdown(Shutdown::Both)
@@ -572,6 +619,38 @@ impl MirrorServerInstance {
         let _ = self.socket.shutdown(Shutdown::Both); // Ignore shutdown errors.
     }
 }
+/// Client side of an update request.  This is used by rustogramer
+/// itself to check the mirror server (see the selftest module).
+///
+/// ### Parameters
+/// * peer - connection to the mirror server.
+///
+/// ### Returns
+/// * Ok((type, body)) - type is FULL_UPDATE or PARTIAL_UPDATE.  For a
+/// FULL_UPDATE the body is an image of the shared memory region's header
+/// and used spectrum storage; for a PARTIAL_UPDATE, just the storage.
+/// * Err(message) on failure.
+///
+pub fn request_update<T: Read + Write>(peer: &mut T) -> Result<(u32, Vec<u8>), String> {
+    let header = MessageHeader {
+        msg_size: mem::size_of::<MessageHeader>() as u32,
+        msg_type: REQUEST_UPDATE,
+    };
+    header.write(peer)?;
+    peer.flush()
+        .map_err(|e| format!("Failed to flush update request: {}", e))?;
+
+    let reply = MessageHeader::read(peer)?;
+    match reply.msg_type {
+        FULL_UPDATE | PARTIAL_UPDATE => {
+            let mut body = vec![0; reply.body_size()];
+            peer.read_exact(&mut body)
+                .map_err(|e| format!("Failed to read update body: {}", e))?;
+            Ok((reply.msg_type, body))
+        }
+        t => Err(format!("Unexpected reply type to an update request: {}", t)),
+    }
+}
 /// MirrorServer listens for connections and, spawns off a MirrorServerInstance thread
 /// to handle requests by the connected client.
 /// The server is the owner of the initial copy of the shared mirror directory
@@ -611,7 +690,7 @@ impl MirrorServer {
     fn start_server_instance(&mut self, socket: TcpStream) {
         let shm_name = self.shm_name.clone();
         let dir = self.mirror_directory.clone();
-        thread::spawn(move || {
+        crash::spawn("mirror-client", move || {
             let mut instance = MirrorServerInstance::new(&shm_name, socket, dir);
             instance.run();
         });
@@ -782,6 +861,28 @@ mod dentry_tests {
         let entry = DirectoryEntry::new("localhost", "file:/some/path");
         assert_eq!("file:/some/path", entry.key().as_str());
     }
+    #[test]
+    fn new_client_1() {
+        let connected = Instant::now() - Duration::from_secs(5);
+        let entry = DirectoryEntry::new_client(
+            "127.0.0.1",
+            "localhost",
+            "Xa3b",
+            "file:/some/path",
+            connected,
+        );
+        assert_eq!("127.0.0.1", entry.host());
+        assert_eq!("localhost", entry.hostname());
+        assert_eq!("Xa3b", entry.key());
+        assert_eq!("file:/some/path", entry.memory());
+        assert!(entry.age() >= Duration::from_secs(5));
+
+        // Simple entries use the host as the name:
+
+        let entry = DirectoryEntry::new("127.0.0.1", "Xa3b");
+        assert_eq!("127.0.0.1", entry.hostname());
+        assert_eq!("", entry.memory());
+    }
 }
 #[cfg(test)]
 mod directory_tests {
@@ -821,6 +922,34 @@ mod directory_tests {
         );
     }
     #[test]
+    fn add_3() {
+        // add_entry also rejects duplicate host/key pairs:
+
+        let mut dir = Directory::new();
+        dir.add("127.0.0.1", "Xa3b").expect("added ok");
+        let result = dir.add_entry(DirectoryEntry::new_client(
+            "127.0.0.1",
+            "localhost",
+            "Xa3b",
+            "file:/some/path",
+            Instant::now(),
+        ));
+        assert!(result.is_err());
+
+        dir.add_entry(DirectoryEntry::new_client(
+            "127.0.0.1",
+            "localhost",
+            "Xa3c",
+            "file:/some/path",
+            Instant::now(),
+        ))
+        .expect("Adding entry");
+        let key = Directory::compute_index("127.0.0.1", "Xa3c");
+        let entry = dir.items.get(&key).expect("Didn't find item");
+        assert_eq!("localhost", entry.hostname());
+        assert_eq!("file:/some/path", entry.memory());
+    }
+    #[test]
     fn iterator_1() {
         // Be able to iterate over values..
 
@@ -916,8 +1045,8 @@ mod mirror_server_tests {
     use std::mem;
     use std::net::{Shutdown, TcpStream};
     use std::sync::mpsc::{channel, Sender};
+    use std::thread;
     use tempfile;
-    use thread;
 
     const SERVER_PORT: u16 = 10000;
 
@@ -1486,6 +1615,42 @@ mod mirror_server_tests {
         teardown(&sender, offset);
     }
     #[test]
+    fn mirror_4() {
+        // The size of the spectrum region allows for the channel size of
+        // each spectrum: slot 0 is a 10x10 TwodLong at offset 0 and slot
+        // 2 a 100x100 TwodWord at (word) offset 200, just after it.
+
+        let offset = 14;
+        let (mem, sender) = setup(SERVER_PORT + offset, 1024 * 1024);
+        {
+            let mut map =
+                unsafe { memmap::MmapMut::map_mut(mem.as_file()) }.expect("mapping shared memory");
+            let header = unsafe {
+                (map.as_mut_ptr() as *mut XamineSharedMemory)
+                    .as_mut()
+                    .unwrap()
+            };
+            header.dsp_xy[0].xchans = 10;
+            header.dsp_xy[0].ychans = 10;
+            header.dsp_offsets[0] = 0;
+            header.dsp_types[0] = SpectrumTypes::TwodLong;
+            header.dsp_xy[2].xchans = 100;
+            header.dsp_xy[2].ychans = 100;
+            header.dsp_offsets[2] = 200;
+            header.dsp_types[2] = SpectrumTypes::TwodWord;
+        }
+
+        let mut stream = connect_server(offset);
+        let (kind, image) = request_update(&mut stream).expect("Requesting update");
+        assert_eq!(FULL_UPDATE, kind);
+        assert_eq!(
+            mem::size_of::<XamineSharedMemory>() + 100 * mem::size_of::<u32>() + 100 * 100 * 2,
+            image.len()
+        );
+
+        teardown(&sender, offset);
+    }
+    #[test]
     fn partial_1() {
         // If there are no spectra defined, a second
         // request will give me a partial update with no payload:
@@ -1768,6 +1933,33 @@ mod mirror_server_tests {
             unsafe { psoup = psoup.add(1) };
         }
 
+        teardown(&sender, offset);
+    }
+    #[test]
+    fn client_1() {
+        // request_update gets the same data as mirror_2 and the
+        // image can be read by sharedmem::read_spectrum.
+
+        let offset = 13;
+        let (mem, sender) = setup(SERVER_PORT + offset, 1024 * 1024);
+        init_mirror_2shm(&mem);
+        let mut stream = connect_server(offset);
+
+        let (reply_type, body) = request_update(&mut stream).expect("Requesting update");
+        assert_eq!(FULL_UPDATE, reply_type);
+        assert_eq!(
+            mem::size_of::<XamineSharedMemory>() + 1024 * mem::size_of::<u32>(),
+            body.len()
+        );
+        let (_, channels) = read_spectrum(&body, 0).expect("Reading spectrum");
+        assert_eq!((0..1024).collect::<Vec<u32>>(), channels);
+
+        // Nothing changed so the next one is partial:
+
+        let (reply_type, body) = request_update(&mut stream).expect("Requesting update");
+        assert_eq!(PARTIAL_UPDATE, reply_type);
+        assert_eq!(1024 * mem::size_of::<u32>(), body.len());
+
         teardown(&sender, offset);
     }
 }
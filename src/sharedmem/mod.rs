@@ -8,10 +8,20 @@
 //! -  Support for entering graphical objecs onto shared memory
 //! spectra.
 //!
+//! 2d spectra can be bound with 32 bit (long), 16 bit (word) or 8 bit
+//! (byte) channels (see ChannelWidth).  Narrow channels halve or
+//! quarter the shared memory a large 2d spectrum needs.  Channel values
+//! that don't fit saturate at the largest value the channel can hold and
+//! are counted.  Xamine has no narrow 1d spectrum types so 1d spectra
+//! always have long channels.  As in Xamine, dsp_offsets are in units of
+//! the spectrum's channel size; spectrum storage is allocated in multiples
+//! of 4 bytes so that every spectrum is long aligned.
+//!
 extern crate dirs;
 
 //use std::fs::File;
 use std::mem;
+use std::ptr;
 
 use crate::messaging::spectrum_messages;
 pub mod binder;
@@ -40,6 +50,65 @@ pub enum SpectrumTypes {
     TwodByte = 1,
 }
 
+impl SpectrumTypes {
+    /// Number of bytes in a channel of a spectrum of this type.
+    pub fn channel_size(&self) -> usize {
+        match self {
+            SpectrumTypes::TwodByte => mem::size_of::<u8>(),
+            SpectrumTypes::TwodWord => mem::size_of::<u16>(),
+            _ => mem::size_of::<u32>(),
+        }
+    }
+}
+
+/// Width of the channels of a spectrum bound into shared memory.
+///
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum ChannelWidth {
+    Long,
+    Word,
+    Byte,
+}
+
+impl ChannelWidth {
+    /// Parse a width name: long, word or byte.
+    pub fn parse(name: &str) -> Result<ChannelWidth, String> {
+        match name {
+            "long" => Ok(ChannelWidth::Long),
+            "word" => Ok(ChannelWidth::Word),
+            "byte" => Ok(ChannelWidth::Byte),
+            _ => Err(format!(
+                "Invalid channel type '{}' must be long, word or byte",
+                name
+            )),
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChannelWidth::Long => "long",
+            ChannelWidth::Word => "word",
+            ChannelWidth::Byte => "byte",
+        }
+    }
+    /// Largest value a channel can hold.
+    pub fn max_value(&self) -> u32 {
+        match self {
+            ChannelWidth::Long => u32::MAX,
+            ChannelWidth::Word => u16::MAX as u32,
+            ChannelWidth::Byte => u8::MAX as u32,
+        }
+    }
+    // The width of channels in spectra of a type.
+
+    fn of_type(spectrum_type: SpectrumTypes) -> ChannelWidth {
+        match spectrum_type {
+            SpectrumTypes::TwodByte => ChannelWidth::Byte,
+            SpectrumTypes::TwodWord => ChannelWidth::Word,
+            _ => ChannelWidth::Long,
+        }
+    }
+}
+
 /// The dimension of a spectrum:
 
 #[repr(C)]
@@ -233,6 +302,12 @@ impl StorageAllocator {
 
         (total_free, biggest_free, total_alloc, biggest_alloc)
     }
+    /// Return the number of free and allocated extents in that order.
+    /// Many free extents mean the free space is fragmented.
+    ///
+    pub fn extent_counts(&self) -> (usize, usize) {
+        (self.free_extents.len(), self.allocated_extents.len())
+    }
 }
 
 ///  This struct, and its implementation, define an Xamine
@@ -242,6 +317,8 @@ impl StorageAllocator {
 
 pub struct SharedMemory {
     bindings: Vec<String>,
+    overflows: Vec<bool>,
+    saturated: Vec<usize>,
     backing_store: tempfile::NamedTempFile,
     map: memmap::MmapMut,
     allocator: StorageAllocator,
@@ -257,6 +334,8 @@ impl SharedMemory {
 
         for _ in 0..XAMINE_MAXSPEC {
             shm.bindings.push(String::new());
+            shm.overflows.push(true);
+            shm.saturated.push(0);
         }
         shm
     }
@@ -276,15 +355,18 @@ impl SharedMemory {
         let header = self.map.as_mut_ptr() as *mut XamineSharedMemory;
         unsafe { header.as_mut().unwrap() }
     }
-    fn slot_as_pointer(&mut self, slot: usize) -> *mut u32 {
-        let header = self.get_header();
-        // this is why for 1ds we initialized ychans to 1 not zero.
+    // Byte offset of a spectrum's storage in the spectrum storage.
 
-        let offset = header.dsp_offsets[slot];
+    fn slot_byte_offset(&mut self, slot: usize) -> usize {
+        let header = self.get_header();
+        (header.dsp_offsets[slot] as usize) * header.dsp_types[slot].channel_size()
+    }
+    fn slot_as_pointer(&mut self, slot: usize) -> *mut u32 {
+        let offset = self.slot_byte_offset(slot);
 
         // Make a *mut u32 pointer to the spectrum data:
 
-        unsafe { (self.spectrum_pointer() as *mut u32).offset(offset as isize) }
+        unsafe { self.spectrum_pointer().add(offset) as *mut u32 }
     }
     /// Create a new Xamine shared memory region and initialize
     /// it so that there are no spectra in it.
@@ -347,6 +429,8 @@ impl SharedMemory {
         //
         let mut result = SharedMemory {
             bindings: vec![],
+            overflows: vec![],
+            saturated: vec![],
             backing_store: file,
             map,
             allocator: StorageAllocator::new(specsize),
@@ -377,14 +461,22 @@ impl SharedMemory {
     /// * name - name of the spectrum.
     /// * xaxis - (low, high, bins)
     /// * yaxis - (low, high, bins).
+    /// * include_overflows - if true the under/overflow channels are
+    /// part of the shared memory copy, otherwise they are excluded.
+    /// * width - width of the channels of 2d spectra.  1d spectra always
+    ///   have long channels.
     ///
     /// Notes:
-    ///  1.   We only create onedlong and twodlong spectra and its the
-    /// presence of both axes that determine that a spectrum is 2d.
+    ///  1.   We create onedlong and twodlong, twodword or twodbyte spectra
+    /// and its the presence of both axes that determine that a spectrum is 2d.
     //// 2.   The axis specifications are used to fill in the mapping structs.
     ///  3.    In addition to allocating spectrum storage we
     /// add the spectrum to the bindings array.
     /// The slot is automatically offset by one
+    ///  4.   The bins in the axis specifications include the
+    /// under/overflow channels.  If they are excluded, the shared memory
+    /// spectrum has two fewer channels on each axis so that its
+    /// channels map exactly onto [low, high).
     ///
     /// On success, we return a double of the slot number and a pointer
     /// to where the spectrum data should be mirrored.
@@ -398,6 +490,8 @@ impl SharedMemory {
         sname: &str,
         xaxis: (f64, f64, u32),
         yaxis: Option<(f64, f64, u32)>,
+        include_overflows: bool,
+        width: ChannelWidth,
     ) -> Result<(usize, *mut u8), String> {
         // If the name is too long we need to truncate it to
         // TITLE_LENGTH -1 so there's a null termination
@@ -415,15 +509,36 @@ impl SharedMemory {
         let slot = slot.unwrap();
 
         // See if we have sufficent spectrum storage:
-        // We allow for the hidden under/overflow channels here too:
+        // Unless excluded, we allow for the under/overflow channels here too:
+
+        let channels = |bins: u32| {
+            if include_overflows {
+                bins
+            } else {
+                bins.saturating_sub(2)
+            }
+        };
+        let xchans = channels(xaxis.2);
+        let ychans = yaxis.map(|y| channels(y.2));
+        if xchans == 0 || ychans == Some(0) {
+            return Err(String::from("Spectrum has no channels to bind"));
+        }
 
-        let mut required = xaxis.2;
+        let mut required = xchans as usize;
         let mut spectrum_type = SpectrumTypes::OnedLong;
-        if let Some(y) = yaxis {
-            required *= y.2;
-            spectrum_type = SpectrumTypes::TwodLong;
+        if let Some(y) = ychans {
+            required *= y as usize;
+            spectrum_type = match width {
+                ChannelWidth::Long => SpectrumTypes::TwodLong,
+                ChannelWidth::Word => SpectrumTypes::TwodWord,
+                ChannelWidth::Byte => SpectrumTypes::TwodByte,
+            };
         }
-        let storage = self.get_free_spectrum_pointer((required as usize) * mem::size_of::<u32>());
+        // Keep every spectrum long aligned:
+
+        let required =
+            (required * spectrum_type.channel_size()).next_multiple_of(mem::size_of::<u32>());
+        let storage = self.get_free_spectrum_pointer(required);
         if storage.is_none() {
             return Err(format!(
                 "Unable to allocate spectrum storage for {} bytes",
@@ -435,9 +550,9 @@ impl SharedMemory {
         //  Fill in the appropriate header slot.
 
         let header = self.get_header();
-        header.dsp_xy[slot].xchans = xaxis.2;
-        if let Some(y) = yaxis {
-            header.dsp_xy[slot].ychans = y.2;
+        header.dsp_xy[slot].xchans = xchans;
+        if let Some(y) = ychans {
+            header.dsp_xy[slot].ychans = y;
         } else {
             header.dsp_xy[slot].ychans = 1;
         }
@@ -445,7 +560,7 @@ impl SharedMemory {
             header.dsp_titles[slot][i] = c as u8;
             header.dsp_info[slot][i] = c as u8;
         }
-        header.dsp_offsets[slot] = (offset / mem::size_of::<u32>()) as u32;
+        header.dsp_offsets[slot] = (offset / spectrum_type.channel_size()) as u32;
         header.dsp_map[slot].xmin = xaxis.0 as f32;
         header.dsp_map[slot].xmax = xaxis.1 as f32;
         if let Some(y) = yaxis {
@@ -469,9 +584,49 @@ impl SharedMemory {
         // Make the binding
 
         self.bindings[slot] = String::from(sname); // Use origial name.
+        self.overflows[slot] = include_overflows;
+        self.saturated[slot] = 0;
 
         Ok((slot, ptr))
     }
+    /// Set the axis limits and labels displayers see for a bound
+    /// spectrum.  This only changes the header's description of the
+    /// axes, the channels are not touched.
+    ///
+    /// ### Parameters
+    /// * slot - slot the spectrum is bound to.
+    /// * xaxis - (low, high, label) for the x axis.
+    /// * yaxis - (low, high, label) for the y axis if there is one.
+    ///
+    /// Labels are truncated to fit in the header.
+    ///
+    pub fn set_axis_display(
+        &mut self,
+        slot: usize,
+        xaxis: (f64, f64, &str),
+        yaxis: Option<(f64, f64, &str)>,
+    ) {
+        let header = self.get_header();
+        let map = &mut header.dsp_map[slot];
+        map.xmin = xaxis.0 as f32;
+        map.xmax = xaxis.1 as f32;
+        Self::set_label(&mut map.xlabel, xaxis.2);
+        if let Some(y) = yaxis {
+            map.ymin = y.0 as f32;
+            map.ymax = y.1 as f32;
+            Self::set_label(&mut map.ylabel, y.2);
+        } else {
+            map.ylabel[0] = 0;
+        }
+    }
+    // Copy a null terminated label into a title:
+
+    fn set_label(title: &mut SpectrumTitle, label: &str) {
+        let bytes = label.as_bytes();
+        let n = bytes.len().min(TITLE_LENGTH - 1);
+        title[..n].copy_from_slice(&bytes[..n]);
+        title[n] = 0;
+    }
     /// unbind a spectrum from shared memory:
     /// Set the binding string empty.
     /// set the header spectrum type id to undefined.
@@ -479,9 +634,9 @@ impl SharedMemory {
     ///
     pub fn unbind(&mut self, slot: usize) {
         self.bindings[slot] = String::new();
+        let offset = self.slot_byte_offset(slot);
         let header = self.get_header();
         header.dsp_types[slot] = SpectrumTypes::Undefined;
-        let offset = (header.dsp_offsets[slot] as usize) * mem::size_of::<u32>();
         self.allocator
             .free_trusted(offset)
             .expect("BUG: Failed to free spectrum storage");
@@ -495,31 +650,82 @@ impl SharedMemory {
 
         // this is why for 1ds we initialized ychans to 1 not zero.
 
-        let size = header.dsp_xy[slot].xchans * header.dsp_xy[slot].ychans;
-
-        // Make a *mut u32 pointer to the spectrum data:
+        let size = (header.dsp_xy[slot].xchans * header.dsp_xy[slot].ychans) as usize
+            * header.dsp_types[slot].channel_size();
 
-        let mut pspectrum = self.slot_as_pointer(slot);
-        for _ in 0..size {
-            unsafe {
-                *pspectrum = 0;
-                pspectrum = pspectrum.offset(1);
-            };
+        let pspectrum = self.slot_as_pointer(slot) as *mut u8;
+        unsafe {
+            ptr::write_bytes(pspectrum, 0, size);
         }
+        self.saturated[slot] = 0;
     }
     /// Given a reference to SpectrumContents and a spectrum slot,
     /// Copies the channel values into the target spectrum.
     /// note that no clear is done prior to the copy.  
     /// That's something the caller needs to do if necessary.
 
+    ///
+    /// If the binding excludes the under/overflow channels, those
+    /// channels are skipped and the bin numbers, which include them,
+    /// are mapped to the smaller shared memory spectrum.
+    ///
+    /// Values too big for the channels are set to the largest value the
+    /// channels can hold.  The number of channels for which this happened
+    /// is available from saturated_channels.
+
     pub fn set_contents(&mut self, slot: usize, contents: &spectrum_messages::SpectrumContents) {
+        let include_overflows = self.overflows[slot];
+        let xchans = self.get_header().dsp_xy[slot].xchans as usize;
+        let width = self.channel_width(slot);
+        let max_value = width.max_value();
         let pspectrum = self.slot_as_pointer(slot);
+        let mut saturated = 0;
         for c in contents.iter() {
+            let bin = if include_overflows {
+                c.bin
+            } else {
+                if c.chan_type != spectrum_messages::ChannelType::Bin {
+                    continue;
+                }
+                // The source has xchans + 2 channels per row:
+
+                let xbin = c.bin % (xchans + 2);
+                let ybin = c.bin / (xchans + 2);
+                if ybin == 0 {
+                    xbin - 1 // 1d spectrum.
+                } else {
+                    (xbin - 1) + (ybin - 1) * xchans
+                }
+            };
+            let value = if c.value > max_value as f64 {
+                saturated += 1;
+                max_value
+            } else {
+                c.value as u32
+            };
             unsafe {
-                let p = pspectrum.add(c.bin);
-                *p = c.value as u32;
+                match width {
+                    ChannelWidth::Long => *pspectrum.add(bin) = value,
+                    ChannelWidth::Word => *(pspectrum as *mut u16).add(bin) = value as u16,
+                    ChannelWidth::Byte => *(pspectrum as *mut u8).add(bin) = value as u8,
+                }
             }
         }
+        self.saturated[slot] = saturated;
+    }
+    /// The width of the channels of the spectrum bound to a slot.
+    pub fn channel_width(&mut self, slot: usize) -> ChannelWidth {
+        ChannelWidth::of_type(self.get_header().dsp_types[slot])
+    }
+    /// Number of channels whose values were too big for the channels
+    /// of the spectrum bound to a slot when its contents were last set.
+    pub fn saturated_channels(&self, slot: usize) -> usize {
+        self.saturated[slot]
+    }
+    /// Returns true if the spectrum bound to the slot includes the
+    /// under/overflow channels.
+    pub fn includes_overflows(&self, slot: usize) -> bool {
+        self.overflows[slot]
     }
     /// return the name of the shared memory segment.
     /// This will be "file:" + backing_store's filename.
@@ -589,10 +795,80 @@ impl SharedMemory {
             self.total_size,
         )
     }
+    /// Provide the number of free and allocated extents in the
+    /// spectrum storage in that order.
+    pub fn extent_counts(&self) -> (usize, usize) {
+        self.allocator.extent_counts()
+    }
     pub fn get_backing_store(&self) -> String {
         String::from(self.backing_store.path().to_string_lossy())
     }
 }
+/// Read a bound spectrum out of an image of the shared memory region.
+/// The image can be the contents of the backing store file or the
+/// body of a mirror FULL_UPDATE, which has the same layout.
+///
+/// ### Parameters
+/// * image - the bytes of the region.
+/// * slot  - the slot the spectrum is bound to.
+///
+/// ### Returns
+/// * Ok((title, channels)) - channels has xchans * ychans values,
+///   whatever the width of the spectrum's channels.
+/// * Err(message) if the slot is not bound or the image is too small.
+///
+pub fn read_spectrum(image: &[u8], slot: usize) -> Result<(String, Vec<u32>), String> {
+    let header_size = mem::size_of::<XamineSharedMemory>();
+    if image.len() < header_size {
+        return Err(String::from(
+            "The image is smaller than the shared memory header",
+        ));
+    }
+    if slot >= XAMINE_MAXSPEC {
+        return Err(format!("{} is not a valid slot number", slot));
+    }
+    // The image need not be aligned so fields are read unaligned:
+
+    let header = image.as_ptr() as *const XamineSharedMemory;
+    let (spectrum_type, xchans, ychans, offset, title) = unsafe {
+        (
+            (ptr::addr_of!((*header).dsp_types[slot]) as *const u32).read_unaligned(),
+            ptr::addr_of!((*header).dsp_xy[slot].xchans).read_unaligned() as usize,
+            ptr::addr_of!((*header).dsp_xy[slot].ychans).read_unaligned() as usize,
+            ptr::addr_of!((*header).dsp_offsets[slot]).read_unaligned() as usize,
+            ptr::addr_of!((*header).dsp_titles[slot]).read_unaligned(),
+        )
+    };
+    if spectrum_type == SpectrumTypes::Undefined as u32 {
+        return Err(format!("Slot {} is not bound", slot));
+    }
+    let channel_size = match spectrum_type {
+        t if t == SpectrumTypes::TwodByte as u32 => SpectrumTypes::TwodByte.channel_size(),
+        t if t == SpectrumTypes::TwodWord as u32 => SpectrumTypes::TwodWord.channel_size(),
+        _ => SpectrumTypes::TwodLong.channel_size(),
+    };
+    let start = header_size + offset * channel_size;
+    let end = start + xchans * ychans * channel_size;
+    if end > image.len() {
+        return Err(format!(
+            "The image does not include all of the spectrum in slot {}",
+            slot
+        ));
+    }
+    let name_length = title.iter().position(|&c| c == 0).unwrap_or(TITLE_LENGTH);
+    let channels = image[start..end]
+        .chunks_exact(channel_size)
+        .map(|c| match channel_size {
+            1 => c[0] as u32,
+            2 => u16::from_ne_bytes(c.try_into().unwrap()) as u32,
+            _ => u32::from_ne_bytes(c.try_into().unwrap()),
+        })
+        .collect();
+    Ok((
+        String::from_utf8_lossy(&title[..name_length]).to_string(),
+        channels,
+    ))
+}
 // Tests for the allocator:
 
 #[cfg(test)]
@@ -732,3 +1008,156 @@ mod allocator_tests {
         arena.free_trusted(extent2).expect("Failed to free extent2");
     }
 }
+#[cfg(test)]
+mod image_tests {
+    use super::*;
+    use crate::messaging::spectrum_messages::{Channel, ChannelType};
+    use std::fs;
+
+    #[test]
+    fn read_1() {
+        // Read a 1d and a 2d back from the backing store:
+
+        let mut shm = SharedMemory::new(1024 * 1024).expect("Making shared memory");
+        let (slot1, _) = shm
+            .bind_spectrum("oned", (0.0, 10.0, 12), None, true, ChannelWidth::Long)
+            .expect("Binding oned");
+        let (slot2, _) = shm
+            .bind_spectrum(
+                "twod",
+                (0.0, 4.0, 6),
+                Some((0.0, 2.0, 4)),
+                true,
+                ChannelWidth::Long,
+            )
+            .expect("Binding twod");
+        shm.clear_contents(slot1);
+        shm.clear_contents(slot2);
+        shm.set_contents(
+            slot1,
+            &vec![Channel {
+                chan_type: ChannelType::Bin,
+                x: 5.0,
+                y: 0.0,
+                bin: 6,
+                value: 100.0,
+            }],
+        );
+        shm.set_contents(
+            slot2,
+            &vec![Channel {
+                chan_type: ChannelType::Bin,
+                x: 1.0,
+                y: 1.0,
+                bin: 2 + 2 * 6,
+                value: 50.0,
+            }],
+        );
+
+        let image = fs::read(shm.get_backing_store()).expect("Reading backing store");
+        let (name, channels) = read_spectrum(&image, slot1).expect("Reading oned");
+        assert_eq!("oned", name);
+        assert_eq!(12, channels.len());
+        assert_eq!(100, channels[6]);
+        assert_eq!(100, channels.iter().sum::<u32>());
+
+        let (name, channels) = read_spectrum(&image, slot2).expect("Reading twod");
+        assert_eq!("twod", name);
+        assert_eq!(24, channels.len());
+        assert_eq!(50, channels[14]);
+        assert_eq!(50, channels.iter().sum::<u32>());
+    }
+    #[test]
+    fn read_2() {
+        // Failures:
+
+        let mut shm = SharedMemory::new(1024 * 1024).expect("Making shared memory");
+        let (slot, _) = shm
+            .bind_spectrum("oned", (0.0, 10.0, 12), None, true, ChannelWidth::Long)
+            .expect("Binding oned");
+        let image = fs::read(shm.get_backing_store()).expect("Reading backing store");
+
+        assert!(read_spectrum(&image, slot + 1).is_err()); // Not bound.
+        assert!(read_spectrum(&image, XAMINE_MAXSPEC).is_err()); // Bad slot.
+        assert!(read_spectrum(&image[..100], slot).is_err()); // No header.
+        let header_size = mem::size_of::<XamineSharedMemory>();
+        assert!(read_spectrum(&image[..header_size + 4], slot).is_err()); // No channels.
+    }
+    #[test]
+    fn narrow_1() {
+        // Word and byte 2d spectra save space and saturate:
+
+        let mut shm = SharedMemory::new(1024 * 1024).expect("Making shared memory");
+        let mut slots = vec![];
+        for (name, width) in [
+            ("long", ChannelWidth::Long),
+            ("word", ChannelWidth::Word),
+            ("byte", ChannelWidth::Byte),
+        ] {
+            let (slot, _) = shm
+                .bind_spectrum(name, (0.0, 4.0, 6), Some((0.0, 2.0, 3)), true, width)
+                .expect("Binding");
+            assert_eq!(width, shm.channel_width(slot));
+            shm.clear_contents(slot);
+            shm.set_contents(
+                slot,
+                &vec![
+                    Channel {
+                        chan_type: ChannelType::Bin,
+                        x: 1.0,
+                        y: 1.0,
+                        bin: 2 + 6,
+                        value: 100.0,
+                    },
+                    Channel {
+                        chan_type: ChannelType::Bin,
+                        x: 2.0,
+                        y: 1.0,
+                        bin: 3 + 6,
+                        value: 70000.0,
+                    },
+                ],
+            );
+            slots.push(slot);
+        }
+        // 18 longs, 18 words and 18 bytes rounded up to a long:
+
+        assert_eq!(18 * 4 + 18 * 2 + 20, shm.statistics().2);
+        assert_eq!(0, shm.saturated_channels(slots[0]));
+        assert_eq!(1, shm.saturated_channels(slots[1]));
+        assert_eq!(1, shm.saturated_channels(slots[2]));
+
+        let image = fs::read(shm.get_backing_store()).expect("Reading backing store");
+        for (slot, max) in slots.iter().zip([70000, 65535, 255]) {
+            let (_, channels) = read_spectrum(&image, *slot).expect("Reading");
+            assert_eq!(18, channels.len());
+            assert_eq!(100, channels[8]);
+            assert_eq!(max, channels[9]);
+            assert_eq!(100 + max, channels.iter().sum::<u32>());
+        }
+
+        // Unbinding frees the storage:
+
+        for slot in slots {
+            shm.unbind(slot);
+        }
+        assert_eq!(0, shm.statistics().2);
+    }
+    #[test]
+    fn narrow_2() {
+        // 1d spectra are always long:
+
+        let mut shm = SharedMemory::new(1024 * 1024).expect("Making shared memory");
+        let (slot, _) = shm
+            .bind_spectrum("oned", (0.0, 10.0, 12), None, true, ChannelWidth::Byte)
+            .expect("Binding oned");
+        assert_eq!(ChannelWidth::Long, shm.channel_width(slot));
+    }
+    #[test]
+    fn width_1() {
+        for w in [ChannelWidth::Long, ChannelWidth::Word, ChannelWidth::Byte] {
+            assert_eq!(w, ChannelWidth::parse(w.name()).unwrap());
+        }
+        assert!(ChannelWidth::parse("short").is_err());
+    }
+}
@@ -0,0 +1,462 @@
+//!  This module supports load testing by generating synthetic events.
+//!  When enabled (rustogramer --enable-simulation), a simulation thread
+//!  can be started that generates events at a requested rate.  Each
+//!  event contains a value for each of a set of parameters, drawn from a
+//!  distribution chosen for that parameter.  Events are sent to the
+//!  histogram server in chunks just as the processing thread does for
+//!  data read from a file so that spectra, the binder and mirror
+//!  clients all see a realistic load.
+//!
+//!  Only one simulation can run at a time.  The Simulator
+//!  struct manages the simulation thread and is shared with the REST
+//!  handlers in the Rocket state.
+//!
+use crate::messaging;
+use crate::messaging::spectrum_messages;
+use crate::parameters;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Maximum number of events sent to the histogram server in one request.
+pub const DEFAULT_SIMULATION_CHUNKSIZE: u64 = 1000;
+
+// Longest time the simulation thread sleeps before checking for
+// a stop request:
+
+const MAX_SLEEP: Duration = Duration::from_millis(100);
+
+/// The distributions from which parameter values can be drawn.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Distribution {
+    Uniform { low: f64, high: f64 },
+    Gaussian { mean: f64, sigma: f64 },
+    Constant(f64),
+}
+
+impl Distribution {
+    /// Parse a distribution from its textual form which is one of:
+    ///
+    /// * uniform:low:high
+    /// * gaussian:mean:sigma
+    /// * constant:value
+    ///
+    pub fn parse(spec: &str) -> Result<Distribution, String> {
+        let fields: Vec<&str> = spec.split(':').collect();
+        let mut values = Vec::<f64>::new();
+        for f in fields.iter().skip(1) {
+            values.push(
+                f.trim()
+                    .parse::<f64>()
+                    .map_err(|_| format!("Invalid number '{}' in distribution '{}'", f, spec))?,
+            );
+        }
+        match (fields[0], values.len()) {
+            ("uniform", 2) => {
+                if values[0] < values[1] {
+                    Ok(Distribution::Uniform {
+                        low: values[0],
+                        high: values[1],
+                    })
+                } else {
+                    Err(format!("Uniform distribution '{}' needs low < high", spec))
+                }
+            }
+            ("gaussian", 2) => {
+                if values[1] >= 0.0 {
+                    Ok(Distribution::Gaussian {
+                        mean: values[0],
+                        sigma: values[1],
+                    })
+                } else {
+                    Err(format!("Gaussian distribution '{}' needs sigma >= 0", spec))
+                }
+            }
+            ("constant", 1) => Ok(Distribution::Constant(values[0])),
+            _ => Err(format!(
+                "Invalid distribution '{}' must be uniform:low:high, gaussian:mean:sigma or constant:value",
+                spec
+            )),
+        }
+    }
+    /// Draw a value from the distribution.
+    pub fn sample(&self, gen: &mut Generator) -> f64 {
+        match self {
+            Distribution::Uniform { low, high } => low + (high - low) * gen.next_f64(),
+            Distribution::Gaussian { mean, sigma } => mean + sigma * gen.next_normal(),
+            Distribution::Constant(value) => *value,
+        }
+    }
+}
+
+/// A small xorshift64* pseudo random number generator.  This is
+/// not of cryptographic quality but it is fast, reproducible for a
+/// given seed and good enough to spread counts over spectra.
+pub struct Generator {
+    state: u64,
+}
+impl Generator {
+    pub fn new(seed: u64) -> Generator {
+        Generator {
+            state: if seed == 0 { 0x9e3779b97f4a7c15 } else { seed },
+        }
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+    /// Uniform in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+    /// Unit normal via the Box-Muller transform.
+    pub fn next_normal(&mut self) -> f64 {
+        let u1 = 1.0 - self.next_f64(); // (0, 1] so ln is finite.
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+/// Describes a simulation run:
+///
+/// * parameters - the parameter ids and the distribution of each.
+/// * rate - events per second, 0.0 means as fast as possible.
+/// * events - if Some, the number of events to generate, otherwise the
+/// simulation runs until stopped.
+/// * seed - seed for the random number generator.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulationSpec {
+    pub parameters: Vec<(u32, Distribution)>,
+    pub rate: f64,
+    pub events: Option<u64>,
+    pub seed: u64,
+}
+
+/// Status of the current or most recent simulation.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SimulationStatus {
+    pub running: bool,
+    pub events: u64,
+    pub elapsed: Duration,
+    pub error: Option<String>,
+}
+
+/// Manages the simulation thread.
+pub struct Simulator {
+    enabled: bool,
+    status: Arc<Mutex<SimulationStatus>>,
+    stop_flag: Arc<AtomicBool>,
+    thread: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+impl Simulator {
+    /// Create the simulator.  If enabled is false, simulations
+    /// can never be started.
+    pub fn new(enabled: bool) -> Simulator {
+        Simulator {
+            enabled,
+            status: Arc::new(Mutex::new(SimulationStatus::default())),
+            stop_flag: Arc::new(AtomicBool::new(false)),
+            thread: Mutex::new(None),
+        }
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+    /// Start a simulation.
+    ///
+    /// ### Parameters
+    /// * chan - channel to the histogram server.
+    /// * spec - describes the simulation.
+    ///
+    /// ### Returns
+    /// Err if simulation is not enabled, a simulation is already running
+    /// or the spec is invalid.
+    pub fn start(
+        &self,
+        chan: &mpsc::Sender<messaging::Request>,
+        spec: SimulationSpec,
+    ) -> Result<(), String> {
+        if !self.enabled {
+            return Err(String::from(
+                "Simulation is not enabled, run rustogramer with --enable-simulation",
+            ));
+        }
+        if spec.parameters.is_empty() {
+            return Err(String::from("At least one parameter must be simulated"));
+        }
+        if !spec.rate.is_finite() || spec.rate < 0.0 {
+            return Err(format!("Invalid event rate {}", spec.rate));
+        }
+        let mut handle = self.thread.lock().unwrap();
+        if self.status.lock().unwrap().running {
+            return Err(String::from("A simulation is already running"));
+        }
+        if let Some(prior) = handle.take() {
+            let _ = prior.join();
+        }
+        *self.status.lock().unwrap() = SimulationStatus {
+            running: true,
+            ..SimulationStatus::default()
+        };
+        self.stop_flag.store(false, Ordering::SeqCst);
+
+        let api = spectrum_messages::SpectrumMessageClient::new(chan);
+        let status = self.status.clone();
+        let stop_flag = self.stop_flag.clone();
+        *handle = Some(thread::spawn(move || {
+            simulation_thread(api, spec, status, stop_flag)
+        }));
+        Ok(())
+    }
+    /// Stop the running simulation, if any, and wait for its thread
+    /// to exit.
+    pub fn stop(&self) {
+        self.stop_flag.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.lock().unwrap().take() {
+            let _ = thread.join();
+        }
+    }
+    pub fn status(&self) -> SimulationStatus {
+        self.status.lock().unwrap().clone()
+    }
+}
+
+/// Produce a seed from the time of day for simulations that don't
+/// specify one.
+pub fn time_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(1)
+}
+
+// The simulation thread.  Events are generated in chunks.  When a rate
+// is requested, chunks are limited to about a tenth of a second of
+// events and the thread sleeps as needed so that the number of events
+// generated tracks the elapsed time.
+
+fn simulation_thread(
+    api: spectrum_messages::SpectrumMessageClient,
+    spec: SimulationSpec,
+    status: Arc<Mutex<SimulationStatus>>,
+    stop_flag: Arc<AtomicBool>,
+) {
+    let mut gen = Generator::new(spec.seed);
+    let start = Instant::now();
+    let mut generated: u64 = 0;
+    let mut chunk_size = DEFAULT_SIMULATION_CHUNKSIZE;
+    if spec.rate > 0.0 {
+        chunk_size = chunk_size.min((spec.rate / 10.0) as u64).max(1);
+    }
+
+    while !stop_flag.load(Ordering::SeqCst) {
+        let mut n = chunk_size;
+        if let Some(limit) = spec.events {
+            n = n.min(limit - generated);
+            if n == 0 {
+                break;
+            }
+        }
+        let events: Vec<parameters::Event> = (0..n)
+            .map(|_| {
+                spec.parameters
+                    .iter()
+                    .map(|(id, dist)| parameters::EventParameter::new(*id, dist.sample(&mut gen)))
+                    .collect()
+            })
+            .collect();
+        if let Err(msg) = api.process_events(&events) {
+            status.lock().unwrap().error = Some(msg);
+            break;
+        }
+        generated += n;
+        {
+            let mut s = status.lock().unwrap();
+            s.events = generated;
+            s.elapsed = start.elapsed();
+        }
+        // Pace the simulation:
+
+        if spec.rate > 0.0 {
+            let due = Duration::from_secs_f64(generated as f64 / spec.rate);
+            while !stop_flag.load(Ordering::SeqCst) {
+                let now = start.elapsed();
+                if now >= due {
+                    break;
+                }
+                thread::sleep((due - now).min(MAX_SLEEP));
+            }
+        }
+    }
+    let mut s = status.lock().unwrap();
+    s.elapsed = start.elapsed();
+    s.running = false;
+}
+
+#[cfg(test)]
+mod simulation_tests {
+    use super::*;
+    use crate::histogramer;
+    use crate::trace;
+
+    #[test]
+    fn parse_1() {
+        assert_eq!(
+            Ok(Distribution::Uniform {
+                low: 0.0,
+                high: 1024.0
+            }),
+            Distribution::parse("uniform:0:1024")
+        );
+        assert_eq!(
+            Ok(Distribution::Gaussian {
+                mean: 512.0,
+                sigma: 20.5
+            }),
+            Distribution::parse("gaussian:512:20.5")
+        );
+        assert_eq!(
+            Ok(Distribution::Constant(3.5)),
+            Distribution::parse("constant:3.5")
+        );
+    }
+    #[test]
+    fn parse_2() {
+        for bad in [
+            "",
+            "uniform",
+            "uniform:1",
+            "uniform:10:1",
+            "gaussian:1:-1",
+            "constant:1:2",
+            "poisson:10",
+            "uniform:a:b",
+        ] {
+            assert!(Distribution::parse(bad).is_err(), "{}", bad);
+        }
+    }
+    #[test]
+    fn sample_1() {
+        let mut gen = Generator::new(1234);
+        let uniform = Distribution::parse("uniform:10:20").unwrap();
+        let constant = Distribution::parse("constant:5").unwrap();
+        for _ in 0..1000 {
+            let v = uniform.sample(&mut gen);
+            assert!((10.0..20.0).contains(&v));
+            assert_eq!(5.0, constant.sample(&mut gen));
+        }
+    }
+    #[test]
+    fn sample_2() {
+        // The mean and sigma of the gaussian should be about right:
+
+        let mut gen = Generator::new(5678);
+        let gaussian = Distribution::parse("gaussian:100:10").unwrap();
+        let n = 10000;
+        let values: Vec<f64> = (0..n).map(|_| gaussian.sample(&mut gen)).collect();
+        let mean = values.iter().sum::<f64>() / n as f64;
+        let variance = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / n as f64;
+        assert!((mean - 100.0).abs() < 1.0, "{}", mean);
+        assert!((variance.sqrt() - 10.0).abs() < 1.0, "{}", variance.sqrt());
+    }
+    #[test]
+    fn generator_1() {
+        // Same seed same sequence:
+
+        let mut g1 = Generator::new(42);
+        let mut g2 = Generator::new(42);
+        for _ in 0..100 {
+            assert_eq!(g1.next_u64(), g2.next_u64());
+        }
+    }
+    #[test]
+    fn start_1() {
+        // Disabled simulator:
+
+        let tracedb = trace::SharedTraceStore::new();
+        let (_, chan) = histogramer::start_server(tracedb);
+        let sim = Simulator::new(false);
+        assert!(sim
+            .start(
+                &chan,
+                SimulationSpec {
+                    parameters: vec![(1, Distribution::Constant(1.0))],
+                    rate: 0.0,
+                    events: Some(10),
+                    seed: 1
+                }
+            )
+            .is_err());
+        assert!(!sim.status().running);
+        histogramer::stop_server(&chan);
+    }
+    #[test]
+    fn start_2() {
+        // Fixed number of events as fast as possible:
+
+        let tracedb = trace::SharedTraceStore::new();
+        let (_, chan) = histogramer::start_server(tracedb);
+        let sim = Simulator::new(true);
+        sim.start(
+            &chan,
+            SimulationSpec {
+                parameters: vec![(1, Distribution::Constant(1.0))],
+                rate: 0.0,
+                events: Some(2500),
+                seed: 1,
+            },
+        )
+        .expect("Starting simulation");
+
+        // Wait for it to finish:
+
+        sim.thread.lock().unwrap().take().unwrap().join().unwrap();
+
+        let status = sim.status();
+        assert!(!status.running);
+        assert_eq!(2500, status.events);
+        assert!(status.error.is_none());
+        histogramer::stop_server(&chan);
+    }
+    #[test]
+    fn start_3() {
+        // Invalid specs and duplicate starts:
+
+        let tracedb = trace::SharedTraceStore::new();
+        let (_, chan) = histogramer::start_server(tracedb);
+        let sim = Simulator::new(true);
+        let spec = SimulationSpec {
+            parameters: vec![],
+            rate: 0.0,
+            events: None,
+            seed: 1,
+        };
+        assert!(sim.start(&chan, spec.clone()).is_err());
+        assert!(sim
+            .start(
+                &chan,
+                SimulationSpec {
+                    parameters: vec![(1, Distribution::Constant(1.0))],
+                    rate: -1.0,
+                    ..spec.clone()
+                }
+            )
+            .is_err());
+
+        let spec = SimulationSpec {
+            parameters: vec![(1, Distribution::Constant(1.0))],
+            rate: 100.0,
+            ..spec
+        };
+        sim.start(&chan, spec.clone()).expect("Starting");
+        assert!(sim.start(&chan, spec).is_err());
+        sim.stop();
+        assert!(!sim.status().running);
+
+        histogramer::stop_server(&chan);
+    }
+}
@@ -0,0 +1,416 @@
+//!  Writes and reads a spectrum as comma separated values so that it
+//!  can be pulled into spreadsheets and Python/pandas without custom
+//!  parsers.  A file holds a single spectrum:  a metadata header
+//!  block of comment lines describing it followed by a table of its
+//!  non-empty channels e.g.:
+//!
+//!  ```text
+//!  # name: oned
+//!  # type: 1
+//!  # x_parameters: p1
+//!  # y_parameters:
+//!  # x_axis: 0, 1024, 1024
+//!  # y_axis:
+//!  x_bin,x,counts
+//!  11,10,1
+//!  ```
+//!
+//!  The parameter lists are separated by ", ".  Axes are low, high,
+//!  bins where, as in SpecTcl, the bins do not count the
+//!  under/overflow bins.  Axes a spectrum does not have are empty.
+//!
+//!  Each channel has its bin numbers, in which bin 0 is the underflow
+//!  bin just as in JSON spectrum files, its world coordinates and its
+//!  counts.  Spectra with a y axis have the columns
+//!  x_bin,y_bin,x,y,counts.  In pandas:
+//!
+//!  ```text
+//!  pandas.read_csv("oned.csv", comment="#")
+//!  ```
+//!
+//!  When reading, the columns can be in any order and only the world
+//!  coordinates and counts are required as those are what's used to
+//!  fill the spectrum.  Blank lines are ignored.  The parameters and
+//!  axes must be those the spectrum type needs.
+//!
+use crate::messaging::spectrum_messages::ChannelType;
+use crate::rest::spectrumio::{
+    check_definition, SpectrumChannel, SpectrumFileData, SpectrumProperties,
+};
+use std::io::{BufRead, BufReader, Read, Write};
+
+fn fdwrite(fd: &mut dyn Write, b: &str) -> Result<(), String> {
+    fd.write_all(b.as_bytes()).map_err(|e| format!("{}", e))
+}
+
+fn write_metadata(fd: &mut dyn Write, key: &str, value: &str) -> Result<(), String> {
+    if value.is_empty() {
+        fdwrite(fd, &format!("# {}:\n", key))
+    } else {
+        fdwrite(fd, &format!("# {}: {}\n", key, value))
+    }
+}
+
+// Axes in files don't count the under/overflow bins:
+
+fn axis_text(axis: Option<(f64, f64, u32)>) -> String {
+    match axis {
+        Some((low, high, bins)) => format!("{}, {}, {}", low, high, bins.saturating_sub(2)),
+        None => String::new(),
+    }
+}
+
+/// Write a spectrum as CSV to any object that supports the Write
+/// trait.
+///
+/// ### Parameters
+/// * fd - where to write the spectrum.
+/// * spectrum - the spectrum to write.
+///
+/// ### Returns
+/// Result<(), String> - the error string describes what failed.
+pub fn write_spectrum(fd: &mut dyn Write, spectrum: &SpectrumFileData) -> Result<(), String> {
+    let def = &spectrum.definition;
+    write_metadata(fd, "name", &def.name)?;
+    write_metadata(fd, "type", &def.type_string)?;
+    write_metadata(fd, "x_parameters", &def.x_parameters.join(", "))?;
+    write_metadata(fd, "y_parameters", &def.y_parameters.join(", "))?;
+    write_metadata(fd, "x_axis", &axis_text(def.x_axis))?;
+    write_metadata(fd, "y_axis", &axis_text(def.y_axis))?;
+    if def.y_axis.is_some() {
+        fdwrite(fd, "x_bin,y_bin,x,y,counts\n")?;
+        for c in spectrum.channels.iter() {
+            fdwrite(
+                fd,
+                &format!(
+                    "{},{},{},{},{}\n",
+                    c.x_bin, c.y_bin, c.x_coord, c.y_coord, c.value
+                ),
+            )?;
+        }
+    } else {
+        fdwrite(fd, "x_bin,x,counts\n")?;
+        for c in spectrum.channels.iter() {
+            fdwrite(fd, &format!("{},{},{}\n", c.x_bin, c.x_coord, c.value))?;
+        }
+    }
+    Ok(())
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str, what: &str) -> Result<T, String> {
+    text.trim()
+        .parse::<T>()
+        .map_err(|_| format!("Invalid {}: '{}'", what, text.trim()))
+}
+
+fn parse_list(value: &str) -> Vec<String> {
+    if value.is_empty() {
+        vec![]
+    } else {
+        value.split(',').map(|s| String::from(s.trim())).collect()
+    }
+}
+
+fn parse_axis(value: &str) -> Result<Option<(f64, f64, u32)>, String> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    let fields: Vec<&str> = value.split(',').collect();
+    if fields.len() != 3 {
+        return Err(format!("An axis must be low, high, bins: '{}'", value));
+    }
+    Ok(Some((
+        parse_number::<f64>(fields[0], "axis low limit")?,
+        parse_number::<f64>(fields[1], "axis high limit")?,
+        parse_number::<u32>(fields[2], "bin count")?,
+    )))
+}
+
+// Set the definition item described by a "# key: value" line.
+// Unknown keys are ignored so that people can annotate files.
+
+fn parse_metadata(line: &str, def: &mut SpectrumProperties) -> Result<(), String> {
+    let (key, value) = match line.split_once(':') {
+        Some((key, value)) => (key.trim(), value.trim()),
+        None => return Ok(()),
+    };
+    match key {
+        "name" => def.name = String::from(value),
+        "type" => def.type_string = String::from(value),
+        "x_parameters" => def.x_parameters = parse_list(value),
+        "y_parameters" => def.y_parameters = parse_list(value),
+        "x_axis" => def.x_axis = parse_axis(value)?,
+        "y_axis" => def.y_axis = parse_axis(value)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+// The positions of the columns of the channel table:
+
+struct Columns {
+    x_bin: Option<usize>,
+    y_bin: Option<usize>,
+    x: usize,
+    y: Option<usize>,
+    counts: usize,
+    count: usize,
+}
+
+fn parse_columns(line: &str, two_d: bool) -> Result<Columns, String> {
+    let names: Vec<&str> = line.split(',').map(|s| s.trim()).collect();
+    let find = |name: &str| names.iter().position(|n| *n == name);
+    let required = |name: &str| find(name).ok_or(format!("There is no {} column", name));
+    Ok(Columns {
+        x_bin: find("x_bin"),
+        y_bin: find("y_bin"),
+        x: required("x")?,
+        y: if two_d { Some(required("y")?) } else { None },
+        counts: required("counts")?,
+        count: names.len(),
+    })
+}
+
+fn parse_channel(line: &str, columns: &Columns) -> Result<SpectrumChannel, String> {
+    let fields: Vec<&str> = line.split(',').collect();
+    if fields.len() != columns.count {
+        return Err(format!(
+            "Expected {} values, got {}",
+            columns.count,
+            fields.len()
+        ));
+    }
+    let bin = |column: Option<usize>, what: &str| -> Result<usize, String> {
+        column.map_or(Ok(0), |i| parse_number::<usize>(fields[i], what))
+    };
+    Ok(SpectrumChannel {
+        chan_type: ChannelType::Bin,
+        x_coord: parse_number::<f64>(fields[columns.x], "x coordinate")?,
+        y_coord: match columns.y {
+            Some(i) => parse_number::<f64>(fields[i], "y coordinate")?,
+            None => 0.0,
+        },
+        x_bin: bin(columns.x_bin, "x bin")?,
+        y_bin: bin(columns.y_bin, "y bin")?,
+        value: parse_number::<u64>(fields[columns.counts], "counts")?,
+    })
+}
+
+/// Read a spectrum written as CSV from any object that supports the
+/// Read trait.
+///
+/// ### Parameters
+/// * f - the file.
+///
+/// ### Returns
+/// Result<Vec<SpectrumFileData>, String> - the spectrum, for
+/// consistency with the other readers a vector of one, or a string
+/// describing what failed along with the line number.
+pub fn read_spectra<T: Read>(f: &mut T) -> Result<Vec<SpectrumFileData>, String> {
+    let mut definition = SpectrumProperties {
+        name: String::new(),
+        type_string: String::new(),
+        x_parameters: vec![],
+        y_parameters: vec![],
+        x_axis: None,
+        y_axis: None,
+        gate: None,
+        fold: None,
+    };
+    let mut columns: Option<Columns> = None;
+    let mut channels = Vec::<SpectrumChannel>::new();
+    for (i, line) in BufReader::new(f).lines().enumerate() {
+        let line = line.map_err(|e| format!("{}", e))?;
+        let line = line.trim();
+        let result = if line.is_empty() {
+            Ok(())
+        } else if let Some(metadata) = line.strip_prefix('#') {
+            parse_metadata(metadata, &mut definition)
+        } else if let Some(c) = &columns {
+            parse_channel(line, c).map(|c| channels.push(c))
+        } else {
+            parse_columns(line, definition.y_axis.is_some()).map(|c| columns = Some(c))
+        };
+        result.map_err(|e| format!("Line {}: {}", i + 1, e))?;
+    }
+    if definition.name.is_empty() || definition.type_string.is_empty() {
+        return Err(String::from("The spectrum name and type are required"));
+    }
+    if definition.x_axis.is_none() && definition.y_axis.is_none() {
+        return Err(String::from("The spectrum has no axes"));
+    }
+    check_definition(&definition)?;
+    Ok(vec![SpectrumFileData {
+        definition,
+        channels,
+    }])
+}
+
+#[cfg(test)]
+mod csv_tests {
+    use super::*;
+
+    fn channel(x_bin: usize, y_bin: usize, x: f64, y: f64, value: u64) -> SpectrumChannel {
+        SpectrumChannel {
+            chan_type: ChannelType::Bin,
+            x_coord: x,
+            y_coord: y,
+            x_bin,
+            y_bin,
+            value,
+        }
+    }
+    fn spectrum(
+        type_string: &str,
+        x_parameters: &[&str],
+        x_axis: Option<(f64, f64, u32)>,
+        y_parameters: &[&str],
+        y_axis: Option<(f64, f64, u32)>,
+        channels: Vec<SpectrumChannel>,
+    ) -> SpectrumFileData {
+        SpectrumFileData {
+            definition: SpectrumProperties {
+                name: String::from("a spectrum"),
+                type_string: String::from(type_string),
+                x_parameters: x_parameters.iter().map(|s| String::from(*s)).collect(),
+                y_parameters: y_parameters.iter().map(|s| String::from(*s)).collect(),
+                x_axis,
+                y_axis,
+                gate: None,
+                fold: None,
+            },
+            channels,
+        }
+    }
+    fn write(spectrum: &SpectrumFileData) -> String {
+        let mut text = Vec::<u8>::new();
+        write_spectrum(&mut text, spectrum).expect("Writing CSV");
+        String::from_utf8(text).expect("UTF-8")
+    }
+
+    #[test]
+    fn write_1() {
+        let text = write(&spectrum(
+            "1",
+            &["p1"],
+            Some((0.0, 1024.0, 1026)),
+            &[],
+            None,
+            vec![
+                channel(1, 0, 0.0, 0.0, 10),
+                channel(1025, 0, 1024.0, 0.0, 2),
+            ],
+        ));
+        assert_eq!(
+            vec![
+                "# name: a spectrum",
+                "# type: 1",
+                "# x_parameters: p1",
+                "# y_parameters:",
+                "# x_axis: 0, 1024, 1024",
+                "# y_axis:",
+                "x_bin,x,counts",
+                "1,0,10",
+                "1025,1024,2"
+            ],
+            text.lines().collect::<Vec<&str>>()
+        );
+    }
+    #[test]
+    fn write_2() {
+        let text = write(&spectrum(
+            "g2",
+            &["x1", "x2"],
+            Some((-1.5, 1.5, 12)),
+            &["x1", "x2"],
+            Some((0.0, 100.0, 52)),
+            vec![channel(3, 4, -0.9, 6.0, 5)],
+        ));
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!("# x_parameters: x1, x2", lines[2]);
+        assert_eq!("# x_axis: -1.5, 1.5, 10", lines[4]);
+        assert_eq!("# y_axis: 0, 100, 50", lines[5]);
+        assert_eq!(
+            vec!["x_bin,y_bin,x,y,counts", "3,4,-0.9,6,5"],
+            lines[6..].to_vec()
+        );
+    }
+    #[test]
+    fn read_1() {
+        // What we write we read back with the bins excluding
+        // under/overflow:
+
+        let written = spectrum(
+            "2",
+            &["x"],
+            Some((-1.5, 1.5, 12)),
+            &["y"],
+            Some((0.0, 100.0, 52)),
+            vec![channel(3, 4, -0.9, 6.0, 5), channel(1, 1, -1.5, 0.0, 1)],
+        );
+        let text = write(&written);
+        let read = read_spectra(&mut text.as_bytes()).expect("Reading CSV");
+        assert_eq!(1, read.len());
+        let def = &read[0].definition;
+        assert_eq!("a spectrum", def.name);
+        assert_eq!("2", def.type_string);
+        assert_eq!(vec!["x"], def.x_parameters);
+        assert_eq!(vec!["y"], def.y_parameters);
+        assert_eq!(Some((-1.5, 1.5, 10)), def.x_axis);
+        assert_eq!(Some((0.0, 100.0, 50)), def.y_axis);
+        let channels: Vec<(usize, usize, f64, f64, u64)> = read[0]
+            .channels
+            .iter()
+            .map(|c| (c.x_bin, c.y_bin, c.x_coord, c.y_coord, c.value))
+            .collect();
+        assert_eq!(vec![(3, 4, -0.9, 6.0, 5), (1, 1, -1.5, 0.0, 1)], channels);
+    }
+    #[test]
+    fn read_2() {
+        // Edited files:  columns in any order, bins optional, comments
+        // and blank lines:
+
+        let text = "# name: s\n# type: 1\n# made in a spreadsheet\n# x_parameters: p\n# x_axis: 0, 10, 10\n\ncounts,x\n3,1.5\n";
+        let read = read_spectra(&mut text.as_bytes()).expect("Reading CSV");
+        let c = &read[0].channels;
+        assert_eq!(1, c.len());
+        assert_eq!((0, 1.5, 3), (c[0].x_bin, c[0].x_coord, c[0].value));
+        assert!(read[0].definition.y_axis.is_none());
+    }
+    #[test]
+    fn read_3() {
+        // Errors:
+
+        let e =
+            read_spectra(&mut "# type: 1\n# x_axis: 0, 1, 1\nx,counts\n".as_bytes()).unwrap_err();
+        assert_eq!("The spectrum name and type are required", e);
+        let e = read_spectra(&mut "# name: s\n# type: 1\nx,counts\n".as_bytes()).unwrap_err();
+        assert_eq!("The spectrum has no axes", e);
+        let e = read_spectra(
+            &mut "# name: s\n# type: 1\n# x_axis: 0, 10, 10\n\ncounts,x\n3,1.5\n".as_bytes(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            "s does not have the parameters and axes a type 1 spectrum needs",
+            e
+        );
+        let e = read_spectra(
+            &mut "# name: s\n# type: 2\n# x_parameters: x\n# x_axis: 0, 1, 1\n# y_axis: 0, 1, 1\n"
+                .as_bytes(),
+        )
+        .unwrap_err();
+        assert!(e.starts_with("s does not have"), "{}", e);
+        let e =
+            read_spectra(&mut "# name: s\n# type: xx\n# x_axis: 0, 1, 1\n".as_bytes()).unwrap_err();
+        assert_eq!("Unsupported spectrum type xx", e);
+
+        let e = read_spectra(&mut "# name: s\n# x_axis: 0, 1\n".as_bytes()).unwrap_err();
+        assert!(e.starts_with("Line 2:"), "{}", e);
+        let e = read_spectra(&mut "# x_axis: 0, 1, 1\nx_bin,counts\n".as_bytes()).unwrap_err();
+        assert_eq!("Line 2: There is no x column", e);
+        let e = read_spectra(&mut "# x_axis: 0, 1, 1\nx,counts\n1,2,3\n".as_bytes()).unwrap_err();
+        assert_eq!("Line 3: Expected 2 values, got 3", e);
+        let e = read_spectra(&mut "# x_axis: 0, 1, 1\nx,counts\n1,lots\n".as_bytes()).unwrap_err();
+        assert_eq!("Line 3: Invalid counts: 'lots'", e);
+    }
+}
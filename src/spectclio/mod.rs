@@ -34,6 +34,9 @@
 //! lines for 2-d spectra.
 //!
 
+pub mod csv;
+pub mod root;
+
 use crate::messaging::spectrum_messages::ChannelType;
 use crate::rest::spectrum;
 use crate::rest::spectrumio::{SpectrumChannel, SpectrumFileData, SpectrumProperties};
@@ -57,9 +60,13 @@ fn is_1d(t: &str) -> bool {
     match t {
         "1" => true,
         "g1" => true,
+        "S" => true,
+        "b" => true,
+        "snap1" => true,
         "g2" => false,
         "gd" => false,
         "s" => false,
+        "gs" => false,
         "2" => false,
         "m2" => false,
         _ => {
@@ -68,6 +75,13 @@ fn is_1d(t: &str) -> bool {
     }
 }
 
+// Is a spectrum type string for a summary or gamma summary?  These have
+// an x channel for each parameter (list) and only write the y axis.
+
+fn is_summary(t: &str) -> bool {
+    t == "s" || t == "gs"
+}
+
 // Write a 1d channel:
 
 fn write_1(fd: &mut dyn Write, c: &SpectrumChannel) -> Result<(), String> {
@@ -116,7 +130,7 @@ pub fn write_spectrum(fd: &mut dyn Write, spectra: &[SpectrumFileData]) -> Resul
         fdwrite(fd, &format!("\"{}\"", spectrum.definition.name))?;
         fdwrite(fd, " (")?;
         if let Some((_, _, bins)) = spectrum.definition.x_axis {
-            if spectrum.definition.type_string != "s" {
+            if !is_summary(&spectrum.definition.type_string) {
                 let bins = bins - 2;
                 fdwrite(fd, &bins.to_string())?;
             }
@@ -125,7 +139,7 @@ pub fn write_spectrum(fd: &mut dyn Write, spectra: &[SpectrumFileData]) -> Resul
             let bins = bins - 2;
             
             fdwrite(fd, &format!(" {}", &bins.to_string()))?;
-            if spectrum.definition.type_string == "s" {
+            if is_summary(&spectrum.definition.type_string) {
                 fdwrite(fd, " ")?;
             }
         
@@ -144,9 +158,15 @@ pub fn write_spectrum(fd: &mut dyn Write, spectra: &[SpectrumFileData]) -> Resul
         fdwrite(fd, &format!("{} long\n", spectrum.definition.type_string,))?;
 
         // Parenthesized names of parameters (x) - if not pgamma, or 2dsum
-        //  this is one list otherwise two:
+        //  this is one list otherwise two.  Gamma summaries have a list
+        //  for each x channel:
 
-        if spectrum.definition.type_string.as_str() != "gd"
+        if spectrum.definition.type_string.as_str() == "gs" {
+            for list in spectrum.definition.x_parameters.iter() {
+                let params: Vec<String> = list.split_whitespace().map(String::from).collect();
+                write_string_list(fd, &params)?;
+            }
+        } else if spectrum.definition.type_string.as_str() != "gd"
             && spectrum.definition.type_string.as_str() != "m2"
         {
             let mut params = spectrum.definition.x_parameters.clone();
@@ -161,9 +181,9 @@ pub fn write_spectrum(fd: &mut dyn Write, spectra: &[SpectrumFileData]) -> Resul
         fdwrite(fd, "\n")?;
 
         // axis low and high for all defined axes:
-        // Note for summary we just write the y axis.
+        // Note for (gamma) summary we just write the y axis.
 
-        if spectrum.definition.type_string.as_str() != "s" {
+        if !is_summary(&spectrum.definition.type_string) {
             if let Some((lo, hi, _)) = spectrum.definition.x_axis {
                 write_axis_def(fd, lo, hi)?;
             }
@@ -289,16 +309,46 @@ fn parse_paren_list(line: &str) -> Result<(Vec<String>, Vec<String>), String> {
     Ok((xparams, yparams))
 }
 
+// Gamma summary spectra have a parenthesized parameter list for each
+// x channel:
+//
+// (param1 [param2...]) [(param1 [param2...])...]
+//
+fn parse_paren_lists(line: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut result = Vec::<Vec<String>>::new();
+    let mut remainder = line;
+    while let Some(open) = remainder.find('(') {
+        let close = remainder
+            .find(')')
+            .ok_or_else(|| format!("Found a '(' with no matching ')' in '{}'", line))?;
+        if close < open {
+            return Err(format!("Found a ')' with no matching '(' in '{}'", line));
+        }
+        result.push(
+            remainder[open + 1..close]
+                .split_whitespace()
+                .map(String::from)
+                .collect(),
+        );
+        remainder = &remainder[close + 1..];
+    }
+    if result.is_empty() {
+        return Err(format!("Can't find the parameter lists in '{}'", line));
+    }
+    Ok(result)
+}
+
 // Reorganize the parameter lists according to spectrum type:
-// At present, the only reorganization is that a "2" spectrum requires
-// Xparams be the first element of the x params array and yaparms the second:
+// At present, the only reorganization is that "2" and strip chart ("S")
+// spectra require Xparams be the first element of the x params array and
+// yaparms the second:
 //
 fn reorganize_params(
     xparams: Vec<String>,
     yparams: Vec<String>,
     sptype: &str,
 ) -> (Vec<String>, Vec<String>) {
-    if sptype != "2" {
+    if sptype != "2" && sptype != "S" {
         (xparams, yparams)
     } else {
         (vec![xparams[0].clone()], vec![xparams[1].clone()])
@@ -489,19 +539,40 @@ fn read_header<T: Read>(l: &mut Lines<BufReader<T>>) -> Result<SpectrumPropertie
         return Err((false, format!("Error reading parameters line: {}", s)));
     }
     let param_line = param_line.unwrap();
-    let parameters = parse_paren_list(&param_line);
-    if let Err(s) = parameters {
-        return Err((
-            false,
-            format!("Unable to parse parameters from '{}': {}", param_line, s),
-        ));
-    }
-    let (mut xparams, mut yparams) = parameters.unwrap();
 
-    // Each parameter leads and ends with which must be stripped off
+    let (xparams, yparams) = if spectrum_type == "gs" {
+        // Gamma summary parameter lists become one space separated
+        // x parameter per x channel:
+
+        match parse_paren_lists(&param_line) {
+            Ok(mut lists) => (
+                lists
+                    .iter_mut()
+                    .map(|list| unquote(list).join(" "))
+                    .collect(),
+                vec![],
+            ),
+            Err(s) => {
+                return Err((
+                    false,
+                    format!("Unable to parse parameters from '{}': {}", param_line, s),
+                ));
+            }
+        }
+    } else {
+        let parameters = parse_paren_list(&param_line);
+        if let Err(s) = parameters {
+            return Err((
+                false,
+                format!("Unable to parse parameters from '{}': {}", param_line, s),
+            ));
+        }
+        let (mut xparams, mut yparams) = parameters.unwrap();
+
+        // Each parameter leads and ends with which must be stripped off
 
-    let xparams = unquote(&mut xparams);
-    let yparams = unquote(&mut yparams);
+        (unquote(&mut xparams), unquote(&mut yparams))
+    };
 
     // Have to process x/y parameters according to spectrum type:
     // Note that by now the spectrum type is supported.
@@ -561,6 +632,8 @@ fn read_header<T: Read>(l: &mut Lines<BufReader<T>>) -> Result<SpectrumPropertie
         } else {
             Some((yaxis.0, yaxis.1, ybins))
         },
+        gate: None,
+        fold: None,
     };
     Ok(result)
 }
@@ -569,7 +642,7 @@ fn transform(bins: u32, low: f64, high: f64, chan: usize) -> f64 {
     if bins == 0 {
         return 0.0;
     }
-    (chan as f64) * (high - low) / (bins as f64)
+    low + (chan as f64) * (high - low) / (bins as f64)
 }
 // Compute the coordinates of a channel given its
 // definition:
@@ -600,7 +673,7 @@ fn read_spectrum<T: Read>(l: &mut Lines<BufReader<T>>) -> Result<SpectrumFileDat
 
     // Summary spectra need to adjust the definition:
 
-    let def = if definition.type_string == "s" {
+    let def = if is_summary(&definition.type_string) {
         let mut result = definition.clone();
         result.x_axis = Some((
             0.0,
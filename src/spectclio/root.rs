@@ -0,0 +1,565 @@
+//!  Writes spectra so that they can be pulled into ROOT.  Rather than
+//!  the binary .root file format, which requires ROOT's streamers,
+//!  spectra are written as an unnamed ROOT macro.  Running the macro:
+//!
+//!  ```text
+//!  root -l spectra.C
+//!  ```
+//!
+//!  or, from within ROOT, `.x spectra.C`, creates a histogram for each
+//!  spectrum in the current directory.  One dimensional spectra become
+//!  TH1D histograms and spectra with a y axis (2, g2, gd, m2 and s)
+//!  become TH2D histograms.  Each histogram is named and titled after
+//!  its spectrum; the axis titles are the spectrum's parameters.
+//!
+//!  Rustogramer's bin numbers include the underflow channel as bin 0
+//!  just as ROOT's do, so channels map directly onto ROOT bins.  Once
+//!  the channels are set, the number of entries is set to the sum of
+//!  the counts.
+//!
+//!  Macros can also be read back (see read_root_macro).  The reader
+//!  understands the histograms we write as well as the TH1 and TH2
+//!  histograms (D, F, I, S and C flavors) ROOT itself saves with
+//!  SavePrimitive:  a spectrum is made for each histogram constructor,
+//!  its channels are set by SetBinContent and its parameters are
+//!  taken from the axis titles.  Everything else in the macro is
+//!  ignored.  Since the macro does not record spectrum types, one
+//!  dimensional histograms are read as type 1 spectra and two
+//!  dimensional histograms as type 2 spectra.  A histogram without
+//!  axis titles cannot be read as its spectrum would have no
+//!  parameters.
+//!
+use crate::messaging::spectrum_messages::ChannelType;
+use crate::rest::spectrumio::{
+    check_definition, SpectrumChannel, SpectrumFileData, SpectrumProperties,
+};
+use chrono::prelude::*;
+use std::io::{BufRead, BufReader, Read, Write};
+
+fn fdwrite(fd: &mut dyn Write, b: &str) -> Result<(), String> {
+    fd.write_all(b.as_bytes()).map_err(|e| format!("{}", e))
+}
+
+// Make a string safe to put in a C++ string literal:
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// The axis title is the list of parameters:
+
+fn write_axis_title(fd: &mut dyn Write, axis: &str, parameters: &[String]) -> Result<(), String> {
+    if parameters.is_empty() {
+        Ok(())
+    } else {
+        fdwrite(
+            fd,
+            &format!(
+                "      h->Get{}axis()->SetTitle({});\n",
+                axis,
+                quote(&parameters.join(", "))
+            ),
+        )
+    }
+}
+
+fn write_histogram(fd: &mut dyn Write, spectrum: &SpectrumFileData) -> Result<(), String> {
+    let def = &spectrum.definition;
+    let name = quote(&def.name);
+    let (xlow, xhigh, xbins) = match def.x_axis {
+        Some(axis) => axis,
+        None => return Err(format!("Spectrum {} has no x axis", def.name)),
+    };
+    // Axes count the under/overflow bins, ROOT does not:
+
+    fdwrite(fd, "   {\n")?;
+    if let Some((ylow, yhigh, ybins)) = def.y_axis {
+        fdwrite(
+            fd,
+            &format!(
+                "      TH2D* h = new TH2D({}, {}, {}, {}, {}, {}, {}, {});\n",
+                name,
+                name,
+                xbins - 2,
+                xlow,
+                xhigh,
+                ybins - 2,
+                ylow,
+                yhigh
+            ),
+        )?;
+        for c in spectrum.channels.iter() {
+            fdwrite(
+                fd,
+                &format!(
+                    "      h->SetBinContent({}, {}, {});\n",
+                    c.x_bin, c.y_bin, c.value
+                ),
+            )?;
+        }
+    } else {
+        fdwrite(
+            fd,
+            &format!(
+                "      TH1D* h = new TH1D({}, {}, {}, {}, {});\n",
+                name,
+                name,
+                xbins - 2,
+                xlow,
+                xhigh
+            ),
+        )?;
+        for c in spectrum.channels.iter() {
+            fdwrite(
+                fd,
+                &format!("      h->SetBinContent({}, {});\n", c.x_bin, c.value),
+            )?;
+        }
+    }
+    write_axis_title(fd, "X", &def.x_parameters)?;
+    write_axis_title(fd, "Y", &def.y_parameters)?;
+    let entries: u64 = spectrum.channels.iter().map(|c| c.value).sum();
+    fdwrite(fd, &format!("      h->SetEntries({});\n", entries))?;
+    fdwrite(fd, "   }\n")
+}
+
+/// Write spectra as a ROOT macro to any object that supports the
+/// Write trait.
+///
+/// ### Parameters
+/// * fd - where to write the macro.
+/// * spectra - the spectra to write.
+///
+/// ### Returns
+/// Result<(), String> - the error string describes what failed.
+pub fn write_root_macro(fd: &mut dyn Write, spectra: &[SpectrumFileData]) -> Result<(), String> {
+    fdwrite(
+        fd,
+        &format!(
+            "// Spectra written by rustogramer {}\n{{\n",
+            Local::now().format("%a %b %e %T %Y")
+        ),
+    )?;
+    for spectrum in spectra.iter() {
+        write_histogram(fd, spectrum)?;
+    }
+    fdwrite(fd, "}\n")
+}
+
+// Split the argument list of a call, which starts just after its
+// open parenthesis, into its arguments.  String literals are
+// unquoted.
+
+fn parse_arguments(text: &str) -> Result<Vec<String>, String> {
+    let mut result = Vec::<String>::new();
+    let mut arg = String::new();
+    let mut chars = text.chars();
+    loop {
+        match chars.next() {
+            None => return Err(format!("Unterminated argument list: {}", text)),
+            Some('"') => loop {
+                match chars.next() {
+                    None => return Err(format!("Unterminated string: {}", text)),
+                    Some('"') => break,
+                    Some('\\') => {
+                        if let Some(c) = chars.next() {
+                            arg.push(c);
+                        }
+                    }
+                    Some(c) => arg.push(c),
+                }
+            },
+            Some(',') => {
+                result.push(arg.trim().to_string());
+                arg.clear();
+            }
+            Some(')') => {
+                result.push(arg.trim().to_string());
+                return Ok(result);
+            }
+            Some(c) => arg.push(c),
+        }
+    }
+}
+// Find a call to function in a line and return its arguments:
+
+fn find_call(line: &str, function: &str) -> Result<Option<Vec<String>>, String> {
+    match line.find(function) {
+        Some(i) => parse_arguments(&line[i + function.len()..]).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str, what: &str) -> Result<T, String> {
+    text.parse::<T>()
+        .map_err(|_| format!("Invalid {}: '{}'", what, text))
+}
+
+// The constructors of the histograms we read with their dimension:
+
+const CONSTRUCTORS: [(&str, usize); 10] = [
+    ("new TH1D(", 1),
+    ("new TH1F(", 1),
+    ("new TH1I(", 1),
+    ("new TH1S(", 1),
+    ("new TH1C(", 1),
+    ("new TH2D(", 2),
+    ("new TH2F(", 2),
+    ("new TH2I(", 2),
+    ("new TH2S(", 2),
+    ("new TH2C(", 2),
+];
+
+// Make the spectrum definition from the constructor arguments
+// name, title, nbinsx, xlow, xhigh[, nbinsy, ylow, yhigh].  As with
+// the other spectrum file readers, the axes do not count the
+// under/overflow bins:
+
+fn make_definition(args: &[String], dimension: usize) -> Result<SpectrumProperties, String> {
+    if args.len() != 2 + 3 * dimension {
+        return Err(format!(
+            "A {} dimensional histogram needs {} constructor arguments, got {}",
+            dimension,
+            2 + 3 * dimension,
+            args.len()
+        ));
+    }
+    let axis = |i: usize| -> Result<(f64, f64, u32), String> {
+        Ok((
+            parse_number::<f64>(&args[i + 1], "axis low limit")?,
+            parse_number::<f64>(&args[i + 2], "axis high limit")?,
+            parse_number::<u32>(&args[i], "bin count")?,
+        ))
+    };
+    Ok(SpectrumProperties {
+        name: args[0].clone(),
+        type_string: dimension.to_string(),
+        x_parameters: vec![],
+        y_parameters: vec![],
+        x_axis: Some(axis(2)?),
+        y_axis: if dimension == 2 { Some(axis(5)?) } else { None },
+        gate: None,
+        fold: None,
+    })
+}
+
+// The coordinate of the center of a bin of an axis; bin 0 is the
+// underflow.
+
+fn bin_center(axis: Option<(f64, f64, u32)>, bin: usize) -> f64 {
+    match axis {
+        Some((low, high, bins)) => {
+            let width = (high - low) / bins as f64;
+            low + (bin as f64 - 0.5) * width
+        }
+        None => 0.0,
+    }
+}
+
+// Make a channel from the SetBinContent arguments:
+
+fn make_channel(args: &[String], def: &SpectrumProperties) -> Result<SpectrumChannel, String> {
+    let dimension = if def.y_axis.is_some() { 2 } else { 1 };
+    if args.len() != dimension + 1 {
+        return Err(format!(
+            "SetBinContent for {} needs {} arguments, got {}",
+            def.name,
+            dimension + 1,
+            args.len()
+        ));
+    }
+    let x_bin = parse_number::<usize>(&args[0], "x bin")?;
+    let y_bin = if dimension == 2 {
+        parse_number::<usize>(&args[1], "y bin")?
+    } else {
+        0
+    };
+    let text = &args[dimension];
+    let value = match text.parse::<u64>() {
+        Ok(v) => v,
+        Err(_) => parse_number::<f64>(text, "bin content")?.round() as u64,
+    };
+    Ok(SpectrumChannel {
+        chan_type: ChannelType::Bin,
+        x_coord: bin_center(def.x_axis, x_bin),
+        y_coord: bin_center(def.y_axis, y_bin),
+        x_bin,
+        y_bin,
+        value,
+    })
+}
+
+fn parse_line(line: &str, spectra: &mut Vec<SpectrumFileData>) -> Result<(), String> {
+    let line = line.trim();
+    if line.starts_with("//") {
+        return Ok(());
+    }
+    for (constructor, dimension) in CONSTRUCTORS.iter() {
+        if let Some(args) = find_call(line, constructor)? {
+            spectra.push(SpectrumFileData {
+                definition: make_definition(&args, *dimension)?,
+                channels: vec![],
+            });
+            return Ok(());
+        }
+    }
+    // Everything else applies to the most recent histogram:
+
+    let current = match spectra.last_mut() {
+        Some(s) => s,
+        None => return Ok(()),
+    };
+    if let Some(args) = find_call(line, "->SetBinContent(")? {
+        let channel = make_channel(&args, &current.definition)?;
+        if channel.value != 0 {
+            current.channels.push(channel);
+        }
+    } else if let Some(args) = find_call(line, "->GetXaxis()->SetTitle(")? {
+        current.definition.x_parameters = parameter_list(&args);
+    } else if let Some(args) = find_call(line, "->GetYaxis()->SetTitle(")? {
+        current.definition.y_parameters = parameter_list(&args);
+    }
+    Ok(())
+}
+
+fn parameter_list(args: &[String]) -> Vec<String> {
+    match args.first() {
+        Some(title) if !title.is_empty() => title.split(", ").map(String::from).collect(),
+        _ => vec![],
+    }
+}
+
+/// Read the spectra in a ROOT macro from any object that supports
+/// the Read trait.
+///
+/// ### Parameters
+/// * f - the macro.
+///
+/// ### Returns
+/// Result<Vec<SpectrumFileData>, String> - the spectra in the macro
+/// or a string describing what failed along with the line number.
+pub fn read_root_macro<T: Read>(f: &mut T) -> Result<Vec<SpectrumFileData>, String> {
+    let mut spectra = Vec::<SpectrumFileData>::new();
+    for (i, line) in BufReader::new(f).lines().enumerate() {
+        let line = line.map_err(|e| format!("{}", e))?;
+        parse_line(&line, &mut spectra).map_err(|e| format!("Line {}: {}", i + 1, e))?;
+    }
+    // The parameters come from the axis titles which follow the
+    // constructor, so the definitions can only be checked at the end:
+
+    for spectrum in spectra.iter() {
+        check_definition(&spectrum.definition)?;
+    }
+    Ok(spectra)
+}
+
+#[cfg(test)]
+mod root_tests {
+    use super::*;
+
+    fn channel(x_bin: usize, y_bin: usize, value: u64) -> SpectrumChannel {
+        SpectrumChannel {
+            chan_type: ChannelType::Bin,
+            x_coord: 0.0,
+            y_coord: 0.0,
+            x_bin,
+            y_bin,
+            value,
+        }
+    }
+    fn spectrum(
+        name: &str,
+        x_parameters: &[&str],
+        x_axis: Option<(f64, f64, u32)>,
+        y_parameters: &[&str],
+        y_axis: Option<(f64, f64, u32)>,
+        channels: Vec<SpectrumChannel>,
+    ) -> SpectrumFileData {
+        SpectrumFileData {
+            definition: SpectrumProperties {
+                name: String::from(name),
+                type_string: String::from(if y_axis.is_some() { "2" } else { "1" }),
+                x_parameters: x_parameters.iter().map(|s| String::from(*s)).collect(),
+                y_parameters: y_parameters.iter().map(|s| String::from(*s)).collect(),
+                x_axis,
+                y_axis,
+                gate: None,
+                fold: None,
+            },
+            channels,
+        }
+    }
+    fn write(spectra: &[SpectrumFileData]) -> String {
+        let mut text = Vec::<u8>::new();
+        write_root_macro(&mut text, spectra).expect("Writing macro");
+        String::from_utf8(text).expect("UTF-8")
+    }
+
+    #[test]
+    fn empty_1() {
+        let text = write(&[]);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(3, lines.len());
+        assert!(lines[0].starts_with("// Spectra written by rustogramer"));
+        assert_eq!("{", lines[1]);
+        assert_eq!("}", lines[2]);
+    }
+    #[test]
+    fn one_d_1() {
+        let text = write(&[spectrum(
+            "s1",
+            &["p1"],
+            Some((0.0, 1024.0, 1026)),
+            &[],
+            None,
+            vec![channel(1, 0, 10), channel(1025, 0, 2)],
+        )]);
+        let lines: Vec<&str> = text.lines().skip(2).collect();
+        assert_eq!(
+            vec![
+                "   {",
+                "      TH1D* h = new TH1D(\"s1\", \"s1\", 1024, 0, 1024);",
+                "      h->SetBinContent(1, 10);",
+                "      h->SetBinContent(1025, 2);",
+                "      h->GetXaxis()->SetTitle(\"p1\");",
+                "      h->SetEntries(12);",
+                "   }",
+                "}"
+            ],
+            lines
+        );
+    }
+    #[test]
+    fn two_d_1() {
+        let text = write(&[spectrum(
+            "s2",
+            &["x1", "x2"],
+            Some((-1.5, 1.5, 12)),
+            &["y"],
+            Some((0.0, 100.0, 52)),
+            vec![channel(3, 4, 5)],
+        )]);
+        let lines: Vec<&str> = text.lines().skip(2).collect();
+        assert_eq!(
+            vec![
+                "   {",
+                "      TH2D* h = new TH2D(\"s2\", \"s2\", 10, -1.5, 1.5, 50, 0, 100);",
+                "      h->SetBinContent(3, 4, 5);",
+                "      h->GetXaxis()->SetTitle(\"x1, x2\");",
+                "      h->GetYaxis()->SetTitle(\"y\");",
+                "      h->SetEntries(5);",
+                "   }",
+                "}"
+            ],
+            lines
+        );
+    }
+    #[test]
+    fn quote_1() {
+        assert_eq!("\"a\\\"b\\\\c\"", quote("a\"b\\c"));
+    }
+    #[test]
+    fn error_1() {
+        let mut text = Vec::<u8>::new();
+        assert!(
+            write_root_macro(&mut text, &[spectrum("bad", &[], None, &[], None, vec![])]).is_err()
+        );
+    }
+    #[test]
+    fn read_1() {
+        // What we write we can read back:
+
+        let written = vec![
+            spectrum(
+                "s1",
+                &["p1"],
+                Some((0.0, 1024.0, 1026)),
+                &[],
+                None,
+                vec![channel(1, 0, 10), channel(1025, 0, 2)],
+            ),
+            spectrum(
+                "a \"2d\" one",
+                &["x"],
+                Some((-1.5, 1.5, 12)),
+                &["y"],
+                Some((0.0, 100.0, 52)),
+                vec![channel(3, 4, 5)],
+            ),
+        ];
+        let text = write(&written);
+        let read = read_root_macro(&mut text.as_bytes()).expect("Reading macro");
+        assert_eq!(2, read.len());
+        for (w, r) in written.iter().zip(read.iter()) {
+            assert_eq!(w.definition.name, r.definition.name);
+            assert_eq!(w.definition.type_string, r.definition.type_string);
+            assert_eq!(w.definition.x_parameters, r.definition.x_parameters);
+            assert_eq!(w.definition.y_parameters, r.definition.y_parameters);
+            let root_axis = |a: Option<(f64, f64, u32)>| a.map(|(l, h, b)| (l, h, b - 2));
+            assert_eq!(root_axis(w.definition.x_axis), r.definition.x_axis);
+            assert_eq!(root_axis(w.definition.y_axis), r.definition.y_axis);
+            let bins = |s: &SpectrumFileData| -> Vec<(usize, usize, u64)> {
+                s.channels
+                    .iter()
+                    .map(|c| (c.x_bin, c.y_bin, c.value))
+                    .collect()
+            };
+            assert_eq!(bins(w), bins(r));
+        }
+        // Channel coordinates are bin centers:
+
+        assert_eq!(0.5, read[0].channels[0].x_coord);
+        assert_eq!(1024.5, read[0].channels[1].x_coord);
+        assert_eq!(-0.75, read[1].channels[0].x_coord);
+        assert_eq!(7.0, read[1].channels[0].y_coord);
+    }
+    #[test]
+    fn read_2() {
+        // Macros saved by ROOT:
+
+        let text = "{
+//=========Macro generated from canvas: c1/c1
+   TH1F *h1__1 = new TH1F(\"h1__1\",\"A title\",4,-2,2);
+   h1__1->SetBinContent(2,3.0);
+   h1__1->SetBinContent(3,0);
+   h1__1->SetEntries(3);
+   h1__1->GetXaxis()->SetTitle(\"p\");
+   h1__1->Draw(\"\");
+}
+";
+        let read = read_root_macro(&mut text.as_bytes()).expect("Reading macro");
+        assert_eq!(1, read.len());
+        let def = &read[0].definition;
+        assert_eq!("h1__1", def.name);
+        assert_eq!("1", def.type_string);
+        assert_eq!(vec![String::from("p")], def.x_parameters);
+        assert_eq!(Some((-2.0, 2.0, 4)), def.x_axis);
+        assert_eq!(1, read[0].channels.len());
+        assert_eq!(
+            (2, 3),
+            (read[0].channels[0].x_bin, read[0].channels[0].value)
+        );
+    }
+    #[test]
+    fn read_3() {
+        // Errors identify the line:
+
+        let text = "{\n   TH1D* h = new TH1D(\"s\", \"s\", 10, 0);\n}\n";
+        let e = read_root_macro(&mut text.as_bytes()).unwrap_err();
+        assert!(e.starts_with("Line 2:"), "{}", e);
+
+        let text = "{\n   TH2D* h = new TH2D(\"s\", \"s\", 10, 0, 10, 10, 0, 10);\n   h->SetBinContent(1, 2);\n";
+        assert!(read_root_macro(&mut text.as_bytes()).is_err());
+
+        let text = "   TH1D* h = new TH1D(\"s, 10, 0, 10);\n";
+        assert!(read_root_macro(&mut text.as_bytes()).is_err());
+
+        // Without axis titles there are no parameters:
+
+        let text =
+            "{\n   TH1F *h = new TH1F(\"h\",\"h\",4,-2,2);\n   h->SetBinContent(2,3.0);\n}\n";
+        assert!(read_root_macro(&mut text.as_bytes()).is_err());
+        let text = "{\n   TH2F *h = new TH2F(\"h\",\"h\",4,-2,2,4,-2,2);\n   h->GetXaxis()->SetTitle(\"x\");\n}\n";
+        assert!(read_root_macro(&mut text.as_bytes()).is_err());
+    }
+}
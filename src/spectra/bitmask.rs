@@ -0,0 +1,227 @@
+use super::*;
+use ndhistogram::value::Sum;
+
+/// Bit mask spectra (SpecTcl type b) are 1-d spectra of the bits that
+/// are set in an integer valued parameter.  If the parameter is
+/// present and the spectrum's gate is satisfied, the channel of each
+/// bit number that is set in the parameter value is incremented.
+/// Bit 0 is the least significant bit.  Parameter values are truncated
+/// to integers and negative values are ignored.
+///
+/// *   applied_gate - conditionalizes the increment of the histogram.
+/// *   name is the spectrum name.
+/// *   histogram is the underlying ndhistogram that maintains the counts.
+/// *   parameter_name is the name of the parameter whose bits are
+///     histogrammed and
+/// *   parameter_id is its id in the flattened event.
+///
+pub struct Bitmask {
+    applied_gate: SpectrumGate,
+    name: String,
+    histogram: H1DContainer,
+    parameter_name: String,
+    parameter_id: u32,
+}
+impl Spectrum for Bitmask {
+    fn check_gate(&mut self, e: &FlatEvent) -> bool {
+        self.applied_gate.check(e)
+    }
+    fn increment(&mut self, e: &FlatEvent) {
+        if let Some(p) = e[self.parameter_id] {
+            if p >= 0.0 && p < u64::MAX as f64 {
+                let mut bits = p as u64;
+                let mut histogram = self.histogram.borrow_mut();
+                while bits != 0 {
+                    let bit = bits.trailing_zeros();
+                    histogram.fill_with(&(bit as f64), e.weight());
+                    bits &= bits - 1; // Clear the lowest set bit.
+                }
+            }
+        }
+    }
+    fn required_parameter(&self) -> Option<u32> {
+        Some(self.parameter_id)
+    }
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+    fn get_type(&self) -> String {
+        String::from("Bitmask")
+    }
+    fn get_xparams(&self) -> Vec<String> {
+        vec![self.parameter_name.clone()]
+    }
+    fn get_yparams(&self) -> Vec<String> {
+        vec![]
+    }
+    fn get_gate(&self) -> Option<String> {
+        self.applied_gate
+            .gate
+            .as_ref()
+            .map(|g| g.condition_name.clone())
+    }
+    fn gate(&mut self, name: &str, dict: &ConditionDictionary) -> Result<(), String> {
+        self.applied_gate.set_gate(name, dict)
+    }
+    fn ungate(&mut self) {
+        self.applied_gate.ungate()
+    }
+    fn gate_is_current(&self, dict: &ConditionDictionary) -> Option<bool> {
+        self.applied_gate.is_current(dict)
+    }
+    fn get_histogram_1d(&self) -> Option<H1DContainer> {
+        Some(Rc::clone(&self.histogram))
+    }
+    fn get_histogram_2d(&self) -> Option<H2DContainer> {
+        None
+    }
+}
+
+impl Bitmask {
+    ///
+    /// Create a new bit mask spectrum.  The spectrum is initially
+    /// ungated.  The parameters are:
+    ///  *   spectrum_name - name of the spectrum.
+    ///  *   param_name - name of the parameter whose bits are histogrammed.
+    ///  *   pdict - the parameter dictionary used for lookup.
+    ///  *   low, high, bins - the axis (in bit numbers) if overriding
+    ///      the parameter's defaults.  Typically this is e.g. 0, 16, 16
+    ///      for a 16 bit parameter.
+    ///
+    pub fn new(
+        spectrum_name: &str,
+        param_name: &str,
+        pdict: &ParameterDictionary,
+        low: Option<f64>,
+        high: Option<f64>,
+        bins: Option<u32>,
+    ) -> Result<Bitmask, String> {
+        let param = pdict
+            .lookup(param_name)
+            .ok_or_else(|| format!("No such parameter: {}", param_name))?;
+        let (low_lim, high_lim, bin_count) = axis_limits(param, low, high, bins)?;
+
+        Ok(Bitmask {
+            applied_gate: SpectrumGate::new(),
+            name: String::from(spectrum_name),
+            histogram: Rc::new(RefCell::new(ndhistogram!(
+                axis::Uniform::new(bin_count as usize, low_lim, high_lim);
+                Sum
+            ))),
+            parameter_name: String::from(param_name),
+            parameter_id: param.get_id(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod bitmask_tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn make_params() -> ParameterDictionary {
+        let mut pd = ParameterDictionary::new();
+        pd.add("mask").unwrap();
+        let p = pd.lookup_mut("mask").unwrap();
+        p.set_limits(0.0, 16.0);
+        p.set_bins(16);
+        pd
+    }
+    fn make_event(pd: &ParameterDictionary, value: f64) -> FlatEvent {
+        let e = vec![EventParameter::new(
+            pd.lookup("mask").unwrap().get_id(),
+            value,
+        )];
+        let mut fe = FlatEvent::new();
+        fe.load_event(&e);
+        fe
+    }
+    fn contents(s: &Bitmask) -> Vec<f64> {
+        s.histogram.borrow().values().map(|v| v.get()).collect()
+    }
+
+    #[test]
+    fn new_1() {
+        // Default axis from the parameter:
+
+        let pd = make_params();
+        let s = Bitmask::new("test", "mask", &pd, None, None, None).expect("Making spectrum");
+        assert!(s.applied_gate.gate.is_none());
+        assert_eq!("test", s.get_name());
+        assert_eq!("Bitmask", s.get_type());
+        assert_eq!(vec!["mask"], s.get_xparams());
+        assert!(s.get_yparams().is_empty());
+        assert_eq!(Some((0.0, 16.0, 18)), s.get_xaxis());
+        assert!(s.get_yaxis().is_none());
+        assert_eq!(Some(1), s.required_parameter());
+    }
+    #[test]
+    fn new_2() {
+        // Override the axis and failures:
+
+        let pd = make_params();
+        let s = Bitmask::new("test", "mask", &pd, Some(0.0), Some(32.0), Some(32))
+            .expect("Making spectrum");
+        assert_eq!(Some((0.0, 32.0, 34)), s.get_xaxis());
+
+        assert!(Bitmask::new("test", "none", &pd, None, None, None).is_err());
+        let mut pd = ParameterDictionary::new();
+        pd.add("mask").unwrap();
+        assert!(Bitmask::new("test", "mask", &pd, None, Some(16.0), Some(16)).is_err());
+    }
+    #[test]
+    fn incr_1() {
+        // Each set bit increments its channel:
+
+        let pd = make_params();
+        let mut s = Bitmask::new("test", "mask", &pd, None, None, None).unwrap();
+        s.handle_event(&make_event(&pd, 0b1000_0000_0000_0101 as f64));
+        s.handle_event(&make_event(&pd, 0b0100 as f64));
+        s.handle_event(&make_event(&pd, 0.0));
+
+        let c = contents(&s);
+        assert_eq!(1.0, c[1]); // bit 0.
+        assert_eq!(2.0, c[3]); // bit 2.
+        assert_eq!(1.0, c[16]); // bit 15.
+        assert_eq!(4.0, c.iter().sum::<f64>());
+    }
+    #[test]
+    fn incr_2() {
+        // Values are truncated, negatives are ignored and bits past
+        // the axis are overflows:
+
+        let pd = make_params();
+        let mut s = Bitmask::new("test", "mask", &pd, None, None, None).unwrap();
+        s.handle_event(&make_event(&pd, 2.7));
+        s.handle_event(&make_event(&pd, -1.0));
+        s.handle_event(&make_event(&pd, (1u32 << 20) as f64));
+
+        let c = contents(&s);
+        assert_eq!(1.0, c[2]); // bit 1.
+        assert_eq!(1.0, c[17]); // overflow.
+        assert_eq!(2.0, c.iter().sum::<f64>());
+    }
+    #[test]
+    fn incr_3() {
+        // Gated - True increments, False does not:
+
+        let pd = make_params();
+        let mut s = Bitmask::new("test", "mask", &pd, None, None, None).unwrap();
+        let mut gd = ConditionDictionary::new();
+        gd.insert(
+            String::from("true"),
+            Rc::new(RefCell::new(Box::new(True {}))),
+        );
+        gd.insert(
+            String::from("false"),
+            Rc::new(RefCell::new(Box::new(False {}))),
+        );
+        s.gate("true", &gd).expect("Gating");
+        s.handle_event(&make_event(&pd, 1.0));
+        s.gate("false", &gd).expect("Gating");
+        s.handle_event(&make_event(&pd, 1.0));
+
+        assert_eq!(1.0, contents(&s)[1]);
+    }
+}
@@ -0,0 +1,298 @@
+use super::*;
+use ndhistogram::value::Sum;
+///
+/// Gamma summary spectra are to Multi1d spectra what summary spectra
+/// are to 1d spectra.  Think of the spectrum as an array of vertical
+/// channel strips in a 2-d spectrum where each strip is the Multi1d
+/// (SpecTcl gamma 1d) spectrum of its own list of parameters.  Each x
+/// channel might, for example, be a ring of a segmented detector and
+/// its parameters the segments in that ring.
+///
+/// The x axis has one channel for each parameter list.  If the
+/// spectrum's gate is satisfied, for each x channel, the y value of
+/// each of its parameters present in the event is incremented.
+///
+/// Creating a spectrum requires:
+///
+/// *   A list of existing parameters for each x channel.
+/// *   The range of the y axis.
+/// *   The number of bins on the y axis.
+///
+/// As with summary spectra, if these are defaulted, they are the
+/// minimum of the parameters' default low limits, the maximum of their
+/// default high limits and the maximum of their default bins.
+///
+pub struct GammaSummary {
+    applied_gate: SpectrumGate,
+    name: String,
+    histogram: H2DContainer,
+
+    // Parameter information - one list for each x channel:
+    param_names: Vec<Vec<String>>,
+    param_ids: Vec<Vec<u32>>,
+}
+
+impl Spectrum for GammaSummary {
+    fn check_gate(&mut self, e: &FlatEvent) -> bool {
+        self.applied_gate.check(e)
+    }
+    // The index of a parameter list is the x channel.
+    // Increment for all of the valid ids in each list:
+
+    fn increment(&mut self, e: &FlatEvent) {
+        let mut histogram = self.histogram.borrow_mut();
+        for (x, ids) in self.param_ids.iter().enumerate() {
+            for id in ids {
+                if let Some(y) = e[*id] {
+                    histogram.fill_with(&(x as f64, y), e.weight());
+                }
+            }
+        }
+    }
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+    fn get_type(&self) -> String {
+        String::from("GammaSummary")
+    }
+    // Each x parameter is the space separated list of the parameters
+    // of one x channel:
+
+    fn get_xparams(&self) -> Vec<String> {
+        self.param_names.iter().map(|l| l.join(" ")).collect()
+    }
+    fn get_yparams(&self) -> Vec<String> {
+        vec![]
+    }
+    fn get_gate(&self) -> Option<String> {
+        self.applied_gate
+            .gate
+            .as_ref()
+            .map(|g| g.condition_name.clone())
+    }
+    fn gate(&mut self, name: &str, dict: &ConditionDictionary) -> Result<(), String> {
+        self.applied_gate.set_gate(name, dict)
+    }
+    fn ungate(&mut self) {
+        self.applied_gate.ungate()
+    }
+    fn gate_is_current(&self, dict: &ConditionDictionary) -> Option<bool> {
+        self.applied_gate.is_current(dict)
+    }
+    fn get_histogram_1d(&self) -> Option<H1DContainer> {
+        None
+    }
+    fn get_histogram_2d(&self) -> Option<H2DContainer> {
+        Some(Rc::clone(&self.histogram))
+    }
+}
+impl GammaSummary {
+    /// Generate the spectrum.
+    /// This fails if:
+    /// *    There are no parameter lists or any list is empty.
+    /// *    Any of the parameters is not defined.
+    /// *    Any axis spec is defaulted but none of the parameters
+    ///      provide a default for it.
+    ///
+    pub fn new(
+        name: &str,
+        params: Vec<Vec<String>>,
+        pdict: &ParameterDictionary,
+        ylow: Option<f64>,
+        yhigh: Option<f64>,
+        bins: Option<u32>,
+    ) -> Result<GammaSummary, String> {
+        if params.is_empty() {
+            return Err(String::from("There must be at least one parameter list"));
+        }
+        let mut low = None;
+        let mut high = None;
+        let mut nbins = None;
+
+        let mut param_ids = Vec::<Vec<u32>>::new();
+        for (x, list) in params.iter().enumerate() {
+            if list.is_empty() {
+                return Err(format!("The parameter list for x channel {} is empty", x));
+            }
+            let mut ids = Vec::<u32>::new();
+            for name in list {
+                if let Some(p) = pdict.lookup(name) {
+                    ids.push(p.get_id());
+                    let lims = p.get_limits();
+                    low = optmin(low, lims.0);
+                    high = optmax(high, lims.1);
+                    nbins = optmax(nbins, p.get_bins());
+                } else {
+                    return Err(format!("Parameter {} does not exist", name));
+                }
+            }
+            param_ids.push(ids);
+        }
+        // Override defaults:
+
+        let low = ylow
+            .or(low)
+            .ok_or_else(|| String::from("None of the parameters can default the axis low limit"))?;
+        let high = yhigh.or(high).ok_or_else(|| {
+            String::from("None of the parameters can default the axis high limit")
+        })?;
+        let nbins = bins
+            .or(nbins)
+            .ok_or_else(|| String::from("None of the parameters can default the bin count"))?;
+
+        Ok(GammaSummary {
+            applied_gate: SpectrumGate::new(),
+            name: String::from(name),
+            histogram: Rc::new(RefCell::new(ndhistogram!(
+                axis::Uniform::new(params.len(), 0.0, params.len() as f64),
+                axis::Uniform::new(nbins as usize, low, high);
+                Sum
+            ))),
+            param_names: params,
+            param_ids,
+        })
+    }
+}
+
+#[cfg(test)]
+mod gammasummary_tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // Make 10 parameters with default axes and split them into
+    // lists of 2 (param.0, param.1), (param.2, param.3)...
+
+    fn make_params() -> (ParameterDictionary, Vec<Vec<String>>) {
+        let mut pd = ParameterDictionary::new();
+        let mut lists = Vec::<Vec<String>>::new();
+        for i in 0..10 {
+            let name = format!("param.{}", i);
+            pd.add(&name).unwrap();
+            let p = pd.lookup_mut(&name).unwrap();
+            p.set_limits(0.0, 1023.0);
+            p.set_bins(1024);
+            if i % 2 == 0 {
+                lists.push(vec![name]);
+            } else {
+                lists.last_mut().unwrap().push(name);
+            }
+        }
+        (pd, lists)
+    }
+    fn make_event(pd: &ParameterDictionary, values: &[(&str, f64)]) -> FlatEvent {
+        let mut e = Event::new();
+        for (name, value) in values {
+            let id = pd.lookup(name).unwrap().get_id();
+            e.push(EventParameter::new(id, *value));
+        }
+        let mut fe = FlatEvent::new();
+        fe.load_event(&e);
+        fe
+    }
+
+    #[test]
+    fn new_1() {
+        // Default y axis:
+
+        let (pd, lists) = make_params();
+        let s = GammaSummary::new("test", lists.clone(), &pd, None, None, None)
+            .expect("Making spectrum");
+        assert!(s.applied_gate.gate.is_none());
+        assert_eq!("test", s.get_name());
+        assert_eq!("GammaSummary", s.get_type());
+        assert_eq!(lists, s.param_names);
+        assert_eq!(vec![1, 2], s.param_ids[0]);
+        assert_eq!(vec![9, 10], s.param_ids[4]);
+        assert_eq!(
+            vec!["param.0 param.1", "param.2 param.3"],
+            s.get_xparams()[0..2]
+        );
+        assert!(s.get_yparams().is_empty());
+
+        assert_eq!(Some((0.0, 5.0, 7)), s.get_xaxis());
+        assert_eq!(Some((0.0, 1023.0, 1026)), s.get_yaxis());
+    }
+    #[test]
+    fn new_2() {
+        // Override the y axis:
+
+        let (pd, lists) = make_params();
+        let s = GammaSummary::new("test", lists, &pd, Some(-1.0), Some(1.0), Some(200))
+            .expect("Making spectrum");
+        assert_eq!(Some((-1.0, 1.0, 202)), s.get_yaxis());
+    }
+    #[test]
+    fn new_3() {
+        // Failures:
+
+        let (pd, lists) = make_params();
+        assert!(GammaSummary::new("test", vec![], &pd, None, None, None).is_err());
+        let mut empty = lists.clone();
+        empty.push(vec![]);
+        assert!(GammaSummary::new("test", empty, &pd, None, None, None).is_err());
+        let mut missing = lists.clone();
+        missing[1].push(String::from("no-such-parameter"));
+        assert!(GammaSummary::new("test", missing, &pd, None, None, None).is_err());
+
+        // No defaults:
+
+        let mut pd = ParameterDictionary::new();
+        pd.add("p").unwrap();
+        let lists = vec![vec![String::from("p")]];
+        assert!(GammaSummary::new("test", lists.clone(), &pd, None, Some(1.0), Some(2)).is_err());
+        assert!(GammaSummary::new("test", lists.clone(), &pd, Some(0.0), None, Some(2)).is_err());
+        assert!(GammaSummary::new("test", lists, &pd, Some(0.0), Some(1.0), None).is_err());
+    }
+    #[test]
+    fn foldable_1() {
+        let (pd, lists) = make_params();
+        let s = GammaSummary::new("test", lists, &pd, None, None, None).unwrap();
+        assert!(!s.can_fold());
+    }
+    #[test]
+    fn incr_1() {
+        // Each parameter present increments its x channel:
+
+        let (pd, lists) = make_params();
+        let mut s = GammaSummary::new("test", lists, &pd, None, None, None).unwrap();
+        let fe = make_event(
+            &pd,
+            &[("param.0", 10.0), ("param.1", 20.0), ("param.5", 30.0)],
+        );
+        s.handle_event(&fe);
+
+        let h = s.histogram.borrow();
+        assert_eq!(1.0, h.value(&(0.0, 10.0)).unwrap().get());
+        assert_eq!(1.0, h.value(&(0.0, 20.0)).unwrap().get());
+        assert_eq!(1.0, h.value(&(2.0, 30.0)).unwrap().get());
+        let total: f64 = h.iter().map(|c| c.value.get()).sum();
+        assert_eq!(3.0, total);
+    }
+    #[test]
+    fn incr_2() {
+        // Gated - True increments, False does not:
+
+        let (pd, lists) = make_params();
+        let mut s = GammaSummary::new("test", lists, &pd, None, None, None).unwrap();
+        let mut gd = ConditionDictionary::new();
+        gd.insert(
+            String::from("true"),
+            Rc::new(RefCell::new(Box::new(True {}))),
+        );
+        gd.insert(
+            String::from("false"),
+            Rc::new(RefCell::new(Box::new(False {}))),
+        );
+        let fe = make_event(&pd, &[("param.3", 10.0)]);
+
+        s.gate("true", &gd).expect("Gating");
+        assert_eq!(Some(String::from("true")), s.get_gate());
+        s.handle_event(&fe);
+        s.gate("false", &gd).expect("Gating");
+        s.handle_event(&fe);
+
+        let h = s.histogram.borrow();
+        assert_eq!(1.0, h.value(&(1.0, 10.0)).unwrap().get());
+    }
+}
@@ -10,6 +10,7 @@
 //!
 //!  *  onedrange - Integration 1d from low/high pair.
 //!  *  twod      - Integrate 2d over contour object.
+//!  *  contours  - Integrate 2d over the union of several contours.
 //!
 //! Each of these takes what iter gave it and returns
 //! Option&lt;SumElement&gt; object.
@@ -18,7 +19,7 @@
 //!  the payload are the sum elements for the integration.
 
 use crate::conditions::twod;
-use crate::messaging::spectrum_messages; // Need for reconstiting contours.
+use crate::messaging::{condition_messages, spectrum_messages}; // Need for reconstiting contours.
 
 use libm::sqrt;
 
@@ -39,8 +40,21 @@ pub enum AreaOfInterest {
         low: f64,
         high: f64,
     },
-    Twod(twod::Contour), // 2d contour of interest.
-    All,                 // No limits.
+    Twod(twod::Contour),          // 2d contour of interest.
+    Contours(Vec<twod::Contour>), // Inside any of these (ContourSet).
+    All,                          // No limits.
+}
+/// Describes the channels a fold removes from a spectrum.  Folds act
+/// when a spectrum is incremented, so the channels inside a fold's
+/// region of interest can only hold counts that got there some other
+/// way (e.g. contents read from file).  Integrations over folded spectra
+/// skip those channels so that results are consistent with increments.
+#[derive(Clone)]
+pub enum FoldExclusion {
+    None,
+    Oned { low: f64, high: f64 }, // 1-d fold on a 1-d spectrum - x in the slice.
+    Twod { low: f64, high: f64 }, // 1-d fold on a 2-d spectrum - x or y in the slice.
+    Contour(twod::Contour),       // 2-d fold on a 2-d spectrum - (x,y) in the contour.
 }
 /// The results of an integration.
 
@@ -50,6 +64,70 @@ pub struct Integration {
     pub centroid: (f64, f64),
     pub fwhm: (f64, f64),
 }
+/// Determine if a channel is inside an area of interest.
+///
+/// ### Parameters:
+///   *  chan - the channel.
+///   *  aoi - the area of interest.
+///
+/// ### Returns:
+///   true if the channel coordinates are inside the area of interest.
+///   The channel type is not considered.
+pub fn inside(chan: &spectrum_messages::Channel, aoi: &AreaOfInterest) -> bool {
+    match aoi {
+        AreaOfInterest::All => true,
+        AreaOfInterest::Oned { low, high } => (chan.x >= *low) && (chan.x <= *high),
+        AreaOfInterest::Twod(c) => c.inside(chan.x, chan.y),
+        AreaOfInterest::Contours(cs) => cs.iter().any(|c| c.inside(chan.x, chan.y)),
+    }
+}
+/// Make the 2-d area of interest described by a contour-like condition.
+/// The parameters of the condition are irrelevant; only its shape is used.
+///
+/// ### Parameters:
+///   *  props - the condition's properties as gotten from the histogram server.
+///      This can be a Contour, a C2Band, a MultiContour or a ContourSet.
+///
+/// ### Returns:
+///   Result&lt;AreaOfInterest, String&gt; - an error if the condition
+///   is not contour-like or its points can't make a contour.
+pub fn contour_aoi(
+    props: condition_messages::ConditionProperties,
+) -> Result<AreaOfInterest, String> {
+    match props.type_name.as_str() {
+        "ContourSet" => {
+            let mut contours = Vec::<twod::Contour>::new();
+            for polygon in props.polygons {
+                let pts = polygon
+                    .iter()
+                    .map(|(x, y)| twod::Point::new(*x, *y))
+                    .collect();
+                match twod::Contour::new(0, 1, pts) {
+                    Some(c) => contours.push(c),
+                    None => {
+                        return Err(String::from(
+                            "A contour set polygon has too few points to be a contour",
+                        ))
+                    }
+                }
+            }
+            if contours.is_empty() {
+                Err(String::from("The contour set has no polygons"))
+            } else {
+                Ok(AreaOfInterest::Contours(contours))
+            }
+        }
+        // A gamma contour has the same shape as a contour:
+        "MultiContour" => {
+            condition_messages::reconstitute_contour(condition_messages::ConditionProperties {
+                type_name: String::from("Contour"),
+                ..props
+            })
+            .map(AreaOfInterest::Twod)
+        }
+        _ => condition_messages::reconstitute_contour(props).map(AreaOfInterest::Twod),
+    }
+}
 // This function handles a single channel returning a SumElement
 // Parameters
 //  ch : the channel to evaluate.
@@ -69,11 +147,7 @@ fn sum_channel(chan: &spectrum_messages::Channel, aoi: &AreaOfInterest) -> SumEl
             }
         }
         spectrum_messages::ChannelType::Bin => {
-            if match aoi {
-                AreaOfInterest::All => true,
-                AreaOfInterest::Oned { low, high } => (chan.x >= *low) && (chan.x <= *high),
-                AreaOfInterest::Twod(c) => c.inside(chan.x, chan.y),
-            } {
+            if inside(chan, aoi) {
                 SumElement {
                     contents: chan.value,
                     wsum: (chan.value * chan.x, chan.value * chan.y),
@@ -87,16 +161,45 @@ fn sum_channel(chan: &spectrum_messages::Channel, aoi: &AreaOfInterest) -> SumEl
         }
     }
 }
+// True if a channel is one that the fold removes from the spectrum:
+
+fn folded_out(chan: &spectrum_messages::Channel, fold: &FoldExclusion) -> bool {
+    match fold {
+        FoldExclusion::None => false,
+        FoldExclusion::Oned { low, high } => (chan.x >= *low) && (chan.x <= *high),
+        FoldExclusion::Twod { low, high } => {
+            ((chan.x >= *low) && (chan.x <= *high)) || ((chan.y >= *low) && (chan.y <= *high))
+        }
+        FoldExclusion::Contour(c) => c.inside(chan.x, chan.y),
+    }
+}
+// Sum a channel unless the fold removes it:
+
+fn sum_unfolded_channel(
+    chan: &spectrum_messages::Channel,
+    aoi: &AreaOfInterest,
+    fold: &FoldExclusion,
+) -> SumElement {
+    if folded_out(chan, fold) {
+        SumElement {
+            contents: 0.0,
+            wsum: (0.0, 0.0),
+        }
+    } else {
+        sum_channel(chan, aoi)
+    }
+}
 // COmpute (centroid.x, centroid.y, counts)
 fn centroid(
     contents: &spectrum_messages::SpectrumContents,
     aoi: &AreaOfInterest,
+    fold: &FoldExclusion,
 ) -> (f64, f64, f64) {
     let mut wsums = (0.0_f64, 0.0_f64);
     let mut counts = 0.0_f64;
 
     for chan in contents {
-        let contribution = sum_channel(chan, aoi);
+        let contribution = sum_unfolded_channel(chan, aoi, fold);
         counts += contribution.contents;
         wsums.0 += contribution.wsum.0;
         wsums.1 += contribution.wsum.1;
@@ -113,10 +216,11 @@ fn fwhm(
     total_counts: f64,
     contents: &spectrum_messages::SpectrumContents,
     aoi: &AreaOfInterest,
+    fold: &FoldExclusion,
 ) -> (f64, f64) {
     let mut sqsums = (0.0_f64, 0.0_f64);
     for chan in contents {
-        let contribution = sum_channel(chan, aoi);
+        let contribution = sum_unfolded_channel(chan, aoi, fold);
         sqsums.0 += contribution.contents * (chan.x - centroid.0) * (chan.x - centroid.0);
         sqsums.1 += contribution.contents * (chan.y - centroid.1) * (chan.y - centroid.1);
     }
@@ -140,9 +244,14 @@ fn fwhm(
 /// *  1-d just use limits that include the entire range of the axis.
 /// *  Construct a rectangular contour that spans the entire range of both axes.
 ///
+/// Channels the fold of a folded spectrum removes are never included in
+/// the integration.
+///
 /// ### Parameters:
 ///   *  contents - spectrum contents gotten from the histogram server.
 ///   *  aoi - an area of interest that defines the region of integration.
+///   *  fold - describes the channels the spectrum's fold removes
+///      (FoldExclusion::None if the spectrum is not folded).
 ///
 /// ### Returns:
 ///   Instanceo of an Integration.
@@ -153,9 +262,10 @@ fn fwhm(
 pub fn integrate(
     contents: &spectrum_messages::SpectrumContents,
     aoi: AreaOfInterest,
+    fold: FoldExclusion,
 ) -> Integration {
-    let (cx, cy, counts) = centroid(contents, &aoi);
-    let width = fwhm((cx, cy), counts, contents, &aoi);
+    let (cx, cy, counts) = centroid(contents, &aoi, &fold);
+    let width = fwhm((cx, cy), counts, contents, &aoi, &fold);
 
     Integration {
         sum: counts,
@@ -172,14 +282,19 @@ mod test_utilities {
     //
 
     pub fn make_contour() -> Contour {
+        make_contour_at(100.0)
+    }
+    // The same diamond centered at x:
+
+    pub fn make_contour_at(x: f64) -> Contour {
         Contour::new(
             0,
             1,
             vec![
-                Point::new(100.0, 0.0),
-                Point::new(150.0, 50.0),
-                Point::new(100.0, 100.0),
-                Point::new(50.0, 50.0),
+                Point::new(x, 0.0),
+                Point::new(x + 50.0, 50.0),
+                Point::new(x, 100.0),
+                Point::new(x - 50.0, 50.0),
             ],
         )
         .unwrap()
@@ -340,6 +455,29 @@ mod integrate_channel_tests {
         assert_eq!(0.0, value.contents);
         assert_eq!((0.0, 0.0), value.wsum);
     }
+    #[test]
+    fn contours_1() {
+        // Inside either of a set of contours counts:
+
+        let aoi = AreaOfInterest::Contours(vec![
+            test_utilities::make_contour(),
+            test_utilities::make_contour_at(300.0),
+        ]);
+        for (x, y, expected) in [
+            (100.0, 50.0, 100.0),
+            (300.0, 50.0, 100.0),
+            (200.0, 50.0, 0.0),
+        ] {
+            let chan = Channel {
+                chan_type: ChannelType::Bin,
+                x,
+                y,
+                bin: 0,
+                value: 100.0,
+            };
+            assert_eq!(expected, sum_channel(&chan, &aoi).contents, "{}, {}", x, y);
+        }
+    }
 }
 #[cfg(test)]
 mod integration_tests {
@@ -373,7 +511,7 @@ mod integration_tests {
         // Empty spectrum returns 0's:
 
         let contents: SpectrumContents = vec![];
-        let result = integrate(&contents, AreaOfInterest::All);
+        let result = integrate(&contents, AreaOfInterest::All, FoldExclusion::None);
 
         assert_eq!(
             Integration {
@@ -389,7 +527,7 @@ mod integration_tests {
         // 1d spke for all:
 
         let contents = make_spike_1d(100.0, 250.0);
-        let result = integrate(&contents, AreaOfInterest::All);
+        let result = integrate(&contents, AreaOfInterest::All, FoldExclusion::None);
 
         assert_eq!(
             Integration {
@@ -411,6 +549,7 @@ mod integration_tests {
                 low: 50.0,
                 high: 150.0,
             },
+            FoldExclusion::None,
         );
 
         assert_eq!(
@@ -433,6 +572,7 @@ mod integration_tests {
                 low: 150.0,
                 high: 250.0,
             },
+            FoldExclusion::None,
         );
 
         assert_eq!(
@@ -451,7 +591,7 @@ mod integration_tests {
         let mut contents = make_spike_1d(100.0, 250.0);
         let spike2 = make_spike_1d(110.0, 200.0);
         contents.push(spike2[0]);
-        let result = integrate(&contents, AreaOfInterest::All);
+        let result = integrate(&contents, AreaOfInterest::All, FoldExclusion::None);
         assert_eq!(450.0, result.sum);
         let csbc = (100.0 * 250.0 + 110.0 * 200.0) / 450.0;
         assert_eq!((csbc, 0.0), result.centroid);
@@ -470,7 +610,7 @@ mod integration_tests {
         // single spike - AOI is all:
 
         let contents = make_spike_2d(100.0, 200.0, 400.0);
-        let result = integrate(&contents, AreaOfInterest::All);
+        let result = integrate(&contents, AreaOfInterest::All, FoldExclusion::None);
 
         assert_eq!(
             Integration {
@@ -486,7 +626,11 @@ mod integration_tests {
         // single spike inside contour AOI
 
         let contents = make_spike_2d(100.0, 50.0, 1234.0);
-        let result = integrate(&contents, AreaOfInterest::Twod(make_contour()));
+        let result = integrate(
+            &contents,
+            AreaOfInterest::Twod(make_contour()),
+            FoldExclusion::None,
+        );
 
         assert_eq!(
             Integration {
@@ -502,7 +646,11 @@ mod integration_tests {
         // Single spike outside the AOI:
 
         let contents = make_spike_2d(150.0, 60.0, 1243.0); // Right and above.
-        let result = integrate(&contents, AreaOfInterest::Twod(make_contour()));
+        let result = integrate(
+            &contents,
+            AreaOfInterest::Twod(make_contour()),
+            FoldExclusion::None,
+        );
         assert_eq!(
             Integration {
                 sum: 0.0,
@@ -519,7 +667,7 @@ mod integration_tests {
         let mut contents = make_spike_2d(100.0, 60.0, 100.0);
         let other_spike = make_spike_2d(120.0, 70.0, 150.0);
         contents.push(other_spike[0]);
-        let result = integrate(&contents, AreaOfInterest::All);
+        let result = integrate(&contents, AreaOfInterest::All, FoldExclusion::None);
 
         // X centroid and fwhm:
 
@@ -539,4 +687,150 @@ mod integration_tests {
         assert_eq!(cy, result.centroid.1);
         assert_eq!(fwhmy, result.fwhm.1);
     }
+    #[test]
+    fn folded_1() {
+        // 1-d fold on a 1-d spectrum removes the spike in the slice:
+
+        let mut contents = make_spike_1d(100.0, 250.0);
+        let spike2 = make_spike_1d(200.0, 100.0);
+        contents.push(spike2[0]);
+        let result = integrate(
+            &contents,
+            AreaOfInterest::All,
+            FoldExclusion::Oned {
+                low: 50.0,
+                high: 150.0,
+            },
+        );
+        assert_eq!(
+            Integration {
+                sum: 100.0,
+                centroid: (200.0, 0.0),
+                fwhm: (0.0, 0.0)
+            },
+            result
+        );
+    }
+    #[test]
+    fn folded_2() {
+        // 1-d fold on a 2-d spectrum removes channels with either
+        // coordinate in the slice:
+
+        let mut contents = make_spike_2d(100.0, 300.0, 100.0); // x in slice.
+        contents.push(make_spike_2d(300.0, 100.0, 100.0)[0]); // y in slice.
+        contents.push(make_spike_2d(300.0, 300.0, 150.0)[0]); // Survives.
+        let result = integrate(
+            &contents,
+            AreaOfInterest::All,
+            FoldExclusion::Twod {
+                low: 50.0,
+                high: 150.0,
+            },
+        );
+        assert_eq!(
+            Integration {
+                sum: 150.0,
+                centroid: (300.0, 300.0),
+                fwhm: (0.0, 0.0)
+            },
+            result
+        );
+    }
+    #[test]
+    fn folded_3() {
+        // 2-d fold removes the channels inside the contour:
+
+        let mut contents = make_spike_2d(100.0, 50.0, 100.0); // inside.
+        contents.push(make_spike_2d(200.0, 50.0, 150.0)[0]); // outside
+        let result = integrate(
+            &contents,
+            AreaOfInterest::All,
+            FoldExclusion::Contour(make_contour()),
+        );
+        assert_eq!(
+            Integration {
+                sum: 150.0,
+                centroid: (200.0, 50.0),
+                fwhm: (0.0, 0.0)
+            },
+            result
+        );
+    }
+    #[test]
+    fn contours_1() {
+        // Integrate over the union of two contours - the spike between
+        // them is not included:
+
+        let mut contents = make_spike_2d(100.0, 50.0, 100.0); // In the first
+        contents.push(make_spike_2d(300.0, 50.0, 300.0)[0]); // In the second.
+        contents.push(make_spike_2d(200.0, 50.0, 1000.0)[0]); // In neither.
+        let aoi = AreaOfInterest::Contours(vec![
+            make_contour(),
+            super::test_utilities::make_contour_at(300.0),
+        ]);
+        let result = integrate(&contents, aoi, FoldExclusion::None);
+        assert_eq!(400.0, result.sum);
+        assert_eq!((250.0, 50.0), result.centroid);
+
+        let var: f64 = 100.0 * (100.0 - 250.0_f64).powi(2) + 300.0 * (300.0 - 250.0_f64).powi(2);
+        assert_eq!((GAMMA * sqrt(var) / 400.0, 0.0), result.fwhm);
+    }
+    fn make_props(
+        type_name: &str,
+        points: Vec<(f64, f64)>,
+        polygons: Vec<Vec<(f64, f64)>>,
+    ) -> condition_messages::ConditionProperties {
+        condition_messages::ConditionProperties {
+            cond_name: String::from("aoi"),
+            type_name: String::from(type_name),
+            points,
+            polygons,
+            gates: vec![],
+            parameters: vec![1, 2],
+            disabled: None,
+        }
+    }
+    #[test]
+    fn contour_aoi_1() {
+        // Contours and gamma contours make Twod areas of interest:
+
+        let square = vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        for type_name in ["Contour", "MultiContour"] {
+            match contour_aoi(make_props(type_name, square.clone(), vec![])) {
+                Ok(AreaOfInterest::Twod(c)) => assert!(c.inside(5.0, 5.0)),
+                _ => panic!("{} did not make a Twod AOI", type_name),
+            }
+        }
+    }
+    #[test]
+    fn contour_aoi_2() {
+        // Contour sets make a Contours area of interest:
+
+        let squares = vec![
+            vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)],
+            vec![(20.0, 0.0), (30.0, 0.0), (30.0, 10.0), (20.0, 10.0)],
+        ];
+        let points = squares.iter().flatten().cloned().collect();
+        match contour_aoi(make_props("ContourSet", points, squares)) {
+            Ok(AreaOfInterest::Contours(cs)) => {
+                assert_eq!(2, cs.len());
+                assert!(cs[0].inside(5.0, 5.0));
+                assert!(cs[1].inside(25.0, 5.0));
+            }
+            _ => panic!("ContourSet did not make a Contours AOI"),
+        }
+    }
+    #[test]
+    fn contour_aoi_3() {
+        // Failures - not contour-like and degenerate polygons:
+
+        assert!(contour_aoi(make_props("Cut", vec![(0.0, 0.0), (10.0, 0.0)], vec![])).is_err());
+        assert!(contour_aoi(make_props("ContourSet", vec![], vec![])).is_err());
+        assert!(contour_aoi(make_props(
+            "ContourSet",
+            vec![(0.0, 0.0), (10.0, 0.0)],
+            vec![vec![(0.0, 0.0), (10.0, 0.0)]]
+        ))
+        .is_err());
+    }
 }
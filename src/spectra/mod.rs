@@ -20,6 +20,9 @@
 //!     detector array.  This summary spectrum allows one to easily see channels that
 //!     are failed or, if the elements are gain matched, how well the gain matching
 //!     is done aross the array.
+//!  *  Gamma summary.  A summary spectrum where each x channel has its own
+//!     list of parameters rather than a single parameter.  Each vertical strip is
+//!     the Multi-1d spectrum of its parameters.
 //!  *  Multi-1d.  In SpecTcl, this was called a gamma 1d:  The histogram is a single
 //!     axis histogram, any number of parameters are allowed.  If the applied condition
 //!     is accepted for the event, the spectrum is incremented for each of the parameters
@@ -37,6 +40,15 @@
 //!     histogram with parameters 1,3 on the x axis and 5,7,8 on the y axis, the following
 //!     parameter pairs will be used to increment the spectrum:
 //!     (1,5), (1,7), (1,8), (3,5), (3,7), (3,8).
+//!  *  StripChart - The histogram has one axis, a time parameter and a value
+//!     parameter.  If the applied gate is satisfied and both parameters are present,
+//!     the time channel is incremented by the value.  The time axis scrolls to
+//!     follow times that are outside of it.
+//!  *  Bitmask - The histogram has one axis and a single integer valued parameter.
+//!     If the applied gate is satisfied, the channel of each bit that is set in the
+//!     parameter is incremented.
+//!  *  Snapshot1D - The histogram has one axis and no parameters.  Events never
+//!     increment it;  its contents are set when it is made (e.g. by a projection).
 //!
 
 use super::conditions::*;
@@ -59,6 +71,9 @@ pub use twod::*;
 pub mod summary;
 pub use summary::*;
 
+pub mod gammasummary;
+pub use gammasummary::*;
+
 pub mod multi1d;
 pub use multi1d::*;
 
@@ -71,6 +86,15 @@ pub use twodsum::*;
 pub mod pgamma;
 pub use pgamma::*;
 
+pub mod stripchart;
+pub use stripchart::*;
+
+pub mod bitmask;
+pub use bitmask::*;
+
+pub mod snapshot;
+pub use snapshot::*;
+
 pub mod integration;
 pub use integration::*;
 
@@ -119,6 +143,28 @@ impl SpectrumGate {
     pub fn ungate(&mut self) {
         self.gate = None
     }
+    /// Determine if the applied gate still refers to the condition
+    /// of the same name in the dictionary.  This is the case if the
+    /// condition was edited (which replaces its contents in place).
+    /// It is not the case if the condition was deleted, even if a new
+    /// condition with that name was later created.
+    ///
+    /// ### Returns
+    /// * None - the spectrum is ungated.
+    /// * Some(true) - the gate refers to the dictionary's condition.
+    /// * Some(false) - the gate is dangling.
+    pub fn is_current(&self, dict: &ConditionDictionary) -> Option<bool> {
+        if let Some(g) = &self.gate {
+            if let (Some(applied), Some(current)) = (g.gate.upgrade(), dict.get(&g.condition_name))
+            {
+                Some(Rc::ptr_eq(&applied, current))
+            } else {
+                Some(false)
+            }
+        } else {
+            None
+        }
+    }
     /// Evaluate the gate for an event  The following cases and results
     /// are considered
     /// *   self.gate.is_none() - the spectrum is ungated, true is returned.
@@ -193,10 +239,10 @@ pub type H2DContainer = Rc<RefCell<H2D>>;
 /// Along with the interfaces we need:
 /// Normally clients of spectra use:
 ///
-/// *     handle_event to process an event.  This will
-///       check any applied gate before attempting to call increment
-/// *     gate to gate a spectrum on a condition or replace the gate.
-/// *     ungate to remove the gate condition of a spectrum, if any.
+/// *  handle_event to process an event.  This will
+///    check any applied gate before attempting to call increment
+/// *  gate to gate a spectrum on a condition or replace the gate.
+/// *  ungate to remove the gate condition of a spectrum, if any.
 pub trait Spectrum {
     // Method that handle incrementing/gating
     fn check_gate(&mut self, e: &FlatEvent) -> bool;
@@ -249,6 +295,7 @@ pub trait Spectrum {
 
     fn gate(&mut self, name: &str, dict: &ConditionDictionary) -> Result<(), String>;
     fn ungate(&mut self);
+    fn gate_is_current(&self, dict: &ConditionDictionary) -> Option<bool>;
 
     // manipulate the underlying histogram:
 
@@ -528,9 +575,19 @@ impl SpectrumStorage {
     /// *    Keep lists of spectra that have been deleted (upgrade gave None)
     /// when all this is done, remove those spectra from the associated arrays.
     ///
-    pub fn process_event(&mut self, e: &Event) {
+    /// Spectrum increments are scaled by a weight.  Normally this is 1.0
+    /// but when only a sample of the events is histogrammed, the spectra
+    /// can approximate the counts that would have been seen had all events
+    /// been histogrammed.
+    ///
+    /// ### Parameters
+    /// *  e - the raw event.
+    /// *  weight - amount by which each channel is incremented.
+    ///
+    pub fn process_weighted_event(&mut self, e: &Event, weight: f64) {
         let mut fe = FlatEvent::new();
         fe.load_event(e);
+        fe.set_weight(weight);
 
         for p in e.iter() {
             let id = p.id as usize;
@@ -548,7 +605,7 @@ impl SpectrumStorage {
         Self::prune_spectra(&mut self.other_spectra, &dropped_list);
     }
     /// Delete a spectrum.
-    /// Given how we handle spectra in process_event, we only need to remove
+    /// Given how we handle spectra in process_weighted_event, we only need to remove
     /// the item from the dict.  When the next event that would
     /// attempt to increment the spectrum is is processed it will be pruned from
     /// the appropriate spectrum list.
@@ -564,6 +621,44 @@ impl SpectrumStorage {
             None
         }
     }
+    /// The span of the parameter id space indexed to find the
+    /// spectra to increment.  This is one more than the largest
+    /// parameter id that has (or had) a spectrum that requires it.
+    ///
+    pub fn id_span(&self) -> usize {
+        self.spectra_by_parameter.len()
+    }
+    /// Number of parameter index slots allocated.  This can be
+    /// more than the id_span.
+    ///
+    pub fn id_allocated(&self) -> usize {
+        self.spectra_by_parameter.capacity()
+    }
+    /// Release parameter index storage that is no longer needed:
+    /// *  References to deleted spectra are removed.
+    /// *  Parameters with no more spectra get None.
+    /// *  Trailing None elements are removed and the unused
+    /// allocation is released.
+    ///
+    pub fn compact(&mut self) {
+        for slot in self.spectra_by_parameter.iter_mut() {
+            if let Some(spectra) = slot {
+                spectra.retain(|s| s.strong_count() > 0);
+            }
+            if matches!(slot, Some(spectra) if spectra.is_empty()) {
+                *slot = None;
+            }
+        }
+        self.other_spectra.retain(|s| s.strong_count() > 0);
+        let span = self
+            .spectra_by_parameter
+            .iter()
+            .rposition(|s| s.is_some())
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        self.spectra_by_parameter.truncate(span);
+        self.spectra_by_parameter.shrink_to_fit();
+    }
 }
 
 // Utility function to figure out the axis limits given
@@ -726,6 +821,52 @@ mod gate_tests {
         g.ungate();
         assert!(g.gate.is_none());
     }
+    #[test]
+    fn spgate_current_1() {
+        // Ungated spectra have no current gate:
+
+        let dict = ConditionDictionary::new();
+        let g = SpectrumGate::new();
+        assert!(g.is_current(&dict).is_none());
+    }
+    #[test]
+    fn spgate_current_2() {
+        // Replacing the condition's contents keeps the gate current:
+
+        let mut dict = ConditionDictionary::new();
+        let mut g = SpectrumGate::new();
+        dict.insert(
+            String::from("gate"),
+            Rc::new(RefCell::new(Box::new(True {}))),
+        );
+        g.set_gate("gate", &dict).expect("Couldn't find gate");
+        assert_eq!(Some(true), g.is_current(&dict));
+
+        dict.get("gate").unwrap().replace(Box::new(False {}));
+        assert_eq!(Some(true), g.is_current(&dict));
+    }
+    #[test]
+    fn spgate_current_3() {
+        // Replacing the container in the dictionary leaves the gate dangling
+        // as does deleting it:
+
+        let mut dict = ConditionDictionary::new();
+        let mut g = SpectrumGate::new();
+        dict.insert(
+            String::from("gate"),
+            Rc::new(RefCell::new(Box::new(True {}))),
+        );
+        g.set_gate("gate", &dict).expect("Couldn't find gate");
+
+        dict.insert(
+            String::from("gate"),
+            Rc::new(RefCell::new(Box::new(True {}))),
+        );
+        assert_eq!(Some(false), g.is_current(&dict));
+
+        dict.remove("gate");
+        assert_eq!(Some(false), g.is_current(&dict));
+    }
     // Test for checking the gate
     // - Ungated is always true:
     // - Gated gives the result of the gate.
@@ -1279,7 +1420,7 @@ mod spec_storage_tests {
                 counter += 1.0;
             }
 
-            store.process_event(&event);
+            store.process_weighted_event(&event, 1.0);
         }
 
         // We're just going to be sure each spectrum has 100 counts:
@@ -1389,7 +1530,7 @@ mod spec_storage_tests {
                 counter += 1.0;
             }
 
-            store.process_event(&event);
+            store.process_weighted_event(&event, 1.0);
         }
 
         // We're just going to be sure each spectrum has 100 counts:
@@ -1422,6 +1563,36 @@ mod spec_storage_tests {
         assert_eq!(0.0, sum2);
     }
     #[test]
+    fn prcevent_3() {
+        // Weighted events increment by the weight:
+
+        let pdict = make_params();
+        let spec1 = Oned::new("spec1", "param.1", &pdict, None, None, None)
+            .expect("Failed to make spectrum 1");
+        let mut store = SpectrumStorage::new();
+        store.add(Rc::new(RefCell::new(spec1)));
+
+        let p1 = pdict.lookup("param.1").expect("param.1 should be created");
+        for i in 0..100 {
+            let event = vec![EventParameter::new(p1.get_id(), i as f64)];
+            store.process_weighted_event(&event, 10.0);
+        }
+
+        let s1 = store
+            .get("spec1")
+            .expect("Failed to fetch spec1 from store");
+        let h1 =
+            s1.0.borrow()
+                .get_histogram_1d()
+                .expect("Failed to get 1d histogram");
+
+        let mut sum1 = 0.0;
+        for c in h1.borrow().iter() {
+            sum1 += c.value.get();
+        }
+        assert_eq!(1000.0, sum1);
+    }
+    #[test]
     fn remove_1() {
         // Remove nonexistent spectrum returns None
 
@@ -1464,6 +1635,44 @@ mod spec_storage_tests {
         assert_eq!(String::from("spec1"), s1.unwrap().borrow().get_name());
         assert!(store.remove("spec1").is_none());
     }
+    #[test]
+    fn compact_1() {
+        // Compacting drops the index slots of deleted spectra:
+
+        let pdict = make_params();
+        let low_id = pdict.lookup("param.1").unwrap().get_id() as usize;
+        let high_id = pdict.lookup("param.15").unwrap().get_id() as usize;
+        let spec1 = Oned::new("spec1", "param.1", &pdict, None, None, None)
+            .expect("Failed to make spectrum 1");
+        let spec2 = Oned::new("spec2", "param.15", &pdict, None, None, None)
+            .expect("Failed to make spectrum 2");
+
+        let mut store = SpectrumStorage::new();
+        store.add(Rc::new(RefCell::new(spec1)));
+        store.add(Rc::new(RefCell::new(spec2)));
+        assert_eq!(high_id + 1, store.id_span());
+
+        // Deleting alone leaves the index as is:
+
+        assert!(store.remove("spec2").is_some());
+        assert_eq!(high_id + 1, store.id_span());
+
+        store.compact();
+        assert_eq!(low_id + 1, store.id_span());
+        assert_eq!(low_id + 1, store.id_allocated());
+        assert_eq!(
+            1,
+            store.spectra_by_parameter[low_id].as_ref().unwrap().len()
+        );
+
+        // The remaining spectrum is still incremented:
+
+        store.process_weighted_event(&vec![EventParameter::new(low_id as u32, 100.0)], 1.0);
+        let s1 = store.get("spec1").unwrap();
+        let h1 = s1.0.borrow().get_histogram_1d().unwrap();
+        let sum: f64 = h1.borrow().iter().map(|c| c.value.get()).sum();
+        assert_eq!(1.0, sum);
+    }
 }
 // tests for the trait function to get statistics.
 // Note that this can be tested here using simple 1-d and 2-d histograms
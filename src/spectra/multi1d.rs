@@ -44,7 +44,7 @@ impl Spectrum for Multi1d {
         let mut histogram = self.histogram.borrow_mut();
         for id in ids {
             if let Some(x) = e[id] {
-                histogram.fill(&x);
+                histogram.fill_with(&x, e.weight());
             }
         }
     }
@@ -76,6 +76,9 @@ impl Spectrum for Multi1d {
     fn ungate(&mut self) {
         self.applied_gate.ungate()
     }
+    fn gate_is_current(&self, dict: &ConditionDictionary) -> Option<bool> {
+        self.applied_gate.is_current(dict)
+    }
     fn get_histogram_1d(&self) -> Option<H1DContainer> {
         Some(Rc::clone(&self.histogram))
     }
@@ -52,7 +52,7 @@ impl Spectrum for Multi2d {
             let y = e[pair.1];
             if let Some(x) = x {
                 if let Some(y) = y {
-                    histogram.fill(&(x, y));
+                    histogram.fill_with(&(x, y), e.weight());
                 }
             }
         }
@@ -83,6 +83,9 @@ impl Spectrum for Multi2d {
     fn ungate(&mut self) {
         self.applied_gate.ungate()
     }
+    fn gate_is_current(&self, dict: &ConditionDictionary) -> Option<bool> {
+        self.applied_gate.is_current(dict)
+    }
     fn get_histogram_1d(&self) -> Option<H1DContainer> {
         None
     }
@@ -655,7 +658,7 @@ mod fold_tests {
     use super::test_support::{make_params, test_points};
     use super::*;
     use crate::conditions::cut::{Cut, MultiCut};
-    use crate::conditions::twod::MultiContour;
+    use crate::conditions::twod::{MultiBand, MultiContour, Point};
     use crate::conditions::ConditionDictionary;
     use crate::parameters::{EventParameter, FlatEvent};
     use std::cell::RefCell;
@@ -866,4 +869,37 @@ mod fold_tests {
 
         assert_eq!(vec![(1, 2), (1, 3)], ps);
     }
+    #[test]
+    fn getpair_4() {
+        // Gamma bands fold too - pairs below the band are removed:
+
+        let mut pdict = ParameterDictionary::new();
+        let pnames = make_params(&mut pdict);
+        let mut spec = Multi2d::new("test", pnames, &pdict, None, None, None, None, None, None)
+            .expect("Making spectrum");
+
+        let band = MultiBand::new(
+            &[1, 2, 3],
+            vec![Point::new(0.0, 100.0), Point::new(1000.0, 100.0)],
+        )
+        .expect("Making band");
+        let mut gdict = ConditionDictionary::new();
+        gdict.insert(String::from("gb"), Rc::new(RefCell::new(Box::new(band))));
+
+        spec.fold("gb", &gdict)
+            .expect("Unable to fold multi2ds with multi band.");
+
+        let rawe = vec![
+            EventParameter::new(1, 50.0),
+            EventParameter::new(2, 70.0),
+            EventParameter::new(3, 400.0),
+        ];
+        let mut ev = FlatEvent::new();
+        ev.load_event(&rawe);
+
+        let mut ps = spec.get_parameter_pairs(&ev);
+        ps.sort();
+
+        assert_eq!(vec![(1, 3), (2, 3)], ps);
+    }
 }
@@ -23,7 +23,7 @@ impl Spectrum for Oned {
     }
     fn increment(&mut self, e: &FlatEvent) {
         if let Some(p) = e[self.parameter_id] {
-            self.histogram.borrow_mut().fill(&p);
+            self.histogram.borrow_mut().fill_with(&p, e.weight());
         }
     }
     fn required_parameter(&self) -> Option<u32> {
@@ -55,6 +55,9 @@ impl Spectrum for Oned {
     fn ungate(&mut self) {
         self.applied_gate.ungate()
     }
+    fn gate_is_current(&self, dict: &ConditionDictionary) -> Option<bool> {
+        self.applied_gate.is_current(dict)
+    }
     fn get_histogram_1d(&self) -> Option<H1DContainer> {
         Some(Rc::clone(&self.histogram))
     }
@@ -70,7 +70,7 @@ impl Spectrum for PGamma {
             let y = e[iy];
             if let Some(x) = x {
                 if let Some(y) = y {
-                    histogram.fill(&(x, y));
+                    histogram.fill_with(&(x, y), e.weight());
                 }
             }
         }
@@ -110,6 +110,9 @@ impl Spectrum for PGamma {
     fn ungate(&mut self) {
         self.applied_gate.ungate()
     }
+    fn gate_is_current(&self, dict: &ConditionDictionary) -> Option<bool> {
+        self.applied_gate.is_current(dict)
+    }
     fn get_histogram_1d(&self) -> Option<H1DContainer> {
         None
     }
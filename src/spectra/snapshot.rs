@@ -0,0 +1,121 @@
+use super::*;
+use ndhistogram::value::Sum;
+
+/// Snapshot 1d spectra have an axis but no parameters.  Events never
+/// increment them; their contents are set when they are made.  They
+/// hold data that no parameter can faithfully increment, such as the
+/// strip totals of the Y projection of a Summary spectrum.
+///
+/// *   applied_gate - kept so that, like other snapshots, the spectrum
+///     can be gated on a false condition.  It does not matter to
+///     increments.
+/// *   name is the spectrum name.
+/// *   histogram is the underlying ndhistogram that holds the counts.
+///
+pub struct Snapshot1D {
+    applied_gate: SpectrumGate,
+    name: String,
+    histogram: H1DContainer,
+}
+impl Spectrum for Snapshot1D {
+    fn check_gate(&mut self, e: &FlatEvent) -> bool {
+        self.applied_gate.check(e)
+    }
+    fn increment(&mut self, _e: &FlatEvent) {}
+
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+    fn get_type(&self) -> String {
+        String::from("Snapshot1D")
+    }
+    fn get_xparams(&self) -> Vec<String> {
+        vec![]
+    }
+    fn get_yparams(&self) -> Vec<String> {
+        vec![]
+    }
+    fn get_gate(&self) -> Option<String> {
+        self.applied_gate
+            .gate
+            .as_ref()
+            .map(|g| g.condition_name.clone())
+    }
+    fn gate(&mut self, name: &str, dict: &ConditionDictionary) -> Result<(), String> {
+        self.applied_gate.set_gate(name, dict)
+    }
+    fn ungate(&mut self) {
+        self.applied_gate.ungate()
+    }
+    fn gate_is_current(&self, dict: &ConditionDictionary) -> Option<bool> {
+        self.applied_gate.is_current(dict)
+    }
+    fn get_histogram_1d(&self) -> Option<H1DContainer> {
+        Some(Rc::clone(&self.histogram))
+    }
+    fn get_histogram_2d(&self) -> Option<H2DContainer> {
+        None
+    }
+}
+
+impl Snapshot1D {
+    ///
+    /// Create a new, empty, snapshot spectrum.  The parameters are:
+    ///  *   spectrum_name - name of the spectrum.
+    ///  *   low, high, bins - the axis.  As there are no parameters
+    ///      there are no defaults.
+    ///
+    pub fn new(spectrum_name: &str, low: f64, high: f64, bins: u32) -> Result<Snapshot1D, String> {
+        if low >= high || bins == 0 {
+            return Err(String::from(
+                "Snapshot spectra need low < high and at least one bin",
+            ));
+        }
+        Ok(Snapshot1D {
+            applied_gate: SpectrumGate::new(),
+            name: String::from(spectrum_name),
+            histogram: Rc::new(RefCell::new(ndhistogram!(
+                axis::Uniform::new(bins as usize, low, high);
+                Sum
+            ))),
+        })
+    }
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use super::*;
+
+    #[test]
+    fn new_1() {
+        let s = Snapshot1D::new("test", 0.0, 16.0, 16).expect("Making spectrum");
+        assert!(s.applied_gate.gate.is_none());
+        assert_eq!("test", s.get_name());
+        assert_eq!("Snapshot1D", s.get_type());
+        assert!(s.get_xparams().is_empty());
+        assert!(s.get_yparams().is_empty());
+        assert_eq!(Some((0.0, 16.0, 18)), s.get_xaxis());
+        assert!(s.get_yaxis().is_none());
+        assert!(s.required_parameter().is_none());
+
+        assert!(Snapshot1D::new("test", 16.0, 0.0, 16).is_err());
+        assert!(Snapshot1D::new("test", 0.0, 16.0, 0).is_err());
+    }
+    #[test]
+    fn incr_1() {
+        // Events never change the contents:
+
+        let mut pd = ParameterDictionary::new();
+        pd.add("p").unwrap();
+        let e = vec![EventParameter::new(pd.lookup("p").unwrap().get_id(), 1.0)];
+        let mut fe = FlatEvent::new();
+        fe.load_event(&e);
+
+        let mut s = Snapshot1D::new("test", 0.0, 16.0, 16).unwrap();
+        s.handle_event(&fe);
+        assert_eq!(
+            0.0,
+            s.histogram.borrow().values().map(|v| v.get()).sum::<f64>()
+        );
+    }
+}
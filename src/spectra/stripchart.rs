@@ -0,0 +1,364 @@
+use super::*;
+use ndhistogram::value::Sum;
+///
+/// Strip chart spectra (SpecTcl type S) plot a value against time.
+/// They are defined on two parameters; a time parameter that selects
+/// the channel and a value parameter that is added to that channel.
+/// If both parameters are present in an event and the spectrum's gate
+/// is satisfied, the time channel is incremented by the value.
+///
+/// The time axis scrolls:  If a time is to the right of the axis, the
+/// axis and its contents are shifted left by just enough channels to
+/// make the time the last channel.  Similarly, a time to the left of
+/// the axis (e.g. a new run) shifts the axis right.  Counts that are
+/// shifted off the axis are added to the underflow or overflow channel.
+/// Times that aren't finite or are too far from the axis for its
+/// channels to be told apart (e.g. 1.0e300) are ignored.
+///
+/// *   applied_gate - conditionalizes the increment of the histogram.
+/// *   name is the spectrum name.
+/// *   histogram is the underlying ndhistogram that maintains the counts.
+/// *   time_name, time_id - the time parameter name and id.
+/// *   value_name, value_id - the value parameter name and id.
+///
+pub struct StripChart {
+    applied_gate: SpectrumGate,
+    name: String,
+    histogram: H1DContainer,
+    time_name: String,
+    time_id: u32,
+    value_name: String,
+    value_id: u32,
+}
+
+impl Spectrum for StripChart {
+    fn check_gate(&mut self, e: &FlatEvent) -> bool {
+        self.applied_gate.check(e)
+    }
+    fn increment(&mut self, e: &FlatEvent) {
+        if let (Some(time), Some(value)) = (e[self.time_id], e[self.value_id]) {
+            if self.scroll_to(time) {
+                self.histogram
+                    .borrow_mut()
+                    .fill_with(&time, value * e.weight());
+            }
+        }
+    }
+    fn required_parameter(&self) -> Option<u32> {
+        Some(self.time_id)
+    }
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+    fn get_type(&self) -> String {
+        String::from("StripChart")
+    }
+    // The time parameter is the x parameter and the value the
+    // y parameter:
+
+    fn get_xparams(&self) -> Vec<String> {
+        vec![self.time_name.clone()]
+    }
+    fn get_yparams(&self) -> Vec<String> {
+        vec![self.value_name.clone()]
+    }
+    fn get_gate(&self) -> Option<String> {
+        self.applied_gate
+            .gate
+            .as_ref()
+            .map(|g| g.condition_name.clone())
+    }
+    fn gate(&mut self, name: &str, dict: &ConditionDictionary) -> Result<(), String> {
+        self.applied_gate.set_gate(name, dict)
+    }
+    fn ungate(&mut self) {
+        self.applied_gate.ungate()
+    }
+    fn gate_is_current(&self, dict: &ConditionDictionary) -> Option<bool> {
+        self.applied_gate.is_current(dict)
+    }
+    fn get_histogram_1d(&self) -> Option<H1DContainer> {
+        Some(Rc::clone(&self.histogram))
+    }
+    fn get_histogram_2d(&self) -> Option<H2DContainer> {
+        None
+    }
+}
+
+impl StripChart {
+    ///
+    /// Create a new strip chart spectrum.  The spectrum is initially
+    /// ungated.  The parameters are:
+    ///  *   spectrum_name - name of the spectrum.
+    ///  *   time - name of the time parameter (x axis).
+    ///  *   value - name of the parameter whose value is accumulated.
+    ///  *   pdict - the parameter dictionary used for lookup.
+    ///  *   low, high, bins - the initial time axis.  These default
+    ///      from the time parameter.
+    ///
+    pub fn new(
+        spectrum_name: &str,
+        time: &str,
+        value: &str,
+        pdict: &ParameterDictionary,
+        low: Option<f64>,
+        high: Option<f64>,
+        bins: Option<u32>,
+    ) -> Result<StripChart, String> {
+        let time_param = pdict
+            .lookup(time)
+            .ok_or_else(|| format!("No such parameter: {}", time))?;
+        let value_param = pdict
+            .lookup(value)
+            .ok_or_else(|| format!("No such parameter: {}", value))?;
+        let (low_lim, high_lim, bin_count) = axis_limits(time_param, low, high, bins)?;
+        if bin_count == 0 || low_lim >= high_lim {
+            return Err(String::from(
+                "The time axis must have bins and low must be less than high",
+            ));
+        }
+
+        Ok(StripChart {
+            applied_gate: SpectrumGate::new(),
+            name: String::from(spectrum_name),
+            histogram: Rc::new(RefCell::new(ndhistogram!(
+                axis::Uniform::new(bin_count as usize, low_lim, high_lim);
+                Sum
+            ))),
+            time_name: String::from(time),
+            time_id: time_param.get_id(),
+            value_name: String::from(value),
+            value_id: value_param.get_id(),
+        })
+    }
+    // If necessary, shift the time axis so that it includes time.
+    // The shift is a whole number of channels so the bin boundaries
+    // don't move relative to the data.  Returns false, leaving the
+    // axis alone, if time can't be put on the axis.
+
+    fn scroll_to(&mut self, time: f64) -> bool {
+        if !time.is_finite() {
+            return false;
+        }
+        let axis = self.histogram.borrow().axes().as_tuple().0.clone();
+        let low = *axis.low();
+        let high = *axis.high();
+        if time >= low && time < high {
+            return true;
+        }
+        let bins = axis.num_bins() - 2; // Without under/overflow.
+        let width = (high - low) / bins as f64;
+        let shift = if time >= high {
+            ((time - high) / width).floor() + 1.0
+        } else {
+            -((low - time) / width).ceil()
+        };
+        let new_low = low + shift * width;
+        let new_high = high + shift * width;
+
+        // Far enough away, rounding leaves an axis that doesn't hold time:
+
+        if time < new_low || time >= new_high {
+            return false;
+        }
+        let mut scrolled = ndhistogram!(
+            axis::Uniform::new(bins, new_low, new_high);
+            Sum
+        );
+        let old = self.histogram.borrow();
+        let last = bins as i64 + 1; // Index of the overflow channel.
+
+        // Shifting by the whole axis or more puts all the counts in the
+        // underflow or overflow so larger shifts need not be computed:
+
+        let shift = shift.clamp(-(last as f64), last as f64) as i64;
+        for (index, value) in old.values().enumerate() {
+            let new_index = if index == 0 || index as i64 == last {
+                index as i64
+            } else {
+                (index as i64 - shift).clamp(0, last)
+            };
+            if let Some(sum) = scrolled.value_at_index_mut(new_index as usize) {
+                sum.fill_with(value.get());
+            }
+        }
+        drop(old);
+        *self.histogram.borrow_mut() = scrolled;
+        true
+    }
+}
+
+#[cfg(test)]
+mod stripchart_tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn make_params() -> ParameterDictionary {
+        let mut pd = ParameterDictionary::new();
+        pd.add("time").unwrap();
+        pd.add("value").unwrap();
+        let p = pd.lookup_mut("time").unwrap();
+        p.set_limits(0.0, 100.0);
+        p.set_bins(100);
+        pd
+    }
+    fn make_event(pd: &ParameterDictionary, time: f64, value: f64) -> FlatEvent {
+        let e = vec![
+            EventParameter::new(pd.lookup("time").unwrap().get_id(), time),
+            EventParameter::new(pd.lookup("value").unwrap().get_id(), value),
+        ];
+        let mut fe = FlatEvent::new();
+        fe.load_event(&e);
+        fe
+    }
+    fn contents(s: &StripChart) -> Vec<f64> {
+        s.histogram.borrow().values().map(|v| v.get()).collect()
+    }
+
+    #[test]
+    fn new_1() {
+        // Default axis from the time parameter:
+
+        let pd = make_params();
+        let s = StripChart::new("test", "time", "value", &pd, None, None, None)
+            .expect("Making spectrum");
+        assert!(s.applied_gate.gate.is_none());
+        assert_eq!("test", s.get_name());
+        assert_eq!("StripChart", s.get_type());
+        assert_eq!(vec!["time"], s.get_xparams());
+        assert_eq!(vec!["value"], s.get_yparams());
+        assert_eq!(Some((0.0, 100.0, 102)), s.get_xaxis());
+        assert!(s.get_yaxis().is_none());
+        assert_eq!(Some(1), s.required_parameter());
+    }
+    #[test]
+    fn new_2() {
+        // Failures:
+
+        let pd = make_params();
+        assert!(StripChart::new("test", "none", "value", &pd, None, None, None).is_err());
+        assert!(StripChart::new("test", "time", "none", &pd, None, None, None).is_err());
+        assert!(
+            StripChart::new("test", "time", "value", &pd, Some(10.0), Some(0.0), None).is_err()
+        );
+
+        // Value has no defaults - it's not used for the axis:
+
+        assert!(StripChart::new("test", "value", "time", &pd, None, None, None).is_err());
+    }
+    #[test]
+    fn incr_1() {
+        // In range increments the time channel by the value:
+
+        let pd = make_params();
+        let mut s = StripChart::new("test", "time", "value", &pd, None, None, None).unwrap();
+        s.handle_event(&make_event(&pd, 10.5, 3.0));
+        s.handle_event(&make_event(&pd, 10.2, 2.0));
+        let h = s.histogram.borrow();
+        assert_eq!(5.0, h.value(&10.0).unwrap().get());
+        let total: f64 = h.values().map(|v| v.get()).sum();
+        assert_eq!(5.0, total);
+    }
+    #[test]
+    fn incr_2() {
+        // Time past the axis scrolls left:
+
+        let pd = make_params();
+        let mut s = StripChart::new("test", "time", "value", &pd, None, None, None).unwrap();
+        s.handle_event(&make_event(&pd, 0.5, 1.0));
+        s.handle_event(&make_event(&pd, 50.5, 2.0));
+        s.handle_event(&make_event(&pd, 120.5, 3.0));
+        assert_eq!(Some((21.0, 121.0, 102)), s.get_xaxis());
+
+        let c = contents(&s);
+        assert_eq!(1.0, c[0]); // Shifted off into underflow.
+        assert_eq!(2.0, c[1 + 29]); // 50 is channel 29.
+        assert_eq!(3.0, c[100]); // Last channel.
+        assert_eq!(6.0, c.iter().sum::<f64>());
+    }
+    #[test]
+    fn incr_3() {
+        // Time before the axis scrolls right:
+
+        let pd = make_params();
+        let mut s = StripChart::new("test", "time", "value", &pd, None, None, None).unwrap();
+        s.handle_event(&make_event(&pd, 99.5, 1.0));
+        s.handle_event(&make_event(&pd, 10.5, 2.0));
+        s.handle_event(&make_event(&pd, -5.5, 3.0));
+        assert_eq!(Some((-6.0, 94.0, 102)), s.get_xaxis());
+
+        let c = contents(&s);
+        assert_eq!(1.0, c[101]); // Shifted off into overflow.
+        assert_eq!(2.0, c[1 + 16]);
+        assert_eq!(3.0, c[1]);
+    }
+    #[test]
+    fn incr_4() {
+        // Gated - True increments, False does not:
+
+        let pd = make_params();
+        let mut s = StripChart::new("test", "time", "value", &pd, None, None, None).unwrap();
+        let mut gd = ConditionDictionary::new();
+        gd.insert(
+            String::from("true"),
+            Rc::new(RefCell::new(Box::new(True {}))),
+        );
+        gd.insert(
+            String::from("false"),
+            Rc::new(RefCell::new(Box::new(False {}))),
+        );
+        s.gate("true", &gd).expect("Gating");
+        s.handle_event(&make_event(&pd, 10.5, 3.0));
+        s.gate("false", &gd).expect("Gating");
+        s.handle_event(&make_event(&pd, 200.5, 3.0));
+
+        assert_eq!(Some((0.0, 100.0, 102)), s.get_xaxis());
+        assert_eq!(3.0, contents(&s).iter().sum::<f64>());
+    }
+    #[test]
+    fn incr_5() {
+        // Times far past the axis move all counts to the underflow:
+
+        let pd = make_params();
+        let mut s = StripChart::new("test", "time", "value", &pd, None, None, None).unwrap();
+        s.handle_event(&make_event(&pd, 50.5, 2.0));
+        s.handle_event(&make_event(&pd, 1.0e12, 3.0));
+        assert_eq!(Some((1.0e12 - 99.0, 1.0e12 + 1.0, 102)), s.get_xaxis());
+
+        let c = contents(&s);
+        assert_eq!(2.0, c[0]);
+        assert_eq!(3.0, c[100]);
+        assert_eq!(5.0, c.iter().sum::<f64>());
+
+        // and far before the axis, to the overflow:
+
+        s.handle_event(&make_event(&pd, -1.0e12, 4.0));
+        assert_eq!(Some((-1.0e12, -1.0e12 + 100.0, 102)), s.get_xaxis());
+        let c = contents(&s);
+        assert_eq!(2.0, c[0]);
+        assert_eq!(3.0, c[101]);
+        assert_eq!(4.0, c[1]);
+    }
+    #[test]
+    fn incr_6() {
+        // Times that can't be put on the axis are ignored:
+
+        let pd = make_params();
+        let mut s = StripChart::new("test", "time", "value", &pd, None, None, None).unwrap();
+        s.handle_event(&make_event(&pd, 10.5, 1.0));
+        for time in [
+            1.0e300,
+            -1.0e300,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NAN,
+        ] {
+            s.handle_event(&make_event(&pd, time, 2.0));
+        }
+        assert_eq!(Some((0.0, 100.0, 102)), s.get_xaxis());
+        let c = contents(&s);
+        assert_eq!(1.0, c[11]);
+        assert_eq!(1.0, c.iter().sum::<f64>());
+    }
+}
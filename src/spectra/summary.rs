@@ -49,7 +49,7 @@ impl Spectrum for Summary {
         let mut histogram = self.histogram.borrow_mut();
         for (x, id) in self.param_ids.iter().enumerate() {
             if let Some(y) = e[*id] {
-                histogram.fill(&(x as f64, y));
+                histogram.fill_with(&(x as f64, y), e.weight());
             }
         }
     }
@@ -82,6 +82,9 @@ impl Spectrum for Summary {
     fn ungate(&mut self) {
         self.applied_gate.ungate()
     }
+    fn gate_is_current(&self, dict: &ConditionDictionary) -> Option<bool> {
+        self.applied_gate.is_current(dict)
+    }
     fn get_histogram_1d(&self) -> Option<H1DContainer> {
         None
     }
@@ -37,7 +37,7 @@ impl Spectrum for Twod {
 
         if let Some(x) = x {
             if let Some(y) = y {
-                self.histogram.borrow_mut().fill(&(x, y));
+                self.histogram.borrow_mut().fill_with(&(x, y), e.weight());
             }
         }
     }
@@ -70,6 +70,9 @@ impl Spectrum for Twod {
     fn ungate(&mut self) {
         self.applied_gate.ungate()
     }
+    fn gate_is_current(&self, dict: &ConditionDictionary) -> Option<bool> {
+        self.applied_gate.is_current(dict)
+    }
     fn get_histogram_1d(&self) -> Option<H1DContainer> {
         None
     }
@@ -63,7 +63,7 @@ impl Spectrum for TwodSum {
             let y = e[yid];
             if let Some(x) = x {
                 if let Some(y) = y {
-                    histogram.fill(&(x, y));
+                    histogram.fill_with(&(x, y), e.weight());
                 }
             }
         }
@@ -102,6 +102,9 @@ impl Spectrum for TwodSum {
     fn ungate(&mut self) {
         self.applied_gate.ungate()
     }
+    fn gate_is_current(&self, dict: &ConditionDictionary) -> Option<bool> {
+        self.applied_gate.is_current(dict)
+    }
     fn get_histogram_1d(&self) -> Option<H1DContainer> {
         None
     }
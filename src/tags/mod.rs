@@ -0,0 +1,204 @@
+//!  This module provides the spectrum tag store.  Tags are arbitrary
+//!  strings attached to spectra, e.g. "monitoring", "pid" or
+//!  "det=hagrid".  They group spectra more flexibly than name glob
+//!  patterns can when an experiment's spectra are heterogeneous:
+//!  spectra can be listed, cleared, bound and written by tag via the
+//!  /spectcl/tag REST interface.
+//!
+//!  Tags are kept by spectrum name, outside of the histogramer, and are
+//!  saved in analysis sessions.  Tags of spectra that no longer exist
+//!  are forgotten the next time spectra are looked up by tag.
+//!
+//!  A spectrum's tags are a set so that tagging it twice is harmless.
+//!  The store is locked for each operation as the tag, session and exit
+//!  handlers can run at the same time on Rocket's worker threads.
+//!
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// The tags of one spectrum, sorted.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SpectrumTags {
+    pub spectrum: String,
+    pub tags: Vec<String>,
+}
+
+/// The shared tag store.
+#[derive(Clone, Default)]
+pub struct SharedTagStore {
+    store: Arc<Mutex<HashMap<String, BTreeSet<String>>>>,
+}
+
+impl SharedTagStore {
+    pub fn new() -> SharedTagStore {
+        SharedTagStore {
+            store: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+    /// Add tags to a spectrum.  Tags the spectrum already has are
+    /// ignored.  Fails, adding nothing, if a tag is empty.
+    pub fn add(&self, spectrum: &str, tags: &[String]) -> Result<(), String> {
+        if tags.iter().any(|t| t.is_empty()) {
+            return Err(String::from("Tags cannot be empty"));
+        }
+        self.store
+            .lock()
+            .unwrap()
+            .entry(String::from(spectrum))
+            .or_default()
+            .extend(tags.iter().cloned());
+        Ok(())
+    }
+    /// Remove tags from a spectrum.  If no tags are given, all of the
+    /// spectrum's tags are removed.  Tags the spectrum doesn't have are
+    /// ignored.
+    pub fn remove(&self, spectrum: &str, tags: &[String]) {
+        let mut store = self.store.lock().unwrap();
+        if let Some(existing) = store.get_mut(spectrum) {
+            if tags.is_empty() {
+                existing.clear();
+            } else {
+                for t in tags {
+                    existing.remove(t);
+                }
+            }
+            if existing.is_empty() {
+                store.remove(spectrum);
+            }
+        }
+    }
+    /// The tags of a spectrum, sorted.
+    pub fn tags(&self, spectrum: &str) -> Vec<String> {
+        self.store
+            .lock()
+            .unwrap()
+            .get(spectrum)
+            .map(|t| t.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+    /// The names of the spectra that have all of the tags, sorted.
+    pub fn tagged(&self, tags: &[String]) -> Vec<String> {
+        let mut result: Vec<String> = self
+            .store
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, t)| tags.iter().all(|tag| t.contains(tag)))
+            .map(|(name, _)| name.clone())
+            .collect();
+        result.sort();
+        result
+    }
+    /// The tags of the spectra whose names match a glob pattern, sorted
+    /// by spectrum name.
+    pub fn list(&self, pattern: &str) -> Result<Vec<SpectrumTags>, String> {
+        let pat = Pattern::new(pattern).map_err(|e| String::from(e.msg))?;
+        let mut result: Vec<SpectrumTags> = self
+            .store
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(name, _)| pat.matches(name))
+            .map(|(name, t)| SpectrumTags {
+                spectrum: name.clone(),
+                tags: t.iter().cloned().collect(),
+            })
+            .collect();
+        result.sort_by(|a, b| a.spectrum.cmp(&b.spectrum));
+        Ok(result)
+    }
+    /// Forget the tags of spectra for which exists is false.
+    pub fn retain<F>(&self, exists: F)
+    where
+        F: Fn(&str) -> bool,
+    {
+        self.store.lock().unwrap().retain(|name, _| exists(name));
+    }
+}
+
+#[cfg(test)]
+mod tag_store_tests {
+    use super::*;
+
+    fn tags(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| String::from(*s)).collect()
+    }
+
+    #[test]
+    fn add_1() {
+        let store = SharedTagStore::new();
+        assert!(store.tags("s1").is_empty());
+        store
+            .add("s1", &tags(&["pid", "monitoring"]))
+            .expect("Adding");
+        store
+            .add("s1", &tags(&["pid", "det=hagrid"]))
+            .expect("Adding");
+        assert_eq!(tags(&["det=hagrid", "monitoring", "pid"]), store.tags("s1"));
+    }
+    #[test]
+    fn add_2() {
+        // Empty tags are rejected:
+
+        let store = SharedTagStore::new();
+        assert!(store.add("s1", &tags(&["pid", ""])).is_err());
+        assert!(store.tags("s1").is_empty());
+    }
+    #[test]
+    fn remove_1() {
+        let store = SharedTagStore::new();
+        store.add("s1", &tags(&["a", "b", "c"])).expect("Adding");
+        store.remove("s1", &tags(&["b", "d"]));
+        assert_eq!(tags(&["a", "c"]), store.tags("s1"));
+        store.remove("s1", &[]);
+        assert!(store.tags("s1").is_empty());
+        assert!(store.list("*").unwrap().is_empty());
+
+        store.remove("nosuch", &[]);
+    }
+    #[test]
+    fn tagged_1() {
+        // Spectra must have all the tags:
+
+        let store = SharedTagStore::new();
+        store
+            .add("s2", &tags(&["pid", "det=hagrid"]))
+            .expect("Adding");
+        store.add("s1", &tags(&["pid"])).expect("Adding");
+        store.add("s3", &tags(&["monitoring"])).expect("Adding");
+        assert_eq!(tags(&["s1", "s2"]), store.tagged(&tags(&["pid"])));
+        assert_eq!(tags(&["s2"]), store.tagged(&tags(&["pid", "det=hagrid"])));
+        assert!(store.tagged(&tags(&["none"])).is_empty());
+    }
+    #[test]
+    fn list_1() {
+        let store = SharedTagStore::new();
+        store.add("time", &tags(&["b"])).expect("Adding");
+        store.add("energy", &tags(&["b", "a"])).expect("Adding");
+        assert_eq!(
+            vec![
+                SpectrumTags {
+                    spectrum: String::from("energy"),
+                    tags: tags(&["a", "b"])
+                },
+                SpectrumTags {
+                    spectrum: String::from("time"),
+                    tags: tags(&["b"])
+                }
+            ],
+            store.list("*").unwrap()
+        );
+        assert_eq!(1, store.list("t*").unwrap().len());
+        assert!(store.list("[").is_err());
+    }
+    #[test]
+    fn retain_1() {
+        let store = SharedTagStore::new();
+        store.add("s1", &tags(&["a"])).expect("Adding");
+        store.add("s2", &tags(&["a"])).expect("Adding");
+        store.retain(|name| name == "s2");
+        assert_eq!(tags(&["s2"]), store.tagged(&tags(&["a"])));
+    }
+}
@@ -0,0 +1,147 @@
+//!  This module provides the spectrum template store.  A spectrum
+//!  template is a named, partial spectrum definition: the spectrum
+//!  type, the axis definitions and, optionally, a condition to gate
+//!  the spectrum on.  Spectra are then made from a template by
+//!  supplying only a name and the parameters.  This allows a
+//!  collaboration to standardize the spectra it makes e.g. all of
+//!  the energy spectra of a detector array.
+//!
+//!  Templates are defined, listed and used via the /spectcl/template
+//!  REST interface and are saved in analysis sessions.
+//!
+//!  Templates have no effect on histogramming so the histogramer does
+//!  not know about them.  The template, session and exit REST handlers
+//!  each reach them through the SharedTemplateStore Rocket manages.
+//!
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// A spectrum template.  The spectrum type and axes are in the form
+/// used by /spectcl/spectrum/create, e.g. type "1" and axes
+/// "{0 1024 1024}".
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SpectrumTemplate {
+    pub name: String,
+    pub spectrum_type: String,
+    pub axes: String,
+    pub gate: Option<String>,
+}
+
+/// The shared template store.
+#[derive(Clone, Default)]
+pub struct SharedTemplateStore {
+    store: Arc<Mutex<HashMap<String, SpectrumTemplate>>>,
+}
+
+impl SharedTemplateStore {
+    pub fn new() -> SharedTemplateStore {
+        SharedTemplateStore {
+            store: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+    /// Define a template, replacing any existing template with the
+    /// same name.
+    ///
+    /// ### Returns
+    /// * true if an existing template was replaced.
+    pub fn define(&self, template: SpectrumTemplate) -> bool {
+        self.store
+            .lock()
+            .unwrap()
+            .insert(template.name.clone(), template)
+            .is_some()
+    }
+    /// Delete a template.
+    pub fn delete(&self, name: &str) -> Result<(), String> {
+        match self.store.lock().unwrap().remove(name) {
+            Some(_) => Ok(()),
+            None => Err(format!("No such spectrum template: {}", name)),
+        }
+    }
+    /// Return a copy of a template if it exists.
+    pub fn get(&self, name: &str) -> Option<SpectrumTemplate> {
+        self.store.lock().unwrap().get(name).cloned()
+    }
+    /// Return the templates whose names match a glob pattern sorted by name.
+    pub fn list(&self, pattern: &str) -> Result<Vec<SpectrumTemplate>, String> {
+        let pat = Pattern::new(pattern).map_err(|e| String::from(e.msg))?;
+        let mut result: Vec<SpectrumTemplate> = self
+            .store
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|t| pat.matches(&t.name))
+            .cloned()
+            .collect();
+        result.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod template_store_tests {
+    use super::*;
+
+    fn template(name: &str, gate: Option<&str>) -> SpectrumTemplate {
+        SpectrumTemplate {
+            name: String::from(name),
+            spectrum_type: String::from("1"),
+            axes: String::from("{0 1024 1024}"),
+            gate: gate.map(String::from),
+        }
+    }
+
+    #[test]
+    fn define_1() {
+        // New templates are not replacements:
+
+        let store = SharedTemplateStore::new();
+        assert!(!store.define(template("energy", None)));
+        let t = store.get("energy").expect("Getting energy");
+        assert_eq!(template("energy", None), t);
+    }
+    #[test]
+    fn define_2() {
+        // Defining an existing template replaces it:
+
+        let store = SharedTemplateStore::new();
+        store.define(template("energy", None));
+        assert!(store.define(template("energy", Some("cut"))));
+        assert_eq!(Some(String::from("cut")), store.get("energy").unwrap().gate);
+        assert_eq!(1, store.list("*").unwrap().len());
+    }
+    #[test]
+    fn delete_1() {
+        let store = SharedTemplateStore::new();
+        assert!(store.delete("energy").is_err());
+        store.define(template("energy", None));
+        store.delete("energy").expect("Deleting energy");
+        assert!(store.get("energy").is_none());
+    }
+    #[test]
+    fn list_1() {
+        // Listing is sorted and filtered:
+
+        let store = SharedTemplateStore::new();
+        store.define(template("time", None));
+        store.define(template("energy", None));
+        store.define(template("energy.gated", Some("cut")));
+        let names: Vec<String> = store
+            .list("*")
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        assert_eq!(vec!["energy", "energy.gated", "time"], names);
+        let names: Vec<String> = store
+            .list("energy*")
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        assert_eq!(vec!["energy", "energy.gated"], names);
+        assert!(store.list("[").is_err());
+    }
+}
@@ -7,14 +7,20 @@
 #[cfg(test)]
 
 pub mod rest_common {
+    use crate::autosave;
+    use crate::crash;
     use crate::histogramer;
+    use crate::jobs;
     use crate::messaging;
     use crate::processing;
     use crate::rest::{
-        MirrorState, SharedBinderChannel, SharedHistogramChannel, SharedProcessingApi,
+        limits, names, MirrorState, SharedBinderChannel, SharedHistogramChannel,
+        SharedProcessingApi,
     };
     use crate::sharedmem::binder;
+    use crate::simulation;
     use crate::trace;
+    use crate::treevariables;
 
     use rocket;
     use rocket::Build;
@@ -32,6 +38,7 @@ pub mod rest_common {
     ///
     pub fn setup() -> Rocket<Build> {
         let tracedb = trace::SharedTraceStore::new();
+        let treevariables = treevariables::SharedTreeVariableStore::new();
         let (_, hg_sender) = histogramer::start_server(tracedb.clone());
         let (binder_req, _jh) = binder::start_server(&hg_sender, 32 * 1024 * 1024, &tracedb);
 
@@ -45,8 +52,17 @@ pub mod rest_common {
             .manage(Mutex::new(binder_req))
             .manage(Mutex::new(processing::ProcessingApi::new(
                 &hg_sender.clone(),
+                &tracedb,
+                &treevariables,
             )))
             .manage(tracedb.clone())
+            .manage(treevariables)
+            .manage(limits::RestLimits::default())
+            .manage(names::NamePolicy::default())
+            .manage(simulation::Simulator::new(true))
+            .manage(crash::SharedCrashStore::new())
+            .manage(autosave::AutoSaver::new())
+            .manage(jobs::SharedJobStore::new())
     }
     /// Teardown the infrastructure that was created by the
     /// setup function:
@@ -18,13 +18,55 @@
 //! with when they were declared and the prune thread
 //! will go over all stored traces removing the expired ones.
 //! this prevents the trace store from growing without bounds.
+//! Clients that stop fetching without saying they're done are
+//! eventually removed entirely (see STALE_CLIENT_LIFETIMES).
 //!
+//! Run state changes seen in the data are also retained in a bounded
+//! run log, independent of any client, so that clients that were not
+//! tracing when the run boundaries went by can still find them.
 //!
+//! Clients can also register interest in only some trace categories
+//! and/or only traces on objects whose names match glob patterns (see
+//! TraceFilter).  Traces the client is not interested in are never
+//! queued for it, reducing both storage and trace traffic.
+//!
+//! Similarly, a bounded history of the gates applied to and removed from
+//! each spectrum is retained so that it's possible to find out whether, when
+//! and by whom a spectrum's gate was changed.
+//!
+use glob::Pattern;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time;
 
+/// Maximum number of entries retained in the run log.  When exceeded,
+/// the oldest entries are discarded.
+pub const MAX_RUN_LOG_ENTRIES: usize = 1000;
+/// Maximum number of gate application history entries retained for
+/// each spectrum.  When exceeded, the oldest entries are discarded.
+pub const MAX_APPLICATION_HISTORY_ENTRIES: usize = 100;
+/// A client that has not fetched its traces (or set its filter) for
+/// this many trace lifetimes is assumed to have gone away and is
+/// removed by the prune.
+pub const STALE_CLIENT_LIFETIMES: u32 = 10;
+/// Clients are never considered stale sooner than this after their
+/// last fetch, no matter how short their trace lifetime.
+pub const MIN_STALE_CLIENT_AGE: time::Duration = time::Duration::from_secs(60);
+
+/// The trace categories.  These are the names of the fields of
+/// a trace fetch response in which traces in each category are returned.
+pub const TRACE_CATEGORIES: [&str; 8] = [
+    "parameter",
+    "spectrum",
+    "gate",
+    "binding",
+    "run",
+    "treevariable",
+    "fold",
+    "source",
+];
+
 /// The various types of traces
 /// If the payload for an enumerated type
 /// is just a string, that's the name of the affected
@@ -48,6 +90,148 @@ pub enum TraceEvent {
         name: String,
         binding_id: usize,
     },
+    /// A run state change item was seen in the data.
+    /// change is one of "begin", "end", "pause" or "resume".
+    RunStateChange {
+        change: String,
+        run: u32,
+        title: String,
+    },
+    /// A tree variable's value or units were modified,
+    /// either by the data or by a client.
+    TreeVariableChanged(String),
+    /// A condition was applied to a spectrum as a fold.
+    FoldApplied {
+        spectrum: String,
+        condition: String,
+    },
+    /// The fold was removed from the named spectrum.
+    FoldRemoved(String),
+    /// A data source was attached for analysis.  In a chained
+    /// replay, this fires for each file as analysis moves on to it.
+    SourceAttached(String),
+    /// The named data source was detached.
+    SourceDetached(String),
+}
+impl TraceEvent {
+    /// The category of the trace; one of TRACE_CATEGORIES.
+    pub fn category(&self) -> &'static str {
+        match self {
+            TraceEvent::NewParameter(_) | TraceEvent::ParameterModified(_) => "parameter",
+            TraceEvent::SpectrumCreated(_) | TraceEvent::SpectrumDeleted(_) => "spectrum",
+            TraceEvent::ConditionCreated(_)
+            | TraceEvent::ConditionModified(_)
+            | TraceEvent::ConditionDeleted(_) => "gate",
+            TraceEvent::SpectrumBound { .. } | TraceEvent::SpectrumUnbound { .. } => "binding",
+            TraceEvent::RunStateChange { .. } => "run",
+            TraceEvent::TreeVariableChanged(_) => "treevariable",
+            TraceEvent::FoldApplied { .. } | TraceEvent::FoldRemoved(_) => "fold",
+            TraceEvent::SourceAttached(_) | TraceEvent::SourceDetached(_) => "source",
+        }
+    }
+    /// The name of the object the trace is about.  For fold traces this is
+    /// the spectrum.  Run state changes are not about a named object
+    /// so this is None.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            TraceEvent::NewParameter(name)
+            | TraceEvent::ParameterModified(name)
+            | TraceEvent::SpectrumCreated(name)
+            | TraceEvent::SpectrumDeleted(name)
+            | TraceEvent::ConditionCreated(name)
+            | TraceEvent::ConditionModified(name)
+            | TraceEvent::ConditionDeleted(name)
+            | TraceEvent::TreeVariableChanged(name)
+            | TraceEvent::FoldRemoved(name)
+            | TraceEvent::SourceAttached(name)
+            | TraceEvent::SourceDetached(name) => Some(name),
+            TraceEvent::SpectrumBound { name, .. } | TraceEvent::SpectrumUnbound { name, .. } => {
+                Some(name)
+            }
+            TraceEvent::FoldApplied { spectrum, .. } => Some(spectrum),
+            TraceEvent::RunStateChange { .. } => None,
+        }
+    }
+}
+/// Selects the traces a client is interested in:
+///
+/// * categories - the trace categories wanted.  Empty means all of them.
+/// * patterns - glob patterns the name of the traced object must match
+/// at least one of.  Empty means any name.  Traces that are not about
+/// a named object (run state changes) are not subject to the patterns.
+///
+#[derive(Clone, Default)]
+pub struct TraceFilter {
+    categories: Vec<String>,
+    patterns: Vec<Pattern>,
+}
+impl TraceFilter {
+    /// Make a filter.
+    ///
+    /// ### Parameters
+    /// * categories - names of the categories wanted (see TRACE_CATEGORIES).
+    /// * patterns - glob patterns for the names of the traced objects.
+    ///
+    /// ### Returns
+    /// * Ok(TraceFilter) on success.
+    /// * Err(message) if a category is unknown or a pattern is invalid.
+    ///
+    pub fn new(categories: &[String], patterns: &[String]) -> Result<TraceFilter, String> {
+        for category in categories {
+            if !TRACE_CATEGORIES.contains(&category.as_str()) {
+                return Err(format!(
+                    "Unknown trace category '{}' must be one of {}",
+                    category,
+                    TRACE_CATEGORIES.join(", ")
+                ));
+            }
+        }
+        let mut compiled = Vec::new();
+        for pattern in patterns {
+            compiled.push(
+                Pattern::new(pattern)
+                    .map_err(|e| format!("Invalid pattern '{}': {}", pattern, e.msg))?,
+            );
+        }
+        Ok(TraceFilter {
+            categories: categories.to_vec(),
+            patterns: compiled,
+        })
+    }
+    /// Returns true if the event passes the filter.
+    pub fn accepts(&self, event: &TraceEvent) -> bool {
+        if !self.categories.is_empty() && !self.categories.iter().any(|c| c == event.category()) {
+            return false;
+        }
+        match event.name() {
+            Some(name) => self.patterns.is_empty() || self.patterns.iter().any(|p| p.matches(name)),
+            None => true,
+        }
+    }
+}
+/// An entry in the run log:
+///
+#[derive(Clone)]
+pub struct RunLogEntry {
+    pub stamp: time::SystemTime,
+    pub change: String,
+    pub run: u32,
+    pub title: String,
+}
+/// An entry in a spectrum's gate application history:
+///
+/// * stamp - when the gate was applied or removed.
+/// * gate - the gate that was applied, None if the gate was removed.
+/// * who - who asked for the change (e.g. the ReST client's address).
+/// * run - the run being analyzed at the time according to the run log,
+/// None if no run was active.
+///
+#[derive(Clone)]
+pub struct ApplicationLogEntry {
+    pub stamp: time::SystemTime,
+    pub gate: Option<String>,
+    pub who: String,
+    pub run: Option<u32>,
 }
 /// Traces are timestamped with when they are logged.
 /// as descsribed above, this enables aging:
@@ -68,18 +252,35 @@ impl StampedTraceEvent {
 /// * A client token.
 /// * A trace lifetime
 /// * A time orderd vector of traces.
+/// * A filter that selects the traces that are queued.
+/// * When the client last fetched its traces, used to detect stale
+///   clients.
 ///
 pub struct ClientTraces {
     trace_lifetime: time::Duration,
     trace_store: Vec<StampedTraceEvent>,
+    filter: TraceFilter,
+    last_access: time::Instant,
 }
 impl ClientTraces {
     pub fn new(lifetime: time::Duration) -> ClientTraces {
         ClientTraces {
             trace_lifetime: lifetime,
             trace_store: Vec::new(),
+            filter: TraceFilter::default(),
+            last_access: time::Instant::now(),
         }
     }
+    /// True if the client has not been heard from for long enough
+    /// that it's assumed to have gone away.
+    pub fn is_stale(&self, now: time::Instant) -> bool {
+        let age = now.saturating_duration_since(self.last_access);
+        let limit = self
+            .trace_lifetime
+            .saturating_mul(STALE_CLIENT_LIFETIMES)
+            .max(MIN_STALE_CLIENT_AGE);
+        age > limit
+    }
 }
 
 /// This struct provides the trace store.  We need
@@ -87,11 +288,15 @@ impl ClientTraces {
 /// * next_client - the token to be given to the next
 /// client.
 /// * A hash of ClientTraces.
+/// * The run log.
+/// * The gate application history of each spectrum.
 ///
 pub struct TraceStore {
     next_client: u64,
     stop_prune_thread: bool,
     client_traces: HashMap<u64, ClientTraces>,
+    run_log: Vec<RunLogEntry>,
+    application_history: HashMap<String, Vec<ApplicationLogEntry>>,
 }
 
 /// A shared TraceStore just holds a TraceStore in an Arc/Mutex
@@ -109,7 +314,9 @@ pub struct SharedTraceStore {
 impl SharedTraceStore {
     fn add_to_all(&self, stamped_event: StampedTraceEvent) {
         for (_, v) in self.store.lock().unwrap().client_traces.iter_mut() {
-            v.trace_store.push(stamped_event.clone());
+            if v.filter.accepts(&stamped_event.event) {
+                v.trace_store.push(stamped_event.clone());
+            }
         }
     }
     //
@@ -119,6 +326,8 @@ impl SharedTraceStore {
                 next_client: 0,
                 stop_prune_thread: false,
                 client_traces: HashMap::new(),
+                run_log: Vec::new(),
+                application_history: HashMap::new(),
             })),
         }
     }
@@ -140,14 +349,28 @@ impl SharedTraceStore {
 
         result
     }
+    /// Set the filter that selects the traces queued for a client.
+    /// Traces already queued are not affected.
+    ///
+    pub fn set_filter(&self, token: u64, filter: TraceFilter) -> Result<(), String> {
+        match self.store.lock().unwrap().client_traces.get_mut(&token) {
+            Some(client) => {
+                client.filter = filter;
+                client.last_access = time::Instant::now();
+                Ok(())
+            }
+            None => Err(String::from("No such client token")),
+        }
+    }
     /// Prune the client trace stores.
     /// for each client, we only retain those elements for which
     /// their timestamp is newer than the lifetime specified by
-    /// that client.
+    /// that client.  Stale clients are removed.
     ///
     pub fn prune(&self) {
         let mut store = self.store.lock().unwrap();
         let now = time::Instant::now();
+        store.client_traces.retain(|_, v| !v.is_stale(now));
         for (_, v) in store.client_traces.iter_mut() {
             v.trace_store.retain(|x| {
                 let age = now.duration_since(x.stamp);
@@ -159,6 +382,9 @@ impl SharedTraceStore {
     ///
 
     pub fn add_event(&self, event: TraceEvent) {
+        if let TraceEvent::RunStateChange { change, run, title } = &event {
+            self.log_run_change(change, *run, title);
+        }
         let stamped_event = StampedTraceEvent {
             stamp: time::Instant::now(),
             event,
@@ -166,6 +392,83 @@ impl SharedTraceStore {
         self.add_to_all(stamped_event);
     }
 
+    // Add an entry to the run log, trimming the oldest if needed:
+
+    fn log_run_change(&self, change: &str, run: u32, title: &str) {
+        let mut store = self.store.lock().unwrap();
+        store.run_log.push(RunLogEntry {
+            stamp: time::SystemTime::now(),
+            change: String::from(change),
+            run,
+            title: String::from(title),
+        });
+        if store.run_log.len() > MAX_RUN_LOG_ENTRIES {
+            let excess = store.run_log.len() - MAX_RUN_LOG_ENTRIES;
+            store.run_log.drain(0..excess);
+        }
+    }
+    /// Return a copy of the run log, oldest entry first.
+    ///
+    pub fn get_run_log(&self) -> Vec<RunLogEntry> {
+        self.store.lock().unwrap().run_log.clone()
+    }
+    /// Record the application or removal of a spectrum's gate.
+    ///
+    /// ### Parameters
+    /// * spectrum - name of the spectrum.
+    /// * gate - name of the gate applied, None if the gate was removed.
+    /// * who - who asked for the change.
+    ///
+    pub fn log_application(&self, spectrum: &str, gate: Option<&str>, who: &str) {
+        let mut store = self.store.lock().unwrap();
+        // The run is active unless the most recent state change ended it:
+
+        let run = store
+            .run_log
+            .last()
+            .filter(|e| e.change != "end")
+            .map(|e| e.run);
+        let history = store
+            .application_history
+            .entry(String::from(spectrum))
+            .or_default();
+        history.push(ApplicationLogEntry {
+            stamp: time::SystemTime::now(),
+            gate: gate.map(String::from),
+            who: String::from(who),
+            run,
+        });
+        if history.len() > MAX_APPLICATION_HISTORY_ENTRIES {
+            let excess = history.len() - MAX_APPLICATION_HISTORY_ENTRIES;
+            history.drain(0..excess);
+        }
+    }
+    /// Return the gate application histories, oldest entry first,
+    /// of the spectra whose names match a glob pattern.  Spectra that were
+    /// deleted keep their history.
+    ///
+    /// ### Returns
+    /// * Ok(Vec<(spectrum name, history)>) sorted by spectrum name.
+    /// * Err(message) if the pattern is invalid.
+    ///
+    pub fn get_application_history(
+        &self,
+        pattern: &str,
+    ) -> Result<Vec<(String, Vec<ApplicationLogEntry>)>, String> {
+        let pattern = Pattern::new(pattern).map_err(|e| String::from(e.msg))?;
+        let mut result: Vec<(String, Vec<ApplicationLogEntry>)> = self
+            .store
+            .lock()
+            .unwrap()
+            .application_history
+            .iter()
+            .filter(|(name, _)| pattern.matches(name))
+            .map(|(name, history)| (name.clone(), history.clone()))
+            .collect();
+        result.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(result)
+    }
+
     /// Given a client token,
     /// Return its traces and clear them:
 
@@ -176,6 +479,7 @@ impl SharedTraceStore {
             let traces = store.client_traces.get_mut(&token).unwrap();
             let result = traces.trace_store.clone();
             traces.trace_store.clear();
+            traces.last_access = time::Instant::now();
             Ok(result)
         } else {
             Err(String::from("No such client token"))
@@ -386,6 +690,46 @@ mod trace_store_tests {
         });
     }
     #[test]
+    fn ts_prune_2() {
+        // Clients that haven't fetched for long enough are removed:
+
+        let store = SharedTraceStore::new();
+        let stale = store.new_client(time::Duration::from_secs(10));
+        let fresh = store.new_client(time::Duration::from_secs(10));
+        let long_lived = store.new_client(time::Duration::from_secs(100));
+        let old = time::Instant::now()
+            .checked_sub(time::Duration::from_secs(500))
+            .unwrap();
+        {
+            let mut locked_store = store.store.lock().unwrap();
+            for token in [stale, long_lived] {
+                locked_store
+                    .client_traces
+                    .get_mut(&token)
+                    .unwrap()
+                    .last_access = old;
+            }
+        }
+        store.prune();
+
+        assert!(store.get_traces(stale).is_err());
+        assert!(store.get_traces(fresh).is_ok());
+        assert!(store.get_traces(long_lived).is_ok());
+    }
+    #[test]
+    fn stale_1() {
+        let client = ClientTraces::new(time::Duration::from_secs(1));
+        let now = time::Instant::now();
+        assert!(!client.is_stale(now));
+        assert!(!client.is_stale(now + time::Duration::from_secs(30)));
+        assert!(client.is_stale(now + MIN_STALE_CLIENT_AGE + time::Duration::from_secs(1)));
+
+        let client = ClientTraces::new(time::Duration::from_secs(60));
+        let now = time::Instant::now();
+        assert!(!client.is_stale(now + time::Duration::from_secs(500)));
+        assert!(client.is_stale(now + time::Duration::from_secs(601)));
+    }
+    #[test]
     fn ts_get_1() {
         // get traces from a bad token is an error:
 
@@ -474,6 +818,61 @@ mod trace_store_tests {
         )
     }
     #[test]
+    fn filter_1() {
+        // Bad categories and patterns are errors:
+
+        assert!(TraceFilter::new(&[String::from("junk")], &[]).is_err());
+        assert!(TraceFilter::new(&[], &[String::from("[")]).is_err());
+
+        let filter = TraceFilter::new(&[String::from("binding")], &[String::from("s.*")])
+            .expect("Making filter");
+        assert!(filter.accepts(&TraceEvent::SpectrumBound {
+            name: String::from("s.1"),
+            binding_id: 1
+        }));
+        assert!(!filter.accepts(&TraceEvent::SpectrumBound {
+            name: String::from("t.1"),
+            binding_id: 1
+        }));
+        assert!(!filter.accepts(&TraceEvent::SpectrumCreated(String::from("s.1"))));
+
+        // Run state changes are not subject to patterns:
+
+        let filter = TraceFilter::new(&[], &[String::from("s.*")]).expect("Making filter");
+        assert!(filter.accepts(&TraceEvent::RunStateChange {
+            change: String::from("begin"),
+            run: 1,
+            title: String::from("")
+        }));
+        assert!(filter.accepts(&TraceEvent::FoldApplied {
+            spectrum: String::from("s.1"),
+            condition: String::from("c")
+        }));
+    }
+    #[test]
+    fn filter_2() {
+        // Only the traces a client wants are queued for it:
+
+        let store = SharedTraceStore::new();
+        let tok1 = store.new_client(time::Duration::from_secs(10));
+        let tok2 = store.new_client(time::Duration::from_secs(10));
+        store
+            .set_filter(
+                tok2,
+                TraceFilter::new(&[String::from("spectrum")], &[]).expect("Making filter"),
+            )
+            .expect("Setting filter");
+        assert!(store.set_filter(1234, TraceFilter::default()).is_err());
+
+        store.add_event(TraceEvent::NewParameter(String::from("p1")));
+        store.add_event(TraceEvent::SpectrumCreated(String::from("aspec")));
+
+        assert_eq!(2, store.get_traces(tok1).expect("tok1 traces").len());
+        let traces = store.get_traces(tok2).expect("tok2 traces");
+        assert_eq!(1, traces.len());
+        assert!(matches!(&traces[0].event, TraceEvent::SpectrumCreated(s) if s == "aspec"));
+    }
+    #[test]
     fn ts_thread_start_stop() {
         // Start and stop prune thread - we're not going to test that it works
         // as the asynchronism makes that really hard.
@@ -484,4 +883,99 @@ mod trace_store_tests {
         store.stop_prune();
         assert!(jh.join().is_ok());
     }
+    #[test]
+    fn run_log_1() {
+        // Run state changes are logged even without clients:
+
+        let store = SharedTraceStore::new();
+        store.add_event(TraceEvent::RunStateChange {
+            change: String::from("begin"),
+            run: 12,
+            title: String::from("A title"),
+        });
+        store.add_event(TraceEvent::NewParameter(String::from("george")));
+
+        let log = store.get_run_log();
+        assert_eq!(1, log.len());
+        assert_eq!("begin", log[0].change);
+        assert_eq!(12, log[0].run);
+        assert_eq!("A title", log[0].title);
+    }
+    #[test]
+    fn run_log_2() {
+        // The run log is bounded:
+
+        let store = SharedTraceStore::new();
+        for i in 0..(MAX_RUN_LOG_ENTRIES + 10) {
+            store.add_event(TraceEvent::RunStateChange {
+                change: String::from("end"),
+                run: i as u32,
+                title: String::from(""),
+            });
+        }
+        let log = store.get_run_log();
+        assert_eq!(MAX_RUN_LOG_ENTRIES, log.len());
+        assert_eq!(10, log[0].run);
+    }
+    #[test]
+    fn application_history_1() {
+        // Applications and removals are recorded per spectrum along with
+        // the active run:
+
+        let store = SharedTraceStore::new();
+        store.log_application("s1", Some("g1"), "127.0.0.1");
+        store.add_event(TraceEvent::RunStateChange {
+            change: String::from("begin"),
+            run: 3,
+            title: String::from(""),
+        });
+        store.log_application("s2", Some("g2"), "127.0.0.1");
+        store.log_application("s1", None, "somehost");
+        store.add_event(TraceEvent::RunStateChange {
+            change: String::from("end"),
+            run: 3,
+            title: String::from(""),
+        });
+        store.log_application("s1", Some("g3"), "127.0.0.1");
+
+        let history = store.get_application_history("*").expect("History");
+        assert_eq!(2, history.len());
+        assert_eq!("s1", history[0].0);
+        assert_eq!("s2", history[1].0);
+
+        let s1 = &history[0].1;
+        assert_eq!(3, s1.len());
+        assert_eq!(Some(String::from("g1")), s1[0].gate);
+        assert_eq!("127.0.0.1", s1[0].who);
+        assert!(s1[0].run.is_none());
+        assert!(s1[1].gate.is_none());
+        assert_eq!("somehost", s1[1].who);
+        assert_eq!(Some(3), s1[1].run);
+        assert_eq!(Some(String::from("g3")), s1[2].gate);
+        assert!(s1[2].run.is_none());
+
+        assert_eq!(Some(3), history[1].1[0].run);
+
+        // Patterns select spectra:
+
+        let history = store.get_application_history("s2").expect("History");
+        assert_eq!(1, history.len());
+        assert_eq!("s2", history[0].0);
+        assert!(store.get_application_history("[").is_err());
+    }
+    #[test]
+    fn application_history_2() {
+        // Each spectrum's history is bounded:
+
+        let store = SharedTraceStore::new();
+        for i in 0..(MAX_APPLICATION_HISTORY_ENTRIES + 5) {
+            store.log_application("s", Some(&format!("g{}", i)), "me");
+        }
+        store.log_application("t", None, "me");
+
+        let history = store.get_application_history("*").expect("History");
+        assert_eq!(MAX_APPLICATION_HISTORY_ENTRIES, history[0].1.len());
+        assert_eq!(Some(String::from("g5")), history[0].1[0].gate);
+        assert_eq!(1, history[1].1.len());
+    }
 }
@@ -0,0 +1,219 @@
+//!  This module provides the tree variable store.  Tree variables
+//!  are named steering values (e.g. calibration constants) with
+//!  units of measure.  The FRIB analysis pipeline records the values
+//!  of its tree variables in VARIABLE_VALUES ring items.  As data are
+//!  replayed, the processing thread enters those values here, where they
+//!  can be seen and modified via the /spectcl/treevariable REST interface.
+//!
+//!  The processing thread writes values from the data into the store
+//!  while REST handlers read and modify them, so the clones of a
+//!  SharedTreeVariableStore all refer to one mutex protected map.
+//!
+//!  Each variable has a changed flag.  As in SpecTcl, this is a latched
+//!  flag that is set when the variable is modified by a user (not when
+//!  its value comes from the data) so that only modified variables need
+//!  be saved.
+//!
+use glob::Pattern;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// A tree variable:
+#[derive(Clone, Debug, PartialEq)]
+pub struct TreeVariable {
+    name: String,
+    value: f64,
+    units: String,
+    changed: bool,
+}
+impl TreeVariable {
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+    pub fn units(&self) -> String {
+        self.units.clone()
+    }
+    pub fn changed(&self) -> bool {
+        self.changed
+    }
+}
+
+/// The shared tree variable store.
+#[derive(Clone, Default)]
+pub struct SharedTreeVariableStore {
+    store: Arc<Mutex<HashMap<String, TreeVariable>>>,
+}
+
+impl SharedTreeVariableStore {
+    pub fn new() -> SharedTreeVariableStore {
+        SharedTreeVariableStore {
+            store: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+    /// Enter a value that came from the data.  If the variable does not
+    /// exist it is created.  The changed flag is not modified.
+    ///
+    /// ### Returns
+    /// * true if the variable is new or its value or units differ from
+    /// what was stored.
+    pub fn update(&self, name: &str, value: f64, units: &str) -> bool {
+        let mut store = self.store.lock().unwrap();
+        if let Some(v) = store.get_mut(name) {
+            let modified = v.value != value || v.units != units;
+            v.value = value;
+            v.units = String::from(units);
+            modified
+        } else {
+            store.insert(
+                String::from(name),
+                TreeVariable {
+                    name: String::from(name),
+                    value,
+                    units: String::from(units),
+                    changed: false,
+                },
+            );
+            true
+        }
+    }
+    /// Set the value and units of an existing variable.  This is a user
+    /// modification so the changed flag is set.
+    pub fn set(&self, name: &str, value: f64, units: &str) -> Result<(), String> {
+        if let Some(v) = self.store.lock().unwrap().get_mut(name) {
+            v.value = value;
+            v.units = String::from(units);
+            v.changed = true;
+            Ok(())
+        } else {
+            Err(format!("No such tree variable: {}", name))
+        }
+    }
+    /// Return a copy of a variable if it exists.
+    pub fn get(&self, name: &str) -> Option<TreeVariable> {
+        self.store.lock().unwrap().get(name).cloned()
+    }
+    /// Return the variables whose names match a glob pattern sorted by name.
+    pub fn list(&self, pattern: &str) -> Result<Vec<TreeVariable>, String> {
+        let pat = Pattern::new(pattern).map_err(|e| String::from(e.msg))?;
+        let mut result: Vec<TreeVariable> = self
+            .store
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|v| pat.matches(&v.name))
+            .cloned()
+            .collect();
+        result.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(result)
+    }
+    /// Return the state of a variable's changed flag.
+    pub fn is_changed(&self, name: &str) -> Result<bool, String> {
+        if let Some(v) = self.store.lock().unwrap().get(name) {
+            Ok(v.changed)
+        } else {
+            Err(format!("No such tree variable: {}", name))
+        }
+    }
+    /// Set a variable's changed flag.
+    pub fn set_changed(&self, name: &str) -> Result<(), String> {
+        if let Some(v) = self.store.lock().unwrap().get_mut(name) {
+            v.changed = true;
+            Ok(())
+        } else {
+            Err(format!("No such tree variable: {}", name))
+        }
+    }
+}
+
+#[cfg(test)]
+mod treevar_store_tests {
+    use super::*;
+
+    #[test]
+    fn update_1() {
+        // New variables are made and report a modification:
+
+        let store = SharedTreeVariableStore::new();
+        assert!(store.update("slope", 1.5, "keV/channel"));
+        let vars = store.list("*").unwrap();
+        assert_eq!(1, vars.len());
+        assert_eq!("slope", vars[0].name());
+        assert_eq!(1.5, vars[0].value());
+        assert_eq!("keV/channel", vars[0].units());
+        assert!(!vars[0].changed());
+    }
+    #[test]
+    fn update_2() {
+        // Only real changes are reported:
+
+        let store = SharedTreeVariableStore::new();
+        store.update("slope", 1.5, "keV/channel");
+        assert!(!store.update("slope", 1.5, "keV/channel"));
+        assert!(store.update("slope", 2.0, "keV/channel"));
+        assert!(store.update("slope", 2.0, "MeV/channel"));
+        assert!(!store.is_changed("slope").unwrap());
+    }
+    #[test]
+    fn set_1() {
+        let store = SharedTreeVariableStore::new();
+        assert!(store.set("slope", 1.0, "").is_err());
+        store.update("slope", 1.5, "keV/channel");
+        store.set("slope", 2.5, "MeV/channel").unwrap();
+
+        let vars = store.list("slope").unwrap();
+        assert_eq!(2.5, vars[0].value());
+        assert_eq!("MeV/channel", vars[0].units());
+        assert!(vars[0].changed());
+    }
+    #[test]
+    fn get_1() {
+        let store = SharedTreeVariableStore::new();
+        assert!(store.get("slope").is_none());
+        store.update("slope", 1.5, "keV/channel");
+        let v = store.get("slope").expect("Getting slope");
+        assert_eq!("slope", v.name());
+        assert_eq!(1.5, v.value());
+        assert_eq!("keV/channel", v.units());
+    }
+    #[test]
+    fn list_1() {
+        // Listing is sorted and filtered:
+
+        let store = SharedTreeVariableStore::new();
+        store.update("b", 1.0, "");
+        store.update("a", 2.0, "");
+        store.update("c.0", 3.0, "");
+        let names: Vec<String> = store.list("*").unwrap().iter().map(|v| v.name()).collect();
+        assert_eq!(vec!["a", "b", "c.0"], names);
+        let names: Vec<String> = store.list("c*").unwrap().iter().map(|v| v.name()).collect();
+        assert_eq!(vec!["c.0"], names);
+        assert!(store.list("[").is_err());
+    }
+    #[test]
+    fn changed_1() {
+        let store = SharedTreeVariableStore::new();
+        assert!(store.is_changed("a").is_err());
+        assert!(store.set_changed("a").is_err());
+        store.update("a", 1.0, "");
+        assert!(!store.is_changed("a").unwrap());
+        store.set_changed("a").unwrap();
+        assert!(store.is_changed("a").unwrap());
+
+        // Data doesn't reset the flag:
+
+        store.update("a", 2.0, "");
+        assert!(store.is_changed("a").unwrap());
+    }
+    #[test]
+    fn shared_1() {
+        // Clones share the store:
+
+        let store = SharedTreeVariableStore::new();
+        let copy = store.clone();
+        copy.update("a", 1.0, "");
+        assert_eq!(1, store.list("*").unwrap().len());
+    }
+}
@@ -0,0 +1,409 @@
+//!  Unpacking of event built data.  The NSCLDAQ event builder glues
+//!  the fragments of an event from several data sources into a single
+//!  physics event.  Its body is the number of bytes in the body
+//!  (including that count) followed by the fragments.  Each fragment
+//!  is a fragment header:
+//!
+//!  *  u64 timestamp.
+//!  *  u32 source id.
+//!  *  u32 payload size in bytes.
+//!  *  u32 barrier type.
+//!
+//!  followed by the payload which is the ring item from the data source.
+//!
+//!  An EvbUnpacker splits events into their fragments and hands the
+//!  physics event in each fragment to the unpacker for its source id.
+//!  Fragments from sources that have no unpacker are ignored.  Each
+//!  source's parameters are mapped to parameters of the EvbUnpacker
+//!  so that sources with the same kind of unpacker don't collide.
+//!  The parameters of an EvbUnpacker whose basename is _evb_ are:
+//!
+//!  *  evb.fragments - the number of fragments in the event.
+//!  *  evb.sss.timestamp - the timestamp of the fragment from source
+//!     sss in seconds (computed from the event builder clock frequency).
+//!  *  evb.sss.name - the parameter _name_ of the unpacker for source sss.
+//!
+use super::{Registry, Unpacker};
+use crate::ring_items::analysis_ring_items::{ParameterDefinitions, ParameterItem};
+use crate::ring_items::event_item::PhysicsEvent;
+use crate::ring_items::{FromRaw, RingItemRef, RingVersion};
+use std::collections::HashMap;
+
+/// Number of bytes in a fragment header.
+pub const FRAGMENT_HEADER_SIZE: usize = 20;
+/// Id of the parameter that counts the fragments in an event.
+pub const FRAGMENTS_ID: u32 = 1;
+
+/// A fragment of an event built physics event.
+#[derive(Debug, PartialEq)]
+pub struct Fragment<'a> {
+    pub timestamp: u64,
+    pub source_id: u32,
+    pub barrier_type: u32,
+    pub payload: &'a [u8],
+}
+
+fn get_long(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Split the body of an event built physics event into its fragments.
+pub fn fragments(body: &[u8]) -> Result<Vec<Fragment<'_>>, String> {
+    if body.len() < 4 {
+        return Err(String::from("Event body is too small to hold its size"));
+    }
+    let size = get_long(body, 0) as usize;
+    if size < 4 || size > body.len() {
+        return Err(format!(
+            "Event built body size {} does not fit the {} byte event body",
+            size,
+            body.len()
+        ));
+    }
+    let mut result = Vec::new();
+    let mut offset = 4;
+    while offset < size {
+        if offset + FRAGMENT_HEADER_SIZE > size {
+            return Err(format!("Truncated fragment header at offset {}", offset));
+        }
+        let source_id = get_long(body, offset + 8);
+        let payload_size = get_long(body, offset + 12) as usize;
+        let payload_start = offset + FRAGMENT_HEADER_SIZE;
+        if payload_start + payload_size > size {
+            return Err(format!(
+                "The fragment from source {} at offset {} overruns the event",
+                source_id, offset
+            ));
+        }
+        result.push(Fragment {
+            timestamp: u64::from_ne_bytes(body[offset..offset + 8].try_into().unwrap()),
+            source_id,
+            barrier_type: get_long(body, offset + 16),
+            payload: &body[payload_start..payload_start + payload_size],
+        });
+        offset = payload_start + payload_size;
+    }
+    Ok(result)
+}
+
+/// The unpacker for a source id:
+///
+/// * source - the source id.
+/// * unpacker - name of the unpacker in the Registry.
+/// * parameters - number of parameters the unpacker produces.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvbSource {
+    pub source: u32,
+    pub unpacker: String,
+    pub parameters: usize,
+}
+
+/// Describes an EvbUnpacker:
+///
+/// * name - the unpacker's name.
+/// * frequency - the event builder clock frequency in MHz.
+/// * basename - the base name of the unpacker's parameters.
+/// * sources - the unpackers of the source ids in the order their
+///   parameters are defined.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvbDescription {
+    pub name: String,
+    pub frequency: f64,
+    pub basename: String,
+    pub sources: Vec<EvbSource>,
+}
+
+impl EvbDescription {
+    pub fn new(name: &str, frequency: f64, basename: &str) -> Result<EvbDescription, String> {
+        if !(frequency.is_finite() && frequency > 0.0) {
+            return Err(format!(
+                "The clock frequency must be positive, not {}",
+                frequency
+            ));
+        }
+        if basename.is_empty() {
+            return Err(String::from("The parameter base name cannot be empty"));
+        }
+        Ok(EvbDescription {
+            name: String::from(name),
+            frequency,
+            basename: String::from(basename),
+            sources: Vec::new(),
+        })
+    }
+    /// Set the unpacker of a source id, replacing any it already has.
+    /// The unpacker must be one that the registry can make.
+    pub fn set_source(
+        &mut self,
+        registry: &Registry,
+        source: u32,
+        unpacker: &str,
+        parameters: usize,
+    ) -> Result<(), String> {
+        registry.create(unpacker, parameters)?;
+        let new_source = EvbSource {
+            source,
+            unpacker: String::from(unpacker),
+            parameters,
+        };
+        match self.sources.iter_mut().find(|s| s.source == source) {
+            Some(existing) => *existing = new_source,
+            None => self.sources.push(new_source),
+        }
+        Ok(())
+    }
+}
+
+// A source's unpacker.  Its parameter ids are offset by offset; the
+// timestamp parameter's id is offset itself.
+
+struct SourceSlot {
+    unpacker: Box<dyn Unpacker>,
+    offset: u32,
+}
+
+/// Unpacks event built physics events.
+pub struct EvbUnpacker {
+    ticks_per_second: f64,
+    sources: HashMap<u32, SourceSlot>,
+    definitions: ParameterDefinitions,
+}
+
+impl EvbUnpacker {
+    /// The sources' unpackers are made by the registry.
+    pub fn new(description: &EvbDescription, registry: &Registry) -> Result<EvbUnpacker, String> {
+        let mut definitions = ParameterDefinitions::new();
+        definitions.add(FRAGMENTS_ID, &format!("{}.fragments", description.basename));
+        let mut sources = HashMap::new();
+        let mut offset = FRAGMENTS_ID + 1;
+        for s in description.sources.iter() {
+            let unpacker = registry.create(&s.unpacker, s.parameters)?;
+            let prefix = format!("{}.{}", description.basename, s.source);
+            definitions.add(offset, &format!("{}.timestamp", prefix));
+            let mut last = 0;
+            for def in unpacker.definitions().iter() {
+                definitions.add(offset + def.id(), &format!("{}.{}", prefix, def.name()));
+                last = last.max(def.id());
+            }
+            sources.insert(s.source, SourceSlot { unpacker, offset });
+            offset += last + 1;
+        }
+        Ok(EvbUnpacker {
+            ticks_per_second: description.frequency * 1.0e6,
+            sources,
+            definitions,
+        })
+    }
+}
+
+impl Unpacker for EvbUnpacker {
+    fn definitions(&self) -> ParameterDefinitions {
+        self.definitions.clone()
+    }
+    fn unpack(
+        &mut self,
+        event: &mut PhysicsEvent,
+        parameters: &mut ParameterItem,
+    ) -> Result<(), String> {
+        let fragments = fragments(event.body())?;
+        parameters.add(FRAGMENTS_ID, fragments.len() as f64);
+        for f in fragments {
+            if let Some(slot) = self.sources.get_mut(&f.source_id) {
+                parameters.add(slot.offset, f.timestamp as f64 / self.ticks_per_second);
+                let item = RingItemRef::from_bytes(f.payload).map_err(|e| {
+                    format!("Source {}: invalid fragment ring item: {}", f.source_id, e)
+                })?;
+                if let Some(mut physics) =
+                    FromRaw::<PhysicsEvent>::to_specific(&item, RingVersion::V12)
+                {
+                    let mut unpacked = ParameterItem::new(parameters.trigger());
+                    slot.unpacker
+                        .unpack(&mut physics, &mut unpacked)
+                        .map_err(|e| format!("Source {}: {}", f.source_id, e))?;
+                    for p in unpacked.iter() {
+                        parameters.add(slot.offset + p.id(), p.value());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Build the body of an event built physics event from its fragments.
+/// Used to make test data.
+#[cfg(test)]
+pub fn build_body(fragments: &[(u64, u32, Vec<u8>)]) -> Vec<u8> {
+    let mut body = vec![0_u8; 4];
+    for (timestamp, source, payload) in fragments {
+        body.extend(timestamp.to_ne_bytes());
+        body.extend(source.to_ne_bytes());
+        body.extend((payload.len() as u32).to_ne_bytes());
+        body.extend(0_u32.to_ne_bytes());
+        body.extend(payload);
+    }
+    let size = body.len() as u32;
+    body[0..4].copy_from_slice(&size.to_ne_bytes());
+    body
+}
+
+#[cfg(test)]
+mod evb_tests {
+    use super::*;
+    use crate::ring_items::ToRaw;
+
+    // The bytes of a physics event ring item holding 16 bit words:
+
+    fn fragment_item(words: &[u16]) -> Vec<u8> {
+        let mut event = PhysicsEvent::new(None);
+        for w in words {
+            event.add(*w);
+        }
+        let mut bytes = vec![];
+        event.to_raw().write_item(&mut bytes).unwrap();
+        bytes
+    }
+    fn built_event(fragments: &[(u64, u32, Vec<u8>)]) -> PhysicsEvent {
+        let mut event = PhysicsEvent::new(None);
+        for b in build_body(fragments) {
+            event.add(b);
+        }
+        event
+    }
+    fn description() -> EvbDescription {
+        let mut d = EvbDescription::new("test", 100.0, "evb").expect("Making description");
+        d.set_source(&Registry::new(), 2, "words", 2)
+            .expect("Adding source 2");
+        d.set_source(&Registry::new(), 5, "longs", 1)
+            .expect("Adding source 5");
+        d
+    }
+
+    #[test]
+    fn fragments_1() {
+        let body = build_body(&[(100, 2, vec![1, 2, 3, 4]), (200, 5, vec![])]);
+        let result = fragments(&body).expect("Splitting");
+        assert_eq!(
+            vec![
+                Fragment {
+                    timestamp: 100,
+                    source_id: 2,
+                    barrier_type: 0,
+                    payload: &[1, 2, 3, 4]
+                },
+                Fragment {
+                    timestamp: 200,
+                    source_id: 5,
+                    barrier_type: 0,
+                    payload: &[]
+                }
+            ],
+            result
+        );
+        assert!(fragments(&build_body(&[])).unwrap().is_empty());
+    }
+    #[test]
+    fn fragments_2() {
+        // Bad sizes:
+
+        assert!(fragments(&[1, 0]).is_err());
+        let mut body = build_body(&[(100, 2, vec![1, 2, 3, 4])]);
+        body.truncate(body.len() - 1);
+        assert!(fragments(&body).is_err());
+        let size = body.len() as u32;
+        body[0..4].copy_from_slice(&size.to_ne_bytes());
+        assert!(fragments(&body).is_err()); // Payload overruns.
+        body[0..4].copy_from_slice(&10_u32.to_ne_bytes());
+        assert!(fragments(&body).is_err()); // Header truncated.
+    }
+    #[test]
+    fn description_1() {
+        let d = description();
+        assert_eq!(2, d.sources.len());
+
+        let mut d = d;
+        d.set_source(&Registry::new(), 2, "longs", 4)
+            .expect("Replacing");
+        assert_eq!(
+            vec![
+                EvbSource {
+                    source: 2,
+                    unpacker: String::from("longs"),
+                    parameters: 4
+                },
+                EvbSource {
+                    source: 5,
+                    unpacker: String::from("longs"),
+                    parameters: 1
+                }
+            ],
+            d.sources
+        );
+        assert!(d.set_source(&Registry::new(), 3, "junk", 4).is_err());
+        assert!(d.set_source(&Registry::new(), 3, "words", 0).is_err());
+        assert!(EvbDescription::new("bad", 0.0, "evb").is_err());
+        assert!(EvbDescription::new("bad", 10.0, "").is_err());
+    }
+    #[test]
+    fn definitions_1() {
+        let unpacker = EvbUnpacker::new(&description(), &Registry::new()).expect("Making unpacker");
+        let defs: Vec<(u32, String)> = unpacker
+            .definitions()
+            .iter()
+            .map(|d| (d.id(), d.name()))
+            .collect();
+        assert_eq!(
+            vec![
+                (1, String::from("evb.fragments")),
+                (2, String::from("evb.2.timestamp")),
+                (3, String::from("evb.2.words.000")),
+                (4, String::from("evb.2.words.001")),
+                (5, String::from("evb.5.timestamp")),
+                (6, String::from("evb.5.longs.000")),
+            ],
+            defs
+        );
+    }
+    #[test]
+    fn unpack_1() {
+        // Fragments from unknown sources are ignored:
+
+        let mut unpacker =
+            EvbUnpacker::new(&description(), &Registry::new()).expect("Making unpacker");
+        let mut long_event = PhysicsEvent::new(None);
+        long_event.add(7_u32);
+        let mut long_bytes = vec![];
+        long_event.to_raw().write_item(&mut long_bytes).unwrap();
+
+        let mut event = built_event(&[
+            (200_000_000, 2, fragment_item(&[10, 20])),
+            (0, 9, fragment_item(&[1])),
+            (300_000_000, 5, long_bytes),
+        ]);
+        let mut parameters = ParameterItem::new(12);
+        unpacker
+            .unpack(&mut event, &mut parameters)
+            .expect("Unpacking");
+        let values: Vec<(u32, f64)> = parameters.iter().map(|p| (p.id(), p.value())).collect();
+        assert_eq!(
+            vec![(1, 3.0), (2, 2.0), (3, 10.0), (4, 20.0), (5, 3.0), (6, 7.0)],
+            values
+        );
+    }
+    #[test]
+    fn unpack_2() {
+        // Errors in a source's unpacker fail the event:
+
+        let mut unpacker =
+            EvbUnpacker::new(&description(), &Registry::new()).expect("Making unpacker");
+        let mut event = built_event(&[(0, 2, fragment_item(&[1, 2, 3]))]);
+        let mut parameters = ParameterItem::new(0);
+        let result = unpacker.unpack(&mut event, &mut parameters);
+        assert!(result.unwrap_err().starts_with("Source 2:"));
+
+        let mut event = built_event(&[(0, 2, vec![1, 2])]);
+        assert!(unpacker.unpack(&mut event, &mut parameters).is_err());
+    }
+}
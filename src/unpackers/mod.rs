@@ -0,0 +1,486 @@
+//!  Unpackers turn the bodies of raw NSCLDAQ physics events into
+//!  parameters.  They are used by the evt2par program to convert
+//!  raw event files into parameter files rustogramer can analyze
+//!  at sites that don't have the FRIB analysis pipeline.
+//!
+//!  The module provides:
+//!
+//!  -  The Unpacker trait which unpackers implement.
+//!  -  A few generic unpackers that can be selected by name (see create
+//!     and Registry).  Unpackers for other digitizers are added by
+//!     implementing Unpacker and adding a factory to Registry::new.
+//!  -  convert which runs an unpacker over a raw event file writing
+//!  a parameter file.
+//!  -  The evb module which unpacks event built data by handing each
+//!     fragment to the unpacker for its source id.
+//!
+use crate::ring_items;
+use crate::ring_items::analysis_ring_items::{
+    ParameterDefinitions, ParameterFileWriter, ParameterItem,
+};
+use crate::ring_items::event_item::PhysicsEvent;
+use crate::ring_items::{FromRaw, RingItem, RingItemError};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::mem;
+
+pub mod evb;
+
+/// Unpackers implement this trait.
+pub trait Unpacker {
+    /// Provide the definitions of the parameters the unpacker produces.
+    /// These are written to the parameter file before any events.
+    fn definitions(&self) -> ParameterDefinitions;
+    /// Unpack a single event.
+    ///
+    /// ### Parameters
+    /// * event - the physics event to unpack.
+    /// * parameters - the parameter item to which the unpacked parameters
+    /// should be added.
+    ///
+    /// ### Returns
+    /// * Result<(), String> - on error the string describes what went wrong
+    /// and the event is not written.
+    fn unpack(
+        &mut self,
+        event: &mut PhysicsEvent,
+        parameters: &mut ParameterItem,
+    ) -> Result<(), String>;
+}
+
+/// Generic unpacker that turns each word of the event body into a
+/// parameter.  Parameter ids are 1..=count and the names are
+/// prefix.nnn where nnn is the word number in the event.
+///
+pub struct WordUnpacker {
+    prefix: String,
+    word_size: usize,
+    count: usize,
+}
+
+impl WordUnpacker {
+    /// Unpacker for 16 bit words named words.nnn
+    pub fn words(count: usize) -> WordUnpacker {
+        WordUnpacker {
+            prefix: String::from("words"),
+            word_size: mem::size_of::<u16>(),
+            count,
+        }
+    }
+    /// Unpacker for 32 bit words named longs.nnn
+    pub fn longs(count: usize) -> WordUnpacker {
+        WordUnpacker {
+            prefix: String::from("longs"),
+            word_size: mem::size_of::<u32>(),
+            count,
+        }
+    }
+}
+
+impl Unpacker for WordUnpacker {
+    fn definitions(&self) -> ParameterDefinitions {
+        let mut result = ParameterDefinitions::new();
+        for i in 0..self.count {
+            result.add((i + 1) as u32, &format!("{}.{:03}", self.prefix, i));
+        }
+        result
+    }
+    fn unpack(
+        &mut self,
+        event: &mut PhysicsEvent,
+        parameters: &mut ParameterItem,
+    ) -> Result<(), String> {
+        let size = event.body_size();
+        if !size.is_multiple_of(self.word_size) {
+            return Err(format!(
+                "Event body size {} is not a multiple of the {} byte word size",
+                size, self.word_size
+            ));
+        }
+        let words = size / self.word_size;
+        if words > self.count {
+            return Err(format!(
+                "Event has {} words but only {} parameters are defined",
+                words, self.count
+            ));
+        }
+        event.rewind();
+        for i in 0..words {
+            let value = if self.word_size == mem::size_of::<u16>() {
+                event.get::<u16>().map(|w| w as f64)
+            } else {
+                event.get::<u32>().map(|w| w as f64)
+            };
+            if let Some(value) = value {
+                parameters.add((i + 1) as u32, value);
+            } else {
+                return Err(format!("Unable to fetch word {} from the event", i));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The names of the unpackers create knows about.
+pub fn unpacker_names() -> Vec<&'static str> {
+    vec!["words", "longs"]
+}
+
+/// Makes an unpacker that produces at most the given number of
+/// parameters.
+pub type UnpackerFactory = fn(usize) -> Box<dyn Unpacker>;
+
+/// Unpackers that can be created by name.  A new registry knows the
+/// unpackers create knows.
+///
+#[derive(Clone)]
+pub struct Registry {
+    factories: BTreeMap<String, UnpackerFactory>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        let mut factories: BTreeMap<String, UnpackerFactory> = BTreeMap::new();
+        factories.insert(String::from("words"), |n| Box::new(WordUnpacker::words(n)));
+        factories.insert(String::from("longs"), |n| Box::new(WordUnpacker::longs(n)));
+        Registry { factories }
+    }
+    /// Add an unpacker.  Names must be unique.
+    #[cfg(test)]
+    pub fn register(&mut self, name: &str, factory: UnpackerFactory) -> Result<(), String> {
+        if self.factories.contains_key(name) {
+            return Err(format!("An unpacker named {} is already registered", name));
+        }
+        self.factories.insert(String::from(name), factory);
+        Ok(())
+    }
+    /// The names of the registered unpackers, sorted.
+    pub fn names(&self) -> Vec<String> {
+        self.factories.keys().cloned().collect()
+    }
+    /// Create a registered unpacker given its name.
+    ///
+    /// ### Parameters
+    /// * name - name of the unpacker.
+    /// * count - maximum number of parameters the unpacker produces.
+    ///
+    /// ### Returns
+    /// * Result<Box<dyn Unpacker>, String> - the error string describes
+    ///   why the unpacker could not be created.
+    pub fn create(&self, name: &str, count: usize) -> Result<Box<dyn Unpacker>, String> {
+        if count == 0 {
+            return Err(String::from(
+                "An unpacker must produce at least one parameter",
+            ));
+        }
+        match self.factories.get(name) {
+            Some(factory) => Ok(factory(count)),
+            None => Err(format!(
+                "{} is not a known unpacker; must be one of {}",
+                name,
+                self.names().join(", ")
+            )),
+        }
+    }
+}
+
+/// Create an unpacker given its name.
+///
+/// ### Parameters
+/// * name - name of the unpacker (see unpacker_names).
+/// * count - maximum number of parameters the unpacker produces.
+///
+/// ### Returns
+/// * Result<Box<dyn Unpacker>, String> - the error string describes
+/// why the unpacker could not be created.
+pub fn create(name: &str, count: usize) -> Result<Box<dyn Unpacker>, String> {
+    Registry::new().create(name, count)
+}
+
+/// Statistics about a conversion.
+/// * items - number of ring items read.
+/// * bytes - number of bytes read from the event file.
+/// * events - number of physics events read.
+/// * written - number of parameter items written.
+/// * errors - number of events that failed to unpack.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ConversionStatistics {
+    pub items: usize,
+    pub bytes: u64,
+    pub events: usize,
+    pub written: usize,
+    pub errors: usize,
+}
+
+/// Run an unpacker over a raw event file writing a parameter file.
+/// The parameter definitions come from the unpacker.  Each physics
+/// event that unpacks successfully is written as a parameter item whose
+/// trigger number is the number of the event in the file.  Run state
+/// change items are copied to the parameter file so that rustogramer
+/// sees the run boundaries.  Other items are dropped.
+///
+/// ### Parameters
+/// * src - the raw event file.
+/// * writer - writes the parameter file.
+/// * unpacker - unpacks physics events.
+/// * progress - called after every progress_interval physics events
+/// (never if progress_interval is 0).
+/// * on_error - called with the trigger number and the error message
+/// when an event fails to unpack.  Conversion continues.
+///
+/// ### Returns
+/// * Result<ConversionStatistics, String> - errors are those that prevent
+/// the conversion from continuing; reading a bad ring item or
+/// writing to the parameter file.
+pub fn convert<R: Read, W: Write>(
+    src: &mut R,
+    writer: &mut ParameterFileWriter<W>,
+    unpacker: &mut dyn Unpacker,
+    progress_interval: usize,
+    progress: &mut dyn FnMut(&ConversionStatistics),
+    on_error: &mut dyn FnMut(u64, &str),
+) -> Result<ConversionStatistics, String> {
+    let version = writer.version();
+    let mut stats = ConversionStatistics::default();
+    if let Err(e) = writer.write_definitions(&unpacker.definitions()) {
+        return Err(format!("Failed to write parameter definitions: {}", e));
+    }
+
+    loop {
+        let item = match RingItem::read_item(src) {
+            Ok(item) => item,
+            Err(RingItemError::HeaderReadFailed) => break, // End of file.
+            Err(e) => {
+                return Err(format!(
+                    "Failed to read ring item at offset {}: {}",
+                    stats.bytes, e
+                ))
+            }
+        };
+        stats.items += 1;
+        stats.bytes += item.size() as u64;
+
+        match item.type_id() {
+            ring_items::PHYSICS_EVENT => {
+                let trigger = stats.events as u64;
+                stats.events += 1;
+                if let Some(mut event) = FromRaw::<PhysicsEvent>::to_specific(&item, version) {
+                    let mut parameters = ParameterItem::new(trigger);
+                    match unpacker.unpack(&mut event, &mut parameters) {
+                        Ok(()) => {
+                            if let Err(e) = writer.write_event(&parameters) {
+                                return Err(format!("Failed to write event {}: {}", trigger, e));
+                            }
+                            stats.written += 1;
+                        }
+                        Err(msg) => {
+                            stats.errors += 1;
+                            on_error(trigger, &msg);
+                        }
+                    }
+                } else {
+                    stats.errors += 1;
+                    on_error(trigger, "Unable to decode the physics event");
+                }
+                if progress_interval > 0 && stats.events % progress_interval == 0 {
+                    progress(&stats);
+                }
+            }
+            ring_items::BEGIN_RUN
+            | ring_items::END_RUN
+            | ring_items::PAUSE_RUN
+            | ring_items::RESUME_RUN => {
+                if let Err(e) = writer.write_item(&item) {
+                    return Err(format!("Failed to copy a state change item: {}", e));
+                }
+            }
+            _ => {} // Everything else is dropped.
+        }
+    }
+    if let Err(e) = writer.flush() {
+        return Err(format!("Failed to flush the parameter file: {}", e));
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod unpacker_tests {
+    use super::*;
+    use crate::ring_items::{RingVersion, ToRaw};
+
+    #[test]
+    fn create_1() {
+        assert!(create("words", 10).is_ok());
+        assert!(create("longs", 10).is_ok());
+        assert!(create("junk", 10).is_err());
+        assert!(create("words", 0).is_err());
+    }
+    #[test]
+    fn registry_1() {
+        fn bytes(count: usize) -> Box<dyn Unpacker> {
+            Box::new(WordUnpacker {
+                prefix: String::from("bytes"),
+                word_size: 1,
+                count,
+            })
+        }
+        let mut registry = Registry::new();
+        assert_eq!(vec!["longs", "words"], registry.names());
+        assert!(registry.create("bytes", 4).is_err());
+
+        registry.register("bytes", bytes).expect("Registering");
+        assert!(registry.register("bytes", bytes).is_err());
+        assert_eq!(vec!["bytes", "longs", "words"], registry.names());
+        let unpacker = registry.create("bytes", 4).expect("Creating");
+        assert_eq!(4, unpacker.definitions().iter().count());
+        assert!(registry.create("bytes", 0).is_err());
+    }
+    #[test]
+    fn defs_1() {
+        let unpacker = WordUnpacker::words(3);
+        let defs = unpacker.definitions();
+        let defs: Vec<(u32, String)> = defs.iter().map(|d| (d.id(), d.name())).collect();
+        assert_eq!(
+            vec![
+                (1, String::from("words.000")),
+                (2, String::from("words.001")),
+                (3, String::from("words.002"))
+            ],
+            defs
+        );
+    }
+    #[test]
+    fn unpack_1() {
+        // 16 bit words:
+
+        let mut unpacker = WordUnpacker::words(4);
+        let mut event = PhysicsEvent::new(None);
+        event.add(1_u16).add(2_u16).add(3_u16);
+        let mut parameters = ParameterItem::new(0);
+        unpacker.unpack(&mut event, &mut parameters).unwrap();
+        let values: Vec<(u32, f64)> = parameters.iter().map(|p| (p.id(), p.value())).collect();
+        assert_eq!(vec![(1, 1.0), (2, 2.0), (3, 3.0)], values);
+    }
+    #[test]
+    fn unpack_2() {
+        // 32 bit words:
+
+        let mut unpacker = WordUnpacker::longs(4);
+        let mut event = PhysicsEvent::new(None);
+        event.add(0x10000_u32).add(5_u32);
+        let mut parameters = ParameterItem::new(0);
+        unpacker.unpack(&mut event, &mut parameters).unwrap();
+        let values: Vec<(u32, f64)> = parameters.iter().map(|p| (p.id(), p.value())).collect();
+        assert_eq!(vec![(1, 65536.0), (2, 5.0)], values);
+    }
+    #[test]
+    fn unpack_3() {
+        // Too many words and odd sizes are errors:
+
+        let mut unpacker = WordUnpacker::words(1);
+        let mut event = PhysicsEvent::new(None);
+        event.add(1_u16).add(2_u16);
+        let mut parameters = ParameterItem::new(0);
+        assert!(unpacker.unpack(&mut event, &mut parameters).is_err());
+
+        let mut unpacker = WordUnpacker::longs(4);
+        let mut event = PhysicsEvent::new(None);
+        event.add(1_u16);
+        assert!(unpacker.unpack(&mut event, &mut parameters).is_err());
+    }
+    #[test]
+    fn convert_1() {
+        // Convert a small event file:
+
+        let mut src: Vec<u8> = vec![];
+        let begin = RingItem::new(ring_items::BEGIN_RUN);
+        begin.write_item(&mut src).unwrap();
+        for i in 0..5_u16 {
+            let mut event = PhysicsEvent::new(None);
+            event.add(i).add(i * 2);
+            event.to_raw().write_item(&mut src).unwrap();
+        }
+        let mut bad = PhysicsEvent::new(None); // Too many words.
+        bad.add(1_u16).add(2_u16).add(3_u16);
+        bad.to_raw().write_item(&mut src).unwrap();
+        RingItem::new(ring_items::PERIODIC_SCALERS)
+            .write_item(&mut src)
+            .unwrap();
+
+        let mut writer = ParameterFileWriter::new(vec![], RingVersion::V12).unwrap();
+        let mut unpacker = WordUnpacker::words(2);
+        let mut progress_calls = 0;
+        let mut errors = vec![];
+        let stats = convert(
+            &mut src.as_slice(),
+            &mut writer,
+            &mut unpacker,
+            2,
+            &mut |_| progress_calls += 1,
+            &mut |trigger, _| errors.push(trigger),
+        )
+        .unwrap();
+
+        assert_eq!(
+            ConversionStatistics {
+                items: 8,
+                bytes: src.len() as u64,
+                events: 6,
+                written: 5,
+                errors: 1
+            },
+            stats
+        );
+        assert_eq!(3, progress_calls);
+        assert_eq!(vec![5], errors);
+
+        // Format, definitions, begin, 5 events:
+
+        assert_eq!(8, writer.items_written());
+        let out = writer.into_inner();
+        let mut offset = 0;
+        let mut types = vec![];
+        let mut triggers = vec![];
+        while offset < out.len() {
+            let item = ring_items::RingItemRef::from_bytes(&out[offset..]).unwrap();
+            offset += item.size() as usize;
+            types.push(item.type_id());
+            if let Some(p) = FromRaw::<ParameterItem>::to_specific(&item, RingVersion::V12) {
+                assert_eq!(2, p.iter().count());
+                triggers.push(p.trigger());
+            }
+        }
+        assert_eq!(ring_items::FORMAT_ITEM, types[0]);
+        assert_eq!(ring_items::PARAMETER_DEFINITIONS, types[1]);
+        assert_eq!(ring_items::BEGIN_RUN, types[2]);
+        assert_eq!(vec![0, 1, 2, 3, 4], triggers);
+    }
+    #[test]
+    fn convert_2() {
+        // Truncated ring items are fatal:
+
+        let mut src: Vec<u8> = vec![];
+        let mut event = PhysicsEvent::new(None);
+        event.add(1_u16);
+        event.to_raw().write_item(&mut src).unwrap();
+        src.pop();
+
+        let mut writer = ParameterFileWriter::new(vec![], RingVersion::V12).unwrap();
+        let mut unpacker = WordUnpacker::words(2);
+        let result = convert(
+            &mut src.as_slice(),
+            &mut writer,
+            &mut unpacker,
+            0,
+            &mut |_| {},
+            &mut |_, _| {},
+        );
+        assert!(result.is_err());
+    }
+}